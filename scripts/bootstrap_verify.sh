@@ -24,6 +24,10 @@ PROJECT_ROOT="$(dirname "$SCRIPT_DIR")"
 BUILD_DIR="$PROJECT_ROOT/target/release"
 VERIFY_DIR="/tmp/oite_bootstrap_verify"
 OITE_BIN="$BUILD_DIR/oitec"
+# Windows (Git Bash) builds produce oitec.exe
+if [[ -f "$OITE_BIN.exe" ]]; then
+    OITE_BIN="$OITE_BIN.exe"
+fi
 
 # Colors for output
 RED='\033[0;31m'
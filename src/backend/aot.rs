@@ -9,16 +9,19 @@
 //! - Platform-specific binary output
 //! - Link-time optimization (LTO)
 
+use super::llvm::linker::LinkOptions;
 use super::{BackendConfig, BackendError, BackendKind, LtoMode};
 use crate::ir::IrModule;
+use crate::platform::{DLL_EXTENSION, EXE_EXTENSION, OBJECT_EXTENSION, STATIC_LIB_EXTENSION};
+use crate::runtime::abi_version;
 use std::path::{Path, PathBuf};
 
 /// AOT compilation target format
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum OutputFormat {
-    /// Object file (.o)
+    /// Object file (.o/.obj)
     Object,
-    /// Static library (.a)
+    /// Static library (.a/.lib)
     StaticLib,
     /// Shared library (.so/.dylib/.dll)
     SharedLib,
@@ -27,6 +30,32 @@ pub enum OutputFormat {
     Executable,
 }
 
+impl OutputFormat {
+    /// File extension of this format on the host platform (empty for Unix executables)
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Object => OBJECT_EXTENSION,
+            OutputFormat::StaticLib => STATIC_LIB_EXTENSION,
+            OutputFormat::SharedLib => DLL_EXTENSION,
+            OutputFormat::Executable => EXE_EXTENSION,
+        }
+    }
+
+    /// Whether this format is a library a host program links against
+    pub fn is_library(self) -> bool {
+        matches!(self, OutputFormat::StaticLib | OutputFormat::SharedLib)
+    }
+
+    /// Default output path for a build of `stem`
+    pub fn default_output(self, stem: &str) -> PathBuf {
+        let path = PathBuf::from(stem);
+        match self.extension() {
+            "" => path,
+            ext => path.with_extension(ext),
+        }
+    }
+}
+
 /// AOT compilation options
 #[derive(Debug, Clone)]
 pub struct AotOptions {
@@ -38,6 +67,10 @@ pub struct AotOptions {
     pub lto_mode: LtoMode,
     /// Strip debug symbols
     pub strip: bool,
+    /// Prebuilt runtime library to link against (`build --runtime`)
+    pub runtime: Option<PathBuf>,
+    /// Linker selection, extra libraries and pass-through arguments
+    pub link: LinkOptions,
 }
 
 impl Default for AotOptions {
@@ -47,6 +80,8 @@ impl Default for AotOptions {
             target: None,
             lto_mode: LtoMode::None,
             strip: false,
+            runtime: None,
+            link: LinkOptions::default(),
         }
     }
 }
@@ -112,20 +147,20 @@ impl AotCompiler {
         match self.config.kind {
             BackendKind::LlvmAot => {
                 // Use LLVM backend
-                let obj_file = output.with_extension("o");
+                let obj_file = output.with_extension(OBJECT_EXTENSION);
                 super::llvm::compile_to_object_file(module, &self.config, &obj_file)?;
 
                 // Link if output format is executable or shared library
                 match self.options.format {
                     OutputFormat::Executable | OutputFormat::SharedLib => {
-                        // Find runtime library (required)
-                        let runtime_lib = find_runtime_library()?;
+                        let runtime_lib = self.runtime_library()?;
                         super::llvm::linker::link_object_files_with_lto(
                             std::slice::from_ref(&obj_file),
                             output,
                             self.options.format,
-                            Some(&runtime_lib),
+                            runtime_lib.as_deref(),
                             self.options.lto_mode,
+                            &self.options.link,
                         )?;
                     }
                     OutputFormat::Object => {
@@ -181,7 +216,7 @@ impl AotCompiler {
 
         // Run LTO if enabled
         let obj_file = if self.options.lto_mode != super::LtoMode::None {
-            let temp_obj = temp_dir.join("lto_output.o");
+            let temp_obj = temp_dir.join(format!("lto_output.{}", OBJECT_EXTENSION));
             lto::run_lto(
                 &bitcode_files,
                 &temp_obj,
@@ -194,14 +229,14 @@ impl AotCompiler {
             let mut obj_files = Vec::new();
             let tools_dir = PathBuf::new(); // Will use PATH
             for (i, bc_file) in bitcode_files.iter().enumerate() {
-                let obj_file = temp_dir.join(format!("module_{}.o", i));
+                let obj_file = temp_dir.join(format!("module_{}.{}", i, OBJECT_EXTENSION));
                 // Use llc to generate object from bitcode
                 lto::generate_object_file(bc_file, &obj_file, self.config.opt_level, &tools_dir)?;
                 obj_files.push(obj_file);
             }
 
             // Link objects
-            let linked_obj = temp_dir.join("linked.o");
+            let linked_obj = temp_dir.join(format!("linked.{}", OBJECT_EXTENSION));
             super::llvm::linker::link_object_files(
                 &obj_files,
                 &linked_obj,
@@ -214,15 +249,16 @@ impl AotCompiler {
         // Link if output format is executable or shared library
         match self.options.format {
             OutputFormat::Executable | OutputFormat::SharedLib => {
-                // Runtime stubs are now implemented directly in LLVM IR (see abi.rs),
-                // so no external runtime library is needed for basic operations.
-                let runtime_lib: Option<std::path::PathBuf> = None;
+                // Runtime stubs are implemented directly in LLVM IR (see abi.rs),
+                // so the runtime library is only linked when one is given
+                let runtime_lib = self.runtime_library()?;
                 super::llvm::linker::link_object_files_with_lto(
                     &[obj_file],
                     output,
                     self.options.format,
                     runtime_lib.as_deref(),
                     self.options.lto_mode,
+                    &self.options.link,
                 )?;
             }
             OutputFormat::Object => {
@@ -241,6 +277,17 @@ impl AotCompiler {
         Ok(())
     }
 
+    /// The runtime library to link, once checked against this compiler's ABI
+    fn runtime_library(&self) -> Result<Option<PathBuf>, BackendError> {
+        match &self.options.runtime {
+            Some(path) => {
+                verify_runtime_library(path)?;
+                Ok(Some(path.clone()))
+            }
+            None => Ok(None),
+        }
+    }
+
     /// Compile an IR module to bytes (object file in memory)
     pub fn compile_to_bytes(&mut self, module: &IrModule) -> Result<Vec<u8>, BackendError> {
         match self.config.kind {
@@ -251,7 +298,8 @@ impl AotCompiler {
                     .duration_since(UNIX_EPOCH)
                     .unwrap()
                     .as_nanos();
-                let temp_file = std::env::temp_dir().join(format!("ot_{}.o", timestamp));
+                let temp_file =
+                    std::env::temp_dir().join(format!("ot_{}.{}", timestamp, OBJECT_EXTENSION));
                 super::llvm::compile_to_object_file(module, &self.config, &temp_file)?;
 
                 // Read object file bytes
@@ -320,173 +368,81 @@ pub fn default_target() -> String {
     target_lexicon::Triple::host().to_string()
 }
 
-/// Find or build the runtime library
-///
-/// NOTE: Runtime stubs are now implemented directly in LLVM IR in abi.rs,
-/// so this function is no longer needed for basic programs. It remains
-/// for future use when we want to link additional runtime features.
-fn find_runtime_library() -> Result<PathBuf, BackendError> {
-    // Runtime stubs are now generated in LLVM IR, so no external library needed
-    return Err(BackendError::AotError(
-        "Runtime library not needed - stubs are generated in LLVM IR".into(),
-    ));
-
-    #[allow(unreachable_code)]
-    // Get manifest directory (project root)
-    let manifest_dir = std::env::var("CARGO_MANIFEST_DIR")
-        .map(PathBuf::from)
-        .unwrap_or_else(|_| {
-            // Fall back to current directory if not in cargo build
-            std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."))
-        });
-
-    // Determine profile based on whether we're in release mode
-    // Default to release for AOT builds
-    let profile = if cfg!(debug_assertions) {
-        "debug"
-    } else {
-        "release"
-    };
-    let runtime_lib = manifest_dir
-        .join("target")
-        .join(profile)
-        .join("libruntime.a");
-
-    // If library exists, return it
-    if runtime_lib.exists() {
-        return Ok(runtime_lib);
-    }
-
-    // Library doesn't exist - try to build it on-demand
-    // Note: This may fail due to Rust std linking issues, which is okay for simple programs
-    println!("Building runtime library...");
-    if let Err(e) = build_runtime_library(
-        &manifest_dir,
-        runtime_lib.parent().unwrap(),
-        profile == "release",
-    ) {
-        eprintln!("[WARN] Failed to build runtime library: {}", e);
+/// Check that the runtime library at `path` can run the binaries this
+/// compiler builds, returning its ABI version
+pub fn verify_runtime_library(path: &Path) -> Result<u32, BackendError> {
+    let bytes = std::fs::read(path)
+        .map_err(|e| BackendError::AotError(format!("Failed to read {}: {}", path.display(), e)))?;
+    let Some(abi) = abi_version::runtime_abi_in(&bytes) else {
         return Err(BackendError::AotError(format!(
-            "Runtime library build failed: {}. Simple programs may work without it.",
-            e
+            "{} is not a runtime library (it has no ABI marker); build one with `build --emit-runtime`",
+            path.display()
         )));
-    }
-
-    if runtime_lib.exists() {
-        Ok(runtime_lib)
-    } else {
-        Err(BackendError::AotError(format!(
-            "Failed to build runtime library at {}",
-            runtime_lib.display()
-        )))
-    }
+    };
+    abi_version::check_runtime_library(abi)
+        .map_err(|e| BackendError::AotError(format!("{}: {}", path.display(), e)))?;
+    Ok(abi.0)
 }
 
-/// Build the runtime library on-demand
-fn build_runtime_library(
-    manifest_dir: &PathBuf,
-    output_dir: &Path,
-    release: bool,
-) -> Result<(), BackendError> {
+/// Build the runtime as a static library at `output`, from the source tree
+/// this compiler was built from, and return its ABI version
+pub fn build_runtime_library(output: &Path, release: bool) -> Result<u32, BackendError> {
     use std::process::Command;
 
-    // Create output directory if it doesn't exist
-    std::fs::create_dir_all(output_dir)
-        .map_err(|e| BackendError::AotError(format!("Failed to create output directory: {}", e)))?;
-
+    let manifest_dir = std::env::var("CARGO_MANIFEST_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from(env!("CARGO_MANIFEST_DIR")));
     let cargo_toml = manifest_dir.join("Cargo.toml");
     if !cargo_toml.exists() {
         return Err(BackendError::AotError(format!(
-            "Cargo.toml not found at {}",
+            "Cargo.toml not found at {}; the runtime is built from the compiler's source tree",
             cargo_toml.display()
         )));
     }
 
-    // Clean old rlibs to ensure we build fresh without hashbrown/vm_interop
-    let profile_dir = if release { "release" } else { "debug" };
-    let deps_dir = manifest_dir.join("target").join(profile_dir).join("deps");
-    if let Ok(entries) = std::fs::read_dir(&deps_dir) {
-        for entry in entries.flatten() {
-            let path = entry.path();
-            if let Some(name) = path.file_name().and_then(|n| n.to_str())
-                && name.starts_with("libscript")
-                && name.ends_with(".rlib")
-            {
-                let _ = std::fs::remove_file(&path);
-            }
-        }
-    }
-
-    // Use cargo build to build the library
-    // This will handle dependencies correctly
+    // A separate target directory keeps the runtime's feature set from
+    // invalidating the compiler's own build
+    let target_dir = manifest_dir.join("target").join("runtime");
     let mut cmd = Command::new("cargo");
-    cmd.arg("build")
+    cmd.arg("rustc")
         .arg("--lib")
+        .arg("--crate-type")
+        .arg("staticlib")
         .arg("--manifest-path")
-        .arg(cargo_toml.to_string_lossy().as_ref())
+        .arg(&cargo_toml)
+        .arg("--target-dir")
+        .arg(&target_dir)
         // Disable default features (vm_interop) for standalone staticlib
         .arg("--no-default-features")
         // Prevent build script from running (avoid recursion)
         .env("TSCL_BUILDING_RUNTIME", "1");
-
-    // Use release profile (LTO is disabled in Cargo.toml)
     if release {
         cmd.arg("--release");
     }
 
-    let output = cmd
+    let result = cmd
         .output()
-        .map_err(|e| BackendError::AotError(format!("Failed to execute cargo rustc: {}", e)))?;
-
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(BackendError::AotError(format!(
-            "cargo rustc failed to build runtime library:\n{}",
-            stderr
-        )));
-    }
-
-    // cargo build outputs rlib by default
-    // rlibs are actually ar archives and can be used for static linking
-    let profile_dir = if release { "release" } else { "debug" };
-    let deps_dir = manifest_dir.join("target").join(profile_dir).join("deps");
-
-    // Look for libscript*.rlib (cargo build produces rlib by default)
-    let runtime_lib = output_dir.join("libruntime.a");
-
-    // Try to find the rlib file
-    let mut found_lib = None;
-    if let Ok(entries) = std::fs::read_dir(&deps_dir) {
-        for entry in entries.flatten() {
-            let path = entry.path();
-            if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
-                // Look for libscript*.rlib files
-                if name.starts_with("libscript") && name.ends_with(".rlib") {
-                    found_lib = Some(path);
-                    break;
-                }
-            }
-        }
-    }
-
-    if let Some(source_lib) = found_lib {
-        // Copy rlib and rename to .a (rlibs are ar archives, compatible with static linking)
-        std::fs::copy(&source_lib, &runtime_lib).map_err(|e| {
-            BackendError::AotError(format!(
-                "Failed to copy {} to {}: {}",
-                source_lib.display(),
-                runtime_lib.display(),
-                e
-            ))
-        })?;
-    } else {
+        .map_err(|e| BackendError::AotError(format!("Failed to run cargo: {}", e)))?;
+    if !result.status.success() {
         return Err(BackendError::AotError(format!(
-            "Runtime library not found after build in {}. Expected libscript*.rlib",
-            deps_dir.display()
+            "cargo failed to build the runtime library:\n{}",
+            String::from_utf8_lossy(&result.stderr)
         )));
     }
 
-    Ok(())
+    let lib_name = if cfg!(windows) { "oite" } else { "liboite" };
+    let built = target_dir
+        .join(if release { "release" } else { "debug" })
+        .join(format!("{}.{}", lib_name, STATIC_LIB_EXTENSION));
+    std::fs::copy(&built, output).map_err(|e| {
+        BackendError::AotError(format!(
+            "Failed to copy {} to {}: {}",
+            built.display(),
+            output.display(),
+            e
+        ))
+    })?;
+    verify_runtime_library(output)
 }
 
 #[cfg(test)]
@@ -513,4 +469,17 @@ mod tests {
         assert_eq!(opts.lto_mode, LtoMode::None);
         assert!(!opts.strip);
     }
+
+    #[test]
+    fn test_default_output_uses_platform_extensions() {
+        let exe = OutputFormat::Executable.default_output("app");
+        let obj = OutputFormat::Object.default_output("app");
+        if cfg!(windows) {
+            assert_eq!(exe, PathBuf::from("app.exe"));
+            assert_eq!(obj, PathBuf::from("app.obj"));
+        } else {
+            assert_eq!(exe, PathBuf::from("app"));
+            assert_eq!(obj, PathBuf::from("app.o"));
+        }
+    }
 }
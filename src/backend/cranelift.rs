@@ -7,23 +7,27 @@
 //! - Specialized ops (AddNum, etc.) compile to direct FP instructions
 //! - Dynamic ops (AddAny, etc.) call runtime stubs
 //! - Borrow ops are zero-cost (just pointer copies)
+//! - Loop headers and calls poll the interrupt flag (see `runtime::safepoint`)
 
 use cranelift::prelude::*;
 use cranelift_codegen::ir::{FuncRef, StackSlot};
 use cranelift_codegen::settings;
-use cranelift_jit::{JITBuilder, JITModule};
 use cranelift_module::{FuncId, Linkage, Module};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
+use super::jit_module::JitModule;
 use super::layout::VALUE_SIZE;
 use super::{BackendConfig, BackendError};
+use crate::ir::intrinsics::Intrinsic;
 use crate::ir::{BasicBlock, BlockId, IrFunction, IrModule, IrOp, Literal, Terminator, ValueId};
+use crate::runtime::heap::{ElementKind, NativeTypedArray};
+use crate::runtime::safepoint;
 
 /// Cranelift code generator
 #[allow(dead_code)]
 pub struct CraneliftCodegen {
     /// The JIT module being built
-    module: JITModule,
+    module: JitModule,
     /// Codegen context (reused for each function)
     ctx: codegen::Context,
     /// Function builder context (reused)
@@ -42,7 +46,8 @@ impl CraneliftCodegen {
         // Create JIT builder with appropriate settings for the platform
         let mut flag_builder = settings::builder();
 
-        // Disable PLT on aarch64 since it's not supported in cranelift-jit 0.113
+        // Calls are direct; JitModule adds veneers for out-of-range targets
+        // and does not implement GOT relocations, so no PIC.
         flag_builder.set("use_colocated_libcalls", "true").unwrap();
         flag_builder.set("is_pic", "false").unwrap();
 
@@ -53,12 +58,10 @@ impl CraneliftCodegen {
             .finish(settings::Flags::new(flag_builder))
             .map_err(|e| BackendError::Cranelift(format!("Failed to create ISA: {}", e)))?;
 
-        let mut builder = JITBuilder::with_isa(isa, cranelift_module::default_libcall_names());
+        let mut module = JitModule::new(isa);
 
         // Register runtime stubs as symbols
-        Self::register_runtime_symbols(&mut builder);
-
-        let module = JITModule::new(builder);
+        Self::register_runtime_symbols(&mut module);
 
         let ctx = module.make_context();
 
@@ -76,46 +79,55 @@ impl CraneliftCodegen {
     }
 
     /// Register runtime stub functions as symbols for the JIT
-    fn register_runtime_symbols(builder: &mut JITBuilder) {
+    fn register_runtime_symbols(module: &mut JitModule) {
         use crate::runtime::stubs::*;
 
         // Allocation stubs
-        builder.symbol("ot_alloc_object", ot_alloc_object as *const u8);
-        builder.symbol("ot_alloc_array", ot_alloc_array as *const u8);
-        builder.symbol("ot_alloc_string", ot_alloc_string as *const u8);
+        module.symbol("ot_alloc_object", ot_alloc_object as *const u8);
+        module.symbol("ot_alloc_array", ot_alloc_array as *const u8);
+        module.symbol("ot_alloc_string", ot_alloc_string as *const u8);
 
         // Property access stubs
-        builder.symbol("ot_get_prop", ot_get_prop as *const u8);
-        builder.symbol("ot_set_prop", ot_set_prop as *const u8);
-        builder.symbol("ot_get_element", ot_get_element as *const u8);
-        builder.symbol("ot_set_element", ot_set_element as *const u8);
+        module.symbol("ot_get_prop", ot_get_prop as *const u8);
+        module.symbol("ot_set_prop", ot_set_prop as *const u8);
+        module.symbol("ot_get_element", ot_get_element as *const u8);
+        module.symbol("ot_set_element", ot_set_element as *const u8);
+
+        // Typed array stubs
+        module.symbol("ot_alloc_typed_array", ot_alloc_typed_array as *const u8);
+        module.symbol("ot_typed_array_get", ot_typed_array_get as *const u8);
+        module.symbol("ot_typed_array_set", ot_typed_array_set as *const u8);
+        module.symbol("ot_typed_array_len", ot_typed_array_len as *const u8);
 
         // Dynamic arithmetic stubs
-        builder.symbol("ot_add_any", ot_add_any as *const u8);
-        builder.symbol("ot_sub_any", ot_sub_any as *const u8);
-        builder.symbol("ot_mul_any", ot_mul_any as *const u8);
-        builder.symbol("ot_div_any", ot_div_any as *const u8);
-        builder.symbol("ot_mod_any", ot_mod_any as *const u8);
+        module.symbol("ot_add_any", ot_add_any as *const u8);
+        module.symbol("ot_sub_any", ot_sub_any as *const u8);
+        module.symbol("ot_mul_any", ot_mul_any as *const u8);
+        module.symbol("ot_div_any", ot_div_any as *const u8);
+        module.symbol("ot_mod_any", ot_mod_any as *const u8);
 
         // Comparison stubs
-        builder.symbol("ot_eq_strict", ot_eq_strict as *const u8);
-        builder.symbol("ot_lt", ot_lt as *const u8);
-        builder.symbol("ot_gt", ot_gt as *const u8);
-        builder.symbol("ot_lte", ot_lte as *const u8);
-        builder.symbol("ot_gte", ot_gte as *const u8);
-        builder.symbol("ot_not", ot_not as *const u8);
-        builder.symbol("ot_neg", ot_neg as *const u8);
+        module.symbol("ot_eq_strict", ot_eq_strict as *const u8);
+        module.symbol("ot_lt", ot_lt as *const u8);
+        module.symbol("ot_gt", ot_gt as *const u8);
+        module.symbol("ot_lte", ot_lte as *const u8);
+        module.symbol("ot_gte", ot_gte as *const u8);
+        module.symbol("ot_not", ot_not as *const u8);
+        module.symbol("ot_neg", ot_neg as *const u8);
 
         // Type conversion stubs
-        builder.symbol("ot_to_boolean", ot_to_boolean as *const u8);
-        builder.symbol("ot_to_number", ot_to_number as *const u8);
+        module.symbol("ot_to_boolean", ot_to_boolean as *const u8);
+        module.symbol("ot_to_number", ot_to_number as *const u8);
 
         // Console/IO stubs
-        builder.symbol("ot_console_log", ot_console_log as *const u8);
-        builder.symbol("ot_call", ot_call as *const u8);
+        module.symbol("ot_console_log", ot_console_log as *const u8);
+        module.symbol("ot_call", ot_call as *const u8);
 
         // Closure stubs
-        builder.symbol("ot_make_closure", ot_make_closure as *const u8);
+        module.symbol("ot_make_closure", ot_make_closure as *const u8);
+
+        // Safepoint slow path
+        module.symbol("ot_safepoint", safepoint::ot_safepoint as *const u8);
     }
 
     /// Declare a runtime stub function in the module
@@ -302,7 +314,7 @@ impl CraneliftCodegen {
 /// Translate a function from tscl IR to Cranelift IR
 fn translate_function(
     builder: &mut FunctionBuilder,
-    module: &mut JITModule,
+    module: &mut JitModule,
     ir_func: &IrFunction,
     ir_module: &IrModule,
     func_ids: &HashMap<String, FuncId>,
@@ -318,6 +330,7 @@ fn translate_function(
         local_stores: HashMap::new(),
         phi_params: HashMap::new(),
         block_phis: HashMap::new(),
+        loop_headers: ir_func.loop_headers(),
     };
 
     // Create Cranelift blocks for each IR block
@@ -419,18 +432,28 @@ struct TranslationContext<'a> {
     phi_params: HashMap<ValueId, (BlockId, usize)>,
     /// Phi entries for each block: BlockId -> Vec<(dst, entries)>
     block_phis: HashMap<BlockId, Vec<(ValueId, Vec<(BlockId, ValueId)>)>>,
+    /// Blocks that start a loop iteration and get a safepoint poll
+    loop_headers: HashSet<BlockId>,
 }
 
 /// Translate a single basic block
 fn translate_block(
     builder: &mut FunctionBuilder,
-    module: &mut JITModule,
+    module: &mut JitModule,
     ctx: &mut TranslationContext,
     block: &BasicBlock,
 ) -> Result<(), BackendError> {
+    if ctx.loop_headers.contains(&block.id) {
+        translate_safepoint(builder, module, ctx)?;
+    }
+
     // Translate each operation
     for op in &block.ops {
         translate_op(builder, module, ctx, op)?;
+        // A stop that unwound the callee unwinds this frame too
+        if matches!(op, IrOp::Call(_, _, _)) {
+            translate_safepoint(builder, module, ctx)?;
+        }
     }
 
     // Translate terminator, passing current block ID for phi argument resolution
@@ -439,10 +462,50 @@ fn translate_block(
     Ok(())
 }
 
+/// Poll the interrupt flag at the top of a loop iteration or after a call.
+///
+/// The flag is almost always clear, so the check is a load and a branch; the
+/// call to `ot_safepoint` and the early return live in cold blocks. On return
+/// the builder is positioned in a fresh block where the loop body continues.
+fn translate_safepoint(
+    builder: &mut FunctionBuilder,
+    module: &mut JitModule,
+    ctx: &mut TranslationContext,
+) -> Result<(), BackendError> {
+    let flag_addr = builder
+        .ins()
+        .iconst(types::I64, safepoint::interrupt_flag_addr() as i64);
+    let flag = builder
+        .ins()
+        .load(types::I32, MemFlags::trusted(), flag_addr, 0);
+
+    let slow_block = builder.create_block();
+    let stop_block = builder.create_block();
+    let continue_block = builder.create_block();
+    builder.set_cold_block(slow_block);
+    builder.set_cold_block(stop_block);
+    builder
+        .ins()
+        .brif(flag, slow_block, &[], continue_block, &[]);
+
+    builder.switch_to_block(slow_block);
+    let stop = call_stub_no_args(builder, module, ctx, "ot_safepoint")?;
+    builder
+        .ins()
+        .brif(stop, stop_block, &[], continue_block, &[]);
+
+    builder.switch_to_block(stop_block);
+    let undefined = translate_literal(builder, &Literal::Undefined);
+    builder.ins().return_(&[undefined]);
+
+    builder.switch_to_block(continue_block);
+    Ok(())
+}
+
 /// Translate a single IR operation
 fn translate_op(
     builder: &mut FunctionBuilder,
-    module: &mut JITModule,
+    module: &mut JitModule,
     ctx: &mut TranslationContext,
     op: &IrOp,
 ) -> Result<(), BackendError> {
@@ -690,6 +753,48 @@ fn translate_op(
             call_stub(builder, module, ctx, "ot_set_element", &[*arr, *val])?;
         }
 
+        // === Typed Array Operations ===
+        IrOp::TypedArrayLen(dst, arr) => {
+            let ptr = typed_array_ptr(builder, ctx, *arr)?;
+            let len = builder.ins().uload32(
+                MemFlags::trusted(),
+                ptr,
+                NativeTypedArray::LEN_OFFSET as i32,
+            );
+            let len = builder.ins().fcvt_from_uint(types::F64, len);
+            let result = builder.ins().bitcast(types::I64, MemFlags::new(), len);
+            ctx.values.insert(*dst, result);
+        }
+
+        IrOp::TypedLoad(dst, arr, idx, _, true) => {
+            let result = call_stub(builder, module, ctx, "ot_typed_array_get", &[*arr, *idx])?;
+            ctx.values.insert(*dst, result);
+        }
+
+        IrOp::TypedLoad(dst, arr, idx, kind, false) => {
+            let addr = typed_element_addr(builder, ctx, *arr, *idx, *kind)?;
+            let value = load_typed_element(builder, addr, *kind);
+            let result = builder.ins().bitcast(types::I64, MemFlags::new(), value);
+            ctx.values.insert(*dst, result);
+        }
+
+        IrOp::TypedStore(arr, idx, val, _, true) => {
+            call_stub(
+                builder,
+                module,
+                ctx,
+                "ot_typed_array_set",
+                &[*arr, *idx, *val],
+            )?;
+        }
+
+        IrOp::TypedStore(arr, idx, val, kind, false) => {
+            let addr = typed_element_addr(builder, ctx, *arr, *idx, *kind)?;
+            let bits = get_value(ctx, *val)?;
+            let value = builder.ins().bitcast(types::F64, MemFlags::new(), bits);
+            store_typed_element(builder, addr, value, *kind);
+        }
+
         // === Copy/Move Operations ===
         IrOp::Copy(dst, src)
         | IrOp::Move(dst, src)
@@ -876,6 +981,25 @@ fn translate_op(
             let _ = get_value(ctx, *val)?;
         }
 
+        IrOp::Intrinsic(dst, intrinsic, args) => {
+            let mut operands = Vec::with_capacity(args.len());
+            for arg in args {
+                let bits = get_value(ctx, *arg)?;
+                operands.push(builder.ins().bitcast(types::F64, MemFlags::new(), bits));
+            }
+            let result = translate_intrinsic(builder, *intrinsic, &operands)?;
+            let result = builder.ins().bitcast(types::I64, MemFlags::new(), result);
+            ctx.values.insert(*dst, result);
+        }
+
+        // SIMD vectors are compiled by the LLVM backend only
+        IrOp::Simd(_, kind, op, _) => {
+            return Err(BackendError::UnsupportedOp(format!(
+                "SIMD operation {}.{} is not supported by the Cranelift backend",
+                kind, op
+            )));
+        }
+
         // Bitwise operations - not implemented in Cranelift backend yet
         IrOp::BitAnd(_, _, _)
         | IrOp::BitOr(_, _, _)
@@ -1057,7 +1181,7 @@ fn resolve_function_address(ctx: &TranslationContext, func_val: ValueId) -> Opti
 /// Call a function indirectly using the ot_call runtime stub.
 fn call_indirect_function(
     builder: &mut FunctionBuilder,
-    module: &mut JITModule,
+    module: &mut JitModule,
     ctx: &mut TranslationContext,
     func_ptr: Value,
     args: &[Value],
@@ -1079,10 +1203,221 @@ fn call_indirect_function(
     }
 }
 
+/// Untag a NaN-boxed typed array into a pointer to its `NativeTypedArray`
+fn typed_array_ptr(
+    builder: &mut FunctionBuilder,
+    ctx: &TranslationContext,
+    arr: ValueId,
+) -> Result<Value, BackendError> {
+    const PAYLOAD_MASK: i64 = 0x0000_FFFF_FFFF_FFFF;
+    let bits = get_value(ctx, arr)?;
+    Ok(builder.ins().band_imm(bits, PAYLOAD_MASK))
+}
+
+/// Address of element `idx` of a typed array. The index must already be
+/// known to be an in-bounds integer.
+fn typed_element_addr(
+    builder: &mut FunctionBuilder,
+    ctx: &TranslationContext,
+    arr: ValueId,
+    idx: ValueId,
+    kind: ElementKind,
+) -> Result<Value, BackendError> {
+    let ptr = typed_array_ptr(builder, ctx, arr)?;
+    let data = builder.ins().load(
+        types::I64,
+        MemFlags::trusted(),
+        ptr,
+        NativeTypedArray::DATA_OFFSET as i32,
+    );
+    let idx = get_value(ctx, idx)?;
+    let idx = builder.ins().bitcast(types::F64, MemFlags::new(), idx);
+    let idx = builder.ins().fcvt_to_uint_sat(types::I64, idx);
+    let offset = builder.ins().imul_imm(idx, kind.size() as i64);
+    Ok(builder.ins().iadd(data, offset))
+}
+
+/// Load one element and widen it to f64
+fn load_typed_element(builder: &mut FunctionBuilder, addr: Value, kind: ElementKind) -> Value {
+    let flags = MemFlags::trusted();
+    match kind {
+        ElementKind::Int8 => {
+            let v = builder.ins().sload8(types::I32, flags, addr, 0);
+            builder.ins().fcvt_from_sint(types::F64, v)
+        }
+        ElementKind::Uint8 | ElementKind::Uint8Clamped => {
+            let v = builder.ins().uload8(types::I32, flags, addr, 0);
+            builder.ins().fcvt_from_uint(types::F64, v)
+        }
+        ElementKind::Int16 => {
+            let v = builder.ins().sload16(types::I32, flags, addr, 0);
+            builder.ins().fcvt_from_sint(types::F64, v)
+        }
+        ElementKind::Uint16 => {
+            let v = builder.ins().uload16(types::I32, flags, addr, 0);
+            builder.ins().fcvt_from_uint(types::F64, v)
+        }
+        ElementKind::Int32 => {
+            let v = builder.ins().load(types::I32, flags, addr, 0);
+            builder.ins().fcvt_from_sint(types::F64, v)
+        }
+        ElementKind::Uint32 => {
+            let v = builder.ins().load(types::I32, flags, addr, 0);
+            builder.ins().fcvt_from_uint(types::F64, v)
+        }
+        ElementKind::Float32 => {
+            let v = builder.ins().load(types::F32, flags, addr, 0);
+            builder.ins().fpromote(types::F64, v)
+        }
+        ElementKind::Float64 => builder.ins().load(types::F64, flags, addr, 0),
+    }
+}
+
+/// Convert an f64 to the element type and store it
+fn store_typed_element(
+    builder: &mut FunctionBuilder,
+    addr: Value,
+    value: Value,
+    kind: ElementKind,
+) {
+    let flags = MemFlags::trusted();
+    match kind {
+        ElementKind::Float64 => {
+            builder.ins().store(flags, value, addr, 0);
+        }
+        ElementKind::Float32 => {
+            let v = builder.ins().fdemote(types::F32, value);
+            builder.ins().store(flags, v, addr, 0);
+        }
+        ElementKind::Uint8Clamped => {
+            // Clamp to [0, 255] and round half to even; NaN saturates to 0
+            let lo = builder.ins().f64const(0.0);
+            let hi = builder.ins().f64const(255.0);
+            let v = builder.ins().fmin(value, hi);
+            let v = builder.ins().fmax(v, lo);
+            let v = builder.ins().nearest(v);
+            let v = builder.ins().fcvt_to_uint_sat(types::I32, v);
+            builder.ins().istore8(flags, v, addr, 0);
+        }
+        _ => {
+            // Integer elements wrap like ToInt32
+            let int = truncate_to_i64(builder, value);
+            match kind.size() {
+                1 => builder.ins().istore8(flags, int, addr, 0),
+                2 => builder.ins().istore16(flags, int, addr, 0),
+                _ => builder.ins().istore32(flags, int, addr, 0),
+            };
+        }
+    }
+}
+
+/// Truncate an f64 to an i64 whose low 32 bits are its ToInt32. Going
+/// through i64 keeps those bits exact for |x| < 2^63; NaN and infinities
+/// give 0.
+fn truncate_to_i64(builder: &mut FunctionBuilder, value: Value) -> Value {
+    let int = builder.ins().fcvt_to_sint_sat(types::I64, value);
+    let magnitude = builder.ins().fabs(value);
+    let limit = builder.ins().f64const(9.223_372_036_854_776e18);
+    let in_range = builder.ins().fcmp(FloatCC::LessThan, magnitude, limit);
+    let zero = builder.ins().iconst(types::I64, 0);
+    builder.ins().select(in_range, int, zero)
+}
+
+/// ToUint32 of an f64, as an i32
+fn to_uint32(builder: &mut FunctionBuilder, value: Value) -> Value {
+    let int = truncate_to_i64(builder, value);
+    builder.ins().ireduce(types::I32, int)
+}
+
+/// Replace any NaN with the canonical one, which is safe to NaN-box
+fn canonicalize_nan(builder: &mut FunctionBuilder, value: Value) -> Value {
+    let is_nan = builder.ins().fcmp(FloatCC::Unordered, value, value);
+    let nan = builder.ins().f64const(f64::NAN);
+    builder.ins().select(is_nan, nan, value)
+}
+
+/// Compile an intrinsic inline, on f64 operands, to an f64 result
+fn translate_intrinsic(
+    builder: &mut FunctionBuilder,
+    intrinsic: Intrinsic,
+    args: &[Value],
+) -> Result<Value, BackendError> {
+    if args.len() != intrinsic.arity() {
+        return Err(BackendError::Cranelift(format!(
+            "intrinsic {} takes {} operands, got {}",
+            intrinsic.name(),
+            intrinsic.arity(),
+            args.len()
+        )));
+    }
+    let x = args[0];
+    let result = match intrinsic {
+        Intrinsic::Clz32 | Intrinsic::Ctz32 | Intrinsic::Popcnt32 => {
+            let int = to_uint32(builder, x);
+            let count = match intrinsic {
+                Intrinsic::Clz32 => builder.ins().clz(int),
+                Intrinsic::Ctz32 => builder.ins().ctz(int),
+                _ => builder.ins().popcnt(int),
+            };
+            builder.ins().fcvt_from_uint(types::F64, count)
+        }
+        Intrinsic::Imul => {
+            let a = to_uint32(builder, x);
+            let b = to_uint32(builder, args[1]);
+            let product = builder.ins().imul(a, b);
+            builder.ins().fcvt_from_sint(types::F64, product)
+        }
+        Intrinsic::Fround => {
+            let single = builder.ins().fdemote(types::F32, x);
+            builder.ins().fpromote(types::F64, single)
+        }
+        Intrinsic::Sqrt => builder.ins().sqrt(x),
+        Intrinsic::Abs => builder.ins().fabs(x),
+        Intrinsic::Floor => builder.ins().floor(x),
+        Intrinsic::Ceil => builder.ins().ceil(x),
+        Intrinsic::Trunc => builder.ins().trunc(x),
+        Intrinsic::F32ToBits => {
+            let single = builder.ins().fdemote(types::F32, x);
+            let bits = builder.ins().bitcast(types::I32, MemFlags::new(), single);
+            let is_nan = builder.ins().fcmp(FloatCC::Unordered, x, x);
+            let nan = builder.ins().iconst(types::I32, 0x7FC0_0000);
+            let bits = builder.ins().select(is_nan, nan, bits);
+            builder.ins().fcvt_from_uint(types::F64, bits)
+        }
+        Intrinsic::F32FromBits => {
+            let bits = to_uint32(builder, x);
+            let single = builder.ins().bitcast(types::F32, MemFlags::new(), bits);
+            let double = builder.ins().fpromote(types::F64, single);
+            canonicalize_nan(builder, double)
+        }
+        Intrinsic::F64HighBits => {
+            let bits = builder.ins().bitcast(types::I64, MemFlags::new(), x);
+            let high = builder.ins().ushr_imm(bits, 32);
+            builder.ins().fcvt_from_uint(types::F64, high)
+        }
+        Intrinsic::F64LowBits => {
+            let bits = builder.ins().bitcast(types::I64, MemFlags::new(), x);
+            let low = builder.ins().ireduce(types::I32, bits);
+            builder.ins().fcvt_from_uint(types::F64, low)
+        }
+        Intrinsic::F64FromBits => {
+            let high = to_uint32(builder, x);
+            let low = to_uint32(builder, args[1]);
+            let high = builder.ins().uextend(types::I64, high);
+            let low = builder.ins().uextend(types::I64, low);
+            let high = builder.ins().ishl_imm(high, 32);
+            let bits = builder.ins().bor(high, low);
+            let double = builder.ins().bitcast(types::F64, MemFlags::new(), bits);
+            canonicalize_nan(builder, double)
+        }
+    };
+    Ok(result)
+}
+
 /// Call a runtime stub with IR value IDs as arguments
 fn call_stub(
     builder: &mut FunctionBuilder,
-    module: &mut JITModule,
+    module: &mut JitModule,
     ctx: &mut TranslationContext,
     name: &str,
     args: &[ValueId],
@@ -1098,7 +1433,7 @@ fn call_stub(
 /// Call a runtime stub with Cranelift values as arguments
 fn call_stub_with_values(
     builder: &mut FunctionBuilder,
-    module: &mut JITModule,
+    module: &mut JitModule,
     ctx: &mut TranslationContext,
     name: &str,
     args: &[Value],
@@ -1142,7 +1477,7 @@ fn call_stub_with_values(
 /// Call a runtime stub with no arguments
 fn call_stub_no_args(
     builder: &mut FunctionBuilder,
-    module: &mut JITModule,
+    module: &mut JitModule,
     ctx: &mut TranslationContext,
     name: &str,
 ) -> Result<Value, BackendError> {
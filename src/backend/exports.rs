@@ -0,0 +1,182 @@
+//! Export manifest for library builds
+//!
+//! `oitec build --format lib` (or `dylib`) writes `<output>.exports.json`
+//! next to the library, listing the functions a host program can call:
+//!
+//! ```json
+//! {
+//!   "abi": "nan-boxed-i64",
+//!   "functions": [
+//!     {
+//!       "name": "scale",
+//!       "symbol": "func_12",
+//!       "entry": "ot_export_scale",
+//!       "source": "src/math.ot",
+//!       "params": [{ "name": "v", "type": "number" }],
+//!       "returns": "number"
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! Every argument and return value crosses the boundary as a NaN-boxed
+//! 64-bit value (`int64_t`); a function returning `void` returns nothing.
+//! `entry` is the function's plugin entry point, callable through
+//! `runtime.callPlugin` (see `runtime::plugin`).
+//! Types come from the parameter and return annotations where they are
+//! written, and from IR type inference otherwise.
+
+use crate::ir::{IrFunction, IrModule, IrType};
+use crate::runtime::plugin::export_symbol;
+use crate::types::{FunctionType, Type};
+use serde_json::{Value, json};
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// Calling convention recorded in the manifest
+pub const ABI: &str = "nan-boxed-i64";
+
+/// Top-level functions a library exposes, by source name: the ones bound to
+/// exactly one function. The top-level script itself (`main`) is not one.
+pub fn exported_functions(module: &IrModule) -> Vec<(&str, &IrFunction)> {
+    let mut functions: Vec<(&str, &IrFunction)> = module
+        .function_names
+        .iter()
+        .filter_map(|(name, &idx)| Some((name.as_str(), module.functions.get(idx)?)))
+        .filter(|(_, func)| func.name != "main")
+        .collect();
+    functions.sort_by_key(|(name, _)| *name);
+    functions
+}
+
+/// Symbols of the functions in `exported_functions`
+pub fn exported_symbols(module: &IrModule) -> HashSet<String> {
+    exported_functions(module)
+        .into_iter()
+        .map(|(_, func)| func.name.clone())
+        .collect()
+}
+
+/// Where the manifest of a library written to `output` goes
+pub fn manifest_path(output: &Path) -> PathBuf {
+    let mut name = output.file_name().unwrap_or_default().to_os_string();
+    name.push(".exports.json");
+    output.with_file_name(name)
+}
+
+/// Manifest of the functions exported by each `(source path, module,
+/// declared signatures)` of a build
+pub fn manifest(modules: &[(&str, &IrModule, &BTreeMap<String, FunctionType>)]) -> Value {
+    let mut functions = Vec::new();
+    for (source, module, signatures) in modules {
+        for (name, func) in exported_functions(module) {
+            let declared = signatures.get(name);
+            let params: Vec<Value> = func
+                .params
+                .iter()
+                .enumerate()
+                .map(|(i, (param, ty))| {
+                    let annotation = declared.and_then(|sig| sig.params.get(i)).map(|(_, t)| t);
+                    json!({ "name": param, "type": type_name(annotation, ty) })
+                })
+                .collect();
+            let returns = type_name(declared.map(|sig| &sig.return_ty), &func.return_ty);
+            functions.push(json!({
+                "name": name,
+                "symbol": func.name,
+                "entry": export_symbol(name),
+                "source": source,
+                "params": params,
+                "returns": returns,
+            }));
+        }
+    }
+    json!({ "abi": ABI, "functions": functions })
+}
+
+/// The annotated type when there is a useful one, else the inferred type
+fn type_name(annotation: Option<&Type>, inferred: &IrType) -> String {
+    match annotation {
+        Some(Type::Any | Type::Error) | None => inferred_name(inferred).to_string(),
+        Some(ty) => ty.to_string(),
+    }
+}
+
+fn inferred_name(ty: &IrType) -> &'static str {
+    match ty {
+        IrType::Number => "number",
+        IrType::String => "string",
+        IrType::Boolean => "boolean",
+        IrType::Array => "any[]",
+        IrType::TypedArray(kind) => kind.name(),
+        IrType::Simd(_) => "number[]",
+        IrType::Function => "Function",
+        IrType::Object | IrType::Struct(_) => "object",
+        IrType::Ref(inner) | IrType::MutRef(inner) => inferred_name(inner),
+        IrType::Void | IrType::Never => "void",
+        IrType::Any => "any",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn module() -> IrModule {
+        let mut module = IrModule::new();
+        for (name, symbol) in [("scale", "func_4"), ("log", "func_9")] {
+            let mut func = IrFunction::new(symbol.to_string());
+            func.params = vec![
+                ("v".to_string(), IrType::Any),
+                ("by".to_string(), IrType::Number),
+            ];
+            func.return_ty = IrType::Void;
+            let idx = module.add_function(func);
+            module.function_names.insert(name.to_string(), idx);
+        }
+        module.add_function(IrFunction::new("main".to_string()));
+        module
+    }
+
+    #[test]
+    fn test_manifest_lists_exported_functions() {
+        let module = module();
+        let mut signatures = BTreeMap::new();
+        signatures.insert(
+            "scale".to_string(),
+            FunctionType::new(
+                vec![
+                    ("v".to_string(), Type::Array(Box::new(Type::Number))),
+                    ("by".to_string(), Type::Any),
+                ],
+                Type::Number,
+            ),
+        );
+        let manifest = manifest(&[("math.ot", &module, &signatures)]);
+        assert_eq!(manifest["abi"], ABI);
+        let functions = manifest["functions"].as_array().unwrap();
+        assert_eq!(functions.len(), 2);
+        assert_eq!(functions[0]["name"], "log");
+        assert_eq!(functions[0]["params"][0]["type"], "any");
+        assert_eq!(functions[0]["returns"], "void");
+        assert_eq!(functions[1]["symbol"], "func_4");
+        assert_eq!(functions[1]["entry"], "ot_export_scale");
+        assert_eq!(functions[1]["source"], "math.ot");
+        assert_eq!(functions[1]["params"][0]["type"], "number[]");
+        assert_eq!(functions[1]["params"][1]["type"], "number");
+        assert_eq!(functions[1]["returns"], "number");
+
+        assert_eq!(
+            exported_symbols(&module),
+            HashSet::from(["func_4".to_string(), "func_9".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_manifest_sits_next_to_the_library() {
+        assert_eq!(
+            manifest_path(Path::new("out/libmath.a")),
+            Path::new("out/libmath.a.exports.json")
+        );
+    }
+}
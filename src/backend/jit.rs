@@ -10,6 +10,7 @@ use super::cranelift::CraneliftCodegen;
 use super::{BackendConfig, BackendError};
 use crate::ir::IrModule;
 use crate::runtime::abi::OtValue;
+use crate::runtime::safepoint;
 
 /// JIT runtime for executing compiled code
 pub struct JitRuntime {
@@ -68,6 +69,7 @@ impl JitRuntime {
         let main_fn: extern "C" fn() -> u64 = unsafe { std::mem::transmute(ptr) };
         let result = main_fn();
 
+        check_interrupted()?;
         Ok(OtValue::from_bits(result))
     }
 
@@ -103,6 +105,7 @@ impl JitRuntime {
             }
         };
 
+        check_interrupted()?;
         Ok(OtValue::from_bits(result))
     }
 
@@ -120,6 +123,15 @@ impl JitRuntime {
     }
 }
 
+/// Turn a stop request that unwound compiled code into an error, clearing it
+/// so the next call runs normally.
+fn check_interrupted() -> Result<(), BackendError> {
+    if safepoint::take_stop() {
+        return Err(BackendError::JitError("execution interrupted".into()));
+    }
+    Ok(())
+}
+
 /// Compiled function handle for type-safe calls
 pub struct CompiledFunction {
     ptr: *const u8,
@@ -247,4 +259,155 @@ mod tests {
         let val = result.unwrap();
         assert_eq!(val.as_number(), Some(7.0));
     }
+
+    #[test]
+    fn test_safepoint_stops_infinite_loop() {
+        let config = BackendConfig::default();
+        let mut runtime = JitRuntime::new(&config).unwrap();
+
+        // bb0: jump bb1; bb1: jump bb1
+        let mut func = IrFunction::new("spin".to_string());
+        let entry = func.alloc_block();
+        let header = func.alloc_block();
+        func.block_mut(entry).terminate(Terminator::Jump(header));
+        func.block_mut(header).terminate(Terminator::Jump(header));
+
+        let mut module = IrModule::new();
+        module.add_function(func);
+        runtime.compile(&module).unwrap();
+
+        safepoint::set_time_limit(std::time::Duration::from_millis(20));
+        let result = runtime.call_func("spin", &[]);
+        assert!(
+            matches!(result, Err(BackendError::JitError(msg)) if msg == "execution interrupted")
+        );
+
+        // The stop request was consumed
+        assert_eq!(safepoint::pending(), 0);
+    }
+
+    #[test]
+    fn test_stop_unwinds_compiled_callers() {
+        use crate::runtime::heap::{ElementKind, NativeTypedArray, heap};
+
+        let config = BackendConfig::default();
+        let mut runtime = JitRuntime::new(&config).unwrap();
+        let mut module = IrModule::new();
+
+        // func_1: bb0: jump bb1; bb1: jump bb1
+        let mut spin = IrFunction::new("func_1".to_string());
+        let entry = spin.alloc_block();
+        let header = spin.alloc_block();
+        spin.block_mut(entry).terminate(Terminator::Jump(header));
+        spin.block_mut(header).terminate(Terminator::Jump(header));
+        let spin = module.add_function(spin);
+        module.function_addrs.insert(1, spin);
+
+        // caller(out): func_1(); out[0] = 1; return out
+        let mut caller = IrFunction::new("caller".to_string());
+        caller.params = vec![("out".to_string(), IrType::Any)];
+        let entry = caller.alloc_block();
+        let out = caller.alloc_value(IrType::Any);
+        let callee = caller.alloc_value(IrType::Number);
+        let result = caller.alloc_value(IrType::Any);
+        let index = caller.alloc_value(IrType::Number);
+        let one = caller.alloc_value(IrType::Number);
+        let block = caller.block_mut(entry);
+        block.push(IrOp::Const(callee, Literal::Number(1.0)));
+        block.push(IrOp::Call(result, callee, vec![]));
+        block.push(IrOp::Const(index, Literal::Number(0.0)));
+        block.push(IrOp::Const(one, Literal::Number(1.0)));
+        block.push(IrOp::TypedStore(
+            out,
+            index,
+            one,
+            ElementKind::Float64,
+            false,
+        ));
+        block.terminate(Terminator::Return(Some(out)));
+        module.add_function(caller);
+        runtime.compile(&module).unwrap();
+
+        let array = heap().alloc_typed_array(ElementKind::Float64, 1).unwrap();
+        safepoint::set_time_limit(std::time::Duration::from_millis(20));
+        let result = runtime.call_func("caller", &[OtValue::pointer(array)]);
+        assert!(
+            matches!(result, Err(BackendError::JitError(msg)) if msg == "execution interrupted")
+        );
+
+        // The caller returned at the call instead of going on to the store
+        let array = unsafe { array.as_mut::<NativeTypedArray>() };
+        assert_eq!(array.get(0), Some(0.0));
+    }
+    #[test]
+    fn test_intrinsics_match_the_interpreter() {
+        use crate::ir::intrinsics::Intrinsic;
+
+        let config = BackendConfig::default();
+        let mut runtime = JitRuntime::new(&config).unwrap();
+
+        // One function per intrinsic: return intrinsic(a[, b])
+        let mut module = IrModule::new();
+        for intrinsic in Intrinsic::ALL {
+            let mut func = IrFunction::new(format!("intrinsic_{}", intrinsic.name()));
+            let entry = func.alloc_block();
+            let mut args = Vec::new();
+            for i in 0..intrinsic.arity() {
+                func.params.push((format!("p{}", i), IrType::Number));
+                args.push(func.alloc_value(IrType::Number));
+            }
+            let result = func.alloc_value(IrType::Number);
+            func.block_mut(entry)
+                .push(IrOp::Intrinsic(result, intrinsic, args));
+            func.block_mut(entry)
+                .terminate(Terminator::Return(Some(result)));
+            module.add_function(func);
+        }
+        runtime.compile(&module).unwrap();
+
+        let inputs = [
+            0.0,
+            -0.0,
+            1.0,
+            -1.0,
+            2.5,
+            -7.75,
+            65536.0,
+            4294967295.0,
+            4294967296.5,
+            -2147483649.0,
+            // NaN bit patterns whose payload would read as a boxed tag
+            2145386496.0,
+            2147221504.0,
+            1e-310,
+            1.5e300,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+            f64::NAN,
+        ];
+        for intrinsic in Intrinsic::ALL {
+            let name = format!("intrinsic_{}", intrinsic.name());
+            let calls: Vec<Vec<f64>> = match intrinsic.arity() {
+                1 => inputs.iter().map(|&a| vec![a]).collect(),
+                _ => inputs
+                    .iter()
+                    .flat_map(|&a| inputs.iter().map(move |&b| vec![a, b]))
+                    .collect(),
+            };
+            for args in calls {
+                let values: Vec<OtValue> = args.iter().map(|&n| OtValue::number(n)).collect();
+                let got = runtime.call_func(&name, &values).unwrap();
+                let got = got.as_number().unwrap();
+                let expected = intrinsic.apply(&args);
+                assert!(
+                    got.to_bits() == expected.to_bits() || got.is_nan() && expected.is_nan(),
+                    "{:?}{:?} = {} (expected {})",
+                    intrinsic,
+                    args,
+                    got,
+                    expected
+                );
+            }
+        }
+    }
 }
@@ -0,0 +1,579 @@
+//! Cranelift module for the JIT, with W^X code memory
+//!
+//! Functions are compiled into pages owned by `CodeMemory`, linked in
+//! `finalize_definitions`, and only then made executable. Code pages are
+//! never writable and executable at the same time:
+//!
+//! - Apple Silicon: pages are mapped with `MAP_JIT` and the writing thread
+//!   toggles `pthread_jit_write_protect_np` around every write. The hardened
+//!   runtime refuses `mprotect`-based transitions there.
+//! - Everywhere else: pages are mapped read-write and flipped to
+//!   read-execute once the functions in them are linked.
+//!
+//! Relocations are applied for x86-64 and AArch64. Direct calls whose target
+//! is out of range of the call instruction (±2 GiB for `call rel32`, ±128 MiB
+//! for `bl`) are redirected through a veneer placed right after the caller.
+
+use cranelift_codegen::binemit::Reloc;
+use cranelift_codegen::control::ControlPlane;
+use cranelift_codegen::ir::{self, LibCall};
+use cranelift_codegen::isa::{OwnedTargetIsa, TargetIsa};
+use cranelift_codegen::{Context, FinalizedMachReloc};
+use cranelift_module::{
+    DataDescription, DataId, FuncId, Linkage, Module, ModuleDeclarations, ModuleError, ModuleReloc,
+    ModuleRelocTarget, ModuleResult,
+};
+use std::collections::HashMap;
+use std::io;
+
+/// Bytes reserved per veneer: an indirect jump plus its 8-byte target
+const VENEER_SIZE: usize = 16;
+
+/// Smallest mapping requested from the OS for code
+const CHUNK_SIZE: usize = 64 * 1024;
+
+pub struct JitModule {
+    isa: OwnedTargetIsa,
+    declarations: ModuleDeclarations,
+    /// Host symbols callable from JIT code (runtime stubs)
+    symbols: HashMap<String, *const u8>,
+    memory: CodeMemory,
+    functions: HashMap<FuncId, CompiledFunction>,
+    /// Defined since the last `finalize_definitions`
+    unlinked: Vec<FuncId>,
+}
+
+struct CompiledFunction {
+    ptr: *mut u8,
+    relocs: Vec<ModuleReloc>,
+    /// Start of the veneer slots following the code
+    veneers: *mut u8,
+}
+
+impl JitModule {
+    pub fn new(isa: OwnedTargetIsa) -> Self {
+        Self {
+            isa,
+            declarations: ModuleDeclarations::default(),
+            symbols: HashMap::new(),
+            memory: CodeMemory::default(),
+            functions: HashMap::new(),
+            unlinked: Vec::new(),
+        }
+    }
+
+    /// Make a host function available to imported declarations named `name`.
+    pub fn symbol(&mut self, name: &str, ptr: *const u8) {
+        self.symbols.insert(name.to_string(), ptr);
+    }
+
+    /// Link every function defined since the last call and make it executable.
+    pub fn finalize_definitions(&mut self) -> Result<(), String> {
+        let unlinked = std::mem::take(&mut self.unlinked);
+        let mut patches = Vec::new();
+        for id in &unlinked {
+            let function = &self.functions[id];
+            let mut veneer = function.veneers;
+            for reloc in &function.relocs {
+                let target = self
+                    .resolve(&reloc.name)?
+                    .wrapping_offset(reloc.addend as isize);
+                let at = unsafe { function.ptr.add(reloc.offset as usize) };
+                let slot = if is_direct_call(reloc.kind) {
+                    let slot = veneer;
+                    veneer = unsafe { veneer.add(VENEER_SIZE) };
+                    Some(slot)
+                } else {
+                    None
+                };
+                patches.push((at, reloc.kind, target, slot));
+            }
+        }
+
+        self.memory.write(|| {
+            patches
+                .iter()
+                .try_for_each(|&(at, kind, target, veneer)| unsafe {
+                    apply_reloc(at, kind, target, veneer)
+                })
+        })?;
+        self.memory
+            .seal()
+            .map_err(|e| format!("failed to make JIT code executable: {}", e))
+    }
+
+    /// Entry point of a defined function; valid after `finalize_definitions`.
+    pub fn get_finalized_function(&self, id: FuncId) -> *const u8 {
+        self.functions[&id].ptr
+    }
+
+    fn resolve(&self, target: &ModuleRelocTarget) -> Result<*const u8, String> {
+        match target {
+            ModuleRelocTarget::User {
+                namespace: 0,
+                index,
+            } => {
+                let id = FuncId::from_u32(*index);
+                if let Some(function) = self.functions.get(&id) {
+                    return Ok(function.ptr);
+                }
+                let name = self.declarations.get_function_decl(id).linkage_name(id);
+                self.symbols
+                    .get(name.as_ref())
+                    .copied()
+                    .or_else(|| lookup_host_symbol(&name))
+                    .ok_or_else(|| format!("undefined symbol '{}' in JIT code", name))
+            }
+            ModuleRelocTarget::FunctionOffset(id, offset) => self
+                .functions
+                .get(id)
+                .map(|function| function.ptr.wrapping_add(*offset as usize) as *const u8)
+                .ok_or_else(|| format!("reference into undefined function {}", id)),
+            ModuleRelocTarget::LibCall(libcall) => libcall_address(*libcall)
+                .ok_or_else(|| format!("libcall {} is not available to JIT code", libcall)),
+            other => Err(format!("unsupported relocation target {}", other)),
+        }
+    }
+
+    // Error type is fixed by the Module trait
+    #[allow(clippy::result_large_err)]
+    fn install(
+        &mut self,
+        id: FuncId,
+        alignment: u64,
+        code: &[u8],
+        relocs: Vec<ModuleReloc>,
+    ) -> ModuleResult<()> {
+        let decl = self.declarations.get_function_decl(id);
+        if !decl.linkage.is_definable() {
+            return Err(ModuleError::InvalidImportDefinition(
+                decl.linkage_name(id).into_owned(),
+            ));
+        }
+        if self.functions.contains_key(&id) {
+            return Err(ModuleError::DuplicateDefinition(
+                decl.linkage_name(id).into_owned(),
+            ));
+        }
+
+        let calls = relocs.iter().filter(|r| is_direct_call(r.kind)).count();
+        let veneer_offset = code.len().next_multiple_of(8);
+        let size = veneer_offset + calls * VENEER_SIZE;
+        let align = (alignment as usize).max(16);
+        let ptr =
+            self.memory
+                .allocate(size.max(1), align)
+                .map_err(|err| ModuleError::Allocation {
+                    message: "unable to allocate JIT code memory",
+                    err,
+                })?;
+        self.memory
+            .write(|| unsafe { std::ptr::copy_nonoverlapping(code.as_ptr(), ptr, code.len()) });
+
+        self.functions.insert(
+            id,
+            CompiledFunction {
+                ptr,
+                relocs,
+                veneers: unsafe { ptr.add(veneer_offset) },
+            },
+        );
+        self.unlinked.push(id);
+        Ok(())
+    }
+}
+
+impl Module for JitModule {
+    fn isa(&self) -> &dyn TargetIsa {
+        &*self.isa
+    }
+
+    fn declarations(&self) -> &ModuleDeclarations {
+        &self.declarations
+    }
+
+    fn declare_function(
+        &mut self,
+        name: &str,
+        linkage: Linkage,
+        signature: &ir::Signature,
+    ) -> ModuleResult<FuncId> {
+        let (id, _) = self
+            .declarations
+            .declare_function(name, linkage, signature)?;
+        Ok(id)
+    }
+
+    fn declare_anonymous_function(&mut self, signature: &ir::Signature) -> ModuleResult<FuncId> {
+        self.declarations.declare_anonymous_function(signature)
+    }
+
+    fn declare_data(
+        &mut self,
+        name: &str,
+        linkage: Linkage,
+        writable: bool,
+        tls: bool,
+    ) -> ModuleResult<DataId> {
+        let (id, _) = self
+            .declarations
+            .declare_data(name, linkage, writable, tls)?;
+        Ok(id)
+    }
+
+    fn declare_anonymous_data(&mut self, writable: bool, tls: bool) -> ModuleResult<DataId> {
+        self.declarations.declare_anonymous_data(writable, tls)
+    }
+
+    fn define_function_with_control_plane(
+        &mut self,
+        id: FuncId,
+        ctx: &mut Context,
+        ctrl_plane: &mut ControlPlane,
+    ) -> ModuleResult<()> {
+        ctx.compile(self.isa(), ctrl_plane)?;
+        let compiled = ctx
+            .compiled_code()
+            .expect("compile() succeeded without producing code");
+        let relocs = compiled
+            .buffer
+            .relocs()
+            .iter()
+            .map(|reloc| ModuleReloc::from_mach_reloc(reloc, &ctx.func, id))
+            .collect();
+        let alignment = compiled.buffer.alignment as u64;
+        self.install(id, alignment, compiled.code_buffer(), relocs)
+    }
+
+    fn define_function_bytes(
+        &mut self,
+        id: FuncId,
+        func: &ir::Function,
+        alignment: u64,
+        bytes: &[u8],
+        relocs: &[FinalizedMachReloc],
+    ) -> ModuleResult<()> {
+        let relocs = relocs
+            .iter()
+            .map(|reloc| ModuleReloc::from_mach_reloc(reloc, func, id))
+            .collect();
+        self.install(id, alignment, bytes, relocs)
+    }
+
+    fn define_data(&mut self, _id: DataId, _data: &DataDescription) -> ModuleResult<()> {
+        Err(ModuleError::Allocation {
+            message: "data objects are not supported by the JIT",
+            err: io::ErrorKind::Unsupported.into(),
+        })
+    }
+}
+
+/// Relocations that encode a direct branch, and so may need a veneer
+fn is_direct_call(kind: Reloc) -> bool {
+    matches!(
+        kind,
+        Reloc::Arm64Call | Reloc::X86CallPCRel4 | Reloc::X86CallPLTRel4
+    )
+}
+
+/// Patch one relocation at `at`. `veneer` is the caller's spare slot for
+/// direct calls that can't reach `target`.
+unsafe fn apply_reloc(
+    at: *mut u8,
+    kind: Reloc,
+    target: *const u8,
+    veneer: Option<*mut u8>,
+) -> Result<(), String> {
+    let pc_relative = |to: *const u8| (to as i64).wrapping_sub(at as i64);
+    unsafe {
+        match kind {
+            Reloc::Abs8 => std::ptr::write_unaligned(at as *mut u64, target as u64),
+            Reloc::Abs4 => {
+                let value = u32::try_from(target as u64)
+                    .map_err(|_| format!("Abs4 relocation target {:p} above 4 GiB", target))?;
+                std::ptr::write_unaligned(at as *mut u32, value)
+            }
+            Reloc::X86PCRel4 => {
+                let delta = i32::try_from(pc_relative(target))
+                    .map_err(|_| format!("X86PCRel4 relocation to {:p} out of range", target))?;
+                std::ptr::write_unaligned(at as *mut i32, delta)
+            }
+            Reloc::X86CallPCRel4 | Reloc::X86CallPLTRel4 => {
+                // The addend (-4) is already in `target`; the veneer is
+                // addressed from the same place, so apply it there too.
+                let delta = match i32::try_from(pc_relative(target)) {
+                    Ok(delta) => delta,
+                    Err(_) => {
+                        let slot = veneer.ok_or("missing veneer slot for call")?;
+                        write_x86_64_veneer(slot, target.wrapping_add(4));
+                        i32::try_from(pc_relative(slot.wrapping_sub(4)))
+                            .map_err(|_| "call veneer out of range".to_string())?
+                    }
+                };
+                std::ptr::write_unaligned(at as *mut i32, delta)
+            }
+            Reloc::Arm64Call => {
+                let mut delta = pc_relative(target);
+                if !fits_arm64_branch(delta) {
+                    let slot = veneer.ok_or("missing veneer slot for call")?;
+                    write_aarch64_veneer(slot, target);
+                    delta = pc_relative(slot);
+                }
+                let insn = std::ptr::read_unaligned(at as *const u32);
+                let imm26 = ((delta >> 2) as u32) & 0x03ff_ffff;
+                std::ptr::write_unaligned(at as *mut u32, (insn & 0xfc00_0000) | imm26)
+            }
+            other => return Err(format!("unsupported relocation kind {:?}", other)),
+        }
+    }
+    Ok(())
+}
+
+/// Whether a byte offset fits the signed 26-bit word immediate of `b`/`bl`
+fn fits_arm64_branch(delta: i64) -> bool {
+    delta % 4 == 0 && (-(1 << 27)..(1 << 27)).contains(&delta)
+}
+
+/// `ldr x16, #8; br x16; .quad target`
+unsafe fn write_aarch64_veneer(slot: *mut u8, target: *const u8) {
+    unsafe {
+        std::ptr::write_unaligned(slot as *mut u32, 0x5800_0050);
+        std::ptr::write_unaligned(slot.add(4) as *mut u32, 0xd61f_0200);
+        std::ptr::write_unaligned(slot.add(8) as *mut u64, target as u64);
+    }
+}
+
+/// `jmp [rip + 0]; .quad target`
+unsafe fn write_x86_64_veneer(slot: *mut u8, target: *const u8) {
+    unsafe {
+        std::ptr::copy_nonoverlapping([0xff, 0x25, 0, 0, 0, 0].as_ptr(), slot, 6);
+        std::ptr::write_unaligned(slot.add(6) as *mut u64, target as u64);
+    }
+}
+
+/// Functions Cranelift calls instead of inlining an instruction sequence
+fn libcall_address(libcall: LibCall) -> Option<*const u8> {
+    extern "C" fn ceil_f32(x: f32) -> f32 {
+        x.ceil()
+    }
+    extern "C" fn ceil_f64(x: f64) -> f64 {
+        x.ceil()
+    }
+    extern "C" fn floor_f32(x: f32) -> f32 {
+        x.floor()
+    }
+    extern "C" fn floor_f64(x: f64) -> f64 {
+        x.floor()
+    }
+    extern "C" fn trunc_f32(x: f32) -> f32 {
+        x.trunc()
+    }
+    extern "C" fn trunc_f64(x: f64) -> f64 {
+        x.trunc()
+    }
+    extern "C" fn nearest_f32(x: f32) -> f32 {
+        x.round_ties_even()
+    }
+    extern "C" fn nearest_f64(x: f64) -> f64 {
+        x.round_ties_even()
+    }
+    extern "C" fn fma_f32(a: f32, b: f32, c: f32) -> f32 {
+        a.mul_add(b, c)
+    }
+    extern "C" fn fma_f64(a: f64, b: f64, c: f64) -> f64 {
+        a.mul_add(b, c)
+    }
+
+    let address = match libcall {
+        LibCall::CeilF32 => ceil_f32 as *const u8,
+        LibCall::CeilF64 => ceil_f64 as *const u8,
+        LibCall::FloorF32 => floor_f32 as *const u8,
+        LibCall::FloorF64 => floor_f64 as *const u8,
+        LibCall::TruncF32 => trunc_f32 as *const u8,
+        LibCall::TruncF64 => trunc_f64 as *const u8,
+        LibCall::NearestF32 => nearest_f32 as *const u8,
+        LibCall::NearestF64 => nearest_f64 as *const u8,
+        LibCall::FmaF32 => fma_f32 as *const u8,
+        LibCall::FmaF64 => fma_f64 as *const u8,
+        LibCall::Memcpy => libc::memcpy as *const u8,
+        LibCall::Memset => libc::memset as *const u8,
+        LibCall::Memmove => libc::memmove as *const u8,
+        LibCall::Memcmp => libc::memcmp as *const u8,
+        _ => return None,
+    };
+    Some(address)
+}
+
+/// Symbols not registered with `JitModule::symbol`, looked up in the process
+#[cfg(unix)]
+fn lookup_host_symbol(name: &str) -> Option<*const u8> {
+    let name = std::ffi::CString::new(name).ok()?;
+    let ptr = unsafe { libc::dlsym(libc::RTLD_DEFAULT, name.as_ptr()) };
+    (!ptr.is_null()).then_some(ptr as *const u8)
+}
+
+#[cfg(not(unix))]
+fn lookup_host_symbol(_name: &str) -> Option<*const u8> {
+    None
+}
+
+/// Pages holding JIT code
+#[derive(Default)]
+struct CodeMemory {
+    chunks: Vec<Chunk>,
+}
+
+struct Chunk {
+    pages: region::Allocation,
+    used: usize,
+    /// Executable; nothing more is placed here
+    sealed: bool,
+}
+
+impl CodeMemory {
+    fn allocate(&mut self, size: usize, align: usize) -> io::Result<*mut u8> {
+        if let Some(chunk) = self.chunks.last_mut()
+            && !chunk.sealed
+        {
+            let offset = chunk.used.next_multiple_of(align);
+            if offset + size <= chunk.pages.len() {
+                chunk.used = offset + size;
+                return Ok(unsafe { chunk.pages.as_mut_ptr::<u8>().add(offset) });
+            }
+        }
+
+        let pages = region::alloc(size.max(CHUNK_SIZE), WRITABLE).map_err(to_io_error)?;
+        let mut chunk = Chunk {
+            pages,
+            used: size,
+            sealed: false,
+        };
+        // Page-aligned, so any smaller alignment holds
+        let ptr = chunk.pages.as_mut_ptr::<u8>();
+        self.chunks.push(chunk);
+        Ok(ptr)
+    }
+
+    /// Run `f`, which writes to unsealed chunks, with write access enabled.
+    fn write<T>(&self, f: impl FnOnce() -> T) -> T {
+        let _window = WriteWindow::open();
+        f()
+    }
+
+    /// Make every unsealed chunk executable.
+    fn seal(&mut self) -> io::Result<()> {
+        for chunk in self.chunks.iter_mut().filter(|chunk| !chunk.sealed) {
+            let start = chunk.pages.as_ptr::<u8>();
+            if !APPLE_JIT {
+                unsafe {
+                    region::protect(start, chunk.pages.len(), region::Protection::READ_EXECUTE)
+                }
+                .map_err(to_io_error)?;
+            }
+            unsafe { wasmtime_jit_icache_coherence::clear_cache(start.cast(), chunk.used)? };
+            chunk.sealed = true;
+        }
+        wasmtime_jit_icache_coherence::pipeline_flush_mt()
+    }
+}
+
+fn to_io_error(err: region::Error) -> io::Error {
+    match err {
+        region::Error::SystemCall(err) => err,
+        other => io::Error::other(other.to_string()),
+    }
+}
+
+/// Apple Silicon maps code `MAP_JIT` and switches write access per thread
+const APPLE_JIT: bool = cfg!(all(target_vendor = "apple", target_arch = "aarch64"));
+
+/// Initial protection of new code pages. `region` adds `MAP_JIT` for RWX
+/// mappings on Apple Silicon; the thread-local write protection then keeps
+/// them from being writable and executable at once.
+const WRITABLE: region::Protection = if APPLE_JIT {
+    region::Protection::READ_WRITE_EXECUTE
+} else {
+    region::Protection::READ_WRITE
+};
+
+/// Lifts `MAP_JIT` write protection for the current thread while alive
+struct WriteWindow;
+
+impl WriteWindow {
+    fn open() -> Self {
+        #[cfg(all(target_vendor = "apple", target_arch = "aarch64"))]
+        unsafe {
+            libc::pthread_jit_write_protect_np(0);
+        }
+        WriteWindow
+    }
+}
+
+impl Drop for WriteWindow {
+    fn drop(&mut self) {
+        #[cfg(all(target_vendor = "apple", target_arch = "aarch64"))]
+        unsafe {
+            libc::pthread_jit_write_protect_np(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arm64_branch_range() {
+        assert!(fits_arm64_branch(0));
+        assert!(fits_arm64_branch((1 << 27) - 4));
+        assert!(fits_arm64_branch(-(1 << 27)));
+        assert!(!fits_arm64_branch(1 << 27));
+        assert!(!fits_arm64_branch(6));
+    }
+
+    #[test]
+    fn test_out_of_range_arm64_call_goes_through_veneer() {
+        let mut code = [0u8; 4 + VENEER_SIZE];
+        code[..4].copy_from_slice(&0x9400_0000u32.to_le_bytes()); // bl #0
+        let at = code.as_mut_ptr();
+        let veneer = unsafe { at.add(4) };
+        let target = (at as usize).wrapping_add(1 << 30) as *const u8;
+        unsafe { apply_reloc(at, Reloc::Arm64Call, target, Some(veneer)).unwrap() };
+
+        // bl +4 (to the veneer), which loads and jumps to the real target
+        assert_eq!(
+            u32::from_le_bytes(code[..4].try_into().unwrap()),
+            0x9400_0001
+        );
+        assert_eq!(
+            u32::from_le_bytes(code[4..8].try_into().unwrap()),
+            0x5800_0050
+        );
+        assert_eq!(
+            u64::from_le_bytes(code[12..20].try_into().unwrap()),
+            target as u64
+        );
+    }
+
+    #[test]
+    fn test_code_memory_runs_written_code() {
+        #[cfg(target_arch = "x86_64")]
+        let ret42: &[u8] = &[0xb8, 42, 0, 0, 0, 0xc3]; // mov eax, 42; ret
+        #[cfg(target_arch = "aarch64")]
+        let ret42: &[u8] = &[0x40, 0x05, 0x80, 0x52, 0xc0, 0x03, 0x5f, 0xd6]; // mov w0, #42; ret
+        #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+        return;
+
+        #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+        {
+            let mut memory = CodeMemory::default();
+            let ptr = memory.allocate(ret42.len(), 16).unwrap();
+            memory.write(|| unsafe {
+                std::ptr::copy_nonoverlapping(ret42.as_ptr(), ptr, ret42.len())
+            });
+            memory.seal().unwrap();
+            let f: extern "C" fn() -> u32 = unsafe { std::mem::transmute(ptr) };
+            assert_eq!(f(), 42);
+        }
+    }
+}
@@ -130,6 +130,9 @@ pub fn value_size(ty: &IrType) -> u32 {
         // Typed arrays: same as untyped (pointer to heap)
         IrType::TypedArray(_) => VALUE_SIZE,
 
+        // Vectors: all lanes, unboxed
+        IrType::Simd(_) => 16,
+
         // Struct reference: pointer to heap-allocated struct
         IrType::Struct(_) => VALUE_SIZE,
 
@@ -14,6 +14,8 @@ use std::ffi::{CString, c_char};
 use std::ptr;
 
 use crate::backend::BackendError;
+use crate::runtime::ABI_VERSION;
+use crate::runtime::plugin::{PLUGIN_ABI_SYMBOL, export_symbol};
 
 /// Declare and define all runtime stubs in the LLVM module
 pub unsafe fn declare_runtime_stubs(
@@ -32,6 +34,339 @@ pub unsafe fn declare_runtime_stubs(
         // Simple stubs that just return undefined or passthrough
         define_simple_stubs(module, context, stubs)?;
 
+        // The plugin loader lives in the runtime library
+        declare_plugin_stubs(module, context, stubs);
+
+        // Error reporting, replaced by the runtime library's when linked
+        define_error_stubs(module, context, stubs)?;
+
+        Ok(())
+    }
+}
+
+/// Emit the startup version check into `function` at the builder's position:
+/// `if (ot_abi_check) ot_abi_check(ABI_VERSION);`. The symbol is weak, so
+/// binaries linked without the runtime library skip the check. Leaves the
+/// builder at the end of the continuation block.
+pub unsafe fn build_abi_check(
+    module: LLVMModuleRef,
+    context: LLVMContextRef,
+    builder: LLVMBuilderRef,
+    function: LLVMValueRef,
+) {
+    unsafe {
+        let i32_ty = LLVMInt32TypeInContext(context);
+        let void_ty = LLVMVoidTypeInContext(context);
+        let mut param_types = vec![i32_ty];
+        let check_ty = LLVMFunctionType(void_ty, param_types.as_mut_ptr(), 1, 0);
+
+        let name = CString::new("ot_abi_check").unwrap();
+        let mut check = LLVMGetNamedFunction(module, name.as_ptr());
+        if check.is_null() {
+            check = LLVMAddFunction(module, name.as_ptr(), check_ty);
+            LLVMSetLinkage(check, llvm_sys::LLVMLinkage::LLVMExternalWeakLinkage);
+        }
+
+        let call_bb = LLVMAppendBasicBlockInContext(
+            context,
+            function,
+            b"abi_check\0".as_ptr() as *const c_char,
+        );
+        let cont_bb =
+            LLVMAppendBasicBlockInContext(context, function, b"abi_ok\0".as_ptr() as *const c_char);
+        let linked = LLVMBuildIsNotNull(builder, check, b"has_runtime\0".as_ptr() as *const c_char);
+        LLVMBuildCondBr(builder, linked, call_bb, cont_bb);
+
+        LLVMPositionBuilderAtEnd(builder, call_bb);
+        let mut args = vec![LLVMConstInt(i32_ty, ABI_VERSION as u64, 0)];
+        LLVMBuildCall2(
+            builder,
+            check_ty,
+            check,
+            args.as_mut_ptr(),
+            1,
+            b"\0".as_ptr() as *const c_char,
+        );
+        LLVMBuildBr(builder, cont_bb);
+
+        LLVMPositionBuilderAtEnd(builder, cont_bb);
+    }
+}
+
+/// Emit `ot_runtime_init()` into the generated `main`, so runtime panics
+/// are reported with the script stack
+pub unsafe fn build_runtime_init(
+    module: LLVMModuleRef,
+    context: LLVMContextRef,
+    builder: LLVMBuilderRef,
+) {
+    unsafe {
+        let name = CString::new("ot_runtime_init").unwrap();
+        let init = LLVMGetNamedFunction(module, name.as_ptr());
+        if init.is_null() {
+            return;
+        }
+        let init_ty = LLVMFunctionType(LLVMVoidTypeInContext(context), ptr::null_mut(), 0, 0);
+        LLVMBuildCall2(
+            builder,
+            init_ty,
+            init,
+            ptr::null_mut(),
+            0,
+            b"\0".as_ptr() as *const c_char,
+        );
+    }
+}
+
+/// Define the error reporting symbols of ABI 4 with weak linkage, so the
+/// runtime library's definitions win when it is linked. Without it the
+/// frame calls do nothing and `ot_runtime_error` prints its message without
+/// a trace and exits with status 70.
+unsafe fn define_error_stubs(
+    module: LLVMModuleRef,
+    context: LLVMContextRef,
+    stubs: &mut BTreeMap<String, LLVMValueRef>,
+) -> Result<(), BackendError> {
+    unsafe {
+        let void_ty = LLVMVoidTypeInContext(context);
+        let i32_ty = LLVMInt32TypeInContext(context);
+        let i64_ty = LLVMInt64TypeInContext(context);
+        let i8_ptr_ty = LLVMPointerType(LLVMInt8TypeInContext(context), 0);
+
+        let declarations: [(&str, Vec<LLVMTypeRef>); 5] = [
+            ("ot_runtime_init", vec![]),
+            ("ot_frame_enter", vec![i8_ptr_ty, i64_ty, i8_ptr_ty, i64_ty]),
+            ("ot_frame_exit", vec![]),
+            ("ot_frame_line", vec![i32_ty]),
+            ("ot_runtime_error", vec![i32_ty, i8_ptr_ty, i64_ty]),
+        ];
+        for (name, mut param_types) in declarations {
+            let func_name = CString::new(name).unwrap();
+            if !LLVMGetNamedFunction(module, func_name.as_ptr()).is_null() {
+                continue;
+            }
+            let func_ty = LLVMFunctionType(
+                void_ty,
+                param_types.as_mut_ptr(),
+                param_types.len() as u32,
+                0,
+            );
+            let func = LLVMAddFunction(module, func_name.as_ptr(), func_ty);
+            if func.is_null() {
+                return Err(BackendError::Llvm(format!("Failed to create {}", name)));
+            }
+            LLVMSetLinkage(func, llvm_sys::LLVMLinkage::LLVMWeakAnyLinkage);
+            LLVMSetVisibility(func, llvm_sys::LLVMVisibility::LLVMDefaultVisibility);
+
+            let entry_bb =
+                LLVMAppendBasicBlockInContext(context, func, b"entry\0".as_ptr() as *const c_char);
+            let builder = LLVMCreateBuilderInContext(context);
+            LLVMPositionBuilderAtEnd(builder, entry_bb);
+            if name == "ot_runtime_error" {
+                // write(2, "InternalError: "), write(2, message), write(2, "\n"), exit(70)
+                let write_name = CString::new("write").unwrap();
+                let write = LLVMGetNamedFunction(module, write_name.as_ptr());
+                let mut write_params = vec![i32_ty, i8_ptr_ty, i64_ty];
+                let write_ty = LLVMFunctionType(i64_ty, write_params.as_mut_ptr(), 3, 0);
+                let stderr = LLVMConstInt(i32_ty, 2, 0);
+                let label = b"InternalError: \0";
+                let pieces = [
+                    (
+                        LLVMBuildGlobalStringPtr(
+                            builder,
+                            label.as_ptr() as *const c_char,
+                            b"error_label\0".as_ptr() as *const c_char,
+                        ),
+                        LLVMConstInt(i64_ty, label.len() as u64 - 1, 0),
+                    ),
+                    (LLVMGetParam(func, 1), LLVMGetParam(func, 2)),
+                    (
+                        LLVMBuildGlobalStringPtr(
+                            builder,
+                            b"\n\0".as_ptr() as *const c_char,
+                            b"newline\0".as_ptr() as *const c_char,
+                        ),
+                        LLVMConstInt(i64_ty, 1, 0),
+                    ),
+                ];
+                for (data, len) in pieces {
+                    let mut args = vec![stderr, data, len];
+                    LLVMBuildCall2(
+                        builder,
+                        write_ty,
+                        write,
+                        args.as_mut_ptr(),
+                        3,
+                        b"\0".as_ptr() as *const c_char,
+                    );
+                }
+                let exit_name = CString::new("exit").unwrap();
+                let exit = LLVMGetNamedFunction(module, exit_name.as_ptr());
+                let mut exit_params = vec![i32_ty];
+                let exit_ty = LLVMFunctionType(void_ty, exit_params.as_mut_ptr(), 1, 0);
+                let mut args = vec![LLVMConstInt(i32_ty, 70, 0)];
+                LLVMBuildCall2(
+                    builder,
+                    exit_ty,
+                    exit,
+                    args.as_mut_ptr(),
+                    1,
+                    b"\0".as_ptr() as *const c_char,
+                );
+                LLVMBuildUnreachable(builder);
+            } else {
+                LLVMBuildRetVoid(builder);
+            }
+            LLVMDisposeBuilder(builder);
+            stubs.insert(name.to_string(), func);
+        }
+        Ok(())
+    }
+}
+
+/// Declare `ot_plugin_load` and `ot_plugin_call`. They have no IR body: a
+/// program that loads plugins links the runtime library, which defines them.
+unsafe fn declare_plugin_stubs(
+    module: LLVMModuleRef,
+    context: LLVMContextRef,
+    stubs: &mut BTreeMap<String, LLVMValueRef>,
+) {
+    unsafe {
+        let i64_ty = LLVMInt64TypeInContext(context);
+        let i64_ptr_ty = LLVMPointerType(i64_ty, 0);
+        // ot_plugin_load(path) and ot_plugin_call(plugin, name, argc, argv)
+        let declarations: [(&str, Vec<LLVMTypeRef>); 2] = [
+            ("ot_plugin_load", vec![i64_ty]),
+            ("ot_plugin_call", vec![i64_ty, i64_ty, i64_ty, i64_ptr_ty]),
+        ];
+        for (name, mut param_types) in declarations {
+            let func_name = CString::new(name).unwrap();
+            let mut func = LLVMGetNamedFunction(module, func_name.as_ptr());
+            if func.is_null() {
+                let func_ty = LLVMFunctionType(
+                    i64_ty,
+                    param_types.as_mut_ptr(),
+                    param_types.len() as u32,
+                    0,
+                );
+                func = LLVMAddFunction(module, func_name.as_ptr(), func_ty);
+            }
+            stubs.insert(name.to_string(), func);
+        }
+    }
+}
+
+/// Emit `ot_plugin_abi`, the ABI version a plugin was built for. Every
+/// module of a library carries it, so it is linkonce and the copies merge.
+pub unsafe fn define_plugin_abi(module: LLVMModuleRef, context: LLVMContextRef) {
+    unsafe {
+        let name = CString::new(PLUGIN_ABI_SYMBOL).unwrap();
+        if !LLVMGetNamedGlobal(module, name.as_ptr()).is_null() {
+            return;
+        }
+        let i32_ty = LLVMInt32TypeInContext(context);
+        let global = LLVMAddGlobal(module, i32_ty, name.as_ptr());
+        LLVMSetInitializer(global, LLVMConstInt(i32_ty, ABI_VERSION as u64, 0));
+        LLVMSetGlobalConstant(global, 1);
+        LLVMSetLinkage(global, llvm_sys::LLVMLinkage::LLVMLinkOnceODRLinkage);
+        LLVMSetVisibility(global, llvm_sys::LLVMVisibility::LLVMDefaultVisibility);
+    }
+}
+
+/// Emit the plugin entry point `ot_export_<name>(argc, argv)`, which calls
+/// `target` with its `arity` arguments read from `argv`. Arguments past
+/// `argc` are `undefined`; a function without a result returns `undefined`.
+pub unsafe fn define_export_wrapper(
+    module: LLVMModuleRef,
+    context: LLVMContextRef,
+    name: &str,
+    target: LLVMValueRef,
+    arity: usize,
+    returns_value: bool,
+) -> Result<(), BackendError> {
+    unsafe {
+        let i64_ty = LLVMInt64TypeInContext(context);
+        let i64_ptr_ty = LLVMPointerType(i64_ty, 0);
+        let mut param_types = vec![i64_ty, i64_ptr_ty];
+        let func_ty = LLVMFunctionType(i64_ty, param_types.as_mut_ptr(), 2, 0);
+
+        let wrapper_name = CString::new(export_symbol(name))
+            .map_err(|_| BackendError::Llvm(format!("Invalid export name: {}", name)))?;
+        let wrapper = LLVMAddFunction(module, wrapper_name.as_ptr(), func_ty);
+        if wrapper.is_null() {
+            return Err(BackendError::Llvm(format!(
+                "Failed to create the entry point of {}",
+                name
+            )));
+        }
+        LLVMSetLinkage(wrapper, llvm_sys::LLVMLinkage::LLVMExternalLinkage);
+        LLVMSetVisibility(wrapper, llvm_sys::LLVMVisibility::LLVMDefaultVisibility);
+
+        let entry_bb =
+            LLVMAppendBasicBlockInContext(context, wrapper, b"entry\0".as_ptr() as *const c_char);
+        let builder = LLVMCreateBuilderInContext(context);
+        LLVMPositionBuilderAtEnd(builder, entry_bb);
+
+        let argc = LLVMGetParam(wrapper, 0);
+        let argv = LLVMGetParam(wrapper, 1);
+        let undefined = LLVMConstInt(i64_ty, 0x7FF8000000000001u64, 0);
+        // Missing arguments are read from here instead of past the end of argv
+        let undefined_slot = LLVMBuildAlloca(
+            builder,
+            i64_ty,
+            b"undefined_slot\0".as_ptr() as *const c_char,
+        );
+        LLVMBuildStore(builder, undefined, undefined_slot);
+
+        let mut args = Vec::with_capacity(arity);
+        for i in 0..arity {
+            let mut index = LLVMConstInt(i64_ty, i as u64, 0);
+            let passed = LLVMBuildICmp(
+                builder,
+                llvm_sys::LLVMIntPredicate::LLVMIntULT,
+                index,
+                argc,
+                b"passed\0".as_ptr() as *const c_char,
+            );
+            let arg_ptr = LLVMBuildGEP2(
+                builder,
+                i64_ty,
+                argv,
+                &mut index,
+                1,
+                b"arg_ptr\0".as_ptr() as *const c_char,
+            );
+            let slot = LLVMBuildSelect(
+                builder,
+                passed,
+                arg_ptr,
+                undefined_slot,
+                b"arg_slot\0".as_ptr() as *const c_char,
+            );
+            args.push(LLVMBuildLoad2(
+                builder,
+                i64_ty,
+                slot,
+                b"arg\0".as_ptr() as *const c_char,
+            ));
+        }
+
+        // A call returning void can't be named
+        let call_name: &[u8] = if returns_value { b"result\0" } else { b"\0" };
+        let result = LLVMBuildCall2(
+            builder,
+            LLVMGlobalGetValueType(target),
+            target,
+            if args.is_empty() {
+                ptr::null_mut()
+            } else {
+                args.as_mut_ptr()
+            },
+            args.len() as u32,
+            call_name.as_ptr() as *const c_char,
+        );
+        LLVMBuildRet(builder, if returns_value { result } else { undefined });
+        LLVMDisposeBuilder(builder);
         Ok(())
     }
 }
@@ -53,6 +388,28 @@ unsafe fn declare_libc_functions(
             LLVMAddFunction(module, printf_name.as_ptr(), printf_ty);
         }
 
+        // ssize_t write(int fd, const void* buf, size_t count)
+        let i64_ty = LLVMInt64TypeInContext(context);
+        let write_name = CString::new("write").unwrap();
+        if LLVMGetNamedFunction(module, write_name.as_ptr()).is_null() {
+            let mut param_types = vec![i32_ty, i8_ptr_ty, i64_ty];
+            let write_ty = LLVMFunctionType(i64_ty, param_types.as_mut_ptr(), 3, 0);
+            LLVMAddFunction(module, write_name.as_ptr(), write_ty);
+        }
+
+        // void exit(int status)
+        let exit_name = CString::new("exit").unwrap();
+        if LLVMGetNamedFunction(module, exit_name.as_ptr()).is_null() {
+            let mut param_types = vec![i32_ty];
+            let exit_ty = LLVMFunctionType(
+                LLVMVoidTypeInContext(context),
+                param_types.as_mut_ptr(),
+                1,
+                0,
+            );
+            LLVMAddFunction(module, exit_name.as_ptr(), exit_ty);
+        }
+
         Ok(())
     }
 }
@@ -464,6 +821,30 @@ unsafe fn define_simple_stubs(
             create_void_stub("ot_set_element", &mut [i64_ty, i64_ty, i64_ty])?,
         );
 
+        // Typed array stubs (bounds-checked paths; in-bounds accesses are inline)
+        stubs.insert(
+            "ot_alloc_typed_array".to_string(),
+            create_returning_undefined("ot_alloc_typed_array", &mut [i64_ty, i64_ty])?,
+        );
+        stubs.insert(
+            "ot_typed_array_get".to_string(),
+            create_returning_undefined("ot_typed_array_get", &mut [i64_ty, i64_ty])?,
+        );
+        stubs.insert(
+            "ot_typed_array_set".to_string(),
+            create_void_stub("ot_typed_array_set", &mut [i64_ty, i64_ty, i64_ty])?,
+        );
+        stubs.insert(
+            "ot_typed_array_len".to_string(),
+            create_returning_undefined("ot_typed_array_len", &mut [i64_ty])?,
+        );
+
+        // SIMD vector operands, loaded from the typed array this returns
+        stubs.insert(
+            "ot_simd_lanes".to_string(),
+            create_returning_undefined("ot_simd_lanes", &mut [i64_ty, i64_ty, i64_ty])?,
+        );
+
         // Dynamic arithmetic stubs - perform actual operations
         // These treat values as NaN-boxed doubles: bitcast to double, operate, bitcast back
 
@@ -7,16 +7,21 @@
 #![allow(clippy::missing_safety_doc)]
 
 use llvm_sys::prelude::*;
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::ffi::{CString, c_char};
 
 use crate::backend::BackendError;
+use crate::ir::intrinsics::Intrinsic;
+use crate::ir::simd::{SimdKind, SimdOp};
 use crate::ir::{
     BasicBlock, BlockId, IrFunction, IrModule, IrOp, IrType, Literal, Terminator, ValueId,
 };
 
 use super::abi;
+use super::debuginfo::DebugInfo;
 use super::types;
+use crate::runtime::error::ErrorKind;
+use crate::runtime::heap::{ElementKind, NativeTypedArray};
 
 /// LLVM code generator for AOT compilation
 pub struct LlvmCodegen {
@@ -30,6 +35,10 @@ pub struct LlvmCodegen {
     pub stubs: BTreeMap<String, LLVMValueRef>,
     /// Compiled function values (name -> function value)
     pub functions: BTreeMap<String, LLVMValueRef>,
+    /// Functions that keep default visibility for a library's host
+    pub exported: HashSet<String>,
+    /// Emit DWARF line tables for modules with a source file
+    pub debug_info: bool,
 }
 
 impl LlvmCodegen {
@@ -66,6 +75,8 @@ impl LlvmCodegen {
                 target_triple,
                 stubs: BTreeMap::new(),
                 functions: BTreeMap::new(),
+                exported: HashSet::new(),
+                debug_info: false,
             })
         }
     }
@@ -89,9 +100,41 @@ impl LlvmCodegen {
                 self.declare_function(&func_name, func, &struct_types)?;
             }
 
+            // Line tables need a source file to point at
+            let debug = match &ir_module.source_file {
+                Some(file) if self.debug_info => {
+                    Some(DebugInfo::new(self.module, self.context, file))
+                }
+                _ => None,
+            };
+
             // Compile each function
             for func in &ir_module.functions {
-                self.compile_function(func, ir_module, &struct_types)?;
+                self.compile_function(func, ir_module, &struct_types, debug.as_ref())?;
+            }
+            if let Some(debug) = &debug {
+                debug.finish();
+            }
+
+            // A library's exports get plugin entry points
+            if !self.exported.is_empty() {
+                abi::define_plugin_abi(self.module, self.context);
+                for (name, func) in crate::backend::exports::exported_functions(ir_module) {
+                    if !self.exported.contains(&func.name) {
+                        continue;
+                    }
+                    if let Some(&target) = self.functions.get(&func.name) {
+                        let returns_value = !matches!(func.return_ty, IrType::Void | IrType::Never);
+                        abi::define_export_wrapper(
+                            self.module,
+                            self.context,
+                            name,
+                            target,
+                            func.params.len(),
+                            returns_value,
+                        )?;
+                    }
+                }
             }
 
             // Create C-compatible main wrapper if tscl main exists
@@ -175,6 +218,11 @@ impl LlvmCodegen {
                     let builder = llvm_sys::core::LLVMCreateBuilderInContext(self.context);
                     llvm_sys::core::LLVMPositionBuilderAtEnd(builder, entry);
 
+                    // Report runtime panics, then refuse to start against a
+                    // runtime that can't serve this binary
+                    abi::build_runtime_init(self.module, self.context, builder);
+                    abi::build_abi_check(self.module, self.context, builder, c_main);
+
                     let ot_main_ty = llvm_sys::core::LLVMGlobalGetValueType(ot_main);
                     let _ot_result = llvm_sys::core::LLVMBuildCall2(
                         builder,
@@ -268,8 +316,8 @@ impl LlvmCodegen {
 
             // Set visibility: hidden for internal functions, default for main and runtime stubs
             // This enables LTO to eliminate unused code
-            if name == "main" {
-                // Main must be visible for linking
+            if name == "main" || self.exported.contains(name) {
+                // Main and a library's exports must be visible for linking
                 llvm_sys::core::LLVMSetVisibility(
                     func_val,
                     llvm_sys::LLVMVisibility::LLVMDefaultVisibility,
@@ -292,6 +340,7 @@ impl LlvmCodegen {
         func: &IrFunction,
         ir_module: &IrModule,
         struct_types: &BTreeMap<u32, LLVMTypeRef>,
+        debug: Option<&DebugInfo>,
     ) -> Result<(), BackendError> {
         unsafe {
             let func_name = if func.name.is_empty() {
@@ -327,8 +376,28 @@ impl LlvmCodegen {
                 functions: &self.functions,
                 function_addrs: &ir_module.function_addrs,
                 return_ty: func.return_ty.clone(),
+                globals: HashMap::new(),
+                debug: None,
+                source_lines: &func.source_lines,
+                frame_name: func_name.clone(),
             };
 
+            // Debuggers and profilers show the script's name for the function
+            if let Some(debug) = debug {
+                let name = ir_module
+                    .function_names
+                    .iter()
+                    .filter(|&(_, &idx)| std::ptr::eq(&ir_module.functions[idx], func))
+                    .map(|(name, _)| name.as_str())
+                    .min()
+                    .unwrap_or(&func_name);
+                let line = func.source_lines.values().min().copied().unwrap_or(1);
+                let scope = debug.function(func_val, name, line);
+                debug.set_line(builder, scope, line);
+                ctx.debug = Some((debug, scope));
+                ctx.frame_name = name.to_string();
+            }
+
             // Create blocks for all IR blocks
             for block in &func.blocks {
                 let block_name = format!("bb{}\0", block.id.0);
@@ -367,6 +436,13 @@ impl LlvmCodegen {
                     let param = llvm_sys::core::LLVMGetParam(func_val, i as u32);
                     ctx.values.insert(ValueId(i as u32), param);
                 }
+
+                // Debug builds keep a shadow stack for runtime error traces
+                if let Some((debug, _)) = ctx.debug {
+                    let (name, name_len) = global_string(&ctx, &ctx.frame_name);
+                    let (file, file_len) = global_string(&ctx, &debug.source_file);
+                    call_void_stub(&ctx, "ot_frame_enter", &[name, name_len, file, file_len])?;
+                }
             }
 
             // Translate each block
@@ -438,6 +514,14 @@ struct TranslationContext<'a> {
     function_addrs: &'a HashMap<usize, usize>,
     /// Function return type (for handling Return(None) correctly)
     return_ty: IrType,
+    /// Values loaded from a global, by the global's name
+    globals: HashMap<ValueId, String>,
+    /// Debug info and the function's scope, when emitting line tables
+    debug: Option<(&'a DebugInfo, LLVMMetadataRef)>,
+    /// Source line of each value
+    source_lines: &'a HashMap<ValueId, u32>,
+    /// Function name shown in runtime error traces
+    frame_name: String,
 }
 
 /// Translate a basic block
@@ -448,7 +532,19 @@ unsafe fn translate_block(
 ) -> Result<(), BackendError> {
     unsafe {
         // Translate operations
+        let mut current_line = None;
         for op in &block.ops {
+            // Ops without a result stay on the previous op's line
+            if let Some((debug, scope)) = ctx.debug
+                && let Some(&line) = op.dest().and_then(|dst| ctx.source_lines.get(&dst))
+                && current_line != Some(line)
+            {
+                debug.set_line(ctx.builder, scope, line);
+                let i32_ty = llvm_sys::core::LLVMInt32TypeInContext(ctx.context);
+                let line_val = llvm_sys::core::LLVMConstInt(i32_ty, line as u64, 0);
+                call_void_stub(ctx, "ot_frame_line", &[line_val])?;
+                current_line = Some(line);
+            }
             translate_op(ctx, op)?;
         }
 
@@ -683,6 +779,13 @@ unsafe fn translate_op(ctx: &mut TranslationContext, op: &IrOp) -> Result<(), Ba
                     return Err(BackendError::Llvm(format!("Invalid local slot: {}", slot)));
                 }
             }
+            IrOp::LoadGlobal(dst, name) => {
+                // Globals are not materialized in native code yet; method
+                // calls on stdlib objects (console.log) dispatch by name
+                let undefined = translate_literal(ctx, &Literal::Undefined)?;
+                ctx.values.insert(*dst, undefined);
+                ctx.globals.insert(*dst, name.clone());
+            }
             IrOp::StoreLocal(slot, src) => {
                 if let Some(&alloca) = ctx.locals.get(*slot as usize) {
                     let val = get_value(ctx, *src)?;
@@ -942,6 +1045,55 @@ unsafe fn translate_op(ctx: &mut TranslationContext, op: &IrOp) -> Result<(), Ba
                 let val_val = get_value(ctx, *val)?;
                 call_stub(ctx, "ot_set_element", &[obj_val, idx_val, val_val])?;
             }
+            IrOp::TypedArrayLen(dst, arr) => {
+                let arr_val = get_value(ctx, *arr)?;
+                let i32_ty = llvm_sys::core::LLVMInt32TypeInContext(ctx.context);
+                let len_ptr = typed_array_field(ctx, arr_val, NativeTypedArray::LEN_OFFSET);
+                let len = llvm_sys::core::LLVMBuildLoad2(
+                    ctx.builder,
+                    i32_ty,
+                    len_ptr,
+                    b"len\0".as_ptr() as *const c_char,
+                );
+                let len = llvm_sys::core::LLVMBuildUIToFP(
+                    ctx.builder,
+                    len,
+                    llvm_sys::core::LLVMDoubleTypeInContext(ctx.context),
+                    b"len_f64\0".as_ptr() as *const c_char,
+                );
+                ctx.values.insert(*dst, double_to_bits(ctx, len));
+            }
+            IrOp::TypedLoad(dst, arr, idx, _, true) => {
+                let arr_val = get_value(ctx, *arr)?;
+                let idx_val = get_value(ctx, *idx)?;
+                let result = call_stub(ctx, "ot_typed_array_get", &[arr_val, idx_val])?;
+                ctx.values.insert(*dst, result);
+            }
+            IrOp::TypedLoad(dst, arr, idx, kind, false) => {
+                let addr =
+                    typed_element_addr(ctx, get_value(ctx, *arr)?, get_value(ctx, *idx)?, *kind);
+                let value = load_typed_element(ctx, addr, *kind);
+                ctx.values.insert(*dst, double_to_bits(ctx, value));
+            }
+            IrOp::TypedStore(arr, idx, val, kind, checked) => {
+                let arr_val = get_value(ctx, *arr)?;
+                let idx_val = get_value(ctx, *idx)?;
+                let val_val = get_value(ctx, *val)?;
+                // Clamped stores round half to even, which needs an intrinsic;
+                // they keep going through the runtime
+                if *checked || *kind == ElementKind::Uint8Clamped {
+                    call_stub(ctx, "ot_typed_array_set", &[arr_val, idx_val, val_val])?;
+                } else {
+                    let addr = typed_element_addr(ctx, arr_val, idx_val, *kind);
+                    let value = llvm_sys::core::LLVMBuildBitCast(
+                        ctx.builder,
+                        val_val,
+                        llvm_sys::core::LLVMDoubleTypeInContext(ctx.context),
+                        b"val_f64\0".as_ptr() as *const c_char,
+                    );
+                    store_typed_element(ctx, addr, value, *kind);
+                }
+            }
             IrOp::NewArray(dst) => {
                 let capacity = llvm_sys::core::LLVMConstInt(
                     llvm_sys::core::LLVMInt64TypeInContext(ctx.context),
@@ -966,16 +1118,76 @@ unsafe fn translate_op(ctx: &mut TranslationContext, op: &IrOp) -> Result<(), Ba
                 let result = call_indirect(ctx, func_ptr, &arg_values)?;
                 ctx.values.insert(*dst, result);
             }
-            IrOp::CallMethod(dst, _obj, name, args) => {
+            IrOp::CallMethod(dst, obj, name, args) => {
+                let on_runtime = ctx.globals.get(obj).is_some_and(|g| g == "runtime");
                 if name == "log" && !args.is_empty() {
                     let arg_val = get_value(ctx, args[0])?;
                     let result = call_stub(ctx, "ot_console_log", &[arg_val])?;
                     ctx.values.insert(*dst, result);
+                } else if on_runtime && name == "loadPlugin" {
+                    let path = match args.first() {
+                        Some(path) => get_value(ctx, *path)?,
+                        None => translate_literal(ctx, &Literal::Undefined)?,
+                    };
+                    let result = call_stub(ctx, "ot_plugin_load", &[path])?;
+                    ctx.values.insert(*dst, result);
+                } else if on_runtime && name == "callPlugin" && args.len() >= 2 {
+                    // runtime.callPlugin(plugin, name, ...args)
+                    let plugin = get_value(ctx, args[0])?;
+                    let func_name = get_value(ctx, args[1])?;
+                    let (argc, argv) = build_arg_array(ctx, &args[2..])?;
+                    let result =
+                        call_stub(ctx, "ot_plugin_call", &[plugin, func_name, argc, argv])?;
+                    ctx.values.insert(*dst, result);
                 } else {
                     let undefined = translate_literal(ctx, &Literal::Undefined)?;
                     ctx.values.insert(*dst, undefined);
                 }
             }
+            IrOp::ToNum(dst, val) => {
+                let v = get_value(ctx, *val)?;
+                let result = call_stub(ctx, "ot_to_number", &[v])?;
+                ctx.values.insert(*dst, result);
+            }
+            IrOp::Intrinsic(dst, intrinsic, args) => {
+                if args.len() != intrinsic.arity() {
+                    return Err(BackendError::Llvm(format!(
+                        "intrinsic {} takes {} operands, got {}",
+                        intrinsic.name(),
+                        intrinsic.arity(),
+                        args.len()
+                    )));
+                }
+                let double_ty = llvm_sys::core::LLVMDoubleTypeInContext(ctx.context);
+                let mut operands = Vec::with_capacity(args.len());
+                for arg in args {
+                    operands.push(llvm_sys::core::LLVMBuildBitCast(
+                        ctx.builder,
+                        get_value(ctx, *arg)?,
+                        double_ty,
+                        b"bitcast\0".as_ptr() as *const c_char,
+                    ));
+                }
+                let result = translate_intrinsic(ctx, *intrinsic, &operands);
+                ctx.values.insert(*dst, double_to_bits(ctx, result));
+            }
+            IrOp::Simd(dst, kind, op, args) => {
+                if args.len() != op.arity(*kind) {
+                    return Err(BackendError::Llvm(format!(
+                        "SIMD operation {}.{} takes {} operands, got {}",
+                        kind,
+                        op,
+                        op.arity(*kind),
+                        args.len()
+                    )));
+                }
+                let operands: Vec<LLVMValueRef> = args
+                    .iter()
+                    .map(|id| get_value(ctx, *id))
+                    .collect::<Result<_, _>>()?;
+                let result = translate_simd(ctx, *kind, op, &operands)?;
+                ctx.values.insert(*dst, result);
+            }
             IrOp::MakeClosure(dst, addr, env) => {
                 let func_addr = llvm_sys::core::LLVMConstInt(
                     llvm_sys::core::LLVMInt64TypeInContext(ctx.context),
@@ -997,6 +1209,551 @@ unsafe fn translate_op(ctx: &mut TranslationContext, op: &IrOp) -> Result<(), Ba
     }
 }
 
+/// Pointer to the field at `offset` of the `NativeTypedArray` boxed in `arr`
+unsafe fn typed_array_field(
+    ctx: &TranslationContext,
+    arr: LLVMValueRef,
+    offset: usize,
+) -> LLVMValueRef {
+    unsafe {
+        let i64_ty = llvm_sys::core::LLVMInt64TypeInContext(ctx.context);
+        let ptr_ty = llvm_sys::core::LLVMPointerTypeInContext(ctx.context, 0);
+        let payload = llvm_sys::core::LLVMConstInt(i64_ty, 0x0000_FFFF_FFFF_FFFF, 0);
+        let addr = llvm_sys::core::LLVMBuildAnd(
+            ctx.builder,
+            arr,
+            payload,
+            b"arr_ptr\0".as_ptr() as *const c_char,
+        );
+        let offset = llvm_sys::core::LLVMConstInt(i64_ty, offset as u64, 0);
+        let addr = llvm_sys::core::LLVMBuildAdd(
+            ctx.builder,
+            addr,
+            offset,
+            b"field\0".as_ptr() as *const c_char,
+        );
+        llvm_sys::core::LLVMBuildIntToPtr(
+            ctx.builder,
+            addr,
+            ptr_ty,
+            b"field_ptr\0".as_ptr() as *const c_char,
+        )
+    }
+}
+
+/// Address of element `idx` of a typed array. The index must already be
+/// known to be an in-bounds integer.
+unsafe fn typed_element_addr(
+    ctx: &TranslationContext,
+    arr: LLVMValueRef,
+    idx: LLVMValueRef,
+    kind: ElementKind,
+) -> LLVMValueRef {
+    unsafe {
+        let i64_ty = llvm_sys::core::LLVMInt64TypeInContext(ctx.context);
+        let ptr_ty = llvm_sys::core::LLVMPointerTypeInContext(ctx.context, 0);
+        let data_ptr = typed_array_field(ctx, arr, NativeTypedArray::DATA_OFFSET);
+        let data = llvm_sys::core::LLVMBuildLoad2(
+            ctx.builder,
+            ptr_ty,
+            data_ptr,
+            b"data\0".as_ptr() as *const c_char,
+        );
+        let idx = llvm_sys::core::LLVMBuildBitCast(
+            ctx.builder,
+            idx,
+            llvm_sys::core::LLVMDoubleTypeInContext(ctx.context),
+            b"idx_f64\0".as_ptr() as *const c_char,
+        );
+        let mut idx = llvm_sys::core::LLVMBuildFPToUI(
+            ctx.builder,
+            idx,
+            i64_ty,
+            b"idx\0".as_ptr() as *const c_char,
+        );
+        llvm_sys::core::LLVMBuildGEP2(
+            ctx.builder,
+            element_llvm_type(ctx, kind),
+            data,
+            &mut idx,
+            1,
+            b"elem_ptr\0".as_ptr() as *const c_char,
+        )
+    }
+}
+
+/// LLVM type of one stored element
+unsafe fn element_llvm_type(ctx: &TranslationContext, kind: ElementKind) -> LLVMTypeRef {
+    unsafe {
+        match kind {
+            ElementKind::Float64 => llvm_sys::core::LLVMDoubleTypeInContext(ctx.context),
+            ElementKind::Float32 => llvm_sys::core::LLVMFloatTypeInContext(ctx.context),
+            _ => llvm_sys::core::LLVMIntTypeInContext(ctx.context, kind.size() as u32 * 8),
+        }
+    }
+}
+
+/// Load one element and widen it to a double
+unsafe fn load_typed_element(
+    ctx: &TranslationContext,
+    addr: LLVMValueRef,
+    kind: ElementKind,
+) -> LLVMValueRef {
+    unsafe {
+        let double_ty = llvm_sys::core::LLVMDoubleTypeInContext(ctx.context);
+        let elem_ty = element_llvm_type(ctx, kind);
+        let value = llvm_sys::core::LLVMBuildLoad2(
+            ctx.builder,
+            elem_ty,
+            addr,
+            b"elem\0".as_ptr() as *const c_char,
+        );
+        let name = b"elem_f64\0".as_ptr() as *const c_char;
+        match kind {
+            ElementKind::Float64 => value,
+            ElementKind::Float32 => {
+                llvm_sys::core::LLVMBuildFPExt(ctx.builder, value, double_ty, name)
+            }
+            ElementKind::Int8 | ElementKind::Int16 | ElementKind::Int32 => {
+                llvm_sys::core::LLVMBuildSIToFP(ctx.builder, value, double_ty, name)
+            }
+            _ => llvm_sys::core::LLVMBuildUIToFP(ctx.builder, value, double_ty, name),
+        }
+    }
+}
+
+/// Convert a double to the element type and store it. Integer elements wrap
+/// like ToInt32: truncating through i64 keeps the low bits exact for
+/// |x| < 2^63, and NaN and infinities store 0.
+unsafe fn store_typed_element(
+    ctx: &TranslationContext,
+    addr: LLVMValueRef,
+    value: LLVMValueRef,
+    kind: ElementKind,
+) {
+    unsafe {
+        let b = ctx.builder;
+        let value = match kind {
+            ElementKind::Float64 => value,
+            ElementKind::Float32 => llvm_sys::core::LLVMBuildFPTrunc(
+                b,
+                value,
+                llvm_sys::core::LLVMFloatTypeInContext(ctx.context),
+                b"elem_f32\0".as_ptr() as *const c_char,
+            ),
+            _ => llvm_sys::core::LLVMBuildTrunc(
+                b,
+                truncate_to_i64(ctx, value),
+                element_llvm_type(ctx, kind),
+                b"elem\0".as_ptr() as *const c_char,
+            ),
+        };
+        llvm_sys::core::LLVMBuildStore(ctx.builder, value, addr);
+    }
+}
+
+/// Truncate a double to an i64 whose low 32 bits are its ToInt32. Going
+/// through i64 keeps those bits exact for |x| < 2^63; NaN and infinities
+/// give 0.
+unsafe fn truncate_to_i64(ctx: &TranslationContext, value: LLVMValueRef) -> LLVMValueRef {
+    unsafe {
+        let b = ctx.builder;
+        let double_ty = llvm_sys::core::LLVMDoubleTypeInContext(ctx.context);
+        let i64_ty = llvm_sys::core::LLVMInt64TypeInContext(ctx.context);
+        let limit = llvm_sys::core::LLVMConstReal(double_ty, 9.223_372_036_854_776e18);
+        let zero = llvm_sys::core::LLVMConstReal(double_ty, 0.0);
+        let neg = llvm_sys::core::LLVMBuildFNeg(b, value, b"neg\0".as_ptr() as *const c_char);
+        let below = llvm_sys::core::LLVMBuildFCmp(
+            b,
+            llvm_sys::LLVMRealPredicate::LLVMRealOLT,
+            value,
+            limit,
+            b"below\0".as_ptr() as *const c_char,
+        );
+        let above = llvm_sys::core::LLVMBuildFCmp(
+            b,
+            llvm_sys::LLVMRealPredicate::LLVMRealOLT,
+            neg,
+            limit,
+            b"above\0".as_ptr() as *const c_char,
+        );
+        let in_range =
+            llvm_sys::core::LLVMBuildAnd(b, below, above, b"in_range\0".as_ptr() as *const c_char);
+        let value = llvm_sys::core::LLVMBuildSelect(
+            b,
+            in_range,
+            value,
+            zero,
+            b"finite\0".as_ptr() as *const c_char,
+        );
+        llvm_sys::core::LLVMBuildFPToSI(b, value, i64_ty, b"int\0".as_ptr() as *const c_char)
+    }
+}
+
+/// ToUint32 of a double, as an i32
+unsafe fn to_uint32(ctx: &TranslationContext, value: LLVMValueRef) -> LLVMValueRef {
+    unsafe {
+        llvm_sys::core::LLVMBuildTrunc(
+            ctx.builder,
+            truncate_to_i64(ctx, value),
+            llvm_sys::core::LLVMInt32TypeInContext(ctx.context),
+            b"uint32\0".as_ptr() as *const c_char,
+        )
+    }
+}
+
+/// Replace any NaN with the canonical one, which is safe to NaN-box
+unsafe fn canonicalize_nan(ctx: &TranslationContext, value: LLVMValueRef) -> LLVMValueRef {
+    unsafe {
+        let double_ty = llvm_sys::core::LLVMDoubleTypeInContext(ctx.context);
+        let is_nan = llvm_sys::core::LLVMBuildFCmp(
+            ctx.builder,
+            llvm_sys::LLVMRealPredicate::LLVMRealUNO,
+            value,
+            value,
+            b"is_nan\0".as_ptr() as *const c_char,
+        );
+        llvm_sys::core::LLVMBuildSelect(
+            ctx.builder,
+            is_nan,
+            llvm_sys::core::LLVMConstReal(double_ty, f64::NAN),
+            value,
+            b"canonical\0".as_ptr() as *const c_char,
+        )
+    }
+}
+
+/// Call an LLVM intrinsic such as `llvm.sqrt.f64`, declaring it on first use
+unsafe fn call_llvm_intrinsic(
+    ctx: &TranslationContext,
+    name: &str,
+    ret_ty: LLVMTypeRef,
+    args: &[LLVMValueRef],
+) -> LLVMValueRef {
+    unsafe {
+        let name_cstr = CString::new(name).unwrap();
+        let mut param_types: Vec<LLVMTypeRef> = args
+            .iter()
+            .map(|arg| llvm_sys::core::LLVMTypeOf(*arg))
+            .collect();
+        let func_ty = llvm_sys::core::LLVMFunctionType(
+            ret_ty,
+            param_types.as_mut_ptr(),
+            param_types.len() as u32,
+            0,
+        );
+        let mut func = llvm_sys::core::LLVMGetNamedFunction(ctx.module, name_cstr.as_ptr());
+        if func.is_null() {
+            func = llvm_sys::core::LLVMAddFunction(ctx.module, name_cstr.as_ptr(), func_ty);
+        }
+        let mut args_mut = args.to_vec();
+        llvm_sys::core::LLVMBuildCall2(
+            ctx.builder,
+            func_ty,
+            func,
+            args_mut.as_mut_ptr(),
+            args_mut.len() as u32,
+            b"intrinsic\0".as_ptr() as *const c_char,
+        )
+    }
+}
+
+/// Compile an intrinsic inline, on double operands, to a double result
+unsafe fn translate_intrinsic(
+    ctx: &TranslationContext,
+    intrinsic: Intrinsic,
+    args: &[LLVMValueRef],
+) -> LLVMValueRef {
+    unsafe {
+        let b = ctx.builder;
+        let double_ty = llvm_sys::core::LLVMDoubleTypeInContext(ctx.context);
+        let float_ty = llvm_sys::core::LLVMFloatTypeInContext(ctx.context);
+        let i32_ty = llvm_sys::core::LLVMInt32TypeInContext(ctx.context);
+        let i64_ty = llvm_sys::core::LLVMInt64TypeInContext(ctx.context);
+        let name = b"intrinsic_f64\0".as_ptr() as *const c_char;
+        let x = args[0];
+        match intrinsic {
+            Intrinsic::Clz32 | Intrinsic::Ctz32 => {
+                let llvm_name = if intrinsic == Intrinsic::Clz32 {
+                    "llvm.ctlz.i32"
+                } else {
+                    "llvm.cttz.i32"
+                };
+                // Zero is not poison: it counts 32 bits
+                let zero_is_poison = llvm_sys::core::LLVMConstInt(
+                    llvm_sys::core::LLVMInt1TypeInContext(ctx.context),
+                    0,
+                    0,
+                );
+                let count = call_llvm_intrinsic(
+                    ctx,
+                    llvm_name,
+                    i32_ty,
+                    &[to_uint32(ctx, x), zero_is_poison],
+                );
+                llvm_sys::core::LLVMBuildUIToFP(b, count, double_ty, name)
+            }
+            Intrinsic::Popcnt32 => {
+                let count =
+                    call_llvm_intrinsic(ctx, "llvm.ctpop.i32", i32_ty, &[to_uint32(ctx, x)]);
+                llvm_sys::core::LLVMBuildUIToFP(b, count, double_ty, name)
+            }
+            Intrinsic::Imul => {
+                let product = llvm_sys::core::LLVMBuildMul(
+                    b,
+                    to_uint32(ctx, x),
+                    to_uint32(ctx, args[1]),
+                    b"imul\0".as_ptr() as *const c_char,
+                );
+                llvm_sys::core::LLVMBuildSIToFP(b, product, double_ty, name)
+            }
+            Intrinsic::Fround => {
+                let single = llvm_sys::core::LLVMBuildFPTrunc(b, x, float_ty, name);
+                llvm_sys::core::LLVMBuildFPExt(b, single, double_ty, name)
+            }
+            Intrinsic::Sqrt => call_llvm_intrinsic(ctx, "llvm.sqrt.f64", double_ty, &[x]),
+            Intrinsic::Abs => call_llvm_intrinsic(ctx, "llvm.fabs.f64", double_ty, &[x]),
+            Intrinsic::Floor => call_llvm_intrinsic(ctx, "llvm.floor.f64", double_ty, &[x]),
+            Intrinsic::Ceil => call_llvm_intrinsic(ctx, "llvm.ceil.f64", double_ty, &[x]),
+            Intrinsic::Trunc => call_llvm_intrinsic(ctx, "llvm.trunc.f64", double_ty, &[x]),
+            Intrinsic::F32ToBits => {
+                let single = llvm_sys::core::LLVMBuildFPTrunc(b, x, float_ty, name);
+                let bits = llvm_sys::core::LLVMBuildBitCast(b, single, i32_ty, name);
+                let is_nan = llvm_sys::core::LLVMBuildFCmp(
+                    b,
+                    llvm_sys::LLVMRealPredicate::LLVMRealUNO,
+                    x,
+                    x,
+                    b"is_nan\0".as_ptr() as *const c_char,
+                );
+                let nan = llvm_sys::core::LLVMConstInt(i32_ty, 0x7FC0_0000, 0);
+                let bits = llvm_sys::core::LLVMBuildSelect(b, is_nan, nan, bits, name);
+                llvm_sys::core::LLVMBuildUIToFP(b, bits, double_ty, name)
+            }
+            Intrinsic::F32FromBits => {
+                let single = llvm_sys::core::LLVMBuildBitCast(b, to_uint32(ctx, x), float_ty, name);
+                let double = llvm_sys::core::LLVMBuildFPExt(b, single, double_ty, name);
+                canonicalize_nan(ctx, double)
+            }
+            Intrinsic::F64HighBits => {
+                let bits = llvm_sys::core::LLVMBuildBitCast(b, x, i64_ty, name);
+                let shift = llvm_sys::core::LLVMConstInt(i64_ty, 32, 0);
+                let high = llvm_sys::core::LLVMBuildLShr(b, bits, shift, name);
+                llvm_sys::core::LLVMBuildUIToFP(b, high, double_ty, name)
+            }
+            Intrinsic::F64LowBits => {
+                let bits = llvm_sys::core::LLVMBuildBitCast(b, x, i64_ty, name);
+                let low = llvm_sys::core::LLVMBuildTrunc(b, bits, i32_ty, name);
+                llvm_sys::core::LLVMBuildUIToFP(b, low, double_ty, name)
+            }
+            Intrinsic::F64FromBits => {
+                let high = llvm_sys::core::LLVMBuildZExt(b, to_uint32(ctx, x), i64_ty, name);
+                let low = llvm_sys::core::LLVMBuildZExt(b, to_uint32(ctx, args[1]), i64_ty, name);
+                let shift = llvm_sys::core::LLVMConstInt(i64_ty, 32, 0);
+                let high = llvm_sys::core::LLVMBuildShl(b, high, shift, name);
+                let bits = llvm_sys::core::LLVMBuildOr(b, high, low, name);
+                let double = llvm_sys::core::LLVMBuildBitCast(b, bits, double_ty, name);
+                canonicalize_nan(ctx, double)
+            }
+        }
+    }
+}
+
+/// Name suffix of LLVM intrinsics on a kind's vector type
+fn simd_suffix(kind: SimdKind) -> &'static str {
+    match kind {
+        SimdKind::F64x2 => "v2f64",
+        SimdKind::F32x4 => "v4f32",
+        SimdKind::I32x4 => "v4i32",
+    }
+}
+
+/// Pointer to the lanes of the typed array a vector is boxed in
+unsafe fn simd_data(ctx: &TranslationContext, arr: LLVMValueRef) -> LLVMValueRef {
+    unsafe {
+        llvm_sys::core::LLVMBuildLoad2(
+            ctx.builder,
+            llvm_sys::core::LLVMPointerTypeInContext(ctx.context, 0),
+            typed_array_field(ctx, arr, NativeTypedArray::DATA_OFFSET),
+            b"lanes\0".as_ptr() as *const c_char,
+        )
+    }
+}
+
+/// Compile a SIMD operation. Vectors are LLVM vector values; numbers and
+/// boxed vectors are NaN-boxed i64s.
+unsafe fn translate_simd(
+    ctx: &TranslationContext,
+    kind: SimdKind,
+    op: &SimdOp,
+    args: &[LLVMValueRef],
+) -> Result<LLVMValueRef, BackendError> {
+    unsafe {
+        let b = ctx.builder;
+        let i32_ty = llvm_sys::core::LLVMInt32TypeInContext(ctx.context);
+        let i64_ty = llvm_sys::core::LLVMInt64TypeInContext(ctx.context);
+        let double_ty = llvm_sys::core::LLVMDoubleTypeInContext(ctx.context);
+        let vector_ty = types::simd_vector_type(ctx.context, kind);
+        let lane_ty = llvm_sys::core::LLVMGetElementType(vector_ty);
+        let lane_align = kind.element().size() as u32;
+        let is_int = kind == SimdKind::I32x4;
+
+        let result = match op {
+            SimdOp::Make => {
+                let mut vector = llvm_sys::core::LLVMGetUndef(vector_ty);
+                for (i, &arg) in args.iter().enumerate() {
+                    let x = llvm_sys::core::LLVMBuildBitCast(
+                        b,
+                        arg,
+                        double_ty,
+                        b"lane_f64\0".as_ptr() as *const c_char,
+                    );
+                    let lane = match kind {
+                        SimdKind::F64x2 => x,
+                        SimdKind::F32x4 => llvm_sys::core::LLVMBuildFPTrunc(
+                            b,
+                            x,
+                            lane_ty,
+                            b"lane\0".as_ptr() as *const c_char,
+                        ),
+                        SimdKind::I32x4 => to_uint32(ctx, x),
+                    };
+                    vector = llvm_sys::core::LLVMBuildInsertElement(
+                        b,
+                        vector,
+                        lane,
+                        llvm_sys::core::LLVMConstInt(i32_ty, i as u64, 0),
+                        b"vector\0".as_ptr() as *const c_char,
+                    );
+                }
+                vector
+            }
+            SimdOp::Add if is_int => llvm_sys::core::LLVMBuildAdd(
+                b,
+                args[0],
+                args[1],
+                b"add\0".as_ptr() as *const c_char,
+            ),
+            SimdOp::Sub if is_int => llvm_sys::core::LLVMBuildSub(
+                b,
+                args[0],
+                args[1],
+                b"sub\0".as_ptr() as *const c_char,
+            ),
+            SimdOp::Mul if is_int => llvm_sys::core::LLVMBuildMul(
+                b,
+                args[0],
+                args[1],
+                b"mul\0".as_ptr() as *const c_char,
+            ),
+            SimdOp::Add => llvm_sys::core::LLVMBuildFAdd(
+                b,
+                args[0],
+                args[1],
+                b"add\0".as_ptr() as *const c_char,
+            ),
+            SimdOp::Sub => llvm_sys::core::LLVMBuildFSub(
+                b,
+                args[0],
+                args[1],
+                b"sub\0".as_ptr() as *const c_char,
+            ),
+            SimdOp::Mul => llvm_sys::core::LLVMBuildFMul(
+                b,
+                args[0],
+                args[1],
+                b"mul\0".as_ptr() as *const c_char,
+            ),
+            SimdOp::Shuffle(indices) => {
+                let mut mask: Vec<LLVMValueRef> = indices
+                    .iter()
+                    .map(|&i| llvm_sys::core::LLVMConstInt(i32_ty, i as u64, 0))
+                    .collect();
+                let mask = llvm_sys::core::LLVMConstVector(mask.as_mut_ptr(), mask.len() as u32);
+                llvm_sys::core::LLVMBuildShuffleVector(
+                    b,
+                    args[0],
+                    args[1],
+                    mask,
+                    b"shuffle\0".as_ptr() as *const c_char,
+                )
+            }
+            SimdOp::ReduceAdd if is_int => {
+                let name = format!("llvm.vector.reduce.add.{}", simd_suffix(kind));
+                let sum = call_llvm_intrinsic(ctx, &name, lane_ty, &[args[0]]);
+                let sum = llvm_sys::core::LLVMBuildSIToFP(
+                    b,
+                    sum,
+                    double_ty,
+                    b"sum_f64\0".as_ptr() as *const c_char,
+                );
+                double_to_bits(ctx, sum)
+            }
+            SimdOp::ReduceAdd => {
+                // Without fast-math flags the lanes are added in order,
+                // starting from -0.0, like the VM
+                let name = format!("llvm.vector.reduce.fadd.{}", simd_suffix(kind));
+                let start = llvm_sys::core::LLVMConstReal(lane_ty, -0.0);
+                let mut sum = call_llvm_intrinsic(ctx, &name, lane_ty, &[start, args[0]]);
+                if kind == SimdKind::F32x4 {
+                    sum = llvm_sys::core::LLVMBuildFPExt(
+                        b,
+                        sum,
+                        double_ty,
+                        b"sum_f64\0".as_ptr() as *const c_char,
+                    );
+                }
+                double_to_bits(ctx, canonicalize_nan(ctx, sum))
+            }
+            SimdOp::Box => {
+                let arr = call_stub(
+                    ctx,
+                    "ot_alloc_typed_array",
+                    &[
+                        llvm_sys::core::LLVMConstInt(i64_ty, kind.element() as u64, 0),
+                        llvm_sys::core::LLVMConstInt(i64_ty, kind.lanes() as u64, 0),
+                    ],
+                )?;
+                let store = llvm_sys::core::LLVMBuildStore(b, args[0], simd_data(ctx, arr));
+                llvm_sys::core::LLVMSetAlignment(store, lane_align);
+                arr
+            }
+            SimdOp::Unbox => {
+                let arr = call_stub(
+                    ctx,
+                    "ot_simd_lanes",
+                    &[
+                        args[0],
+                        llvm_sys::core::LLVMConstInt(i64_ty, kind.element() as u64, 0),
+                        llvm_sys::core::LLVMConstInt(i64_ty, kind.lanes() as u64, 0),
+                    ],
+                )?;
+                let load = llvm_sys::core::LLVMBuildLoad2(
+                    b,
+                    vector_ty,
+                    simd_data(ctx, arr),
+                    b"vector\0".as_ptr() as *const c_char,
+                );
+                llvm_sys::core::LLVMSetAlignment(load, lane_align);
+                load
+            }
+        };
+        Ok(result)
+    }
+}
+
+/// Reinterpret a double as the i64 bits of a NaN-boxed number
+unsafe fn double_to_bits(ctx: &TranslationContext, value: LLVMValueRef) -> LLVMValueRef {
+    unsafe {
+        llvm_sys::core::LLVMBuildBitCast(
+            ctx.builder,
+            value,
+            llvm_sys::core::LLVMInt64TypeInContext(ctx.context),
+            b"bits\0".as_ptr() as *const c_char,
+        )
+    }
+}
+
 /// Translate a terminator
 unsafe fn translate_terminator(
     ctx: &mut TranslationContext,
@@ -1032,6 +1789,9 @@ unsafe fn translate_terminator(
                 llvm_sys::core::LLVMBuildCondBr(ctx.builder, bool_val, true_block, false_block);
             }
             Terminator::Return(val) => {
+                if ctx.debug.is_some() {
+                    call_void_stub(ctx, "ot_frame_exit", &[])?;
+                }
                 if let Some(v) = val {
                     let ret_val = get_value(ctx, *v)?;
                     llvm_sys::core::LLVMBuildRet(ctx.builder, ret_val);
@@ -1051,6 +1811,15 @@ unsafe fn translate_terminator(
                 }
             }
             Terminator::Unreachable => {
+                // Reaching it is a compiler bug; report it instead of running on
+                let kind = llvm_sys::core::LLVMConstInt(
+                    llvm_sys::core::LLVMInt32TypeInContext(ctx.context),
+                    ErrorKind::Unreachable as u64,
+                    0,
+                );
+                let (message, message_len) =
+                    global_string(ctx, "reached code the compiler marked unreachable");
+                call_void_stub(ctx, "ot_runtime_error", &[kind, message, message_len])?;
                 llvm_sys::core::LLVMBuildUnreachable(ctx.builder);
             }
         }
@@ -1140,6 +1909,53 @@ unsafe fn bool_to_ot_value(
     }
 }
 
+/// Store `args` in a stack array for a stub taking `(argc, argv)`. The array
+/// is allocated in the entry block, so a call inside a loop reuses it.
+unsafe fn build_arg_array(
+    ctx: &TranslationContext,
+    args: &[ValueId],
+) -> Result<(LLVMValueRef, LLVMValueRef), BackendError> {
+    unsafe {
+        let i64_ty = llvm_sys::core::LLVMInt64TypeInContext(ctx.context);
+        let argc = llvm_sys::core::LLVMConstInt(i64_ty, args.len() as u64, 0);
+        if args.is_empty() {
+            let null =
+                llvm_sys::core::LLVMConstPointerNull(llvm_sys::core::LLVMPointerType(i64_ty, 0));
+            return Ok((argc, null));
+        }
+
+        let entry = llvm_sys::core::LLVMGetEntryBasicBlock(ctx.func_val);
+        let entry_builder = llvm_sys::core::LLVMCreateBuilderInContext(ctx.context);
+        let first = llvm_sys::core::LLVMGetFirstInstruction(entry);
+        if first.is_null() {
+            llvm_sys::core::LLVMPositionBuilderAtEnd(entry_builder, entry);
+        } else {
+            llvm_sys::core::LLVMPositionBuilderBefore(entry_builder, first);
+        }
+        let argv = llvm_sys::core::LLVMBuildAlloca(
+            entry_builder,
+            llvm_sys::core::LLVMArrayType2(i64_ty, args.len() as u64),
+            b"argv\0".as_ptr() as *const c_char,
+        );
+        llvm_sys::core::LLVMDisposeBuilder(entry_builder);
+
+        for (i, id) in args.iter().enumerate() {
+            let value = get_value(ctx, *id)?;
+            let mut index = llvm_sys::core::LLVMConstInt(i64_ty, i as u64, 0);
+            let slot = llvm_sys::core::LLVMBuildGEP2(
+                ctx.builder,
+                i64_ty,
+                argv,
+                &mut index,
+                1,
+                b"arg_slot\0".as_ptr() as *const c_char,
+            );
+            llvm_sys::core::LLVMBuildStore(ctx.builder, value, slot);
+        }
+        Ok((argc, argv))
+    }
+}
+
 /// Call a runtime stub function
 unsafe fn call_stub(
     ctx: &TranslationContext,
@@ -1168,6 +1984,48 @@ unsafe fn call_stub(
     }
 }
 
+/// Call a runtime stub that returns nothing
+unsafe fn call_void_stub(
+    ctx: &TranslationContext,
+    name: &str,
+    args: &[LLVMValueRef],
+) -> Result<(), BackendError> {
+    unsafe {
+        let stub = ctx
+            .stubs
+            .get(name)
+            .copied()
+            .ok_or_else(|| BackendError::Llvm(format!("Runtime stub not found: {}", name)))?;
+
+        // A call returning void can't be named
+        let mut args_mut = args.to_vec();
+        llvm_sys::core::LLVMBuildCall2(
+            ctx.builder,
+            llvm_sys::core::LLVMGlobalGetValueType(stub),
+            stub,
+            args_mut.as_mut_ptr(),
+            args_mut.len() as u32,
+            b"\0".as_ptr() as *const c_char,
+        );
+        Ok(())
+    }
+}
+
+/// A constant string as the (pointer, i64 length) pair runtime stubs take
+unsafe fn global_string(ctx: &TranslationContext, s: &str) -> (LLVMValueRef, LLVMValueRef) {
+    unsafe {
+        let data = CString::new(s.replace('\0', "")).unwrap();
+        let ptr = llvm_sys::core::LLVMBuildGlobalStringPtr(
+            ctx.builder,
+            data.as_ptr(),
+            b"str\0".as_ptr() as *const c_char,
+        );
+        let i64_ty = llvm_sys::core::LLVMInt64TypeInContext(ctx.context);
+        let len = llvm_sys::core::LLVMConstInt(i64_ty, data.as_bytes().len() as u64, 0);
+        (ptr, len)
+    }
+}
+
 /// Call a function indirectly (or directly if it's a known function)
 ///
 /// This generates a direct LLVM call by:
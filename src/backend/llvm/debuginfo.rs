@@ -0,0 +1,195 @@
+//! DWARF debug info
+//!
+//! Line tables map machine code back to script source lines, so debuggers
+//! and profilers show script-level frames.
+
+#![allow(clippy::missing_safety_doc)]
+
+use llvm_sys::debuginfo::*;
+use llvm_sys::prelude::*;
+use std::ffi::c_char;
+use std::path::{Path, PathBuf};
+use std::ptr;
+
+/// Debug info for one module's source file
+pub struct DebugInfo {
+    builder: LLVMDIBuilderRef,
+    file: LLVMMetadataRef,
+    context: LLVMContextRef,
+    /// The source path as given to the compiler, for runtime error traces
+    pub source_file: String,
+}
+
+impl DebugInfo {
+    /// Start a compile unit for `source_file` in `module`
+    pub unsafe fn new(module: LLVMModuleRef, context: LLVMContextRef, source_file: &str) -> Self {
+        unsafe {
+            add_module_flag(
+                module,
+                context,
+                "Debug Info Version",
+                LLVMDebugMetadataVersion(),
+            );
+            add_module_flag(module, context, "Dwarf Version", 4);
+
+            // An absolute directory lets debuggers find the source from anywhere
+            let path =
+                std::fs::canonicalize(source_file).unwrap_or_else(|_| PathBuf::from(source_file));
+            let name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let directory = path
+                .parent()
+                .filter(|dir| !dir.as_os_str().is_empty())
+                .unwrap_or(Path::new("."))
+                .to_string_lossy()
+                .into_owned();
+
+            let builder = LLVMCreateDIBuilder(module);
+            let file = LLVMDIBuilderCreateFile(
+                builder,
+                name.as_ptr() as *const c_char,
+                name.len(),
+                directory.as_ptr() as *const c_char,
+                directory.len(),
+            );
+
+            // DWARF has no language code for JavaScript or TypeScript
+            let producer = concat!("oitec ", env!("CARGO_PKG_VERSION"));
+            LLVMDIBuilderCreateCompileUnit(
+                builder,
+                LLVMDWARFSourceLanguage::LLVMDWARFSourceLanguageC,
+                file,
+                producer.as_ptr() as *const c_char,
+                producer.len(),
+                0,
+                ptr::null(),
+                0,
+                0,
+                ptr::null(),
+                0,
+                LLVMDWARFEmissionKind::LLVMDWARFEmissionKindLineTablesOnly,
+                0,
+                0,
+                0,
+                ptr::null(),
+                0,
+                ptr::null(),
+                0,
+            );
+
+            Self {
+                builder,
+                file,
+                context,
+                source_file: source_file.to_string(),
+            }
+        }
+    }
+
+    /// Describe `func`, defined at `line`, and return its scope
+    pub unsafe fn function(&self, func: LLVMValueRef, name: &str, line: u32) -> LLVMMetadataRef {
+        unsafe {
+            let ty = LLVMDIBuilderCreateSubroutineType(
+                self.builder,
+                self.file,
+                ptr::null_mut(),
+                0,
+                LLVMDIFlagZero,
+            );
+            let scope = LLVMDIBuilderCreateFunction(
+                self.builder,
+                self.file,
+                name.as_ptr() as *const c_char,
+                name.len(),
+                ptr::null(),
+                0,
+                self.file,
+                line,
+                ty,
+                0,
+                1,
+                line,
+                LLVMDIFlagZero,
+                0,
+            );
+            LLVMSetSubprogram(func, scope);
+            scope
+        }
+    }
+
+    /// Attribute the instructions `builder` emits next to `line` in `scope`
+    pub unsafe fn set_line(&self, builder: LLVMBuilderRef, scope: LLVMMetadataRef, line: u32) {
+        unsafe {
+            let location =
+                LLVMDIBuilderCreateDebugLocation(self.context, line, 0, scope, ptr::null_mut());
+            llvm_sys::core::LLVMSetCurrentDebugLocation2(builder, location);
+        }
+    }
+
+    /// Complete the debug info once every function is translated
+    pub unsafe fn finish(&self) {
+        unsafe { LLVMDIBuilderFinalize(self.builder) }
+    }
+}
+
+impl Drop for DebugInfo {
+    fn drop(&mut self) {
+        unsafe { LLVMDisposeDIBuilder(self.builder) }
+    }
+}
+
+unsafe fn add_module_flag(module: LLVMModuleRef, context: LLVMContextRef, key: &str, value: u32) {
+    unsafe {
+        let i32_ty = llvm_sys::core::LLVMInt32TypeInContext(context);
+        let value = llvm_sys::core::LLVMConstInt(i32_ty, value as u64, 0);
+        llvm_sys::core::LLVMAddModuleFlag(
+            module,
+            llvm_sys::LLVMModuleFlagBehavior::LLVMModuleFlagBehaviorWarning,
+            key.as_ptr() as *const c_char,
+            key.len(),
+            llvm_sys::core::LLVMValueAsMetadata(value),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::backend::llvm::LlvmCodegen;
+    use crate::compiler::Compiler;
+    use std::ffi::CStr;
+
+    /// The LLVM IR of `source` compiled with debug info, as text
+    fn debug_ir(source: &str) -> String {
+        let mut compiler = Compiler::new();
+        let bytecode = compiler.compile(source).unwrap();
+        let mut module =
+            crate::ir::lower::lower_module_with_lines(&bytecode, &compiler.line_table).unwrap();
+        module.source_file = Some("lines.ot".to_string());
+
+        let mut codegen = LlvmCodegen::new(crate::backend::aot::default_target()).unwrap();
+        codegen.debug_info = true;
+        codegen.compile_module(&module).unwrap();
+        unsafe {
+            let text = llvm_sys::core::LLVMPrintModuleToString(codegen.module);
+            let ir = CStr::from_ptr(text).to_string_lossy().into_owned();
+            llvm_sys::core::LLVMDisposeMessage(text);
+            ir
+        }
+    }
+
+    #[test]
+    fn test_debug_build_emits_line_tables() {
+        let ir = debug_ir(
+            "function add(a, b) {\n    let sum = a + b;\n    return sum;\n}\nconsole.log(add(1, 2));\n",
+        );
+        assert!(ir.contains("!DICompileUnit("), "{}", ir);
+        assert!(ir.contains("emissionKind: LineTablesOnly"), "{}", ir);
+        assert!(ir.contains("!DIFile(filename: \"lines.ot\""), "{}", ir);
+        assert!(ir.contains("!DISubprogram(name: \"add\""), "{}", ir);
+        assert!(ir.contains("!DILocation(line: 2,"), "{}", ir);
+        assert!(ir.contains("!DILocation(line: 3,"), "{}", ir);
+        assert!(ir.contains("!DILocation(line: 5,"), "{}", ir);
+    }
+}
@@ -1,13 +1,53 @@
 //! Static linking support for LLVM AOT compilation
 //!
 //! This module provides functions to link object files with the runtime library
-//! using external linkers (clang/ld).
+//! using external linkers (clang/gcc/cc, or MSVC `link.exe` on Windows).
 
+use std::ffi::OsString;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 
 use super::super::{BackendError, LtoMode, aot::OutputFormat};
 
+/// Which program links the final binary (`build --linker`)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum LinkerChoice {
+    /// The first of clang, gcc and cc found on the system
+    #[default]
+    Auto,
+    /// LLVM's lld, through the C compiler driver (`lld-link` for MSVC)
+    Lld,
+    /// mold, through the C compiler driver
+    Mold,
+    /// A compiler driver or linker command, run as given
+    Command(String),
+}
+
+impl LinkerChoice {
+    /// Parse a `--linker` value; names other than `auto`, `lld` and `mold`
+    /// are commands
+    pub fn parse(name: &str) -> Self {
+        match name {
+            "auto" => LinkerChoice::Auto,
+            "lld" => LinkerChoice::Lld,
+            "mold" => LinkerChoice::Mold,
+            command => LinkerChoice::Command(command.to_string()),
+        }
+    }
+}
+
+/// How to invoke the linker, beyond the objects and runtime
+#[derive(Debug, Clone, Default)]
+pub struct LinkOptions {
+    pub linker: LinkerChoice,
+    /// Library search directories (`-L`)
+    pub search_paths: Vec<PathBuf>,
+    /// Libraries to link, by name (`-l`)
+    pub libraries: Vec<String>,
+    /// Arguments passed to the linker unchanged (`--link-arg`)
+    pub args: Vec<String>,
+}
+
 /// Link object files with runtime library to create an executable or library
 pub fn link_object_files(
     objects: &[PathBuf],
@@ -15,7 +55,14 @@ pub fn link_object_files(
     format: OutputFormat,
     runtime_lib: Option<&Path>,
 ) -> Result<(), BackendError> {
-    link_object_files_with_lto(objects, output, format, runtime_lib, LtoMode::None)
+    link_object_files_with_lto(
+        objects,
+        output,
+        format,
+        runtime_lib,
+        LtoMode::None,
+        &LinkOptions::default(),
+    )
 }
 
 /// Link object files with runtime library, supporting LTO
@@ -25,11 +72,18 @@ pub fn link_object_files_with_lto(
     format: OutputFormat,
     runtime_lib: Option<&Path>,
     lto_mode: LtoMode,
+    options: &LinkOptions,
 ) -> Result<(), BackendError> {
-    // Detect linker (prefer clang, fall back to cc/ld)
-    let linker = detect_linker()?;
+    let (linker, fuse_ld) = resolve_linker(&options.linker)?;
 
+    let msvc = is_msvc_linker(&linker);
     let mut cmd = Command::new(&linker);
+    if msvc {
+        cmd.arg("/NOLOGO");
+    }
+    if let Some(flag) = fuse_ld {
+        cmd.arg(flag);
+    }
 
     // Add LTO flags if LTO is enabled
     if lto_mode != LtoMode::None {
@@ -66,6 +120,12 @@ pub fn link_object_files_with_lto(
             cmd.arg("-Wl,-z,nodlopen"); // Prevent runtime loading variations
             cmd.arg("-Wl,--no-undefined"); // Ensure all symbols resolved at link time
         }
+
+        #[cfg(windows)]
+        if msvc {
+            // Windows-specific determinism flags
+            cmd.arg("/Brepro"); // Hash-based timestamp and GUID in the PE header
+        }
     }
 
     // Debug logging (disabled by default for deterministic builds)
@@ -126,20 +186,28 @@ pub fn link_object_files_with_lto(
         // No additional libraries needed since we removed HashMap dependency
     }
 
+    // User libraries come after the objects and runtime that reference them
+    cmd.args(library_args(options, msvc));
+
     // Set output format
     match format {
+        OutputFormat::Executable if msvc => {
+            // Objects from LLVM carry no /DEFAULTLIB directives, so name the CRT
+            cmd.args(["/SUBSYSTEM:CONSOLE", "/DEFAULTLIB:libcmt"]);
+            cmd.arg(msvc_out(output));
+        }
         OutputFormat::Executable => {
             cmd.arg("-o").arg(output);
         }
         OutputFormat::StaticLib => {
             return create_static_library(objects, output);
         }
+        OutputFormat::SharedLib if msvc => {
+            cmd.args(["/DLL", "/DEFAULTLIB:msvcrt"]);
+            cmd.arg(msvc_out(output));
+        }
         OutputFormat::SharedLib => {
-            if linker.contains("clang") || linker.contains("gcc") {
-                cmd.args(["-shared", "-o"]).arg(output);
-            } else {
-                cmd.args(["-shared", "-o"]).arg(output);
-            }
+            cmd.args(["-shared", "-o"]).arg(output);
         }
         OutputFormat::Object => {
             // No linking needed for object files
@@ -173,9 +241,14 @@ pub fn link_object_files_with_lto(
 
 /// Create a static library from object files
 pub fn create_static_library(objects: &[PathBuf], output: &Path) -> Result<(), BackendError> {
-    // Use ar to create static library
-    let mut cmd = Command::new("ar");
-    cmd.arg("rcs").arg(output);
+    // Use ar to create static library (lib.exe for MSVC toolchains)
+    let archiver = detect_archiver()?;
+    let mut cmd = Command::new(&archiver);
+    if archiver == "ar" {
+        cmd.arg("rcs").arg(output);
+    } else {
+        cmd.arg("/NOLOGO").arg(msvc_out(output));
+    }
 
     for obj in objects {
         cmd.arg(obj);
@@ -183,11 +256,12 @@ pub fn create_static_library(objects: &[PathBuf], output: &Path) -> Result<(), B
 
     let status = cmd
         .status()
-        .map_err(|e| BackendError::Llvm(format!("Failed to execute ar: {}", e)))?;
+        .map_err(|e| BackendError::Llvm(format!("Failed to execute {}: {}", archiver, e)))?;
 
     if !status.success() {
         return Err(BackendError::Llvm(format!(
-            "ar failed with exit code: {:?}",
+            "{} failed with exit code: {:?}",
+            archiver,
             status.code()
         )));
     }
@@ -252,7 +326,7 @@ fn link_with_rustc(
 
 /// Detect available linker on the system
 /// For Rust runtime libraries, prefer rustc which handles std linking automatically
-fn detect_linker() -> Result<String, BackendError> {
+pub fn detect_linker() -> Result<String, BackendError> {
     // If we have Rust runtime dependencies, use rustc for linking
     // This ensures libstd and other Rust libraries are linked correctly
     if Command::new("rustc").arg("--version").output().is_ok() {
@@ -272,11 +346,147 @@ fn detect_linker() -> Result<String, BackendError> {
     }
 
     // Try cc (common on Unix)
+    #[cfg(not(windows))]
     if Command::new("cc").arg("--version").output().is_ok() {
         return Ok("cc".to_string());
     }
 
+    // MSVC-style linkers (Visual Studio developer prompt, or LLVM's lld-link)
+    #[cfg(windows)]
+    for linker in ["lld-link", "link"] {
+        if Command::new(linker).arg("/?").output().is_ok() {
+            return Ok(linker.to_string());
+        }
+    }
+
+    let tried = if cfg!(windows) {
+        "clang, gcc, lld-link, link"
+    } else {
+        "clang, gcc, cc"
+    };
+    Err(BackendError::Llvm(format!(
+        "No suitable linker found (tried {})",
+        tried
+    )))
+}
+
+/// The linker command for `choice`, with the `-fuse-ld` flag that selects
+/// its backend when it goes through a compiler driver
+fn resolve_linker(choice: &LinkerChoice) -> Result<(String, Option<&'static str>), BackendError> {
+    match choice {
+        LinkerChoice::Auto => Ok((detect_linker()?, None)),
+        LinkerChoice::Command(command) => Ok((command.clone(), None)),
+        LinkerChoice::Lld => {
+            let driver = detect_linker()?;
+            if is_msvc_linker(&driver) {
+                return Ok(("lld-link".to_string(), None));
+            }
+            require_tool("ld.lld", "lld")?;
+            Ok((driver, Some("-fuse-ld=lld")))
+        }
+        LinkerChoice::Mold => {
+            let driver = detect_linker()?;
+            if is_msvc_linker(&driver) {
+                return Err(BackendError::Llvm(
+                    "mold does not link MSVC targets; use --linker lld".into(),
+                ));
+            }
+            require_tool("mold", "mold")?;
+            Ok((driver, Some("-fuse-ld=mold")))
+        }
+    }
+}
+
+/// Fail early with a readable error when a requested linker isn't installed
+fn require_tool(tool: &str, linker: &str) -> Result<(), BackendError> {
+    if Command::new(tool).arg("--version").output().is_ok() {
+        return Ok(());
+    }
+    Err(BackendError::Llvm(format!(
+        "--linker {} needs `{}` in PATH",
+        linker, tool
+    )))
+}
+
+/// Search paths, libraries and pass-through arguments in the linker's syntax
+fn library_args(options: &LinkOptions, msvc: bool) -> Vec<OsString> {
+    let mut args = Vec::new();
+    for dir in &options.search_paths {
+        let mut arg = OsString::from(if msvc { "/LIBPATH:" } else { "-L" });
+        arg.push(dir);
+        args.push(arg);
+    }
+    for lib in &options.libraries {
+        args.push(match msvc {
+            true if lib.ends_with(".lib") => lib.into(),
+            true => format!("{}.lib", lib).into(),
+            false => format!("-l{}", lib).into(),
+        });
+    }
+    args.extend(options.args.iter().map(OsString::from));
+    args
+}
+
+/// Detect the static library tool: `ar`, or `llvm-lib`/`lib` on Windows
+fn detect_archiver() -> Result<String, BackendError> {
+    #[cfg(windows)]
+    for archiver in ["llvm-lib", "lib"] {
+        if Command::new(archiver).arg("/?").output().is_ok() {
+            return Ok(archiver.to_string());
+        }
+    }
+
+    if Command::new("ar").arg("--version").output().is_ok() {
+        return Ok("ar".to_string());
+    }
+
     Err(BackendError::Llvm(
-        "No suitable linker found (tried clang, gcc, cc)".into(),
+        "No static library tool found (tried ar, llvm-lib, lib)".into(),
     ))
 }
+
+/// Whether the linker takes MSVC-style `/FLAG` arguments
+fn is_msvc_linker(linker: &str) -> bool {
+    matches!(linker, "link" | "lld-link")
+}
+
+/// `/OUT:<path>` argument for MSVC-style tools
+fn msvc_out(output: &Path) -> OsString {
+    let mut arg = OsString::from("/OUT:");
+    arg.push(output);
+    arg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_linker_choice_parse() {
+        assert_eq!(LinkerChoice::parse("auto"), LinkerChoice::Auto);
+        assert_eq!(LinkerChoice::parse("lld"), LinkerChoice::Lld);
+        assert_eq!(LinkerChoice::parse("mold"), LinkerChoice::Mold);
+        assert_eq!(
+            LinkerChoice::parse("cc"),
+            LinkerChoice::Command("cc".to_string())
+        );
+    }
+
+    #[test]
+    fn test_library_args_follow_linker_syntax() {
+        let options = LinkOptions {
+            search_paths: vec![PathBuf::from("/opt/lib")],
+            libraries: vec!["m".to_string(), "ssl.lib".to_string()],
+            args: vec!["-Wl,--as-needed".to_string()],
+            ..LinkOptions::default()
+        };
+        assert_eq!(
+            library_args(&options, false),
+            ["-L/opt/lib", "-lm", "-lssl.lib", "-Wl,--as-needed"].map(OsString::from)
+        );
+        assert_eq!(
+            library_args(&options, true),
+            ["/LIBPATH:/opt/lib", "m.lib", "ssl.lib", "-Wl,--as-needed"].map(OsString::from)
+        );
+    }
+}
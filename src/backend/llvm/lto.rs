@@ -68,6 +68,7 @@ pub fn run_thinlto(
         // Export main symbol to prevent elimination
         cmd.arg("--exported-symbol=main");
         cmd.arg("--exported-symbol=_main");
+        cmd.arg("--relocation-model=pic");
 
         // Add optimization level
         match opt_level {
@@ -384,6 +385,7 @@ pub fn generate_object_file(
     let mut cmd = Command::new(&llc);
     cmd.arg("-filetype=obj")
         .arg("--function-sections") // Preserve function sections
+        .arg("--relocation-model=pic") // Match the in-process object emitter
         .arg("-o")
         .arg(output_file);
 
@@ -13,6 +13,7 @@ pub mod abi;
 pub mod bitcode;
 pub mod cache;
 pub mod codegen;
+pub mod debuginfo;
 pub mod linker;
 pub mod lto;
 pub mod object;
@@ -34,10 +35,14 @@ pub fn compile_to_object_file(
     output_path: &Path,
 ) -> Result<(), BackendError> {
     // Get target triple
-    let target_triple = object::get_default_target_triple()?;
+    let target_triple = object::target_triple(config)?;
 
     // Create codegen
     let mut codegen = LlvmCodegen::new(target_triple.clone())?;
+    if config.export_functions {
+        codegen.exported = crate::backend::exports::exported_symbols(module);
+    }
+    codegen.debug_info = config.debug_info;
 
     // Compile module
     codegen.compile_module(module)?;
@@ -67,10 +72,14 @@ pub fn compile_to_bitcode_file(
     output_path: &Path,
 ) -> Result<(), BackendError> {
     // Get target triple
-    let target_triple = object::get_default_target_triple()?;
+    let target_triple = object::target_triple(config)?;
 
     // Create codegen
     let mut codegen = LlvmCodegen::new(target_triple.clone())?;
+    if config.export_functions {
+        codegen.exported = crate::backend::exports::exported_symbols(module);
+    }
+    codegen.debug_info = config.debug_info;
 
     // Compile module
     codegen.compile_module(module)?;
@@ -113,10 +122,14 @@ pub fn compile_to_llvm_ir_file(
     output_path: &Path,
 ) -> Result<(), BackendError> {
     // Get target triple
-    let target_triple = object::get_default_target_triple()?;
+    let target_triple = object::target_triple(config)?;
 
     // Create codegen
     let mut codegen = LlvmCodegen::new(target_triple.clone())?;
+    if config.export_functions {
+        codegen.exported = crate::backend::exports::exported_symbols(module);
+    }
+    codegen.debug_info = config.debug_info;
 
     // Compile module
     codegen.compile_module(module)?;
@@ -9,7 +9,7 @@ use std::ffi::{CStr, CString, c_char};
 use std::path::Path;
 use std::ptr;
 
-use crate::backend::{BackendError, OptLevel};
+use crate::backend::{BackendConfig, BackendError, OptLevel};
 
 /// Get the default target triple for the current platform
 pub fn get_default_target_triple() -> Result<String, BackendError> {
@@ -27,16 +27,31 @@ pub fn get_default_target_triple() -> Result<String, BackendError> {
     }
 }
 
+/// The triple `config` targets: its own, or the host's
+pub fn target_triple(config: &BackendConfig) -> Result<String, BackendError> {
+    match &config.target {
+        Some(triple) => Ok(triple.clone()),
+        None => get_default_target_triple(),
+    }
+}
+
 /// Create a target machine for the given target triple
 pub unsafe fn create_target_machine(
     target_triple: &str,
     opt_level: OptLevel,
 ) -> Result<LLVMTargetMachineRef, BackendError> {
     unsafe {
-        // Initialize LLVM targets
+        // Initialize LLVM targets; cross-compiling needs every backend
+        // this LLVM was built with
         llvm_sys::target::LLVM_InitializeNativeTarget();
         llvm_sys::target::LLVM_InitializeNativeAsmPrinter();
         llvm_sys::target::LLVM_InitializeNativeAsmParser();
+        if get_default_target_triple()? != target_triple {
+            llvm_sys::target::LLVM_InitializeAllTargetInfos();
+            llvm_sys::target::LLVM_InitializeAllTargets();
+            llvm_sys::target::LLVM_InitializeAllTargetMCs();
+            llvm_sys::target::LLVM_InitializeAllAsmPrinters();
+        }
 
         let triple_cstr = CString::new(target_triple).unwrap();
 
@@ -80,7 +95,9 @@ pub unsafe fn create_target_machine(
             cpu_cstr.as_ptr(),
             features_cstr.as_ptr(),
             level,
-            LLVMRelocMode::LLVMRelocDefault,
+            // Position-independent code links into PIE executables, which
+            // distributions like Debian make the compiler driver's default
+            LLVMRelocMode::LLVMRelocPIC,
             LLVMCodeModel::LLVMCodeModelDefault,
         );
 
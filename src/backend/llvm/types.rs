@@ -4,6 +4,7 @@ use llvm_sys::prelude::*;
 use std::collections::BTreeMap;
 
 use crate::backend::BackendError;
+use crate::ir::simd::SimdKind;
 use crate::ir::{IrStructDef, IrType};
 
 /// Convert an IR type to an LLVM type
@@ -31,6 +32,7 @@ pub fn ir_type_to_llvm_type(
                 // Typed arrays are pointers
                 Ok(llvm_sys::core::LLVMInt64TypeInContext(ctx))
             }
+            IrType::Simd(kind) => Ok(simd_vector_type(ctx, *kind)),
             IrType::Function => {
                 // Functions are pointers
                 Ok(llvm_sys::core::LLVMInt64TypeInContext(ctx))
@@ -60,6 +62,18 @@ pub fn ir_type_to_llvm_type(
     }
 }
 
+/// The LLVM vector type of a SIMD kind (`<2 x double>`, ...)
+pub fn simd_vector_type(ctx: LLVMContextRef, kind: SimdKind) -> LLVMTypeRef {
+    unsafe {
+        let lane = match kind {
+            SimdKind::F64x2 => llvm_sys::core::LLVMDoubleTypeInContext(ctx),
+            SimdKind::F32x4 => llvm_sys::core::LLVMFloatTypeInContext(ctx),
+            SimdKind::I32x4 => llvm_sys::core::LLVMInt32TypeInContext(ctx),
+        };
+        llvm_sys::core::LLVMVectorType(lane, kind.lanes() as u32)
+    }
+}
+
 /// Create an LLVM struct type from an IR struct definition
 pub fn create_struct_type(
     ctx: LLVMContextRef,
@@ -7,12 +7,15 @@
 //! - `layout.rs` - Memory layout calculation for structs/arrays
 //! - `cranelift.rs` - IR to Cranelift IR translation
 //! - `jit.rs` - JIT compilation and execution runtime
+//! - `jit_module.rs` - W^X code memory and relocation linking for the JIT
 //! - `aot.rs` - Ahead-of-time compilation pipeline (future)
 //! - `tier.rs` - Tiered compilation manager
 
 pub mod aot;
 pub mod cranelift;
+pub mod exports;
 pub mod jit;
+pub mod jit_module;
 pub mod layout;
 pub mod llvm;
 pub mod tier;
@@ -70,6 +73,11 @@ pub struct BackendConfig {
     pub bounds_check: bool,
     /// Link-time optimization mode
     pub lto_mode: LtoMode,
+    /// Give the functions in `exports::exported_functions` default
+    /// visibility, so the host of a library can call them
+    pub export_functions: bool,
+    /// Target triple for AOT code; the host's when unset
+    pub target: Option<String>,
 }
 
 impl Default for BackendConfig {
@@ -80,6 +88,8 @@ impl Default for BackendConfig {
             debug_info: false,
             bounds_check: true,
             lto_mode: LtoMode::None,
+            export_functions: false,
+            target: None,
         }
     }
 }
@@ -124,6 +134,12 @@ impl std::fmt::Display for BackendError {
 
 impl std::error::Error for BackendError {}
 
+/// Whether Cranelift can generate code for the host, i.e. whether
+/// `oitec jit` works in this build
+pub fn jit_available() -> bool {
+    cranelift_native::builder().is_ok()
+}
+
 /// Compile an IR module to native code
 pub fn compile(module: &IrModule, config: &BackendConfig) -> Result<CompiledModule, BackendError> {
     match config.kind {
@@ -0,0 +1,321 @@
+//! Benchmark measurement and reporting for `oitec bench`
+//!
+//! Samples are taken one iteration at a time after a warmup phase that ends
+//! once timings settle. Results can be printed as JSON and compared against a
+//! previously saved baseline so CI can flag regressions.
+
+use serde_json::{Value, json};
+use std::time::{Duration, Instant};
+
+/// Samples per window when deciding whether warmup is over
+const WARMUP_WINDOW: usize = 5;
+/// Warmup ends when consecutive window means differ by less than this
+const WARMUP_TOLERANCE: f64 = 0.05;
+
+/// How many samples to take
+#[derive(Debug, Clone)]
+pub struct MeasureOptions {
+    /// Fixed number of measured iterations
+    pub iterations: Option<u32>,
+    /// Keep measuring until this much time has passed (at least one iteration)
+    pub duration: Option<Duration>,
+    /// Upper bound on warmup iterations; 0 disables warmup
+    pub max_warmup: u32,
+}
+
+impl Default for MeasureOptions {
+    fn default() -> Self {
+        Self {
+            iterations: None,
+            duration: None,
+            max_warmup: 50,
+        }
+    }
+}
+
+impl MeasureOptions {
+    /// Iteration count used when neither iterations nor duration is given
+    pub const DEFAULT_ITERATIONS: u32 = 100;
+}
+
+/// Summary statistics over per-iteration timings, in nanoseconds
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stats {
+    pub samples: usize,
+    pub mean: f64,
+    pub median: f64,
+    pub p90: f64,
+    pub p99: f64,
+    pub min: f64,
+    pub max: f64,
+    pub stddev: f64,
+}
+
+impl Stats {
+    pub fn from_samples(samples: &[f64]) -> Self {
+        if samples.is_empty() {
+            return Self {
+                samples: 0,
+                mean: 0.0,
+                median: 0.0,
+                p90: 0.0,
+                p99: 0.0,
+                min: 0.0,
+                max: 0.0,
+                stddev: 0.0,
+            };
+        }
+        let mut sorted = samples.to_vec();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        let n = sorted.len() as f64;
+        let mean = sorted.iter().sum::<f64>() / n;
+        // Sample standard deviation (n - 1); zero for a single sample
+        let variance = if sorted.len() > 1 {
+            sorted.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0)
+        } else {
+            0.0
+        };
+        Self {
+            samples: sorted.len(),
+            mean,
+            median: percentile(&sorted, 50.0),
+            p90: percentile(&sorted, 90.0),
+            p99: percentile(&sorted, 99.0),
+            min: sorted[0],
+            max: sorted[sorted.len() - 1],
+            stddev: variance.sqrt(),
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "samples": self.samples,
+            "mean_ns": self.mean,
+            "median_ns": self.median,
+            "p90_ns": self.p90,
+            "p99_ns": self.p99,
+            "min_ns": self.min,
+            "max_ns": self.max,
+            "stddev_ns": self.stddev,
+        })
+    }
+
+    pub fn from_json(value: &Value) -> Option<Self> {
+        let field = |name: &str| value.get(name).and_then(Value::as_f64);
+        Some(Self {
+            samples: value.get("samples")?.as_u64()? as usize,
+            mean: field("mean_ns")?,
+            median: field("median_ns")?,
+            p90: field("p90_ns")?,
+            p99: field("p99_ns")?,
+            min: field("min_ns")?,
+            max: field("max_ns")?,
+            stddev: field("stddev_ns")?,
+        })
+    }
+}
+
+/// Linear-interpolated percentile of an already sorted slice
+fn percentile(sorted: &[f64], pct: f64) -> f64 {
+    if sorted.len() == 1 {
+        return sorted[0];
+    }
+    let rank = pct / 100.0 * (sorted.len() - 1) as f64;
+    let lower = rank.floor() as usize;
+    let upper = rank.ceil() as usize;
+    let weight = rank - lower as f64;
+    sorted[lower] * (1.0 - weight) + sorted[upper] * weight
+}
+
+/// Result of measuring one engine
+#[derive(Debug, Clone)]
+pub struct Measurement {
+    pub warmup_iterations: u32,
+    pub stats: Stats,
+}
+
+/// Time `f` per iteration: warm up until timings settle, then sample.
+/// Stops early (returning what was collected) if `f` returns an error.
+pub fn measure<E>(
+    options: &MeasureOptions,
+    mut f: impl FnMut() -> Result<(), E>,
+) -> Result<Measurement, E> {
+    let mut time_once = || -> Result<f64, E> {
+        let start = Instant::now();
+        f()?;
+        Ok(start.elapsed().as_nanos() as f64)
+    };
+
+    let mut warmup = Vec::new();
+    while (warmup.len() as u32) < options.max_warmup && !is_settled(&warmup) {
+        warmup.push(time_once()?);
+    }
+
+    let mut samples = Vec::new();
+    let start = Instant::now();
+    loop {
+        samples.push(time_once()?);
+        let count = samples.len() as u32;
+        let done = match (options.iterations, options.duration) {
+            (Some(n), Some(d)) => count >= n && start.elapsed() >= d,
+            (Some(n), None) => count >= n,
+            (None, Some(d)) => start.elapsed() >= d,
+            (None, None) => count >= MeasureOptions::DEFAULT_ITERATIONS,
+        };
+        if done {
+            break;
+        }
+    }
+
+    Ok(Measurement {
+        warmup_iterations: warmup.len() as u32,
+        stats: Stats::from_samples(&samples),
+    })
+}
+
+/// Warmup is over when the last two windows have similar means
+fn is_settled(warmup: &[f64]) -> bool {
+    if warmup.len() < WARMUP_WINDOW * 2 {
+        return false;
+    }
+    let window_mean = |w: &[f64]| w.iter().sum::<f64>() / w.len() as f64;
+    let recent = window_mean(&warmup[warmup.len() - WARMUP_WINDOW..]);
+    let previous =
+        window_mean(&warmup[warmup.len() - WARMUP_WINDOW * 2..warmup.len() - WARMUP_WINDOW]);
+    previous > 0.0 && ((recent - previous) / previous).abs() < WARMUP_TOLERANCE
+}
+
+/// Change of one engine's median against the baseline
+#[derive(Debug, Clone)]
+pub struct Comparison {
+    pub engine: String,
+    pub baseline_median: f64,
+    pub current_median: f64,
+    /// Relative change, e.g. 0.10 = 10% slower
+    pub change: f64,
+    pub regressed: bool,
+}
+
+/// Compare medians per engine; engines missing from either report are skipped.
+pub fn compare(current: &Value, baseline: &Value, threshold: f64) -> Vec<Comparison> {
+    let mut comparisons = Vec::new();
+    for engine in ["vm", "jit"] {
+        let median = |report: &Value| {
+            report
+                .get(engine)
+                .and_then(|e| e.get("stats"))
+                .and_then(Stats::from_json)
+                .map(|s| s.median)
+        };
+        let (Some(current_median), Some(baseline_median)) = (median(current), median(baseline))
+        else {
+            continue;
+        };
+        if baseline_median <= 0.0 {
+            continue;
+        }
+        let change = (current_median - baseline_median) / baseline_median;
+        comparisons.push(Comparison {
+            engine: engine.to_string(),
+            baseline_median,
+            current_median,
+            change,
+            regressed: change > threshold,
+        });
+    }
+    comparisons
+}
+
+/// Human-readable duration for a nanosecond count
+pub fn format_ns(ns: f64) -> String {
+    if ns >= 1e9 {
+        format!("{:.2} s", ns / 1e9)
+    } else if ns >= 1e6 {
+        format!("{:.2} ms", ns / 1e6)
+    } else if ns >= 1e3 {
+        format!("{:.2} µs", ns / 1e3)
+    } else {
+        format!("{:.0} ns", ns)
+    }
+}
+
+/// Print the statistics block used by the human-readable report
+pub fn print_measurement(m: &Measurement) {
+    let s = &m.stats;
+    println!(
+        "  Samples: {} (after {} warmup)",
+        s.samples, m.warmup_iterations
+    );
+    println!(
+        "  Median: {}  Mean: {}  Stddev: {}",
+        format_ns(s.median),
+        format_ns(s.mean),
+        format_ns(s.stddev)
+    );
+    println!(
+        "  Min: {}  p90: {}  p99: {}  Max: {}",
+        format_ns(s.min),
+        format_ns(s.p90),
+        format_ns(s.p99),
+        format_ns(s.max)
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stats_from_samples() {
+        let stats = Stats::from_samples(&[4.0, 1.0, 3.0, 2.0, 5.0]);
+        assert_eq!(stats.samples, 5);
+        assert_eq!(stats.mean, 3.0);
+        assert_eq!(stats.median, 3.0);
+        assert_eq!(stats.min, 1.0);
+        assert_eq!(stats.max, 5.0);
+        assert!((stats.p90 - 4.6).abs() < 1e-9);
+        assert!((stats.stddev - 2.5f64.sqrt()).abs() < 1e-9);
+        assert_eq!(Stats::from_json(&stats.to_json()), Some(stats));
+    }
+
+    #[test]
+    fn test_warmup_settles() {
+        let noisy = [100.0, 80.0, 60.0, 50.0, 40.0, 30.0, 20.0, 10.0, 10.0, 10.0];
+        assert!(!is_settled(&noisy));
+        let steady = [10.0; WARMUP_WINDOW * 2];
+        assert!(is_settled(&steady));
+    }
+
+    #[test]
+    fn test_measure_respects_iteration_count() {
+        let options = MeasureOptions {
+            iterations: Some(7),
+            duration: None,
+            max_warmup: 0,
+        };
+        let mut calls = 0;
+        let m = measure(&options, || {
+            calls += 1;
+            Ok::<(), ()>(())
+        })
+        .unwrap();
+        assert_eq!(m.warmup_iterations, 0);
+        assert_eq!(m.stats.samples, 7);
+        assert_eq!(calls, 7);
+    }
+
+    #[test]
+    fn test_compare_flags_regressions() {
+        let report = |vm: f64, jit: f64| {
+            json!({
+                "vm": { "stats": Stats::from_samples(&[vm]).to_json() },
+                "jit": { "stats": Stats::from_samples(&[jit]).to_json() },
+            })
+        };
+        let comparisons = compare(&report(120.0, 95.0), &report(100.0, 100.0), 0.05);
+        assert_eq!(comparisons.len(), 2);
+        assert!(comparisons[0].regressed);
+        assert!((comparisons[0].change - 0.2).abs() < 1e-9);
+        assert!(!comparisons[1].regressed);
+    }
+}
@@ -5,14 +5,50 @@
 //! - Copy vs Move semantics based on type
 //! - Borrow tracking for Ref<T> and MutRef<T>
 //! - Lifetime analysis for references
+//!
+//! Code being migrated can opt out with comment directives:
+//! `// @script-ignore borrow` exempts the next line (or its own line when it
+//! trails code) and `// @script-ignore-file borrow` exempts the whole file.
 
 use std::collections::{HashMap, HashSet};
+use swc_common::Spanned;
 use swc_ecma_ast::*;
 
 use crate::types::Type;
 use crate::types::error::{BorrowKind, Span, TypeError, TypeErrors};
 use crate::types::registry::TypeRegistry;
 
+/// Exempts one line from ownership checks
+pub const IGNORE_DIRECTIVE: &str = "@script-ignore borrow";
+/// Exempts the whole file from ownership checks
+pub const IGNORE_FILE_DIRECTIVE: &str = "@script-ignore-file borrow";
+
+/// What the compiler does with ownership errors (`--borrow-check=...`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BorrowCheckLevel {
+    /// Skip the analysis
+    Off,
+    /// Report errors as warnings and compile anyway
+    Warn,
+    /// Reject the program
+    #[default]
+    Error,
+}
+
+impl BorrowCheckLevel {
+    pub fn parse(level: &str) -> Result<Self, String> {
+        match level {
+            "off" => Ok(Self::Off),
+            "warn" => Ok(Self::Warn),
+            "error" => Ok(Self::Error),
+            _ => Err(format!(
+                "invalid borrow-check level '{}' (expected off, warn or error)",
+                level
+            )),
+        }
+    }
+}
+
 #[derive(PartialEq, Clone, Copy, Debug)]
 pub enum VarKind {
     Primitive,
@@ -21,6 +57,18 @@ pub enum VarKind {
     BorrowMut,
 }
 
+/// How a closure refers to a variable from an enclosing scope. Ordered by
+/// strength: a closure that both reads and assigns captures mutably.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug)]
+pub enum CaptureMode {
+    /// Only reads it: a shared borrow
+    Read,
+    /// Assigns to it or one of its properties
+    Mutate,
+    /// Takes ownership (`move(() => ...)`)
+    Move,
+}
+
 #[derive(PartialEq, Clone, Copy, Debug)]
 pub enum VarState {
     Owned,
@@ -39,6 +87,12 @@ pub struct VarInfo {
     pub mut_borrow: bool,
     pub def_span: Span,
     pub moved_span: Option<Span>,
+    /// Set when the move happens in a loop body and the loop can come back
+    /// around to a use: the location of that loop
+    pub moved_in_loop: Option<Span>,
+    /// Strongest by-reference capture by a closure; the variable can't be
+    /// moved out from under it
+    pub captured_by: Option<CaptureMode>,
     pub scope_depth: usize,
 }
 
@@ -53,6 +107,8 @@ impl VarInfo {
             mut_borrow: false,
             def_span: span,
             moved_span: None,
+            moved_in_loop: None,
+            captured_by: None,
             scope_depth,
         }
     }
@@ -68,6 +124,7 @@ impl VarInfo {
             Type::MutRef(_) => VarKind::BorrowMut,
             Type::String
             | Type::Array(_)
+            | Type::TypedArray(_)
             | Type::Object(_)
             | Type::Function(_)
             | Type::Struct(_)
@@ -93,6 +150,13 @@ pub struct BorrowChecker {
     errors: TypeErrors,
     scope_depth: usize,
     scope_stack: Vec<HashSet<String>>,
+    /// Source position of each line start, for locations in messages;
+    /// empty when the source wasn't provided
+    line_starts: Vec<u32>,
+    /// 1-based lines exempted by `@script-ignore borrow`
+    ignored_lines: HashSet<u32>,
+    /// Set by `@script-ignore-file borrow`
+    ignore_file: bool,
 }
 
 impl Default for BorrowChecker {
@@ -109,6 +173,9 @@ impl BorrowChecker {
             errors: TypeErrors::new(),
             scope_depth: 0,
             scope_stack: vec![HashSet::new()],
+            line_starts: Vec::new(),
+            ignored_lines: HashSet::new(),
+            ignore_file: false,
         }
     }
 
@@ -119,6 +186,77 @@ impl BorrowChecker {
             errors: TypeErrors::new(),
             scope_depth: 0,
             scope_stack: vec![HashSet::new()],
+            line_starts: Vec::new(),
+            ignored_lines: HashSet::new(),
+            ignore_file: false,
+        }
+    }
+
+    /// Provide the text being checked, which the parser placed at source
+    /// position `start`, so messages can point at lines and directives in
+    /// comments take effect.
+    pub fn set_source(&mut self, start: u32, source: &str) {
+        self.line_starts = std::iter::once(start)
+            .chain(
+                source
+                    .match_indices('\n')
+                    .map(|(i, _)| start + i as u32 + 1),
+            )
+            .collect();
+        self.scan_directives(source);
+    }
+
+    fn scan_directives(&mut self, source: &str) {
+        self.ignored_lines.clear();
+        self.ignore_file = false;
+        // A directive on a line of its own waits for the next line of code
+        let mut pending = false;
+        for (index, text) in source.lines().enumerate() {
+            let line = index as u32 + 1;
+            let (code, comment) = match text.find("//") {
+                Some(at) => (text[..at].trim(), Some(text[at + 2..].trim())),
+                None => (text.trim(), None),
+            };
+            if !code.is_empty() && pending {
+                self.ignored_lines.insert(line);
+                pending = false;
+            }
+            match comment {
+                Some(c) if c.starts_with(IGNORE_FILE_DIRECTIVE) => self.ignore_file = true,
+                Some(c) if c.starts_with(IGNORE_DIRECTIVE) && code.is_empty() => pending = true,
+                Some(c) if c.starts_with(IGNORE_DIRECTIVE) => {
+                    self.ignored_lines.insert(line);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Whether a statement starting at `pos` is exempted by a directive
+    fn is_ignored(&self, pos: u32) -> bool {
+        if self.ignore_file {
+            return true;
+        }
+        let line = self.line_starts.partition_point(|&start| start <= pos) as u32;
+        line > 0 && self.ignored_lines.contains(&line)
+    }
+
+    fn span(&self, span: swc_common::Span) -> Span {
+        let (lo, hi) = (span.lo.0, span.hi.0);
+        match self.line_starts.partition_point(|&start| start <= lo) {
+            0 => Span::from_range(lo, hi),
+            line => Span::new(lo, hi, line as u32 - 1, lo - self.line_starts[line - 1]),
+        }
+    }
+
+    /// Explains a move that happened on an earlier trip around a loop
+    fn loop_note(&self, info: &VarInfo) -> String {
+        match info.moved_in_loop {
+            Some(_) if self.line_starts.is_empty() => {
+                " (value moved in previous iteration of loop)".to_string()
+            }
+            Some(at) => format!(" (value moved in previous iteration of loop at {})", at),
+            None => String::new(),
         }
     }
 
@@ -165,6 +303,17 @@ impl BorrowChecker {
     }
 
     pub fn analyze_stmt(&mut self, stmt: &Stmt) -> Result<(), String> {
+        if !self.is_ignored(stmt.span().lo.0) {
+            return self.check_stmt(stmt);
+        }
+        // Still track the statement, but drop what it reports
+        let reported = self.errors.errors.len();
+        let _ = self.check_stmt(stmt);
+        self.errors.errors.truncate(reported);
+        Ok(())
+    }
+
+    fn check_stmt(&mut self, stmt: &Stmt) -> Result<(), String> {
         match stmt {
             Stmt::Decl(Decl::Var(var_decl)) => {
                 for decl in &var_decl.decls {
@@ -183,14 +332,67 @@ impl BorrowChecker {
             }
             Stmt::If(if_stmt) => {
                 self.analyze_expr(&if_stmt.test)?;
+                // Each branch starts from the state before the `if`
+                let before = self.symbols.clone();
                 self.analyze_stmt(&if_stmt.cons)?;
+                let after_cons = std::mem::replace(&mut self.symbols, before);
                 if let Some(alt) = &if_stmt.alt {
                     self.analyze_stmt(alt)?;
                 }
+                self.join(after_cons);
             }
             Stmt::While(while_stmt) => {
-                self.analyze_expr(&while_stmt.test)?;
-                self.analyze_stmt(&while_stmt.body)?;
+                self.analyze_loop(while_stmt.span, &while_stmt.body, |this| {
+                    this.analyze_expr(&while_stmt.test)?;
+                    this.analyze_stmt(&while_stmt.body)
+                })?;
+            }
+            Stmt::DoWhile(do_while) => {
+                self.analyze_loop(do_while.span, &do_while.body, |this| {
+                    this.analyze_stmt(&do_while.body)?;
+                    this.analyze_expr(&do_while.test)
+                })?;
+            }
+            Stmt::ForOf(ForOfStmt {
+                span,
+                left,
+                right,
+                body,
+                ..
+            })
+            | Stmt::ForIn(ForInStmt {
+                span,
+                left,
+                right,
+                body,
+            }) => {
+                self.analyze_expr(right)?;
+                self.analyze_loop(*span, body, |this| {
+                    // A declared loop variable is a fresh binding every iteration
+                    this.enter_scope();
+                    match left {
+                        ForHead::VarDecl(var_decl) => {
+                            for decl in &var_decl.decls {
+                                if let Pat::Ident(ident) = &decl.name {
+                                    this.define(
+                                        ident.id.sym.to_string(),
+                                        Type::Any,
+                                        Span::default(),
+                                    );
+                                }
+                            }
+                        }
+                        ForHead::Pat(pat) => {
+                            if let Pat::Ident(ident) = pat.as_ref() {
+                                this.reinitialize(ident.id.sym.as_ref(), Type::Any);
+                            }
+                        }
+                        ForHead::UsingDecl(_) => {}
+                    }
+                    let result = this.analyze_stmt(body);
+                    this.exit_scope();
+                    result
+                })?;
             }
             Stmt::For(for_stmt) => {
                 self.enter_scope();
@@ -206,13 +408,16 @@ impl BorrowChecker {
                         }
                     }
                 }
-                if let Some(test) = &for_stmt.test {
-                    self.analyze_expr(test)?;
-                }
-                if let Some(update) = &for_stmt.update {
-                    self.analyze_expr(update)?;
-                }
-                self.analyze_stmt(&for_stmt.body)?;
+                self.analyze_loop(for_stmt.span, &for_stmt.body, |this| {
+                    if let Some(test) = &for_stmt.test {
+                        this.analyze_expr(test)?;
+                    }
+                    this.analyze_stmt(&for_stmt.body)?;
+                    if let Some(update) = &for_stmt.update {
+                        this.analyze_expr(update)?;
+                    }
+                    Ok(())
+                })?;
                 self.exit_scope();
             }
             Stmt::Return(ret) => {
@@ -228,6 +433,63 @@ impl BorrowChecker {
         Ok(())
     }
 
+    /// Check one trip around a loop, then again from the state the back edge
+    /// carries, until no further variables are moved. A variable moved in
+    /// the body that the next iteration uses again is reported against the
+    /// loop. Bindings declared inside the body are recreated on every trip,
+    /// so moving them is fine. A body that always breaks or returns never
+    /// takes the back edge, so it is checked once.
+    fn analyze_loop(
+        &mut self,
+        span: swc_common::Span,
+        body: &Stmt,
+        iteration: impl Fn(&mut Self) -> Result<(), String>,
+    ) -> Result<(), String> {
+        let loop_at = self.span(span);
+        let entry = self.symbols.clone();
+        let moved_names = |symbols: &HashMap<String, VarInfo>| -> HashSet<String> {
+            symbols
+                .iter()
+                .filter(|(_, info)| info.state == VarState::Moved)
+                .map(|(name, _)| name.clone())
+                .collect()
+        };
+        let mut moved_at_top = moved_names(&entry);
+        iteration(self)?;
+        if always_exits(body) {
+            let end = std::mem::replace(&mut self.symbols, entry);
+            self.join(end);
+            return Ok(());
+        }
+        loop {
+            // The next iteration starts from either the entry state or the
+            // end of this one; the loop may also run zero times
+            let end = std::mem::replace(&mut self.symbols, entry.clone());
+            self.join(end);
+
+            for (name, info) in self.symbols.iter_mut() {
+                let was_moved = entry
+                    .get(name)
+                    .is_none_or(|before| before.state == VarState::Moved);
+                if info.state == VarState::Moved && !was_moved {
+                    info.moved_in_loop = Some(loop_at);
+                }
+            }
+            let moved = moved_names(&self.symbols);
+            if moved == moved_at_top {
+                // Past the loop these are ordinary moves
+                for info in self.symbols.values_mut() {
+                    if info.moved_in_loop == Some(loop_at) {
+                        info.moved_in_loop = None;
+                    }
+                }
+                return Ok(());
+            }
+            moved_at_top = moved;
+            iteration(self)?;
+        }
+    }
+
     fn analyze_var_decl(&mut self, decl: &VarDeclarator) -> Result<(), String> {
         let name = match &decl.name {
             Pat::Ident(ident) => ident.id.sym.to_string(),
@@ -237,12 +499,7 @@ impl BorrowChecker {
         let ty = self.determine_type(decl);
 
         if let Some(init) = &decl.init {
-            // Bare identifier = ownership transfer; member access = borrow
-            if let Expr::Ident(id) = init.as_ref() {
-                self.process_move(id.sym.as_ref())?;
-            } else {
-                self.analyze_expr(init)?;
-            }
+            self.analyze_moved_expr(init)?;
         }
 
         self.define(name, ty, Span::default());
@@ -282,6 +539,14 @@ impl BorrowChecker {
                     .map(|info| info.ty.clone())
                     .unwrap_or(Type::Any)
             }
+            Expr::Call(call) => match cloned_ident(call) {
+                Some(source) => self
+                    .symbols
+                    .get(source.sym.as_ref())
+                    .map(|info| info.ty.clone())
+                    .unwrap_or(Type::Any),
+                None => Type::Any,
+            },
             _ => Type::Any,
         }
     }
@@ -303,9 +568,14 @@ impl BorrowChecker {
                 }
             }
             Expr::Assign(assign) => {
-                if let AssignTarget::Simple(SimpleAssignTarget::Ident(id)) = &assign.left {
-                    let name = id.id.sym.to_string();
-                    if let Some(info) = self.symbols.get(&name)
+                let target = match &assign.left {
+                    AssignTarget::Simple(SimpleAssignTarget::Ident(id)) => {
+                        Some(id.id.sym.to_string())
+                    }
+                    _ => None,
+                };
+                if let Some(name) = &target {
+                    if let Some(info) = self.symbols.get(name)
                         && info.immut_borrows > 0
                     {
                         return Err(format!(
@@ -313,11 +583,17 @@ impl BorrowChecker {
                             name
                         ));
                     }
+                    // `x += y` reads the old value first
+                    if assign.op != AssignOp::Assign {
+                        self.process_use(name)?;
+                    }
                 }
-                if let Expr::Ident(id) = assign.right.as_ref() {
-                    self.process_move(id.sym.as_ref())?;
-                } else {
-                    self.analyze_expr(&assign.right)?;
+                self.analyze_moved_expr(&assign.right)?;
+                if let Some(name) = &target
+                    && assign.op == AssignOp::Assign
+                {
+                    let ty = self.infer_type(&assign.right);
+                    self.reinitialize(name, ty);
                 }
             }
             Expr::Bin(bin) => {
@@ -328,6 +604,18 @@ impl BorrowChecker {
                 self.analyze_expr(&un.arg)?;
             }
             Expr::Call(call) => {
+                // A clone only reads its source: no move, and no borrow left behind
+                if let Some(source) = cloned_ident(call) {
+                    self.process_use(source.sym.as_ref())?;
+                    // `structuredClone(x, { transfer: [buf] })`
+                    for arg in call.args.iter().skip(1) {
+                        self.analyze_expr(&arg.expr)?;
+                    }
+                    return Ok(());
+                }
+                if let Some(closure) = moved_closure(call) {
+                    return self.analyze_closure_expr(closure, true);
+                }
                 for arg in &call.args {
                     if let Expr::Ident(id) = arg.expr.as_ref() {
                         self.process_borrow(id.sym.as_ref(), false)?;
@@ -353,16 +641,16 @@ impl BorrowChecker {
                     }
                 }
             }
-            Expr::Arrow(arrow) => {
-                self.analyze_closure(&arrow.params, &arrow.body)?;
-            }
-            Expr::Fn(fn_expr) => {
-                self.analyze_fn_closure(fn_expr)?;
+            Expr::Arrow(_) | Expr::Fn(_) => {
+                self.analyze_closure_expr(expr, false)?;
             }
             Expr::Cond(cond) => {
                 self.analyze_expr(&cond.test)?;
+                let before = self.symbols.clone();
                 self.analyze_expr(&cond.cons)?;
+                let after_cons = std::mem::replace(&mut self.symbols, before);
                 self.analyze_expr(&cond.alt)?;
+                self.join(after_cons);
             }
             Expr::Paren(paren) => {
                 self.analyze_expr(&paren.expr)?;
@@ -375,21 +663,45 @@ impl BorrowChecker {
         Ok(())
     }
 
+    /// Check an expression whose value is stored somewhere. A bare
+    /// identifier is an ownership transfer, and so is either arm of a
+    /// conditional; member access is a borrow.
+    fn analyze_moved_expr(&mut self, expr: &Expr) -> Result<(), String> {
+        match expr {
+            Expr::Ident(id) => self.process_move(id.sym.as_ref()),
+            Expr::Paren(paren) => self.analyze_moved_expr(&paren.expr),
+            Expr::Cond(cond) => {
+                self.analyze_expr(&cond.test)?;
+                let before = self.symbols.clone();
+                self.analyze_moved_expr(&cond.cons)?;
+                let after_cons = std::mem::replace(&mut self.symbols, before);
+                self.analyze_moved_expr(&cond.alt)?;
+                self.join(after_cons);
+                Ok(())
+            }
+            _ => self.analyze_expr(expr),
+        }
+    }
+
     fn process_use(&mut self, name: &str) -> Result<(), String> {
         if let Some(info) = self.symbols.get_mut(name) {
             if info.state == VarState::Moved {
-                let moved_at = info.moved_span.unwrap_or_default();
+                let moved_at = info.moved_in_loop.or(info.moved_span).unwrap_or_default();
+                let note = self.loop_note(&self.symbols[name]);
                 self.errors.push(TypeError::UseAfterMove {
                     var: name.to_string(),
                     moved_at,
                     used_at: Span::default(),
                 });
-                return Err(format!("BORROW ERROR: Use of moved variable '{}'", name));
+                return Err(format!(
+                    "BORROW ERROR: Use of moved variable '{}'{}",
+                    name, note
+                ));
             }
 
             if info.state == VarState::CapturedByAsync {
                 return Err(format!(
-                    "BORROW ERROR: '{}' was moved into an async closure! Cannot use after capture.",
+                    "BORROW ERROR: '{}' was moved into a closure! Cannot use after capture.",
                     name
                 ));
             }
@@ -401,18 +713,29 @@ impl BorrowChecker {
     fn process_move(&mut self, name: &str) -> Result<(), String> {
         if let Some(info) = self.symbols.get_mut(name) {
             if info.state == VarState::Moved {
-                let moved_at = info.moved_span.unwrap_or_default();
+                let moved_at = info.moved_in_loop.or(info.moved_span).unwrap_or_default();
+                let note = self.loop_note(&self.symbols[name]);
                 self.errors.push(TypeError::UseAfterMove {
                     var: name.to_string(),
                     moved_at,
                     used_at: Span::default(),
                 });
-                return Err(format!("BORROW ERROR: Use of moved variable '{}'", name));
+                return Err(format!(
+                    "BORROW ERROR: Use of moved variable '{}'{}",
+                    name, note
+                ));
             }
 
             if info.state == VarState::CapturedByAsync {
                 return Err(format!(
-                    "BORROW ERROR: '{}' was moved into an async closure! Cannot use after capture.",
+                    "BORROW ERROR: '{}' was moved into a closure! Cannot use after capture.",
+                    name
+                ));
+            }
+
+            if info.is_move() && !info.is_global() && info.captured_by.is_some() {
+                return Err(format!(
+                    "BORROW ERROR: Cannot move '{}' while a closure captures it by reference",
                     name
                 ));
             }
@@ -425,23 +748,66 @@ impl BorrowChecker {
         Ok(())
     }
 
-    fn process_borrow(&mut self, name: &str, mutable: bool) -> Result<(), String> {
-        if let Some(info) = self.symbols.get_mut(name) {
-            if info.state == VarState::Moved {
-                return Err(format!(
-                    "BORROW ERROR: Cannot borrow moved variable '{}'",
-                    name
-                ));
+    /// Merge the state at the end of another branch into the current one.
+    /// A variable moved on either path counts as moved afterwards, since the
+    /// code that follows runs after both.
+    fn join(&mut self, other: HashMap<String, VarInfo>) {
+        for (name, theirs) in other {
+            let Some(ours) = self.symbols.get_mut(&name) else {
+                self.symbols.insert(name, theirs);
+                continue;
+            };
+            if theirs.state == VarState::Moved
+                || (theirs.state == VarState::CapturedByAsync && ours.state != VarState::Moved)
+            {
+                ours.state = theirs.state;
+                ours.moved_span = theirs.moved_span;
+                ours.moved_in_loop = ours.moved_in_loop.or(theirs.moved_in_loop);
             }
+            ours.immut_borrows = ours.immut_borrows.max(theirs.immut_borrows);
+            ours.mut_borrow |= theirs.mut_borrow;
+            ours.captured_by = ours.captured_by.max(theirs.captured_by);
+        }
+    }
+
+    /// A plain assignment gives the binding a fresh value, so an earlier
+    /// move out of it no longer matters.
+    fn reinitialize(&mut self, name: &str, ty: Type) {
+        if let Some(info) = self.symbols.get_mut(name)
+            && info.state == VarState::Moved
+        {
+            info.kind = VarInfo::kind_from_type(&ty);
+            info.ty = ty;
+            info.state = VarState::Owned;
+            info.moved_span = None;
+        }
+    }
 
+    fn process_borrow(&mut self, name: &str, mutable: bool) -> Result<(), String> {
+        if let Some(info) = self.symbols.get(name)
+            && info.state == VarState::Moved
+        {
+            return Err(format!(
+                "BORROW ERROR: Cannot borrow moved variable '{}'{}",
+                name,
+                self.loop_note(info)
+            ));
+        }
+        if let Some(info) = self.symbols.get_mut(name) {
             if info.state == VarState::CapturedByAsync {
                 return Err(format!(
-                    "BORROW ERROR: Cannot borrow '{}' - it was captured by an async closure!",
+                    "BORROW ERROR: Cannot borrow '{}' - it was moved into a closure!",
                     name
                 ));
             }
 
             if mutable {
+                if info.captured_by == Some(CaptureMode::Mutate) {
+                    return Err(format!(
+                        "BORROW ERROR: Cannot borrow '{}' as mutable while a closure mutates it",
+                        name
+                    ));
+                }
                 if info.immut_borrows > 0 {
                     self.errors.push(TypeError::BorrowConflict {
                         var: name.to_string(),
@@ -496,66 +862,54 @@ impl BorrowChecker {
         }
     }
 
-    fn analyze_closure(&mut self, params: &[Pat], body: &BlockStmtOrExpr) -> Result<(), String> {
-        let param_names: HashSet<String> = params
-            .iter()
-            .filter_map(|p| {
-                if let Pat::Ident(id) = p {
-                    Some(id.id.sym.to_string())
-                } else {
-                    None
-                }
-            })
-            .collect();
-
-        let mut captured = HashSet::new();
-        match body {
-            BlockStmtOrExpr::Expr(e) => {
-                self.scan_expr_for_captures(e, &param_names, &mut captured);
-            }
-            BlockStmtOrExpr::BlockStmt(block) => {
-                for stmt in &block.stmts {
-                    self.scan_stmt_for_captures(stmt, &param_names, &mut captured);
+    /// Check the captures of an arrow or function expression. `moved`
+    /// closures take ownership of everything they capture.
+    fn analyze_closure_expr(&mut self, closure: &Expr, moved: bool) -> Result<(), String> {
+        let mut params = HashSet::new();
+        let mut captured = HashMap::new();
+        match closure {
+            Expr::Arrow(arrow) => {
+                params.extend(arrow.params.iter().filter_map(|p| match p {
+                    Pat::Ident(id) => Some(id.id.sym.to_string()),
+                    _ => None,
+                }));
+                match arrow.body.as_ref() {
+                    BlockStmtOrExpr::Expr(e) => {
+                        self.scan_expr_for_captures(e, &params, &mut captured);
+                    }
+                    BlockStmtOrExpr::BlockStmt(block) => {
+                        for stmt in &block.stmts {
+                            self.scan_stmt_for_captures(stmt, &params, &mut captured);
+                        }
+                    }
                 }
             }
-        }
-
-        for var_name in &captured {
-            self.process_capture(var_name)?;
-        }
-
-        Ok(())
-    }
-
-    fn analyze_fn_closure(&mut self, fn_expr: &FnExpr) -> Result<(), String> {
-        let param_names: HashSet<String> = fn_expr
-            .function
-            .params
-            .iter()
-            .filter_map(|p| {
-                if let Pat::Ident(id) = &p.pat {
-                    Some(id.id.sym.to_string())
-                } else {
-                    None
+            Expr::Fn(fn_expr) => {
+                params.extend(fn_expr.function.params.iter().filter_map(|p| match &p.pat {
+                    Pat::Ident(id) => Some(id.id.sym.to_string()),
+                    _ => None,
+                }));
+                if let Some(body) = &fn_expr.function.body {
+                    for stmt in &body.stmts {
+                        self.scan_stmt_for_captures(stmt, &params, &mut captured);
+                    }
                 }
-            })
-            .collect();
-
-        let mut captured = HashSet::new();
-        if let Some(body) = &fn_expr.function.body {
-            for stmt in &body.stmts {
-                self.scan_stmt_for_captures(stmt, &param_names, &mut captured);
             }
+            _ => return self.analyze_expr(closure),
         }
 
-        for var_name in &captured {
-            self.process_capture(var_name)?;
+        // Sorted so the first error reported doesn't depend on hashing
+        let mut captured: Vec<(String, CaptureMode)> = captured.into_iter().collect();
+        captured.sort();
+        for (var_name, mode) in &captured {
+            let mode = if moved { CaptureMode::Move } else { *mode };
+            self.process_capture(var_name, mode)?;
         }
 
         Ok(())
     }
 
-    fn process_capture(&mut self, name: &str) -> Result<(), String> {
+    fn process_capture(&mut self, name: &str, mode: CaptureMode) -> Result<(), String> {
         if let Some(info) = self.symbols.get_mut(name) {
             if info.is_global() {
                 return Ok(());
@@ -568,32 +922,62 @@ impl BorrowChecker {
                 ));
             }
 
-            if info.immut_borrows > 0 || info.mut_borrow {
+            match mode {
+                CaptureMode::Read => {
+                    if info.mut_borrow {
+                        return Err(format!(
+                            "LIFETIME ERROR: Cannot capture '{}' while it is mutably borrowed",
+                            name
+                        ));
+                    }
+                }
+                CaptureMode::Mutate | CaptureMode::Move => {
+                    if info.immut_borrows > 0 || info.mut_borrow {
+                        return Err(format!(
+                            "LIFETIME ERROR: Cannot capture '{}' while it has active borrow(s)",
+                            name
+                        ));
+                    }
+                }
+            }
+
+            if mode != CaptureMode::Move {
+                info.captured_by = info.captured_by.max(Some(mode));
+            } else if info.captured_by.is_some() && info.is_move() {
                 return Err(format!(
-                    "LIFETIME ERROR: Cannot capture '{}' while it has active borrow(s)",
+                    "BORROW ERROR: Cannot move '{}' into a closure while another closure captures it by reference",
                     name
                 ));
-            }
-
-            if info.is_move() {
+            } else if info.is_move() {
                 info.state = VarState::CapturedByAsync;
             }
         }
         Ok(())
     }
 
+    /// Record a capture of `name`, keeping the strongest mode seen
+    fn note_capture(
+        &self,
+        name: &str,
+        mode: CaptureMode,
+        local_vars: &HashSet<String>,
+        captured: &mut HashMap<String, CaptureMode>,
+    ) {
+        if !local_vars.contains(name) && self.symbols.contains_key(name) {
+            let entry = captured.entry(name.to_string()).or_insert(mode);
+            *entry = (*entry).max(mode);
+        }
+    }
+
     fn scan_expr_for_captures(
         &self,
         expr: &Expr,
         local_vars: &HashSet<String>,
-        captured: &mut HashSet<String>,
+        captured: &mut HashMap<String, CaptureMode>,
     ) {
         match expr {
             Expr::Ident(id) => {
-                let name = id.sym.to_string();
-                if !local_vars.contains(&name) && self.symbols.contains_key(&name) {
-                    captured.insert(name);
-                }
+                self.note_capture(id.sym.as_ref(), CaptureMode::Read, local_vars, captured);
             }
             Expr::Bin(bin) => {
                 self.scan_expr_for_captures(&bin.left, local_vars, captured);
@@ -628,11 +1012,35 @@ impl BorrowChecker {
                 }
             }
             Expr::Assign(assign) => {
+                match &assign.left {
+                    AssignTarget::Simple(SimpleAssignTarget::Ident(id)) => {
+                        self.note_capture(
+                            id.id.sym.as_ref(),
+                            CaptureMode::Mutate,
+                            local_vars,
+                            captured,
+                        );
+                    }
+                    AssignTarget::Simple(SimpleAssignTarget::Member(member)) => {
+                        self.scan_mutated_member(member, local_vars, captured);
+                    }
+                    _ => {}
+                }
                 self.scan_expr_for_captures(&assign.right, local_vars, captured);
             }
-            Expr::Unary(un) => {
-                self.scan_expr_for_captures(&un.arg, local_vars, captured);
-            }
+            Expr::Update(update) => match update.arg.as_ref() {
+                Expr::Ident(id) => {
+                    self.note_capture(id.sym.as_ref(), CaptureMode::Mutate, local_vars, captured);
+                }
+                Expr::Member(member) => self.scan_mutated_member(member, local_vars, captured),
+                other => self.scan_expr_for_captures(other, local_vars, captured),
+            },
+            Expr::Unary(un) => match (un.op, un.arg.as_ref()) {
+                (UnaryOp::Delete, Expr::Member(member)) => {
+                    self.scan_mutated_member(member, local_vars, captured);
+                }
+                _ => self.scan_expr_for_captures(&un.arg, local_vars, captured),
+            },
             Expr::Cond(cond) => {
                 self.scan_expr_for_captures(&cond.test, local_vars, captured);
                 self.scan_expr_for_captures(&cond.cons, local_vars, captured);
@@ -645,11 +1053,30 @@ impl BorrowChecker {
         }
     }
 
+    /// `a.b.c = ...` mutates whatever `a` refers to
+    fn scan_mutated_member(
+        &self,
+        member: &MemberExpr,
+        local_vars: &HashSet<String>,
+        captured: &mut HashMap<String, CaptureMode>,
+    ) {
+        match member.obj.as_ref() {
+            Expr::Ident(id) => {
+                self.note_capture(id.sym.as_ref(), CaptureMode::Mutate, local_vars, captured);
+            }
+            Expr::Member(inner) => self.scan_mutated_member(inner, local_vars, captured),
+            other => self.scan_expr_for_captures(other, local_vars, captured),
+        }
+        if let MemberProp::Computed(c) = &member.prop {
+            self.scan_expr_for_captures(&c.expr, local_vars, captured);
+        }
+    }
+
     fn scan_stmt_for_captures(
         &self,
         stmt: &Stmt,
         local_vars: &HashSet<String>,
-        captured: &mut HashSet<String>,
+        captured: &mut HashMap<String, CaptureMode>,
     ) {
         match stmt {
             Stmt::Expr(expr_stmt) => {
@@ -711,6 +1138,59 @@ impl BorrowChecker {
     }
 }
 
+/// The variable copied by an explicit clone, `x.clone()` or
+/// `structuredClone(x, options?)`. The result is an independent value.
+fn cloned_ident(call: &CallExpr) -> Option<&Ident> {
+    let Callee::Expr(callee) = &call.callee else {
+        return None;
+    };
+    match (callee.as_ref(), call.args.as_slice()) {
+        (Expr::Member(member), []) => match (member.obj.as_ref(), &member.prop) {
+            (Expr::Ident(source), MemberProp::Ident(prop)) if prop.sym == "clone" => Some(source),
+            _ => None,
+        },
+        (Expr::Ident(func), [arg] | [arg, _])
+            if func.sym == "structuredClone" && arg.spread.is_none() =>
+        {
+            match arg.expr.as_ref() {
+                Expr::Ident(source) => Some(source),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// The closure in `move(() => ...)`, which takes ownership of its captures
+fn moved_closure(call: &CallExpr) -> Option<&Expr> {
+    let Callee::Expr(callee) = &call.callee else {
+        return None;
+    };
+    match (callee.as_ref(), call.args.as_slice()) {
+        (Expr::Ident(func), [arg]) if func.sym == "move" && arg.spread.is_none() => {
+            match arg.expr.as_ref() {
+                closure @ (Expr::Arrow(_) | Expr::Fn(_)) => Some(closure),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Whether every path through `stmt` leaves by `break`, `return` or
+/// `throw`, so the code after it (or the next loop iteration) never runs
+fn always_exits(stmt: &Stmt) -> bool {
+    match stmt {
+        Stmt::Break(_) | Stmt::Return(_) | Stmt::Throw(_) => true,
+        Stmt::Block(block) => block.stmts.iter().any(always_exits),
+        Stmt::If(if_stmt) => {
+            always_exits(&if_stmt.cons) && if_stmt.alt.as_deref().is_some_and(always_exits)
+        }
+        Stmt::Labeled(labeled) => always_exits(&labeled.body),
+        _ => false,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -836,6 +1316,42 @@ mod tests {
         assert!(checker.process_use("arr").is_ok()); // Still valid
     }
 
+    #[test]
+    fn test_reassignment_after_move() {
+        let mut checker = BorrowChecker::new();
+        checker.enter_scope();
+        checker.define(
+            "path".to_string(),
+            Type::Array(Box::new(Type::Any)),
+            Span::default(),
+        );
+
+        assert!(checker.process_move("path").is_ok());
+        assert!(checker.process_use("path").is_err());
+        checker.reinitialize("path", Type::Array(Box::new(Type::Any)));
+        assert!(checker.process_use("path").is_ok());
+        assert!(checker.process_move("path").is_ok()); // Moves again
+        assert!(checker.process_use("path").is_err());
+    }
+
+    #[test]
+    fn test_join_keeps_move_from_either_branch() {
+        let mut checker = BorrowChecker::new();
+        checker.enter_scope();
+        let ty = Type::Array(Box::new(Type::Any));
+        checker.define("a".to_string(), ty.clone(), Span::default());
+        checker.define("b".to_string(), ty, Span::default());
+
+        let before = checker.symbols.clone();
+        assert!(checker.process_move("a").is_ok());
+        let after_cons = std::mem::replace(&mut checker.symbols, before);
+        assert!(checker.process_use("a").is_ok()); // Untouched on this path
+        checker.join(after_cons);
+
+        assert!(checker.process_use("a").is_err());
+        assert!(checker.process_use("b").is_ok());
+    }
+
     #[test]
     fn test_property_access_does_not_move() {
         let mut checker = BorrowChecker::new();
@@ -854,17 +1370,13 @@ mod tests {
     }
 }
 
-#[test]
-fn test_move_tracking_full_flow() {
+/// Parse `source` as a script and run a fresh checker over it, stopping at
+/// the first error. The checker comes back for tests that inspect it.
+#[cfg(test)]
+fn check_src(source: &str) -> (Result<(), String>, BorrowChecker) {
     use swc_common::{FileName, SourceMap, sync::Lrc};
     use swc_ecma_parser::{Parser, StringInput, Syntax, lexer::Lexer};
 
-    let source = r#"
-        let data = [1, 2, 3];
-        let other = data;
-        console.log(data.length);
-    "#;
-
     let cm: Lrc<SourceMap> = Default::default();
     let fm = cm.new_source_file(
         FileName::Custom("test.ot".into()).into(),
@@ -872,38 +1384,32 @@ fn test_move_tracking_full_flow() {
     );
     let syntax = Syntax::Typescript(Default::default());
     let lexer = Lexer::new(syntax, Default::default(), StringInput::from(&*fm), None);
-    let mut parser = Parser::new_from(lexer);
-    let program = parser.parse_program().unwrap();
-
+    let body = match Parser::new_from(lexer).parse_program().unwrap() {
+        swc_ecma_ast::Program::Script(script) => script.body,
+        _ => panic!("Expected Script"),
+    };
     let mut checker = BorrowChecker::new();
     checker.enter_scope();
+    let result = body.iter().try_for_each(|stmt| checker.analyze_stmt(stmt));
+    (result, checker)
+}
 
-    let mut found_error = false;
-    match &program {
-        swc_ecma_ast::Program::Script(script) => {
-            for stmt in &script.body {
-                if let Err(e) = checker.analyze_stmt(stmt) {
-                    assert!(e.contains("Cannot borrow moved variable"));
-                    found_error = true;
-                    break;
-                }
-            }
-        }
-        _ => panic!("Expected Script"),
-    }
+#[test]
+fn test_move_tracking_full_flow() {
+    let source = r#"
+        let data = [1, 2, 3];
+        let other = data;
+        console.log(data.length);
+    "#;
 
-    checker.exit_scope();
-    assert!(
-        found_error,
-        "Expected borrow error for use of moved variable"
-    );
+    let err = check_src(source)
+        .0
+        .expect_err("Expected borrow error for use of moved variable");
+    assert!(err.contains("Cannot borrow moved variable"));
 }
 
 #[test]
 fn test_member_access_borrows_not_moves() {
-    use swc_common::{FileName, SourceMap, sync::Lrc};
-    use swc_ecma_parser::{Parser, StringInput, Syntax, lexer::Lexer};
-
     let source = r#"
         let arr = [{kind: "test"}];
         let c = arr[0];
@@ -911,29 +1417,375 @@ fn test_member_access_borrows_not_moves() {
         console.log(arr.length);
     "#;
 
-    let cm: Lrc<SourceMap> = Default::default();
-    let fm = cm.new_source_file(
-        FileName::Custom("test.ot".into()).into(),
-        source.to_string(),
+    check_src(source)
+        .0
+        .expect("Member access should borrow, not move");
+}
+
+#[test]
+fn test_reassignment_ends_previous_move() {
+    // A fresh value makes `parts` usable again
+    assert!(
+        check_src(
+            r#"
+            let parts = ["a", "b"];
+            let joined = parts;
+            parts = [];
+            console.log(parts.length);
+        "#
+        )
+        .0
+        .is_ok()
     );
-    let syntax = Syntax::Typescript(Default::default());
-    let lexer = Lexer::new(syntax, Default::default(), StringInput::from(&*fm), None);
-    let mut parser = Parser::new_from(lexer);
-    let program = parser.parse_program().unwrap();
+    // Compound assignment reads the moved value
+    assert!(
+        check_src(
+            r#"
+            let name = "a";
+            let taken = name;
+            name += "b";
+        "#
+        )
+        .0
+        .is_err()
+    );
+    // Reassigning from the moved binding itself is still a use
+    assert!(
+        check_src(
+            r#"
+            let items = [1];
+            let other = items;
+            items = items;
+        "#
+        )
+        .0
+        .is_err()
+    );
+}
 
-    let mut checker = BorrowChecker::new();
-    checker.enter_scope();
+#[test]
+fn test_moves_are_tracked_per_branch() {
+    // The else branch never sees the move in the then branch
+    assert!(
+        check_src(
+            r#"
+            let data = [1, 2];
+            let flag = true;
+            if (flag) { let taken = data; } else { console.log(data.length); }
+        "#
+        )
+        .0
+        .is_ok()
+    );
+    // After the `if`, the move may have happened
+    assert!(
+        check_src(
+            r#"
+            let data = [1, 2];
+            let flag = true;
+            if (flag) { let taken = data; }
+            console.log(data.length);
+        "#
+        )
+        .0
+        .is_err()
+    );
+    // Both paths give `data` a fresh value
+    assert!(
+        check_src(
+            r#"
+            let data = [1, 2];
+            let flag = true;
+            if (flag) { let taken = data; data = []; } else { let kept = data; data = [3]; }
+            console.log(data.length);
+        "#
+        )
+        .0
+        .is_ok()
+    );
+}
 
-    match &program {
-        swc_ecma_ast::Program::Script(script) => {
-            for stmt in &script.body {
-                checker
-                    .analyze_stmt(stmt)
-                    .expect("Member access should borrow, not move");
-            }
-        }
-        _ => panic!("Expected Script"),
-    }
+#[test]
+fn test_conditional_arms_move() {
+    let err = check_src(
+        r#"
+        let a = [1];
+        let x = [2];
+        let c = true;
+        let b = c ? a : x;
+        console.log(a.length);
+    "#,
+    )
+    .0
+    .unwrap_err();
+    assert_eq!(err, "BORROW ERROR: Cannot borrow moved variable 'a'");
+    // Either arm may have run
+    assert!(
+        check_src(
+            r#"
+            let a = [1];
+            let x = [2];
+            let c = true;
+            let b = c ? a : x;
+            console.log(x.length);
+        "#
+        )
+        .0
+        .is_err()
+    );
+    // Only the chosen arm's value is stored
+    assert!(
+        check_src(
+            r#"
+            let a = [1];
+            let c = true;
+            let b = c ? a.length : 0;
+            console.log(a.length);
+        "#
+        )
+        .0
+        .is_ok()
+    );
+}
+
+#[test]
+fn test_move_in_loop_reports_previous_iteration() {
+    use swc_common::{FileName, SourceMap, sync::Lrc};
+    use swc_ecma_parser::{Parser, StringInput, Syntax, lexer::Lexer};
+
+    let check = |source: &str| {
+        let cm: Lrc<SourceMap> = Default::default();
+        let fm = cm.new_source_file(
+            FileName::Custom("test.ot".into()).into(),
+            source.to_string(),
+        );
+        let syntax = Syntax::Typescript(Default::default());
+        let lexer = Lexer::new(syntax, Default::default(), StringInput::from(&*fm), None);
+        let body = match Parser::new_from(lexer).parse_program().unwrap() {
+            swc_ecma_ast::Program::Script(script) => script.body,
+            _ => panic!("Expected Script"),
+        };
+        let mut checker = BorrowChecker::new();
+        checker.set_source(fm.start_pos.0, source);
+        checker.enter_scope();
+        body.iter().try_for_each(|stmt| checker.analyze_stmt(stmt))
+    };
+
+    let err =
+        check("let buf = [1];\nlet i = 0;\nwhile (i < 3) {\n  let sink = buf;\n  i = i + 1;\n}\n")
+            .unwrap_err();
+    assert_eq!(
+        err,
+        "BORROW ERROR: Use of moved variable 'buf' (value moved in previous iteration of loop at 3:1)"
+    );
+
+    // Used before the move on the next trip
+    let err = check(
+        "let buf = [1];\nfor (let i = 0; i < 3; i = i + 1) {\n  let empty = buf == null;\n  let sink = buf;\n}\n",
+    )
+    .unwrap_err();
+    assert!(err.contains("previous iteration of loop at 2:1"), "{}", err);
+
+    // Loop-local bindings are new each iteration
+    assert!(
+        check("let rows = [[1], [2]];\nfor (const row of rows) {\n  let owned = row;\n}\n").is_ok()
+    );
+    // Reassigned before the next iteration
+    assert!(
+        check("let buf = [1];\nlet i = 0;\nwhile (i < 3) {\n  let sink = buf;\n  buf = [];\n  i = i + 1;\n}\n")
+            .is_ok()
+    );
+    // After the loop the move is an ordinary one
+    let err = check(
+        "let buf = [1];\nlet n = 0;\ndo {\n  n = n + 1;\n} while (n < 1);\nlet a = buf;\nlet b = buf;\n",
+    )
+    .unwrap_err();
+    assert_eq!(err, "BORROW ERROR: Use of moved variable 'buf'");
+}
+
+#[test]
+fn test_clone_is_independent_of_source() {
+    use swc_common::{FileName, SourceMap, sync::Lrc};
+    use swc_ecma_parser::{Parser, StringInput, Syntax, lexer::Lexer};
+
+    let check = |source: &str| {
+        let cm: Lrc<SourceMap> = Default::default();
+        let fm = cm.new_source_file(
+            FileName::Custom("test.ot".into()).into(),
+            source.to_string(),
+        );
+        let syntax = Syntax::Typescript(Default::default());
+        let lexer = Lexer::new(syntax, Default::default(), StringInput::from(&*fm), None);
+        let body = match Parser::new_from(lexer).parse_program().unwrap() {
+            swc_ecma_ast::Program::Script(script) => script.body,
+            _ => panic!("Expected Script"),
+        };
+        let mut checker = BorrowChecker::new();
+        checker.set_source(fm.start_pos.0, source);
+        checker.enter_scope();
+        body.iter().try_for_each(|stmt| checker.analyze_stmt(stmt))
+    };
+
+    // Cloning neither moves nor leaves a borrow that blocks the move
+    assert!(check("let buf = [1];\nlet copy = buf.clone();\nlet sink = buf;\n").is_ok());
+    assert!(check("let buf = [1];\nlet copy = structuredClone(buf);\nlet sink = buf;\n").is_ok());
+    assert!(
+        check(
+            "let buf = [1];\nlet copy = structuredClone(buf, { transfer: [] });\nlet sink = buf;\n"
+        )
+        .is_ok()
+    );
+    assert!(
+        check("let buf = [1];\nwhile (true) {\n  let sink = buf.clone();\n}\nlet last = buf;\n")
+            .is_ok()
+    );
+    // The copy is owned like any other value
+    assert_eq!(
+        check("let a = [1];\nlet b = a.clone();\nlet c = b;\nlet d = b;\n").unwrap_err(),
+        "BORROW ERROR: Use of moved variable 'b'"
+    );
+    // Too late once the value has moved
+    assert_eq!(
+        check("let buf = [1];\nlet sink = buf;\nlet copy = buf.clone();\n").unwrap_err(),
+        "BORROW ERROR: Use of moved variable 'buf'"
+    );
+}
+
+#[test]
+fn test_ignore_directives_exempt_lines() {
+    use swc_common::{FileName, SourceMap, sync::Lrc};
+    use swc_ecma_parser::{Parser, StringInput, Syntax, lexer::Lexer};
+
+    let check = |source: &str| {
+        let cm: Lrc<SourceMap> = Default::default();
+        let fm = cm.new_source_file(
+            FileName::Custom("test.ot".into()).into(),
+            source.to_string(),
+        );
+        let syntax = Syntax::Typescript(Default::default());
+        let lexer = Lexer::new(syntax, Default::default(), StringInput::from(&*fm), None);
+        let body = match Parser::new_from(lexer).parse_program().unwrap() {
+            swc_ecma_ast::Program::Script(script) => script.body,
+            _ => panic!("Expected Script"),
+        };
+        let mut checker = BorrowChecker::new();
+        checker.set_source(fm.start_pos.0, source);
+        checker.enter_scope();
+        let result = body.iter().try_for_each(|stmt| checker.analyze_stmt(stmt));
+        result.map(|_| checker.has_errors())
+    };
+
+    // On its own line the directive covers the next line of code
+    assert_eq!(
+        check("let buf = [1];\nlet a = buf;\n// @script-ignore borrow\n\nlet b = buf;\n"),
+        Ok(false)
+    );
+    // Trailing code, it covers its own line
+    assert_eq!(
+        check("let buf = [1];\nlet a = buf;\nlet b = buf; // @script-ignore borrow\n"),
+        Ok(false)
+    );
+    // Only that line: the move is still tracked
+    assert_eq!(
+        check("let buf = [1];\n// @script-ignore borrow\nlet a = buf;\nlet b = buf;\n")
+            .unwrap_err(),
+        "BORROW ERROR: Use of moved variable 'buf'"
+    );
+    assert_eq!(
+        check("// @script-ignore-file borrow\nlet buf = [1];\nlet a = buf;\nlet b = buf;\n"),
+        Ok(false)
+    );
+}
+
+#[test]
+fn test_closure_capture_modes() {
+    use swc_common::{FileName, SourceMap, sync::Lrc};
+    use swc_ecma_parser::{Parser, StringInput, Syntax, lexer::Lexer};
+
+    let check = |source: &str| {
+        let cm: Lrc<SourceMap> = Default::default();
+        let fm = cm.new_source_file(
+            FileName::Custom("test.ot".into()).into(),
+            source.to_string(),
+        );
+        let syntax = Syntax::Typescript(Default::default());
+        let lexer = Lexer::new(syntax, Default::default(), StringInput::from(&*fm), None);
+        let body = match Parser::new_from(lexer).parse_program().unwrap() {
+            swc_ecma_ast::Program::Script(script) => script.body,
+            _ => panic!("Expected Script"),
+        };
+        let mut checker = BorrowChecker::new();
+        checker.enter_scope();
+        let result = body.iter().try_for_each(|stmt| checker.analyze_stmt(stmt));
+        (result, checker)
+    };
 
-    checker.exit_scope();
+    // Reading closures borrow, so the variable stays usable
+    let (result, checker) = check(
+        "let data = { v: 1 };\nlet f = () => data.v;\nlet g = () => data.v;\nlet x = data.v;\n",
+    );
+    assert!(result.is_ok());
+    assert_eq!(
+        checker.lookup("data").unwrap().captured_by,
+        Some(CaptureMode::Read)
+    );
+    // ... but it can't be moved out from under them
+    assert_eq!(
+        check("let data = { v: 1 };\nlet f = () => data.v;\nlet sink = data;\n")
+            .0
+            .unwrap_err(),
+        "BORROW ERROR: Cannot move 'data' while a closure captures it by reference"
+    );
+
+    // Assignments through the capture make it a mutable one
+    let (result, checker) =
+        check("let data = { v: 1 };\nlet f = () => { data.v = 2; };\nlet g = () => data.v;\n");
+    assert!(result.is_ok());
+    assert_eq!(
+        checker.lookup("data").unwrap().captured_by,
+        Some(CaptureMode::Mutate)
+    );
+
+    // move(...) takes ownership
+    assert_eq!(
+        check("let data = { v: 1 };\nlet f = move(() => data.v);\nlet x = data.v;\n")
+            .0
+            .unwrap_err(),
+        "BORROW ERROR: Cannot borrow 'data' - it was moved into a closure!"
+    );
+    assert_eq!(
+        check("let data = { v: 1 };\nlet f = () => data.v;\nlet g = move(() => data);\n")
+            .0
+            .unwrap_err(),
+        "BORROW ERROR: Cannot move 'data' into a closure while another closure captures it by reference"
+    );
+}
+
+#[test]
+fn test_loop_that_always_exits_runs_once() {
+    assert!(
+        check_src("let a = [1];\nwhile (true) {\n  let b = a;\n  break;\n}\n")
+            .0
+            .is_ok()
+    );
+    assert!(
+        check_src(
+            "let a = [1];\nlet n = 0;\nfor (;;) {\n  if (n > 1) { let b = a; return; } else { break; }\n}\n"
+        )
+        .0
+        .is_ok()
+    );
+    // Still a move once the loop is done
+    let err = check_src("let a = [1];\nwhile (true) {\n  let b = a;\n  break;\n}\nlet c = a;\n")
+        .0
+        .unwrap_err();
+    assert_eq!(err, "BORROW ERROR: Use of moved variable 'a'");
+    // A body that can fall through still loops
+    assert!(
+        check_src(
+            "let a = [1];\nlet n = 0;\nwhile (n < 2) {\n  let b = a;\n  if (n > 0) { break; }\n  n = n + 1;\n}\n"
+        )
+        .0
+        .is_err()
+    );
 }
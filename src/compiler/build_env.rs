@@ -0,0 +1,199 @@
+//! Build-time environment substitution
+//!
+//! During `oitec build`, `import.meta.env.X` and `process.env.X` are
+//! replaced by the values the build was given, so they cost nothing at run
+//! time:
+//!
+//! - `import.meta.env.MODE` is the build mode (`development`, or
+//!   `production` with `--release`/`--dist`); `DEV` and `PROD` are booleans
+//!   derived from it, and `process.env.NODE_ENV` is the mode too
+//! - other names come from the `[env]` table of `script.toml` and from
+//!   `--define NAME=VALUE`, which wins
+//! - an unknown `import.meta.env.X` is `undefined`; an unknown
+//!   `process.env.X` is left for the running process to answer
+//!
+//! Once the variables are constants, an `if` or `?:` whose condition is a
+//! constant (`process.env.NODE_ENV !== "production"`, `!import.meta.env.DEV`)
+//! only compiles the branch that runs, so development-only code never
+//! reaches the binary.
+
+use super::Codegen;
+use crate::vm::value::JsValue;
+use std::collections::HashMap;
+use swc_ecma_ast::*;
+
+/// Values substituted into one build
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BuildEnv {
+    pub mode: String,
+    /// Named variables: the manifest's `[env]`, then `--define`s
+    pub vars: HashMap<String, String>,
+}
+
+impl BuildEnv {
+    pub fn new(mode: &str) -> Self {
+        Self {
+            mode: mode.to_string(),
+            vars: HashMap::new(),
+        }
+    }
+
+    /// Parse a `--define NAME=VALUE` argument into `vars`
+    pub fn define(&mut self, arg: &str) -> Result<(), String> {
+        match arg.split_once('=') {
+            Some((name, value)) if !name.is_empty() => {
+                self.vars.insert(name.to_string(), value.to_string());
+                Ok(())
+            }
+            _ => Err(format!("--define expects NAME=VALUE, got '{}'", arg)),
+        }
+    }
+
+    /// `import.meta.env.<name>`
+    fn import_meta(&self, name: &str) -> JsValue {
+        match name {
+            "MODE" => JsValue::String(self.mode.clone()),
+            "DEV" => JsValue::Boolean(self.mode != "production"),
+            "PROD" => JsValue::Boolean(self.mode == "production"),
+            _ => self
+                .vars
+                .get(name)
+                .map_or(JsValue::Undefined, |v| JsValue::String(v.clone())),
+        }
+    }
+
+    /// `process.env.<name>`, if the build knows it
+    fn process_env(&self, name: &str) -> Option<JsValue> {
+        match self.vars.get(name) {
+            Some(value) => Some(JsValue::String(value.clone())),
+            None if name == "NODE_ENV" => Some(JsValue::String(self.mode.clone())),
+            None => None,
+        }
+    }
+
+    /// Value of an `import.meta.env.X` or `process.env.X` expression
+    pub(super) fn lookup(&self, member: &MemberExpr) -> Option<JsValue> {
+        let name = match &member.prop {
+            MemberProp::Ident(id) => id.sym.to_string(),
+            MemberProp::Computed(ComputedPropName { expr, .. }) => match expr.as_ref() {
+                Expr::Lit(Lit::Str(s)) => s.value.to_string_lossy().into_owned(),
+                _ => return None,
+            },
+            _ => return None,
+        };
+        let Expr::Member(env) = member.obj.as_ref() else {
+            return None;
+        };
+        if !matches!(&env.prop, MemberProp::Ident(id) if &*id.sym == "env") {
+            return None;
+        }
+        match env.obj.as_ref() {
+            Expr::MetaProp(MetaPropExpr {
+                kind: MetaPropKind::ImportMeta,
+                ..
+            }) => Some(self.import_meta(&name)),
+            Expr::Ident(id) if &*id.sym == "process" => self.process_env(&name),
+            _ => None,
+        }
+    }
+}
+
+/// Loose truthiness of a constant
+fn truthy(value: &JsValue) -> bool {
+    match value {
+        JsValue::Boolean(b) => *b,
+        JsValue::Number(n) => *n != 0.0 && !n.is_nan(),
+        JsValue::String(s) => !s.is_empty(),
+        JsValue::Null | JsValue::Undefined => false,
+        _ => true,
+    }
+}
+
+/// `===` between two constants
+fn strict_equals(a: &JsValue, b: &JsValue) -> bool {
+    match (a, b) {
+        (JsValue::String(a), JsValue::String(b)) => a == b,
+        (JsValue::Number(a), JsValue::Number(b)) => a == b,
+        (JsValue::Boolean(a), JsValue::Boolean(b)) => a == b,
+        (JsValue::Null, JsValue::Null) | (JsValue::Undefined, JsValue::Undefined) => true,
+        _ => false,
+    }
+}
+
+impl Codegen {
+    /// Substituted value of a build variable reference
+    pub(super) fn build_env_value(&self, member: &MemberExpr) -> Option<JsValue> {
+        self.build_env.as_ref()?.lookup(member)
+    }
+
+    /// Value of `expr` if it is a constant once build variables are
+    /// substituted. Only literals, build variables and the operators that
+    /// compare or combine them are folded.
+    fn static_value(&self, expr: &Expr) -> Option<JsValue> {
+        match expr {
+            Expr::Paren(paren) => self.static_value(&paren.expr),
+            Expr::Member(member) => self.build_env_value(member),
+            Expr::Lit(Lit::Str(s)) => Some(JsValue::String(s.value.to_string_lossy().into_owned())),
+            Expr::Lit(Lit::Num(n)) => Some(JsValue::Number(n.value)),
+            Expr::Lit(Lit::Bool(b)) => Some(JsValue::Boolean(b.value)),
+            Expr::Lit(Lit::Null(_)) => Some(JsValue::Null),
+            Expr::Ident(id) if &*id.sym == "undefined" => Some(JsValue::Undefined),
+            Expr::Unary(unary) if unary.op == UnaryOp::Bang => {
+                let value = self.static_value(&unary.arg)?;
+                Some(JsValue::Boolean(!truthy(&value)))
+            }
+            Expr::Bin(bin) => {
+                let left = self.static_value(&bin.left)?;
+                match bin.op {
+                    BinaryOp::LogicalAnd if !truthy(&left) => return Some(left),
+                    BinaryOp::LogicalOr if truthy(&left) => return Some(left),
+                    BinaryOp::LogicalAnd | BinaryOp::LogicalOr => {
+                        return self.static_value(&bin.right);
+                    }
+                    _ => {}
+                }
+                let right = self.static_value(&bin.right)?;
+                let equal = strict_equals(&left, &right);
+                // `==` only agrees with `===` when no conversion happens
+                let same_type = std::mem::discriminant(&left) == std::mem::discriminant(&right);
+                match bin.op {
+                    BinaryOp::EqEqEq => Some(JsValue::Boolean(equal)),
+                    BinaryOp::NotEqEq => Some(JsValue::Boolean(!equal)),
+                    BinaryOp::EqEq if same_type => Some(JsValue::Boolean(equal)),
+                    BinaryOp::NotEq if same_type => Some(JsValue::Boolean(!equal)),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether a branch condition is known at compile time. Only builds
+    /// with a `BuildEnv` fold conditions, so `run` compiles every branch.
+    pub(super) fn static_condition(&self, test: &Expr) -> Option<bool> {
+        self.build_env.as_ref()?;
+        self.static_value(test).map(|value| truthy(&value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_define_parses_name_and_value() {
+        let mut env = BuildEnv::new("production");
+        env.define("API_URL=https://example.com/?a=b").unwrap();
+        assert_eq!(env.vars["API_URL"], "https://example.com/?a=b");
+        assert!(env.define("=x").is_err());
+        assert!(env.define("FLAG").is_err());
+
+        assert_eq!(
+            env.process_env("NODE_ENV"),
+            Some(JsValue::String("production".into()))
+        );
+        assert_eq!(env.process_env("HOME"), None);
+        assert_eq!(env.import_meta("DEV"), JsValue::Boolean(false));
+        assert_eq!(env.import_meta("MISSING"), JsValue::Undefined);
+    }
+}
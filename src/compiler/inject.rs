@@ -0,0 +1,244 @@
+//! Compile-time dependency injection
+//!
+//! `@Injectable()` marks a class whose constructor parameters the compiler
+//! fills in. Each parameter is resolved from its type annotation
+//! (`constructor(private repo: UserRepo)`), or from `@Inject(UserRepo)` when
+//! the annotation names an interface. `inject(UserService)` compiles to the
+//! code that builds the whole graph, in effect
+//! `new UserService(new UserRepo())`, so nothing is looked up at run time.
+//!
+//! Instances are shared: a class is constructed on its first injection and
+//! cached on the class. `@Injectable({ scope: "transient" })` builds a new
+//! instance every time. Unknown dependencies and cycles are compile errors.
+
+use super::Codegen;
+use crate::vm::opcodes::OpCode;
+use crate::vm::value::JsValue;
+use std::collections::HashMap;
+use swc_ecma_ast::*;
+
+/// Property of the class that caches its shared instance
+const INSTANCE_KEY: &str = "__di_instance__";
+
+#[derive(Clone, PartialEq)]
+pub(super) struct Injectable {
+    /// (parameter name, class to inject) for each constructor parameter
+    deps: Vec<(String, Option<String>)>,
+    /// Build a new instance for every injection
+    transient: bool,
+}
+
+/// `Foo` and its arguments for `@Foo(...)`, or `Foo` alone for `@Foo`
+fn decorator_call(expr: &Expr) -> Option<(&str, &[ExprOrSpread])> {
+    match expr {
+        Expr::Ident(id) => Some((&*id.sym, &[])),
+        Expr::Call(call) => match &call.callee {
+            Callee::Expr(callee) => match callee.as_ref() {
+                Expr::Ident(id) => Some((&*id.sym, call.args.as_slice())),
+                _ => None,
+            },
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Whether `decorator` is the compiler's `@Injectable`, which is never
+/// called at run time
+pub(super) fn is_injectable_decorator(decorator: &Decorator) -> bool {
+    decorator_call(&decorator.expr).is_some_and(|(name, _)| name == "Injectable")
+}
+
+/// `{ scope: "transient" }`
+fn is_transient(options: &Expr) -> bool {
+    let Expr::Object(obj) = options else {
+        return false;
+    };
+    obj.props.iter().any(|prop| {
+        let PropOrSpread::Prop(prop) = prop else {
+            return false;
+        };
+        let Prop::KeyValue(kv) = prop.as_ref() else {
+            return false;
+        };
+        let is_scope = matches!(&kv.key, PropName::Ident(key) if &*key.sym == "scope");
+        is_scope
+            && matches!(kv.value.as_ref(),
+                Expr::Lit(Lit::Str(s)) if s.value.to_string_lossy() == "transient")
+    })
+}
+
+/// Class to inject for a constructor parameter: the `@Inject(Class)`
+/// argument, else the type annotation
+fn param_dependency(decorators: &[Decorator], binding: Option<&BindingIdent>) -> Option<String> {
+    let explicit = decorators.iter().find_map(|d| {
+        let (name, args) = decorator_call(&d.expr)?;
+        match (name, args.first().map(|arg| arg.expr.as_ref())) {
+            ("Inject", Some(Expr::Ident(id))) => Some(id.sym.to_string()),
+            _ => None,
+        }
+    });
+    explicit.or_else(|| {
+        let type_ann = binding?.type_ann.as_ref()?;
+        match type_ann.type_ann.as_ref() {
+            TsType::TsTypeRef(TsTypeRef {
+                type_name: TsEntityName::Ident(id),
+                ..
+            }) => Some(id.sym.to_string()),
+            _ => None,
+        }
+    })
+}
+
+/// Constructor dependencies of `class`. A class without a constructor takes
+/// those of its injectable superclass.
+fn constructor_deps(
+    class: &Class,
+    injectables: &HashMap<String, Injectable>,
+) -> Vec<(String, Option<String>)> {
+    let ctor = class.body.iter().find_map(|member| match member {
+        ClassMember::Constructor(ctor) => Some(ctor),
+        _ => None,
+    });
+    let Some(ctor) = ctor else {
+        return match class.super_class.as_deref() {
+            Some(Expr::Ident(parent)) => injectables
+                .get(&*parent.sym)
+                .map(|parent| parent.deps.clone())
+                .unwrap_or_default(),
+            _ => Vec::new(),
+        };
+    };
+    ctor.params
+        .iter()
+        .map(|param| {
+            let (decorators, binding) = match param {
+                ParamOrTsParamProp::Param(p) => (
+                    p.decorators.as_slice(),
+                    match &p.pat {
+                        Pat::Ident(id) => Some(id),
+                        _ => None,
+                    },
+                ),
+                ParamOrTsParamProp::TsParamProp(p) => (
+                    p.decorators.as_slice(),
+                    match &p.param {
+                        TsParamPropParam::Ident(id) => Some(id),
+                        _ => None,
+                    },
+                ),
+            };
+            let name = binding.map_or_else(|| "_".to_string(), |id| id.id.sym.to_string());
+            (name, param_dependency(decorators, binding))
+        })
+        .collect()
+}
+
+impl Codegen {
+    /// Remember `class` if it is `@Injectable`, so later `inject` calls can
+    /// build it
+    pub(super) fn register_injectable(&mut self, class: &Class, name: Option<&str>) {
+        let decorator = class.decorators.iter().find(|d| is_injectable_decorator(d));
+        let Some(decorator) = decorator else {
+            return;
+        };
+        let Some(name) = name else {
+            self.errors
+                .push("@Injectable() can only be used on a named class".to_string());
+            return;
+        };
+        let transient = decorator_call(&decorator.expr)
+            .and_then(|(_, args)| args.first())
+            .is_some_and(|options| is_transient(&options.expr));
+        let deps = constructor_deps(class, &self.injectables);
+        self.injectables
+            .insert(name.to_string(), Injectable { deps, transient });
+    }
+
+    /// `inject(Class)`: leaves an instance of `Class`, with its dependencies
+    /// built first
+    pub(super) fn gen_inject(&mut self, args: &[ExprOrSpread]) {
+        let target = match args {
+            [arg] if arg.spread.is_none() => match arg.expr.as_ref() {
+                Expr::Ident(id) => Some(id.sym.to_string()),
+                _ => None,
+            },
+            _ => None,
+        };
+        let result = match target {
+            Some(target) => self.gen_resolve(&target, &mut Vec::new()),
+            None => Err("inject() takes a class name, e.g. inject(UserService)".to_string()),
+        };
+        if let Err(e) = result {
+            self.errors.push(e);
+            self.instructions.push(OpCode::Push(JsValue::Undefined));
+        }
+    }
+
+    /// Emit the code building `class`. `path` holds the classes being built
+    /// around it, to report cycles.
+    fn gen_resolve(&mut self, class: &str, path: &mut Vec<String>) -> Result<(), String> {
+        if path.iter().any(|c| c == class) {
+            path.push(class.to_string());
+            return Err(format!("dependency cycle: {}", path.join(" -> ")));
+        }
+        let Some(injectable) = self.injectables.get(class).cloned() else {
+            return Err(match path.last() {
+                Some(parent) => format!(
+                    "cannot inject {} into {}: it is not an @Injectable() class",
+                    class, parent
+                ),
+                None => format!("cannot inject {}: it is not an @Injectable() class", class),
+            });
+        };
+        if let Some((param, _)) = injectable.deps.iter().find(|(_, dep)| dep.is_none()) {
+            return Err(format!(
+                "cannot inject parameter '{}' of {}: give it a class type or @Inject(Class)",
+                param, class
+            ));
+        }
+
+        // Shared: reuse the cached instance when there is one
+        // Stack: [] -> [cached or undefined]
+        let mut cached_jump = None;
+        if !injectable.transient {
+            self.instructions.push(OpCode::Load(class.to_string()));
+            self.instructions
+                .push(OpCode::GetProp(INSTANCE_KEY.to_string()));
+            self.instructions.push(OpCode::Dup);
+            let build_jump = self.instructions.len();
+            self.instructions.push(OpCode::JumpIfFalse(0));
+            cached_jump = Some(self.instructions.len());
+            self.instructions.push(OpCode::Jump(0));
+            let build = self.instructions.len();
+            if let OpCode::JumpIfFalse(ref mut addr) = self.instructions[build_jump] {
+                *addr = build;
+            }
+            self.instructions.push(OpCode::Pop);
+        }
+
+        // Stack: [dep1, dep2, ..., class] -> [instance]
+        path.push(class.to_string());
+        for (_, dep) in &injectable.deps {
+            self.gen_resolve(dep.as_deref().unwrap_or_default(), path)?;
+        }
+        path.pop();
+        self.instructions.push(OpCode::Load(class.to_string()));
+        self.instructions
+            .push(OpCode::Construct(injectable.deps.len()));
+
+        if let Some(cached_jump) = cached_jump {
+            // Stack: [instance, instance, class] -> [instance]
+            self.instructions.push(OpCode::Dup);
+            self.instructions.push(OpCode::Load(class.to_string()));
+            self.instructions.push(OpCode::Swap);
+            self.instructions
+                .push(OpCode::SetProp(INSTANCE_KEY.to_string()));
+            let end = self.instructions.len();
+            if let OpCode::Jump(ref mut addr) = self.instructions[cached_jump] {
+                *addr = end;
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,14 +1,30 @@
-use crate::vm::opcodes::OpCode;
+use crate::vm::opcodes::{OpCode, pinned_global_slot};
 use std::collections::HashSet;
 use swc_ecma_ast::*;
 pub mod borrow_ck;
-use crate::compiler::borrow_ck::BorrowChecker;
+pub mod build_env;
+mod inject;
+pub mod reuse;
+pub mod unions;
+use crate::compiler::borrow_ck::{BorrowCheckLevel, BorrowChecker};
+use crate::vm::bytecode_cache::BytecodeCache;
 use crate::vm::value::JsValue;
-use swc_common::{FileName, SourceMap, sync::Lrc};
+use swc_common::{BytePos, FileName, SourceMap, Spanned, sync::Lrc};
 use swc_ecma_parser::{Parser, StringInput, Syntax, lexer::Lexer};
 
 pub struct Compiler {
     pub borrow_checker: BorrowChecker,
+    /// (instruction index, 1-based source line) of each statement in the
+    /// last compiled source, ordered by instruction index
+    pub line_table: Vec<(usize, u32)>,
+    pub borrow_check: BorrowCheckLevel,
+    /// Ownership errors downgraded by `BorrowCheckLevel::Warn` in the last
+    /// compile
+    pub warnings: Vec<String>,
+    /// Values substituted for `import.meta.env`/`process.env` by `build`
+    pub build_env: Option<build_env::BuildEnv>,
+    /// Where compiles are saved and looked up, if anywhere
+    pub bytecode_cache: Option<BytecodeCache>,
 }
 
 impl Default for Compiler {
@@ -21,6 +37,11 @@ impl Compiler {
     pub fn new() -> Self {
         Self {
             borrow_checker: BorrowChecker::new(),
+            line_table: Vec::new(),
+            borrow_check: BorrowCheckLevel::default(),
+            warnings: Vec::new(),
+            build_env: None,
+            bytecode_cache: None,
         }
     }
 
@@ -33,6 +54,37 @@ impl Compiler {
         source: &str,
         syntax_override: Option<Syntax>,
     ) -> Result<Vec<OpCode>, String> {
+        // `build` substitutes values the source doesn't show, so its
+        // compiles are never cached
+        let cache = self
+            .bytecode_cache
+            .clone()
+            .filter(|_| self.build_env.is_none())
+            .map(|cache| {
+                let key = BytecodeCache::key(source, &syntax_override, self.borrow_check);
+                (cache, key)
+            });
+        if let Some((cache, key)) = &cache
+            && let Some(cached) = cache.load(key)
+        {
+            self.line_table = cached.line_table;
+            self.warnings = cached.warnings;
+            return Ok(cached.bytecode);
+        }
+        let bytecode = self.compile_source(source, syntax_override)?;
+        if let Some((cache, key)) = &cache {
+            cache.store(key, &bytecode, &self.line_table, &self.warnings);
+        }
+        Ok(bytecode)
+    }
+
+    fn compile_source(
+        &mut self,
+        source: &str,
+        syntax_override: Option<Syntax>,
+    ) -> Result<Vec<OpCode>, String> {
+        let (source, unions) = unions::desugar(source);
+        let source = source.as_str();
         let cm: Lrc<SourceMap> = Default::default();
         let fm = cm.new_source_file(
             FileName::Custom("main.ot".into()).into(),
@@ -51,37 +103,39 @@ impl Compiler {
             .parse_program()
             .map_err(|e| format!("Parsing error: {:?}", e))?;
 
-        self.borrow_checker.enter_scope(); // Script vars at depth 1, globals at 0
-
-        let result = match &program {
-            Program::Module(module) => {
-                let mut result = Ok(());
-                for item in &module.body {
-                    if let ModuleItem::Stmt(stmt) = item
-                        && let Err(e) = self.borrow_checker.analyze_stmt(stmt)
-                    {
-                        result = Err(e);
-                        break;
-                    }
-                }
-                result
-            }
-            Program::Script(script) => {
-                let mut result = Ok(());
-                for stm in &script.body {
-                    if let Err(e) = self.borrow_checker.analyze_stmt(stm) {
+        self.warnings.clear();
+        if self.borrow_check != BorrowCheckLevel::Off {
+            self.borrow_checker.set_source(fm.start_pos.0, source);
+            self.borrow_checker.enter_scope(); // Script vars at depth 1, globals at 0
+            let stmts: Vec<&Stmt> = match &program {
+                Program::Module(module) => module
+                    .body
+                    .iter()
+                    .filter_map(|item| match item {
+                        ModuleItem::Stmt(stmt) => Some(stmt),
+                        _ => None,
+                    })
+                    .collect(),
+                Program::Script(script) => script.body.iter().collect(),
+            };
+            let mut result = Ok(());
+            for stmt in stmts {
+                match self.borrow_checker.analyze_stmt(stmt) {
+                    Ok(()) => {}
+                    Err(e) if self.borrow_check == BorrowCheckLevel::Warn => self.warnings.push(e),
+                    Err(e) => {
                         result = Err(e);
                         break;
                     }
                 }
-                result
             }
-        };
-
-        self.borrow_checker.exit_scope();
-        result?;
+            self.borrow_checker.exit_scope();
+            result?;
+        }
 
         let mut codegen = Codegen::new();
+        codegen.unions = unions;
+        codegen.build_env = self.build_env.clone();
         match &program {
             Program::Module(module) => {
                 codegen.generate(module);
@@ -90,33 +144,147 @@ impl Compiler {
                 codegen.generate_script(script);
             }
         }
+        if !codegen.errors.is_empty() {
+            return Err(codegen.errors.join("\n"));
+        }
+        // Callers print these with their own "Warning: " prefix
+        self.warnings.extend(
+            codegen
+                .warnings
+                .iter()
+                .map(|w| w.strip_prefix("Warning: ").unwrap_or(w).to_string()),
+        );
 
+        self.line_table = codegen
+            .statement_starts
+            .iter()
+            .map(|&(ip, pos)| (ip, cm.lookup_char_pos(BytePos(pos)).line as u32))
+            .collect();
+        self.line_table.sort_unstable();
+        self.line_table.dedup();
+
+        pin_globals(&mut codegen.instructions);
         Ok(codegen.instructions)
     }
 }
 
+/// Resolve loads of stdlib globals (`console`, `JSON`, ...) to their pinned
+/// slots. A name the chunk binds or assigns anywhere keeps the by-name
+/// lookup, so user code shadowing a global still sees its own binding.
+fn pin_globals(instructions: &mut [OpCode]) {
+    let bound: HashSet<String> = instructions
+        .iter()
+        .filter_map(|op| match op {
+            OpCode::Let(name) | OpCode::Store(name) | OpCode::Drop(name) => Some(name.clone()),
+            _ => None,
+        })
+        .collect();
+    for op in instructions.iter_mut() {
+        if let OpCode::Load(name) = op
+            && !bound.contains(name)
+            && let Some(slot) = pinned_global_slot(name)
+        {
+            *op = OpCode::LoadGlobal(slot);
+        }
+    }
+}
+
 struct LoopContext {
     start_addr: usize,
     break_jumps: Vec<usize>,
     continue_jumps: Vec<usize>,
 }
 
+/// Where an assignment or update writes. A member's object and computed key
+/// are evaluated once into hidden locals, so the place can be read and then
+/// written back.
+enum Place {
+    Var(String),
+    Member { obj: String, prop: PlaceProp },
+}
+
+enum PlaceProp {
+    Named(String),
+    /// Hidden local holding the key
+    Computed(String),
+    /// `#x`
+    Private(String),
+}
+
+/// How many arguments a function declaration takes
+#[derive(Clone, PartialEq)]
+struct Arity {
+    required: usize,
+    /// `None` with a rest parameter
+    max: Option<usize>,
+    /// Index in `binding_scopes` of the scope that declared it
+    depth: usize,
+}
+
+impl Arity {
+    fn of(params: &[swc_ecma_ast::Param], depth: usize) -> Self {
+        // Parameters after the first optional one are optional too
+        let required = params
+            .iter()
+            .take_while(|param| match &param.pat {
+                Pat::Ident(id) => !id.id.optional,
+                Pat::Assign(_) | Pat::Rest(_) => false,
+                _ => true,
+            })
+            .count();
+        let max = if params.iter().any(|param| matches!(param.pat, Pat::Rest(_))) {
+            None
+        } else {
+            Some(params.len())
+        };
+        Self {
+            required,
+            max,
+            depth,
+        }
+    }
+}
+
 pub struct Codegen {
     pub instructions: Vec<OpCode>,
     scope_stack: Vec<Vec<String>>,
     in_function: bool,
     in_async_function: bool,
+    /// Inside an arrow that captured the enclosing `this` and `new.target`
+    /// as `__this__` and `__new_target__`
+    lexical_this: bool,
     /// Tracks which variables are available in the current scope chain.
     /// Used to detect "upvars" (variables captured from outer scopes).
     outer_scope_vars: HashSet<String>,
+    /// Names bound in each enclosing scope, innermost last. The first is
+    /// the top level: its bindings live in the global frame or module
+    /// environment, which every call can reach, so closures share them
+    /// instead of copying them.
+    binding_scopes: Vec<HashSet<String>>,
+    /// Parameter counts of the function declarations in scope, for the
+    /// arity warning at direct calls
+    function_arities: std::collections::HashMap<String, Arity>,
     /// Stack of loop contexts for nested loops (break/continue support)
     loop_stack: Vec<LoopContext>,
     /// Maps private field names to their indices for the current class
     private_field_indices: std::collections::HashMap<String, usize>,
-    /// Maps private method names to their indices for the current class
+    /// Maps private methods (`#m`) and accessors (`getter:#x`, `setter:#x`)
+    /// to their indices for the current class
     private_method_indices: std::collections::HashMap<String, usize>,
     /// Warnings collected during compilation
     pub warnings: Vec<String>,
+    /// Errors that reject the program, e.g. an unresolvable `inject(...)`
+    pub errors: Vec<String>,
+    /// (first instruction, span start) of every statement that emitted code
+    pub statement_starts: Vec<(usize, u32)>,
+    /// Folded members of each const enum, inlined at every use
+    const_enums: std::collections::HashMap<String, std::collections::HashMap<String, JsValue>>,
+    /// `@Injectable()` classes seen so far, by name
+    injectables: std::collections::HashMap<String, inject::Injectable>,
+    /// Tagged unions declared in the source (see unions.rs)
+    pub unions: Vec<unions::Union>,
+    /// Build variables to substitute (see build_env.rs)
+    pub build_env: Option<build_env::BuildEnv>,
 }
 
 impl Default for Codegen {
@@ -132,11 +300,20 @@ impl Codegen {
             scope_stack: vec![Vec::new()],
             in_function: false,
             in_async_function: false,
+            lexical_this: false,
             outer_scope_vars: HashSet::new(),
+            binding_scopes: vec![HashSet::new()],
+            function_arities: std::collections::HashMap::new(),
             loop_stack: Vec::new(),
             private_field_indices: std::collections::HashMap::new(),
             private_method_indices: std::collections::HashMap::new(),
             warnings: Vec::new(),
+            errors: Vec::new(),
+            statement_starts: Vec::new(),
+            const_enums: std::collections::HashMap::new(),
+            injectables: std::collections::HashMap::new(),
+            unions: Vec::new(),
+            build_env: None,
         }
     }
 
@@ -185,6 +362,100 @@ impl Codegen {
         }
     }
 
+    /// Enter a function body or block for binding resolution
+    fn push_binding_scope(&mut self) {
+        self.binding_scopes.push(HashSet::new());
+    }
+
+    /// Leave the innermost binding scope, and forget the arities of the
+    /// functions it declared
+    fn pop_binding_scope(&mut self) {
+        self.binding_scopes.pop();
+        let depth = self.binding_scopes.len();
+        self.function_arities.retain(|_, arity| arity.depth < depth);
+    }
+
+    /// Enter a block scope
+    fn enter_scope(&mut self) {
+        self.scope_stack.push(Vec::new());
+        self.push_binding_scope();
+    }
+
+    /// Leave a block scope, returning the locals to drop
+    fn exit_scope(&mut self) -> Option<Vec<String>> {
+        self.pop_binding_scope();
+        self.scope_stack.pop()
+    }
+
+    /// Record a new binding in the current scope
+    fn declare(&mut self, name: String) {
+        if let Some(scope) = self.binding_scopes.last_mut() {
+            scope.insert(name.clone());
+        }
+        // A binding in the scope of a known function replaces it
+        let depth = self.binding_scopes.len() - 1;
+        if self
+            .function_arities
+            .get(&name)
+            .is_some_and(|arity| arity.depth == depth)
+        {
+            self.function_arities.remove(&name);
+        }
+        self.outer_scope_vars.insert(name);
+    }
+
+    /// Warn when a direct call to a function declaration in scope passes
+    /// fewer arguments than it requires or more than it takes. The call
+    /// still runs: missing parameters are `undefined` and extra arguments
+    /// are dropped, as in JavaScript.
+    fn check_arity(&mut self, callee: &Expr, args: &[ExprOrSpread]) {
+        let Expr::Ident(id) = callee else {
+            return;
+        };
+        let name = id.sym.as_str();
+        let Some(arity) = self.function_arities.get(name) else {
+            return;
+        };
+        // Only while the declaration is the nearest binding of the name
+        let depth = self.binding_scopes.iter().rposition(|s| s.contains(name));
+        if depth != Some(arity.depth) || args.iter().any(|arg| arg.spread.is_some()) {
+            return;
+        }
+        let count = args.len();
+        let (bound, limit) = match arity.max {
+            _ if count < arity.required => {
+                let exact = arity.max == Some(arity.required);
+                (if exact { "" } else { "at least " }, arity.required)
+            }
+            Some(max) if count > max => (
+                if max == arity.required {
+                    ""
+                } else {
+                    "at most "
+                },
+                max,
+            ),
+            _ => return,
+        };
+        self.warnings.push(format!(
+            "Warning: '{}' takes {}{} argument{} but is called with {}",
+            name,
+            bound,
+            limit,
+            if limit == 1 { "" } else { "s" },
+            count
+        ));
+    }
+
+    /// Whether closures reach `name` where it lives rather than copying it:
+    /// true when the nearest binding of `name` is a top-level one
+    fn is_shared(&self, name: &str) -> bool {
+        self.binding_scopes
+            .iter()
+            .rposition(|scope| scope.contains(name))
+            == Some(0)
+    }
+
     fn collect_free_vars_in_expr(
         &self,
         expr: &Expr,
@@ -195,7 +466,10 @@ impl Codegen {
             Expr::Ident(id) => {
                 let name = id.sym.to_string();
                 // If not a local/param AND exists in outer scope, it's a captured var
-                if !local_vars.contains(&name) && self.outer_scope_vars.contains(&name) {
+                if !local_vars.contains(&name)
+                    && self.outer_scope_vars.contains(&name)
+                    && !self.is_shared(&name)
+                {
                     free_vars.insert(name);
                 }
             }
@@ -260,62 +534,82 @@ impl Codegen {
         free_vars
     }
 
+    fn gen_this(&mut self) {
+        if self.lexical_this {
+            self.instructions.push(OpCode::Load("__this__".to_string()));
+        } else {
+            self.instructions.push(OpCode::LoadThis);
+        }
+    }
+
+    /// new.target - the constructor that was called with new
+    fn gen_new_target(&mut self) {
+        if self.lexical_this {
+            self.instructions
+                .push(OpCode::Load("__new_target__".to_string()));
+        } else {
+            self.instructions.push(OpCode::NewTarget);
+        }
+    }
+
     pub fn generate(&mut self, module: &Module) -> Vec<OpCode> {
+        self.gen_union_constructors();
         for item in &module.body {
-            match item {
-                ModuleItem::Stmt(stmt) => {
-                    self.gen_stmt(stmt);
-                }
-                ModuleItem::ModuleDecl(decl) => {
-                    self.gen_module_decl(decl);
-                }
-            }
+            self.gen_module_item(item);
         }
         self.instructions.push(OpCode::Halt);
         self.instructions.clone()
     }
 
+    fn gen_module_item(&mut self, item: &ModuleItem) {
+        match item {
+            ModuleItem::Stmt(stmt) => {
+                self.gen_stmt(stmt);
+            }
+            ModuleItem::ModuleDecl(decl) => {
+                let start = self.instructions.len();
+                self.gen_module_decl(decl);
+                if self.instructions.len() > start {
+                    self.statement_starts.push((start, decl.span().lo.0));
+                }
+            }
+        }
+    }
+
     fn gen_module_decl(&mut self, decl: &ModuleDecl) {
         match decl {
             ModuleDecl::ExportDecl(export_decl) => {
                 self.gen_decl(&export_decl.decl);
             }
             ModuleDecl::ExportDefaultDecl(export_default) => match &export_default.decl {
+                // A named class or function is declared under its name, which
+                // the runtime exports as `default`; an anonymous one is bound
+                // as `default` itself
                 DefaultDecl::Class(class_expr) => {
-                    self.gen_class(
-                        &class_expr.class,
-                        class_expr.ident.as_ref().map(|id| id.sym.as_str()),
-                    );
-                }
-                DefaultDecl::Fn(fn_expr) => {
-                    let name = if let Some(id) = fn_expr.ident.clone() {
-                        Some(id.sym.to_string())
-                    } else {
-                        None
-                    };
-                    self.gen_fn_decl(name, &fn_expr.function);
-                }
-                _ => {}
-            },
-            ModuleDecl::ExportDefaultExpr(export_default) => match &*export_default.expr {
-                Expr::Fn(fn_expr) => {
-                    let name = if let Some(id) = fn_expr.ident.clone() {
-                        Some(id.sym.to_string())
-                    } else {
-                        None
-                    };
-                    self.gen_fn_decl(name, &fn_expr.function);
-                }
-                Expr::Class(class_expr) => {
-                    self.gen_class(
-                        &class_expr.class,
-                        class_expr.ident.as_ref().map(|id| id.sym.as_str()),
-                    );
-                }
+                    let name = class_expr.ident.as_ref().map(|id| id.sym.to_string());
+                    self.gen_class(&class_expr.class, name.as_deref());
+                    let binding = name.unwrap_or_else(|| "default".to_string());
+                    self.instructions.push(OpCode::Let(binding.clone()));
+                    self.declare(binding);
+                }
+                DefaultDecl::Fn(fn_expr) => match &fn_expr.ident {
+                    Some(id) => self.gen_fn_decl(Some(id.sym.to_string()), &fn_expr.function),
+                    None => {
+                        self.gen_expr(&Expr::Fn(fn_expr.clone()));
+                        self.instructions.push(OpCode::Let("default".to_string()));
+                    }
+                },
                 _ => {}
             },
+            // `export default <value>`: bound as `default`, which no script
+            // can declare, for the importer to read
+            ModuleDecl::ExportDefaultExpr(export_default) => {
+                self.gen_expr(&export_default.expr);
+                self.instructions.push(OpCode::Let("default".to_string()));
+            }
             ModuleDecl::Import(import) => {
                 let src = import.src.value.to_string_lossy().into_owned();
+                let json = self.import_is_json(&src, &import.with);
 
                 for spec in &import.specifiers {
                     match spec {
@@ -331,169 +625,89 @@ impl Codegen {
                                 })
                                 .unwrap_or_else(|| local.clone());
 
-                            self.instructions
-                                .push(OpCode::Push(JsValue::String(src.clone())));
-                            self.instructions.push(OpCode::ImportAsync(src.clone()));
-                            self.instructions.push(OpCode::GetExport {
-                                name: imported.clone(),
-                                is_default: false,
+                            self.gen_import(&src, json);
+                            self.instructions.push(OpCode::ImportBinding {
+                                name: imported,
+                                local,
                             });
-                            self.instructions.push(OpCode::Let(local));
                         }
                         ImportSpecifier::Default(default) => {
                             let local = default.local.sym.to_string();
 
-                            self.instructions
-                                .push(OpCode::Push(JsValue::String(src.clone())));
-                            self.instructions.push(OpCode::ImportAsync(src.clone()));
-                            self.instructions.push(OpCode::GetExport {
+                            self.gen_import(&src, json);
+                            self.instructions.push(OpCode::ImportBinding {
                                 name: "default".to_string(),
-                                is_default: true,
+                                local,
                             });
-                            self.instructions.push(OpCode::Let(local));
                         }
                         ImportSpecifier::Namespace(ns) => {
                             let local = ns.local.sym.to_string();
 
-                            self.instructions
-                                .push(OpCode::Push(JsValue::String(src.clone())));
-                            self.instructions.push(OpCode::ImportAsync(src.clone()));
+                            self.gen_import(&src, json);
                             self.instructions.push(OpCode::Let(local));
                         }
                     }
                 }
 
                 if import.specifiers.is_empty() {
-                    self.instructions
-                        .push(OpCode::Push(JsValue::String(src.clone())));
-                    self.instructions.push(OpCode::ImportAsync(src.clone()));
+                    self.gen_import(&src, json);
                     self.instructions.push(OpCode::Pop);
                 }
-
-                if let Some(_with) = &import.with {
-                    self.warnings.push(format!(
-                        "Warning: Import assertions for '{}' are not fully supported",
-                        import.src.value.to_string_lossy()
-                    ));
-                }
             }
             ModuleDecl::ExportNamed(named) => {
-                if let Some(src) = &named.src {
-                    let src_str = src.value.to_string_lossy().into_owned();
-                    for spec in &named.specifiers {
-                        match spec {
-                            ExportSpecifier::Named(named) => {
-                                let export_name = named
-                                    .exported
-                                    .as_ref()
-                                    .map(|e| {
-                                        let atom = e.atom();
-                                        let s: &str = &atom;
-                                        s.to_string()
-                                    })
-                                    .unwrap_or_else(|| {
-                                        let atom = named.orig.atom();
-                                        let s: &str = &atom;
-                                        s.to_string()
-                                    });
-                                let _local_name = {
-                                    let atom = named.orig.atom();
-                                    let s: &str = &atom;
-                                    s.to_string()
-                                };
-
-                                self.instructions
-                                    .push(OpCode::Push(JsValue::String(src_str.clone())));
-                                self.instructions.push(OpCode::ImportAsync(src_str.clone()));
-                                self.instructions.push(OpCode::GetExport {
-                                    name: export_name.clone(),
-                                    is_default: false,
-                                });
-                                self.instructions.push(OpCode::Let(export_name.clone()));
-                                self.instructions.push(OpCode::Load(export_name.clone()));
-                                self.instructions.push(OpCode::Store(export_name));
-                            }
-                            ExportSpecifier::Default(_) => {
-                                self.instructions
-                                    .push(OpCode::Push(JsValue::String(src_str.clone())));
-                                self.instructions.push(OpCode::ImportAsync(src_str.clone()));
-                                self.instructions.push(OpCode::GetExport {
-                                    name: "default".to_string(),
-                                    is_default: true,
-                                });
-                                self.instructions.push(OpCode::Let("default".to_string()));
-                                self.instructions.push(OpCode::Load("default".to_string()));
-                                self.instructions.push(OpCode::Store("default".to_string()));
-                            }
-                            ExportSpecifier::Namespace(ns) => {
-                                let name = {
-                                    let atom = ns.name.atom();
+                // `export { a as b }` binds nothing: the runtime reads `b`
+                // from the module's binding `a`
+                let Some(src) = &named.src else {
+                    return;
+                };
+                let src_str = src.value.to_string_lossy().into_owned();
+                let json = self.import_is_json(&src_str, &named.with);
+                for spec in &named.specifiers {
+                    self.gen_import(&src_str, json);
+                    match spec {
+                        ExportSpecifier::Named(named) => {
+                            let orig = {
+                                let atom = named.orig.atom();
+                                let s: &str = &atom;
+                                s.to_string()
+                            };
+                            let exported = named
+                                .exported
+                                .as_ref()
+                                .map(|e| {
+                                    let atom = e.atom();
                                     let s: &str = &atom;
                                     s.to_string()
-                                };
-                                self.instructions
-                                    .push(OpCode::Push(JsValue::String(src_str.clone())));
-                                self.instructions.push(OpCode::ImportAsync(src_str.clone()));
-                                self.instructions.push(OpCode::Let(name.clone()));
-                            }
+                                })
+                                .unwrap_or_else(|| orig.clone());
+                            self.instructions.push(OpCode::ReExport {
+                                name: orig,
+                                exported,
+                            });
                         }
-                    }
-                } else {
-                    for spec in &named.specifiers {
-                        match spec {
-                            ExportSpecifier::Named(named) => {
-                                let export_name = named
-                                    .exported
-                                    .as_ref()
-                                    .map(|e| {
-                                        let atom = e.atom();
-                                        let s: &str = &atom;
-                                        s.to_string()
-                                    })
-                                    .unwrap_or_else(|| {
-                                        let atom = named.orig.atom();
-                                        let s: &str = &atom;
-                                        s.to_string()
-                                    });
-                                let local_name = {
-                                    let atom = named.orig.atom();
-                                    let s: &str = &atom;
-                                    s.to_string()
-                                };
-
-                                self.instructions.push(OpCode::Load(local_name));
-                                self.instructions.push(OpCode::Dup);
-                                self.instructions.push(OpCode::Store(export_name));
-                            }
-                            ExportSpecifier::Default(_) => {
-                                self.instructions.push(OpCode::Load("default".to_string()));
-                                self.instructions.push(OpCode::Dup);
-                                self.instructions.push(OpCode::Store("default".to_string()));
-                            }
-                            ExportSpecifier::Namespace(ns) => {
-                                let name = {
-                                    let atom = ns.name.atom();
-                                    let s: &str = &atom;
-                                    s.to_string()
-                                };
-                                self.instructions.push(OpCode::Load(name.clone()));
-                                self.instructions.push(OpCode::Dup);
-                                self.instructions.push(OpCode::Store(name));
-                            }
+                        ExportSpecifier::Default(default) => {
+                            self.instructions.push(OpCode::ReExport {
+                                name: "default".to_string(),
+                                exported: default.exported.sym.to_string(),
+                            });
+                        }
+                        // `export * as ns from`: the namespace itself
+                        ExportSpecifier::Namespace(ns) => {
+                            let name = {
+                                let atom = ns.name.atom();
+                                let s: &str = &atom;
+                                s.to_string()
+                            };
+                            self.instructions.push(OpCode::Let(name));
                         }
                     }
                 }
             }
             ModuleDecl::ExportAll(all) => {
                 let src_str = all.src.value.to_string_lossy().into_owned();
-                self.warnings.push(format!(
-                    "Warning: 'export * from' is not yet fully implemented for '{}'",
-                    src_str
-                ));
-                self.instructions
-                    .push(OpCode::Push(JsValue::String(src_str.clone())));
-                self.instructions.push(OpCode::ImportAsync(src_str.clone()));
-                self.instructions.push(OpCode::Pop);
+                let json = self.import_is_json(&src_str, &all.with);
+                self.gen_import(&src_str, json);
+                self.instructions.push(OpCode::ReExportAll);
             }
             ModuleDecl::TsImportEquals(_) => {}
             ModuleDecl::TsExportAssignment(_) => {}
@@ -501,6 +715,54 @@ impl Codegen {
         }
     }
 
+    /// Stack: [] -> [namespace of `src`]
+    fn gen_import(&mut self, src: &str, json: bool) {
+        self.instructions
+            .push(OpCode::Push(JsValue::String(src.to_string())));
+        self.instructions.push(OpCode::ImportAsync {
+            specifier: src.to_string(),
+            json,
+        });
+    }
+
+    /// Whether an import's attributes (`with { type: "json" }`) ask for
+    /// JSON. Other types are not supported and load the file as usual.
+    fn import_is_json(&mut self, src: &str, with: &Option<Box<ObjectLit>>) -> bool {
+        let Some(with) = with else {
+            return false;
+        };
+        let ty = with.props.iter().find_map(|prop| {
+            let PropOrSpread::Prop(prop) = prop else {
+                return None;
+            };
+            let Prop::KeyValue(kv) = prop.as_ref() else {
+                return None;
+            };
+            let key = match &kv.key {
+                PropName::Ident(id) => id.sym.to_string(),
+                PropName::Str(s) => s.value.to_string_lossy().into_owned(),
+                _ => return None,
+            };
+            match kv.value.as_ref() {
+                Expr::Lit(Lit::Str(value)) if key == "type" => {
+                    Some(value.value.to_string_lossy().into_owned())
+                }
+                _ => None,
+            }
+        });
+        match ty.as_deref() {
+            Some("json") => true,
+            Some(other) => {
+                self.warnings.push(format!(
+                    "Warning: Import type '{}' of '{}' is not supported",
+                    other, src
+                ));
+                false
+            }
+            None => false,
+        }
+    }
+
     fn gen_decl(&mut self, decl: &Decl) {
         match decl {
             Decl::Fn(fn_decl) => {
@@ -508,58 +770,20 @@ impl Codegen {
                 self.gen_fn_decl(Some(name), &fn_decl.function);
             }
             Decl::Class(class_decl) => {
-                self.gen_class(&class_decl.class, Some(class_decl.ident.sym.as_str()));
+                let class_name = class_decl.ident.sym.to_string();
+                self.gen_class(&class_decl.class, Some(class_name.as_str()));
+                self.instructions.push(OpCode::Let(class_name.clone()));
+                self.declare(class_name);
             }
             Decl::Var(var_decl) => {
                 self.gen_var_decl(var_decl);
             }
             Decl::TsEnum(enum_decl) => {
-                // Generate enum as a runtime object
+                // Exported const enums keep their object for importers
                 let enum_name = enum_decl.id.sym.to_string();
-
-                // Create new object
-                self.instructions.push(OpCode::NewObject);
-
-                // Add each enum member as a property
-                for member in &enum_decl.members {
-                    // Duplicate the object reference for each property
-                    self.instructions.push(OpCode::Dup);
-
-                    // Get the member name
-                    let member_name = match &member.id {
-                        swc_ecma_ast::TsEnumMemberId::Ident(ident) => ident.sym.to_string(),
-                        swc_ecma_ast::TsEnumMemberId::Str(s) => s
-                            .value
-                            .as_str()
-                            .expect("Invalid string enum member")
-                            .to_string(),
-                    };
-
-                    // Push the value
-                    match &member.init {
-                        Some(expr) => {
-                            // Compute the value
-                            self.gen_expr(expr);
-                        }
-                        None => {
-                            // Auto-incrementing number for unvalued variants
-                            let idx = enum_decl
-                                .members
-                                .iter()
-                                .position(|m| m.id == member.id)
-                                .unwrap_or(0);
-                            self.instructions
-                                .push(OpCode::Push(JsValue::Number(idx as f64)));
-                        }
-                    }
-
-                    // Set the property
-                    self.instructions.push(OpCode::SetProp(member_name));
-                }
-
-                // Store the enum object in a variable
+                self.gen_enum(enum_decl, true);
                 self.instructions.push(OpCode::Store(enum_name.clone()));
-                self.outer_scope_vars.insert(enum_name);
+                self.declare(enum_name);
             }
             Decl::TsModule(_) => {
                 // TypeScript modules are compile-time only, skip
@@ -574,7 +798,194 @@ impl Codegen {
         }
     }
 
+    /// Pushes the runtime object for an enum: `{ Red: 0, 0: "Red" }` for
+    /// numeric members, a plain `{ Key: "value" }` for string ones. A const
+    /// enum whose members all fold is recorded for inlining instead, and
+    /// emits nothing unless `keep_object` is set. Returns whether an object
+    /// was pushed.
+    fn gen_enum(&mut self, enum_decl: &TsEnumDecl, keep_object: bool) -> bool {
+        let enum_name = enum_decl.id.sym.to_string();
+
+        // Fold what can be folded first; members refer to earlier ones by name
+        let mut known: std::collections::HashMap<String, JsValue> =
+            std::collections::HashMap::new();
+        let mut members = Vec::with_capacity(enum_decl.members.len());
+        let mut next = Some(0.0);
+        for member in &enum_decl.members {
+            let name = match &member.id {
+                TsEnumMemberId::Ident(ident) => ident.sym.to_string(),
+                TsEnumMemberId::Str(s) => s.value.to_string_lossy().into_owned(),
+            };
+            let value = match &member.init {
+                Some(init) => self.enum_const(init, &enum_name, &known),
+                None => {
+                    if next.is_none() {
+                        self.warnings.push(format!(
+                            "Warning: Enum member '{}.{}' must have an initializer",
+                            enum_name, name
+                        ));
+                    }
+                    next.map(JsValue::Number)
+                }
+            };
+            next = match &value {
+                Some(JsValue::Number(n)) => Some(n + 1.0),
+                _ => None,
+            };
+            if let Some(value) = &value {
+                known.insert(name.clone(), value.clone());
+            }
+            members.push((name, value, member.init.as_deref()));
+        }
+
+        if enum_decl.is_const {
+            if members.iter().all(|(_, value, _)| value.is_some()) {
+                self.const_enums.insert(enum_name.clone(), known);
+                if !keep_object {
+                    return false;
+                }
+            } else {
+                self.warnings.push(format!(
+                    "Warning: const enum '{}' has members that are not constant; it is kept as a regular enum",
+                    enum_name
+                ));
+            }
+        }
+
+        self.instructions.push(OpCode::NewObject);
+        for (name, value, init) in members {
+            match value {
+                Some(value) => {
+                    // Reverse mapping for numeric members only
+                    if let JsValue::Number(n) = value {
+                        self.instructions.push(OpCode::Dup);
+                        self.instructions
+                            .push(OpCode::Push(JsValue::String(name.clone())));
+                        self.instructions
+                            .push(OpCode::SetProp(crate::stdlib::number_to_string(n)));
+                    }
+                    self.instructions.push(OpCode::Dup);
+                    self.instructions.push(OpCode::Push(value));
+                    self.instructions.push(OpCode::SetProp(name));
+                }
+                None => {
+                    // Computed at run time: E[E.name = init] = "name"
+                    self.instructions.push(OpCode::Dup);
+                    match init {
+                        Some(init) => self.gen_expr(init),
+                        None => self.instructions.push(OpCode::Push(JsValue::Undefined)),
+                    }
+                    self.instructions.push(OpCode::SetProp(name.clone()));
+                    self.instructions.push(OpCode::Dup);
+                    self.instructions.push(OpCode::Dup);
+                    self.instructions.push(OpCode::GetProp(name.clone()));
+                    self.instructions.push(OpCode::Push(JsValue::String(name)));
+                    self.instructions.push(OpCode::Swap);
+                    self.instructions.push(OpCode::SetPropComputed);
+                }
+            }
+        }
+        true
+    }
+
+    /// Value of a constant enum initializer: literals, earlier members (bare
+    /// or through a const enum), and arithmetic, bitwise and string
+    /// concatenation on those. `None` when it needs to run.
+    fn enum_const(
+        &self,
+        expr: &Expr,
+        enum_name: &str,
+        known: &std::collections::HashMap<String, JsValue>,
+    ) -> Option<JsValue> {
+        match expr {
+            Expr::Lit(Lit::Num(num)) => Some(JsValue::Number(num.value)),
+            Expr::Lit(Lit::Str(s)) => Some(JsValue::String(s.value.to_string_lossy().into_owned())),
+            Expr::Tpl(tpl) if tpl.exprs.is_empty() => Some(JsValue::String(
+                tpl.quasis
+                    .iter()
+                    .map(|q| q.cooked.as_ref().map(|c| c.to_string_lossy().into_owned()))
+                    .collect::<Option<String>>()?,
+            )),
+            Expr::Paren(paren) => self.enum_const(&paren.expr, enum_name, known),
+            Expr::Ident(ident) => known.get(ident.sym.as_str()).cloned(),
+            Expr::Member(member) => {
+                let Expr::Ident(obj) = member.obj.as_ref() else {
+                    return None;
+                };
+                let prop = static_prop_name(&member.prop)?;
+                if obj.sym == enum_name {
+                    known.get(&prop).cloned()
+                } else {
+                    self.const_enums.get(obj.sym.as_str())?.get(&prop).cloned()
+                }
+            }
+            Expr::Unary(unary) => {
+                let JsValue::Number(n) = self.enum_const(&unary.arg, enum_name, known)? else {
+                    return None;
+                };
+                match unary.op {
+                    UnaryOp::Minus => Some(JsValue::Number(-n)),
+                    UnaryOp::Plus => Some(JsValue::Number(n)),
+                    UnaryOp::Tilde => Some(JsValue::Number(!to_int32(n) as f64)),
+                    _ => None,
+                }
+            }
+            Expr::Bin(bin) => {
+                let left = self.enum_const(&bin.left, enum_name, known)?;
+                let right = self.enum_const(&bin.right, enum_name, known)?;
+                match (left, right) {
+                    (JsValue::Number(a), JsValue::Number(b)) => {
+                        let shift = (to_int32(b) as u32) & 31;
+                        Some(JsValue::Number(match bin.op {
+                            BinaryOp::Add => a + b,
+                            BinaryOp::Sub => a - b,
+                            BinaryOp::Mul => a * b,
+                            BinaryOp::Div => a / b,
+                            BinaryOp::Mod => a % b,
+                            BinaryOp::Exp => a.powf(b),
+                            BinaryOp::BitOr => (to_int32(a) | to_int32(b)) as f64,
+                            BinaryOp::BitAnd => (to_int32(a) & to_int32(b)) as f64,
+                            BinaryOp::BitXor => (to_int32(a) ^ to_int32(b)) as f64,
+                            BinaryOp::LShift => to_int32(a).wrapping_shl(shift) as f64,
+                            BinaryOp::RShift => (to_int32(a) >> shift) as f64,
+                            BinaryOp::ZeroFillRShift => ((to_int32(a) as u32) >> shift) as f64,
+                            _ => return None,
+                        }))
+                    }
+                    (a, b) if bin.op == BinaryOp::Add => {
+                        let text = |v: JsValue| match v {
+                            JsValue::String(s) => s,
+                            JsValue::Number(n) => crate::stdlib::number_to_string(n),
+                            _ => String::new(),
+                        };
+                        Some(JsValue::String(format!("{}{}", text(a), text(b))))
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Folded value of `E.Member` / `E["Member"]` when `E` is a const enum
+    /// not shadowed by a local
+    fn const_enum_member(&self, member: &MemberExpr) -> Option<JsValue> {
+        let Expr::Ident(obj) = member.obj.as_ref() else {
+            return None;
+        };
+        let members = self.const_enums.get(obj.sym.as_str())?;
+        if self
+            .scope_stack
+            .iter()
+            .any(|scope| scope.iter().any(|name| name == obj.sym.as_str()))
+        {
+            return None;
+        }
+        members.get(&static_prop_name(&member.prop)?).cloned()
+    }
+
     pub fn generate_script(&mut self, script: &Script) -> Vec<OpCode> {
+        self.gen_union_constructors();
         for stmt in &script.body {
             self.gen_stmt(stmt);
         }
@@ -594,11 +1005,15 @@ impl Codegen {
             self.instructions.push(OpCode::Push(JsValue::Function {
                 address: ip,
                 env: None, // Named function declarations typically don't capture
+                bound: None,
             }));
             self.instructions.push(OpCode::Let(name.clone()));
 
             // Track this function name in outer scope
-            self.outer_scope_vars.insert(name.clone());
+            self.declare(name.clone());
+            let depth = self.binding_scopes.len() - 1;
+            self.function_arities
+                .insert(name.clone(), Arity::of(&fn_decl.params, depth));
 
             // 2. Add jump to skip over function body
             let jump_target = self.instructions.len() + 1; // Will be updated after compiling body
@@ -613,18 +1028,11 @@ impl Codegen {
 
         // 3. Compile function body
         self.in_function = true;
+        self.push_binding_scope();
+        let saved_lexical_this = std::mem::replace(&mut self.lexical_this, false);
         self.in_async_function = is_async;
 
-        // Inside the function body, we must pop arguments into locals
-        // We process them in REVERSE order because of how they sit on the stack
-        for param in fn_decl.params.iter().rev() {
-            if let Pat::Ident(id) = &param.pat {
-                let param_name = id.id.sym.to_string();
-                // The value is already on the stack from the Caller
-                // Parameters are new bindings in the function scope
-                self.instructions.push(OpCode::Let(param_name));
-            }
-        }
+        self.gen_params(fn_decl.params.iter().map(|param| &param.pat));
         let stmts = &fn_decl.body.as_ref().unwrap().stmts;
 
         let mut last_instr_was_return = false;
@@ -633,10 +1041,15 @@ impl Codegen {
             self.gen_stmt(s);
             // Check if the last instruction emitted was a Return
             last_instr_was_return = self.instructions.len() > before
-                && matches!(self.instructions.last(), Some(OpCode::Return));
+                && matches!(
+                    self.instructions.last(),
+                    Some(OpCode::Return | OpCode::ReturnMulti(_))
+                );
         }
 
         self.in_function = false;
+        self.pop_binding_scope();
+        self.lexical_this = saved_lexical_this;
         self.in_async_function = false;
 
         // If the last statement wasn't a return, we need to handle implicit return
@@ -646,42 +1059,15 @@ impl Codegen {
                 self.instructions.push(OpCode::Push(JsValue::Undefined));
                 // For async functions, wrap in Promise.resolve()
                 if is_async {
-                    self.instructions
-                        .push(OpCode::Push(JsValue::String("Promise".to_string())));
-                    self.instructions.push(OpCode::Load("Promise".to_string()));
-                    self.instructions
-                        .push(OpCode::Push(JsValue::String("resolve".to_string())));
-                    self.instructions
-                        .push(OpCode::GetProp("resolve".to_string()));
-                    // Stack: [undefined, Promise, PromiseObj, resolveFn]
-                    // Pop PromiseObj and Promise, keeping resolveFn and undefined
-                    self.instructions.push(OpCode::Pop);
-                    self.instructions.push(OpCode::Pop);
-                    // Stack: [undefined, resolveFn]
-                    // Swap to get [resolveFn, undefined]
-                    self.instructions.push(OpCode::Swap);
-                    self.instructions.push(OpCode::Call(1));
+                    self.gen_promise_resolve();
                 }
             } else {
-                // Non-empty body but last statement wasn't a return
-                // The last expression's result is on the stack, need to return it
-                // For async functions, wrap in Promise.resolve()
+                // Non-empty body but last statement wasn't a return. Statements
+                // leave nothing on the stack, so an async function resolves
+                // its promise with undefined.
                 if is_async {
-                    self.instructions
-                        .push(OpCode::Push(JsValue::String("Promise".to_string())));
-                    self.instructions.push(OpCode::Load("Promise".to_string()));
-                    self.instructions
-                        .push(OpCode::Push(JsValue::String("resolve".to_string())));
-                    self.instructions
-                        .push(OpCode::GetProp("resolve".to_string()));
-                    // Stack: [returnValue, Promise, PromiseObj, resolveFn]
-                    // Pop PromiseObj and Promise, keeping resolveFn
-                    self.instructions.push(OpCode::Pop);
-                    self.instructions.push(OpCode::Pop);
-                    // Stack: [returnValue, resolveFn]
-                    // Swap to get [resolveFn, returnValue]
-                    self.instructions.push(OpCode::Swap);
-                    self.instructions.push(OpCode::Call(1));
+                    self.instructions.push(OpCode::Push(JsValue::Undefined));
+                    self.gen_promise_resolve();
                 }
             }
             self.instructions.push(OpCode::Return);
@@ -699,12 +1085,66 @@ impl Codegen {
     fn gen_var_decl(&mut self, var_decl: &VarDecl) {
         for decl in &var_decl.decls {
             if let Some(init) = &decl.init {
+                if let Pat::Array(arr_pat) = &decl.name
+                    && matches!(init.as_ref(), Expr::Call(_))
+                    && arr_pat
+                        .elems
+                        .iter()
+                        .all(|elem| matches!(elem, None | Some(Pat::Ident(_))))
+                {
+                    // `let [a, b] = f()`: unpack onto the stack so a callee that
+                    // ends in ReturnMulti can skip building the array. Bindings
+                    // are made from the top of the stack, i.e. last element first.
+                    self.gen_expr(init);
+                    self.instructions.push(OpCode::Unpack(arr_pat.elems.len()));
+                    for elem in arr_pat.elems.iter().rev() {
+                        match elem {
+                            Some(elem_pat) => self.gen_pattern_binding(elem_pat),
+                            None => self.instructions.push(OpCode::Pop),
+                        }
+                    }
+                    continue;
+                }
+                if let (Pat::Ident(id), Expr::Class(class_expr)) = (&decl.name, init.as_ref())
+                    && class_expr.ident.is_none()
+                {
+                    // `const Foo = class { ... }` takes its name from the binding
+                    self.gen_class(&class_expr.class, Some(id.id.sym.as_str()));
+                    self.gen_pattern_binding(&decl.name);
+                    continue;
+                }
                 self.gen_expr(init);
                 self.gen_pattern_binding(&decl.name);
             }
         }
     }
 
+    /// Function prologue: bind each parameter from the frame's argument
+    /// window, so a caller passing too few or too many arguments cannot
+    /// shift the bindings
+    fn gen_params<'p>(&mut self, params: impl IntoIterator<Item = &'p Pat>) {
+        for (index, pat) in params.into_iter().enumerate() {
+            match pat {
+                Pat::Ident(id) => {
+                    // Parameters are new bindings in the function scope, and
+                    // closures created in it capture them like any local
+                    let name = id.id.sym.to_string();
+                    self.instructions.push(OpCode::LoadArg(index));
+                    self.instructions.push(OpCode::Let(name.clone()));
+                    self.declare(name);
+                }
+                Pat::Rest(rest) => {
+                    self.instructions.push(OpCode::LoadRestArgs(index));
+                    self.gen_pattern_binding(&rest.arg);
+                }
+                _ => {
+                    self.instructions.push(OpCode::LoadArg(index));
+                    self.gen_pattern_binding(pat);
+                }
+            }
+        }
+    }
+
     /// Generate code to bind a pattern to a value on the stack.
     /// The value to destructure should already be on top of the stack.
     fn gen_pattern_binding(&mut self, pat: &Pat) {
@@ -713,7 +1153,7 @@ impl Codegen {
                 // Simple variable binding
                 let name = id.id.sym.to_string();
                 self.instructions.push(OpCode::Let(name.clone()));
-                self.outer_scope_vars.insert(name);
+                self.declare(name);
             }
             Pat::Object(obj_pat) => {
                 // Object destructuring: let { x, y } = obj
@@ -766,7 +1206,7 @@ impl Codegen {
                             }
 
                             self.instructions.push(OpCode::Let(key_name.clone()));
-                            self.outer_scope_vars.insert(key_name);
+                            self.declare(key_name);
                         }
                         swc_ecma_ast::ObjectPatProp::Rest(rest) => {
                             // { ...rest } - not fully implemented yet
@@ -774,7 +1214,7 @@ impl Codegen {
                                 let name = id.id.sym.to_string();
                                 // For now, just bind the remaining object
                                 self.instructions.push(OpCode::Let(name.clone()));
-                                self.outer_scope_vars.insert(name);
+                                self.declare(name);
                             }
                         }
                     }
@@ -827,8 +1267,28 @@ impl Codegen {
     }
 
     fn gen_stmt(&mut self, stmt: &Stmt) {
+        let start = self.instructions.len();
+        self.gen_stmt_code(stmt);
+        if self.instructions.len() > start {
+            self.statement_starts.push((start, stmt.span().lo.0));
+        }
+    }
+
+    fn gen_stmt_code(&mut self, stmt: &Stmt) {
         match stmt {
             Stmt::Return(ret_stmt) => {
+                if !self.in_async_function
+                    && let Some(arg) = &ret_stmt.arg
+                    && let Some(elems) = multi_return_elems(arg)
+                {
+                    // `return [a, b]`: leave the values on the stack instead of
+                    // allocating an array the caller will immediately destructure
+                    for elem in &elems {
+                        self.gen_expr(elem);
+                    }
+                    self.instructions.push(OpCode::ReturnMulti(elems.len()));
+                    return;
+                }
                 if let Some(arg) = &ret_stmt.arg {
                     self.gen_expr(arg); // Pushes the return value to stack
                 } else {
@@ -836,26 +1296,18 @@ impl Codegen {
                 }
                 // For async functions, wrap the return value in Promise.resolve()
                 if self.in_async_function {
-                    self.instructions
-                        .push(OpCode::Push(JsValue::String("Promise".to_string())));
-                    self.instructions.push(OpCode::Load("Promise".to_string()));
-                    self.instructions
-                        .push(OpCode::Push(JsValue::String("resolve".to_string())));
-                    self.instructions
-                        .push(OpCode::GetProp("resolve".to_string()));
-                    self.instructions.push(OpCode::Swap);
-                    self.instructions.push(OpCode::Call(1));
+                    self.gen_promise_resolve();
                 }
                 self.instructions.push(OpCode::Return);
             }
             // RECURSION: Handle the Block
             Stmt::Block(block) => {
-                self.scope_stack.push(Vec::new()); // Enter new scope
+                self.enter_scope(); // Enter new scope
                 for s in &block.stmts {
                     self.gen_stmt(s);
                 }
                 // Exit scope: Drop variables
-                if let Some(locals) = self.scope_stack.pop() {
+                if let Some(locals) = self.exit_scope() {
                     for name in locals.into_iter().rev() {
                         self.instructions.push(OpCode::Drop(name));
                     }
@@ -872,55 +1324,14 @@ impl Codegen {
                 let class_name = class_decl.ident.sym.to_string();
                 self.gen_class(&class_decl.class, Some(class_name.as_str()));
                 self.instructions.push(OpCode::Let(class_name.clone()));
-                self.outer_scope_vars.insert(class_name);
+                self.declare(class_name);
             }
             Stmt::Decl(Decl::TsEnum(enum_decl)) => {
-                // Generate enum as a runtime object at statement level
                 let enum_name = enum_decl.id.sym.to_string();
-
-                // Create new object
-                self.instructions.push(OpCode::NewObject);
-
-                // Add each enum member as a property
-                for member in &enum_decl.members {
-                    // Duplicate the object reference for each property
-                    self.instructions.push(OpCode::Dup);
-
-                    // Get the member name
-                    let member_name = match &member.id {
-                        swc_ecma_ast::TsEnumMemberId::Ident(ident) => ident.sym.to_string(),
-                        swc_ecma_ast::TsEnumMemberId::Str(s) => s
-                            .value
-                            .as_str()
-                            .expect("Invalid string enum member")
-                            .to_string(),
-                    };
-
-                    // Push the value
-                    match &member.init {
-                        Some(expr) => {
-                            // Compute the value
-                            self.gen_expr(expr);
-                        }
-                        None => {
-                            // Auto-incrementing number for unvalued variants
-                            let idx = enum_decl
-                                .members
-                                .iter()
-                                .position(|m| m.id == member.id)
-                                .unwrap_or(0);
-                            self.instructions
-                                .push(OpCode::Push(JsValue::Number(idx as f64)));
-                        }
-                    }
-
-                    // Set the property
-                    self.instructions.push(OpCode::SetProp(member_name));
+                if self.gen_enum(enum_decl, false) {
+                    self.instructions.push(OpCode::Let(enum_name.clone()));
+                    self.declare(enum_name);
                 }
-
-                // Store the enum object in a variable
-                self.instructions.push(OpCode::Let(enum_name.clone()));
-                self.outer_scope_vars.insert(enum_name);
             }
             Stmt::Decl(Decl::TsModule(_)) => {
                 // TypeScript modules are compile-time only, skip at runtime
@@ -932,13 +1343,12 @@ impl Codegen {
                 // Type aliases are compile-time only, skip at runtime
             }
             Stmt::Expr(expr_stmt) => {
-                self.gen_expr(&expr_stmt.expr);
                 // Expression statements (e.g. `foo();`) should always discard their result in JS.
-                // This is critical for proper stack management - without this Pop, values from
+                // This is critical for proper stack management - without it, values from
                 // expression statements (like assignments) accumulate on the stack and corrupt
                 // the stack state when calling functions from within object literals or other
                 // expressions that expect the stack to be clean.
-                self.instructions.push(OpCode::Pop);
+                self.gen_effect(&expr_stmt.expr);
             }
             Stmt::While(while_stmt) => {
                 let loop_start = self.instructions.len();
@@ -984,6 +1394,15 @@ impl Codegen {
                 }
             }
             Stmt::If(if_stmt) => {
+                // A condition fixed by the build only compiles its branch
+                if let Some(taken) = self.static_condition(&if_stmt.test) {
+                    if taken {
+                        self.gen_stmt(&if_stmt.cons);
+                    } else if let Some(alt) = &if_stmt.alt {
+                        self.gen_stmt(alt);
+                    }
+                    return;
+                }
                 self.gen_expr(&if_stmt.test);
                 let else_jump_idx = self.instructions.len();
                 self.instructions.push(OpCode::JumpIfFalse(0));
@@ -1012,7 +1431,7 @@ impl Codegen {
                 }
             }
             Stmt::For(for_stmt) => {
-                self.scope_stack.push(Vec::new());
+                self.enter_scope();
                 if let Some(init) = &for_stmt.init {
                     match init {
                         swc_ecma_ast::VarDeclOrExpr::VarDecl(var_decl) => {
@@ -1040,8 +1459,7 @@ impl Codegen {
                 self.gen_stmt(&for_stmt.body);
                 let continue_target = self.instructions.len();
                 if let Some(update) = &for_stmt.update {
-                    self.gen_expr(update);
-                    self.instructions.push(OpCode::Pop);
+                    self.gen_effect(update);
                 }
                 self.instructions.push(OpCode::Jump(loop_start));
                 let loop_end = self.instructions.len();
@@ -1060,14 +1478,14 @@ impl Codegen {
                         }
                     }
                 }
-                if let Some(locals) = self.scope_stack.pop() {
+                if let Some(locals) = self.exit_scope() {
                     for name in locals.into_iter().rev() {
                         self.instructions.push(OpCode::Drop(name));
                     }
                 }
             }
             Stmt::ForOf(for_of_stmt) => {
-                self.scope_stack.push(Vec::new());
+                self.enter_scope();
                 self.gen_expr(&for_of_stmt.right);
                 let iter_name = "__for_of_iter__".to_string();
                 self.instructions.push(OpCode::Let(iter_name.clone()));
@@ -1139,14 +1557,14 @@ impl Codegen {
                         }
                     }
                 }
-                if let Some(locals) = self.scope_stack.pop() {
+                if let Some(locals) = self.exit_scope() {
                     for name in locals.into_iter().rev() {
                         self.instructions.push(OpCode::Drop(name));
                     }
                 }
             }
             Stmt::ForIn(for_in_stmt) => {
-                self.scope_stack.push(Vec::new());
+                self.enter_scope();
                 self.gen_expr(&for_in_stmt.right);
                 self.instructions.push(OpCode::Load("Object".to_string()));
                 self.instructions.push(OpCode::GetProp("keys".to_string()));
@@ -1221,7 +1639,7 @@ impl Codegen {
                         }
                     }
                 }
-                if let Some(locals) = self.scope_stack.pop() {
+                if let Some(locals) = self.exit_scope() {
                     for name in locals.into_iter().rev() {
                         self.instructions.push(OpCode::Drop(name));
                     }
@@ -1276,12 +1694,12 @@ impl Codegen {
                 });
 
                 // 2. Emit try block
-                self.scope_stack.push(Vec::new());
+                self.enter_scope();
                 for s in &try_stmt.block.stmts {
                     self.gen_stmt(s);
                 }
                 // Drop try block scope variables
-                if let Some(locals) = self.scope_stack.pop() {
+                if let Some(locals) = self.exit_scope() {
                     for name in locals.into_iter().rev() {
                         self.instructions.push(OpCode::Drop(name));
                     }
@@ -1298,7 +1716,7 @@ impl Codegen {
                 let catch_addr = if has_catch {
                     let addr = self.instructions.len();
                     if let Some(handler) = &try_stmt.handler {
-                        self.scope_stack.push(Vec::new());
+                        self.enter_scope();
 
                         // Bind exception to catch parameter if present
                         if let Some(param) = &handler.param {
@@ -1321,7 +1739,7 @@ impl Codegen {
                         }
 
                         // Drop catch block scope variables
-                        if let Some(locals) = self.scope_stack.pop() {
+                        if let Some(locals) = self.exit_scope() {
                             for name in locals.into_iter().rev() {
                                 self.instructions.push(OpCode::Drop(name));
                             }
@@ -1336,12 +1754,12 @@ impl Codegen {
                 let finally_addr = if has_finally {
                     let addr = self.instructions.len();
                     if let Some(finalizer) = &try_stmt.finalizer {
-                        self.scope_stack.push(Vec::new());
+                        self.enter_scope();
                         for s in &finalizer.stmts {
                             self.gen_stmt(s);
                         }
                         // Drop finally block scope variables
-                        if let Some(locals) = self.scope_stack.pop() {
+                        if let Some(locals) = self.exit_scope() {
                             for name in locals.into_iter().rev() {
                                 self.instructions.push(OpCode::Drop(name));
                             }
@@ -1372,6 +1790,77 @@ impl Codegen {
                     *addr = if has_finally { finally_addr } else { end_addr };
                 }
             }
+            Stmt::Switch(switch) => {
+                self.check_union_switch(switch);
+                // The discriminant is kept in a local, so a case body can
+                // return or break without leaving it on the stack
+                let discriminant = format!("__switch_{}__", self.instructions.len());
+                self.gen_expr(&switch.discriminant);
+                self.instructions.push(OpCode::Let(discriminant.clone()));
+
+                // Test the cases in order; a match jumps to its body
+                let mut body_jumps = Vec::new();
+                for case in &switch.cases {
+                    let Some(test) = &case.test else {
+                        body_jumps.push(None);
+                        continue;
+                    };
+                    self.instructions.push(OpCode::Load(discriminant.clone()));
+                    self.gen_expr(test);
+                    self.instructions.push(OpCode::Eq);
+                    let next_test = self.instructions.len() + 2;
+                    self.instructions.push(OpCode::JumpIfFalse(next_test));
+                    body_jumps.push(Some(self.instructions.len()));
+                    self.instructions.push(OpCode::Jump(0));
+                }
+                // No match: the default case, or past the switch
+                let no_match_jump = self.instructions.len();
+                self.instructions.push(OpCode::Jump(0));
+
+                // Bodies follow each other, so cases without `break` fall through
+                self.loop_stack.push(LoopContext {
+                    start_addr: no_match_jump,
+                    break_jumps: Vec::new(),
+                    continue_jumps: Vec::new(),
+                });
+                let mut default_start = None;
+                for (case, body_jump) in switch.cases.iter().zip(body_jumps) {
+                    let body_start = self.instructions.len();
+                    match body_jump {
+                        Some(idx) => {
+                            if let OpCode::Jump(ref mut addr) = self.instructions[idx] {
+                                *addr = body_start;
+                            }
+                        }
+                        None => default_start = Some(body_start),
+                    }
+                    for stmt in &case.cons {
+                        self.gen_stmt(stmt);
+                    }
+                }
+                let switch_end = self.instructions.len();
+                if let OpCode::Jump(ref mut addr) = self.instructions[no_match_jump] {
+                    *addr = default_start.unwrap_or(switch_end);
+                }
+                if let Some(switch_ctx) = self.loop_stack.pop() {
+                    for break_idx in switch_ctx.break_jumps {
+                        if let OpCode::Jump(ref mut addr) = self.instructions[break_idx] {
+                            *addr = switch_end;
+                        }
+                    }
+                    // `continue` belongs to the enclosing loop
+                    match self.loop_stack.last_mut() {
+                        Some(outer) => outer.continue_jumps.extend(switch_ctx.continue_jumps),
+                        None => {
+                            for cont_idx in switch_ctx.continue_jumps {
+                                if let OpCode::Jump(ref mut addr) = self.instructions[cont_idx] {
+                                    *addr = switch_end;
+                                }
+                            }
+                        }
+                    }
+                }
+            }
             Stmt::Throw(throw_stmt) => {
                 // Throw statement: push exception value and emit Throw opcode
                 self.gen_expr(&throw_stmt.arg);
@@ -1430,6 +1919,7 @@ impl Codegen {
                     self.instructions.push(OpCode::Push(JsValue::Function {
                         address: start_ip,
                         env: None,
+                        bound: None,
                     }));
                 }
 
@@ -1439,16 +1929,11 @@ impl Codegen {
                 let prev_in_function = self.in_function;
                 let prev_async = self.in_async_function;
                 self.in_function = true;
+                self.push_binding_scope();
+                let prev_lexical_this = std::mem::replace(&mut self.lexical_this, false);
                 self.in_async_function = is_async;
 
-                // Pop args into locals (reverse order)
-                // Parameters are new bindings in the function scope
-                for param in fn_expr.function.params.iter().rev() {
-                    if let Pat::Ident(id) = &param.pat {
-                        let param_name = id.id.sym.to_string();
-                        self.instructions.push(OpCode::Let(param_name));
-                    }
-                }
+                self.gen_params(fn_expr.function.params.iter().map(|param| &param.pat));
 
                 if let Some(body) = &fn_expr.function.body {
                     let stmts = &body.stmts;
@@ -1458,55 +1943,31 @@ impl Codegen {
                         let before = self.instructions.len();
                         self.gen_stmt(s);
                         last_instr_was_return = self.instructions.len() > before
-                            && matches!(self.instructions.last(), Some(OpCode::Return));
+                            && matches!(
+                                self.instructions.last(),
+                                Some(OpCode::Return | OpCode::ReturnMulti(_))
+                            );
                     }
 
                     // For async functions with no return statement at the end, wrap the result
                     if is_async && !last_instr_was_return {
-                        if stmts.is_empty() {
-                            self.instructions.push(OpCode::Push(JsValue::Undefined));
-                        }
+                        // Statements leave nothing on the stack
+                        self.instructions.push(OpCode::Push(JsValue::Undefined));
                         // Wrap in Promise.resolve() and add Return
-                        self.instructions
-                            .push(OpCode::Push(JsValue::String("Promise".to_string())));
-                        self.instructions.push(OpCode::Load("Promise".to_string()));
-                        self.instructions
-                            .push(OpCode::Push(JsValue::String("resolve".to_string())));
-                        self.instructions
-                            .push(OpCode::GetProp("resolve".to_string()));
-                        // Stack: [returnValue, Promise, PromiseObj, resolveFn]
-                        // Pop PromiseObj and Promise, keeping resolveFn
-                        self.instructions.push(OpCode::Pop);
-                        self.instructions.push(OpCode::Pop);
-                        // Stack: [returnValue, resolveFn]
-                        // Swap to get [resolveFn, returnValue]
-                        self.instructions.push(OpCode::Swap);
-                        self.instructions.push(OpCode::Call(1));
+                        self.gen_promise_resolve();
                         self.instructions.push(OpCode::Return);
                     }
                 } else {
                     self.instructions.push(OpCode::Push(JsValue::Undefined));
                     // For async functions with no body, wrap undefined in Promise.resolve()
                     if is_async {
-                        self.instructions
-                            .push(OpCode::Push(JsValue::String("Promise".to_string())));
-                        self.instructions.push(OpCode::Load("Promise".to_string()));
-                        self.instructions
-                            .push(OpCode::Push(JsValue::String("resolve".to_string())));
-                        self.instructions
-                            .push(OpCode::GetProp("resolve".to_string()));
-                        // Stack: [undefined, Promise, PromiseObj, resolveFn]
-                        // Pop PromiseObj and Promise, keeping resolveFn
-                        self.instructions.push(OpCode::Pop);
-                        self.instructions.push(OpCode::Pop);
-                        // Stack: [undefined, resolveFn]
-                        // Swap to get [resolveFn, undefined]
-                        self.instructions.push(OpCode::Swap);
-                        self.instructions.push(OpCode::Call(1));
+                        self.gen_promise_resolve();
                     }
                     self.instructions.push(OpCode::Return);
                 }
                 self.in_function = prev_in_function;
+                self.pop_binding_scope();
+                self.lexical_this = prev_lexical_this;
                 self.in_async_function = prev_async;
 
                 let after_body = self.instructions.len();
@@ -1536,7 +1997,10 @@ impl Codegen {
 
                 // 2. Detect captured variables (upvars) from outer scopes
                 let captured_vars = self.collect_free_vars_in_arrow_body(&arrow.body, &params);
-                let has_captures = !captured_vars.is_empty();
+                // Arrows have no `this` or `new.target` of their own: capture
+                // the enclosing ones alongside the upvars
+                let captures_this = arrow_uses_this(&arrow.body);
+                let has_captures = !captured_vars.is_empty() || captures_this;
 
                 if has_captures {
                     // 3. Create Environment Object on the Heap
@@ -1548,6 +2012,18 @@ impl Codegen {
                         self.instructions.push(OpCode::Load(var_name.clone())); // Load value
                         self.instructions.push(OpCode::SetProp(var_name.clone())); // Store in env
                     }
+                    if captures_this {
+                        // A nested arrow copies its parent's captures, so
+                        // they all see the enclosing function's `this`
+                        self.instructions.push(OpCode::Dup);
+                        self.gen_this();
+                        self.instructions
+                            .push(OpCode::SetProp("__this__".to_string()));
+                        self.instructions.push(OpCode::Dup);
+                        self.gen_new_target();
+                        self.instructions
+                            .push(OpCode::SetProp("__new_target__".to_string()));
+                    }
 
                     // 5. Calculate function body start address
                     // Layout: ... MakeClosure Jump [body...] ...
@@ -1559,6 +2035,7 @@ impl Codegen {
                     self.instructions.push(OpCode::Push(JsValue::Function {
                         address: start_ip,
                         env: None,
+                        bound: None,
                     }));
                 }
 
@@ -1567,19 +2044,13 @@ impl Codegen {
 
                 let prev_in_function = self.in_function;
                 let prev_async = self.in_async_function;
+                let prev_lexical_this = self.lexical_this;
                 self.in_function = true;
+                self.push_binding_scope();
                 self.in_async_function = arrow.is_async;
+                self.lexical_this = captures_this;
 
-                // Pop args into locals (reverse order)
-                // Parameters are new bindings in the function scope
-                for param in arrow.params.iter().rev() {
-                    if let Pat::Ident(id) = param {
-                        let param_name = id.id.sym.to_string();
-                        self.instructions.push(OpCode::Let(param_name));
-                    } else {
-                        println!("Warning: Non-identifier arrow params not supported yet.");
-                    }
-                }
+                self.gen_params(&arrow.params);
 
                 match &*arrow.body {
                     BlockStmtOrExpr::Expr(e) => {
@@ -1587,21 +2058,7 @@ impl Codegen {
                         self.gen_expr(e);
                         // For async arrows, wrap the return value in Promise.resolve()
                         if arrow.is_async {
-                            self.instructions
-                                .push(OpCode::Push(JsValue::String("Promise".to_string())));
-                            self.instructions.push(OpCode::Load("Promise".to_string()));
-                            self.instructions
-                                .push(OpCode::Push(JsValue::String("resolve".to_string())));
-                            self.instructions
-                                .push(OpCode::GetProp("resolve".to_string()));
-                            // Stack: [returnValue, Promise, PromiseObj, resolveFn]
-                            // Pop PromiseObj and Promise, keeping resolveFn
-                            self.instructions.push(OpCode::Pop);
-                            self.instructions.push(OpCode::Pop);
-                            // Stack: [returnValue, resolveFn]
-                            // Swap to get [resolveFn, returnValue]
-                            self.instructions.push(OpCode::Swap);
-                            self.instructions.push(OpCode::Call(1));
+                            self.gen_promise_resolve();
                         }
                         self.instructions.push(OpCode::Return);
                     }
@@ -1613,7 +2070,10 @@ impl Codegen {
                             let before = self.instructions.len();
                             self.gen_stmt(s);
                             last_instr_was_return = self.instructions.len() > before
-                                && matches!(self.instructions.last(), Some(OpCode::Return));
+                                && matches!(
+                                    self.instructions.last(),
+                                    Some(OpCode::Return | OpCode::ReturnMulti(_))
+                                );
                         }
 
                         if stmts.is_empty() {
@@ -1622,21 +2082,11 @@ impl Codegen {
 
                         // For async arrows with no return statement at the end, wrap the result
                         if arrow.is_async && !last_instr_was_return {
-                            self.instructions
-                                .push(OpCode::Push(JsValue::String("Promise".to_string())));
-                            self.instructions.push(OpCode::Load("Promise".to_string()));
-                            self.instructions
-                                .push(OpCode::Push(JsValue::String("resolve".to_string())));
-                            self.instructions
-                                .push(OpCode::GetProp("resolve".to_string()));
-                            // Stack: [returnValue, Promise, PromiseObj, resolveFn]
-                            // Pop PromiseObj and Promise, keeping resolveFn
-                            self.instructions.push(OpCode::Pop);
-                            self.instructions.push(OpCode::Pop);
-                            // Stack: [returnValue, resolveFn]
-                            // Swap to get [resolveFn, returnValue]
-                            self.instructions.push(OpCode::Swap);
-                            self.instructions.push(OpCode::Call(1));
+                            if !stmts.is_empty() {
+                                // Statements leave nothing on the stack
+                                self.instructions.push(OpCode::Push(JsValue::Undefined));
+                            }
+                            self.gen_promise_resolve();
                         }
 
                         if !last_instr_was_return {
@@ -1646,7 +2096,9 @@ impl Codegen {
                 }
 
                 self.in_function = prev_in_function;
+                self.pop_binding_scope();
                 self.in_async_function = prev_async;
+                self.lexical_this = prev_lexical_this;
 
                 let after_body = self.instructions.len();
                 if let OpCode::Jump(ref mut target) = self.instructions[jump_idx] {
@@ -1673,34 +2125,28 @@ impl Codegen {
                 self.instructions.push(OpCode::Load(id.sym.to_string()));
             }
             Expr::Bin(bin) => {
+                // Brand check: #x in obj
+                if bin.op == BinaryOp::In
+                    && let Expr::PrivateName(pn) = bin.left.as_ref()
+                {
+                    let name = format!("#{}", pn.name);
+                    let slot = ["getter:", "setter:"]
+                        .iter()
+                        .find_map(|prefix| {
+                            self.private_method_indices
+                                .get(&format!("{}{}", prefix, name))
+                                .copied()
+                        })
+                        .unwrap_or_else(|| self.private_slot(&name));
+                    self.gen_expr(&bin.right);
+                    self.instructions.push(OpCode::HasPrivateProp(slot));
+                    return;
+                }
                 self.gen_expr(&bin.left);
                 self.gen_expr(&bin.right);
-                match bin.op {
-                    BinaryOp::Add => self.instructions.push(OpCode::Add),
-                    BinaryOp::Sub => self.instructions.push(OpCode::Sub),
-                    BinaryOp::Mul => self.instructions.push(OpCode::Mul),
-                    BinaryOp::Div => self.instructions.push(OpCode::Div),
-                    BinaryOp::Mod => self.instructions.push(OpCode::Mod),
-                    BinaryOp::EqEq => self.instructions.push(OpCode::EqEq), // == (loose equality)
-                    BinaryOp::EqEqEq => self.instructions.push(OpCode::Eq), // === (strict equality)
-                    BinaryOp::NotEq => self.instructions.push(OpCode::NeEq), // != (loose inequality)
-                    BinaryOp::NotEqEq => self.instructions.push(OpCode::Ne), // !== (strict inequality)
-                    BinaryOp::Lt => self.instructions.push(OpCode::Lt),
-                    BinaryOp::LtEq => self.instructions.push(OpCode::LtEq),
-                    BinaryOp::Gt => self.instructions.push(OpCode::Gt),
-                    BinaryOp::GtEq => self.instructions.push(OpCode::GtEq),
-                    BinaryOp::LogicalAnd => self.instructions.push(OpCode::And),
-                    BinaryOp::LogicalOr => self.instructions.push(OpCode::Or),
-                    BinaryOp::InstanceOf => self.instructions.push(OpCode::InstanceOf),
-                    // Bitwise operators
-                    BinaryOp::BitAnd => self.instructions.push(OpCode::BitAnd),
-                    BinaryOp::BitOr => self.instructions.push(OpCode::BitOr),
-                    BinaryOp::BitXor => self.instructions.push(OpCode::Xor),
-                    BinaryOp::LShift => self.instructions.push(OpCode::ShiftLeft),
-                    BinaryOp::RShift => self.instructions.push(OpCode::ShiftRight),
-                    BinaryOp::ZeroFillRShift => self.instructions.push(OpCode::ShiftRightUnsigned),
-                    BinaryOp::Exp => self.instructions.push(OpCode::Pow),
-                    _ => println!("Warning: Operator {:?} not supported", bin.op),
+                match binary_opcode(bin.op) {
+                    Some(op) => self.instructions.push(op),
+                    None => println!("Warning: Operator {:?} not supported", bin.op),
                 }
             }
             Expr::Unary(unary) => {
@@ -1790,6 +2236,22 @@ impl Codegen {
                 }
             }
             Expr::Call(call_expr) => {
+                // inject(Class), unless the script has its own `inject`
+                if let Callee::Expr(callee_expr) = &call_expr.callee
+                    && let Expr::Ident(id) = callee_expr.as_ref()
+                    && &*id.sym == "inject"
+                    && !self.outer_scope_vars.contains("inject")
+                {
+                    self.gen_inject(&call_expr.args);
+                    return;
+                }
+                // Variant(...) of a tagged union builds the object in place
+                if let Callee::Expr(callee_expr) = &call_expr.callee
+                    && let Expr::Ident(id) = callee_expr.as_ref()
+                    && self.gen_variant(&id.sym, &call_expr.args)
+                {
+                    return;
+                }
                 if let Callee::Expr(callee_expr) = &call_expr.callee
                     && let Expr::Member(member) = callee_expr.as_ref()
                 {
@@ -1804,6 +2266,12 @@ impl Codegen {
                             .push(OpCode::CallMethod(id.sym.to_string(), call_expr.args.len()));
                         return;
                     }
+                    if let MemberProp::PrivateName(pn) = &member.prop {
+                        let slot = self.private_slot(&format!("#{}", pn.name));
+                        self.instructions
+                            .push(OpCode::CallPrivateMethod(slot, call_expr.args.len()));
+                        return;
+                    }
                 }
                 // detect if this is a 'require' call
                 if let Callee::Expr(expr) = &call_expr.callee
@@ -1817,6 +2285,9 @@ impl Codegen {
                 }
 
                 let arg_count = call_expr.args.len();
+                if let Callee::Expr(callee) = &call_expr.callee {
+                    self.check_arity(callee, &call_expr.args);
+                }
                 for arg in &call_expr.args {
                     self.gen_expr(&arg.expr);
                 }
@@ -1842,69 +2313,7 @@ impl Codegen {
                     }
                 }
             }
-            Expr::Assign(assign_expr) => {
-                // Handle different assignment targets
-                match &assign_expr.left {
-                    AssignTarget::Simple(simple) => match simple {
-                        SimpleAssignTarget::Ident(binding_ident) => {
-                            // Simple variable assignment: x = value
-                            self.gen_expr(&assign_expr.right);
-                            let name = binding_ident.id.sym.to_string();
-                            // JS assignment expressions evaluate to the assigned value.
-                            // Our `Store` opcode consumes the value, so `Dup` ensures one copy
-                            // remains on the stack for expression context (e.g. `a = 1;`).
-                            self.instructions.push(OpCode::Dup);
-                            self.instructions.push(OpCode::Store(name));
-                        }
-                        SimpleAssignTarget::Member(member_expr) => {
-                            // Member assignment: obj.prop = value or this.prop = value
-                            // Stack order for SetProp: [object, value] -> pops both, sets prop
-                            self.gen_expr(&member_expr.obj); // Push the object
-                            self.gen_expr(&assign_expr.right); // Push the value
-
-                            match &member_expr.prop {
-                                MemberProp::Ident(id) => {
-                                    // obj.prop = value
-                                    self.instructions.push(OpCode::SetProp(id.sym.to_string()));
-                                }
-                                MemberProp::Computed(computed) => {
-                                    // obj[key] = value
-                                    // Stack: [obj, value, key]
-                                    self.gen_expr(&computed.expr); // Push the key
-                                    self.instructions.push(OpCode::SetPropComputed);
-                                }
-                                MemberProp::PrivateName(pn) => {
-                                    // obj.#field = value - use SetPrivateProp
-                                    let field_name = format!("#{}", pn.name);
-
-                                    // If this is the first time seeing this private field, assign it an index
-                                    if !self.private_field_indices.contains_key(&field_name) {
-                                        let new_index = self.private_field_indices.len();
-                                        self.private_field_indices
-                                            .insert(field_name.clone(), new_index);
-                                    }
-
-                                    if let Some(field_index) =
-                                        self.private_field_indices.get(&field_name)
-                                    {
-                                        self.instructions
-                                            .push(OpCode::SetPrivateProp(*field_index));
-                                    } else {
-                                        println!(
-                                            "Warning: Private field '{}' not found",
-                                            field_name
-                                        );
-                                        // Pop the value
-                                        self.instructions.push(OpCode::Pop);
-                                    }
-                                }
-                            }
-                        }
-                        _ => println!("Warning: Complex assignment target not supported."),
-                    },
-                    _ => println!("Warning: Complex assignment targets not supported yet."),
-                }
-            }
+            Expr::Assign(assign_expr) => self.gen_assign(assign_expr, true),
             Expr::Object(obj_lit) => {
                 self.instructions.push(OpCode::NewObject);
 
@@ -1925,6 +2334,7 @@ impl Codegen {
                                             .as_str()
                                             .expect("Invalid string key")
                                             .to_string(),
+                                        PropName::Num(num) => num.value.to_string(),
                                         _ => continue,
                                     };
 
@@ -1975,6 +2385,16 @@ impl Codegen {
                 }
             }
             Expr::Member(member) => {
+                // Members of a const enum are inlined
+                if let Some(value) = self.const_enum_member(member) {
+                    self.instructions.push(OpCode::Push(value));
+                    return;
+                }
+                // So are build variables
+                if let Some(value) = self.build_env_value(member) {
+                    self.instructions.push(OpCode::Push(value));
+                    return;
+                }
                 // Regular obj.prop access
                 // 1. Load the Object/Array
                 self.gen_expr(&member.obj);
@@ -1989,51 +2409,27 @@ impl Codegen {
                         self.gen_expr(&computed.expr); // Push the index expression
                         self.instructions.push(OpCode::GetPropComputed);
                     }
-                    // Handle #privateField
+                    // Handle #privateField, #privateMethod and private getters
                     MemberProp::PrivateName(pn) => {
                         // Private field name in swc doesn't include the #
-                        // We need to add it to our tracking
-                        let field_name = format!("#{}", pn.name);
-
-                        // If this is the first time seeing this private field in the current class context,
-                        // assign it an index
-                        if !self.private_field_indices.contains_key(&field_name) {
-                            let new_index = self.private_field_indices.len();
-                            self.private_field_indices
-                                .insert(field_name.clone(), new_index);
-                        }
-
-                        if let Some(field_index) = self.private_field_indices.get(&field_name) {
-                            self.instructions.push(OpCode::GetPrivateProp(*field_index));
-                        } else {
-                            println!("Warning: Private field '{}' not found", field_name);
-                            self.instructions.push(OpCode::Push(JsValue::Undefined));
-                        }
+                        self.gen_private_get(&format!("#{}", pn.name));
                     }
                 }
             }
             Expr::This(_) => {
-                self.instructions.push(OpCode::LoadThis);
+                self.gen_this();
             }
-            Expr::MetaProp(meta_prop) => {
-                match meta_prop.kind {
-                    MetaPropKind::NewTarget => {
-                        // new.target - push the constructor that was called with new
-                        self.instructions.push(OpCode::NewTarget);
-                    }
-                    MetaPropKind::ImportMeta => {
-                        // import.meta - not yet supported
-                        self.instructions.push(OpCode::Push(JsValue::Undefined));
-                    }
+            Expr::MetaProp(meta_prop) => match meta_prop.kind {
+                MetaPropKind::NewTarget => {
+                    self.gen_new_target();
                 }
-            }
+                MetaPropKind::ImportMeta => {
+                    self.instructions.push(OpCode::ImportMeta);
+                }
+            },
             Expr::New(new_expr) => {
                 // new Foo(arg1, arg2) compiles to:
-                // 1. Create new empty object that will be `this`
-                self.instructions.push(OpCode::NewObject);
-                self.instructions.push(OpCode::Dup); // Keep a copy for return value
-
-                // 2. Push arguments
+                // 1. Push arguments (Construct makes the `this` object)
                 let arg_count = new_expr.args.as_ref().map(|a| a.len()).unwrap_or(0);
                 if let Some(args) = &new_expr.args {
                     for arg in args {
@@ -2041,10 +2437,10 @@ impl Codegen {
                     }
                 }
 
-                // 3. Push the constructor function
+                // 2. Push the constructor function
                 self.gen_expr(&new_expr.callee);
 
-                // 4. Call with construct semantics
+                // 3. Call with construct semantics
                 self.instructions.push(OpCode::Construct(arg_count));
             }
             Expr::Paren(paren_expr) => {
@@ -2055,6 +2451,15 @@ impl Codegen {
                 // Conditional expression: condition ? consequent : alternate
                 // Stack: [condition, consequent, alternate] -> [result]
 
+                if let Some(taken) = self.static_condition(&cond_expr.test) {
+                    self.gen_expr(if taken {
+                        &cond_expr.cons
+                    } else {
+                        &cond_expr.alt
+                    });
+                    return;
+                }
+
                 // Compile condition
                 self.gen_expr(&cond_expr.test);
 
@@ -2131,125 +2536,14 @@ impl Codegen {
                     self.instructions.push(OpCode::Await);
                 }
             }
-            Expr::Update(update_expr) => {
-                if let Expr::Ident(id) = update_expr.arg.as_ref() {
-                    let name = id.sym.to_string();
-                    self.instructions.push(OpCode::Load(name.clone()));
-                    if update_expr.prefix {
-                        self.instructions.push(OpCode::Push(JsValue::Number(1.0)));
-                        if update_expr.op == UpdateOp::PlusPlus {
-                            self.instructions.push(OpCode::Add);
-                        } else {
-                            self.instructions.push(OpCode::Sub);
-                        }
-                        self.instructions.push(OpCode::Dup);
-                        self.instructions.push(OpCode::Store(name));
-                    } else {
-                        self.instructions.push(OpCode::Dup);
-                        self.instructions.push(OpCode::Push(JsValue::Number(1.0)));
-                        if update_expr.op == UpdateOp::PlusPlus {
-                            self.instructions.push(OpCode::Add);
-                        } else {
-                            self.instructions.push(OpCode::Sub);
-                        }
-                        self.instructions.push(OpCode::Store(name));
-                    }
-                } else if let Expr::Member(member) = update_expr.arg.as_ref() {
-                    // Member expression: obj.prop++ / ++obj.prop / obj[key]++ / ++obj[key]
-                    let op = update_expr.op;
-                    if update_expr.prefix {
-                        // ++obj.prop: compute new value, store it, leave new value on stack
-                        // Stack: [obj, obj] -> GetProp -> [obj, old] -> +1 -> [obj, new] -> SetProp -> []
-                        // Then reload to get new value on stack
-                        self.gen_expr(&member.obj);
-                        self.instructions.push(OpCode::Dup);
-                        match &member.prop {
-                            MemberProp::Ident(id) => {
-                                self.instructions.push(OpCode::GetProp(id.sym.to_string()));
-                            }
-                            MemberProp::Computed(c) => {
-                                self.gen_expr(&c.expr);
-                                self.instructions.push(OpCode::GetPropComputed);
-                            }
-                            _ => {}
-                        }
-                        self.instructions.push(OpCode::Push(JsValue::Number(1.0)));
-                        if op == UpdateOp::PlusPlus {
-                            self.instructions.push(OpCode::Add);
-                        } else {
-                            self.instructions.push(OpCode::Sub);
-                        }
-                        // Stack: [obj, new_val] -> SetProp -> []
-                        match &member.prop {
-                            MemberProp::Ident(id) => {
-                                self.instructions.push(OpCode::SetProp(id.sym.to_string()));
-                            }
-                            MemberProp::Computed(c) => {
-                                self.gen_expr(&c.expr);
-                                self.instructions.push(OpCode::SetPropComputed);
-                            }
-                            _ => {}
-                        }
-                        // Reload the new value
-                        self.gen_expr(&member.obj);
-                        match &member.prop {
-                            MemberProp::Ident(id) => {
-                                self.instructions.push(OpCode::GetProp(id.sym.to_string()));
-                            }
-                            MemberProp::Computed(c) => {
-                                self.gen_expr(&c.expr);
-                                self.instructions.push(OpCode::GetPropComputed);
-                            }
-                            _ => {}
-                        }
-                    } else {
-                        // obj.prop++: get old value, save it, compute new, store back
-                        // Stack: [obj, obj] -> GetProp -> [obj, old] -> Swap -> [old, obj]
-                        // -> Dup -> [old, obj, obj] -> GetProp -> [old, obj, old2]
-                        // -> +1 -> [old, obj, new] -> SetProp -> [old]
-                        self.gen_expr(&member.obj);
-                        self.instructions.push(OpCode::Dup);
-                        match &member.prop {
-                            MemberProp::Ident(id) => {
-                                self.instructions.push(OpCode::GetProp(id.sym.to_string()));
-                            }
-                            MemberProp::Computed(c) => {
-                                self.gen_expr(&c.expr);
-                                self.instructions.push(OpCode::GetPropComputed);
-                            }
-                            _ => {}
-                        }
-                        // Stack: [obj, old_val] -> Swap -> [old_val, obj]
-                        self.instructions.push(OpCode::Swap);
-                        self.instructions.push(OpCode::Dup);
-                        match &member.prop {
-                            MemberProp::Ident(id) => {
-                                self.instructions.push(OpCode::GetProp(id.sym.to_string()));
-                            }
-                            MemberProp::Computed(c) => {
-                                self.gen_expr(&c.expr);
-                                self.instructions.push(OpCode::GetPropComputed);
-                            }
-                            _ => {}
-                        }
-                        self.instructions.push(OpCode::Push(JsValue::Number(1.0)));
-                        if op == UpdateOp::PlusPlus {
-                            self.instructions.push(OpCode::Add);
-                        } else {
-                            self.instructions.push(OpCode::Sub);
-                        }
-                        // Stack: [old_val, obj, new_val] -> SetProp -> [old_val]
-                        match &member.prop {
-                            MemberProp::Ident(id) => {
-                                self.instructions.push(OpCode::SetProp(id.sym.to_string()));
-                            }
-                            MemberProp::Computed(c) => {
-                                self.gen_expr(&c.expr);
-                                self.instructions.push(OpCode::SetPropComputed);
-                            }
-                            _ => {}
-                        }
+            Expr::Update(update_expr) => self.gen_update(update_expr, true),
+            Expr::Seq(seq) => {
+                // a, b, c: evaluated in order, the last one is the value
+                if let Some((last, rest)) = seq.exprs.split_last() {
+                    for expr in rest {
+                        self.gen_effect(expr);
                     }
+                    self.gen_expr(last);
                 }
             }
             Expr::TsAs(ts_as) => {
@@ -2264,10 +2558,315 @@ impl Codegen {
                 // TypeScript non-null assertion `expr!` - evaluate inner expression
                 self.gen_expr(&ts_non_null.expr);
             }
+            Expr::Class(class_expr) => {
+                let name = class_expr.ident.as_ref().map(|id| id.sym.to_string());
+                self.gen_class(&class_expr.class, name.as_deref());
+                // A named class expression can refer to itself from its methods
+                if let Some(name) = name {
+                    self.instructions.push(OpCode::Dup);
+                    self.instructions.push(OpCode::Let(name));
+                }
+            }
             _ => {}
         }
     }
 
+    /// Wrap the value on top of the stack in `Promise.resolve(value)`, the
+    /// result an async function returns
+    fn gen_promise_resolve(&mut self) {
+        self.instructions.push(OpCode::Load("Promise".to_string()));
+        self.instructions
+            .push(OpCode::GetProp("resolve".to_string()));
+        // Stack: [value, resolveFn]; Call pops the callee, then its argument
+        self.instructions.push(OpCode::Call(1));
+    }
+
+    /// Evaluate `expr` for its side effects only. Assignments and updates
+    /// skip producing a value nobody reads.
+    fn gen_effect(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Assign(assign) => self.gen_assign(assign, false),
+            Expr::Update(update) => self.gen_update(update, false),
+            _ => {
+                self.gen_expr(expr);
+                self.instructions.push(OpCode::Pop);
+            }
+        }
+    }
+
+    /// `target = value`, `target op= value` and the short-circuiting
+    /// `&&=`, `||=` and `??=`. With `keep_value` the assigned value (or, when
+    /// a logical assignment skips, the target's value) is left on the stack.
+    fn gen_assign(&mut self, assign: &AssignExpr, keep_value: bool) {
+        let AssignTarget::Simple(target) = &assign.left else {
+            println!("Warning: Complex assignment targets not supported yet.");
+            if keep_value {
+                self.instructions.push(OpCode::Push(JsValue::Undefined));
+            }
+            return;
+        };
+        if let SimpleAssignTarget::Ident(id) = target {
+            // A reassigned function may take any number of arguments
+            self.function_arities.remove(id.sym.as_str());
+        }
+
+        if assign.op == AssignOp::Assign
+            && !keep_value
+            && let SimpleAssignTarget::Member(member) = target
+        {
+            // Nothing reads the result, so the object and key can stay on
+            // the stack instead of going through a place
+            self.gen_expr(&member.obj);
+            match &member.prop {
+                MemberProp::Ident(id) => {
+                    self.gen_expr(&assign.right);
+                    self.instructions.push(OpCode::SetProp(id.sym.to_string()));
+                }
+                MemberProp::Computed(computed) => {
+                    // The key is evaluated before the value
+                    // Stack: [obj, key, value] -> [obj, value, key]
+                    self.gen_expr(&computed.expr);
+                    self.gen_expr(&assign.right);
+                    self.instructions.push(OpCode::Swap);
+                    self.instructions.push(OpCode::SetPropComputed);
+                }
+                MemberProp::PrivateName(pn) => {
+                    self.gen_expr(&assign.right);
+                    self.gen_private_set(&format!("#{}", pn.name));
+                }
+            }
+            return;
+        }
+
+        let Some(place) = self.gen_place(target) else {
+            println!("Warning: Complex assignment target not supported.");
+            if keep_value {
+                self.instructions.push(OpCode::Push(JsValue::Undefined));
+            }
+            return;
+        };
+
+        match assign.op {
+            AssignOp::Assign => {
+                self.gen_expr(&assign.right);
+                self.gen_place_set(&place, keep_value);
+            }
+            AssignOp::AndAssign | AssignOp::OrAssign | AssignOp::NullishAssign => {
+                // The value is only evaluated, and the target only written,
+                // when the current value passes the test
+                self.gen_place_get(&place);
+                self.instructions.push(OpCode::Dup);
+                let skip = match assign.op {
+                    AssignOp::AndAssign => {
+                        self.instructions.push(OpCode::JumpIfFalse(0));
+                        self.instructions.len() - 1
+                    }
+                    AssignOp::OrAssign => {
+                        let assign_addr = self.instructions.len() + 2;
+                        self.instructions.push(OpCode::JumpIfFalse(assign_addr));
+                        self.instructions.push(OpCode::Jump(0));
+                        self.instructions.len() - 1
+                    }
+                    _ => {
+                        // `== null` holds for null and undefined
+                        self.instructions.push(OpCode::Push(JsValue::Null));
+                        self.instructions.push(OpCode::EqEq);
+                        self.instructions.push(OpCode::JumpIfFalse(0));
+                        self.instructions.len() - 1
+                    }
+                };
+                self.instructions.push(OpCode::Pop);
+                self.gen_expr(&assign.right);
+                self.gen_place_set(&place, true);
+                self.patch_jump(skip);
+                if !keep_value {
+                    self.instructions.push(OpCode::Pop);
+                }
+            }
+            op => {
+                self.gen_place_get(&place);
+                self.gen_expr(&assign.right);
+                match op.to_update().and_then(binary_opcode) {
+                    Some(op) => self.instructions.push(op),
+                    None => println!("Warning: Operator {:?} not supported", op),
+                }
+                self.gen_place_set(&place, keep_value);
+            }
+        }
+    }
+
+    /// `++x`, `x++`, `--x` and `x--`. With `keep_value` the prefix forms
+    /// leave the new value on the stack and the postfix forms the old one.
+    fn gen_update(&mut self, update: &UpdateExpr, keep_value: bool) {
+        let Some(place) = self.gen_expr_place(&update.arg) else {
+            println!("Warning: Invalid update target.");
+            if keep_value {
+                self.instructions.push(OpCode::Push(JsValue::Undefined));
+            }
+            return;
+        };
+        self.gen_place_get(&place);
+        let keep_old = keep_value && !update.prefix;
+        if keep_old {
+            self.instructions.push(OpCode::Dup);
+        }
+        self.instructions.push(OpCode::Push(JsValue::Number(1.0)));
+        if update.op == UpdateOp::PlusPlus {
+            self.instructions.push(OpCode::Add);
+        } else {
+            self.instructions.push(OpCode::Sub);
+        }
+        self.gen_place_set(&place, keep_value && !keep_old);
+    }
+
+    /// Evaluate the object and key of an assignment target
+    fn gen_place(&mut self, target: &SimpleAssignTarget) -> Option<Place> {
+        match target {
+            SimpleAssignTarget::Ident(binding_ident) => {
+                Some(Place::Var(binding_ident.id.sym.to_string()))
+            }
+            SimpleAssignTarget::Member(member) => Some(self.gen_member_place(member)),
+            SimpleAssignTarget::Paren(paren) => self.gen_expr_place(&paren.expr),
+            SimpleAssignTarget::TsAs(ts_as) => self.gen_expr_place(&ts_as.expr),
+            SimpleAssignTarget::TsNonNull(ts_non_null) => self.gen_expr_place(&ts_non_null.expr),
+            SimpleAssignTarget::TsTypeAssertion(ts_assert) => self.gen_expr_place(&ts_assert.expr),
+            SimpleAssignTarget::TsSatisfies(ts_satisfies) => {
+                self.gen_expr_place(&ts_satisfies.expr)
+            }
+            _ => None,
+        }
+    }
+
+    /// `gen_place` for an expression used as a target (`x` in `x++`)
+    fn gen_expr_place(&mut self, expr: &Expr) -> Option<Place> {
+        match expr {
+            Expr::Ident(id) => Some(Place::Var(id.sym.to_string())),
+            Expr::Member(member) => Some(self.gen_member_place(member)),
+            Expr::Paren(paren) => self.gen_expr_place(&paren.expr),
+            Expr::TsAs(ts_as) => self.gen_expr_place(&ts_as.expr),
+            Expr::TsNonNull(ts_non_null) => self.gen_expr_place(&ts_non_null.expr),
+            Expr::TsTypeAssertion(ts_assert) => self.gen_expr_place(&ts_assert.expr),
+            Expr::TsSatisfies(ts_satisfies) => self.gen_expr_place(&ts_satisfies.expr),
+            _ => None,
+        }
+    }
+
+    fn gen_member_place(&mut self, member: &MemberExpr) -> Place {
+        // Named after the first instruction, like the switch discriminant,
+        // so places nested in the key get their own locals
+        let id = self.instructions.len();
+        let obj = format!("__ref_{}__", id);
+        self.gen_expr(&member.obj);
+        self.instructions.push(OpCode::Let(obj.clone()));
+        let prop = match &member.prop {
+            MemberProp::Ident(ident) => PlaceProp::Named(ident.sym.to_string()),
+            MemberProp::Computed(computed) => {
+                let key = format!("__key_{}__", id);
+                self.gen_expr(&computed.expr);
+                self.instructions.push(OpCode::Let(key.clone()));
+                PlaceProp::Computed(key)
+            }
+            MemberProp::PrivateName(pn) => PlaceProp::Private(format!("#{}", pn.name)),
+        };
+        Place::Member { obj, prop }
+    }
+
+    /// Push the current value of `place`
+    fn gen_place_get(&mut self, place: &Place) {
+        match place {
+            Place::Var(name) => self.instructions.push(OpCode::Load(name.clone())),
+            Place::Member { obj, prop } => {
+                self.instructions.push(OpCode::Load(obj.clone()));
+                match prop {
+                    PlaceProp::Named(name) => self.instructions.push(OpCode::GetProp(name.clone())),
+                    PlaceProp::Computed(key) => {
+                        self.instructions.push(OpCode::Load(key.clone()));
+                        self.instructions.push(OpCode::GetPropComputed);
+                    }
+                    PlaceProp::Private(name) => self.gen_private_get(name),
+                }
+            }
+        }
+    }
+
+    /// Pop a value into `place`, leaving a copy on the stack with `keep_value`
+    fn gen_place_set(&mut self, place: &Place, keep_value: bool) {
+        if keep_value {
+            self.instructions.push(OpCode::Dup);
+        }
+        match place {
+            Place::Var(name) => self.instructions.push(OpCode::Store(name.clone())),
+            Place::Member { obj, prop } => {
+                // Stack: [value] -> [value, obj] -> [obj, value]
+                self.instructions.push(OpCode::Load(obj.clone()));
+                self.instructions.push(OpCode::Swap);
+                match prop {
+                    PlaceProp::Named(name) => self.instructions.push(OpCode::SetProp(name.clone())),
+                    PlaceProp::Computed(key) => {
+                        self.instructions.push(OpCode::Load(key.clone()));
+                        self.instructions.push(OpCode::SetPropComputed);
+                    }
+                    PlaceProp::Private(name) => self.gen_private_set(name),
+                }
+            }
+        }
+    }
+
+    /// `obj.#x`: [obj] -> [value], through the private getter if there is one
+    fn gen_private_get(&mut self, name: &str) {
+        if let Some(slot) = self.private_method_indices.get(&format!("getter:{}", name)) {
+            self.instructions.push(OpCode::CallPrivateMethod(*slot, 0));
+        } else if self
+            .private_method_indices
+            .contains_key(&format!("setter:{}", name))
+        {
+            // Setter without a getter reads as undefined
+            self.instructions.push(OpCode::Pop);
+            self.instructions.push(OpCode::Push(JsValue::Undefined));
+        } else {
+            let slot = self.private_slot(name);
+            self.instructions.push(OpCode::GetPrivateProp(slot));
+        }
+    }
+
+    /// `obj.#x = value`: [obj, value] -> [], through the private setter if
+    /// there is one
+    fn gen_private_set(&mut self, name: &str) {
+        if let Some(slot) = self.private_method_indices.get(&format!("setter:{}", name)) {
+            // Call it as obj.#x(value), dropping its result
+            // Stack: [obj, value] -> [value, obj]
+            self.instructions.push(OpCode::Swap);
+            self.instructions.push(OpCode::CallPrivateMethod(*slot, 1));
+            self.instructions.push(OpCode::Pop);
+        } else {
+            let slot = self.private_slot(name);
+            self.instructions.push(OpCode::SetPrivateProp(slot));
+        }
+    }
+
+    /// Point the jump at `idx` to the next instruction
+    fn patch_jump(&mut self, idx: usize) {
+        let target = self.instructions.len();
+        if let OpCode::Jump(addr) | OpCode::JumpIfFalse(addr) = &mut self.instructions[idx] {
+            *addr = target;
+        }
+    }
+
+    /// Private storage slot of `key`: `#x` for a field or method, or
+    /// `getter:#x` / `setter:#x` for an accessor. Methods and accessors get
+    /// their slots when the class body is collected; a field gets one the
+    /// first time it is seen.
+    fn private_slot(&mut self, key: &str) -> usize {
+        if let Some(slot) = self.private_method_indices.get(key) {
+            return *slot;
+        }
+        let next_slot = self.private_field_indices.len() + self.private_method_indices.len();
+        *self
+            .private_field_indices
+            .entry(key.to_string())
+            .or_insert(next_slot)
+    }
+
     fn gen_class(&mut self, class: &Class, name: Option<&str>) {
         // Check if this class has a superclass
         let has_super = class.super_class.is_some();
@@ -2278,8 +2877,14 @@ impl Codegen {
 
         // Handle class decorators
         // Compile class decorators: @decorator class Foo {}
-        // Each decorator is applied to the class after it's created
-        let class_decorators: Vec<&Decorator> = class.decorators.iter().collect();
+        // Each decorator is applied to the class after it's created.
+        // `@Injectable` is resolved by the compiler instead (see inject.rs)
+        self.register_injectable(class, name);
+        let class_decorators: Vec<&Decorator> = class
+            .decorators
+            .iter()
+            .filter(|d| !inject::is_injectable_decorator(d))
+            .collect();
 
         // For now, we just store decorator count - actual application happens after class is created
         let _decorator_count = class_decorators.len();
@@ -2288,11 +2893,17 @@ impl Codegen {
         // Private fields would be handled as regular fields with special naming
 
         // Collect constructor params and body
-        let mut constructor_params: Vec<String> = Vec::new();
+        let mut constructor_params: Vec<Pat> = Vec::new();
+        // `constructor(private repo: Repo)` also stores the argument on `this`
+        let mut parameter_props: Vec<String> = Vec::new();
         let mut constructor_body: Option<&BlockStmt> = None;
 
         // Collect private field declarations for initialization
-        let mut private_field_decls: Vec<(String, &Expr)> = Vec::new();
+        let mut private_field_decls: Vec<(String, Option<&Expr>)> = Vec::new();
+
+        // Collect private methods and accessors, keyed like the slots in
+        // `private_method_indices`
+        let mut private_methods: Vec<(String, &PrivateMethod)> = Vec::new();
 
         // Collect class property declarations
         let mut class_prop_decls: Vec<(String, &Expr)> = Vec::new();
@@ -2302,15 +2913,17 @@ impl Codegen {
                 for param in &ctor.params {
                     match param {
                         ParamOrTsParamProp::Param(p) => {
-                            if let Pat::Ident(id) = &p.pat {
-                                constructor_params.push(id.id.sym.to_string());
-                            }
+                            constructor_params.push(p.pat.clone());
                         }
-                        ParamOrTsParamProp::TsParamProp(ts_prop) => {
-                            if let TsParamPropParam::Ident(id) = &ts_prop.param {
-                                constructor_params.push(id.id.sym.to_string());
+                        ParamOrTsParamProp::TsParamProp(ts_prop) => match &ts_prop.param {
+                            TsParamPropParam::Ident(id) => {
+                                constructor_params.push(Pat::Ident(id.clone()));
+                                parameter_props.push(id.id.sym.to_string());
                             }
-                        }
+                            TsParamPropParam::Assign(assign) => {
+                                constructor_params.push(Pat::Assign(assign.clone()));
+                            }
+                        },
                     }
                 }
                 constructor_body = ctor.body.as_ref();
@@ -2319,18 +2932,35 @@ impl Codegen {
             // Collect private field declarations
             if let ClassMember::PrivateProp(prop) = member {
                 let field_name = format!("#{}", prop.key.name);
-                if !self.private_field_indices.contains_key(&field_name) {
-                    let new_index = self.private_field_indices.len();
-                    self.private_field_indices
-                        .insert(field_name.clone(), new_index);
-                }
-                if let Some(value) = &prop.value {
-                    private_field_decls.push((field_name, value.as_ref()));
-                }
-            }
-
-            // Collect public class property declarations
-            if let ClassMember::ClassProp(prop) = member {
+                self.private_slot(&field_name);
+                // Fields without an initializer still start out as undefined,
+                // so that `#x in obj` holds for every instance
+                private_field_decls.push((field_name, prop.value.as_deref()));
+            }
+
+            // Collect private methods, getters and setters; each gets its own slot
+            if let ClassMember::PrivateMethod(method) = member
+                && !method.is_static
+            {
+                let name = format!("#{}", method.key.name);
+                let key = match method.kind {
+                    MethodKind::Getter => format!("getter:{}", name),
+                    MethodKind::Setter => format!("setter:{}", name),
+                    MethodKind::Method => name,
+                };
+                let next_slot =
+                    self.private_field_indices.len() + self.private_method_indices.len();
+                self.private_method_indices
+                    .entry(key.clone())
+                    .or_insert(next_slot);
+                private_methods.push((key, method));
+            }
+
+            // Collect public class property declarations; static ones are
+            // initialized on the wrapper after the methods are defined
+            if let ClassMember::ClassProp(prop) = member
+                && !prop.is_static
+            {
                 let prop_name = match &prop.key {
                     PropName::Ident(id) => id.sym.to_string(),
                     PropName::Str(s) => s.value.to_string_lossy().into_owned(),
@@ -2346,11 +2976,48 @@ impl Codegen {
         // Store private field indices for use in constructor
         let _private_field_count = 0; // Will be set dynamically as we encounter private fields
 
+        // Compile private method bodies once, ahead of the constructor, which
+        // copies them into each instance's private slots
+        let mut private_method_slots: Vec<(usize, usize)> = Vec::new(); // (slot, address)
+        if !private_methods.is_empty() {
+            let methods_jump_idx = self.instructions.len();
+            self.instructions.push(OpCode::Jump(0));
+
+            for (key, method) in &private_methods {
+                let slot = self.private_method_indices[key];
+                let address = self.instructions.len();
+                let saved_in_function = self.in_function;
+                self.in_function = true;
+                self.push_binding_scope();
+                let saved_lexical_this = std::mem::replace(&mut self.lexical_this, false);
+
+                self.gen_params(method.function.params.iter().map(|param| &param.pat));
+                if let Some(body) = &method.function.body {
+                    for stmt in &body.stmts {
+                        self.gen_stmt(stmt);
+                    }
+                }
+                self.instructions.push(OpCode::LoadThis);
+                self.instructions.push(OpCode::Return);
+                self.in_function = saved_in_function;
+                self.pop_binding_scope();
+                self.lexical_this = saved_lexical_this;
+
+                private_method_slots.push((slot, address));
+            }
+
+            let after_methods = self.instructions.len();
+            if let OpCode::Jump(ref mut addr) = self.instructions[methods_jump_idx] {
+                *addr = after_methods;
+            }
+        }
+
         // Create constructor function
         let constructor_start = self.instructions.len() + 2;
         self.instructions.push(OpCode::Push(JsValue::Function {
             address: constructor_start,
             env: None,
+            bound: None,
         }));
 
         // Jump over constructor body
@@ -2360,10 +3027,10 @@ impl Codegen {
         // Constructor body
         let saved_in_function = self.in_function;
         self.in_function = true;
+        self.push_binding_scope();
+        let saved_lexical_this = std::mem::replace(&mut self.lexical_this, false);
 
-        for param in constructor_params.iter().rev() {
-            self.instructions.push(OpCode::Let(param.clone()));
-        }
+        self.gen_params(&constructor_params);
 
         // Set up private field storage for this instance
         // Create storage array for private fields (one entry per field)
@@ -2378,8 +3045,8 @@ impl Codegen {
             self.instructions
                 .push(OpCode::Push(JsValue::Number(i as f64)));
             // Stack: [storage, storage, index]
-            // Create object (this will be the "WeakMap" for this field)
-            self.instructions.push(OpCode::NewObject);
+            // Create the WeakMap for this field
+            self.instructions.push(OpCode::NewWeakMap);
             // Stack: [storage, storage, index, field_map]
             // Swap to get [storage, storage, field_map, index]
             self.instructions.push(OpCode::Swap);
@@ -2390,23 +3057,46 @@ impl Codegen {
         }
 
         // Store the private storage array in this.__private_storage__
+        self.instructions.push(OpCode::Dup);
+        // Stack: [storage, storage]
         self.instructions.push(OpCode::LoadThis);
-        // Stack: [this]
+        // Stack: [storage, storage, this]
         self.instructions.push(OpCode::Swap);
-        // Stack: [storage_array, this]
+        // Stack: [storage, this, storage]
         self.instructions
             .push(OpCode::SetProp("__private_storage__".to_string()));
-        // Stack: []
+        // Stack: [storage]
 
         // Store the private storage array in a temp for later use
         self.instructions
             .push(OpCode::Let("__private_storage__".to_string()));
         // Stack: []
 
+        // Install private methods before any field initializer can call them
+        for (slot, address) in &private_method_slots {
+            self.instructions.push(OpCode::LoadThis);
+            self.instructions.push(OpCode::Push(JsValue::Function {
+                address: *address,
+                env: None,
+                bound: None,
+            }));
+            // Stack: [this, method]
+            self.instructions.push(OpCode::SetPrivateProp(*slot));
+        }
+
+        for param in &parameter_props {
+            self.instructions.push(OpCode::LoadThis);
+            self.instructions.push(OpCode::Load(param.clone()));
+            self.instructions.push(OpCode::SetProp(param.clone()));
+        }
+
         // Initialize private field declarations
         for (field_name, value_expr) in &private_field_decls {
             // Generate the value
-            self.gen_expr(value_expr);
+            match value_expr {
+                Some(value_expr) => self.gen_expr(value_expr),
+                None => self.instructions.push(OpCode::Push(JsValue::Undefined)),
+            }
             // Stack: [value]
 
             // Get the field index
@@ -2448,6 +3138,8 @@ impl Codegen {
         self.instructions.push(OpCode::LoadThis);
         self.instructions.push(OpCode::Return);
         self.in_function = saved_in_function;
+        self.pop_binding_scope();
+        self.lexical_this = saved_lexical_this;
 
         // Backpatch jump
         let after_constructor = self.instructions.len();
@@ -2568,6 +3260,14 @@ impl Codegen {
             self.instructions
                 .push(OpCode::SetProp("__super__".to_string()));
             // Stack: []
+
+            // Set wrapper.__proto__ = parent so static members are inherited
+            self.instructions
+                .push(OpCode::Load("__wrapper__".to_string()));
+            self.instructions
+                .push(OpCode::Load("__parent__".to_string()));
+            self.instructions
+                .push(OpCode::SetProp("__proto__".to_string()));
         }
 
         // Add methods to prototype
@@ -2599,13 +3299,27 @@ impl Codegen {
                             MethodKind::Method => (name, false, false),
                         }
                     }
-                    _ => continue, // Skip computed names for now
+                    PropName::Computed(computed) => {
+                        // Only `[Symbol.operator.plus]`-style names for now
+                        let Some(name) = operator_symbol(&computed.expr) else {
+                            continue;
+                        };
+                        match crate::vm::operators::operator_key(name) {
+                            Some(key) => (key, false, false),
+                            None => {
+                                self.errors
+                                    .push(format!("unknown operator Symbol.operator.{}", name));
+                                continue;
+                            }
+                        }
+                    }
+                    _ => continue,
                 };
 
                 let unique_name = format!("__method_{}", prop_name.replace(":", "_"));
 
                 // Get parameters - getters have no params, setters have one param (value)
-                let params: Vec<String> = if is_getter {
+                let params: Vec<&Pat> = if is_getter {
                     Vec::new() // Getters take no parameters
                 } else if is_setter {
                     // Setters take one parameter (the value)
@@ -2613,27 +3327,11 @@ impl Codegen {
                         .function
                         .params
                         .iter()
-                        .filter_map(|p| {
-                            if let Pat::Ident(id) = &p.pat {
-                                Some(id.id.sym.to_string())
-                            } else {
-                                None
-                            }
-                        })
+                        .take(1)
+                        .map(|p| &p.pat)
                         .collect()
                 } else {
-                    method
-                        .function
-                        .params
-                        .iter()
-                        .filter_map(|p| {
-                            if let Pat::Ident(id) = &p.pat {
-                                Some(id.id.sym.to_string())
-                            } else {
-                                None
-                            }
-                        })
-                        .collect()
+                    method.function.params.iter().map(|p| &p.pat).collect()
                 };
 
                 // Push function placeholder
@@ -2641,6 +3339,7 @@ impl Codegen {
                 self.instructions.push(OpCode::Push(JsValue::Function {
                     address: method_start,
                     env: None,
+                    bound: None,
                 }));
 
                 // Jump over method body
@@ -2650,10 +3349,10 @@ impl Codegen {
                 // Compile method body
                 let saved_in_function = self.in_function;
                 self.in_function = true;
+                self.push_binding_scope();
+                let saved_lexical_this = std::mem::replace(&mut self.lexical_this, false);
 
-                for param in params.iter().rev() {
-                    self.instructions.push(OpCode::Let(param.clone()));
-                }
+                self.gen_params(params);
 
                 if let Some(body) = &method.function.body {
                     for stmt in &body.stmts {
@@ -2664,6 +3363,8 @@ impl Codegen {
                 self.instructions.push(OpCode::LoadThis);
                 self.instructions.push(OpCode::Return);
                 self.in_function = saved_in_function;
+                self.pop_binding_scope();
+                self.lexical_this = saved_lexical_this;
 
                 // Backpatch method jump
                 let after_method = self.instructions.len();
@@ -2674,17 +3375,24 @@ impl Codegen {
                 // Store method in a temp
                 self.instructions.push(OpCode::Let(unique_name.clone()));
 
-                // Set prototype.method = method_function (or getter/setter)
-                self.instructions
-                    .push(OpCode::Load("__proto__".to_string()));
-                // Stack: [prototype]
+                // Set prototype.method = method_function (or getter/setter);
+                // static methods go on the wrapper itself
+                let target = if method.is_static {
+                    "__wrapper__"
+                } else {
+                    "__proto__"
+                };
+                self.instructions.push(OpCode::Load(target.to_string()));
+                // Stack: [target]
                 self.instructions.push(OpCode::Load(unique_name.clone()));
-                // Stack: [prototype, method]
+                // Stack: [target, method]
                 self.instructions.push(OpCode::SetProp(prop_name));
                 // Stack: []
             }
         }
 
+        self.gen_static_init(class, name);
+
         // Restore wrapper to stack for return
         self.instructions
             .push(OpCode::Load("__wrapper__".to_string()));
@@ -2701,4 +3409,321 @@ impl Codegen {
             // Stack: [decorated_wrapper]
         }
     }
+
+    /// Run static field initializers and `static { }` blocks in source order,
+    /// with `this` bound to the class wrapper. They are compiled into one
+    /// function that is called as a method of the wrapper and then removed.
+    fn gen_static_init(&mut self, class: &Class, name: Option<&str>) {
+        let has_static_init = class.body.iter().any(|member| match member {
+            ClassMember::ClassProp(prop) => prop.is_static && prop.value.is_some(),
+            ClassMember::StaticBlock(_) => true,
+            _ => false,
+        });
+        if !has_static_init {
+            return;
+        }
+
+        // The class is visible by name from its own static initializers
+        if let Some(class_name) = name {
+            self.instructions
+                .push(OpCode::Load("__wrapper__".to_string()));
+            self.instructions.push(OpCode::Let(class_name.to_string()));
+        }
+
+        let init_start = self.instructions.len() + 2;
+        self.instructions.push(OpCode::Push(JsValue::Function {
+            address: init_start,
+            env: None,
+            bound: None,
+        }));
+        let init_jump_idx = self.instructions.len();
+        self.instructions.push(OpCode::Jump(0));
+
+        let saved_in_function = self.in_function;
+        self.in_function = true;
+        self.push_binding_scope();
+        let saved_lexical_this = std::mem::replace(&mut self.lexical_this, false);
+        for member in &class.body {
+            match member {
+                ClassMember::ClassProp(prop) if prop.is_static => {
+                    let Some(value) = &prop.value else {
+                        continue;
+                    };
+                    let prop_name = match &prop.key {
+                        PropName::Ident(id) => id.sym.to_string(),
+                        PropName::Str(s) => s.value.to_string_lossy().into_owned(),
+                        PropName::Num(num) => num.value.to_string(),
+                        _ => continue,
+                    };
+                    self.gen_expr(value);
+                    // Stack: [value]
+                    self.instructions.push(OpCode::LoadThis);
+                    self.instructions.push(OpCode::Swap);
+                    // Stack: [this, value]
+                    self.instructions.push(OpCode::SetProp(prop_name));
+                }
+                ClassMember::StaticBlock(block) => {
+                    for stmt in &block.body.stmts {
+                        self.gen_stmt(stmt);
+                    }
+                }
+                _ => {}
+            }
+        }
+        self.instructions.push(OpCode::LoadThis);
+        self.instructions.push(OpCode::Return);
+        self.in_function = saved_in_function;
+        self.pop_binding_scope();
+        self.lexical_this = saved_lexical_this;
+
+        let after_init = self.instructions.len();
+        if let OpCode::Jump(ref mut addr) = self.instructions[init_jump_idx] {
+            *addr = after_init;
+        }
+        // Stack: [init]
+
+        self.instructions
+            .push(OpCode::Let("__static_init__".to_string()));
+        self.instructions
+            .push(OpCode::Load("__wrapper__".to_string()));
+        self.instructions
+            .push(OpCode::Load("__static_init__".to_string()));
+        self.instructions
+            .push(OpCode::SetProp("__static_init__".to_string()));
+
+        // wrapper.__static_init__(), then delete it again
+        self.instructions
+            .push(OpCode::Load("__wrapper__".to_string()));
+        self.instructions
+            .push(OpCode::CallMethod("__static_init__".to_string(), 0));
+        self.instructions.push(OpCode::Pop);
+        self.instructions
+            .push(OpCode::Load("__wrapper__".to_string()));
+        self.instructions
+            .push(OpCode::Delete("__static_init__".to_string()));
+        self.instructions.push(OpCode::Pop);
+    }
+}
+
+/// Opcode of a binary operator that evaluates both operands
+fn binary_opcode(op: BinaryOp) -> Option<OpCode> {
+    Some(match op {
+        BinaryOp::Add => OpCode::Add,
+        BinaryOp::Sub => OpCode::Sub,
+        BinaryOp::Mul => OpCode::Mul,
+        BinaryOp::Div => OpCode::Div,
+        BinaryOp::Mod => OpCode::Mod,
+        BinaryOp::EqEq => OpCode::EqEq,  // == (loose equality)
+        BinaryOp::EqEqEq => OpCode::Eq,  // === (strict equality)
+        BinaryOp::NotEq => OpCode::NeEq, // != (loose inequality)
+        BinaryOp::NotEqEq => OpCode::Ne, // !== (strict inequality)
+        BinaryOp::Lt => OpCode::Lt,
+        BinaryOp::LtEq => OpCode::LtEq,
+        BinaryOp::Gt => OpCode::Gt,
+        BinaryOp::GtEq => OpCode::GtEq,
+        BinaryOp::LogicalAnd => OpCode::And,
+        BinaryOp::LogicalOr => OpCode::Or,
+        BinaryOp::InstanceOf => OpCode::InstanceOf,
+        BinaryOp::In => OpCode::In,
+        // Bitwise operators
+        BinaryOp::BitAnd => OpCode::BitAnd,
+        BinaryOp::BitOr => OpCode::BitOr,
+        BinaryOp::BitXor => OpCode::Xor,
+        BinaryOp::LShift => OpCode::ShiftLeft,
+        BinaryOp::RShift => OpCode::ShiftRight,
+        BinaryOp::ZeroFillRShift => OpCode::ShiftRightUnsigned,
+        BinaryOp::Exp => OpCode::Pow,
+        _ => return None,
+    })
+}
+
+/// Name in `obj.name` or `obj["name"]`
+fn static_prop_name(prop: &MemberProp) -> Option<String> {
+    match prop {
+        MemberProp::Ident(id) => Some(id.sym.to_string()),
+        MemberProp::Computed(computed) => match computed.expr.as_ref() {
+            Expr::Lit(Lit::Str(s)) => Some(s.value.to_string_lossy().into_owned()),
+            _ => None,
+        },
+        MemberProp::PrivateName(_) => None,
+    }
+}
+
+/// ECMAScript ToInt32, for folding bitwise enum initializers
+fn to_int32(n: f64) -> i32 {
+    if !n.is_finite() {
+        return 0;
+    }
+    (n.trunc().rem_euclid(4294967296.0) as u32) as i32
+}
+
+/// Elements of a `return [a, b, ...]` array literal that can be returned
+/// without materializing the array (no holes, no spreads).
+fn multi_return_elems(expr: &Expr) -> Option<Vec<&Expr>> {
+    let expr = match expr {
+        Expr::Paren(paren) => paren.expr.as_ref(),
+        other => other,
+    };
+    let Expr::Array(arr_lit) = expr else {
+        return None;
+    };
+    if arr_lit.elems.len() < 2 {
+        return None;
+    }
+    arr_lit
+        .elems
+        .iter()
+        .map(|elem| match elem {
+            Some(ExprOrSpread { spread: None, expr }) => Some(expr.as_ref()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// `name` for a `Symbol.operator.name` expression
+fn operator_symbol(expr: &Expr) -> Option<&str> {
+    let Expr::Member(outer) = expr else {
+        return None;
+    };
+    let (Expr::Member(inner), MemberProp::Ident(name)) = (outer.obj.as_ref(), &outer.prop) else {
+        return None;
+    };
+    match (inner.obj.as_ref(), &inner.prop) {
+        (Expr::Ident(symbol), MemberProp::Ident(operator))
+            if &*symbol.sym == "Symbol" && &*operator.sym == "operator" =>
+        {
+            Some(&*name.sym)
+        }
+        _ => None,
+    }
+}
+
+/// Whether an arrow body refers to `this` or `new.target`, which arrows take
+/// from the enclosing function. Regular functions, methods and classes bind
+/// their own, so their bodies are not searched. Anything not matched here
+/// answers yes, which only costs a capture that goes unused.
+fn arrow_uses_this(body: &BlockStmtOrExpr) -> bool {
+    match body {
+        BlockStmtOrExpr::Expr(e) => expr_uses_this(e),
+        BlockStmtOrExpr::BlockStmt(block) => block.stmts.iter().any(stmt_uses_this),
+    }
+}
+
+fn stmt_uses_this(stmt: &Stmt) -> bool {
+    let opt = |e: &Option<Box<Expr>>| e.as_deref().is_some_and(expr_uses_this);
+    match stmt {
+        Stmt::Expr(expr_stmt) => expr_uses_this(&expr_stmt.expr),
+        Stmt::Return(ret) => opt(&ret.arg),
+        Stmt::Throw(throw) => expr_uses_this(&throw.arg),
+        Stmt::Block(block) => block.stmts.iter().any(stmt_uses_this),
+        Stmt::Decl(Decl::Var(var_decl)) => var_decl.decls.iter().any(|d| opt(&d.init)),
+        Stmt::Decl(_) => false,
+        Stmt::If(if_stmt) => {
+            expr_uses_this(&if_stmt.test)
+                || stmt_uses_this(&if_stmt.cons)
+                || if_stmt.alt.as_deref().is_some_and(stmt_uses_this)
+        }
+        Stmt::While(while_stmt) => {
+            expr_uses_this(&while_stmt.test) || stmt_uses_this(&while_stmt.body)
+        }
+        Stmt::DoWhile(do_while) => expr_uses_this(&do_while.test) || stmt_uses_this(&do_while.body),
+        Stmt::For(for_stmt) => {
+            let init = match &for_stmt.init {
+                Some(VarDeclOrExpr::VarDecl(var_decl)) => {
+                    var_decl.decls.iter().any(|d| opt(&d.init))
+                }
+                Some(VarDeclOrExpr::Expr(e)) => expr_uses_this(e),
+                None => false,
+            };
+            init || opt(&for_stmt.test) || opt(&for_stmt.update) || stmt_uses_this(&for_stmt.body)
+        }
+        Stmt::ForOf(for_of) => expr_uses_this(&for_of.right) || stmt_uses_this(&for_of.body),
+        Stmt::ForIn(for_in) => expr_uses_this(&for_in.right) || stmt_uses_this(&for_in.body),
+        Stmt::Try(try_stmt) => {
+            try_stmt.block.stmts.iter().any(stmt_uses_this)
+                || try_stmt
+                    .handler
+                    .as_ref()
+                    .is_some_and(|h| h.body.stmts.iter().any(stmt_uses_this))
+                || try_stmt
+                    .finalizer
+                    .as_ref()
+                    .is_some_and(|f| f.stmts.iter().any(stmt_uses_this))
+        }
+        Stmt::Switch(switch) => {
+            expr_uses_this(&switch.discriminant)
+                || switch
+                    .cases
+                    .iter()
+                    .any(|case| opt(&case.test) || case.cons.iter().any(stmt_uses_this))
+        }
+        Stmt::Labeled(labeled) => stmt_uses_this(&labeled.body),
+        Stmt::Empty(_) | Stmt::Break(_) | Stmt::Continue(_) | Stmt::Debugger(_) => false,
+        _ => true,
+    }
+}
+
+fn expr_uses_this(expr: &Expr) -> bool {
+    let member = |m: &MemberExpr| {
+        expr_uses_this(&m.obj)
+            || matches!(&m.prop, MemberProp::Computed(c) if expr_uses_this(&c.expr))
+    };
+    match expr {
+        Expr::This(_) => true,
+        Expr::MetaProp(meta_prop) => meta_prop.kind == MetaPropKind::NewTarget,
+        Expr::Ident(_) | Expr::Lit(_) | Expr::PrivateName(_) => false,
+        Expr::Fn(_) | Expr::Class(_) => false,
+        Expr::Arrow(arrow) => arrow_uses_this(&arrow.body),
+        Expr::Paren(paren) => expr_uses_this(&paren.expr),
+        Expr::Bin(bin) => expr_uses_this(&bin.left) || expr_uses_this(&bin.right),
+        Expr::Unary(unary) => expr_uses_this(&unary.arg),
+        Expr::Update(update) => expr_uses_this(&update.arg),
+        Expr::Await(await_expr) => expr_uses_this(&await_expr.arg),
+        Expr::Yield(yield_expr) => yield_expr.arg.as_deref().is_some_and(expr_uses_this),
+        Expr::Cond(cond) => {
+            expr_uses_this(&cond.test) || expr_uses_this(&cond.cons) || expr_uses_this(&cond.alt)
+        }
+        Expr::Seq(seq) => seq.exprs.iter().any(|e| expr_uses_this(e)),
+        Expr::Member(m) => member(m),
+        Expr::Assign(assign) => {
+            expr_uses_this(&assign.right)
+                || match &assign.left {
+                    AssignTarget::Simple(SimpleAssignTarget::Member(m)) => member(m),
+                    AssignTarget::Simple(SimpleAssignTarget::Ident(_)) => false,
+                    _ => true,
+                }
+        }
+        Expr::Call(call) => {
+            let callee = match &call.callee {
+                Callee::Expr(callee) => expr_uses_this(callee),
+                _ => true,
+            };
+            callee || call.args.iter().any(|arg| expr_uses_this(&arg.expr))
+        }
+        Expr::New(new_expr) => {
+            expr_uses_this(&new_expr.callee)
+                || new_expr
+                    .args
+                    .iter()
+                    .flatten()
+                    .any(|arg| expr_uses_this(&arg.expr))
+        }
+        Expr::Array(arr) => arr.elems.iter().flatten().any(|e| expr_uses_this(&e.expr)),
+        Expr::Object(obj) => obj.props.iter().any(|prop| match prop {
+            PropOrSpread::Spread(spread) => expr_uses_this(&spread.expr),
+            PropOrSpread::Prop(p) => match p.as_ref() {
+                Prop::KeyValue(kv) => {
+                    expr_uses_this(&kv.value)
+                        || matches!(&kv.key, PropName::Computed(c) if expr_uses_this(&c.expr))
+                }
+                // Methods and accessors bind their own `this`
+                _ => false,
+            },
+        }),
+        Expr::Tpl(tpl) => tpl.exprs.iter().any(|e| expr_uses_this(e)),
+        Expr::TaggedTpl(tagged) => {
+            expr_uses_this(&tagged.tag) || tagged.tpl.exprs.iter().any(|e| expr_uses_this(e))
+        }
+        _ => true,
+    }
 }
@@ -0,0 +1,209 @@
+//! Reusing the code of unchanged top-level items
+//!
+//! `Codegen::generate_item` compiles one item of a module and keeps it as a
+//! `CompiledItem`. That is the item's instructions with addresses relative
+//! to its first one, plus the state it left for the items after it:
+//! top-level bindings, function arities, const enums, injectables and
+//! warnings. `reuse_item` appends the item again without compiling it.
+//!
+//! The code is what `generate` would emit as long as the item's text and the
+//! state it reads are unchanged. That state is keyed by the names the item
+//! mentions, so a caller tracking which items declare those names knows
+//! when the item needs compiling again (see `lsp::incremental`). Hidden
+//! locals named after an instruction index (`__switch_12__`) move with the
+//! addresses. Statement starts are not recorded for reused items.
+
+use super::inject::Injectable;
+use super::{Arity, Codegen};
+use crate::vm::opcodes::OpCode;
+use crate::vm::value::JsValue;
+use std::collections::HashMap;
+use swc_ecma_ast::ModuleItem;
+
+/// Hidden locals named after the instruction that creates them
+const INDEXED_LOCALS: [&str; 3] = ["__switch_", "__ref_", "__key_"];
+
+/// A top-level item's code and the state compiling it left behind
+#[derive(Clone)]
+pub struct CompiledItem {
+    /// Instructions, with addresses relative to the first one
+    code: Vec<OpCode>,
+    /// Names bound in the top-level scope
+    bindings: Vec<String>,
+    /// Names closures created later can capture
+    outer_vars: Vec<String>,
+    /// Locals added to the top-level block scope
+    locals: Vec<String>,
+    /// Changed entries of each map; `None` removes the name
+    arities: Vec<(String, Option<Arity>)>,
+    const_enums: Vec<(String, Option<HashMap<String, JsValue>>)>,
+    injectables: Vec<(String, Option<Injectable>)>,
+    warnings: Vec<String>,
+    errors: Vec<String>,
+}
+
+impl CompiledItem {
+    pub fn code(&self) -> &[OpCode] {
+        &self.code
+    }
+
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    pub fn errors(&self) -> &[String] {
+        &self.errors
+    }
+
+    /// Every name whose state the item changed. An item after it that
+    /// mentions one of them may compile differently when this one changes.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.bindings
+            .iter()
+            .chain(&self.outer_vars)
+            .chain(&self.locals)
+            .chain(self.arities.iter().map(|(name, _)| name))
+            .chain(self.const_enums.iter().map(|(name, _)| name))
+            .chain(self.injectables.iter().map(|(name, _)| name))
+            .map(String::as_str)
+    }
+}
+
+impl Codegen {
+    /// Compile one top-level item of a module, as `generate` would at this
+    /// point, and keep what is needed to reuse it
+    pub fn generate_item(&mut self, item: &ModuleItem) -> CompiledItem {
+        let start = self.instructions.len();
+        let bindings = self.binding_scopes[0].clone();
+        let outer_vars = self.outer_scope_vars.clone();
+        let locals = self.scope_stack[0].len();
+        let arities = self.function_arities.clone();
+        let const_enums = self.const_enums.clone();
+        let injectables = self.injectables.clone();
+        let (warnings, errors) = (self.warnings.len(), self.errors.len());
+
+        self.gen_module_item(item);
+
+        let relative = |name: &String| relocate_name(name, start, 0);
+        CompiledItem {
+            code: self.instructions[start..]
+                .iter()
+                .map(|op| relocate(op, start, 0))
+                .collect(),
+            bindings: self.binding_scopes[0]
+                .difference(&bindings)
+                .map(relative)
+                .collect(),
+            outer_vars: self
+                .outer_scope_vars
+                .difference(&outer_vars)
+                .map(relative)
+                .collect(),
+            locals: self.scope_stack[0][locals..].iter().map(relative).collect(),
+            arities: changes(&arities, &self.function_arities),
+            const_enums: changes(&const_enums, &self.const_enums),
+            injectables: changes(&injectables, &self.injectables),
+            warnings: self.warnings[warnings..].to_vec(),
+            errors: self.errors[errors..].to_vec(),
+        }
+    }
+
+    /// Append an item compiled earlier, leaving the same state as compiling
+    /// it here would
+    pub fn reuse_item(&mut self, item: &CompiledItem) {
+        let start = self.instructions.len();
+        let absolute = |name: &String| relocate_name(name, 0, start);
+        self.instructions
+            .extend(item.code.iter().map(|op| relocate(op, 0, start)));
+        self.binding_scopes[0].extend(item.bindings.iter().map(absolute));
+        self.outer_scope_vars
+            .extend(item.outer_vars.iter().map(absolute));
+        self.scope_stack[0].extend(item.locals.iter().map(absolute));
+        apply(&mut self.function_arities, &item.arities);
+        apply(&mut self.const_enums, &item.const_enums);
+        apply(&mut self.injectables, &item.injectables);
+        self.warnings.extend_from_slice(&item.warnings);
+        self.errors.extend_from_slice(&item.errors);
+    }
+}
+
+/// `op` moved from a chunk starting at `from` to one starting at `to`
+fn relocate(op: &OpCode, from: usize, to: usize) -> OpCode {
+    let shift = |addr: usize| (addr + to).saturating_sub(from);
+    match op {
+        OpCode::Jump(addr) => OpCode::Jump(shift(*addr)),
+        OpCode::JumpIfFalse(addr) => OpCode::JumpIfFalse(shift(*addr)),
+        OpCode::MakeClosure(addr) => OpCode::MakeClosure(shift(*addr)),
+        OpCode::Push(JsValue::Function {
+            address,
+            env,
+            bound,
+        }) => OpCode::Push(JsValue::Function {
+            address: shift(*address),
+            env: *env,
+            bound: *bound,
+        }),
+        // 0 means there is no handler
+        OpCode::SetupTry {
+            catch_addr,
+            finally_addr,
+        } => OpCode::SetupTry {
+            catch_addr: if *catch_addr != 0 {
+                shift(*catch_addr)
+            } else {
+                0
+            },
+            finally_addr: if *finally_addr != 0 {
+                shift(*finally_addr)
+            } else {
+                0
+            },
+        },
+        OpCode::Let(name) => OpCode::Let(relocate_name(name, from, to)),
+        OpCode::Store(name) => OpCode::Store(relocate_name(name, from, to)),
+        OpCode::Load(name) => OpCode::Load(relocate_name(name, from, to)),
+        OpCode::Drop(name) => OpCode::Drop(relocate_name(name, from, to)),
+        other => other.clone(),
+    }
+}
+
+/// `name`, renamed if it is a hidden local numbered by instruction index
+fn relocate_name(name: &str, from: usize, to: usize) -> String {
+    for prefix in INDEXED_LOCALS {
+        let index = name
+            .strip_prefix(prefix)
+            .and_then(|rest| rest.strip_suffix("__"))
+            .and_then(|index| index.parse::<usize>().ok());
+        if let Some(index) = index {
+            return format!("{}{}__", prefix, (index + to).saturating_sub(from));
+        }
+    }
+    name.to_string()
+}
+
+/// Entries of `after` that differ from `before`, and `None` for each name
+/// `after` no longer has
+fn changes<V: Clone + PartialEq>(
+    before: &HashMap<String, V>,
+    after: &HashMap<String, V>,
+) -> Vec<(String, Option<V>)> {
+    let removed = before
+        .keys()
+        .filter(|name| !after.contains_key(*name))
+        .map(|name| (name.clone(), None));
+    after
+        .iter()
+        .filter(|(name, value)| before.get(*name) != Some(*value))
+        .map(|(name, value)| (name.clone(), Some(value.clone())))
+        .chain(removed)
+        .collect()
+}
+
+fn apply<V: Clone>(map: &mut HashMap<String, V>, changes: &[(String, Option<V>)]) {
+    for (name, value) in changes {
+        match value {
+            Some(value) => map.insert(name.clone(), value.clone()),
+            None => map.remove(name),
+        };
+    }
+}
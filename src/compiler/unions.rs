@@ -0,0 +1,446 @@
+//! Tagged unions: `type Shape = Circle(radius: number) | Rect(number, number)`
+//!
+//! A declaration whose alternatives are written as calls is not a TypeScript
+//! type, so it is taken out of the source before parsing and replaced with
+//! `type Shape = any;`, keeping every line where it was. Each variant becomes
+//! a constructor: `Circle(2)` builds `{ tag: "Circle", radius: 2 }`, and a
+//! variant without a payload is a single shared object. Payload fields take
+//! their label when there is one, `value` when the variant has a single
+//! unlabeled payload, and `_0`, `_1`, ... otherwise.
+//!
+//! A `switch` over `x.tag` whose cases name variants of a union must cover
+//! all of them or have a `default`.
+
+use super::{Codegen, static_prop_name};
+use crate::vm::opcodes::OpCode;
+use crate::vm::value::JsValue;
+use swc_ecma_ast::{Expr, ExprOrSpread, Lit, SwitchStmt};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Union {
+    pub name: String,
+    pub variants: Vec<Variant>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Variant {
+    pub name: String,
+    /// Payload field names, in constructor argument order
+    pub fields: Vec<String>,
+}
+
+/// Replace the tagged union declarations in `source`, returning the source
+/// to parse and the unions it declared
+pub fn desugar(source: &str) -> (String, Vec<Union>) {
+    let mut output = String::with_capacity(source.len());
+    let mut unions = Vec::new();
+    let mut copied = 0;
+    let mut line_start = 0;
+    while line_start < source.len() {
+        if line_start >= copied
+            && let Some((head_end, end, union)) = parse_declaration(source, line_start)
+        {
+            output.push_str(&source[copied..head_end]);
+            output.push_str(" any;");
+            // Keep the lines of what was removed, so spans still point at
+            // the right line
+            output.extend(source[head_end..end].matches('\n').map(|_| '\n'));
+            copied = end;
+            unions.push(union);
+        }
+        line_start = match source[line_start..].find('\n') {
+            Some(i) => line_start + i + 1,
+            None => source.len(),
+        };
+    }
+    output.push_str(&source[copied..]);
+    (output, unions)
+}
+
+/// A union declaration starting on the line at `start`: the end of its
+/// `type Name<...> =` head, the end of the declaration and the union
+fn parse_declaration(source: &str, start: usize) -> Option<(usize, usize, Union)> {
+    let mut cursor = Cursor { source, pos: start };
+    cursor.skip_spaces();
+    if cursor.eat_word("export") {
+        cursor.skip_spaces();
+    }
+    if !cursor.eat_word("type") {
+        return None;
+    }
+    cursor.skip_spaces();
+    let name = cursor.ident()?;
+    cursor.skip_spaces();
+    if cursor.peek() == Some('<') {
+        cursor.balanced('<', '>')?;
+        cursor.skip_spaces();
+    }
+    if cursor.peek() != Some('=') {
+        return None;
+    }
+    cursor.pos += 1;
+    let head_end = cursor.pos;
+
+    let mut variants = Vec::new();
+    let mut has_payload = false;
+    loop {
+        cursor.skip_whitespace();
+        if cursor.peek() == Some('|') {
+            cursor.pos += 1;
+            cursor.skip_whitespace();
+        }
+        let variant = cursor.ident()?;
+        // `typeof(x)` and friends are type operators, not variants
+        if matches!(
+            variant.as_str(),
+            "typeof" | "keyof" | "readonly" | "infer" | "unique"
+        ) {
+            return None;
+        }
+        cursor.skip_spaces();
+        let fields = if cursor.peek() == Some('(') {
+            has_payload = true;
+            let args = cursor.balanced('(', ')')?;
+            field_names(args)
+        } else {
+            Vec::new()
+        };
+        variants.push(Variant {
+            name: variant,
+            fields,
+        });
+
+        cursor.skip_spaces();
+        match cursor.peek() {
+            Some('|') => continue,
+            Some(';') => {
+                cursor.pos += 1;
+                break;
+            }
+            None => break,
+            Some('\n' | '\r') => {
+                // The declaration goes on if the next line continues it
+                let line_end = cursor.pos;
+                cursor.skip_whitespace();
+                if cursor.peek() != Some('|') {
+                    cursor.pos = line_end;
+                    break;
+                }
+            }
+            Some(_) => return None,
+        }
+    }
+    // `type A = B | C` without payloads is an ordinary type alias
+    if !has_payload {
+        return None;
+    }
+    Some((head_end, cursor.pos, Union { name, variants }))
+}
+
+/// Field names for the payload `args` of a variant, e.g. `x: number, string`
+fn field_names(args: &str) -> Vec<String> {
+    let params = split_top_level(args);
+    let count = params.len();
+    params
+        .iter()
+        .enumerate()
+        .map(|(i, param)| {
+            let label = param
+                .split_once(':')
+                .map(|(label, _)| label.trim().trim_end_matches('?'))
+                .filter(|label| is_ident(label));
+            match label {
+                Some(label) => label.to_string(),
+                None if count == 1 => "value".to_string(),
+                None => format!("_{}", i),
+            }
+        })
+        .collect()
+}
+
+/// Split on commas outside of brackets
+fn split_top_level(args: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0i32;
+    let mut part_start = 0;
+    let mut prev = ' ';
+    for (i, c) in args.char_indices() {
+        match c {
+            '(' | '[' | '{' | '<' => depth += 1,
+            // `=>` is not a closing bracket
+            '>' if prev == '=' => {}
+            ')' | ']' | '}' | '>' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(args[part_start..i].trim());
+                part_start = i + 1;
+            }
+            _ => {}
+        }
+        prev = c;
+    }
+    let last = args[part_start..].trim();
+    if !last.is_empty() {
+        parts.push(last);
+    }
+    parts
+}
+
+fn is_ident(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_alphabetic() || c == '_' || c == '$')
+        && chars.all(|c| c.is_alphanumeric() || c == '_' || c == '$')
+}
+
+struct Cursor<'a> {
+    source: &'a str,
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn peek(&self) -> Option<char> {
+        self.source[self.pos..].chars().next()
+    }
+
+    /// Spaces and tabs, staying on the line
+    fn skip_spaces(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t')) {
+            self.pos += 1;
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += self.peek().map_or(1, char::len_utf8);
+        }
+    }
+
+    fn eat_word(&mut self, word: &str) -> bool {
+        let Some(after) = self.source[self.pos..].strip_prefix(word) else {
+            return false;
+        };
+        if after
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_alphanumeric() || c == '_' || c == '$')
+        {
+            return false;
+        }
+        self.pos += word.len();
+        true
+    }
+
+    fn ident(&mut self) -> Option<String> {
+        let rest = &self.source[self.pos..];
+        let len = rest
+            .char_indices()
+            .find(|&(_, c)| !(c.is_alphanumeric() || c == '_' || c == '$'))
+            .map_or(rest.len(), |(i, _)| i);
+        let ident = &rest[..len];
+        if !is_ident(ident) {
+            return None;
+        }
+        self.pos += len;
+        Some(ident.to_string())
+    }
+
+    /// Skip from `open` to its matching `close`, returning what is between
+    fn balanced(&mut self, open: char, close: char) -> Option<&'a str> {
+        let start = self.pos + open.len_utf8();
+        let mut depth = 0;
+        let mut prev = ' ';
+        for (i, c) in self.source[self.pos..].char_indices() {
+            if c == open {
+                depth += 1;
+            } else if c == close && !(close == '>' && prev == '=') {
+                depth -= 1;
+                if depth == 0 {
+                    let end = self.pos + i;
+                    self.pos = end + close.len_utf8();
+                    return Some(&self.source[start..end]);
+                }
+            } else if c == ';' {
+                return None;
+            }
+            prev = c;
+        }
+        None
+    }
+}
+
+impl Codegen {
+    /// Define every variant at the start of the program: a constructor
+    /// function for variants with a payload, the shared object otherwise
+    pub(super) fn gen_union_constructors(&mut self) {
+        let variants: Vec<Variant> = self
+            .unions
+            .iter()
+            .flat_map(|union| union.variants.iter().cloned())
+            .collect();
+        for variant in variants {
+            if variant.fields.is_empty() {
+                self.gen_tag(&variant.name);
+                self.instructions.push(OpCode::Let(variant.name));
+                continue;
+            }
+            let start = self.instructions.len() + 2; // Push + Jump
+            self.instructions.push(OpCode::Push(JsValue::Function {
+                address: start,
+                env: None,
+                bound: None,
+            }));
+            let jump_idx = self.instructions.len();
+            self.instructions.push(OpCode::Jump(0));
+            for (index, field) in variant.fields.iter().enumerate() {
+                self.instructions.push(OpCode::LoadArg(index));
+                self.instructions.push(OpCode::Let(field.clone()));
+            }
+            self.gen_tag(&variant.name);
+            for field in &variant.fields {
+                self.instructions.push(OpCode::Dup);
+                self.instructions.push(OpCode::Load(field.clone()));
+                self.instructions.push(OpCode::SetProp(field.clone()));
+            }
+            self.instructions.push(OpCode::Return);
+            let after = self.instructions.len();
+            if let OpCode::Jump(ref mut addr) = self.instructions[jump_idx] {
+                *addr = after;
+            }
+            self.instructions.push(OpCode::Let(variant.name));
+        }
+    }
+
+    /// Stack: [] -> [{ tag }]
+    fn gen_tag(&mut self, name: &str) {
+        self.instructions.push(OpCode::NewObject);
+        self.instructions.push(OpCode::Dup);
+        self.instructions
+            .push(OpCode::Push(JsValue::String(name.to_string())));
+        self.instructions.push(OpCode::SetProp("tag".to_string()));
+    }
+
+    /// Build `name(args)` in place if `name` is a variant with a payload.
+    /// Returns false to compile it as an ordinary call.
+    pub(super) fn gen_variant(&mut self, name: &str, args: &[ExprOrSpread]) -> bool {
+        if self.outer_scope_vars.contains(name) {
+            return false;
+        }
+        let Some(variant) = self
+            .unions
+            .iter()
+            .flat_map(|union| &union.variants)
+            .find(|variant| variant.name == name && !variant.fields.is_empty())
+            .cloned()
+        else {
+            return false;
+        };
+        if args.iter().any(|arg| arg.spread.is_some()) {
+            return false;
+        }
+        if args.len() != variant.fields.len() {
+            self.errors.push(format!(
+                "{} takes {} argument(s), got {}",
+                name,
+                variant.fields.len(),
+                args.len()
+            ));
+        }
+        self.gen_tag(name);
+        for (i, field) in variant.fields.iter().enumerate() {
+            self.instructions.push(OpCode::Dup);
+            match args.get(i) {
+                Some(arg) => self.gen_expr(&arg.expr),
+                None => self.instructions.push(OpCode::Push(JsValue::Undefined)),
+            }
+            self.instructions.push(OpCode::SetProp(field.clone()));
+        }
+        true
+    }
+
+    /// A `switch (x.tag)` whose cases are all variants of one union must
+    /// name every variant, unless it has a `default`
+    pub(super) fn check_union_switch(&mut self, switch: &SwitchStmt) {
+        let discriminant = match switch.discriminant.as_ref() {
+            Expr::Paren(paren) => paren.expr.as_ref(),
+            other => other,
+        };
+        let Expr::Member(member) = discriminant else {
+            return;
+        };
+        if static_prop_name(&member.prop).as_deref() != Some("tag")
+            || switch.cases.iter().any(|case| case.test.is_none())
+        {
+            return;
+        }
+        let mut tags = Vec::new();
+        for case in &switch.cases {
+            match case.test.as_deref() {
+                Some(Expr::Lit(Lit::Str(s))) => tags.push(s.value.to_string_lossy().into_owned()),
+                _ => return,
+            }
+        }
+        let Some(union) = self.unions.iter().find(|union| {
+            tags.iter()
+                .all(|tag| union.variants.iter().any(|v| v.name == *tag))
+        }) else {
+            return;
+        };
+        let missing: Vec<&str> = union
+            .variants
+            .iter()
+            .filter(|v| !tags.contains(&v.name))
+            .map(|v| v.name.as_str())
+            .collect();
+        if !missing.is_empty() {
+            self.errors.push(format!(
+                "switch over {} is not exhaustive: missing {} (add the cases or a default)",
+                union.name,
+                missing.join(", ")
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_desugar_keeps_lines_and_collects_variants() {
+        let source = "let a = 1;\n\
+                      export type Shape<T> =\n  | Circle(radius: number)\n  | Rect(number, number)\n  | Tagged(T)\n  | Empty;\n\
+                      type Alias = A | B;\n\
+                      let b = Circle(1);\n";
+        let (output, unions) = desugar(source);
+        assert_eq!(
+            output,
+            "let a = 1;\nexport type Shape<T> = any;\n\n\n\n\ntype Alias = A | B;\nlet b = Circle(1);\n"
+        );
+        assert_eq!(unions.len(), 1);
+        assert_eq!(unions[0].name, "Shape");
+        let fields: Vec<(&str, Vec<&str>)> = unions[0]
+            .variants
+            .iter()
+            .map(|v| {
+                (
+                    v.name.as_str(),
+                    v.fields.iter().map(String::as_str).collect(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            fields,
+            vec![
+                ("Circle", vec!["radius"]),
+                ("Rect", vec!["_0", "_1"]),
+                ("Tagged", vec!["value"]),
+                ("Empty", vec![]),
+            ]
+        );
+
+        let (output, unions) = desugar("type R<T, E> = Ok(T) | Err(E); run();");
+        assert_eq!(output, "type R<T, E> = any; run();");
+        assert_eq!(unions[0].variants.len(), 2);
+    }
+}
@@ -0,0 +1,447 @@
+//! Toolchain diagnostics for `oitec doctor`
+//!
+//! Native builds lean on tools outside this binary: LLVM 18 (its libraries to
+//! build oitec from source, its command-line tools for LTO) and a C compiler
+//! driver to link. Each check reports what it found; anything missing comes
+//! with an install command for the host's package manager. The last check
+//! builds and runs a one-line program, which catches the problems the
+//! individual checks can't see.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::backend::llvm::linker;
+
+/// The LLVM major version oitec is built against (`llvm-sys` 180)
+pub const LLVM_MAJOR: u32 = 18;
+
+/// Tools `--release` and `--dist` run for link-time optimization
+const LTO_TOOLS: [&str; 4] = ["llvm-link", "llc", "opt", "llvm-lto"];
+
+/// Outcome of one check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Ok,
+    /// Works, but some builds will fail
+    Warning,
+    /// Native builds won't work until this is fixed
+    Missing,
+    /// Not run because an earlier check failed
+    Skipped,
+}
+
+impl Status {
+    fn label(self) -> &'static str {
+        match self {
+            Status::Ok => "ok",
+            Status::Warning => "warn",
+            Status::Missing => "missing",
+            Status::Skipped => "skipped",
+        }
+    }
+}
+
+/// What one check found
+#[derive(Debug, Clone)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: Status,
+    pub detail: String,
+    /// How to fix it, when it isn't ok
+    pub hint: Option<String>,
+}
+
+impl Check {
+    fn new(name: &'static str, status: Status, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            status,
+            detail: detail.into(),
+            hint: None,
+        }
+    }
+
+    fn hint(mut self, hint: Option<String>) -> Self {
+        self.hint = hint;
+        self
+    }
+}
+
+/// Something doctor can tell the user to install
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Package {
+    Llvm,
+    Polly,
+    CCompiler,
+}
+
+/// Host package manager family, for install hints
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Distro {
+    Debian,
+    Fedora,
+    Arch,
+    Alpine,
+    Suse,
+    MacOs,
+    Windows,
+    Unknown,
+}
+
+impl Distro {
+    pub fn detect() -> Self {
+        if cfg!(target_os = "macos") {
+            return Distro::MacOs;
+        }
+        if cfg!(windows) {
+            return Distro::Windows;
+        }
+        std::fs::read_to_string("/etc/os-release")
+            .map(|text| Distro::from_os_release(&text))
+            .unwrap_or(Distro::Unknown)
+    }
+
+    /// Classify by the `ID` and `ID_LIKE` fields of `/etc/os-release`
+    pub fn from_os_release(text: &str) -> Self {
+        let ids: Vec<String> = text
+            .lines()
+            .filter_map(|line| {
+                line.strip_prefix("ID=")
+                    .or_else(|| line.strip_prefix("ID_LIKE="))
+            })
+            .flat_map(|value| {
+                value
+                    .trim_matches(|c| c == '"' || c == '\'')
+                    .split_whitespace()
+                    .map(str::to_lowercase)
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        for id in &ids {
+            let distro = match id.as_str() {
+                "debian" | "ubuntu" => Distro::Debian,
+                "fedora" | "rhel" | "centos" => Distro::Fedora,
+                "arch" => Distro::Arch,
+                "alpine" => Distro::Alpine,
+                "suse" | "opensuse" => Distro::Suse,
+                _ => continue,
+            };
+            return distro;
+        }
+        Distro::Unknown
+    }
+
+    /// The command that installs `package`, if doctor knows it here
+    pub fn install(self, package: Package) -> Option<&'static str> {
+        use Package::*;
+        let command = match (self, package) {
+            (Distro::Debian, Llvm) => "sudo apt install llvm-18 llvm-18-dev",
+            (Distro::Debian, Polly) => "sudo apt install libpolly-18-dev",
+            (Distro::Debian, CCompiler) => "sudo apt install build-essential",
+            (Distro::Fedora, Llvm) => "sudo dnf install llvm18 llvm18-devel",
+            (Distro::Fedora, Polly) => "sudo dnf install polly-devel",
+            (Distro::Fedora, CCompiler) => "sudo dnf install gcc",
+            (Distro::Arch, Llvm | Polly) => "sudo pacman -S llvm18",
+            (Distro::Arch, CCompiler) => "sudo pacman -S base-devel",
+            (Distro::Alpine, Llvm | Polly) => "apk add llvm18 llvm18-dev",
+            (Distro::Alpine, CCompiler) => "apk add build-base",
+            (Distro::Suse, Llvm | Polly) => "sudo zypper install llvm18 llvm18-devel",
+            (Distro::Suse, CCompiler) => "sudo zypper install gcc",
+            (Distro::MacOs, Llvm | Polly) => "brew install llvm@18",
+            (Distro::MacOs, CCompiler) => "xcode-select --install",
+            (Distro::Windows, Llvm | Polly | CCompiler) => "winget install LLVM.LLVM",
+            (Distro::Unknown, _) => return None,
+        };
+        Some(command)
+    }
+
+    /// Where this package manager puts LLVM 18, for `LLVM_SYS_180_PREFIX`
+    fn llvm_prefix(self) -> Option<&'static str> {
+        match self {
+            Distro::Debian => Some("/usr/lib/llvm-18"),
+            Distro::Arch => Some("/usr/lib/llvm18"),
+            Distro::Alpine => Some("/usr/lib/llvm18"),
+            Distro::MacOs => Some("$(brew --prefix llvm@18)"),
+            _ => None,
+        }
+    }
+}
+
+/// Major version of an `llvm-config --version` string like `18.1.8`
+pub fn llvm_major(version: &str) -> Option<u32> {
+    version.trim().split('.').next()?.parse().ok()
+}
+
+/// Run every check, building with the compiler at `oitec`
+pub fn run_checks(oitec: &Path) -> Vec<Check> {
+    let distro = Distro::detect();
+    let llvm_config = find_llvm_config();
+    let mut checks = vec![
+        check_llvm(distro, llvm_config.as_deref()),
+        check_lto_tools(llvm_config.as_deref()),
+        check_polly(distro, llvm_config.as_deref()),
+        check_c_compiler(distro),
+    ];
+    let build = if checks.last().is_some_and(|c| c.status == Status::Missing) {
+        Check::new("build", Status::Skipped, "needs a C compiler to link")
+    } else {
+        check_build(oitec)
+    };
+    checks.push(build);
+    checks
+}
+
+/// Print `checks`; false if any is missing
+pub fn print_report(checks: &[Check]) -> bool {
+    for check in checks {
+        println!(
+            "[{:<7}] {:<10} {}",
+            check.status.label(),
+            check.name,
+            check.detail
+        );
+        if let Some(hint) = &check.hint {
+            for line in hint.lines() {
+                println!("{:21}{}", "", line);
+            }
+        }
+    }
+    let missing = checks
+        .iter()
+        .filter(|c| c.status == Status::Missing)
+        .count();
+    if missing == 0 {
+        println!("\nNative builds are ready.");
+    } else {
+        println!(
+            "\n{} problem{} found; `oitec build` needs them fixed.",
+            missing,
+            if missing == 1 { "" } else { "s" }
+        );
+    }
+    missing == 0
+}
+
+/// `llvm-config` from `LLVM_SYS_180_PREFIX`, else the versioned or plain one
+/// in PATH
+fn find_llvm_config() -> Option<PathBuf> {
+    let mut candidates = Vec::new();
+    if let Ok(prefix) = std::env::var("LLVM_SYS_180_PREFIX") {
+        candidates.push(Path::new(&prefix).join("bin").join("llvm-config"));
+    }
+    candidates.push(PathBuf::from(format!("llvm-config-{}", LLVM_MAJOR)));
+    candidates.push(PathBuf::from("llvm-config"));
+    candidates
+        .into_iter()
+        .find(|path| tool_output(path, &["--version"]).is_some())
+}
+
+fn check_llvm(distro: Distro, llvm_config: Option<&Path>) -> Check {
+    let mut hint = distro.install(Package::Llvm).map(String::from);
+    if let Some(prefix) = distro.llvm_prefix() {
+        let export = format!("export LLVM_SYS_180_PREFIX={}", prefix);
+        hint = Some(match hint {
+            Some(install) => format!("{}\n{}", install, export),
+            None => export,
+        });
+    }
+    let Some(path) = llvm_config else {
+        return Check::new("LLVM", Status::Missing, "llvm-config not found").hint(hint);
+    };
+    let version = tool_output(path, &["--version"]).unwrap_or_default();
+    match llvm_major(&version) {
+        Some(LLVM_MAJOR) => Check::new(
+            "LLVM",
+            Status::Ok,
+            format!("{} ({})", version, path.display()),
+        ),
+        _ => Check::new(
+            "LLVM",
+            Status::Missing,
+            format!(
+                "found {} at {}, need LLVM {}",
+                version,
+                path.display(),
+                LLVM_MAJOR
+            ),
+        )
+        .hint(hint),
+    }
+}
+
+/// The LTO pipeline finds its tools the same way `backend::llvm::lto` does
+fn check_lto_tools(llvm_config: Option<&Path>) -> Check {
+    let prefix_bin = std::env::var("LLVM_SYS_180_PREFIX")
+        .ok()
+        .map(|prefix| Path::new(&prefix).join("bin"));
+    let missing: Vec<&str> = LTO_TOOLS
+        .into_iter()
+        .filter(|tool| {
+            let in_prefix = prefix_bin.as_ref().is_some_and(|bin| {
+                bin.join(tool)
+                    .with_extension(std::env::consts::EXE_EXTENSION)
+                    .exists()
+            });
+            !in_prefix && tool_output(Path::new(tool), &["--version"]).is_none()
+        })
+        .collect();
+    if missing.is_empty() {
+        return Check::new("LTO tools", Status::Ok, LTO_TOOLS.join(", "));
+    }
+    let hint = llvm_config
+        .and_then(|path| tool_output(path, &["--prefix"]))
+        .map(|prefix| format!("export LLVM_SYS_180_PREFIX={}", prefix));
+    Check::new(
+        "LTO tools",
+        Status::Warning,
+        format!(
+            "{} not found; --release and --dist need them",
+            missing.join(", ")
+        ),
+    )
+    .hint(hint)
+}
+
+/// Some LLVM builds list Polly among their libraries, and linking oitec
+/// fails when its archive isn't installed alongside them
+fn check_polly(distro: Distro, llvm_config: Option<&Path>) -> Check {
+    let Some(path) = llvm_config else {
+        return Check::new("Polly", Status::Skipped, "needs llvm-config");
+    };
+    let components = tool_output(path, &["--components"]).unwrap_or_default();
+    if !components.split_whitespace().any(|c| c == "polly") {
+        return Check::new("Polly", Status::Ok, "not used by this LLVM");
+    }
+    let libdir = tool_output(path, &["--libdir"]).unwrap_or_default();
+    let found = std::fs::read_dir(&libdir).is_ok_and(|entries| {
+        entries.flatten().any(|entry| {
+            let name = entry.file_name().to_string_lossy().to_lowercase();
+            name.starts_with("libpolly") || name.starts_with("polly")
+        })
+    });
+    if found {
+        Check::new("Polly", Status::Ok, format!("in {}", libdir))
+    } else {
+        Check::new(
+            "Polly",
+            Status::Missing,
+            format!("LLVM lists polly but {} has no Polly library", libdir),
+        )
+        .hint(distro.install(Package::Polly).map(String::from))
+    }
+}
+
+fn check_c_compiler(distro: Distro) -> Check {
+    match linker::detect_linker() {
+        Ok(driver) => {
+            let version = tool_output(Path::new(&driver), &["--version"])
+                .and_then(|out| out.lines().next().map(String::from))
+                .unwrap_or_default();
+            Check::new(
+                "C compiler",
+                Status::Ok,
+                format!("{} ({})", driver, version),
+            )
+        }
+        Err(e) => Check::new("C compiler", Status::Missing, e.to_string())
+            .hint(distro.install(Package::CCompiler).map(String::from)),
+    }
+}
+
+/// Build and run a program that prints 42
+fn check_build(oitec: &Path) -> Check {
+    let dir = std::env::temp_dir().join(format!("oitec-doctor-{}", std::process::id()));
+    let result = build_and_run(oitec, &dir);
+    let _ = std::fs::remove_dir_all(&dir);
+    match result {
+        Ok(()) => Check::new(
+            "build",
+            Status::Ok,
+            "compiled, linked and ran a test program",
+        ),
+        Err(e) => Check::new("build", Status::Missing, e),
+    }
+}
+
+fn build_and_run(oitec: &Path, dir: &Path) -> Result<(), String> {
+    std::fs::create_dir_all(dir).map_err(|e| format!("cannot create {}: {}", dir.display(), e))?;
+    let source = dir.join("doctor.ot");
+    let binary = dir
+        .join("doctor")
+        .with_extension(std::env::consts::EXE_EXTENSION);
+    std::fs::write(&source, "console.log(6 * 7);\n")
+        .map_err(|e| format!("cannot write {}: {}", source.display(), e))?;
+
+    let output = Command::new(oitec)
+        .arg("build")
+        .arg(&source)
+        .arg("-o")
+        .arg(&binary)
+        .output()
+        .map_err(|e| format!("cannot run {}: {}", oitec.display(), e))?;
+    if !output.status.success() || !binary.exists() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let last = stderr.lines().last().unwrap_or("no output");
+        return Err(format!("`oitec build` failed: {}", last));
+    }
+
+    let output = Command::new(&binary)
+        .output()
+        .map_err(|e| format!("built binary won't start: {}", e))?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    if !output.status.success() || stdout.trim() != "42" {
+        return Err(format!(
+            "built binary printed {:?} and exited with {:?}, expected 42",
+            stdout.trim(),
+            output.status.code()
+        ));
+    }
+    Ok(())
+}
+
+/// Trimmed stdout of a tool that ran successfully
+fn tool_output(tool: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new(tool).args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_distro_from_os_release() {
+        let debian = "PRETTY_NAME=\"Debian GNU/Linux 12 (bookworm)\"\nID=debian\n";
+        assert_eq!(Distro::from_os_release(debian), Distro::Debian);
+        let mint = "ID=linuxmint\nID_LIKE=\"ubuntu debian\"\n";
+        assert_eq!(Distro::from_os_release(mint), Distro::Debian);
+        let rocky = "ID=\"rocky\"\nID_LIKE=\"rhel centos fedora\"\n";
+        assert_eq!(Distro::from_os_release(rocky), Distro::Fedora);
+        let tumbleweed = "ID=\"opensuse-tumbleweed\"\nID_LIKE=\"opensuse suse\"\n";
+        assert_eq!(Distro::from_os_release(tumbleweed), Distro::Suse);
+        assert_eq!(Distro::from_os_release("ID=nixos\n"), Distro::Unknown);
+    }
+
+    #[test]
+    fn test_install_hints() {
+        assert_eq!(
+            Distro::Debian.install(Package::Polly),
+            Some("sudo apt install libpolly-18-dev")
+        );
+        assert_eq!(
+            Distro::MacOs.install(Package::Llvm),
+            Some("brew install llvm@18")
+        );
+        assert_eq!(Distro::Unknown.install(Package::CCompiler), None);
+    }
+
+    #[test]
+    fn test_llvm_major() {
+        assert_eq!(llvm_major("18.1.8\n"), Some(18));
+        assert_eq!(llvm_major("17.0.6"), Some(17));
+        assert_eq!(llvm_major(""), None);
+    }
+}
@@ -0,0 +1,336 @@
+//! Shared front end for the `oitec` subcommands
+//!
+//! Running, checking, benchmarking, JIT-compiling and building a file all
+//! start the same way: read the source, pick the syntax from the extension,
+//! apply the project's `script.toml` settings and compile to bytecode.
+//! `CompilationDriver` does that once so every subcommand agrees on
+//! decorators, `.tsx` detection, borrow checking and prelude loading.
+
+use crate::compiler::Compiler;
+use crate::compiler::borrow_ck::BorrowCheckLevel;
+use crate::manifest::Manifest;
+use crate::vm::VM;
+use crate::vm::bytecode_cache::BytecodeCache;
+use crate::vm::opcodes::OpCode;
+use std::fs;
+use std::path::{Path, PathBuf};
+use swc_ecma_parser::{Syntax, TsSyntax};
+
+/// Default path for the prelude file
+pub const PRELUDE_PATH: &str = "std/prelude.ot";
+
+/// Bootstrap compiler files (loaded in order when running bootstrap tests)
+pub const BOOTSTRAP_FILES: &[&str] = &[
+    "bootstrap/types.ot",
+    "bootstrap/lexer.ot",
+    "bootstrap/parser.ot",
+    "bootstrap/emitter.ot",
+    "bootstrap/ir.ot",
+    "bootstrap/ir_builder.ot",
+    "bootstrap/codegen.ot",
+    "bootstrap/pipeline.ot",
+];
+
+/// Modular compiler files (loaded in dependency order)
+pub const MODULAR_COMPILER_FILES: &[&str] = &[
+    // Level 1: No dependencies
+    "compiler/lexer/token.ot",
+    "compiler/ast/types.ot",
+    "compiler/ir/mod.ot",
+    // Level 2: Depends on level 1
+    "compiler/lexer/mod.ot",
+    "compiler/parser/expr.ot",
+    "compiler/parser/stmt.ot",
+    "compiler/ir/builder.ot",
+    "compiler/passes/types.ot",
+    "compiler/passes/typecheck.ot",
+    "compiler/passes/opt.ot",
+    "compiler/passes/lifetime_constraints.ot",
+    "compiler/passes/borrow_ck.ot",
+    // Level 3: Depends on level 2
+    "compiler/parser/mod.ot",
+    "compiler/passes/mod.ot",
+    "compiler/codegen/mod.ot",
+    // Level 4: Depends on level 3
+    "compiler/codegen/emitter.ot",
+    // Level 5: Backend modules
+    "compiler/backend/llvm/runtime.ot",
+    "compiler/backend/llvm/types.ot",
+    "compiler/backend/llvm/mod.ot",
+    // Level 6: Top-level pipeline
+    "compiler/pipeline.ot",
+];
+
+/// Source syntax for a file, chosen by extension. `.js`/`.mjs`/`.jsx` are
+/// plain ECMAScript; everything else, `.ot` included, is TypeScript with
+/// decorators, and `.tsx` also enables JSX. Every parse of a source file
+/// picks its syntax here.
+pub fn syntax_for_path(path: &str) -> Syntax {
+    if path.ends_with(".js") || path.ends_with(".mjs") || path.ends_with(".jsx") {
+        Syntax::Es(Default::default())
+    } else {
+        Syntax::Typescript(TsSyntax {
+            decorators: true,
+            tsx: path.ends_with(".tsx"),
+            ..Default::default()
+        })
+    }
+}
+
+/// Read a source file
+pub fn read_source(path: &str) -> Result<String, String> {
+    fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))
+}
+
+/// `bytecode` with its top-level `Return` (the one right before the final
+/// `Halt`) turned into `Halt`, so the script's frame and return value stay
+/// on the VM after `run_until_halt`
+pub fn keep_top_level_result(mut bytecode: Vec<OpCode>) -> Vec<OpCode> {
+    if let Some(i) = bytecode
+        .windows(2)
+        .position(|pair| matches!(pair, [OpCode::Return, OpCode::Halt]))
+    {
+        bytecode[i] = OpCode::Halt;
+    }
+    bytecode
+}
+
+/// The settings a project's files compile with: the manifest's
+/// per-directory `[compiler]` options under the command line's overrides.
+/// The VM keeps a copy for the modules it imports.
+#[derive(Debug, Clone, Default)]
+pub struct CompileSettings {
+    pub manifest: Manifest,
+    /// `--borrow-check`, which wins over the manifest for every file
+    pub borrow_check: Option<BorrowCheckLevel>,
+    /// Where compiles are cached, if anywhere (see `vm::bytecode_cache`)
+    pub bytecode_cache: Option<BytecodeCache>,
+}
+
+impl CompileSettings {
+    /// Borrow checking for the file at `path`
+    pub fn borrow_check_for(&self, path: &Path) -> BorrowCheckLevel {
+        self.borrow_check
+            .or_else(|| self.manifest.compiler_options_for(path).borrow_check)
+            .unwrap_or_default()
+    }
+
+    /// Whether `check` and `build` check the file at `path` against its
+    /// type annotations
+    pub fn typecheck_for(&self, path: &Path) -> bool {
+        self.manifest
+            .compiler_options_for(path)
+            .typecheck
+            .unwrap_or(true)
+    }
+}
+
+/// Compiles entry files and the scripts loaded ahead of them
+#[derive(Default)]
+pub struct CompilationDriver {
+    pub compiler: Compiler,
+    /// Settings for entry files and their imports; the prelude and compiler
+    /// modules are always checked strictly
+    pub settings: CompileSettings,
+}
+
+impl CompilationDriver {
+    /// A driver for `entry`, with the settings of the `script.toml` that
+    /// governs it
+    pub fn new(entry: &str) -> Self {
+        Self {
+            compiler: Compiler::new(),
+            settings: CompileSettings {
+                manifest: Manifest::discover_or_default(Path::new(entry)),
+                borrow_check: None,
+                bytecode_cache: None,
+            },
+        }
+    }
+
+    /// Override the project's borrow checking (`--borrow-check`)
+    pub fn with_borrow_check(mut self, level: Option<BorrowCheckLevel>) -> Self {
+        if level.is_some() {
+            self.settings.borrow_check = level;
+        }
+        self
+    }
+
+    /// Reuse compiles saved in the project's bytecode cache, and save new
+    /// ones there (`--no-bytecode-cache` leaves this off)
+    pub fn with_bytecode_cache(mut self, enabled: bool) -> Self {
+        if enabled {
+            self.settings.bytecode_cache =
+                Some(BytecodeCache::for_project(&self.settings.manifest));
+        }
+        self
+    }
+
+    /// Have `vm` compile the modules it imports with these settings
+    pub fn configure_vm(&self, vm: &mut VM) {
+        vm.compile_settings = self.settings.clone();
+    }
+
+    /// Whether the file at `path` is checked against its type annotations
+    pub fn typecheck(&self, path: &str) -> bool {
+        self.settings.typecheck_for(Path::new(path))
+    }
+
+    /// Compile an entry file's source with the settings of its directory.
+    /// Warnings are left in `compiler.warnings`.
+    pub fn compile(&mut self, path: &str, source: &str) -> Result<Vec<OpCode>, String> {
+        self.compiler.borrow_check = self.settings.borrow_check_for(Path::new(path));
+        self.compiler.bytecode_cache = self.settings.bytecode_cache.clone();
+        self.compiler
+            .compile_with_syntax(source, Some(syntax_for_path(path)))
+    }
+
+    /// Read and compile an entry file, returning its source with the bytecode
+    pub fn compile_file(&mut self, path: &str) -> Result<(String, Vec<OpCode>), String> {
+        let source = read_source(path)?;
+        let bytecode = self
+            .compile(path, &source)
+            .map_err(|e| format!("Compilation failed: {}", e))?;
+        Ok((source, bytecode))
+    }
+
+    /// Print the last compile's warnings to stderr
+    pub fn print_warnings(&self) {
+        for warning in &self.compiler.warnings {
+            eprintln!("Warning: {}", warning);
+        }
+    }
+
+    /// Compile and run a support script (the prelude, a compiler module)
+    /// to completion. `append` keeps the program already loaded in `vm`.
+    pub fn run_script(&mut self, vm: &mut VM, path: &str, append: bool) -> Result<(), String> {
+        let source = read_source(path)?;
+        self.compiler.borrow_check = BorrowCheckLevel::default();
+        self.compiler.bytecode_cache = self.settings.bytecode_cache.clone();
+        let bytecode = self
+            .compiler
+            .compile_with_syntax(&source, Some(syntax_for_path(path)))
+            .map_err(|e| format!("Failed to compile {}: {}", path, e))?;
+        let bytecode_len = bytecode.len();
+
+        if append {
+            let offset = vm.append_program(bytecode);
+            eprintln!("  {} ({} ops at offset {})", path, bytecode_len, offset);
+        } else {
+            vm.load_program_with_path(bytecode, PathBuf::from(path));
+            eprintln!("  {} ({} ops)", path, bytecode_len);
+        }
+
+        vm.run_until_halt();
+        Ok(())
+    }
+
+    /// Run the prelude, when there is one. It sets up global constants
+    /// (OP, TOKEN, TYPE) and utility functions.
+    pub fn load_prelude(&mut self, vm: &mut VM) -> Result<(), String> {
+        if Path::new(PRELUDE_PATH).exists() {
+            self.run_script(vm, PRELUDE_PATH, false)?;
+        }
+        Ok(())
+    }
+
+    /// Run the compiler modules an entry file under `bootstrap/`, `tests/`
+    /// or `compiler/` expects to find loaded
+    pub fn load_compiler_modules(&mut self, vm: &mut VM, entry: &str) -> Result<(), String> {
+        // Compare with forward slashes so Windows paths (`bootstrap\lexer.ot`) match too
+        let unix_path = entry.replace('\\', "/");
+        let is_bootstrap = unix_path.contains("bootstrap/") || unix_path.contains("tests/");
+        let is_modular_compiler =
+            unix_path.contains("compiler/") && !unix_path.contains("bootstrap/");
+
+        if is_bootstrap {
+            for bootstrap_file in BOOTSTRAP_FILES {
+                if Path::new(bootstrap_file).exists() {
+                    self.run_script(vm, bootstrap_file, true)?;
+                } else {
+                    eprintln!("Warning: Bootstrap file not found: {}", bootstrap_file);
+                }
+            }
+        }
+
+        if is_modular_compiler {
+            for modular_file in MODULAR_COMPILER_FILES {
+                // Skip the main file being run if it's in the list
+                if *modular_file == unix_path {
+                    continue;
+                }
+                if Path::new(modular_file).exists() {
+                    self.run_script(vm, modular_file, true)?;
+                } else {
+                    eprintln!("Warning: Modular compiler file not found: {}", modular_file);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_syntax_follows_the_extension() {
+        let ts = |path: &str| match syntax_for_path(path) {
+            Syntax::Typescript(ts) => Some((ts.decorators, ts.tsx)),
+            _ => None,
+        };
+        assert_eq!(ts("app.ot"), Some((true, false)));
+        assert_eq!(ts("app.ts"), Some((true, false)));
+        assert_eq!(ts("view.tsx"), Some((true, true)));
+        assert_eq!(ts("app.js"), None);
+        assert_eq!(ts("view.jsx"), None);
+    }
+
+    #[test]
+    fn test_settings_follow_the_directory() {
+        let settings = CompileSettings {
+            manifest: Manifest::parse(
+                "[compiler]\nborrow-check = \"warn\"\n\
+                 [compiler.dirs.vendor]\nborrow-check = \"off\"\ntypecheck = false\n",
+            )
+            .unwrap(),
+            borrow_check: None,
+            bytecode_cache: None,
+        };
+        let vendored = Path::new("vendor/lib.ot");
+        assert_eq!(settings.borrow_check_for(vendored), BorrowCheckLevel::Off);
+        assert!(!settings.typecheck_for(vendored));
+        let own = Path::new("src/main.ot");
+        assert_eq!(settings.borrow_check_for(own), BorrowCheckLevel::Warn);
+        assert!(settings.typecheck_for(own));
+
+        let mut driver = CompilationDriver {
+            settings,
+            ..Default::default()
+        }
+        .with_borrow_check(Some(BorrowCheckLevel::Error));
+        assert_eq!(
+            driver.settings.borrow_check_for(vendored),
+            BorrowCheckLevel::Error
+        );
+        driver.settings.borrow_check = None;
+        let moved_twice = "let a = [1]; let b = a; let c = a;";
+        assert!(driver.compile("src/main.ot", moved_twice).is_ok());
+        assert!(!driver.compiler.warnings.is_empty());
+        assert!(driver.compile("vendor/lib.ot", moved_twice).is_ok());
+        assert!(driver.compiler.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_keep_top_level_result() {
+        let bytecode = vec![
+            OpCode::Push(crate::vm::value::JsValue::Number(1.0)),
+            OpCode::Return,
+            OpCode::Halt,
+        ];
+        let kept = keep_top_level_result(bytecode);
+        assert!(matches!(kept[1], OpCode::Halt));
+        assert_eq!(kept.len(), 3);
+    }
+}
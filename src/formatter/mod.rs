@@ -0,0 +1,193 @@
+//! Source formatter (`oitec fmt`)
+//!
+//! Parses a file with SWC and pretty-prints it with a stable, configurable
+//! style. Formatting is idempotent: `format(format(x)) == format(x)`, which
+//! `format_source` verifies before returning, so `--check` can be used as a
+//! CI gate.
+//!
+//! Constructs the printer has no layout rules for (TypeScript declarations,
+//! JSX, ...) and statements with comments inside expressions are copied from
+//! the source as-is, so formatting never drops code or comments.
+
+mod printer;
+
+use crate::driver::syntax_for_path;
+use printer::{Printer, SourceComment};
+use swc_common::comments::SingleThreadedComments;
+use swc_common::{FileName, SourceMap, Spanned, sync::Lrc};
+use swc_ecma_parser::{Parser, StringInput, lexer::Lexer};
+
+/// Preferred quote character for string literals
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuoteStyle {
+    Double,
+    Single,
+}
+
+impl QuoteStyle {
+    fn char(self) -> char {
+        match self {
+            QuoteStyle::Double => '"',
+            QuoteStyle::Single => '\'',
+        }
+    }
+}
+
+/// Whether multi-line lists get a comma after their last element
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrailingComma {
+    None,
+    All,
+}
+
+/// Formatting style
+#[derive(Debug, Clone)]
+pub struct FormatOptions {
+    /// Spaces per indentation level
+    pub indent_width: usize,
+    pub quote_style: QuoteStyle,
+    pub trailing_comma: TrailingComma,
+    /// Lists that don't fit in this many columns are split one element per line
+    pub line_width: usize,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self {
+            indent_width: 4,
+            quote_style: QuoteStyle::Double,
+            trailing_comma: TrailingComma::None,
+            line_width: 80,
+        }
+    }
+}
+
+/// Format a source file. Fails on syntax errors.
+pub fn format_source(source: &str, path: &str, options: &FormatOptions) -> Result<String, String> {
+    let formatted = format_once(source, path, options)?;
+    let again = format_once(&formatted, path, options)
+        .map_err(|e| format!("formatter produced invalid output: {}", e))?;
+    if again != formatted {
+        return Err("formatter output is not stable; leaving file unchanged".to_string());
+    }
+    Ok(formatted)
+}
+
+fn format_once(source: &str, path: &str, options: &FormatOptions) -> Result<String, String> {
+    let cm: Lrc<SourceMap> = Default::default();
+    let fm = cm.new_source_file(
+        FileName::Custom(path.to_string()).into(),
+        source.to_string(),
+    );
+    let base = fm.start_pos.0 as usize;
+    let comments = SingleThreadedComments::default();
+    let lexer = Lexer::new(
+        syntax_for_path(path),
+        Default::default(),
+        StringInput::from(&*fm),
+        Some(&comments),
+    );
+    let mut parser = Parser::new_from(lexer);
+    let module = parser
+        .parse_module()
+        .map_err(|e| syntax_error(source, base, e.span().lo.0 as usize, &e.kind().msg()))?;
+    if let Some(e) = parser.take_errors().into_iter().next() {
+        return Err(syntax_error(
+            source,
+            base,
+            e.span().lo.0 as usize,
+            &e.kind().msg(),
+        ));
+    }
+
+    let mut collected: Vec<SourceComment> = {
+        let (leading, trailing) = comments.borrow_all();
+        leading
+            .values()
+            .chain(trailing.values())
+            .flatten()
+            .map(|c| SourceComment {
+                lo: (c.span.lo.0 as usize).saturating_sub(base),
+                hi: (c.span.hi.0 as usize).saturating_sub(base),
+            })
+            .collect()
+    };
+    collected.sort_by_key(|c| c.lo);
+    collected.dedup_by_key(|c| c.lo);
+
+    let mut printer = Printer::new(source, base, options, &collected);
+    printer.module(&module);
+    Ok(printer.finish())
+}
+
+fn syntax_error(source: &str, base: usize, pos: usize, msg: &str) -> String {
+    let offset = pos.saturating_sub(base).min(source.len());
+    let line = source[..offset].matches('\n').count() + 1;
+    let col = offset - source[..offset].rfind('\n').map_or(0, |i| i + 1) + 1;
+    format!("syntax error at {}:{}: {}", line, col, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fmt(source: &str) -> String {
+        format_source(source, "test.ot", &FormatOptions::default()).expect("format failed")
+    }
+
+    #[test]
+    fn test_normalizes_spacing_and_indentation() {
+        let out = fmt("function add(a:number,b:number):number{return a+b}\nlet x=add( 1,2 )");
+        assert_eq!(
+            out,
+            "function add(a: number, b: number): number {\n    return a + b;\n}\nlet x = add(1, 2);\n"
+        );
+    }
+
+    #[test]
+    fn test_preserves_comments_and_blank_lines() {
+        let src = "// header\n\nlet a = 1; // trailing\n\n\n/* block */\nif (a) {\n  // inside\n  a = 2\n}\n";
+        assert_eq!(
+            fmt(src),
+            "// header\n\nlet a = 1; // trailing\n\n/* block */\nif (a) {\n    // inside\n    a = 2;\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_quote_style_and_trailing_commas() {
+        let options = FormatOptions {
+            indent_width: 2,
+            quote_style: QuoteStyle::Single,
+            trailing_comma: TrailingComma::All,
+            line_width: 80,
+        };
+        let src = "let s = \"it's\"; let t = \"plain\";\nlet o = {\na: 1, b: \"x\"};\n";
+        let out = format_source(src, "test.ot", &options).unwrap();
+        assert_eq!(
+            out,
+            "let s = \"it's\";\nlet t = 'plain';\nlet o = {\n  a: 1,\n  b: 'x',\n};\n"
+        );
+    }
+
+    #[test]
+    fn test_breaks_long_lists() {
+        let out = fmt(
+            "let items = [\"alpha\", \"beta\", \"gamma\", \"delta\", \"epsilon\", \"zeta\", \"eta\", \"theta\"];",
+        );
+        assert!(out.starts_with("let items = [\n    \"alpha\",\n"));
+        assert!(out.ends_with("    \"theta\"\n];\n"));
+    }
+
+    #[test]
+    fn test_idempotent_on_bootstrap_style_code() {
+        let src = "class Lexer {\n    pos: number = 0;\n\n    constructor(private source: string) {}\n\n    peek(): number {\n        return this.pos < this.source.length ? this.source.charCodeAt(this.pos) : -1;\n    }\n}\nexport { Lexer };\nconst run = async (xs) => { for (const x of xs) { await x; } };\n";
+        let once = fmt(src);
+        assert_eq!(fmt(&once), once);
+    }
+
+    #[test]
+    fn test_rejects_syntax_errors() {
+        let err = format_source("let = ;", "test.ot", &FormatOptions::default()).unwrap_err();
+        assert!(err.starts_with("syntax error at 1:"));
+    }
+}
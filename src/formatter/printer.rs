@@ -0,0 +1,1572 @@
+//! AST printer for the formatter
+//!
+//! Output is built line by line in a `String`. Lists (arguments, parameters,
+//! array and object literals, import/export specifiers) are first rendered
+//! flat by a throwaway printer; if that rendering needs a line break or does
+//! not fit the line width, the list is printed one element per line instead.
+
+use super::{FormatOptions, TrailingComma};
+use swc_common::{Span, Spanned};
+use swc_ecma_ast::*;
+
+/// A comment's byte range in the source (delimiters included)
+#[derive(Debug, Clone, Copy)]
+pub(super) struct SourceComment {
+    pub lo: usize,
+    pub hi: usize,
+}
+
+pub(super) struct Printer<'a> {
+    src: &'a str,
+    base: usize,
+    opts: &'a FormatOptions,
+    comments: &'a [SourceComment],
+    emitted: Vec<bool>,
+    out: String,
+    indent: usize,
+    at_line_start: bool,
+    /// Source offset where the previously printed item ended
+    last_end: usize,
+    /// Flat mode: any line break marks the attempt as failed
+    flat: bool,
+    broke: bool,
+}
+
+impl<'a> Printer<'a> {
+    pub fn new(
+        src: &'a str,
+        base: usize,
+        opts: &'a FormatOptions,
+        comments: &'a [SourceComment],
+    ) -> Self {
+        Self {
+            src,
+            base,
+            opts,
+            comments,
+            emitted: vec![false; comments.len()],
+            out: String::new(),
+            indent: 0,
+            at_line_start: true,
+            last_end: 0,
+            flat: false,
+            broke: false,
+        }
+    }
+
+    pub fn finish(mut self) -> String {
+        let trimmed = self.out.trim_end().len();
+        self.out.truncate(trimmed);
+        self.out.push('\n');
+        self.out
+    }
+
+    // ------------------------------------------------------------------
+    // Output primitives
+    // ------------------------------------------------------------------
+
+    fn w(&mut self, s: &str) {
+        if s.is_empty() {
+            return;
+        }
+        if self.at_line_start {
+            let width = self.indent * self.opts.indent_width;
+            self.out.extend(std::iter::repeat_n(' ', width));
+            self.at_line_start = false;
+        }
+        self.out.push_str(s);
+    }
+
+    fn newline(&mut self) {
+        if self.flat {
+            self.broke = true;
+            return;
+        }
+        self.out.push('\n');
+        self.at_line_start = true;
+    }
+
+    /// Start a new line unless already at the start of one.
+    fn ensure_line(&mut self) {
+        if !self.at_line_start {
+            self.newline();
+        }
+    }
+
+    /// Start a new line preceded by an empty one. Never at the start of the
+    /// file or directly after an opening brace.
+    fn blank_line(&mut self) {
+        self.ensure_line();
+        if !self.out.is_empty()
+            && !self.out.ends_with("\n\n")
+            && !self.out.trim_end().ends_with('{')
+        {
+            self.out.push('\n');
+        }
+    }
+
+    fn column(&self) -> usize {
+        if self.at_line_start {
+            return self.indent * self.opts.indent_width;
+        }
+        let line_start = self.out.rfind('\n').map_or(0, |i| i + 1);
+        self.out[line_start..].chars().count()
+    }
+
+    fn fits(&self, s: &str) -> bool {
+        !s.contains('\n') && self.column() + s.chars().count() <= self.opts.line_width
+    }
+
+    /// Render with `f` in flat mode; `None` if it needed a line break.
+    fn try_flat(&self, f: impl FnOnce(&mut Printer<'a>)) -> Option<String> {
+        let mut sub = Printer {
+            src: self.src,
+            base: self.base,
+            opts: self.opts,
+            comments: self.comments,
+            emitted: self.emitted.clone(),
+            out: String::new(),
+            indent: self.indent,
+            at_line_start: false,
+            last_end: self.last_end,
+            flat: true,
+            broke: false,
+        };
+        f(&mut sub);
+        (!sub.broke).then_some(sub.out)
+    }
+
+    // ------------------------------------------------------------------
+    // Source access and comments
+    // ------------------------------------------------------------------
+
+    fn lo(&self, span: Span) -> usize {
+        (span.lo.0 as usize).saturating_sub(self.base)
+    }
+
+    fn hi(&self, span: Span) -> usize {
+        (span.hi.0 as usize).saturating_sub(self.base)
+    }
+
+    fn text(&self, span: Span) -> &'a str {
+        let src: &'a str = self.src;
+        src.get(self.lo(span)..self.hi(span)).unwrap_or("")
+    }
+
+    /// Copy a node from the source unchanged.
+    fn verbatim(&mut self, span: Span) {
+        self.mark_comments_within(self.lo(span), self.hi(span));
+        let text = self.text(span);
+        if text.contains('\n') && self.flat {
+            self.broke = true;
+        }
+        self.w(text);
+    }
+
+    /// Copy a multi-line node, shifting continuation lines to the current indent.
+    fn verbatim_block(&mut self, lo: usize, hi: usize) {
+        self.mark_comments_within(lo, hi);
+        let src: &'a str = self.src;
+        let text = &src[lo..hi];
+        // Template literal contents must not be re-indented
+        if text.contains('`') {
+            self.w(text);
+            return;
+        }
+        let line_start = src[..lo].rfind('\n').map_or(0, |i| i + 1);
+        let original_indent = src[line_start..lo].len() - src[line_start..lo].trim_start().len();
+        for (i, line) in text.split('\n').enumerate() {
+            if i > 0 {
+                self.newline();
+                let strip = line
+                    .char_indices()
+                    .take_while(|(idx, c)| *idx < original_indent && c.is_whitespace())
+                    .count();
+                let line = line[strip..].trim_end();
+                self.w(line);
+            } else {
+                self.w(line.trim_end());
+            }
+        }
+    }
+
+    fn mark_comments_within(&mut self, lo: usize, hi: usize) {
+        for (i, c) in self.comments.iter().enumerate() {
+            if c.lo >= lo && c.hi <= hi {
+                self.emitted[i] = true;
+            }
+        }
+    }
+
+    fn has_pending_comment_within(&self, lo: usize, hi: usize) -> bool {
+        self.comments
+            .iter()
+            .zip(&self.emitted)
+            .any(|(c, emitted)| !emitted && c.lo > lo && c.hi < hi)
+    }
+
+    fn has_blank_line(&self, from: usize, to: usize) -> bool {
+        if from >= to {
+            return false;
+        }
+        let segments: Vec<&str> = self.src[from..to].split('\n').collect();
+        segments.len() >= 3
+            && segments[1..segments.len() - 1]
+                .iter()
+                .any(|s| s.trim().is_empty())
+    }
+
+    /// Print unemitted comments that start before `pos`, each on its own line.
+    fn flush_comments_before(&mut self, pos: usize) {
+        for i in 0..self.comments.len() {
+            let c = self.comments[i];
+            if self.emitted[i] || c.lo >= pos {
+                continue;
+            }
+            if self.has_blank_line(self.last_end, c.lo) {
+                self.blank_line();
+            } else {
+                self.ensure_line();
+            }
+            self.verbatim_block(c.lo, c.hi);
+            self.emitted[i] = true;
+            self.last_end = c.hi;
+            self.newline();
+        }
+    }
+
+    /// Attach a comment that follows `end` on the same source line.
+    fn trailing_comment(&mut self, end: usize) {
+        let src: &'a str = self.src;
+        for i in 0..self.comments.len() {
+            let c = self.comments[i];
+            if self.emitted[i] || c.lo < end {
+                continue;
+            }
+            let text = &src[c.lo..c.hi];
+            if src[end..c.lo].trim_matches([' ', '\t']).is_empty() && !text.contains('\n') {
+                self.w(" ");
+                self.w(text);
+                self.emitted[i] = true;
+                self.last_end = c.hi;
+            }
+            return;
+        }
+    }
+
+    /// Print statement-like items on their own lines with comments and
+    /// single blank lines preserved. `end` is where the enclosing body ends.
+    fn items<T: Spanned>(&mut self, items: &[T], end: usize, print: impl Fn(&mut Self, &T)) {
+        for item in items {
+            let span = item.span();
+            let (lo, hi) = (self.lo(span), self.hi(span));
+            self.flush_comments_before(lo);
+            if self.has_blank_line(self.last_end, lo) {
+                self.blank_line();
+            } else {
+                self.ensure_line();
+            }
+
+            // Comments inside expressions have no layout rule: keep the
+            // item exactly as written in that case
+            let snapshot = (self.out.len(), self.emitted.clone(), self.at_line_start);
+            print(self, item);
+            if self.has_pending_comment_within(lo, hi) {
+                self.out.truncate(snapshot.0);
+                self.emitted = snapshot.1;
+                self.at_line_start = snapshot.2;
+                self.verbatim_block(lo, hi);
+            }
+            self.last_end = hi;
+            self.trailing_comment(hi);
+        }
+        self.flush_comments_before(end);
+    }
+
+    // ------------------------------------------------------------------
+    // Lists
+    // ------------------------------------------------------------------
+
+    /// Print a comma-separated list, flat if it fits, else one item per line.
+    fn list<T>(
+        &mut self,
+        open: &str,
+        close: &str,
+        items: &[T],
+        opts: ListOpts,
+        print: impl Fn(&mut Self, &T),
+    ) {
+        if items.is_empty() {
+            self.w(open);
+            self.w(close);
+            return;
+        }
+        if !opts.force_break {
+            let flat = self.try_flat(|p| {
+                p.w(open);
+                if opts.pad {
+                    p.w(" ");
+                }
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        p.w(", ");
+                    }
+                    print(p, item);
+                }
+                if opts.pad {
+                    p.w(" ");
+                }
+                p.w(close);
+            });
+            if let Some(flat) = flat
+                && self.fits(&flat)
+            {
+                self.w(&flat);
+                return;
+            }
+            if opts.hug_last && self.hug_last(open, close, items, &print) {
+                return;
+            }
+        }
+        if self.flat {
+            self.broke = true;
+            return;
+        }
+
+        self.w(open);
+        self.indent += 1;
+        for (i, item) in items.iter().enumerate() {
+            self.newline();
+            print(self, item);
+            let last = i + 1 == items.len();
+            if !last || (opts.trailing && self.opts.trailing_comma == TrailingComma::All) {
+                self.w(",");
+            }
+        }
+        self.indent -= 1;
+        self.newline();
+        self.w(close);
+    }
+
+    /// `f(a, b, () => {` ... `})`: keep leading items inline when only the
+    /// last one (a function or object) needs to span lines.
+    fn hug_last<T>(
+        &mut self,
+        open: &str,
+        close: &str,
+        items: &[T],
+        print: &impl Fn(&mut Self, &T),
+    ) -> bool {
+        let (last, head) = items.split_last().expect("non-empty list");
+        let Some(prefix) = self.try_flat(|p| {
+            p.w(open);
+            for item in head {
+                print(p, item);
+                p.w(", ");
+            }
+        }) else {
+            return false;
+        };
+        if !self.fits(&prefix) {
+            return false;
+        }
+        self.w(&prefix);
+        print(self, last);
+        self.w(close);
+        true
+    }
+
+    // ------------------------------------------------------------------
+    // Modules and statements
+    // ------------------------------------------------------------------
+
+    pub fn module(&mut self, module: &Module) {
+        if let Some(shebang) = &module.shebang {
+            self.w("#!");
+            self.w(shebang);
+            self.newline();
+            self.last_end = self.src.find('\n').unwrap_or(self.src.len());
+        }
+        self.items(&module.body, self.src.len(), |p, item| match item {
+            ModuleItem::Stmt(stmt) => p.stmt(stmt),
+            ModuleItem::ModuleDecl(decl) => p.module_decl(decl),
+        });
+    }
+
+    fn module_decl(&mut self, decl: &ModuleDecl) {
+        match decl {
+            ModuleDecl::Import(import) if import.phase == ImportPhase::Evaluation => {
+                self.w("import ");
+                if import.type_only {
+                    self.w("type ");
+                }
+                let mut named = Vec::new();
+                let mut wrote_binding = false;
+                for spec in &import.specifiers {
+                    match spec {
+                        ImportSpecifier::Default(d) => {
+                            self.w(&d.local.sym);
+                            wrote_binding = true;
+                        }
+                        ImportSpecifier::Namespace(ns) => {
+                            if wrote_binding {
+                                self.w(", ");
+                            }
+                            self.w("* as ");
+                            self.w(&ns.local.sym);
+                            wrote_binding = true;
+                        }
+                        ImportSpecifier::Named(n) => named.push(n),
+                    }
+                }
+                if !named.is_empty() {
+                    if wrote_binding {
+                        self.w(", ");
+                    }
+                    self.list("{", "}", &named, ListOpts::braces(), |p, n| {
+                        if n.is_type_only {
+                            p.w("type ");
+                        }
+                        if let Some(imported) = &n.imported {
+                            p.export_name(imported);
+                            p.w(" as ");
+                        }
+                        p.w(&n.local.sym);
+                    });
+                }
+                if wrote_binding || !named.is_empty() {
+                    self.w(" from ");
+                }
+                self.string(&import.src);
+                self.import_with(&import.with);
+                self.w(";");
+            }
+            ModuleDecl::ExportDecl(export) => {
+                self.w("export ");
+                self.decl(&export.decl);
+            }
+            ModuleDecl::ExportNamed(export)
+                if !export
+                    .specifiers
+                    .iter()
+                    .any(|s| matches!(s, ExportSpecifier::Default(_))) =>
+            {
+                self.w("export ");
+                if export.type_only {
+                    self.w("type ");
+                }
+                match export.specifiers.as_slice() {
+                    [ExportSpecifier::Namespace(ns)] => {
+                        self.w("* as ");
+                        self.export_name(&ns.name);
+                    }
+                    specifiers => {
+                        self.list("{", "}", specifiers, ListOpts::braces(), |p, s| {
+                            if let ExportSpecifier::Named(n) = s {
+                                if n.is_type_only {
+                                    p.w("type ");
+                                }
+                                p.export_name(&n.orig);
+                                if let Some(exported) = &n.exported {
+                                    p.w(" as ");
+                                    p.export_name(exported);
+                                }
+                            } else {
+                                p.verbatim(s.span());
+                            }
+                        });
+                    }
+                }
+                if let Some(src) = &export.src {
+                    self.w(" from ");
+                    self.string(src);
+                    self.import_with(&export.with);
+                }
+                self.w(";");
+            }
+            ModuleDecl::ExportDefaultExpr(export) => {
+                self.w("export default ");
+                self.expr(&export.expr);
+                self.w(";");
+            }
+            ModuleDecl::ExportDefaultDecl(export) => match &export.decl {
+                DefaultDecl::Fn(f) => {
+                    self.w("export default ");
+                    self.function(f.ident.as_ref(), &f.function);
+                }
+                DefaultDecl::Class(c) => {
+                    self.w("export default ");
+                    self.class(c.ident.as_ref(), &c.class, false);
+                }
+                _ => self.verbatim(export.span),
+            },
+            ModuleDecl::ExportAll(export) => {
+                self.w("export ");
+                if export.type_only {
+                    self.w("type ");
+                }
+                self.w("* from ");
+                self.string(&export.src);
+                self.import_with(&export.with);
+                self.w(";");
+            }
+            other => self.verbatim(other.span()),
+        }
+    }
+
+    fn import_with(&mut self, with: &Option<Box<ObjectLit>>) {
+        if let Some(with) = with {
+            self.w(" with ");
+            self.object_lit(with);
+        }
+    }
+
+    fn export_name(&mut self, name: &ModuleExportName) {
+        match name {
+            ModuleExportName::Ident(id) => self.w(&id.sym),
+            ModuleExportName::Str(s) => self.string(s),
+        }
+    }
+
+    fn block(&mut self, block: &BlockStmt) {
+        let (lo, hi) = (self.lo(block.span), self.hi(block.span));
+        if block.stmts.is_empty() && !self.has_pending_comment_within(lo, hi) {
+            self.w("{}");
+            return;
+        }
+        self.w("{");
+        self.indent += 1;
+        self.last_end = lo + 1;
+        self.items(&block.stmts, hi.saturating_sub(1), |p, s| p.stmt(s));
+        self.indent -= 1;
+        self.ensure_line();
+        self.w("}");
+    }
+
+    /// Body of if/while/for: braces stay on the same line, a single
+    /// statement goes on its own indented line.
+    fn body(&mut self, body: &Stmt) {
+        if let Stmt::Block(block) = body {
+            self.w(" ");
+            self.block(block);
+        } else if let Stmt::Empty(_) = body {
+            self.w(";");
+        } else {
+            self.indent += 1;
+            self.newline();
+            self.stmt(body);
+            self.indent -= 1;
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Block(block) => self.block(block),
+            Stmt::Empty(_) => self.w(";"),
+            Stmt::Debugger(_) => self.w("debugger;"),
+            Stmt::Return(ret) => {
+                self.w("return");
+                if let Some(arg) = &ret.arg {
+                    self.w(" ");
+                    self.expr(arg);
+                }
+                self.w(";");
+            }
+            Stmt::Labeled(labeled) => {
+                self.w(&labeled.label.sym);
+                self.w(": ");
+                self.stmt(&labeled.body);
+            }
+            Stmt::Break(brk) => {
+                self.w("break");
+                if let Some(label) = &brk.label {
+                    self.w(" ");
+                    self.w(&label.sym);
+                }
+                self.w(";");
+            }
+            Stmt::Continue(cont) => {
+                self.w("continue");
+                if let Some(label) = &cont.label {
+                    self.w(" ");
+                    self.w(&label.sym);
+                }
+                self.w(";");
+            }
+            Stmt::If(if_stmt) => self.if_stmt(if_stmt),
+            Stmt::Switch(switch) => {
+                self.w("switch (");
+                self.expr(&switch.discriminant);
+                self.w(") {");
+                self.indent += 1;
+                self.last_end = self.lo(switch.span);
+                let end = self.hi(switch.span).saturating_sub(1);
+                self.items(&switch.cases, end, |p, case| {
+                    match &case.test {
+                        Some(test) => {
+                            p.w("case ");
+                            p.expr(test);
+                            p.w(":");
+                        }
+                        None => p.w("default:"),
+                    }
+                    match case.cons.as_slice() {
+                        [Stmt::Block(block)] => {
+                            p.w(" ");
+                            p.block(block);
+                        }
+                        cons => {
+                            p.indent += 1;
+                            p.items(cons, p.hi(case.span), |p, s| p.stmt(s));
+                            p.indent -= 1;
+                        }
+                    }
+                });
+                self.indent -= 1;
+                self.ensure_line();
+                self.w("}");
+            }
+            Stmt::Throw(throw) => {
+                self.w("throw ");
+                self.expr(&throw.arg);
+                self.w(";");
+            }
+            Stmt::Try(try_stmt) => {
+                self.w("try ");
+                self.block(&try_stmt.block);
+                if let Some(handler) = &try_stmt.handler {
+                    self.w(" catch ");
+                    if let Some(param) = &handler.param {
+                        self.w("(");
+                        self.pat(param);
+                        self.w(") ");
+                    }
+                    self.block(&handler.body);
+                }
+                if let Some(finalizer) = &try_stmt.finalizer {
+                    self.w(" finally ");
+                    self.block(finalizer);
+                }
+            }
+            Stmt::While(while_stmt) => {
+                self.w("while (");
+                self.expr(&while_stmt.test);
+                self.w(")");
+                self.body(&while_stmt.body);
+            }
+            Stmt::DoWhile(do_while) => {
+                self.w("do");
+                self.body(&do_while.body);
+                if matches!(*do_while.body, Stmt::Block(_)) {
+                    self.w(" ");
+                } else {
+                    self.newline();
+                }
+                self.w("while (");
+                self.expr(&do_while.test);
+                self.w(");");
+            }
+            Stmt::For(for_stmt) => {
+                self.w("for (");
+                match &for_stmt.init {
+                    Some(VarDeclOrExpr::VarDecl(var)) => self.var_decl(var),
+                    Some(VarDeclOrExpr::Expr(e)) => self.expr(e),
+                    None => {}
+                }
+                self.w(";");
+                if let Some(test) = &for_stmt.test {
+                    self.w(" ");
+                    self.expr(test);
+                }
+                self.w(";");
+                if let Some(update) = &for_stmt.update {
+                    self.w(" ");
+                    self.expr(update);
+                }
+                self.w(")");
+                self.body(&for_stmt.body);
+            }
+            Stmt::ForIn(for_in) => {
+                self.w("for (");
+                self.for_head(&for_in.left);
+                self.w(" in ");
+                self.expr(&for_in.right);
+                self.w(")");
+                self.body(&for_in.body);
+            }
+            Stmt::ForOf(for_of) => {
+                self.w(if for_of.is_await {
+                    "for await ("
+                } else {
+                    "for ("
+                });
+                self.for_head(&for_of.left);
+                self.w(" of ");
+                self.expr(&for_of.right);
+                self.w(")");
+                self.body(&for_of.body);
+            }
+            Stmt::Decl(decl) => self.decl(decl),
+            Stmt::Expr(expr_stmt) => {
+                self.expr(&expr_stmt.expr);
+                self.w(";");
+            }
+            other => self.verbatim(other.span()),
+        }
+    }
+
+    fn if_stmt(&mut self, if_stmt: &IfStmt) {
+        self.w("if (");
+        self.expr(&if_stmt.test);
+        self.w(")");
+        self.body(&if_stmt.cons);
+        if let Some(alt) = &if_stmt.alt {
+            if matches!(*if_stmt.cons, Stmt::Block(_)) {
+                self.w(" ");
+            } else {
+                self.newline();
+            }
+            self.w("else");
+            match alt.as_ref() {
+                Stmt::If(nested) => {
+                    self.w(" ");
+                    self.if_stmt(nested);
+                }
+                other => self.body(other),
+            }
+        }
+    }
+
+    fn for_head(&mut self, head: &ForHead) {
+        match head {
+            ForHead::VarDecl(var) => self.var_decl(var),
+            ForHead::Pat(pat) => self.pat(pat),
+            other => self.verbatim(other.span()),
+        }
+    }
+
+    fn decl(&mut self, decl: &Decl) {
+        match decl {
+            Decl::Var(var) => {
+                self.var_decl(var);
+                self.w(";");
+            }
+            Decl::Fn(f) => {
+                if f.declare {
+                    self.w("declare ");
+                }
+                self.function(Some(&f.ident), &f.function);
+            }
+            Decl::Class(c) => {
+                if c.declare {
+                    self.w("declare ");
+                }
+                self.class(Some(&c.ident), &c.class, false);
+            }
+            other => self.verbatim(other.span()),
+        }
+    }
+
+    fn var_decl(&mut self, var: &VarDecl) {
+        if var.decls.iter().any(|d| d.definite) {
+            self.verbatim(var.span);
+            return;
+        }
+        if var.declare {
+            self.w("declare ");
+        }
+        self.w(match var.kind {
+            VarDeclKind::Var => "var ",
+            VarDeclKind::Let => "let ",
+            VarDeclKind::Const => "const ",
+        });
+        for (i, declarator) in var.decls.iter().enumerate() {
+            if i > 0 {
+                self.w(", ");
+            }
+            self.pat(&declarator.name);
+            if let Some(init) = &declarator.init {
+                self.w(" = ");
+                self.expr(init);
+            }
+        }
+    }
+
+    // ------------------------------------------------------------------
+    // Functions and classes
+    // ------------------------------------------------------------------
+
+    fn function(&mut self, name: Option<&Ident>, f: &Function) {
+        self.decorators(&f.decorators);
+        if f.is_async {
+            self.w("async ");
+        }
+        self.w("function");
+        if f.is_generator {
+            self.w("*");
+        }
+        if let Some(name) = name {
+            self.w(" ");
+            self.w(&name.sym);
+        }
+        self.signature(f);
+        self.fn_body(&f.body);
+    }
+
+    /// Type parameters, parameter list and return type.
+    fn signature(&mut self, f: &Function) {
+        if let Some(type_params) = &f.type_params {
+            self.verbatim(type_params.span);
+        }
+        self.params(&f.params);
+        self.type_ann(&f.return_type);
+    }
+
+    fn params(&mut self, params: &[Param]) {
+        let has_rest = params.iter().any(|p| matches!(p.pat, Pat::Rest(_)));
+        self.list("(", ")", params, ListOpts::parens(!has_rest), |p, param| {
+            for decorator in &param.decorators {
+                p.verbatim(decorator.span);
+                p.w(" ");
+            }
+            p.pat(&param.pat);
+        });
+    }
+
+    fn fn_body(&mut self, body: &Option<BlockStmt>) {
+        match body {
+            Some(block) => {
+                self.w(" ");
+                self.block(block);
+            }
+            None => self.w(";"),
+        }
+    }
+
+    fn decorators(&mut self, decorators: &[Decorator]) {
+        for decorator in decorators {
+            self.verbatim(decorator.span);
+            self.newline();
+        }
+    }
+
+    fn class(&mut self, name: Option<&Ident>, class: &Class, is_expr: bool) {
+        if !is_expr {
+            self.decorators(&class.decorators);
+        }
+        if class.is_abstract {
+            self.w("abstract ");
+        }
+        self.w("class");
+        if let Some(name) = name {
+            self.w(" ");
+            self.w(&name.sym);
+        }
+        if let Some(type_params) = &class.type_params {
+            self.verbatim(type_params.span);
+        }
+        if let Some(super_class) = &class.super_class {
+            self.w(" extends ");
+            self.expr(super_class);
+            if let Some(type_args) = &class.super_type_params {
+                self.verbatim(type_args.span);
+            }
+        }
+        if !class.implements.is_empty() {
+            self.w(" implements ");
+            for (i, implemented) in class.implements.iter().enumerate() {
+                if i > 0 {
+                    self.w(", ");
+                }
+                self.verbatim(implemented.span);
+            }
+        }
+        self.w(" ");
+
+        let (lo, hi) = (self.lo(class.span), self.hi(class.span));
+        let members: Vec<&ClassMember> = class
+            .body
+            .iter()
+            .filter(|m| !matches!(m, ClassMember::Empty(_)))
+            .collect();
+        if members.is_empty() && !self.has_pending_comment_within(lo, hi) {
+            self.w("{}");
+            return;
+        }
+        self.w("{");
+        self.indent += 1;
+        self.last_end = members.first().map_or(lo, |m| {
+            self.src[..self.lo(m.span())].rfind('{').unwrap_or(lo)
+        });
+        self.items(&members, hi.saturating_sub(1), |p, m| p.class_member(m));
+        self.indent -= 1;
+        self.ensure_line();
+        self.w("}");
+    }
+
+    fn class_member(&mut self, member: &ClassMember) {
+        match member {
+            ClassMember::Constructor(ctor) => {
+                self.accessibility(ctor.accessibility);
+                self.w("constructor");
+                let has_rest = ctor.params.iter().any(|p| {
+                    matches!(
+                        p,
+                        ParamOrTsParamProp::Param(Param {
+                            pat: Pat::Rest(_),
+                            ..
+                        })
+                    )
+                });
+                self.list(
+                    "(",
+                    ")",
+                    &ctor.params,
+                    ListOpts::parens(!has_rest),
+                    |p, param| match param {
+                        ParamOrTsParamProp::Param(param) => {
+                            for decorator in &param.decorators {
+                                p.verbatim(decorator.span);
+                                p.w(" ");
+                            }
+                            p.pat(&param.pat);
+                        }
+                        other => p.verbatim(other.span()),
+                    },
+                );
+                self.fn_body(&ctor.body);
+            }
+            ClassMember::Method(method) => {
+                self.decorators(&method.function.decorators);
+                self.accessibility(method.accessibility);
+                self.method_modifiers(method.is_static, method.is_abstract, method.is_override);
+                self.method_head(method.kind, &method.function);
+                self.prop_name(&method.key);
+                if method.is_optional {
+                    self.w("?");
+                }
+                self.signature(&method.function);
+                self.fn_body(&method.function.body);
+            }
+            ClassMember::PrivateMethod(method) => {
+                self.decorators(&method.function.decorators);
+                self.accessibility(method.accessibility);
+                self.method_modifiers(method.is_static, method.is_abstract, method.is_override);
+                self.method_head(method.kind, &method.function);
+                self.w("#");
+                self.w(&method.key.name);
+                if method.is_optional {
+                    self.w("?");
+                }
+                self.signature(&method.function);
+                self.fn_body(&method.function.body);
+            }
+            ClassMember::ClassProp(prop) => {
+                self.decorators(&prop.decorators);
+                if prop.declare {
+                    self.w("declare ");
+                }
+                self.accessibility(prop.accessibility);
+                self.method_modifiers(prop.is_static, prop.is_abstract, prop.is_override);
+                if prop.readonly {
+                    self.w("readonly ");
+                }
+                self.prop_name(&prop.key);
+                self.field_rest(prop.is_optional, prop.definite, &prop.type_ann, &prop.value);
+            }
+            ClassMember::PrivateProp(prop) => {
+                self.decorators(&prop.decorators);
+                self.accessibility(prop.accessibility);
+                self.method_modifiers(prop.is_static, false, prop.is_override);
+                if prop.readonly {
+                    self.w("readonly ");
+                }
+                self.w("#");
+                self.w(&prop.key.name);
+                self.field_rest(prop.is_optional, prop.definite, &prop.type_ann, &prop.value);
+            }
+            ClassMember::StaticBlock(block) => {
+                self.w("static ");
+                self.block(&block.body);
+            }
+            other => self.verbatim(other.span()),
+        }
+    }
+
+    fn accessibility(&mut self, accessibility: Option<Accessibility>) {
+        match accessibility {
+            Some(Accessibility::Public) => self.w("public "),
+            Some(Accessibility::Protected) => self.w("protected "),
+            Some(Accessibility::Private) => self.w("private "),
+            _ => {}
+        }
+    }
+
+    fn method_modifiers(&mut self, is_static: bool, is_abstract: bool, is_override: bool) {
+        if is_static {
+            self.w("static ");
+        }
+        if is_abstract {
+            self.w("abstract ");
+        }
+        if is_override {
+            self.w("override ");
+        }
+    }
+
+    fn method_head(&mut self, kind: MethodKind, f: &Function) {
+        match kind {
+            MethodKind::Getter => self.w("get "),
+            MethodKind::Setter => self.w("set "),
+            _ => {
+                if f.is_async {
+                    self.w("async ");
+                }
+                if f.is_generator {
+                    self.w("*");
+                }
+            }
+        }
+    }
+
+    fn field_rest(
+        &mut self,
+        optional: bool,
+        definite: bool,
+        type_ann: &Option<Box<TsTypeAnn>>,
+        value: &Option<Box<Expr>>,
+    ) {
+        if optional {
+            self.w("?");
+        }
+        if definite {
+            self.w("!");
+        }
+        self.type_ann(type_ann);
+        if let Some(value) = value {
+            self.w(" = ");
+            self.expr(value);
+        }
+        self.w(";");
+    }
+
+    fn type_ann(&mut self, type_ann: &Option<Box<TsTypeAnn>>) {
+        if let Some(ann) = type_ann {
+            self.w(": ");
+            self.verbatim(ann.type_ann.span());
+        }
+    }
+
+    // ------------------------------------------------------------------
+    // Patterns
+    // ------------------------------------------------------------------
+
+    fn pat(&mut self, pat: &Pat) {
+        match pat {
+            Pat::Ident(binding) => {
+                self.w(&binding.id.sym);
+                if binding.id.optional {
+                    self.w("?");
+                }
+                self.type_ann(&binding.type_ann);
+            }
+            Pat::Array(arr) => {
+                if matches!(arr.elems.last(), Some(None)) {
+                    self.verbatim(arr.span);
+                    return;
+                }
+                self.list(
+                    "[",
+                    "]",
+                    &arr.elems,
+                    ListOpts::brackets(false),
+                    |p, elem| {
+                        if let Some(elem) = elem {
+                            p.pat(elem);
+                        }
+                    },
+                );
+                if arr.optional {
+                    self.w("?");
+                }
+                self.type_ann(&arr.type_ann);
+            }
+            Pat::Object(obj) => {
+                let has_rest = obj
+                    .props
+                    .iter()
+                    .any(|p| matches!(p, ObjectPatProp::Rest(_)));
+                let opts = ListOpts {
+                    trailing: !has_rest,
+                    ..ListOpts::braces()
+                };
+                self.list("{", "}", &obj.props, opts, |p, prop| match prop {
+                    ObjectPatProp::KeyValue(kv) => {
+                        p.prop_name(&kv.key);
+                        p.w(": ");
+                        p.pat(&kv.value);
+                    }
+                    ObjectPatProp::Assign(assign) => {
+                        p.w(&assign.key.id.sym);
+                        p.type_ann(&assign.key.type_ann);
+                        if let Some(value) = &assign.value {
+                            p.w(" = ");
+                            p.expr(value);
+                        }
+                    }
+                    ObjectPatProp::Rest(rest) => {
+                        p.w("...");
+                        p.pat(&rest.arg);
+                    }
+                });
+                if obj.optional {
+                    self.w("?");
+                }
+                self.type_ann(&obj.type_ann);
+            }
+            Pat::Rest(rest) => {
+                self.w("...");
+                self.pat(&rest.arg);
+                self.type_ann(&rest.type_ann);
+            }
+            Pat::Assign(assign) => {
+                self.pat(&assign.left);
+                self.w(" = ");
+                self.expr(&assign.right);
+            }
+            Pat::Expr(expr) => self.expr(expr),
+            other => self.verbatim(other.span()),
+        }
+    }
+
+    // ------------------------------------------------------------------
+    // Expressions
+    // ------------------------------------------------------------------
+
+    fn expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::This(_) => self.w("this"),
+            Expr::Ident(id) => self.w(&id.sym),
+            Expr::PrivateName(name) => {
+                self.w("#");
+                self.w(&name.name);
+            }
+            Expr::Lit(Lit::Str(s)) => self.string(s),
+            Expr::Lit(Lit::Bool(b)) => self.w(if b.value { "true" } else { "false" }),
+            Expr::Lit(Lit::Null(_)) => self.w("null"),
+            Expr::Lit(lit) => self.verbatim(lit.span()),
+            Expr::Array(arr) => {
+                if matches!(arr.elems.last(), Some(None)) {
+                    self.verbatim(arr.span);
+                    return;
+                }
+                self.list("[", "]", &arr.elems, ListOpts::brackets(true), |p, elem| {
+                    if let Some(elem) = elem {
+                        p.expr_or_spread(elem);
+                    }
+                });
+            }
+            Expr::Object(obj) => self.object_lit(obj),
+            Expr::Fn(f) => self.function(f.ident.as_ref(), &f.function),
+            Expr::Class(c) => self.class(c.ident.as_ref(), &c.class, true),
+            Expr::Arrow(arrow) => self.arrow(arrow),
+            Expr::Unary(unary) => {
+                let op = unary.op.as_str();
+                self.w(op);
+                if op.chars().all(|c| c.is_ascii_alphabetic()) {
+                    self.w(" ");
+                } else if let Expr::Unary(UnaryExpr { op: inner, .. }) = unary.arg.as_ref()
+                    && inner.as_str() == op
+                {
+                    self.w(" ");
+                } else if let Expr::Update(UpdateExpr {
+                    prefix: true,
+                    op: inner,
+                    ..
+                }) = unary.arg.as_ref()
+                    && inner.as_str().starts_with(op)
+                {
+                    self.w(" ");
+                }
+                self.expr(&unary.arg);
+            }
+            Expr::Update(update) => {
+                if update.prefix {
+                    self.w(update.op.as_str());
+                    self.expr(&update.arg);
+                } else {
+                    self.expr(&update.arg);
+                    self.w(update.op.as_str());
+                }
+            }
+            Expr::Bin(bin) => {
+                self.expr(&bin.left);
+                self.w(" ");
+                self.w(bin.op.as_str());
+                self.w(" ");
+                self.expr(&bin.right);
+            }
+            Expr::Assign(assign) => {
+                match &assign.left {
+                    AssignTarget::Simple(SimpleAssignTarget::Ident(binding)) => {
+                        self.w(&binding.id.sym)
+                    }
+                    AssignTarget::Simple(SimpleAssignTarget::Member(member)) => self.member(member),
+                    AssignTarget::Pat(AssignTargetPat::Array(arr)) => {
+                        self.pat(&Pat::Array(arr.clone()))
+                    }
+                    AssignTarget::Pat(AssignTargetPat::Object(obj)) => {
+                        self.pat(&Pat::Object(obj.clone()))
+                    }
+                    other => self.verbatim(other.span()),
+                }
+                self.w(" ");
+                self.w(assign.op.as_str());
+                self.w(" ");
+                self.expr(&assign.right);
+            }
+            Expr::Member(member) => self.member(member),
+            Expr::SuperProp(super_prop) => {
+                self.w("super");
+                match &super_prop.prop {
+                    SuperProp::Ident(id) => {
+                        self.w(".");
+                        self.w(&id.sym);
+                    }
+                    SuperProp::Computed(computed) => {
+                        self.w("[");
+                        self.expr(&computed.expr);
+                        self.w("]");
+                    }
+                }
+            }
+            Expr::Cond(cond) => {
+                self.expr(&cond.test);
+                self.w(" ? ");
+                self.expr(&cond.cons);
+                self.w(" : ");
+                self.expr(&cond.alt);
+            }
+            Expr::Call(call) => {
+                match &call.callee {
+                    Callee::Super(_) => self.w("super"),
+                    Callee::Import(_) => self.w("import"),
+                    Callee::Expr(callee) => self.expr(callee),
+                }
+                if let Some(type_args) = &call.type_args {
+                    self.verbatim(type_args.span);
+                }
+                self.args(&call.args);
+            }
+            Expr::New(new) => {
+                self.w("new ");
+                self.expr(&new.callee);
+                if let Some(type_args) = &new.type_args {
+                    self.verbatim(type_args.span);
+                }
+                self.args(new.args.as_deref().unwrap_or(&[]));
+            }
+            Expr::Seq(seq) => {
+                for (i, e) in seq.exprs.iter().enumerate() {
+                    if i > 0 {
+                        self.w(", ");
+                    }
+                    self.expr(e);
+                }
+            }
+            Expr::Yield(yield_expr) => {
+                self.w(if yield_expr.delegate {
+                    "yield*"
+                } else {
+                    "yield"
+                });
+                if let Some(arg) = &yield_expr.arg {
+                    self.w(" ");
+                    self.expr(arg);
+                }
+            }
+            Expr::Await(await_expr) => {
+                self.w("await ");
+                self.expr(&await_expr.arg);
+            }
+            Expr::Paren(paren) => {
+                self.w("(");
+                self.expr(&paren.expr);
+                self.w(")");
+            }
+            Expr::OptChain(chain) => match chain.base.as_ref() {
+                OptChainBase::Member(member) => {
+                    self.expr(&member.obj);
+                    match &member.prop {
+                        MemberProp::Ident(id) => {
+                            self.w(if chain.optional { "?." } else { "." });
+                            self.w(&id.sym);
+                        }
+                        MemberProp::PrivateName(name) => {
+                            self.w(if chain.optional { "?.#" } else { ".#" });
+                            self.w(&name.name);
+                        }
+                        MemberProp::Computed(computed) => {
+                            self.w(if chain.optional { "?.[" } else { "[" });
+                            self.expr(&computed.expr);
+                            self.w("]");
+                        }
+                    }
+                }
+                OptChainBase::Call(call) => {
+                    self.expr(&call.callee);
+                    if chain.optional {
+                        self.w("?.");
+                    }
+                    if let Some(type_args) = &call.type_args {
+                        self.verbatim(type_args.span);
+                    }
+                    self.args(&call.args);
+                }
+            },
+            Expr::TsAs(as_expr) => {
+                self.expr(&as_expr.expr);
+                self.w(" as ");
+                self.verbatim(as_expr.type_ann.span());
+            }
+            Expr::TsSatisfies(satisfies) => {
+                self.expr(&satisfies.expr);
+                self.w(" satisfies ");
+                self.verbatim(satisfies.type_ann.span());
+            }
+            Expr::TsConstAssertion(assertion) => {
+                self.expr(&assertion.expr);
+                self.w(" as const");
+            }
+            Expr::TsNonNull(non_null) => {
+                self.expr(&non_null.expr);
+                self.w("!");
+            }
+            other => self.verbatim(other.span()),
+        }
+    }
+
+    fn member(&mut self, member: &MemberExpr) {
+        self.expr(&member.obj);
+        match &member.prop {
+            MemberProp::Ident(id) => {
+                self.w(".");
+                self.w(&id.sym);
+            }
+            MemberProp::PrivateName(name) => {
+                self.w(".#");
+                self.w(&name.name);
+            }
+            MemberProp::Computed(computed) => {
+                self.w("[");
+                self.expr(&computed.expr);
+                self.w("]");
+            }
+        }
+    }
+
+    fn args(&mut self, args: &[ExprOrSpread]) {
+        let huggable = args.last().is_some_and(|last| {
+            last.spread.is_none()
+                && matches!(
+                    last.expr.as_ref(),
+                    Expr::Arrow(_) | Expr::Fn(_) | Expr::Object(_)
+                )
+        });
+        let opts = ListOpts {
+            hug_last: huggable,
+            ..ListOpts::parens(true)
+        };
+        self.list("(", ")", args, opts, |p, arg| p.expr_or_spread(arg));
+    }
+
+    fn expr_or_spread(&mut self, e: &ExprOrSpread) {
+        if e.spread.is_some() {
+            self.w("...");
+        }
+        self.expr(&e.expr);
+    }
+
+    fn arrow(&mut self, arrow: &ArrowExpr) {
+        if arrow.is_async {
+            self.w("async ");
+        }
+        if let Some(type_params) = &arrow.type_params {
+            self.verbatim(type_params.span);
+        }
+        let has_rest = arrow.params.iter().any(|p| matches!(p, Pat::Rest(_)));
+        self.list(
+            "(",
+            ")",
+            &arrow.params,
+            ListOpts::parens(!has_rest),
+            |p, pat| p.pat(pat),
+        );
+        self.type_ann(&arrow.return_type);
+        self.w(" => ");
+        match arrow.body.as_ref() {
+            BlockStmtOrExpr::BlockStmt(block) => self.block(block),
+            BlockStmtOrExpr::Expr(body) => self.expr(body),
+        }
+    }
+
+    fn object_lit(&mut self, obj: &ObjectLit) {
+        // An object written across lines stays expanded
+        let force_break = obj.props.first().is_some_and(|first| {
+            let start = self.lo(obj.span);
+            let first_lo = self.lo(first.span());
+            self.src
+                .get(start..first_lo)
+                .is_some_and(|gap| gap.contains('\n'))
+        });
+        let opts = ListOpts {
+            force_break,
+            ..ListOpts::braces()
+        };
+        self.list("{", "}", &obj.props, opts, |p, prop| match prop {
+            PropOrSpread::Spread(spread) => {
+                p.w("...");
+                p.expr(&spread.expr);
+            }
+            PropOrSpread::Prop(prop) => p.prop(prop),
+        });
+    }
+
+    fn prop(&mut self, prop: &Prop) {
+        match prop {
+            Prop::Shorthand(id) => self.w(&id.sym),
+            Prop::KeyValue(kv) => {
+                self.prop_name(&kv.key);
+                self.w(": ");
+                self.expr(&kv.value);
+            }
+            Prop::Assign(assign) => {
+                self.w(&assign.key.sym);
+                self.w(" = ");
+                self.expr(&assign.value);
+            }
+            Prop::Getter(getter) => {
+                self.w("get ");
+                self.prop_name(&getter.key);
+                self.w("()");
+                self.type_ann(&getter.type_ann);
+                self.fn_body(&getter.body);
+            }
+            Prop::Setter(setter) if setter.this_param.is_none() => {
+                self.w("set ");
+                self.prop_name(&setter.key);
+                self.w("(");
+                self.pat(&setter.param);
+                self.w(")");
+                self.fn_body(&setter.body);
+            }
+            Prop::Method(method) => {
+                self.method_head(MethodKind::Method, &method.function);
+                self.prop_name(&method.key);
+                self.signature(&method.function);
+                self.fn_body(&method.function.body);
+            }
+            other => self.verbatim(other.span()),
+        }
+    }
+
+    fn prop_name(&mut self, name: &PropName) {
+        match name {
+            PropName::Ident(id) => self.w(&id.sym),
+            PropName::Str(s) => self.string(s),
+            PropName::Computed(computed) => {
+                self.w("[");
+                self.expr(&computed.expr);
+                self.w("]");
+            }
+            other => self.verbatim(other.span()),
+        }
+    }
+
+    /// Print a string literal in the preferred quote style, unless that
+    /// would need more escapes than the alternative.
+    fn string(&mut self, s: &Str) {
+        let raw = self.text(s.span);
+        let quoted = requote(raw, self.opts.quote_style.char());
+        self.w(&quoted);
+    }
+}
+
+/// Layout options for `Printer::list`
+#[derive(Clone, Copy)]
+struct ListOpts {
+    /// Spaces inside the delimiters when flat: `{ a, b }`
+    pad: bool,
+    /// Trailing comma allowed when broken (not after rest elements)
+    trailing: bool,
+    force_break: bool,
+    hug_last: bool,
+}
+
+impl ListOpts {
+    fn parens(trailing: bool) -> Self {
+        Self {
+            pad: false,
+            trailing,
+            force_break: false,
+            hug_last: false,
+        }
+    }
+
+    fn brackets(trailing: bool) -> Self {
+        Self::parens(trailing)
+    }
+
+    fn braces() -> Self {
+        Self {
+            pad: true,
+            trailing: true,
+            force_break: false,
+            hug_last: false,
+        }
+    }
+}
+
+/// Re-quote a raw string literal (quotes included) with `preferred`,
+/// falling back to the other quote when it needs fewer escapes.
+fn requote(raw: &str, preferred: char) -> String {
+    let Some(first) = raw.chars().next() else {
+        return raw.to_string();
+    };
+    if (first != '"' && first != '\'') || raw.len() < 2 {
+        return raw.to_string();
+    }
+    let inner = &raw[1..raw.len() - 1];
+    let alternate = if preferred == '"' { '\'' } else { '"' };
+    let count = |q: char| inner.chars().filter(|c| *c == q).count();
+    let quote = if count(preferred) > count(alternate) {
+        alternate
+    } else {
+        preferred
+    };
+    let other = if quote == '"' { '\'' } else { '"' };
+
+    let mut out = String::with_capacity(raw.len() + 2);
+    out.push(quote);
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                // No need to escape the quote we are not using
+                Some(next) if next == other => out.push(next),
+                Some(next) => {
+                    out.push('\\');
+                    out.push(next);
+                }
+                None => out.push('\\'),
+            }
+        } else if c == quote {
+            out.push('\\');
+            out.push(c);
+        } else {
+            out.push(c);
+        }
+    }
+    out.push(quote);
+    out
+}
@@ -11,6 +11,8 @@
 //! - **Deterministic ordering**: Functions, blocks, and values are ordered consistently.
 //! - **Human-readable**: Text format that can be inspected and debugged.
 //! - **Versioned**: Includes IR format version for forward/backward compatibility.
+//! - **Complete**: Carries what the backends need (value types, exported
+//!   names), so `parse` can read a module back.
 
 use crate::ir::{IrFunction, IrModule, IrOp, Terminator};
 use crate::runtime::ABI_VERSION;
@@ -39,6 +41,21 @@ pub fn serialize_module(module: &IrModule) -> String {
     output.push_str("; ============================================================\n");
     output.push('\n');
 
+    // Names a library exports, and the user's main()
+    let mut exports: Vec<_> = module.function_names.iter().collect();
+    exports.sort();
+    for (name, &idx) in exports {
+        if let Some(func) = module.functions.get(idx) {
+            output.push_str(&format!("export {} = {}\n", name, func.name));
+        }
+    }
+    if let Some(addr) = module.user_main_addr {
+        output.push_str(&format!("user_main = func_{}\n", addr));
+    }
+    if !module.function_names.is_empty() || module.user_main_addr.is_some() {
+        output.push('\n');
+    }
+
     // Struct definitions (sorted by ID for determinism)
     let mut struct_ids: Vec<_> = module.structs.keys().collect();
     struct_ids.sort_by_key(|id| id.0);
@@ -99,20 +116,30 @@ fn serialize_function(output: &mut String, func: &IrFunction, _sorted_idx: usize
 
     // Basic blocks (in creation order for determinism)
     for block in &func.blocks {
-        serialize_block(output, block);
+        serialize_block(output, func, block);
     }
 
     output.push_str("}\n");
 }
 
-/// Serialize a basic block.
-fn serialize_block(output: &mut String, block: &crate::ir::BasicBlock) {
+/// Serialize a basic block. Results are annotated with their type
+/// (`v3: num = ...`).
+fn serialize_block(output: &mut String, func: &IrFunction, block: &crate::ir::BasicBlock) {
     output.push_str(&format!("{}:\n", block.id));
 
     // Operations
     for op in &block.ops {
+        let mut line = String::new();
+        serialize_op(&mut line, op);
         output.push_str("    ");
-        serialize_op(output, op);
+        if let Some(d) = op.dest()
+            && let Some(ty) = func.value_types.get(&d)
+            && let Some(rest) = line.strip_prefix(&format!("{} = ", d))
+        {
+            output.push_str(&format!("{}: {} = {}", d, ty, rest));
+        } else {
+            output.push_str(&line);
+        }
         output.push('\n');
     }
 
@@ -149,13 +176,32 @@ fn serialize_op(output: &mut String, op: &IrOp) {
         IrOp::Not(d, a) => output.push_str(&format!("{} = not {}", d, a)),
         IrOp::And(d, a, b) => output.push_str(&format!("{} = and {}, {}", d, a, b)),
         IrOp::Or(d, a, b) => output.push_str(&format!("{} = or {}, {}", d, a, b)),
-        IrOp::BitAnd(d, a, b) => output.push_str(&format!("{} = and {}, {}", d, a, b)),
-        IrOp::BitOr(d, a, b) => output.push_str(&format!("{} = or {}, {}", d, a, b)),
+        IrOp::BitAnd(d, a, b) => output.push_str(&format!("{} = bit.and {}, {}", d, a, b)),
+        IrOp::BitOr(d, a, b) => output.push_str(&format!("{} = bit.or {}, {}", d, a, b)),
         IrOp::Xor(d, a, b) => output.push_str(&format!("{} = xor {}, {}", d, a, b)),
         IrOp::Shl(d, a, b) => output.push_str(&format!("{} = shl {}, {}", d, a, b)),
         IrOp::Shr(d, a, b) => output.push_str(&format!("{} = shr {}, {}", d, a, b)),
         IrOp::ShrU(d, a, b) => output.push_str(&format!("{} = shr.u {}, {}", d, a, b)),
         IrOp::Pow(d, a, b) => output.push_str(&format!("{} = pow {}, {}", d, a, b)),
+        IrOp::Intrinsic(d, intrinsic, args) => {
+            let args_str: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+            output.push_str(&format!(
+                "{} = intrinsic {}({})",
+                d,
+                intrinsic.name(),
+                args_str.join(", ")
+            ));
+        }
+        IrOp::Simd(d, kind, op, args) => {
+            let args_str: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+            output.push_str(&format!(
+                "{} = simd {}.{}({})",
+                d,
+                kind,
+                op,
+                args_str.join(", ")
+            ));
+        }
         IrOp::LoadLocal(d, slot) => output.push_str(&format!("{} = load.local ${}", d, slot)),
         IrOp::StoreLocal(slot, v) => output.push_str(&format!("store.local ${}, {}", slot, v)),
         IrOp::LoadGlobal(d, name) => output.push_str(&format!("{} = load.global @{}", d, name)),
@@ -176,6 +222,9 @@ fn serialize_op(output: &mut String, op: &IrOp) {
         IrOp::NewArray(d) => output.push_str(&format!("{} = new.array", d)),
         IrOp::ArrayLen(d, arr) => output.push_str(&format!("{} = array.len {}", d, arr)),
         IrOp::ArrayPush(arr, val) => output.push_str(&format!("array.push {}, {}", arr, val)),
+        IrOp::TypedArrayLen(_, _) | IrOp::TypedLoad(..) | IrOp::TypedStore(..) => {
+            output.push_str(&op.to_string())
+        }
         IrOp::Call(d, func, args) => {
             let args_str: Vec<String> = args.iter().map(|a| a.to_string()).collect();
             output.push_str(&format!("{} = call {}({})", d, func, args_str.join(", ")));
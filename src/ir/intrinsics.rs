@@ -0,0 +1,221 @@
+//! Numeric intrinsics
+//!
+//! A call such as `Math.clz32(x)` or `std.intrinsics.f32ToBits(x)` through
+//! the untouched global lowers to `IrOp::Intrinsic` instead of a method call,
+//! and the native backends compile it to one instruction (or a short inline
+//! sequence) rather than a runtime call. The VM registers the same functions
+//! as natives; `Intrinsic::apply` is the semantics both follow.
+//!
+//! Numbers cannot hold 64-bit integers exactly, so the bits of an f64 are
+//! read and written as two unsigned 32-bit words.
+
+use crate::runtime::heap::to_uint32;
+
+/// A numeric builtin with a single-instruction native lowering.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Intrinsic {
+    /// `Math.clz32(x)`: leading zero bits of ToUint32(x)
+    Clz32,
+    /// `Math.imul(a, b)`: wrapping 32-bit integer multiply
+    Imul,
+    /// `Math.fround(x)`: round to the nearest f32
+    Fround,
+    /// `Math.sqrt(x)`
+    Sqrt,
+    /// `Math.abs(x)`
+    Abs,
+    /// `Math.floor(x)`
+    Floor,
+    /// `Math.ceil(x)`
+    Ceil,
+    /// `Math.trunc(x)`
+    Trunc,
+    /// `std.intrinsics.popcnt32(x)`: set bits of ToUint32(x)
+    Popcnt32,
+    /// `std.intrinsics.ctz32(x)`: trailing zero bits of ToUint32(x)
+    Ctz32,
+    /// `std.intrinsics.f32ToBits(x)`: bits of `Math.fround(x)`
+    F32ToBits,
+    /// `std.intrinsics.f32FromBits(bits)`
+    F32FromBits,
+    /// `std.intrinsics.f64HighBits(x)`: upper 32 bits of x
+    F64HighBits,
+    /// `std.intrinsics.f64LowBits(x)`: lower 32 bits of x
+    F64LowBits,
+    /// `std.intrinsics.f64FromBits(high, low)`
+    F64FromBits,
+}
+
+/// Global object path of the `std.intrinsics` namespace.
+pub const STD_INTRINSICS: &str = "std.intrinsics";
+
+/// Bits of the canonical f32 NaN.
+const F32_NAN_BITS: u32 = 0x7FC0_0000;
+
+impl Intrinsic {
+    /// Every intrinsic, in declaration order.
+    pub const ALL: [Intrinsic; 15] = [
+        Intrinsic::Clz32,
+        Intrinsic::Imul,
+        Intrinsic::Fround,
+        Intrinsic::Sqrt,
+        Intrinsic::Abs,
+        Intrinsic::Floor,
+        Intrinsic::Ceil,
+        Intrinsic::Trunc,
+        Intrinsic::Popcnt32,
+        Intrinsic::Ctz32,
+        Intrinsic::F32ToBits,
+        Intrinsic::F32FromBits,
+        Intrinsic::F64HighBits,
+        Intrinsic::F64LowBits,
+        Intrinsic::F64FromBits,
+    ];
+
+    /// Method name, as written in source and printed in IR.
+    pub fn name(self) -> &'static str {
+        match self {
+            Intrinsic::Clz32 => "clz32",
+            Intrinsic::Imul => "imul",
+            Intrinsic::Fround => "fround",
+            Intrinsic::Sqrt => "sqrt",
+            Intrinsic::Abs => "abs",
+            Intrinsic::Floor => "floor",
+            Intrinsic::Ceil => "ceil",
+            Intrinsic::Trunc => "trunc",
+            Intrinsic::Popcnt32 => "popcnt32",
+            Intrinsic::Ctz32 => "ctz32",
+            Intrinsic::F32ToBits => "f32ToBits",
+            Intrinsic::F32FromBits => "f32FromBits",
+            Intrinsic::F64HighBits => "f64HighBits",
+            Intrinsic::F64LowBits => "f64LowBits",
+            Intrinsic::F64FromBits => "f64FromBits",
+        }
+    }
+
+    /// Look up an intrinsic by its method name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|i| i.name() == name)
+    }
+
+    /// The global object the intrinsic is a method of: `Math` or
+    /// `std.intrinsics`.
+    pub fn namespace(self) -> &'static str {
+        match self {
+            Intrinsic::Clz32
+            | Intrinsic::Imul
+            | Intrinsic::Fround
+            | Intrinsic::Sqrt
+            | Intrinsic::Abs
+            | Intrinsic::Floor
+            | Intrinsic::Ceil
+            | Intrinsic::Trunc => "Math",
+            _ => STD_INTRINSICS,
+        }
+    }
+
+    /// The intrinsic called as `namespace.method(...)` with `argc` arguments.
+    /// Other arities keep the ordinary call.
+    pub fn lookup(namespace: &str, method: &str, argc: usize) -> Option<Self> {
+        Self::from_name(method).filter(|i| i.namespace() == namespace && i.arity() == argc)
+    }
+
+    /// Number of arguments.
+    pub fn arity(self) -> usize {
+        match self {
+            Intrinsic::Imul | Intrinsic::F64FromBits => 2,
+            _ => 1,
+        }
+    }
+
+    /// Evaluate on numeric arguments (already converted with ToNumber).
+    /// Missing arguments read as NaN. NaN results are canonical, so they
+    /// are safe to NaN-box.
+    pub fn apply(self, args: &[f64]) -> f64 {
+        let arg = |i: usize| args.get(i).copied().unwrap_or(f64::NAN);
+        let x = arg(0);
+        let result = match self {
+            Intrinsic::Clz32 => to_uint32(x).leading_zeros() as f64,
+            Intrinsic::Imul => (to_uint32(x) as i32).wrapping_mul(to_uint32(arg(1)) as i32) as f64,
+            Intrinsic::Fround => x as f32 as f64,
+            Intrinsic::Sqrt => x.sqrt(),
+            Intrinsic::Abs => x.abs(),
+            Intrinsic::Floor => x.floor(),
+            Intrinsic::Ceil => x.ceil(),
+            Intrinsic::Trunc => x.trunc(),
+            Intrinsic::Popcnt32 => to_uint32(x).count_ones() as f64,
+            Intrinsic::Ctz32 => to_uint32(x).trailing_zeros() as f64,
+            Intrinsic::F32ToBits => {
+                let single = x as f32;
+                if single.is_nan() {
+                    F32_NAN_BITS as f64
+                } else {
+                    single.to_bits() as f64
+                }
+            }
+            Intrinsic::F32FromBits => f32::from_bits(to_uint32(x)) as f64,
+            Intrinsic::F64HighBits => (x.to_bits() >> 32) as f64,
+            Intrinsic::F64LowBits => x.to_bits() as u32 as f64,
+            Intrinsic::F64FromBits => {
+                let high = to_uint32(x) as u64;
+                let low = to_uint32(arg(1)) as u64;
+                f64::from_bits(high << 32 | low)
+            }
+        };
+        if result.is_nan() { f64::NAN } else { result }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup_respects_namespace_and_arity() {
+        assert_eq!(
+            Intrinsic::lookup("Math", "clz32", 1),
+            Some(Intrinsic::Clz32)
+        );
+        assert_eq!(Intrinsic::lookup("Math", "imul", 2), Some(Intrinsic::Imul));
+        assert_eq!(Intrinsic::lookup("Math", "imul", 1), None);
+        assert_eq!(Intrinsic::lookup("Math", "f32ToBits", 1), None);
+        assert_eq!(
+            Intrinsic::lookup(STD_INTRINSICS, "f32ToBits", 1),
+            Some(Intrinsic::F32ToBits)
+        );
+        for intrinsic in Intrinsic::ALL {
+            assert_eq!(Intrinsic::from_name(intrinsic.name()), Some(intrinsic));
+        }
+    }
+
+    #[test]
+    fn test_integer_intrinsics_wrap_like_to_int32() {
+        assert_eq!(Intrinsic::Clz32.apply(&[1.0]), 31.0);
+        assert_eq!(Intrinsic::Clz32.apply(&[0.0]), 32.0);
+        assert_eq!(Intrinsic::Clz32.apply(&[-1.0]), 0.0);
+        assert_eq!(Intrinsic::Clz32.apply(&[f64::NAN]), 32.0);
+        assert_eq!(Intrinsic::Imul.apply(&[0xffff_ffff_u32 as f64, 5.0]), -5.0);
+        assert_eq!(Intrinsic::Imul.apply(&[65536.0, 65536.0]), 0.0);
+        assert_eq!(Intrinsic::Imul.apply(&[3.0]), 0.0);
+        assert_eq!(Intrinsic::Popcnt32.apply(&[255.5]), 8.0);
+        assert_eq!(Intrinsic::Ctz32.apply(&[8.0]), 3.0);
+        assert_eq!(Intrinsic::Ctz32.apply(&[0.0]), 32.0);
+    }
+
+    #[test]
+    fn test_bit_casts_round_trip() {
+        let x = -1.5e-300;
+        let high = Intrinsic::F64HighBits.apply(&[x]);
+        let low = Intrinsic::F64LowBits.apply(&[x]);
+        assert_eq!(Intrinsic::F64FromBits.apply(&[high, low]), x);
+
+        assert_eq!(Intrinsic::F32ToBits.apply(&[1.0]), 0x3f80_0000 as f64);
+        assert_eq!(Intrinsic::F32FromBits.apply(&[0x3f80_0000 as f64]), 1.0);
+        assert_eq!(Intrinsic::Fround.apply(&[0.1]), 0.1f32 as f64);
+
+        // NaN payloads never escape into NaN-boxed values
+        let nan = Intrinsic::F64FromBits.apply(&[0xffff_ffff_u32 as f64, 1.0]);
+        assert_eq!(nan.to_bits(), f64::NAN.to_bits());
+        assert_eq!(Intrinsic::F32ToBits.apply(&[f64::NAN]), F32_NAN_BITS as f64);
+    }
+}
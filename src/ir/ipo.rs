@@ -0,0 +1,835 @@
+//! Interprocedural optimization passes.
+//!
+//! These look across function boundaries, and run from `-O2` up (see
+//! `opt::optimize_module_at`):
+//! - Inlining of direct calls to small functions
+//! - Constant propagation across calls: into parameters every call site
+//!   passes the same constant, and out of functions that always return one
+//! - Dead function elimination: functions nothing reachable refers to
+//!
+//! A direct call is a `call` whose callee is a function's address constant,
+//! possibly by way of a local slot only ever assigned that function. That is
+//! how the lowering refers to functions, and how the backends recognize calls
+//! they can make directly. A function whose address is used any other way
+//! (passed as an argument, stored in a property, captured by a closure)
+//! escapes: not all of its calls can be seen, so its parameters are left
+//! alone.
+
+use crate::ir::opt::{replace_uses_in_op, replace_uses_in_terminator};
+use crate::ir::{
+    BlockId, IrFunction, IrModule, IrOp, IrType, LifetimeId, Literal, Terminator, ValueId,
+};
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+
+/// Limits on inlining, in IR operations.
+#[derive(Debug, Clone, Copy)]
+pub struct InlineBudget {
+    /// Largest callee inlined at any call site.
+    pub callee_size: usize,
+    /// Largest callee inlined at its only call site, where no copy of it
+    /// stays behind once dead function elimination drops the original.
+    pub single_site_size: usize,
+    /// Size a caller may grow to through inlining.
+    pub caller_size: usize,
+}
+
+// ============================================================================
+// Function References
+// ============================================================================
+
+/// How a function uses the addresses of the module's functions.
+#[derive(Default)]
+struct FunctionRefs {
+    /// Values holding a function's address.
+    values: HashMap<ValueId, usize>,
+    /// Addresses called or escaping. One only stored into a local that is
+    /// never read is not a reference.
+    referenced: HashSet<usize>,
+    /// Addresses used other than as a callee.
+    escaped: HashSet<usize>,
+    /// Direct calls: (block index, op index, callee address).
+    calls: Vec<(usize, usize, usize)>,
+}
+
+fn function_refs(func: &IrFunction, addrs: &HashMap<usize, usize>) -> FunctionRefs {
+    let address = |lit: &Literal| match lit {
+        Literal::Number(n)
+            if *n >= 0.0 && n.fract() == 0.0 && addrs.contains_key(&(*n as usize)) =>
+        {
+            Some(*n as usize)
+        }
+        _ => None,
+    };
+    let ops = || func.blocks.iter().flat_map(|block| block.ops.iter());
+    let callees: HashSet<ValueId> = ops()
+        .filter_map(|op| match op {
+            IrOp::Call(_, callee, _) => Some(*callee),
+            _ => None,
+        })
+        .collect();
+
+    // A slot holds a function when every store to it stores that function.
+    // Knowing more values only ever settles more slots, so this converges.
+    let mut refs = FunctionRefs::default();
+    let mut slots: HashMap<u32, Option<usize>> = HashMap::new();
+    loop {
+        slots.clear();
+        for op in ops() {
+            if let IrOp::StoreLocal(slot, val) = op {
+                let stored = refs.values.get(val).copied();
+                slots
+                    .entry(*slot)
+                    .and_modify(|held| {
+                        if *held != stored {
+                            *held = None;
+                        }
+                    })
+                    .or_insert(stored);
+            }
+        }
+
+        let known = refs.values.len();
+        for op in ops() {
+            let held = match op {
+                // A number typed as one is data, even when it happens to
+                // equal a function's address, unless it is called: type
+                // inference types every numeric constant, addresses included
+                IrOp::Const(dst, _)
+                    if func.value_types.get(dst) == Some(&IrType::Number)
+                        && !callees.contains(dst) =>
+                {
+                    None
+                }
+                IrOp::Const(_, lit) => address(lit),
+                IrOp::Copy(_, src) | IrOp::Move(_, src) => refs.values.get(src).copied(),
+                IrOp::LoadLocal(_, slot) => slots.get(slot).copied().flatten(),
+                _ => None,
+            };
+            if let (Some(dst), Some(addr)) = (op.dest(), held) {
+                refs.values.insert(dst, addr);
+            }
+        }
+        if refs.values.len() == known {
+            break;
+        }
+    }
+
+    for (b, block) in func.blocks.iter().enumerate() {
+        for (i, op) in block.ops.iter().enumerate() {
+            match op {
+                IrOp::MakeClosure(_, addr, _) if addrs.contains_key(&(*addr as usize)) => {
+                    refs.escaped.insert(*addr as usize);
+                }
+                IrOp::Call(_, callee, _) => {
+                    if let Some(&addr) = refs.values.get(callee) {
+                        refs.calls.push((b, i, addr));
+                        refs.referenced.insert(addr);
+                    }
+                }
+                _ => {}
+            }
+
+            for used in op.uses() {
+                let Some(&addr) = refs.values.get(&used) else {
+                    continue;
+                };
+                let allowed = match op {
+                    IrOp::Call(_, callee, args) => *callee == used && !args.contains(&used),
+                    IrOp::StoreLocal(slot, _) => matches!(slots.get(slot), Some(Some(_))),
+                    IrOp::Copy(_, _) | IrOp::Move(_, _) => true,
+                    _ => false,
+                };
+                if !allowed {
+                    refs.escaped.insert(addr);
+                }
+            }
+        }
+        for used in block.terminator.uses() {
+            if let Some(&addr) = refs.values.get(&used) {
+                refs.escaped.insert(addr);
+            }
+        }
+    }
+    refs.referenced.extend(refs.escaped.iter().copied());
+    refs
+}
+
+/// Functions called from outside the module's own code: the top-level
+/// script, a user `main()`, monomorphized functions and, for a library,
+/// its exports.
+fn roots(module: &IrModule, keep_exported: bool) -> HashSet<usize> {
+    let mut roots: HashSet<usize> = module
+        .functions
+        .iter()
+        .enumerate()
+        .filter(|(_, func)| func.name == "main")
+        .map(|(idx, _)| idx)
+        .collect();
+    roots.extend(
+        module
+            .user_main_addr
+            .and_then(|addr| module.get_function_idx_by_addr(addr)),
+    );
+    roots.extend(module.mono_cache.keys().map(|(idx, _)| *idx));
+    if keep_exported {
+        roots.extend(module.function_names.values().copied());
+    }
+    roots
+}
+
+/// Addresses of functions that may be called in ways the IR doesn't show.
+fn escaped_functions(
+    module: &IrModule,
+    refs: &[FunctionRefs],
+    keep_exported: bool,
+) -> HashSet<usize> {
+    let roots = roots(module, keep_exported);
+    let mut escaped: HashSet<usize> = refs
+        .iter()
+        .flat_map(|r| r.escaped.iter().copied())
+        .collect();
+    escaped.extend(
+        module
+            .function_addrs
+            .iter()
+            .filter(|(_, idx)| roots.contains(idx))
+            .map(|(addr, _)| *addr),
+    );
+    escaped
+}
+
+fn function_size(func: &IrFunction) -> usize {
+    func.blocks.iter().map(|block| block.ops.len() + 1).sum()
+}
+
+/// A constant and its type, which tells a function's address (typed `fn`)
+/// from a number
+type Constant = (Literal, IrType);
+
+/// Known constant values of a function.
+fn constants(func: &IrFunction) -> HashMap<ValueId, Constant> {
+    func.blocks
+        .iter()
+        .flat_map(|block| block.ops.iter())
+        .filter_map(|op| match op {
+            IrOp::Const(dst, lit) => {
+                let ty = match func.value_types.get(dst) {
+                    Some(IrType::Function) => IrType::Function,
+                    _ => lit.ir_type(),
+                };
+                Some((*dst, (lit.clone(), ty)))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+// ============================================================================
+// Inlining
+// ============================================================================
+
+/// Inline direct calls to functions within `budget`. Returns the number of
+/// call sites inlined.
+pub fn inline_calls(module: &mut IrModule, budget: &InlineBudget, keep_exported: bool) -> usize {
+    let addrs = module.function_addrs.clone();
+    let refs: Vec<FunctionRefs> = module
+        .functions
+        .iter()
+        .map(|func| function_refs(func, &addrs))
+        .collect();
+    let escaped = escaped_functions(module, &refs, keep_exported);
+    let mut sites: HashMap<usize, usize> = HashMap::new();
+    for (_, _, addr) in refs.iter().flat_map(|r| r.calls.iter()) {
+        *sites.entry(*addr).or_default() += 1;
+    }
+
+    let mut inlined = 0;
+    for (caller_idx, caller_refs) in refs.iter().enumerate() {
+        // Latest call first, so splitting a block leaves earlier sites in place
+        let mut calls = caller_refs.calls.clone();
+        calls.sort_unstable_by(|a, b| b.cmp(a));
+        for (block, index, addr) in calls {
+            let Some(&callee_idx) = addrs.get(&addr) else {
+                continue;
+            };
+            if callee_idx == caller_idx {
+                continue;
+            }
+            let callee = &module.functions[callee_idx];
+            let size = function_size(callee);
+            let limit = if sites[&addr] == 1 && !escaped.contains(&addr) {
+                budget.single_site_size
+            } else {
+                budget.callee_size
+            };
+            if size > limit
+                || function_size(&module.functions[caller_idx]) + size > budget.caller_size
+                || !is_inlinable(callee, addr, &addrs)
+            {
+                continue;
+            }
+            let callee = callee.clone();
+            inline_call(
+                &mut module.functions[caller_idx],
+                BlockId(block as u32),
+                index,
+                &callee,
+            );
+            inlined += 1;
+        }
+    }
+    inlined
+}
+
+/// Whether a function's body can stand in for a call to it: it doesn't
+/// read `this` (which a call would bind), call itself, or loop back to its
+/// entry block.
+fn is_inlinable(func: &IrFunction, addr: usize, addrs: &HashMap<usize, usize>) -> bool {
+    let entry = func.entry_block();
+    !func.blocks.is_empty()
+        && func.name != "main"
+        && !func
+            .blocks
+            .iter()
+            .any(|block| block.terminator.successors().contains(&entry))
+        && !func
+            .blocks
+            .iter()
+            .flat_map(|block| block.ops.iter())
+            .any(|op| matches!(op, IrOp::LoadThis(_)))
+        && !function_refs(func, addrs)
+            .calls
+            .iter()
+            .any(|(_, _, callee)| *callee == addr)
+}
+
+/// Replace the call at `index` in `block` with a copy of `callee`'s body.
+///
+/// The block is split at the call: its ops before the call jump to the
+/// copied entry block, each `return` jumps to a new block holding the ops
+/// after the call, and the call's result becomes a copy of the returned
+/// value, or a load of a local slot that each `return` stores to. Callee
+/// values and local slots are numbered after the caller's, and the new
+/// blocks directly follow the split block; parameters become the
+/// arguments, missing ones `undefined`.
+fn inline_call(caller: &mut IrFunction, block: BlockId, index: usize, callee: &IrFunction) {
+    let IrOp::Call(result, _, args) = caller.block(block).ops[index].clone() else {
+        return;
+    };
+
+    let value_base = caller.next_value;
+    caller.next_value += callee.next_value;
+    let lifetime_base = caller.next_lifetime;
+    caller.next_lifetime += callee.next_lifetime;
+    let slot_base = caller.locals.len() as u32;
+    caller.locals.extend(
+        callee
+            .locals
+            .iter()
+            .map(|(name, ty)| (format!("{}.{}", callee.name, name), ty.clone())),
+    );
+
+    let mut values: HashMap<ValueId, ValueId> = (0..callee.next_value)
+        .map(|v| (ValueId(v), ValueId(value_base + v)))
+        .collect();
+    let mut prologue = Vec::new();
+    for param in 0..callee.params.len() {
+        let arg = match args.get(param) {
+            Some(&arg) => arg,
+            None => {
+                let undefined = caller.alloc_value(IrType::Any);
+                prologue.push(IrOp::Const(undefined, Literal::Undefined));
+                undefined
+            }
+        };
+        values.insert(ValueId(param as u32), arg);
+    }
+    for (val, ty) in &callee.value_types {
+        if val.0 as usize >= callee.params.len() {
+            caller.value_types.insert(values[val], ty.clone());
+        }
+    }
+    for (val, &line) in &callee.source_lines {
+        if val.0 as usize >= callee.params.len() {
+            caller.source_lines.insert(values[val], line);
+        }
+    }
+    for (val, info) in &callee.value_info {
+        if val.0 as usize >= callee.params.len() {
+            let mut info = info.clone();
+            info.borrowed_from = info
+                .borrowed_from
+                .map(|src| values.get(&src).copied().unwrap_or(src));
+            info.lifetime = info.lifetime.map(|l| LifetimeId(l.0 + lifetime_base));
+            caller.value_info.insert(values[val], info);
+        }
+    }
+
+    let first_new = caller.next_block;
+    let blocks: HashMap<BlockId, BlockId> = callee
+        .blocks
+        .iter()
+        .map(|b| (b.id, caller.alloc_block()))
+        .collect();
+    let continuation = caller.alloc_block();
+
+    let (tail, terminator) = {
+        let head = caller.block_mut(block);
+        let tail = head.ops.split_off(index + 1);
+        head.ops.pop();
+        head.ops.extend(prologue);
+        let terminator = std::mem::replace(
+            &mut head.terminator,
+            Terminator::Jump(blocks[&callee.entry_block()]),
+        );
+        (tail, terminator)
+    };
+
+    // Several returns meet in a local slot, as lowered control flow does
+    let return_count = callee
+        .blocks
+        .iter()
+        .filter(|b| matches!(b.terminator, Terminator::Return(_)))
+        .count();
+    let return_slot = (return_count > 1)
+        .then(|| caller.add_local(format!("{}.return", callee.name), callee.return_ty.clone()));
+
+    let mut returns = Vec::new();
+    for callee_block in &callee.blocks {
+        let id = blocks[&callee_block.id];
+        let mut ops: Vec<IrOp> = callee_block
+            .ops
+            .iter()
+            .map(|op| {
+                let mut op = op.clone();
+                rename_op(&mut op, &values, &blocks, slot_base);
+                op
+            })
+            .collect();
+        let terminator = match &callee_block.terminator {
+            Terminator::Return(value) => {
+                let value = match value {
+                    Some(value) => values.get(value).copied().unwrap_or(*value),
+                    None => {
+                        let undefined = caller.alloc_value(IrType::Any);
+                        ops.push(IrOp::Const(undefined, Literal::Undefined));
+                        undefined
+                    }
+                };
+                if let Some(slot) = return_slot {
+                    ops.push(IrOp::StoreLocal(slot, value));
+                }
+                returns.push(value);
+                Terminator::Jump(continuation)
+            }
+            other => {
+                let mut term = other.clone();
+                replace_uses_in_terminator(&mut term, &values);
+                match &mut term {
+                    Terminator::Jump(target) => *target = blocks[&*target],
+                    Terminator::Branch(_, t, f) => {
+                        *t = blocks[&*t];
+                        *f = blocks[&*f];
+                    }
+                    _ => {}
+                }
+                term
+            }
+        };
+        let copied = caller.block_mut(id);
+        copied.ops = ops;
+        copied.terminator = terminator;
+    }
+
+    // A callee that never returns leaves the continuation unreachable
+    let mut ops = match (returns.as_slice(), return_slot) {
+        (_, Some(slot)) => vec![IrOp::LoadLocal(result, slot)],
+        ([value], None) => vec![IrOp::Copy(result, *value)],
+        _ => vec![IrOp::Const(result, Literal::Undefined)],
+    };
+    ops.extend(tail);
+    // Phis after the call now see its edges leave from the continuation
+    for succ in terminator.successors() {
+        for op in &mut caller.block_mut(succ).ops {
+            if let IrOp::Phi(_, entries) = op {
+                for (pred, _) in entries {
+                    if *pred == block {
+                        *pred = continuation;
+                    }
+                }
+            }
+        }
+    }
+    let rest = caller.block_mut(continuation);
+    rest.ops = ops;
+    rest.terminator = terminator;
+
+    // Lay the copied blocks out right after the call. Passes and backends
+    // that walk blocks in order then reach the continuation, which now
+    // defines the values the ops after the call produced, before the blocks
+    // that followed the call and use them.
+    let split = block.0;
+    let added = caller.next_block - first_new;
+    caller.renumber_blocks(|id| match id.0 {
+        old if old <= split => id,
+        old if old < first_new => BlockId(old + added),
+        new => BlockId(split + 1 + new - first_new),
+    });
+}
+
+/// Renumber an inlined op's values, phi blocks and local slots.
+fn rename_op(
+    op: &mut IrOp,
+    values: &HashMap<ValueId, ValueId>,
+    blocks: &HashMap<BlockId, BlockId>,
+    slot_base: u32,
+) {
+    replace_uses_in_op(op, values);
+    if let Some(dst) = op.dest_mut()
+        && let Some(&renamed) = values.get(dst)
+    {
+        *dst = renamed;
+    }
+    match op {
+        IrOp::LoadLocal(_, slot) | IrOp::StoreLocal(slot, _) => *slot += slot_base,
+        IrOp::Phi(_, entries) => {
+            for (pred, _) in entries {
+                if let Some(&renamed) = blocks.get(pred) {
+                    *pred = renamed;
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+// ============================================================================
+// Interprocedural Constant Propagation
+// ============================================================================
+
+/// Propagate constants across direct calls: a parameter every call site
+/// passes the same constant becomes that constant (when the function
+/// doesn't escape), and the result of a call to a function that always
+/// returns the same constant becomes that constant. The calls themselves
+/// stay, for their side effects. Returns the number of replacements.
+pub fn propagate_constants(module: &mut IrModule, keep_exported: bool) -> usize {
+    let addrs = module.function_addrs.clone();
+    let refs: Vec<FunctionRefs> = module
+        .functions
+        .iter()
+        .map(|func| function_refs(func, &addrs))
+        .collect();
+    let escaped = escaped_functions(module, &refs, keep_exported);
+    let consts: Vec<HashMap<ValueId, Constant>> = module.functions.iter().map(constants).collect();
+
+    // Call sites of each function: (caller, block, op)
+    let mut sites: HashMap<usize, Vec<(usize, usize, usize)>> = HashMap::new();
+    for (caller, caller_refs) in refs.iter().enumerate() {
+        for &(block, index, addr) in &caller_refs.calls {
+            sites.entry(addr).or_default().push((caller, block, index));
+        }
+    }
+
+    // Parameters
+    let mut params: Vec<(usize, usize, Constant)> = Vec::new();
+    for (addr, calls) in &sites {
+        let Some(&callee) = addrs.get(addr) else {
+            continue;
+        };
+        if escaped.contains(addr) {
+            continue;
+        }
+        for param in 0..module.functions[callee].params.len() {
+            let mut passed = calls.iter().map(|&(caller, block, index)| {
+                match &module.functions[caller].blocks[block].ops[index] {
+                    IrOp::Call(_, _, args) => match args.get(param) {
+                        Some(arg) => consts[caller].get(arg).cloned(),
+                        None => Some((Literal::Undefined, IrType::Any)),
+                    },
+                    _ => None,
+                }
+            });
+            let Some(Some(first)) = passed.next() else {
+                continue;
+            };
+            if passed.all(|lit| lit.as_ref() == Some(&first)) {
+                params.push((callee, param, first));
+            }
+        }
+    }
+
+    // Results, before parameter constants shift the ops of entry blocks
+    let mut replaced = 0;
+    let returned: HashMap<usize, Constant> = addrs
+        .iter()
+        .filter_map(|(&addr, &idx)| {
+            Some((
+                addr,
+                returned_constant(&module.functions[idx], &consts[idx])?,
+            ))
+        })
+        .collect();
+    for (caller, caller_refs) in refs.iter().enumerate() {
+        let mut calls: Vec<_> = caller_refs
+            .calls
+            .iter()
+            .filter_map(|&(block, index, addr)| Some((block, index, returned.get(&addr)?.clone())))
+            .collect();
+        if calls.is_empty() {
+            continue;
+        }
+        // Latest call first, so inserting after one leaves earlier sites in place
+        calls.sort_unstable_by_key(|&(block, index, _)| Reverse((block, index)));
+        let func = &mut module.functions[caller];
+        let mut uses = HashMap::new();
+        for (block, index, (lit, ty)) in calls {
+            let Some(result) = func.blocks[block].ops[index].dest() else {
+                continue;
+            };
+            let constant = func.alloc_value(ty);
+            func.blocks[block]
+                .ops
+                .insert(index + 1, IrOp::Const(constant, lit));
+            uses.insert(result, constant);
+        }
+        replaced += uses.len();
+        for block in &mut func.blocks {
+            for op in &mut block.ops {
+                replace_uses_in_op(op, &uses);
+            }
+            replace_uses_in_terminator(&mut block.terminator, &uses);
+        }
+    }
+    replaced += params.len();
+    for (callee, param, (lit, ty)) in params {
+        let func = &mut module.functions[callee];
+        let constant = func.alloc_value(ty);
+        let entry = func.entry_block();
+        func.block_mut(entry)
+            .ops
+            .insert(0, IrOp::Const(constant, lit));
+        let uses = HashMap::from([(ValueId(param as u32), constant)]);
+        for block in &mut func.blocks {
+            for op in &mut block.ops {
+                replace_uses_in_op(op, &uses);
+            }
+            replace_uses_in_terminator(&mut block.terminator, &uses);
+        }
+    }
+
+    replaced
+}
+
+/// The constant every `return` of a function returns, if there is one.
+fn returned_constant(func: &IrFunction, consts: &HashMap<ValueId, Constant>) -> Option<Constant> {
+    let mut returned = None;
+    for block in &func.blocks {
+        let Terminator::Return(value) = &block.terminator else {
+            continue;
+        };
+        let lit = match value {
+            Some(value) => consts.get(value)?.clone(),
+            None => (Literal::Undefined, IrType::Any),
+        };
+        match &returned {
+            Some(seen) if *seen != lit => return None,
+            Some(_) => {}
+            None => returned = Some(lit),
+        }
+    }
+    returned
+}
+
+// ============================================================================
+// Dead Function Elimination
+// ============================================================================
+
+/// Drop functions that nothing reachable from the roots refers to,
+/// renumbering the module's function indices. Returns the number dropped.
+pub fn remove_dead_functions(module: &mut IrModule, keep_exported: bool) -> usize {
+    let addrs = module.function_addrs.clone();
+    let mut live: HashSet<usize> = HashSet::new();
+    let mut worklist: Vec<usize> = roots(module, keep_exported).into_iter().collect();
+    while let Some(idx) = worklist.pop() {
+        if idx >= module.functions.len() || !live.insert(idx) {
+            continue;
+        }
+        let refs = function_refs(&module.functions[idx], &addrs);
+        worklist.extend(refs.referenced.iter().filter_map(|addr| addrs.get(addr)));
+    }
+
+    let dead = module.functions.len() - live.len();
+    if dead == 0 {
+        return 0;
+    }
+    let mut renumbered: HashMap<usize, usize> = HashMap::new();
+    let functions = std::mem::take(&mut module.functions);
+    for (idx, func) in functions.into_iter().enumerate() {
+        if live.contains(&idx) {
+            renumbered.insert(idx, module.functions.len());
+            module.functions.push(func);
+        }
+    }
+    module.function_addrs = addrs
+        .into_iter()
+        .filter_map(|(addr, idx)| Some((addr, *renumbered.get(&idx)?)))
+        .collect();
+    module.function_names = std::mem::take(&mut module.function_names)
+        .into_iter()
+        .filter_map(|(name, idx)| Some((name, *renumbered.get(&idx)?)))
+        .collect();
+    module.mono_cache = std::mem::take(&mut module.mono_cache)
+        .into_iter()
+        .filter_map(|((idx, types), id)| Some(((*renumbered.get(&idx)?, types), id)))
+        .collect();
+    dead
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::Compiler;
+    use crate::ir::lower::lower_module;
+    use crate::ir::opt;
+
+    fn module(source: &str) -> IrModule {
+        let bytecode = Compiler::new().compile(source).unwrap();
+        let mut module = lower_module(&bytecode).unwrap();
+        opt::optimize_module(&mut module);
+        module
+    }
+
+    fn main_calls(module: &IrModule) -> usize {
+        let main = module.functions.iter().find(|f| f.name == "main").unwrap();
+        main.blocks
+            .iter()
+            .flat_map(|block| block.ops.iter())
+            .filter(|op| matches!(op, IrOp::Call(_, _, _)))
+            .count()
+    }
+
+    #[test]
+    fn test_inline_small_functions_and_drop_them() {
+        let mut module = module(
+            "function add(a, b) { return a + b; }\n\
+             function unused(x) { return x; }\n\
+             let r = add(1, 2);\n\
+             let s = add(r, 4);\n",
+        );
+        assert_eq!(module.functions.len(), 3);
+        assert_eq!(main_calls(&module), 2);
+
+        let budget = InlineBudget {
+            callee_size: 40,
+            single_site_size: 40,
+            caller_size: 1000,
+        };
+        assert_eq!(inline_calls(&mut module, &budget, false), 2);
+        assert_eq!(main_calls(&module), 0);
+        opt::optimize_module(&mut module);
+
+        // Nothing calls either function any more
+        assert_eq!(remove_dead_functions(&mut module, false), 2);
+        assert_eq!(module.functions.len(), 1);
+        assert!(module.function_addrs.is_empty());
+
+        // A library keeps its named functions
+        let mut library = self::module("function add(a, b) { return a + b; }\n");
+        assert_eq!(remove_dead_functions(&mut library, true), 0);
+    }
+
+    #[test]
+    fn test_inlined_returns_meet_in_a_local_slot() {
+        let mut module = module(
+            "function pick(x) { if (x > 1) { return 1; } return 2; }\n\
+             let r = pick(5);\n\
+             let s = r + 1;\n",
+        );
+        let budget = InlineBudget {
+            callee_size: 40,
+            single_site_size: 40,
+            caller_size: 1000,
+        };
+        assert_eq!(inline_calls(&mut module, &budget, false), 1);
+
+        // Backends don't lower phis, so the returns store to a slot instead
+        let main = module.functions.iter().find(|f| f.name == "main").unwrap();
+        assert!(
+            !main
+                .blocks
+                .iter()
+                .flat_map(|b| b.ops.iter())
+                .any(|op| matches!(op, IrOp::Phi(_, _)))
+        );
+        assert!(
+            main.locals
+                .iter()
+                .any(|(name, _)| name.ends_with(".return"))
+        );
+
+        // The copied blocks are laid out after the call, ids still in order
+        assert!(
+            main.blocks
+                .iter()
+                .enumerate()
+                .all(|(i, block)| block.id.0 as usize == i)
+        );
+        assert!(crate::ir::verify::verify_module(&module).is_ok());
+    }
+
+    #[test]
+    fn test_recursive_callee_survives_inlining() {
+        let bytecode = Compiler::new()
+            .compile(
+                "function fact(n: number): number { if (n <= 1) { return 1; } return n * fact(n - 1); }\n\
+                 function run(x: number): number { return fact(x); }\n\
+                 let r = run(5);\n",
+            )
+            .unwrap();
+        let mut module = lower_module(&bytecode).unwrap();
+        crate::ir::typecheck::typecheck_module(&mut module);
+        opt::optimize_module_at(&mut module, opt::OptLevel::O2, false);
+
+        // `run` is inlined, but `fact` still calls itself and stays
+        assert_eq!(module.functions.len(), 2, "{}", module);
+        let fact = &module.functions[module.function_addrs.values().copied().next().unwrap()];
+        assert_eq!(function_refs(fact, &module.function_addrs).calls.len(), 1);
+    }
+
+    #[test]
+    fn test_constants_cross_call_boundaries() {
+        let mut module = module(
+            "function scale(x, k) { return x * k; }\n\
+             function answer() { return 42; }\n\
+             let a = scale(2, 3);\n\
+             let b = scale(5, 3);\n\
+             let c = answer();\n",
+        );
+        assert!(propagate_constants(&mut module, false) >= 2);
+
+        // Both calls pass k = 3, but x differs
+        let scale = module
+            .functions
+            .iter()
+            .find(|f| f.params.len() == 2)
+            .unwrap();
+        let uses: Vec<ValueId> = scale
+            .blocks
+            .iter()
+            .flat_map(|block| block.ops.iter().flat_map(|op| op.uses()))
+            .collect();
+        assert!(uses.contains(&ValueId(0)));
+        assert!(!uses.contains(&ValueId(1)));
+
+        // The call to answer() stays; its result is the constant
+        assert_eq!(main_calls(&module), 3);
+        let main = module.functions.iter().find(|f| f.name == "main").unwrap();
+        assert!(
+            main.blocks
+                .iter()
+                .flat_map(|b| b.ops.iter())
+                .any(|op| { matches!(op, IrOp::Const(_, Literal::Number(n)) if *n == 42.0) })
+        );
+    }
+}
@@ -0,0 +1,573 @@
+//! Loop optimizations.
+//!
+//! - Loop detection: natural loops, one per header, from the back edges of a
+//!   depth-first walk
+//! - Loop-invariant code motion: pure operations whose operands don't change
+//!   in the loop move to its preheader
+//! - Strength reduction: `i * c`, for an induction variable `i` (a local
+//!   stepped by an integer constant once per iteration) and an integer
+//!   constant `c`, becomes a local of its own stepped by `step * c` next to
+//!   `i`
+//!
+//! Values carried from one iteration to the next live in local slots (see
+//! `lower`), so a local the loop never stores to is invariant.
+
+use crate::ir::simd::SimdOp;
+use crate::ir::{BlockId, IrFunction, IrOp, IrType, Literal, Terminator, ValueId};
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+/// A natural loop.
+#[derive(Debug, Clone)]
+pub struct Loop {
+    pub header: BlockId,
+    /// Blocks of the loop, header included.
+    pub blocks: BTreeSet<BlockId>,
+    /// Blocks with an edge back to the header.
+    pub latches: Vec<BlockId>,
+}
+
+/// The function's natural loops, innermost first. Loops that can be entered
+/// other than through their header are left out.
+pub fn find_loops(func: &IrFunction) -> Vec<Loop> {
+    let mut preds: HashMap<BlockId, Vec<BlockId>> = HashMap::new();
+    for block in &func.blocks {
+        for succ in block.terminator.successors() {
+            preds.entry(succ).or_default().push(block.id);
+        }
+    }
+
+    let mut latches: HashMap<BlockId, Vec<BlockId>> = HashMap::new();
+    for (latch, header) in back_edges(func) {
+        latches.entry(header).or_default().push(latch);
+    }
+
+    let mut loops: Vec<Loop> = latches
+        .into_iter()
+        .filter_map(|(header, latches)| {
+            let mut blocks = BTreeSet::from([header]);
+            let mut worklist = latches.clone();
+            while let Some(block) = worklist.pop() {
+                if block == func.entry_block() && block != header {
+                    return None;
+                }
+                if blocks.insert(block) {
+                    worklist.extend(preds.get(&block).into_iter().flatten().copied());
+                }
+            }
+            Some(Loop {
+                header,
+                blocks,
+                latches,
+            })
+        })
+        .collect();
+    loops.sort_by_key(|l| (l.blocks.len(), l.header.0));
+    loops
+}
+
+/// Edges to a block still on the path of a depth-first walk from the entry.
+fn back_edges(func: &IrFunction) -> Vec<(BlockId, BlockId)> {
+    let mut edges = Vec::new();
+    if func.blocks.is_empty() {
+        return edges;
+    }
+    let entry = func.entry_block();
+    let mut visited = HashSet::from([entry]);
+    let mut on_path = HashSet::from([entry]);
+    let mut stack = vec![(entry, func.successors_of(entry))];
+    while let Some((block, pending)) = stack.last_mut() {
+        let block = *block;
+        match pending.pop() {
+            Some(succ) if on_path.contains(&succ) => edges.push((block, succ)),
+            Some(succ) => {
+                if visited.insert(succ) {
+                    on_path.insert(succ);
+                    stack.push((succ, func.successors_of(succ)));
+                }
+            }
+            None => {
+                on_path.remove(&block);
+                stack.pop();
+            }
+        }
+    }
+    edges
+}
+
+/// Run the loop optimizations, innermost loops first. Returns the number of
+/// operations hoisted or strength-reduced.
+pub fn optimize_loops(func: &mut IrFunction) -> usize {
+    let mut changed = 0;
+    let mut done = HashSet::new();
+    // Preheaders added for inner loops belong to the outer ones, so loops
+    // are found again after each
+    while let Some(l) = find_loops(func)
+        .into_iter()
+        .find(|l| !done.contains(&l.header))
+    {
+        done.insert(l.header);
+        let Some(preheader) = preheader(func, &l) else {
+            continue;
+        };
+        changed += hoist_invariants(func, &l, preheader);
+        changed += reduce_strength(func, &l, preheader);
+    }
+    func.compute_predecessors();
+    changed
+}
+
+/// The block everything entering the loop comes through, added if there is
+/// no such block yet. `None` when it can't be added: the header is the
+/// entry block, or its phis merge several ways in.
+fn preheader(func: &mut IrFunction, l: &Loop) -> Option<BlockId> {
+    let outside: Vec<BlockId> = func
+        .blocks
+        .iter()
+        .filter(|block| {
+            !l.blocks.contains(&block.id) && block.terminator.successors().contains(&l.header)
+        })
+        .map(|block| block.id)
+        .collect();
+    match outside.as_slice() {
+        [] => return None,
+        [pred] if matches!(func.block(*pred).terminator, Terminator::Jump(_)) => {
+            return Some(*pred);
+        }
+        [_] => {}
+        _ if func
+            .block(l.header)
+            .ops
+            .iter()
+            .any(|op| matches!(op, IrOp::Phi(_, _))) =>
+        {
+            return None;
+        }
+        _ => {}
+    }
+
+    let preheader = func.alloc_block();
+    func.block_mut(preheader).terminator = Terminator::Jump(l.header);
+    for pred in &outside {
+        match &mut func.block_mut(*pred).terminator {
+            Terminator::Jump(target) => *target = preheader,
+            Terminator::Branch(_, t, f) => {
+                if *t == l.header {
+                    *t = preheader;
+                }
+                if *f == l.header {
+                    *f = preheader;
+                }
+            }
+            _ => {}
+        }
+    }
+    for op in &mut func.block_mut(l.header).ops {
+        if let IrOp::Phi(_, entries) = op {
+            for (pred, _) in entries {
+                if outside.contains(pred) {
+                    *pred = preheader;
+                }
+            }
+        }
+    }
+    Some(preheader)
+}
+
+/// Local slots the loop stores to.
+fn stored_slots(func: &IrFunction, l: &Loop) -> HashSet<u32> {
+    l.blocks
+        .iter()
+        .flat_map(|&id| func.block(id).ops.iter())
+        .filter_map(|op| match op {
+            IrOp::StoreLocal(slot, _) => Some(*slot),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Whether `op` computes the same value wherever it runs, given the same
+/// operands, without side effects or traps. Comparisons and bitwise
+/// operations can call `valueOf` on objects, so only those on numbers and
+/// booleans qualify.
+fn is_pure(func: &IrFunction, op: &IrOp, stored: &HashSet<u32>) -> bool {
+    let primitive = |v: &ValueId| {
+        func.value_types
+            .get(v)
+            .is_some_and(|ty| matches!(ty, IrType::Number | IrType::Boolean))
+    };
+    match op {
+        IrOp::Const(_, _)
+        | IrOp::Copy(_, _)
+        | IrOp::AddNum(_, _, _)
+        | IrOp::SubNum(_, _, _)
+        | IrOp::MulNum(_, _, _)
+        | IrOp::DivNum(_, _, _)
+        | IrOp::ModNum(_, _, _)
+        | IrOp::NegNum(_, _)
+        | IrOp::Intrinsic(_, _, _)
+        | IrOp::EqStrict(_, _, _)
+        | IrOp::NeStrict(_, _, _)
+        | IrOp::Not(_, _)
+        | IrOp::TypeOf(_, _) => true,
+        // Boxing allocates a typed array, and unboxing reads one the loop
+        // may write
+        IrOp::Simd(_, _, op, _) => !matches!(op, SimdOp::Box | SimdOp::Unbox),
+        IrOp::Lt(_, a, b)
+        | IrOp::LtEq(_, a, b)
+        | IrOp::Gt(_, a, b)
+        | IrOp::GtEq(_, a, b)
+        | IrOp::And(_, a, b)
+        | IrOp::Or(_, a, b)
+        | IrOp::BitAnd(_, a, b)
+        | IrOp::BitOr(_, a, b)
+        | IrOp::Xor(_, a, b)
+        | IrOp::Shl(_, a, b)
+        | IrOp::Shr(_, a, b)
+        | IrOp::ShrU(_, a, b)
+        | IrOp::Pow(_, a, b) => primitive(a) && primitive(b),
+        IrOp::LoadLocal(_, slot) => !stored.contains(slot),
+        _ => false,
+    }
+}
+
+// ============================================================================
+// Loop-Invariant Code Motion
+// ============================================================================
+
+/// Move the loop's invariant operations to the end of its preheader.
+fn hoist_invariants(func: &mut IrFunction, l: &Loop, preheader: BlockId) -> usize {
+    let stored = stored_slots(func, l);
+    let defined: HashSet<ValueId> = l
+        .blocks
+        .iter()
+        .flat_map(|&id| func.block(id).ops.iter())
+        .filter_map(|op| op.dest())
+        .collect();
+
+    // Operands come before the ops using them, so this order is one the
+    // preheader can run them in
+    let mut invariant = HashSet::new();
+    let mut order = Vec::new();
+    loop {
+        let found = order.len();
+        for &id in &l.blocks {
+            for op in &func.block(id).ops {
+                let Some(dst) = op.dest() else {
+                    continue;
+                };
+                if !invariant.contains(&dst)
+                    && is_pure(func, op, &stored)
+                    && op
+                        .uses()
+                        .iter()
+                        .all(|v| !defined.contains(v) || invariant.contains(v))
+                {
+                    invariant.insert(dst);
+                    order.push(dst);
+                }
+            }
+        }
+        if order.len() == found {
+            break;
+        }
+    }
+    if order.is_empty() {
+        return 0;
+    }
+
+    let mut hoisted: HashMap<ValueId, IrOp> = HashMap::new();
+    for &id in &l.blocks {
+        let block = func.block_mut(id);
+        for op in std::mem::take(&mut block.ops) {
+            match op.dest() {
+                Some(dst) if invariant.contains(&dst) => {
+                    hoisted.insert(dst, op);
+                }
+                _ => block.ops.push(op),
+            }
+        }
+    }
+    let target = func.block_mut(preheader);
+    target
+        .ops
+        .extend(order.iter().filter_map(|dst| hoisted.remove(dst)));
+    order.len()
+}
+
+// ============================================================================
+// Strength Reduction
+// ============================================================================
+
+/// A local stepped by a constant once per iteration.
+struct Induction {
+    /// Block and index of its store in the loop.
+    store: (BlockId, usize),
+    step: f64,
+}
+
+fn integer(lit: Option<&Literal>) -> Option<f64> {
+    match lit {
+        Some(Literal::Number(n)) if n.fract() == 0.0 && n.abs() < 9.0e15 => Some(*n),
+        _ => None,
+    }
+}
+
+/// Induction variables of the loop: locals whose only store in the loop
+/// is `i = i + step` (or `i - step`) with an integer constant `step`, and
+/// whose stores anywhere else are integer constants, so `i * c` can be
+/// stepped exactly.
+fn induction_variables(
+    func: &IrFunction,
+    l: &Loop,
+    defs: &HashMap<ValueId, &IrOp>,
+) -> HashMap<u32, Induction> {
+    let constant = |v: &ValueId| match defs.get(v) {
+        Some(IrOp::Const(_, lit)) => integer(Some(lit)),
+        _ => None,
+    };
+    let loads =
+        |v: &ValueId, slot: u32| matches!(defs.get(v), Some(IrOp::LoadLocal(_, s)) if *s == slot);
+
+    let mut stores: HashMap<u32, Vec<(BlockId, usize, ValueId)>> = HashMap::new();
+    let mut outside_ok: HashMap<u32, bool> = HashMap::new();
+    for block in &func.blocks {
+        for (i, op) in block.ops.iter().enumerate() {
+            let IrOp::StoreLocal(slot, val) = op else {
+                continue;
+            };
+            if l.blocks.contains(&block.id) {
+                stores.entry(*slot).or_default().push((block.id, i, *val));
+            } else {
+                let ok = outside_ok.entry(*slot).or_insert(true);
+                *ok &= constant(val).is_some();
+            }
+        }
+    }
+
+    stores
+        .into_iter()
+        .filter_map(|(slot, stores)| {
+            let [(block, index, val)] = stores.as_slice() else {
+                return None;
+            };
+            if outside_ok.get(&slot) == Some(&false) {
+                return None;
+            }
+            let step = match defs.get(val)? {
+                IrOp::AddNum(_, a, b) if loads(a, slot) => constant(b)?,
+                IrOp::AddNum(_, a, b) if loads(b, slot) => constant(a)?,
+                IrOp::SubNum(_, a, b) if loads(a, slot) => -constant(b)?,
+                _ => return None,
+            };
+            Some((
+                slot,
+                Induction {
+                    store: (*block, *index),
+                    step,
+                },
+            ))
+        })
+        .collect()
+}
+
+/// Replace `i * c` in the loop with a local kept equal to it.
+fn reduce_strength(func: &mut IrFunction, l: &Loop, preheader: BlockId) -> usize {
+    let defs: HashMap<ValueId, &IrOp> = func
+        .blocks
+        .iter()
+        .flat_map(|block| block.ops.iter())
+        .filter_map(|op| Some((op.dest()?, op)))
+        .collect();
+    let ivs = induction_variables(func, l, &defs);
+    if ivs.is_empty() {
+        return 0;
+    }
+
+    // (block, index, dst, induction slot, factor)
+    let mut products = Vec::new();
+    for &id in &l.blocks {
+        let ops = &func.block(id).ops;
+        for (i, op) in ops.iter().enumerate() {
+            let IrOp::MulNum(dst, a, b) = op else {
+                continue;
+            };
+            let operands = [(a, b), (b, a)];
+            let Some((slot, factor)) = operands.iter().find_map(|&(x, c)| {
+                let IrOp::LoadLocal(_, slot) = defs.get(x)? else {
+                    return None;
+                };
+                ivs.contains_key(slot).then_some(())?;
+                let factor = match defs.get(c)? {
+                    IrOp::Const(_, lit) => integer(Some(lit))?,
+                    _ => return None,
+                };
+                // `x` must still be the local's value at the multiplication
+                let loaded = ops[..i].iter().rposition(|op| op.dest() == Some(*x))?;
+                let restored = ops[loaded..i]
+                    .iter()
+                    .any(|op| matches!(op, IrOp::StoreLocal(s, _) if s == slot));
+                (!restored).then_some((*slot, factor))
+            }) else {
+                continue;
+            };
+            products.push((id, i, *dst, slot, factor));
+        }
+    }
+    if products.is_empty() {
+        return 0;
+    }
+
+    let mut reduced: HashMap<(u32, u64), u32> = HashMap::new();
+    let mut steps: Vec<(BlockId, usize, u32, f64)> = Vec::new();
+    let count = products.len();
+    for (block, index, dst, slot, factor) in products {
+        let key = (slot, factor.to_bits());
+        let product = match reduced.get(&key) {
+            Some(&product) => product,
+            None => {
+                let name = format!("{}*{}", func.locals[slot as usize].0, factor);
+                let product = func.add_local(name, IrType::Number);
+                // Start from the induction variable's value on entry
+                let current = func.alloc_value(IrType::Number);
+                let k = func.alloc_value(IrType::Number);
+                let start = func.alloc_value(IrType::Number);
+                func.block_mut(preheader).ops.extend([
+                    IrOp::LoadLocal(current, slot),
+                    IrOp::Const(k, Literal::Number(factor)),
+                    IrOp::MulNum(start, current, k),
+                    IrOp::StoreLocal(product, start),
+                ]);
+                let (store_block, store_index) = ivs[&slot].store;
+                steps.push((store_block, store_index, product, ivs[&slot].step * factor));
+                reduced.insert(key, product);
+                product
+            }
+        };
+        func.block_mut(block).ops[index] = IrOp::LoadLocal(dst, product);
+    }
+
+    // Step each product right after its induction variable, latest first so
+    // the indices of the others stay put
+    steps.sort_by_key(|&(block, index, ..)| Reverse((block, index)));
+    for (block, index, product, step) in steps {
+        let current = func.alloc_value(IrType::Number);
+        let k = func.alloc_value(IrType::Number);
+        let next = func.alloc_value(IrType::Number);
+        let ops = &mut func.block_mut(block).ops;
+        ops.splice(
+            index + 1..index + 1,
+            [
+                IrOp::LoadLocal(current, product),
+                IrOp::Const(k, Literal::Number(step)),
+                IrOp::AddNum(next, current, k),
+                IrOp::StoreLocal(product, next),
+            ],
+        );
+    }
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// ```text
+    /// let i = 0; let k = 3;
+    /// while (i < 100) { x = k * 2 + i * 4; i = i + 1; }
+    /// ```
+    fn counting_loop() -> IrFunction {
+        let mut func = IrFunction::new("count".to_string());
+        let entry = func.alloc_block();
+        let header = func.alloc_block();
+        let body = func.alloc_block();
+        let exit = func.alloc_block();
+        let i = func.add_local("i".to_string(), IrType::Number);
+        let k = func.add_local("k".to_string(), IrType::Number);
+        let x = func.add_local("x".to_string(), IrType::Number);
+        let v: Vec<ValueId> = (0..15).map(|_| func.alloc_value(IrType::Number)).collect();
+
+        {
+            let block = func.block_mut(entry);
+            block.push(IrOp::Const(v[0], Literal::Number(0.0)));
+            block.push(IrOp::StoreLocal(i, v[0]));
+            block.push(IrOp::Const(v[1], Literal::Number(3.0)));
+            block.push(IrOp::StoreLocal(k, v[1]));
+            block.terminate(Terminator::Jump(header));
+        }
+        {
+            let block = func.block_mut(header);
+            block.push(IrOp::LoadLocal(v[2], i));
+            block.push(IrOp::Const(v[3], Literal::Number(100.0)));
+            block.push(IrOp::Lt(v[4], v[2], v[3]));
+            block.terminate(Terminator::Branch(v[4], body, exit));
+        }
+        {
+            let block = func.block_mut(body);
+            block.push(IrOp::LoadLocal(v[5], k));
+            block.push(IrOp::Const(v[6], Literal::Number(2.0)));
+            block.push(IrOp::MulNum(v[7], v[5], v[6]));
+            block.push(IrOp::LoadLocal(v[8], i));
+            block.push(IrOp::Const(v[9], Literal::Number(4.0)));
+            block.push(IrOp::MulNum(v[10], v[8], v[9]));
+            block.push(IrOp::AddNum(v[11], v[7], v[10]));
+            block.push(IrOp::StoreLocal(x, v[11]));
+            block.push(IrOp::LoadLocal(v[12], i));
+            block.push(IrOp::Const(v[13], Literal::Number(1.0)));
+            block.push(IrOp::AddNum(v[14], v[12], v[13]));
+            block.push(IrOp::StoreLocal(i, v[14]));
+            block.terminate(Terminator::Jump(header));
+        }
+        func.block_mut(exit).terminate(Terminator::Return(None));
+        func.compute_predecessors();
+        func
+    }
+
+    #[test]
+    fn test_find_loops() {
+        let func = counting_loop();
+        let loops = find_loops(&func);
+        assert_eq!(loops.len(), 1);
+        assert_eq!(loops[0].header, BlockId(1));
+        assert_eq!(loops[0].blocks, BTreeSet::from([BlockId(1), BlockId(2)]));
+        assert_eq!(loops[0].latches, vec![BlockId(2)]);
+    }
+
+    #[test]
+    fn test_invariants_hoisted_and_products_stepped() {
+        let mut func = counting_loop();
+        assert!(optimize_loops(&mut func) > 0);
+
+        // `k * 2` is computed once, before the loop
+        let entry = &func.block(BlockId(0)).ops;
+        assert!(
+            entry
+                .iter()
+                .any(|op| matches!(op, IrOp::MulNum(d, _, _) if *d == ValueId(7)))
+        );
+        // `i * 4` became a local of its own, stepped by 4 after `i`
+        let body = &func.block(BlockId(2)).ops;
+        assert!(!body.iter().any(|op| matches!(op, IrOp::MulNum(_, _, _))));
+        assert_eq!(func.locals[3].0, "i*4");
+        assert!(
+            body.iter()
+                .any(|op| matches!(op, IrOp::LoadLocal(d, 3) if *d == ValueId(10)))
+        );
+        let steps: Vec<&IrOp> = body
+            .iter()
+            .skip_while(|op| !matches!(op, IrOp::StoreLocal(0, _)))
+            .collect();
+        assert!(matches!(steps.last(), Some(IrOp::StoreLocal(3, _))));
+        assert!(
+            steps
+                .iter()
+                .any(|op| matches!(op, IrOp::Const(_, Literal::Number(n)) if *n == 4.0))
+        );
+
+        // `i` changes every iteration, so its loads stay
+        assert!(matches!(
+            func.block(BlockId(1)).ops.first(),
+            Some(IrOp::LoadLocal(_, 0))
+        ));
+    }
+}
@@ -7,8 +7,12 @@
 //! 3. Convert stack operations to explicit value assignments
 //! 4. Insert phi nodes at CFG merge points
 
-use crate::ir::{BlockId, IrFunction, IrModule, IrOp, IrType, Literal, Terminator, ValueId};
-use crate::vm::opcodes::OpCode;
+use crate::ir::intrinsics::{Intrinsic, STD_INTRINSICS};
+use crate::ir::simd::{STD_SIMD, SimdKind, SimdOp};
+use crate::ir::{
+    BlockId, IrFunction, IrModule, IrOp, IrType, Literal, Terminator, ValueId, verify,
+};
+use crate::vm::opcodes::{OpCode, PINNED_GLOBALS};
 use crate::vm::value::JsValue;
 use std::collections::{HashMap, HashSet};
 
@@ -56,6 +60,18 @@ pub struct Lowerer {
     local_values: HashMap<u32, ValueId>,
     /// Block entry states for phi node generation.
     block_entry_stacks: HashMap<BlockId, Vec<ValueId>>,
+    /// Parameter values, read by `LoadArg`.
+    params: Vec<ValueId>,
+    /// (instruction index, source line) of each statement, ordered by index.
+    lines: Vec<(usize, u32)>,
+    /// Index of the first instruction within `lines`' numbering.
+    line_base: usize,
+    /// Source line of the instruction being lowered.
+    line: Option<u32>,
+    /// Values holding an intrinsic namespace (`Math`, `std.intrinsics`,
+    /// `std.simd.f64x2`, ...) read from its pinned global, by namespace
+    /// path.
+    namespaces: HashMap<ValueId, &'static str>,
 }
 
 impl Lowerer {
@@ -73,6 +89,11 @@ impl Lowerer {
             var_to_slot: HashMap::new(),
             local_values: HashMap::new(),
             block_entry_stacks: HashMap::new(),
+            params: Vec::new(),
+            lines: Vec::new(),
+            line_base: 0,
+            line: None,
+            namespaces: HashMap::new(),
         }
     }
 
@@ -80,17 +101,23 @@ impl Lowerer {
     pub fn new_with_params(name: String, param_names: &[String]) -> Self {
         let mut lowerer = Self::new(name);
 
-        // Add parameters to the function and pre-populate the stack
+        // Add parameters to the function; the prologue's `LoadArg`s push them
         for param_name in param_names {
             let param_val = lowerer.alloc_value(IrType::Any);
             lowerer.func.params.push((param_name.clone(), IrType::Any));
-            // Pre-populate stack with parameter values (they'll be popped by Let)
-            lowerer.stack.push(param_val);
+            lowerer.params.push(param_val);
         }
 
         lowerer
     }
 
+    /// Attribute values to source lines from a compiler line table, whose
+    /// instruction indices are offset by `base` from this function's.
+    pub fn set_line_table(&mut self, line_table: &[(usize, u32)], base: usize) {
+        self.lines = line_table.to_vec();
+        self.line_base = base;
+    }
+
     /// Lower a sequence of bytecode instructions to SSA IR.
     pub fn lower(mut self, instructions: &[OpCode]) -> Result<IrFunction, LowerError> {
         // Pass 1: Identify basic block boundaries
@@ -131,11 +158,11 @@ impl Lowerer {
                         self.block_starts.insert(i + 1);
                     }
                 }
-                OpCode::Return | OpCode::Halt => {
+                OpCode::Return | OpCode::ReturnMulti(_) | OpCode::Halt
+                    if i + 1 < instructions.len() =>
+                {
                     // Instruction after terminator is a block start
-                    if i + 1 < instructions.len() {
-                        self.block_starts.insert(i + 1);
-                    }
+                    self.block_starts.insert(i + 1);
                 }
                 OpCode::Call(_) | OpCode::CallMethod(_, _) => {
                     // Calls can throw, so next instruction could be a catch block
@@ -166,7 +193,18 @@ impl Lowerer {
 
     /// Allocate a new SSA value with the given type.
     fn alloc_value(&mut self, ty: IrType) -> ValueId {
-        self.func.alloc_value(ty)
+        let value = self.func.alloc_value(ty);
+        if let Some(line) = self.line {
+            self.func.source_lines.insert(value, line);
+        }
+        value
+    }
+
+    /// Source line of the statement containing instruction `idx`.
+    fn line_at(&self, idx: usize) -> Option<u32> {
+        let ip = self.line_base + idx;
+        let end = self.lines.partition_point(|&(start, _)| start <= ip);
+        end.checked_sub(1).map(|i| self.lines[i].1)
     }
 
     /// Push a value onto the abstract stack.
@@ -189,6 +227,75 @@ impl Lowerer {
         self.func.block_mut(self.current_block).push(op);
     }
 
+    /// Lower `std.simd.<kind>(...)` and `std.simd.<kind>.<method>(...)`
+    /// to vector operations on unboxed operands, boxing vector results.
+    /// `None` keeps the ordinary call: wrong arities, and shuffles whose
+    /// lanes aren't valid constants.
+    fn lower_simd_call(
+        &mut self,
+        namespace: &str,
+        method: &str,
+        args: &[ValueId],
+    ) -> Option<ValueId> {
+        if namespace == STD_SIMD {
+            let kind = SimdKind::from_name(method).filter(|kind| kind.lanes() == args.len())?;
+            let lanes = args
+                .iter()
+                .map(|&arg| {
+                    let num = self.alloc_value(IrType::Number);
+                    self.emit(IrOp::ToNum(num, arg));
+                    num
+                })
+                .collect();
+            let vector = self.emit_simd(kind, SimdOp::Make, lanes);
+            return Some(self.emit_simd(kind, SimdOp::Box, vec![vector]));
+        }
+
+        let kind = SimdKind::ALL
+            .into_iter()
+            .find(|kind| kind.path() == namespace)?;
+        let (op, operands) = match (method, args) {
+            ("shuffle", [a, b, indices @ ..]) => {
+                let indices = indices
+                    .iter()
+                    .map(|&i| self.constant_number(i))
+                    .collect::<Option<Vec<_>>>()?;
+                (SimdOp::shuffle(kind, &indices)?, vec![*a, *b])
+            }
+            _ => {
+                let op = SimdOp::from_method(method).filter(|op| op.arity(kind) == args.len())?;
+                (op, args.to_vec())
+            }
+        };
+        let vectors = operands
+            .into_iter()
+            .map(|arg| self.emit_simd(kind, SimdOp::Unbox, vec![arg]))
+            .collect();
+        let result = self.emit_simd(kind, op.clone(), vectors);
+        Some(match op {
+            SimdOp::ReduceAdd => result,
+            _ => self.emit_simd(kind, SimdOp::Box, vec![result]),
+        })
+    }
+
+    fn emit_simd(&mut self, kind: SimdKind, op: SimdOp, args: Vec<ValueId>) -> ValueId {
+        let dst = self.alloc_value(op.result_type(kind));
+        self.emit(IrOp::Simd(dst, kind, op, args));
+        dst
+    }
+
+    /// The number `value` holds, if it is a constant of the current block.
+    fn constant_number(&self, value: ValueId) -> Option<f64> {
+        self.func
+            .block(self.current_block)
+            .ops
+            .iter()
+            .find_map(|op| match op {
+                IrOp::Const(dst, Literal::Number(n)) if *dst == value => Some(*n),
+                _ => None,
+            })
+    }
+
     /// Set the terminator for the current block.
     fn terminate(&mut self, term: Terminator) {
         self.func.block_mut(self.current_block).terminate(term);
@@ -280,6 +387,7 @@ impl Lowerer {
                 _ => {}
             }
 
+            self.line = self.line_at(i);
             self.lower_instruction(i, op)?;
         }
 
@@ -324,7 +432,7 @@ impl Lowerer {
                     worklist.push(*target);
                     worklist.push(ip + 1); // Fall-through
                 }
-                OpCode::Return | OpCode::Halt => {
+                OpCode::Return | OpCode::ReturnMulti(_) | OpCode::Halt => {
                     // No successors
                 }
                 _ => {
@@ -586,6 +694,16 @@ impl Lowerer {
                 self.push(dst);
             }
 
+            OpCode::LoadGlobal(slot) => {
+                let name = PINNED_GLOBALS[*slot as usize];
+                let dst = self.alloc_value(IrType::Any);
+                self.emit(IrOp::LoadGlobal(dst, name.to_string()));
+                if name == "Math" || name == "std" {
+                    self.namespaces.insert(dst, name);
+                }
+                self.push(dst);
+            }
+
             OpCode::Drop(_name) => {
                 // Drop is a no-op in SSA form
             }
@@ -623,6 +741,15 @@ impl Lowerer {
                 let obj = self.pop()?;
                 let dst = self.alloc_value(IrType::Any);
                 self.emit(IrOp::GetProp(dst, obj, name.clone()));
+                let namespace = match (self.namespaces.get(&obj).copied(), name.as_str()) {
+                    (Some("std"), "intrinsics") => Some(STD_INTRINSICS),
+                    (Some("std"), "simd") => Some(STD_SIMD),
+                    (Some(STD_SIMD), name) => SimdKind::from_name(name).map(SimdKind::path),
+                    _ => None,
+                };
+                if let Some(namespace) = namespace {
+                    self.namespaces.insert(dst, namespace);
+                }
                 self.push(dst);
             }
 
@@ -635,18 +762,18 @@ impl Lowerer {
             }
 
             OpCode::SetPropComputed => {
-                // For now, just pop the values - the VM handles this
-                let _key = self.pop()?;
-                let _val = self.pop()?;
+                // Stack: [..., obj, val, key]; like the VM, nothing is pushed
+                let key = self.pop()?;
+                let val = self.pop()?;
                 let obj = self.pop()?;
-                self.push(obj);
+                self.emit(IrOp::SetElement(obj, key, val));
             }
 
             OpCode::GetPropComputed => {
-                // For now, just pop the values and push undefined
-                let _key = self.pop()?;
-                let _obj = self.pop()?;
+                let key = self.pop()?;
+                let obj = self.pop()?;
                 let dst = self.alloc_value(IrType::Any);
+                self.emit(IrOp::GetElement(dst, obj, key));
                 self.push(dst);
             }
 
@@ -666,8 +793,9 @@ impl Lowerer {
             }
 
             OpCode::StoreElement => {
-                let val = self.pop()?;
+                // Stack: [..., arr, val, idx]
                 let idx = self.pop()?;
+                let val = self.pop()?;
                 let arr = self.pop()?;
                 self.emit(IrOp::SetElement(arr, idx, val));
             }
@@ -709,6 +837,37 @@ impl Lowerer {
                 self.terminate(Terminator::Return(ret_val));
             }
 
+            OpCode::ReturnMulti(count) => {
+                // The IR has single-value returns: build the array explicitly.
+                // Call sites unpack it with constant-index loads, which
+                // scalar replacement removes once both sides are in one function.
+                let mut values = Vec::with_capacity(*count);
+                for _ in 0..*count {
+                    values.push(self.pop()?);
+                }
+                values.reverse();
+
+                let arr = self.alloc_value(IrType::Array);
+                self.emit(IrOp::NewArray(arr));
+                for (i, val) in values.into_iter().enumerate() {
+                    let idx = self.alloc_value(IrType::Number);
+                    self.emit(IrOp::Const(idx, Literal::Number(i as f64)));
+                    self.emit(IrOp::SetElement(arr, idx, val));
+                }
+                self.terminate(Terminator::Return(Some(arr)));
+            }
+
+            OpCode::Unpack(count) => {
+                let arr = self.pop()?;
+                for i in 0..*count {
+                    let idx = self.alloc_value(IrType::Number);
+                    self.emit(IrOp::Const(idx, Literal::Number(i as f64)));
+                    let dst = self.alloc_value(IrType::Any);
+                    self.emit(IrOp::GetElement(dst, arr, idx));
+                    self.push(dst);
+                }
+            }
+
             OpCode::Halt => {
                 // If there's a value on the stack, return it (for REPL-style scripts)
                 let ret_val = if self.stack.is_empty() {
@@ -719,6 +878,16 @@ impl Lowerer {
                 self.terminate(Terminator::Return(ret_val));
             }
 
+            // Parameters; a missing argument reads as undefined
+            OpCode::LoadArg(index) => match self.params.get(*index) {
+                Some(&param) => self.push(param),
+                None => {
+                    let dst = self.alloc_value(IrType::Any);
+                    self.emit(IrOp::Const(dst, Literal::Undefined));
+                    self.push(dst);
+                }
+            },
+
             // Function calls
             OpCode::Call(argc) => {
                 let func_val = self.pop()?;
@@ -746,6 +915,33 @@ impl Lowerer {
                 }
                 args.reverse();
 
+                // Intrinsics convert their arguments to numbers, like the
+                // VM natives, then compile inline
+                if let Some(intrinsic) = self
+                    .namespaces
+                    .get(&obj)
+                    .and_then(|namespace| Intrinsic::lookup(namespace, name, *argc))
+                {
+                    let args = args
+                        .into_iter()
+                        .map(|arg| {
+                            let num = self.alloc_value(IrType::Number);
+                            self.emit(IrOp::ToNum(num, arg));
+                            num
+                        })
+                        .collect();
+                    let dst = self.alloc_value(IrType::Number);
+                    self.emit(IrOp::Intrinsic(dst, intrinsic, args));
+                    self.push(dst);
+                    return Ok(());
+                }
+                if let Some(&namespace) = self.namespaces.get(&obj)
+                    && let Some(dst) = self.lower_simd_call(namespace, name, &args)
+                {
+                    self.push(dst);
+                    return Ok(());
+                }
+
                 let dst = self.alloc_value(IrType::Any);
                 self.emit(IrOp::CallMethod(dst, obj, name.clone(), args));
                 self.push(dst);
@@ -862,7 +1058,7 @@ impl Lowerer {
             // === ES Modules ===
             // ES modules require async loading which isn't supported in AOT yet
             // These opcodes will work in JIT mode but emit stubs for AOT
-            OpCode::ImportAsync(_specifier) => {
+            OpCode::ImportAsync { .. } => {
                 // Pop the module specifier URL
                 let _url = self.pop()?;
                 // Emit undefined as a placeholder for the promise
@@ -890,6 +1086,28 @@ impl Lowerer {
                 self.push(dst);
             }
 
+            OpCode::ImportMeta => {
+                // No module metadata in AOT builds yet
+                let dst = self.alloc_value(IrType::Any);
+                self.emit(IrOp::Const(dst, Literal::Undefined));
+                self.push(dst);
+            }
+
+            OpCode::ImportBinding { name: _, local } => {
+                // Bind the import to undefined for now, like GetExport
+                let _namespace = self.pop()?;
+                let val = self.alloc_value(IrType::Any);
+                self.emit(IrOp::Const(val, Literal::Undefined));
+                let slot = self.get_or_create_local(local);
+                self.emit(IrOp::StoreLocal(slot, val));
+                self.local_values.insert(slot, val);
+            }
+
+            OpCode::ReExport { .. } | OpCode::ReExportAll => {
+                // Nothing to export from an AOT module yet
+                let _namespace = self.pop()?;
+            }
+
             OpCode::ModuleResolutionError { .. } => {
                 // Module resolution error - pop and ignore for AOT
                 let _specifier = self.pop()?;
@@ -954,12 +1172,31 @@ impl Lowerer {
                 self.push(dst);
             }
 
-            // Spread operations - not yet supported in IR, fall back to interpreter
-            OpCode::ArrayPush | OpCode::ArraySpread | OpCode::ObjectSpread => {
+            // Spread and rest operations - not yet supported in IR, fall back to interpreter
+            OpCode::ArrayPush
+            | OpCode::ArraySpread
+            | OpCode::ObjectSpread
+            | OpCode::LoadRestArgs(_) => {
                 // For now, these operations require runtime support
                 // and are handled by the interpreter
                 return Err(LowerError::UnsupportedOpcode(format!("{:?}", op)));
             }
+
+            // Private methods and brand checks look up the receiver's private
+            // slots at runtime - handled by the interpreter
+            OpCode::CallPrivateMethod(_, _) | OpCode::HasPrivateProp(_) => {
+                return Err(LowerError::UnsupportedOpcode(format!("{:?}", op)));
+            }
+
+            // Weak maps hold private state outside the IR's object model
+            OpCode::NewWeakMap => {
+                return Err(LowerError::UnsupportedOpcode(format!("{:?}", op)));
+            }
+
+            // `in` walks the prototype chain - handled by the interpreter
+            OpCode::In => {
+                return Err(LowerError::UnsupportedOpcode(format!("{:?}", op)));
+            }
         }
 
         Ok(())
@@ -1005,6 +1242,15 @@ pub struct ExtractedFunction {
 
 /// Lower an entire bytecode module to SSA IR.
 pub fn lower_module(instructions: &[OpCode]) -> Result<IrModule, LowerError> {
+    lower_module_with_lines(instructions, &[])
+}
+
+/// Lower a bytecode module, recording each value's source line from the
+/// compiler's line table.
+pub fn lower_module_with_lines(
+    instructions: &[OpCode],
+    line_table: &[(usize, u32)],
+) -> Result<IrModule, LowerError> {
     let mut module = IrModule::new();
 
     // Step 1: Extract all function definitions from bytecode
@@ -1013,11 +1259,13 @@ pub fn lower_module(instructions: &[OpCode]) -> Result<IrModule, LowerError> {
     // Step 1.5: Build a map of variable name -> function address
     // This is used to pre-initialize function references in extracted functions
     let mut func_var_addrs: HashMap<String, usize> = HashMap::new();
+    let mut func_var_counts: HashMap<String, usize> = HashMap::new();
     for i in 0..instructions.len().saturating_sub(1) {
         if let OpCode::Push(JsValue::Function { address, .. }) = &instructions[i]
             && let OpCode::Let(name) | OpCode::Store(name) = &instructions[i + 1]
         {
             func_var_addrs.insert(name.clone(), *address);
+            *func_var_counts.entry(name.clone()).or_insert(0) += 1;
         }
     }
 
@@ -1034,6 +1282,7 @@ pub fn lower_module(instructions: &[OpCode]) -> Result<IrModule, LowerError> {
             func_info.address,
             func_info.self_reference_var.as_ref(),
             &func_var_addrs,
+            line_table,
         ) {
             Ok(ir_func) => {
                 module.add_function(ir_func);
@@ -1046,7 +1295,8 @@ pub fn lower_module(instructions: &[OpCode]) -> Result<IrModule, LowerError> {
     }
 
     // Step 3: Lower the main code (treating skipped function bodies as jumps)
-    let lowerer = Lowerer::new("main".to_string());
+    let mut lowerer = Lowerer::new("main".to_string());
+    lowerer.set_line_table(line_table, 0);
     let main_func = lowerer.lower(instructions)?;
     module.add_function(main_func);
 
@@ -1055,6 +1305,15 @@ pub fn lower_module(instructions: &[OpCode]) -> Result<IrModule, LowerError> {
         module.function_addrs.insert(func_info.address, i);
     }
 
+    // Names bound to more than one function are ambiguous
+    for (name, address) in &func_var_addrs {
+        if func_var_counts[name] == 1
+            && let Some(&idx) = module.function_addrs.get(address)
+        {
+            module.function_names.insert(name.clone(), idx);
+        }
+    }
+
     // Step 4: Detect user-defined main() function
     // Look for pattern: Push(Function { address: X, ... }) followed by Let("main")
     for i in 0..instructions.len().saturating_sub(1) {
@@ -1067,6 +1326,16 @@ pub fn lower_module(instructions: &[OpCode]) -> Result<IrModule, LowerError> {
         }
     }
 
+    // Debug builds check the lowering's output
+    if cfg!(debug_assertions)
+        && let Err(errors) = verify::check_module(&module)
+    {
+        eprintln!(
+            "Warning: Lowering produced invalid IR:\n{}",
+            verify::list(&errors)
+        );
+    }
+
     Ok(module)
 }
 
@@ -1078,10 +1347,12 @@ fn lower_extracted_function(
     base_addr: usize,
     self_ref_var: Option<&String>,
     func_var_addrs: &HashMap<String, usize>,
+    line_table: &[(usize, u32)],
 ) -> Result<IrFunction, LowerError> {
     // Rebase jump targets to be relative to the function start
     let rebased = rebase_jump_targets(instructions, base_addr);
     let mut lowerer = Lowerer::new_with_params(name.to_string(), param_names);
+    lowerer.set_line_table(line_table, base_addr);
 
     for param_name in param_names {
         lowerer.get_or_create_local(param_name);
@@ -1089,7 +1360,7 @@ fn lower_extracted_function(
 
     if let Some(var_name) = self_ref_var {
         let slot = lowerer.get_or_create_local(var_name);
-        let funct_addr_val = lowerer.alloc_value(IrType::Any);
+        let funct_addr_val = lowerer.alloc_value(IrType::Function);
         let addr_num = base_addr as f64;
 
         lowerer.emit(IrOp::Const(funct_addr_val, Literal::Number(addr_num)));
@@ -1154,8 +1425,10 @@ fn rebase_jump_targets(instructions: &[OpCode], base_addr: usize) -> Vec<OpCode>
 /// Push(Function { address: X, env: ... })
 /// Let("name") or Store("name")
 /// Jump(Y)  <- Jumps over the function body
-/// [X] Let("param1")  <- Parameter binding
-/// [X+1] Let("param2")  <- Another parameter
+/// [X] LoadArg(0)  <- Parameter binding
+/// [X+1] Let("param1")
+/// [X+2] LoadArg(1)  <- Another parameter
+/// [X+3] Let("param2")
 /// ... function body ...
 /// [Y-1] Return
 /// [Y] ... main code continues ...
@@ -1164,10 +1437,10 @@ fn extract_functions(instructions: &[OpCode]) -> Vec<ExtractedFunction> {
     let mut functions = Vec::new();
 
     for (i, op) in instructions.iter().enumerate() {
-        if let OpCode::Push(JsValue::Function { address, env }) = op {
+        if let OpCode::Push(JsValue::Function { address, env, .. }) = op {
             // Find the end of this function (the Return before the next main code)
             if let Some(end_addr) = find_function_end(*address, instructions) {
-                // Detect parameters: consecutive Let instructions at function start
+                // Detect parameters: the LoadArg/Let pairs at function start
                 let (param_count, param_names) = detect_function_params(*address, instructions);
 
                 let func_var_name = if i + 1 < instructions.len() {
@@ -1223,17 +1496,17 @@ fn detect_self_reference(
     false
 }
 
-/// Detect function parameters from leading Let instructions.
-/// Returns (count, names).
+/// Detect function parameters from the leading `LoadArg(i), Let(name)`
+/// pairs. Returns (count, names).
 fn detect_function_params(start: usize, instructions: &[OpCode]) -> (usize, Vec<String>) {
     let mut params = Vec::new();
 
-    for i in start..instructions.len() {
-        match &instructions[i] {
-            OpCode::Let(name) => {
+    for pair in instructions.get(start..).unwrap_or_default().chunks(2) {
+        match pair {
+            [OpCode::LoadArg(index), OpCode::Let(name)] if *index == params.len() => {
                 params.push(name.clone());
             }
-            _ => break, // Stop at first non-Let instruction
+            _ => break, // Stop at the first non-parameter instruction
         }
     }
 
@@ -1250,7 +1523,7 @@ fn find_function_end(start: usize, instructions: &[OpCode]) -> Option<usize> {
 
     for i in start..instructions.len() {
         match &instructions[i] {
-            OpCode::Return => {
+            OpCode::Return | OpCode::ReturnMulti(_) => {
                 last_return = Some(i);
                 // Check if next instruction might be different function or main code
                 if i + 1 < instructions.len() {
@@ -1356,6 +1629,7 @@ mod tests {
             OpCode::Push(JsValue::Function {
                 address: 100,
                 env: None,
+                bound: None,
             }),
             OpCode::Push(JsValue::Number(1.0)),
             OpCode::Push(JsValue::Number(2.0)),
@@ -1375,6 +1649,83 @@ mod tests {
         assert!(has_call);
     }
 
+    /// The value `func` defines with the constant `literal`
+    fn const_value(func: &IrFunction, literal: Literal) -> ValueId {
+        func.blocks[0]
+            .ops
+            .iter()
+            .find_map(|op| match op {
+                IrOp::Const(dst, lit) if *lit == literal => Some(*dst),
+                _ => None,
+            })
+            .expect("constant")
+    }
+
+    #[test]
+    fn test_lower_computed_property_read() {
+        // "abc"[1]
+        let instructions = vec![
+            OpCode::Push(JsValue::String("abc".to_string())),
+            OpCode::Push(JsValue::Number(1.0)),
+            OpCode::GetPropComputed,
+            OpCode::Return,
+        ];
+
+        let func = lower_function("test", &instructions).unwrap();
+
+        let obj = const_value(&func, Literal::String("abc".to_string()));
+        let key = const_value(&func, Literal::Number(1.0));
+        let read = func.blocks[0]
+            .ops
+            .iter()
+            .find_map(|op| match op {
+                IrOp::GetElement(dst, o, k) if (*o, *k) == (obj, key) => Some(*dst),
+                _ => None,
+            })
+            .expect("the read becomes a GetElement of the object and key");
+        assert!(matches!(
+            func.blocks[0].terminator,
+            Terminator::Return(Some(ret)) if ret == read
+        ));
+    }
+
+    #[test]
+    fn test_lower_store_element_operand_order() {
+        // let a = [7]: the array literal stores each element as
+        // Dup, <value>, <index>, StoreElement
+        let instructions = vec![
+            OpCode::NewArray(1),
+            OpCode::Dup,
+            OpCode::Push(JsValue::Number(7.0)),
+            OpCode::Push(JsValue::Number(0.0)),
+            OpCode::StoreElement,
+            OpCode::Return,
+        ];
+
+        let func = lower_function("test", &instructions).unwrap();
+
+        let value = const_value(&func, Literal::Number(7.0));
+        let index = const_value(&func, Literal::Number(0.0));
+        let stores: Vec<_> = func.blocks[0]
+            .ops
+            .iter()
+            .filter_map(|op| match op {
+                IrOp::SetElement(arr, idx, val) => Some((*arr, *idx, *val)),
+                _ => None,
+            })
+            .collect();
+        let [(arr, idx, val)] = stores[..] else {
+            panic!("expected one store, got {:?}", stores);
+        };
+        assert_eq!((idx, val), (index, value));
+        assert!(
+            func.blocks[0]
+                .ops
+                .iter()
+                .any(|op| matches!(op, IrOp::NewArray(dst) if *dst == arr))
+        );
+    }
+
     #[test]
     fn test_lower_loop() {
         // while (x < 10) { x = x + 1 }
@@ -1413,4 +1764,151 @@ mod tests {
         // Should have multiple blocks due to the loop
         assert!(func.blocks.len() >= 3);
     }
+
+    #[test]
+    fn test_values_carry_source_lines() {
+        let mut compiler = crate::compiler::Compiler::new();
+        let source = "let a = 1;\nfunction twice(x) {\n    return x * 2;\n}\nlet b = twice(a);\n";
+        let bytecode = compiler.compile(source).unwrap();
+        let module = lower_module_with_lines(&bytecode, &compiler.line_table).unwrap();
+
+        let line_of = |func: &IrFunction, matches: fn(&IrOp) -> bool| {
+            let op = func
+                .blocks
+                .iter()
+                .flat_map(|b| &b.ops)
+                .find(|op| matches(op));
+            op.and_then(|op| func.source_lines.get(&op.dest()?).copied())
+        };
+        let twice = module.functions.iter().find(|f| f.name != "main").unwrap();
+        assert_eq!(line_of(twice, |op| matches!(op, IrOp::MulAny(..))), Some(3));
+        let main = module.functions.iter().find(|f| f.name == "main").unwrap();
+        assert_eq!(line_of(main, |op| matches!(op, IrOp::Call(..))), Some(5));
+
+        // Without a line table no lines are recorded
+        let module = lower_module(&bytecode).unwrap();
+        assert!(module.functions.iter().all(|f| f.source_lines.is_empty()));
+    }
+
+    #[test]
+    fn test_intrinsic_calls_lower_inline() {
+        let ops = |source: &str| -> Vec<IrOp> {
+            let bytecode = crate::compiler::Compiler::new().compile(source).unwrap();
+            let module = lower_module(&bytecode).unwrap();
+            let func = module.functions.iter().find(|f| f.name != "main").unwrap();
+            func.blocks.iter().flat_map(|b| b.ops.clone()).collect()
+        };
+
+        let kernel = ops(
+            "function k(x, y) { return Math.imul(x, y) + std.intrinsics.popcnt32(x) + Math.clz32(); }",
+        );
+        let intrinsics: Vec<_> = kernel
+            .iter()
+            .filter_map(|op| match op {
+                IrOp::Intrinsic(_, intrinsic, args) => Some((*intrinsic, args.len())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(intrinsics, [(Intrinsic::Imul, 2), (Intrinsic::Popcnt32, 1)]);
+        // A call with the wrong arity stays a method call
+        assert!(
+            kernel
+                .iter()
+                .any(|op| matches!(op, IrOp::CallMethod(_, _, name, _) if name == "clz32"))
+        );
+
+        // A binding named `Math` is not the intrinsic namespace
+        let shadowed =
+            ops("function k(x) { let Math = { clz32: (v) => v }; return Math.clz32(x); }");
+        assert!(!shadowed.iter().any(|op| matches!(op, IrOp::Intrinsic(..))));
+    }
+
+    #[test]
+    fn test_simd_calls_lower_to_vector_ops() {
+        let kernel = |source: &str| -> IrFunction {
+            let bytecode = crate::compiler::Compiler::new().compile(source).unwrap();
+            let module = lower_module(&bytecode).unwrap();
+            module
+                .functions
+                .into_iter()
+                .find(|f| f.name != "main")
+                .unwrap()
+        };
+        let simd_ops = |func: &IrFunction| -> Vec<SimdOp> {
+            func.blocks
+                .iter()
+                .flat_map(|b| &b.ops)
+                .filter_map(|op| match op {
+                    IrOp::Simd(_, SimdKind::F64x2, op, _) => Some(op.clone()),
+                    _ => None,
+                })
+                .collect()
+        };
+
+        let mut func = kernel(
+            "function k(a, b) {
+                 return std.simd.f64x2.reduceAdd(
+                     std.simd.f64x2.mul(std.simd.f64x2.shuffle(a, b, 1, 2), std.simd.f64x2(3, 4)));
+             }",
+        );
+        assert_eq!(
+            simd_ops(&func),
+            [
+                SimdOp::Unbox,
+                SimdOp::Unbox,
+                SimdOp::Shuffle(vec![1, 2]),
+                SimdOp::Box,
+                SimdOp::Make,
+                SimdOp::Box,
+                SimdOp::Unbox,
+                SimdOp::Unbox,
+                SimdOp::Mul,
+                SimdOp::Box,
+                SimdOp::Unbox,
+                SimdOp::ReduceAdd,
+            ]
+        );
+        // Vectors passed straight on stay unboxed
+        crate::ir::opt::optimize_function(&mut func);
+        assert_eq!(
+            simd_ops(&func),
+            [
+                SimdOp::Unbox,
+                SimdOp::Unbox,
+                SimdOp::Shuffle(vec![1, 2]),
+                SimdOp::Make,
+                SimdOp::Mul,
+                SimdOp::ReduceAdd,
+            ]
+        );
+
+        // `s.f64x2(3, 4)` is an ordinary call, which could write the
+        // shuffle's box before `mul` reads it
+        let mut func = kernel(
+            "function k(a, b) {
+                 let s = std.simd;
+                 return std.simd.f64x2.mul(std.simd.f64x2.shuffle(a, b, 1, 2), s.f64x2(3, 4));
+             }",
+        );
+        crate::ir::opt::optimize_function(&mut func);
+        assert_eq!(
+            simd_ops(&func),
+            [
+                SimdOp::Unbox,
+                SimdOp::Unbox,
+                SimdOp::Shuffle(vec![1, 2]),
+                SimdOp::Box,
+                SimdOp::Unbox,
+                SimdOp::Unbox,
+                SimdOp::Mul,
+                SimdOp::Box,
+            ]
+        );
+
+        // Lanes out of range or not constant keep the method call
+        let func = kernel(
+            "function k(a, b, i) { return std.simd.f64x2.shuffle(a, b, 0, 4)[0] + std.simd.f64x2.shuffle(a, b, 0, i)[0]; }",
+        );
+        assert!(simd_ops(&func).is_empty());
+    }
 }
@@ -13,15 +13,24 @@
 //! - Native backends (Cranelift, LLVM)
 
 pub mod format;
+pub mod intrinsics;
+pub mod ipo;
+pub mod loops;
 pub mod lower;
 pub mod opt;
+pub mod parse;
+pub mod simd;
 pub mod stubs;
 pub mod typecheck;
 pub mod verify;
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
 
+use crate::runtime::heap::ElementKind;
+use intrinsics::Intrinsic;
+use simd::{SimdKind, SimdOp};
+
 // ============================================================================
 // Type System
 // ============================================================================
@@ -69,8 +78,10 @@ pub enum IrType {
     Object,
     /// JavaScript-like array (heap-allocated)
     Array,
-    /// Typed array with element type
-    TypedArray(Box<IrType>),
+    /// Typed array (`Float64Array`, ...) with unboxed elements
+    TypedArray(ElementKind),
+    /// Unboxed SIMD vector, held in a register
+    Simd(SimdKind),
     /// Function closure
     Function,
     /// Named struct type (with known layout)
@@ -142,7 +153,7 @@ impl IrType {
     /// Get the element type for array types.
     pub fn element_type(&self) -> Option<&IrType> {
         match self {
-            IrType::TypedArray(inner) => Some(inner),
+            IrType::TypedArray(_) => Some(&IrType::Number),
             IrType::Array => Some(&IrType::Any),
             _ => None,
         }
@@ -167,7 +178,8 @@ impl fmt::Display for IrType {
             IrType::Boolean => write!(f, "bool"),
             IrType::Object => write!(f, "obj"),
             IrType::Array => write!(f, "arr"),
-            IrType::TypedArray(elem) => write!(f, "{}[]", elem),
+            IrType::TypedArray(kind) => write!(f, "{}[]", kind.short_name()),
+            IrType::Simd(kind) => write!(f, "{}", kind),
             IrType::Function => write!(f, "fn"),
             IrType::Struct(id) => write!(f, "{}", id),
             IrType::Ref(inner) => write!(f, "&{}", inner),
@@ -363,7 +375,7 @@ impl fmt::Display for Literal {
 // ============================================================================
 
 /// Unique identifier for a basic block.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct BlockId(pub u32);
 
 impl fmt::Display for BlockId {
@@ -426,6 +438,10 @@ pub enum IrOp {
     ShrU(ValueId, ValueId, ValueId),
     /// Power: dst = a ** b
     Pow(ValueId, ValueId, ValueId),
+    /// Numeric intrinsic on number operands: dst = Math.clz32(a), ...
+    Intrinsic(ValueId, Intrinsic, Vec<ValueId>),
+    /// SIMD vector operation: dst = std.simd.f64x2.add(a, b), ...
+    Simd(ValueId, SimdKind, SimdOp, Vec<ValueId>),
 
     // === Comparison ===
     /// Strict equality: dst = a === b
@@ -481,6 +497,17 @@ pub enum IrOp {
     /// Push to array: arr.push(val)
     ArrayPush(ValueId, ValueId),
 
+    // === Typed Array Operations ===
+    /// Typed array length: dst = arr.length
+    TypedArrayLen(ValueId, ValueId),
+    /// Typed array load: dst = arr[idx]. When `checked` is false the index
+    /// is known to be an in-bounds integer and the backend reads memory
+    /// directly.
+    TypedLoad(ValueId, ValueId, ValueId, ElementKind, bool),
+    /// Typed array store: arr[idx] = val. Unchecked stores also know `val`
+    /// is a number, so it is converted to the element type inline.
+    TypedStore(ValueId, ValueId, ValueId, ElementKind, bool),
+
     // === Function Operations ===
     /// Call function: dst = func(args...)
     Call(ValueId, ValueId, Vec<ValueId>),
@@ -581,6 +608,8 @@ impl IrOp {
             | IrOp::Shr(d, _, _)
             | IrOp::ShrU(d, _, _)
             | IrOp::Pow(d, _, _)
+            | IrOp::Intrinsic(d, _, _)
+            | IrOp::Simd(d, _, _, _)
             | IrOp::LoadLocal(d, _)
             | IrOp::LoadGlobal(d, _)
             | IrOp::NewObject(d)
@@ -588,6 +617,8 @@ impl IrOp {
             | IrOp::GetElement(d, _, _)
             | IrOp::NewArray(d)
             | IrOp::ArrayLen(d, _)
+            | IrOp::TypedArrayLen(d, _)
+            | IrOp::TypedLoad(d, _, _, _, _)
             | IrOp::Call(d, _, _)
             | IrOp::CallMethod(d, _, _, _)
             | IrOp::MakeClosure(d, _, _)
@@ -620,6 +651,92 @@ impl IrOp {
             | IrOp::SetProp(_, _, _)
             | IrOp::SetElement(_, _, _)
             | IrOp::ArrayPush(_, _)
+            | IrOp::TypedStore(_, _, _, _, _)
+            // Borrow operations without dest
+            | IrOp::DerefStore(_, _)
+            | IrOp::EndBorrow(_)
+            // Struct operations without dest
+            | IrOp::StructSetField(_, _, _)
+            | IrOp::StructSetFieldNamed(_, _, _) => None,
+        }
+    }
+
+    /// The destination value (if any) of this operation, for renaming it.
+    pub fn dest_mut(&mut self) -> Option<&mut ValueId> {
+        match self {
+            IrOp::Const(d, _)
+            | IrOp::AddNum(d, _, _)
+            | IrOp::SubNum(d, _, _)
+            | IrOp::MulNum(d, _, _)
+            | IrOp::DivNum(d, _, _)
+            | IrOp::ModNum(d, _, _)
+            | IrOp::NegNum(d, _)
+            | IrOp::AddAny(d, _, _)
+            | IrOp::SubAny(d, _, _)
+            | IrOp::MulAny(d, _, _)
+            | IrOp::DivAny(d, _, _)
+            | IrOp::ModAny(d, _, _)
+            | IrOp::NegAny(d, _)
+            | IrOp::EqStrict(d, _, _)
+            | IrOp::NeStrict(d, _, _)
+            | IrOp::Lt(d, _, _)
+            | IrOp::LtEq(d, _, _)
+            | IrOp::Gt(d, _, _)
+            | IrOp::GtEq(d, _, _)
+            | IrOp::Not(d, _)
+            | IrOp::And(d, _, _)
+            | IrOp::Or(d, _, _)
+            | IrOp::BitAnd(d, _, _)
+            | IrOp::BitOr(d, _, _)
+            | IrOp::Xor(d, _, _)
+            | IrOp::Shl(d, _, _)
+            | IrOp::Shr(d, _, _)
+            | IrOp::ShrU(d, _, _)
+            | IrOp::Pow(d, _, _)
+            | IrOp::Intrinsic(d, _, _)
+            | IrOp::Simd(d, _, _, _)
+            | IrOp::LoadLocal(d, _)
+            | IrOp::LoadGlobal(d, _)
+            | IrOp::NewObject(d)
+            | IrOp::GetProp(d, _, _)
+            | IrOp::GetElement(d, _, _)
+            | IrOp::NewArray(d)
+            | IrOp::ArrayLen(d, _)
+            | IrOp::TypedArrayLen(d, _)
+            | IrOp::TypedLoad(d, _, _, _, _)
+            | IrOp::Call(d, _, _)
+            | IrOp::CallMethod(d, _, _, _)
+            | IrOp::MakeClosure(d, _, _)
+            | IrOp::TypeCheck(d, _, _)
+            | IrOp::TypeGuard(d, _, _)
+            | IrOp::ToBool(d, _)
+            | IrOp::ToNum(d, _)
+            | IrOp::Phi(d, _)
+            | IrOp::Copy(d, _)
+            | IrOp::LoadThis(d)
+            // Borrow operations
+            | IrOp::Borrow(d, _)
+            | IrOp::BorrowMut(d, _)
+            | IrOp::Deref(d, _)
+            // Struct operations
+            | IrOp::StructNew(d, _)
+            | IrOp::StructGetField(d, _, _)
+            | IrOp::StructGetFieldNamed(d, _, _)
+            // Monomorphized calls
+            | IrOp::CallMono(d, _, _)
+            // Move operations
+            | IrOp::Move(d, _)
+            | IrOp::Clone(d, _)
+            // Type operations
+            | IrOp::TypeOf(d, _)
+            | IrOp::DeleteProp(d, _, _) => Some(d),
+
+            IrOp::StoreLocal(_, _)
+            | IrOp::StoreGlobal(_, _)
+            | IrOp::SetProp(_, _, _)
+            | IrOp::SetElement(_, _, _)
+            | IrOp::ArrayPush(_, _)
+            | IrOp::TypedStore(_, _, _, _, _)
             // Borrow operations without dest
             | IrOp::DerefStore(_, _)
             | IrOp::EndBorrow(_)
@@ -668,6 +785,7 @@ impl IrOp {
             | IrOp::ToNum(_, a)
             | IrOp::Copy(_, a)
             | IrOp::ArrayLen(_, a)
+            | IrOp::TypedArrayLen(_, a)
             | IrOp::TypeCheck(_, a, _)
             | IrOp::TypeGuard(_, a, _)
             // Borrow operations
@@ -693,6 +811,8 @@ impl IrOp {
             IrOp::GetElement(_, obj, key) => vec![*obj, *key],
             IrOp::SetElement(obj, key, val) => vec![*obj, *key, *val],
             IrOp::ArrayPush(arr, val) => vec![*arr, *val],
+            IrOp::TypedLoad(_, arr, idx, _, _) => vec![*arr, *idx],
+            IrOp::TypedStore(arr, idx, val, _, _) => vec![*arr, *idx, *val],
 
             // Struct field operations
             IrOp::StructGetField(_, src, _) => vec![*src],
@@ -710,7 +830,9 @@ impl IrOp {
                 uses.extend(args.iter().copied());
                 uses
             }
-            IrOp::CallMono(_, _, args) => args.clone(),
+            IrOp::CallMono(_, _, args)
+            | IrOp::Intrinsic(_, _, args)
+            | IrOp::Simd(_, _, _, args) => args.clone(),
             IrOp::MakeClosure(_, _, env) => vec![*env],
 
             IrOp::Phi(_, entries) => entries.iter().map(|(_, v)| *v).collect(),
@@ -819,6 +941,8 @@ pub struct IrFunction {
     pub value_types: HashMap<ValueId, IrType>,
     /// Value ownership and storage info (for borrow checking).
     pub value_info: HashMap<ValueId, ValueInfo>,
+    /// Source line of the statement that produced each value (for debug info).
+    pub source_lines: HashMap<ValueId, u32>,
 }
 
 impl IrFunction {
@@ -834,6 +958,7 @@ impl IrFunction {
             next_lifetime: 0,
             value_types: HashMap::new(),
             value_info: HashMap::new(),
+            source_lines: HashMap::new(),
         }
     }
 
@@ -940,6 +1065,116 @@ impl IrFunction {
             self.blocks[succ.0 as usize].predecessors.push(pred);
         }
     }
+
+    /// Give each block the id `new_id` maps it to and lay the blocks out in
+    /// that order, rewriting branch targets and phi edges to match.
+    /// `new_id` must be a permutation that keeps the entry block first.
+    pub fn renumber_blocks(&mut self, new_id: impl Fn(BlockId) -> BlockId) {
+        for block in &mut self.blocks {
+            block.id = new_id(block.id);
+            match &mut block.terminator {
+                Terminator::Jump(target) => *target = new_id(*target),
+                Terminator::Branch(_, t, f) => {
+                    *t = new_id(*t);
+                    *f = new_id(*f);
+                }
+                Terminator::Return(_) | Terminator::Unreachable => {}
+            }
+            for op in &mut block.ops {
+                if let IrOp::Phi(_, entries) = op {
+                    for (pred, _) in entries {
+                        *pred = new_id(*pred);
+                    }
+                }
+            }
+        }
+        self.blocks.sort_by_key(|block| block.id.0);
+        self.compute_predecessors();
+    }
+
+    /// Local slots written exactly once, in the entry block, before any read
+    /// of them, mapped to the stored value.
+    ///
+    /// Every load of such a slot sees that value, which is how parameters
+    /// (bound by the function prologue) keep their type and identity through
+    /// `load.local`.
+    pub fn entry_bound_locals(&self) -> HashMap<u32, ValueId> {
+        let entry = self.entry_block();
+        let entry_is_loop_header = self
+            .blocks
+            .iter()
+            .any(|b| b.terminator.successors().contains(&entry));
+        if self.blocks.is_empty() || entry_is_loop_header {
+            return HashMap::new();
+        }
+
+        let mut store_counts: HashMap<u32, usize> = HashMap::new();
+        for block in &self.blocks {
+            for op in &block.ops {
+                if let IrOp::StoreLocal(slot, _) = op {
+                    *store_counts.entry(*slot).or_default() += 1;
+                }
+            }
+        }
+
+        let mut bound = HashMap::new();
+        let mut read = HashSet::new();
+        for op in &self.block(entry).ops {
+            match op {
+                IrOp::LoadLocal(_, slot) => {
+                    read.insert(*slot);
+                }
+                IrOp::StoreLocal(slot, val)
+                    if store_counts.get(slot) == Some(&1) && !read.contains(slot) =>
+                {
+                    bound.insert(*slot, *val);
+                }
+                _ => {}
+            }
+        }
+        bound
+    }
+
+    /// Blocks that are the target of a back edge, i.e. the headers of loops.
+    ///
+    /// Found with a depth-first walk from the entry block: an edge to a block
+    /// that is still on the walk's path closes a cycle.
+    pub fn loop_headers(&self) -> HashSet<BlockId> {
+        let mut headers = HashSet::new();
+        if self.blocks.is_empty() {
+            return headers;
+        }
+
+        let mut visited = HashSet::new();
+        let mut on_path = HashSet::new();
+        // (block, successors still to visit)
+        let mut stack = vec![(self.entry_block(), self.successors_of(self.entry_block()))];
+        visited.insert(self.entry_block());
+        on_path.insert(self.entry_block());
+        while let Some((block, pending)) = stack.last_mut() {
+            let block = *block;
+            match pending.pop() {
+                Some(succ) if on_path.contains(&succ) => {
+                    headers.insert(succ);
+                }
+                Some(succ) => {
+                    if visited.insert(succ) {
+                        on_path.insert(succ);
+                        stack.push((succ, self.successors_of(succ)));
+                    }
+                }
+                None => {
+                    on_path.remove(&block);
+                    stack.pop();
+                }
+            }
+        }
+        headers
+    }
+
+    fn successors_of(&self, id: BlockId) -> Vec<BlockId> {
+        self.block(id).terminator.successors()
+    }
 }
 
 // ============================================================================
@@ -1021,6 +1256,7 @@ impl IrStructDef {
             | IrType::Struct(_)
             | IrType::Ref(_)
             | IrType::MutRef(_) => 8,
+            IrType::Simd(_) => 16,
             IrType::Any => 16, // Tagged value: tag + payload
         }
     }
@@ -1072,8 +1308,12 @@ pub struct IrModule {
     next_mono_id: u32,
     /// Mapping from bytecode address to function index (for extracted functions).
     pub function_addrs: HashMap<usize, usize>,
+    /// Function index of each name bound to exactly one function.
+    pub function_names: HashMap<String, usize>,
     /// Bytecode address of user-defined main() function, if any.
     pub user_main_addr: Option<usize>,
+    /// Path of the source file the module was lowered from, if any.
+    pub source_file: Option<String>,
 }
 
 impl IrModule {
@@ -1086,7 +1326,9 @@ impl IrModule {
             mono_cache: HashMap::new(),
             next_mono_id: 0,
             function_addrs: HashMap::new(),
+            function_names: HashMap::new(),
             user_main_addr: None,
+            source_file: None,
         }
     }
 
@@ -1175,13 +1417,27 @@ impl fmt::Display for IrOp {
             IrOp::Not(d, a) => write!(f, "{} = not {}", d, a),
             IrOp::And(d, a, b) => write!(f, "{} = and {}, {}", d, a, b),
             IrOp::Or(d, a, b) => write!(f, "{} = or {}, {}", d, a, b),
-            IrOp::BitAnd(d, a, b) => write!(f, "{} = and {}, {}", d, a, b),
-            IrOp::BitOr(d, a, b) => write!(f, "{} = or {}, {}", d, a, b),
+            IrOp::BitAnd(d, a, b) => write!(f, "{} = bit.and {}, {}", d, a, b),
+            IrOp::BitOr(d, a, b) => write!(f, "{} = bit.or {}, {}", d, a, b),
             IrOp::Xor(d, a, b) => write!(f, "{} = xor {}, {}", d, a, b),
             IrOp::Shl(d, a, b) => write!(f, "{} = shl {}, {}", d, a, b),
             IrOp::Shr(d, a, b) => write!(f, "{} = shr {}, {}", d, a, b),
             IrOp::ShrU(d, a, b) => write!(f, "{} = shr.u {}, {}", d, a, b),
             IrOp::Pow(d, a, b) => write!(f, "{} = pow {}, {}", d, a, b),
+            IrOp::Intrinsic(d, intrinsic, args) => {
+                let args_str: Vec<_> = args.iter().map(|a| format!("{}", a)).collect();
+                write!(
+                    f,
+                    "{} = intrinsic {}({})",
+                    d,
+                    intrinsic.name(),
+                    args_str.join(", ")
+                )
+            }
+            IrOp::Simd(d, kind, op, args) => {
+                let args_str: Vec<_> = args.iter().map(|a| format!("{}", a)).collect();
+                write!(f, "{} = simd {}.{}({})", d, kind, op, args_str.join(", "))
+            }
             IrOp::LoadLocal(d, slot) => write!(f, "{} = load.local ${}", d, slot),
             IrOp::StoreLocal(slot, v) => write!(f, "store.local ${}, {}", slot, v),
             IrOp::LoadGlobal(d, name) => write!(f, "{} = load.global @{}", d, name),
@@ -1194,6 +1450,25 @@ impl fmt::Display for IrOp {
             IrOp::NewArray(d) => write!(f, "{} = new.array", d),
             IrOp::ArrayLen(d, arr) => write!(f, "{} = array.len {}", d, arr),
             IrOp::ArrayPush(arr, val) => write!(f, "array.push {}, {}", arr, val),
+            IrOp::TypedArrayLen(d, arr) => write!(f, "{} = typed.len {}", d, arr),
+            IrOp::TypedLoad(d, arr, idx, kind, checked) => write!(
+                f,
+                "{} = typed.load.{}{} {}, [{}]",
+                d,
+                kind.short_name(),
+                if *checked { "" } else { ".unchecked" },
+                arr,
+                idx
+            ),
+            IrOp::TypedStore(arr, idx, val, kind, checked) => write!(
+                f,
+                "typed.store.{}{} {}, [{}], {}",
+                kind.short_name(),
+                if *checked { "" } else { ".unchecked" },
+                arr,
+                idx,
+                val
+            ),
             IrOp::Call(d, func, args) => {
                 let args_str: Vec<_> = args.iter().map(|a| format!("{}", a)).collect();
                 write!(f, "{} = call {}({})", d, func, args_str.join(", "))
@@ -1397,5 +1672,28 @@ mod tests {
 
         assert_eq!(func.block(then_block).predecessors.len(), 1);
         assert_eq!(func.block(else_block).predecessors.len(), 1);
+        assert!(func.loop_headers().is_empty());
+    }
+
+    #[test]
+    fn test_loop_headers() {
+        // bb0 -> bb1 <-> bb2, bb1 -> bb3
+        let mut func = IrFunction::new("count".to_string());
+        let entry = func.alloc_block();
+        let header = func.alloc_block();
+        let body = func.alloc_block();
+        let exit = func.alloc_block();
+        let cond = func.alloc_value(IrType::Boolean);
+
+        func.block_mut(entry).terminate(Terminator::Jump(header));
+        {
+            let block = func.block_mut(header);
+            block.push(IrOp::Const(cond, Literal::Boolean(true)));
+            block.terminate(Terminator::Branch(cond, body, exit));
+        }
+        func.block_mut(body).terminate(Terminator::Jump(header));
+        func.block_mut(exit).terminate(Terminator::Return(None));
+
+        assert_eq!(func.loop_headers(), HashSet::from([header]));
     }
 }
@@ -5,8 +5,21 @@
 //! - Constant Folding
 //! - Common Subexpression Elimination (CSE)
 //! - Copy Propagation
-
-use crate::ir::{IrFunction, IrModule, IrOp, Literal, Terminator, ValueId};
+//! - Scalar Replacement of non-escaping arrays (multi-value returns) and
+//!   objects (tagged union values)
+//! - Bounds Check Elimination for loop-guarded typed array accesses
+//! - SIMD unboxing: vectors passed straight between vector operations
+//!   stay in registers
+//!
+//! `optimize_module_at` adds the interprocedural passes of `ipo` and the
+//! loop optimizations of `loops` from `-O2` up.
+
+use crate::ir::intrinsics::Intrinsic;
+use crate::ir::ipo::{self, InlineBudget};
+use crate::ir::loops;
+use crate::ir::simd::{SimdKind, SimdOp};
+use crate::ir::verify;
+use crate::ir::{BlockId, IrFunction, IrModule, IrOp, IrType, Literal, Terminator, ValueId};
 use std::collections::{HashMap, HashSet};
 
 // ============================================================================
@@ -66,6 +79,7 @@ fn has_side_effects(op: &IrOp) -> bool {
             | IrOp::SetProp(_, _, _)
             | IrOp::SetElement(_, _, _)
             | IrOp::ArrayPush(_, _)
+            | IrOp::TypedStore(_, _, _, _, _)
             | IrOp::Call(_, _, _)
             | IrOp::CallMethod(_, _, _, _)
     )
@@ -79,8 +93,9 @@ fn has_side_effects(op: &IrOp) -> bool {
 pub fn constant_folding(func: &mut IrFunction) {
     // Track known constant values
     let mut constants: HashMap<ValueId, Literal> = HashMap::new();
-    // Track constants stored in local slots
-    let mut local_constants: HashMap<u32, Literal> = HashMap::new();
+    // Track constants stored in local slots: `None` once a slot has been
+    // stored anything else, since blocks are not visited in execution order
+    let mut slot_stores: HashMap<u32, Option<Literal>> = HashMap::new();
 
     // First pass: collect constants and track local stores
     for block in &func.blocks {
@@ -90,13 +105,16 @@ pub fn constant_folding(func: &mut IrFunction) {
                     constants.insert(*dst, lit.clone());
                 }
                 IrOp::StoreLocal(slot, src) => {
-                    // If source is a constant, track it in the local slot
-                    if let Some(lit) = constants.get(src) {
-                        local_constants.insert(*slot, lit.clone());
-                    } else {
-                        // If we store a non-constant, remove the slot from local_constants
-                        local_constants.remove(slot);
-                    }
+                    // A slot is constant only if every store agrees on it
+                    let stored = constants.get(src).cloned();
+                    slot_stores
+                        .entry(*slot)
+                        .and_modify(|held| {
+                            if *held != stored {
+                                *held = None;
+                            }
+                        })
+                        .or_insert(stored);
                 }
                 // Bitwise operations
                 IrOp::BitAnd(_, _, _)
@@ -113,6 +131,11 @@ pub fn constant_folding(func: &mut IrFunction) {
         }
     }
 
+    let local_constants: HashMap<u32, Literal> = slot_stores
+        .into_iter()
+        .filter_map(|(slot, lit)| Some((slot, lit?)))
+        .collect();
+
     // Second pass: fold operations and propagate local constants
     for block in &mut func.blocks {
         let ops = std::mem::take(&mut block.ops);
@@ -260,6 +283,32 @@ fn fold_op(
             IrOp::NeStrict(dst, a, b)
         }
 
+        IrOp::ToNum(dst, a) => {
+            if let Some(Literal::Number(va)) = constants.get(&a) {
+                let result = *va;
+                constants.insert(dst, Literal::Number(result));
+                return IrOp::Const(dst, Literal::Number(result));
+            }
+            IrOp::ToNum(dst, a)
+        }
+
+        // Intrinsics on constant numbers
+        IrOp::Intrinsic(dst, intrinsic, args) => {
+            let values: Option<Vec<f64>> = args
+                .iter()
+                .map(|arg| match constants.get(arg) {
+                    Some(Literal::Number(n)) => Some(*n),
+                    _ => None,
+                })
+                .collect();
+            if let Some(values) = values {
+                let result = intrinsic.apply(&values);
+                constants.insert(dst, Literal::Number(result));
+                return IrOp::Const(dst, Literal::Number(result));
+            }
+            IrOp::Intrinsic(dst, intrinsic, args)
+        }
+
         // Logical NOT
         IrOp::Not(dst, a) => {
             if let Some(Literal::Boolean(va)) = constants.get(&a) {
@@ -312,6 +361,7 @@ enum ExprKey {
     LoadLocal(u32),
     LoadGlobal(String),
     GetProp(ValueId, String),
+    Intrinsic(Intrinsic, Vec<ValueId>),
 }
 
 /// Eliminate redundant computations.
@@ -345,6 +395,7 @@ pub fn common_subexpression_elimination(func: &mut IrFunction) {
                         | IrOp::StoreGlobal(_, _)
                         | IrOp::SetProp(_, _, _)
                         | IrOp::SetElement(_, _, _)
+                        | IrOp::TypedStore(_, _, _, _, _)
                 ) {
                     available.clear();
                 }
@@ -376,6 +427,9 @@ fn extract_expr_key(op: &IrOp) -> Option<(ExprKey, ValueId)> {
         IrOp::LoadLocal(d, slot) => Some((ExprKey::LoadLocal(*slot), *d)),
         IrOp::LoadGlobal(d, name) => Some((ExprKey::LoadGlobal(name.clone()), *d)),
         IrOp::GetProp(d, obj, name) => Some((ExprKey::GetProp(*obj, name.clone()), *d)),
+        IrOp::Intrinsic(d, intrinsic, args) => {
+            Some((ExprKey::Intrinsic(*intrinsic, args.clone()), *d))
+        }
         // Bitwise operations - not supported for CSE yet
         IrOp::BitAnd(_, _, _)
         | IrOp::BitOr(_, _, _)
@@ -427,7 +481,7 @@ pub fn copy_propagation(func: &mut IrFunction) {
 }
 
 /// Replace uses in an operation.
-fn replace_uses_in_op(op: &mut IrOp, copies: &HashMap<ValueId, ValueId>) {
+pub(super) fn replace_uses_in_op(op: &mut IrOp, copies: &HashMap<ValueId, ValueId>) {
     let resolve = |v: &mut ValueId| {
         if let Some(&src) = copies.get(v) {
             *v = src;
@@ -471,6 +525,7 @@ fn replace_uses_in_op(op: &mut IrOp, copies: &HashMap<ValueId, ValueId>) {
         | IrOp::ToNum(_, a)
         | IrOp::Copy(_, a)
         | IrOp::ArrayLen(_, a)
+        | IrOp::TypedArrayLen(_, a)
         | IrOp::TypeCheck(_, a, _)
         | IrOp::TypeGuard(_, a, _)
         | IrOp::Borrow(_, a)
@@ -515,6 +570,17 @@ fn replace_uses_in_op(op: &mut IrOp, copies: &HashMap<ValueId, ValueId>) {
             resolve(val);
         }
 
+        IrOp::TypedLoad(_, arr, idx, _, _) => {
+            resolve(arr);
+            resolve(idx);
+        }
+
+        IrOp::TypedStore(arr, idx, val, _, _) => {
+            resolve(arr);
+            resolve(idx);
+            resolve(val);
+        }
+
         IrOp::Call(_, func_val, args) => {
             resolve(func_val);
             for arg in args {
@@ -546,7 +612,7 @@ fn replace_uses_in_op(op: &mut IrOp, copies: &HashMap<ValueId, ValueId>) {
             resolve(b);
         }
 
-        IrOp::CallMono(_, _, args) => {
+        IrOp::CallMono(_, _, args) | IrOp::Intrinsic(_, _, args) | IrOp::Simd(_, _, _, args) => {
             for arg in args {
                 resolve(arg);
             }
@@ -564,7 +630,10 @@ fn replace_uses_in_op(op: &mut IrOp, copies: &HashMap<ValueId, ValueId>) {
 }
 
 /// Replace uses in a terminator.
-fn replace_uses_in_terminator(term: &mut Terminator, copies: &HashMap<ValueId, ValueId>) {
+pub(super) fn replace_uses_in_terminator(
+    term: &mut Terminator,
+    copies: &HashMap<ValueId, ValueId>,
+) {
     let resolve = |v: &mut ValueId| {
         if let Some(&src) = copies.get(v) {
             *v = src;
@@ -644,22 +713,470 @@ pub fn simplify_branches(func: &mut IrFunction) {
     }
 }
 
+// ============================================================================
+// Scalar Replacement of Arrays
+// ============================================================================
+
+/// Replace short-lived arrays with the values stored into them.
+///
+/// `return [a, b]` followed by `let [x, y] = ...` lowers to a NewArray,
+/// constant-index SetElements and constant-index GetElements. When the array
+/// never escapes its block (only constant-index element accesses use it),
+/// each load becomes a copy of the stored value and the array is dropped.
+pub fn scalar_replace_arrays(func: &mut IrFunction) {
+    let mut constant_indices: HashMap<ValueId, usize> = HashMap::new();
+    for block in &func.blocks {
+        for op in &block.ops {
+            if let IrOp::Const(dst, Literal::Number(n)) = op
+                && *n >= 0.0
+                && n.fract() == 0.0
+            {
+                constant_indices.insert(*dst, *n as usize);
+            }
+        }
+    }
+
+    // Array -> defining block, for arrays whose every use is a
+    // constant-index element access in that same block
+    let mut candidates: HashMap<ValueId, usize> = HashMap::new();
+    for (block_idx, block) in func.blocks.iter().enumerate() {
+        for op in &block.ops {
+            if let IrOp::NewArray(dst) = op {
+                candidates.insert(*dst, block_idx);
+            }
+        }
+    }
+    for (block_idx, block) in func.blocks.iter().enumerate() {
+        for op in &block.ops {
+            let accessed = match op {
+                IrOp::SetElement(arr, idx, val) if constant_indices.contains_key(idx) => {
+                    candidates.remove(val);
+                    Some(*arr)
+                }
+                IrOp::GetElement(_, arr, idx) if constant_indices.contains_key(idx) => Some(*arr),
+                IrOp::NewArray(_) => None,
+                other => {
+                    for val in other.uses() {
+                        candidates.remove(&val);
+                    }
+                    None
+                }
+            };
+            if let Some(arr) = accessed
+                && candidates.get(&arr).is_some_and(|def| *def != block_idx)
+            {
+                candidates.remove(&arr);
+            }
+        }
+        for val in block.terminator.uses() {
+            candidates.remove(&val);
+        }
+    }
+
+    if candidates.is_empty() {
+        return;
+    }
+
+    for block in &mut func.blocks {
+        let mut elements: HashMap<(ValueId, usize), ValueId> = HashMap::new();
+        let ops = std::mem::take(&mut block.ops);
+        for op in ops {
+            match op {
+                IrOp::NewArray(dst) if candidates.contains_key(&dst) => {}
+                IrOp::SetElement(arr, idx, val) if candidates.contains_key(&arr) => {
+                    elements.insert((arr, constant_indices[&idx]), val);
+                }
+                IrOp::GetElement(dst, arr, idx) if candidates.contains_key(&arr) => {
+                    match elements.get(&(arr, constant_indices[&idx])) {
+                        Some(val) => block.ops.push(IrOp::Copy(dst, *val)),
+                        None => block.ops.push(IrOp::Const(dst, Literal::Undefined)),
+                    }
+                }
+                other => block.ops.push(other),
+            }
+        }
+    }
+}
+
+// ============================================================================
+// Scalar Replacement of Objects
+// ============================================================================
+
+/// Replace short-lived objects with the values stored into their properties.
+///
+/// A tagged union value such as `Circle(2)` lowers to a NewObject followed by
+/// SetProps of `tag` and the payload. When the object is only read with
+/// GetProps of properties already set, in the block that built it, either
+/// directly or through a local it is the only value ever stored to, each read
+/// becomes a copy of the stored value and the object is dropped. Constant
+/// folding then decides `shape.tag === "Circle"` for a known tag.
+pub fn scalar_replace_objects(func: &mut IrFunction) {
+    let mut store_counts: HashMap<u32, usize> = HashMap::new();
+    for block in &func.blocks {
+        for op in &block.ops {
+            if let IrOp::StoreLocal(slot, _) = op {
+                *store_counts.entry(*slot).or_default() += 1;
+            }
+        }
+    }
+
+    // Object -> defining block; local slot -> the object it holds; loaded
+    // value -> the object it was loaded from
+    let mut candidates: HashMap<ValueId, usize> = HashMap::new();
+    let mut slots: HashMap<u32, ValueId> = HashMap::new();
+    let mut aliases: HashMap<ValueId, ValueId> = HashMap::new();
+    for (block_idx, block) in func.blocks.iter().enumerate() {
+        for op in &block.ops {
+            match op {
+                IrOp::NewObject(dst) => {
+                    candidates.insert(*dst, block_idx);
+                }
+                IrOp::StoreLocal(slot, val)
+                    if candidates.contains_key(val) && store_counts[slot] == 1 =>
+                {
+                    slots.insert(*slot, *val);
+                }
+                IrOp::LoadLocal(dst, slot) => {
+                    if let Some(obj) = slots.get(slot) {
+                        aliases.insert(*dst, *obj);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    let resolve = |val: &ValueId| aliases.get(val).copied().unwrap_or(*val);
+
+    let mut escaped: HashSet<ValueId> = HashSet::new();
+    for (block_idx, block) in func.blocks.iter().enumerate() {
+        let local = |obj: ValueId| candidates.get(&obj) == Some(&block_idx);
+        // Slots whose store this block has passed, and properties it has set
+        let mut stored: HashSet<u32> = HashSet::new();
+        let mut set: HashSet<(ValueId, &str)> = HashSet::new();
+        for op in &block.ops {
+            match op {
+                IrOp::NewObject(_) => {}
+                IrOp::StoreLocal(slot, val) if slots.get(slot) == Some(val) && local(*val) => {
+                    stored.insert(*slot);
+                }
+                IrOp::LoadLocal(_, slot) => {
+                    if let Some(obj) = slots.get(slot)
+                        && !stored.contains(slot)
+                    {
+                        escaped.insert(*obj);
+                    }
+                }
+                IrOp::SetProp(obj, name, val) => {
+                    let obj = resolve(obj);
+                    if local(obj) {
+                        set.insert((obj, name.as_str()));
+                    } else {
+                        escaped.insert(obj);
+                    }
+                    escaped.insert(resolve(val));
+                }
+                IrOp::GetProp(_, obj, name) => {
+                    let obj = resolve(obj);
+                    if !local(obj) || !set.contains(&(obj, name.as_str())) {
+                        escaped.insert(obj);
+                    }
+                }
+                other => escaped.extend(other.uses().iter().map(resolve)),
+            }
+        }
+        escaped.extend(block.terminator.uses().iter().map(resolve));
+    }
+    candidates.retain(|obj, _| !escaped.contains(obj));
+    if candidates.is_empty() {
+        return;
+    }
+
+    let replaced = |val: &ValueId| candidates.contains_key(&resolve(val));
+    for block in &mut func.blocks {
+        let mut props: HashMap<(ValueId, String), ValueId> = HashMap::new();
+        let ops = std::mem::take(&mut block.ops);
+        for op in ops {
+            match op {
+                IrOp::NewObject(dst) if replaced(&dst) => {}
+                IrOp::StoreLocal(slot, val) if slots.get(&slot) == Some(&val) && replaced(&val) => {
+                }
+                IrOp::LoadLocal(dst, _) if aliases.contains_key(&dst) && replaced(&dst) => {}
+                IrOp::SetProp(obj, name, val) if replaced(&obj) => {
+                    props.insert((resolve(&obj), name), val);
+                }
+                IrOp::GetProp(dst, obj, name) if replaced(&obj) => {
+                    block
+                        .ops
+                        .push(IrOp::Copy(dst, props[&(resolve(&obj), name)]));
+                }
+                other => block.ops.push(other),
+            }
+        }
+    }
+}
+
+// ============================================================================
+// Bounds Check Elimination
+// ============================================================================
+
+/// Drop the bounds check of typed array accesses guarded by a loop condition.
+///
+/// `for (let i = 0; i < a.length; i++) s += a[i];` branches on
+/// `lt i, (typed.len a)` right before the body. In the taken branch the index
+/// is below the length, and it is a non-negative integer when every value
+/// the counter is ever assigned is one (an unassigned counter fails the
+/// comparison). Accesses of the same array at the same index in that block,
+/// before the counter is reassigned, become unchecked, which the backends
+/// compile to a direct load or store. Stores additionally need a value
+/// already known to be a number. Returns whether any check was removed.
+pub fn eliminate_bounds_checks(func: &mut IrFunction) -> bool {
+    let facts = BoundsFacts::new(func);
+
+    // Guarded block -> (guard block, index, array)
+    let mut guards: HashMap<BlockId, (BlockId, ValueId, ValueId)> = HashMap::new();
+    for block in &func.blocks {
+        let Terminator::Branch(cond, taken, other) = &block.terminator else {
+            continue;
+        };
+        if taken == other || facts.predecessors.get(taken).map(Vec::len) != Some(1) {
+            continue;
+        }
+        let (idx, len) = match facts.def(*cond) {
+            Some(IrOp::Lt(_, idx, len)) => (*idx, *len),
+            Some(IrOp::Gt(_, len, idx)) => (*idx, *len),
+            _ => continue,
+        };
+        if let Some(IrOp::TypedArrayLen(_, arr)) = facts.def(len) {
+            guards.insert(*taken, (block.id, idx, facts.resolve(*arr)));
+        }
+    }
+
+    let mut unchecked: Vec<(BlockId, usize)> = Vec::new();
+    for (&taken, &(guard_block, guard_idx, guard_arr)) in &guards {
+        let mut stored = HashSet::new();
+        for (i, op) in func.block(taken).ops.iter().enumerate() {
+            match op {
+                IrOp::StoreLocal(slot, _) => {
+                    stored.insert(*slot);
+                }
+                IrOp::TypedLoad(_, arr, idx, _, true) | IrOp::TypedStore(arr, idx, _, _, true)
+                    if facts.stores_number(op)
+                        && facts.resolve(*arr) == guard_arr
+                        && facts.same_index(*idx, guard_idx, guard_block, &stored)
+                        && facts.non_negative_int(
+                            *idx,
+                            &mut HashSet::new(),
+                            &mut HashSet::new(),
+                        ) =>
+                {
+                    unchecked.push((taken, i));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let changed = !unchecked.is_empty();
+    for (block, i) in unchecked {
+        match &mut func.block_mut(block).ops[i] {
+            IrOp::TypedLoad(_, _, _, _, checked) | IrOp::TypedStore(_, _, _, _, checked) => {
+                *checked = false;
+            }
+            _ => {}
+        }
+    }
+    changed
+}
+
+/// Def-use facts `eliminate_bounds_checks` reasons with.
+struct BoundsFacts<'a> {
+    func: &'a IrFunction,
+    defs: HashMap<ValueId, (BlockId, usize, &'a IrOp)>,
+    stores: HashMap<u32, Vec<ValueId>>,
+    bound_locals: HashMap<u32, ValueId>,
+    predecessors: HashMap<BlockId, Vec<BlockId>>,
+}
+
+impl<'a> BoundsFacts<'a> {
+    fn new(func: &'a IrFunction) -> Self {
+        let mut defs = HashMap::new();
+        let mut stores: HashMap<u32, Vec<ValueId>> = HashMap::new();
+        let mut predecessors: HashMap<BlockId, Vec<BlockId>> = HashMap::new();
+        for block in &func.blocks {
+            for (i, op) in block.ops.iter().enumerate() {
+                if let Some(dst) = op.dest() {
+                    defs.insert(dst, (block.id, i, op));
+                }
+                if let IrOp::StoreLocal(slot, val) = op {
+                    stores.entry(*slot).or_default().push(*val);
+                }
+            }
+            for succ in block.terminator.successors() {
+                predecessors.entry(succ).or_default().push(block.id);
+            }
+        }
+        Self {
+            func,
+            defs,
+            stores,
+            bound_locals: func.entry_bound_locals(),
+            predecessors,
+        }
+    }
+
+    /// Loads always qualify; a store only when its value is a number.
+    fn stores_number(&self, op: &IrOp) -> bool {
+        match op {
+            IrOp::TypedStore(_, _, val, _, _) => {
+                self.func.value_types.get(val) == Some(&IrType::Number)
+            }
+            _ => true,
+        }
+    }
+
+    fn def(&self, val: ValueId) -> Option<&'a IrOp> {
+        self.defs.get(&self.resolve(val)).map(|(_, _, op)| *op)
+    }
+
+    /// Follow copies and loads of single-value slots to the original value.
+    fn resolve(&self, val: ValueId) -> ValueId {
+        let mut val = val;
+        for _ in 0..self.defs.len() + 1 {
+            val = match self.defs.get(&val) {
+                Some((_, _, IrOp::Copy(_, src) | IrOp::Move(_, src))) => *src,
+                Some((_, _, IrOp::LoadLocal(_, slot))) => match self.bound_locals.get(slot) {
+                    Some(src) => *src,
+                    None => return val,
+                },
+                _ => return val,
+            };
+        }
+        val
+    }
+
+    /// Whether `idx` (read in the guarded block) holds the value the guard
+    /// in `guard_block` compared: the same SSA value, or a reload of the
+    /// same slot with no store since the guard's load.
+    fn same_index(
+        &self,
+        idx: ValueId,
+        guard_idx: ValueId,
+        guard_block: BlockId,
+        stored: &HashSet<u32>,
+    ) -> bool {
+        let (idx, guard_idx) = (self.resolve(idx), self.resolve(guard_idx));
+        if idx == guard_idx {
+            return true;
+        }
+        let (
+            Some((_, _, IrOp::LoadLocal(_, slot))),
+            Some((block, pos, IrOp::LoadLocal(_, guard_slot))),
+        ) = (self.defs.get(&idx), self.defs.get(&guard_idx))
+        else {
+            return false;
+        };
+        if slot != guard_slot || *block != guard_block || stored.contains(slot) {
+            return false;
+        }
+        // Nothing in the guard block reassigns the slot after the compared load
+        !self.func.block(*block).ops[*pos..]
+            .iter()
+            .any(|op| matches!(op, IrOp::StoreLocal(s, _) if s == slot))
+    }
+
+    /// Whether `val` is always a non-negative integer (or, for a counter
+    /// slot read before its first store, undefined). Cycles through phis and
+    /// slots are assumed to hold, as in an inductive invariant.
+    fn non_negative_int(
+        &self,
+        val: ValueId,
+        seen_values: &mut HashSet<ValueId>,
+        seen_slots: &mut HashSet<u32>,
+    ) -> bool {
+        let val = self.resolve(val);
+        if !seen_values.insert(val) {
+            return true;
+        }
+        match self.defs.get(&val).map(|(_, _, op)| *op) {
+            Some(IrOp::Const(_, Literal::Number(n))) => *n >= 0.0 && n.fract() == 0.0,
+            Some(IrOp::TypedArrayLen(_, _) | IrOp::ArrayLen(_, _)) => true,
+            Some(
+                IrOp::AddNum(_, a, b)
+                | IrOp::AddAny(_, a, b)
+                | IrOp::MulNum(_, a, b)
+                | IrOp::MulAny(_, a, b),
+            ) => {
+                self.non_negative_int(*a, seen_values, seen_slots)
+                    && self.non_negative_int(*b, seen_values, seen_slots)
+            }
+            Some(IrOp::Phi(_, entries)) => entries
+                .iter()
+                .all(|(_, v)| self.non_negative_int(*v, seen_values, seen_slots)),
+            Some(IrOp::LoadLocal(_, slot)) => {
+                if !seen_slots.insert(*slot) {
+                    return true;
+                }
+                self.stores.get(slot).is_some_and(|vals| {
+                    vals.iter()
+                        .all(|v| self.non_negative_int(*v, seen_values, seen_slots))
+                })
+            }
+            _ => false,
+        }
+    }
+}
+
+// ============================================================================
+// SIMD Unboxing
+// ============================================================================
+
+/// Read the lanes of a vector boxed earlier in the same block from the
+/// register that was boxed, instead of from the typed array. The box is
+/// mutable once it escapes, so anything with side effects in between
+/// keeps the load. Boxes nothing reads any more are left to DCE.
+pub fn unbox_simd_vectors(func: &mut IrFunction) {
+    for block in &mut func.blocks {
+        // Box result -> kind and vector it holds
+        let mut boxed: HashMap<ValueId, (SimdKind, ValueId)> = HashMap::new();
+        for op in &mut block.ops {
+            match op {
+                IrOp::Simd(dst, kind, SimdOp::Box, args) => {
+                    if let [vector] = args[..] {
+                        boxed.insert(*dst, (*kind, vector));
+                    }
+                }
+                IrOp::Simd(dst, kind, SimdOp::Unbox, args) => {
+                    if let [arg] = args[..]
+                        && let Some(&(boxed_kind, vector)) = boxed.get(&arg)
+                        && boxed_kind == *kind
+                    {
+                        *op = IrOp::Copy(*dst, vector);
+                    }
+                }
+                _ if has_side_effects(op) => boxed.clear(),
+                _ => {}
+            }
+        }
+    }
+}
+
 // ============================================================================
 // Optimization Pipeline
 // ============================================================================
 
 /// Run all optimizations on a function.
 pub fn optimize_function(func: &mut IrFunction) {
+    // Debug builds check that each pass keeps a well-formed function so
+    let mut valid = verify::checks_passes(func);
+
     // Run passes until no changes
     for _ in 0..10 {
         let before = format!("{}", func);
 
-        constant_folding(func);
-        copy_propagation(func);
-        dead_code_elimination(func);
-        common_subexpression_elimination(func);
-        simplify_branches(func);
-        remove_unreachable_blocks(func);
+        for (name, pass) in PASSES {
+            pass(func);
+            valid = verify::check_pass(name, func, valid);
+        }
 
         let after = format!("{}", func);
         if before == after {
@@ -670,17 +1187,107 @@ pub fn optimize_function(func: &mut IrFunction) {
     func.compute_predecessors();
 }
 
-/// Run all optimizations on a module.
+/// The passes of `optimize_function`, in order.
+const PASSES: [(&str, fn(&mut IrFunction)); 10] = [
+    ("constant folding", constant_folding),
+    ("array scalar replacement", scalar_replace_arrays),
+    ("object scalar replacement", scalar_replace_objects),
+    ("SIMD unboxing", unbox_simd_vectors),
+    ("copy propagation", copy_propagation),
+    ("dead code elimination", dead_code_elimination),
+    (
+        "common subexpression elimination",
+        common_subexpression_elimination,
+    ),
+    ("branch simplification", simplify_branches),
+    ("unreachable block elimination", remove_unreachable_blocks),
+    ("bounds check elimination", |func| {
+        eliminate_bounds_checks(func);
+    }),
+];
+
 pub fn optimize_module(module: &mut IrModule) {
     for func in &mut module.functions {
         optimize_function(func);
     }
 }
 
+/// Optimization level of a build (`oitec build -O0` to `-O3`).
+///
+/// The function-local passes run at every level: the backends rely on them
+/// to turn calls through locals into direct calls. `O0` and `O1` differ only
+/// in what the backend does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum OptLevel {
+    O0,
+    #[default]
+    O1,
+    /// Also inlining, constant propagation across calls, dead function
+    /// elimination, loop-invariant code motion and strength reduction.
+    O2,
+    /// As `O2`, inlining larger functions, with a second round.
+    O3,
+}
+
+impl OptLevel {
+    /// The level a `-O<n>` flag names.
+    pub fn from_flag(flag: &str) -> Option<Self> {
+        match flag {
+            "-O0" => Some(Self::O0),
+            "-O1" => Some(Self::O1),
+            "-O2" => Some(Self::O2),
+            "-O3" => Some(Self::O3),
+            _ => None,
+        }
+    }
+
+    /// Inlining limits, `None` below `O2`.
+    pub fn inline_budget(self) -> Option<InlineBudget> {
+        match self {
+            Self::O0 | Self::O1 => None,
+            Self::O2 => Some(InlineBudget {
+                callee_size: 24,
+                single_site_size: 80,
+                caller_size: 2_000,
+            }),
+            Self::O3 => Some(InlineBudget {
+                callee_size: 64,
+                single_site_size: 240,
+                caller_size: 8_000,
+            }),
+        }
+    }
+}
+
+/// Run the optimizations of `level` on a module. `keep_exported` keeps a
+/// library's named functions, which its host calls, and their parameters.
+pub fn optimize_module_at(module: &mut IrModule, level: OptLevel, keep_exported: bool) {
+    optimize_module(module);
+    let Some(budget) = level.inline_budget() else {
+        return;
+    };
+    let rounds = if level == OptLevel::O3 { 2 } else { 1 };
+    // Debug builds check these passes too, as `optimize_function` does
+    let mut valid = module.functions.iter().all(verify::checks_passes);
+    for _ in 0..rounds {
+        ipo::inline_calls(module, &budget, keep_exported);
+        valid = verify::check_module_pass("inlining", module, valid);
+        ipo::propagate_constants(module, keep_exported);
+        valid = verify::check_module_pass("constant propagation", module, valid);
+        optimize_module(module);
+    }
+    ipo::remove_dead_functions(module, keep_exported);
+    valid = verify::check_module_pass("dead function elimination", module, valid);
+    for func in &mut module.functions {
+        loops::optimize_loops(func);
+    }
+    verify::check_module_pass("loop optimization", module, valid);
+    optimize_module(module);
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::ir::IrType;
 
     #[test]
     fn test_constant_folding() {
@@ -761,6 +1368,129 @@ mod tests {
         assert!(has_copy, "Duplicate add should become copy");
     }
 
+    #[test]
+    fn test_scalar_replace_arrays() {
+        let mut func = IrFunction::new("test".to_string());
+        let entry = func.alloc_block();
+
+        let a = func.alloc_value(IrType::Number);
+        let b = func.alloc_value(IrType::Number);
+        let arr = func.alloc_value(IrType::Array);
+        let i0 = func.alloc_value(IrType::Number);
+        let i1 = func.alloc_value(IrType::Number);
+        let x = func.alloc_value(IrType::Any);
+        let y = func.alloc_value(IrType::Any);
+        let sum = func.alloc_value(IrType::Number);
+
+        {
+            let block = func.block_mut(entry);
+            block.push(IrOp::Const(a, Literal::Number(1.0)));
+            block.push(IrOp::Const(b, Literal::Number(2.0)));
+            block.push(IrOp::NewArray(arr));
+            block.push(IrOp::Const(i0, Literal::Number(0.0)));
+            block.push(IrOp::SetElement(arr, i0, a));
+            block.push(IrOp::Const(i1, Literal::Number(1.0)));
+            block.push(IrOp::SetElement(arr, i1, b));
+            block.push(IrOp::GetElement(x, arr, i0));
+            block.push(IrOp::GetElement(y, arr, i1));
+            block.push(IrOp::AddNum(sum, x, y));
+            block.terminate(Terminator::Return(Some(sum)));
+        }
+
+        scalar_replace_arrays(&mut func);
+
+        let ops = &func.blocks[entry.0 as usize].ops;
+        assert!(
+            !ops.iter()
+                .any(|op| matches!(op, IrOp::NewArray(_) | IrOp::SetElement(..))),
+            "Non-escaping array should be removed"
+        );
+        assert!(
+            ops.iter()
+                .any(|op| matches!(op, IrOp::Copy(d, s) if *d == x && *s == a))
+        );
+        assert!(
+            ops.iter()
+                .any(|op| matches!(op, IrOp::Copy(d, s) if *d == y && *s == b))
+        );
+    }
+
+    #[test]
+    fn test_scalar_replace_keeps_escaping_arrays() {
+        let mut func = IrFunction::new("test".to_string());
+        let entry = func.alloc_block();
+
+        let a = func.alloc_value(IrType::Number);
+        let arr = func.alloc_value(IrType::Array);
+        let i0 = func.alloc_value(IrType::Number);
+
+        {
+            let block = func.block_mut(entry);
+            block.push(IrOp::Const(a, Literal::Number(1.0)));
+            block.push(IrOp::NewArray(arr));
+            block.push(IrOp::Const(i0, Literal::Number(0.0)));
+            block.push(IrOp::SetElement(arr, i0, a));
+            block.terminate(Terminator::Return(Some(arr)));
+        }
+
+        scalar_replace_arrays(&mut func);
+
+        let ops = &func.blocks[entry.0 as usize].ops;
+        assert!(ops.iter().any(|op| matches!(op, IrOp::NewArray(_))));
+        assert!(ops.iter().any(|op| matches!(op, IrOp::SetElement(..))));
+    }
+
+    #[test]
+    fn test_scalar_replace_objects_decides_known_tags() {
+        let mut func = IrFunction::new("test".to_string());
+        let entry = func.alloc_block();
+
+        let shape = func.alloc_value(IrType::Object);
+        let tag = func.alloc_value(IrType::String);
+        let radius = func.alloc_value(IrType::Number);
+        let loaded = func.alloc_value(IrType::Any);
+        let loaded_tag = func.alloc_value(IrType::Any);
+        let expected = func.alloc_value(IrType::String);
+        let is_circle = func.alloc_value(IrType::Boolean);
+        let kept = func.alloc_value(IrType::Object);
+
+        {
+            let block = func.block_mut(entry);
+            block.push(IrOp::NewObject(shape));
+            block.push(IrOp::Const(tag, Literal::String("Circle".to_string())));
+            block.push(IrOp::SetProp(shape, "tag".to_string(), tag));
+            block.push(IrOp::Const(radius, Literal::Number(2.0)));
+            block.push(IrOp::SetProp(shape, "radius".to_string(), radius));
+            block.push(IrOp::StoreLocal(0, shape));
+            block.push(IrOp::LoadLocal(loaded, 0));
+            block.push(IrOp::GetProp(loaded_tag, loaded, "tag".to_string()));
+            block.push(IrOp::Const(expected, Literal::String("Circle".to_string())));
+            block.push(IrOp::EqStrict(is_circle, loaded_tag, expected));
+            // An object that is returned must stay
+            block.push(IrOp::NewObject(kept));
+            block.push(IrOp::SetProp(kept, "tag".to_string(), tag));
+            block.push(IrOp::StoreLocal(1, is_circle));
+            block.terminate(Terminator::Return(Some(kept)));
+        }
+
+        scalar_replace_objects(&mut func);
+        constant_folding(&mut func);
+
+        let ops = &func.blocks[entry.0 as usize].ops;
+        assert!(
+            !ops.iter()
+                .any(|op| matches!(op, IrOp::NewObject(o) if *o == shape))
+        );
+        assert!(
+            ops.iter()
+                .any(|op| matches!(op, IrOp::NewObject(o) if *o == kept))
+        );
+        assert!(
+            ops.iter()
+                .any(|op| matches!(op, IrOp::Const(d, Literal::Boolean(true)) if *d == is_circle))
+        );
+    }
+
     #[test]
     fn test_branch_simplification() {
         let mut func = IrFunction::new("test".to_string());
@@ -799,4 +1529,88 @@ mod tests {
             "Branch should be simplified to jump"
         );
     }
+
+    /// `for (let i = 0; i < a.length; i = i <step> 1) { a[i]; i = ...; a[i] }`
+    fn typed_array_loop(decrement: bool) -> (IrFunction, BlockId) {
+        use crate::runtime::heap::ElementKind;
+
+        let mut func = IrFunction::new("sum".to_string());
+        let entry = func.alloc_block();
+        let header = func.alloc_block();
+        let body = func.alloc_block();
+        let exit = func.alloc_block();
+        let kind = ElementKind::Float64;
+        let mut v = |ty: IrType| func.alloc_value(ty);
+        let (arr, zero) = (v(IrType::TypedArray(kind)), v(IrType::Number));
+        let (i, a, len, cond) = (
+            v(IrType::Any),
+            v(IrType::Any),
+            v(IrType::Number),
+            v(IrType::Boolean),
+        );
+        let (a2, i2, x) = (v(IrType::Any), v(IrType::Any), v(IrType::Any));
+        let (i3, one, next) = (v(IrType::Any), v(IrType::Number), v(IrType::Any));
+        let (a3, i4, y) = (v(IrType::Any), v(IrType::Any), v(IrType::Any));
+
+        let block = func.block_mut(entry);
+        block.push(IrOp::StoreLocal(0, arr));
+        block.push(IrOp::Const(zero, Literal::Number(0.0)));
+        block.push(IrOp::StoreLocal(1, zero));
+        block.terminate(Terminator::Jump(header));
+
+        let block = func.block_mut(header);
+        block.push(IrOp::LoadLocal(i, 1));
+        block.push(IrOp::LoadLocal(a, 0));
+        block.push(IrOp::TypedArrayLen(len, a));
+        block.push(IrOp::Lt(cond, i, len));
+        block.terminate(Terminator::Branch(cond, body, exit));
+
+        let block = func.block_mut(body);
+        block.push(IrOp::LoadLocal(a2, 0));
+        block.push(IrOp::LoadLocal(i2, 1));
+        block.push(IrOp::TypedLoad(x, a2, i2, kind, true));
+        block.push(IrOp::LoadLocal(i3, 1));
+        block.push(IrOp::Const(one, Literal::Number(1.0)));
+        if decrement {
+            block.push(IrOp::SubAny(next, i3, one));
+        } else {
+            block.push(IrOp::AddAny(next, i3, one));
+        }
+        block.push(IrOp::StoreLocal(1, next));
+        block.push(IrOp::LoadLocal(a3, 0));
+        block.push(IrOp::LoadLocal(i4, 1));
+        block.push(IrOp::TypedLoad(y, a3, i4, kind, true));
+        block.terminate(Terminator::Jump(header));
+
+        func.block_mut(exit).terminate(Terminator::Return(None));
+        (func, body)
+    }
+
+    fn checked_loads(func: &IrFunction, block: BlockId) -> Vec<bool> {
+        func.block(block)
+            .ops
+            .iter()
+            .filter_map(|op| match op {
+                IrOp::TypedLoad(_, _, _, _, checked) => Some(*checked),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_bounds_check_elimination() {
+        let (mut func, body) = typed_array_loop(false);
+        eliminate_bounds_checks(&mut func);
+        // The load after the counter is reassigned keeps its check
+        assert_eq!(checked_loads(&func, body), vec![false, true]);
+        assert!(func.to_string().contains("typed.load.f64.unchecked"));
+    }
+
+    #[test]
+    fn test_bounds_check_kept_for_decreasing_counter() {
+        // i - 1 may go negative, so `i < a.length` alone proves nothing
+        let (mut func, body) = typed_array_loop(true);
+        eliminate_bounds_checks(&mut func);
+        assert_eq!(checked_loads(&func, body), vec![true, true]);
+    }
 }
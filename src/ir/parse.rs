@@ -0,0 +1,768 @@
+//! Parser for the IR text format.
+//!
+//! Reads back what `format::serialize_module` writes (`--emit-ir`), so `.ir`
+//! files can be verified, optimized and compiled like lowered source, and
+//! optimization passes can be tested on hand-written IR:
+//!
+//! ```text
+//! export add = func_3
+//!
+//! fn func_3(a: num, b: num) -> num {
+//! bb0:
+//!     v2: num = add.num v0, v1
+//!     return v2
+//! }
+//! ```
+//!
+//! Parameters are `v0..vN`. A result without a type annotation is `any`, or
+//! the type of its literal for `const`. Functions named `func_<address>` are
+//! registered under that bytecode address, which is how calls refer to them.
+
+use crate::ir::intrinsics::Intrinsic;
+use crate::ir::simd::{SimdKind, SimdOp};
+use crate::ir::{
+    BlockId, FieldId, IrFunction, IrModule, IrOp, IrStructId, IrType, Literal, MonoFuncId,
+    Terminator, ValueId, ValueInfo,
+};
+use crate::runtime::heap::ElementKind;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+/// A parse error, with the 1-based line it was found on.
+#[derive(Debug)]
+pub struct ParseError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Parse a module in the IR text format.
+pub fn parse_module(text: &str) -> Result<IrModule, ParseError> {
+    let mut module = IrModule::new();
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with(';'));
+    let mut exports = Vec::new();
+    let mut user_main = None;
+
+    while let Some((at, line)) = lines.next() {
+        let error = |message: String| ParseError { line: at, message };
+        if let Some(rest) = line.strip_prefix("export ") {
+            let (name, func) = rest
+                .split_once(" = ")
+                .ok_or_else(|| error(format!("expected `export <name> = <fn>`: {}", line)))?;
+            exports.push((at, name.to_string(), func.to_string()));
+        } else if let Some(func) = line.strip_prefix("user_main = ") {
+            user_main = Some((at, func.to_string()));
+        } else if line.starts_with("struct ") {
+            parse_struct(&mut module, line, &mut lines).map_err(error)?;
+        } else if line.starts_with("fn ") {
+            let func = parse_function(line, at, &mut lines)?;
+            module.add_function(func);
+        } else {
+            return Err(error(format!("unexpected `{}`", line)));
+        }
+    }
+
+    for (idx, func) in module.functions.iter().enumerate() {
+        if let Some(addr) = function_addr(&func.name) {
+            module.function_addrs.insert(addr, idx);
+        }
+    }
+    for (line, name, func) in exports {
+        let idx = module
+            .functions
+            .iter()
+            .position(|f| f.name == func)
+            .ok_or_else(|| ParseError {
+                line,
+                message: format!("no function `{}`", func),
+            })?;
+        module.function_names.insert(name, idx);
+    }
+    if let Some((line, func)) = user_main {
+        let addr = function_addr(&func).ok_or_else(|| ParseError {
+            line,
+            message: format!("`{}` is not a `func_<address>` name", func),
+        })?;
+        module.user_main_addr = Some(addr);
+    }
+    Ok(module)
+}
+
+fn function_addr(name: &str) -> Option<usize> {
+    name.strip_prefix("func_")?.parse().ok()
+}
+
+/// `struct Name {`, its fields (`name: ty // offset: n`) and a
+/// `// size: n, alignment: n` line, up to `}`.
+fn parse_struct<'a>(
+    module: &mut IrModule,
+    header: &str,
+    lines: &mut impl Iterator<Item = (usize, &'a str)>,
+) -> Result<(), String> {
+    let name = header
+        .strip_prefix("struct ")
+        .and_then(|rest| rest.strip_suffix(" {"))
+        .ok_or_else(|| format!("expected `struct <name> {{`: {}", header))?;
+    let id = module.define_struct(name.to_string());
+    let def = module.get_struct_mut(id).expect("struct was just defined");
+    for (_, line) in lines.by_ref() {
+        if line == "}" {
+            return Ok(());
+        }
+        if let Some(layout) = line.strip_prefix("// size: ") {
+            let (size, alignment) = layout
+                .split_once(", alignment: ")
+                .ok_or_else(|| format!("expected `// size: n, alignment: n`: {}", line))?;
+            def.size = number(size)?;
+            def.alignment = number(alignment)?;
+            continue;
+        }
+        let (field, offset) = line
+            .split_once(" // offset: ")
+            .ok_or_else(|| format!("expected `<field>: <type> // offset: n`: {}", line))?;
+        let (field, ty) = field
+            .split_once(": ")
+            .ok_or_else(|| format!("expected `<field>: <type>`: {}", field))?;
+        def.fields
+            .push((field.to_string(), parse_type(ty)?, number(offset)?));
+    }
+    Err(format!("struct `{}` is not closed", name))
+}
+
+/// A function, from its `fn` line up to the closing `}`.
+fn parse_function<'a>(
+    header: &str,
+    header_line: usize,
+    lines: &mut impl Iterator<Item = (usize, &'a str)>,
+) -> Result<IrFunction, ParseError> {
+    let mut func = parse_signature(header).map_err(|message| ParseError {
+        line: header_line,
+        message,
+    })?;
+    let mut types: HashMap<ValueId, IrType> = func
+        .params
+        .iter()
+        .enumerate()
+        .map(|(i, (_, ty))| (ValueId(i as u32), ty.clone()))
+        .collect();
+    let mut max_value = func.params.len() as u32;
+    let mut blocks: BTreeMap<u32, (Vec<IrOp>, Option<Terminator>)> = BTreeMap::new();
+    let mut current: Option<u32> = None;
+    let mut last_line = header_line;
+
+    for (at, line) in lines.by_ref() {
+        last_line = at;
+        let error = |message: String| ParseError { line: at, message };
+        if line == "}" {
+            return finish_function(func, blocks, types, max_value).map_err(error);
+        }
+        if let Some(local) = line.strip_prefix("local ") {
+            let (slot, ty, name) = parse_local(local).map_err(error)?;
+            if slot as usize != func.locals.len() {
+                return Err(error(format!("expected local ${}", func.locals.len())));
+            }
+            func.add_local(name, ty);
+            continue;
+        }
+        if let Some(label) = line.strip_suffix(':') {
+            let id = block_id(label).map_err(error)?.0;
+            if blocks.insert(id, (Vec::new(), None)).is_some() {
+                return Err(error(format!("{} is defined twice", label)));
+            }
+            current = Some(id);
+            continue;
+        }
+
+        let block = current
+            .and_then(|id| blocks.get_mut(&id))
+            .ok_or_else(|| error("operation outside a block".to_string()))?;
+        if block.1.is_some() {
+            return Err(error("operation after the block's terminator".to_string()));
+        }
+        match parse_terminator(line).map_err(error)? {
+            Some(term) => {
+                for v in term.uses() {
+                    max_value = max_value.max(v.0 + 1);
+                }
+                block.1 = Some(term);
+            }
+            None => {
+                let (op, ty) = parse_op(line).map_err(error)?;
+                for v in op.uses().into_iter().chain(op.dest()) {
+                    max_value = max_value.max(v.0 + 1);
+                }
+                if let Some(d) = op.dest() {
+                    let ty = ty.unwrap_or_else(|| match &op {
+                        IrOp::Const(_, lit) => lit.ir_type(),
+                        _ => IrType::Any,
+                    });
+                    types.insert(d, ty);
+                }
+                block.0.push(op);
+            }
+        }
+    }
+    Err(ParseError {
+        line: last_line,
+        message: format!("function `{}` is not closed", func.name),
+    })
+}
+
+/// Build the function's blocks and values once its body is read.
+fn finish_function(
+    mut func: IrFunction,
+    blocks: BTreeMap<u32, (Vec<IrOp>, Option<Terminator>)>,
+    types: HashMap<ValueId, IrType>,
+    max_value: u32,
+) -> Result<IrFunction, String> {
+    for (expected, (id, (ops, term))) in blocks.into_iter().enumerate() {
+        if id as usize != expected {
+            return Err(format!("bb{} is missing", expected));
+        }
+        let term = term.ok_or_else(|| format!("bb{} has no terminator", id))?;
+        let block = func.alloc_block();
+        func.block_mut(block).ops = ops;
+        func.block_mut(block).terminate(term);
+    }
+    if func.blocks.is_empty() {
+        return Err(format!("function `{}` has no blocks", func.name));
+    }
+    let count = func.blocks.len() as u32;
+    for block in &func.blocks {
+        let targets = block.terminator.successors().into_iter().chain(
+            block
+                .ops
+                .iter()
+                .filter_map(|op| match op {
+                    IrOp::Phi(_, entries) => Some(entries.iter().map(|(b, _)| *b)),
+                    _ => None,
+                })
+                .flatten(),
+        );
+        for target in targets {
+            if target.0 >= count {
+                return Err(format!("{} refers to undefined {}", block.id, target));
+            }
+        }
+    }
+
+    func.next_value = max_value;
+    for (id, ty) in types {
+        func.value_info.insert(id, ValueInfo::new(ty.clone()));
+        func.value_types.insert(id, ty);
+    }
+    func.compute_predecessors();
+    Ok(func)
+}
+
+/// `fn name(a: ty, b: ty) -> ty {`
+fn parse_signature(line: &str) -> Result<IrFunction, String> {
+    let expected = || format!("expected `fn <name>(<params>) -> <type> {{`: {}", line);
+    let rest = line
+        .strip_prefix("fn ")
+        .and_then(|rest| rest.strip_suffix(" {"))
+        .ok_or_else(expected)?;
+    let (signature, return_ty) = rest.rsplit_once(" -> ").ok_or_else(expected)?;
+    let (name, params) = signature
+        .split_once('(')
+        .and_then(|(name, params)| Some((name, params.strip_suffix(')')?)))
+        .ok_or_else(expected)?;
+
+    let mut func = IrFunction::new(name.to_string());
+    func.return_ty = parse_type(return_ty)?;
+    for param in list(params) {
+        let (name, ty) = param
+            .split_once(": ")
+            .ok_or_else(|| format!("expected `<param>: <type>`: {}", param))?;
+        func.params.push((name.to_string(), parse_type(ty)?));
+    }
+    Ok(func)
+}
+
+/// `$slot: ty = name`
+fn parse_local(text: &str) -> Result<(u32, IrType, String), String> {
+    let expected = || format!("expected `local $<slot>: <type> = <name>`: {}", text);
+    let (slot, rest) = text.split_once(": ").ok_or_else(expected)?;
+    let (ty, name) = rest.split_once(" = ").ok_or_else(expected)?;
+    Ok((local_slot(slot)?, parse_type(ty)?, name.to_string()))
+}
+
+fn parse_terminator(line: &str) -> Result<Option<Terminator>, String> {
+    let term = match line.split_once(' ') {
+        None if line == "return" => Terminator::Return(None),
+        None if line == "unreachable" => Terminator::Unreachable,
+        Some(("return", v)) => Terminator::Return(Some(value(v)?)),
+        Some(("jump", target)) => Terminator::Jump(block_id(target)?),
+        Some(("branch", rest)) => {
+            let [cond, t, f] = operands(rest)?;
+            Terminator::Branch(value(cond)?, block_id(t)?, block_id(f)?)
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(term))
+}
+
+/// An operation, and the type its result is annotated with.
+fn parse_op(line: &str) -> Result<(IrOp, Option<IrType>), String> {
+    // `v3: num = add.num v1, v2`, or `store.local $0, v1` without a result
+    let (dest, ty, body) = match line.split_once(" = ") {
+        Some((lhs, body)) if value(lhs.split_once(": ").map_or(lhs, |(d, _)| d)).is_ok() => {
+            match lhs.split_once(": ") {
+                Some((d, ty)) => (Some(value(d)?), Some(parse_type(ty)?), body),
+                None => (Some(value(lhs)?), None, body),
+            }
+        }
+        _ => (None, None, line),
+    };
+    let (mnemonic, args) = body.split_once(' ').unwrap_or((body, ""));
+    let d = || dest.ok_or_else(|| format!("`{}` needs a result value", mnemonic));
+
+    let binary: Option<fn(ValueId, ValueId, ValueId) -> IrOp> = match mnemonic {
+        "add.num" => Some(IrOp::AddNum),
+        "sub.num" => Some(IrOp::SubNum),
+        "mul.num" => Some(IrOp::MulNum),
+        "div.num" => Some(IrOp::DivNum),
+        "mod.num" => Some(IrOp::ModNum),
+        "add.any" => Some(IrOp::AddAny),
+        "sub.any" => Some(IrOp::SubAny),
+        "mul.any" => Some(IrOp::MulAny),
+        "div.any" => Some(IrOp::DivAny),
+        "mod.any" => Some(IrOp::ModAny),
+        "bit.and" => Some(IrOp::BitAnd),
+        "bit.or" => Some(IrOp::BitOr),
+        "xor" => Some(IrOp::Xor),
+        "shl" => Some(IrOp::Shl),
+        "shr" => Some(IrOp::Shr),
+        "shr.u" => Some(IrOp::ShrU),
+        "pow" => Some(IrOp::Pow),
+        "eq.strict" => Some(IrOp::EqStrict),
+        "ne.strict" => Some(IrOp::NeStrict),
+        "lt" => Some(IrOp::Lt),
+        "le" => Some(IrOp::LtEq),
+        "gt" => Some(IrOp::Gt),
+        "ge" => Some(IrOp::GtEq),
+        "and" => Some(IrOp::And),
+        "or" => Some(IrOp::Or),
+        _ => None,
+    };
+    if let Some(op) = binary {
+        let [a, b] = operands(args)?;
+        return Ok((op(d()?, value(a)?, value(b)?), ty));
+    }
+    let unary: Option<fn(ValueId, ValueId) -> IrOp> = match mnemonic {
+        "neg.num" => Some(IrOp::NegNum),
+        "neg.any" => Some(IrOp::NegAny),
+        "not" => Some(IrOp::Not),
+        "array.len" => Some(IrOp::ArrayLen),
+        "typed.len" => Some(IrOp::TypedArrayLen),
+        "to.bool" => Some(IrOp::ToBool),
+        "to.num" => Some(IrOp::ToNum),
+        "typeof" => Some(IrOp::TypeOf),
+        "copy" => Some(IrOp::Copy),
+        "borrow" => Some(IrOp::Borrow),
+        "borrow.mut" => Some(IrOp::BorrowMut),
+        "deref" => Some(IrOp::Deref),
+        "move" => Some(IrOp::Move),
+        "clone" => Some(IrOp::Clone),
+        _ => None,
+    };
+    if let Some(op) = unary {
+        return Ok((op(d()?, value(args)?), ty));
+    }
+
+    let op = match mnemonic {
+        "const" => IrOp::Const(d()?, parse_literal(args)?),
+        "load.local" => IrOp::LoadLocal(d()?, local_slot(args)?),
+        "store.local" => {
+            let [slot, v] = operands(args)?;
+            IrOp::StoreLocal(local_slot(slot)?, value(v)?)
+        }
+        "load.global" => IrOp::LoadGlobal(d()?, global(args)?.to_string()),
+        "store.global" => {
+            let (name, v) = args.rsplit_once(", ").ok_or_else(|| malformed(body))?;
+            IrOp::StoreGlobal(global(name)?.to_string(), value(v)?)
+        }
+        "new.object" => IrOp::NewObject(d()?),
+        "new.array" => IrOp::NewArray(d()?),
+        "load.this" => IrOp::LoadThis(d()?),
+        "get.prop" => {
+            let (obj, name) = args.split_once(", .").ok_or_else(|| malformed(body))?;
+            IrOp::GetProp(d()?, value(obj)?, name.to_string())
+        }
+        "set.prop" => {
+            let (obj, rest) = args.split_once(", .").ok_or_else(|| malformed(body))?;
+            let (name, v) = rest.rsplit_once(", ").ok_or_else(|| malformed(body))?;
+            IrOp::SetProp(value(obj)?, name.to_string(), value(v)?)
+        }
+        "get.elem" => {
+            let [obj, key] = operands(args)?;
+            IrOp::GetElement(d()?, value(obj)?, value(bracketed(key)?)?)
+        }
+        "set.elem" => {
+            let [obj, key, v] = operands(args)?;
+            IrOp::SetElement(value(obj)?, value(bracketed(key)?)?, value(v)?)
+        }
+        "array.push" => {
+            let [arr, v] = operands(args)?;
+            IrOp::ArrayPush(value(arr)?, value(v)?)
+        }
+        "call" => {
+            let (callee, call_args) = call(args)?;
+            IrOp::Call(d()?, value(callee)?, call_args)
+        }
+        "call.method" => {
+            let (target, call_args) = call(args)?;
+            let (obj, method) = target.split_once('.').ok_or_else(|| malformed(body))?;
+            IrOp::CallMethod(d()?, value(obj)?, method.to_string(), call_args)
+        }
+        "intrinsic" => {
+            let (name, call_args) = call(args)?;
+            let intrinsic = Intrinsic::from_name(name)
+                .ok_or_else(|| format!("unknown intrinsic `{}`", name))?;
+            IrOp::Intrinsic(d()?, intrinsic, call_args)
+        }
+        "simd" => {
+            let (target, call_args) = call(args)?;
+            let (kind, op) = target.split_once('.').ok_or_else(|| malformed(body))?;
+            let kind =
+                SimdKind::from_name(kind).ok_or_else(|| format!("unknown SIMD kind `{}`", kind))?;
+            let op = SimdOp::parse(op).ok_or_else(|| format!("unknown SIMD operation `{}`", op))?;
+            IrOp::Simd(d()?, kind, op, call_args)
+        }
+        "call.mono" => {
+            let (mono, call_args) = call(args)?;
+            let id = mono.strip_prefix("mono#").ok_or_else(|| malformed(body))?;
+            IrOp::CallMono(d()?, MonoFuncId(number(id)?), call_args)
+        }
+        "make.closure" => {
+            let [func, env] = operands(args)?;
+            let addr = func.strip_prefix("func#").ok_or_else(|| malformed(body))?;
+            IrOp::MakeClosure(d()?, number(addr)?, value(env)?)
+        }
+        "typecheck" | "typeguard" => {
+            let (v, check) = args.split_once(", ").ok_or_else(|| malformed(body))?;
+            let (v, check) = (value(v)?, parse_type(check)?);
+            if mnemonic == "typecheck" {
+                IrOp::TypeCheck(d()?, v, check)
+            } else {
+                IrOp::TypeGuard(d()?, v, check)
+            }
+        }
+        "delete" => {
+            let (obj, prop) = args.split_once('.').ok_or_else(|| malformed(body))?;
+            IrOp::DeleteProp(d()?, value(obj)?, prop.to_string())
+        }
+        "phi" => {
+            let entries = list(args)
+                .into_iter()
+                .map(|entry| {
+                    let (block, v) = bracketed(entry)?
+                        .split_once(": ")
+                        .ok_or_else(|| malformed(entry))?;
+                    Ok((block_id(block)?, value(v)?))
+                })
+                .collect::<Result<_, String>>()?;
+            IrOp::Phi(d()?, entries)
+        }
+        "deref.store" => {
+            let [target, v] = operands(args)?;
+            IrOp::DerefStore(value(target)?, value(v)?)
+        }
+        "end.borrow" => IrOp::EndBorrow(value(args)?),
+        "struct.new" => {
+            let id = args
+                .strip_prefix("struct#")
+                .ok_or_else(|| malformed(body))?;
+            IrOp::StructNew(d()?, IrStructId(number(id)?))
+        }
+        "struct.get" => {
+            let (src, field) = args.split_once(", ").ok_or_else(|| malformed(body))?;
+            match field.strip_prefix('.') {
+                Some(name) => IrOp::StructGetFieldNamed(d()?, value(src)?, name.to_string()),
+                None => IrOp::StructGetField(d()?, value(src)?, field_id(field)?),
+            }
+        }
+        "struct.set" => {
+            let (target, rest) = args.split_once(", ").ok_or_else(|| malformed(body))?;
+            let (field, v) = rest.rsplit_once(", ").ok_or_else(|| malformed(body))?;
+            match field.strip_prefix('.') {
+                Some(name) => {
+                    IrOp::StructSetFieldNamed(value(target)?, name.to_string(), value(v)?)
+                }
+                None => IrOp::StructSetField(value(target)?, field_id(field)?, value(v)?),
+            }
+        }
+        _ if mnemonic.starts_with("typed.load.") => {
+            let (kind, checked) = element_kind(&mnemonic["typed.load.".len()..])?;
+            let [arr, idx] = operands(args)?;
+            IrOp::TypedLoad(d()?, value(arr)?, value(bracketed(idx)?)?, kind, checked)
+        }
+        _ if mnemonic.starts_with("typed.store.") => {
+            let (kind, checked) = element_kind(&mnemonic["typed.store.".len()..])?;
+            let [arr, idx, v] = operands(args)?;
+            IrOp::TypedStore(
+                value(arr)?,
+                value(bracketed(idx)?)?,
+                value(v)?,
+                kind,
+                checked,
+            )
+        }
+        _ => return Err(format!("unknown operation `{}`", mnemonic)),
+    };
+    Ok((op, ty))
+}
+
+fn parse_type(text: &str) -> Result<IrType, String> {
+    if let Some(inner) = text.strip_prefix("&mut ") {
+        return Ok(IrType::MutRef(Box::new(parse_type(inner)?)));
+    }
+    if let Some(inner) = text.strip_prefix('&') {
+        return Ok(IrType::Ref(Box::new(parse_type(inner)?)));
+    }
+    if let Some(id) = text.strip_prefix("struct#") {
+        return Ok(IrType::Struct(IrStructId(number(id)?)));
+    }
+    if let Some(kind) = SimdKind::from_name(text) {
+        return Ok(IrType::Simd(kind));
+    }
+    if let Some(kind) = text.strip_suffix("[]") {
+        return ElementKind::from_short_name(kind)
+            .map(IrType::TypedArray)
+            .ok_or_else(|| format!("unknown element type `{}`", kind));
+    }
+    Ok(match text {
+        "num" => IrType::Number,
+        "str" => IrType::String,
+        "bool" => IrType::Boolean,
+        "obj" => IrType::Object,
+        "arr" => IrType::Array,
+        "fn" => IrType::Function,
+        "any" => IrType::Any,
+        "!" => IrType::Never,
+        "void" => IrType::Void,
+        _ => return Err(format!("unknown type `{}`", text)),
+    })
+}
+
+fn parse_literal(text: &str) -> Result<Literal, String> {
+    Ok(match text {
+        "true" => Literal::Boolean(true),
+        "false" => Literal::Boolean(false),
+        "null" => Literal::Null,
+        "undefined" => Literal::Undefined,
+        _ if text.starts_with('"') => {
+            let quoted = text
+                .strip_prefix('"')
+                .and_then(|s| s.strip_suffix('"'))
+                .ok_or_else(|| format!("unterminated string {}", text))?;
+            Literal::String(unescape(quoted)?)
+        }
+        _ => Literal::Number(
+            text.parse()
+                .map_err(|_| format!("invalid constant `{}`", text))?,
+        ),
+    })
+}
+
+/// Undo `str::escape_debug`.
+fn unescape(text: &str) -> Result<String, String> {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some('t') => out.push('\t'),
+            Some('0') => out.push('\0'),
+            Some(c @ ('\\' | '"' | '\'')) => out.push(c),
+            Some('u') => {
+                let code: String = chars.by_ref().take_while(|&c| c != '}').collect();
+                let c = code
+                    .strip_prefix('{')
+                    .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| format!("invalid escape \\u{}}}", code))?;
+                out.push(c);
+            }
+            other => return Err(format!("invalid escape \\{}", other.unwrap_or(' '))),
+        }
+    }
+    Ok(out)
+}
+
+fn value(text: &str) -> Result<ValueId, String> {
+    text.strip_prefix('v')
+        .and_then(|n| n.parse().ok())
+        .map(ValueId)
+        .ok_or_else(|| format!("expected a value, found `{}`", text))
+}
+
+fn block_id(text: &str) -> Result<BlockId, String> {
+    text.strip_prefix("bb")
+        .and_then(|n| n.parse().ok())
+        .map(BlockId)
+        .ok_or_else(|| format!("expected a block, found `{}`", text))
+}
+
+fn local_slot(text: &str) -> Result<u32, String> {
+    text.strip_prefix('$')
+        .and_then(|n| n.parse().ok())
+        .ok_or_else(|| format!("expected a local slot, found `{}`", text))
+}
+
+fn field_id(text: &str) -> Result<FieldId, String> {
+    text.strip_prefix("field#")
+        .and_then(|n| n.parse().ok())
+        .map(FieldId)
+        .ok_or_else(|| format!("expected a field, found `{}`", text))
+}
+
+fn global(text: &str) -> Result<&str, String> {
+    text.strip_prefix('@')
+        .ok_or_else(|| format!("expected a global, found `{}`", text))
+}
+
+fn number<T: std::str::FromStr>(text: &str) -> Result<T, String> {
+    text.parse()
+        .map_err(|_| format!("expected a number, found `{}`", text))
+}
+
+/// The element kind of a `typed.load`/`typed.store`, and whether it checks
+/// bounds.
+fn element_kind(text: &str) -> Result<(ElementKind, bool), String> {
+    let (name, checked) = match text.strip_suffix(".unchecked") {
+        Some(name) => (name, false),
+        None => (text, true),
+    };
+    ElementKind::from_short_name(name)
+        .map(|kind| (kind, checked))
+        .ok_or_else(|| format!("unknown element type `{}`", name))
+}
+
+fn bracketed(text: &str) -> Result<&str, String> {
+    text.strip_prefix('[')
+        .and_then(|s| s.strip_suffix(']'))
+        .ok_or_else(|| format!("expected `[...]`, found `{}`", text))
+}
+
+/// `callee(a, b)`
+fn call(text: &str) -> Result<(&str, Vec<ValueId>), String> {
+    let (callee, args) = text
+        .split_once('(')
+        .and_then(|(callee, args)| Some((callee, args.strip_suffix(')')?)))
+        .ok_or_else(|| format!("expected `<callee>(<args>)`, found `{}`", text))?;
+    let args = list(args)
+        .into_iter()
+        .map(value)
+        .collect::<Result<_, _>>()?;
+    Ok((callee, args))
+}
+
+/// Exactly `N` comma-separated operands.
+fn operands<const N: usize>(text: &str) -> Result<[&str; N], String> {
+    list(text)
+        .try_into()
+        .map_err(|_| format!("expected {} operands, found `{}`", N, text))
+}
+
+fn list(text: &str) -> Vec<&str> {
+    if text.is_empty() {
+        Vec::new()
+    } else {
+        text.split(", ").collect()
+    }
+}
+
+fn malformed(text: &str) -> String {
+    format!("malformed operation `{}`", text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::Compiler;
+    use crate::ir::format::serialize_module;
+    use crate::ir::{lower, opt, typecheck, verify};
+
+    #[test]
+    fn test_emitted_ir_round_trips() {
+        let source = "function mix(a, b) { return (a & b) | (a * 2); }\nlet s = 'x\\ty';\nlet o = { k: mix(3, 5) };\nconsole.log(o.k, s);\nlet v = std.simd.f32x4.shuffle(std.simd.f32x4(1, 2, 3, 4), [5, 6, 7, 8], 0, 5, 2, 7);\n";
+        let bytecode = Compiler::new().compile(source).unwrap();
+        let mut module = lower::lower_module(&bytecode).unwrap();
+        typecheck::typecheck_module(&mut module);
+        opt::optimize_module(&mut module);
+        let text = serialize_module(&module);
+
+        let parsed = parse_module(&text).unwrap();
+        assert_eq!(serialize_module(&parsed), text);
+        assert!(text.contains("bit.and"));
+        assert!(text.contains("simd f32x4.shuffle[0, 5, 2, 7]("));
+        assert_eq!(parsed.function_names, module.function_names);
+        assert_eq!(parsed.function_addrs.len(), module.function_addrs.len());
+        for (addr, &idx) in &module.function_addrs {
+            assert_eq!(
+                parsed.get_function_by_addr(*addr).unwrap().name,
+                module.functions[idx].name
+            );
+        }
+    }
+
+    #[test]
+    fn test_hand_written_ir() {
+        let text = "\
+fn main() -> any {
+    local $0: num = i
+
+bb0:
+    v0 = const 1
+    store.local $0, v0
+    jump bb1
+bb1:
+    v1: num = load.local $0
+    v2 = const \"a\\\"b\"
+    v3: bool = lt v1, v1
+    branch v3, bb1, bb2
+bb2:
+    return v2
+}
+";
+        let module = parse_module(text).unwrap();
+        let func = &module.functions[0];
+        assert_eq!(func.blocks.len(), 3);
+        assert_eq!(func.block(BlockId(1)).predecessors.len(), 2);
+        assert_eq!(func.value_types[&ValueId(0)], IrType::Number);
+        assert_eq!(func.value_types[&ValueId(3)], IrType::Boolean);
+        assert!(matches!(
+            &func.block(BlockId(1)).ops[1],
+            IrOp::Const(_, Literal::String(s)) if s == "a\"b"
+        ));
+        assert!(verify::verify_module(&module).is_ok());
+    }
+
+    #[test]
+    fn test_errors_name_the_line() {
+        let err = parse_module("fn main() -> any {\nbb0:\n    v0 = frob v1\n    return\n}\n")
+            .unwrap_err();
+        assert_eq!(err.line, 3);
+        assert!(err.message.contains("frob"));
+
+        let err = parse_module("fn main() -> any {\nbb0:\n    jump bb4\n}\n").unwrap_err();
+        assert!(err.message.contains("bb4"));
+    }
+}
@@ -0,0 +1,296 @@
+//! SIMD vectors (`std.simd`)
+//!
+//! `std.simd.f64x2(1, 2)` builds a vector of two f64 lanes, and
+//! `std.simd.f64x2.add(a, b)`, `sub`, `mul`, `shuffle` and `reduceAdd`
+//! work on whole vectors. A vector is an array of its lanes: a plain array
+//! in the VM and a typed array of the lane type in native code, so `v[i]`
+//! reads lane `i` either way.
+//!
+//! Called through the untouched global, the operations lower to
+//! `IrOp::Simd` on unboxed vectors of type `IrType::Simd`, boxed into a
+//! typed array only where a vector leaves the chain of vector operations.
+//! The LLVM backend compiles them to vector instructions; the VM runs the
+//! lane-by-lane definitions below, which give the same results.
+
+use crate::ir::IrType;
+use crate::runtime::heap::{ElementKind, to_uint32};
+use std::fmt;
+
+/// Global object path of the `std.simd` namespace.
+pub const STD_SIMD: &str = "std.simd";
+
+/// Lane type and count of a vector.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SimdKind {
+    /// Two f64 lanes
+    F64x2,
+    /// Four f32 lanes
+    F32x4,
+    /// Four wrapping 32-bit integer lanes
+    I32x4,
+}
+
+impl SimdKind {
+    /// Every kind, in declaration order.
+    pub const ALL: [SimdKind; 3] = [SimdKind::F64x2, SimdKind::F32x4, SimdKind::I32x4];
+
+    /// Name under `std.simd`, also used for the IR type.
+    pub fn name(self) -> &'static str {
+        match self {
+            SimdKind::F64x2 => "f64x2",
+            SimdKind::F32x4 => "f32x4",
+            SimdKind::I32x4 => "i32x4",
+        }
+    }
+
+    /// Look up a kind by name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.name() == name)
+    }
+
+    /// Global object path of the kind's operations (`std.simd.f64x2`).
+    pub fn path(self) -> &'static str {
+        match self {
+            SimdKind::F64x2 => "std.simd.f64x2",
+            SimdKind::F32x4 => "std.simd.f32x4",
+            SimdKind::I32x4 => "std.simd.i32x4",
+        }
+    }
+
+    /// Number of lanes.
+    pub fn lanes(self) -> usize {
+        match self {
+            SimdKind::F64x2 => 2,
+            SimdKind::F32x4 | SimdKind::I32x4 => 4,
+        }
+    }
+
+    /// Element type of the typed array a boxed vector is stored in.
+    pub fn element(self) -> ElementKind {
+        match self {
+            SimdKind::F64x2 => ElementKind::Float64,
+            SimdKind::F32x4 => ElementKind::Float32,
+            SimdKind::I32x4 => ElementKind::Int32,
+        }
+    }
+
+    /// Convert a number to a lane value, as storing it in the typed array
+    /// does. NaN results are canonical.
+    pub fn lane(self, x: f64) -> f64 {
+        let lane = match self {
+            SimdKind::F64x2 => x,
+            SimdKind::F32x4 => x as f32 as f64,
+            SimdKind::I32x4 => to_uint32(x) as i32 as f64,
+        };
+        if lane.is_nan() { f64::NAN } else { lane }
+    }
+
+    /// Apply `Add`, `Sub` or `Mul` to each pair of lanes.
+    pub fn binary(self, op: &SimdOp, a: &[f64], b: &[f64]) -> Vec<f64> {
+        a.iter()
+            .zip(b)
+            .map(|(&x, &y)| match (self, op) {
+                (SimdKind::I32x4, SimdOp::Mul) => (x as i32).wrapping_mul(y as i32) as f64,
+                (_, SimdOp::Add) => self.lane(x + y),
+                (_, SimdOp::Sub) => self.lane(x - y),
+                (_, SimdOp::Mul) => self.lane(x * y),
+                _ => f64::NAN,
+            })
+            .collect()
+    }
+
+    /// Lanes of `a` followed by the lanes of `b`, picked by `indices`.
+    pub fn shuffle(self, a: &[f64], b: &[f64], indices: &[u8]) -> Vec<f64> {
+        indices
+            .iter()
+            .map(|&i| {
+                let i = i as usize;
+                if i < a.len() { a[i] } else { b[i - a.len()] }
+            })
+            .collect()
+    }
+
+    /// Sum of the lanes, added first to last in the lane type.
+    pub fn reduce_add(self, v: &[f64]) -> f64 {
+        v.iter().fold(-0.0, |sum, &x| self.lane(sum + x))
+    }
+}
+
+impl fmt::Display for SimdKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// An operation on vectors of one kind.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SimdOp {
+    /// Build a vector from its lanes, given as numbers
+    Make,
+    /// Lane-wise `a + b`
+    Add,
+    /// Lane-wise `a - b`
+    Sub,
+    /// Lane-wise `a * b`
+    Mul,
+    /// Lanes of `a` followed by the lanes of `b`, picked by index
+    Shuffle(Vec<u8>),
+    /// Sum of the lanes, as a number
+    ReduceAdd,
+    /// Store a vector in a new typed array
+    Box,
+    /// Read the lanes of a boxed vector (any array of numbers)
+    Unbox,
+}
+
+impl SimdOp {
+    /// The lane-wise operation `std.simd.<kind>.<method>(a, b)`, or
+    /// `reduceAdd`. `shuffle` takes constant lanes and is matched apart.
+    pub fn from_method(method: &str) -> Option<Self> {
+        Some(match method {
+            "add" => SimdOp::Add,
+            "sub" => SimdOp::Sub,
+            "mul" => SimdOp::Mul,
+            "reduceAdd" => SimdOp::ReduceAdd,
+            _ => return None,
+        })
+    }
+
+    /// The valid shuffle of `kind` picking `indices`: one index per lane,
+    /// each naming a lane of the two operands.
+    pub fn shuffle(kind: SimdKind, indices: &[f64]) -> Option<Self> {
+        if indices.len() != kind.lanes() {
+            return None;
+        }
+        indices
+            .iter()
+            .map(|&i| {
+                (i.fract() == 0.0 && i >= 0.0 && i < (2 * kind.lanes()) as f64).then_some(i as u8)
+            })
+            .collect::<Option<Vec<u8>>>()
+            .map(SimdOp::Shuffle)
+    }
+
+    /// Parse the operation as printed in IR (`add`, `shuffle[0, 3]`, ...).
+    pub fn parse(text: &str) -> Option<Self> {
+        if let Some(indices) = text
+            .strip_prefix("shuffle[")
+            .and_then(|rest| rest.strip_suffix(']'))
+        {
+            return indices
+                .split(", ")
+                .map(|i| i.parse().ok())
+                .collect::<Option<Vec<u8>>>()
+                .map(SimdOp::Shuffle);
+        }
+        Some(match text {
+            "make" => SimdOp::Make,
+            "box" => SimdOp::Box,
+            "unbox" => SimdOp::Unbox,
+            _ => return Self::from_method(text),
+        })
+    }
+
+    /// Number of operands.
+    pub fn arity(&self, kind: SimdKind) -> usize {
+        match self {
+            SimdOp::Make => kind.lanes(),
+            SimdOp::Add | SimdOp::Sub | SimdOp::Mul | SimdOp::Shuffle(_) => 2,
+            SimdOp::ReduceAdd | SimdOp::Box | SimdOp::Unbox => 1,
+        }
+    }
+
+    /// Type every operand must have; `None` takes any value.
+    pub fn operand_type(&self, kind: SimdKind) -> Option<IrType> {
+        match self {
+            SimdOp::Make => Some(IrType::Number),
+            SimdOp::Unbox => None,
+            _ => Some(IrType::Simd(kind)),
+        }
+    }
+
+    /// Type of the result.
+    pub fn result_type(&self, kind: SimdKind) -> IrType {
+        match self {
+            SimdOp::ReduceAdd => IrType::Number,
+            SimdOp::Box => IrType::TypedArray(kind.element()),
+            _ => IrType::Simd(kind),
+        }
+    }
+}
+
+impl fmt::Display for SimdOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SimdOp::Make => f.write_str("make"),
+            SimdOp::Add => f.write_str("add"),
+            SimdOp::Sub => f.write_str("sub"),
+            SimdOp::Mul => f.write_str("mul"),
+            SimdOp::Shuffle(indices) => {
+                let indices: Vec<String> = indices.iter().map(|i| i.to_string()).collect();
+                write!(f, "shuffle[{}]", indices.join(", "))
+            }
+            SimdOp::ReduceAdd => f.write_str("reduceAdd"),
+            SimdOp::Box => f.write_str("box"),
+            SimdOp::Unbox => f.write_str("unbox"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lanes_wrap_and_round_like_typed_arrays() {
+        assert_eq!(SimdKind::I32x4.lane(2147483648.0), -2147483648.0);
+        assert_eq!(SimdKind::I32x4.lane(f64::NAN), 0.0);
+        assert_eq!(SimdKind::F32x4.lane(0.1), 0.1f32 as f64);
+
+        let (a, b) = ([2147483647.0, 3.0, -4.0, 65536.0], [1.0, 2.0, 3.0, 65536.0]);
+        assert_eq!(
+            SimdKind::I32x4.binary(&SimdOp::Add, &a, &b),
+            [-2147483648.0, 5.0, -1.0, 131072.0]
+        );
+        assert_eq!(
+            SimdKind::I32x4.binary(&SimdOp::Mul, &a, &b),
+            [2147483647.0, 6.0, -12.0, 0.0]
+        );
+        assert_eq!(SimdKind::I32x4.reduce_add(&a), -2147418114.0);
+        assert_eq!(SimdKind::F64x2.reduce_add(&[-0.0, -0.0]), -0.0);
+        assert!(SimdKind::F64x2.reduce_add(&[-0.0, -0.0]).is_sign_negative());
+    }
+
+    #[test]
+    fn test_shuffle_picks_from_both_operands() {
+        let op = SimdOp::shuffle(SimdKind::F64x2, &[1.0, 2.0]).unwrap();
+        assert_eq!(op, SimdOp::Shuffle(vec![1, 2]));
+        assert_eq!(
+            SimdKind::F64x2.shuffle(&[1.0, 2.0], &[3.0, 4.0], &[1, 2]),
+            [2.0, 3.0]
+        );
+
+        assert_eq!(SimdOp::shuffle(SimdKind::F64x2, &[0.0, 4.0]), None);
+        assert_eq!(SimdOp::shuffle(SimdKind::F64x2, &[0.5, 1.0]), None);
+        assert_eq!(SimdOp::shuffle(SimdKind::F32x4, &[0.0, 1.0]), None);
+    }
+
+    #[test]
+    fn test_ops_print_and_parse() {
+        for op in [
+            SimdOp::Make,
+            SimdOp::Add,
+            SimdOp::Sub,
+            SimdOp::Mul,
+            SimdOp::Shuffle(vec![0, 7, 3, 4]),
+            SimdOp::ReduceAdd,
+            SimdOp::Box,
+            SimdOp::Unbox,
+        ] {
+            assert_eq!(SimdOp::parse(&op.to_string()), Some(op));
+        }
+        for kind in SimdKind::ALL {
+            assert_eq!(SimdKind::from_name(kind.name()), Some(kind));
+        }
+    }
+}
@@ -7,6 +7,8 @@
 //!
 //! This mapping is used by the Cranelift/LLVM backends to generate native code.
 
+use crate::ir::intrinsics::Intrinsic;
+use crate::ir::simd::SimdOp;
 use crate::ir::{IrOp, IrType};
 
 /// How an IR operation should be compiled.
@@ -63,12 +65,22 @@ pub enum InlineOp {
     Shr,
     /// Integer right shift (logical/unsigned).
     ShrU,
+    /// Numeric intrinsic (count leading zeros, sqrt, bit casts, ...).
+    Intrinsic(Intrinsic),
+    /// SIMD vector instruction (lane-wise arithmetic, shuffle, reduction).
+    Simd,
     /// Copy value (register move).
     Copy,
     /// Load from local slot (stack load).
     LoadLocal,
     /// Store to local slot (stack store).
     StoreLocal,
+    /// Typed array length (header load).
+    TypedArrayLen,
+    /// Typed array element load without a bounds check.
+    TypedLoad,
+    /// Typed array element store without a bounds check.
+    TypedStore,
     /// Unconditional jump.
     Jump,
     /// Conditional branch.
@@ -129,6 +141,16 @@ pub mod stubs {
     pub const GET_ELEMENT: StubCall = StubCall::new("ot_get_element", 2);
     pub const SET_ELEMENT: StubCall = StubCall::new("ot_set_element", 3).with_side_effects();
 
+    // Typed array stubs (bounds-checked accesses)
+    pub const ALLOC_TYPED_ARRAY: StubCall =
+        StubCall::new("ot_alloc_typed_array", 2).with_side_effects();
+    pub const TYPED_ARRAY_GET: StubCall = StubCall::new("ot_typed_array_get", 2);
+    pub const TYPED_ARRAY_SET: StubCall =
+        StubCall::new("ot_typed_array_set", 3).with_side_effects();
+
+    // SIMD stubs
+    pub const SIMD_LANES: StubCall = StubCall::new("ot_simd_lanes", 3);
+
     // Dynamic arithmetic stubs
     pub const ADD_ANY: StubCall = StubCall::new("ot_add_any", 2);
     pub const SUB_ANY: StubCall = StubCall::new("ot_sub_any", 2);
@@ -203,6 +225,14 @@ pub fn compile_strategy(op: &IrOp) -> CompileStrategy {
         IrOp::Shr(_, _, _) => CompileStrategy::Inline(InlineOp::Shr),
         IrOp::ShrU(_, _, _) => CompileStrategy::Inline(InlineOp::ShrU),
         IrOp::Pow(_, _, _) => CompileStrategy::StubCall(stubs::POW),
+        IrOp::Intrinsic(_, intrinsic, _) => {
+            CompileStrategy::Inline(InlineOp::Intrinsic(*intrinsic))
+        }
+
+        // SIMD vectors - vector instructions, boxed in typed arrays
+        IrOp::Simd(_, _, SimdOp::Box, _) => CompileStrategy::StubCall(stubs::ALLOC_TYPED_ARRAY),
+        IrOp::Simd(_, _, SimdOp::Unbox, _) => CompileStrategy::StubCall(stubs::SIMD_LANES),
+        IrOp::Simd(_, _, _, _) => CompileStrategy::Inline(InlineOp::Simd),
 
         // Local variable access - inline stack operations
         IrOp::LoadLocal(_, _) => CompileStrategy::Inline(InlineOp::LoadLocal),
@@ -224,6 +254,13 @@ pub fn compile_strategy(op: &IrOp) -> CompileStrategy {
         IrOp::ArrayLen(_, _) => CompileStrategy::StubCall(stubs::GET_PROP), // .length property
         IrOp::ArrayPush(_, _) => CompileStrategy::StubCall(stubs::CALL),    // .push method
 
+        // Typed arrays - direct memory access once the bounds check is gone
+        IrOp::TypedArrayLen(_, _) => CompileStrategy::Inline(InlineOp::TypedArrayLen),
+        IrOp::TypedLoad(_, _, _, _, true) => CompileStrategy::StubCall(stubs::TYPED_ARRAY_GET),
+        IrOp::TypedLoad(_, _, _, _, false) => CompileStrategy::Inline(InlineOp::TypedLoad),
+        IrOp::TypedStore(_, _, _, _, true) => CompileStrategy::StubCall(stubs::TYPED_ARRAY_SET),
+        IrOp::TypedStore(_, _, _, _, false) => CompileStrategy::Inline(InlineOp::TypedStore),
+
         // Function operations
         IrOp::Call(_, _, _) => CompileStrategy::StubCall(stubs::CALL),
         IrOp::CallMethod(_, _, _, _) => CompileStrategy::StubCall(stubs::CALL),
@@ -342,4 +379,17 @@ mod tests {
         let d = ValueId(0);
         assert!(can_inline(&IrOp::Const(d, Literal::Number(42.0))));
     }
+
+    #[test]
+    fn test_unchecked_typed_access_is_inline() {
+        use crate::runtime::heap::ElementKind;
+
+        let (arr, idx, dst) = (ValueId(0), ValueId(1), ValueId(2));
+        let kind = ElementKind::Float64;
+
+        assert!(needs_stub(&IrOp::TypedLoad(dst, arr, idx, kind, true)));
+        assert!(can_inline(&IrOp::TypedLoad(dst, arr, idx, kind, false)));
+        assert!(can_inline(&IrOp::TypedStore(arr, idx, dst, kind, false)));
+        assert!(can_inline(&IrOp::TypedArrayLen(dst, arr)));
+    }
 }
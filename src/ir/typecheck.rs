@@ -7,7 +7,9 @@
 //!   Before: v3 = add.any v1, v2  (where v1: num, v2: num)
 //!   After:  v3 = add.num v1, v2
 
-use crate::ir::{BlockId, IrFunction, IrModule, IrOp, IrType, ValueId};
+use crate::ir::{BlockId, IrFunction, IrModule, IrOp, IrType, ValueId, ValueInfo, opt};
+use crate::types::Type;
+use crate::types::annotations::MonoHint;
 use std::collections::{HashMap, HashSet, VecDeque};
 
 /// Type inference context for a function.
@@ -19,6 +21,8 @@ pub struct TypeChecker<'a> {
     in_worklist: HashSet<BlockId>,
     /// Type information for each value.
     types: HashMap<ValueId, IrType>,
+    /// Slots that hold a single value for the whole function.
+    bound_locals: HashMap<u32, ValueId>,
     /// Whether any changes were made in the current iteration.
     changed: bool,
 }
@@ -33,11 +37,13 @@ impl<'a> TypeChecker<'a> {
             types.insert(val, ty.clone());
         }
 
+        let bound_locals = func.entry_bound_locals();
         Self {
             func,
             worklist: VecDeque::new(),
             in_worklist: HashSet::new(),
             types,
+            bound_locals,
             changed: false,
         }
     }
@@ -186,9 +192,13 @@ impl<'a> TypeChecker<'a> {
                 self.set_type(*dst, result_ty);
             }
 
-            // Local loads get Any (unless we track local types)
-            IrOp::LoadLocal(dst, _) => {
-                self.set_type(*dst, IrType::Any);
+            // Local loads get Any, unless the slot only ever holds one value
+            IrOp::LoadLocal(dst, slot) => {
+                let ty = match self.bound_locals.get(slot) {
+                    Some(&val) => self.get_type(val),
+                    None => IrType::Any,
+                };
+                self.set_type(*dst, ty);
             }
 
             // Global loads get Any
@@ -220,6 +230,21 @@ impl<'a> TypeChecker<'a> {
                 self.set_type(*dst, IrType::Number);
             }
 
+            // Typed array lengths and in-bounds loads are numbers; a checked
+            // load may produce undefined
+            IrOp::TypedArrayLen(dst, _) => {
+                self.set_type(*dst, IrType::Number);
+            }
+
+            IrOp::TypedLoad(dst, _, _, _, checked) => {
+                let ty = if *checked {
+                    IrType::Any
+                } else {
+                    IrType::Number
+                };
+                self.set_type(*dst, ty);
+            }
+
             // Function call returns Any (without more analysis)
             IrOp::Call(dst, _, _) => {
                 self.set_type(*dst, IrType::Any);
@@ -280,6 +305,7 @@ impl<'a> TypeChecker<'a> {
             | IrOp::SetProp(_, _, _)
             | IrOp::SetElement(_, _, _)
             | IrOp::ArrayPush(_, _)
+            | IrOp::TypedStore(_, _, _, _, _)
             | IrOp::DerefStore(_, _)
             | IrOp::EndBorrow(_)
             | IrOp::StructSetField(_, _, _)
@@ -334,10 +360,15 @@ impl<'a> TypeChecker<'a> {
             | IrOp::Shl(dst, _, _)
             | IrOp::Shr(dst, _, _)
             | IrOp::ShrU(dst, _, _)
-            | IrOp::Pow(dst, _, _) => {
+            | IrOp::Pow(dst, _, _)
+            | IrOp::Intrinsic(dst, _, _) => {
                 self.set_type(*dst, IrType::Number);
             }
 
+            IrOp::Simd(dst, kind, op, _) => {
+                self.set_type(*dst, op.result_type(*kind));
+            }
+
             // TypeOf always produces a string
             IrOp::TypeOf(dst, _) => {
                 self.set_type(*dst, IrType::String);
@@ -430,6 +461,26 @@ fn specialize_op(op: IrOp, types: &HashMap<ValueId, IrType>) -> IrOp {
             }
         }
 
+        // Converting a number is a no-op
+        IrOp::ToNum(dst, a) if get_type(a) == IrType::Number => IrOp::Copy(dst, a),
+
+        // Element access on a typed array reads unboxed storage. The bounds
+        // check stays until `opt::eliminate_bounds_checks` proves it redundant.
+        IrOp::GetElement(dst, arr, idx) => match get_type(arr) {
+            IrType::TypedArray(kind) => IrOp::TypedLoad(dst, arr, idx, kind, true),
+            _ => IrOp::GetElement(dst, arr, idx),
+        },
+
+        IrOp::SetElement(arr, idx, val) => match get_type(arr) {
+            IrType::TypedArray(kind) => IrOp::TypedStore(arr, idx, val, kind, true),
+            _ => IrOp::SetElement(arr, idx, val),
+        },
+
+        IrOp::GetProp(dst, arr, name) => match get_type(arr) {
+            IrType::TypedArray(_) if name == "length" => IrOp::TypedArrayLen(dst, arr),
+            _ => IrOp::GetProp(dst, arr, name),
+        },
+
         // All other operations pass through unchanged
         other => other,
     }
@@ -440,6 +491,13 @@ pub fn typecheck_function(func: &mut IrFunction) {
     let mut checker = TypeChecker::new(func);
     checker.infer();
     specialize_ops(func);
+
+    // In-bounds typed array loads are numbers: infer again so arithmetic on
+    // them specializes as well
+    if opt::eliminate_bounds_checks(func) {
+        TypeChecker::new(func).infer();
+        specialize_ops(func);
+    }
 }
 
 /// Run type inference and specialization on a module.
@@ -449,6 +507,62 @@ pub fn typecheck_module(module: &mut IrModule) {
     }
 }
 
+/// Record the front end's call-site instantiations in the mono cache, and
+/// seed the parameter types of a function called with only one set of
+/// concrete argument types. Run before `typecheck_module`.
+pub fn apply_mono_hints(module: &mut IrModule, hints: &[MonoHint]) {
+    let mut seeds: HashMap<usize, Option<Vec<IrType>>> = HashMap::new();
+    for hint in hints {
+        let Some(&idx) = module.function_names.get(&hint.function) else {
+            continue;
+        };
+        let types: Vec<IrType> = hint.params.iter().map(hint_type).collect();
+        module.get_or_create_mono(idx, types.clone());
+        let seed = (!types.contains(&IrType::Any)).then_some(types);
+        match seeds.get(&idx) {
+            None => {
+                seeds.insert(idx, seed);
+            }
+            Some(prev) if *prev != seed => {
+                seeds.insert(idx, None);
+            }
+            Some(_) => {}
+        }
+    }
+
+    for (idx, types) in seeds {
+        let (Some(types), Some(func)) = (types, module.functions.get_mut(idx)) else {
+            continue;
+        };
+        if func.params.len() != types.len() {
+            continue;
+        }
+        // Parameters are bound last to first, so `params` is in reverse
+        // source order while their values are allocated in source order
+        let count = types.len();
+        for (i, ty) in types.into_iter().enumerate() {
+            func.params[count - 1 - i].1 = ty.clone();
+            func.value_types.insert(ValueId(i as u32), ty.clone());
+            func.value_info
+                .insert(ValueId(i as u32), ValueInfo::new(ty));
+        }
+    }
+}
+
+/// IR type of a front-end argument type
+fn hint_type(ty: &Type) -> IrType {
+    match ty {
+        Type::Number => IrType::Number,
+        Type::String => IrType::String,
+        Type::Boolean => IrType::Boolean,
+        Type::Array(_) => IrType::Array,
+        Type::TypedArray(kind) => IrType::TypedArray(*kind),
+        Type::Object(_) | Type::Struct(_) | Type::Generic(..) => IrType::Object,
+        Type::Function(_) => IrType::Function,
+        _ => IrType::Any,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -510,6 +624,56 @@ mod tests {
         assert!(has_add_any, "String concat should remain AddAny");
     }
 
+    #[test]
+    fn test_mono_hints_seed_single_instantiation() {
+        let mut module = IrModule::new();
+        for name in ["add", "show"] {
+            let mut func = IrFunction::new(name.to_string());
+            let entry = func.alloc_block();
+            let a = func.alloc_value(IrType::Any);
+            let b = func.alloc_value(IrType::Any);
+            let c = func.alloc_value(IrType::Any);
+            func.params = vec![
+                ("b".to_string(), IrType::Any),
+                ("a".to_string(), IrType::Any),
+            ];
+            let block = func.block_mut(entry);
+            block.push(IrOp::AddAny(c, a, b));
+            block.terminate(Terminator::Return(Some(c)));
+            let idx = module.add_function(func);
+            module.function_names.insert(name.to_string(), idx);
+        }
+
+        let hint = |function: &str, params: Vec<Type>| MonoHint {
+            function: function.to_string(),
+            params,
+        };
+        apply_mono_hints(
+            &mut module,
+            &[
+                hint("add", vec![Type::Number, Type::Number]),
+                hint("show", vec![Type::Number, Type::Number]),
+                hint("show", vec![Type::String, Type::Number]),
+            ],
+        );
+        typecheck_module(&mut module);
+
+        assert_eq!(module.mono_cache.len(), 3);
+        let add = &module.functions[0];
+        assert_eq!(add.params[0].1, IrType::Number);
+        let ops = &add.blocks[0].ops;
+        assert!(ops.iter().any(|op| matches!(op, IrOp::AddNum(_, _, _))));
+        // Called two ways, so left dynamic
+        let show = &module.functions[1];
+        assert_eq!(show.params[0].1, IrType::Any);
+        assert!(
+            show.blocks[0]
+                .ops
+                .iter()
+                .any(|op| matches!(op, IrOp::AddAny(_, _, _)))
+        );
+    }
+
     #[test]
     fn test_type_meet() {
         assert_eq!(type_meet(IrType::Number, IrType::Number), IrType::Number);
@@ -517,4 +681,41 @@ mod tests {
         assert_eq!(type_meet(IrType::Never, IrType::Number), IrType::Number);
         assert_eq!(type_meet(IrType::Any, IrType::Number), IrType::Any);
     }
+
+    #[test]
+    fn test_typed_array_access_specialization() {
+        use crate::runtime::heap::ElementKind;
+
+        let mut func = IrFunction::new("test".to_string());
+        let entry = func.alloc_block();
+        let kind = ElementKind::Int32;
+
+        let param = func.alloc_value(IrType::TypedArray(kind));
+        let arr = func.alloc_value(IrType::Any);
+        let idx = func.alloc_value(IrType::Any);
+        let elem = func.alloc_value(IrType::Any);
+        let len = func.alloc_value(IrType::Any);
+
+        {
+            let block = func.block_mut(entry);
+            block.push(IrOp::StoreLocal(0, param));
+            // Loads of a slot bound once in the entry block keep its type
+            block.push(IrOp::LoadLocal(arr, 0));
+            block.push(IrOp::Const(idx, Literal::Number(0.0)));
+            block.push(IrOp::GetElement(elem, arr, idx));
+            block.push(IrOp::SetElement(arr, idx, elem));
+            block.push(IrOp::GetProp(len, arr, "length".to_string()));
+            block.terminate(Terminator::Return(Some(len)));
+        }
+
+        typecheck_function(&mut func);
+
+        assert_eq!(func.value_types.get(&arr), Some(&IrType::TypedArray(kind)));
+        let ops = &func.blocks[entry.0 as usize].ops;
+        assert!(
+            matches!(ops[3], IrOp::TypedLoad(d, a, i, k, true) if d == elem && a == arr && i == idx && k == kind)
+        );
+        assert!(matches!(ops[4], IrOp::TypedStore(a, _, _, k, true) if a == arr && k == kind));
+        assert!(matches!(ops[5], IrOp::TypedArrayLen(d, a) if d == len && a == arr));
+    }
 }
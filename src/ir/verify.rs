@@ -1,14 +1,21 @@
 //! IR verification pass.
 //!
 //! Validates that IR is well-formed before native code generation:
-//! - All used values are defined
-//! - All blocks have terminators
+//! - All used values are defined, by an operation or as a parameter
 //! - SSA property (each value defined exactly once)
-//! - Type consistency
+//! - Dominance (each use is reached only through its definition)
+//! - Block invariants (reachable blocks have terminators, jump targets and
+//!   phi entries exist, phis come first)
+//! - Type consistency with what the typecheck pass recorded
+//! - Direct calls name functions the module still has
 //! - Ownership validity (no use after move)
 //! - Borrow rules (no mutable + immutable overlap)
+//!
+//! `--verify-ir` runs everything. Debug builds also check the lowering's
+//! output and the result of every optimization pass (`check_module`,
+//! `check_pass`), leaving out ownership, which lowered IR doesn't track.
 
-use crate::ir::{BlockId, IrFunction, IrModule, IrOp, IrType, Terminator, ValueId};
+use crate::ir::{BlockId, IrFunction, IrModule, IrOp, IrType, Literal, Terminator, ValueId};
 use std::collections::{HashMap, HashSet};
 
 /// Verification error.
@@ -18,8 +25,12 @@ pub enum VerifyError {
     UndefinedValue(ValueId, BlockId),
     /// Value defined multiple times (violates SSA).
     MultipleDefinitions(ValueId),
+    /// Value used where its definition doesn't dominate the use.
+    NotDominated(ValueId, BlockId),
     /// Block missing terminator.
     MissingTerminator(BlockId),
+    /// Phi after another operation of its block.
+    MisplacedPhi(BlockId),
     /// Type mismatch in operation.
     TypeMismatch {
         op: String,
@@ -39,6 +50,8 @@ pub enum VerifyError {
     },
     /// Invalid local slot access.
     InvalidLocalSlot(u32),
+    /// Call to a function address the module has no function at.
+    MissingFunction(usize),
 }
 
 impl std::fmt::Display for VerifyError {
@@ -50,9 +63,19 @@ impl std::fmt::Display for VerifyError {
             VerifyError::MultipleDefinitions(val) => {
                 write!(f, "Value {} defined multiple times (SSA violation)", val)
             }
+            VerifyError::NotDominated(val, block) => {
+                write!(
+                    f,
+                    "Value {} used in block {} is not defined on every path to it",
+                    val, block
+                )
+            }
             VerifyError::MissingTerminator(block) => {
                 write!(f, "Block {} missing terminator", block)
             }
+            VerifyError::MisplacedPhi(block) => {
+                write!(f, "Phi after a non-phi operation in block {}", block)
+            }
             VerifyError::TypeMismatch { op, expected, got } => {
                 write!(
                     f,
@@ -79,12 +102,47 @@ impl std::fmt::Display for VerifyError {
             VerifyError::InvalidLocalSlot(slot) => {
                 write!(f, "Invalid local slot ${}", slot)
             }
+            VerifyError::MissingFunction(addr) => {
+                write!(f, "Call to function {} which is not in the module", addr)
+            }
         }
     }
 }
 
 impl std::error::Error for VerifyError {}
 
+/// A verification error and where it was found.
+#[derive(Debug)]
+pub struct LocatedError {
+    /// Name of the function.
+    pub function: String,
+    /// Block of the offending operation or terminator.
+    pub block: Option<BlockId>,
+    /// The offending operation or terminator, as printed in IR dumps.
+    pub op: Option<String>,
+    pub error: VerifyError,
+}
+
+impl std::fmt::Display for LocatedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.function.is_empty() {
+            write!(f, "<anonymous>")?;
+        } else {
+            write!(f, "{}", self.function)?;
+        }
+        if let Some(block) = self.block {
+            write!(f, " {}", block)?;
+        }
+        write!(f, ": {}", self.error)?;
+        if let Some(op) = &self.op {
+            write!(f, " (`{}`)", op)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for LocatedError {}
+
 /// IR verifier.
 pub struct Verifier<'a> {
     func: &'a IrFunction,
@@ -92,26 +150,52 @@ pub struct Verifier<'a> {
     defined: HashSet<ValueId>,
     /// Values that have been moved.
     moved: HashSet<ValueId>,
+    /// Blocks reachable from the entry.
+    reachable: HashSet<BlockId>,
+    /// Immediate dominator of each reachable block (the entry's is itself).
+    idom: HashMap<BlockId, BlockId>,
+    /// Whether to check ownership.
+    ownership: bool,
     /// Errors found.
-    errors: Vec<VerifyError>,
+    errors: Vec<LocatedError>,
 }
 
 impl<'a> Verifier<'a> {
     pub fn new(func: &'a IrFunction) -> Self {
+        let (reachable, idom) = dominators(func);
         Self {
             func,
             defined: HashSet::new(),
             moved: HashSet::new(),
+            reachable,
+            idom,
+            ownership: true,
             errors: Vec::new(),
         }
     }
 
+    /// Leave out the ownership checks.
+    pub fn without_ownership(mut self) -> Self {
+        self.ownership = false;
+        self
+    }
+
     /// Run all verification passes on the function.
-    pub fn verify(mut self) -> Result<(), Vec<VerifyError>> {
+    pub fn verify(self) -> Result<(), Vec<VerifyError>> {
+        self.verify_located()
+            .map_err(|errors| errors.into_iter().map(|e| e.error).collect())
+    }
+
+    /// Run all verification passes, keeping where each error was found.
+    pub fn verify_located(mut self) -> Result<(), Vec<LocatedError>> {
         self.verify_structure();
         self.verify_ssa();
         self.verify_control_flow();
-        self.verify_ownership();
+        self.verify_dominance();
+        self.verify_types();
+        if self.ownership {
+            self.verify_ownership();
+        }
 
         if self.errors.is_empty() {
             Ok(())
@@ -120,42 +204,98 @@ impl<'a> Verifier<'a> {
         }
     }
 
-    /// Verify basic structure: blocks have terminators, locals are valid.
+    fn report(&mut self, block: BlockId, op: Option<String>, error: VerifyError) {
+        self.errors.push(LocatedError {
+            function: self.func.name.clone(),
+            block: Some(block),
+            op,
+            error,
+        });
+    }
+
+    /// Verify basic structure: reachable blocks have terminators, phis come
+    /// first and name existing blocks, locals are valid.
     fn verify_structure(&mut self) {
-        for block in &self.func.blocks {
+        let func = self.func;
+        for block in &func.blocks {
             // Check for terminator
             if matches!(block.terminator, Terminator::Unreachable) && !self.is_dead_block(block.id)
             {
-                self.errors.push(VerifyError::MissingTerminator(block.id));
+                self.report(block.id, None, VerifyError::MissingTerminator(block.id));
+            }
+
+            let mut past_phis = false;
+            for op in &block.ops {
+                match op {
+                    IrOp::Phi(_, entries) => {
+                        if past_phis {
+                            self.report(
+                                block.id,
+                                Some(op.to_string()),
+                                VerifyError::MisplacedPhi(block.id),
+                            );
+                        }
+                        for (pred, _) in entries {
+                            if pred.0 as usize >= func.blocks.len() {
+                                self.report(
+                                    block.id,
+                                    Some(op.to_string()),
+                                    VerifyError::InvalidBlockTarget(block.id, *pred),
+                                );
+                            }
+                        }
+                    }
+                    IrOp::LoadLocal(_, slot) | IrOp::StoreLocal(slot, _)
+                        if *slot as usize >= func.locals.len() =>
+                    {
+                        past_phis = true;
+                        self.report(
+                            block.id,
+                            Some(op.to_string()),
+                            VerifyError::InvalidLocalSlot(*slot),
+                        );
+                    }
+                    _ => past_phis = true,
+                }
             }
         }
     }
 
     /// Verify SSA property: each value defined exactly once, used after definition.
     fn verify_ssa(&mut self) {
-        // Collect all definitions
-        let mut definitions: HashMap<ValueId, BlockId> = HashMap::new();
+        let func = self.func;
+        // Parameters are defined on entry
+        self.defined
+            .extend((0..func.params.len() as u32).map(ValueId));
 
-        for block in &self.func.blocks {
+        // Collect all definitions
+        let mut definitions: HashSet<ValueId> = self.defined.clone();
+        for block in &func.blocks {
             for op in &block.ops {
                 if let Some(dest) = op.dest() {
-                    if let std::collections::hash_map::Entry::Vacant(e) = definitions.entry(dest) {
-                        e.insert(block.id);
+                    if definitions.insert(dest) {
                         self.defined.insert(dest);
                     } else {
-                        self.errors.push(VerifyError::MultipleDefinitions(dest));
+                        self.report(
+                            block.id,
+                            Some(op.to_string()),
+                            VerifyError::MultipleDefinitions(dest),
+                        );
                     }
                 }
             }
         }
 
         // Check all uses have definitions
-        for block in &self.func.blocks {
+        for block in &func.blocks {
             for op in &block.ops {
                 for used in op.uses() {
                     if !self.defined.contains(&used) {
-                        self.errors
-                            .push(VerifyError::UndefinedValue(used, block.id));
+                        self.report(
+                            block.id,
+                            Some(op.to_string()),
+                            VerifyError::UndefinedValue(used, block.id),
+                        );
                     }
                 }
             }
@@ -163,8 +303,11 @@ impl<'a> Verifier<'a> {
             // Check terminator uses
             for used in block.terminator.uses() {
                 if !self.defined.contains(&used) {
-                    self.errors
-                        .push(VerifyError::UndefinedValue(used, block.id));
+                    self.report(
+                        block.id,
+                        Some(block.terminator.to_string()),
+                        VerifyError::UndefinedValue(used, block.id),
+                    );
                 }
             }
         }
@@ -172,24 +315,150 @@ impl<'a> Verifier<'a> {
 
     /// Verify control flow: all jump targets exist.
     fn verify_control_flow(&mut self) {
-        let block_ids: HashSet<_> = self.func.blocks.iter().map(|b| b.id).collect();
+        let func = self.func;
+        let block_ids: HashSet<_> = func.blocks.iter().map(|b| b.id).collect();
 
-        for block in &self.func.blocks {
+        for block in &func.blocks {
             for succ in block.terminator.successors() {
                 if !block_ids.contains(&succ) {
-                    self.errors
-                        .push(VerifyError::InvalidBlockTarget(block.id, succ));
+                    self.report(
+                        block.id,
+                        Some(block.terminator.to_string()),
+                        VerifyError::InvalidBlockTarget(block.id, succ),
+                    );
                 }
             }
         }
     }
 
+    /// Verify dominance: every use in a reachable block comes after its
+    /// definition in the same block, or in a block its definition's block
+    /// dominates. A phi's operand only needs to reach the end of the
+    /// predecessor it comes from.
+    fn verify_dominance(&mut self) {
+        let func = self.func;
+        let mut def_block: HashMap<ValueId, (BlockId, usize)> = HashMap::new();
+        for block in &func.blocks {
+            for (i, op) in block.ops.iter().enumerate() {
+                if let Some(dest) = op.dest() {
+                    def_block.entry(dest).or_insert((block.id, i));
+                }
+            }
+        }
+        // Whether `val` is available at operation `at` of `block`
+        // (`usize::MAX` for the end of the block)
+        let available = |val: ValueId, block: BlockId, at: usize| match def_block.get(&val) {
+            None => true, // a parameter, or undefined (reported by verify_ssa)
+            Some(&(def, i)) if def == block => i < at,
+            Some(&(def, _)) => self.dominates(def, block),
+        };
+
+        let mut errors = Vec::new();
+        for block in &func.blocks {
+            if !self.reachable.contains(&block.id) {
+                continue;
+            }
+            for (i, op) in block.ops.iter().enumerate() {
+                let uses: Vec<(ValueId, bool)> = match op {
+                    IrOp::Phi(_, entries) => entries
+                        .iter()
+                        .filter(|(pred, _)| self.reachable.contains(pred))
+                        .map(|(pred, val)| (*val, available(*val, *pred, usize::MAX)))
+                        .collect(),
+                    _ => op
+                        .uses()
+                        .into_iter()
+                        .map(|val| (val, available(val, block.id, i)))
+                        .collect(),
+                };
+                for (val, ok) in uses {
+                    if !ok {
+                        errors.push((block.id, op.to_string(), val));
+                    }
+                }
+            }
+            for val in block.terminator.uses() {
+                if !available(val, block.id, usize::MAX) {
+                    errors.push((block.id, block.terminator.to_string(), val));
+                }
+            }
+        }
+        for (block, op, val) in errors {
+            self.report(block, Some(op), VerifyError::NotDominated(val, block));
+        }
+    }
+
+    /// Verify types: operations the typecheck pass specialized still have
+    /// operands of the type they were specialized for, and returned values
+    /// match the return type. `any` matches everything.
+    fn verify_types(&mut self) {
+        let func = self.func;
+        let ty = |val: &ValueId| func.value_types.get(val).filter(|ty| ty.is_concrete());
+        let mut errors = Vec::new();
+        for block in &func.blocks {
+            for op in &block.ops {
+                let (expected, operands): (IrType, Vec<ValueId>) = match op {
+                    IrOp::AddNum(_, a, b)
+                    | IrOp::SubNum(_, a, b)
+                    | IrOp::MulNum(_, a, b)
+                    | IrOp::DivNum(_, a, b)
+                    | IrOp::ModNum(_, a, b) => (IrType::Number, vec![*a, *b]),
+                    IrOp::NegNum(_, a) => (IrType::Number, vec![*a]),
+                    IrOp::Intrinsic(_, _, args) => (IrType::Number, args.clone()),
+                    IrOp::Simd(_, kind, op, args) => match op.operand_type(*kind) {
+                        Some(expected) => (expected, args.clone()),
+                        None => continue,
+                    },
+                    IrOp::TypedLoad(_, arr, _, kind, _) | IrOp::TypedStore(arr, _, _, kind, _) => {
+                        (IrType::TypedArray(*kind), vec![*arr])
+                    }
+                    _ => continue,
+                };
+                for operand in operands {
+                    if let Some(got) = ty(&operand)
+                        && *got != expected
+                    {
+                        errors.push((
+                            block.id,
+                            op.to_string(),
+                            VerifyError::TypeMismatch {
+                                op: op.to_string(),
+                                expected: expected.clone(),
+                                got: got.clone(),
+                            },
+                        ));
+                    }
+                }
+            }
+
+            if let Terminator::Return(Some(val)) = &block.terminator
+                && func.return_ty.is_concrete()
+                && func.return_ty != IrType::Void
+                && let Some(got) = ty(val)
+                && *got != func.return_ty
+            {
+                errors.push((
+                    block.id,
+                    block.terminator.to_string(),
+                    VerifyError::ReturnTypeMismatch {
+                        expected: func.return_ty.clone(),
+                        got: Some(got.clone()),
+                    },
+                ));
+            }
+        }
+        for (block, op, error) in errors {
+            self.report(block, Some(op), error);
+        }
+    }
+
     /// Verify ownership: no use after move, valid borrows.
     fn verify_ownership(&mut self) {
+        let func = self.func;
         // Track moved values through the function
         let mut moved_at: HashMap<ValueId, BlockId> = HashMap::new();
 
-        for block in &self.func.blocks {
+        for block in &func.blocks {
             for op in &block.ops {
                 // Check uses are not moved
                 for used in op.uses() {
@@ -197,7 +466,11 @@ impl<'a> Verifier<'a> {
                         // Only report if moved in a predecessor block
                         // (same-block moves are handled by lowering order)
                         if move_block != block.id {
-                            self.errors.push(VerifyError::UseAfterMove(used, block.id));
+                            self.report(
+                                block.id,
+                                Some(op.to_string()),
+                                VerifyError::UseAfterMove(used, block.id),
+                            );
                         }
                     }
                 }
@@ -206,7 +479,7 @@ impl<'a> Verifier<'a> {
                 if Self::is_move_op(op) {
                     for used in op.uses() {
                         // Only move reference types
-                        if let Some(ty) = self.func.value_types.get(&used)
+                        if let Some(ty) = func.value_types.get(&used)
                             && ty.is_reference()
                         {
                             moved_at.insert(used, block.id);
@@ -218,17 +491,22 @@ impl<'a> Verifier<'a> {
         }
     }
 
-    /// Check if a block is dead (unreachable).
+    /// Check if a block is dead (unreachable from the entry).
     fn is_dead_block(&self, block_id: BlockId) -> bool {
-        // Entry block is never dead
-        if block_id.0 == 0 {
-            return false;
-        }
+        !self.reachable.contains(&block_id)
+    }
 
-        // A block is dead if it has no predecessors and isn't the entry
-        self.func.blocks[block_id.0 as usize]
-            .predecessors
-            .is_empty()
+    /// Whether every path from the entry to `b` goes through `a`.
+    fn dominates(&self, a: BlockId, mut b: BlockId) -> bool {
+        loop {
+            if a == b {
+                return true;
+            }
+            match self.idom.get(&b) {
+                Some(&parent) if parent != b => b = parent,
+                _ => return false,
+            }
+        }
     }
 
     /// Check if an operation performs a move.
@@ -245,19 +523,114 @@ impl<'a> Verifier<'a> {
     }
 }
 
+/// Blocks reachable from the entry, and the immediate dominator of each
+/// (Cooper, Harvey and Kennedy's iterative algorithm, over the terminators
+/// rather than the recorded predecessors, which passes may leave stale).
+fn dominators(func: &IrFunction) -> (HashSet<BlockId>, HashMap<BlockId, BlockId>) {
+    let count = func.blocks.len();
+    let exists = |b: &BlockId| (b.0 as usize) < count;
+    if count == 0 {
+        return (HashSet::new(), HashMap::new());
+    }
+
+    // Reverse postorder from the entry
+    let entry = func.entry_block();
+    let mut postorder = Vec::new();
+    let mut visited = HashSet::from([entry]);
+    let mut stack = vec![(entry, func.successors_of(entry))];
+    while let Some((block, pending)) = stack.last_mut() {
+        let block = *block;
+        match pending.pop() {
+            Some(succ) if exists(&succ) && visited.insert(succ) => {
+                stack.push((succ, func.successors_of(succ)));
+            }
+            Some(_) => {}
+            None => {
+                postorder.push(block);
+                stack.pop();
+            }
+        }
+    }
+    let order: HashMap<BlockId, usize> = postorder
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, b)| (*b, i))
+        .collect();
+    let mut preds: HashMap<BlockId, Vec<BlockId>> = HashMap::new();
+    for &block in &postorder {
+        for succ in func.successors_of(block) {
+            if exists(&succ) {
+                preds.entry(succ).or_default().push(block);
+            }
+        }
+    }
+
+    let mut idom = HashMap::from([(entry, entry)]);
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for &block in postorder.iter().rev().skip(1) {
+            let mut new_idom: Option<BlockId> = None;
+            for &pred in preds.get(&block).into_iter().flatten() {
+                if !idom.contains_key(&pred) {
+                    continue;
+                }
+                new_idom = Some(match new_idom {
+                    None => pred,
+                    Some(mut a) => {
+                        let mut b = pred;
+                        while a != b {
+                            while order[&a] > order[&b] {
+                                a = idom[&a];
+                            }
+                            while order[&b] > order[&a] {
+                                b = idom[&b];
+                            }
+                        }
+                        a
+                    }
+                });
+            }
+            if let Some(new_idom) = new_idom
+                && idom.get(&block) != Some(&new_idom)
+            {
+                idom.insert(block, new_idom);
+                changed = true;
+            }
+        }
+    }
+    (visited, idom)
+}
+
 /// Verify a single function.
 pub fn verify_function(func: &IrFunction) -> Result<(), Vec<VerifyError>> {
     Verifier::new(func).verify()
 }
 
 /// Verify all functions in a module.
-pub fn verify_module(module: &IrModule) -> Result<(), Vec<VerifyError>> {
+pub fn verify_module(module: &IrModule) -> Result<(), Vec<LocatedError>> {
+    collect(module, |func| Verifier::new(func).verify_located())
+}
+
+/// Check a module's functions are well-formed, leaving out ownership.
+pub fn check_module(module: &IrModule) -> Result<(), Vec<LocatedError>> {
+    collect(module, |func| {
+        Verifier::new(func).without_ownership().verify_located()
+    })
+}
+
+fn collect(
+    module: &IrModule,
+    verify: impl Fn(&IrFunction) -> Result<(), Vec<LocatedError>>,
+) -> Result<(), Vec<LocatedError>> {
     let mut all_errors = Vec::new();
 
     for func in &module.functions {
-        if let Err(errors) = verify_function(func) {
+        if let Err(errors) = verify(func) {
             all_errors.extend(errors);
         }
+        all_errors.extend(missing_callees(module, func));
     }
 
     if all_errors.is_empty() {
@@ -267,6 +640,85 @@ pub fn verify_module(module: &IrModule) -> Result<(), Vec<VerifyError>> {
     }
 }
 
+/// Direct calls in `func` (of a constant typed `fn`) to an address no
+/// function of `module` has, as when dead function elimination dropped a
+/// function still called
+fn missing_callees(module: &IrModule, func: &IrFunction) -> Vec<LocatedError> {
+    let addresses: HashMap<ValueId, usize> = func
+        .blocks
+        .iter()
+        .flat_map(|block| block.ops.iter())
+        .filter_map(|op| match op {
+            IrOp::Const(dst, Literal::Number(n))
+                if func.value_types.get(dst) == Some(&IrType::Function) =>
+            {
+                Some((*dst, *n as usize))
+            }
+            _ => None,
+        })
+        .collect();
+    let mut errors = Vec::new();
+    for block in &func.blocks {
+        for op in &block.ops {
+            if let IrOp::Call(_, callee, _) = op
+                && let Some(&addr) = addresses.get(callee)
+                && !module.function_addrs.contains_key(&addr)
+            {
+                errors.push(LocatedError {
+                    function: func.name.clone(),
+                    block: Some(block.id),
+                    op: Some(op.to_string()),
+                    error: VerifyError::MissingFunction(addr),
+                });
+            }
+        }
+    }
+    errors
+}
+
+/// After optimization pass `pass`: in debug builds, panic if it made a
+/// well-formed `func` ill-formed. Returns whether `func` is (still)
+/// well-formed; always `false` in release builds, which don't check.
+pub fn check_pass(pass: &str, func: &IrFunction, was_valid: bool) -> bool {
+    if !cfg!(debug_assertions) || !was_valid {
+        return false;
+    }
+    match Verifier::new(func).without_ownership().verify_located() {
+        Ok(()) => true,
+        Err(errors) => panic!("{} produced invalid IR:\n{}", pass, list(&errors)),
+    }
+}
+
+/// `check_pass` for a pass over the whole module.
+pub fn check_module_pass(pass: &str, module: &IrModule, was_valid: bool) -> bool {
+    if !cfg!(debug_assertions) || !was_valid {
+        return false;
+    }
+    match check_module(module) {
+        Ok(()) => true,
+        Err(errors) => panic!("{} produced invalid IR:\n{}", pass, list(&errors)),
+    }
+}
+
+/// Whether passes over `func` are checked: in debug builds, when it is
+/// well-formed to begin with.
+pub fn checks_passes(func: &IrFunction) -> bool {
+    cfg!(debug_assertions)
+        && Verifier::new(func)
+            .without_ownership()
+            .verify_located()
+            .is_ok()
+}
+
+/// One `  - error` line per error.
+pub fn list(errors: &[LocatedError]) -> String {
+    errors
+        .iter()
+        .map(|e| format!("  - {}", e))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -348,4 +800,121 @@ mod tests {
                 .any(|e| matches!(e, VerifyError::MultipleDefinitions(_)))
         );
     }
+
+    /// bb0 branches to bb1 (defining v1) and bb2 (defining v2), which both
+    /// jump to bb3.
+    fn diamond(merge: IrOp, result: ValueId) -> IrFunction {
+        let mut func = IrFunction::new("pick".to_string());
+        let blocks: Vec<BlockId> = (0..4).map(|_| func.alloc_block()).collect();
+        let v: Vec<ValueId> = (0..3).map(|_| func.alloc_value(IrType::Number)).collect();
+        let cond = func.alloc_value(IrType::Boolean);
+
+        func.block_mut(blocks[0])
+            .push(IrOp::Const(cond, Literal::Boolean(true)));
+        func.block_mut(blocks[0])
+            .terminate(Terminator::Branch(cond, blocks[1], blocks[2]));
+        func.block_mut(blocks[1])
+            .push(IrOp::Const(v[1], Literal::Number(1.0)));
+        func.block_mut(blocks[1])
+            .terminate(Terminator::Jump(blocks[3]));
+        func.block_mut(blocks[2])
+            .push(IrOp::Const(v[2], Literal::Number(2.0)));
+        func.block_mut(blocks[2])
+            .terminate(Terminator::Jump(blocks[3]));
+        func.block_mut(blocks[3]).push(merge);
+        func.block_mut(blocks[3])
+            .terminate(Terminator::Return(Some(result)));
+        func
+    }
+
+    #[test]
+    fn test_verify_dominance() {
+        // Returning v1 after the merge uses it on the path through bb2 too
+        let func = diamond(IrOp::Copy(ValueId(0), ValueId(1)), ValueId(0));
+        let errors = verify_function(&func).unwrap_err();
+        assert!(errors.iter().any(|e| matches!(
+            e,
+            VerifyError::NotDominated(v, b) if v.0 == 1 && b.0 == 3
+        )));
+
+        // A phi only needs each operand at the end of its predecessor
+        let phi = IrOp::Phi(
+            ValueId(0),
+            vec![(BlockId(1), ValueId(1)), (BlockId(2), ValueId(2))],
+        );
+        assert!(verify_function(&diamond(phi, ValueId(0))).is_ok());
+    }
+
+    #[test]
+    fn test_errors_name_function_and_operation() {
+        let mut module = IrModule::new();
+        let mut func = IrFunction::new("shout".to_string());
+        func.params.push(("s".to_string(), IrType::String));
+        let entry = func.alloc_block();
+        let s = func.alloc_value(IrType::String);
+        let doubled = func.alloc_value(IrType::Number);
+        {
+            let block = func.block_mut(entry);
+            block.push(IrOp::AddNum(doubled, s, s));
+            block.push(IrOp::Phi(ValueId(9), vec![]));
+            block.terminate(Terminator::Return(Some(doubled)));
+        }
+        module.add_function(func);
+
+        let errors = verify_module(&module).unwrap_err();
+        // The parameter counts as defined
+        assert!(
+            !errors
+                .iter()
+                .any(|e| matches!(e.error, VerifyError::UndefinedValue(..)))
+        );
+        assert!(errors.iter().any(|e| matches!(
+            &e.error,
+            VerifyError::TypeMismatch {
+                got: IrType::String,
+                ..
+            }
+        )));
+        assert!(
+            errors
+                .iter()
+                .any(|e| matches!(e.error, VerifyError::MisplacedPhi(_)))
+        );
+        let message = errors
+            .iter()
+            .find(|e| matches!(e.error, VerifyError::TypeMismatch { .. }))
+            .unwrap()
+            .to_string();
+        assert!(message.starts_with("shout bb0: "), "{}", message);
+        assert!(message.contains("(`v1 = add.num v0, v0`)"), "{}", message);
+    }
+
+    #[test]
+    fn test_call_to_missing_function_is_rejected() {
+        let mut module = IrModule::new();
+        let mut func = IrFunction::new("main".to_string());
+        let entry = func.alloc_block();
+        let callee = func.alloc_value(IrType::Function);
+        let number = func.alloc_value(IrType::Number);
+        let result = func.alloc_value(IrType::Any);
+        let other = func.alloc_value(IrType::Any);
+        {
+            let block = func.block_mut(entry);
+            block.push(IrOp::Const(callee, Literal::Number(7.0)));
+            block.push(IrOp::Call(result, callee, vec![]));
+            // Calling a plain number is a runtime error, not bad IR
+            block.push(IrOp::Const(number, Literal::Number(3.0)));
+            block.push(IrOp::Call(other, number, vec![]));
+            block.terminate(Terminator::Return(None));
+        }
+        module.add_function(func);
+
+        let errors = check_module(&module).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(errors[0].error, VerifyError::MissingFunction(7)));
+        assert_eq!(
+            errors[0].to_string(),
+            "main bb0: Call to function 7 which is not in the module (`v2 = call v0()`)"
+        );
+    }
 }
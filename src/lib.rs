@@ -35,12 +35,20 @@ pub mod build;
 #[cfg(feature = "vm_interop")]
 pub mod compiler;
 #[cfg(feature = "vm_interop")]
+pub mod driver;
+#[cfg(feature = "vm_interop")]
 pub mod ir;
 #[cfg(feature = "vm_interop")]
 pub mod loader;
 #[cfg(feature = "vm_interop")]
+pub mod manifest;
+#[cfg(feature = "vm_interop")]
+pub mod platform;
+#[cfg(feature = "vm_interop")]
 pub mod stdlib;
 #[cfg(feature = "vm_interop")]
+pub mod transform;
+#[cfg(feature = "vm_interop")]
 pub mod types;
 #[cfg(feature = "vm_interop")]
 pub mod vm;
@@ -0,0 +1,229 @@
+//! Debug section of bytecode files
+//!
+//! Bytecode deployed without its sources can carry what error reporting
+//! needs to stay readable: function names, a line table and, optionally, the
+//! source text itself (plain or deflate-compressed). The section sits after
+//! the last instruction and is found from the end of the file:
+//!
+//! ```text
+//! section:  flags u8 | file string
+//!           | varint count, (varint instruction, string name)*
+//!           | varint count, (varint instruction, varint line)*
+//!           | [varint length, source bytes]    (if FLAG_SOURCE)
+//! trailer:  u32 LE section length | "TSDI"
+//! ```
+//!
+//! Positions are instruction indices rather than byte offsets, so anything
+//! that counts the instructions it emits can write the section. Version 2
+//! files (see `encoder`) hold the section inside the container instead, with
+//! no trailer.
+
+use super::decoder::{BytecodeDecoder, CONTAINER_VERSION, LoaderError, MAGIC};
+use flate2::Compression;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use std::io::{Read, Write};
+
+/// Marks the end of a file that carries a debug section
+pub const DEBUG_MAGIC: &[u8; 4] = b"TSDI";
+const TRAILER_LEN: usize = 8;
+
+const FLAG_SOURCE: u8 = 1;
+const FLAG_COMPRESSED: u8 = 2;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DebugInfo {
+    /// Path of the source the program was compiled from
+    pub file: String,
+    /// (entry instruction, name) of each named function
+    pub functions: Vec<(usize, String)>,
+    /// (first instruction, 1-based line) of each statement, by instruction
+    pub lines: Vec<(usize, u32)>,
+    pub source: Option<String>,
+}
+
+/// Split a bytecode file into its program and its debug section, if any.
+/// Version 2 files keep their debug section inside and are not split.
+pub fn split(bytes: &[u8]) -> (&[u8], Option<&[u8]>) {
+    if bytes.starts_with(MAGIC) && bytes.get(4) == Some(&CONTAINER_VERSION) {
+        return (bytes, None);
+    }
+    let Some(body) = bytes.len().checked_sub(TRAILER_LEN) else {
+        return (bytes, None);
+    };
+    if &bytes[body + 4..] != DEBUG_MAGIC {
+        return (bytes, None);
+    }
+    let len = u32::from_le_bytes(bytes[body..body + 4].try_into().unwrap()) as usize;
+    match body.checked_sub(len) {
+        Some(start) => (&bytes[..start], Some(&bytes[start..body])),
+        None => (bytes, None),
+    }
+}
+
+impl DebugInfo {
+    /// The section plus its trailer, ready to append to a program
+    pub fn encode(&self, compress: bool) -> Vec<u8> {
+        let mut out = self.encode_section(compress);
+        let len = out.len() as u32;
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(DEBUG_MAGIC);
+        out
+    }
+
+    /// The section alone, as version 2 files carry it
+    pub(super) fn encode_section(&self, compress: bool) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut flags = 0;
+        if self.source.is_some() {
+            flags |= FLAG_SOURCE;
+            if compress {
+                flags |= FLAG_COMPRESSED;
+            }
+        }
+        out.push(flags);
+        write_string(&mut out, &self.file);
+        write_varint(&mut out, self.functions.len() as u64);
+        for (ip, name) in &self.functions {
+            write_varint(&mut out, *ip as u64);
+            write_string(&mut out, name);
+        }
+        write_varint(&mut out, self.lines.len() as u64);
+        for &(ip, line) in &self.lines {
+            write_varint(&mut out, ip as u64);
+            write_varint(&mut out, line as u64);
+        }
+        if let Some(source) = &self.source {
+            let bytes = if compress {
+                let mut encoder = DeflateEncoder::new(Vec::new(), Compression::best());
+                encoder
+                    .write_all(source.as_bytes())
+                    .expect("deflating into memory cannot fail");
+                encoder.finish().expect("deflating into memory cannot fail")
+            } else {
+                source.as_bytes().to_vec()
+            };
+            write_varint(&mut out, bytes.len() as u64);
+            out.extend_from_slice(&bytes);
+        }
+        out
+    }
+
+    /// Parse a section as returned by `split`
+    pub fn decode(section: &[u8]) -> Result<Self, LoaderError> {
+        let mut reader = BytecodeDecoder::over(section);
+        let flags = reader.read_u8()?;
+        let file = reader.read_string()?;
+
+        let count = reader.read_varint()?;
+        let mut functions = Vec::new();
+        for _ in 0..count {
+            let ip = reader.read_varint()? as usize;
+            functions.push((ip, reader.read_string()?));
+        }
+        let count = reader.read_varint()?;
+        let mut lines = Vec::new();
+        for _ in 0..count {
+            let ip = reader.read_varint()? as usize;
+            lines.push((ip, reader.read_varint()? as u32));
+        }
+
+        let source = if flags & FLAG_SOURCE != 0 {
+            let len = usize::try_from(reader.read_varint()?).unwrap_or(usize::MAX);
+            let bytes = reader.take(len, "source bytes")?;
+            let bytes = if flags & FLAG_COMPRESSED != 0 {
+                let mut inflated = Vec::new();
+                DeflateDecoder::new(bytes)
+                    .read_to_end(&mut inflated)
+                    .map_err(LoaderError::Compression)?;
+                inflated
+            } else {
+                bytes.to_vec()
+            };
+            Some(String::from_utf8(bytes).map_err(LoaderError::InvalidUtf8)?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            file,
+            functions,
+            lines,
+            source,
+        })
+    }
+
+    /// Shift every instruction index, for a program loaded at `base`
+    pub fn rebase(&mut self, base: usize) {
+        for (ip, _) in &mut self.functions {
+            *ip += base;
+        }
+        for (ip, _) in &mut self.lines {
+            *ip += base;
+        }
+    }
+
+    /// Name of the function whose code starts at `address`
+    pub fn function_name(&self, address: usize) -> Option<&str> {
+        self.functions
+            .iter()
+            .find(|(ip, _)| *ip == address)
+            .map(|(_, name)| name.as_str())
+    }
+
+    /// Line of the statement that instruction `ip` belongs to
+    pub fn line_at(&self, ip: usize) -> Option<u32> {
+        let after = self.lines.partition_point(|&(start, _)| start <= ip);
+        after.checked_sub(1).map(|i| self.lines[i].1)
+    }
+
+    /// Text of a 1-based source line, when the source is embedded
+    pub fn source_line(&self, line: u32) -> Option<&str> {
+        let index = (line as usize).checked_sub(1)?;
+        self.source.as_deref()?.lines().nth(index)
+    }
+}
+
+pub(super) fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let mut byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value != 0 {
+            byte |= 0x80;
+        }
+        out.push(byte);
+        if value == 0 {
+            break;
+        }
+    }
+}
+
+pub(super) fn write_string(out: &mut Vec<u8>, s: &str) {
+    write_varint(out, s.len() as u64);
+    out.extend_from_slice(s.as_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_with_compressed_source() {
+        let info = DebugInfo {
+            file: "app.ot".to_string(),
+            functions: vec![(3, "main".to_string())],
+            lines: vec![(0, 1), (3, 2), (7, 4)],
+            source: Some("let a = 1;\nfunction main() {\n\n  throw a;\n}\n".to_string()),
+        };
+        for compress in [false, true] {
+            let mut file = vec![0x01, 0x02];
+            file.extend(info.encode(compress));
+            let (program, section) = split(&file);
+            assert_eq!(program, &[0x01, 0x02]);
+            assert_eq!(DebugInfo::decode(section.unwrap()).unwrap(), info);
+        }
+        assert_eq!(info.line_at(8), Some(4));
+        assert_eq!(info.source_line(4), Some("  throw a;"));
+        assert_eq!(split(&[0x01, 0x02]), (&[0x01, 0x02][..], None));
+    }
+}
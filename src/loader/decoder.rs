@@ -1,26 +1,45 @@
 //! Bytecode decoder for loading binary files produced by bootstrap/emitter.ot
+//! (version 3) and by `oitec --emit-bc` (version 2, see `encoder`)
 //!
-//! The binary format uses:
+//! The version 3 format uses:
 //! - u8 for opcodes and small values
 //! - LEB128 (varint) for variable-length integers
 //! - Little-endian u32 for addresses
 //! - Little-endian f64 for floating point numbers
 //! - Varint-prefixed UTF-8 for strings
+//!
+//! Version 1 is the same stream except that LOAD_ARG and LOAD_REST_ARGS take
+//! a single-byte index. Version 1 files, and files without a header, are
+//! still read that way.
 
+use super::debug_info::{self, DebugInfo};
 use crate::vm::opcodes::OpCode;
+use crate::vm::startup_snapshot::Reader;
 use crate::vm::value::JsValue;
 use std::collections::HashMap;
 
 /// Magic bytes for TSCL bytecode files
 pub const MAGIC: &[u8; 4] = b"TSCL";
-/// Current bytecode format version
-pub const VERSION: u8 = 1;
+/// Version of the instruction stream bootstrap/emitter.ot writes
+pub const VERSION: u8 = 3;
+/// Earlier instruction stream, with one-byte argument indices
+pub const STREAM_V1: u8 = 1;
+/// Version of the container `encoder` writes. Both formats share one
+/// numbering, so the next version of either is 4.
+pub const CONTAINER_VERSION: u8 = 2;
+/// Container flag: a debug section follows the code
+pub const FLAG_DEBUG: u8 = 1;
 
 /// Errors that can occur during bytecode loading
 #[derive(Debug)]
 pub enum LoaderError {
-    /// Unexpected end of file
-    UnexpectedEof,
+    /// The file ended in the middle of a value
+    UnexpectedEof {
+        /// What was being read (`"u32 address"`, `"string bytes"`, ...)
+        reading: &'static str,
+        needed: usize,
+        available: usize,
+    },
     /// Invalid opcode byte
     InvalidOpcode(u8),
     /// Invalid type tag for PUSH instruction
@@ -33,39 +52,315 @@ pub enum LoaderError {
     UnsupportedVersion(u8),
     /// Varint overflow (too many continuation bytes)
     VarintOverflow,
-    /// Address not found in mapping (internal error)
+    /// A jump, closure or handler address that isn't the start of an instruction
     AddressNotFound(u32),
+    /// Embedded source that does not inflate
+    Compression(std::io::Error),
+    /// The container's contents don't match its checksum
+    ChecksumMismatch { expected: u32, found: u32 },
+    /// Container flags this build doesn't know
+    UnsupportedFlags(u8),
+    /// A version 2 code section that doesn't decode
+    InvalidCode(String),
 }
 
 impl std::fmt::Display for LoaderError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            LoaderError::UnexpectedEof => write!(f, "Unexpected end of file"),
-            LoaderError::InvalidOpcode(op) => write!(f, "Invalid opcode: {}", op),
-            LoaderError::InvalidTypeTag(tag) => write!(f, "Invalid type tag: {}", tag),
+            LoaderError::UnexpectedEof {
+                reading,
+                needed,
+                available,
+            } => write!(
+                f,
+                "Unexpected end of file reading {}: needed {} byte(s), {} left",
+                reading, needed, available
+            ),
+            LoaderError::InvalidOpcode(op) => write!(
+                f,
+                "Invalid opcode: expected 0-{} or 255, found {}",
+                LAST_OPCODE, op
+            ),
+            LoaderError::InvalidTypeTag(tag) => {
+                write!(f, "Invalid PUSH type tag: expected 0-5, found {}", tag)
+            }
             LoaderError::InvalidUtf8(e) => write!(f, "Invalid UTF-8: {}", e),
             LoaderError::InvalidMagic => write!(f, "Invalid magic bytes (not a TSCL file)"),
-            LoaderError::UnsupportedVersion(v) => write!(f, "Unsupported bytecode version: {}", v),
+            LoaderError::UnsupportedVersion(v) => {
+                write!(
+                    f,
+                    "Unsupported bytecode version: {} (this build reads versions {} to {})",
+                    v, STREAM_V1, VERSION
+                )?;
+                if *v > VERSION {
+                    write!(f, "; the file was written by a newer oitec")?;
+                }
+                Ok(())
+            }
             LoaderError::VarintOverflow => write!(f, "Varint overflow"),
             LoaderError::AddressNotFound(addr) => {
-                write!(f, "Address {} not found in mapping", addr)
+                write!(f, "Address {:#x} is not the start of an instruction", addr)
             }
+            LoaderError::Compression(e) => write!(f, "Invalid compressed source: {}", e),
+            LoaderError::ChecksumMismatch { expected, found } => write!(
+                f,
+                "Checksum mismatch: the file records {:08x} but its contents give {:08x} (corrupt or truncated)",
+                expected, found
+            ),
+            LoaderError::UnsupportedFlags(flags) => {
+                write!(f, "Unsupported container flags: {:#04x}", flags)
+            }
+            LoaderError::InvalidCode(e) => write!(f, "Invalid code section: {}", e),
         }
     }
 }
 
 impl std::error::Error for LoaderError {}
 
+/// The instruction a decode error belongs to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InstructionContext {
+    /// Index of the instruction in the decoded program
+    pub index: usize,
+    /// Byte offset of its opcode
+    pub offset: usize,
+    pub opcode: u8,
+}
+
+/// A `LoaderError` with where in the file it happened
+#[derive(Debug)]
+pub struct DecodeError {
+    /// Byte offset at which decoding failed
+    pub offset: usize,
+    pub instruction: Option<InstructionContext>,
+    pub kind: LoaderError,
+}
+
+impl std::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "at byte {:#x}", self.offset)?;
+        if let Some(instr) = &self.instruction {
+            write!(
+                f,
+                " (instruction #{} at {:#x}, opcode {} {})",
+                instr.index,
+                instr.offset,
+                instr.opcode,
+                opcode_name(instr.opcode).unwrap_or("<unknown>")
+            )?;
+        }
+        write!(f, ": {}", self.kind)
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// Highest opcode byte below HALT (255)
+const LAST_OPCODE: u8 = 72;
+
+/// Emitter name of an opcode byte (bootstrap/emitter.ot)
+pub fn opcode_name(opcode: u8) -> Option<&'static str> {
+    const NAMES: [&str; LAST_OPCODE as usize + 1] = [
+        "LOAD_THIS",
+        "PUSH",
+        "ADD",
+        "SUB",
+        "MUL",
+        "DIV",
+        "PRINT",
+        "POP",
+        "STORE",
+        "LOAD",
+        "DROP",
+        "CALL",
+        "RETURN",
+        "JUMP",
+        "NEW_OBJECT",
+        "SET_PROP",
+        "GET_PROP",
+        "DUP",
+        "EQ",
+        "EQ_EQ",
+        "NE",
+        "NE_EQ",
+        "LT",
+        "LT_EQ",
+        "GT",
+        "GT_EQ",
+        "MOD",
+        "AND",
+        "OR",
+        "NOT",
+        "NEG",
+        "NEW_ARRAY",
+        "STORE_ELEMENT",
+        "LOAD_ELEMENT",
+        "JUMP_IF_FALSE",
+        "",
+        "BIN_MOD",
+        "BIN_POW",
+        "BIN_EQ",
+        "BIN_EQ_EQ",
+        "BIN_NE",
+        "BIN_NE_EQ",
+        "BIN_LT",
+        "BIN_LT_EQ",
+        "BIN_GT",
+        "BIN_GT_EQ",
+        "BIN_SHL",
+        "BIN_SHR",
+        "BIN_USHR",
+        "BIN_BIT_AND",
+        "BIN_XOR",
+        "BIN_BIT_OR",
+        "BIN_AND",
+        "BIN_OR",
+        "CALL_METHOD",
+        "REQUIRE",
+        "MAKE_CLOSURE",
+        "CONSTRUCT",
+        "STORE_LOCAL",
+        "LOAD_LOCAL",
+        "SWAP",
+        "TYPEOF",
+        "THROW",
+        "SETUP_TRY",
+        "POP_TRY",
+        "GET_PROP_COMPUTED",
+        "SET_PROP_COMPUTED",
+        "ARRAY_PUSH",
+        "ARRAY_SPREAD",
+        "OBJECT_SPREAD",
+        "LET",
+        "LOAD_ARG",
+        "LOAD_REST_ARGS",
+    ];
+    match opcode {
+        255 => Some("HALT"),
+        _ => NAMES
+            .get(opcode as usize)
+            .copied()
+            .filter(|name| !name.is_empty()),
+    }
+}
+
+/// Header fields and decode outcome of a bytecode file, for `--inspect-bc`
+pub struct BytecodeInfo {
+    pub size: usize,
+    /// `None` for legacy files without the TSCL header
+    pub version: Option<u8>,
+    pub reserved: [u8; 3],
+    pub program: Result<Vec<OpCode>, DecodeError>,
+    /// Trailing debug section, if the file has one
+    pub debug: Option<Result<DebugInfo, LoaderError>>,
+}
+
+/// The contents of a bytecode file
+#[derive(Debug, Clone, Default)]
+pub struct BytecodeFile {
+    /// (entry instruction, name) of each named function
+    pub functions: Vec<(usize, String)>,
+    pub program: Vec<OpCode>,
+    /// Line table and source; its function list is the function table
+    pub debug: Option<DebugInfo>,
+}
+
 /// Bytecode decoder that reads binary files and produces OpCode vectors
 pub struct BytecodeDecoder<'a> {
     bytes: &'a [u8],
     pos: usize,
+    /// Instruction being decoded, for error context
+    current: Option<InstructionContext>,
+    /// Stream version, which sets how argument indices are read
+    stream_version: u8,
 }
 
 impl<'a> BytecodeDecoder<'a> {
-    /// Create a new decoder for the given bytes
+    /// Create a new decoder for the given file contents. A trailing debug
+    /// section is not part of the program and is skipped.
     pub fn new(bytes: &'a [u8]) -> Self {
-        Self { bytes, pos: 0 }
+        Self::over(debug_info::split(bytes).0)
+    }
+
+    /// Decoder over raw bytes, with no file structure assumed
+    pub(super) fn over(bytes: &'a [u8]) -> Self {
+        Self {
+            bytes,
+            pos: 0,
+            current: None,
+            stream_version: VERSION,
+        }
+    }
+
+    /// The part of a bytecode file that holds the program
+    pub fn program_bytes(bytes: &[u8]) -> &[u8] {
+        debug_info::split(bytes).0
+    }
+
+    /// Parse the debug section of a bytecode file, if it has one
+    pub fn debug_info(bytes: &'a [u8]) -> Option<Result<DebugInfo, LoaderError>> {
+        if Self::is_container(bytes) {
+            return match Self::decode_file(bytes) {
+                Ok(file) => file.debug.map(Ok),
+                Err(e) => Some(Err(e.kind)),
+            };
+        }
+        debug_info::split(bytes).1.map(DebugInfo::decode)
+    }
+
+    /// Whether `bytes` start like a version 2 file
+    pub fn is_container(bytes: &[u8]) -> bool {
+        bytes.starts_with(MAGIC) && bytes.get(4) == Some(&CONTAINER_VERSION)
+    }
+
+    /// Decode a bytecode file of either version. A version 2 file's
+    /// checksum is checked before anything else is read.
+    pub fn decode_file(bytes: &'a [u8]) -> Result<BytecodeFile, DecodeError> {
+        if Self::is_container(bytes) {
+            let mut decoder = Self::over(bytes);
+            decoder.validate_header().map_err(|e| decoder.error(e))?;
+            return decoder.decode_container();
+        }
+        let program = Self::new(bytes).decode_all()?;
+        let debug = match Self::debug_info(bytes) {
+            Some(Ok(debug)) => Some(debug),
+            Some(Err(kind)) => {
+                return Err(DecodeError {
+                    offset: Self::program_bytes(bytes).len(),
+                    instruction: None,
+                    kind,
+                });
+            }
+            None => None,
+        };
+        Ok(BytecodeFile {
+            functions: debug
+                .as_ref()
+                .map(|debug| debug.functions.clone())
+                .unwrap_or_default(),
+            program,
+            debug,
+        })
+    }
+
+    /// Read the header without decoding, then decode the program.
+    pub fn inspect(bytes: &'a [u8]) -> BytecodeInfo {
+        let mut decoder = Self::new(bytes);
+        let (version, reserved) = match decoder.validate_header() {
+            Ok(version) => (Some(version), [bytes[5], bytes[6], bytes[7]]),
+            Err(_) => (
+                (bytes.len() >= 5 && &bytes[0..4] == MAGIC).then(|| bytes[4]),
+                [0; 3],
+            ),
+        };
+        decoder.reset();
+        BytecodeInfo {
+            size: bytes.len(),
+            version,
+            reserved,
+            program: decoder.decode_all(),
+            debug: Self::debug_info(bytes),
+        }
     }
 
     /// Reset position to start (useful for legacy files without header)
@@ -96,61 +391,54 @@ impl<'a> BytecodeDecoder<'a> {
         }
 
         let version = self.bytes[4];
-        if version != VERSION {
+        if ![STREAM_V1, CONTAINER_VERSION, VERSION].contains(&version) {
             return Err(LoaderError::UnsupportedVersion(version));
         }
 
         // Skip header (8 bytes: magic + version + reserved)
         self.pos = 8;
+        self.stream_version = version;
         Ok(version)
     }
 
-    /// Read a single byte
-    fn read_u8(&mut self) -> Result<u8, LoaderError> {
-        if self.pos >= self.bytes.len() {
-            return Err(LoaderError::UnexpectedEof);
+    /// Take the next `len` bytes, or fail without consuming anything
+    pub(super) fn take(
+        &mut self,
+        len: usize,
+        reading: &'static str,
+    ) -> Result<&'a [u8], LoaderError> {
+        let available = self.bytes.len().saturating_sub(self.pos);
+        if len > available {
+            return Err(LoaderError::UnexpectedEof {
+                reading,
+                needed: len,
+                available,
+            });
         }
-        let byte = self.bytes[self.pos];
-        self.pos += 1;
-        Ok(byte)
+        let bytes = &self.bytes[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    /// Read a single byte
+    pub(super) fn read_u8(&mut self) -> Result<u8, LoaderError> {
+        Ok(self.take(1, "byte")?[0])
     }
 
     /// Read a 32-bit little-endian unsigned integer
     fn read_u32_le(&mut self) -> Result<u32, LoaderError> {
-        if self.pos + 4 > self.bytes.len() {
-            return Err(LoaderError::UnexpectedEof);
-        }
-        let value = u32::from_le_bytes([
-            self.bytes[self.pos],
-            self.bytes[self.pos + 1],
-            self.bytes[self.pos + 2],
-            self.bytes[self.pos + 3],
-        ]);
-        self.pos += 4;
-        Ok(value)
+        let bytes = self.take(4, "u32")?;
+        Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
     }
 
     /// Read a 64-bit little-endian floating point number
     fn read_f64_le(&mut self) -> Result<f64, LoaderError> {
-        if self.pos + 8 > self.bytes.len() {
-            return Err(LoaderError::UnexpectedEof);
-        }
-        let value = f64::from_le_bytes([
-            self.bytes[self.pos],
-            self.bytes[self.pos + 1],
-            self.bytes[self.pos + 2],
-            self.bytes[self.pos + 3],
-            self.bytes[self.pos + 4],
-            self.bytes[self.pos + 5],
-            self.bytes[self.pos + 6],
-            self.bytes[self.pos + 7],
-        ]);
-        self.pos += 8;
-        Ok(value)
+        let bytes = self.take(8, "f64")?;
+        Ok(f64::from_le_bytes(bytes.try_into().unwrap()))
     }
 
     /// Read a LEB128-encoded variable-length integer
-    fn read_varint(&mut self) -> Result<u64, LoaderError> {
+    pub(super) fn read_varint(&mut self) -> Result<u64, LoaderError> {
         let mut result: u64 = 0;
         let mut shift = 0;
         loop {
@@ -167,30 +455,47 @@ impl<'a> BytecodeDecoder<'a> {
         Ok(result)
     }
 
-    /// Read a varint-prefixed UTF-8 string
-    fn read_string(&mut self) -> Result<String, LoaderError> {
-        let len = self.read_varint()? as usize;
-        if self.pos + len > self.bytes.len() {
-            return Err(LoaderError::UnexpectedEof);
+    /// Read a LOAD_ARG/LOAD_REST_ARGS index: a varint since version 3, one
+    /// byte before
+    fn read_arg_index(&mut self) -> Result<usize, LoaderError> {
+        if self.stream_version == STREAM_V1 {
+            return Ok(self.read_u8()? as usize);
         }
-        let bytes = self.bytes[self.pos..self.pos + len].to_vec();
-        self.pos += len;
+        Ok(self.read_varint()? as usize)
+    }
+
+    /// Read a varint-prefixed UTF-8 string
+    pub(super) fn read_string(&mut self) -> Result<String, LoaderError> {
+        // Lengths past the end of the file (or usize) are caught by `take`
+        let len = usize::try_from(self.read_varint()?).unwrap_or(usize::MAX);
+        let bytes = self.take(len, "string bytes")?.to_vec();
         String::from_utf8(bytes).map_err(LoaderError::InvalidUtf8)
     }
 
+    /// Attach the current position and instruction to an error
+    fn error(&self, kind: LoaderError) -> DecodeError {
+        DecodeError {
+            offset: self.pos,
+            instruction: self.current,
+            kind,
+        }
+    }
+
     /// Decode all instructions from the bytecode
     /// This is a two-pass decoder:
     /// 1. First pass: decode instructions, record byte offset -> instruction index
     /// 2. Second pass: fix up addresses in Jump, JumpIfFalse, MakeClosure
-    pub fn decode_all(&mut self) -> Result<Vec<OpCode>, LoaderError> {
+    pub fn decode_all(&mut self) -> Result<Vec<OpCode>, DecodeError> {
         // Try to validate header, but if invalid magic, assume legacy format
         if self.pos == 0 {
             match self.validate_header() {
+                Ok(CONTAINER_VERSION) => return self.decode_container().map(|file| file.program),
                 Ok(_) => {} // Header validated, continue from position 8
                 Err(LoaderError::InvalidMagic) => {
                     self.reset(); // Legacy format, start from beginning
+                    self.stream_version = STREAM_V1;
                 }
-                Err(e) => return Err(e),
+                Err(e) => return Err(self.error(e)),
             }
         }
 
@@ -198,68 +503,134 @@ impl<'a> BytecodeDecoder<'a> {
         // Note: We use absolute file positions (including header) because that's
         // what the emitter writes when computing jump addresses with currentOffset()
         let mut instructions = Vec::new();
+        let mut offsets = Vec::new();
         let mut byte_to_instr: HashMap<usize, usize> = HashMap::new();
 
         while !self.is_eof() {
             let byte_offset = self.pos; // Absolute file position
             let instr_index = instructions.len();
             byte_to_instr.insert(byte_offset, instr_index);
+            self.current = Some(InstructionContext {
+                index: instr_index,
+                offset: byte_offset,
+                opcode: self.bytes[byte_offset],
+            });
 
-            let op = self.decode_instruction()?;
+            let op = self.decode_instruction().map_err(|e| self.error(e))?;
             instructions.push(op);
+            offsets.push(byte_offset);
         }
+        // Jumping to the end of the file ends the program
+        byte_to_instr.insert(self.pos, instructions.len());
 
         // Second pass: fix up addresses
-        for op in &mut instructions {
+        for (index, op) in instructions.iter_mut().enumerate() {
+            let resolve = |byte_addr: usize| {
+                byte_to_instr.get(&byte_addr).copied().ok_or(DecodeError {
+                    offset: offsets[index],
+                    instruction: Some(InstructionContext {
+                        index,
+                        offset: offsets[index],
+                        opcode: self.bytes[offsets[index]],
+                    }),
+                    kind: LoaderError::AddressNotFound(byte_addr as u32),
+                })
+            };
             match op {
-                OpCode::Jump(addr) => {
-                    let byte_addr = *addr;
-                    *addr = *byte_to_instr
-                        .get(&byte_addr)
-                        .ok_or(LoaderError::AddressNotFound(byte_addr as u32))?;
-                }
-                OpCode::JumpIfFalse(addr) => {
-                    let byte_addr = *addr;
-                    *addr = *byte_to_instr
-                        .get(&byte_addr)
-                        .ok_or(LoaderError::AddressNotFound(byte_addr as u32))?;
-                }
-                OpCode::MakeClosure(addr) => {
-                    let byte_addr = *addr;
-                    *addr = *byte_to_instr
-                        .get(&byte_addr)
-                        .ok_or(LoaderError::AddressNotFound(byte_addr as u32))?;
+                OpCode::Jump(addr) | OpCode::JumpIfFalse(addr) | OpCode::MakeClosure(addr) => {
+                    *addr = resolve(*addr)?;
                 }
                 OpCode::Push(JsValue::Function { address, .. }) => {
-                    let byte_addr = *address;
-                    *address = *byte_to_instr
-                        .get(&byte_addr)
-                        .ok_or(LoaderError::AddressNotFound(byte_addr as u32))?;
+                    *address = resolve(*address)?;
                 }
                 OpCode::SetupTry {
                     catch_addr,
                     finally_addr,
                 } => {
                     if *catch_addr != 0 {
-                        let byte_addr = *catch_addr;
-                        *catch_addr = *byte_to_instr
-                            .get(&byte_addr)
-                            .ok_or(LoaderError::AddressNotFound(byte_addr as u32))?;
+                        *catch_addr = resolve(*catch_addr)?;
                     }
                     if *finally_addr != 0 {
-                        let byte_addr = *finally_addr;
-                        *finally_addr = *byte_to_instr
-                            .get(&byte_addr)
-                            .ok_or(LoaderError::AddressNotFound(byte_addr as u32))?;
+                        *finally_addr = resolve(*finally_addr)?;
                     }
                 }
                 _ => {}
             }
         }
+        self.current = None;
 
         Ok(instructions)
     }
 
+    /// Decode the rest of a version 2 file, the header already read
+    fn decode_container(&mut self) -> Result<BytecodeFile, DecodeError> {
+        let Some(body_len) = self.bytes.len().checked_sub(4) else {
+            return Err(self.error(LoaderError::UnexpectedEof {
+                reading: "checksum",
+                needed: 4,
+                available: self.bytes.len(),
+            }));
+        };
+        let (body, checksum) = self.bytes.split_at(body_len);
+        let expected = u32::from_le_bytes(checksum.try_into().unwrap());
+        let found = super::encoder::checksum(body);
+        if expected != found {
+            return Err(self.error(LoaderError::ChecksumMismatch { expected, found }));
+        }
+        let flags = body[5];
+        if flags & !FLAG_DEBUG != 0 {
+            return Err(self.error(LoaderError::UnsupportedFlags(flags)));
+        }
+        self.bytes = body;
+
+        let read_strings = |decoder: &mut Self| -> Result<Vec<String>, LoaderError> {
+            let count = decoder.read_varint()?;
+            (0..count).map(|_| decoder.read_string()).collect()
+        };
+        let constants = read_strings(self).map_err(|e| self.error(e))?;
+        let mut functions = Vec::new();
+        for _ in 0..self.read_varint().map_err(|e| self.error(e))? {
+            let entry = self.read_varint().map_err(|e| self.error(e))? as usize;
+            let name = self.read_string().map_err(|e| self.error(e))?;
+            functions.push((entry, name));
+        }
+
+        let len =
+            usize::try_from(self.read_varint().map_err(|e| self.error(e))?).unwrap_or(usize::MAX);
+        let code_start = self.pos;
+        let code = self.take(len, "code").map_err(|e| self.error(e))?;
+        let mut reader = Reader::with_pool(code, &constants);
+        let program = reader
+            .program()
+            .and_then(|program| {
+                if reader.finished() {
+                    Ok(program)
+                } else {
+                    Err("bytes after the last instruction".to_string())
+                }
+            })
+            .map_err(|e| DecodeError {
+                offset: code_start,
+                instruction: None,
+                kind: LoaderError::InvalidCode(e),
+            })?;
+
+        let debug = if flags & FLAG_DEBUG != 0 {
+            let section = &self.bytes[self.pos..];
+            let mut debug = DebugInfo::decode(section).map_err(|e| self.error(e))?;
+            debug.functions = functions.clone();
+            self.pos = self.bytes.len();
+            Some(debug)
+        } else {
+            None
+        };
+        Ok(BytecodeFile {
+            functions,
+            program,
+            debug,
+        })
+    }
+
     /// Decode a single instruction
     fn decode_instruction(&mut self) -> Result<OpCode, LoaderError> {
         let opcode = self.read_u8()?;
@@ -451,6 +822,10 @@ impl<'a> BytecodeDecoder<'a> {
             // Let (create new variable binding)
             70 => Ok(OpCode::Let(self.read_string()?)),
 
+            // LoadArg / LoadRestArgs (argument index)
+            71 => Ok(OpCode::LoadArg(self.read_arg_index()?)),
+            72 => Ok(OpCode::LoadRestArgs(self.read_arg_index()?)),
+
             // Halt
             255 => Ok(OpCode::Halt),
 
@@ -511,6 +886,53 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_decode_load_arg_index_past_255() {
+        // LOAD_ARG 300, LOAD_REST_ARGS 2
+        let bytes = vec![71, 0xAC, 0x02, 72, 2];
+        let mut decoder = BytecodeDecoder::new(&bytes);
+        assert!(matches!(
+            decoder.decode_instruction().unwrap(),
+            OpCode::LoadArg(300)
+        ));
+        assert!(matches!(
+            decoder.decode_instruction().unwrap(),
+            OpCode::LoadRestArgs(2)
+        ));
+        assert_eq!(opcode_name(71), Some("LOAD_ARG"));
+    }
+
+    #[test]
+    fn test_self_hosted_opcode_tables_match_the_decoder() {
+        // (name, byte) entries between `start` and the closing brace
+        fn table<'a>(source: &'a str, start: &str, sep: char) -> Vec<(&'a str, u8)> {
+            let body = &source[source.find(start).unwrap()..];
+            body.lines()
+                .skip(1)
+                .take_while(|line| !line.trim_start().starts_with('}'))
+                .filter_map(|line| line.trim().trim_end_matches(',').split_once(sep))
+                .map(|(name, byte)| (name.trim(), byte.trim().parse().unwrap()))
+                .collect()
+        }
+
+        let named = (0..=u8::MAX).filter(|&b| opcode_name(b).is_some()).count();
+        for (file, entries) in [
+            (
+                "bootstrap/types.ot",
+                table(include_str!("../../bootstrap/types.ot"), "enum OP {", '='),
+            ),
+            (
+                "std/prelude.ot",
+                table(include_str!("../../std/prelude.ot"), "let OP = {", ':'),
+            ),
+        ] {
+            assert_eq!(entries.len(), named, "{}", file);
+            for (name, byte) in entries {
+                assert_eq!(opcode_name(byte), Some(name), "{}: {}", file, name);
+            }
+        }
+    }
+
     #[test]
     fn test_decode_push_boolean() {
         // PUSH TRUE
@@ -590,6 +1012,87 @@ mod tests {
         assert_eq!(decoder.position(), 8);
     }
 
+    #[test]
+    fn test_truncated_file_reports_offset_and_instruction() {
+        let mut bytes = b"TSCL".to_vec();
+        bytes.extend_from_slice(&[VERSION, 0, 0, 0]);
+        bytes.push(7); // POP
+        bytes.extend_from_slice(&[13, 0x10, 0x00]); // JUMP, u32 cut short
+
+        let err = BytecodeDecoder::new(&bytes).decode_all().unwrap_err();
+        assert_eq!(err.offset, 10);
+        assert_eq!(
+            err.instruction,
+            Some(InstructionContext {
+                index: 1,
+                offset: 9,
+                opcode: 13
+            })
+        );
+        assert!(matches!(
+            err.kind,
+            LoaderError::UnexpectedEof {
+                needed: 4,
+                available: 2,
+                ..
+            }
+        ));
+        assert_eq!(
+            err.to_string(),
+            "at byte 0xa (instruction #1 at 0x9, opcode 13 JUMP): Unexpected end of file reading u32: needed 4 byte(s), 2 left"
+        );
+    }
+
+    #[test]
+    fn test_oversized_string_length_is_an_error() {
+        // STORE with a length varint near u64::MAX
+        let mut bytes = vec![8];
+        bytes.extend_from_slice(&[0xff; 9]);
+        bytes.push(0x01);
+        let err = BytecodeDecoder::new(&bytes).decode_all().unwrap_err();
+        assert!(matches!(err.kind, LoaderError::UnexpectedEof { .. }));
+    }
+
+    #[test]
+    fn test_bad_jump_target() {
+        let mut bytes = vec![13];
+        bytes.extend_from_slice(&3u32.to_le_bytes()); // middle of the JUMP itself
+        let err = BytecodeDecoder::new(&bytes).decode_all().unwrap_err();
+        assert!(matches!(err.kind, LoaderError::AddressNotFound(3)));
+        assert_eq!(err.instruction.map(|i| i.index), Some(0));
+    }
+
+    #[test]
+    fn test_version_1_argument_indices_are_one_byte() {
+        // LOAD_ARG 0x81, LOAD_REST_ARGS 2, HALT, with and without a header
+        let body = [71, 0x81, 72, 2, 255];
+        let with_header = |version: u8, body: &[u8]| {
+            let mut bytes = b"TSCL".to_vec();
+            bytes.extend_from_slice(&[version, 0, 0, 0]);
+            bytes.extend_from_slice(body);
+            bytes
+        };
+
+        for bytes in [with_header(STREAM_V1, &body), body.to_vec()] {
+            let program = BytecodeDecoder::new(&bytes).decode_all().unwrap();
+            assert!(matches!(
+                program[..],
+                [OpCode::LoadArg(0x81), OpCode::LoadRestArgs(2), OpCode::Halt]
+            ));
+        }
+
+        // Version 3 reads the index as a varint: 0x81 0x02 is 257
+        let bytes = with_header(VERSION, &[71, 0x81, 0x02, 255]);
+        let program = BytecodeDecoder::new(&bytes).decode_all().unwrap();
+        assert!(matches!(program[..], [OpCode::LoadArg(257), OpCode::Halt]));
+
+        let err = BytecodeDecoder::new(&with_header(VERSION + 1, &body))
+            .decode_all()
+            .unwrap_err();
+        assert!(matches!(err.kind, LoaderError::UnsupportedVersion(4)));
+        assert!(err.to_string().contains("reads versions 1 to 3"));
+    }
+
     #[test]
     fn test_invalid_magic() {
         let bytes = b"NOTV1234";
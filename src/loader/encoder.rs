@@ -0,0 +1,150 @@
+//! Bytecode file writer (version 2)
+//!
+//! Version 1 files are a bare instruction stream in the emitter's encoding,
+//! which covers only the instructions the bootstrap compiler emits. Version
+//! 2 wraps the VM's own instruction encoding (shared with startup snapshots,
+//! see `vm::startup_snapshot`) in a container the loader can validate:
+//!
+//! ```text
+//! header:    "TSCL" | u8 version (2) | u8 flags | u8 0 | u8 0
+//! constants: varint count, string*
+//! functions: varint count, (varint instruction, string name)*
+//! code:      varint length, (varint count, instruction*)
+//! debug:     debug section, without its trailer       (if FLAG_DEBUG)
+//! checksum:  u32 LE CRC-32 of everything before it
+//! ```
+//!
+//! Strings in the code are varint indices into the constant pool, and
+//! addresses are instruction indices. The debug section's function list is
+//! left empty: the function table, which is always there, stands in for it.
+//! A file with another version, unknown flags or a checksum that doesn't
+//! match is refused before any of it is used, so a change to the
+//! instruction encoding needs a new `CONTAINER_VERSION`.
+
+use super::debug_info::{write_string, write_varint};
+use super::decoder::{BytecodeFile, CONTAINER_VERSION, FLAG_DEBUG, MAGIC};
+use crate::vm::startup_snapshot::Writer;
+
+/// CRC-32 of a file's contents, as its trailer records it
+pub fn checksum(bytes: &[u8]) -> u32 {
+    let mut crc = flate2::Crc::new();
+    crc.update(bytes);
+    crc.sum()
+}
+
+impl BytecodeFile {
+    /// The file as version 2 bytes. `compress` deflates embedded source.
+    pub fn encode(&self, compress: bool) -> Result<Vec<u8>, String> {
+        let mut code = Writer::pooled();
+        code.program(&self.program)?;
+        let constants = code.take_pool();
+
+        let mut out = MAGIC.to_vec();
+        let flags = if self.debug.is_some() { FLAG_DEBUG } else { 0 };
+        out.extend_from_slice(&[CONTAINER_VERSION, flags, 0, 0]);
+        write_varint(&mut out, constants.len() as u64);
+        for constant in &constants {
+            write_string(&mut out, constant);
+        }
+        write_varint(&mut out, self.functions.len() as u64);
+        for (entry, name) in &self.functions {
+            write_varint(&mut out, *entry as u64);
+            write_string(&mut out, name);
+        }
+        write_varint(&mut out, code.bytes.len() as u64);
+        out.extend_from_slice(&code.bytes);
+        if let Some(debug) = &self.debug {
+            let mut debug = debug.clone();
+            debug.functions.clear();
+            out.extend(debug.encode_section(compress));
+        }
+        let sum = checksum(&out);
+        out.extend_from_slice(&sum.to_le_bytes());
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loader::decoder::{LoaderError, VERSION};
+    use crate::loader::{BytecodeDecoder, DebugInfo};
+    use crate::vm::opcodes::OpCode;
+    use crate::vm::value::JsValue;
+
+    fn file() -> BytecodeFile {
+        let program = crate::compiler::Compiler::new()
+            .compile(
+                "function greet(name) { return 'hi ' + name; }\nlet s = greet('a') + greet('b');\n",
+            )
+            .unwrap();
+        BytecodeFile {
+            functions: vec![(3, "greet".to_string())],
+            program,
+            debug: Some(DebugInfo {
+                file: "greet.ot".to_string(),
+                functions: Vec::new(),
+                lines: vec![(0, 1), (5, 2)],
+                source: Some("function greet(name) {}".to_string()),
+            }),
+        }
+    }
+
+    #[test]
+    fn test_container_round_trips() {
+        let file = file();
+        let bytes = file.encode(true).unwrap();
+        assert!(BytecodeDecoder::is_container(&bytes));
+
+        let decoded = BytecodeDecoder::decode_file(&bytes).unwrap();
+        assert_eq!(
+            format!("{:?}", decoded.program),
+            format!("{:?}", file.program)
+        );
+        assert_eq!(decoded.functions, file.functions);
+        let debug = decoded.debug.unwrap();
+        assert_eq!(debug.functions, file.functions);
+        assert_eq!(debug.lines, vec![(0, 1), (5, 2)]);
+        assert_eq!(debug.source.as_deref(), Some("function greet(name) {}"));
+
+        // The older entry points read version 2 files too
+        let program = BytecodeDecoder::new(&bytes).decode_all().unwrap();
+        assert_eq!(program.len(), file.program.len());
+        assert_eq!(BytecodeDecoder::program_bytes(&bytes).len(), bytes.len());
+        assert!(matches!(BytecodeDecoder::debug_info(&bytes), Some(Ok(_))));
+
+        // Strings are pooled: "hi " is stored once however often it's used
+        let mut pooled = file.clone();
+        pooled.debug = None;
+        pooled
+            .program
+            .push(OpCode::Push(JsValue::String("hi ".to_string())));
+        let bytes = pooled.encode(false).unwrap();
+        assert_eq!(bytes.windows(3).filter(|w| *w == b"hi ").count(), 1);
+    }
+
+    #[test]
+    fn test_incompatible_or_damaged_files_are_refused() {
+        let bytes = file().encode(false).unwrap();
+
+        let mut corrupt = bytes.clone();
+        corrupt[12] ^= 0x40;
+        let err = BytecodeDecoder::decode_file(&corrupt).unwrap_err();
+        assert!(matches!(err.kind, LoaderError::ChecksumMismatch { .. }));
+        assert!(BytecodeDecoder::new(&corrupt).decode_all().is_err());
+
+        let mut newer = bytes.clone();
+        newer[4] = VERSION + 1;
+        let err = BytecodeDecoder::new(&newer).decode_all().unwrap_err();
+        assert!(matches!(err.kind, LoaderError::UnsupportedVersion(4)));
+        assert!(err.to_string().contains("written by a newer oitec"));
+
+        // Flags are covered by the checksum, so fix it up after changing them
+        let mut flagged = bytes[..bytes.len() - 4].to_vec();
+        flagged[5] |= 0x80;
+        let sum = checksum(&flagged);
+        flagged.extend_from_slice(&sum.to_le_bytes());
+        let err = BytecodeDecoder::decode_file(&flagged).unwrap_err();
+        assert!(matches!(err.kind, LoaderError::UnsupportedFlags(_)));
+    }
+}
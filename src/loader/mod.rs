@@ -1,8 +1,12 @@
 //! Bytecode loader module for loading pre-compiled .bc files
 //!
 //! This module provides functionality to decode binary bytecode files
-//! produced by the bootstrap compiler (bootstrap/emitter.ot).
+//! produced by the bootstrap compiler (bootstrap/emitter.ot), and to write
+//! the versioned container format (`encoder`).
 
+mod debug_info;
 mod decoder;
+mod encoder;
 
-pub use decoder::BytecodeDecoder;
+pub use debug_info::DebugInfo;
+pub use decoder::{BytecodeDecoder, BytecodeFile, CONTAINER_VERSION};
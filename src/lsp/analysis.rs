@@ -0,0 +1,969 @@
+//! Per-document analysis backing the language server
+//!
+//! A document is parsed with SWC to collect declarations (for outlines and
+//! go-to-definition), compiled for ownership diagnostics, and lowered to SSA IR
+//! so hover can show the types inferred by `ir::typecheck`.
+
+use crate::driver::CompilationDriver;
+use crate::ir::{IrOp, IrType, Terminator};
+use crate::vm::opcodes::OpCode;
+use crate::vm::value::JsValue;
+use std::collections::HashMap;
+use swc_common::{FileName, SourceMap, Span, Spanned, sync::Lrc};
+use swc_ecma_ast::*;
+use swc_ecma_parser::{Parser, StringInput, lexer::Lexer};
+
+/// Zero-based line/character position (UTF-16 columns, as LSP expects).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub line: u32,
+    pub character: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Range {
+    pub start: Position,
+    pub end: Position,
+}
+
+/// Maps byte offsets to LSP positions and back.
+pub struct LineIndex {
+    text: String,
+    line_starts: Vec<usize>,
+}
+
+impl LineIndex {
+    pub fn new(text: &str) -> Self {
+        let mut line_starts = vec![0];
+        for (i, b) in text.bytes().enumerate() {
+            if b == b'\n' {
+                line_starts.push(i + 1);
+            }
+        }
+        Self {
+            text: text.to_string(),
+            line_starts,
+        }
+    }
+
+    pub fn position(&self, offset: usize) -> Position {
+        let offset = offset.min(self.text.len());
+        let line = match self.line_starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(next) => next - 1,
+        };
+        let start = self.line_starts[line];
+        let character = self.text[start..offset]
+            .chars()
+            .map(|c| c.len_utf16() as u32)
+            .sum();
+        Position {
+            line: line as u32,
+            character,
+        }
+    }
+
+    pub fn range(&self, start: usize, end: usize) -> Range {
+        Range {
+            start: self.position(start),
+            end: self.position(end),
+        }
+    }
+
+    pub fn offset(&self, pos: Position) -> usize {
+        let Some(&start) = self.line_starts.get(pos.line as usize) else {
+            return self.text.len();
+        };
+        let mut units = 0;
+        for (i, c) in self.text[start..].char_indices() {
+            if units >= pos.character || c == '\n' {
+                return start + i;
+            }
+            units += c.len_utf16() as u32;
+        }
+        self.text.len()
+    }
+
+    /// The identifier under (or immediately before) a byte offset.
+    pub fn word_at(&self, offset: usize) -> Option<(String, usize, usize)> {
+        let is_ident = |c: char| c.is_alphanumeric() || c == '_' || c == '$';
+        let offset = offset.min(self.text.len());
+        let start = self.text[..offset]
+            .char_indices()
+            .rev()
+            .take_while(|(_, c)| is_ident(*c))
+            .last()
+            .map(|(i, _)| i)
+            .unwrap_or(offset);
+        let end = self.text[offset..]
+            .char_indices()
+            .find(|(_, c)| !is_ident(*c))
+            .map(|(i, _)| offset + i)
+            .unwrap_or(self.text.len());
+        if start == end {
+            return None;
+        }
+        Some((self.text[start..end].to_string(), start, end))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiagnosticSeverity {
+    Error = 1,
+    Warning = 2,
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub range: Range,
+    pub severity: DiagnosticSeverity,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    Function,
+    Class,
+    Method,
+    Property,
+    Variable,
+    Parameter,
+    Import,
+    Interface,
+    TypeAlias,
+    Enum,
+}
+
+impl SymbolKind {
+    /// Numeric `SymbolKind` from the LSP specification.
+    pub fn lsp_kind(self) -> u32 {
+        match self {
+            SymbolKind::Import => 2,
+            SymbolKind::Class => 5,
+            SymbolKind::Method => 6,
+            SymbolKind::Property => 7,
+            SymbolKind::Enum => 10,
+            SymbolKind::Interface => 11,
+            SymbolKind::Function => 12,
+            SymbolKind::Variable | SymbolKind::Parameter => 13,
+            SymbolKind::TypeAlias => 26,
+        }
+    }
+}
+
+/// A declaration found in the document.
+#[derive(Debug, Clone)]
+pub struct Declaration {
+    pub name: String,
+    pub kind: SymbolKind,
+    /// Byte range of the whole declaration
+    pub span: (usize, usize),
+    /// Byte range of the declared identifier
+    pub name_span: (usize, usize),
+    /// Byte range in which the name is visible
+    pub scope: (usize, usize),
+    pub exported: bool,
+    /// For imports: (module specifier, imported name)
+    pub import: Option<(String, String)>,
+    /// Index of the enclosing declaration (for the document outline)
+    pub container: Option<usize>,
+}
+
+/// Types inferred by lowering the document to IR.
+#[derive(Debug, Default, Clone)]
+pub struct InferredTypes {
+    /// Function name -> (parameters, return type)
+    pub functions: HashMap<String, (Vec<(String, IrType)>, IrType)>,
+    /// Variable name -> type (joined across every store)
+    pub variables: HashMap<String, IrType>,
+}
+
+impl InferredTypes {
+    /// Add another table's entries; a variable stored with different types
+    /// becomes `Any`, and later functions replace earlier ones of the same name.
+    pub fn merge(&mut self, other: &InferredTypes) {
+        for (name, ty) in &other.variables {
+            self.variables
+                .entry(name.clone())
+                .and_modify(|existing| {
+                    if existing != ty {
+                        *existing = IrType::Any;
+                    }
+                })
+                .or_insert_with(|| ty.clone());
+        }
+        for (name, signature) in &other.functions {
+            self.functions.insert(name.clone(), signature.clone());
+        }
+    }
+}
+
+/// Result of analyzing one document.
+pub struct Analysis {
+    pub index: LineIndex,
+    pub diagnostics: Vec<Diagnostic>,
+    pub declarations: Vec<Declaration>,
+    pub types: InferredTypes,
+}
+
+/// Pick the parser syntax from a document path, the way the CLI does
+pub use crate::driver::syntax_for_path;
+
+/// Analyze a document's source text.
+pub fn analyze(source: &str, path: &str) -> Analysis {
+    let index = LineIndex::new(source);
+    let mut diagnostics = Vec::new();
+    let mut declarations = Vec::new();
+    let mut types = InferredTypes::default();
+
+    let cm: Lrc<SourceMap> = Default::default();
+    let fm = cm.new_source_file(
+        FileName::Custom(path.to_string()).into(),
+        source.to_string(),
+    );
+    let base = fm.start_pos.0 as usize;
+    let lexer = Lexer::new(
+        syntax_for_path(path),
+        Default::default(),
+        StringInput::from(&*fm),
+        None,
+    );
+    let mut parser = Parser::new_from(lexer);
+    let parsed = parser.parse_module();
+
+    for err in parser.take_errors() {
+        diagnostics.push(syntax_diagnostic(
+            &index,
+            base,
+            err.span(),
+            &err.kind().msg(),
+        ));
+    }
+
+    match parsed {
+        Ok(module) => {
+            declarations = collect_declarations(&module, base);
+
+            // Syntax is fine: run the compiler for ownership/lifetime errors,
+            // with the borrow checking script.toml sets for this file
+            if diagnostics.is_empty() {
+                let mut driver = CompilationDriver::new(path);
+                match driver.compile(path, source) {
+                    Ok(bytecode) => types = infer_types(&bytecode),
+                    Err(e) => diagnostics.push(Diagnostic {
+                        range: index.range(0, 0),
+                        severity: DiagnosticSeverity::Error,
+                        message: e,
+                    }),
+                }
+                for warning in driver.compiler.warnings {
+                    diagnostics.push(Diagnostic {
+                        range: index.range(0, 0),
+                        severity: DiagnosticSeverity::Warning,
+                        message: warning,
+                    });
+                }
+            }
+        }
+        Err(err) => {
+            diagnostics.push(syntax_diagnostic(
+                &index,
+                base,
+                err.span(),
+                &err.kind().msg(),
+            ));
+        }
+    }
+
+    Analysis {
+        index,
+        diagnostics,
+        declarations,
+        types,
+    }
+}
+
+/// Declarations in `module`, with offsets relative to span position `base`.
+pub(super) fn collect_declarations(module: &Module, base: usize) -> Vec<Declaration> {
+    let mut declarations = Vec::new();
+    let mut collector = DeclCollector {
+        base,
+        decls: &mut declarations,
+    };
+    collector.collect_module(module);
+    declarations
+}
+
+fn syntax_diagnostic(index: &LineIndex, base: usize, span: Span, msg: &str) -> Diagnostic {
+    let start = (span.lo.0 as usize).saturating_sub(base);
+    let end = (span.hi.0 as usize).saturating_sub(base).max(start);
+    Diagnostic {
+        range: index.range(start, end),
+        severity: DiagnosticSeverity::Error,
+        message: msg.to_string(),
+    }
+}
+
+impl Analysis {
+    /// Find the declaration that the identifier at `offset` refers to.
+    /// Prefers the innermost visible declaration, then the latest one
+    /// declared before the cursor.
+    pub fn definition_at(&self, offset: usize) -> Option<&Declaration> {
+        let (word, _, _) = self.index.word_at(offset)?;
+        self.resolve(&word, offset)
+    }
+
+    pub fn resolve(&self, name: &str, offset: usize) -> Option<&Declaration> {
+        self.declarations
+            .iter()
+            .filter(|d| d.name == name && d.scope.0 <= offset && offset <= d.scope.1)
+            .min_by_key(|d| {
+                let scope_len = d.scope.1 - d.scope.0;
+                let distance = if d.name_span.0 <= offset {
+                    offset - d.name_span.0
+                } else {
+                    usize::MAX / 2
+                };
+                (scope_len, distance)
+            })
+    }
+
+    /// A top-level exported declaration with the given name.
+    pub fn export_named(&self, name: &str) -> Option<&Declaration> {
+        self.declarations
+            .iter()
+            .find(|d| d.exported && d.container.is_none() && d.name == name)
+    }
+
+    /// Markdown hover text for the identifier at `offset`.
+    pub fn hover_at(&self, offset: usize) -> Option<(String, Range)> {
+        let (word, start, end) = self.index.word_at(offset)?;
+        let decl = self.resolve(&word, offset);
+        let text = match decl.map(|d| d.kind) {
+            Some(SymbolKind::Function) | Some(SymbolKind::Method) => {
+                match self.types.functions.get(&word) {
+                    Some((params, ret)) => {
+                        let params: Vec<String> = params
+                            .iter()
+                            .map(|(name, ty)| format!("{}: {}", name, ty))
+                            .collect();
+                        format!("function {}({}): {}", word, params.join(", "), ret)
+                    }
+                    None => format!("function {}", word),
+                }
+            }
+            Some(SymbolKind::Class) => format!("class {}", word),
+            Some(SymbolKind::Interface) => format!("interface {}", word),
+            Some(SymbolKind::TypeAlias) => format!("type {}", word),
+            Some(SymbolKind::Enum) => format!("enum {}", word),
+            Some(SymbolKind::Import) => {
+                let (source, imported) = decl.and_then(|d| d.import.clone()).unwrap_or_default();
+                format!("import {{ {} }} from \"{}\"", imported, source)
+            }
+            _ => {
+                let ty = self.types.variables.get(&word)?;
+                let prefix = if decl.map(|d| d.kind) == Some(SymbolKind::Parameter) {
+                    "(parameter) "
+                } else {
+                    ""
+                };
+                format!("{}{}: {}", prefix, word, ty)
+            }
+        };
+        Some((
+            format!("```typescript\n{}\n```", text),
+            self.index.range(start, end),
+        ))
+    }
+}
+
+/// Walks the AST recording declarations with their byte ranges.
+struct DeclCollector<'a> {
+    base: usize,
+    decls: &'a mut Vec<Declaration>,
+}
+
+impl DeclCollector<'_> {
+    fn offsets(&self, span: Span) -> (usize, usize) {
+        (
+            (span.lo.0 as usize).saturating_sub(self.base),
+            (span.hi.0 as usize).saturating_sub(self.base),
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn push(
+        &mut self,
+        name: String,
+        kind: SymbolKind,
+        span: Span,
+        name_span: Span,
+        scope: (usize, usize),
+        exported: bool,
+        container: Option<usize>,
+    ) -> usize {
+        let idx = self.decls.len();
+        let span = self.offsets(span);
+        let name_span = self.offsets(name_span);
+        self.decls.push(Declaration {
+            name,
+            kind,
+            span,
+            name_span,
+            scope,
+            exported,
+            import: None,
+            container,
+        });
+        idx
+    }
+
+    fn collect_module(&mut self, module: &Module) {
+        // Top-level names are visible throughout the document
+        let scope = (0, usize::MAX);
+        for item in &module.body {
+            match item {
+                ModuleItem::Stmt(stmt) => self.collect_stmt(stmt, scope, None, false),
+                ModuleItem::ModuleDecl(decl) => match decl {
+                    ModuleDecl::Import(import) => {
+                        let source = import.src.value.to_string_lossy().into_owned();
+                        for spec in &import.specifiers {
+                            let (local, imported) = match spec {
+                                ImportSpecifier::Named(named) => {
+                                    let imported = named
+                                        .imported
+                                        .as_ref()
+                                        .map(|i| {
+                                            let atom = i.atom();
+                                            let s: &str = &atom;
+                                            s.to_string()
+                                        })
+                                        .unwrap_or_else(|| named.local.sym.to_string());
+                                    (&named.local, imported)
+                                }
+                                ImportSpecifier::Default(default) => {
+                                    (&default.local, "default".to_string())
+                                }
+                                ImportSpecifier::Namespace(ns) => (&ns.local, "*".to_string()),
+                            };
+                            let idx = self.push(
+                                local.sym.to_string(),
+                                SymbolKind::Import,
+                                import.span,
+                                local.span,
+                                scope,
+                                false,
+                                None,
+                            );
+                            self.decls[idx].import = Some((source.clone(), imported));
+                        }
+                    }
+                    ModuleDecl::ExportDecl(export) => {
+                        self.collect_decl(&export.decl, scope, None, true);
+                    }
+                    ModuleDecl::ExportDefaultDecl(export) => match &export.decl {
+                        DefaultDecl::Fn(fn_expr) => {
+                            let name_span = fn_expr.ident.as_ref().map_or(export.span, |i| i.span);
+                            let idx = self.push(
+                                "default".to_string(),
+                                SymbolKind::Function,
+                                export.span,
+                                name_span,
+                                scope,
+                                true,
+                                None,
+                            );
+                            self.collect_function(&fn_expr.function, Some(idx));
+                        }
+                        DefaultDecl::Class(class_expr) => {
+                            let name_span =
+                                class_expr.ident.as_ref().map_or(export.span, |i| i.span);
+                            let idx = self.push(
+                                "default".to_string(),
+                                SymbolKind::Class,
+                                export.span,
+                                name_span,
+                                scope,
+                                true,
+                                None,
+                            );
+                            self.collect_class(&class_expr.class, Some(idx));
+                        }
+                        _ => {}
+                    },
+                    _ => {}
+                },
+            }
+        }
+    }
+
+    fn collect_decl(
+        &mut self,
+        decl: &Decl,
+        scope: (usize, usize),
+        container: Option<usize>,
+        exported: bool,
+    ) {
+        match decl {
+            Decl::Fn(fn_decl) => {
+                let idx = self.push(
+                    fn_decl.ident.sym.to_string(),
+                    SymbolKind::Function,
+                    fn_decl.function.span,
+                    fn_decl.ident.span,
+                    scope,
+                    exported,
+                    container,
+                );
+                self.collect_function(&fn_decl.function, Some(idx));
+            }
+            Decl::Class(class_decl) => {
+                let idx = self.push(
+                    class_decl.ident.sym.to_string(),
+                    SymbolKind::Class,
+                    class_decl.class.span,
+                    class_decl.ident.span,
+                    scope,
+                    exported,
+                    container,
+                );
+                self.collect_class(&class_decl.class, Some(idx));
+            }
+            Decl::Var(var_decl) => {
+                for declarator in &var_decl.decls {
+                    let kind = match declarator.init.as_deref() {
+                        Some(Expr::Arrow(_)) | Some(Expr::Fn(_)) => SymbolKind::Function,
+                        Some(Expr::Class(_)) => SymbolKind::Class,
+                        _ => SymbolKind::Variable,
+                    };
+                    let first = self.decls.len();
+                    self.collect_pat(
+                        &declarator.name,
+                        kind,
+                        declarator.span,
+                        scope,
+                        exported,
+                        container,
+                    );
+                    let owner = (self.decls.len() > first).then_some(first);
+                    match declarator.init.as_deref() {
+                        Some(Expr::Arrow(arrow)) => self.collect_arrow(arrow, owner),
+                        Some(Expr::Fn(fn_expr)) => self.collect_function(&fn_expr.function, owner),
+                        Some(Expr::Class(class_expr)) => {
+                            self.collect_class(&class_expr.class, owner)
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            Decl::TsInterface(iface) => {
+                self.push(
+                    iface.id.sym.to_string(),
+                    SymbolKind::Interface,
+                    iface.span,
+                    iface.id.span,
+                    scope,
+                    exported,
+                    container,
+                );
+            }
+            Decl::TsTypeAlias(alias) => {
+                self.push(
+                    alias.id.sym.to_string(),
+                    SymbolKind::TypeAlias,
+                    alias.span,
+                    alias.id.span,
+                    scope,
+                    exported,
+                    container,
+                );
+            }
+            Decl::TsEnum(ts_enum) => {
+                self.push(
+                    ts_enum.id.sym.to_string(),
+                    SymbolKind::Enum,
+                    ts_enum.span,
+                    ts_enum.id.span,
+                    scope,
+                    exported,
+                    container,
+                );
+            }
+            _ => {}
+        }
+    }
+
+    fn collect_pat(
+        &mut self,
+        pat: &Pat,
+        kind: SymbolKind,
+        span: Span,
+        scope: (usize, usize),
+        exported: bool,
+        container: Option<usize>,
+    ) {
+        match pat {
+            Pat::Ident(binding) => {
+                self.push(
+                    binding.id.sym.to_string(),
+                    kind,
+                    span,
+                    binding.id.span,
+                    scope,
+                    exported,
+                    container,
+                );
+            }
+            Pat::Array(array) => {
+                for elem in array.elems.iter().flatten() {
+                    self.collect_pat(elem, kind, span, scope, exported, container);
+                }
+            }
+            Pat::Object(object) => {
+                for prop in &object.props {
+                    match prop {
+                        ObjectPatProp::KeyValue(kv) => {
+                            self.collect_pat(&kv.value, kind, span, scope, exported, container)
+                        }
+                        ObjectPatProp::Assign(assign) => {
+                            self.push(
+                                assign.key.id.sym.to_string(),
+                                kind,
+                                span,
+                                assign.key.id.span,
+                                scope,
+                                exported,
+                                container,
+                            );
+                        }
+                        ObjectPatProp::Rest(rest) => {
+                            self.collect_pat(&rest.arg, kind, span, scope, exported, container)
+                        }
+                    }
+                }
+            }
+            Pat::Rest(rest) => self.collect_pat(&rest.arg, kind, span, scope, exported, container),
+            Pat::Assign(assign) => {
+                self.collect_pat(&assign.left, kind, span, scope, exported, container)
+            }
+            _ => {}
+        }
+    }
+
+    fn collect_function(&mut self, function: &Function, container: Option<usize>) {
+        let scope = self.offsets(function.span);
+        for param in &function.params {
+            self.collect_pat(
+                &param.pat,
+                SymbolKind::Parameter,
+                param.span,
+                scope,
+                false,
+                container,
+            );
+        }
+        if let Some(body) = &function.body {
+            for stmt in &body.stmts {
+                self.collect_stmt(stmt, scope, container, false);
+            }
+        }
+    }
+
+    fn collect_arrow(&mut self, arrow: &ArrowExpr, container: Option<usize>) {
+        let scope = self.offsets(arrow.span);
+        for param in &arrow.params {
+            self.collect_pat(
+                param,
+                SymbolKind::Parameter,
+                param.span(),
+                scope,
+                false,
+                container,
+            );
+        }
+        if let BlockStmtOrExpr::BlockStmt(body) = arrow.body.as_ref() {
+            for stmt in &body.stmts {
+                self.collect_stmt(stmt, scope, container, false);
+            }
+        }
+    }
+
+    fn collect_class(&mut self, class: &Class, container: Option<usize>) {
+        let scope = self.offsets(class.span);
+        for member in &class.body {
+            match member {
+                ClassMember::Method(method) => {
+                    if let PropName::Ident(id) = &method.key {
+                        let idx = self.push(
+                            id.sym.to_string(),
+                            SymbolKind::Method,
+                            method.span,
+                            id.span,
+                            scope,
+                            false,
+                            container,
+                        );
+                        self.collect_function(&method.function, Some(idx));
+                    }
+                }
+                ClassMember::Constructor(ctor) => {
+                    let idx = self.push(
+                        "constructor".to_string(),
+                        SymbolKind::Method,
+                        ctor.span,
+                        ctor.key.span(),
+                        scope,
+                        false,
+                        container,
+                    );
+                    let ctor_scope = self.offsets(ctor.span);
+                    for param in &ctor.params {
+                        if let ParamOrTsParamProp::Param(param) = param {
+                            self.collect_pat(
+                                &param.pat,
+                                SymbolKind::Parameter,
+                                param.span,
+                                ctor_scope,
+                                false,
+                                Some(idx),
+                            );
+                        }
+                    }
+                    if let Some(body) = &ctor.body {
+                        for stmt in &body.stmts {
+                            self.collect_stmt(stmt, ctor_scope, Some(idx), false);
+                        }
+                    }
+                }
+                ClassMember::ClassProp(prop) => {
+                    if let PropName::Ident(id) = &prop.key {
+                        self.push(
+                            id.sym.to_string(),
+                            SymbolKind::Property,
+                            prop.span,
+                            id.span,
+                            scope,
+                            false,
+                            container,
+                        );
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn collect_stmt(
+        &mut self,
+        stmt: &Stmt,
+        scope: (usize, usize),
+        container: Option<usize>,
+        exported: bool,
+    ) {
+        match stmt {
+            Stmt::Decl(decl) => self.collect_decl(decl, scope, container, exported),
+            Stmt::Block(block) => {
+                let inner = self.offsets(block.span);
+                for stmt in &block.stmts {
+                    self.collect_stmt(stmt, inner, container, false);
+                }
+            }
+            Stmt::If(if_stmt) => {
+                self.collect_stmt(&if_stmt.cons, scope, container, false);
+                if let Some(alt) = &if_stmt.alt {
+                    self.collect_stmt(alt, scope, container, false);
+                }
+            }
+            Stmt::For(for_stmt) => {
+                let inner = self.offsets(for_stmt.span);
+                if let Some(VarDeclOrExpr::VarDecl(var_decl)) = &for_stmt.init {
+                    self.collect_decl(&Decl::Var(var_decl.clone()), inner, container, false);
+                }
+                self.collect_stmt(&for_stmt.body, inner, container, false);
+            }
+            Stmt::ForIn(for_in) => {
+                let inner = self.offsets(for_in.span);
+                if let ForHead::VarDecl(var_decl) = &for_in.left {
+                    self.collect_decl(&Decl::Var(var_decl.clone()), inner, container, false);
+                }
+                self.collect_stmt(&for_in.body, inner, container, false);
+            }
+            Stmt::ForOf(for_of) => {
+                let inner = self.offsets(for_of.span);
+                if let ForHead::VarDecl(var_decl) = &for_of.left {
+                    self.collect_decl(&Decl::Var(var_decl.clone()), inner, container, false);
+                }
+                self.collect_stmt(&for_of.body, inner, container, false);
+            }
+            Stmt::While(while_stmt) => self.collect_stmt(&while_stmt.body, scope, container, false),
+            Stmt::DoWhile(do_while) => self.collect_stmt(&do_while.body, scope, container, false),
+            Stmt::Labeled(labeled) => self.collect_stmt(&labeled.body, scope, container, false),
+            Stmt::Try(try_stmt) => {
+                let block = Stmt::Block(try_stmt.block.clone());
+                self.collect_stmt(&block, scope, container, false);
+                if let Some(handler) = &try_stmt.handler {
+                    let inner = self.offsets(handler.span);
+                    if let Some(param) = &handler.param {
+                        self.collect_pat(
+                            param,
+                            SymbolKind::Variable,
+                            handler.span,
+                            inner,
+                            false,
+                            container,
+                        );
+                    }
+                    for stmt in &handler.body.stmts {
+                        self.collect_stmt(stmt, inner, container, false);
+                    }
+                }
+                if let Some(finalizer) = &try_stmt.finalizer {
+                    let block = Stmt::Block(finalizer.clone());
+                    self.collect_stmt(&block, scope, container, false);
+                }
+            }
+            Stmt::Switch(switch) => {
+                let inner = self.offsets(switch.span);
+                for case in &switch.cases {
+                    for stmt in &case.cons {
+                        self.collect_stmt(stmt, inner, container, false);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Lower compiled bytecode to IR and record the inferred types of functions
+/// and named variables. Returns empty tables if lowering fails.
+pub fn infer_types(bytecode: &[OpCode]) -> InferredTypes {
+    let mut types = InferredTypes::default();
+
+    // Extracted functions are named `func_<address>`; map them back to the
+    // variable each one is bound to.
+    let mut func_names: HashMap<String, String> = HashMap::new();
+    for pair in bytecode.windows(2) {
+        if let OpCode::Push(JsValue::Function { address, .. }) = &pair[0]
+            && let OpCode::Let(name) | OpCode::Store(name) = &pair[1]
+        {
+            func_names.insert(format!("func_{}", address), name.clone());
+        }
+    }
+
+    let Ok(mut module) = crate::ir::lower::lower_module(bytecode) else {
+        return types;
+    };
+    crate::ir::typecheck::typecheck_module(&mut module);
+
+    for func in &module.functions {
+        let ty_of = |v| func.value_types.get(v).cloned().unwrap_or(IrType::Any);
+
+        for block in &func.blocks {
+            for op in &block.ops {
+                let (name, ty) = match op {
+                    IrOp::StoreLocal(slot, v) => match func.locals.get(*slot as usize) {
+                        Some((name, _)) => (name.clone(), ty_of(v)),
+                        None => continue,
+                    },
+                    IrOp::StoreGlobal(name, v) => (name.clone(), ty_of(v)),
+                    _ => continue,
+                };
+                types
+                    .variables
+                    .entry(name)
+                    .and_modify(|existing| {
+                        if *existing != ty {
+                            *existing = IrType::Any;
+                        }
+                    })
+                    .or_insert(ty);
+            }
+        }
+
+        let Some(name) = func_names.get(&func.name) else {
+            continue;
+        };
+        let mut ret: Option<IrType> = None;
+        for block in &func.blocks {
+            if let Terminator::Return(value) = &block.terminator {
+                let ty = value.as_ref().map(ty_of).unwrap_or(IrType::Void);
+                ret = Some(match ret {
+                    Some(prev) if prev != ty => IrType::Any,
+                    _ => ty,
+                });
+            }
+        }
+        types.functions.insert(
+            name.clone(),
+            (func.params.clone(), ret.unwrap_or(IrType::Void)),
+        );
+    }
+
+    types
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_index_round_trip() {
+        let index = LineIndex::new("let a = 1;\nlet é = a;\n");
+        let pos = index.position(15);
+        assert_eq!(
+            pos,
+            Position {
+                line: 1,
+                character: 4
+            }
+        );
+        assert_eq!(index.offset(pos), 15);
+        assert_eq!(index.word_at(12).map(|w| w.0), Some("let".to_string()));
+    }
+
+    #[test]
+    fn test_definition_prefers_innermost_scope() {
+        let source = "let x = 1;\nfunction f(x) {\n  return x;\n}\nx;\n";
+        let analysis = analyze(source, "test.ot");
+
+        let inner_use = source.find("return x").unwrap() + 7;
+        let decl = analysis.definition_at(inner_use).unwrap();
+        assert_eq!(decl.kind, SymbolKind::Parameter);
+
+        let outer_use = source.rfind("x;").unwrap();
+        let decl = analysis.definition_at(outer_use).unwrap();
+        assert_eq!(decl.kind, SymbolKind::Variable);
+    }
+
+    #[test]
+    fn test_syntax_errors_become_diagnostics() {
+        let analysis = analyze("let = ;", "broken.ot");
+        assert!(!analysis.diagnostics.is_empty());
+        assert_eq!(analysis.diagnostics[0].range.start.line, 0);
+    }
+
+    #[test]
+    fn test_borrow_check_follows_the_manifest() {
+        let dir = std::env::temp_dir().join(format!("oite_lsp_manifest_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("script.toml"),
+            "[compiler]\nborrow-check = \"warn\"\n",
+        )
+        .unwrap();
+        let path = dir.join("main.ot");
+        let moved_twice = "let a = [1]; let b = a; let c = a;";
+
+        let analysis = analyze(moved_twice, &path.to_string_lossy());
+        assert!(!analysis.diagnostics.is_empty());
+        assert!(
+            analysis
+                .diagnostics
+                .iter()
+                .all(|d| d.severity == DiagnosticSeverity::Warning)
+        );
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
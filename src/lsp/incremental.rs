@@ -0,0 +1,529 @@
+//! Incremental re-analysis of open documents
+//!
+//! An open document is kept as a list of top-level items, each holding its
+//! own single-item AST. An edit that falls strictly inside one item reparses
+//! only that item's text; any other edit (or an item that no longer parses on
+//! its own) falls back to parsing the whole file.
+//!
+//! Each item caches its declarations, its compiled code (see
+//! `compiler::reuse`) and the types inferred from that code. The module's
+//! bytecode is put together from the items in order, and an item's code is
+//! reused when its dependency key is unchanged. The key hashes the item's
+//! text with the keys of the items that last declared the names it mentions.
+//! It therefore changes when such a declaration is edited, removed or moved
+//! past the item, and also when anything that declaration depends on
+//! changes. Borrow checking threads state through the module in order, so
+//! it is re-run over the cached ASTs on every update (no reparsing needed).
+
+use crate::compiler::Codegen;
+use crate::compiler::borrow_ck::BorrowChecker;
+use crate::compiler::reuse::CompiledItem;
+use crate::lsp::analysis::{
+    self, Analysis, Declaration, Diagnostic, DiagnosticSeverity, InferredTypes, LineIndex, Range,
+};
+use crate::vm::opcodes::OpCode;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use swc_common::{DUMMY_SP, FileName, SourceMap, Spanned, sync::Lrc};
+use swc_ecma_ast::{Module, ModuleItem};
+use swc_ecma_parser::{Parser, StringInput, lexer::Lexer};
+
+/// One `contentChanges` entry; `range: None` replaces the whole document.
+#[derive(Debug, Clone)]
+pub struct TextChange {
+    pub range: Option<Range>,
+    pub text: String,
+}
+
+/// What the last update had to redo.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct UpdateStats {
+    pub full_reparse: bool,
+    pub reparsed_items: usize,
+    /// Items compiled again, which also infers their types again
+    pub recompiled_items: usize,
+    /// Items whose code was reused
+    pub reused_items: usize,
+}
+
+/// A top-level statement or module declaration.
+struct Item {
+    /// Byte range in the document
+    start: usize,
+    len: usize,
+    /// Single-item module holding the AST
+    module: Module,
+    hash: u64,
+    /// Identifier-like words appearing in the item (over-approximates uses)
+    references: HashSet<String>,
+    /// Declarations with offsets relative to `start`
+    declarations: Vec<Declaration>,
+    /// Code and types from the last compile, and the dependency key it had
+    compiled: Option<(u64, CompiledItem, InferredTypes)>,
+}
+
+impl Item {
+    /// `origin` is the span position of the item's first byte.
+    fn new(item: ModuleItem, origin: usize, start: usize, text: &str) -> Self {
+        let len = item.span().hi.0 as usize - origin;
+        let module = Module {
+            span: DUMMY_SP,
+            body: vec![item],
+            shebang: None,
+        };
+        let declarations = analysis::collect_declarations(&module, origin);
+        let source = &text[start..start + len];
+        Self {
+            start,
+            len,
+            module,
+            hash: hash_text(source),
+            references: identifiers(source),
+            declarations,
+            compiled: None,
+        }
+    }
+}
+
+/// An open document with its cached per-item analysis.
+pub struct IncrementalDocument {
+    pub path: String,
+    text: String,
+    /// `None` after a parse failure; the next edit reparses the whole file
+    items: Option<Vec<Item>>,
+    pub analysis: Analysis,
+    /// Bytecode of the whole module; empty while it does not parse
+    pub program: Vec<OpCode>,
+    pub last_update: UpdateStats,
+}
+
+impl IncrementalDocument {
+    pub fn open(path: String, text: String) -> Self {
+        let mut doc = Self {
+            analysis: Analysis {
+                index: LineIndex::new(""),
+                diagnostics: Vec::new(),
+                declarations: Vec::new(),
+                types: InferredTypes::default(),
+            },
+            path,
+            text,
+            items: None,
+            program: Vec::new(),
+            last_update: UpdateStats::default(),
+        };
+        doc.apply_changes(&[]);
+        doc
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Apply edits in order and bring the analysis up to date.
+    pub fn apply_changes(&mut self, changes: &[TextChange]) {
+        let mut stats = UpdateStats::default();
+        let mut items = self.items.take();
+        let mut needs_full = items.is_none();
+
+        for change in changes {
+            let Some(range) = change.range else {
+                self.text = change.text.clone();
+                needs_full = true;
+                continue;
+            };
+            let index = LineIndex::new(&self.text);
+            let start = index.offset(range.start);
+            let end = index.offset(range.end).max(start);
+            self.text.replace_range(start..end, &change.text);
+
+            if !needs_full && let Some(list) = &mut items {
+                if splice(list, &self.text, &self.path, start, end, change.text.len()) {
+                    stats.reparsed_items += 1;
+                } else {
+                    needs_full = true;
+                }
+            }
+        }
+
+        if needs_full {
+            stats.full_reparse = true;
+            match reparse_all(&self.text, &self.path, items.unwrap_or_default()) {
+                Some(list) => items = Some(list),
+                None => {
+                    // Report syntax errors the same way a one-shot analysis does
+                    self.items = None;
+                    self.program.clear();
+                    self.analysis = analysis::analyze(&self.text, &self.path);
+                    self.last_update = stats;
+                    return;
+                }
+            }
+        }
+
+        let mut list = items.unwrap_or_default();
+        (self.analysis, self.program) = analyze_items(&self.text, &mut list, &mut stats);
+        self.items = Some(list);
+        self.last_update = stats;
+    }
+}
+
+/// Reparse the one item containing the edit `[start, old_end)` (now
+/// `new_len` bytes long). Returns false if the edit touches an item
+/// boundary or the new item text does not parse as exactly one item.
+fn splice(
+    items: &mut [Item],
+    text: &str,
+    path: &str,
+    start: usize,
+    old_end: usize,
+    new_len: usize,
+) -> bool {
+    let Some(idx) = items
+        .iter()
+        .position(|item| item.start < start && old_end < item.start + item.len)
+    else {
+        return false;
+    };
+    let item_start = items[idx].start;
+    let item_len = items[idx].len + new_len - (old_end - start);
+    let Some(source) = text.get(item_start..item_start + item_len) else {
+        return false;
+    };
+    // Without a closing `;` or `}` the item's end depends on what follows it
+    if !source.ends_with(';') && !source.ends_with('}') {
+        return false;
+    }
+
+    let Some((module, base)) = parse(source, path) else {
+        return false;
+    };
+    let mut body = module.body;
+    if body.len() != 1 {
+        return false;
+    }
+    let item = body.remove(0);
+    let span = item.span();
+    if span.lo.0 as usize != base || span.hi.0 as usize - base != item_len {
+        return false;
+    }
+
+    items[idx] = Item::new(item, base, item_start, text);
+    let delta = new_len as isize - (old_end - start) as isize;
+    for later in &mut items[idx + 1..] {
+        later.start = (later.start as isize + delta) as usize;
+    }
+    true
+}
+
+/// Parse the whole file into items, keeping the compiled code of items whose
+/// text is unchanged. `analyze_items` decides whether it can still be used.
+fn reparse_all(text: &str, path: &str, previous: Vec<Item>) -> Option<Vec<Item>> {
+    let (module, base) = parse(text, path)?;
+
+    let mut cached: HashMap<u64, Item> = HashMap::new();
+    for item in previous {
+        cached.entry(item.hash).or_insert(item);
+    }
+
+    let mut items = Vec::with_capacity(module.body.len());
+    for module_item in module.body {
+        let origin = module_item.span().lo.0 as usize;
+        let mut item = Item::new(module_item, origin, origin - base, text);
+        if let Some(old) = cached.remove(&item.hash) {
+            item.compiled = old.compiled;
+        }
+        items.push(item);
+    }
+    Some(items)
+}
+
+fn parse(source: &str, path: &str) -> Option<(Module, usize)> {
+    let cm: Lrc<SourceMap> = Default::default();
+    let fm = cm.new_source_file(
+        FileName::Custom(path.to_string()).into(),
+        source.to_string(),
+    );
+    let base = fm.start_pos.0 as usize;
+    let lexer = Lexer::new(
+        analysis::syntax_for_path(path),
+        Default::default(),
+        StringInput::from(&*fm),
+        None,
+    );
+    let mut parser = Parser::new_from(lexer);
+    let module = parser.parse_module().ok()?;
+    parser.take_errors().is_empty().then_some((module, base))
+}
+
+/// Put the module's code together, compiling only the items whose
+/// dependency key changed, then borrow check it and assemble the analysis.
+fn analyze_items(
+    text: &str,
+    items: &mut [Item],
+    stats: &mut UpdateStats,
+) -> (Analysis, Vec<OpCode>) {
+    let index = LineIndex::new(text);
+    let first_line = |item: &Item| {
+        let len = text[item.start..item.start + item.len]
+            .find('\n')
+            .unwrap_or(item.len);
+        index.range(item.start, item.start + len)
+    };
+
+    let mut codegen = Codegen::new();
+    let mut types = InferredTypes::default();
+    let mut compile_diagnostics = Vec::new();
+    // Dependency key of the item that last changed each top-level name
+    let mut declared_by: HashMap<String, u64> = HashMap::new();
+    for item in items.iter_mut() {
+        let mut deps: Vec<(&String, u64)> = item
+            .references
+            .iter()
+            .filter_map(|word| declared_by.get(word).map(|key| (word, *key)))
+            .collect();
+        deps.sort_unstable();
+        let mut hasher = DefaultHasher::new();
+        (item.hash, deps).hash(&mut hasher);
+        let key = hasher.finish();
+
+        let (compiled, item_types) = match item.compiled.take() {
+            Some((old_key, compiled, item_types)) if old_key == key => {
+                codegen.reuse_item(&compiled);
+                stats.reused_items += 1;
+                (compiled, item_types)
+            }
+            _ => {
+                let compiled = codegen.generate_item(&item.module.body[0]);
+                let mut bytecode = compiled.code().to_vec();
+                bytecode.push(OpCode::Halt);
+                stats.recompiled_items += 1;
+                (compiled, analysis::infer_types(&bytecode))
+            }
+        };
+        for name in compiled.names() {
+            declared_by.insert(name.to_string(), key);
+        }
+        types.merge(&item_types);
+
+        // Reported like a one-shot analysis does, but on the item
+        let messages = compiled
+            .errors()
+            .iter()
+            .map(|e| (DiagnosticSeverity::Error, e))
+            .chain(
+                compiled
+                    .warnings()
+                    .iter()
+                    .map(|w| (DiagnosticSeverity::Warning, w)),
+            );
+        for (severity, message) in messages {
+            compile_diagnostics.push(Diagnostic {
+                range: first_line(&*item),
+                severity,
+                message: message
+                    .strip_prefix("Warning: ")
+                    .unwrap_or(message)
+                    .to_string(),
+            });
+        }
+        item.compiled = Some((key, compiled, item_types));
+    }
+    codegen.instructions.push(OpCode::Halt);
+
+    // Same sequence as Compiler::compile_with_syntax, stopping at the first
+    // error, which is reported on the item that caused it
+    let mut diagnostics = Vec::new();
+    let mut checker = BorrowChecker::new();
+    checker.enter_scope();
+    for item in items.iter() {
+        if let Some(ModuleItem::Stmt(stmt)) = item.module.body.first()
+            && let Err(message) = checker.analyze_stmt(stmt)
+        {
+            diagnostics.push(Diagnostic {
+                range: first_line(item),
+                severity: DiagnosticSeverity::Error,
+                message,
+            });
+            break;
+        }
+    }
+    checker.exit_scope();
+    diagnostics.extend(compile_diagnostics);
+
+    // Item-relative declarations become document offsets
+    let mut declarations = Vec::new();
+    for item in items.iter() {
+        let first = declarations.len();
+        let shift = |(lo, hi): (usize, usize)| (lo + item.start, hi + item.start);
+        declarations.extend(item.declarations.iter().map(|d| {
+            let mut d = d.clone();
+            d.span = shift(d.span);
+            d.name_span = shift(d.name_span);
+            if d.scope.1 != usize::MAX {
+                d.scope = shift(d.scope);
+            }
+            d.container = d.container.map(|c| c + first);
+            d
+        }));
+    }
+
+    let analysis = Analysis {
+        index,
+        diagnostics,
+        declarations,
+        types,
+    };
+    (analysis, codegen.instructions)
+}
+
+fn hash_text(text: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    text.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Identifier-like words in `text`, including ones inside strings and comments.
+fn identifiers(text: &str) -> HashSet<String> {
+    let mut words = HashSet::new();
+    let mut current = String::new();
+    for c in text.chars().chain(std::iter::once(' ')) {
+        if c.is_alphanumeric() || c == '_' || c == '$' {
+            current.push(c);
+        } else if !current.is_empty() {
+            if !current.starts_with(|c: char| c.is_ascii_digit()) {
+                words.insert(current.clone());
+            }
+            current.clear();
+        }
+    }
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lsp::analysis::Position;
+    use crate::vm::value::JsValue;
+
+    fn edit(doc: &IncrementalDocument, from: usize, to: usize, text: &str) -> TextChange {
+        let index = LineIndex::new(doc.text());
+        TextChange {
+            range: Some(index.range(from, to)),
+            text: text.to_string(),
+        }
+    }
+
+    const SOURCE: &str = "function f(a) {\n    return a + 1;\n}\n\nfunction g() {\n    return 2;\n}\n\nlet x = f(1);\n";
+
+    #[test]
+    fn test_edit_inside_function_reparses_one_item() {
+        let mut doc = IncrementalDocument::open("test.ot".to_string(), SOURCE.to_string());
+        assert!(doc.last_update.full_reparse);
+        assert_eq!(doc.last_update.recompiled_items, 3);
+
+        let at = SOURCE.find("a + 1").unwrap() + 4;
+        let change = edit(&doc, at, at + 1, "10");
+        doc.apply_changes(&[change]);
+
+        // f changed; `let x = f(1)` depends on it, g does not
+        assert_eq!(
+            doc.last_update,
+            UpdateStats {
+                full_reparse: false,
+                reparsed_items: 1,
+                recompiled_items: 2,
+                reused_items: 1,
+            }
+        );
+        assert!(doc.text().contains("return a + 10;"));
+
+        // Offsets after the edit line up with a from-scratch analysis
+        let fresh = analysis::analyze(doc.text(), "test.ot");
+        let spans = |a: &Analysis| -> Vec<_> {
+            a.declarations
+                .iter()
+                .map(|d| (d.name.clone(), d.span, d.name_span, d.container))
+                .collect()
+        };
+        assert_eq!(spans(&doc.analysis), spans(&fresh));
+    }
+
+    #[test]
+    fn test_reused_code_matches_a_full_compile() {
+        let source = "const enum Size { Small = 1 }\n\n\
+                      const enum Limit { Max = Size.Small * 10 }\n\n\
+                      function pick(n) {\n    return n + Limit.Max;\n}\n\n\
+                      function other(k) {\n    switch (k) {\n        case 1: return \"one\";\n        default: return \"many\";\n    }\n}\n\n\
+                      let picked = pick(1);\n";
+        let full = |doc: &IncrementalDocument| {
+            let (module, _) = parse(doc.text(), "test.ot").unwrap();
+            format!("{:?}", Codegen::new().generate(&module))
+        };
+        let mut doc = IncrementalDocument::open("test.ot".to_string(), source.to_string());
+        assert_eq!(doc.last_update.recompiled_items, 5);
+        assert_eq!(format!("{:?}", doc.program), full(&doc));
+
+        // pick grows, so `other` and its switch local move when reused
+        let at = source.find("n + ").unwrap();
+        doc.apply_changes(&[edit(&doc, at, at + 1, "n * 2")]);
+        assert_eq!(doc.last_update.recompiled_items, 2);
+        assert_eq!(doc.last_update.reused_items, 3);
+        assert_eq!(format!("{:?}", doc.program), full(&doc));
+
+        // Limit folds Size and pick inlines Limit, so editing Size reaches
+        // pick (and `picked`) through Limit
+        let at = doc.text().find("Small = 1").unwrap() + 8;
+        doc.apply_changes(&[edit(&doc, at, at + 1, "3")]);
+        assert_eq!(doc.last_update.recompiled_items, 4);
+        assert_eq!(doc.last_update.reused_items, 1);
+        assert_eq!(format!("{:?}", doc.program), full(&doc));
+        assert!(
+            doc.program
+                .iter()
+                .any(|op| matches!(op, OpCode::Push(JsValue::Number(n)) if *n == 30.0))
+        );
+    }
+
+    #[test]
+    fn test_boundary_edit_falls_back_to_full_reparse() {
+        let mut doc = IncrementalDocument::open("test.ot".to_string(), SOURCE.to_string());
+        let at = SOURCE.find("let x").unwrap();
+        let change = edit(&doc, at, at, "let y = 3;\n");
+        doc.apply_changes(&[change]);
+
+        assert!(doc.last_update.full_reparse);
+        // Only the new statement is compiled; unchanged items are reused
+        assert_eq!(doc.last_update.recompiled_items, 1);
+        assert_eq!(doc.last_update.reused_items, 3);
+        assert!(doc.analysis.resolve("y", doc.text().len()).is_some());
+    }
+
+    #[test]
+    fn test_recovers_after_syntax_error() {
+        let mut doc = IncrementalDocument::open("test.ot".to_string(), SOURCE.to_string());
+        let at = SOURCE.find("return 2").unwrap();
+        let broken = edit(&doc, at, at + 8, "return (");
+        doc.apply_changes(&[broken]);
+        assert!(!doc.analysis.diagnostics.is_empty());
+
+        let fixed = TextChange {
+            range: Some(Range {
+                start: Position {
+                    line: 5,
+                    character: 4,
+                },
+                end: Position {
+                    line: 5,
+                    character: 12,
+                },
+            }),
+            text: "return 2".to_string(),
+        };
+        doc.apply_changes(&[fixed]);
+        assert_eq!(doc.text(), SOURCE);
+        assert!(doc.analysis.diagnostics.is_empty());
+        assert!(doc.last_update.full_reparse);
+    }
+}
@@ -0,0 +1,547 @@
+//! Language server (`oitec lsp`)
+//!
+//! Speaks JSON-RPC 2.0 over stdio with `Content-Length` framing. Supports:
+//! - textDocument/publishDiagnostics (syntax, ownership and lifetime errors)
+//! - textDocument/hover (types inferred by `ir::typecheck`)
+//! - textDocument/definition (follows imports across the module graph)
+//! - textDocument/documentSymbol (hierarchical outline)
+//!
+//! Documents are synced incrementally: an edit inside a single top-level
+//! item reparses only that item. Only that item and the items that depend on
+//! its declarations are compiled again. The others reuse their code (see
+//! `incremental`).
+
+pub mod analysis;
+pub mod incremental;
+
+use crate::lsp::analysis::{Analysis, Declaration, Position, Range, SymbolKind};
+use crate::lsp::incremental::{IncrementalDocument, TextChange};
+use crate::manifest::Manifest;
+use crate::module::ModuleResolver;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+/// JSON-RPC error code for unknown methods
+const METHOD_NOT_FOUND: i64 = -32601;
+
+/// Run the language server until the client sends `exit`.
+pub fn run_server() -> Result<(), String> {
+    let stdin = io::stdin();
+    let mut input = stdin.lock();
+    let stdout = io::stdout();
+    let mut output = stdout.lock();
+
+    let mut server = Server::new();
+    while let Some(message) = read_message(&mut input)? {
+        for reply in server.handle(&message) {
+            write_message(&mut output, &reply)?;
+        }
+        if server.exit {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Read one framed message. Returns `None` on end of input.
+fn read_message(input: &mut impl BufRead) -> Result<Option<Value>, String> {
+    let mut content_length = None;
+    loop {
+        let mut line = String::new();
+        let n = input.read_line(&mut line).map_err(|e| e.to_string())?;
+        if n == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(len) = line.strip_prefix("Content-Length:") {
+            content_length = len.trim().parse::<usize>().ok();
+        }
+    }
+
+    let len = content_length.ok_or("Missing Content-Length header")?;
+    let mut body = vec![0u8; len];
+    input.read_exact(&mut body).map_err(|e| e.to_string())?;
+    serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(|e| format!("Invalid JSON-RPC message: {}", e))
+}
+
+fn write_message(output: &mut impl Write, message: &Value) -> Result<(), String> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)
+        .and_then(|_| output.flush())
+        .map_err(|e| e.to_string())
+}
+
+/// Server state: open documents and lifecycle flags.
+pub struct Server {
+    documents: HashMap<String, IncrementalDocument>,
+    shutdown: bool,
+    exit: bool,
+}
+
+impl Default for Server {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Server {
+    pub fn new() -> Self {
+        Self {
+            documents: HashMap::new(),
+            shutdown: false,
+            exit: false,
+        }
+    }
+
+    /// Handle one incoming message, returning responses and notifications to send.
+    pub fn handle(&mut self, message: &Value) -> Vec<Value> {
+        let method = message["method"].as_str().unwrap_or("");
+        let params = &message["params"];
+        let id = message.get("id").cloned();
+
+        let mut out = Vec::new();
+        let result = match method {
+            "initialize" => Some(json!({
+                "capabilities": {
+                    "textDocumentSync": 2,
+                    "hoverProvider": true,
+                    "definitionProvider": true,
+                    "documentSymbolProvider": true,
+                },
+                "serverInfo": { "name": "oitec", "version": env!("CARGO_PKG_VERSION") },
+            })),
+            "shutdown" => {
+                self.shutdown = true;
+                Some(Value::Null)
+            }
+            "exit" => {
+                self.exit = true;
+                None
+            }
+            "textDocument/didOpen" => {
+                let uri = params["textDocument"]["uri"].as_str().unwrap_or("");
+                let text = params["textDocument"]["text"].as_str().unwrap_or("");
+                let doc = IncrementalDocument::open(uri_to_path(uri), text.to_string());
+                self.documents.insert(uri.to_string(), doc);
+                out.push(self.diagnostics(uri));
+                None
+            }
+            "textDocument/didChange" => {
+                let uri = params["textDocument"]["uri"].as_str().unwrap_or("");
+                let changes: Vec<TextChange> = params["contentChanges"]
+                    .as_array()
+                    .map(|changes| changes.iter().filter_map(parse_change).collect())
+                    .unwrap_or_default();
+                if let Some(doc) = self.documents.get_mut(uri) {
+                    doc.apply_changes(&changes);
+                    out.push(self.diagnostics(uri));
+                } else if let Some(change) = changes.iter().rfind(|c| c.range.is_none()) {
+                    // Change for a document we never saw opened; only usable if whole
+                    let doc = IncrementalDocument::open(uri_to_path(uri), change.text.clone());
+                    self.documents.insert(uri.to_string(), doc);
+                    out.push(self.diagnostics(uri));
+                }
+                None
+            }
+            "textDocument/didClose" => {
+                let uri = params["textDocument"]["uri"].as_str().unwrap_or("");
+                self.documents.remove(uri);
+                out.push(publish_diagnostics(uri, Vec::new()));
+                None
+            }
+            "textDocument/hover" => Some(self.hover(params)),
+            "textDocument/definition" => Some(self.definition(params)),
+            "textDocument/documentSymbol" => Some(self.document_symbols(params)),
+            _ => {
+                if let Some(id) = id {
+                    out.push(json!({
+                        "jsonrpc": "2.0",
+                        "id": id,
+                        "error": {
+                            "code": METHOD_NOT_FOUND,
+                            "message": format!("Unsupported method: {}", method),
+                        },
+                    }));
+                }
+                return out;
+            }
+        };
+
+        if let (Some(id), Some(result)) = (id, result) {
+            out.insert(0, json!({ "jsonrpc": "2.0", "id": id, "result": result }));
+        }
+        out
+    }
+
+    /// Build the publishDiagnostics notification for an open document.
+    fn diagnostics(&self, uri: &str) -> Value {
+        let Some(doc) = self.documents.get(uri) else {
+            return publish_diagnostics(uri, Vec::new());
+        };
+        let diagnostics = doc
+            .analysis
+            .diagnostics
+            .iter()
+            .map(|d| {
+                json!({
+                    "range": range_json(d.range),
+                    "severity": d.severity as u32,
+                    "source": "oitec",
+                    "message": d.message,
+                })
+            })
+            .collect();
+        publish_diagnostics(uri, diagnostics)
+    }
+
+    fn document_at(&self, params: &Value) -> Option<(&IncrementalDocument, usize)> {
+        let uri = params["textDocument"]["uri"].as_str()?;
+        let doc = self.documents.get(uri)?;
+        let position = Position {
+            line: params["position"]["line"].as_u64()? as u32,
+            character: params["position"]["character"].as_u64()? as u32,
+        };
+        Some((doc, doc.analysis.index.offset(position)))
+    }
+
+    fn hover(&self, params: &Value) -> Value {
+        let Some((doc, offset)) = self.document_at(params) else {
+            return Value::Null;
+        };
+        match doc.analysis.hover_at(offset) {
+            Some((markdown, range)) => json!({
+                "contents": { "kind": "markdown", "value": markdown },
+                "range": range_json(range),
+            }),
+            None => Value::Null,
+        }
+    }
+
+    fn definition(&self, params: &Value) -> Value {
+        let Some((doc, offset)) = self.document_at(params) else {
+            return Value::Null;
+        };
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or("");
+        let Some(decl) = doc.analysis.definition_at(offset) else {
+            return Value::Null;
+        };
+
+        // Imports jump to the exporting module's declaration
+        if let Some((source, imported)) = &decl.import
+            && let Some(location) = resolve_import(&doc.path, source, imported)
+        {
+            return location;
+        }
+
+        let range = doc.analysis.index.range(decl.name_span.0, decl.name_span.1);
+        json!({ "uri": uri, "range": range_json(range) })
+    }
+
+    fn document_symbols(&self, params: &Value) -> Value {
+        let Some(doc) = params["textDocument"]["uri"]
+            .as_str()
+            .and_then(|uri| self.documents.get(uri))
+        else {
+            return Value::Null;
+        };
+        Value::Array(symbol_tree(&doc.analysis, None))
+    }
+}
+
+/// Build nested DocumentSymbol objects for declarations under `container`.
+fn symbol_tree(analysis: &Analysis, container: Option<usize>) -> Vec<Value> {
+    analysis
+        .declarations
+        .iter()
+        .enumerate()
+        .filter(|(_, d)| d.container == container && d.kind != SymbolKind::Parameter)
+        .map(|(idx, d)| {
+            json!({
+                "name": d.name,
+                "kind": d.kind.lsp_kind(),
+                "range": range_json(analysis.index.range(d.span.0, d.span.1)),
+                "selectionRange": range_json(analysis.index.range(d.name_span.0, d.name_span.1)),
+                "children": symbol_tree(analysis, Some(idx)),
+            })
+        })
+        .collect()
+}
+
+/// Locate an imported binding in the module it comes from.
+fn resolve_import(importer: &str, specifier: &str, imported: &str) -> Option<Value> {
+    let importer = Path::new(importer);
+    let resolved = ModuleResolver::for_manifest(&Manifest::discover_or_default(importer))
+        .resolve(specifier, importer)
+        .ok()?;
+    let target: &PathBuf = &resolved.path;
+    let uri = crate::platform::file_url(target);
+
+    let source = std::fs::read_to_string(target).ok()?;
+    let target_path = target.to_string_lossy();
+    let analysis = analysis::analyze(&source, &target_path);
+    let range = match analysis.export_named(imported) {
+        Some(Declaration { name_span, .. }) => analysis.index.range(name_span.0, name_span.1),
+        // Namespace imports (or unknown exports) point at the module itself
+        None => analysis.index.range(0, 0),
+    };
+    Some(json!({ "uri": uri, "range": range_json(range) }))
+}
+
+/// Parse one `TextDocumentContentChangeEvent`; a missing range replaces the document.
+fn parse_change(change: &Value) -> Option<TextChange> {
+    let position = |p: &Value| {
+        Some(Position {
+            line: p["line"].as_u64()? as u32,
+            character: p["character"].as_u64()? as u32,
+        })
+    };
+    let range = match change.get("range") {
+        Some(range) if !range.is_null() => Some(Range {
+            start: position(&range["start"])?,
+            end: position(&range["end"])?,
+        }),
+        _ => None,
+    };
+    Some(TextChange {
+        range,
+        text: change["text"].as_str()?.to_string(),
+    })
+}
+
+fn publish_diagnostics(uri: &str, diagnostics: Vec<Value>) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": { "uri": uri, "diagnostics": diagnostics },
+    })
+}
+
+fn range_json(range: Range) -> Value {
+    json!({
+        "start": { "line": range.start.line, "character": range.start.character },
+        "end": { "line": range.end.line, "character": range.end.character },
+    })
+}
+
+/// Convert a `file://` URI to a filesystem path (percent-decoding bytes).
+fn uri_to_path(uri: &str) -> String {
+    let raw = uri.strip_prefix("file://").unwrap_or(uri);
+    let bytes = raw.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%'
+            && i + 2 < bytes.len()
+            && let Some(byte) = std::str::from_utf8(&bytes[i + 1..i + 3])
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        {
+            decoded.push(byte);
+            i += 3;
+            continue;
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(id: u64, method: &str, params: Value) -> Value {
+        json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })
+    }
+
+    fn notification(method: &str, params: Value) -> Value {
+        json!({ "jsonrpc": "2.0", "method": method, "params": params })
+    }
+
+    fn open(server: &mut Server, uri: &str, text: &str) -> Vec<Value> {
+        server.handle(&notification(
+            "textDocument/didOpen",
+            json!({ "textDocument": { "uri": uri, "languageId": "typescript", "version": 1, "text": text } }),
+        ))
+    }
+
+    fn at(uri: &str, line: u32, character: u32) -> Value {
+        json!({
+            "textDocument": { "uri": uri },
+            "position": { "line": line, "character": character },
+        })
+    }
+
+    #[test]
+    fn test_messages_are_framed_by_content_length() {
+        let mut buffer = Vec::new();
+        write_message(&mut buffer, &request(1, "shutdown", Value::Null)).unwrap();
+        write_message(&mut buffer, &notification("exit", Value::Null)).unwrap();
+
+        let mut input = io::Cursor::new(buffer);
+        let first = read_message(&mut input).unwrap().unwrap();
+        assert_eq!(first["method"], "shutdown");
+        assert_eq!(first["id"], 1);
+        let second = read_message(&mut input).unwrap().unwrap();
+        assert_eq!(second["method"], "exit");
+        assert_eq!(read_message(&mut input).unwrap(), None);
+
+        let mut missing = io::Cursor::new(b"Content-Type: x\r\n\r\n{}".to_vec());
+        assert!(read_message(&mut missing).is_err());
+    }
+
+    #[test]
+    fn test_initialize_and_lifecycle() {
+        let mut server = Server::new();
+        let replies = server.handle(&request(1, "initialize", json!({ "capabilities": {} })));
+        assert_eq!(replies.len(), 1);
+        assert_eq!(replies[0]["id"], 1);
+        let capabilities = &replies[0]["result"]["capabilities"];
+        assert_eq!(capabilities["textDocumentSync"], 2);
+        assert_eq!(capabilities["hoverProvider"], true);
+        assert_eq!(capabilities["definitionProvider"], true);
+
+        let replies = server.handle(&request(2, "workspace/unknown", json!({})));
+        assert_eq!(replies[0]["error"]["code"], METHOD_NOT_FOUND);
+
+        let replies = server.handle(&request(3, "shutdown", Value::Null));
+        assert_eq!(replies[0]["result"], Value::Null);
+        assert!(server.handle(&notification("exit", Value::Null)).is_empty());
+        assert!(server.exit);
+    }
+
+    #[test]
+    fn test_did_open_and_did_change_publish_diagnostics() {
+        let mut server = Server::new();
+        let uri = "file:///tmp/oite_lsp_diagnostics.ot";
+        let replies = open(&mut server, uri, "let a = 1;\nlet b = ;\n");
+        assert_eq!(replies.len(), 1);
+        assert_eq!(replies[0]["method"], "textDocument/publishDiagnostics");
+        assert_eq!(replies[0]["params"]["uri"], uri);
+        let diagnostics = replies[0]["params"]["diagnostics"].as_array().unwrap();
+        assert!(!diagnostics.is_empty());
+        assert_eq!(diagnostics[0]["range"]["start"]["line"], 1);
+
+        // An incremental edit that completes the statement clears them
+        let replies = server.handle(&notification(
+            "textDocument/didChange",
+            json!({
+                "textDocument": { "uri": uri, "version": 2 },
+                "contentChanges": [{
+                    "range": {
+                        "start": { "line": 1, "character": 8 },
+                        "end": { "line": 1, "character": 8 },
+                    },
+                    "text": "a",
+                }],
+            }),
+        ));
+        assert_eq!(replies[0]["params"]["diagnostics"], json!([]));
+
+        // A change without a range replaces the whole document
+        let replies = server.handle(&notification(
+            "textDocument/didChange",
+            json!({
+                "textDocument": { "uri": uri, "version": 3 },
+                "contentChanges": [{ "text": "let c = ;\n" }],
+            }),
+        ));
+        let diagnostics = replies[0]["params"]["diagnostics"].as_array().unwrap();
+        assert_eq!(diagnostics[0]["range"]["start"]["line"], 0);
+
+        let replies = server.handle(&notification(
+            "textDocument/didClose",
+            json!({ "textDocument": { "uri": uri } }),
+        ));
+        assert_eq!(replies[0]["params"]["diagnostics"], json!([]));
+    }
+
+    #[test]
+    fn test_hover_shows_inferred_types() {
+        let mut server = Server::new();
+        let uri = "file:///tmp/oite_lsp_hover.ot";
+        open(
+            &mut server,
+            uri,
+            "function add(a: number, b: number): number {\n  return a + b;\n}\nlet total = add(1, 2);\nlet count = 3;\n",
+        );
+
+        let replies = server.handle(&request(1, "textDocument/hover", at(uri, 3, 13)));
+        let hover = &replies[0]["result"];
+        assert_eq!(hover["contents"]["kind"], "markdown");
+        let text = hover["contents"]["value"].as_str().unwrap();
+        assert!(text.contains("function add(a: "), "{}", text);
+        assert_eq!(
+            hover["range"]["start"],
+            json!({ "line": 3, "character": 12 })
+        );
+
+        let replies = server.handle(&request(2, "textDocument/hover", at(uri, 4, 5)));
+        let text = replies[0]["result"]["contents"]["value"].as_str().unwrap();
+        assert!(text.contains("count: num"), "{}", text);
+
+        // Nothing to show on whitespace or in an unopened document
+        let replies = server.handle(&request(3, "textDocument/hover", at(uri, 2, 5)));
+        assert_eq!(replies[0]["result"], Value::Null);
+        let replies = server.handle(&request(
+            4,
+            "textDocument/hover",
+            at("file:///tmp/unopened.ot", 0, 0),
+        ));
+        assert_eq!(replies[0]["result"], Value::Null);
+    }
+
+    #[test]
+    fn test_definition_in_file_and_across_imports() {
+        let dir = std::env::temp_dir().join(format!("oite_lsp_definition_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let math = dir.join("math.ot");
+        std::fs::write(
+            &math,
+            "// helpers\nexport function square(x) {\n  return x * x;\n}\n",
+        )
+        .unwrap();
+        let main = dir.join("main.ot");
+        let source = "import { square } from \"./math\";\nlet n = 3;\nlet s = square(n);\n";
+        std::fs::write(&main, source).unwrap();
+        let uri = crate::platform::file_url(&main);
+
+        let mut server = Server::new();
+        open(&mut server, &uri, source);
+
+        // A local binding resolves within the document
+        let replies = server.handle(&request(1, "textDocument/definition", at(&uri, 2, 15)));
+        let location = &replies[0]["result"];
+        assert_eq!(location["uri"], uri.as_str());
+        assert_eq!(
+            location["range"]["start"],
+            json!({ "line": 1, "character": 4 })
+        );
+
+        // An imported one resolves to the exporting module's declaration
+        let replies = server.handle(&request(2, "textDocument/definition", at(&uri, 2, 9)));
+        let location = &replies[0]["result"];
+        let target = std::fs::canonicalize(&math).unwrap();
+        assert_eq!(location["uri"], crate::platform::file_url(&target).as_str());
+        assert_eq!(
+            location["range"]["start"],
+            json!({ "line": 1, "character": 16 })
+        );
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_uri_to_path_decodes_percent_escapes() {
+        assert_eq!(uri_to_path("file:///tmp/my%20dir/a.ot"), "/tmp/my dir/a.ot");
+        assert_eq!(uri_to_path("file:///tmp/100%"), "/tmp/100%");
+        assert_eq!(uri_to_path("/plain/path.ot"), "/plain/path.ot");
+    }
+}
@@ -22,161 +22,485 @@
 #![allow(clippy::field_reassign_with_default)]
 
 mod backend;
+mod bench;
 mod compiler;
-use compiler::Compiler;
+use compiler::borrow_ck::BorrowCheckLevel;
+mod doctor;
+mod driver;
+mod formatter;
 mod ir;
 mod loader;
+mod lsp;
+mod manifest;
+mod module;
+mod package;
+mod platform;
 mod runtime;
 mod stdlib;
+mod transform;
 pub mod types;
 mod vm;
 
-use swc_ecma_parser::{Syntax, TsSyntax};
-
+use crate::driver::{
+    BOOTSTRAP_FILES, CompilationDriver, keep_top_level_result, read_source, syntax_for_path,
+};
 use crate::ir::IrModule;
 use crate::loader::BytecodeDecoder;
-use crate::vm::VM;
+use crate::manifest::Manifest;
+use crate::stdlib::console::ConsoleLocale;
+use crate::vm::permissions::{self, Permissions};
+use crate::vm::{Coverage, Tracer, VM};
 use std::env;
 use std::fs;
 use std::path::{Path, PathBuf};
 #[cfg(test)]
 mod tests;
 
-/// Default path for the prelude file
-const PRELUDE_PATH: &str = "std/prelude.ot";
-
-/// Bootstrap compiler files (loaded in order when running bootstrap tests)
-const BOOTSTRAP_FILES: &[&str] = &[
-    "bootstrap/types.ot",
-    "bootstrap/lexer.ot",
-    "bootstrap/parser.ot",
-    "bootstrap/emitter.ot",
-    "bootstrap/ir.ot",
-    "bootstrap/ir_builder.ot",
-    "bootstrap/codegen.ot",
-    "bootstrap/pipeline.ot",
-];
-
-/// Modular compiler files (loaded in dependency order)
-const MODULAR_COMPILER_FILES: &[&str] = &[
-    // Level 1: No dependencies
-    "compiler/lexer/token.ot",
-    "compiler/ast/types.ot",
-    "compiler/ir/mod.ot",
-    // Level 2: Depends on level 1
-    "compiler/lexer/mod.ot",
-    "compiler/parser/expr.ot",
-    "compiler/parser/stmt.ot",
-    "compiler/ir/builder.ot",
-    "compiler/passes/types.ot",
-    "compiler/passes/typecheck.ot",
-    "compiler/passes/opt.ot",
-    "compiler/passes/lifetime_constraints.ot",
-    "compiler/passes/borrow_ck.ot",
-    // Level 3: Depends on level 2
-    "compiler/parser/mod.ot",
-    "compiler/passes/mod.ot",
-    "compiler/codegen/mod.ot",
-    // Level 4: Depends on level 3
-    "compiler/codegen/emitter.ot",
-    // Level 5: Backend modules
-    "compiler/backend/llvm/runtime.ot",
-    "compiler/backend/llvm/types.ot",
-    "compiler/backend/llvm/mod.ot",
-    // Level 6: Top-level pipeline
-    "compiler/pipeline.ot",
-];
-
-/// Helper to load and run a script file
-fn load_and_run_script(
-    vm: &mut VM,
-    compiler: &mut Compiler,
-    path: &str,
-    append: bool,
-) -> Result<(), String> {
-    let source = fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
-
-    // Determine syntax based on file extension
-    let syntax = if path.ends_with(".ts") || path.ends_with(".tsx") {
-        // Enable decorators for TypeScript
-        let ts_syntax = TsSyntax {
-            decorators: true,
-            tsx: path.ends_with(".tsx"),
-            ..Default::default()
-        };
-        Some(Syntax::Typescript(ts_syntax))
-    } else if path.ends_with(".js") || path.ends_with(".jsx") {
-        Some(Syntax::Es(Default::default()))
-    } else {
-        // Default to TypeScript with decorators for .ot files
-        let ts_syntax = TsSyntax {
-            decorators: true,
-            ..Default::default()
-        };
-        Some(Syntax::Typescript(ts_syntax))
+/// Load and run a pre-compiled bytecode file
+fn run_binary_file(vm: &mut VM, path: &str) -> Result<(), String> {
+    let bytes =
+        fs::read(path).map_err(|e| format!("Failed to read binary file {}: {}", path, e))?;
+
+    let program = BytecodeDecoder::new(&bytes)
+        .decode_all()
+        .map_err(|e| format!("Failed to decode {} {}", path, e))?;
+    let base = vm.append_program(program);
+    match BytecodeDecoder::debug_info(&bytes) {
+        Some(Ok(mut debug)) => {
+            debug.rebase(base);
+            vm.debug_info = Some(debug);
+        }
+        Some(Err(e)) => eprintln!("Warning: ignoring debug section of {}: {}", path, e),
+        None => {}
+    }
+    vm.run_event_loop();
+    Ok(())
+}
+
+/// `--embed-debug <file> [--source <file>] [--compress]`: attach function
+/// names and, with `--source`, the source text to a bytecode file, replacing
+/// any debug section it already has
+fn embed_debug_info(args: &[String]) {
+    let mut path = None;
+    let mut source_path = None;
+    let mut compress = false;
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--source" if i + 1 < args.len() => {
+                source_path = Some(args[i + 1].clone());
+                i += 1;
+            }
+            "--compress" => compress = true,
+            other if path.is_none() && !other.starts_with("--") => path = Some(other.to_string()),
+            other => {
+                eprintln!("Error: unexpected argument '{}'", other);
+                std::process::exit(1);
+            }
+        }
+        i += 1;
+    }
+    let Some(path) = path else {
+        eprintln!("Usage: oitec --embed-debug <file> [--source <file>] [--compress]");
+        std::process::exit(1);
+    };
+
+    let bytes = match fs::read(&path) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            eprintln!("Failed to read binary file {}: {}", path, e);
+            std::process::exit(1);
+        }
+    };
+    let program = match BytecodeDecoder::new(&bytes).decode_all() {
+        Ok(program) => program,
+        Err(e) => {
+            eprintln!("Failed to decode {} {}", path, e);
+            std::process::exit(1);
+        }
     };
+    let source = source_path.as_ref().map(|source_path| {
+        fs::read_to_string(source_path).unwrap_or_else(|e| {
+            eprintln!("Failed to read source file {}: {}", source_path, e);
+            std::process::exit(1);
+        })
+    });
 
-    let bytecode = compiler
-        .compile_with_syntax(&source, syntax)
-        .map_err(|e| format!("Failed to compile {}: {}", path, e))?;
-    let bytecode_len = bytecode.len();
+    let mut functions: Vec<(usize, String)> =
+        vm::profiler::function_names(&program).into_iter().collect();
+    functions.sort();
+    let debug = loader::DebugInfo {
+        file: source_path.unwrap_or_else(|| path.clone()),
+        functions,
+        lines: Vec::new(),
+        source,
+    };
 
-    if append {
-        let offset = vm.append_program(bytecode);
-        eprintln!("  {} ({} ops at offset {})", path, bytecode_len, offset);
+    // Version 2 files carry the section inside the container, so they are
+    // written afresh
+    let (out, program_len) = if BytecodeDecoder::is_container(&bytes) {
+        let mut file = loader::BytecodeFile {
+            functions: debug.functions.clone(),
+            program,
+            debug: None,
+        };
+        let encoded = file.encode(compress).and_then(|bare| {
+            file.debug = Some(debug.clone());
+            Ok((file.encode(compress)?, bare.len()))
+        });
+        match encoded {
+            Ok(encoded) => encoded,
+            Err(e) => {
+                eprintln!("Failed to encode {}: {}", path, e);
+                std::process::exit(1);
+            }
+        }
     } else {
-        let path_buf = PathBuf::from(path);
-        vm.load_program_with_path(bytecode, path_buf);
-        eprintln!("  {} ({} ops)", path, bytecode_len);
+        let program_len = BytecodeDecoder::program_bytes(&bytes).len();
+        let mut out = bytes[..program_len].to_vec();
+        out.extend(debug.encode(compress));
+        (out, program_len)
+    };
+    if let Err(e) = fs::write(&path, &out) {
+        eprintln!("Failed to write {}: {}", path, e);
+        std::process::exit(1);
     }
-
-    vm.run_until_halt();
-    Ok(())
+    println!(
+        "Embedded debug info in {} ({} functions{}, {} bytes)",
+        path,
+        debug.functions.len(),
+        if debug.source.is_some() {
+            ", source"
+        } else {
+            ""
+        },
+        out.len() - program_len
+    );
 }
 
-/// Load and run a pre-compiled bytecode file
-fn run_binary_file(vm: &mut VM, path: &str) -> Result<(), String> {
-    let bytes =
-        fs::read(path).map_err(|e| format!("Failed to read binary file {}: {}", path, e))?;
+/// `--emit-bc <file> [-o <output>] [--source] [--compress]`: compile a
+/// source file to a version 2 bytecode file with its function table and
+/// line table, and with `--source` its text
+fn emit_bytecode(args: &[String]) {
+    let mut path = None;
+    let mut output = None;
+    let mut include_source = false;
+    let mut compress = false;
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "-o" | "--output" if i + 1 < args.len() => {
+                output = Some(args[i + 1].clone());
+                i += 1;
+            }
+            "--source" => include_source = true,
+            "--compress" => compress = true,
+            other if path.is_none() && !other.starts_with('-') => path = Some(other.to_string()),
+            other => {
+                eprintln!("Error: unexpected argument '{}'", other);
+                std::process::exit(1);
+            }
+        }
+        i += 1;
+    }
+    let Some(path) = path else {
+        eprintln!("Usage: oitec --emit-bc <file> [-o <output>] [--source] [--compress]");
+        std::process::exit(1);
+    };
+    let output = output.unwrap_or_else(|| {
+        Path::new(&path)
+            .with_extension("bc")
+            .to_string_lossy()
+            .into_owned()
+    });
 
-    let mut decoder = BytecodeDecoder::new(&bytes);
+    let mut driver = CompilationDriver::new(&path);
+    let compiled = driver.compile_file(&path);
+    driver.print_warnings();
+    let (source, program) = match compiled {
+        Ok(compiled) => compiled,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    let mut functions: Vec<(usize, String)> =
+        vm::profiler::function_names(&program).into_iter().collect();
+    functions.sort();
+    let file = loader::BytecodeFile {
+        functions,
+        program,
+        debug: Some(loader::DebugInfo {
+            file: path.clone(),
+            functions: Vec::new(),
+            lines: driver.compiler.line_table.clone(),
+            source: include_source.then_some(source),
+        }),
+    };
+    let written = file.encode(compress).and_then(|bytes| {
+        fs::write(&output, &bytes)
+            .map_err(|e| e.to_string())
+            .map(|()| bytes)
+    });
+    match written {
+        Ok(bytes) => println!(
+            "Wrote {} ({} instructions, {} bytes)",
+            output,
+            file.program.len(),
+            bytes.len()
+        ),
+        Err(e) => {
+            eprintln!("Failed to write {}: {}", output, e);
+            std::process::exit(1);
+        }
+    }
+}
 
-    match decoder.decode_all() {
+/// Print a bytecode file's header and instructions without running it
+fn inspect_bytecode(path: &str) {
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            eprintln!("Failed to read binary file {}: {}", path, e);
+            std::process::exit(1);
+        }
+    };
+    let info = BytecodeDecoder::inspect(&bytes);
+
+    println!("File:     {} ({} bytes)", path, info.size);
+    match info.version {
+        Some(version) if version == loader::CONTAINER_VERSION => println!(
+            "Header:   TSCL version {} (flags {:02x}, checksummed)",
+            version, info.reserved[0]
+        ),
+        Some(version) => println!(
+            "Header:   TSCL version {} (reserved {:02x} {:02x} {:02x})",
+            version, info.reserved[0], info.reserved[1], info.reserved[2]
+        ),
+        None => println!("Header:   none (legacy headerless file)"),
+    }
+    match &info.debug {
+        Some(Ok(debug)) => println!(
+            "Debug:    {} ({} functions, {} lines{})",
+            debug.file,
+            debug.functions.len(),
+            debug.lines.len(),
+            match &debug.source {
+                Some(source) => format!(", {} bytes of source", source.len()),
+                None => String::new(),
+            }
+        ),
+        Some(Err(e)) => println!("Debug:    unreadable ({})", e),
+        None => println!("Debug:    none"),
+    }
+    match &info.program {
         Ok(program) => {
-            println!("Loaded {} instructions from binary file", program.len());
-            // Debug: print each instruction
+            let mut counts: std::collections::BTreeMap<String, usize> = Default::default();
+            for op in program {
+                let name = format!("{:?}", op);
+                let name = name.split(['(', ' ', '{']).next().unwrap_or_default();
+                *counts.entry(name.to_string()).or_insert(0) += 1;
+            }
+            println!("Program:  {} instructions", program.len());
+            let mut counts: Vec<_> = counts.into_iter().collect();
+            counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+            for (name, count) in &counts {
+                println!("  {:>6}  {}", count, name);
+            }
+            println!();
             for (i, op) in program.iter().enumerate() {
-                println!("  [{}] {:?}", i, op);
+                println!("  [{:>5}] {:?}", i, op);
             }
-            // Debug: check if console is in global frame
-            if let Some(frame) = vm.call_stack.first() {
-                println!("Global frame has {} locals", frame.locals.len());
-                if frame.locals.contains_key("console") {
-                    println!("  - console: found");
-                } else {
-                    println!("  - console: NOT FOUND!");
-                }
+        }
+        Err(e) => {
+            eprintln!("Decode error {}", e);
+            if let Some(instr) = e.instruction {
+                let end = (instr.offset + 16).min(bytes.len());
+                let dump: Vec<String> = bytes[instr.offset..end]
+                    .iter()
+                    .map(|b| format!("{:02x}", b))
+                    .collect();
+                eprintln!("  bytes at {:#x}: {}", instr.offset, dump.join(" "));
+            }
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Print a readable listing of a source or bytecode file's instructions
+fn disassemble_file(args: &[String]) {
+    let mut path = None;
+    let mut interleave = false;
+    for arg in args {
+        match arg.as_str() {
+            "--source" => interleave = true,
+            other if path.is_none() && !other.starts_with('-') => path = Some(other.to_string()),
+            other => {
+                eprintln!("Error: unexpected argument '{}'", other);
+                std::process::exit(1);
+            }
+        }
+    }
+    let Some(path) = path else {
+        eprintln!("Usage: oitec disasm [--source] <file.ot|file.bc>");
+        std::process::exit(1);
+    };
+
+    let bytes = match fs::read(&path) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            eprintln!("Failed to read {}: {}", path, e);
+            std::process::exit(1);
+        }
+    };
+    let is_bytecode =
+        bytes.starts_with(b"TSCL") || Path::new(&path).extension().is_some_and(|e| e == "bc");
+    let (program, functions, lines, source) = if is_bytecode {
+        let file = match BytecodeDecoder::decode_file(&bytes) {
+            Ok(file) => file,
+            Err(e) => {
+                eprintln!("Decode error {}", e);
+                std::process::exit(1);
+            }
+        };
+        let (lines, source) = match file.debug {
+            // Without embedded source, the file it was compiled from
+            Some(debug) => {
+                let source = debug.source.or_else(|| {
+                    interleave
+                        .then(|| fs::read_to_string(&debug.file).ok())
+                        .flatten()
+                });
+                (debug.lines, source)
+            }
+            None => (Vec::new(), None),
+        };
+        let functions = if file.functions.is_empty() {
+            vm::profiler::function_names(&file.program)
+        } else {
+            file.functions.into_iter().collect()
+        };
+        (file.program, functions, lines, source)
+    } else {
+        let mut driver = CompilationDriver::new(&path);
+        let compiled = driver.compile_file(&path);
+        driver.print_warnings();
+        match compiled {
+            Ok((source, program)) => {
+                let functions = vm::profiler::function_names(&program);
+                let lines = driver.compiler.line_table.clone();
+                (program, functions, lines, Some(source))
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
             }
-            let offset = vm.append_program(program);
-            vm.run_event_loop();
-            Ok(())
         }
-        Err(e) => Err(format!("Failed to decode bytecode: {}", e)),
+    };
+
+    if interleave && source.is_none() {
+        eprintln!(
+            "Warning: {} has no source to show alongside its instructions",
+            path
+        );
     }
+    let interleave = source
+        .as_deref()
+        .filter(|_| interleave)
+        .map(|source| (lines.as_slice(), source));
+    print!(
+        "{}",
+        vm::disasm::disassemble(&program, &functions, interleave)
+    );
 }
 
 fn main() {
     let args: Vec<String> = env::args().collect();
-    if args.len() < 2 {
+    // An executable written by `oitec bundle` runs its program and nothing else
+    if let Some(bundle) = vm::bundle::Bundle::embedded() {
+        run_bundle(bundle, &args[1..]);
+        return;
+    }
+    // A bare `oitec` in a project runs the entry named in its script.toml
+    let project_entry = args.len() < 2
+        && !Manifest::discover_or_default(Path::new("."))
+            .entries()
+            .is_empty();
+    if args.len() < 2 && !project_entry {
         eprintln!("Usage: {} <command> [args...]", args[0]);
         eprintln!("Commands:");
-        eprintln!("  check <filename>     Check a .ot file for errors (for LSP)");
+        eprintln!("  check [--no-typecheck] <filename>");
+        eprintln!("                       Check a .ot file for errors (for LSP)");
+        eprintln!("  lsp                  Start the language server on stdio");
         eprintln!("  ir <filename>        Dump SSA IR for a .ot file");
-        eprintln!("  jit <filename>       Run a .ot file with JIT compilation");
-        eprintln!("  bench <filename>     Benchmark VM vs JIT for a .ot file");
+        eprintln!("  jit [--time-limit <ms>] <filename>");
+        eprintln!("                       Run a .ot file with JIT compilation");
+        eprintln!("  bench [options] <filename>  Benchmark VM vs JIT for a .ot file");
+        eprintln!(
+            "  bootstrap-test [--strict] <filename>  Compare Rust and self-hosted compiler output"
+        );
         eprintln!("  build [options] <filename>  Build a .ot file to native binary");
-        eprintln!("  <filename>           Run a .ot file (VM interpreter)");
+        eprintln!("  doctor                      Check the toolchain native builds need");
+        eprintln!("  fmt [options] <files>       Format source files in place (--check for CI)");
+        eprintln!(
+            "  add <name>@<version> | add <name> --git <url> [--rev <rev>]  Add a dependency and install it"
+        );
+        eprintln!(
+            "  install [--locked]          Install script.toml's dependencies into script_modules/"
+        );
+        eprintln!(
+            "  profile [options] <filename>  Profile a .ot file in the VM (collapsed stacks or speedscope)"
+        );
+        eprintln!(
+            "  heap [--json] <filename>    Run a .ot file and report heap usage by type and root"
+        );
+        #[cfg(feature = "corpus-runner")]
+        eprintln!(
+            "  corpus [--max-steps <n>] [--keep <dir>] <path>...  Replay samples with invariant checks"
+        );
+        eprintln!("  dev <filename> [args...]    Run a .ot file and reload modules as they change");
+        eprintln!(
+            "  snapshot [-o <file>] [<filename>]  Save the VM after the prelude (and the compiler modules <filename> needs)"
+        );
+        eprintln!(
+            "  bundle [-o <file>] <filename>  Write an executable that runs <filename> without oitec"
+        );
+        eprintln!("  <filename>           Run a .ot file (VM interpreter); without one, the");
+        eprintln!("                       [build] entry of script.toml");
+        eprintln!(
+            "  --coverage [--coverage-format lcov|json] [--coverage-output <file>] <filename>"
+        );
+        eprintln!("                       Run a .ot file and write line coverage (lcov.info)");
+        eprintln!("  --trace <file> [--trace-function <name>]... <filename>");
+        eprintln!("                       Run a .ot file and log every executed opcode to <file>");
+        eprintln!("  --warn-slow-tasks <ms> <filename>");
+        eprintln!("                       Report event-loop tasks that run longer than <ms>");
+        eprintln!("  --borrow-check=off|warn|error <filename>");
+        eprintln!("                       Ownership errors: skip, report as warnings, or reject");
+        eprintln!("  --no-bytecode-cache <filename>");
+        eprintln!("                       Compile from source instead of using .cache/bytecode");
+        eprintln!("  --snapshot <file> <filename>");
+        eprintln!("                       Start from a snapshot instead of loading the prelude");
+        eprintln!("  --prefetch <filename>");
+        eprintln!("                       Compile statically imported modules in the background");
+        eprintln!("  --locale <tag> <filename>");
+        eprintln!("                       Write console numbers the way <tag> does (e.g. de-DE)");
+        eprintln!(
+            "  --sandbox [--allow-<read|write|net|env|run|all>[=<list>]]... [--prompt] <filename>"
+        );
+        eprintln!(
+            "                       Only grant the listed capabilities; --prompt asks for the rest"
+        );
         eprintln!("  --run-binary <file>  Run a bytecode file (.bc)");
+        eprintln!("  --inspect-bc <file>  Print a bytecode file's header and instructions");
+        eprintln!("  disasm [--source] <file>");
+        eprintln!(
+            "                       List a .ot or .bc file's instructions with labels and names"
+        );
+        eprintln!("  --emit-bc <file> [-o <output>] [--source] [--compress]");
+        eprintln!("                       Compile a .ot file to a bytecode file (.bc)");
+        eprintln!("  --embed-debug <file> [--source <file>] [--compress]");
+        eprintln!("                       Add function names (and source text) to a bytecode file");
         eprintln!();
         eprintln!("Build options:");
         eprintln!("  --backend <llvm|cranelift>  Choose code generator (default: llvm)");
@@ -184,6 +508,7 @@ fn main() {
         eprintln!("  --release                      Optimize with ThinLTO");
         eprintln!("  --dist                         Full LTO for maximum performance");
         eprintln!("  --debug                        No optimization, debug info");
+        eprintln!("  -O0 | -O1 | -O2 | -O3          Optimization level (-O2 inlines functions)");
         eprintln!("  --format <exe|lib|dylib|obj>   Output format");
         eprintln!("  --emit-ir                      Emit SSA IR to .ir file");
         eprintln!("  --emit-llvm                    Emit LLVM IR to .ll file");
@@ -192,16 +517,25 @@ fn main() {
         return;
     }
 
-    let command = &args[1];
+    let command = args.get(1).map(String::as_str).unwrap_or_default();
 
     // Handle "check" command for LSP diagnostics
     if command == "check" {
-        if args.len() < 3 {
-            eprintln!("Usage: {} check <filename>", args[0]);
+        let typecheck = !args[2..].iter().any(|a| a == "--no-typecheck");
+        let Some(filename) = args[2..].iter().find(|a| !a.starts_with("--")) else {
+            eprintln!("Usage: {} check [--no-typecheck] <filename>", args[0]);
+            std::process::exit(1);
+        };
+        check_file(filename, typecheck);
+        return;
+    }
+
+    // Handle "lsp" command to run the language server over stdio
+    if command == "lsp" {
+        if let Err(e) = lsp::run_server() {
+            eprintln!("Language server error: {}", e);
             std::process::exit(1);
         }
-        let filename = &args[2];
-        check_file(filename);
         return;
     }
 
@@ -218,23 +552,50 @@ fn main() {
 
     // Handle "jit" command for JIT compilation
     if command == "jit" {
-        if args.len() < 3 {
-            eprintln!("Usage: {} jit <filename>", args[0]);
-            std::process::exit(1);
+        let mut time_limit = None;
+        let mut filename = None;
+        let mut i = 2;
+        while i < args.len() {
+            match args[i].as_str() {
+                "--time-limit" => {
+                    i += 1;
+                    match args.get(i).and_then(|ms| ms.parse::<f64>().ok()) {
+                        Some(ms) if ms >= 0.0 => {
+                            time_limit = Some(std::time::Duration::from_secs_f64(ms / 1000.0))
+                        }
+                        _ => {
+                            eprintln!("Error: --time-limit requires a duration in milliseconds");
+                            std::process::exit(1);
+                        }
+                    }
+                }
+                other if filename.is_none() && !other.starts_with("--") => {
+                    filename = Some(other.to_string())
+                }
+                other => {
+                    eprintln!("Error: Unknown option: {}", other);
+                    std::process::exit(1);
+                }
+            }
+            i += 1;
         }
-        let filename = &args[2];
-        run_jit(filename);
+        let Some(filename) = filename else {
+            eprintln!("Usage: {} jit [--time-limit <ms>] <filename>", args[0]);
+            std::process::exit(1);
+        };
+        run_jit(&filename, time_limit);
         return;
     }
 
     // Handle "bench" command for benchmarking
     if command == "bench" {
-        if args.len() < 3 {
-            eprintln!("Usage: {} bench <filename>", args[0]);
-            std::process::exit(1);
-        }
-        let filename = &args[2];
-        run_benchmark(filename);
+        run_benchmark(&args[2..]);
+        return;
+    }
+
+    // Handle "bootstrap-test" command to compare the two compiler pipelines
+    if command == "bootstrap-test" {
+        bootstrap_test(&args[2..]);
         return;
     }
 
@@ -244,158 +605,674 @@ fn main() {
         return;
     }
 
-    let filename = command;
+    // Handle "doctor" command to diagnose the native build toolchain
+    if command == "doctor" {
+        let oitec = env::current_exe().unwrap_or_else(|_| PathBuf::from(&args[0]));
+        let checks = doctor::run_checks(&oitec);
+        if !doctor::print_report(&checks) {
+            std::process::exit(1);
+        }
+        return;
+    }
 
-    // Check if we should run in binary mode
-    let run_binary = args.iter().any(|a| a == "--run-binary")
-        || filename.ends_with(".bc")
-        || filename.ends_with(".otb");
+    // Handle "add" and "install" commands for package dependencies
+    if command == "add" {
+        add_package(&args[2..]);
+        return;
+    }
+    if command == "install" {
+        let locked = args[2..].iter().any(|a| a == "--locked");
+        install_packages(&Manifest::discover_or_default(Path::new(".")), locked);
+        return;
+    }
 
-    let mut vm = VM::new();
-    let mut compiler = Compiler::new();
+    // Handle "fmt" command to format source files
+    if command == "fmt" {
+        fmt_files(&args[2..]);
+        return;
+    }
 
-    // Setup standard library
-    vm.setup_stdlib();
+    // Handle "profile" command to sample where interpreted time goes
+    if command == "profile" {
+        profile_file(&args[2..]);
+        return;
+    }
 
-    // Binary mode: load and run pre-compiled bytecode directly
-    if run_binary {
-        // Running bytecode file
-        if let Err(e) = run_binary_file(&mut vm, filename) {
-            eprintln!("{}", e);
-        }
+    // Handle "heap" command to inspect memory left on the heap
+    if command == "heap" {
+        heap_report(&args[2..]);
         return;
     }
 
-    // 1. Load and run prelude first (if exists)
-    // This sets up global constants (OP, TOKEN, TYPE) and utility functions
-    if Path::new(PRELUDE_PATH).exists() {
-        // Loading prelude
-        if let Err(e) = load_and_run_script(&mut vm, &mut compiler, PRELUDE_PATH, false) {
-            eprintln!("{}", e);
-            return;
-        }
+    // Handle "dev" command to keep a script running while it is edited
+    if command == "dev" {
+        dev_server(&args[2..]);
+        return;
     }
 
-    // 2. Check if this is a bootstrap file that needs the compiler modules
-    let is_bootstrap = filename.contains("bootstrap/") || filename.contains("tests/");
-    let is_modular_compiler = filename.contains("compiler/") && !filename.contains("bootstrap/");
+    // Handle "snapshot" command to save the VM for faster startup
+    if command == "snapshot" {
+        make_snapshot(&args[2..]);
+        return;
+    }
 
-    if is_bootstrap {
-        // Loading bootstrap compiler modules
-        for bootstrap_file in BOOTSTRAP_FILES {
-            if Path::new(bootstrap_file).exists() {
-                if let Err(e) = load_and_run_script(&mut vm, &mut compiler, bootstrap_file, true) {
-                    eprintln!("{}", e);
-                    return;
-                }
-            } else {
-                eprintln!("Warning: Bootstrap file not found: {}", bootstrap_file);
-            }
-        }
+    // Handle "bundle" command to ship a script as one executable
+    if command == "bundle" {
+        make_bundle(&args[2..]);
+        return;
     }
 
-    if is_modular_compiler {
-        // Loading modular compiler modules
-        for modular_file in MODULAR_COMPILER_FILES {
-            // Skip the main file being run if it's in the list
-            if modular_file == filename {
-                continue;
-            }
-            if Path::new(modular_file).exists() {
-                if let Err(e) = load_and_run_script(&mut vm, &mut compiler, modular_file, true) {
-                    eprintln!("{}", e);
-                    return;
-                }
-            } else {
-                eprintln!("Warning: Modular compiler file not found: {}", modular_file);
-            }
-        }
+    // Handle "corpus" command to shake out interpreter invariant violations
+    #[cfg(feature = "corpus-runner")]
+    if command == "corpus" {
+        run_corpus(&args[2..]);
+        return;
     }
 
-    // 3. Load and run the main script
-    let main_source = match fs::read_to_string(filename) {
-        Ok(s) => s,
-        Err(e) => {
-            eprintln!("Failed to read {}: {}", filename, e);
-            return;
+    // Handle "--inspect-bc" to look inside a bytecode file without running it
+    if command == "--inspect-bc" {
+        if args.len() < 3 {
+            eprintln!("Usage: {} --inspect-bc <file>", args[0]);
+            std::process::exit(1);
         }
-    };
-
-    // Determine syntax based on file extension
-    let syntax = if filename.ends_with(".ts") || filename.ends_with(".tsx") {
-        let ts_syntax = TsSyntax {
-            decorators: true,
-            tsx: filename.ends_with(".tsx"),
-            ..Default::default()
-        };
-        Some(Syntax::Typescript(ts_syntax))
-    } else if filename.ends_with(".js") || filename.ends_with(".jsx") {
-        Some(Syntax::Es(Default::default()))
-    } else {
-        // Default to TypeScript with decorators for .ot files
-        let ts_syntax = TsSyntax {
-            decorators: true,
-            ..Default::default()
-        };
-        Some(Syntax::Typescript(ts_syntax))
-    };
+        inspect_bytecode(&args[2]);
+        return;
+    }
 
-    match compiler.compile_with_syntax(&main_source, syntax) {
-        Ok(main_bytecode) => {
-            let offset = vm.append_program(main_bytecode);
-            // Update the current module path to the main script for relative imports
-            vm.set_current_module_path(PathBuf::from(filename));
+    // Handle "disasm" to read the instructions a file compiles to
+    if command == "disasm" {
+        disassemble_file(&args[2..]);
+        return;
+    }
 
-            // Set script arguments (__args__) for the script
-            // Arguments after the filename are passed to the script
-            let script_args: Vec<String> = args[2..].to_vec();
-            vm.set_script_args(script_args);
+    // Handle "--emit-bc" to compile a file to a bytecode file
+    if command == "--emit-bc" {
+        emit_bytecode(&args[2..]);
+        return;
+    }
 
-            vm.run_event_loop();
-        }
-        Err(e) => {
-            eprintln!("Compilation failed: {}", e);
-        }
+    // Handle "--embed-debug" to keep errors from deployed bytecode readable
+    if command == "--embed-debug" {
+        embed_debug_info(&args[2..]);
+        return;
     }
-}
 
-/// Dump SSA IR for a file
-fn dump_ir(filename: &str) {
-    let source = match fs::read_to_string(filename) {
+    // Leading --coverage/--trace options apply to the run; everything after
+    // the filename is passed to the script
+    let mut coverage = None;
+    let mut trace_output = None;
+    let mut trace_functions = Vec::new();
+    let mut warn_slow_tasks = None;
+    let mut borrow_check = None;
+    let mut prefetch = false;
+    let mut bytecode_cache = true;
+    let mut snapshot = None;
+    let mut operator_overloading = false;
+    let mut console_locale = None;
+    let mut permissions = Permissions::unrestricted();
+    let mut first = 1;
+    while args.get(first).is_some_and(|a| {
+        a.starts_with("--coverage")
+            || a.starts_with("--trace")
+            || a.starts_with("--borrow-check=")
+            || a.starts_with("--allow-")
+            || a == "--run-binary"
+            || a == "--warn-slow-tasks"
+            || a == "--prefetch"
+            || a == "--no-bytecode-cache"
+            || a == "--snapshot"
+            || a == "--operator-overloading"
+            || a == "--locale"
+            || a == "--sandbox"
+            || a == "--prompt"
+    }) {
+        match args[first].as_str() {
+            // Checked again below, together with the file extension
+            "--run-binary" => {}
+            "--prefetch" => prefetch = true,
+            "--no-bytecode-cache" => bytecode_cache = false,
+            "--snapshot" => {
+                first += 1;
+                match args.get(first) {
+                    Some(path) => snapshot = Some(path.clone()),
+                    None => {
+                        eprintln!("Error: --snapshot requires a snapshot file");
+                        std::process::exit(1);
+                    }
+                }
+            }
+            "--operator-overloading" => operator_overloading = true,
+            // Grants made before --sandbox are kept
+            "--sandbox" => {
+                if !permissions.is_sandboxed() {
+                    permissions = Permissions::sandboxed();
+                }
+            }
+            "--prompt" => {
+                if !permissions.is_sandboxed() {
+                    permissions = Permissions::sandboxed();
+                }
+                permissions.set_prompter(permissions::terminal_prompt);
+            }
+            "--coverage" => {
+                coverage.get_or_insert_with(CoverageOptions::default);
+            }
+            "--coverage-format" => {
+                first += 1;
+                let options = coverage.get_or_insert_with(CoverageOptions::default);
+                options.json = match args.get(first).map(String::as_str) {
+                    Some("lcov") => false,
+                    Some("json") => true,
+                    _ => {
+                        eprintln!("Error: --coverage-format must be 'lcov' or 'json'");
+                        std::process::exit(1);
+                    }
+                };
+            }
+            "--coverage-output" => {
+                first += 1;
+                let options = coverage.get_or_insert_with(CoverageOptions::default);
+                match args.get(first) {
+                    Some(path) => options.output = Some(path.clone()),
+                    None => {
+                        eprintln!("Error: --coverage-output requires a value");
+                        std::process::exit(1);
+                    }
+                }
+            }
+            "--trace" => {
+                first += 1;
+                match args.get(first) {
+                    Some(path) => trace_output = Some(path.clone()),
+                    None => {
+                        eprintln!("Error: --trace requires an output file");
+                        std::process::exit(1);
+                    }
+                }
+            }
+            "--trace-function" => {
+                first += 1;
+                match args.get(first) {
+                    Some(name) => trace_functions.push(name.clone()),
+                    None => {
+                        eprintln!("Error: --trace-function requires a function name");
+                        std::process::exit(1);
+                    }
+                }
+            }
+            "--warn-slow-tasks" => {
+                first += 1;
+                match args.get(first).and_then(|ms| ms.parse::<f64>().ok()) {
+                    Some(ms) if ms >= 0.0 => {
+                        warn_slow_tasks = Some(std::time::Duration::from_secs_f64(ms / 1000.0))
+                    }
+                    _ => {
+                        eprintln!("Error: --warn-slow-tasks requires a duration in milliseconds");
+                        std::process::exit(1);
+                    }
+                }
+            }
+            "--locale" => {
+                first += 1;
+                match args.get(first).map(|tag| ConsoleLocale::parse(tag)) {
+                    Some(Ok(locale)) => console_locale = Some(locale),
+                    Some(Err(e)) => {
+                        eprintln!("Error: {}", e);
+                        std::process::exit(1);
+                    }
+                    None => {
+                        eprintln!("Error: --locale requires a language tag");
+                        std::process::exit(1);
+                    }
+                }
+            }
+            other if other.starts_with("--allow-") => {
+                // --allow-<name>[=<target>,...] grants inside the sandbox
+                let (name, targets) = match other["--allow-".len()..].split_once('=') {
+                    Some((name, list)) => (
+                        name,
+                        Some(list.split(',').map(str::to_string).collect::<Vec<_>>()),
+                    ),
+                    None => (&other["--allow-".len()..], None),
+                };
+                let caps = if name == "all" {
+                    Ok(permissions::Capability::ALL.to_vec())
+                } else {
+                    permissions::Capability::parse(name).map(|cap| vec![cap])
+                };
+                match caps {
+                    Ok(caps) => {
+                        if !permissions.is_sandboxed() {
+                            permissions = Permissions::sandboxed();
+                        }
+                        for cap in caps {
+                            permissions.allow(cap, targets.clone());
+                        }
+                    }
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            other if other.starts_with("--borrow-check=") => {
+                match BorrowCheckLevel::parse(&other["--borrow-check=".len()..]) {
+                    Ok(level) => borrow_check = Some(level),
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            other => {
+                eprintln!("Error: Unknown option: {}", other);
+                std::process::exit(1);
+            }
+        }
+        first += 1;
+    }
+    // Without a file, run the project's entry; script.toml's [stdlib]
+    // settings apply where no flag is given
+    let entry = args.get(first).cloned().or_else(|| {
+        let entries = Manifest::discover_or_default(Path::new(".")).entries();
+        Some(entries.first()?.to_string_lossy().into_owned())
+    });
+    let Some(filename) = entry.as_ref() else {
+        eprintln!(
+            "Usage: {} [--coverage [--coverage-format lcov|json] [--coverage-output <file>]] [--trace <file> [--trace-function <name>]...] [--warn-slow-tasks <ms>] [--borrow-check=off|warn|error] [--prefetch] [--no-bytecode-cache] [--snapshot <file>] [--operator-overloading] [--locale <tag>] [--sandbox] [--allow-<read|write|net|env|run|all>[=<list>]] [--prompt] <filename> [args...]",
+            args[0]
+        );
+        std::process::exit(1);
+    };
+    if !trace_functions.is_empty() && trace_output.is_none() {
+        eprintln!("Error: --trace-function requires --trace <file>");
+        std::process::exit(1);
+    }
+
+    // Check if we should run in binary mode
+    let stdlib = Manifest::discover_or_default(Path::new(filename)).stdlib;
+    let operator_overloading = operator_overloading || stdlib.operator_overloading == Some(true);
+    let console_locale = console_locale.or(stdlib.locale);
+    let run_binary = args.iter().any(|a| a == "--run-binary")
+        || filename.ends_with(".bc")
+        || filename.ends_with(".otb");
+
+    let mut vm = VM::new();
+    // The command line wins over script.toml; the prelude and compiler
+    // modules are always checked strictly
+    let mut driver = CompilationDriver::new(filename)
+        .with_borrow_check(borrow_check)
+        .with_bytecode_cache(bytecode_cache);
+    driver.configure_vm(&mut vm);
+
+    // Setup standard library; runtime.features reports the permissions and
+    // whether operators dispatch to methods
+    vm.permissions = permissions;
+    vm.operator_overloading = operator_overloading;
+    vm.setup_stdlib();
+    vm.loop_stats.warn_slow = warn_slow_tasks;
+    vm.console_locale = console_locale;
+
+    // Binary mode: load and run pre-compiled bytecode directly
+    if run_binary {
+        // Running bytecode file
+        if let Err(e) = run_binary_file(&mut vm, filename) {
+            eprintln!("{}", e);
+        }
+        return;
+    }
+
+    // 1. Load and run the prelude, then the compiler modules bootstrap and
+    // compiler sources expect, or restore a snapshot made after them
+    let loaded = match &snapshot {
+        Some(path) => fs::read(path)
+            .map_err(|e| format!("Failed to read {}: {}", path, e))
+            .and_then(|bytes| {
+                vm::startup_snapshot::restore(&mut vm, &bytes)
+                    .map_err(|e| format!("Error: {}: {}", path, e))
+            }),
+        None => driver
+            .load_prelude(&mut vm)
+            .and_then(|()| driver.load_compiler_modules(&mut vm, filename)),
+    };
+    if let Err(e) = loaded {
+        eprintln!("{}", e);
+        return;
+    }
+
+    // 2. Load and run the main script
+    let main_source = match read_source(filename) {
         Ok(s) => s,
         Err(e) => {
-            eprintln!("Failed to read {}: {}", filename, e);
+            eprintln!("{}", e);
+            return;
+        }
+    };
+
+    // Imports compile on other threads while the entry module compiles and runs
+    if prefetch {
+        vm.prefetch_imports(Path::new(filename), &main_source);
+    }
+    let compiled = driver.compile(filename, &main_source);
+    driver.print_warnings();
+    match compiled {
+        Ok(main_bytecode) => {
+            let offset = vm.append_program(main_bytecode);
+            // Only the main script and the modules it imports are measured
+            if coverage.is_some() {
+                let mut counters = Coverage::new();
+                counters.add_chunk(Path::new(filename), offset, &driver.compiler.line_table);
+                vm.coverage = Some(counters);
+            }
+            // Likewise the trace starts at the main script, past the prelude
+            if let Some(path) = &trace_output {
+                match fs::File::create(path) {
+                    Ok(file) => {
+                        let out = Box::new(std::io::BufWriter::new(file));
+                        vm.tracer = Some(Tracer::new(out, trace_functions.clone()));
+                    }
+                    Err(e) => {
+                        eprintln!("Failed to create {}: {}", path, e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            // Update the current module path to the main script for relative imports
+            vm.set_current_module_path(PathBuf::from(filename));
+
+            // Set script arguments (__args__) for the script
+            // Arguments after the filename are passed to the script
+            let script_args: Vec<String> = args.get(first + 1..).unwrap_or_default().to_vec();
+            vm.set_script_args(script_args);
+
+            vm.run_event_loop();
+            vm.cancel_prefetch();
+
+            if let (Some(options), Some(counters)) = (coverage, vm.coverage.take()) {
+                write_coverage(&options, &counters);
+            }
+            if let (Some(path), Some(tracer)) = (trace_output, vm.tracer.take()) {
+                match tracer.finish() {
+                    Ok(count) => eprintln!("Traced {} instructions to {}", count, path),
+                    Err(e) => {
+                        eprintln!("Failed to write {}: {}", path, e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            if let Some(code) = stdlib::process::exit_code(&vm) {
+                std::process::exit(code);
+            }
+        }
+        Err(e) => {
+            vm.cancel_prefetch();
+            eprintln!("Compilation failed: {}", e);
+        }
+    }
+}
+
+/// `oitec snapshot [-o <file>] [<filename>]`: load the prelude, and the
+/// compiler modules a run of `<filename>` would load, then save the VM for
+/// `--snapshot` (see vm/startup_snapshot.rs)
+fn make_snapshot(args: &[String]) {
+    let mut output = format!("prelude.{}", vm::startup_snapshot::EXTENSION);
+    let mut entry = None;
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--output" | "-o" => {
+                i += 1;
+                match args.get(i) {
+                    Some(path) => output = path.clone(),
+                    None => {
+                        eprintln!("Error: --output requires a file");
+                        std::process::exit(1);
+                    }
+                }
+            }
+            other if entry.is_none() && !other.starts_with('-') => entry = Some(other.to_string()),
+            other => {
+                eprintln!("Error: Unknown option: {}", other);
+                std::process::exit(1);
+            }
+        }
+        i += 1;
+    }
+
+    let mut vm = VM::new();
+    let mut driver = CompilationDriver::new(entry.as_deref().unwrap_or("."));
+    driver.configure_vm(&mut vm);
+    let written = driver
+        .load_prelude(&mut vm)
+        .and_then(|()| match &entry {
+            Some(entry) => driver.load_compiler_modules(&mut vm, entry),
+            None => Ok(()),
+        })
+        .and_then(|()| vm::startup_snapshot::save(&vm))
+        .and_then(|bytes| {
+            fs::write(&output, &bytes)
+                .map(|()| bytes.len())
+                .map_err(|e| format!("Failed to write {}: {}", output, e))
+        });
+    match written {
+        Ok(size) => eprintln!("Wrote {} ({} bytes)", output, size),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// `oitec bundle [-o <file>] <filename>`: compile a script and the modules
+/// it imports, and append them with the prelude's snapshot to a copy of
+/// this executable (see vm/bundle.rs)
+/// Values `import.meta.env`/`process.env` are fixed to at build time: the
+/// manifest's `[env]`, then each `--define NAME=VALUE`
+fn resolve_build_env(
+    mode: &str,
+    manifest: &Manifest,
+    defines: &[String],
+) -> compiler::build_env::BuildEnv {
+    let mut build_env = compiler::build_env::BuildEnv::new(mode);
+    build_env.vars = manifest.env.clone();
+    for define in defines {
+        if let Err(e) = build_env.define(define) {
+            eprintln!("Error: {}", e);
             std::process::exit(1);
         }
+    }
+    build_env
+}
+
+fn make_bundle(args: &[String]) {
+    let mut output = None;
+    let mut entry = None;
+    let mut mode = None;
+    let mut defines = Vec::new();
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--output" | "-o" => {
+                i += 1;
+                match args.get(i) {
+                    Some(path) => output = Some(PathBuf::from(path)),
+                    None => {
+                        eprintln!("Error: --output requires a file");
+                        std::process::exit(1);
+                    }
+                }
+            }
+            "--mode" => {
+                i += 1;
+                match args.get(i) {
+                    Some(value) => mode = Some(value.clone()),
+                    None => {
+                        eprintln!("Error: --mode requires a value");
+                        std::process::exit(1);
+                    }
+                }
+            }
+            "--define" | "-D" => {
+                i += 1;
+                match args.get(i) {
+                    Some(define) => defines.push(define.clone()),
+                    None => {
+                        eprintln!("Error: --define requires NAME=VALUE");
+                        std::process::exit(1);
+                    }
+                }
+            }
+            other if entry.is_none() && !other.starts_with('-') => entry = Some(other.to_string()),
+            other => {
+                eprintln!("Error: Unknown option: {}", other);
+                std::process::exit(1);
+            }
+        }
+        i += 1;
+    }
+    let Some(entry) = entry else {
+        eprintln!(
+            "Usage: oitec bundle [-o <file>] [--mode <mode>] [--define NAME=VALUE]... <filename>"
+        );
+        eprintln!("A bundle is built in production mode unless --mode says otherwise.");
+        std::process::exit(1);
     };
+    let output = output.unwrap_or_else(|| {
+        Path::new(&entry)
+            .with_extension(env::consts::EXE_EXTENSION)
+            .file_name()
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from("app"))
+    });
 
-    // Determine syntax based on file extension
-    let syntax = if filename.ends_with(".ts") || filename.ends_with(".tsx") {
-        let ts_syntax = TsSyntax {
-            decorators: true,
-            tsx: filename.ends_with(".tsx"),
-            ..Default::default()
-        };
-        Some(Syntax::Typescript(ts_syntax))
-    } else if filename.ends_with(".js") || filename.ends_with(".jsx") {
-        Some(Syntax::Es(Default::default()))
+    let mut driver = CompilationDriver::new(&entry);
+    // A bundle is what gets deployed, so it defaults to production
+    let mode = mode.unwrap_or_else(|| "production".to_string());
+    driver.compiler.build_env = Some(resolve_build_env(
+        &mode,
+        &driver.settings.manifest,
+        &defines,
+    ));
+    let built = vm::bundle::Bundle::build(&mut driver, &entry);
+    driver.print_warnings();
+    let written = built.and_then(|bundle| {
+        let runtime = env::current_exe()
+            .map_err(|e| format!("Failed to locate the oitec executable: {}", e))?;
+        bundle.write_executable(&runtime, &output)
+    });
+    match written {
+        Ok(size) => eprintln!("Wrote {} ({} bytes)", output.display(), size),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Run the bundle appended to this executable, passing it every argument
+fn run_bundle(bundle: Result<vm::bundle::Bundle, String>, args: &[String]) {
+    let mut vm = VM::new();
+    if let Err(e) = bundle.and_then(|bundle| bundle.install(&mut vm)) {
+        eprintln!("Error: bundled program: {}", e);
+        std::process::exit(1);
+    }
+    vm.set_script_args(args.to_vec());
+    vm.run_event_loop();
+    if let Some(code) = stdlib::process::exit_code(&vm) {
+        std::process::exit(code);
+    }
+}
+
+/// `oitec dev <filename> [args...]`: run a script, then keep it running and
+/// apply edits to it and its imports as they are saved (see vm/hot.rs). A
+/// change nothing accepts restarts it.
+fn dev_server(args: &[String]) {
+    let Some(filename) = args.first() else {
+        eprintln!("Usage: oitec dev <filename> [args...]");
+        std::process::exit(1);
+    };
+    let entry = PathBuf::from(filename);
+    loop {
+        let mut vm = VM::new();
+        let mut driver = CompilationDriver::new(filename);
+        driver.configure_vm(&mut vm);
+        if let Err(e) = driver.load_prelude(&mut vm) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+
+        let compiled = read_source(filename).and_then(|source| {
+            driver
+                .compile(filename, &source)
+                .map_err(|e| format!("Compilation failed: {}", e))
+        });
+        driver.print_warnings();
+        match compiled {
+            Ok(bytecode) => {
+                vm.append_program(bytecode);
+                vm.set_current_module_path(entry.clone());
+                vm.set_script_args(args[1..].to_vec());
+                vm::hot::HotReload::start(&mut vm, &entry);
+                vm.run_event_loop();
+                if !vm
+                    .hot
+                    .as_ref()
+                    .is_some_and(vm::hot::HotReload::reload_requested)
+                {
+                    return;
+                }
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                eprintln!("[dev] waiting for {} to change", filename);
+                vm::hot::wait_for_change(std::slice::from_ref(&entry));
+            }
+        }
+        eprintln!("[dev] restarting {}", filename);
+    }
+}
+
+/// Report settings for `oitec --coverage`
+#[derive(Default)]
+struct CoverageOptions {
+    json: bool,
+    output: Option<String>,
+}
+
+fn write_coverage(options: &CoverageOptions, coverage: &Coverage) {
+    let (contents, default_output) = if options.json {
+        (format!("{:#}\n", coverage.to_json()), "coverage.json")
     } else {
-        // Default to TypeScript with decorators for .ot files
-        let ts_syntax = TsSyntax {
-            decorators: true,
-            ..Default::default()
-        };
-        Some(Syntax::Typescript(ts_syntax))
+        (coverage.to_lcov(), "lcov.info")
     };
+    let output = options
+        .output
+        .clone()
+        .unwrap_or_else(|| default_output.to_string());
+    if let Err(e) = fs::write(&output, contents) {
+        eprintln!("Failed to write {}: {}", output, e);
+        std::process::exit(1);
+    }
+
+    eprintln!();
+    eprintln!("=== Coverage ===");
+    for line in coverage.summary() {
+        eprintln!("  {}", line);
+    }
+    eprintln!("Wrote {}", output);
+}
 
-    let mut compiler = Compiler::new();
-    let bytecode = match compiler.compile_with_syntax(&source, syntax) {
-        Ok(bc) => bc,
+/// Dump SSA IR for a file
+fn dump_ir(filename: &str) {
+    let mut driver = CompilationDriver::new(filename);
+    let bytecode = match driver.compile_file(filename) {
+        Ok((_, bytecode)) => bytecode,
         Err(e) => {
-            eprintln!("Compilation failed: {}", e);
+            eprintln!("{}", e);
             std::process::exit(1);
         }
     };
+    driver.print_warnings();
 
     println!("=== Bytecode ({} instructions) ===", bytecode.len());
     for (i, op) in bytecode.iter().enumerate() {
@@ -426,40 +1303,48 @@ fn dump_ir(filename: &str) {
     }
 }
 
+/// Print annotation errors as `file:line:col: message`. Returns the number
+/// of errors.
+fn print_type_errors(filename: &str, errors: &[types::error::TypeError]) -> usize {
+    for e in errors {
+        let span = e.span();
+        eprintln!("{}:{}:{}: {:#}", filename, span.line + 1, span.col + 1, e);
+    }
+    errors.len()
+}
+
 /// Check a file for errors without running it
-fn check_file(filename: &str) {
-    let source = match fs::read_to_string(filename) {
+fn check_file(filename: &str, typecheck: bool) {
+    let source = match read_source(filename) {
         Ok(s) => s,
         Err(e) => {
-            eprintln!("{}:1:1: Failed to read file: {}", filename, e);
+            eprintln!("{}:1:1: {}", filename, e);
             std::process::exit(1);
         }
     };
+    let syntax = syntax_for_path(filename);
 
-    // Determine syntax based on file extension
-    let syntax = if filename.ends_with(".ts") || filename.ends_with(".tsx") {
-        let ts_syntax = TsSyntax {
-            decorators: true,
-            tsx: filename.ends_with(".tsx"),
-            ..Default::default()
-        };
-        Some(Syntax::Typescript(ts_syntax))
-    } else if filename.ends_with(".js") || filename.ends_with(".jsx") {
-        Some(Syntax::Es(Default::default()))
+    let mut driver = CompilationDriver::new(filename);
+    let type_errors = if typecheck && driver.typecheck(filename) {
+        let errors = types::annotations::check_source(&source, syntax);
+        print_type_errors(filename, &errors)
     } else {
-        // Default to TypeScript with decorators for .ot files
-        let ts_syntax = TsSyntax {
-            decorators: true,
-            ..Default::default()
-        };
-        Some(Syntax::Typescript(ts_syntax))
+        0
     };
 
-    let mut compiler = Compiler::new();
-    match compiler.compile_with_syntax(&source, syntax) {
+    let compiled = driver.compile(filename, &source);
+    for warning in &driver.compiler.warnings {
+        eprintln!("{}:1:1: warning: {}", filename, warning);
+    }
+    match compiled {
         Ok(_) => {
-            // Success - no errors
-            std::process::exit(0);
+            // Imports must resolve with the project's extension priority
+            let unresolved = module::unresolved_imports(Path::new(filename), &source);
+            for (line, e) in &unresolved {
+                eprintln!("{}:{}:1: {}", filename, line, e);
+            }
+            let failed = type_errors > 0 || !unresolved.is_empty();
+            std::process::exit(if failed { 1 } else { 0 });
         }
         Err(e) => {
             // Parse error message to extract line/column if possible
@@ -492,45 +1377,20 @@ fn check_file(filename: &str) {
 }
 
 /// Run a file using JIT compilation
-fn run_jit(filename: &str) {
+fn run_jit(filename: &str, time_limit: Option<std::time::Duration>) {
     use crate::backend::{BackendConfig, jit::JitRuntime};
-
-    let source = match fs::read_to_string(filename) {
-        Ok(s) => s,
-        Err(e) => {
-            eprintln!("Failed to read {}: {}", filename, e);
-            std::process::exit(1);
-        }
-    };
-
-    // Determine syntax based on file extension
-    let syntax = if filename.ends_with(".ts") || filename.ends_with(".tsx") {
-        let ts_syntax = TsSyntax {
-            decorators: true,
-            tsx: filename.ends_with(".tsx"),
-            ..Default::default()
-        };
-        Some(Syntax::Typescript(ts_syntax))
-    } else if filename.ends_with(".js") || filename.ends_with(".jsx") {
-        Some(Syntax::Es(Default::default()))
-    } else {
-        // Default to TypeScript with decorators for .ot files
-        let ts_syntax = TsSyntax {
-            decorators: true,
-            ..Default::default()
-        };
-        Some(Syntax::Typescript(ts_syntax))
-    };
+    use crate::runtime::safepoint;
 
     // Compile to bytecode
-    let mut compiler = Compiler::new();
-    let bytecode = match compiler.compile_with_syntax(&source, syntax) {
-        Ok(bc) => bc,
+    let mut driver = CompilationDriver::new(filename);
+    let bytecode = match driver.compile_file(filename) {
+        Ok((_, bytecode)) => bytecode,
         Err(e) => {
-            eprintln!("Compilation failed: {}", e);
+            eprintln!("{}", e);
             std::process::exit(1);
         }
     };
+    driver.print_warnings();
 
     println!("=== Bytecode ({} instructions) ===", bytecode.len());
     for (i, op) in bytecode.iter().enumerate() {
@@ -581,8 +1441,13 @@ fn run_jit(filename: &str) {
                         println!("  - {}", name);
                     }
 
-                    // Try to call main
+                    // Try to call main; Ctrl-C and the time limit stop
+                    // compiled loops at their next safepoint
                     println!("\n=== Execution ===");
+                    safepoint::install_sigint_handler();
+                    if let Some(limit) = time_limit {
+                        safepoint::set_time_limit(limit);
+                    }
                     match runtime.call_main() {
                         Ok(result) => {
                             println!("Result: {:?}", result);
@@ -606,161 +1471,288 @@ fn run_jit(filename: &str) {
 }
 
 /// Run a benchmark comparing VM vs JIT performance
-fn run_benchmark(filename: &str) {
+fn run_benchmark(args: &[String]) {
     use crate::backend::{BackendConfig, jit::JitRuntime};
-    use std::time::Instant;
+    use crate::bench::{MeasureOptions, compare, format_ns, measure, print_measurement};
+    use serde_json::{Value, json};
+    use std::time::{Duration, Instant};
 
-    let source = match fs::read_to_string(filename) {
-        Ok(s) => s,
-        Err(e) => {
-            eprintln!("Failed to read {}: {}", filename, e);
-            std::process::exit(1);
-        }
-    };
+    let mut filename = None;
+    let mut options = MeasureOptions::default();
+    let mut json_output = false;
+    let mut baseline_path = None;
+    let mut save_baseline = None;
+    let mut threshold = 5.0;
 
-    // Determine syntax based on file extension
-    let syntax = if filename.ends_with(".ts") || filename.ends_with(".tsx") {
-        let ts_syntax = TsSyntax {
-            decorators: true,
-            tsx: filename.ends_with(".tsx"),
-            ..Default::default()
+    // Parse arguments
+    let mut i = 0;
+    while i < args.len() {
+        let flag = args[i].as_str();
+        let value = |i: usize| -> String {
+            match args.get(i) {
+                Some(v) => v.clone(),
+                None => {
+                    eprintln!("Error: {} requires a value", flag);
+                    std::process::exit(1);
+                }
+            }
         };
-        Some(Syntax::Typescript(ts_syntax))
-    } else if filename.ends_with(".js") || filename.ends_with(".jsx") {
-        Some(Syntax::Es(Default::default()))
-    } else {
-        // Default to TypeScript with decorators for .ot files
-        let ts_syntax = TsSyntax {
-            decorators: true,
-            ..Default::default()
+        let number = |i: usize| -> f64 {
+            match value(i).parse::<f64>() {
+                Ok(n) if n >= 0.0 => n,
+                _ => {
+                    eprintln!("Error: {} requires a non-negative number", flag);
+                    std::process::exit(1);
+                }
+            }
         };
-        Some(Syntax::Typescript(ts_syntax))
-    };
-
-    // Compile to bytecode
-    let mut compiler = Compiler::new();
-    let bytecode = match compiler.compile_with_syntax(&source, syntax) {
-        Ok(bc) => bc,
-        Err(e) => {
-            eprintln!("Compilation failed: {}", e);
-            std::process::exit(1);
+        match flag {
+            "--iterations" | "-n" => {
+                i += 1;
+                options.iterations = Some((number(i) as u32).max(1));
+            }
+            "--duration" => {
+                i += 1;
+                options.duration = Some(Duration::from_secs_f64(number(i)));
+            }
+            "--warmup" => {
+                i += 1;
+                options.max_warmup = number(i) as u32;
+            }
+            "--threshold" => {
+                i += 1;
+                threshold = number(i);
+            }
+            "--baseline" => {
+                i += 1;
+                baseline_path = Some(value(i));
+            }
+            "--save-baseline" => {
+                i += 1;
+                save_baseline = Some(value(i));
+            }
+            "--json" => json_output = true,
+            _ if !flag.starts_with('-') && filename.is_none() => {
+                filename = Some(flag.to_string());
+            }
+            _ => {
+                eprintln!("Error: Unknown option: {}", flag);
+                std::process::exit(1);
+            }
         }
+        i += 1;
+    }
+    let Some(filename) = filename else {
+        eprintln!(
+            "Usage: oitec bench [--iterations <n>] [--duration <secs>] [--warmup <n>] [--json] [--baseline <file>] [--save-baseline <file>] [--threshold <pct>] <filename>"
+        );
+        std::process::exit(1);
     };
+    let filename = filename.as_str();
 
-    println!("=== Benchmark: {} ===\n", filename);
-
-    const ITERATIONS: u32 = 100;
-
-    // Benchmark VM (without prelude for fair comparison)
-    // Note: For VM, we replace top-level Return with Halt to keep the frame intact
-    println!("VM Interpreter ({} iterations):", ITERATIONS);
-    let mut vm_bytecode = bytecode.clone();
-    // Replace the last Return before Halt with just letting execution continue
-    for i in 0..vm_bytecode.len() {
-        if matches!(vm_bytecode[i], crate::vm::opcodes::OpCode::Return)
-            && i + 1 < vm_bytecode.len()
-            && matches!(vm_bytecode[i + 1], crate::vm::opcodes::OpCode::Halt)
-        {
-            // The return value is already on the stack - just skip to Halt
-            vm_bytecode[i] = crate::vm::opcodes::OpCode::Halt;
-            break;
+    // Compile to bytecode
+    let mut driver = CompilationDriver::new(filename);
+    let bytecode = match driver.compile_file(filename) {
+        Ok((_, bytecode)) => bytecode,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
         }
+    };
+    driver.print_warnings();
+
+    if !json_output {
+        println!("=== Benchmark: {} ===\n", filename);
     }
 
-    let mut vm_results = Vec::new();
-    let vm_start = Instant::now();
-    for _ in 0..ITERATIONS {
+    // Benchmark VM (without prelude for fair comparison), keeping the
+    // script's frame so its return value can be read back
+    let vm_bytecode = keep_top_level_result(bytecode.clone());
+
+    let mut vm_result = crate::vm::value::JsValue::Undefined;
+    let vm_measurement = measure(&options, || {
         let mut vm = VM::new_bare(); // Use bare VM without stdlib for benchmark
         vm.load_program(vm_bytecode.clone());
         vm.run_until_halt();
         // Get the result (top of stack or undefined)
-        let result = vm
+        vm_result = vm
             .stack
             .pop()
             .unwrap_or(crate::vm::value::JsValue::Undefined);
-        vm_results.push(result);
-    }
-    let vm_duration = vm_start.elapsed();
-    println!("  Total time: {:?}", vm_duration);
-    println!("  Per iteration: {:?}", vm_duration / ITERATIONS);
-    if let Some(result) = vm_results.first() {
-        println!("  Result: {:?}", result);
+        Ok::<(), String>(())
+    })
+    .unwrap_or_else(|e: String| {
+        eprintln!("VM benchmark failed: {}", e);
+        std::process::exit(1);
+    });
+    if !json_output {
+        println!("VM Interpreter:");
+        print_measurement(&vm_measurement);
+        println!("  Result: {:?}", vm_result);
     }
 
-    // Benchmark JIT
-    println!("\nJIT Compilation:");
+    // Benchmark JIT; failures are reported and leave the JIT section empty
+    let jit = (|| -> Result<_, String> {
+        let mut module =
+            ir::lower::lower_module(&bytecode).map_err(|e| format!("IR lowering failed: {}", e))?;
+        ir::typecheck::typecheck_module(&mut module);
+        ir::opt::optimize_module(&mut module);
 
-    // Lower to IR
-    let module = match ir::lower::lower_module(&bytecode) {
-        Ok(mut m) => {
-            ir::typecheck::typecheck_module(&mut m);
-            ir::opt::optimize_module(&mut m);
-            m
-        }
+        let config = BackendConfig::default();
+        let mut runtime =
+            JitRuntime::new(&config).map_err(|e| format!("Failed to create JIT runtime: {}", e))?;
+
+        let compile_start = Instant::now();
+        runtime
+            .compile(&module)
+            .map_err(|e| format!("JIT compilation failed: {}", e))?;
+        let compile_duration = compile_start.elapsed();
+
+        let mut result = None;
+        let measurement = measure(&options, || {
+            result = Some(
+                runtime
+                    .call_main()
+                    .map_err(|e| format!("Execution error: {}", e))?,
+            );
+            Ok::<(), String>(())
+        })?;
+        Ok((measurement, compile_duration, result))
+    })();
+    let jit = match jit {
+        Ok(jit) => Some(jit),
         Err(e) => {
-            eprintln!("  IR lowering failed: {}", e);
-            return;
+            eprintln!("JIT: {}", e);
+            None
         }
     };
+    if !json_output && let Some((measurement, compile_duration, result)) = &jit {
+        println!("\nJIT:");
+        println!("  Compilation time: {:?}", compile_duration);
+        print_measurement(measurement);
+        if let Some(result) = result {
+            println!("  Result: {:?}", result);
+        }
+    }
 
-    // JIT compile
-    let config = BackendConfig::default();
-    let mut runtime = match JitRuntime::new(&config) {
-        Ok(rt) => rt,
-        Err(e) => {
-            eprintln!("  Failed to create JIT runtime: {}", e);
-            return;
+    let mut report = json!({
+        "file": filename,
+        "vm": {
+            "warmup_iterations": vm_measurement.warmup_iterations,
+            "stats": vm_measurement.stats.to_json(),
+        },
+        "jit": match &jit {
+            Some((measurement, compile_duration, _)) => json!({
+                "warmup_iterations": measurement.warmup_iterations,
+                "compile_ns": compile_duration.as_nanos() as f64,
+                "stats": measurement.stats.to_json(),
+            }),
+            None => Value::Null,
+        },
+    });
+
+    // Summary
+    if !json_output && let Some((measurement, compile_duration, _)) = &jit {
+        println!("\n=== Summary ===");
+        let vm_median = vm_measurement.stats.median;
+        let jit_median = measurement.stats.median;
+        let speedup = vm_median / jit_median.max(1.0);
+
+        println!("VM:  {:>12} /iter (median)", format_ns(vm_median));
+        println!("JIT: {:>12} /iter (median)", format_ns(jit_median));
+        println!("JIT compilation: {:>10.2} µs", compile_duration.as_micros());
+
+        if speedup > 1.0 {
+            println!("\nJIT is {:.2}x faster than VM", speedup);
+        } else {
+            println!("\nVM is {:.2}x faster than JIT", 1.0 / speedup);
         }
-    };
 
-    let compile_start = Instant::now();
-    if let Err(e) = runtime.compile(&module) {
-        eprintln!("  JIT compilation failed: {}", e);
-        return;
+        // Break-even analysis
+        let break_even = compile_duration.as_nanos() as f64 / (vm_median - jit_median).max(1.0);
+        if speedup > 1.0 {
+            println!("Break-even point: {:.0} iterations", break_even);
+        }
     }
-    let compile_duration = compile_start.elapsed();
-    println!("  Compilation time: {:?}", compile_duration);
 
-    // Run JIT
-    println!("\nJIT Execution ({} iterations):", ITERATIONS);
-    let mut jit_results = Vec::new();
-    let jit_start = Instant::now();
-    for _ in 0..ITERATIONS {
-        match runtime.call_main() {
-            Ok(result) => jit_results.push(result),
+    // Compare medians against a saved baseline
+    let mut regressed = false;
+    if let Some(path) = &baseline_path {
+        let baseline: Value = match fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|s| serde_json::from_str(&s).map_err(|e| e.to_string()))
+        {
+            Ok(baseline) => baseline,
             Err(e) => {
-                eprintln!("  Execution error: {}", e);
-                break;
+                eprintln!("Error: Could not load baseline {}: {}", path, e);
+                std::process::exit(1);
+            }
+        };
+        let comparisons = compare(&report, &baseline, threshold / 100.0);
+        regressed = comparisons.iter().any(|c| c.regressed);
+        if !json_output {
+            println!("\n=== Baseline ({}) ===", path);
+            for c in &comparisons {
+                println!(
+                    "{:<4} {:>12} -> {:>12}  {:+.1}%{}",
+                    c.engine.to_uppercase(),
+                    format_ns(c.baseline_median),
+                    format_ns(c.current_median),
+                    c.change * 100.0,
+                    if c.regressed { "  REGRESSION" } else { "" }
+                );
             }
         }
-    }
-    let jit_duration = jit_start.elapsed();
-    println!("  Total time: {:?}", jit_duration);
-    println!("  Per iteration: {:?}", jit_duration / ITERATIONS);
-    if let Some(result) = jit_results.first() {
-        println!("  Result: {:?}", result);
+        report["baseline"] = json!({
+            "path": path,
+            "threshold_pct": threshold,
+            "comparisons": comparisons
+                .iter()
+                .map(|c| json!({
+                    "engine": c.engine,
+                    "baseline_median_ns": c.baseline_median,
+                    "current_median_ns": c.current_median,
+                    "change_pct": c.change * 100.0,
+                    "regressed": c.regressed,
+                }))
+                .collect::<Vec<_>>(),
+        });
     }
 
-    // Summary
-    println!("\n=== Summary ===");
-    let vm_per_iter = vm_duration.as_nanos() as f64 / ITERATIONS as f64;
-    let jit_per_iter = jit_duration.as_nanos() as f64 / ITERATIONS as f64;
-    let speedup = vm_per_iter / jit_per_iter;
+    if let Some(path) = &save_baseline {
+        let mut saved = report.clone();
+        if let Some(obj) = saved.as_object_mut() {
+            obj.remove("baseline");
+        }
+        let text = serde_json::to_string_pretty(&saved).unwrap_or_default();
+        if let Err(e) = fs::write(path, text + "\n") {
+            eprintln!("Error: Could not write baseline {}: {}", path, e);
+            std::process::exit(1);
+        }
+        if !json_output {
+            println!("\nBaseline saved to {}", path);
+        }
+    }
 
-    println!("VM:  {:>10.2} µs/iter", vm_per_iter / 1000.0);
-    println!("JIT: {:>10.2} µs/iter", jit_per_iter / 1000.0);
-    println!("JIT compilation: {:>10.2} µs", compile_duration.as_micros());
+    if json_output {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&report).unwrap_or_default()
+        );
+    }
 
-    if speedup > 1.0 {
-        println!("\nJIT is {:.2}x faster than VM", speedup);
-    } else {
-        println!("\nVM is {:.2}x faster than JIT", 1.0 / speedup);
+    if regressed {
+        eprintln!("Performance regression beyond {}% threshold", threshold);
+        std::process::exit(1);
     }
+}
 
-    // Break-even analysis
-    let break_even = compile_duration.as_nanos() as f64 / (vm_per_iter - jit_per_iter).max(1.0);
-    if speedup > 1.0 {
-        println!("Break-even point: {:.0} iterations", break_even);
+/// Native code generation level matching an IR optimization level
+fn native_opt_level(level: ir::opt::OptLevel) -> backend::OptLevel {
+    match level {
+        ir::opt::OptLevel::O0 => backend::OptLevel::None,
+        ir::opt::OptLevel::O1 => backend::OptLevel::Speed,
+        ir::opt::OptLevel::O2 | ir::opt::OptLevel::O3 => backend::OptLevel::SpeedAndSize,
     }
 }
 
@@ -768,19 +1760,30 @@ fn run_benchmark(filename: &str) {
 fn build_file(args: &[String]) {
     use crate::backend::{
         BackendConfig, BackendKind, LtoMode, OptLevel,
-        aot::{AotCompiler, AotOptions, OutputFormat},
+        aot::{self, AotCompiler, AotOptions, OutputFormat},
+        exports,
+        llvm::linker::{LinkOptions, LinkerChoice},
     };
 
     let mut filenames = Vec::new();
     let mut output = None;
-    let mut backend = BackendKind::LlvmAot;
+    let mut backend = None;
     let mut opt_level = OptLevel::None; // Default to dev mode
+    let mut ir_opt_level = ir::opt::OptLevel::O1;
+    let mut opt_flag = false;
+    let mut target = None;
     let mut format = OutputFormat::Executable;
     let mut lto_mode = LtoMode::None;
     let mut emit_ir = false;
     let mut emit_llvm = false;
     let mut emit_obj = false;
+    let mut emit_runtime = false;
+    let mut runtime = None;
+    let mut link = LinkOptions::default();
     let mut verify_ir = false;
+    let mut typecheck = true;
+    let mut mode = None;
+    let mut defines = Vec::new();
 
     // Parse arguments
     let mut i = 0;
@@ -793,14 +1796,22 @@ fn build_file(args: &[String]) {
                     std::process::exit(1);
                 }
                 backend = match args[i].as_str() {
-                    "llvm" => BackendKind::LlvmAot,
-                    "cranelift" => BackendKind::CraneliftAot,
+                    "llvm" => Some(BackendKind::LlvmAot),
+                    "cranelift" => Some(BackendKind::CraneliftAot),
                     _ => {
                         eprintln!("Error: Unknown backend: {}", args[i]);
                         std::process::exit(1);
                     }
                 };
             }
+            "--target" => {
+                i += 1;
+                if i >= args.len() {
+                    eprintln!("Error: --target requires a target triple");
+                    std::process::exit(1);
+                }
+                target = Some(args[i].clone());
+            }
             "--output" | "-o" => {
                 i += 1;
                 if i >= args.len() {
@@ -811,15 +1822,26 @@ fn build_file(args: &[String]) {
             }
             "--release" => {
                 opt_level = OptLevel::SpeedAndSize;
+                ir_opt_level = ir::opt::OptLevel::O2;
                 lto_mode = LtoMode::Thin; // Release uses ThinLTO
+                opt_flag = true;
             }
             "--dist" => {
                 opt_level = OptLevel::SpeedAndSize;
+                ir_opt_level = ir::opt::OptLevel::O2;
                 lto_mode = LtoMode::Full; // Dist uses Full LTO
+                opt_flag = true;
             }
             "--debug" => {
                 opt_level = OptLevel::None;
+                ir_opt_level = ir::opt::OptLevel::O0;
                 lto_mode = LtoMode::None;
+                opt_flag = true;
+            }
+            flag @ ("-O0" | "-O1" | "-O2" | "-O3") => {
+                ir_opt_level = ir::opt::OptLevel::from_flag(flag).unwrap_or_default();
+                opt_level = native_opt_level(ir_opt_level);
+                opt_flag = true;
             }
             "--format" => {
                 i += 1;
@@ -847,9 +1869,66 @@ fn build_file(args: &[String]) {
             "--emit-obj" => {
                 emit_obj = true;
             }
+            "--emit-runtime" => {
+                emit_runtime = true;
+            }
+            "--runtime" => {
+                i += 1;
+                if i >= args.len() {
+                    eprintln!("Error: --runtime requires a library path");
+                    std::process::exit(1);
+                }
+                runtime = Some(PathBuf::from(&args[i]));
+            }
+            "--linker" => {
+                i += 1;
+                if i >= args.len() {
+                    eprintln!("Error: --linker requires lld, mold or a command");
+                    std::process::exit(1);
+                }
+                link.linker = LinkerChoice::parse(&args[i]);
+            }
+            "-L" | "-l" | "--link-arg" => {
+                let flag = args[i].clone();
+                i += 1;
+                if i >= args.len() {
+                    eprintln!("Error: {} requires a value", flag);
+                    std::process::exit(1);
+                }
+                match flag.as_str() {
+                    "-L" => link.search_paths.push(PathBuf::from(&args[i])),
+                    "-l" => link.libraries.push(args[i].clone()),
+                    _ => link.args.push(args[i].clone()),
+                }
+            }
+            arg if arg.len() > 2 && arg.starts_with("-L") => {
+                link.search_paths.push(PathBuf::from(&arg[2..]));
+            }
+            arg if arg.len() > 2 && arg.starts_with("-l") => {
+                link.libraries.push(arg[2..].to_string());
+            }
             "--verify-ir" => {
                 verify_ir = true;
             }
+            "--no-typecheck" => {
+                typecheck = false;
+            }
+            "--mode" => {
+                i += 1;
+                if i >= args.len() {
+                    eprintln!("Error: --mode requires a value");
+                    std::process::exit(1);
+                }
+                mode = Some(args[i].clone());
+            }
+            "--define" | "-D" => {
+                i += 1;
+                if i >= args.len() {
+                    eprintln!("Error: --define requires NAME=VALUE");
+                    std::process::exit(1);
+                }
+                defines.push(args[i].clone());
+            }
             _ => {
                 if !args[i].starts_with('-') {
                     filenames.push(args[i].clone());
@@ -862,74 +1941,161 @@ fn build_file(args: &[String]) {
         i += 1;
     }
 
+    // Projects that link many objects build one runtime library to share
+    if emit_runtime {
+        let output =
+            output.unwrap_or_else(|| format!("libscript_rt.{}", platform::STATIC_LIB_EXTENSION));
+        println!("Building runtime library...");
+        match aot::build_runtime_library(Path::new(&output), opt_level != OptLevel::None) {
+            Ok(version) => println!("Runtime library written to: {} (ABI {})", output, version),
+            Err(e) => {
+                eprintln!("Runtime library build failed: {}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    // script.toml fills in what the command line leaves out
+    let manifest =
+        Manifest::discover_or_default(filenames.first().map_or(Path::new("."), Path::new));
+    if filenames.is_empty() {
+        filenames = manifest
+            .entries()
+            .iter()
+            .map(|entry| entry.to_string_lossy().into_owned())
+            .collect();
+    }
+    let backend = backend.unwrap_or(match manifest.build.backend.as_deref() {
+        Some("cranelift") => BackendKind::CraneliftAot,
+        _ => BackendKind::LlvmAot,
+    });
+    if !opt_flag && let Some(level) = manifest.build.opt_level {
+        ir_opt_level = ir::opt::OptLevel::from_flag(&format!("-O{}", level)).unwrap_or_default();
+        opt_level = native_opt_level(ir_opt_level);
+    }
+    let target = target.or_else(|| manifest.build.target.clone());
+
     if filenames.is_empty() {
         eprintln!("Error: No input file specified");
         eprintln!(
-            "Usage: {} build [--backend llvm] [--output <file>] [--release|--dist] [--emit-ir|--emit-llvm|--emit-obj] [--verify-ir] <filename>...",
+            "Usage: {} build [--backend llvm] [--output <file>] [--release|--dist] [-O0..-O3] [--target <triple>] [--mode <mode>] [--define NAME=VALUE]... [--emit-ir|--emit-llvm|--emit-obj] [--runtime <lib>] [--linker <lld|mold|cc>] [-L <dir>] [-l <lib>] [--link-arg <arg>]... [--verify-ir] [--no-typecheck] <filename>...",
             env::args().next().unwrap()
         );
         eprintln!("Emission flags:");
-        eprintln!("  --emit-ir       Output SSA IR to file.ir");
+        eprintln!("  --emit-ir       Output SSA IR to file.ir (builds like a source file)");
         eprintln!("  --emit-llvm     Output LLVM IR to file.ll");
-        eprintln!("  --emit-obj      Output object file to file.o");
+        eprintln!("  --emit-obj      Output object file to file.o (file.obj on Windows)");
+        eprintln!("  --emit-runtime  Build the runtime library alone (-o, default libscript_rt.a)");
         eprintln!("  --verify-ir     Validate SSA IR and exit");
+        eprintln!("  --no-typecheck  Skip checking values against their type annotations");
+        eprintln!("  --runtime <lib> Link against a prebuilt runtime library, checking its ABI");
+        eprintln!("  --target <t>    Target triple for native code (default: the host)");
+        eprintln!("Linking:");
+        eprintln!("  --linker <name> lld, mold, or a linker command such as cc (default: first");
+        eprintln!("                  of clang, gcc, cc)");
+        eprintln!("  -L <dir>        Add a library search directory");
+        eprintln!("  -l <lib>        Link a system library, e.g. -l m");
+        eprintln!("  --link-arg <a>  Pass an argument to the linker unchanged");
+        eprintln!("Optimization:");
+        eprintln!("  -O0, -O1        Function-local IR passes (default; -O0 for --debug)");
+        eprintln!("  -O2             Also inline small functions, propagate constants across");
+        eprintln!("                  calls, drop unused functions and hoist loop-invariant");
+        eprintln!("                  code out of loops (--release, --dist)");
+        eprintln!("  -O3             As -O2, inlining larger functions");
+        eprintln!("Build variables (import.meta.env.X, process.env.X):");
+        eprintln!("  --mode <mode>   MODE and NODE_ENV (default: production for --release/--dist)");
+        eprintln!("  --define N=V    Substitute V for N, over the manifest's [env]");
+        eprintln!("Without files, builds the [build] entry of script.toml, whose backend,");
+        eprintln!("opt-level and target settings apply where no flag is given.");
         std::process::exit(1);
     }
 
+    // import.meta.env/process.env are fixed at build time
+    let mode = mode.unwrap_or_else(|| {
+        let release = opt_level != OptLevel::None;
+        (if release { "production" } else { "development" }).to_string()
+    });
+    let build_env = resolve_build_env(&mode, &manifest, &defines);
+
     // Compile all source files to IR modules
     let mut modules = Vec::new();
-    let mut compiler = Compiler::new();
+    // Declared signatures of each file's functions, for a library's exports
+    let mut signatures = Vec::new();
+    let mut driver = CompilationDriver::new(&filenames[0]);
+    driver.compiler.build_env = Some(build_env);
 
     for filename in &filenames {
-        // Read source file
-        let source = match fs::read_to_string(filename) {
+        let source = match read_source(filename) {
             Ok(s) => s,
             Err(e) => {
-                eprintln!("Failed to read {}: {}", filename, e);
+                eprintln!("{}", e);
                 std::process::exit(1);
             }
         };
+        // A `.ir` file (`--emit-ir` output) is already lowered and typed
+        let is_ir = Path::new(filename)
+            .extension()
+            .is_some_and(|ext| ext == "ir");
+        let mut module = if is_ir {
+            signatures.push(Default::default());
+            match ir::parse::parse_module(&source) {
+                Ok(m) => m,
+                Err(e) => {
+                    eprintln!("IR parsing failed for {}: {}", filename, e);
+                    std::process::exit(1);
+                }
+            }
+        } else {
+            let syntax = syntax_for_path(filename);
 
-        // Determine syntax
-        let syntax = if filename.ends_with(".ts") || filename.ends_with(".tsx") {
-            let ts_syntax = TsSyntax {
-                decorators: true,
-                tsx: filename.ends_with(".tsx"),
-                ..Default::default()
+            // Annotation errors stop the build; call-site types feed specialization
+            let checked = typecheck && driver.typecheck(filename);
+            let analysis = if checked || format.is_library() {
+                types::annotations::analyze_source(&source, syntax)
+            } else {
+                Default::default()
             };
-            Some(Syntax::Typescript(ts_syntax))
-        } else if filename.ends_with(".js") || filename.ends_with(".jsx") {
-            Some(Syntax::Es(Default::default()))
-        } else {
-            // Default to TypeScript with decorators for .ot files
-            let ts_syntax = TsSyntax {
-                decorators: true,
-                ..Default::default()
+            let hints = if checked {
+                if print_type_errors(filename, &analysis.errors) > 0 {
+                    std::process::exit(1);
+                }
+                analysis.hints
+            } else {
+                Vec::new()
             };
-            Some(Syntax::Typescript(ts_syntax))
-        };
+            signatures.push(analysis.signatures);
 
-        // Compile to bytecode
-        let bytecode = match compiler.compile_with_syntax(&source, syntax) {
-            Ok(bc) => bc,
-            Err(e) => {
-                eprintln!("Compilation failed for {}: {}", filename, e);
-                std::process::exit(1);
-            }
-        };
+            // Compile to bytecode
+            let bytecode = match driver.compile(filename, &source) {
+                Ok(bc) => bc,
+                Err(e) => {
+                    eprintln!("Compilation failed for {}: {}", filename, e);
+                    std::process::exit(1);
+                }
+            };
+            driver.print_warnings();
 
-        // Lower to SSA IR
-        let mut module = match ir::lower::lower_module(&bytecode) {
-            Ok(m) => m,
-            Err(e) => {
-                eprintln!("IR lowering failed for {}: {}", filename, e);
-                std::process::exit(1);
-            }
+            // Lower to SSA IR, keeping source lines for debug info
+            let lines = &driver.compiler.line_table;
+            let mut module = match ir::lower::lower_module_with_lines(&bytecode, lines) {
+                Ok(m) => m,
+                Err(e) => {
+                    eprintln!("IR lowering failed for {}: {}", filename, e);
+                    std::process::exit(1);
+                }
+            };
+
+            module.source_file = Some(filename.clone());
+
+            // Run type inference
+            ir::typecheck::apply_mono_hints(&mut module, &hints);
+            ir::typecheck::typecheck_module(&mut module);
+            module
         };
 
-        // Run type inference and optimizations
-        ir::typecheck::typecheck_module(&mut module);
-        ir::opt::optimize_module(&mut module);
+        // Run optimizations
+        ir::opt::optimize_module_at(&mut module, ir_opt_level, format.is_library());
 
         // Verify IR if requested
         if verify_ir {
@@ -950,9 +2116,11 @@ fn build_file(args: &[String]) {
 
         // Emit IR if requested
         if emit_ir {
+            // Don't overwrite the `.ir` file being built
+            let extension = if is_ir { "opt.ir" } else { "ir" };
             let ir_output = Path::new(filename)
                 .file_stem()
-                .map(|s| Path::new(filename).with_extension("ir").to_path_buf())
+                .map(|s| Path::new(filename).with_extension(extension).to_path_buf())
                 .unwrap_or_else(|| PathBuf::from("output.ir"));
 
             match ir::format::write_ir_to_file(&module, &ir_output) {
@@ -993,12 +2161,16 @@ fn build_file(args: &[String]) {
         debug_info: opt_level == OptLevel::None,
         bounds_check: true,
         lto_mode,
+        export_functions: format.is_library(),
+        target,
     };
 
     let mut aot = AotCompiler::new(&config);
     let mut options = AotOptions::default();
     options.format = format;
     options.lto_mode = lto_mode;
+    options.runtime = runtime;
+    options.link = link;
     aot = aot.with_options(options);
 
     // Compile all modules (with LTO support if enabled)
@@ -1007,16 +2179,17 @@ fn build_file(args: &[String]) {
     // Determine output path
     let output_path = output.unwrap_or_else(|| {
         // Use first filename as default output name
-        Path::new(&filenames[0])
+        let stem = Path::new(&filenames[0])
             .file_stem()
             .unwrap()
             .to_string_lossy()
-            .to_string()
+            .to_string();
+        format.default_output(&stem).to_string_lossy().to_string()
     });
 
     // Emit object file if requested
     if emit_obj {
-        let obj_output = Path::new(&output_path).with_extension("o");
+        let obj_output = Path::new(&output_path).with_extension(platform::OBJECT_EXTENSION);
         match aot.compile_modules_to_object(&module_refs, &obj_output) {
             Ok(()) => {
                 println!("Object file written to: {}", obj_output.display());
@@ -1052,4 +2225,716 @@ fn build_file(args: &[String]) {
             std::process::exit(1);
         }
     }
+
+    // Host programs bind to a library through its export manifest
+    if format.is_library() {
+        let exported: Vec<_> = filenames
+            .iter()
+            .zip(&modules)
+            .zip(&signatures)
+            .map(|((filename, module), signatures)| (filename.as_str(), module, signatures))
+            .collect();
+        let manifest_path = exports::manifest_path(Path::new(&output_path));
+        let manifest =
+            serde_json::to_string_pretty(&exports::manifest(&exported)).unwrap_or_default();
+        match fs::write(&manifest_path, manifest + "\n") {
+            Ok(()) => println!("Exports written to: {}", manifest_path.display()),
+            Err(e) => {
+                eprintln!("Failed to write {}: {}", manifest_path.display(), e);
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+/// `oitec add`: record a dependency in script.toml, then install
+fn add_package(args: &[String]) {
+    let mut spec = None;
+    let mut git = None;
+    let mut rev = None;
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            flag @ ("--git" | "--rev") => {
+                i += 1;
+                let Some(value) = args.get(i) else {
+                    eprintln!("Error: {} requires a value", flag);
+                    std::process::exit(1);
+                };
+                if flag == "--git" {
+                    git = Some(value.as_str());
+                } else {
+                    rev = Some(value.as_str());
+                }
+            }
+            arg if spec.is_none() && !arg.starts_with('-') => spec = Some(arg),
+            arg => {
+                eprintln!("Error: Unexpected argument: {}", arg);
+                std::process::exit(1);
+            }
+        }
+        i += 1;
+    }
+    let Some(spec) = spec else {
+        eprintln!(
+            "Usage: {} add <name>@<version> | add <name> --git <url> [--rev <rev>]",
+            env::args().next().unwrap()
+        );
+        std::process::exit(1);
+    };
+
+    let (name, source) = match package::parse_package_spec(spec, git, rev) {
+        Ok(dependency) => dependency,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+    // Without a project, start one in the working directory
+    let manifest_path =
+        Manifest::find(Path::new(".")).unwrap_or_else(|| PathBuf::from(manifest::MANIFEST_FILE));
+    if let Err(e) = package::add_dependency(&manifest_path, &name, &source) {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+    println!("Added {} = {}", name, source.to_toml());
+    match Manifest::load(&manifest_path) {
+        Ok(manifest) => install_packages(&manifest, false),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// `oitec install`: fetch the project's dependencies into script_modules
+fn install_packages(manifest: &Manifest, locked: bool) {
+    match package::install(manifest, locked) {
+        Ok(report) => {
+            for name in &report.fetched {
+                println!("Installed {}", name);
+            }
+            for name in &report.removed {
+                println!("Removed {}", name);
+            }
+            println!(
+                "{} package(s) installed, {} up to date",
+                report.fetched.len(),
+                report.unchanged.len()
+            );
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn fmt_files(args: &[String]) {
+    use crate::formatter::{FormatOptions, QuoteStyle, TrailingComma, format_source};
+
+    let mut paths = Vec::new();
+    let mut options = FormatOptions::default();
+    let mut check = false;
+
+    // Parse arguments
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--check" => {
+                check = true;
+            }
+            "--indent" | "--line-width" => {
+                let flag = args[i].clone();
+                i += 1;
+                let value = args.get(i).and_then(|v| v.parse::<usize>().ok());
+                match value {
+                    Some(n) if n > 0 => {
+                        if flag == "--indent" {
+                            options.indent_width = n;
+                        } else {
+                            options.line_width = n;
+                        }
+                    }
+                    _ => {
+                        eprintln!("Error: {} requires a positive number", flag);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            "--quotes" => {
+                i += 1;
+                options.quote_style = match args.get(i).map(String::as_str) {
+                    Some("double") => QuoteStyle::Double,
+                    Some("single") => QuoteStyle::Single,
+                    _ => {
+                        eprintln!("Error: --quotes must be 'single' or 'double'");
+                        std::process::exit(1);
+                    }
+                };
+            }
+            "--trailing-commas" => {
+                i += 1;
+                options.trailing_comma = match args.get(i).map(String::as_str) {
+                    Some("none") => TrailingComma::None,
+                    Some("all") => TrailingComma::All,
+                    _ => {
+                        eprintln!("Error: --trailing-commas must be 'none' or 'all'");
+                        std::process::exit(1);
+                    }
+                };
+            }
+            _ => {
+                if !args[i].starts_with('-') {
+                    paths.push(PathBuf::from(&args[i]));
+                } else {
+                    eprintln!("Error: Unknown option: {}", args[i]);
+                    std::process::exit(1);
+                }
+            }
+        }
+        i += 1;
+    }
+
+    if paths.is_empty() {
+        eprintln!(
+            "Usage: {} fmt [--check] [--indent <n>] [--quotes single|double] [--trailing-commas none|all] [--line-width <n>] <files|dirs>...",
+            env::args().next().unwrap_or_else(|| "oitec".to_string())
+        );
+        std::process::exit(1);
+    }
+
+    let mut files = Vec::new();
+    for path in &paths {
+        if path.is_dir() {
+            collect_source_files(path, &Manifest::discover_or_default(path), &mut files);
+        } else {
+            files.push(path.clone());
+        }
+    }
+
+    let mut unformatted = 0;
+    let mut failed = false;
+    for file in &files {
+        let display = file.display();
+        let source = match fs::read_to_string(file) {
+            Ok(source) => source,
+            Err(e) => {
+                eprintln!("Error: Could not read {}: {}", display, e);
+                failed = true;
+                continue;
+            }
+        };
+        let formatted = match format_source(&source, &file.to_string_lossy(), &options) {
+            Ok(formatted) => formatted,
+            Err(e) => {
+                eprintln!("{}: {}", display, e);
+                failed = true;
+                continue;
+            }
+        };
+        if formatted == source {
+            continue;
+        }
+        if check {
+            println!("Would reformat: {}", display);
+            unformatted += 1;
+        } else if let Err(e) = fs::write(file, formatted) {
+            eprintln!("Error: Could not write {}: {}", display, e);
+            failed = true;
+        } else {
+            println!("Formatted: {}", display);
+        }
+    }
+
+    if check && unformatted > 0 {
+        eprintln!("{} of {} file(s) need formatting", unformatted, files.len());
+        std::process::exit(1);
+    }
+    if failed {
+        std::process::exit(1);
+    }
+}
+
+/// Recursively collect formattable source files under `dir`, sorted by path.
+fn collect_source_files(dir: &Path, manifest: &Manifest, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let mut entries: Vec<PathBuf> = entries.filter_map(|e| e.ok().map(|e| e.path())).collect();
+    entries.sort();
+    for path in entries {
+        let hidden = path
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+        if hidden || path.ends_with("target") || path.ends_with("node_modules") {
+            continue;
+        }
+        if path.is_dir() {
+            collect_source_files(&path, manifest, files);
+        } else if manifest.is_module_path(&path) {
+            files.push(path);
+        }
+    }
+}
+
+/// Compile a file with both the Rust compiler and the self-hosted bootstrap
+/// compiler, then compare the generated bytecode and the program output.
+///
+/// Output differences always fail. Bytecode differences are reported and only
+/// fail with `--strict`, since the two code generators are not expected to
+/// agree instruction for instruction yet.
+fn bootstrap_test(args: &[String]) {
+    let mut filename = None;
+    let mut strict = false;
+    for arg in args {
+        match arg.as_str() {
+            "--strict" => strict = true,
+            _ if !arg.starts_with('-') && filename.is_none() => filename = Some(arg.clone()),
+            _ => {
+                eprintln!("Error: Unknown option: {}", arg);
+                std::process::exit(1);
+            }
+        }
+    }
+    let Some(filename) = filename else {
+        eprintln!("Usage: oitec bootstrap-test [--strict] <filename>");
+        std::process::exit(1);
+    };
+
+    let source = match read_source(&filename) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    println!("=== Bootstrap parity: {} ===", filename);
+
+    let rust_bytecode = match CompilationDriver::new(&filename).compile(&filename, &source) {
+        Ok(bytecode) => bytecode,
+        Err(e) => {
+            eprintln!("Rust compiler failed: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let bootstrap_bytecode = match compile_with_bootstrap(&source) {
+        Ok(bytecode) => bytecode,
+        Err(e) => {
+            eprintln!("Bootstrap compiler failed: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    println!("Rust compiler:      {} ops", rust_bytecode.len());
+    println!("Bootstrap compiler: {} ops", bootstrap_bytecode.len());
+
+    // Compare instruction by instruction using the disassembly text
+    let rust_ops: Vec<String> = rust_bytecode.iter().map(|op| format!("{:?}", op)).collect();
+    let bootstrap_ops: Vec<String> = bootstrap_bytecode
+        .iter()
+        .map(|op| format!("{:?}", op))
+        .collect();
+    let common = rust_ops.len().min(bootstrap_ops.len());
+    let mismatched: Vec<usize> = (0..common)
+        .filter(|&i| rust_ops[i] != bootstrap_ops[i])
+        .collect();
+    let bytecode_diffs = mismatched.len() + rust_ops.len().abs_diff(bootstrap_ops.len());
+
+    if bytecode_diffs == 0 {
+        println!("Bytecode: identical");
+    } else {
+        println!("Bytecode: {} differing instruction(s)", bytecode_diffs);
+        for &i in mismatched.iter().take(10) {
+            println!("  [{:>4}] rust:      {}", i, rust_ops[i]);
+            println!("         bootstrap: {}", bootstrap_ops[i]);
+        }
+        if mismatched.len() > 10 {
+            println!("  ... {} more", mismatched.len() - 10);
+        }
+    }
+
+    let rust_output = run_captured(rust_bytecode, &filename);
+    let bootstrap_output = run_captured(bootstrap_bytecode, &filename);
+
+    let output_matches = rust_output == bootstrap_output;
+    if output_matches {
+        println!(
+            "Output: identical ({} line(s))",
+            rust_output.lines().count()
+        );
+    } else {
+        println!("Output: differs");
+        let rust_lines: Vec<&str> = rust_output.lines().collect();
+        let bootstrap_lines: Vec<&str> = bootstrap_output.lines().collect();
+        let first = (0..rust_lines.len().max(bootstrap_lines.len()))
+            .find(|&i| rust_lines.get(i) != bootstrap_lines.get(i))
+            .unwrap_or(0);
+        println!("  first difference at line {}:", first + 1);
+        println!(
+            "  rust:      {}",
+            rust_lines.get(first).unwrap_or(&"<end of output>")
+        );
+        println!(
+            "  bootstrap: {}",
+            bootstrap_lines.get(first).unwrap_or(&"<end of output>")
+        );
+    }
+
+    if !output_matches || (strict && bytecode_diffs > 0) {
+        println!("FAIL");
+        std::process::exit(1);
+    }
+    println!("PASS");
+}
+
+/// Load the bootstrap compiler into a fresh VM and use it to compile `source`.
+fn compile_with_bootstrap(source: &str) -> Result<Vec<vm::opcodes::OpCode>, String> {
+    use crate::vm::value::{HeapData, JsValue};
+
+    let mut vm = VM::new();
+    let mut driver = CompilationDriver::default();
+
+    driver.load_prelude(&mut vm)?;
+    for bootstrap_file in BOOTSTRAP_FILES {
+        if !Path::new(bootstrap_file).exists() {
+            return Err(format!("Bootstrap file not found: {}", bootstrap_file));
+        }
+        driver.run_script(&mut vm, bootstrap_file, true)?;
+    }
+
+    // Hand the source over as a global and compile it with the emitter, which
+    // writes the stack bytecode `BytecodeDecoder` reads (the IR pipeline's
+    // codegen.ot targets a register format the VM doesn't load)
+    vm.call_stack[0].locals.insert(
+        "__bootstrap_source__".into(),
+        JsValue::String(source.to_string()),
+    );
+    let entry = driver
+        .compiler
+        .compile("let __bootstrap_result__ = compile(__bootstrap_source__);")
+        .map_err(|e| format!("Failed to compile driver: {}", e))?;
+    vm.captured_output = Some(String::new());
+    vm.append_program(entry);
+    vm.run_until_halt();
+    let log = vm.captured_output.take().unwrap_or_default();
+
+    // compile returns a ByteStream, or null when parsing failed
+    let result = vm.call_stack[0].locals.get("__bootstrap_result__").cloned();
+    let bytes = match result {
+        Some(JsValue::Object(ptr)) => match vm.heap.get(ptr).map(|o| &o.data) {
+            Some(HeapData::ByteStream(bytes)) => bytes.clone(),
+            _ => return Err("compile did not return a ByteStream".to_string()),
+        },
+        _ => {
+            let log = log.trim();
+            return Err(if log.is_empty() {
+                "compile returned no bytecode".to_string()
+            } else {
+                log.to_string()
+            });
+        }
+    };
+
+    BytecodeDecoder::new(&bytes)
+        .decode_all()
+        .map_err(|e| format!("Failed to decode bootstrap bytecode: {}", e))
+}
+
+/// Run a program in a fresh VM (stdlib and prelude loaded) and return what it
+/// printed with console.log.
+fn run_captured(bytecode: Vec<vm::opcodes::OpCode>, path: &str) -> String {
+    let mut vm = VM::new();
+    if let Err(e) = CompilationDriver::default().load_prelude(&mut vm) {
+        eprintln!("{}", e);
+    }
+    vm.captured_output = Some(String::new());
+    vm.append_program(bytecode);
+    vm.set_current_module_path(PathBuf::from(path));
+    vm.run_event_loop();
+    vm.captured_output.take().unwrap_or_default()
+}
+
+/// Run a file in the VM with the sampling profiler enabled and write the
+/// samples as collapsed stacks (for flamegraph.pl/inferno) or speedscope JSON.
+fn profile_file(args: &[String]) {
+    use crate::vm::profiler::{DEFAULT_INTERVAL, Profiler};
+
+    let mut speedscope = false;
+    let mut output = None;
+    let mut interval = DEFAULT_INTERVAL;
+
+    // Options come before the filename; everything after it goes to the script
+    let mut i = 0;
+    while i < args.len() && args[i].starts_with('-') {
+        match args[i].as_str() {
+            "--format" => {
+                i += 1;
+                speedscope = match args.get(i).map(String::as_str) {
+                    Some("collapsed") => false,
+                    Some("speedscope") => true,
+                    _ => {
+                        eprintln!("Error: --format must be 'collapsed' or 'speedscope'");
+                        std::process::exit(1);
+                    }
+                };
+            }
+            "--output" | "-o" => {
+                i += 1;
+                match args.get(i) {
+                    Some(path) => output = Some(path.clone()),
+                    None => {
+                        eprintln!("Error: --output requires a value");
+                        std::process::exit(1);
+                    }
+                }
+            }
+            "--interval" => {
+                i += 1;
+                match args.get(i).and_then(|v| v.parse::<u32>().ok()) {
+                    Some(n) if n > 0 => interval = n,
+                    _ => {
+                        eprintln!("Error: --interval requires a positive number of instructions");
+                        std::process::exit(1);
+                    }
+                }
+            }
+            other => {
+                eprintln!("Error: Unknown option: {}", other);
+                std::process::exit(1);
+            }
+        }
+        i += 1;
+    }
+    let Some(filename) = args.get(i) else {
+        eprintln!(
+            "Usage: oitec profile [--format collapsed|speedscope] [--output <file>] [--interval <n>] <filename> [args...]"
+        );
+        std::process::exit(1);
+    };
+
+    let mut vm = VM::new();
+    let mut driver = CompilationDriver::new(filename);
+    driver.configure_vm(&mut vm);
+    if let Err(e) = driver.load_prelude(&mut vm) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+    let bytecode = match driver.compile_file(filename) {
+        Ok((_, bytecode)) => bytecode,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    driver.print_warnings();
+
+    vm.append_program(bytecode);
+    vm.set_current_module_path(PathBuf::from(filename));
+    vm.set_script_args(args[i + 1..].to_vec());
+    vm.profiler = Some(Profiler::new(interval));
+    vm.run_event_loop();
+
+    let profile = match vm.profiler.take() {
+        Some(profiler) => profiler.finish(&vm.program),
+        None => return,
+    };
+    let (contents, default_output) = if speedscope {
+        (
+            profile.to_speedscope(filename).to_string(),
+            "profile.speedscope.json",
+        )
+    } else {
+        (profile.to_collapsed(), "profile.collapsed")
+    };
+    let output = output.unwrap_or_else(|| default_output.to_string());
+    if let Err(e) = fs::write(&output, contents) {
+        eprintln!("Failed to write {}: {}", output, e);
+        std::process::exit(1);
+    }
+
+    let total = profile.total_ns().max(1);
+    eprintln!();
+    eprintln!("=== Profile: {} ===", filename);
+    eprintln!("  {:>7}  {:>10}  Function", "Self %", "Self ms");
+    for (name, ns) in profile.self_times().iter().take(10) {
+        eprintln!(
+            "  {:>6.1}%  {:>10.2}  {}",
+            *ns as f64 * 100.0 / total as f64,
+            *ns as f64 / 1e6,
+            name
+        );
+    }
+    eprintln!("Wrote {}", output);
+}
+
+/// Run a file in the VM, then report what its heap holds when it finishes.
+/// `corpus [--max-steps <n>] [--keep <dir>] <path>...`: run every sample
+/// under the paths with invariant checks, report the ones that break the
+/// interpreter and, with `--keep`, copy the samples that add coverage
+#[cfg(feature = "corpus-runner")]
+fn run_corpus(args: &[String]) {
+    use crate::vm::corpus::{self, CorpusRunner, Outcome};
+
+    let mut max_steps = corpus::DEFAULT_MAX_STEPS;
+    let mut keep = None;
+    let mut paths = Vec::new();
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--max-steps" => {
+                i += 1;
+                match args.get(i).and_then(|n| n.parse::<u64>().ok()) {
+                    Some(n) if n > 0 => max_steps = n,
+                    _ => {
+                        eprintln!("Error: --max-steps requires a positive number");
+                        std::process::exit(1);
+                    }
+                }
+            }
+            "--keep" => {
+                i += 1;
+                match args.get(i) {
+                    Some(dir) => keep = Some(PathBuf::from(dir)),
+                    None => {
+                        eprintln!("Error: --keep requires a directory");
+                        std::process::exit(1);
+                    }
+                }
+            }
+            other if other.starts_with("--") => {
+                eprintln!("Error: Unknown option: {}", other);
+                std::process::exit(1);
+            }
+            other => paths.push(PathBuf::from(other)),
+        }
+        i += 1;
+    }
+    if paths.is_empty() {
+        eprintln!("Usage: oitec corpus [--max-steps <n>] [--keep <dir>] <path>...");
+        std::process::exit(1);
+    }
+    if let Some(dir) = &keep
+        && let Err(e) = fs::create_dir_all(dir)
+    {
+        eprintln!("Error: cannot create {}: {}", dir.display(), e);
+        std::process::exit(1);
+    }
+
+    let mut samples = Vec::new();
+    for path in &paths {
+        match corpus::collect_samples(path) {
+            Ok(found) => samples.extend(found),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let mut runner = CorpusRunner::new(max_steps);
+    let (mut completed, mut script_errors, mut step_limits, mut failures, mut skipped) =
+        (0, 0, 0, 0, 0);
+    let mut kept = 0;
+    for sample in &samples {
+        let report = match runner.run_sample(sample) {
+            Ok(report) => report,
+            Err(e) => {
+                eprintln!("skip {}", e);
+                skipped += 1;
+                continue;
+            }
+        };
+        match &report.outcome {
+            Outcome::Completed => completed += 1,
+            Outcome::ScriptError(_) => script_errors += 1,
+            Outcome::StepLimit => step_limits += 1,
+            Outcome::Violation(message) => {
+                println!("VIOLATION {}: {}", sample.display(), message);
+                failures += 1;
+            }
+            Outcome::Crash(message) => {
+                println!("CRASH {}: {}", sample.display(), message);
+                failures += 1;
+            }
+        }
+        if let Some(dir) = &keep
+            && report.new_edges > 0
+        {
+            let name = sample.file_name().expect("samples are files");
+            let target = dir.join(format!("{:05}-{}", kept, name.to_string_lossy()));
+            if let Err(e) = fs::copy(sample, &target) {
+                eprintln!("Error: cannot copy {}: {}", sample.display(), e);
+                std::process::exit(1);
+            }
+            kept += 1;
+        }
+    }
+
+    println!(
+        "{} samples: {} completed, {} threw, {} hit the step limit, {} failed, {} skipped",
+        samples.len(),
+        completed,
+        script_errors,
+        step_limits,
+        failures,
+        skipped
+    );
+    println!("{} opcode pairs covered", runner.edge_count());
+    if let Some(dir) = &keep {
+        println!("kept {} samples in {}", kept, dir.display());
+    }
+    if failures > 0 {
+        std::process::exit(1);
+    }
+}
+
+fn heap_report(args: &[String]) {
+    use crate::vm::heap_snapshot::HeapSnapshot;
+
+    let mut json = false;
+    let mut i = 0;
+    while i < args.len() && args[i].starts_with('-') {
+        match args[i].as_str() {
+            "--json" => json = true,
+            other => {
+                eprintln!("Error: Unknown option: {}", other);
+                std::process::exit(1);
+            }
+        }
+        i += 1;
+    }
+    let Some(filename) = args.get(i) else {
+        eprintln!("Usage: oitec heap [--json] <filename> [args...]");
+        std::process::exit(1);
+    };
+
+    let mut vm = VM::new();
+    let mut driver = CompilationDriver::new(filename);
+    driver.configure_vm(&mut vm);
+    if let Err(e) = driver.load_prelude(&mut vm) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+    let bytecode = match driver.compile_file(filename) {
+        Ok((_, bytecode)) => bytecode,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    driver.print_warnings();
+
+    vm.append_program(bytecode);
+    vm.set_current_module_path(PathBuf::from(filename));
+    vm.set_script_args(args[i + 1..].to_vec());
+    vm.run_event_loop();
+
+    let snapshot = HeapSnapshot::capture(&vm);
+    if json {
+        println!("{:#}", snapshot.to_json());
+    } else {
+        snapshot.print();
+    }
 }
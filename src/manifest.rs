@@ -0,0 +1,791 @@
+//! Project manifest (`script.toml`)
+//!
+//! The manifest is found by walking up from the file being compiled. It is
+//! optional; without one every setting falls back to its default.
+//!
+//! ```toml
+//! [modules]
+//! # Tried in this order when an import omits the extension. Default
+//! # extensions that aren't listed are tried afterwards.
+//! extensions = ["tscl", "ot"]
+//! # Directories bare specifiers (`import "utils/math"`) are looked up in,
+//! # relative to this file
+//! roots = ["src", "lib"]
+//!
+//! [build]
+//! # What `build` compiles, and `oitec` runs, when no file is given
+//! entry = "src/main.ot"
+//! # Defaults for --backend, -O<n> and --target
+//! backend = "llvm"
+//! opt-level = 2
+//! target = "x86_64-unknown-linux-gnu"
+//!
+//! [compiler]
+//! # off, warn or error (the default); `--borrow-check=` overrides it
+//! borrow-check = "warn"
+//! # Check values against their type annotations in `check` and `build`
+//! # (the default); `--no-typecheck` turns it off everywhere
+//! typecheck = true
+//!
+//! # Settings for the files under a directory, relative to this file. A
+//! # nested directory's settings win over its parent's, and what no
+//! # directory sets comes from [compiler].
+//! [compiler.dirs.vendor]
+//! borrow-check = "off"
+//! typecheck = false
+//!
+//! [env]
+//! # Substituted for import.meta.env.X / process.env.X by `build`;
+//! # `--define X=...` overrides them
+//! API_URL = "https://api.example.com"
+//!
+//! [transforms]
+//! # Importable non-TypeScript files, by extension (see transform.rs)
+//! json = "json"
+//!
+//! [stdlib]
+//! # Defaults for --operator-overloading and --locale
+//! operator-overloading = true
+//! locale = "de-DE"
+//!
+//! [dependencies]
+//! # Packages `oitec install` places in script_modules/ (see package.rs): a
+//! # registry version, or a git repository at a branch, tag or commit
+//! colors = "1.2.0"
+//! router = { git = "https://github.com/acme/router", rev = "v0.3.0" }
+//!
+//! [registry]
+//! # Where registry versions are downloaded from
+//! url = "https://registry.example.com"
+//! ```
+
+use crate::compiler::borrow_ck::BorrowCheckLevel;
+use crate::stdlib::console::ConsoleLocale;
+use crate::transform::Transformer;
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+
+/// File name looked up in the source directory and its ancestors
+pub const MANIFEST_FILE: &str = "script.toml";
+
+/// Directory installed packages live in, next to the manifest
+pub const MODULES_DIR: &str = "script_modules";
+
+/// Extensions tried by the resolver when the manifest doesn't say otherwise
+pub const DEFAULT_EXTENSIONS: [&str; 4] = ["ot", "ts", "tscl", "js"];
+
+/// Settings from `[compiler]` or one of its `[compiler.dirs]` tables; unset
+/// ones fall back to the enclosing table, then to the defaults
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CompilerOptions {
+    /// Ownership check severity
+    pub borrow_check: Option<BorrowCheckLevel>,
+    /// Whether `check` and `build` check values against type annotations
+    pub typecheck: Option<bool>,
+}
+
+impl CompilerOptions {
+    /// These options, with `fallback` filling in the ones left unset
+    pub fn or(&self, fallback: &CompilerOptions) -> CompilerOptions {
+        CompilerOptions {
+            borrow_check: self.borrow_check.or(fallback.borrow_check),
+            typecheck: self.typecheck.or(fallback.typecheck),
+        }
+    }
+
+    fn parse(table: &toml::Table, name: &str) -> Result<Self, String> {
+        let mut options = Self::default();
+        if let Some(level) = table.get("borrow-check") {
+            let level = level
+                .as_str()
+                .ok_or_else(|| format!("{}.borrow-check must be a string", name))?;
+            options.borrow_check = Some(BorrowCheckLevel::parse(level)?);
+        }
+        if let Some(typecheck) = table.get("typecheck") {
+            let typecheck = typecheck
+                .as_bool()
+                .ok_or_else(|| format!("{}.typecheck must be a boolean", name))?;
+            options.typecheck = Some(typecheck);
+        }
+        Ok(options)
+    }
+}
+
+/// Settings from `[build]`; each command-line flag overrides its setting
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BuildOptions {
+    /// Entry files relative to the manifest, for commands given no file
+    pub entries: Vec<PathBuf>,
+    /// Code generator, `llvm` or `cranelift`
+    pub backend: Option<String>,
+    /// Optimization level 0 to 3, as `-O<n>`
+    pub opt_level: Option<u8>,
+    /// Target triple for native code
+    pub target: Option<String>,
+}
+
+/// Runtime features from `[stdlib]`; each command-line flag overrides its
+/// setting
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StdlibOptions {
+    /// Dispatch operators on objects to their methods
+    pub operator_overloading: Option<bool>,
+    /// Number formatting for console output
+    pub locale: Option<ConsoleLocale>,
+}
+
+/// Where a `[dependencies]` entry comes from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DependencySource {
+    /// A version's tarball from a package registry; `[registry]` when the
+    /// entry names none
+    Registry {
+        version: String,
+        registry: Option<String>,
+    },
+    /// A git repository at a branch, tag or commit, or its default branch
+    Git { url: String, rev: Option<String> },
+}
+
+impl DependencySource {
+    pub(crate) fn parse(value: &toml::Value, name: &str) -> Result<Self, String> {
+        let field = |key: &str| -> Result<Option<String>, String> {
+            match value.get(key) {
+                None => Ok(None),
+                Some(toml::Value::String(s)) => Ok(Some(s.clone())),
+                Some(_) => Err(format!("dependencies.{}.{} must be a string", name, key)),
+            }
+        };
+        let source = match value {
+            toml::Value::String(version) => Self::Registry {
+                version: version.clone(),
+                registry: None,
+            },
+            toml::Value::Table(_) => match (field("git")?, field("version")?) {
+                (Some(url), None) => Self::Git {
+                    url,
+                    rev: field("rev")?,
+                },
+                (None, Some(version)) => Self::Registry {
+                    version,
+                    registry: field("registry")?,
+                },
+                _ => {
+                    return Err(format!(
+                        "dependencies.{} needs exactly one of `git` and `version`",
+                        name
+                    ));
+                }
+            },
+            _ => {
+                return Err(format!(
+                    "dependencies.{} must be a version or a table",
+                    name
+                ));
+            }
+        };
+        source.check(name)?;
+        Ok(source)
+    }
+
+    /// Reject values that would be misread where they are used: a version
+    /// goes into the tarball URL, and a git URL or revision is passed to
+    /// `git`, which would take one starting with `-` as an option. Manifests
+    /// of dependencies are as untrusted as the packages themselves.
+    pub(crate) fn check(&self, name: &str) -> Result<(), String> {
+        match self {
+            Self::Registry { version, .. } => {
+                if version.is_empty()
+                    || version.starts_with(['-', '.'])
+                    || !version
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '+'))
+                {
+                    return Err(format!(
+                        "dependencies.{}: invalid version '{}'",
+                        name, version
+                    ));
+                }
+            }
+            Self::Git { url, rev } => {
+                if url.is_empty() || url.starts_with('-') {
+                    return Err(format!("dependencies.{}: invalid git URL '{}'", name, url));
+                }
+                if let Some(rev) = rev
+                    && (rev.is_empty() || rev.starts_with('-'))
+                {
+                    return Err(format!(
+                        "dependencies.{}: invalid git revision '{}'",
+                        name, rev
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The entry as it is written in `[dependencies]`
+    pub fn to_toml(&self) -> String {
+        let quote = |s: &str| toml::Value::String(s.to_string()).to_string();
+        match self {
+            Self::Registry {
+                version,
+                registry: None,
+            } => quote(version),
+            Self::Registry {
+                version,
+                registry: Some(registry),
+            } => format!(
+                "{{ version = {}, registry = {} }}",
+                quote(version),
+                quote(registry)
+            ),
+            Self::Git { url, rev: None } => format!("{{ git = {} }}", quote(url)),
+            Self::Git {
+                url,
+                rev: Some(rev),
+            } => format!("{{ git = {}, rev = {} }}", quote(url), quote(rev)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Manifest {
+    /// Path of the `script.toml` this was read from, if any
+    pub path: Option<PathBuf>,
+    /// Module extensions in resolution priority order, without the leading dot
+    pub extensions: Vec<String>,
+    /// Directories for bare specifiers, relative to the manifest
+    pub roots: Vec<PathBuf>,
+    /// Project-wide compiler settings from `[compiler]`
+    pub compiler: CompilerOptions,
+    /// Per-directory settings from `[compiler.dirs]`, keyed by the
+    /// directory relative to the manifest
+    pub dirs: Vec<(PathBuf, CompilerOptions)>,
+    /// Build-time environment variables from `[env]`
+    pub env: HashMap<String, String>,
+    /// Source transformers by extension, from `[transforms]`; `.json` files
+    /// are JSON unless that says otherwise
+    pub transforms: HashMap<String, Transformer>,
+    /// Defaults for `build`, from `[build]`
+    pub build: BuildOptions,
+    /// Runtime features, from `[stdlib]`
+    pub stdlib: StdlibOptions,
+    /// Packages to install, by name, from `[dependencies]`
+    pub dependencies: Vec<(String, DependencySource)>,
+    /// Default package registry URL, from `[registry]`
+    pub registry: Option<String>,
+}
+
+impl Default for Manifest {
+    fn default() -> Self {
+        Self {
+            path: None,
+            extensions: DEFAULT_EXTENSIONS.iter().map(|e| e.to_string()).collect(),
+            roots: Vec::new(),
+            compiler: CompilerOptions::default(),
+            dirs: Vec::new(),
+            env: HashMap::new(),
+            transforms: HashMap::from([("json".to_string(), Transformer::Json)]),
+            build: BuildOptions::default(),
+            stdlib: StdlibOptions::default(),
+            dependencies: Vec::new(),
+            registry: None,
+        }
+    }
+}
+
+impl Manifest {
+    /// Find the nearest `script.toml` at or above `start` (a file or directory).
+    pub fn find(start: &Path) -> Option<PathBuf> {
+        let start = if start.is_file() {
+            start.parent()?
+        } else {
+            start
+        };
+        let absolute = crate::platform::canonicalize(start).unwrap_or_else(|_| start.to_path_buf());
+        absolute
+            .ancestors()
+            .map(|dir| dir.join(MANIFEST_FILE))
+            .find(|candidate| candidate.is_file())
+    }
+
+    /// Load the manifest that applies to `start`, or the defaults if there is none.
+    pub fn discover(start: &Path) -> Result<Self, String> {
+        match Self::find(start) {
+            Some(path) => Self::load(&path),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Like `discover`, but reports a broken manifest on stderr and uses the defaults.
+    pub fn discover_or_default(start: &Path) -> Self {
+        Self::discover(start).unwrap_or_else(|e| {
+            eprintln!("Warning: {}", e);
+            Self::default()
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let source = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let mut manifest =
+            Self::parse(&source).map_err(|e| format!("{}: {}", path.display(), e))?;
+        manifest.path = Some(path.to_path_buf());
+        Ok(manifest)
+    }
+
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let table: toml::Table = source.parse().map_err(|e: toml::de::Error| e.to_string())?;
+        let mut manifest = Self::default();
+
+        if let Some(modules) = table.get("modules") {
+            let modules = modules.as_table().ok_or("[modules] must be a table")?;
+            if let Some(extensions) = modules.get("extensions") {
+                let list = extensions
+                    .as_array()
+                    .ok_or("modules.extensions must be an array of strings")?;
+                let mut declared = Vec::new();
+                for ext in list {
+                    let ext = ext
+                        .as_str()
+                        .ok_or("modules.extensions must be an array of strings")?;
+                    declared.push(normalize_extension(ext)?);
+                }
+                manifest.extensions = with_defaults(declared);
+            }
+            if let Some(roots) = modules.get("roots") {
+                for root in strings(roots, "modules.roots")? {
+                    manifest
+                        .roots
+                        .push(project_relative(&root, "modules.roots")?);
+                }
+            }
+        }
+
+        if let Some(build) = table.get("build") {
+            let build = build.as_table().ok_or("[build] must be a table")?;
+            if let Some(entry) = build.get("entry") {
+                let entries = match entry {
+                    toml::Value::String(entry) => vec![entry.clone()],
+                    _ => strings(entry, "build.entry")?,
+                };
+                for entry in entries {
+                    manifest
+                        .build
+                        .entries
+                        .push(project_relative(&entry, "build.entry")?);
+                }
+            }
+            if let Some(backend) = build.get("backend") {
+                match backend.as_str() {
+                    Some(name @ ("llvm" | "cranelift")) => {
+                        manifest.build.backend = Some(name.to_string())
+                    }
+                    _ => return Err("build.backend must be \"llvm\" or \"cranelift\"".into()),
+                }
+            }
+            if let Some(level) = build.get("opt-level") {
+                match level.as_integer() {
+                    Some(level @ 0..=3) => manifest.build.opt_level = Some(level as u8),
+                    _ => return Err("build.opt-level must be 0, 1, 2 or 3".into()),
+                }
+            }
+            if let Some(target) = build.get("target") {
+                let target = target.as_str().ok_or("build.target must be a string")?;
+                manifest.build.target = Some(target.to_string());
+            }
+        }
+
+        if let Some(compiler) = table.get("compiler") {
+            let compiler = compiler.as_table().ok_or("[compiler] must be a table")?;
+            manifest.compiler = CompilerOptions::parse(compiler, "compiler")?;
+            if let Some(dirs) = compiler.get("dirs") {
+                let dirs = dirs.as_table().ok_or("[compiler.dirs] must be a table")?;
+                for (dir, options) in dirs {
+                    let name = format!("compiler.dirs.\"{}\"", dir);
+                    let options = options
+                        .as_table()
+                        .ok_or_else(|| format!("[{}] must be a table", name))?;
+                    manifest.dirs.push((
+                        project_relative(dir, "compiler.dirs")?,
+                        CompilerOptions::parse(options, &name)?,
+                    ));
+                }
+            }
+        }
+
+        if let Some(env) = table.get("env") {
+            let env = env.as_table().ok_or("[env] must be a table")?;
+            for (name, value) in env {
+                // Numbers and booleans are accepted and kept as their text,
+                // the way they would arrive from a real environment
+                let value = match value {
+                    toml::Value::String(s) => s.clone(),
+                    toml::Value::Integer(_) | toml::Value::Float(_) | toml::Value::Boolean(_) => {
+                        value.to_string()
+                    }
+                    _ => return Err(format!("env.{} must be a string", name)),
+                };
+                manifest.env.insert(name.clone(), value);
+            }
+        }
+
+        if let Some(transforms) = table.get("transforms") {
+            let transforms = transforms
+                .as_table()
+                .ok_or("[transforms] must be a table")?;
+            for (ext, value) in transforms {
+                let transformer =
+                    Transformer::parse(value).map_err(|e| format!("transforms.{}: {}", ext, e))?;
+                manifest
+                    .transforms
+                    .insert(normalize_extension(ext)?, transformer);
+            }
+        }
+
+        if let Some(stdlib) = table.get("stdlib") {
+            let stdlib = stdlib.as_table().ok_or("[stdlib] must be a table")?;
+            if let Some(enabled) = stdlib.get("operator-overloading") {
+                let enabled = enabled
+                    .as_bool()
+                    .ok_or("stdlib.operator-overloading must be a boolean")?;
+                manifest.stdlib.operator_overloading = Some(enabled);
+            }
+            if let Some(locale) = stdlib.get("locale") {
+                let locale = locale.as_str().ok_or("stdlib.locale must be a string")?;
+                manifest.stdlib.locale = Some(
+                    ConsoleLocale::parse(locale).map_err(|e| format!("stdlib.locale: {}", e))?,
+                );
+            }
+        }
+
+        if let Some(dependencies) = table.get("dependencies") {
+            let dependencies = dependencies
+                .as_table()
+                .ok_or("[dependencies] must be a table")?;
+            for (name, value) in dependencies {
+                if !is_package_name(name) {
+                    return Err(format!("dependencies: invalid package name '{}'", name));
+                }
+                manifest
+                    .dependencies
+                    .push((name.clone(), DependencySource::parse(value, name)?));
+            }
+        }
+
+        if let Some(registry) = table.get("registry") {
+            let registry = registry.as_table().ok_or("[registry] must be a table")?;
+            if let Some(url) = registry.get("url") {
+                let url = url.as_str().ok_or("registry.url must be a string")?;
+                manifest.registry = Some(url.trim_end_matches('/').to_string());
+            }
+        }
+
+        Ok(manifest)
+    }
+
+    /// `relative`, a path from the manifest, from the working directory
+    fn project_path(&self, relative: &Path) -> PathBuf {
+        match self.path.as_deref().and_then(Path::parent) {
+            Some(root) => root.join(relative),
+            None => relative.to_path_buf(),
+        }
+    }
+
+    /// Entry files from `[build]`
+    pub fn entries(&self) -> Vec<PathBuf> {
+        self.build
+            .entries
+            .iter()
+            .map(|entry| self.project_path(entry))
+            .collect()
+    }
+
+    /// Directories bare specifiers resolve in: the `[modules]` roots, then
+    /// each `script_modules` at or above the manifest, nearest first
+    pub fn module_roots(&self) -> Vec<PathBuf> {
+        let installed = self
+            .path
+            .as_deref()
+            .and_then(Path::parent)
+            .into_iter()
+            .flat_map(Path::ancestors)
+            .map(|dir| dir.join(MODULES_DIR))
+            .filter(|dir| dir.is_dir());
+        self.roots
+            .iter()
+            .map(|root| self.project_path(root))
+            .chain(installed)
+            .collect()
+    }
+
+    /// Compiler settings for the file at `path`: the `[compiler.dirs]`
+    /// entries holding it, deepest first, over `[compiler]`
+    pub fn compiler_options_for(&self, path: &Path) -> CompilerOptions {
+        let root = self.path.as_deref().and_then(Path::parent);
+        let absolute = root
+            .and_then(|_| crate::platform::canonicalize(path).ok())
+            .unwrap_or_else(|| path.to_path_buf());
+        let relative = root
+            .and_then(|root| absolute.strip_prefix(root).ok())
+            .unwrap_or(&absolute);
+        let mut enclosing: Vec<&(PathBuf, CompilerOptions)> = self
+            .dirs
+            .iter()
+            .filter(|(dir, _)| relative.starts_with(dir))
+            .collect();
+        enclosing.sort_by_key(|(dir, _)| dir.components().count());
+        enclosing
+            .into_iter()
+            .fold(self.compiler.clone(), |outer, (_, inner)| inner.or(&outer))
+    }
+
+    /// Whether `path` has one of the configured module extensions
+    pub fn is_module_path(&self, path: &Path) -> bool {
+        let Some(name) = path.file_name().map(|n| n.to_string_lossy()) else {
+            return false;
+        };
+        self.extensions
+            .iter()
+            .any(|ext| name.len() > ext.len() + 1 && name.ends_with(&format!(".{}", ext)))
+    }
+}
+
+/// Strip a leading dot and reject values that can't be a file extension
+fn normalize_extension(ext: &str) -> Result<String, String> {
+    let ext = ext.strip_prefix('.').unwrap_or(ext);
+    if ext.is_empty() || ext.contains(['/', '\\']) || ext.starts_with('.') {
+        return Err(format!("invalid module extension '{}'", ext));
+    }
+    Ok(ext.to_string())
+}
+
+/// Whether `name` can name a package and its `script_modules` directory
+pub fn is_package_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+/// An array of strings, or an error naming `field`
+fn strings(value: &toml::Value, field: &str) -> Result<Vec<String>, String> {
+    let error = || format!("{} must be an array of strings", field);
+    value
+        .as_array()
+        .ok_or_else(error)?
+        .iter()
+        .map(|item| item.as_str().map(str::to_string).ok_or_else(error))
+        .collect()
+}
+
+/// A path inside the project, relative to the manifest and without `.`
+/// components
+fn project_relative(dir: &str, field: &str) -> Result<PathBuf, String> {
+    let path = Path::new(dir);
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => normalized.push(part),
+            Component::CurDir => {}
+            _ => {
+                return Err(format!(
+                    "{}: '{}' must be a path inside the project",
+                    field, dir
+                ));
+            }
+        }
+    }
+    Ok(normalized)
+}
+
+/// Declared extensions first, then any defaults they didn't mention
+fn with_defaults(declared: Vec<String>) -> Vec<String> {
+    let mut extensions = Vec::new();
+    let defaults = DEFAULT_EXTENSIONS.iter().map(|e| e.to_string());
+    for ext in declared.into_iter().chain(defaults) {
+        if !extensions.contains(&ext) {
+            extensions.push(ext);
+        }
+    }
+    extensions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_declared_extensions_take_priority() {
+        let manifest =
+            Manifest::parse("[modules]\nextensions = [\".tscl\", \"mts\", \"ot\"]\n").unwrap();
+        assert_eq!(manifest.extensions, ["tscl", "mts", "ot", "ts", "js"]);
+        assert!(manifest.is_module_path(Path::new("src/a.mts")));
+        assert!(!manifest.is_module_path(Path::new("src/a.json")));
+    }
+
+    #[test]
+    fn test_rejects_invalid_extensions() {
+        assert!(Manifest::parse("[modules]\nextensions = [\"\"]\n").is_err());
+        assert!(Manifest::parse("[modules]\nextensions = \"ot\"\n").is_err());
+        assert_eq!(Manifest::parse("").unwrap(), Manifest::default());
+    }
+
+    #[test]
+    fn test_borrow_check_level() {
+        let manifest = Manifest::parse("[compiler]\nborrow-check = \"warn\"\n").unwrap();
+        assert_eq!(manifest.compiler.borrow_check, Some(BorrowCheckLevel::Warn));
+        assert!(Manifest::parse("[compiler]\nborrow-check = \"lenient\"\n").is_err());
+    }
+
+    #[test]
+    fn test_nested_directory_settings_win() {
+        let manifest = Manifest::parse(
+            "[compiler]\nborrow-check = \"error\"\n\
+             [compiler.dirs.vendor]\nborrow-check = \"off\"\ntypecheck = false\n\
+             [compiler.dirs.\"./vendor/patched/\"]\nborrow-check = \"warn\"\n",
+        )
+        .unwrap();
+        let options = |path: &str| manifest.compiler_options_for(Path::new(path));
+        assert_eq!(
+            options("src/main.ot"),
+            CompilerOptions {
+                borrow_check: Some(BorrowCheckLevel::Error),
+                typecheck: None,
+            }
+        );
+        assert_eq!(
+            options("vendor/lib.ot"),
+            CompilerOptions {
+                borrow_check: Some(BorrowCheckLevel::Off),
+                typecheck: Some(false),
+            }
+        );
+        assert_eq!(
+            options("vendor/patched/lib.ot"),
+            CompilerOptions {
+                borrow_check: Some(BorrowCheckLevel::Warn),
+                typecheck: Some(false),
+            }
+        );
+        assert_eq!(
+            options("vendored.ot").borrow_check,
+            Some(BorrowCheckLevel::Error)
+        );
+        assert!(Manifest::parse("[compiler.dirs.\"../lib\"]\n").is_err());
+        assert!(Manifest::parse("[compiler.dirs.vendor]\ntypecheck = \"no\"\n").is_err());
+    }
+
+    #[test]
+    fn test_env_values_are_strings() {
+        let manifest =
+            Manifest::parse("[env]\nAPI_URL = \"https://a.example\"\nRETRIES = 3\n").unwrap();
+        assert_eq!(manifest.env["API_URL"], "https://a.example");
+        assert_eq!(manifest.env["RETRIES"], "3");
+        assert!(Manifest::parse("[env]\nLIST = [1]\n").is_err());
+    }
+
+    #[test]
+    fn test_build_and_stdlib_options() {
+        let manifest = Manifest::parse(
+            "[modules]\nroots = [\"src\", \"./lib/\"]\n\
+             [build]\nentry = \"src/main.ot\"\nbackend = \"cranelift\"\nopt-level = 2\n\
+             target = \"aarch64-apple-darwin\"\n\
+             [stdlib]\noperator-overloading = true\nlocale = \"de-DE\"\n",
+        )
+        .unwrap();
+        assert_eq!(manifest.roots, [PathBuf::from("src"), PathBuf::from("lib")]);
+        assert_eq!(manifest.entries(), [PathBuf::from("src/main.ot")]);
+        assert_eq!(manifest.build.backend.as_deref(), Some("cranelift"));
+        assert_eq!(manifest.build.opt_level, Some(2));
+        assert_eq!(
+            manifest.build.target.as_deref(),
+            Some("aarch64-apple-darwin")
+        );
+        assert_eq!(manifest.stdlib.operator_overloading, Some(true));
+        assert!(manifest.stdlib.locale.is_some());
+
+        let entries = Manifest::parse("[build]\nentry = [\"a.ot\", \"b.ot\"]\n").unwrap();
+        assert_eq!(entries.build.entries.len(), 2);
+        assert!(Manifest::parse("[build]\nopt-level = 4\n").is_err());
+        assert!(Manifest::parse("[build]\nbackend = \"gcc\"\n").is_err());
+        assert!(Manifest::parse("[build]\nentry = \"../main.ot\"\n").is_err());
+        assert!(Manifest::parse("[modules]\nroots = \"src\"\n").is_err());
+        assert!(Manifest::parse("[stdlib]\noperator-overloading = \"yes\"\n").is_err());
+    }
+
+    #[test]
+    fn test_dependencies() {
+        let manifest = Manifest::parse(
+            "[dependencies]\ncolors = \"1.2.0\"\n\
+             router = { git = \"https://example.com/router.git\", rev = \"v0.3.0\" }\n\
+             [registry]\nurl = \"https://registry.example.com/\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            manifest.dependencies,
+            [
+                (
+                    "colors".to_string(),
+                    DependencySource::Registry {
+                        version: "1.2.0".to_string(),
+                        registry: None,
+                    }
+                ),
+                (
+                    "router".to_string(),
+                    DependencySource::Git {
+                        url: "https://example.com/router.git".to_string(),
+                        rev: Some("v0.3.0".to_string()),
+                    }
+                ),
+            ]
+        );
+        assert_eq!(
+            manifest.registry.as_deref(),
+            Some("https://registry.example.com")
+        );
+        for (name, source) in &manifest.dependencies {
+            let reparsed = Manifest::parse(&format!(
+                "[dependencies]\n{} = {}\n",
+                name,
+                source.to_toml()
+            ))
+            .unwrap();
+            assert_eq!(&reparsed.dependencies[0].1, source);
+        }
+
+        assert!(Manifest::parse("[dependencies]\n\"../up\" = \"1.0.0\"\n").is_err());
+        assert!(Manifest::parse("[dependencies]\nx = { rev = \"main\" }\n").is_err());
+        assert!(Manifest::parse("[dependencies]\nx = 1\n").is_err());
+        // Nothing a dependency's manifest says may reach git as an option
+        // or leave the registry path
+        assert!(
+            Manifest::parse("[dependencies]\nx = { git = \"--upload-pack=touch\" }\n").is_err()
+        );
+        assert!(
+            Manifest::parse(
+                "[dependencies]\nx = { git = \"https://example.com/x.git\", rev = \"-b\" }\n"
+            )
+            .is_err()
+        );
+        assert!(Manifest::parse("[dependencies]\nx = \"../../evil\"\n").is_err());
+        assert!(Manifest::parse("[dependencies]\nx = \"1.0.0/../../y\"\n").is_err());
+        assert!(Manifest::parse("[dependencies]\nx = \"1.0.0-beta.1+build\"\n").is_ok());
+    }
+
+    #[test]
+    fn test_transforms_by_extension() {
+        let manifest =
+            Manifest::parse("[transforms]\n\".json\" = \"json\"\nsfc = [\"node\", \"sfc.js\"]\n")
+                .unwrap();
+        assert_eq!(manifest.transforms["json"], Transformer::Json);
+        assert_eq!(
+            manifest.transforms["sfc"],
+            Transformer::Command(vec!["node".to_string(), "sfc.js".to_string()])
+        );
+        assert!(Manifest::parse("[transforms]\nyaml = \"yaml\"\n").is_err());
+    }
+}
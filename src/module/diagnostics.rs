@@ -1,5 +1,5 @@
 use std::fmt;
-use std::path::{Path, PathBuf};
+use std::path::PathBuf;
 
 #[derive(Debug, Clone)]
 pub struct SourceLocation {
@@ -70,7 +70,7 @@ impl ModuleError {
             kind: ModuleErrorKind::NotFound { specifier, tried_paths },
             source_location: None,
             dependency_chain: Vec::new(),
-            suggestion: Some("Check the file path and ensure the file exists with a supported extension (.ot, .ts, .js)".to_string()),
+            suggestion: Some("Check the file path and ensure the file exists with a supported extension (.ot, .ts, .tscl, .js, or those listed under [modules] in script.toml)".to_string()),
         }
     }
 
@@ -103,6 +103,7 @@ impl ModuleError {
         module_path: String,
         available: Vec<String>,
     ) -> Self {
+        let suggestion = format!("Available exports: {}", available.join(", "));
         Self {
             kind: ModuleErrorKind::ExportError {
                 export_name,
@@ -111,7 +112,7 @@ impl ModuleError {
             },
             source_location: None,
             dependency_chain: Vec::new(),
-            suggestion: Some(format!("Available exports: {}", available.join(", "))),
+            suggestion: Some(suggestion),
         }
     }
 
@@ -133,7 +134,7 @@ impl ModuleError {
             source_location: None,
             dependency_chain: Vec::new(),
             suggestion: Some(
-                "Import assertions are parsed but not enforced. The assertion will be ignored."
+                "Only `with { type: \"json\" }` is supported; leave the type out to import a module"
                     .to_string(),
             ),
         }
@@ -217,7 +218,7 @@ impl fmt::Display for ModuleError {
             } => {
                 writeln!(
                     f,
-                    "Warning: Import assertion '{}' for '{}' is not fully supported",
+                    "Unsupported import type '{}' for '{}'",
                     assertion_type, specifier
                 )?;
             }
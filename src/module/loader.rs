@@ -1,6 +1,5 @@
 use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::hash::{Hash, Hasher};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::sync::Mutex;
@@ -8,11 +7,15 @@ use std::time::SystemTime;
 
 use sha2::{Digest, Sha256};
 
-use swc_common::{BytePos, FileName, input::StringInput, source_map::SourceMap};
+use swc_common::{FileName, input::StringInput, source_map::SourceMap};
+use swc_ecma_ast::ModuleExportName;
 use swc_ecma_parser::{Parser, Syntax, TsSyntax, lexer::Lexer};
 
-use crate::module::diagnostics::{DependencyInfo, ModuleError, ModuleResult};
-use crate::module::resolver::{ImportAssertions, ModuleResolver, ResolvedModule};
+use crate::manifest::Manifest;
+use crate::module::diagnostics::{ModuleError, ModuleResult};
+use crate::module::resolver::{ImportAssertions, ModuleResolver};
+use crate::platform;
+use crate::vm::module_cache::ModuleId;
 
 #[derive(Debug, Clone)]
 pub struct ParsedModule {
@@ -85,10 +88,10 @@ impl ModuleCache {
     pub fn get(&self, path: &PathBuf) -> Option<Arc<LoadedModule>> {
         if let Some(cached_hash) = self.content_hashes.get(path) {
             let current_hash = self.compute_hash(path);
-            if cached_hash == &current_hash {
-                if let Some(cached) = self.entries.get(path) {
-                    return Some(cached.module.clone());
-                }
+            if cached_hash == &current_hash
+                && let Some(cached) = self.entries.get(path)
+            {
+                return Some(cached.module.clone());
             }
         }
         None
@@ -109,10 +112,10 @@ impl ModuleCache {
     pub fn should_reload(&self, path: &PathBuf) -> bool {
         match fs::metadata(path) {
             Ok(metadata) => {
-                if let Ok(modified) = metadata.modified() {
-                    if let Some(cached_time) = self.modification_times.get(path) {
-                        return modified > *cached_time;
-                    }
+                if let Ok(modified) = metadata.modified()
+                    && let Some(cached_time) = self.modification_times.get(path)
+                {
+                    return modified > *cached_time;
                 }
                 true
             }
@@ -133,10 +136,10 @@ impl ModuleCache {
                 load_time: SystemTime::now(),
             },
         );
-        if let Ok(metadata) = fs::metadata(&path) {
-            if let Ok(modified) = metadata.modified() {
-                self.modification_times.insert(path, modified);
-            }
+        if let Ok(metadata) = fs::metadata(&path)
+            && let Ok(modified) = metadata.modified()
+        {
+            self.modification_times.insert(path, modified);
         }
     }
 
@@ -157,6 +160,9 @@ pub struct ModuleLoader {
     resolver: ModuleResolver,
     cache: Arc<Mutex<ModuleCache>>,
     in_progress: Arc<Mutex<HashSet<PathBuf>>>,
+    /// First canonical path seen for each module file, so symlinked or
+    /// differently-cased imports load the module once
+    identities: Arc<Mutex<HashMap<ModuleId, PathBuf>>>,
 }
 
 impl ModuleLoader {
@@ -165,10 +171,19 @@ impl ModuleLoader {
             resolver: ModuleResolver::new(),
             cache: Arc::new(Mutex::new(ModuleCache::new())),
             in_progress: Arc::new(Mutex::new(HashSet::new())),
+            identities: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Loader resolving imports with the extension priority of `manifest`
+    pub fn with_manifest(manifest: &Manifest) -> Self {
+        Self {
+            resolver: ModuleResolver::for_manifest(manifest),
+            ..Self::new()
         }
     }
 
-    pub fn with_base_path<P: Into<PathBuf>>(mut self, path: P) -> Self {
+    pub fn with_base_path<P: Into<PathBuf>>(self, path: P) -> Self {
         Self {
             resolver: self.resolver.with_base_path(path),
             ..self
@@ -180,14 +195,30 @@ impl ModuleLoader {
     }
 
     pub async fn load(&mut self, entry_path: &Path) -> ModuleResult<Arc<LoadedModule>> {
-        let canonical = fs::canonicalize(entry_path)
-            .map_err(|e| ModuleError::io_error(entry_path.to_path_buf(), e.to_string()))?;
+        self.load_as(entry_path, None).await
+    }
 
-        let cache = self.cache.lock().unwrap();
-        if let Some(cached) = cache.get(&canonical) {
+    /// Load the module at `entry_path` as an import with `assertions` asks for;
+    /// `with { type: "json" }` reads the file as JSON
+    async fn load_as(
+        &mut self,
+        entry_path: &Path,
+        assertions: Option<&ImportAssertions>,
+    ) -> ModuleResult<Arc<LoadedModule>> {
+        let canonical = platform::canonicalize(entry_path)
+            .map_err(|e| ModuleError::io_error(entry_path.to_path_buf(), e.to_string()))?;
+        let canonical = self
+            .identities
+            .lock()
+            .unwrap()
+            .entry(ModuleId::of(&canonical))
+            .or_insert(canonical)
+            .clone();
+
+        let cached = self.cache.lock().unwrap().get(&canonical);
+        if let Some(cached) = cached {
             return Ok(cached);
         }
-        drop(cache);
 
         {
             let in_progress = self.in_progress.lock().unwrap();
@@ -203,14 +234,29 @@ impl ModuleLoader {
 
         let source = fs::read_to_string(&canonical)
             .map_err(|e| ModuleError::io_error(canonical.clone(), e.to_string()))?;
+        let json = assertions.is_some_and(ImportAssertions::is_json);
+        let source = crate::transform::transform_import(&canonical, source, json)
+            .map_err(|e| ModuleError::parse_error(e, canonical.clone(), 0, 0))?;
 
-        let parsed = self.parse_module(&canonical, &source)?;
+        let mut parsed = self.parse_module(&canonical, &source)?;
+        parsed.assertions = assertions.cloned();
         let imports = self.extract_imports(&parsed.ast);
 
         let mut dependencies = Vec::new();
         for import in &imports {
+            if let Some(ty) = import
+                .assertions
+                .as_ref()
+                .and_then(|a| a.unsupported_type())
+            {
+                return Err(ModuleError::unsupported_assertion(
+                    ty.to_string(),
+                    import.specifier.clone(),
+                ));
+            }
             let resolved = self.resolver.resolve(&import.specifier, &canonical)?;
-            let loaded = self.load(&resolved.path).await?;
+            // Boxed: the future recurses into each dependency
+            let loaded = Box::pin(self.load_as(&resolved.path, import.assertions.as_ref())).await?;
             dependencies.push((import.clone(), loaded));
         }
 
@@ -235,10 +281,13 @@ impl ModuleLoader {
     }
 
     fn parse_module(&self, path: &PathBuf, source: &str) -> ModuleResult<ParsedModule> {
-        let source_map = SourceMap::new();
+        let source_map = SourceMap::default();
+        // Tagged union declarations are not TypeScript; parse what the
+        // compiler will see
+        let (parsed_source, _) = crate::compiler::unions::desugar(source);
         let fm = source_map.new_source_file(
             FileName::Custom(path.to_string_lossy().to_string()).into(),
-            source.to_string(),
+            parsed_source,
         );
 
         let lexer = Lexer::new(
@@ -272,15 +321,12 @@ impl ModuleLoader {
                     content_hash,
                 })
             }
-            Err(e) => {
-                let location = format!("{:?}", e.span());
-                Err(ModuleError::parse_error(
-                    format!("Parse error: {}", e.kind().msg()),
-                    path.clone(),
-                    0,
-                    0,
-                ))
-            }
+            Err(e) => Err(ModuleError::parse_error(
+                format!("Parse error: {}", e.kind().msg()),
+                path.clone(),
+                0,
+                0,
+            )),
         }
     }
 
@@ -288,71 +334,70 @@ impl ModuleLoader {
         let mut imports = Vec::new();
 
         for item in &ast.body {
-            if let swc_ecma_ast::ModuleItem::ModuleDecl(decl) = item {
-                if let swc_ecma_ast::ModuleDecl::Import(import) = decl {
-                    let specifier = import.src.value.to_string();
-                    let assertions = import
-                        .with
-                        .as_ref()
-                        .map(|with| self.resolver.parse_import_assertions(Some(with)))
-                        .flatten();
-
-                    for spec in &import.specifiers {
-                        match spec {
-                            swc_ecma_ast::ImportSpecifier::Named(named) => {
-                                let local = named.local.sym.to_string();
-                                let imported = named
-                                    .imported
-                                    .as_ref()
-                                    .map(|i| i.sym.to_string())
-                                    .unwrap_or_else(|| local.clone());
-
-                                imports.push(ModuleImport {
-                                    specifier: specifier.clone(),
-                                    local_name: Some(local),
-                                    imported_name: Some(imported),
-                                    is_namespace: false,
-                                    is_default: false,
-                                    is_side_effect: false,
-                                    assertions: assertions.clone(),
-                                });
-                            }
-                            swc_ecma_ast::ImportSpecifier::Default(default) => {
-                                imports.push(ModuleImport {
-                                    specifier: specifier.clone(),
-                                    local_name: Some(default.local.sym.to_string()),
-                                    imported_name: None,
-                                    is_namespace: false,
-                                    is_default: true,
-                                    is_side_effect: false,
-                                    assertions: assertions.clone(),
-                                });
-                            }
-                            swc_ecma_ast::ImportSpecifier::Namespace(ns) => {
-                                imports.push(ModuleImport {
-                                    specifier: specifier.clone(),
-                                    local_name: Some(ns.local.sym.to_string()),
-                                    imported_name: None,
-                                    is_namespace: true,
-                                    is_default: false,
-                                    is_side_effect: false,
-                                    assertions: assertions.clone(),
-                                });
-                            }
+            if let swc_ecma_ast::ModuleItem::ModuleDecl(swc_ecma_ast::ModuleDecl::Import(import)) =
+                item
+            {
+                let specifier = import.src.value.to_string_lossy().into_owned();
+                let assertions = import
+                    .with
+                    .as_ref()
+                    .and_then(|with| self.resolver.parse_import_assertions(Some(with)));
+
+                for spec in &import.specifiers {
+                    match spec {
+                        swc_ecma_ast::ImportSpecifier::Named(named) => {
+                            let local = named.local.sym.to_string();
+                            let imported = named
+                                .imported
+                                .as_ref()
+                                .map(export_name)
+                                .unwrap_or_else(|| local.clone());
+
+                            imports.push(ModuleImport {
+                                specifier: specifier.clone(),
+                                local_name: Some(local),
+                                imported_name: Some(imported),
+                                is_namespace: false,
+                                is_default: false,
+                                is_side_effect: false,
+                                assertions: assertions.clone(),
+                            });
+                        }
+                        swc_ecma_ast::ImportSpecifier::Default(default) => {
+                            imports.push(ModuleImport {
+                                specifier: specifier.clone(),
+                                local_name: Some(default.local.sym.to_string()),
+                                imported_name: None,
+                                is_namespace: false,
+                                is_default: true,
+                                is_side_effect: false,
+                                assertions: assertions.clone(),
+                            });
+                        }
+                        swc_ecma_ast::ImportSpecifier::Namespace(ns) => {
+                            imports.push(ModuleImport {
+                                specifier: specifier.clone(),
+                                local_name: Some(ns.local.sym.to_string()),
+                                imported_name: None,
+                                is_namespace: true,
+                                is_default: false,
+                                is_side_effect: false,
+                                assertions: assertions.clone(),
+                            });
                         }
                     }
+                }
 
-                    if import.specifiers.is_empty() {
-                        imports.push(ModuleImport {
-                            specifier,
-                            local_name: None,
-                            imported_name: None,
-                            is_namespace: false,
-                            is_default: false,
-                            is_side_effect: true,
-                            assertions,
-                        });
-                    }
+                if import.specifiers.is_empty() {
+                    imports.push(ModuleImport {
+                        specifier,
+                        local_name: None,
+                        imported_name: None,
+                        is_namespace: false,
+                        is_default: false,
+                        is_side_effect: true,
+                        assertions,
+                    });
                 }
             }
         }
@@ -371,13 +416,13 @@ impl ModuleLoader {
                             for spec in &named.specifiers {
                                 let export_name = match spec {
                                     swc_ecma_ast::ExportSpecifier::Named(named) => {
-                                        named.orig.sym.to_string()
+                                        export_name(&named.orig)
                                     }
                                     swc_ecma_ast::ExportSpecifier::Default(_) => {
                                         "default".to_string()
                                     }
                                     swc_ecma_ast::ExportSpecifier::Namespace(ns) => {
-                                        ns.sym.to_string()
+                                        export_name(&ns.name)
                                     }
                                 };
                                 exports.push(ModuleExport {
@@ -390,15 +435,12 @@ impl ModuleLoader {
                             for spec in &named.specifiers {
                                 match spec {
                                     swc_ecma_ast::ExportSpecifier::Named(named) => {
-                                        let exported = named
-                                            .exported
-                                            .as_ref()
-                                            .map(|e| &e.sym)
-                                            .unwrap_or(&named.orig.sym);
+                                        let exported =
+                                            named.exported.as_ref().unwrap_or(&named.orig);
                                         exports.push(ModuleExport {
-                                            name: exported.to_string(),
+                                            name: export_name(exported),
                                             is_default: false,
-                                            local_name: Some(named.orig.sym.to_string()),
+                                            local_name: Some(export_name(&named.orig)),
                                         });
                                     }
                                     swc_ecma_ast::ExportSpecifier::Default(_) => {
@@ -410,7 +452,7 @@ impl ModuleLoader {
                                     }
                                     swc_ecma_ast::ExportSpecifier::Namespace(ns) => {
                                         exports.push(ModuleExport {
-                                            name: ns.sym.to_string(),
+                                            name: export_name(&ns.name),
                                             is_default: false,
                                             local_name: None,
                                         });
@@ -435,31 +477,29 @@ impl ModuleLoader {
                     }
                     _ => {}
                 }
-            } else if let swc_ecma_ast::ModuleItem::Stmt(stmt) = item {
-                if let swc_ecma_ast::Stmt::Decl(decl) = stmt {
-                    if let swc_ecma_ast::Decl::Var(var) = decl {
-                        for declarator in &var.decls {
-                            if let swc_ecma_ast::Pat::Ident(ident) = &declarator.name {
-                                exports.push(ModuleExport {
-                                    name: ident.id.sym.to_string(),
-                                    is_default: false,
-                                    local_name: Some(ident.id.sym.to_string()),
-                                });
-                            }
+            } else if let swc_ecma_ast::ModuleItem::Stmt(swc_ecma_ast::Stmt::Decl(decl)) = item {
+                if let swc_ecma_ast::Decl::Var(var) = decl {
+                    for declarator in &var.decls {
+                        if let swc_ecma_ast::Pat::Ident(ident) = &declarator.name {
+                            exports.push(ModuleExport {
+                                name: ident.id.sym.to_string(),
+                                is_default: false,
+                                local_name: Some(ident.id.sym.to_string()),
+                            });
                         }
-                    } else if let swc_ecma_ast::Decl::Fn(fn_decl) = decl {
-                        exports.push(ModuleExport {
-                            name: fn_decl.ident.sym.to_string(),
-                            is_default: false,
-                            local_name: Some(fn_decl.ident.sym.to_string()),
-                        });
-                    } else if let swc_ecma_ast::Decl::Class(class) = decl {
-                        exports.push(ModuleExport {
-                            name: class.ident.sym.to_string(),
-                            is_default: false,
-                            local_name: Some(class.ident.sym.to_string()),
-                        });
                     }
+                } else if let swc_ecma_ast::Decl::Fn(fn_decl) = decl {
+                    exports.push(ModuleExport {
+                        name: fn_decl.ident.sym.to_string(),
+                        is_default: false,
+                        local_name: Some(fn_decl.ident.sym.to_string()),
+                    });
+                } else if let swc_ecma_ast::Decl::Class(class) = decl {
+                    exports.push(ModuleExport {
+                        name: class.ident.sym.to_string(),
+                        is_default: false,
+                        local_name: Some(class.ident.sym.to_string()),
+                    });
                 }
             }
         }
@@ -507,8 +547,16 @@ impl ModuleLoader {
     }
 }
 
-#[derive(Debug, Clone)]
+/// Text of an import or export name, which may be a string literal
+fn export_name(name: &ModuleExportName) -> String {
+    let atom = name.atom();
+    let s: &str = &atom;
+    s.to_string()
+}
+
+#[derive(Debug, Clone, Default)]
 pub enum ModuleValue {
+    #[default]
     Undefined,
     Null,
     Boolean(bool),
@@ -519,12 +567,6 @@ pub enum ModuleValue {
     Module(Arc<PathBuf>),
 }
 
-impl Default for ModuleValue {
-    fn default() -> Self {
-        ModuleValue::Undefined
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -532,8 +574,8 @@ mod tests {
     #[tokio::test]
     async fn test_load_module() {
         let mut loader = ModuleLoader::new();
-        let temp_dir = tempfile::tempdir().unwrap();
-        let temp_path = temp_dir.path();
+        let temp_path = &std::env::temp_dir().join(format!("oite_loader_{}", std::process::id()));
+        std::fs::create_dir_all(temp_path).unwrap();
 
         std::fs::write(
             temp_path.join("math.ot"),
@@ -568,5 +610,7 @@ export const output = result;
 
         let module = result.unwrap();
         assert!(!module.dependencies.is_empty());
+
+        std::fs::remove_dir_all(temp_path).ok();
     }
 }
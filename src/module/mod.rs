@@ -1,19 +1,19 @@
+// Module errors carry their dependency chain and suggestion inline
+#![allow(clippy::result_large_err)]
+
 pub mod diagnostics;
 pub mod loader;
 pub mod resolver;
 
-pub use diagnostics::{DependencyInfo, ModuleError, ModuleErrorKind, ModuleResult, SourceLocation};
-pub use loader::{
-    LoadedModule, ModuleCache, ModuleExport, ModuleImport, ModuleLoader, ModuleNamespace,
-    ModuleValue, ParsedModule,
-};
-pub use resolver::{ImportAssertions, ModuleResolver, ResolvedModule};
+pub use diagnostics::{ModuleError, ModuleResult};
+pub use resolver::ModuleResolver;
 
-use std::path::PathBuf;
+use crate::manifest::Manifest;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 pub async fn load_module(entry: &str) -> ModuleResult<Arc<loader::LoadedModule>> {
-    let mut loader = loader::ModuleLoader::new();
+    let mut loader = loader::ModuleLoader::with_manifest(&entry_manifest(entry));
     let entry_path = PathBuf::from(entry);
     loader.load(&entry_path).await
 }
@@ -23,8 +23,70 @@ pub fn load_module_sync(entry: &str) -> ModuleResult<Arc<loader::LoadedModule>>
         .map_err(|e| diagnostics::ModuleError::io_error(PathBuf::from(entry), e.to_string()))?;
 
     runtime.block_on(async {
-        let mut loader = loader::ModuleLoader::new();
+        let mut loader = loader::ModuleLoader::with_manifest(&entry_manifest(entry));
         let entry_path = PathBuf::from(entry);
         loader.load(&entry_path).await
     })
 }
+
+/// Manifest governing the module graph rooted at `entry`
+fn entry_manifest(entry: &str) -> Manifest {
+    Manifest::discover_or_default(Path::new(entry))
+}
+
+/// Resolve every relative import and re-export of `source` without loading
+/// the dependencies. Returns the 1-based line and error of each one that
+/// doesn't resolve. Files that fail to parse report nothing here; the
+/// compiler reports their syntax errors.
+pub fn unresolved_imports(path: &Path, source: &str) -> Vec<(usize, ModuleError)> {
+    use swc_common::{FileName, SourceMap, Spanned, input::StringInput};
+    use swc_ecma_ast::{ModuleDecl, ModuleItem};
+    use swc_ecma_parser::{Parser, Syntax, TsSyntax, lexer::Lexer};
+
+    let cm = SourceMap::default();
+    let fm = cm.new_source_file(
+        FileName::Custom(path.to_string_lossy().into_owned()).into(),
+        source.to_string(),
+    );
+    let syntax = Syntax::Typescript(TsSyntax {
+        decorators: true,
+        ..Default::default()
+    });
+    let lexer = Lexer::new(syntax, Default::default(), StringInput::from(&*fm), None);
+    let Ok(module) = Parser::new_from(lexer).parse_module() else {
+        return Vec::new();
+    };
+
+    let resolver = ModuleResolver::for_manifest(&Manifest::discover_or_default(path));
+    let base = fm.start_pos.0 as usize;
+    let mut errors = Vec::new();
+    for item in &module.body {
+        let ModuleItem::ModuleDecl(decl) = item else {
+            continue;
+        };
+        let (src, with) = match decl {
+            ModuleDecl::Import(import) => (&import.src, &import.with),
+            ModuleDecl::ExportAll(export) => (&export.src, &export.with),
+            ModuleDecl::ExportNamed(export) => match &export.src {
+                Some(src) => (src, &export.with),
+                None => continue,
+            },
+            _ => continue,
+        };
+        let specifier = src.value.to_string_lossy();
+        if !specifier.starts_with('.') {
+            continue;
+        }
+        let unsupported = resolver
+            .parse_import_assertions(with.as_deref())
+            .and_then(|assertions| assertions.unsupported_type().map(str::to_string))
+            .map(|ty| ModuleError::unsupported_assertion(ty, specifier.to_string()));
+        if let Some(e) = unsupported.or_else(|| resolver.resolve(&specifier, path).err()) {
+            let offset = (decl.span().lo.0 as usize)
+                .saturating_sub(base)
+                .min(source.len());
+            errors.push((source[..offset].matches('\n').count() + 1, e));
+        }
+    }
+    errors
+}
@@ -1,9 +1,10 @@
-use std::fs;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
-use crate::module::diagnostics::ModuleErrorKind;
-use crate::module::diagnostics::{ModuleError, ModuleResult};
+use crate::manifest::{DEFAULT_EXTENSIONS, Manifest};
+use crate::module::diagnostics::{ModuleError, ModuleErrorKind, ModuleResult};
+use crate::platform;
+use crate::vm::import_candidates;
 
 #[derive(Debug, Clone)]
 pub struct ResolvedModule {
@@ -29,7 +30,9 @@ impl ResolvedModule {
     }
 
     pub fn as_entry(source: &str) -> Self {
-        let path = Arc::new(fs::canonicalize(source).unwrap_or_else(|_| PathBuf::from(source)));
+        let path = Arc::new(
+            platform::canonicalize(Path::new(source)).unwrap_or_else(|_| PathBuf::from(source)),
+        );
         Self {
             path,
             original_specifier: source.to_string(),
@@ -42,7 +45,7 @@ impl ResolvedModule {
 #[derive(Debug, Clone, PartialEq)]
 pub enum ImportAssertions {
     TypeOnly,
-    JSON,
+    Json,
     Custom(Vec<(String, String)>),
 }
 
@@ -52,12 +55,24 @@ impl ImportAssertions {
     }
 
     pub fn is_json(&self) -> bool {
-        matches!(self, ImportAssertions::JSON)
+        matches!(self, ImportAssertions::Json)
+    }
+
+    /// The `type` of a `with { type: ... }` no loader supports
+    pub fn unsupported_type(&self) -> Option<&str> {
+        match self {
+            ImportAssertions::Custom(pairs) => pairs
+                .iter()
+                .find(|(key, _)| key == "type")
+                .map(|(_, value)| value.as_str()),
+            _ => None,
+        }
     }
 }
 
 pub struct ModuleResolver {
-    extensions: [&'static str; 3],
+    /// Extensions tried in order when a specifier omits one (no leading dot)
+    extensions: Vec<String>,
     base_paths: Vec<PathBuf>,
 }
 
@@ -70,11 +85,24 @@ impl Default for ModuleResolver {
 impl ModuleResolver {
     pub fn new() -> Self {
         Self {
-            extensions: [".ot", ".ts", ".js"],
+            extensions: DEFAULT_EXTENSIONS.iter().map(|e| e.to_string()).collect(),
             base_paths: Vec::new(),
         }
     }
 
+    /// Resolver using the extension priority and module roots of the given
+    /// project manifest
+    pub fn for_manifest(manifest: &Manifest) -> Self {
+        Self {
+            extensions: manifest.extensions.clone(),
+            base_paths: manifest.module_roots(),
+        }
+    }
+
+    pub fn extensions(&self) -> &[String] {
+        &self.extensions
+    }
+
     pub fn with_base_path<P: Into<PathBuf>>(mut self, path: P) -> Self {
         self.base_paths.push(path.into());
         self
@@ -89,10 +117,34 @@ impl ModuleResolver {
 
         match first_char {
             '.' => self.resolve_relative(specifier, importer),
+            _ if !self.base_paths.is_empty() => self.resolve_bare(specifier),
             _ => Err(ModuleError::unsupported_specifier(specifier.to_string())),
         }
     }
 
+    /// A bare specifier (`utils/math`), looked up in each base path in turn
+    fn resolve_bare(&self, specifier: &str) -> ModuleResult<ResolvedModule> {
+        let relative = format!("./{}", specifier);
+        let mut tried_paths = Vec::new();
+        for base in &self.base_paths {
+            match self.resolve_relative(&relative, base) {
+                Ok(mut resolved) => {
+                    resolved.original_specifier = specifier.to_string();
+                    return Ok(resolved);
+                }
+                Err(ModuleError {
+                    kind:
+                        ModuleErrorKind::NotFound {
+                            tried_paths: tried, ..
+                        },
+                    ..
+                }) => tried_paths.extend(tried),
+                Err(e) => return Err(e),
+            }
+        }
+        Err(ModuleError::not_found(specifier.to_string(), tried_paths))
+    }
+
     fn resolve_relative(&self, specifier: &str, importer: &Path) -> ModuleResult<ResolvedModule> {
         let importer_dir = if importer.is_file() {
             importer.parent().unwrap_or(Path::new("."))
@@ -100,45 +152,11 @@ impl ModuleResolver {
             importer
         };
 
-        let mut path = importer_dir.to_path_buf();
         let mut tried_paths = Vec::new();
-
-        for component in specifier.split('/') {
-            match component {
-                "." => {}
-                ".." => {
-                    if !path.as_os_str().is_empty() {
-                        path.pop();
-                    }
-                }
-                "" if specifier.starts_with("./") => {}
-                "" if specifier.starts_with("../") => {}
-                _ => path.push(component),
-            };
-        }
-
-        if path.as_os_str().is_empty() || specifier.ends_with('/') {
-            for ext in self.extensions {
-                let index_path = path.join("index").with_extension(&ext[1..]);
-                tried_paths.push(index_path.display().to_string());
-                if index_path.exists() {
-                    let canonical = fs::canonicalize(&index_path)?;
-                    return Ok(ResolvedModule::new(
-                        canonical,
-                        specifier.to_string(),
-                        false,
-                        None,
-                    ));
-                }
-            }
-            return Err(ModuleError::not_found(specifier.to_string(), tried_paths));
-        }
-
-        for ext in self.extensions {
-            let with_ext = path.with_extension(&ext[1..]);
-            tried_paths.push(with_ext.display().to_string());
-            if with_ext.exists() {
-                let canonical = fs::canonicalize(&with_ext)?;
+        for candidate in import_candidates(importer_dir, specifier, &self.extensions) {
+            if candidate.is_file() {
+                let canonical = platform::canonicalize(&candidate)
+                    .map_err(|e| ModuleError::io_error(candidate.clone(), e.to_string()))?;
                 return Ok(ResolvedModule::new(
                     canonical,
                     specifier.to_string(),
@@ -146,22 +164,7 @@ impl ModuleResolver {
                     None,
                 ));
             }
-        }
-
-        if path.exists() && path.is_dir() {
-            for ext in self.extensions {
-                let index_path = path.join("index").with_extension(&ext[1..]);
-                tried_paths.push(index_path.display().to_string());
-                if index_path.exists() {
-                    let canonical = fs::canonicalize(&index_path)?;
-                    return Ok(ResolvedModule::new(
-                        canonical,
-                        specifier.to_string(),
-                        false,
-                        None,
-                    ));
-                }
-            }
+            tried_paths.push(candidate.display().to_string());
         }
 
         Err(ModuleError::not_found(specifier.to_string(), tried_paths))
@@ -171,33 +174,7 @@ impl ModuleResolver {
         &self,
         with: Option<&swc_ecma_ast::ObjectLit>,
     ) -> Option<ImportAssertions> {
-        if with.is_none() {
-            return None;
-        }
-
-        let with = with.unwrap();
-
-        for prop in &with.props {
-            if let swc_ecma_ast::PropOrSpread::Prop(prop) = prop {
-                if let swc_ecma_ast::Prop::KeyValue(kv) = prop.as_ref() {
-                    if let swc_ecma_ast::PropName::Str(key) = &kv.key {
-                        if key.value == "type" {
-                            if let swc_ecma_ast::Expr::Lit(lit) = kv.value.as_ref() {
-                                if let swc_ecma_ast::Lit::Str(str_lit) = lit {
-                                    match str_lit.value.as_str() {
-                                        "json" => return Some(ImportAssertions::JSON),
-                                        "typescript" | "ts" => {
-                                            return Some(ImportAssertions::TypeOnly);
-                                        }
-                                        _ => {}
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-        }
+        let with = with?;
 
         let custom_assertions: Vec<(String, String)> = with
             .props
@@ -231,6 +208,17 @@ impl ModuleResolver {
             })
             .collect();
 
+        // `type` may be written as an identifier or a string key
+        let ty = custom_assertions
+            .iter()
+            .find(|(key, _)| key == "type")
+            .map(|(_, value)| value.as_str());
+        match ty {
+            Some("json") => return Some(ImportAssertions::Json),
+            Some("typescript" | "ts") => return Some(ImportAssertions::TypeOnly),
+            _ => {}
+        }
+
         if custom_assertions.is_empty() {
             None
         } else {
@@ -242,38 +230,66 @@ impl ModuleResolver {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::fs;
+
+    /// A project directory holding the files the relative tests import
+    fn project(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("oite_resolver_{}_{}", name, std::process::id()));
+        fs::create_dir_all(dir.join("src/utils")).unwrap();
+        fs::create_dir_all(dir.join("src/lib")).unwrap();
+        for file in [
+            "src/main.ot",
+            "src/utils.ot",
+            "src/utils/helper.ot",
+            "src/lib/math.ot",
+            "src/foo.js",
+        ] {
+            fs::write(dir.join(file), "").unwrap();
+        }
+        dir
+    }
 
     #[test]
     fn test_resolve_relative_file() {
         let resolver = ModuleResolver::new();
-        let importer = PathBuf::from("/project/src/main.ot");
+        let dir = project("relative");
+        let importer = dir.join("src/main.ot");
 
         let result = resolver.resolve("./utils", &importer);
         assert!(result.is_ok());
 
         let resolved = result.unwrap();
         assert_eq!(resolved.original_specifier, "./utils");
+
+        fs::remove_dir_all(&dir).ok();
     }
 
     #[test]
     fn test_resolve_parent_directory() {
         let resolver = ModuleResolver::new();
-        let importer = PathBuf::from("/project/src/utils/helper.ot");
+        let dir = project("parent");
+        let importer = dir.join("src/utils/helper.ot");
 
         let result = resolver.resolve("../lib/math", &importer);
         assert!(result.is_ok());
 
         let resolved = result.unwrap();
         assert_eq!(resolved.original_specifier, "../lib/math");
+
+        fs::remove_dir_all(&dir).ok();
     }
 
     #[test]
     fn test_resolve_with_extension() {
         let resolver = ModuleResolver::new();
-        let importer = PathBuf::from("/project/src/main.ot");
+        let dir = project("extension");
+        let importer = dir.join("src/main.ot");
 
         let result = resolver.resolve("./foo.js", &importer);
         assert!(result.is_ok());
+
+        fs::remove_dir_all(&dir).ok();
     }
 
     #[test]
@@ -291,4 +307,73 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_manifest_extension_priority() {
+        let dir = std::env::temp_dir().join(format!("oite_resolver_ext_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("util.ot"), "").unwrap();
+        fs::write(dir.join("util.tscl"), "").unwrap();
+        let importer = dir.join("main.ot");
+        fs::write(&importer, "").unwrap();
+
+        let default = ModuleResolver::new().resolve("./util", &importer).unwrap();
+        assert_eq!(default.path.extension().unwrap(), "ot");
+
+        let manifest = Manifest::parse("[modules]\nextensions = [\"tscl\"]\n").unwrap();
+        let custom = ModuleResolver::for_manifest(&manifest)
+            .resolve("./util", &importer)
+            .unwrap();
+        assert_eq!(custom.path.extension().unwrap(), "tscl");
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_bare_specifiers_resolve_in_module_roots() {
+        let dir = std::env::temp_dir().join(format!("oite_resolver_roots_{}", std::process::id()));
+        fs::create_dir_all(dir.join("lib/utils")).unwrap();
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(dir.join("lib/utils/math.ot"), "").unwrap();
+        fs::write(
+            dir.join("script.toml"),
+            "[modules]\nroots = [\"src\", \"lib\"]\n",
+        )
+        .unwrap();
+        let importer = dir.join("src/main.ot");
+        fs::write(&importer, "").unwrap();
+
+        let manifest = Manifest::discover(&importer).unwrap();
+        let resolver = ModuleResolver::for_manifest(&manifest);
+        let resolved = resolver.resolve("utils/math", &importer).unwrap();
+        assert!(resolved.path.ends_with("lib/utils/math.ot"));
+        assert_eq!(resolved.original_specifier, "utils/math");
+        match resolver
+            .resolve("utils/missing", &importer)
+            .unwrap_err()
+            .kind
+        {
+            ModuleErrorKind::NotFound { tried_paths, .. } => assert!(tried_paths.len() > 2),
+            kind => panic!("expected NotFound, got {:?}", kind),
+        }
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_json_imports_resolve_to_the_named_file() {
+        let dir = std::env::temp_dir().join(format!("oite_resolver_json_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("config.json"), "{}").unwrap();
+        fs::write(dir.join("config.ot"), "").unwrap();
+        let importer = dir.join("main.ot");
+        fs::write(&importer, "").unwrap();
+
+        let resolved = ModuleResolver::new()
+            .resolve("./config.json", &importer)
+            .unwrap();
+        assert_eq!(resolved.path.extension().unwrap(), "json");
+
+        fs::remove_dir_all(&dir).ok();
+    }
 }
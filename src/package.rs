@@ -0,0 +1,758 @@
+//! Packages for `oitec add` and `oitec install`
+//!
+//! `script.toml` lists a project's dependencies (see manifest.rs): registry
+//! versions, downloaded as `<registry>/<name>/-/<name>-<version>.tgz`, and git
+//! repositories. `install` places each one, and whatever the packages' own
+//! manifests depend on, in a flat `script_modules/` next to the manifest,
+//! where the module resolver finds bare specifiers (`import "colors"` loads
+//! `script_modules/colors/index.ot`).
+//!
+//! `script.lock` records what was fetched for each package (the tarball URL
+//! or git commit) and a checksum of its files. Later installs fetch exactly
+//! that and refuse files that don't match, so every checkout of a project
+//! gets the same dependencies until `script.toml` changes.
+
+use std::collections::VecDeque;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use sha2::{Digest, Sha256};
+
+use crate::manifest::{DependencySource, MANIFEST_FILE, MODULES_DIR, Manifest, is_package_name};
+
+/// Lockfile name, next to `script.toml`
+pub const LOCK_FILE: &str = "script.lock";
+
+/// Format version written to and expected in `script.lock`
+const LOCK_VERSION: i64 = 1;
+
+/// One installed package, as `script.lock` records it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LockedPackage {
+    pub name: String,
+    /// The `[dependencies]` entry it was installed for
+    pub source: DependencySource,
+    /// What was fetched: the tarball URL, or the git commit
+    pub resolved: String,
+    /// `sha256:` digest of the installed files (see `tree_checksum`)
+    pub checksum: String,
+}
+
+/// The contents of `script.lock`, sorted by package name
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Lockfile {
+    pub packages: Vec<LockedPackage>,
+}
+
+impl Lockfile {
+    /// Read `path`; a missing lockfile is an empty one
+    pub fn load(path: &Path) -> Result<Self, String> {
+        match fs::read_to_string(path) {
+            Ok(source) => Self::parse(&source).map_err(|e| format!("{}: {}", path.display(), e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(format!("Failed to read {}: {}", path.display(), e)),
+        }
+    }
+
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let table: toml::Table = source.parse().map_err(|e: toml::de::Error| e.to_string())?;
+        match table.get("version").and_then(toml::Value::as_integer) {
+            Some(LOCK_VERSION) => {}
+            _ => {
+                return Err(format!(
+                    "unsupported lockfile version (expected {})",
+                    LOCK_VERSION
+                ));
+            }
+        }
+        let mut lockfile = Self::default();
+        let Some(packages) = table.get("package") else {
+            return Ok(lockfile);
+        };
+        let packages = packages
+            .as_array()
+            .ok_or("`package` must be an array of tables")?;
+        for package in packages {
+            let field = |key: &str| {
+                package
+                    .get(key)
+                    .and_then(toml::Value::as_str)
+                    .map(str::to_string)
+                    .ok_or_else(|| format!("every package needs a `{}` string", key))
+            };
+            let name = field("name")?;
+            let source = package
+                .get("source")
+                .ok_or_else(|| format!("package {} has no `source`", name))?;
+            lockfile.packages.push(LockedPackage {
+                source: DependencySource::parse(source, &name)?,
+                resolved: field("resolved")?,
+                checksum: field("checksum")?,
+                name,
+            });
+        }
+        Ok(lockfile)
+    }
+
+    pub fn to_toml(&self) -> String {
+        let quote = |s: &str| toml::Value::String(s.to_string()).to_string();
+        let mut out = format!(
+            "# Written by `oitec install`; do not edit by hand\nversion = {}\n",
+            LOCK_VERSION
+        );
+        for package in &self.packages {
+            out.push_str(&format!(
+                "\n[[package]]\nname = {}\nsource = {}\nresolved = {}\nchecksum = {}\n",
+                quote(&package.name),
+                package.source.to_toml(),
+                quote(&package.resolved),
+                quote(&package.checksum)
+            ));
+        }
+        out
+    }
+
+    /// The entry for `name` if it was installed for `source`
+    fn find(&self, name: &str, source: &DependencySource) -> Option<&LockedPackage> {
+        self.packages
+            .iter()
+            .find(|p| p.name == name && &p.source == source)
+    }
+}
+
+/// What `install` did
+#[derive(Debug, Default)]
+pub struct InstallReport {
+    /// Packages downloaded this time
+    pub fetched: Vec<String>,
+    /// Packages already in `script_modules` as locked
+    pub unchanged: Vec<String>,
+    /// `script_modules` entries no dependency needs any more
+    pub removed: Vec<String>,
+}
+
+/// Install the dependencies of the project `manifest` belongs to, and
+/// theirs, into its `script_modules` and update `script.lock`. With
+/// `locked`, fail instead of changing the lockfile.
+pub fn install(manifest: &Manifest, locked: bool) -> Result<InstallReport, String> {
+    let manifest_path = manifest
+        .path
+        .as_deref()
+        .ok_or_else(|| format!("no {} found", MANIFEST_FILE))?;
+    let root = manifest_path.parent().unwrap_or(Path::new("."));
+    let lock_path = root.join(LOCK_FILE);
+    let previous = Lockfile::load(&lock_path)?;
+    if locked && !lock_path.is_file() {
+        return Err(format!("--locked needs a {}", LOCK_FILE));
+    }
+    let modules = root.join(MODULES_DIR);
+    fs::create_dir_all(&modules)
+        .map_err(|e| format!("Failed to create {}: {}", modules.display(), e))?;
+
+    let mut report = InstallReport::default();
+    let mut lockfile = Lockfile::default();
+    // (name, source, who asked for it)
+    let mut queue: VecDeque<(String, DependencySource, String)> =
+        with_registry(&manifest.dependencies, manifest.registry.as_deref())
+            .map(|(name, source)| (name, source, MANIFEST_FILE.to_string()))
+            .collect();
+    let mut required_by: Vec<(String, String)> = Vec::new();
+
+    while let Some((name, source, parent)) = queue.pop_front() {
+        if let Some(installed) = lockfile.packages.iter().find(|p| p.name == name) {
+            if installed.source != source {
+                let first = &required_by.iter().find(|(n, _)| *n == name).unwrap().1;
+                return Err(format!(
+                    "{} is required as {} by {} but as {} by {}",
+                    name,
+                    installed.source.to_toml(),
+                    first,
+                    source.to_toml(),
+                    parent
+                ));
+            }
+            continue;
+        }
+
+        let pinned = previous.find(&name, &source);
+        if locked && pinned.is_none() {
+            return Err(format!(
+                "{} {} is not in {}; run `oitec install` without --locked",
+                name,
+                source.to_toml(),
+                LOCK_FILE
+            ));
+        }
+        let dest = modules.join(&name);
+        let package = match pinned {
+            Some(pinned) if dest.is_dir() && tree_checksum(&dest)? == pinned.checksum => {
+                report.unchanged.push(name.clone());
+                pinned.clone()
+            }
+            _ => {
+                let package = fetch_package(&name, &source, pinned, &modules)?;
+                report.fetched.push(name.clone());
+                package
+            }
+        };
+
+        let dependency_manifest = dest.join(MANIFEST_FILE);
+        if dependency_manifest.is_file() {
+            let dependency = Manifest::load(&dependency_manifest)?;
+            let registry = dependency
+                .registry
+                .as_deref()
+                .or(manifest.registry.as_deref());
+            for (dep_name, dep_source) in with_registry(&dependency.dependencies, registry) {
+                queue.push_back((dep_name, dep_source, name.clone()));
+            }
+        }
+        required_by.push((name, parent));
+        lockfile.packages.push(package);
+    }
+    lockfile.packages.sort_by(|a, b| a.name.cmp(&b.name));
+
+    if locked && lockfile != previous {
+        return Err(format!(
+            "{} is out of date with {}; run `oitec install` without --locked",
+            LOCK_FILE, MANIFEST_FILE
+        ));
+    }
+
+    // Anything else in script_modules is left from removed dependencies or
+    // an interrupted install
+    let entries = fs::read_dir(&modules)
+        .map_err(|e| format!("Failed to read {}: {}", modules.display(), e))?;
+    for entry in entries.flatten() {
+        let file_name = entry.file_name().to_string_lossy().into_owned();
+        if lockfile.packages.iter().any(|p| p.name == file_name) {
+            continue;
+        }
+        let path = entry.path();
+        let removed = if path.is_dir() {
+            fs::remove_dir_all(&path)
+        } else {
+            fs::remove_file(&path)
+        };
+        removed.map_err(|e| format!("Failed to remove {}: {}", path.display(), e))?;
+        report.removed.push(file_name);
+    }
+
+    if lockfile != previous || !lock_path.is_file() {
+        fs::write(&lock_path, lockfile.to_toml())
+            .map_err(|e| format!("Failed to write {}: {}", lock_path.display(), e))?;
+    }
+    Ok(report)
+}
+
+/// `dependencies` with `registry` filled in where an entry names none
+fn with_registry<'a>(
+    dependencies: &'a [(String, DependencySource)],
+    registry: Option<&'a str>,
+) -> impl Iterator<Item = (String, DependencySource)> + 'a {
+    dependencies.iter().map(move |(name, source)| {
+        let source = match source {
+            DependencySource::Registry {
+                version,
+                registry: None,
+            } => DependencySource::Registry {
+                version: version.clone(),
+                registry: registry.map(str::to_string),
+            },
+            _ => source.clone(),
+        };
+        (name.clone(), source)
+    })
+}
+
+/// Download `name` into `modules`, replacing what is there. A `pinned`
+/// package is fetched exactly as locked and must match its checksum.
+fn fetch_package(
+    name: &str,
+    source: &DependencySource,
+    pinned: Option<&LockedPackage>,
+    modules: &Path,
+) -> Result<LockedPackage, String> {
+    // Fetched next to the destination, and only moved there once complete
+    let staging = modules.join(format!(".{}.partial", name));
+    if staging.exists() {
+        fs::remove_dir_all(&staging)
+            .map_err(|e| format!("Failed to remove {}: {}", staging.display(), e))?;
+    }
+    let resolved = pinned.map(|p| p.resolved.as_str());
+    let fetched = match source {
+        DependencySource::Registry { version, registry } => {
+            let url = match (resolved, registry) {
+                (Some(url), _) => url.to_string(),
+                (None, Some(registry)) => tarball_url(registry, name, version),
+                (None, None) => {
+                    return Err(format!(
+                        "{} {} needs a registry: set [registry] url in {}",
+                        name, version, MANIFEST_FILE
+                    ));
+                }
+            };
+            download(&url).and_then(|bytes| unpack_tarball(&bytes, &staging).map(|_| url))
+        }
+        DependencySource::Git { url, rev } => {
+            git_checkout(url, resolved.or(rev.as_deref()), &staging)
+        }
+    };
+    let result = fetched.and_then(|resolved| {
+        let checksum = tree_checksum(&staging)?;
+        if let Some(pinned) = pinned
+            && pinned.checksum != checksum
+        {
+            return Err(format!(
+                "checksum mismatch for {}: {} has {}, the download has {}",
+                name, LOCK_FILE, pinned.checksum, checksum
+            ));
+        }
+        Ok(LockedPackage {
+            name: name.to_string(),
+            source: source.clone(),
+            resolved,
+            checksum,
+        })
+    });
+    let package = match result {
+        Ok(package) => package,
+        Err(e) => {
+            fs::remove_dir_all(&staging).ok();
+            return Err(format!("Failed to install {}: {}", name, e));
+        }
+    };
+
+    let dest = modules.join(name);
+    if dest.exists() {
+        fs::remove_dir_all(&dest)
+            .map_err(|e| format!("Failed to remove {}: {}", dest.display(), e))?;
+    }
+    fs::rename(&staging, &dest)
+        .map_err(|e| format!("Failed to move {} into place: {}", name, e))?;
+    Ok(package)
+}
+
+/// Where a registry keeps the tarball of `name` at `version`, which
+/// `DependencySource::check` has limited to version characters
+pub fn tarball_url(registry: &str, name: &str, version: &str) -> String {
+    format!(
+        "{}/{}/-/{}-{}.tgz",
+        registry.trim_end_matches('/'),
+        name,
+        name,
+        version
+    )
+}
+
+/// The body of `url`: http(s), or a `file://` URL for local registries
+fn download(url: &str) -> Result<Vec<u8>, String> {
+    if let Some(path) = url.strip_prefix("file://") {
+        return fs::read(path).map_err(|e| format!("{}: {}", url, e));
+    }
+    if !url.starts_with("https://") && !url.starts_with("http://") {
+        return Err(format!("unsupported URL {}", url));
+    }
+    let response = ureq::get(url)
+        .call()
+        .map_err(|e| format!("{}: {}", url, e))?;
+    let mut bytes = Vec::new();
+    response
+        .into_reader()
+        .read_to_end(&mut bytes)
+        .map_err(|e| format!("{}: {}", url, e))?;
+    Ok(bytes)
+}
+
+/// Unpack a gzipped tarball into `dest`. Registries wrap a package in one
+/// top-level directory (`package/`), which is dropped.
+fn unpack_tarball(bytes: &[u8], dest: &Path) -> Result<(), String> {
+    let unpacked = dest.with_extension("unpack");
+    if unpacked.exists() {
+        fs::remove_dir_all(&unpacked).map_err(|e| e.to_string())?;
+    }
+    let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(bytes));
+    archive
+        .unpack(&unpacked)
+        .map_err(|e| format!("bad tarball: {}", e))?;
+
+    let entries: Vec<_> = fs::read_dir(&unpacked)
+        .map_err(|e| e.to_string())?
+        .flatten()
+        .map(|entry| entry.path())
+        .collect();
+    let moved = match entries.as_slice() {
+        [only] if only.is_dir() => fs::rename(only, dest).and_then(|_| fs::remove_dir(&unpacked)),
+        _ => fs::rename(&unpacked, dest),
+    };
+    moved.map_err(|e| e.to_string())
+}
+
+/// Clone `url` into `dest` at `rev` (the default branch when `None`),
+/// without its history. Returns the commit checked out.
+fn git_checkout(url: &str, rev: Option<&str>, dest: &Path) -> Result<String, String> {
+    let git = |dir: Option<&Path>, args: &[&str]| -> Result<String, String> {
+        let mut command = Command::new("git");
+        if let Some(dir) = dir {
+            command.arg("-C").arg(dir);
+        }
+        let output = command
+            .args(args)
+            .output()
+            .map_err(|e| format!("git is needed for git dependencies: {}", e))?;
+        if !output.status.success() {
+            return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    };
+
+    // The manifest checks both, but `rev` may also come from the lockfile
+    if url.starts_with('-') || rev.is_some_and(|rev| rev.starts_with('-')) {
+        return Err(format!("refusing git arguments {} {:?}", url, rev));
+    }
+    let dest_arg = dest.to_string_lossy();
+    git(None, &["clone", "--quiet", "--", url, &dest_arg])?;
+    if let Some(rev) = rev {
+        // A tag or commit checks out as itself, a branch as the remote's.
+        // The trailing `--` makes git read `rev` as a revision, never a path.
+        git(Some(dest), &["checkout", "--quiet", "--detach", rev, "--"])
+            .or_else(|_| {
+                let remote = format!("origin/{}", rev);
+                git(
+                    Some(dest),
+                    &["checkout", "--quiet", "--detach", &remote, "--"],
+                )
+            })
+            .map_err(|e| format!("no revision {} in {}: {}", rev, url, e))?;
+    }
+    let commit = git(Some(dest), &["rev-parse", "HEAD"])?;
+    fs::remove_dir_all(dest.join(".git")).map_err(|e| e.to_string())?;
+    Ok(commit)
+}
+
+/// `sha256:` digest of every file under `dir` (but `.git`): their paths,
+/// in order, each followed by its length and contents
+pub fn tree_checksum(dir: &Path) -> Result<String, String> {
+    fn collect(dir: &Path, prefix: &str, files: &mut Vec<(String, PathBuf)>) -> Result<(), String> {
+        let entries =
+            fs::read_dir(dir).map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name == ".git" {
+                continue;
+            }
+            let relative = format!("{}{}", prefix, name);
+            if entry.file_type().is_ok_and(|t| t.is_dir()) {
+                collect(&entry.path(), &format!("{}/", relative), files)?;
+            } else {
+                files.push((relative, entry.path()));
+            }
+        }
+        Ok(())
+    }
+
+    let mut files = Vec::new();
+    collect(dir, "", &mut files)?;
+    files.sort();
+    let mut hasher = Sha256::new();
+    for (relative, path) in files {
+        // A symlink counts as its target's path, not the file it names
+        let contents = match fs::read_link(&path) {
+            Ok(target) => target.to_string_lossy().into_owned().into_bytes(),
+            Err(_) => {
+                fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?
+            }
+        };
+        hasher.update(relative.as_bytes());
+        hasher.update([0]);
+        hasher.update((contents.len() as u64).to_le_bytes());
+        hasher.update(&contents);
+    }
+    Ok(format!("sha256:{}", hex::encode(hasher.finalize())))
+}
+
+/// Parse `oitec add` arguments: `<name>@<version>`, or `<name>` with
+/// `git` and optionally `rev`
+pub fn parse_package_spec(
+    spec: &str,
+    git: Option<&str>,
+    rev: Option<&str>,
+) -> Result<(String, DependencySource), String> {
+    let (name, source) = match (spec.split_once('@'), git) {
+        (Some((name, version)), None) if !version.is_empty() => (
+            name,
+            DependencySource::Registry {
+                version: version.to_string(),
+                registry: None,
+            },
+        ),
+        (None, Some(url)) => (
+            spec,
+            DependencySource::Git {
+                url: url.to_string(),
+                rev: rev.map(str::to_string),
+            },
+        ),
+        (Some(_), Some(_)) => return Err("a git dependency takes no @version".to_string()),
+        _ => {
+            return Err(format!(
+                "expected <name>@<version> or --git, got '{}'",
+                spec
+            ));
+        }
+    };
+    if !is_package_name(name) {
+        return Err(format!("invalid package name '{}'", name));
+    }
+    source.check(name)?;
+    Ok((name.to_string(), source))
+}
+
+/// Set `name` to `source` under `[dependencies]` in the manifest at `path`,
+/// creating either if needed. The rest of the file is kept as written.
+pub fn add_dependency(path: &Path, name: &str, source: &DependencySource) -> Result<(), String> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+    };
+    let updated = with_dependency(&text, name, source);
+    Manifest::parse(&updated).map_err(|e| format!("{}: {}", path.display(), e))?;
+    fs::write(path, updated).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// `manifest` with the `[dependencies]` line for `name` replaced or added
+fn with_dependency(manifest: &str, name: &str, source: &DependencySource) -> String {
+    let line = format!("{} = {}", name, source.to_toml());
+    let mut lines: Vec<String> = manifest.lines().map(str::to_string).collect();
+    match lines.iter().position(|l| l.trim() == "[dependencies]") {
+        Some(header) => {
+            let start = header + 1;
+            let end = lines[start..]
+                .iter()
+                .position(|l| l.trim_start().starts_with('['))
+                .map_or(lines.len(), |i| start + i);
+            let key = |l: &str| {
+                l.split_once('=')
+                    .map(|(key, _)| key.trim().trim_matches('"').to_string())
+            };
+            match lines[start..end]
+                .iter()
+                .position(|l| key(l).as_deref() == Some(name))
+            {
+                Some(i) => lines[start + i] = line,
+                None => {
+                    // After the section's last entry, before blank lines
+                    let mut at = end;
+                    while at > start && lines[at - 1].trim().is_empty() {
+                        at -= 1;
+                    }
+                    lines.insert(at, line);
+                }
+            }
+        }
+        None => {
+            if lines.last().is_some_and(|l| !l.trim().is_empty()) {
+                lines.push(String::new());
+            }
+            lines.push("[dependencies]".to_string());
+            lines.push(line);
+        }
+    }
+    lines.join("\n") + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registry(version: &str) -> DependencySource {
+        DependencySource::Registry {
+            version: version.to_string(),
+            registry: None,
+        }
+    }
+
+    #[test]
+    fn test_lockfile_round_trip() {
+        let lockfile = Lockfile {
+            packages: vec![
+                LockedPackage {
+                    name: "colors".to_string(),
+                    source: registry("1.2.0"),
+                    resolved: "https://registry.example.com/colors/-/colors-1.2.0.tgz".to_string(),
+                    checksum: "sha256:00".to_string(),
+                },
+                LockedPackage {
+                    name: "router".to_string(),
+                    source: DependencySource::Git {
+                        url: "https://example.com/router.git".to_string(),
+                        rev: Some("v0.3.0".to_string()),
+                    },
+                    resolved: "0123abcd".to_string(),
+                    checksum: "sha256:11".to_string(),
+                },
+            ],
+        };
+        assert_eq!(Lockfile::parse(&lockfile.to_toml()).unwrap(), lockfile);
+        assert!(Lockfile::parse("version = 2\n").is_err());
+    }
+
+    #[test]
+    fn test_package_specs() {
+        assert_eq!(
+            parse_package_spec("colors@1.2.0", None, None).unwrap(),
+            ("colors".to_string(), registry("1.2.0"))
+        );
+        let (name, source) =
+            parse_package_spec("router", Some("https://example.com/r.git"), None).unwrap();
+        assert_eq!(name, "router");
+        assert!(matches!(source, DependencySource::Git { rev: None, .. }));
+        assert!(parse_package_spec("colors", None, None).is_err());
+        assert!(parse_package_spec("../x@1.0.0", None, None).is_err());
+        assert!(parse_package_spec("x@-1", None, None).is_err());
+        assert!(parse_package_spec("x", Some("-uevil"), None).is_err());
+        assert!(parse_package_spec("x", Some("https://example.com/x.git"), Some("--x")).is_err());
+    }
+
+    #[test]
+    fn test_with_dependency_keeps_the_rest_of_the_file() {
+        let manifest = "# app\n[dependencies]\ncolors = \"1.0.0\"\n\n[env]\nA = \"1\"\n";
+        let updated = with_dependency(manifest, "router", &registry("2.0.0"));
+        assert_eq!(
+            updated,
+            "# app\n[dependencies]\ncolors = \"1.0.0\"\nrouter = \"2.0.0\"\n\n[env]\nA = \"1\"\n"
+        );
+        let replaced = with_dependency(&updated, "colors", &registry("1.1.0"));
+        assert!(replaced.contains("colors = \"1.1.0\"\n"));
+        assert!(!replaced.contains("1.0.0"));
+        assert_eq!(
+            with_dependency("[env]\nA = \"1\"\n", "colors", &registry("1.0.0")),
+            "[env]\nA = \"1\"\n\n[dependencies]\ncolors = \"1.0.0\"\n"
+        );
+    }
+
+    /// Write a registry tarball holding `index.ot`, wrapped in `package/`
+    /// as registries do
+    fn write_tarball(path: &Path, index: &[u8]) {
+        let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(
+            fs::File::create(path).unwrap(),
+            flate2::Compression::default(),
+        ));
+        let mut header = tar::Header::new_gnu();
+        header.set_size(index.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "package/index.ot", index)
+            .unwrap();
+        builder.into_inner().unwrap().finish().unwrap();
+    }
+
+    #[test]
+    fn test_install_from_a_local_registry() {
+        let dir = std::env::temp_dir().join(format!("oite_package_{}", std::process::id()));
+        let registry_dir = dir.join("registry/colors/-");
+        fs::create_dir_all(&registry_dir).unwrap();
+        let project = dir.join("app");
+        fs::create_dir_all(&project).unwrap();
+
+        let tarball = registry_dir.join("colors-1.2.0.tgz");
+        let index = b"export const red = \"#f00\";\n";
+        write_tarball(&tarball, index);
+
+        let manifest_path = project.join(MANIFEST_FILE);
+        fs::write(
+            &manifest_path,
+            format!(
+                "[registry]\nurl = \"file://{}\"\n",
+                dir.join("registry").display()
+            ),
+        )
+        .unwrap();
+        add_dependency(&manifest_path, "colors", &registry("1.2.0")).unwrap();
+        let manifest = Manifest::load(&manifest_path).unwrap();
+
+        let report = install(&manifest, false).unwrap();
+        assert_eq!(report.fetched, ["colors"]);
+        let installed = project.join(MODULES_DIR).join("colors/index.ot");
+        assert_eq!(fs::read(&installed).unwrap(), index);
+        let lockfile = Lockfile::load(&project.join(LOCK_FILE)).unwrap();
+        assert_eq!(lockfile.packages.len(), 1);
+        assert!(lockfile.packages[0].resolved.ends_with("colors-1.2.0.tgz"));
+
+        // The resolver finds installed packages by name
+        let resolved = crate::module::ModuleResolver::for_manifest(&manifest)
+            .resolve("colors", &manifest_path)
+            .unwrap();
+        assert!(resolved.path.ends_with("colors/index.ot"));
+
+        // Untouched packages aren't fetched again; changed ones no longer
+        // match the lockfile and are replaced
+        let report = install(&manifest, true).unwrap();
+        assert_eq!(report.unchanged, ["colors"]);
+        fs::write(&installed, "tampered").unwrap();
+        let report = install(&manifest, true).unwrap();
+        assert_eq!(report.fetched, ["colors"]);
+        assert_eq!(fs::read(&installed).unwrap(), index);
+
+        // A download that doesn't match the lockfile is refused
+        write_tarball(&tarball, b"export const red = \"#e00\";\n");
+        fs::remove_dir_all(project.join(MODULES_DIR)).unwrap();
+        let error = install(&manifest, false).unwrap_err();
+        assert!(error.contains("checksum mismatch"), "{}", error);
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_git_checkout_of_a_tag_and_a_branch() {
+        let dir = std::env::temp_dir().join(format!("oite_git_{}", std::process::id()));
+        let repo = dir.join("repo");
+        fs::create_dir_all(&repo).unwrap();
+        let git = |args: &[&str]| {
+            let status = Command::new("git")
+                .arg("-C")
+                .arg(&repo)
+                .args(["-c", "user.name=t", "-c", "user.email=t@t"])
+                .args(args)
+                .output()
+                .unwrap()
+                .status;
+            assert!(status.success(), "git {:?}", args);
+        };
+        git(&["init", "--quiet", "--initial-branch=main"]);
+        fs::write(repo.join("index.ot"), "export const v = 1;\n").unwrap();
+        git(&["add", "."]);
+        git(&["commit", "--quiet", "-m", "one"]);
+        git(&["tag", "v1"]);
+        git(&["checkout", "--quiet", "-b", "next"]);
+        fs::write(repo.join("index.ot"), "export const v = 2;\n").unwrap();
+        git(&["commit", "--quiet", "-am", "two"]);
+        let url = repo.to_string_lossy();
+
+        let tag = dir.join("tag");
+        git_checkout(&url, Some("v1"), &tag).unwrap();
+        assert_eq!(
+            fs::read_to_string(tag.join("index.ot")).unwrap(),
+            "export const v = 1;\n"
+        );
+        assert!(!tag.join(".git").exists());
+        let branch = dir.join("branch");
+        git_checkout(&url, Some("next"), &branch).unwrap();
+        assert_eq!(
+            fs::read_to_string(branch.join("index.ot")).unwrap(),
+            "export const v = 2;\n"
+        );
+
+        // Values that git would read as options are refused, even from a
+        // lockfile that skipped the manifest's checks
+        assert!(git_checkout("--upload-pack=touch", None, &dir.join("a")).is_err());
+        assert!(git_checkout(&url, Some("--orphan=x"), &dir.join("b")).is_err());
+        assert!(!dir.join("a").exists() && !dir.join("b").exists());
+
+        fs::remove_dir_all(&dir).ok();
+    }
+}
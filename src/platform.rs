@@ -0,0 +1,268 @@
+//! Host platform differences
+//!
+//! Native artifact naming and path canonicalization differ between Unix and
+//! Windows; everything in the toolchain that names a file on disk goes
+//! through here rather than hard-coding the Unix conventions.
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub use std::env::consts::{DLL_EXTENSION, EXE_EXTENSION};
+
+/// Extension of relocatable object files (`.o` / `.obj`)
+pub const OBJECT_EXTENSION: &str = if cfg!(windows) { "obj" } else { "o" };
+
+/// Extension of static libraries (`.a` / `.lib`)
+pub const STATIC_LIB_EXTENSION: &str = if cfg!(windows) { "lib" } else { "a" };
+
+/// Value of `process.platform`, using Node's names
+pub const PROCESS_PLATFORM: &str = if cfg!(windows) {
+    "win32"
+} else if cfg!(target_os = "macos") {
+    "darwin"
+} else {
+    std::env::consts::OS
+};
+
+/// Per-user cache directory: `$XDG_CACHE_HOME`, else `%LOCALAPPDATA%` on
+/// Windows, `~/Library/Caches` on macOS and `~/.cache` elsewhere. Relative
+/// values are ignored, as the XDG spec asks.
+pub fn user_cache_dir() -> Option<PathBuf> {
+    let var = |name| {
+        std::env::var_os(name)
+            .map(PathBuf::from)
+            .filter(|path| path.is_absolute())
+    };
+    if let Some(dir) = var("XDG_CACHE_HOME") {
+        return Some(dir);
+    }
+    if cfg!(windows) {
+        return var("LOCALAPPDATA");
+    }
+    let home = var("HOME")?;
+    Some(if cfg!(target_os = "macos") {
+        home.join("Library").join("Caches")
+    } else {
+        home.join(".cache")
+    })
+}
+
+/// Paths longer than this need the verbatim prefix to be opened on Windows
+const WINDOWS_MAX_PATH: usize = 260;
+
+/// `fs::canonicalize`, minus the `\\?\` prefix Windows adds to every result.
+///
+/// Verbatim paths confuse linkers, editors and users reading diagnostics, so
+/// the prefix is dropped whenever the path is still valid without it.
+pub fn canonicalize(path: &Path) -> io::Result<PathBuf> {
+    let canonical = std::fs::canonicalize(path)?;
+    if cfg!(windows) {
+        Ok(strip_verbatim(canonical))
+    } else {
+        Ok(canonical)
+    }
+}
+
+/// `\\?\C:\dir` becomes `C:\dir` and `\\?\UNC\server\share` becomes
+/// `\\server\share`; anything else is returned unchanged.
+pub fn strip_verbatim(path: PathBuf) -> PathBuf {
+    let Some(text) = path.to_str() else {
+        return path;
+    };
+    let stripped = if let Some(rest) = text.strip_prefix(r"\\?\UNC\") {
+        format!(r"\\{}", rest)
+    } else if let Some(rest) = text.strip_prefix(r"\\?\")
+        && is_drive_path(rest)
+    {
+        rest.to_string()
+    } else {
+        return path;
+    };
+    // Components like `.` or trailing dots and spaces are only legal verbatim
+    let literal = stripped
+        .split('\\')
+        .skip(1)
+        .all(|c| c != "." && c != ".." && !c.ends_with(['.', ' ']));
+    if literal && stripped.len() < WINDOWS_MAX_PATH {
+        PathBuf::from(stripped)
+    } else {
+        path
+    }
+}
+
+fn is_drive_path(path: &str) -> bool {
+    let bytes = path.as_bytes();
+    bytes.len() >= 3 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' && bytes[2] == b'\\'
+}
+
+/// Characters other than letters and digits left as they are in a URL path
+const URL_PATH_CHARS: &[u8] = b"-._~/:@!$&'()*+,;=";
+
+/// `file://` URL of an absolute path, percent-encoding anything outside the
+/// characters a URL path allows. Windows paths use forward slashes, with
+/// `C:\dir` becoming `file:///C:/dir` and `\\server\share` becoming
+/// `file://server/share`.
+pub fn file_url(path: &Path) -> String {
+    let text = path.to_string_lossy();
+    let (host, path) = match text.strip_prefix(r"\\") {
+        Some(unc) if cfg!(windows) => unc.split_once('\\').unwrap_or((unc, "")),
+        _ => ("", text.as_ref()),
+    };
+    let mut url = format!("file://{}", host);
+    if !path.starts_with(['/', '\\']) {
+        url.push('/');
+    }
+    for byte in path.bytes() {
+        match byte {
+            b'\\' if cfg!(windows) => url.push('/'),
+            _ if byte.is_ascii_alphanumeric() || URL_PATH_CHARS.contains(&byte) => {
+                url.push(byte as char)
+            }
+            _ => url.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    url
+}
+
+/// A file mapped read-only into memory, for `fs.mmap`.
+///
+/// Pages are loaded on first touch, so large files can be scanned without
+/// reading them into the heap. Hosts without `mmap` read the whole file
+/// instead, which keeps the API but not the memory savings.
+pub struct MappedFile {
+    #[cfg(unix)]
+    ptr: *const u8,
+    #[cfg(unix)]
+    len: usize,
+    #[cfg(not(unix))]
+    data: Vec<u8>,
+}
+
+// The mapping is read-only and owned by this value
+unsafe impl Send for MappedFile {}
+unsafe impl Sync for MappedFile {}
+
+impl MappedFile {
+    #[cfg(unix)]
+    pub fn open(path: &Path) -> io::Result<Self> {
+        use std::os::unix::io::AsRawFd;
+
+        let file = std::fs::File::open(path)?;
+        let len = usize::try_from(file.metadata()?.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "file too large to map"))?;
+        // Zero-length mappings are an error; an empty file needs no pages
+        if len == 0 {
+            return Ok(Self {
+                ptr: std::ptr::NonNull::dangling().as_ptr(),
+                len,
+            });
+        }
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self {
+            ptr: ptr as *const u8,
+            len,
+        })
+    }
+
+    #[cfg(not(unix))]
+    pub fn open(path: &Path) -> io::Result<Self> {
+        Ok(Self {
+            data: std::fs::read(path)?,
+        })
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        #[cfg(unix)]
+        {
+            unsafe { std::slice::from_raw_parts(self.ptr, self.len) }
+        }
+        #[cfg(not(unix))]
+        {
+            &self.data
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for MappedFile {
+    fn drop(&mut self) {
+        if self.len > 0 {
+            unsafe {
+                libc::munmap(self.ptr as *mut libc::c_void, self.len);
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for MappedFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MappedFile")
+            .field("len", &self.as_slice().len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_verbatim() {
+        let strip = |p: &str| strip_verbatim(PathBuf::from(p));
+        assert_eq!(
+            strip(r"\\?\C:\src\main.ot"),
+            PathBuf::from(r"C:\src\main.ot")
+        );
+        assert_eq!(
+            strip(r"\\?\UNC\server\share\a.ot"),
+            PathBuf::from(r"\\server\share\a.ot")
+        );
+        // Only reachable through the verbatim form
+        assert_eq!(strip(r"\\?\C:\src\con."), PathBuf::from(r"\\?\C:\src\con."));
+        assert_eq!(
+            strip(r"\\?\Volume{1234}\a"),
+            PathBuf::from(r"\\?\Volume{1234}\a")
+        );
+        assert_eq!(strip("/home/me/main.ot"), PathBuf::from("/home/me/main.ot"));
+    }
+
+    #[test]
+    fn test_file_url() {
+        let url = |p: &str| file_url(Path::new(p));
+        assert_eq!(url("/home/me/main.ot"), "file:///home/me/main.ot");
+        assert_eq!(url("/srv/my app/#1?.ot"), "file:///srv/my%20app/%231%3F.ot");
+        assert_eq!(url("/tmp/caf\u{e9}.ot"), "file:///tmp/caf%C3%A9.ot");
+        if cfg!(windows) {
+            assert_eq!(url(r"C:\src\main.ot"), "file:///C:/src/main.ot");
+            assert_eq!(url(r"\\server\share\a.ot"), "file://server/share/a.ot");
+        }
+    }
+
+    #[test]
+    fn test_mapped_file() {
+        let dir = std::env::temp_dir().join(format!("oite-mmap-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("data.bin");
+        std::fs::write(&path, b"hello mmap").unwrap();
+        assert_eq!(MappedFile::open(&path).unwrap().as_slice(), b"hello mmap");
+
+        let empty = dir.join("empty.bin");
+        std::fs::write(&empty, b"").unwrap();
+        assert!(MappedFile::open(&empty).unwrap().as_slice().is_empty());
+
+        assert!(MappedFile::open(&dir.join("missing.bin")).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -11,7 +11,7 @@ mod tests {
     /// Test that ABI version is set to the expected value.
     #[test]
     fn test_abi_version() {
-        assert_eq!(ABI_VERSION, 1, "ABI version must be 1");
+        assert_eq!(ABI_VERSION, 5, "ABI version must be 5");
     }
 
     /// Test that IR format version is set to the expected value.
@@ -156,7 +156,7 @@ mod ir_format_tests {
             "IR must contain format version"
         );
         assert!(
-            output1.contains("; ABI version: 1"),
+            output1.contains("; ABI version: 5"),
             "IR must contain ABI version"
         );
     }
@@ -215,14 +215,141 @@ mod abi_stability_tests {
         // This test serves as a canary - if it fails, the ABI has changed
         // and we need to decide whether to bump ABI_VERSION
         assert_eq!(
-            ABI_VERSION, 1,
-            "ABI version must remain 1 until intentional change"
+            ABI_VERSION, 5,
+            "ABI version must remain 5 until intentional change"
         );
 
         // Verify we haven't accidentally changed to a development version
         assert!(
-            ABI_VERSION < 2,
-            "ABI should not be version 2+ without explicit decision"
+            ABI_VERSION < 6,
+            "ABI should not be version 6+ without explicit decision"
+        );
+    }
+}
+
+#[cfg(test)]
+mod abi_symbol_tests {
+    use crate::runtime::abi_version::{ABI_SYMBOLS, abi_symbol};
+    use crate::runtime::{abi_version, error, plugin, stubs};
+
+    /// Every symbol of the stable set is exported by the runtime.
+    #[test]
+    fn test_abi_symbols_are_exported() {
+        let exported: &[(&str, *const ())] = &[
+            ("ot_alloc_object", stubs::ot_alloc_object as *const ()),
+            ("ot_alloc_array", stubs::ot_alloc_array as *const ()),
+            ("ot_alloc_string", stubs::ot_alloc_string as *const ()),
+            ("ot_get_prop", stubs::ot_get_prop as *const ()),
+            ("ot_set_prop", stubs::ot_set_prop as *const ()),
+            ("ot_get_element", stubs::ot_get_element as *const ()),
+            ("ot_set_element", stubs::ot_set_element as *const ()),
+            ("ot_call", stubs::ot_call as *const ()),
+            ("ot_make_closure", stubs::ot_make_closure as *const ()),
+            ("ot_new_target", stubs::ot_new_target as *const ()),
+            ("ot_instanceof", stubs::ot_instanceof as *const ()),
+            ("ot_add_any", stubs::ot_add_any as *const ()),
+            ("ot_sub_any", stubs::ot_sub_any as *const ()),
+            ("ot_mul_any", stubs::ot_mul_any as *const ()),
+            ("ot_div_any", stubs::ot_div_any as *const ()),
+            ("ot_mod_any", stubs::ot_mod_any as *const ()),
+            ("ot_pow", stubs::ot_pow as *const ()),
+            ("ot_neg", stubs::ot_neg as *const ()),
+            ("ot_eq_strict", stubs::ot_eq_strict as *const ()),
+            ("ot_lt", stubs::ot_lt as *const ()),
+            ("ot_gt", stubs::ot_gt as *const ()),
+            ("ot_lte", stubs::ot_lte as *const ()),
+            ("ot_gte", stubs::ot_gte as *const ()),
+            ("ot_not", stubs::ot_not as *const ()),
+            ("ot_to_boolean", stubs::ot_to_boolean as *const ()),
+            ("ot_to_number", stubs::ot_to_number as *const ()),
+            ("ot_f64_to_bytes", stubs::ot_f64_to_bytes as *const ()),
+            ("ot_console_log", stubs::ot_console_log as *const ()),
+            ("ot_fs_exists", stubs::ot_fs_exists as *const ()),
+            ("ot_fs_read_file", stubs::ot_fs_read_file as *const ()),
+            ("ot_fs_write_file", stubs::ot_fs_write_file as *const ()),
+            ("ot_fs_readdir", stubs::ot_fs_readdir as *const ()),
+            ("ot_fs_stat", stubs::ot_fs_stat as *const ()),
+            (
+                "ot_stat_is_directory",
+                stubs::ot_stat_is_directory as *const (),
+            ),
+            ("ot_fs_mkdir", stubs::ot_fs_mkdir as *const ()),
+            ("ot_abi_version", abi_version::ot_abi_version as *const ()),
+            ("ot_abi_check", abi_version::ot_abi_check as *const ()),
+            (
+                "ot_alloc_typed_array",
+                stubs::ot_alloc_typed_array as *const (),
+            ),
+            ("ot_typed_array_get", stubs::ot_typed_array_get as *const ()),
+            ("ot_typed_array_set", stubs::ot_typed_array_set as *const ()),
+            ("ot_typed_array_len", stubs::ot_typed_array_len as *const ()),
+            ("ot_plugin_load", plugin::ot_plugin_load as *const ()),
+            ("ot_plugin_call", plugin::ot_plugin_call as *const ()),
+            ("ot_runtime_init", error::ot_runtime_init as *const ()),
+            ("ot_frame_enter", error::ot_frame_enter as *const ()),
+            ("ot_frame_exit", error::ot_frame_exit as *const ()),
+            ("ot_frame_line", error::ot_frame_line as *const ()),
+            ("ot_runtime_error", error::ot_runtime_error as *const ()),
+            ("ot_simd_lanes", stubs::ot_simd_lanes as *const ()),
+        ];
+        assert_eq!(exported.len(), ABI_SYMBOLS.len());
+        for (name, ptr) in exported {
+            assert!(!ptr.is_null());
+            assert!(abi_symbol(name).is_some(), "{} is not in ABI_SYMBOLS", name);
+        }
+    }
+
+    /// The version 1 symbol set is frozen: binaries built for it must keep
+    /// linking against every later runtime.
+    #[test]
+    fn test_abi_v1_symbols_are_frozen() {
+        let v1: Vec<(&str, usize)> = ABI_SYMBOLS
+            .iter()
+            .filter(|s| s.since == 1)
+            .map(|s| (s.name, s.params))
+            .collect();
+        assert_eq!(
+            v1,
+            vec![
+                ("ot_alloc_object", 0),
+                ("ot_alloc_array", 1),
+                ("ot_alloc_string", 2),
+                ("ot_get_prop", 3),
+                ("ot_set_prop", 4),
+                ("ot_get_element", 2),
+                ("ot_set_element", 3),
+                ("ot_call", 3),
+                ("ot_make_closure", 2),
+                ("ot_new_target", 0),
+                ("ot_instanceof", 2),
+                ("ot_add_any", 2),
+                ("ot_sub_any", 2),
+                ("ot_mul_any", 2),
+                ("ot_div_any", 2),
+                ("ot_mod_any", 2),
+                ("ot_pow", 2),
+                ("ot_neg", 1),
+                ("ot_eq_strict", 2),
+                ("ot_lt", 2),
+                ("ot_gt", 2),
+                ("ot_lte", 2),
+                ("ot_gte", 2),
+                ("ot_not", 1),
+                ("ot_to_boolean", 1),
+                ("ot_to_number", 1),
+                ("ot_f64_to_bytes", 2),
+                ("ot_console_log", 1),
+                ("ot_fs_exists", 1),
+                ("ot_fs_read_file", 1),
+                ("ot_fs_write_file", 2),
+                ("ot_fs_readdir", 1),
+                ("ot_fs_stat", 1),
+                ("ot_stat_is_directory", 1),
+                ("ot_fs_mkdir", 2),
+                ("ot_abi_version", 0),
+                ("ot_abi_check", 1),
+            ],
+            "ABI v1 symbols must never change"
         );
     }
 }
@@ -317,7 +444,7 @@ mod additional_abi_tests {
     #[test]
     fn test_ir_module_header() {
         assert_eq!(IR_FORMAT_VERSION, 1, "IR format version must be 1");
-        assert_eq!(ABI_VERSION, 1, "ABI version must be 1");
+        assert_eq!(ABI_VERSION, 5, "ABI version must be 5");
     }
 
     /// Test 17: Object header size
@@ -1,18 +1,301 @@
-pub const ABI_VERSION: u32 = 1;
+//! Versioned symbol set between AOT-compiled binaries and the runtime
+//!
+//! Generated code may only call the symbols in `ABI_SYMBOLS`. Each entry
+//! records the ABI version that introduced it, and a symbol is never removed
+//! or given a different signature once published, so a binary built for ABI
+//! `n` links against every runtime whose version is `n` or later. Adding
+//! symbols bumps `ABI_VERSION`; a change that breaks existing binaries raises
+//! `ABI_MIN_SUPPORTED` as well.
+//!
+//! Every generated `main` passes the version it was built for to
+//! `ot_abi_check` before running any code, so a binary started against a
+//! runtime that can't serve it stops with a readable message instead of
+//! misbehaving.
+
+use super::error::{self, ErrorKind, RuntimeError};
+
+pub const ABI_VERSION: u32 = 5;
+
+/// Oldest binary ABI this runtime still runs
+pub const ABI_MIN_SUPPORTED: u32 = 1;
 
 pub const ABI_NAME: &str = "tscl";
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AbiCategory {
+    Allocation,
+    Property,
+    Call,
+    String,
+    Arithmetic,
+    Comparison,
+    Conversion,
+    Io,
+    Version,
+    Error,
+}
+
+/// A runtime entry point generated code is allowed to call
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AbiSymbol {
+    pub name: &'static str,
+    pub category: AbiCategory,
+    /// Number of arguments (all passed as 64-bit values or pointers)
+    pub params: usize,
+    /// ABI version that introduced the symbol
+    pub since: u32,
+}
+
+const fn symbol(name: &'static str, category: AbiCategory, params: usize) -> AbiSymbol {
+    symbol_since(name, category, params, 1)
+}
+
+const fn symbol_since(
+    name: &'static str,
+    category: AbiCategory,
+    params: usize,
+    since: u32,
+) -> AbiSymbol {
+    AbiSymbol {
+        name,
+        category,
+        params,
+        since,
+    }
+}
+
+/// The stable symbol set. Append only.
+pub const ABI_SYMBOLS: &[AbiSymbol] = &[
+    symbol("ot_alloc_object", AbiCategory::Allocation, 0),
+    symbol("ot_alloc_array", AbiCategory::Allocation, 1),
+    symbol("ot_alloc_string", AbiCategory::String, 2),
+    symbol("ot_get_prop", AbiCategory::Property, 3),
+    symbol("ot_set_prop", AbiCategory::Property, 4),
+    symbol("ot_get_element", AbiCategory::Property, 2),
+    symbol("ot_set_element", AbiCategory::Property, 3),
+    symbol("ot_call", AbiCategory::Call, 3),
+    symbol("ot_make_closure", AbiCategory::Call, 2),
+    symbol("ot_new_target", AbiCategory::Call, 0),
+    symbol("ot_instanceof", AbiCategory::Call, 2),
+    symbol("ot_add_any", AbiCategory::Arithmetic, 2),
+    symbol("ot_sub_any", AbiCategory::Arithmetic, 2),
+    symbol("ot_mul_any", AbiCategory::Arithmetic, 2),
+    symbol("ot_div_any", AbiCategory::Arithmetic, 2),
+    symbol("ot_mod_any", AbiCategory::Arithmetic, 2),
+    symbol("ot_pow", AbiCategory::Arithmetic, 2),
+    symbol("ot_neg", AbiCategory::Arithmetic, 1),
+    symbol("ot_eq_strict", AbiCategory::Comparison, 2),
+    symbol("ot_lt", AbiCategory::Comparison, 2),
+    symbol("ot_gt", AbiCategory::Comparison, 2),
+    symbol("ot_lte", AbiCategory::Comparison, 2),
+    symbol("ot_gte", AbiCategory::Comparison, 2),
+    symbol("ot_not", AbiCategory::Comparison, 1),
+    symbol("ot_to_boolean", AbiCategory::Conversion, 1),
+    symbol("ot_to_number", AbiCategory::Conversion, 1),
+    symbol("ot_f64_to_bytes", AbiCategory::Conversion, 2),
+    symbol("ot_console_log", AbiCategory::Io, 1),
+    symbol("ot_fs_exists", AbiCategory::Io, 1),
+    symbol("ot_fs_read_file", AbiCategory::Io, 1),
+    symbol("ot_fs_write_file", AbiCategory::Io, 2),
+    symbol("ot_fs_readdir", AbiCategory::Io, 1),
+    symbol("ot_fs_stat", AbiCategory::Io, 1),
+    symbol("ot_stat_is_directory", AbiCategory::Io, 1),
+    symbol("ot_fs_mkdir", AbiCategory::Io, 2),
+    symbol("ot_abi_version", AbiCategory::Version, 0),
+    symbol("ot_abi_check", AbiCategory::Version, 1),
+    // ABI 2: typed arrays
+    symbol_since("ot_alloc_typed_array", AbiCategory::Allocation, 2, 2),
+    symbol_since("ot_typed_array_get", AbiCategory::Property, 2, 2),
+    symbol_since("ot_typed_array_set", AbiCategory::Property, 3, 2),
+    symbol_since("ot_typed_array_len", AbiCategory::Property, 1, 2),
+    // ABI 3: plugins
+    symbol_since("ot_plugin_load", AbiCategory::Call, 1, 3),
+    symbol_since("ot_plugin_call", AbiCategory::Call, 4, 3),
+    // ABI 4: runtime errors
+    symbol_since("ot_runtime_init", AbiCategory::Error, 0, 4),
+    symbol_since("ot_frame_enter", AbiCategory::Error, 4, 4),
+    symbol_since("ot_frame_exit", AbiCategory::Error, 0, 4),
+    symbol_since("ot_frame_line", AbiCategory::Error, 1, 4),
+    symbol_since("ot_runtime_error", AbiCategory::Error, 3, 4),
+    // ABI 5: SIMD vectors
+    symbol_since("ot_simd_lanes", AbiCategory::Conversion, 3, 5),
+];
+
+/// Look up a symbol of the stable set
+pub fn abi_symbol(name: &str) -> Option<&'static AbiSymbol> {
+    ABI_SYMBOLS.iter().find(|symbol| symbol.name == name)
+}
+
+/// Whether this runtime can run a binary built for ABI `required`
+pub fn check_compatible(required: u32) -> Result<(), String> {
+    compatible(required, ABI_VERSION, ABI_MIN_SUPPORTED)
+}
+
+/// Whether a runtime library with the ABI range `(provided, min_supported)`
+/// can run the binaries this toolchain builds
+pub fn check_runtime_library((provided, min_supported): (u32, u32)) -> Result<(), String> {
+    compatible(ABI_VERSION, provided, min_supported)
+}
+
+fn compatible(required: u32, provided: u32, min_supported: u32) -> Result<(), String> {
+    if required > provided {
+        Err(format!(
+            "this program was built for runtime ABI {} but the runtime only provides ABI {}; upgrade the runtime or rebuild with a matching toolchain",
+            required, provided
+        ))
+    } else if required < min_supported {
+        Err(format!(
+            "this program was built for runtime ABI {}, which this runtime no longer supports (oldest supported: {}); rebuild it with a current toolchain",
+            required, min_supported
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Start of the ABI marker in a runtime library, followed by
+/// `<ABI_VERSION>,<ABI_MIN_SUPPORTED>`
+pub const RUNTIME_MARKER: &str = "OITE_RUNTIME_ABI=";
+
+const RUNTIME_MARKER_LEN: usize = 40;
+
+/// The runtime's ABI range as text, so a toolchain can check a library
+/// before linking it (and `strings libscript_rt.a` shows it)
+#[allow(non_upper_case_globals)]
+#[unsafe(no_mangle)]
+#[used]
+pub static ot_runtime_abi: [u8; RUNTIME_MARKER_LEN] = runtime_marker();
+
+const fn runtime_marker() -> [u8; RUNTIME_MARKER_LEN] {
+    let mut marker = [0; RUNTIME_MARKER_LEN];
+    let prefix = RUNTIME_MARKER.as_bytes();
+    let mut len = 0;
+    while len < prefix.len() {
+        marker[len] = prefix[len];
+        len += 1;
+    }
+    len = write_digits(&mut marker, len, ABI_VERSION);
+    marker[len] = b',';
+    write_digits(&mut marker, len + 1, ABI_MIN_SUPPORTED);
+    marker
+}
+
+/// Write `value` in decimal at `at`, returning the index after it
+const fn write_digits(marker: &mut [u8; RUNTIME_MARKER_LEN], at: usize, value: u32) -> usize {
+    let mut digits = 1;
+    let mut rest = value / 10;
+    while rest > 0 {
+        digits += 1;
+        rest /= 10;
+    }
+    let mut i = 0;
+    rest = value;
+    while i < digits {
+        marker[at + digits - 1 - i] = b'0' + (rest % 10) as u8;
+        rest /= 10;
+        i += 1;
+    }
+    at + digits
+}
+
+/// The `(ABI version, oldest supported ABI)` marked in a runtime library's
+/// bytes, if it has a marker
+pub fn runtime_abi_in(bytes: &[u8]) -> Option<(u32, u32)> {
+    let prefix = RUNTIME_MARKER.as_bytes();
+    // The prefix also appears on its own wherever the constant is stored
+    bytes
+        .windows(prefix.len())
+        .enumerate()
+        .filter(|(_, window)| *window == prefix)
+        .find_map(|(at, _)| {
+            let rest = &bytes[at + prefix.len()..];
+            let end = rest
+                .iter()
+                .position(|b| !b.is_ascii_digit() && *b != b',')
+                .unwrap_or(rest.len());
+            let (version, min) = std::str::from_utf8(&rest[..end]).ok()?.split_once(',')?;
+            Some((version.parse().ok()?, min.parse().ok()?))
+        })
+}
+
+/// ABI version of the linked runtime
+#[unsafe(no_mangle)]
+pub extern "C" fn ot_abi_version() -> u32 {
+    ABI_VERSION
+}
+
+/// Called by generated `main` with the ABI the binary was built for.
+/// Exits the process if this runtime can't run it.
+#[unsafe(no_mangle)]
+pub extern "C" fn ot_abi_check(required: u32) {
+    if let Err(e) = check_compatible(required) {
+        error::fail(RuntimeError::new(ErrorKind::Abi, e));
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn test_abi_version() {
-        assert_eq!(ABI_VERSION, 1);
+        assert_eq!(ABI_VERSION, 5);
     }
 
     #[test]
     fn test_abi_name() {
         assert_eq!(ABI_NAME, "tscl");
     }
+
+    #[test]
+    fn test_binaries_run_on_same_or_newer_runtime() {
+        assert!(check_compatible(ABI_VERSION).is_ok());
+        assert!(check_compatible(ABI_MIN_SUPPORTED).is_ok());
+        let err = check_compatible(ABI_VERSION + 1).unwrap_err();
+        assert!(err.contains("upgrade the runtime"));
+        if let Some(older) = ABI_MIN_SUPPORTED.checked_sub(1) {
+            assert!(check_compatible(older).is_err());
+        }
+    }
+
+    #[test]
+    fn test_runtime_marker_reads_back() {
+        let marker = ot_runtime_abi;
+        assert_eq!(
+            runtime_abi_in(&marker),
+            Some((ABI_VERSION, ABI_MIN_SUPPORTED))
+        );
+
+        // Found past a bare prefix, as in a real archive
+        let mut archive = b"!<arch>\0OITE_RUNTIME_ABI=\0junk".to_vec();
+        archive.extend_from_slice(b"OITE_RUNTIME_ABI=12,3\0");
+        assert_eq!(runtime_abi_in(&archive), Some((12, 3)));
+        assert_eq!(runtime_abi_in(b"not a runtime"), None);
+    }
+
+    #[test]
+    fn test_runtime_library_must_serve_this_toolchain() {
+        assert!(check_runtime_library((ABI_VERSION, ABI_MIN_SUPPORTED)).is_ok());
+        assert!(check_runtime_library((ABI_VERSION + 1, ABI_VERSION)).is_ok());
+        let err = check_runtime_library((ABI_VERSION - 1, 1)).unwrap_err();
+        assert!(err.contains("upgrade the runtime"));
+        assert!(check_runtime_library((ABI_VERSION + 2, ABI_VERSION + 1)).is_err());
+    }
+
+    #[test]
+    fn test_symbol_set_is_consistent() {
+        for (i, symbol) in ABI_SYMBOLS.iter().enumerate() {
+            assert!(symbol.name.starts_with("ot_"), "{}", symbol.name);
+            assert!(symbol.since >= 1 && symbol.since <= ABI_VERSION);
+            assert!(
+                ABI_SYMBOLS[..i].iter().all(|s| s.name != symbol.name),
+                "duplicate ABI symbol {}",
+                symbol.name
+            );
+        }
+        // Append only: versions never decrease along the table
+        assert!(ABI_SYMBOLS.windows(2).all(|w| w[0].since <= w[1].since));
+        assert_eq!(abi_symbol("ot_get_prop").unwrap().params, 3);
+        assert!(abi_symbol("ot_unknown").is_none());
+    }
 }
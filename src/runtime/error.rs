@@ -0,0 +1,234 @@
+//! Runtime error reporting for native code
+//!
+//! Debug builds keep a shadow stack of script frames: each compiled function
+//! pushes its name and source file on entry, updates its line as it runs and
+//! pops on return. A runtime error prints its message with a script-level
+//! stack trace, innermost frame first, and exits with the status of its kind:
+//!
+//! ```text
+//! InternalError: reached code the compiler marked unreachable
+//!     at scale (src/math.ot:12)
+//!     at main (src/main.ot:3)
+//! ```
+
+use std::cell::RefCell;
+use std::fmt;
+use std::sync::Once;
+
+/// What went wrong, which decides the label and exit status
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum ErrorKind {
+    /// The binary needs a runtime ABI this runtime doesn't provide
+    Abi = 0,
+    /// Control reached code the compiler marked unreachable
+    Unreachable = 1,
+    /// A runtime function panicked
+    Internal = 2,
+}
+
+impl ErrorKind {
+    /// The kind passed to `ot_runtime_error`; unknown codes are internal
+    pub fn from_code(code: u32) -> Self {
+        match code {
+            0 => ErrorKind::Abi,
+            1 => ErrorKind::Unreachable,
+            _ => ErrorKind::Internal,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ErrorKind::Abi => "Error",
+            ErrorKind::Unreachable | ErrorKind::Internal => "InternalError",
+        }
+    }
+
+    /// 1 when the program can't run here, 70 (EX_SOFTWARE) for a compiler
+    /// or runtime bug
+    pub fn exit_code(self) -> i32 {
+        match self {
+            ErrorKind::Abi => 1,
+            ErrorKind::Unreachable | ErrorKind::Internal => 70,
+        }
+    }
+}
+
+/// A compiled function's activation on the shadow stack
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub function: &'static str,
+    pub file: &'static str,
+    /// 0 until the function reaches its first statement
+    pub line: u32,
+}
+
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.line {
+            0 => write!(f, "at {} ({})", self.function, self.file),
+            line => write!(f, "at {} ({}:{})", self.function, self.file, line),
+        }
+    }
+}
+
+thread_local! {
+    static FRAMES: RefCell<Vec<Frame>> = const { RefCell::new(Vec::new()) };
+}
+
+/// An error with the script stack it was raised on
+#[derive(Debug, Clone)]
+pub struct RuntimeError {
+    pub kind: ErrorKind,
+    pub message: String,
+    /// Innermost frame first; empty in builds without frame tracking
+    pub trace: Vec<Frame>,
+}
+
+impl RuntimeError {
+    /// An error raised now, on the current thread's script stack
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        let trace = FRAMES.with(|frames| match frames.try_borrow() {
+            Ok(frames) => frames.iter().rev().cloned().collect(),
+            Err(_) => Vec::new(),
+        });
+        Self {
+            kind,
+            message: message.into(),
+            trace,
+        }
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.kind.label(), self.message)?;
+        for frame in &self.trace {
+            write!(f, "\n    {}", frame)?;
+        }
+        Ok(())
+    }
+}
+
+/// Print `error` and exit with its kind's status
+pub fn fail(error: RuntimeError) -> ! {
+    eprintln!("{}", error);
+    std::process::exit(error.kind.exit_code())
+}
+
+/// Report panics in runtime functions as internal errors with the script
+/// stack, instead of aborting at the `extern "C"` boundary
+pub fn install_panic_hook() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        std::panic::set_hook(Box::new(|info| {
+            let payload = info.payload();
+            let message = payload
+                .downcast_ref::<&str>()
+                .copied()
+                .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+                .unwrap_or("runtime panic");
+            let message = match info.location() {
+                Some(location) => format!("{} (runtime {})", message, location),
+                None => message.to_string(),
+            };
+            fail(RuntimeError::new(ErrorKind::Internal, message))
+        }));
+    });
+}
+
+/// Read a string the compiled binary keeps in its constant data
+unsafe fn static_str(data: *const u8, len: usize) -> &'static str {
+    if data.is_null() {
+        return "?";
+    }
+    let bytes = unsafe { std::slice::from_raw_parts(data, len) };
+    std::str::from_utf8(bytes).unwrap_or("?")
+}
+
+/// Called by generated `main` before any script code runs
+#[unsafe(no_mangle)]
+pub extern "C" fn ot_runtime_init() {
+    install_panic_hook();
+}
+
+/// Push a frame for `function`, defined in `file`
+#[unsafe(no_mangle)]
+pub extern "C" fn ot_frame_enter(
+    function: *const u8,
+    function_len: usize,
+    file: *const u8,
+    file_len: usize,
+) {
+    let frame = unsafe {
+        Frame {
+            function: static_str(function, function_len),
+            file: static_str(file, file_len),
+            line: 0,
+        }
+    };
+    FRAMES.with(|frames| frames.borrow_mut().push(frame));
+}
+
+/// Pop the innermost frame
+#[unsafe(no_mangle)]
+pub extern "C" fn ot_frame_exit() {
+    FRAMES.with(|frames| frames.borrow_mut().pop());
+}
+
+/// Move the innermost frame to `line`
+#[unsafe(no_mangle)]
+pub extern "C" fn ot_frame_line(line: u32) {
+    FRAMES.with(|frames| {
+        if let Some(frame) = frames.borrow_mut().last_mut() {
+            frame.line = line;
+        }
+    });
+}
+
+/// Raise an error of `kind` (an `ErrorKind` code) from compiled code
+#[unsafe(no_mangle)]
+pub extern "C" fn ot_runtime_error(kind: u32, message: *const u8, message_len: usize) -> ! {
+    let message = unsafe { static_str(message, message_len) };
+    fail(RuntimeError::new(ErrorKind::from_code(kind), message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn enter(function: &'static str, file: &'static str) {
+        ot_frame_enter(function.as_ptr(), function.len(), file.as_ptr(), file.len());
+    }
+
+    #[test]
+    fn test_trace_lists_script_frames_innermost_first() {
+        enter("main", "app.ot");
+        ot_frame_line(7);
+        enter("scale", "math.ot");
+        let error = RuntimeError::new(ErrorKind::Unreachable, "reached unreachable code");
+        assert_eq!(
+            error.to_string(),
+            "InternalError: reached unreachable code\n    at scale (math.ot)\n    at main (app.ot:7)"
+        );
+
+        ot_frame_line(3);
+        ot_frame_exit();
+        let error = RuntimeError::new(ErrorKind::Internal, "boom");
+        assert_eq!(error.trace.len(), 1);
+        assert_eq!(error.trace[0].line, 7);
+        ot_frame_exit();
+        assert!(RuntimeError::new(ErrorKind::Abi, "x").trace.is_empty());
+    }
+
+    #[test]
+    fn test_kinds_round_trip_and_set_exit_codes() {
+        for kind in [ErrorKind::Abi, ErrorKind::Unreachable, ErrorKind::Internal] {
+            assert_eq!(ErrorKind::from_code(kind as u32), kind);
+        }
+        assert_eq!(ErrorKind::from_code(99), ErrorKind::Internal);
+        assert_eq!(ErrorKind::Abi.exit_code(), 1);
+        assert_eq!(ErrorKind::Unreachable.exit_code(), 70);
+        assert_eq!(ErrorKind::Abi.label(), "Error");
+    }
+}
@@ -119,6 +119,161 @@ pub enum ObjectKind {
     Function = 3,
     /// A ByteStream buffer (for bytecode generation).
     ByteStream = 4,
+    /// A typed array with unboxed element storage.
+    TypedArray = 5,
+}
+
+/// Element type of a typed array.
+///
+/// The discriminant is stored in `NativeTypedArray::kind` and passed to the
+/// `ot_alloc_typed_array` stub, so the order must stay stable.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ElementKind {
+    Int8 = 0,
+    Uint8 = 1,
+    Uint8Clamped = 2,
+    Int16 = 3,
+    Uint16 = 4,
+    Int32 = 5,
+    Uint32 = 6,
+    Float32 = 7,
+    Float64 = 8,
+}
+
+impl ElementKind {
+    /// Size of one element in bytes.
+    pub fn size(self) -> usize {
+        match self {
+            ElementKind::Int8 | ElementKind::Uint8 | ElementKind::Uint8Clamped => 1,
+            ElementKind::Int16 | ElementKind::Uint16 => 2,
+            ElementKind::Int32 | ElementKind::Uint32 | ElementKind::Float32 => 4,
+            ElementKind::Float64 => 8,
+        }
+    }
+
+    /// Map a constructor name such as `Float64Array` to its element kind.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "Int8Array" => ElementKind::Int8,
+            "Uint8Array" => ElementKind::Uint8,
+            "Uint8ClampedArray" => ElementKind::Uint8Clamped,
+            "Int16Array" => ElementKind::Int16,
+            "Uint16Array" => ElementKind::Uint16,
+            "Int32Array" => ElementKind::Int32,
+            "Uint32Array" => ElementKind::Uint32,
+            "Float32Array" => ElementKind::Float32,
+            "Float64Array" => ElementKind::Float64,
+            _ => return None,
+        })
+    }
+
+    /// Decode a discriminant produced by `kind as u8`.
+    pub fn from_u8(tag: u8) -> Option<Self> {
+        Some(match tag {
+            0 => ElementKind::Int8,
+            1 => ElementKind::Uint8,
+            2 => ElementKind::Uint8Clamped,
+            3 => ElementKind::Int16,
+            4 => ElementKind::Uint16,
+            5 => ElementKind::Int32,
+            6 => ElementKind::Uint32,
+            7 => ElementKind::Float32,
+            8 => ElementKind::Float64,
+            _ => return None,
+        })
+    }
+
+    /// The constructor name (`Float64Array`, ...).
+    pub fn name(self) -> &'static str {
+        match self {
+            ElementKind::Int8 => "Int8Array",
+            ElementKind::Uint8 => "Uint8Array",
+            ElementKind::Uint8Clamped => "Uint8ClampedArray",
+            ElementKind::Int16 => "Int16Array",
+            ElementKind::Uint16 => "Uint16Array",
+            ElementKind::Int32 => "Int32Array",
+            ElementKind::Uint32 => "Uint32Array",
+            ElementKind::Float32 => "Float32Array",
+            ElementKind::Float64 => "Float64Array",
+        }
+    }
+
+    /// Short element name used in IR dumps (`f64`, `u8c`, ...).
+    pub fn short_name(self) -> &'static str {
+        match self {
+            ElementKind::Int8 => "i8",
+            ElementKind::Uint8 => "u8",
+            ElementKind::Uint8Clamped => "u8c",
+            ElementKind::Int16 => "i16",
+            ElementKind::Uint16 => "u16",
+            ElementKind::Int32 => "i32",
+            ElementKind::Uint32 => "u32",
+            ElementKind::Float32 => "f32",
+            ElementKind::Float64 => "f64",
+        }
+    }
+
+    /// Parse a short element name (inverse of `short_name`).
+    pub fn from_short_name(name: &str) -> Option<Self> {
+        (0..=8)
+            .filter_map(Self::from_u8)
+            .find(|k| k.short_name() == name)
+    }
+
+    /// Read one element at `ptr` and widen it to a JS number.
+    ///
+    /// # Safety
+    /// `ptr` must point to at least `self.size()` readable bytes.
+    pub unsafe fn load(self, ptr: *const u8) -> f64 {
+        unsafe {
+            match self {
+                ElementKind::Int8 => (ptr as *const i8).read_unaligned() as f64,
+                ElementKind::Uint8 | ElementKind::Uint8Clamped => ptr.read() as f64,
+                ElementKind::Int16 => (ptr as *const i16).read_unaligned() as f64,
+                ElementKind::Uint16 => (ptr as *const u16).read_unaligned() as f64,
+                ElementKind::Int32 => (ptr as *const i32).read_unaligned() as f64,
+                ElementKind::Uint32 => (ptr as *const u32).read_unaligned() as f64,
+                ElementKind::Float32 => (ptr as *const f32).read_unaligned() as f64,
+                ElementKind::Float64 => (ptr as *const f64).read_unaligned(),
+            }
+        }
+    }
+
+    /// Convert a JS number to this element type and write it at `ptr`.
+    ///
+    /// Integer kinds wrap modulo 2^n like `ToInt32`; `Uint8Clamped` clamps
+    /// and rounds half to even.
+    ///
+    /// # Safety
+    /// `ptr` must point to at least `self.size()` writable bytes.
+    pub unsafe fn store(self, ptr: *mut u8, value: f64) {
+        unsafe {
+            match self {
+                ElementKind::Int8 => (ptr as *mut i8).write_unaligned(to_uint32(value) as i8),
+                ElementKind::Uint8 => ptr.write(to_uint32(value) as u8),
+                ElementKind::Uint8Clamped => ptr.write(if value.is_nan() {
+                    0
+                } else {
+                    value.clamp(0.0, 255.0).round_ties_even() as u8
+                }),
+                ElementKind::Int16 => (ptr as *mut i16).write_unaligned(to_uint32(value) as i16),
+                ElementKind::Uint16 => (ptr as *mut u16).write_unaligned(to_uint32(value) as u16),
+                ElementKind::Int32 => (ptr as *mut i32).write_unaligned(to_uint32(value) as i32),
+                ElementKind::Uint32 => (ptr as *mut u32).write_unaligned(to_uint32(value)),
+                ElementKind::Float32 => (ptr as *mut f32).write_unaligned(value as f32),
+                ElementKind::Float64 => (ptr as *mut f64).write_unaligned(value),
+            }
+        }
+    }
+}
+
+/// JS `ToUint32`: truncate and wrap modulo 2^32 (NaN and infinities become 0).
+pub fn to_uint32(value: f64) -> u32 {
+    if !value.is_finite() {
+        return 0;
+    }
+    value.trunc().rem_euclid(4294967296.0) as u32
 }
 
 /// Header for all heap-allocated objects.
@@ -191,6 +346,46 @@ pub struct NativeArray {
     pub elements: *mut u64,
 }
 
+/// A native typed array.
+///
+/// Elements are stored unboxed in a separate buffer of `len * kind.size()`
+/// bytes, so compiled code can load and store them directly.
+#[repr(C)]
+pub struct NativeTypedArray {
+    pub header: ObjectHeader,
+    /// Number of elements.
+    pub len: u32,
+    /// Element type.
+    pub kind: ElementKind,
+    /// Pointer to the element buffer.
+    pub data: *mut u8,
+}
+
+impl NativeTypedArray {
+    /// Byte offset of `len`, read by compiled code for `.length`.
+    pub const LEN_OFFSET: usize = std::mem::offset_of!(NativeTypedArray, len);
+    /// Byte offset of the `data` pointer.
+    pub const DATA_OFFSET: usize = std::mem::offset_of!(NativeTypedArray, data);
+
+    /// Read element `index`, or `None` when out of bounds.
+    pub fn get(&self, index: usize) -> Option<f64> {
+        if index >= self.len as usize {
+            return None;
+        }
+        unsafe { Some(self.kind.load(self.data.add(index * self.kind.size()))) }
+    }
+
+    /// Write element `index`; out-of-bounds writes are ignored.
+    pub fn set(&mut self, index: usize, value: f64) {
+        if index < self.len as usize {
+            unsafe {
+                self.kind
+                    .store(self.data.add(index * self.kind.size()), value)
+            }
+        }
+    }
+}
+
 /// A native object (key-value map).
 ///
 /// For simplicity, we use a Rust HashMap internally.
@@ -363,6 +558,29 @@ impl NativeHeap {
         Some(ptr)
     }
 
+    /// Allocate a zero-filled typed array of `len` elements.
+    pub fn alloc_typed_array(&self, kind: ElementKind, len: usize) -> Option<HeapPtr> {
+        let data_size = std::mem::size_of::<NativeTypedArray>() - ObjectHeader::SIZE;
+        let ptr = self.alloc(data_size)?;
+
+        unsafe {
+            let header = ptr.as_mut::<ObjectHeader>();
+            *header = ObjectHeader::new(ObjectKind::TypedArray, data_size as u32);
+
+            let arr = ptr.as_mut::<NativeTypedArray>();
+            arr.len = len as u32;
+            arr.kind = kind;
+            arr.data = if len == 0 {
+                std::ptr::NonNull::<u64>::dangling().as_ptr() as *mut u8
+            } else {
+                let layout = Layout::from_size_align(len * kind.size(), 8).ok()?;
+                alloc::alloc_zeroed(layout)
+            };
+        }
+
+        Some(ptr)
+    }
+
     /// Get the total bytes allocated.
     pub fn total_allocated(&self) -> usize {
         self.total_allocated.load(Ordering::Relaxed)
@@ -456,6 +674,37 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_alloc_typed_array() {
+        let heap = NativeHeap::new();
+        let ptr = heap
+            .alloc_typed_array(ElementKind::Uint8Clamped, 4)
+            .expect("allocation failed");
+
+        unsafe {
+            assert_eq!(ptr.as_ref::<ObjectHeader>().kind, ObjectKind::TypedArray);
+
+            let arr = ptr.as_mut::<NativeTypedArray>();
+            assert_eq!(arr.len, 4);
+            assert_eq!(arr.get(0), Some(0.0));
+            arr.set(0, 300.0);
+            arr.set(1, -5.0);
+            arr.set(2, 2.5);
+            arr.set(4, 1.0);
+            assert_eq!(arr.get(0), Some(255.0));
+            assert_eq!(arr.get(1), Some(0.0));
+            assert_eq!(arr.get(2), Some(2.0));
+            assert_eq!(arr.get(4), None);
+        }
+
+        let ptr = heap.alloc_typed_array(ElementKind::Int8, 1).unwrap();
+        unsafe {
+            let arr = ptr.as_mut::<NativeTypedArray>();
+            arr.set(0, 200.0);
+            assert_eq!(arr.get(0), Some(-56.0));
+        }
+    }
+
     #[test]
     fn test_heap_ptr_roundtrip() {
         let addr: usize = 0x1234_5678_9ABC;
@@ -5,6 +5,10 @@
 //! - Memory allocation and GC (heap.rs)
 //! - Value representation for native interop (abi.rs)
 //! - Extern "C" stubs callable from JIT/AOT code (stubs.rs)
+//! - Spec-exact number formatting shared with the VM (number.rs)
+//! - Loading compiled libraries as plugins (plugin.rs)
+//! - Safepoints that let compiled loops be stopped or paused (safepoint.rs)
+//! - Runtime errors with script-level stack traces (error.rs)
 //!
 //! The VM interpreter continues to use JsValue/HeapObject for backwards compatibility.
 //! Native code uses OtValue (NaN-boxed) for efficient representation.
@@ -12,8 +16,14 @@
 pub mod abi;
 pub mod abi_tests;
 pub mod abi_version;
+// The reactor is epoll/kqueue based; there is no IOCP backend yet
+#[cfg(unix)]
 pub mod r#async;
+pub mod error;
 pub mod heap;
+pub mod number;
+pub mod plugin;
+pub mod safepoint;
 pub mod stubs;
 
 pub use abi_version::ABI_VERSION;
@@ -0,0 +1,356 @@
+//! Spec-exact Number to String conversion
+//!
+//! `String(n)`, `n.toString()`, string concatenation, property keys and
+//! `JSON.stringify` all produce user-visible data, so they follow
+//! Number::toString (ECMA-262 §6.1.6.1.20) exactly: the shortest digits that
+//! round-trip, in plain notation between 1e-7 and 1e21 and exponent notation
+//! outside it. Rust's `f64` Display differs at both ends (`1e21` prints all
+//! 22 digits, `1e-7` prints `0.0000001`) and keeps the sign of `-0`.
+//!
+//! `toFixed` and `toPrecision` round the exact binary value half up, so
+//! `(1.005).toFixed(2)` is `"1.00"` (the double is just below 1.005) while
+//! `(2.5).toFixed(0)` is `"3"`; Rust's `{:.2}` rounds ties to even. Other
+//! radixes print the digits V8 prints, fraction included.
+
+/// `Number::toString(n)` with radix 10
+pub fn number_to_js_string(n: f64) -> String {
+    if n.is_nan() {
+        return "NaN".to_string();
+    }
+    if n == 0.0 {
+        return "0".to_string();
+    }
+    if n.is_infinite() {
+        return if n > 0.0 { "Infinity" } else { "-Infinity" }.to_string();
+    }
+    if n < 0.0 {
+        return format!("-{}", number_to_js_string(-n));
+    }
+
+    let (digits, n) = shortest_digits(n);
+    let k = digits.len() as i32;
+    if k <= n && n <= 21 {
+        // Integer, padded with zeros
+        format!("{}{}", digits, "0".repeat((n - k) as usize))
+    } else if 0 < n && n <= 21 {
+        let (int, frac) = digits.split_at(n as usize);
+        format!("{}.{}", int, frac)
+    } else if -6 < n && n <= 0 {
+        format!("0.{}{}", "0".repeat(-n as usize), digits)
+    } else {
+        let e = n - 1;
+        let sign = if e < 0 { '-' } else { '+' };
+        let (first, rest) = digits.split_at(1);
+        if rest.is_empty() {
+            format!("{}e{}{}", first, sign, e.abs())
+        } else {
+            format!("{}.{}e{}{}", first, rest, sign, e.abs())
+        }
+    }
+}
+
+/// Shortest round-trip decimal digits of a positive finite `n`, and the
+/// exponent `e` with `n = 0.digits × 10^e`
+fn shortest_digits(n: f64) -> (String, i32) {
+    // `{:e}` prints the shortest digits that round-trip, as `d.ddde±x`
+    let formatted = format!("{:e}", n);
+    let (mantissa, exponent) = formatted.split_once('e').unwrap_or((&formatted, "0"));
+    let exponent: i32 = exponent.parse().unwrap_or(0);
+    let digits: String = mantissa.chars().filter(|c| *c != '.').collect();
+    (digits, exponent + 1)
+}
+
+/// Every decimal digit of a positive finite `n` (a double has at most 767
+/// significant ones), trailing zeros dropped, with the exponent `e` of
+/// `n = 0.digits × 10^e`
+fn exact_digits(n: f64) -> (Vec<u8>, i32) {
+    let formatted = format!("{:.766e}", n);
+    let (mantissa, exponent) = formatted.split_once('e').unwrap_or((&formatted, "0"));
+    let exponent: i32 = exponent.parse().unwrap_or(0);
+    let mut digits: Vec<u8> = mantissa
+        .bytes()
+        .filter(u8::is_ascii_digit)
+        .map(|b| b - b'0')
+        .collect();
+    while digits.len() > 1 && digits.last() == Some(&0) {
+        digits.pop();
+    }
+    (digits, exponent + 1)
+}
+
+/// The first `keep` of `digits`, rounded half up on the ones after them.
+/// A carry out of the first digit makes the result one digit longer.
+fn round_half_up(digits: &[u8], keep: usize) -> Vec<u8> {
+    let mut kept: Vec<u8> = (0..keep)
+        .map(|i| digits.get(i).copied().unwrap_or(0))
+        .collect();
+    if digits.get(keep).is_some_and(|&d| d >= 5) {
+        let mut i = keep;
+        loop {
+            if i == 0 {
+                kept.insert(0, 1);
+                break;
+            }
+            i -= 1;
+            if kept[i] == 9 {
+                kept[i] = 0;
+            } else {
+                kept[i] += 1;
+                break;
+            }
+        }
+    }
+    kept
+}
+
+fn digit_string(digits: &[u8]) -> String {
+    digits.iter().map(|d| char::from(b'0' + d)).collect()
+}
+
+/// `Number.prototype.toFixed(fraction_digits)`, for `fraction_digits` in
+/// `0..=100`
+pub fn to_fixed(n: f64, fraction_digits: usize) -> String {
+    if !n.is_finite() || n.abs() >= 1e21 {
+        return number_to_js_string(n);
+    }
+    let sign = if n < 0.0 { "-" } else { "" };
+    // The integer closest to |n| × 10^f
+    let scaled = if n == 0.0 {
+        String::new()
+    } else {
+        let (digits, e) = exact_digits(n.abs());
+        match usize::try_from(e + fraction_digits as i32) {
+            Ok(keep) => digit_string(&round_half_up(&digits, keep)),
+            // Below half a unit in the last place
+            Err(_) => String::new(),
+        }
+    };
+    let scaled = format!("{:0>width$}", scaled, width = fraction_digits + 1);
+    if fraction_digits == 0 {
+        return format!("{}{}", sign, scaled);
+    }
+    let (int, frac) = scaled.split_at(scaled.len() - fraction_digits);
+    format!("{}{}.{}", sign, int, frac)
+}
+
+/// `Number.prototype.toPrecision(precision)`, for `precision` in `1..=100`
+pub fn to_precision(n: f64, precision: usize) -> String {
+    if !n.is_finite() {
+        return number_to_js_string(n);
+    }
+    let sign = if n < 0.0 { "-" } else { "" };
+    let (digits, e) = if n == 0.0 {
+        (vec![0; precision], 0)
+    } else {
+        let (digits, e) = exact_digits(n.abs());
+        let mut rounded = round_half_up(&digits, precision);
+        // n = d.ddd × 10^e from here on
+        let mut e = e - 1;
+        if rounded.len() > precision {
+            rounded.truncate(precision);
+            e += 1;
+        }
+        (rounded, e)
+    };
+    let digits = digit_string(&digits);
+    let p = precision as i32;
+    if e < -6 || e >= p {
+        let (first, rest) = digits.split_at(1);
+        let point = if rest.is_empty() { "" } else { "." };
+        let exp_sign = if e < 0 { '-' } else { '+' };
+        return format!("{}{}{}{}e{}{}", sign, first, point, rest, exp_sign, e.abs());
+    }
+    if e == p - 1 {
+        format!("{}{}", sign, digits)
+    } else if e >= 0 {
+        let (int, frac) = digits.split_at(e as usize + 1);
+        format!("{}{}.{}", sign, int, frac)
+    } else {
+        format!("{}0.{}{}", sign, "0".repeat((-e - 1) as usize), digits)
+    }
+}
+
+/// `Number::toString(n, radix)` for a radix in `2..=36`: the digits V8
+/// prints, with as many fraction digits as it takes to tell `n` from its
+/// neighbouring doubles
+pub fn number_to_radix_string(n: f64, radix: u32) -> String {
+    if radix == 10 || !n.is_finite() {
+        return number_to_js_string(n);
+    }
+    if n == 0.0 {
+        return "0".to_string();
+    }
+    let to_char = |d: u32| std::char::from_digit(d, radix).unwrap_or('0');
+    let radix_f = radix as f64;
+    let value = n.abs();
+    let mut integer = value.floor();
+    let mut fraction = value - integer;
+
+    let mut fraction_digits: Vec<u32> = Vec::new();
+    // Half the gap to the next double: digits below it are noise
+    let mut delta = (0.5 * (f64::from_bits(value.to_bits() + 1) - value)).max(f64::from_bits(1));
+    if fraction >= delta {
+        loop {
+            fraction *= radix_f;
+            delta *= radix_f;
+            let digit = fraction as u32;
+            fraction_digits.push(digit);
+            fraction -= digit as f64;
+            // Round to even, carrying into the digits already written
+            if (fraction > 0.5 || (fraction == 0.5 && digit & 1 == 1)) && fraction + delta > 1.0 {
+                loop {
+                    match fraction_digits.pop() {
+                        None => {
+                            integer += 1.0;
+                            break;
+                        }
+                        Some(last) if last + 1 < radix => {
+                            fraction_digits.push(last + 1);
+                            break;
+                        }
+                        Some(_) => {}
+                    }
+                }
+                break;
+            }
+            if fraction < delta {
+                break;
+            }
+        }
+    }
+
+    // Digits of the integer part beyond a double's precision are zeros
+    let mut integer_digits: Vec<char> = Vec::new();
+    while integer / radix_f >= 9007199254740992.0 {
+        integer /= radix_f;
+        integer_digits.push('0');
+    }
+    loop {
+        let remainder = integer % radix_f;
+        integer_digits.push(to_char(remainder as u32));
+        integer = (integer - remainder) / radix_f;
+        if integer <= 0.0 {
+            break;
+        }
+    }
+
+    let mut out = String::new();
+    if n < 0.0 {
+        out.push('-');
+    }
+    out.extend(integer_digits.iter().rev());
+    if !fraction_digits.is_empty() {
+        out.push('.');
+        out.extend(fraction_digits.into_iter().map(to_char));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches_number_to_string() {
+        let cases = [
+            (0.0, "0"),
+            (-0.0, "0"),
+            (1.0, "1"),
+            (-42.0, "-42"),
+            (0.1 + 0.2, "0.30000000000000004"),
+            (123.456, "123.456"),
+            (1e21, "1e+21"),
+            (1e20, "100000000000000000000"),
+            (123e20, "1.23e+22"),
+            (1e-6, "0.000001"),
+            (1e-7, "1e-7"),
+            (1.5e-7, "1.5e-7"),
+            (f64::MAX, "1.7976931348623157e+308"),
+            (5e-324, "5e-324"),
+            (f64::NAN, "NaN"),
+            (f64::NEG_INFINITY, "-Infinity"),
+        ];
+        for (n, expected) in cases {
+            assert_eq!(number_to_js_string(n), expected, "{:?}", n);
+        }
+    }
+
+    #[test]
+    fn test_to_fixed_rounds_the_exact_value() {
+        let cases = [
+            (1.005, 2, "1.00"),
+            (2.5, 0, "3"),
+            (-2.5, 0, "-3"),
+            (0.5, 0, "1"),
+            (1.45, 1, "1.4"),
+            (0.125, 2, "0.13"),
+            (123.456, 1, "123.5"),
+            (0.000001, 2, "0.00"),
+            (-0.0001, 2, "-0.00"),
+            (-0.0, 2, "0.00"),
+            (9.995, 2, "9.99"),
+            (99.5, 0, "100"),
+            (1e21, 2, "1e+21"),
+            (42.0, 3, "42.000"),
+            (0.1, 20, "0.10000000000000000555"),
+        ];
+        for (n, digits, expected) in cases {
+            assert_eq!(to_fixed(n, digits), expected, "{:?}.toFixed({})", n, digits);
+        }
+    }
+
+    #[test]
+    fn test_to_precision() {
+        let cases = [
+            (123.456, 4, "123.5"),
+            (0.000123, 2, "0.00012"),
+            (0.0000001234, 2, "1.2e-7"),
+            (123456.0, 2, "1.2e+5"),
+            (99.99, 3, "100"),
+            (999.9, 3, "1.00e+3"),
+            (0.0, 3, "0.00"),
+            (-1.5, 1, "-2"),
+            (1.0, 1, "1"),
+            (5e-324, 1, "5e-324"),
+        ];
+        for (n, precision, expected) in cases {
+            assert_eq!(
+                to_precision(n, precision),
+                expected,
+                "{:?}.toPrecision({})",
+                n,
+                precision
+            );
+        }
+    }
+
+    #[test]
+    fn test_radix_strings() {
+        let cases = [
+            (255.0, 16, "ff"),
+            (-255.0, 2, "-11111111"),
+            (0.5, 2, "0.1"),
+            (0.1, 3, "0.0022002200220022002200220022002201"),
+            (3.75, 16, "3.c"),
+            (1e21, 36, "5v1j4f4ds7c000"),
+            (
+                2f64.powi(60),
+                2,
+                "1000000000000000000000000000000000000000000000000000000000000",
+            ),
+            (-0.0, 16, "0"),
+            (std::f64::consts::PI, 7, "3.066365143203613411"),
+            (123.456, 36, "3f.gez4w97ry"),
+            (-1e-7, 5, "-0.000000000044201334330402232142411"),
+        ];
+        for (n, radix, expected) in cases {
+            assert_eq!(
+                number_to_radix_string(n, radix),
+                expected,
+                "{:?}.toString({})",
+                n,
+                radix
+            );
+        }
+    }
+}
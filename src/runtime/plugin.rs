@@ -0,0 +1,311 @@
+//! Loading compiled script libraries at runtime
+//!
+//! A library built with `oitec build --format dylib` is a plugin: for every
+//! top-level function `name` it lists in `<library>.exports.json` it also
+//! exports a wrapper with one fixed signature,
+//!
+//! ```c
+//! int64_t ot_export_<name>(int64_t argc, const int64_t *argv);
+//! ```
+//!
+//! taking the arguments as NaN-boxed values (missing ones read as
+//! `undefined`, extra ones are ignored) and returning the result, or
+//! `undefined` for a function that returns nothing. Next to the wrappers it
+//! exports `ot_plugin_abi`, a `uint32_t` holding the runtime ABI it was
+//! built for, which the loader checks the same way a binary's `main` does.
+//!
+//! A program loads a plugin with `runtime.loadPlugin(path)` and calls into
+//! it with `runtime.callPlugin(plugin, name, ...args)`. Loaded libraries
+//! stay mapped until the process exits, so nothing they return can dangle.
+
+use super::abi::OtValue;
+use super::abi_version::check_compatible;
+use super::heap::{NativeString, ObjectHeader, ObjectKind};
+use std::ffi::c_void;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Symbol holding the ABI version a plugin was built for
+pub const PLUGIN_ABI_SYMBOL: &str = "ot_plugin_abi";
+
+/// Prefix of the per-function entry points
+pub const EXPORT_PREFIX: &str = "ot_export_";
+
+/// The signature of every `ot_export_<name>` entry point
+pub type ExportFn = extern "C" fn(argc: u64, argv: *const u64) -> u64;
+
+/// Entry point symbol of the exported function `name`
+pub fn export_symbol(name: &str) -> String {
+    format!("{}{}", EXPORT_PREFIX, name)
+}
+
+/// A loaded plugin
+pub struct Plugin {
+    pub path: PathBuf,
+    /// Resolves a symbol of the library
+    resolve: Box<dyn Fn(&str) -> Option<*const c_void> + Send>,
+}
+
+impl Plugin {
+    /// A plugin whose symbols come from `resolve`, after checking its ABI
+    pub fn from_resolver(
+        path: PathBuf,
+        resolve: Box<dyn Fn(&str) -> Option<*const c_void> + Send>,
+    ) -> Result<Self, String> {
+        let abi = resolve(PLUGIN_ABI_SYMBOL).ok_or_else(|| {
+            format!(
+                "{} is not a plugin (no {} symbol); build it with --format dylib",
+                path.display(),
+                PLUGIN_ABI_SYMBOL
+            )
+        })?;
+        // SAFETY: a plugin's `ot_plugin_abi` is a `uint32_t`
+        let abi = unsafe { *(abi as *const u32) };
+        check_compatible(abi).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok(Self { path, resolve })
+    }
+
+    /// Open the shared library at `path`
+    #[cfg(unix)]
+    pub fn open(path: &Path) -> Result<Self, String> {
+        use std::ffi::{CStr, CString};
+        use std::os::unix::ffi::OsStrExt;
+
+        let c_path = CString::new(path.as_os_str().as_bytes())
+            .map_err(|_| format!("invalid plugin path {}", path.display()))?;
+        // SAFETY: c_path is a valid C string; the handle is never closed
+        let handle = unsafe { libc::dlopen(c_path.as_ptr(), libc::RTLD_NOW | libc::RTLD_LOCAL) };
+        if handle.is_null() {
+            // SAFETY: dlerror returns a C string describing the last failure
+            let reason = unsafe {
+                let err = libc::dlerror();
+                if err.is_null() {
+                    "unknown error".to_string()
+                } else {
+                    CStr::from_ptr(err).to_string_lossy().into_owned()
+                }
+            };
+            return Err(format!(
+                "failed to load plugin {}: {}",
+                path.display(),
+                reason
+            ));
+        }
+        // The handle is an address, which is Send; the library stays loaded
+        let handle = handle as usize;
+        let resolve = move |symbol: &str| {
+            let symbol = CString::new(symbol).ok()?;
+            // SAFETY: handle came from a successful dlopen and is never closed
+            let ptr = unsafe { libc::dlsym(handle as *mut c_void, symbol.as_ptr()) };
+            (!ptr.is_null()).then_some(ptr as *const c_void)
+        };
+        Self::from_resolver(path.to_path_buf(), Box::new(resolve))
+    }
+
+    /// Open the shared library at `path`
+    #[cfg(not(unix))]
+    pub fn open(path: &Path) -> Result<Self, String> {
+        Err(format!(
+            "failed to load plugin {}: plugins are only supported on Unix so far",
+            path.display()
+        ))
+    }
+
+    /// Entry point of the exported function `name`
+    pub fn entry(&self, name: &str) -> Result<ExportFn, String> {
+        let entry = (self.resolve)(&export_symbol(name)).ok_or_else(|| {
+            format!(
+                "plugin {} does not export a function named '{}'",
+                self.path.display(),
+                name
+            )
+        })?;
+        // SAFETY: every `ot_export_*` symbol of a plugin has the ExportFn
+        // signature, and the library is never unloaded
+        Ok(unsafe { std::mem::transmute::<*const c_void, ExportFn>(entry) })
+    }
+
+    /// Call the exported function `name` with NaN-boxed arguments
+    pub fn call(&self, name: &str, args: &[u64]) -> Result<u64, String> {
+        let entry = self.entry(name)?;
+        Ok(entry(args.len() as u64, args.as_ptr()))
+    }
+}
+
+/// Plugins loaded by this process; a plugin's handle is its index
+static PLUGINS: Mutex<Vec<Plugin>> = Mutex::new(Vec::new());
+
+/// Load the plugin at `path`, or find it if it is already loaded, and
+/// return its handle
+pub fn load(path: &Path) -> Result<usize, String> {
+    let path = std::fs::canonicalize(path)
+        .map_err(|e| format!("failed to load plugin {}: {}", path.display(), e))?;
+    let mut plugins = PLUGINS.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(handle) = plugins.iter().position(|plugin| plugin.path == path) {
+        return Ok(handle);
+    }
+    plugins.push(Plugin::open(&path)?);
+    Ok(plugins.len() - 1)
+}
+
+/// Register an already opened plugin and return its handle
+pub fn register(plugin: Plugin) -> usize {
+    let mut plugins = PLUGINS.lock().unwrap_or_else(|e| e.into_inner());
+    plugins.push(plugin);
+    plugins.len() - 1
+}
+
+/// Call `name` in the plugin with handle `handle`
+pub fn call(handle: usize, name: &str, args: &[u64]) -> Result<u64, String> {
+    // Look the entry point up under the lock but call it outside, so a
+    // plugin may load or call other plugins itself
+    let entry = {
+        let plugins = PLUGINS.lock().unwrap_or_else(|e| e.into_inner());
+        plugins
+            .get(handle)
+            .ok_or_else(|| format!("{} is not a loaded plugin", handle))?
+            .entry(name)?
+    };
+    Ok(entry(args.len() as u64, args.as_ptr()))
+}
+
+/// Handle of a plugin as a script sees it
+pub fn handle_value(handle: usize) -> OtValue {
+    OtValue::number(handle as f64)
+}
+
+/// The plugin handle a script passed back, if it is one
+pub fn handle_of(value: OtValue) -> Option<usize> {
+    let n = value.as_number()?;
+    (n >= 0.0 && n.fract() == 0.0).then_some(n as usize)
+}
+
+/// The text of a string value
+fn string_of(value: OtValue) -> Option<&'static str> {
+    let ptr = value.as_pointer()?;
+    // SAFETY: pointers in OtValues point at runtime heap objects, which
+    // start with an ObjectHeader and are never freed while referenced
+    unsafe {
+        (ptr.as_ref::<ObjectHeader>().kind == ObjectKind::String)
+            .then(|| ptr.as_ref::<NativeString>().as_str())
+    }
+}
+
+/// `runtime.loadPlugin(path)`: the plugin's handle, or undefined (with the
+/// reason on stderr) if it can't be loaded
+#[unsafe(no_mangle)]
+pub extern "C" fn ot_plugin_load(path: u64) -> u64 {
+    let Some(path) = string_of(OtValue::from_bits(path)) else {
+        eprintln!("Error: runtime.loadPlugin expects a path");
+        return OtValue::undefined().to_bits();
+    };
+    match load(Path::new(path)) {
+        Ok(handle) => handle_value(handle).to_bits(),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            OtValue::undefined().to_bits()
+        }
+    }
+}
+
+/// `runtime.callPlugin(plugin, name, ...args)`: the function's result, or
+/// undefined (with the reason on stderr) if it can't be called
+#[unsafe(no_mangle)]
+pub extern "C" fn ot_plugin_call(plugin: u64, name: u64, argc: u64, argv: *const u64) -> u64 {
+    let (Some(handle), Some(name)) = (
+        handle_of(OtValue::from_bits(plugin)),
+        string_of(OtValue::from_bits(name)),
+    ) else {
+        eprintln!("Error: runtime.callPlugin expects a plugin and a function name");
+        return OtValue::undefined().to_bits();
+    };
+    let args = if argv.is_null() || argc == 0 {
+        &[][..]
+    } else {
+        // SAFETY: generated code passes `argc` values at `argv`
+        unsafe { std::slice::from_raw_parts(argv, argc as usize) }
+    };
+    call(handle, name, args).unwrap_or_else(|e| {
+        eprintln!("Error: {}", e);
+        OtValue::undefined().to_bits()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static ABI: u32 = super::super::ABI_VERSION;
+    static TOO_NEW: u32 = super::super::ABI_VERSION + 1;
+
+    extern "C" fn ot_export_add(argc: u64, argv: *const u64) -> u64 {
+        let args = unsafe { std::slice::from_raw_parts(argv, argc as usize) };
+        let arg = |i: usize| {
+            args.get(i)
+                .map(|bits| OtValue::from_bits(*bits))
+                .and_then(OtValue::as_number)
+                .unwrap_or(f64::NAN)
+        };
+        OtValue::number(arg(0) + arg(1)).to_bits()
+    }
+
+    fn plugin(abi: &'static u32) -> Result<Plugin, String> {
+        let abi = abi as *const u32 as usize;
+        let add = ot_export_add as ExportFn as usize;
+        Plugin::from_resolver(
+            PathBuf::from("libmath.so"),
+            Box::new(move |symbol| match symbol {
+                PLUGIN_ABI_SYMBOL => Some(abi as *const c_void),
+                "ot_export_add" => Some(add as *const c_void),
+                _ => None,
+            }),
+        )
+    }
+
+    #[test]
+    fn test_calls_exported_functions() {
+        let plugin = plugin(&ABI).unwrap();
+        let args = [
+            OtValue::number(2.0).to_bits(),
+            OtValue::number(3.5).to_bits(),
+        ];
+        let sum = plugin.call("add", &args).unwrap();
+        assert_eq!(OtValue::from_bits(sum).as_number(), Some(5.5));
+        assert!(plugin.call("sub", &args).unwrap_err().contains("'sub'"));
+
+        let handle = register(plugin);
+        assert_eq!(handle_of(handle_value(handle)), Some(handle));
+        let sum = call(handle, "add", &args[..1]).unwrap();
+        assert!(OtValue::from_bits(sum).as_number().unwrap().is_nan());
+        assert!(call(usize::MAX, "add", &args).is_err());
+    }
+
+    #[test]
+    fn test_stubs_report_bad_arguments() {
+        let undefined = OtValue::undefined().to_bits();
+        assert_eq!(ot_plugin_load(OtValue::number(1.0).to_bits()), undefined);
+        let name = super::super::stubs::ot_alloc_string(b"add".as_ptr(), 3);
+        let handle = register(plugin(&ABI).unwrap());
+        let args = [
+            OtValue::number(4.0).to_bits(),
+            OtValue::number(1.0).to_bits(),
+        ];
+        let sum = ot_plugin_call(handle_value(handle).to_bits(), name, 2, args.as_ptr());
+        assert_eq!(OtValue::from_bits(sum).as_number(), Some(5.0));
+        assert_eq!(
+            ot_plugin_call(undefined, name, 0, std::ptr::null()),
+            undefined
+        );
+    }
+
+    #[test]
+    fn test_rejects_incompatible_plugins() {
+        let err = plugin(&TOO_NEW).err().unwrap();
+        assert!(err.contains("libmath.so"), "{}", err);
+        let err = Plugin::from_resolver(PathBuf::from("liba.so"), Box::new(|_| None))
+            .err()
+            .unwrap();
+        assert!(err.contains("--format dylib"), "{}", err);
+        assert!(load(Path::new("/nonexistent/libplugin.so")).is_err());
+    }
+}
@@ -0,0 +1,142 @@
+//! Safepoints for preempting native code
+//!
+//! Compiled loops load the interrupt flag on every iteration, and compiled
+//! code loads it again after every call, only calling `ot_safepoint` when it
+//! is non-zero, so a poll that finds nothing to do costs one load and a
+//! branch. Anything that needs machine code to stop or pause sets a bit in
+//! the flag:
+//! - `INTERRUPT_STOP`: Ctrl-C or an expired execution time limit. The
+//!   compiled function returns `undefined` at its next safepoint, and each
+//!   compiled caller returns `undefined` as soon as that call comes back,
+//!   until the host clears the bit with `take_stop`.
+//! - `INTERRUPT_GC`: a collection was requested. The handler registered with
+//!   `set_gc_handler` runs inside the safepoint, then execution resumes.
+//!
+//! Like the native heap, the flag is per thread: compiled code polls the flag
+//! of the thread it was compiled on. The flag itself lives forever, so other
+//! threads (a watchdog, a signal handler) can hold on to it.
+
+use std::cell::Cell;
+use std::sync::atomic::{AtomicPtr, AtomicU32, Ordering};
+use std::time::Duration;
+
+/// Unwind out of compiled code
+pub const INTERRUPT_STOP: u32 = 1 << 0;
+/// Pause compiled code to run the garbage collector
+pub const INTERRUPT_GC: u32 = 1 << 1;
+
+thread_local! {
+    static INTERRUPT: &'static AtomicU32 = Box::leak(Box::new(AtomicU32::new(0)));
+    static GC_HANDLER: Cell<Option<fn()>> = const { Cell::new(None) };
+}
+
+/// Flag Ctrl-C sets, i.e. that of the thread that installed the handler
+static SIGINT_TARGET: AtomicPtr<AtomicU32> = AtomicPtr::new(std::ptr::null_mut());
+
+/// The current thread's interrupt flag
+pub fn interrupt_flag() -> &'static AtomicU32 {
+    INTERRUPT.with(|flag| *flag)
+}
+
+/// Address of the current thread's interrupt flag, embedded into compiled code
+pub fn interrupt_flag_addr() -> *const u32 {
+    interrupt_flag().as_ptr()
+}
+
+/// Ask compiled code on this thread to handle `bits` at its next safepoint
+pub fn request(bits: u32) {
+    interrupt_flag().fetch_or(bits, Ordering::SeqCst);
+}
+
+/// Currently requested interrupt bits
+pub fn pending() -> u32 {
+    interrupt_flag().load(Ordering::SeqCst)
+}
+
+/// Clear the stop request, returning whether there was one.
+pub fn take_stop() -> bool {
+    interrupt_flag().fetch_and(!INTERRUPT_STOP, Ordering::SeqCst) & INTERRUPT_STOP != 0
+}
+
+/// Run `handler` whenever a safepoint on this thread sees a GC request
+pub fn set_gc_handler(handler: fn()) {
+    GC_HANDLER.with(|cell| cell.set(Some(handler)));
+}
+
+/// Request a stop on this thread once `limit` has passed.
+pub fn set_time_limit(limit: Duration) {
+    let flag = interrupt_flag();
+    std::thread::spawn(move || {
+        std::thread::sleep(limit);
+        flag.fetch_or(INTERRUPT_STOP, Ordering::SeqCst);
+    });
+}
+
+/// Turn Ctrl-C into a stop request for this thread. A second Ctrl-C while
+/// the first is still pending exits immediately, in case the running code
+/// never reaches a safepoint.
+#[cfg(unix)]
+pub fn install_sigint_handler() {
+    extern "C" fn on_sigint(_signal: libc::c_int) {
+        let flag = SIGINT_TARGET.load(Ordering::SeqCst);
+        // Safety: the pointer comes from a leaked, never-freed flag
+        let was_pending = !flag.is_null()
+            && unsafe { &*flag }.fetch_or(INTERRUPT_STOP, Ordering::SeqCst) & INTERRUPT_STOP != 0;
+        if flag.is_null() || was_pending {
+            unsafe { libc::_exit(130) };
+        }
+    }
+    SIGINT_TARGET.store(
+        interrupt_flag() as *const AtomicU32 as *mut AtomicU32,
+        Ordering::SeqCst,
+    );
+    unsafe {
+        libc::signal(
+            libc::SIGINT,
+            on_sigint as extern "C" fn(libc::c_int) as libc::sighandler_t,
+        );
+    }
+}
+
+#[cfg(not(unix))]
+pub fn install_sigint_handler() {}
+
+/// Slow path of a safepoint poll, called when the interrupt flag is non-zero.
+///
+/// Services a GC request, then returns 1 if the caller must stop (return
+/// `undefined`) or 0 to continue.
+#[unsafe(no_mangle)]
+pub extern "C" fn ot_safepoint() -> u64 {
+    let flag = interrupt_flag();
+    if flag.fetch_and(!INTERRUPT_GC, Ordering::SeqCst) & INTERRUPT_GC != 0
+        && let Some(handler) = GC_HANDLER.with(Cell::get)
+    {
+        handler();
+    }
+    (flag.load(Ordering::SeqCst) & INTERRUPT_STOP != 0) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    thread_local! {
+        static COLLECTIONS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    #[test]
+    fn test_safepoint_services_gc_and_reports_stop() {
+        set_gc_handler(|| COLLECTIONS.with(|n| n.set(n.get() + 1)));
+        request(INTERRUPT_GC);
+        assert_eq!(ot_safepoint(), 0);
+        assert_eq!(COLLECTIONS.with(Cell::get), 1);
+        assert_eq!(pending(), 0);
+
+        request(INTERRUPT_STOP);
+        assert_eq!(ot_safepoint(), 1);
+        assert!(take_stop());
+        assert!(!take_stop());
+        assert_eq!(ot_safepoint(), 0);
+        assert_eq!(COLLECTIONS.with(Cell::get), 1);
+    }
+}
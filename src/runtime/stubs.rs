@@ -14,8 +14,10 @@
 
 use super::abi::OtValue;
 use super::heap::{
-    NativeArray, NativeObject, NativeString, ObjectHeader, ObjectKind, PropertyMap, heap,
+    ElementKind, NativeArray, NativeObject, NativeString, NativeTypedArray, ObjectHeader,
+    ObjectKind, PropertyMap, heap,
 };
+use super::number::number_to_js_string;
 
 // =========================================================================
 // Allocation Stubs
@@ -264,6 +266,109 @@ pub extern "C" fn ot_set_element(arr: u64, index: usize, value: u64) {
     }
 }
 
+// =========================================================================
+// Typed Array Stubs
+// =========================================================================
+
+/// Allocate a zero-filled typed array.
+///
+/// `kind` is an `ElementKind` discriminant. Returns undefined on failure.
+#[unsafe(no_mangle)]
+pub extern "C" fn ot_alloc_typed_array(kind: u8, len: usize) -> u64 {
+    let Some(kind) = ElementKind::from_u8(kind) else {
+        return OtValue::undefined().to_bits();
+    };
+    match heap().alloc_typed_array(kind, len) {
+        Some(ptr) => OtValue::pointer(ptr).to_bits(),
+        None => OtValue::undefined().to_bits(),
+    }
+}
+
+/// Resolve a NaN-boxed index to an element offset, if it is an integer.
+fn typed_index(index: u64) -> Option<usize> {
+    let n = OtValue::from_bits(index).as_number()?;
+    if n >= 0.0 && n.fract() == 0.0 && n < u32::MAX as f64 {
+        Some(n as usize)
+    } else {
+        None
+    }
+}
+
+/// Resolve a NaN-boxed value to a typed array, if it is one.
+fn as_typed_array(arr: u64) -> Option<&'static mut NativeTypedArray> {
+    let ptr = OtValue::from_bits(arr).as_pointer()?;
+    unsafe {
+        if ptr.as_ref::<ObjectHeader>().kind != ObjectKind::TypedArray {
+            return None;
+        }
+        Some(ptr.as_mut::<NativeTypedArray>())
+    }
+}
+
+/// Bounds-checked typed array load (`arr[index]`).
+///
+/// Returns undefined for out-of-bounds or non-integer indices, and when
+/// `arr` is not a typed array.
+#[unsafe(no_mangle)]
+pub extern "C" fn ot_typed_array_get(arr: u64, index: u64) -> u64 {
+    match (as_typed_array(arr), typed_index(index)) {
+        (Some(arr), Some(i)) => match arr.get(i) {
+            Some(n) => OtValue::number(n).to_bits(),
+            None => OtValue::undefined().to_bits(),
+        },
+        _ => OtValue::undefined().to_bits(),
+    }
+}
+
+/// Bounds-checked typed array store (`arr[index] = value`).
+///
+/// Out-of-bounds writes are ignored, as in JavaScript.
+#[unsafe(no_mangle)]
+pub extern "C" fn ot_typed_array_set(arr: u64, index: u64, value: u64) {
+    if let (Some(arr), Some(i)) = (as_typed_array(arr), typed_index(index)) {
+        let n = OtValue::from_bits(ot_to_number(value))
+            .as_number()
+            .unwrap_or(f64::NAN);
+        arr.set(i, n);
+    }
+}
+
+/// Length of a typed array as a NaN-boxed number (0 for non-typed-arrays).
+#[unsafe(no_mangle)]
+pub extern "C" fn ot_typed_array_len(arr: u64) -> u64 {
+    let len = as_typed_array(arr).map_or(0, |a| a.len);
+    OtValue::number(len as f64).to_bits()
+}
+
+/// The lanes of a SIMD vector operand, as a typed array of `kind` with
+/// `lanes` elements that compiled code loads the vector from.
+///
+/// That is `value` itself when it already is one. Otherwise the first
+/// `lanes` elements of an array or typed array are converted to numbers
+/// and copied into a new one; missing lanes read as NaN. Returns undefined when the
+/// allocation fails.
+#[unsafe(no_mangle)]
+pub extern "C" fn ot_simd_lanes(value: u64, kind: u8, lanes: usize) -> u64 {
+    if let Some(arr) = as_typed_array(value)
+        && arr.kind as u8 == kind
+        && arr.len as usize == lanes
+    {
+        return value;
+    }
+    let copy = ot_alloc_typed_array(kind, lanes);
+    let Some(target) = as_typed_array(copy) else {
+        return copy;
+    };
+    for i in 0..lanes {
+        let lane = match as_typed_array(value) {
+            Some(source) => source.get(i),
+            None => OtValue::from_bits(ot_to_number(ot_get_element(value, i))).as_number(),
+        };
+        target.set(i, lane.unwrap_or(f64::NAN));
+    }
+    copy
+}
+
 // =========================================================================
 // Dynamic Dispatch Stubs (for 'any' typed operations)
 // =========================================================================
@@ -703,19 +808,7 @@ pub extern "C" fn ot_console_log(value: u64) {
 /// Convert a OtValue to a string representation.
 fn value_to_string(val: OtValue) -> String {
     if val.is_number() {
-        let n = val.as_number_unchecked();
-        if n.is_nan() {
-            return "NaN".to_string();
-        }
-        if n.is_infinite() {
-            return if n.is_sign_positive() {
-                "Infinity"
-            } else {
-                "-Infinity"
-            }
-            .to_string();
-        }
-        return format!("{}", n);
+        return number_to_js_string(val.as_number_unchecked());
     }
 
     if val.is_boolean() {
@@ -760,6 +853,14 @@ fn value_to_string(val: OtValue) -> String {
                 ObjectKind::ByteStream => {
                     return "[ByteStream]".to_string();
                 }
+                ObjectKind::TypedArray => {
+                    let arr = ptr.as_ref::<NativeTypedArray>();
+                    let parts: Vec<String> = (0..arr.len as usize)
+                        .filter_map(|i| arr.get(i))
+                        .map(number_to_js_string)
+                        .collect();
+                    return parts.join(",");
+                }
             }
         }
     }
@@ -977,4 +1078,47 @@ mod tests {
 
         assert_eq!(OtValue::from_bits(retrieved).as_number(), Some(42.0));
     }
+
+    #[test]
+    fn test_typed_array_stubs() {
+        let arr = ot_alloc_typed_array(ElementKind::Float64 as u8, 3);
+        let num = |n: f64| OtValue::number(n).to_bits();
+
+        ot_typed_array_set(arr, num(1.0), num(2.5));
+        ot_typed_array_set(arr, num(3.0), num(9.0));
+        assert_eq!(
+            OtValue::from_bits(ot_typed_array_get(arr, num(1.0))).as_number(),
+            Some(2.5)
+        );
+        assert!(OtValue::from_bits(ot_typed_array_get(arr, num(3.0))).is_undefined());
+        assert!(OtValue::from_bits(ot_typed_array_get(arr, num(0.5))).is_undefined());
+        assert_eq!(
+            OtValue::from_bits(ot_typed_array_len(arr)).as_number(),
+            Some(3.0)
+        );
+    }
+
+    #[test]
+    fn test_simd_lanes_reuse_or_copy() {
+        let kind = ElementKind::Float32 as u8;
+        let vector = ot_alloc_typed_array(kind, 4);
+        assert_eq!(ot_simd_lanes(vector, kind, 4), vector);
+
+        // Lanes of another element type or length are converted
+        let wide = ot_alloc_typed_array(ElementKind::Float64 as u8, 4);
+        let num = |n: f64| OtValue::number(n).to_bits();
+        ot_typed_array_set(wide, num(0.0), num(0.1));
+        let lanes = ot_simd_lanes(wide, kind, 4);
+        assert_ne!(lanes, wide);
+        assert_eq!(
+            OtValue::from_bits(ot_typed_array_get(lanes, num(0.0))).as_number(),
+            Some(0.1f32 as f64)
+        );
+
+        let arr = ot_alloc_array(2);
+        ot_set_element(arr, 0, num(7.0));
+        let lanes = ot_simd_lanes(arr, ElementKind::Int32 as u8, 4);
+        let lane = |i: f64| OtValue::from_bits(ot_typed_array_get(lanes, num(i))).as_number();
+        assert_eq!((lane(0.0), lane(1.0)), (Some(7.0), Some(0.0)));
+    }
 }
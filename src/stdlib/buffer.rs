@@ -0,0 +1,127 @@
+//! Byte buffers (`fs.mmap`, `buf.subarray`)
+//!
+//! A buffer is a view into shared storage, either owned bytes or a
+//! memory-mapped file. `subarray` and `slice` return new views of the same
+//! storage, so cutting a large mapped file into records copies nothing; bytes
+//! are only copied when a view is turned into a string.
+//!
+//! `Buffer.from`, `Buffer.alloc` and `Buffer.concat` (encoding.rs) make
+//! buffers with owned bytes.
+
+use super::encoding::Encoding;
+use crate::platform::MappedFile;
+use crate::vm::VM;
+use crate::vm::permissions::Capability;
+use crate::vm::value::{BufferStorage, BufferView, HeapData, HeapObject, JsValue};
+use std::path::Path;
+
+/// fs.mmap(path) - a read-only buffer over the file's contents
+pub fn native_mmap(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
+    let Some(JsValue::String(path)) = args.first() else {
+        eprintln!("fs.mmap: path must be a string");
+        return JsValue::Undefined;
+    };
+    if !super::permitted(vm, Capability::Read, path) {
+        return JsValue::Undefined;
+    }
+    match MappedFile::open(Path::new(path)) {
+        Ok(file) => alloc_buffer(vm, BufferView::new(BufferStorage::Mapped(file))),
+        Err(e) => {
+            eprintln!("Error mapping file '{}': {}", path, e);
+            JsValue::Undefined
+        }
+    }
+}
+
+pub fn alloc_buffer(vm: &mut VM, view: BufferView) -> JsValue {
+    let ptr = vm.heap.len();
+    vm.heap.push(HeapObject {
+        data: HeapData::Buffer(view),
+    });
+    JsValue::Object(ptr)
+}
+
+/// Byte at `index`, or undefined when out of range
+pub fn byte_at(view: &BufferView, index: f64) -> JsValue {
+    if index < 0.0 || index.fract() != 0.0 {
+        return JsValue::Undefined;
+    }
+    view.bytes()
+        .get(index as usize)
+        .map_or(JsValue::Undefined, |b| JsValue::Number(*b as f64))
+}
+
+/// Run a buffer method; `None` if there is no method called `name`
+pub fn call_method(
+    vm: &mut VM,
+    view: &BufferView,
+    name: &str,
+    args: &[JsValue],
+) -> Option<JsValue> {
+    let result = match name {
+        "subarray" | "slice" => {
+            let start = relative_index(args.first(), view.len, 0);
+            let end = relative_index(args.get(1), view.len, view.len);
+            alloc_buffer(vm, view.subarray(start, end))
+        }
+        "toString" => {
+            let encoding = match Encoding::from_arg(args.first()) {
+                Ok(encoding) => encoding,
+                Err(e) => {
+                    eprintln!("buf.toString: {}", e);
+                    return Some(JsValue::Undefined);
+                }
+            };
+            let start = relative_index(args.get(1), view.len, 0);
+            let end = relative_index(args.get(2), view.len, view.len);
+            JsValue::String(encoding.encode(view.subarray(start, end).bytes()))
+        }
+        "indexOf" => {
+            let from = relative_index(args.get(1), view.len, 0);
+            let haystack = &view.bytes()[from.min(view.len)..];
+            let found = match args.first() {
+                Some(JsValue::Number(byte)) => haystack.iter().position(|b| *b as f64 == *byte),
+                Some(JsValue::String(needle)) if needle.is_empty() => Some(0),
+                Some(JsValue::String(needle)) => haystack
+                    .windows(needle.len())
+                    .position(|window| window == needle.as_bytes()),
+                _ => None,
+            };
+            JsValue::Number(found.map_or(-1.0, |i| (from + i) as f64))
+        }
+        _ => return None,
+    };
+    Some(result)
+}
+
+/// Resolve a `subarray`-style index: negative counts from the end, missing
+/// or non-numeric uses `default`, and the result is clamped to `0..=len`.
+fn relative_index(arg: Option<&JsValue>, len: usize, default: usize) -> usize {
+    match arg {
+        Some(JsValue::Number(n)) if !n.is_nan() => {
+            let n = n.trunc();
+            if n < 0.0 {
+                (len as f64 + n).max(0.0) as usize
+            } else {
+                n.min(len as f64) as usize
+            }
+        }
+        _ => default,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relative_index() {
+        let n = |v: f64| JsValue::Number(v);
+        assert_eq!(relative_index(None, 10, 7), 7);
+        assert_eq!(relative_index(Some(&n(3.9)), 10, 0), 3);
+        assert_eq!(relative_index(Some(&n(-3.0)), 10, 0), 7);
+        assert_eq!(relative_index(Some(&n(-30.0)), 10, 0), 0);
+        assert_eq!(relative_index(Some(&n(30.0)), 10, 0), 10);
+        assert_eq!(relative_index(Some(&JsValue::Undefined), 10, 10), 10);
+    }
+}
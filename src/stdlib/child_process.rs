@@ -0,0 +1,527 @@
+//! Child processes (`process.spawn`)
+//!
+//! `process.spawn(command, args?, options?)` starts a program without
+//! waiting for it and returns a handle:
+//!
+//! - `pid`, and `exitCode` (`null` while it runs)
+//! - `stdin`, a writable stream, and `stdout`/`stderr`, readable streams,
+//!   for the pipes the options ask for
+//! - `wait()`, a promise of `{ code, signal, stdout, stderr }`: it closes
+//!   stdin and, on a helper thread, collects whatever output no listener
+//!   consumed and waits for the exit, settling the promise from the event
+//!   loop
+//! - `on("exit", fn)`, called from the event loop with the exit code and
+//!   signal; the loop keeps running until an awaited child has exited, and
+//!   on Unix a watcher thread wakes it when that happens
+//! - `kill()`
+//!
+//! Options: `cwd`, `env` (added to the inherited environment), `input` (a
+//! string written to stdin, which is then closed) and `stdout`/`stderr`, each
+//! `"pipe"` (the default), `"inherit"` or `"ignore"`. Spawning needs the run
+//! permission for the command.
+
+use super::stream::{self, DEFAULT_CHUNK_SIZE};
+use crate::vm::permissions::Capability;
+use crate::vm::value::{HeapData, HeapObject, JsValue, Promise};
+use crate::vm::{TaskPriority, VM};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::process::{Child, Command, ExitStatus, Stdio};
+
+pub struct ChildEntry {
+    child: Child,
+    stdin: JsValue,
+    stdout: JsValue,
+    stderr: JsValue,
+    status: Option<ExitStatus>,
+    on_exit: Option<JsValue>,
+}
+
+/// Spawned children by id
+#[derive(Default)]
+pub struct ProcessTable {
+    children: HashMap<usize, ChildEntry>,
+    next_id: usize,
+}
+
+impl ProcessTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether the event loop has to wait for a child's exit
+    pub fn awaiting_exit(&self) -> bool {
+        self.children
+            .values()
+            .any(|entry| entry.on_exit.is_some() && entry.status.is_none())
+    }
+}
+
+/// Parsed `process.spawn` options
+#[derive(Debug, Default, PartialEq)]
+struct SpawnOptions {
+    cwd: Option<String>,
+    env: Vec<(String, String)>,
+    input: Option<String>,
+    stdout: Pipe,
+    stderr: Pipe,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+enum Pipe {
+    #[default]
+    Piped,
+    Inherit,
+    Ignore,
+}
+
+impl Pipe {
+    fn parse(value: Option<&JsValue>) -> Result<Self, String> {
+        match value {
+            None | Some(JsValue::Undefined) => Ok(Pipe::Piped),
+            Some(JsValue::String(s)) if s == "pipe" => Ok(Pipe::Piped),
+            Some(JsValue::String(s)) if s == "inherit" => Ok(Pipe::Inherit),
+            Some(JsValue::String(s)) if s == "ignore" => Ok(Pipe::Ignore),
+            Some(other) => Err(format!(
+                "expected \"pipe\", \"inherit\" or \"ignore\", got {:?}",
+                other
+            )),
+        }
+    }
+
+    fn stdio(self) -> Stdio {
+        match self {
+            Pipe::Piped => Stdio::piped(),
+            Pipe::Inherit => Stdio::inherit(),
+            Pipe::Ignore => Stdio::null(),
+        }
+    }
+}
+
+fn object_props<'a>(vm: &'a VM, value: Option<&JsValue>) -> Option<&'a HashMap<String, JsValue>> {
+    match value {
+        Some(JsValue::Object(ptr)) => match vm.heap.get(*ptr).map(|obj| &obj.data) {
+            Some(HeapData::Object(props)) => Some(props),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn spawn_options(vm: &VM, value: Option<&JsValue>) -> Result<SpawnOptions, String> {
+    let Some(props) = object_props(vm, value) else {
+        return Ok(SpawnOptions::default());
+    };
+    let string = |key: &str| match props.get(key) {
+        None | Some(JsValue::Undefined) => Ok(None),
+        Some(JsValue::String(s)) => Ok(Some(s.clone())),
+        Some(_) => Err(format!("{} must be a string", key)),
+    };
+    let mut env: Vec<(String, String)> = object_props(vm, props.get("env"))
+        .map(|vars| {
+            vars.iter()
+                .filter_map(|(name, value)| match value {
+                    JsValue::String(s) => Some((name.clone(), s.clone())),
+                    JsValue::Number(n) => Some((name.clone(), super::number_to_string(*n))),
+                    _ => None,
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    env.sort();
+    Ok(SpawnOptions {
+        cwd: string("cwd")?,
+        env,
+        input: string("input")?,
+        stdout: Pipe::parse(props.get("stdout")).map_err(|e| format!("stdout: {}", e))?,
+        stderr: Pipe::parse(props.get("stderr")).map_err(|e| format!("stderr: {}", e))?,
+    })
+}
+
+fn command(program: &str, args: &[String], options: &SpawnOptions) -> Command {
+    let mut command = Command::new(program);
+    command
+        .args(args)
+        .envs(options.env.iter().map(|(k, v)| (k, v)))
+        .stdin(Stdio::piped())
+        .stdout(options.stdout.stdio())
+        .stderr(options.stderr.stdio());
+    if let Some(cwd) = &options.cwd {
+        command.current_dir(cwd);
+    }
+    command
+}
+
+/// process.spawn(command, args?, options?)
+pub fn native_spawn(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
+    let Some(JsValue::String(program)) = args.first() else {
+        eprintln!("process.spawn: command must be a string");
+        return JsValue::Undefined;
+    };
+    if !super::permitted(vm, Capability::Run, program) {
+        return JsValue::Undefined;
+    }
+    let argv: Vec<String> = match args.get(1) {
+        Some(JsValue::Object(ptr)) => match vm.heap.get(*ptr).map(|obj| &obj.data) {
+            Some(HeapData::Array(items)) => items
+                .iter()
+                .filter_map(|item| match item {
+                    JsValue::String(s) => Some(s.clone()),
+                    JsValue::Number(n) => Some(super::number_to_string(*n)),
+                    _ => None,
+                })
+                .collect(),
+            _ => Vec::new(),
+        },
+        _ => Vec::new(),
+    };
+    let options = match spawn_options(vm, args.get(2)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("process.spawn: {}", e);
+            return JsValue::Undefined;
+        }
+    };
+
+    let mut spawned = command(program, &argv, &options).spawn();
+    // Shell built-ins and `.cmd` shims need cmd.exe on Windows, as in exec
+    if cfg!(windows)
+        && let Err(e) = &spawned
+        && e.kind() == std::io::ErrorKind::NotFound
+    {
+        let mut shim = vec!["/C".to_string(), program.clone()];
+        shim.extend(argv);
+        spawned = command("cmd", &shim, &options).spawn();
+    }
+    let mut child = match spawned {
+        Ok(child) => child,
+        Err(e) => {
+            eprintln!("process.spawn: failed to run '{}': {}", program, e);
+            return JsValue::Undefined;
+        }
+    };
+
+    let mut stdin = child.stdin.take();
+    if let Some(input) = options.input
+        && let Some(mut pipe) = stdin.take()
+    {
+        // A writer thread, so a child that answers before reading everything
+        // can't deadlock against us
+        std::thread::spawn(move || {
+            let _ = pipe.write_all(input.as_bytes());
+        });
+    }
+    let stdin = match stdin {
+        Some(pipe) => stream::alloc_writable(vm, Box::new(pipe)),
+        None => JsValue::Undefined,
+    };
+    let stdout = match child.stdout.take() {
+        Some(pipe) => stream::alloc_readable(vm, Box::new(pipe), DEFAULT_CHUNK_SIZE, false),
+        None => JsValue::Undefined,
+    };
+    let stderr = match child.stderr.take() {
+        Some(pipe) => stream::alloc_readable(vm, Box::new(pipe), DEFAULT_CHUNK_SIZE, false),
+        None => JsValue::Undefined,
+    };
+
+    let table = &mut vm.processes;
+    let id = table.next_id;
+    table.next_id += 1;
+    table.children.insert(
+        id,
+        ChildEntry {
+            child,
+            stdin,
+            stdout,
+            stderr,
+            status: None,
+            on_exit: None,
+        },
+    );
+    let ptr = vm.heap.len();
+    vm.heap.push(HeapObject {
+        data: HeapData::Process(id),
+    });
+    JsValue::Object(ptr)
+}
+
+/// Exit code and signal of a finished child
+fn exit_values(status: &ExitStatus) -> (JsValue, JsValue) {
+    let code = status
+        .code()
+        .map_or(JsValue::Null, |code| JsValue::Number(code as f64));
+    #[cfg(unix)]
+    let signal = {
+        use std::os::unix::process::ExitStatusExt;
+        status
+            .signal()
+            .map_or(JsValue::Null, |signal| JsValue::Number(signal as f64))
+    };
+    #[cfg(not(unix))]
+    let signal = JsValue::Null;
+    (code, signal)
+}
+
+/// Queue the exit listeners of children that have finished. Called by the
+/// event loop between tasks.
+pub fn pump_exits(vm: &mut VM) {
+    if !vm.processes.awaiting_exit() {
+        return;
+    }
+    let mut exited = Vec::new();
+    for entry in vm.processes.children.values_mut() {
+        if entry.on_exit.is_none() || entry.status.is_some() {
+            continue;
+        }
+        if let Ok(Some(status)) = entry.child.try_wait() {
+            entry.status = Some(status);
+            if let Some(listener) = entry.on_exit.take() {
+                exited.push((listener, status));
+            }
+        }
+    }
+    for (listener, status) in exited {
+        let (code, signal) = exit_values(&status);
+        vm.queue_task(listener, vec![code, signal], TaskPriority::Normal);
+    }
+}
+
+/// Block until child `pid` has exited, leaving it waitable
+#[cfg(unix)]
+fn wait_for_exit(pid: u32) {
+    loop {
+        // SAFETY: waitid only writes into `info`; WNOWAIT leaves the child
+        // waitable
+        let waited = unsafe {
+            let mut info: libc::siginfo_t = std::mem::zeroed();
+            libc::waitid(
+                libc::P_PID,
+                pid as libc::id_t,
+                &mut info,
+                libc::WEXITED | libc::WNOWAIT,
+            )
+        };
+        if waited == 0 || std::io::Error::last_os_error().kind() != std::io::ErrorKind::Interrupted
+        {
+            break;
+        }
+    }
+}
+
+/// Without a way to wait on the child here, reaping it blocks instead
+#[cfg(not(unix))]
+fn wait_for_exit(_pid: u32) {}
+
+/// Wake the event loop when child `pid` exits, so it need not poll. The
+/// child is left for `pump_exits` to reap.
+#[cfg(unix)]
+fn watch_exit(vm: &mut VM, pid: u32) {
+    let completion = vm.scheduler.completion();
+    std::thread::spawn(move || {
+        wait_for_exit(pid);
+        completion.complete(pump_exits);
+    });
+}
+
+/// Without a way to wait on the child here, the event loop polls instead
+#[cfg(not(unix))]
+fn watch_exit(_vm: &mut VM, _pid: u32) {}
+
+fn child_entry(vm: &mut VM, id: usize) -> Option<&mut ChildEntry> {
+    vm.processes.children.get_mut(&id)
+}
+
+/// Property `name` of child `id`
+pub fn get_property(vm: &mut VM, id: usize, name: &str) -> JsValue {
+    let Some(entry) = child_entry(vm, id) else {
+        return JsValue::Undefined;
+    };
+    match name {
+        "pid" => JsValue::Number(entry.child.id() as f64),
+        "stdin" => entry.stdin.clone(),
+        "stdout" => entry.stdout.clone(),
+        "stderr" => entry.stderr.clone(),
+        "exitCode" => {
+            if entry.status.is_none() {
+                entry.status = entry.child.try_wait().ok().flatten();
+            }
+            match &entry.status {
+                Some(status) => exit_values(status).0,
+                None => JsValue::Null,
+            }
+        }
+        _ => JsValue::Undefined,
+    }
+}
+
+fn read_all(reader: Option<Box<dyn Read + Send>>) -> String {
+    let mut bytes = Vec::new();
+    if let Some(mut reader) = reader {
+        let _ = reader.read_to_end(&mut bytes);
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// `child.wait()`: a promise of `{ code, signal, stdout, stderr }`. The
+/// pipes are drained and the exit awaited on a helper thread; the promise
+/// settles once the event loop picks that up.
+fn wait(vm: &mut VM, id: usize) -> Result<Promise, String> {
+    let Some(entry) = child_entry(vm, id) else {
+        return Err("not a child process".to_string());
+    };
+    let (stdin, stdout, stderr) = (
+        entry.stdin.clone(),
+        entry.stdout.clone(),
+        entry.stderr.clone(),
+    );
+    let pid = entry.child.id();
+    // The child may be reading until stdin closes
+    stream::close_value(vm, &stdin)?;
+    let stdout = stream::take_reader(vm, &stdout);
+    let stderr = stream::take_reader(vm, &stderr);
+
+    let promise = Promise::new();
+    let settled = promise.clone();
+    let completion = vm.scheduler.completion();
+    std::thread::spawn(move || {
+        // Drain both pipes at once so a child filling one of them can't block
+        let stderr = std::thread::spawn(move || read_all(stderr));
+        let stdout = read_all(stdout);
+        let stderr = stderr.join().unwrap_or_default();
+        wait_for_exit(pid);
+        completion.complete(move |vm| settle_wait(vm, id, &settled, stdout, stderr));
+    });
+    Ok(promise)
+}
+
+/// Reap child `id` once `wait` saw it exit and settle `promise` with the
+/// result. An `exit` listener still hears about it.
+fn settle_wait(vm: &mut VM, id: usize, promise: &Promise, stdout: String, stderr: String) {
+    let Some(entry) = child_entry(vm, id) else {
+        promise.set_value(JsValue::String("not a child process".to_string()), false);
+        return;
+    };
+    let status = match entry.status {
+        Some(status) => status,
+        None => match entry.child.wait() {
+            Ok(status) => status,
+            Err(e) => {
+                promise.set_value(JsValue::String(e.to_string()), false);
+                return;
+            }
+        },
+    };
+    entry.status = Some(status);
+    let (code, signal) = exit_values(&status);
+    if let Some(listener) = entry.on_exit.take() {
+        vm.queue_task(
+            listener,
+            vec![code.clone(), signal.clone()],
+            TaskPriority::Normal,
+        );
+    }
+
+    let mut result = HashMap::new();
+    result.insert("code".to_string(), code);
+    result.insert("signal".to_string(), signal);
+    result.insert("stdout".to_string(), JsValue::String(stdout));
+    result.insert("stderr".to_string(), JsValue::String(stderr));
+    let ptr = vm.heap.len();
+    vm.heap.push(HeapObject {
+        data: HeapData::Object(result),
+    });
+    promise.set_value(JsValue::Object(ptr), true);
+}
+
+/// Call method `name` on the child at `ptr`. `None` when children have no
+/// such method.
+pub fn call_method(vm: &mut VM, ptr: usize, name: &str, args: &[JsValue]) -> Option<JsValue> {
+    let Some(HeapData::Process(id)) = vm.heap.get(ptr).map(|obj| &obj.data) else {
+        return None;
+    };
+    let id = *id;
+    let result = match name {
+        "wait" => match wait(vm, id) {
+            Ok(promise) => JsValue::Promise(promise),
+            Err(e) => {
+                let promise = Promise::new();
+                promise.set_value(JsValue::String(e), false);
+                JsValue::Promise(promise)
+            }
+        },
+        "kill" => {
+            let Some(entry) = child_entry(vm, id) else {
+                return Some(JsValue::Boolean(false));
+            };
+            JsValue::Boolean(entry.status.is_none() && entry.child.kill().is_ok())
+        }
+        "on" => {
+            let (Some(JsValue::String(event)), Some(listener)) = (args.first(), args.get(1)) else {
+                eprintln!("child.on: expected an event name and a function");
+                return Some(JsValue::Undefined);
+            };
+            if event != "exit" {
+                eprintln!("child.on: unknown event '{}'", event);
+                return Some(JsValue::Undefined);
+            }
+            let listener = listener.clone();
+            let Some(entry) = child_entry(vm, id) else {
+                return Some(JsValue::Undefined);
+            };
+            match entry.status {
+                // Already reaped; still report the exit, asynchronously
+                Some(status) => {
+                    let (code, signal) = exit_values(&status);
+                    vm.queue_task(listener, vec![code, signal], TaskPriority::Normal);
+                }
+                None => {
+                    entry.on_exit = Some(listener);
+                    let pid = entry.child.id();
+                    watch_exit(vm, pid);
+                }
+            }
+            JsValue::Object(ptr)
+        }
+        _ => return None,
+    };
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spawn_options() {
+        let mut vm = VM::new();
+        let mut env = HashMap::new();
+        env.insert("B".to_string(), JsValue::Number(2.0));
+        env.insert("A".to_string(), JsValue::String("x".into()));
+        let env_ptr = vm.heap.len();
+        vm.heap.push(HeapObject {
+            data: HeapData::Object(env),
+        });
+        let mut props = HashMap::new();
+        props.insert("env".to_string(), JsValue::Object(env_ptr));
+        props.insert("cwd".to_string(), JsValue::String("/tmp".into()));
+        props.insert("stderr".to_string(), JsValue::String("inherit".into()));
+        let ptr = vm.heap.len();
+        vm.heap.push(HeapObject {
+            data: HeapData::Object(props),
+        });
+
+        let options = spawn_options(&vm, Some(&JsValue::Object(ptr))).unwrap();
+        assert_eq!(
+            options,
+            SpawnOptions {
+                cwd: Some("/tmp".into()),
+                env: vec![("A".into(), "x".into()), ("B".into(), "2".into())],
+                input: None,
+                stdout: Pipe::Piped,
+                stderr: Pipe::Inherit,
+            }
+        );
+        assert_eq!(spawn_options(&vm, None), Ok(SpawnOptions::default()));
+        assert!(Pipe::parse(Some(&JsValue::String("tty".into()))).is_err());
+    }
+}
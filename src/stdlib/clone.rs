@@ -0,0 +1,356 @@
+//! Structured clone
+//!
+//! `structuredClone(value, { transfer })` copies a value in two steps:
+//! `serialize` walks it into a `Serialized` graph that holds no heap
+//! references, and `deserialize` rebuilds that graph in a heap. A
+//! `Serialized` is `Send`, so the same pair carries messages to another
+//! thread's VM. References shared within the value, cycles included, stay
+//! shared in the copy.
+//!
+//! Objects (own data properties only, so a class instance comes back as a
+//! plain object), arrays, maps, sets, buffers, byte streams and decimals
+//! are cloned. Functions, promises, accessors, weak collections, streams
+//! and processes are not: they fail the clone with a `DataCloneError`.
+//!
+//! Buffers listed in `transfer` move instead of being copied. The clone
+//! takes their bytes as they are, and every view of those bytes left in the
+//! source heap is detached: it reads as an empty buffer from then on.
+
+use crate::stdlib::decimal::Decimal;
+use crate::vm::VM;
+use crate::vm::property::is_internal_key;
+use crate::vm::value::{BufferStorage, BufferView, HeapData, HeapObject, JsValue, NativeResult};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// A `DataCloneError` for a value `structuredClone` cannot copy
+pub fn data_clone_error(message: &str) -> JsValue {
+    JsValue::String(format!("DataCloneError: {}", message))
+}
+
+/// A value serialized out of a heap, ready to be rebuilt in any VM
+#[derive(Debug)]
+pub struct Serialized {
+    root: Entry,
+    records: Vec<Record>,
+}
+
+#[derive(Debug)]
+enum Entry {
+    Number(f64),
+    String(String),
+    Boolean(bool),
+    Null,
+    Undefined,
+    /// Index into `Serialized::records`
+    Record(usize),
+}
+
+#[derive(Debug)]
+enum Record {
+    Object(Vec<(String, Entry)>),
+    Array(Vec<Entry>),
+    Map(Vec<(Entry, Entry)>),
+    Set(Vec<Entry>),
+    ByteStream(Vec<u8>),
+    /// Copied bytes, or the storage of a transferred buffer
+    Buffer(BufferView),
+    Decimal(Decimal),
+}
+
+struct Serializer<'a> {
+    vm: &'a VM,
+    records: Vec<Record>,
+    /// Record of each heap object already reached
+    seen: HashMap<usize, usize>,
+    transfer: &'a [usize],
+}
+
+impl Serializer<'_> {
+    fn entry(&mut self, value: &JsValue) -> Result<Entry, JsValue> {
+        Ok(match value {
+            JsValue::Number(n) => Entry::Number(*n),
+            JsValue::String(s) => Entry::String(s.clone()),
+            JsValue::Boolean(b) => Entry::Boolean(*b),
+            JsValue::Null => Entry::Null,
+            JsValue::Undefined => Entry::Undefined,
+            JsValue::Object(ptr) => Entry::Record(self.record(*ptr)?),
+            JsValue::Function { .. } | JsValue::NativeFunction(_) => {
+                return Err(data_clone_error("functions cannot be cloned"));
+            }
+            JsValue::Accessor(..) => return Err(data_clone_error("accessors cannot be cloned")),
+            JsValue::Promise(_) => return Err(data_clone_error("promises cannot be cloned")),
+        })
+    }
+
+    fn entries(&mut self, values: &[JsValue]) -> Result<Vec<Entry>, JsValue> {
+        values.iter().map(|value| self.entry(value)).collect()
+    }
+
+    fn record(&mut self, ptr: usize) -> Result<usize, JsValue> {
+        if let Some(&index) = self.seen.get(&ptr) {
+            return Ok(index);
+        }
+        // Reserve the record first so cycles back to `ptr` resolve to it
+        let index = self.records.len();
+        self.records.push(Record::Array(Vec::new()));
+        self.seen.insert(ptr, index);
+
+        let vm = self.vm;
+        let record = match vm.heap.get(ptr).map(|obj| &obj.data) {
+            Some(HeapData::Object(props)) => Record::Object(
+                props
+                    .iter()
+                    .filter(|(key, _)| !is_internal_key(key))
+                    .map(|(key, value)| Ok((key.clone(), self.entry(value)?)))
+                    .collect::<Result<_, JsValue>>()?,
+            ),
+            Some(HeapData::Array(items)) => Record::Array(self.entries(items)?),
+            Some(HeapData::Map(entries)) => Record::Map(
+                entries
+                    .iter()
+                    .map(|(k, v)| Ok((self.entry(k)?, self.entry(v)?)))
+                    .collect::<Result<_, JsValue>>()?,
+            ),
+            Some(HeapData::Set(items)) => Record::Set(self.entries(items)?),
+            Some(HeapData::ByteStream(bytes)) => Record::ByteStream(bytes.clone()),
+            Some(HeapData::Buffer(view)) if self.transfer.contains(&ptr) => {
+                Record::Buffer(view.clone())
+            }
+            // The copy owns its bytes, even when the original views a mapped file
+            Some(HeapData::Buffer(view)) => {
+                Record::Buffer(BufferView::new(BufferStorage::Owned(view.bytes().to_vec())))
+            }
+            Some(HeapData::Decimal(value)) => Record::Decimal(value.clone()),
+            Some(HeapData::WeakMap(_)) => {
+                return Err(data_clone_error("a WeakMap cannot be cloned"));
+            }
+            Some(HeapData::WeakSet(_)) => {
+                return Err(data_clone_error("a WeakSet cannot be cloned"));
+            }
+            Some(HeapData::WeakRef(_)) => {
+                return Err(data_clone_error("a WeakRef cannot be cloned"));
+            }
+            Some(HeapData::Stream(_)) => return Err(data_clone_error("a stream cannot be cloned")),
+            Some(HeapData::Process(_)) => {
+                return Err(data_clone_error("a child process cannot be cloned"));
+            }
+            None => Record::Object(Vec::new()),
+        };
+        self.records[index] = record;
+        Ok(index)
+    }
+}
+
+/// Heap pointers of the buffers in a `transfer` list
+fn transfer_list(vm: &VM, transfer: &[JsValue]) -> Result<Vec<usize>, JsValue> {
+    let mut ptrs = Vec::with_capacity(transfer.len());
+    for value in transfer {
+        let ptr = match value {
+            JsValue::Object(ptr)
+                if matches!(
+                    vm.heap.get(*ptr).map(|obj| &obj.data),
+                    Some(HeapData::Buffer(_))
+                ) =>
+            {
+                *ptr
+            }
+            _ => return Err(data_clone_error("only buffers can be transferred")),
+        };
+        if ptrs.contains(&ptr) {
+            return Err(data_clone_error("a buffer is listed twice in transfer"));
+        }
+        ptrs.push(ptr);
+    }
+    Ok(ptrs)
+}
+
+/// Serialize `value` out of the VM's heap, moving the buffers in
+/// `transfer`. Nothing is detached unless the whole value serializes.
+pub fn serialize(
+    vm: &mut VM,
+    value: &JsValue,
+    transfer: &[JsValue],
+) -> Result<Serialized, JsValue> {
+    let transfer = transfer_list(vm, transfer)?;
+    let mut serializer = Serializer {
+        vm,
+        records: Vec::new(),
+        seen: HashMap::new(),
+        transfer: &transfer,
+    };
+    let root = serializer.entry(value)?;
+    let records = serializer.records;
+
+    // Every buffer in `transfer` is detached, reached from `value` or not,
+    // along with the other views of its bytes
+    let moved: Vec<Arc<BufferStorage>> = transfer
+        .iter()
+        .filter_map(|&ptr| match vm.heap.get(ptr).map(|obj| &obj.data) {
+            Some(HeapData::Buffer(view)) => Some(view.storage.clone()),
+            _ => None,
+        })
+        .collect();
+    let detached: Vec<usize> = vm
+        .heap
+        .iter()
+        .filter(|(_, obj)| match &obj.data {
+            HeapData::Buffer(view) => moved
+                .iter()
+                .any(|storage| Arc::ptr_eq(storage, &view.storage)),
+            _ => false,
+        })
+        .map(|(handle, _)| handle)
+        .collect();
+    for handle in detached {
+        vm.heap[handle].data = HeapData::Buffer(BufferView::new(BufferStorage::Owned(Vec::new())));
+    }
+    Ok(Serialized { root, records })
+}
+
+fn value(base: usize, entry: Entry) -> JsValue {
+    match entry {
+        Entry::Number(n) => JsValue::Number(n),
+        Entry::String(s) => JsValue::String(s),
+        Entry::Boolean(b) => JsValue::Boolean(b),
+        Entry::Null => JsValue::Null,
+        Entry::Undefined => JsValue::Undefined,
+        Entry::Record(index) => JsValue::Object(base + index),
+    }
+}
+
+/// Rebuild a serialized value in the VM's heap
+pub fn deserialize(vm: &mut VM, data: Serialized) -> JsValue {
+    let base = vm.heap.len();
+    for record in data.records {
+        let data = match record {
+            Record::Object(props) => HeapData::Object(
+                props
+                    .into_iter()
+                    .map(|(key, entry)| (key, value(base, entry)))
+                    .collect(),
+            ),
+            Record::Array(items) => {
+                HeapData::Array(items.into_iter().map(|e| value(base, e)).collect())
+            }
+            Record::Map(entries) => HeapData::Map(
+                entries
+                    .into_iter()
+                    .map(|(k, v)| (value(base, k), value(base, v)))
+                    .collect(),
+            ),
+            Record::Set(items) => {
+                HeapData::Set(items.into_iter().map(|e| value(base, e)).collect())
+            }
+            Record::ByteStream(bytes) => HeapData::ByteStream(bytes),
+            Record::Buffer(view) => HeapData::Buffer(view),
+            Record::Decimal(value) => HeapData::Decimal(value),
+        };
+        vm.heap.push(HeapObject { data });
+    }
+    value(base, data.root)
+}
+
+/// structuredClone(value, { transfer }) - copy of `value`, moving the
+/// buffers in `transfer` into it
+pub fn native_structured_clone(vm: &mut VM, args: Vec<JsValue>) -> NativeResult {
+    let mut args = args.into_iter();
+    let value = args.next().unwrap_or(JsValue::Undefined);
+    let transfer = match args.next() {
+        Some(JsValue::Object(options)) => match vm.get_prop_with_proto_chain(options, "transfer") {
+            JsValue::Undefined => Vec::new(),
+            JsValue::Object(list) => match vm.heap.get(list).map(|obj| &obj.data) {
+                Some(HeapData::Array(items)) => items.clone(),
+                _ => return Err(super::type_error("transfer must be an array")),
+            },
+            _ => return Err(super::type_error("transfer must be an array")),
+        },
+        _ => Vec::new(),
+    };
+    let data = serialize(vm, &value, &transfer)?;
+    Ok(deserialize(vm, data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn alloc(vm: &mut VM, data: HeapData) -> JsValue {
+        let ptr = vm.heap.len();
+        vm.heap.push(HeapObject { data });
+        JsValue::Object(ptr)
+    }
+
+    fn buffer_len(vm: &VM, value: &JsValue) -> usize {
+        match value {
+            JsValue::Object(ptr) => match &vm.heap[*ptr].data {
+                HeapData::Buffer(view) => view.len,
+                _ => panic!("expected a buffer"),
+            },
+            _ => panic!("expected a buffer"),
+        }
+    }
+
+    #[test]
+    fn test_serialized_values_cross_threads() {
+        let mut vm = VM::new();
+        let buf = alloc(
+            &mut vm,
+            HeapData::Buffer(BufferView::new(BufferStorage::Owned(vec![1, 2, 3]))),
+        );
+        let list = alloc(
+            &mut vm,
+            HeapData::Array(vec![JsValue::Number(1.0), buf.clone()]),
+        );
+        let JsValue::Object(list_ptr) = list else {
+            unreachable!()
+        };
+        if let HeapData::Array(items) = &mut vm.heap[list_ptr].data {
+            items.push(list.clone());
+        }
+
+        let data = serialize(&mut vm, &list, std::slice::from_ref(&buf)).unwrap();
+        assert_eq!(buffer_len(&vm, &buf), 0);
+
+        let copy = std::thread::spawn(move || {
+            let mut other = VM::new();
+            let copy = deserialize(&mut other, data);
+            let JsValue::Object(ptr) = copy else {
+                panic!("expected an array");
+            };
+            let HeapData::Array(items) = &other.heap[ptr].data else {
+                panic!("expected an array");
+            };
+            assert_eq!(items[0], JsValue::Number(1.0));
+            assert_eq!(items[2], JsValue::Object(ptr));
+            buffer_len(&other, &items[1])
+        })
+        .join()
+        .unwrap();
+        assert_eq!(copy, 3);
+    }
+
+    #[test]
+    fn test_uncloneable_values_leave_transfers_attached() {
+        let mut vm = VM::new();
+        let buf = alloc(
+            &mut vm,
+            HeapData::Buffer(BufferView::new(BufferStorage::Owned(vec![1]))),
+        );
+        let weak = alloc(&mut vm, HeapData::WeakSet(Default::default()));
+        let value = alloc(&mut vm, HeapData::Array(vec![buf.clone(), weak]));
+
+        let err = serialize(&mut vm, &value, std::slice::from_ref(&buf)).unwrap_err();
+        assert_eq!(
+            err,
+            JsValue::String("DataCloneError: a WeakSet cannot be cloned".into())
+        );
+        assert_eq!(buffer_len(&vm, &buf), 1);
+
+        let err = serialize(&mut vm, &buf, &[buf.clone(), buf.clone()]).unwrap_err();
+        assert_eq!(
+            err,
+            JsValue::String("DataCloneError: a buffer is listed twice in transfer".into())
+        );
+    }
+}
@@ -0,0 +1,539 @@
+//! Console rendering (`console.log`, `console.error`, `console.table`)
+//!
+//! A string first argument may hold format specifiers, each consuming the
+//! next argument: `%s` (as a string), `%d`/`%i` (as an integer), `%f` (as a
+//! number), `%o`/`%O` (inspected, strings quoted), `%c` (CSS, ignored) and
+//! `%%` for a literal percent sign. Arguments left over follow the formatted
+//! text as usual.
+//!
+//! Console output is for people, so it may be localized: `--locale <tag>`
+//! groups digits and picks the decimal separator the way that locale writes
+//! numbers. Data paths (`String(n)`, `n.toString()`, `JSON.stringify`) never
+//! go through here and stay spec-exact whatever the locale.
+
+use super::{number_to_string, object_entries, to_number};
+use crate::vm::VM;
+use crate::vm::value::{HeapData, JsValue, PromiseState};
+
+/// Digit grouping and decimal separator for console numbers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConsoleLocale {
+    pub group: char,
+    pub decimal: char,
+}
+
+impl ConsoleLocale {
+    /// Locale for a BCP 47 tag such as `en-US` or `de`. Only the language
+    /// subtag is consulted.
+    pub fn parse(tag: &str) -> Result<Self, String> {
+        let language = tag
+            .split(['-', '_'])
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        let (group, decimal) = match language.as_str() {
+            "en" | "ja" | "zh" | "ko" | "he" | "th" => (',', '.'),
+            "de" | "es" | "it" | "nl" | "pt" | "tr" | "id" | "da" | "el" => ('.', ','),
+            "fr" => ('\u{202f}', ','),
+            "ru" | "pl" | "cs" | "sk" | "sv" | "fi" | "nb" | "no" | "uk" | "hu" => ('\u{a0}', ','),
+            _ => {
+                return Err(format!(
+                    "unsupported locale '{}' (known languages: en, ja, zh, ko, he, th, de, es, it, nl, pt, tr, id, da, el, fr, ru, pl, cs, sk, sv, fi, nb, no, uk, hu)",
+                    tag
+                ));
+            }
+        };
+        Ok(Self { group, decimal })
+    }
+
+    /// `n` with this locale's separators. Digits are the spec-exact ones;
+    /// nothing is rounded.
+    pub fn format_number(&self, n: f64) -> String {
+        let plain = number_to_string(n);
+        // NaN, Infinity and exponent notation have no digits to group
+        if !n.is_finite() || plain.contains('e') {
+            return plain.replace('.', &self.decimal.to_string());
+        }
+        let (sign, unsigned) = match plain.strip_prefix('-') {
+            Some(rest) => ("-", rest),
+            None => ("", plain.as_str()),
+        };
+        let (int, frac) = match unsigned.split_once('.') {
+            Some((int, frac)) => (int, Some(frac)),
+            None => (unsigned, None),
+        };
+
+        let mut out = String::from(sign);
+        for (i, digit) in int.chars().enumerate() {
+            if i > 0 && (int.len() - i) % 3 == 0 {
+                out.push(self.group);
+            }
+            out.push(digit);
+        }
+        if let Some(frac) = frac {
+            out.push(self.decimal);
+            out.push_str(frac);
+        }
+        out
+    }
+}
+
+/// How `console.log` shows one argument
+pub fn console_string(vm: &VM, value: &JsValue) -> String {
+    match value {
+        JsValue::String(s) => s.clone(),
+        _ => Inspector::new(vm, vm.console_locale, INSPECT_DEPTH).value(value, 0),
+    }
+}
+
+/// One console line for `args`, expanding format specifiers in a string
+/// first argument
+pub fn format_line(vm: &VM, args: &[JsValue]) -> String {
+    let [JsValue::String(format), rest @ ..] = args else {
+        return args
+            .iter()
+            .map(|arg| console_string(vm, arg))
+            .collect::<Vec<_>>()
+            .join(" ");
+    };
+    // Like Node, a lone string is printed as written, `%%` included
+    if rest.is_empty() {
+        return format.clone();
+    }
+    let mut rest = rest.iter();
+    let mut line = String::new();
+    let mut chars = format.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            line.push(c);
+            continue;
+        }
+        let Some(&spec) = chars.peek() else {
+            line.push(c);
+            break;
+        };
+        if spec == '%' {
+            chars.next();
+            line.push('%');
+            continue;
+        }
+        if !matches!(spec, 's' | 'd' | 'i' | 'f' | 'o' | 'O' | 'c') {
+            line.push(c);
+            continue;
+        }
+        let Some(arg) = rest.next() else {
+            // Nothing left to substitute: the specifier prints as written
+            line.push(c);
+            continue;
+        };
+        chars.next();
+        let mut inspector = Inspector::new(vm, vm.console_locale, INSPECT_DEPTH);
+        match spec {
+            's' => line.push_str(&console_string(vm, arg)),
+            'd' | 'i' | 'f' => {
+                let mut n = match arg {
+                    JsValue::Object(_) if spec != 'f' => f64::NAN,
+                    _ => to_number(vm, arg),
+                };
+                if spec == 'i' {
+                    n = n.trunc();
+                }
+                line.push_str(&inspector.value(&JsValue::Number(n), 0));
+            }
+            'o' | 'O' => line.push_str(&inspector.value(arg, 0)),
+            // %c styles the text in browsers; a terminal has nothing to apply
+            _ => {}
+        }
+    }
+    // Arguments no specifier took follow, separated by spaces
+    for arg in rest {
+        line.push(' ');
+        line.push_str(&console_string(vm, arg));
+    }
+    line
+}
+
+/// `console.table(data, columns?)` as box-drawn lines. `None` when `data`
+/// is not an object, which console.table then logs as it is.
+pub fn table(vm: &VM, data: &JsValue, columns: Option<&[String]>) -> Option<String> {
+    let JsValue::Object(ptr) = data else {
+        return None;
+    };
+    let rows: Vec<(String, JsValue)> = match &vm.heap.get(*ptr)?.data {
+        HeapData::Array(items) => items
+            .iter()
+            .enumerate()
+            .map(|(i, item)| (i.to_string(), item.clone()))
+            .collect(),
+        HeapData::Object(props) => object_entries(props)
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value.clone()))
+            .collect(),
+        _ => return None,
+    };
+
+    let mut inspector = Inspector::new(vm, vm.console_locale, 0);
+    let mut header: Vec<String> = Vec::new();
+    let mut has_values = false;
+    let mut cells: Vec<Vec<(String, String)>> = Vec::new();
+    for (_, row) in &rows {
+        let fields: Vec<(String, String)> = match row {
+            JsValue::Object(row_ptr) => match vm.heap.get(*row_ptr).map(|obj| &obj.data) {
+                Some(HeapData::Object(props)) => object_entries(props)
+                    .into_iter()
+                    .map(|(key, value)| (key.to_string(), inspector.value(value, 0)))
+                    .collect(),
+                Some(HeapData::Array(items)) => items
+                    .iter()
+                    .enumerate()
+                    .map(|(i, item)| (i.to_string(), inspector.value(item, 0)))
+                    .collect(),
+                _ => {
+                    has_values = true;
+                    Vec::new()
+                }
+            },
+            _ => {
+                has_values = true;
+                Vec::new()
+            }
+        };
+        for (key, _) in &fields {
+            if !header.contains(key) {
+                header.push(key.clone());
+            }
+        }
+        cells.push(fields);
+    }
+    if let Some(columns) = columns {
+        header = columns.to_vec();
+    }
+
+    let mut table = vec![
+        std::iter::once("(index)".to_string())
+            .chain(header.iter().cloned())
+            .chain(has_values.then(|| "Values".to_string()))
+            .collect::<Vec<_>>(),
+    ];
+    for ((index, row), fields) in rows.iter().zip(&cells) {
+        let mut line = vec![index.clone()];
+        for column in &header {
+            line.push(
+                fields
+                    .iter()
+                    .find(|(key, _)| key == column)
+                    .map(|(_, shown)| shown.clone())
+                    .unwrap_or_default(),
+            );
+        }
+        if has_values {
+            line.push(if fields.is_empty() && !is_table_row(vm, row) {
+                inspector.value(row, 0)
+            } else {
+                String::new()
+            });
+        }
+        table.push(line);
+    }
+
+    let widths: Vec<usize> = (0..table[0].len())
+        .map(|i| {
+            table
+                .iter()
+                .map(|line| line[i].chars().count())
+                .max()
+                .unwrap_or(0)
+                + 2
+        })
+        .collect();
+    let border = |left: char, middle: char, right: char| {
+        let parts: Vec<String> = widths.iter().map(|w| "─".repeat(*w)).collect();
+        format!("{}{}{}", left, parts.join(&middle.to_string()), right)
+    };
+    let row_line = |line: &[String]| {
+        let parts: Vec<String> = line
+            .iter()
+            .zip(&widths)
+            .map(|(cell, w)| format!(" {}{}", cell, " ".repeat(w - 1 - cell.chars().count())))
+            .collect();
+        format!("│{}│", parts.join("│"))
+    };
+    let mut out = vec![
+        border('┌', '┬', '┐'),
+        row_line(&table[0]),
+        border('├', '┼', '┤'),
+    ];
+    out.extend(table[1..].iter().map(|line| row_line(line)));
+    out.push(border('└', '┴', '┘'));
+    Some(out.join("\n"))
+}
+
+/// Whether a console.table row spreads over columns rather than `Values`
+fn is_table_row(vm: &VM, value: &JsValue) -> bool {
+    matches!(value, JsValue::Object(ptr) if matches!(
+        vm.heap.get(*ptr).map(|obj| &obj.data),
+        Some(HeapData::Object(_) | HeapData::Array(_))
+    ))
+}
+
+/// `label: 1.234ms` for console.time/timeLog/timeEnd
+pub fn elapsed(label: &str, elapsed: std::time::Duration) -> String {
+    let ms = elapsed.as_secs_f64() * 1000.0;
+    if ms >= 1000.0 {
+        format!("{}: {:.3}s", label, ms / 1000.0)
+    } else {
+        format!("{}: {:.3}ms", label, ms)
+    }
+}
+
+/// `util.inspect(value, depth)`: the console rendering without the locale,
+/// and with a top-level string quoted like a nested one
+pub fn inspect(vm: &VM, value: &JsValue, depth: usize) -> String {
+    Inspector::new(vm, None, depth).value(value, 0)
+}
+
+/// How many levels of nesting are shown before objects collapse to `[Object]`
+pub const INSPECT_DEPTH: usize = 2;
+
+/// Items shown per array, map or set before the rest are counted
+const MAX_ITEMS: usize = 100;
+
+/// Bytes of a buffer shown before the rest is summarized
+const MAX_BUFFER_BYTES: usize = 50;
+
+/// Longest single-line rendering before entries go one per line
+const LINE_WIDTH: usize = 72;
+
+struct Inspector<'a> {
+    vm: &'a VM,
+    locale: Option<ConsoleLocale>,
+    depth: usize,
+    /// Objects currently being printed; meeting one again is a cycle
+    seen: Vec<usize>,
+}
+
+impl<'a> Inspector<'a> {
+    fn new(vm: &'a VM, locale: Option<ConsoleLocale>, depth: usize) -> Self {
+        Self {
+            vm,
+            locale,
+            depth,
+            seen: Vec::new(),
+        }
+    }
+
+    fn value(&mut self, value: &JsValue, level: usize) -> String {
+        match value {
+            JsValue::String(s) => quote(s),
+            JsValue::Number(n) => match &self.locale {
+                Some(locale) => locale.format_number(*n),
+                None => number_to_string(*n),
+            },
+            JsValue::Boolean(b) => b.to_string(),
+            JsValue::Null => "null".to_string(),
+            JsValue::Undefined => "undefined".to_string(),
+            JsValue::Object(ptr) => self.object(*ptr, level),
+            JsValue::Function { .. } => "[Function]".to_string(),
+            JsValue::NativeFunction(_) => "[Function (native)]".to_string(),
+            JsValue::Accessor(Some(_), Some(_)) => "[Getter/Setter]".to_string(),
+            JsValue::Accessor(Some(_), None) => "[Getter]".to_string(),
+            JsValue::Accessor(None, _) => "[Setter]".to_string(),
+            JsValue::Promise(promise) => {
+                let (state, settled) = match promise.state.lock() {
+                    Ok(inner) => (inner.state.clone(), inner.value.clone()),
+                    Err(_) => return "Promise {}".to_string(),
+                };
+                let settled = settled.unwrap_or(JsValue::Undefined);
+                match state {
+                    PromiseState::Pending => "Promise { <pending> }".to_string(),
+                    PromiseState::Fulfilled => {
+                        format!("Promise {{ {} }}", self.value(&settled, level + 1))
+                    }
+                    PromiseState::Rejected => {
+                        format!(
+                            "Promise {{ <rejected> {} }}",
+                            self.value(&settled, level + 1)
+                        )
+                    }
+                }
+            }
+        }
+    }
+
+    fn object(&mut self, ptr: usize, level: usize) -> String {
+        if self.seen.contains(&ptr) {
+            return "[Circular]".to_string();
+        }
+        let vm = self.vm;
+        let Some(data) = vm.heap.get(ptr).map(|obj| &obj.data) else {
+            return "undefined".to_string();
+        };
+        if level > self.depth {
+            return match data {
+                HeapData::Object(_) => "[Object]",
+                HeapData::Array(_) => "[Array]",
+                HeapData::Map(_) => "[Map]",
+                HeapData::Set(_) => "[Set]",
+                HeapData::ByteStream(_) => "[ByteStream]",
+                HeapData::Buffer(_) => "[Buffer]",
+                HeapData::Decimal(_) => "[Decimal]",
+                HeapData::WeakMap(_) => "[WeakMap]",
+                HeapData::WeakSet(_) => "[WeakSet]",
+                HeapData::WeakRef(_) => "[WeakRef]",
+                HeapData::Stream(_) => "[Stream]",
+                HeapData::Process(_) => "[ChildProcess]",
+            }
+            .to_string();
+        }
+
+        self.seen.push(ptr);
+        let shown = match data {
+            HeapData::Object(props) => {
+                let items = object_entries(props)
+                    .into_iter()
+                    .map(|(key, value)| {
+                        format!("{}: {}", key_name(key), self.value(value, level + 1))
+                    })
+                    .collect();
+                wrap("", '{', '}', items, level)
+            }
+            HeapData::Array(items) => {
+                let mut shown: Vec<String> = items
+                    .iter()
+                    .take(MAX_ITEMS)
+                    .map(|item| self.value(item, level + 1))
+                    .collect();
+                push_more(&mut shown, items.len());
+                wrap("", '[', ']', shown, level)
+            }
+            HeapData::Map(entries) => {
+                let mut shown: Vec<String> = entries
+                    .iter()
+                    .take(MAX_ITEMS)
+                    .map(|(k, v)| {
+                        format!(
+                            "{} => {}",
+                            self.value(k, level + 1),
+                            self.value(v, level + 1)
+                        )
+                    })
+                    .collect();
+                push_more(&mut shown, entries.len());
+                wrap(&format!("Map({}) ", entries.len()), '{', '}', shown, level)
+            }
+            HeapData::Set(items) => {
+                let mut shown: Vec<String> = items
+                    .iter()
+                    .take(MAX_ITEMS)
+                    .map(|item| self.value(item, level + 1))
+                    .collect();
+                push_more(&mut shown, items.len());
+                wrap(&format!("Set({}) ", items.len()), '{', '}', shown, level)
+            }
+            HeapData::ByteStream(bytes) => format!("ByteStream({})", bytes.len()),
+            HeapData::Buffer(view) => {
+                let mut shown: Vec<String> = view
+                    .bytes()
+                    .iter()
+                    .take(MAX_BUFFER_BYTES)
+                    .map(|b| format!("{:02x}", b))
+                    .collect();
+                if view.len > MAX_BUFFER_BYTES {
+                    shown.push(format!("... {} more bytes", view.len - MAX_BUFFER_BYTES));
+                }
+                format!("<Buffer {}>", shown.join(" "))
+            }
+            HeapData::Decimal(value) => format!("Decimal({})", value),
+            // Like Node, weak collections don't list what they hold
+            HeapData::WeakMap(_) => "WeakMap { <items unknown> }".to_string(),
+            HeapData::WeakSet(_) => "WeakSet { <items unknown> }".to_string(),
+            HeapData::WeakRef(target) => {
+                format!("WeakRef {{ {} }}", self.object(*target, level + 1))
+            }
+            HeapData::Stream(id) => format!("Stream {{ #{} }}", id),
+            HeapData::Process(id) => format!("ChildProcess {{ #{} }}", id),
+        };
+        self.seen.pop();
+        shown
+    }
+}
+
+fn push_more(shown: &mut Vec<String>, total: usize) {
+    if total > MAX_ITEMS {
+        let rest = total - MAX_ITEMS;
+        shown.push(format!(
+            "... {} more item{}",
+            rest,
+            if rest == 1 { "" } else { "s" }
+        ));
+    }
+}
+
+/// `{ a, b }` on one line when it fits, otherwise one entry per line
+/// indented under the opening bracket's line
+fn wrap(prefix: &str, open: char, close: char, items: Vec<String>, level: usize) -> String {
+    if items.is_empty() {
+        return format!("{}{}{}", prefix, open, close);
+    }
+    let line = format!("{}{} {} {}", prefix, open, items.join(", "), close);
+    if line.len() <= LINE_WIDTH && !line.contains('\n') {
+        return line;
+    }
+    let indent = "  ".repeat(level + 1);
+    format!(
+        "{}{}\n{}{}\n{}{}",
+        prefix,
+        open,
+        indent,
+        items.join(&format!(",\n{}", indent)),
+        "  ".repeat(level),
+        close
+    )
+}
+
+/// A property name as written in an object literal
+fn key_name(key: &str) -> String {
+    let mut chars = key.chars();
+    let identifier = chars
+        .next()
+        .is_some_and(|c| c.is_alphabetic() || c == '_' || c == '$')
+        && chars.all(|c| c.is_alphanumeric() || c == '_' || c == '$');
+    if identifier || key.parse::<u32>().is_ok_and(|i| i.to_string() == key) {
+        key.to_string()
+    } else {
+        quote(key)
+    }
+}
+
+fn quote(s: &str) -> String {
+    let mut out = String::from("'");
+    for c in s.chars() {
+        match c {
+            '\'' => out.push_str("\\'"),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c => out.push(c),
+        }
+    }
+    out.push('\'');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_localized_numbers_keep_exact_digits() {
+        let en = ConsoleLocale::parse("en-US").unwrap();
+        let de = ConsoleLocale::parse("de_DE").unwrap();
+        assert_eq!(en.format_number(1234567.891), "1,234,567.891");
+        assert_eq!(de.format_number(1234567.891), "1.234.567,891");
+        assert_eq!(de.format_number(-999.5), "-999,5");
+        assert_eq!(en.format_number(0.1 + 0.2), "0.30000000000000004");
+        assert_eq!(de.format_number(1.5e-7), "1,5e-7");
+        assert_eq!(en.format_number(f64::NAN), "NaN");
+        assert!(ConsoleLocale::parse("xx").is_err());
+    }
+}
@@ -0,0 +1,654 @@
+//! Exact base-10 numbers (`Decimal("0.1")`)
+//!
+//! A decimal is a sign, an arbitrary-length coefficient and a scale, the
+//! number of digits after the point: `12.50` is 1250 with scale 2. Sums,
+//! differences and products are exact and keep the larger scale, so money
+//! amounts stay at cents (`Decimal("1.10").plus("2.20")` is `3.30`). Only
+//! division has to stop somewhere; it rounds to 20 places unless told
+//! otherwise. Numbers are converted through their shortest string form, so
+//! `Decimal(0.1)` is exactly `0.1`.
+
+use crate::stdlib::number_to_string;
+use crate::vm::VM;
+use crate::vm::value::{HeapData, HeapObject, JsValue};
+use std::cmp::Ordering;
+use std::fmt;
+
+/// Places `div` keeps when the call doesn't say
+const DEFAULT_DIV_PLACES: u32 = 20;
+/// Most places a call may ask for, and the largest exponent `parse` takes,
+/// so a typo can't allocate gigabytes of zeros
+const MAX_PLACES: u32 = 10_000;
+
+#[derive(Debug, Clone)]
+pub struct Decimal {
+    negative: bool,
+    /// Coefficient digits, least significant first, without high zeros
+    digits: Vec<u8>,
+    scale: u32,
+}
+
+/// How `round` settles a dropped remainder
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rounding {
+    /// Ties away from zero (2.5 -> 3, -2.5 -> -3)
+    HalfUp,
+    /// Ties to the even neighbour (2.5 -> 2, 3.5 -> 4)
+    HalfEven,
+    /// Towards zero
+    Down,
+    /// Away from zero
+    Up,
+}
+
+impl Rounding {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "halfUp" => Ok(Rounding::HalfUp),
+            "halfEven" => Ok(Rounding::HalfEven),
+            "down" => Ok(Rounding::Down),
+            "up" => Ok(Rounding::Up),
+            _ => Err(format!(
+                "unknown rounding mode '{}' (expected halfUp, halfEven, down or up)",
+                name
+            )),
+        }
+    }
+}
+
+impl Decimal {
+    pub fn zero() -> Self {
+        Self {
+            negative: false,
+            digits: Vec::new(),
+            scale: 0,
+        }
+    }
+
+    fn new(negative: bool, mut digits: Vec<u8>, scale: u32) -> Self {
+        while digits.last() == Some(&0) {
+            digits.pop();
+        }
+        let negative = negative && !digits.is_empty();
+        Self {
+            negative,
+            digits,
+            scale,
+        }
+    }
+
+    /// Parse `-12.50`, `1e-3` or `+7`
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let invalid = || format!("invalid decimal '{}'", text);
+        let trimmed = text.trim();
+        let (negative, rest) = match trimmed.as_bytes().first() {
+            Some(b'-') => (true, &trimmed[1..]),
+            Some(b'+') => (false, &trimmed[1..]),
+            _ => (false, trimmed),
+        };
+        let (mantissa, exponent) = match rest.find(['e', 'E']) {
+            Some(at) => {
+                let exponent: i64 = rest[at + 1..].parse().map_err(|_| invalid())?;
+                if exponent.unsigned_abs() > MAX_PLACES as u64 {
+                    return Err(invalid());
+                }
+                (&rest[..at], exponent)
+            }
+            None => (rest, 0),
+        };
+        let (int_part, frac_part) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+        if (int_part.is_empty() && frac_part.is_empty())
+            || !int_part
+                .bytes()
+                .chain(frac_part.bytes())
+                .all(|b| b.is_ascii_digit())
+        {
+            return Err(invalid());
+        }
+        let mut digits: Vec<u8> = int_part
+            .bytes()
+            .chain(frac_part.bytes())
+            .rev()
+            .map(|b| b - b'0')
+            .collect();
+        let mut scale = frac_part.len() as i64 - exponent;
+        if scale < 0 {
+            digits.splice(0..0, std::iter::repeat_n(0, (-scale) as usize));
+            scale = 0;
+        }
+        let scale = u32::try_from(scale).map_err(|_| invalid())?;
+        Ok(Self::new(negative, digits, scale))
+    }
+
+    pub fn from_f64(n: f64) -> Result<Self, String> {
+        if !n.is_finite() {
+            return Err(format!("{} cannot be a decimal", number_to_string(n)));
+        }
+        Self::parse(&number_to_string(n))
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        self.to_string().parse().unwrap_or(f64::NAN)
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.digits.is_empty()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    /// Digits in the coefficient, one byte each
+    pub fn digit_count(&self) -> usize {
+        self.digits.len()
+    }
+
+    /// Digits after the point
+    pub fn scale(&self) -> u32 {
+        self.scale
+    }
+
+    pub fn neg(&self) -> Self {
+        Self::new(!self.negative, self.digits.clone(), self.scale)
+    }
+
+    pub fn abs(&self) -> Self {
+        Self::new(false, self.digits.clone(), self.scale)
+    }
+
+    /// Coefficient at `scale`, which must not be smaller than `self.scale`
+    fn coefficient_at(&self, scale: u32) -> Vec<u8> {
+        let mut digits = self.digits.clone();
+        if !digits.is_empty() {
+            let pad = (scale - self.scale) as usize;
+            digits.splice(0..0, std::iter::repeat_n(0, pad));
+        }
+        digits
+    }
+
+    pub fn add(&self, other: &Self) -> Self {
+        let scale = self.scale.max(other.scale);
+        let (a, b) = (self.coefficient_at(scale), other.coefficient_at(scale));
+        if self.negative == other.negative {
+            return Self::new(self.negative, add_digits(&a, &b), scale);
+        }
+        match compare_digits(&a, &b) {
+            Ordering::Less => Self::new(other.negative, sub_digits(&b, &a), scale),
+            _ => Self::new(self.negative, sub_digits(&a, &b), scale),
+        }
+    }
+
+    pub fn sub(&self, other: &Self) -> Self {
+        self.add(&other.neg())
+    }
+
+    pub fn mul(&self, other: &Self) -> Self {
+        Self::new(
+            self.negative != other.negative,
+            mul_digits(&self.digits, &other.digits),
+            self.scale + other.scale,
+        )
+    }
+
+    /// `self / other` rounded to `places` digits after the point, with
+    /// trailing zeros dropped. `None` when dividing by zero.
+    pub fn div(&self, other: &Self, places: u32, mode: Rounding) -> Option<Self> {
+        if other.is_zero() {
+            return None;
+        }
+        // Scale the dividend so the integer quotient has one digit more than
+        // `places`, which `round` then settles
+        let shift = places as i64 + 1 + other.scale as i64 - self.scale as i64;
+        let mut dividend = self.digits.clone();
+        let mut divisor = other.digits.clone();
+        if shift >= 0 {
+            dividend.splice(0..0, std::iter::repeat_n(0, shift as usize));
+        } else {
+            divisor.splice(0..0, std::iter::repeat_n(0, (-shift) as usize));
+        }
+        let (mut quotient, remainder) = divmod_digits(&dividend, &divisor);
+        // A remainder past the extra digit still counts for rounding
+        if !remainder.is_empty() {
+            quotient.insert(0, 1);
+        }
+        let negative = self.negative != other.negative;
+        let exact = Self::new(
+            negative,
+            quotient,
+            places + 1 + !remainder.is_empty() as u32,
+        );
+        Some(exact.round(places, mode).trimmed())
+    }
+
+    /// Remainder of `self / other` with the sign of `self`, like `%`
+    pub fn rem(&self, other: &Self) -> Option<Self> {
+        if other.is_zero() {
+            return None;
+        }
+        let scale = self.scale.max(other.scale);
+        let (a, b) = (self.coefficient_at(scale), other.coefficient_at(scale));
+        let (_, remainder) = divmod_digits(&a, &b);
+        Some(Self::new(self.negative, remainder, scale))
+    }
+
+    /// Round to `places` digits after the point. A decimal with fewer
+    /// places is returned as it is.
+    pub fn round(&self, places: u32, mode: Rounding) -> Self {
+        if self.scale <= places {
+            return self.clone();
+        }
+        let dropped = (self.scale - places) as usize;
+        let digit = |i: usize| self.digits.get(i).copied().unwrap_or(0);
+        let first_dropped = digit(dropped - 1);
+        let rest_nonzero = self.digits.iter().take(dropped - 1).any(|d| *d != 0);
+        let mut kept = self.digits.get(dropped..).unwrap_or_default().to_vec();
+        let any_dropped = first_dropped != 0 || rest_nonzero;
+        let round_up = match mode {
+            Rounding::Down => false,
+            Rounding::Up => any_dropped,
+            Rounding::HalfUp => first_dropped >= 5,
+            Rounding::HalfEven => {
+                first_dropped > 5
+                    || (first_dropped == 5
+                        && (rest_nonzero || kept.first().copied().unwrap_or(0) % 2 == 1))
+            }
+        };
+        if round_up {
+            kept = add_digits(&kept, &[1]);
+        }
+        Self::new(self.negative, kept, places)
+    }
+
+    /// The same value at `places` digits after the point, padding with
+    /// zeros or rounding half up, for `toFixed`
+    pub fn with_places(&self, places: u32) -> Self {
+        if self.scale >= places {
+            return self.round(places, Rounding::HalfUp);
+        }
+        Self::new(self.negative, self.coefficient_at(places), places)
+    }
+
+    /// Drop zeros after the point: `2.500` becomes `2.5`
+    pub fn trimmed(&self) -> Self {
+        let zeros = self
+            .digits
+            .iter()
+            .take_while(|d| **d == 0)
+            .count()
+            .min(self.scale as usize);
+        let scale = if self.is_zero() {
+            0
+        } else {
+            self.scale - zeros as u32
+        };
+        Self::new(self.negative, self.digits[zeros..].to_vec(), scale)
+    }
+}
+
+/// Equal values are equal at any scale: `1.10 == 1.1`
+impl PartialEq for Decimal {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Decimal {}
+
+impl Ord for Decimal {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let scale = self.scale.max(other.scale);
+        let magnitude = compare_digits(&self.coefficient_at(scale), &other.coefficient_at(scale));
+        match (self.negative, other.negative) {
+            (false, false) => magnitude,
+            (true, true) => magnitude.reverse(),
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+        }
+    }
+}
+
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let scale = self.scale as usize;
+        let mut text: String = self
+            .digits
+            .iter()
+            .rev()
+            .map(|d| (b'0' + d) as char)
+            .collect();
+        if text.len() <= scale {
+            text.insert_str(0, &"0".repeat(scale + 1 - text.len()));
+        }
+        if scale > 0 {
+            text.insert(text.len() - scale, '.');
+        }
+        if self.negative {
+            text.insert(0, '-');
+        }
+        f.write_str(&text)
+    }
+}
+
+fn compare_digits(a: &[u8], b: &[u8]) -> Ordering {
+    a.len()
+        .cmp(&b.len())
+        .then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_digits(a: &[u8], b: &[u8]) -> Vec<u8> {
+    let mut sum = Vec::with_capacity(a.len().max(b.len()) + 1);
+    let mut carry = 0;
+    for i in 0..a.len().max(b.len()) {
+        let d = a.get(i).unwrap_or(&0) + b.get(i).unwrap_or(&0) + carry;
+        sum.push(d % 10);
+        carry = d / 10;
+    }
+    if carry > 0 {
+        sum.push(carry);
+    }
+    sum
+}
+
+/// `a - b` for `a >= b`
+fn sub_digits(a: &[u8], b: &[u8]) -> Vec<u8> {
+    let mut difference = Vec::with_capacity(a.len());
+    let mut borrow = 0;
+    for (i, &d) in a.iter().enumerate() {
+        let subtrahend = b.get(i).unwrap_or(&0) + borrow;
+        if d >= subtrahend {
+            difference.push(d - subtrahend);
+            borrow = 0;
+        } else {
+            difference.push(d + 10 - subtrahend);
+            borrow = 1;
+        }
+    }
+    while difference.last() == Some(&0) {
+        difference.pop();
+    }
+    difference
+}
+
+fn mul_digits(a: &[u8], b: &[u8]) -> Vec<u8> {
+    if a.is_empty() || b.is_empty() {
+        return Vec::new();
+    }
+    let mut product = vec![0u32; a.len() + b.len()];
+    for (i, &x) in a.iter().enumerate() {
+        for (j, &y) in b.iter().enumerate() {
+            product[i + j] += x as u32 * y as u32;
+        }
+    }
+    let mut carry = 0;
+    let mut digits: Vec<u8> = product
+        .into_iter()
+        .map(|d| {
+            let d = d + carry;
+            carry = d / 10;
+            (d % 10) as u8
+        })
+        .collect();
+    while carry > 0 {
+        digits.push((carry % 10) as u8);
+        carry /= 10;
+    }
+    while digits.last() == Some(&0) {
+        digits.pop();
+    }
+    digits
+}
+
+/// Long division of coefficients: `(a / b, a % b)` for non-zero `b`
+fn divmod_digits(a: &[u8], b: &[u8]) -> (Vec<u8>, Vec<u8>) {
+    let mut quotient = vec![0; a.len()];
+    let mut remainder: Vec<u8> = Vec::new();
+    for (i, &d) in a.iter().enumerate().rev() {
+        if !remainder.is_empty() || d != 0 {
+            remainder.insert(0, d);
+        }
+        let mut q = 0;
+        while compare_digits(&remainder, b) != Ordering::Less {
+            remainder = sub_digits(&remainder, b);
+            q += 1;
+        }
+        quotient[i] = q;
+    }
+    while quotient.last() == Some(&0) {
+        quotient.pop();
+    }
+    (quotient, remainder)
+}
+
+// ============================================================================
+// Script API
+// ============================================================================
+
+pub fn alloc_decimal(vm: &mut VM, value: Decimal) -> JsValue {
+    let ptr = vm.heap.len();
+    vm.heap.push(HeapObject {
+        data: HeapData::Decimal(value),
+    });
+    JsValue::Object(ptr)
+}
+
+/// A decimal from a decimal, a number or a numeric string
+pub fn to_decimal(vm: &VM, value: &JsValue) -> Result<Decimal, String> {
+    match value {
+        JsValue::Number(n) => Decimal::from_f64(*n),
+        JsValue::String(s) => Decimal::parse(s),
+        JsValue::Object(ptr) => match vm.heap.get(*ptr).map(|obj| &obj.data) {
+            Some(HeapData::Decimal(d)) => Ok(d.clone()),
+            _ => Err("cannot convert an object to a decimal".to_string()),
+        },
+        other => Err(format!("cannot convert {:?} to a decimal", other)),
+    }
+}
+
+/// Decimal(value) and new Decimal(value)
+pub fn native_decimal(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
+    match to_decimal(vm, args.first().unwrap_or(&JsValue::Undefined)) {
+        Ok(value) => alloc_decimal(vm, value),
+        Err(e) => {
+            eprintln!("Decimal: {}", e);
+            JsValue::Undefined
+        }
+    }
+}
+
+/// Decimal.isDecimal(value)
+pub fn native_is_decimal(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
+    let is_decimal = match args.first() {
+        Some(JsValue::Object(ptr)) => matches!(
+            vm.heap.get(*ptr).map(|obj| &obj.data),
+            Some(HeapData::Decimal(_))
+        ),
+        _ => false,
+    };
+    JsValue::Boolean(is_decimal)
+}
+
+/// `places` argument: a non-negative integer, or `default` when missing
+fn places_arg(arg: Option<&JsValue>, default: u32) -> Result<u32, String> {
+    match arg {
+        None | Some(JsValue::Undefined) => Ok(default),
+        Some(JsValue::Number(n)) if *n >= 0.0 && n.fract() == 0.0 && *n <= MAX_PLACES as f64 => {
+            Ok(*n as u32)
+        }
+        Some(other) => Err(format!(
+            "places must be an integer from 0 to {}, got {:?}",
+            MAX_PLACES, other
+        )),
+    }
+}
+
+fn rounding_arg(arg: Option<&JsValue>, default: Rounding) -> Result<Rounding, String> {
+    match arg {
+        None | Some(JsValue::Undefined) => Ok(default),
+        Some(JsValue::String(name)) => Rounding::parse(name),
+        Some(other) => Err(format!("rounding mode must be a string, got {:?}", other)),
+    }
+}
+
+/// Run a decimal method; `None` if there is no method called `name`
+pub fn call_method(vm: &mut VM, value: &Decimal, name: &str, args: &[JsValue]) -> Option<JsValue> {
+    let operand = args.first().map(|arg| to_decimal(vm, arg));
+    let other = || {
+        operand
+            .clone()
+            .unwrap_or_else(|| Err("expected a decimal, number or string".to_string()))
+    };
+    let result: Result<JsValue, String> = (|| {
+        let decimal = match name {
+            "plus" => value.add(&other()?),
+            "minus" => value.sub(&other()?),
+            "times" => value.mul(&other()?),
+            "div" => {
+                let places = places_arg(args.get(1), DEFAULT_DIV_PLACES)?;
+                let mode = rounding_arg(args.get(2), Rounding::HalfEven)?;
+                value
+                    .div(&other()?, places, mode)
+                    .ok_or("division by zero")?
+            }
+            "mod" => value.rem(&other()?).ok_or("division by zero")?,
+            "neg" => value.neg(),
+            "abs" => value.abs(),
+            "round" => {
+                let places = places_arg(args.first(), 0)?;
+                value.round(places, rounding_arg(args.get(1), Rounding::HalfUp)?)
+            }
+            "cmp" => {
+                let ordering = value.cmp(&other()?) as i8;
+                return Ok(JsValue::Number(ordering as f64));
+            }
+            "eq" => return Ok(JsValue::Boolean(*value == other()?)),
+            "lt" => return Ok(JsValue::Boolean(*value < other()?)),
+            "lte" => return Ok(JsValue::Boolean(*value <= other()?)),
+            "gt" => return Ok(JsValue::Boolean(*value > other()?)),
+            "gte" => return Ok(JsValue::Boolean(*value >= other()?)),
+            "isZero" => return Ok(JsValue::Boolean(value.is_zero())),
+            "isNegative" => return Ok(JsValue::Boolean(value.is_negative())),
+            "toFixed" => {
+                let places = places_arg(args.first(), 0)?;
+                return Ok(JsValue::String(value.with_places(places).to_string()));
+            }
+            "toString" | "toJSON" => return Ok(JsValue::String(value.to_string())),
+            "toNumber" | "valueOf" => return Ok(JsValue::Number(value.to_f64())),
+            _ => return Err(String::new()),
+        };
+        Ok(alloc_decimal(vm, decimal))
+    })();
+    match result {
+        Ok(value) => Some(value),
+        Err(e) if e.is_empty() => None,
+        Err(e) => {
+            eprintln!("Decimal.{}: {}", name, e);
+            Some(JsValue::Undefined)
+        }
+    }
+}
+
+/// `a <op> b` for a `Symbol.operator` name, when `a` is a decimal
+/// (`--operator-overloading`)
+pub fn apply_operator(vm: &mut VM, name: &str, a: &Decimal, b: Option<&JsValue>) -> JsValue {
+    let method = match name {
+        "plus" => "plus",
+        "minus" => "minus",
+        "times" => "times",
+        "divide" => "div",
+        "remainder" => "mod",
+        "lessThan" => "lt",
+        "lessThanOrEqual" => "lte",
+        "greaterThan" => "gt",
+        "greaterThanOrEqual" => "gte",
+        "negate" => "neg",
+        _ => return JsValue::Undefined,
+    };
+    let args: Vec<JsValue> = b.cloned().into_iter().collect();
+    call_method(vm, a, method, &args).unwrap_or(JsValue::Undefined)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn d(text: &str) -> Decimal {
+        Decimal::parse(text).unwrap()
+    }
+
+    #[test]
+    fn test_parse_and_format() {
+        assert_eq!(d("12.50").to_string(), "12.50");
+        assert_eq!(d("-0.05").to_string(), "-0.05");
+        assert_eq!(d("+7").to_string(), "7");
+        assert_eq!(d(".5").to_string(), "0.5");
+        assert_eq!(d("1.5e3").to_string(), "1500");
+        assert_eq!(d("15e-4").to_string(), "0.0015");
+        assert_eq!(d("-0").to_string(), "0");
+        assert_eq!(Decimal::from_f64(0.1).unwrap().to_string(), "0.1");
+        assert!(Decimal::parse("1.2.3").is_err());
+        assert!(Decimal::parse("abc").is_err());
+        assert!(Decimal::parse("").is_err());
+        assert!(Decimal::parse("1e999999999").is_err());
+        assert!(Decimal::from_f64(f64::NAN).is_err());
+    }
+
+    #[test]
+    fn test_arithmetic_is_exact() {
+        assert_eq!(d("0.1").add(&d("0.2")).to_string(), "0.3");
+        assert_eq!(d("1.10").add(&d("2.20")).to_string(), "3.30");
+        assert_eq!(d("1").sub(&d("1.01")).to_string(), "-0.01");
+        assert_eq!(d("-3").add(&d("3")).to_string(), "0");
+        assert_eq!(d("19.99").mul(&d("3")).to_string(), "59.97");
+        assert_eq!(d("-1.5").mul(&d("-1.5")).to_string(), "2.25");
+        assert_eq!(
+            d("123456789012345678901234567890").add(&d("1")).to_string(),
+            "123456789012345678901234567891"
+        );
+        assert_eq!(d("7.5").rem(&d("2")).unwrap().to_string(), "1.5");
+        assert_eq!(d("-7").rem(&d("2")).unwrap().to_string(), "-1");
+    }
+
+    #[test]
+    fn test_division_and_rounding() {
+        let div = |a: &str, b: &str, places| {
+            d(a).div(&d(b), places, Rounding::HalfEven)
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(div("1", "4", 20), "0.25");
+        assert_eq!(div("10.00", "2", 20), "5");
+        assert_eq!(div("1", "3", 5), "0.33333");
+        assert_eq!(div("2", "3", 2), "0.67");
+        assert_eq!(div("-1", "8", 2), "-0.12");
+        assert_eq!(div("0.125", "0.5", 20), "0.25");
+        assert_eq!(div("1", "0.001", 0), "1000");
+        assert!(d("1").div(&Decimal::zero(), 2, Rounding::HalfUp).is_none());
+
+        assert_eq!(d("2.5").round(0, Rounding::HalfUp).to_string(), "3");
+        assert_eq!(d("2.5").round(0, Rounding::HalfEven).to_string(), "2");
+        assert_eq!(d("3.5").round(0, Rounding::HalfEven).to_string(), "4");
+        assert_eq!(d("-2.5").round(0, Rounding::HalfUp).to_string(), "-3");
+        assert_eq!(d("1.001").round(2, Rounding::Up).to_string(), "1.01");
+        assert_eq!(d("1.999").round(2, Rounding::Down).to_string(), "1.99");
+        assert_eq!(d("0.004").round(2, Rounding::HalfUp).to_string(), "0.00");
+        assert_eq!(d("0.0004").round(2, Rounding::Up).to_string(), "0.01");
+        assert_eq!(d("9.995").round(2, Rounding::HalfUp).to_string(), "10.00");
+        assert_eq!(d("1.5").with_places(3).to_string(), "1.500");
+    }
+
+    #[test]
+    fn test_ordering() {
+        assert_eq!(d("1.10"), d("1.1"));
+        assert_eq!(d("1.10").cmp(&d("1.1")), Ordering::Equal);
+        assert!(d("-2") < d("1"));
+        assert!(d("-2") < d("-1.5"));
+        assert!(d("0.3") > d("0.29"));
+    }
+}
@@ -0,0 +1,347 @@
+//! Text and binary encodings (`TextEncoder`, `TextDecoder`, `Buffer.from`)
+//!
+//! Bytes move between scripts and natives as buffers, but anything that
+//! holds bytes is accepted as input: a buffer, a `ByteStream` or an array of
+//! numbers. Besides UTF-8 text, buffers convert to and from `hex`, `base64`
+//! and `base64url` strings.
+
+use super::buffer::alloc_buffer;
+use crate::vm::VM;
+use crate::vm::value::{BufferStorage, BufferView, HeapData, HeapObject, JsValue};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Utf8,
+    Hex,
+    Base64,
+    Base64Url,
+}
+
+impl Encoding {
+    /// Encoding named by a string argument; a missing one means UTF-8
+    pub fn from_arg(arg: Option<&JsValue>) -> Result<Self, String> {
+        let name = match arg {
+            Some(JsValue::String(name)) => name.to_ascii_lowercase(),
+            None | Some(JsValue::Undefined) => return Ok(Encoding::Utf8),
+            Some(_) => return Err("encoding must be a string".to_string()),
+        };
+        match name.as_str() {
+            "utf8" | "utf-8" => Ok(Encoding::Utf8),
+            "hex" => Ok(Encoding::Hex),
+            "base64" => Ok(Encoding::Base64),
+            "base64url" => Ok(Encoding::Base64Url),
+            _ => Err(format!("unknown encoding '{}'", name)),
+        }
+    }
+
+    pub fn encode(self, bytes: &[u8]) -> String {
+        match self {
+            Encoding::Utf8 => String::from_utf8_lossy(bytes).into_owned(),
+            Encoding::Hex => hex::encode(bytes),
+            Encoding::Base64 => base64_encode(bytes, BASE64_ALPHABET, true),
+            Encoding::Base64Url => base64_encode(bytes, BASE64URL_ALPHABET, false),
+        }
+    }
+
+    pub fn decode(self, text: &str) -> Result<Vec<u8>, String> {
+        match self {
+            Encoding::Utf8 => Ok(text.as_bytes().to_vec()),
+            Encoding::Hex => hex::decode(text).map_err(|e| format!("invalid hex: {}", e)),
+            // Either alphabet decodes either flavour, as in Node
+            Encoding::Base64 | Encoding::Base64Url => base64_decode(text),
+        }
+    }
+}
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const BASE64URL_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+fn base64_encode(bytes: &[u8], alphabet: &[u8; 64], pad: bool) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, b)| n | ((*b as u32) << (16 - 8 * i)));
+        let chars = chunk.len() + 1;
+        for i in 0..4 {
+            if i < chars {
+                out.push(alphabet[((n >> (18 - 6 * i)) & 0x3f) as usize] as char);
+            } else if pad {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+fn base64_decode(text: &str) -> Result<Vec<u8>, String> {
+    let mut out = Vec::with_capacity(text.len() / 4 * 3);
+    let mut acc = 0u32;
+    let mut bits = 0;
+    for c in text.trim_end_matches('=').chars() {
+        let value = match c {
+            'A'..='Z' => c as u32 - 'A' as u32,
+            'a'..='z' => c as u32 - 'a' as u32 + 26,
+            '0'..='9' => c as u32 - '0' as u32 + 52,
+            '+' | '-' => 62,
+            '/' | '_' => 63,
+            c if c.is_ascii_whitespace() => continue,
+            _ => return Err(format!("invalid base64 character '{}'", c)),
+        };
+        acc = (acc << 6) | value;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((acc >> bits) as u8);
+            acc &= (1 << bits) - 1;
+        }
+    }
+    Ok(out)
+}
+
+/// Bytes of a buffer, a ByteStream or an array of numbers
+pub fn bytes_of(vm: &VM, value: &JsValue) -> Option<Vec<u8>> {
+    let JsValue::Object(ptr) = value else {
+        return None;
+    };
+    match vm.heap.get(*ptr).map(|obj| &obj.data)? {
+        HeapData::Buffer(view) => Some(view.bytes().to_vec()),
+        HeapData::ByteStream(bytes) => Some(bytes.clone()),
+        HeapData::Array(items) => Some(
+            items
+                .iter()
+                .map(|item| match item {
+                    JsValue::Number(n) => *n as i64 as u8,
+                    _ => 0,
+                })
+                .collect(),
+        ),
+        _ => None,
+    }
+}
+
+fn owned_buffer(vm: &mut VM, bytes: Vec<u8>) -> JsValue {
+    alloc_buffer(vm, BufferView::new(BufferStorage::Owned(bytes)))
+}
+
+/// Buffer.from(string, encoding?) or Buffer.from(bytes) - a new buffer
+pub fn native_buffer_from(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
+    let bytes = match args.first() {
+        Some(JsValue::String(text)) => {
+            Encoding::from_arg(args.get(1)).and_then(|encoding| encoding.decode(text))
+        }
+        Some(value) => bytes_of(vm, value)
+            .ok_or_else(|| "expected a string, buffer, ByteStream or array".to_string()),
+        None => Err("expected a string, buffer, ByteStream or array".to_string()),
+    };
+    match bytes {
+        Ok(bytes) => owned_buffer(vm, bytes),
+        Err(e) => {
+            eprintln!("Buffer.from: {}", e);
+            JsValue::Undefined
+        }
+    }
+}
+
+/// Buffer.alloc(size) - a zero-filled buffer
+pub fn native_buffer_alloc(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
+    match args.first() {
+        Some(JsValue::Number(size)) if *size >= 0.0 && size.fract() == 0.0 => {
+            owned_buffer(vm, vec![0; *size as usize])
+        }
+        _ => {
+            eprintln!("Buffer.alloc: size must be a non-negative integer");
+            JsValue::Undefined
+        }
+    }
+}
+
+/// Buffer.isBuffer(value)
+pub fn native_is_buffer(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
+    let is_buffer = match args.first() {
+        Some(JsValue::Object(ptr)) => matches!(
+            vm.heap.get(*ptr).map(|obj| &obj.data),
+            Some(HeapData::Buffer(_))
+        ),
+        _ => false,
+    };
+    JsValue::Boolean(is_buffer)
+}
+
+/// Buffer.concat([buf, ...]) - one buffer holding all the bytes
+pub fn native_buffer_concat(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
+    let parts = match args.first() {
+        Some(JsValue::Object(ptr)) => match vm.heap.get(*ptr).map(|obj| &obj.data) {
+            Some(HeapData::Array(items)) => items.clone(),
+            _ => Vec::new(),
+        },
+        _ => Vec::new(),
+    };
+    let mut bytes = Vec::new();
+    for part in &parts {
+        match bytes_of(vm, part) {
+            Some(part) => bytes.extend(part),
+            None => {
+                eprintln!("Buffer.concat: every item must hold bytes");
+                return JsValue::Undefined;
+            }
+        }
+    }
+    owned_buffer(vm, bytes)
+}
+
+/// Native registered under `key` on the global `global`. Natives don't see
+/// `this`, so encoder and decoder objects carry their methods as own
+/// properties, copied from the constructor.
+fn constructor_native(vm: &VM, global: &str, key: &str) -> JsValue {
+    match vm.call_stack[0].locals.get(global) {
+        Some(JsValue::Object(ptr)) => match vm.heap.get(*ptr).map(|obj| &obj.data) {
+            Some(HeapData::Object(props)) => props.get(key).cloned(),
+            _ => None,
+        },
+        _ => None,
+    }
+    .unwrap_or(JsValue::Undefined)
+}
+
+/// `new TextEncoder()` - an object whose `encode(string)` returns the UTF-8
+/// bytes as a buffer
+pub fn native_text_encoder(vm: &mut VM, _args: Vec<JsValue>) -> JsValue {
+    let encode = constructor_native(vm, "TextEncoder", "__encode__");
+    let mut props = HashMap::new();
+    props.insert("encoding".to_string(), JsValue::String("utf-8".to_string()));
+    props.insert("encode".to_string(), encode);
+    let ptr = vm.heap.len();
+    vm.heap.push(HeapObject {
+        data: HeapData::Object(props),
+    });
+    JsValue::Object(ptr)
+}
+
+pub fn native_text_encode(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
+    let text = match args.first() {
+        Some(JsValue::String(s)) => s.clone(),
+        None | Some(JsValue::Undefined) => String::new(),
+        Some(other) => match super::native_string_constructor(vm, vec![other.clone()]) {
+            JsValue::String(s) => s,
+            _ => String::new(),
+        },
+    };
+    owned_buffer(vm, text.into_bytes())
+}
+
+/// `new TextDecoder(label?, { fatal? })` - an object whose `decode(bytes)`
+/// returns the text. Only UTF-8 is supported. Invalid sequences become
+/// U+FFFD unless `fatal` is set, in which case `decode` reports a TypeError
+/// and returns undefined.
+pub fn native_text_decoder(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
+    let label = match args.first() {
+        Some(JsValue::String(label)) => label.trim().to_ascii_lowercase(),
+        _ => "utf-8".to_string(),
+    };
+    if !matches!(label.as_str(), "utf-8" | "utf8" | "unicode-1-1-utf-8") {
+        eprintln!(
+            "RangeError: The encoding label provided ('{}') is invalid.",
+            label
+        );
+        return JsValue::Undefined;
+    }
+    let fatal = match args.get(1) {
+        Some(JsValue::Object(ptr)) => match vm.heap.get(*ptr).map(|obj| &obj.data) {
+            Some(HeapData::Object(options)) => {
+                matches!(options.get("fatal"), Some(JsValue::Boolean(true)))
+            }
+            _ => false,
+        },
+        _ => false,
+    };
+
+    let decode = if fatal {
+        constructor_native(vm, "TextDecoder", "__decode_fatal__")
+    } else {
+        constructor_native(vm, "TextDecoder", "__decode__")
+    };
+    let mut props = HashMap::new();
+    props.insert("encoding".to_string(), JsValue::String("utf-8".to_string()));
+    props.insert("fatal".to_string(), JsValue::Boolean(fatal));
+    props.insert("decode".to_string(), decode);
+    let ptr = vm.heap.len();
+    vm.heap.push(HeapObject {
+        data: HeapData::Object(props),
+    });
+    JsValue::Object(ptr)
+}
+
+/// Input bytes of `decode`, without a leading byte order mark
+fn decode_input(vm: &VM, args: &[JsValue]) -> Result<Vec<u8>, String> {
+    let mut bytes = match args.first() {
+        None | Some(JsValue::Undefined) => Vec::new(),
+        Some(value) => bytes_of(vm, value)
+            .ok_or_else(|| "TypeError: decode expects a buffer, ByteStream or array".to_string())?,
+    };
+    if bytes.starts_with(&[0xef, 0xbb, 0xbf]) {
+        bytes.drain(..3);
+    }
+    Ok(bytes)
+}
+
+pub fn native_text_decode(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
+    match decode_input(vm, &args) {
+        Ok(bytes) => JsValue::String(String::from_utf8_lossy(&bytes).into_owned()),
+        Err(e) => {
+            eprintln!("{}", e);
+            JsValue::Undefined
+        }
+    }
+}
+
+pub fn native_text_decode_fatal(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
+    let text = decode_input(vm, &args).and_then(|bytes| {
+        String::from_utf8(bytes)
+            .map_err(|_| "TypeError: The encoded data was not valid for encoding utf-8".to_string())
+    });
+    match text {
+        Ok(text) => JsValue::String(text),
+        Err(e) => {
+            eprintln!("{}", e);
+            JsValue::Undefined
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base64_round_trip() {
+        for (bytes, encoded) in [
+            (&b""[..], ""),
+            (b"f", "Zg=="),
+            (b"fo", "Zm8="),
+            (b"foo", "Zm9v"),
+            (b"foobar", "Zm9vYmFy"),
+        ] {
+            assert_eq!(Encoding::Base64.encode(bytes), encoded);
+            assert_eq!(Encoding::Base64.decode(encoded).unwrap(), bytes);
+        }
+        assert_eq!(Encoding::Base64Url.encode(&[0xfb, 0xff]), "-_8");
+        assert_eq!(Encoding::Base64.decode("-_8").unwrap(), [0xfb, 0xff]);
+        assert!(Encoding::Base64.decode("ab$c").is_err());
+    }
+
+    #[test]
+    fn test_encoding_names() {
+        let name = |s: &str| Encoding::from_arg(Some(&JsValue::String(s.to_string())));
+        assert_eq!(name("UTF-8"), Ok(Encoding::Utf8));
+        assert_eq!(name("hex"), Ok(Encoding::Hex));
+        assert_eq!(Encoding::from_arg(None), Ok(Encoding::Utf8));
+        assert!(name("latin2").is_err());
+        assert_eq!(Encoding::Hex.decode("00ff").unwrap(), [0, 255]);
+        assert!(Encoding::Hex.decode("0").is_err());
+    }
+}
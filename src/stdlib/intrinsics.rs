@@ -0,0 +1,40 @@
+//! `Math` and `std.intrinsics` numeric intrinsics
+//!
+//! The interpreter's side of `crate::ir::intrinsics`: compiled code runs
+//! these inline, the VM calls them as natives. Arguments go through
+//! ToNumber first, and missing ones read as `undefined` (NaN).
+
+use super::to_number;
+use crate::ir::intrinsics::Intrinsic;
+use crate::vm::VM;
+use crate::vm::value::{JsValue, NativeFn};
+
+fn call(vm: &mut VM, intrinsic: Intrinsic, args: &[JsValue]) -> JsValue {
+    let args: Vec<f64> = args
+        .iter()
+        .take(intrinsic.arity())
+        .map(|arg| to_number(vm, arg))
+        .collect();
+    JsValue::Number(intrinsic.apply(&args))
+}
+
+/// The native implementing `intrinsic`
+pub fn native(intrinsic: Intrinsic) -> NativeFn {
+    match intrinsic {
+        Intrinsic::Clz32 => |vm, args| call(vm, Intrinsic::Clz32, &args),
+        Intrinsic::Imul => |vm, args| call(vm, Intrinsic::Imul, &args),
+        Intrinsic::Fround => |vm, args| call(vm, Intrinsic::Fround, &args),
+        Intrinsic::Sqrt => |vm, args| call(vm, Intrinsic::Sqrt, &args),
+        Intrinsic::Abs => |vm, args| call(vm, Intrinsic::Abs, &args),
+        Intrinsic::Floor => |vm, args| call(vm, Intrinsic::Floor, &args),
+        Intrinsic::Ceil => |vm, args| call(vm, Intrinsic::Ceil, &args),
+        Intrinsic::Trunc => |vm, args| call(vm, Intrinsic::Trunc, &args),
+        Intrinsic::Popcnt32 => |vm, args| call(vm, Intrinsic::Popcnt32, &args),
+        Intrinsic::Ctz32 => |vm, args| call(vm, Intrinsic::Ctz32, &args),
+        Intrinsic::F32ToBits => |vm, args| call(vm, Intrinsic::F32ToBits, &args),
+        Intrinsic::F32FromBits => |vm, args| call(vm, Intrinsic::F32FromBits, &args),
+        Intrinsic::F64HighBits => |vm, args| call(vm, Intrinsic::F64HighBits, &args),
+        Intrinsic::F64LowBits => |vm, args| call(vm, Intrinsic::F64LowBits, &args),
+        Intrinsic::F64FromBits => |vm, args| call(vm, Intrinsic::F64FromBits, &args),
+    }
+}
@@ -1,57 +1,137 @@
 //! Minimal standard library for Oite core
 //!
 //! Contains only essential primitives needed by the language:
-//! - console.log / console.error (debugging)
+//! - console (log, info, warn, error, table, time)
 //! - ByteStream (binary serialization for bootstrap compiler)
 //!
 //! Full standard library functionality (fs, path, json, math, date, etc.)
 //! will be provided by Rolls packages in the future.
 
+pub mod buffer;
+pub mod child_process;
+pub mod clone;
+pub mod console;
+pub mod decimal;
+pub mod encoding;
+pub mod intrinsics;
+pub mod process;
+pub mod simd;
+pub mod stream;
+pub mod string;
+pub mod weak;
+
 use crate::vm::VM;
-use crate::vm::value::{HeapData, HeapObject, JsValue};
+use crate::vm::descriptors::PropertyFlags;
+use crate::vm::permissions::Capability;
+use crate::vm::value::{BufferStorage, BufferView, HeapData, HeapObject, JsValue, NativeResult};
+use console::console_string;
 
 // ============================================================================
 // Console Functions
 // ============================================================================
 
-pub fn native_log(_vm: &mut VM, args: Vec<JsValue>) -> JsValue {
-    for arg in args {
-        match arg {
-            JsValue::String(s) => print!("{}", s),
-            JsValue::Number(n) => print!("{}", n),
-            JsValue::Boolean(b) => print!("{}", b),
-            JsValue::Null => print!("null"),
-            JsValue::Undefined => print!("undefined"),
-            JsValue::Object(ptr) => print!("Object({})", ptr),
-            JsValue::Function { address, env: _ } => print!("Function({})", address),
-            JsValue::NativeFunction(idx) => print!("NativeFunction({})", idx),
-            JsValue::Promise(_) => print!("Promise"),
-            JsValue::Accessor(_, _) => print!("Accessor"),
-        }
-    }
-    println!();
+/// A console line on stdout, or into `captured_output` when set
+fn console_out(vm: &mut VM, line: &str) {
+    match &mut vm.captured_output {
+        Some(captured) => {
+            captured.push_str(line);
+            captured.push('\n');
+        }
+        None => println!("{}", line),
+    }
+}
+
+/// console.log / console.info / console.debug
+pub fn native_log(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
+    let line = console::format_line(vm, &args);
+    console_out(vm, &line);
     JsValue::Undefined
 }
 
-pub fn native_error(_vm: &mut VM, args: Vec<JsValue>) -> JsValue {
-    for arg in args {
-        match arg {
-            JsValue::String(s) => eprint!("{}", s),
-            JsValue::Number(n) => eprint!("{}", n),
-            JsValue::Boolean(b) => eprint!("{}", b),
-            JsValue::Null => eprint!("null"),
-            JsValue::Undefined => eprint!("undefined"),
-            JsValue::Object(ptr) => eprint!("Object({})", ptr),
-            JsValue::Function { address, env: _ } => eprint!("Function({})", address),
-            JsValue::NativeFunction(idx) => eprint!("NativeFunction({})", idx),
-            JsValue::Promise(_) => eprint!("Promise"),
-            JsValue::Accessor(_, _) => eprint!("Accessor"),
-        }
-    }
-    eprintln!();
+/// console.error / console.warn
+pub fn native_error(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
+    eprintln!("{}", console::format_line(vm, &args));
+    JsValue::Undefined
+}
+
+/// console.table(data, columns?)
+pub fn native_table(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
+    let data = args.first().cloned().unwrap_or(JsValue::Undefined);
+    let columns: Option<Vec<String>> = match args.get(1) {
+        Some(JsValue::Object(ptr)) => match vm.heap.get(*ptr).map(|obj| &obj.data) {
+            Some(HeapData::Array(items)) => {
+                Some(items.iter().map(|item| console_string(vm, item)).collect())
+            }
+            _ => None,
+        },
+        _ => None,
+    };
+    match console::table(vm, &data, columns.as_deref()) {
+        Some(table) => console_out(vm, &table),
+        None => return native_log(vm, args),
+    }
     JsValue::Undefined
 }
 
+fn timer_label(vm: &VM, args: &[JsValue]) -> String {
+    match args.first() {
+        None | Some(JsValue::Undefined) => "default".to_string(),
+        Some(label) => console_string(vm, label),
+    }
+}
+
+/// console.time(label?)
+pub fn native_time(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
+    let label = timer_label(vm, &args);
+    if vm.console_timers.contains_key(&label) {
+        eprintln!(
+            "Warning: Label '{}' already exists for console.time()",
+            label
+        );
+        return JsValue::Undefined;
+    }
+    vm.console_timers.insert(label, std::time::Instant::now());
+    JsValue::Undefined
+}
+
+/// console.timeLog(label?, ...data): the elapsed time, timer kept running
+pub fn native_time_log(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
+    let label = timer_label(vm, &args);
+    let Some(started) = vm.console_timers.get(&label) else {
+        eprintln!("Warning: No such label '{}' for console.timeLog()", label);
+        return JsValue::Undefined;
+    };
+    let mut line = console::elapsed(&label, started.elapsed());
+    for arg in args.iter().skip(1) {
+        line.push(' ');
+        line.push_str(&console_string(vm, arg));
+    }
+    console_out(vm, &line);
+    JsValue::Undefined
+}
+
+/// console.timeEnd(label?): the elapsed time, then the timer is gone
+pub fn native_time_end(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
+    let label = timer_label(vm, &args);
+    let Some(started) = vm.console_timers.remove(&label) else {
+        eprintln!("Warning: No such label '{}' for console.timeEnd()", label);
+        return JsValue::Undefined;
+    };
+    let line = console::elapsed(&label, started.elapsed());
+    console_out(vm, &line);
+    JsValue::Undefined
+}
+
+/// util.inspect(value, depth?) - the console rendering of a value as a string
+pub fn native_inspect(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
+    let value = args.first().cloned().unwrap_or(JsValue::Undefined);
+    let depth = match args.get(1) {
+        Some(JsValue::Number(n)) if *n >= 0.0 => *n as usize,
+        _ => console::INSPECT_DEPTH,
+    };
+    JsValue::String(console::inspect(vm, &value, depth))
+}
+
 // ============================================================================
 // Module System (minimal)
 // ============================================================================
@@ -67,12 +147,32 @@ pub fn native_require(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
     JsValue::Undefined
 }
 
+/// A `TypeError` for a fallible native to return as its `Err`
+pub fn type_error(message: &str) -> JsValue {
+    JsValue::String(format!("TypeError: {}", message))
+}
+
 // ============================================================================
 // File I/O (minimal - needed for bootstrap compiler output)
 // ============================================================================
 
-pub fn native_read_file(_vm: &mut VM, args: Vec<JsValue>) -> JsValue {
+/// Check `cap` for `target` on behalf of a native, reporting a denial on
+/// stderr like any other native error
+pub fn permitted(vm: &mut VM, cap: Capability, target: &str) -> bool {
+    match vm.permissions.check(cap, Some(target)) {
+        Ok(()) => true,
+        Err(e) => {
+            eprintln!("{}", e);
+            false
+        }
+    }
+}
+
+pub fn native_read_file(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
     if let Some(JsValue::String(filename)) = args.first() {
+        if !permitted(vm, Capability::Read, filename) {
+            return JsValue::Undefined;
+        }
         match std::fs::read_to_string(filename) {
             Ok(contents) => JsValue::String(contents),
             Err(e) => {
@@ -85,10 +185,13 @@ pub fn native_read_file(_vm: &mut VM, args: Vec<JsValue>) -> JsValue {
     }
 }
 
-pub fn native_write_file(_vm: &mut VM, args: Vec<JsValue>) -> JsValue {
+pub fn native_write_file(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
     if let (Some(JsValue::String(filename)), Some(JsValue::String(contents))) =
         (args.first(), args.get(1))
     {
+        if !permitted(vm, Capability::Write, filename) {
+            return JsValue::Boolean(false);
+        }
         match std::fs::write(filename, contents) {
             Ok(()) => JsValue::Boolean(true),
             Err(e) => {
@@ -101,9 +204,11 @@ pub fn native_write_file(_vm: &mut VM, args: Vec<JsValue>) -> JsValue {
     }
 }
 
-pub fn native_exists_sync(_vm: &mut VM, args: Vec<JsValue>) -> JsValue {
+pub fn native_exists_sync(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
     if let Some(JsValue::String(path)) = args.first() {
-        JsValue::Boolean(std::path::Path::new(path).exists())
+        JsValue::Boolean(
+            permitted(vm, Capability::Read, path) && std::path::Path::new(path).exists(),
+        )
     } else {
         JsValue::Boolean(false)
     }
@@ -111,6 +216,9 @@ pub fn native_exists_sync(_vm: &mut VM, args: Vec<JsValue>) -> JsValue {
 
 pub fn native_mkdir_sync(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
     if let Some(JsValue::String(path)) = args.first() {
+        if !permitted(vm, Capability::Write, path) {
+            return JsValue::Undefined;
+        }
         // Check for { recursive: true } option
         let recursive = if let Some(JsValue::Object(ptr)) = args.get(1) {
             if let Some(HeapObject {
@@ -144,7 +252,9 @@ pub fn native_mkdir_sync(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
 }
 
 pub fn native_readdir_sync(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
-    if let Some(JsValue::String(path)) = args.first() {
+    if let Some(JsValue::String(path)) = args.first()
+        && permitted(vm, Capability::Read, path)
+    {
         match std::fs::read_dir(path) {
             Ok(entries) => {
                 let mut files: Vec<JsValue> = Vec::new();
@@ -170,7 +280,7 @@ pub fn native_readdir_sync(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
             }
         }
     } else {
-        // Return empty array if no path provided
+        // Return empty array if no path provided or reading it isn't allowed
         let arr_ptr = vm.heap.len();
         vm.heap.push(HeapObject {
             data: HeapData::Array(Vec::new()),
@@ -181,6 +291,9 @@ pub fn native_readdir_sync(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
 
 pub fn native_stat_sync(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
     if let Some(JsValue::String(path)) = args.first() {
+        if !permitted(vm, Capability::Read, path) {
+            return JsValue::Null;
+        }
         match std::fs::metadata(path) {
             Ok(metadata) => {
                 let mut stat_props = std::collections::HashMap::new();
@@ -229,6 +342,9 @@ pub fn native_write_binary_file(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
     if let (Some(JsValue::String(filename)), Some(JsValue::Object(ptr))) =
         (args.first(), args.get(1))
     {
+        if !permitted(vm, Capability::Write, filename) {
+            return JsValue::Boolean(false);
+        }
         if let Some(HeapObject {
             data: HeapData::ByteStream(bytes),
         }) = vm.heap.get(*ptr)
@@ -408,7 +524,7 @@ pub fn native_string_constructor(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
     let value = &args[0];
     let result = match value {
         JsValue::String(s) => s.clone(),
-        JsValue::Number(n) => n.to_string(),
+        JsValue::Number(n) => number_to_string(*n),
         JsValue::Boolean(b) => b.to_string(),
         JsValue::Null => "null".to_string(),
         JsValue::Undefined => "undefined".to_string(),
@@ -420,7 +536,7 @@ pub fn native_string_constructor(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
                             .iter()
                             .map(|v| match v {
                                 JsValue::String(s) => s.clone(),
-                                JsValue::Number(n) => n.to_string(),
+                                JsValue::Number(n) => number_to_string(*n),
                                 JsValue::Boolean(b) => b.to_string(),
                                 JsValue::Null => "null".to_string(),
                                 JsValue::Undefined => "".to_string(),
@@ -433,6 +549,13 @@ pub fn native_string_constructor(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
                     HeapData::ByteStream(_) => "[object ByteStream]".to_string(),
                     HeapData::Map(_) => "[object Map]".to_string(),
                     HeapData::Set(_) => "[object Set]".to_string(),
+                    HeapData::Buffer(view) => String::from_utf8_lossy(view.bytes()).into_owned(),
+                    HeapData::Decimal(value) => value.to_string(),
+                    HeapData::WeakMap(_) => "[object WeakMap]".to_string(),
+                    HeapData::WeakSet(_) => "[object WeakSet]".to_string(),
+                    HeapData::WeakRef(_) => "[object WeakRef]".to_string(),
+                    HeapData::Stream(_) => "[object Stream]".to_string(),
+                    HeapData::Process(_) => "[object ChildProcess]".to_string(),
                 }
             } else {
                 "[object Object]".to_string()
@@ -446,6 +569,237 @@ pub fn native_string_constructor(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
     JsValue::String(result)
 }
 
+// ============================================================================
+// Type Conversion (Number/Boolean/parseInt/parseFloat)
+// ============================================================================
+
+/// Format a number exactly the way `String(n)` does. Use this for every
+/// value a script can observe; console output goes through `console`.
+pub fn number_to_string(n: f64) -> String {
+    crate::runtime::number::number_to_js_string(n)
+}
+
+/// ToNumber for strings: trims whitespace, accepts decimal, hex/octal/binary
+/// literals and Infinity; everything else is NaN.
+pub fn string_to_number(s: &str) -> f64 {
+    let s = s.trim();
+    if s.is_empty() {
+        return 0.0;
+    }
+    let radix_body = |prefix_len: usize, radix: u32| {
+        let digits = &s[prefix_len..];
+        if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+            return f64::NAN;
+        }
+        digits.chars().fold(0.0, |acc, c| {
+            acc * radix as f64 + c.to_digit(radix).unwrap_or(0) as f64
+        })
+    };
+    match s.get(..2) {
+        Some("0x") | Some("0X") => return radix_body(2, 16),
+        Some("0o") | Some("0O") => return radix_body(2, 8),
+        Some("0b") | Some("0B") => return radix_body(2, 2),
+        _ => {}
+    }
+    let unsigned = s.strip_prefix(['+', '-']).unwrap_or(s);
+    if unsigned == "Infinity" {
+        return if s.starts_with('-') {
+            f64::NEG_INFINITY
+        } else {
+            f64::INFINITY
+        };
+    }
+    // Rust also accepts "inf"/"nan" spellings, which JS does not
+    if !unsigned.starts_with(|c: char| c.is_ascii_digit() || c == '.') {
+        return f64::NAN;
+    }
+    s.parse::<f64>().unwrap_or(f64::NAN)
+}
+
+/// ToNumber for any value.
+pub fn to_number(vm: &VM, value: &JsValue) -> f64 {
+    match value {
+        JsValue::Number(n) => *n,
+        JsValue::Boolean(b) => {
+            if *b {
+                1.0
+            } else {
+                0.0
+            }
+        }
+        JsValue::Null => 0.0,
+        JsValue::String(s) => string_to_number(s),
+        JsValue::Object(ptr) => match vm.heap.get(*ptr).map(|obj| &obj.data) {
+            // Arrays convert through their string form: [] -> 0, [7] -> 7
+            Some(HeapData::Array(arr)) => match arr.as_slice() {
+                [] => 0.0,
+                [single] => match single {
+                    JsValue::Null | JsValue::Undefined => 0.0,
+                    other => to_number(vm, other),
+                },
+                _ => f64::NAN,
+            },
+            _ => f64::NAN,
+        },
+        _ => f64::NAN,
+    }
+}
+
+/// ToBoolean for any value.
+pub fn to_boolean(value: &JsValue) -> bool {
+    match value {
+        JsValue::Boolean(b) => *b,
+        JsValue::Number(n) => !(*n == 0.0 || n.is_nan()),
+        JsValue::String(s) => !s.is_empty(),
+        JsValue::Null | JsValue::Undefined => false,
+        _ => true,
+    }
+}
+
+/// Number(value) / new Number(value) - wrappers are represented by their primitive
+pub fn native_number_constructor(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
+    match args.first() {
+        Some(value) => JsValue::Number(to_number(vm, value)),
+        None => JsValue::Number(0.0),
+    }
+}
+
+/// Boolean(value) / new Boolean(value)
+pub fn native_boolean_constructor(_vm: &mut VM, args: Vec<JsValue>) -> JsValue {
+    JsValue::Boolean(args.first().is_some_and(to_boolean))
+}
+
+/// parseInt(string, radix?)
+pub fn native_parse_int(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
+    let input = match native_string_constructor(vm, args.first().cloned().into_iter().collect()) {
+        JsValue::String(s) => s,
+        _ => String::new(),
+    };
+    let mut s = input.trim_start();
+    let negative = s.starts_with('-');
+    s = s.strip_prefix(['+', '-']).unwrap_or(s);
+
+    // ToInt32, so 2^32 + 16 still means 16
+    let mut radix = match args.get(1) {
+        Some(value) => {
+            let r = to_number(vm, value);
+            if r.is_finite() {
+                r.trunc().rem_euclid(4294967296.0) as u32 as i32 as i64
+            } else {
+                0
+            }
+        }
+        None => 0,
+    };
+    if radix != 0 && !(2..=36).contains(&radix) {
+        return JsValue::Number(f64::NAN);
+    }
+    let has_hex_prefix = s.starts_with("0x") || s.starts_with("0X");
+    if (radix == 0 || radix == 16) && has_hex_prefix {
+        s = &s[2..];
+        radix = 16;
+    }
+    if radix == 0 {
+        radix = 10;
+    }
+
+    let end = s
+        .find(|c: char| !c.is_digit(radix as u32))
+        .unwrap_or(s.len());
+    let digits = &s[..end];
+    if digits.is_empty() {
+        return JsValue::Number(f64::NAN);
+    }
+    // Decimal digits round once, like a literal; other radixes accumulate
+    let value = if radix == 10 {
+        digits.parse::<f64>().unwrap_or(f64::NAN)
+    } else {
+        digits.chars().fold(0.0, |acc, c| {
+            acc * radix as f64 + c.to_digit(radix as u32).unwrap_or(0) as f64
+        })
+    };
+    JsValue::Number(if negative { -value } else { value })
+}
+
+/// parseFloat(string)
+pub fn native_parse_float(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
+    let input = match native_string_constructor(vm, args.first().cloned().into_iter().collect()) {
+        JsValue::String(s) => s,
+        _ => String::new(),
+    };
+    let s = input.trim_start();
+    let unsigned = s.strip_prefix(['+', '-']).unwrap_or(s);
+    if unsigned.starts_with("Infinity") {
+        return JsValue::Number(if s.starts_with('-') {
+            f64::NEG_INFINITY
+        } else {
+            f64::INFINITY
+        });
+    }
+
+    // Longest prefix matching: sign? digits* (. digits*)? (e sign? digits+)?
+    let bytes = s.as_bytes();
+    let mut end = s.len() - unsigned.len();
+    let mut mantissa_digits = 0;
+    while end < bytes.len() && bytes[end].is_ascii_digit() {
+        end += 1;
+        mantissa_digits += 1;
+    }
+    if end < bytes.len() && bytes[end] == b'.' {
+        end += 1;
+        while end < bytes.len() && bytes[end].is_ascii_digit() {
+            end += 1;
+            mantissa_digits += 1;
+        }
+    }
+    if mantissa_digits == 0 {
+        return JsValue::Number(f64::NAN);
+    }
+    if end < bytes.len() && (bytes[end] == b'e' || bytes[end] == b'E') {
+        let mut exp_end = end + 1;
+        if exp_end < bytes.len() && (bytes[exp_end] == b'+' || bytes[exp_end] == b'-') {
+            exp_end += 1;
+        }
+        let digits_start = exp_end;
+        while exp_end < bytes.len() && bytes[exp_end].is_ascii_digit() {
+            exp_end += 1;
+        }
+        if exp_end > digits_start {
+            end = exp_end;
+        }
+    }
+    JsValue::Number(s[..end].parse::<f64>().unwrap_or(f64::NAN))
+}
+
+/// isNaN(value) - coerces its argument
+pub fn native_is_nan(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
+    let n = args.first().map_or(f64::NAN, |v| to_number(vm, v));
+    JsValue::Boolean(n.is_nan())
+}
+
+/// isFinite(value) - coerces its argument
+pub fn native_is_finite(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
+    let n = args.first().map_or(f64::NAN, |v| to_number(vm, v));
+    JsValue::Boolean(n.is_finite())
+}
+
+/// Number.isNaN(value) - no coercion
+pub fn native_number_is_nan(_vm: &mut VM, args: Vec<JsValue>) -> JsValue {
+    JsValue::Boolean(matches!(args.first(), Some(JsValue::Number(n)) if n.is_nan()))
+}
+
+/// Number.isFinite(value) - no coercion
+pub fn native_number_is_finite(_vm: &mut VM, args: Vec<JsValue>) -> JsValue {
+    JsValue::Boolean(matches!(args.first(), Some(JsValue::Number(n)) if n.is_finite()))
+}
+
+/// Number.isInteger(value)
+pub fn native_number_is_integer(_vm: &mut VM, args: Vec<JsValue>) -> JsValue {
+    JsValue::Boolean(
+        matches!(args.first(), Some(JsValue::Number(n)) if n.is_finite() && n.trunc() == *n),
+    )
+}
+
 pub fn native_string_from_char_code(_vm: &mut VM, args: Vec<JsValue>) -> JsValue {
     let mut result = String::new();
 
@@ -465,6 +819,27 @@ pub fn native_string_from_char_code(_vm: &mut VM, args: Vec<JsValue>) -> JsValue
 // JSON Functions (minimal - needed for compiler AST output)
 // ============================================================================
 
+/// QuoteJSONString: only `"`, `\` and control characters are escaped
+fn json_quote(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\u{8}' => out.push_str("\\b"),
+            '\u{c}' => out.push_str("\\f"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
 fn json_stringify_value(vm: &VM, value: &JsValue, indent: usize, pretty: bool) -> String {
     let indent_str = if pretty {
         "  ".repeat(indent)
@@ -484,30 +859,13 @@ fn json_stringify_value(vm: &VM, value: &JsValue, indent: usize, pretty: bool) -
         JsValue::Undefined => "null".to_string(), // undefined becomes null in JSON
         JsValue::Boolean(b) => b.to_string(),
         JsValue::Number(n) => {
-            if n.is_nan() {
-                "null".to_string()
-            } else if n.is_infinite() {
-                "null".to_string()
+            if n.is_finite() {
+                number_to_string(*n)
             } else {
-                n.to_string()
+                "null".to_string()
             }
         }
-        JsValue::String(s) => {
-            // Escape special characters
-            let escaped: String = s
-                .chars()
-                .map(|c| match c {
-                    '"' => "\\\"".to_string(),
-                    '\\' => "\\\\".to_string(),
-                    '\n' => "\\n".to_string(),
-                    '\r' => "\\r".to_string(),
-                    '\t' => "\\t".to_string(),
-                    c if c.is_control() => format!("\\u{:04x}", c as u32),
-                    c => c.to_string(),
-                })
-                .collect();
-            format!("\"{}\"", escaped)
-        }
+        JsValue::String(s) => json_quote(s),
         JsValue::Object(ptr) => {
             if let Some(HeapObject { data }) = vm.heap.get(*ptr) {
                 match data {
@@ -535,21 +893,23 @@ fn json_stringify_value(vm: &VM, value: &JsValue, indent: usize, pretty: bool) -
                         }
                     }
                     HeapData::Object(props) => {
-                        if props.is_empty() {
+                        // Only enumerable properties, as `Object.keys` lists them
+                        let mut items: Vec<String> = props
+                            .iter()
+                            .filter(|(k, _)| is_enumerable(vm, *ptr, k))
+                            .map(|(k, v)| {
+                                format!(
+                                    "{}{}:{}{}",
+                                    next_indent,
+                                    json_quote(k),
+                                    space,
+                                    json_stringify_value(vm, v, indent + 1, pretty)
+                                )
+                            })
+                            .collect();
+                        if items.is_empty() {
                             "{}".to_string()
                         } else {
-                            let mut items: Vec<String> = props
-                                .iter()
-                                .map(|(k, v)| {
-                                    format!(
-                                        "{}\"{}\":{}{}",
-                                        next_indent,
-                                        k,
-                                        space,
-                                        json_stringify_value(vm, v, indent + 1, pretty)
-                                    )
-                                })
-                                .collect();
                             items.sort(); // Sort for consistent output
                             format!(
                                 "{{{}{}{}{}}}",
@@ -598,8 +958,11 @@ pub fn native_json_parse(_vm: &mut VM, _args: Vec<JsValue>) -> JsValue {
 // ============================================================================
 
 /// Get environment variable
-pub fn native_getenv(_vm: &mut VM, args: Vec<JsValue>) -> JsValue {
+pub fn native_getenv(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
     if let Some(JsValue::String(name)) = args.first() {
+        if !permitted(vm, Capability::Env, name) {
+            return JsValue::Undefined;
+        }
         match std::env::var(name) {
             Ok(value) => JsValue::String(value),
             Err(_) => JsValue::Undefined,
@@ -610,9 +973,12 @@ pub fn native_getenv(_vm: &mut VM, args: Vec<JsValue>) -> JsValue {
 }
 
 /// Set environment variable
-pub fn native_setenv(_vm: &mut VM, args: Vec<JsValue>) -> JsValue {
+pub fn native_setenv(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
     if let (Some(JsValue::String(name)), Some(JsValue::String(value))) = (args.first(), args.get(1))
     {
+        if !permitted(vm, Capability::Env, name) {
+            return JsValue::Undefined;
+        }
         // SAFETY: Setting environment variables is inherently unsafe in multi-threaded contexts,
         // but we control when this is called and it's a common operation in CLI tools.
         unsafe {
@@ -624,6 +990,47 @@ pub fn native_setenv(_vm: &mut VM, args: Vec<JsValue>) -> JsValue {
     }
 }
 
+/// permissions.query({ name, path | host | variable | command }): returns
+/// `{ state }`, one of "granted", "prompt" or "denied". Never prompts.
+pub fn native_permissions_query(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
+    let descriptor = match args.first() {
+        Some(JsValue::Object(ptr)) => match vm.heap.get(*ptr) {
+            Some(HeapObject {
+                data: HeapData::Object(props),
+            }) => props.clone(),
+            _ => Default::default(),
+        },
+        _ => Default::default(),
+    };
+    let Some(JsValue::String(name)) = descriptor.get("name") else {
+        eprintln!("permissions.query: descriptor must have a name");
+        return JsValue::Undefined;
+    };
+    let cap = match Capability::parse(name) {
+        Ok(cap) => cap,
+        Err(e) => {
+            eprintln!("permissions.query: {}", e);
+            return JsValue::Undefined;
+        }
+    };
+    let target = match descriptor.get(cap.target_key()) {
+        Some(JsValue::String(target)) => Some(target.as_str()),
+        _ => None,
+    };
+    let state = vm.permissions.query(cap, target);
+
+    let mut status = std::collections::HashMap::new();
+    status.insert(
+        "state".to_string(),
+        JsValue::String(state.as_str().to_string()),
+    );
+    let ptr = vm.heap.len();
+    vm.heap.push(HeapObject {
+        data: HeapData::Object(status),
+    });
+    JsValue::Object(ptr)
+}
+
 /// Get current working directory
 pub fn native_cwd(_vm: &mut VM, _args: Vec<JsValue>) -> JsValue {
     match std::env::current_dir() {
@@ -632,6 +1039,162 @@ pub fn native_cwd(_vm: &mut VM, _args: Vec<JsValue>) -> JsValue {
     }
 }
 
+/// process.memoryUsage(): heap snapshot summary (see `vm::heap_snapshot`)
+pub fn native_memory_usage(vm: &mut VM, _args: Vec<JsValue>) -> JsValue {
+    let snapshot = crate::vm::heap_snapshot::HeapSnapshot::capture(vm);
+    let mut report = snapshot.to_json();
+    report["heapUsed"] = snapshot.total.bytes.into();
+    json_to_js(vm, &report)
+}
+
+/// runtime.eventLoop(): queue lengths and task timings (see `vm::loop_stats`)
+pub fn native_event_loop_stats(vm: &mut VM, _args: Vec<JsValue>) -> JsValue {
+    use crate::vm::TaskPriority;
+    use crate::vm::loop_stats::millis;
+    let stats = &vm.loop_stats;
+    let report = serde_json::json!({
+        "pendingTimers": vm.pending_timers(),
+        "queuedTasks": vm.task_queue.len(),
+        "queuedByPriority": {
+            "high": vm.task_queue.len_of(TaskPriority::High),
+            "normal": vm.task_queue.len_of(TaskPriority::Normal),
+            "low": vm.task_queue.len_of(TaskPriority::Low),
+        },
+        "resolvedQueue": vm.resolved_queue.len(),
+        "microtasks": vm.scheduler.microtasks(),
+        "immediates": vm.scheduler.immediates(),
+        "pendingCompletions": vm.scheduler.outstanding(),
+        "tasksRun": stats.tasks_run,
+        "slowTasks": stats.slow_tasks,
+        "lastTaskMs": millis(stats.last_task_time),
+        "maxTaskMs": millis(stats.max_task_time),
+        "totalTaskMs": millis(stats.total_task_time),
+        "maxTimerLagMs": millis(stats.max_timer_lag),
+    });
+    json_to_js(vm, &report)
+}
+
+/// runtime.queueTask(callback, priority = "normal"): run `callback` on the
+/// event loop; "high" goes ahead of I/O callbacks, "low" waits for idle time
+pub fn native_queue_task(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
+    use crate::vm::TaskPriority;
+    let mut args = args.into_iter();
+    let callback = args.next().unwrap_or(JsValue::Undefined);
+    if !matches!(
+        callback,
+        JsValue::Function { .. } | JsValue::NativeFunction(_)
+    ) {
+        eprintln!("runtime.queueTask: callback must be a function");
+        return JsValue::Undefined;
+    }
+    let priority = match args.next() {
+        None | Some(JsValue::Undefined) => TaskPriority::Normal,
+        Some(JsValue::String(name)) => match TaskPriority::parse(&name) {
+            Ok(priority) => priority,
+            Err(e) => {
+                eprintln!("runtime.queueTask: {}", e);
+                return JsValue::Undefined;
+            }
+        },
+        Some(_) => {
+            eprintln!("runtime.queueTask: priority must be \"high\", \"normal\" or \"low\"");
+            return JsValue::Undefined;
+        }
+    };
+    vm.queue_task(callback, vec![], priority);
+    JsValue::Undefined
+}
+
+/// A callback argument for the loop's scheduling natives
+fn scheduled_callback(name: &str, callback: Option<JsValue>) -> Option<JsValue> {
+    match callback {
+        Some(callback @ (JsValue::Function { .. } | JsValue::NativeFunction(_))) => Some(callback),
+        _ => {
+            eprintln!("{}: callback must be a function", name);
+            None
+        }
+    }
+}
+
+/// setImmediate(callback, ...args): run `callback` with `args` once the
+/// event loop has handled what is ready now. Returns an id for
+/// `clearImmediate`.
+pub fn native_set_immediate(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
+    let mut args = args.into_iter();
+    let Some(callback) = scheduled_callback("setImmediate", args.next()) else {
+        return JsValue::Undefined;
+    };
+    let id = vm.scheduler.set_immediate(crate::vm::Task {
+        function_ptr: callback,
+        args: args.collect(),
+    });
+    JsValue::Number(id as f64)
+}
+
+/// clearImmediate(id): cancel an immediate that has not run yet
+pub fn native_clear_immediate(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
+    if let Some(JsValue::Number(id)) = args.first()
+        && *id >= 1.0
+        && *id <= u32::MAX as f64
+    {
+        vm.scheduler.clear_immediate(*id as u32);
+    }
+    JsValue::Undefined
+}
+
+/// queueMicrotask(callback): run `callback` as soon as the current task
+/// finishes, before any other task, timer or immediate
+pub fn native_queue_microtask(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
+    let Some(callback) = scheduled_callback("queueMicrotask", args.into_iter().next()) else {
+        return JsValue::Undefined;
+    };
+    vm.scheduler.queue_microtask(crate::vm::Task {
+        function_ptr: callback,
+        args: vec![],
+    });
+    JsValue::Undefined
+}
+
+/// Promise.resolve(value): `value` itself if it is a promise, otherwise a
+/// promise already fulfilled with it. Async functions wrap their result
+/// with it.
+pub fn native_promise_resolve(_vm: &mut VM, args: Vec<JsValue>) -> JsValue {
+    match args.into_iter().next().unwrap_or(JsValue::Undefined) {
+        promise @ JsValue::Promise(_) => promise,
+        value => JsValue::Promise(crate::vm::value::Promise::with_value(value)),
+    }
+}
+
+/// Build heap values mirroring a JSON value
+fn json_to_js(vm: &mut VM, value: &serde_json::Value) -> JsValue {
+    use serde_json::Value;
+    match value {
+        Value::Null => JsValue::Null,
+        Value::Bool(b) => JsValue::Boolean(*b),
+        Value::Number(n) => JsValue::Number(n.as_f64().unwrap_or(f64::NAN)),
+        Value::String(s) => JsValue::String(s.clone()),
+        Value::Array(items) => {
+            let elements = items.iter().map(|item| json_to_js(vm, item)).collect();
+            let ptr = vm.heap.len();
+            vm.heap.push(HeapObject {
+                data: HeapData::Array(elements),
+            });
+            JsValue::Object(ptr)
+        }
+        Value::Object(entries) => {
+            let props = entries
+                .iter()
+                .map(|(key, item)| (key.clone(), json_to_js(vm, item)))
+                .collect();
+            let ptr = vm.heap.len();
+            vm.heap.push(HeapObject {
+                data: HeapData::Object(props),
+            });
+            JsValue::Object(ptr)
+        }
+    }
+}
+
 /// Change current working directory
 pub fn native_chdir(_vm: &mut VM, args: Vec<JsValue>) -> JsValue {
     if let Some(JsValue::String(path)) = args.first() {
@@ -644,13 +1207,18 @@ pub fn native_chdir(_vm: &mut VM, args: Vec<JsValue>) -> JsValue {
     }
 }
 
-/// Exit the process with a status code
-pub fn native_exit(_vm: &mut VM, args: Vec<JsValue>) -> JsValue {
+/// Exit the process with a status code, `process.exitCode` by default
+pub fn native_exit(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
     let code = if let Some(JsValue::Number(n)) = args.first() {
         *n as i32
     } else {
-        0
+        process::exit_code(vm).unwrap_or(0)
     };
+    let _ = std::io::Write::flush(&mut std::io::stdout());
+    // Destructors don't run on exit; keep the log of what led up to it
+    if let Some(tracer) = vm.tracer.take() {
+        let _ = tracer.finish();
+    }
     std::process::exit(code);
 }
 
@@ -664,6 +1232,9 @@ pub fn native_exec(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
         Some(JsValue::String(cmd)) => cmd.clone(),
         _ => return create_exec_error(vm, "exec requires a command string"),
     };
+    if let Err(e) = vm.permissions.check(Capability::Run, Some(&command)) {
+        return create_exec_error(vm, &e);
+    }
 
     // Parse arguments array if provided
     let mut cmd_args: Vec<String> = Vec::new();
@@ -680,7 +1251,20 @@ pub fn native_exec(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
     }
 
     // Execute the command
-    let output = Command::new(&command).args(&cmd_args).output();
+    let mut output = Command::new(&command).args(&cmd_args).output();
+
+    // Shell built-ins (`dir`, `echo`) and `.cmd`/`.bat` shims (`npm`) are
+    // only reachable through cmd.exe on Windows
+    if cfg!(windows)
+        && let Err(e) = &output
+        && e.kind() == std::io::ErrorKind::NotFound
+    {
+        output = Command::new("cmd")
+            .arg("/C")
+            .arg(&command)
+            .args(&cmd_args)
+            .output();
+    }
 
     match output {
         Ok(result) => {
@@ -723,17 +1307,23 @@ fn create_exec_error(vm: &mut VM, message: &str) -> JsValue {
 
 /// Synchronous HTTP fetch - returns response object
 /// Usage: __ffi_fetch(url, options?)
-/// options: { method?: string, headers?: object, body?: string }
+/// options: { method?: string, headers?: object, body?: string, stream?: boolean }
+/// With `stream: true` the response body is a readable stream instead of a
+/// string.
 pub fn native_fetch(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
     let url = match args.first() {
         Some(JsValue::String(u)) => u.clone(),
         _ => return create_fetch_error(vm, "fetch requires a URL string"),
     };
+    if let Err(e) = vm.permissions.check(Capability::Net, Some(url_host(&url))) {
+        return create_fetch_error(vm, &e);
+    }
 
     // Default options
     let mut method = "GET".to_string();
     let mut headers: Vec<(String, String)> = Vec::new();
     let mut body: Option<String> = None;
+    let mut stream = false;
 
     // Parse options if provided
     if let Some(JsValue::Object(opts_ptr)) = args.get(1)
@@ -747,6 +1337,7 @@ pub fn native_fetch(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
         if let Some(JsValue::String(b)) = opts.get("body") {
             body = Some(b.clone());
         }
+        stream = matches!(opts.get("stream"), Some(JsValue::Boolean(true)));
         if let Some(JsValue::Object(hdrs_ptr)) = opts.get("headers")
             && let Some(HeapObject {
                 data: HeapData::Object(hdrs),
@@ -797,10 +1388,10 @@ pub fn native_fetch(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
             }
 
             // Read body
-            let body_text = resp.into_string().unwrap_or_default();
+            let body = response_body(vm, resp, stream);
 
             // Create response object
-            create_fetch_response(vm, status, &status_text, resp_headers, &body_text, false)
+            create_fetch_response(vm, status, &status_text, resp_headers, body, false)
         }
         Err(ureq::Error::Status(code, resp)) => {
             let status_text = resp.status_text().to_string();
@@ -810,19 +1401,29 @@ pub fn native_fetch(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
                     resp_headers.insert(name.to_lowercase(), JsValue::String(value.to_string()));
                 }
             }
-            let body_text = resp.into_string().unwrap_or_default();
-            create_fetch_response(vm, code, &status_text, resp_headers, &body_text, false)
+            let body = response_body(vm, resp, stream);
+            create_fetch_response(vm, code, &status_text, resp_headers, body, false)
         }
         Err(e) => create_fetch_error(vm, &format!("Fetch error: {}", e)),
     }
 }
 
+/// The whole body as a string, or a stream reading it as it arrives
+fn response_body(vm: &mut VM, resp: ureq::Response, stream: bool) -> JsValue {
+    if stream {
+        let reader = resp.into_reader();
+        stream::alloc_readable(vm, reader, stream::DEFAULT_CHUNK_SIZE, false)
+    } else {
+        JsValue::String(resp.into_string().unwrap_or_default())
+    }
+}
+
 fn create_fetch_response(
     vm: &mut VM,
     status: u16,
     status_text: &str,
     headers: std::collections::HashMap<String, JsValue>,
-    body: &str,
+    body: JsValue,
     _redirected: bool,
 ) -> JsValue {
     // Create headers object
@@ -843,7 +1444,7 @@ fn create_fetch_response(
         JsValue::Boolean((200..300).contains(&status)),
     );
     response.insert("headers".to_string(), JsValue::Object(headers_ptr));
-    response.insert("body".to_string(), JsValue::String(body.to_string()));
+    response.insert("body".to_string(), body);
     response.insert("error".to_string(), JsValue::Undefined);
 
     let response_ptr = vm.heap.len();
@@ -853,6 +1454,15 @@ fn create_fetch_response(
     JsValue::Object(response_ptr)
 }
 
+/// `host[:port]` of a URL, what `--allow-net` grants are matched against
+fn url_host(url: &str) -> &str {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or(rest);
+    authority
+        .rsplit_once('@')
+        .map_or(authority, |(_, host)| host)
+}
+
 fn create_fetch_error(vm: &mut VM, message: &str) -> JsValue {
     let mut response = std::collections::HashMap::new();
     response.insert("status".to_string(), JsValue::Number(0.0));
@@ -907,7 +1517,7 @@ pub fn native_stdout_write(_vm: &mut VM, args: Vec<JsValue>) -> JsValue {
     if let Some(val) = args.first() {
         let s = match val {
             JsValue::String(s) => s.clone(),
-            JsValue::Number(n) => n.to_string(),
+            JsValue::Number(n) => number_to_string(*n),
             JsValue::Boolean(b) => b.to_string(),
             JsValue::Null => "null".to_string(),
             JsValue::Undefined => "undefined".to_string(),
@@ -925,11 +1535,25 @@ pub fn native_stdout_write(_vm: &mut VM, args: Vec<JsValue>) -> JsValue {
 
 /// Object.keys(obj) - Returns an array of the object's own enumerable property names
 pub fn native_object_keys(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
+    if let Some(JsValue::Object(ptr)) = args.first()
+        && let Some(vars) = process::env_entries(vm, *ptr)
+    {
+        let keys = vars.into_iter().map(|(name, _)| JsValue::String(name));
+        let arr_ptr = vm.heap.len();
+        vm.heap.push(HeapObject {
+            data: HeapData::Array(keys.collect()),
+        });
+        return JsValue::Object(arr_ptr);
+    }
     if let Some(JsValue::Object(ptr)) = args.first()
         && let Some(HeapObject { data }) = vm.heap.get(*ptr)
     {
         let keys: Vec<JsValue> = match data {
-            HeapData::Object(props) => props.keys().map(|k| JsValue::String(k.clone())).collect(),
+            HeapData::Object(props) => props
+                .keys()
+                .filter(|k| is_enumerable(vm, *ptr, k))
+                .map(|k| JsValue::String(k.clone()))
+                .collect(),
             HeapData::Array(arr) => (0..arr.len())
                 .map(|i| JsValue::String(i.to_string()))
                 .collect(),
@@ -948,3 +1572,704 @@ pub fn native_object_keys(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
     });
     JsValue::Object(arr_ptr)
 }
+
+/// An object's own properties in display order: integer keys ascending,
+/// then the rest by name. Objects record no insertion order, so sorting is
+/// what keeps the output stable. Internal `__name__` slots are left out.
+pub fn object_entries(props: &std::collections::HashMap<String, JsValue>) -> Vec<(&str, &JsValue)> {
+    let mut entries: Vec<(&str, &JsValue)> = props
+        .iter()
+        .filter(|(key, _)| !(key.starts_with("__") && key.ends_with("__")))
+        .map(|(key, value)| (key.as_str(), value))
+        .collect();
+    entries.sort_by(|(a, _), (b, _)| {
+        let index = |key: &str| key.parse::<u32>().ok().filter(|i| i.to_string() == key);
+        match (index(a), index(b)) {
+            (Some(x), Some(y)) => x.cmp(&y),
+            (Some(_), None) => std::cmp::Ordering::Less,
+            (None, Some(_)) => std::cmp::Ordering::Greater,
+            (None, None) => a.cmp(b),
+        }
+    });
+    entries
+}
+
+/// Object.entries(obj) - Returns an array of [key, value] pairs
+pub fn native_object_entries(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
+    let vars = match args.first() {
+        Some(JsValue::Object(ptr)) => process::env_entries(vm, *ptr),
+        _ => None,
+    };
+    let pairs: Vec<(String, JsValue)> = match (args.first(), vars) {
+        (_, Some(vars)) => vars
+            .into_iter()
+            .map(|(name, value)| (name, JsValue::String(value)))
+            .collect(),
+        (Some(JsValue::Object(ptr)), None) => match vm.heap.get(*ptr).map(|obj| &obj.data) {
+            Some(HeapData::Object(props)) => object_entries(props)
+                .into_iter()
+                .filter(|(key, _)| is_enumerable(vm, *ptr, key))
+                .map(|(key, value)| (key.to_string(), value.clone()))
+                .collect(),
+            Some(HeapData::Array(arr)) => arr
+                .iter()
+                .enumerate()
+                .map(|(i, value)| (i.to_string(), value.clone()))
+                .collect(),
+            _ => Vec::new(),
+        },
+        _ => Vec::new(),
+    };
+    let mut entries = Vec::with_capacity(pairs.len());
+    for (key, value) in pairs {
+        let pair_ptr = vm.heap.len();
+        vm.heap.push(HeapObject {
+            data: HeapData::Array(vec![JsValue::String(key), value]),
+        });
+        entries.push(JsValue::Object(pair_ptr));
+    }
+    let arr_ptr = vm.heap.len();
+    vm.heap.push(HeapObject {
+        data: HeapData::Array(entries),
+    });
+    JsValue::Object(arr_ptr)
+}
+
+/// Name of the property stored under `key`. Accessors are stored as
+/// `getter:name` and `setter:name` and take the attributes of `name`.
+fn accessor_name(key: &str) -> Option<&str> {
+    key.strip_prefix("getter:")
+        .or_else(|| key.strip_prefix("setter:"))
+}
+
+/// Whether `Object.keys` lists the stored property `key`
+fn is_enumerable(vm: &VM, ptr: usize, key: &str) -> bool {
+    let name = accessor_name(key).unwrap_or(key);
+    vm.descriptors.flags(ptr, name).enumerable
+}
+
+fn descriptor_key(key: Option<&JsValue>) -> Option<String> {
+    match key {
+        Some(JsValue::String(s)) => Some(s.clone()),
+        Some(JsValue::Number(n)) => Some(number_to_string(*n)),
+        _ => None,
+    }
+}
+
+/// Object.defineProperty(obj, key, descriptor) - Adds or changes a property
+/// with explicit attributes. Attributes the descriptor leaves out are false
+/// for a new property and unchanged for an existing one.
+pub fn native_define_property(vm: &mut VM, args: Vec<JsValue>) -> NativeResult {
+    let (Some(JsValue::Object(ptr)), Some(key), Some(JsValue::Object(desc_ptr))) =
+        (args.first(), descriptor_key(args.get(1)), args.get(2))
+    else {
+        return Err(type_error(
+            "Object.defineProperty expects an object, a key and a descriptor",
+        ));
+    };
+    let (ptr, desc_ptr) = (*ptr, *desc_ptr);
+    let desc = match vm.heap.get(desc_ptr).map(|obj| &obj.data) {
+        Some(HeapData::Object(props)) => props.clone(),
+        _ => std::collections::HashMap::new(),
+    };
+    let getter_key = format!("getter:{}", key);
+    let setter_key = format!("setter:{}", key);
+    let Some(HeapData::Object(props)) = vm.heap.get(ptr).map(|obj| &obj.data) else {
+        return Err(type_error(
+            "Object.defineProperty called on a non-plain object",
+        ));
+    };
+    let exists = [&key, &getter_key, &setter_key]
+        .iter()
+        .any(|k| props.contains_key(k.as_str()));
+
+    let current = vm.descriptors.flags(ptr, &key);
+    let base = if exists { current } else { PropertyFlags::NONE };
+    let flag = |name: &str, default: bool| desc.get(name).map_or(default, to_boolean);
+    let flags = PropertyFlags {
+        writable: flag("writable", base.writable),
+        enumerable: flag("enumerable", base.enumerable),
+        configurable: flag("configurable", base.configurable),
+    };
+
+    if exists && !current.configurable {
+        // Only the value of a writable property may still change
+        let changes_value = desc.contains_key("value") && !current.writable;
+        let changes_kind = desc.contains_key("get") || desc.contains_key("set");
+        if changes_value || changes_kind || flags != current {
+            return Err(type_error(&format!("Cannot redefine property: {}", key)));
+        }
+    }
+    if !exists && !vm.descriptors.is_extensible(ptr) {
+        return Err(type_error(&format!(
+            "Cannot define property {}, object is not extensible",
+            key
+        )));
+    }
+
+    if let Some(HeapData::Object(props)) = vm.heap.get_mut(ptr).map(|obj| &mut obj.data) {
+        let accessor = desc.get("get").or(desc.get("set")).is_some();
+        if accessor {
+            props.remove(&key);
+            for (stored, name) in [(&getter_key, "get"), (&setter_key, "set")] {
+                match desc.get(name) {
+                    Some(f @ JsValue::Function { .. }) => {
+                        props.insert(stored.clone(), f.clone());
+                    }
+                    _ => {
+                        props.remove(stored.as_str());
+                    }
+                }
+            }
+        } else if let Some(value) = desc.get("value") {
+            props.remove(&getter_key);
+            props.remove(&setter_key);
+            props.insert(key.clone(), value.clone());
+        } else if !exists {
+            props.insert(key.clone(), JsValue::Undefined);
+        }
+    }
+    vm.descriptors.set_flags(ptr, &key, flags);
+    Ok(JsValue::Object(ptr))
+}
+
+/// Object.getOwnPropertyDescriptor(obj, key) - `{ value, writable,
+/// enumerable, configurable }` for a data property, `{ get, set,
+/// enumerable, configurable }` for an accessor, or undefined
+pub fn native_get_own_property_descriptor(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
+    let (Some(JsValue::Object(ptr)), Some(key)) = (args.first(), descriptor_key(args.get(1)))
+    else {
+        return JsValue::Undefined;
+    };
+    let ptr = *ptr;
+    let flags = vm.descriptors.flags(ptr, &key);
+    let mut desc = std::collections::HashMap::new();
+    match vm.heap.get(ptr).map(|obj| &obj.data) {
+        Some(HeapData::Object(props)) => {
+            let getter = props.get(&format!("getter:{}", key));
+            let setter = props.get(&format!("setter:{}", key));
+            if let Some(value) = props.get(&key) {
+                desc.insert("value".to_string(), value.clone());
+                desc.insert("writable".to_string(), JsValue::Boolean(flags.writable));
+            } else if getter.is_some() || setter.is_some() {
+                let or_undefined = |f: Option<&JsValue>| f.cloned().unwrap_or(JsValue::Undefined);
+                desc.insert("get".to_string(), or_undefined(getter));
+                desc.insert("set".to_string(), or_undefined(setter));
+            } else {
+                return JsValue::Undefined;
+            }
+        }
+        Some(HeapData::Array(arr)) => {
+            let value = match key.parse::<usize>().ok().and_then(|i| arr.get(i)) {
+                Some(value) => value.clone(),
+                None if key == "length" => JsValue::Number(arr.len() as f64),
+                None => return JsValue::Undefined,
+            };
+            desc.insert("value".to_string(), value);
+            desc.insert("writable".to_string(), JsValue::Boolean(flags.writable));
+        }
+        _ => return JsValue::Undefined,
+    }
+    desc.insert("enumerable".to_string(), JsValue::Boolean(flags.enumerable));
+    desc.insert(
+        "configurable".to_string(),
+        JsValue::Boolean(flags.configurable),
+    );
+    let desc_ptr = vm.heap.len();
+    vm.heap.push(HeapObject {
+        data: HeapData::Object(desc),
+    });
+    JsValue::Object(desc_ptr)
+}
+
+/// Own property names of an object or array, as stored
+fn own_keys(vm: &VM, ptr: usize) -> Vec<String> {
+    match vm.heap.get(ptr).map(|obj| &obj.data) {
+        Some(HeapData::Object(props)) => props
+            .keys()
+            .filter(|k| !(k.starts_with("__") && k.ends_with("__")))
+            .cloned()
+            .collect(),
+        Some(HeapData::Array(arr)) => (0..arr.len()).map(|i| i.to_string()).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Object.freeze(obj) - Makes every own property read-only and
+/// non-configurable and stops new ones being added. Returns `obj`.
+pub fn native_object_freeze(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
+    let target = args.first().cloned().unwrap_or(JsValue::Undefined);
+    if let JsValue::Object(ptr) = target {
+        for stored in own_keys(vm, ptr) {
+            let name = accessor_name(&stored).unwrap_or(&stored);
+            let flags = PropertyFlags {
+                writable: false,
+                configurable: false,
+                ..vm.descriptors.flags(ptr, name)
+            };
+            vm.descriptors.set_flags(ptr, name, flags);
+        }
+        vm.descriptors.prevent_extensions(ptr);
+    }
+    target
+}
+
+/// Object.isFrozen(obj) - Whether `obj` can no longer change. Primitives
+/// are frozen.
+pub fn native_object_is_frozen(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
+    let Some(JsValue::Object(ptr)) = args.first() else {
+        return JsValue::Boolean(true);
+    };
+    let frozen = !vm.descriptors.is_extensible(*ptr)
+        && own_keys(vm, *ptr)
+            .iter()
+            .all(|stored| match accessor_name(stored) {
+                Some(name) => !vm.descriptors.flags(*ptr, name).configurable,
+                None => {
+                    let flags = vm.descriptors.flags(*ptr, stored);
+                    !flags.writable && !flags.configurable
+                }
+            });
+    JsValue::Boolean(frozen)
+}
+
+/// Object.is(a, b) - SameValue: `===`, except that `NaN` is itself and `0`
+/// and `-0` differ
+pub fn native_object_is(_vm: &mut VM, args: Vec<JsValue>) -> JsValue {
+    let a = args.first().cloned().unwrap_or(JsValue::Undefined);
+    let b = args.get(1).cloned().unwrap_or(JsValue::Undefined);
+    JsValue::Boolean(a.same_value(&b))
+}
+
+/// Object.hasOwn(obj, key) - Whether `key` is an own property of `obj`.
+/// Also backs `.hasOwnProperty(key)`, where the receiver is args[0].
+pub fn native_object_has_own(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
+    let target = args.first().cloned().unwrap_or(JsValue::Undefined);
+    let key = crate::vm::property::property_key(args.get(1).unwrap_or(&JsValue::Undefined));
+    JsValue::Boolean(crate::vm::property::has_own_property(vm, &target, &key))
+}
+
+/// move(closure) - marks a closure as taking ownership of what it captures.
+/// Only the borrow checker cares; at run time the closure is returned as is.
+pub fn native_move(_vm: &mut VM, args: Vec<JsValue>) -> JsValue {
+    args.into_iter().next().unwrap_or(JsValue::Undefined)
+}
+
+/// value.clone() - deep copy of objects, arrays, maps and sets, where the
+/// receiver is args[0]. References shared within the value (including
+/// cycles) stay shared in the copy. Unlike `structuredClone` (see `clone`),
+/// functions and prototypes are kept, so a cloned instance keeps its class.
+pub fn native_clone(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
+    let value = args.into_iter().next().unwrap_or(JsValue::Undefined);
+    deep_clone(vm, &value, &mut std::collections::HashMap::new())
+}
+
+fn deep_clone(
+    vm: &mut VM,
+    value: &JsValue,
+    copies: &mut std::collections::HashMap<usize, usize>,
+) -> JsValue {
+    let JsValue::Object(ptr) = value else {
+        return value.clone();
+    };
+    if let Some(&copy) = copies.get(ptr) {
+        return JsValue::Object(copy);
+    }
+    let Some(original) = vm.heap.get(*ptr).map(|obj| obj.data.clone()) else {
+        return value.clone();
+    };
+    // Reserve the slot first so cycles back to `ptr` resolve to the copy
+    let copy = vm.heap.len();
+    vm.heap.push(HeapObject {
+        data: HeapData::Array(Vec::new()),
+    });
+    copies.insert(*ptr, copy);
+    let data = match original {
+        HeapData::Object(props) => HeapData::Object(
+            props
+                .into_iter()
+                .map(|(key, v)| (key, deep_clone(vm, &v, copies)))
+                .collect(),
+        ),
+        HeapData::Array(items) => {
+            HeapData::Array(items.iter().map(|v| deep_clone(vm, v, copies)).collect())
+        }
+        HeapData::Map(entries) => HeapData::Map(
+            entries
+                .iter()
+                .map(|(k, v)| (deep_clone(vm, k, copies), deep_clone(vm, v, copies)))
+                .collect(),
+        ),
+        HeapData::Set(items) => {
+            HeapData::Set(items.iter().map(|v| deep_clone(vm, v, copies)).collect())
+        }
+        bytes @ HeapData::ByteStream(_) => bytes,
+        // The copy owns its bytes, even when the original views a mapped file
+        HeapData::Buffer(view) => {
+            HeapData::Buffer(BufferView::new(BufferStorage::Owned(view.bytes().to_vec())))
+        }
+        data @ (HeapData::Decimal(_)
+        | HeapData::WeakMap(_)
+        | HeapData::WeakSet(_)
+        | HeapData::WeakRef(_)
+        | HeapData::Stream(_)
+        | HeapData::Process(_)) => data,
+    };
+    vm.heap[copy].data = data;
+    JsValue::Object(copy)
+}
+
+// ============================================================================
+// Prototype Extensions (registered via VM::register_prototype_method)
+// ============================================================================
+
+/// ToIntegerOrInfinity of an optional argument; `undefined` is 0
+fn integer_arg(vm: &VM, value: Option<&JsValue>) -> f64 {
+    match value {
+        None | Some(JsValue::Undefined) => 0.0,
+        Some(value) => {
+            let n = to_number(vm, value);
+            if n.is_nan() { 0.0 } else { n.trunc() }
+        }
+    }
+}
+
+fn number_receiver(args: &[JsValue], method: &str) -> Result<f64, JsValue> {
+    match args.first() {
+        Some(JsValue::Number(n)) => Ok(*n),
+        _ => Err(type_error(&format!(
+            "Number.prototype.{} requires that 'this' be a Number",
+            method
+        ))),
+    }
+}
+
+/// Number.prototype.toString(radix?) - receiver is args[0]
+pub fn native_number_to_string(vm: &mut VM, args: Vec<JsValue>) -> NativeResult {
+    let n = number_receiver(&args, "toString")?;
+    let radix = match args.get(1) {
+        None | Some(JsValue::Undefined) => 10.0,
+        radix => integer_arg(vm, radix),
+    };
+    if !(2.0..=36.0).contains(&radix) {
+        return Err(JsValue::String(
+            "RangeError: toString() radix must be between 2 and 36".to_string(),
+        ));
+    }
+    Ok(JsValue::String(
+        crate::runtime::number::number_to_radix_string(n, radix as u32),
+    ))
+}
+
+/// Number.prototype.toFixed(digits?) - receiver is args[0]
+pub fn native_number_to_fixed(vm: &mut VM, args: Vec<JsValue>) -> NativeResult {
+    let n = number_receiver(&args, "toFixed")?;
+    let digits = integer_arg(vm, args.get(1));
+    if !(0.0..=100.0).contains(&digits) {
+        return Err(JsValue::String(
+            "RangeError: toFixed() digits argument must be between 0 and 100".to_string(),
+        ));
+    }
+    Ok(JsValue::String(crate::runtime::number::to_fixed(
+        n,
+        digits as usize,
+    )))
+}
+
+/// Number.prototype.toPrecision(precision?) - receiver is args[0]
+pub fn native_number_to_precision(vm: &mut VM, args: Vec<JsValue>) -> NativeResult {
+    let n = number_receiver(&args, "toPrecision")?;
+    if matches!(args.get(1), None | Some(JsValue::Undefined)) {
+        return Ok(JsValue::String(number_to_string(n)));
+    }
+    let precision = integer_arg(vm, args.get(1));
+    if !n.is_finite() {
+        return Ok(JsValue::String(number_to_string(n)));
+    }
+    if !(1.0..=100.0).contains(&precision) {
+        return Err(JsValue::String(
+            "RangeError: toPrecision() argument must be between 1 and 100".to_string(),
+        ));
+    }
+    Ok(JsValue::String(crate::runtime::number::to_precision(
+        n,
+        precision as usize,
+    )))
+}
+
+/// Boolean.prototype.toString() - receiver is args[0]
+pub fn native_boolean_to_string(_vm: &mut VM, args: Vec<JsValue>) -> JsValue {
+    match args.first() {
+        Some(JsValue::Boolean(b)) => JsValue::String(b.to_string()),
+        _ => JsValue::String("false".to_string()),
+    }
+}
+
+/// The array receiver of an Array.prototype method and its callback
+fn array_and_callback(args: &[JsValue], method: &str) -> Result<(usize, JsValue), JsValue> {
+    match (args.first(), args.get(1)) {
+        (
+            Some(JsValue::Object(ptr)),
+            Some(f @ (JsValue::Function { .. } | JsValue::NativeFunction(_))),
+        ) => Ok((*ptr, f.clone())),
+        _ => Err(type_error(&format!(
+            "Array.prototype.{} expects a function",
+            method
+        ))),
+    }
+}
+
+/// Element `index` of the array at `ptr` as it is now; callbacks may have
+/// changed it since the method started
+fn array_item(vm: &VM, ptr: usize, index: usize) -> Option<JsValue> {
+    match vm.heap.get(ptr).map(|obj| &obj.data) {
+        Some(HeapData::Array(items)) => items.get(index).cloned(),
+        _ => None,
+    }
+}
+
+fn array_len(vm: &VM, ptr: usize) -> usize {
+    match vm.heap.get(ptr).map(|obj| &obj.data) {
+        Some(HeapData::Array(items)) => items.len(),
+        _ => 0,
+    }
+}
+
+/// Call `callback(item, index, array)` for each element present when the
+/// method started, stopping at the first exception
+fn each_item(
+    vm: &mut VM,
+    ptr: usize,
+    callback: &JsValue,
+    mut visit: impl FnMut(JsValue, JsValue),
+) -> Result<(), JsValue> {
+    for index in 0..array_len(vm, ptr) {
+        let Some(item) = array_item(vm, ptr, index) else {
+            break;
+        };
+        let args = vec![
+            item.clone(),
+            JsValue::Number(index as f64),
+            JsValue::Object(ptr),
+        ];
+        let result = vm.call_function(callback.clone(), args)?;
+        visit(item, result);
+    }
+    Ok(())
+}
+
+fn new_array(vm: &mut VM, items: Vec<JsValue>) -> JsValue {
+    let ptr = vm.heap.len();
+    vm.heap.push(HeapObject {
+        data: HeapData::Array(items),
+    });
+    JsValue::Object(ptr)
+}
+
+/// Array.prototype.forEach(callback) - receiver is args[0]
+pub fn native_array_for_each(vm: &mut VM, args: Vec<JsValue>) -> NativeResult {
+    let (ptr, callback) = array_and_callback(&args, "forEach")?;
+    each_item(vm, ptr, &callback, |_, _| {})?;
+    Ok(JsValue::Undefined)
+}
+
+/// Array.prototype.map(callback) - receiver is args[0]
+pub fn native_array_map(vm: &mut VM, args: Vec<JsValue>) -> NativeResult {
+    let (ptr, callback) = array_and_callback(&args, "map")?;
+    let mut mapped = Vec::new();
+    each_item(vm, ptr, &callback, |_, result| mapped.push(result))?;
+    Ok(new_array(vm, mapped))
+}
+
+/// Array.prototype.filter(callback) - receiver is args[0]
+pub fn native_array_filter(vm: &mut VM, args: Vec<JsValue>) -> NativeResult {
+    let (ptr, callback) = array_and_callback(&args, "filter")?;
+    let mut kept = Vec::new();
+    each_item(vm, ptr, &callback, |item, keep| {
+        if to_boolean(&keep) {
+            kept.push(item);
+        }
+    })?;
+    Ok(new_array(vm, kept))
+}
+
+/// Stable merge sort. Unlike `sort_by` it copes with comparators that are
+/// not a total order, which script comparators easily aren't, and stops at
+/// the first exception one throws.
+fn merge_sort(
+    mut items: Vec<JsValue>,
+    after: &mut impl FnMut(&JsValue, &JsValue) -> Result<bool, JsValue>,
+) -> Result<Vec<JsValue>, JsValue> {
+    if items.len() <= 1 {
+        return Ok(items);
+    }
+    let right = items.split_off(items.len() / 2);
+    let mut left = merge_sort(items, after)?.into_iter().peekable();
+    let mut right = merge_sort(right, after)?.into_iter().peekable();
+    let mut merged = Vec::with_capacity(left.len() + right.len());
+    while let (Some(a), Some(b)) = (left.peek(), right.peek()) {
+        let next = if after(a, b)? {
+            right.next()
+        } else {
+            left.next()
+        };
+        merged.extend(next);
+    }
+    merged.extend(left);
+    merged.extend(right);
+    Ok(merged)
+}
+
+/// Array.prototype.sort(compare?) - receiver is args[0]. Without a
+/// comparator elements sort by their string form, `undefined` last.
+pub fn native_array_sort(vm: &mut VM, args: Vec<JsValue>) -> NativeResult {
+    let Some(JsValue::Object(ptr)) = args.first() else {
+        return Err(type_error("Array.prototype.sort called on a non-array"));
+    };
+    let ptr = *ptr;
+    let items = match vm.heap.get(ptr).map(|obj| &obj.data) {
+        Some(HeapData::Array(items)) => items.clone(),
+        _ => return Err(type_error("Array.prototype.sort called on a non-array")),
+    };
+    let (mut present, missing): (Vec<JsValue>, Vec<JsValue>) = items
+        .into_iter()
+        .partition(|item| !matches!(item, JsValue::Undefined));
+
+    present = match args.get(1) {
+        Some(compare @ (JsValue::Function { .. } | JsValue::NativeFunction(_))) => {
+            merge_sort(present, &mut |a, b| {
+                let order = vm.call_function(compare.clone(), vec![a.clone(), b.clone()])?;
+                Ok(matches!(order, JsValue::Number(n) if n > 0.0))
+            })?
+        }
+        Some(JsValue::Undefined) | None => {
+            let mut keyed: Vec<(String, JsValue)> = present
+                .into_iter()
+                .map(
+                    |item| match native_string_constructor(vm, vec![item.clone()]) {
+                        JsValue::String(key) => (key, item),
+                        _ => (String::new(), item),
+                    },
+                )
+                .collect();
+            keyed.sort_by(|(a, _), (b, _)| a.encode_utf16().cmp(b.encode_utf16()));
+            keyed.into_iter().map(|(_, item)| item).collect()
+        }
+        Some(_) => {
+            return Err(type_error(
+                "Array.prototype.sort expects a function or undefined",
+            ));
+        }
+    };
+    present.extend(missing);
+    if let Some(HeapData::Array(items)) = vm.heap.get_mut(ptr).map(|obj| &mut obj.data) {
+        *items = present;
+    }
+    Ok(JsValue::Object(ptr))
+}
+
+/// `Array(...)` / `new Array(...)`: a single number is a length, anything
+/// else the elements
+pub fn native_array_constructor(vm: &mut VM, args: Vec<JsValue>) -> NativeResult {
+    let items = match args.as_slice() {
+        [JsValue::Number(len)] => {
+            if *len < 0.0 || len.fract() != 0.0 || *len > u32::MAX as f64 {
+                return Err(JsValue::String(
+                    "RangeError: Invalid array length".to_string(),
+                ));
+            }
+            vec![JsValue::Undefined; *len as usize]
+        }
+        _ => args,
+    };
+    Ok(new_array(vm, items))
+}
+
+/// Array.isArray(value)
+pub fn native_array_is_array(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
+    let is_array = match args.first() {
+        Some(JsValue::Object(ptr)) => matches!(
+            vm.heap.get(*ptr).map(|obj| &obj.data),
+            Some(HeapData::Array(_))
+        ),
+        _ => false,
+    };
+    JsValue::Boolean(is_array)
+}
+
+/// Array.of(...items)
+pub fn native_array_of(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
+    new_array(vm, args)
+}
+
+/// The elements `Array.from` copies out of `source`: the items of an array
+/// or set, a map's `[key, value]` entries, a string's code points, a
+/// buffer's bytes, or indices `0..length` of an array-like object
+fn iterable_items(vm: &mut VM, source: &JsValue) -> Result<Vec<JsValue>, JsValue> {
+    let ptr = match source {
+        JsValue::String(s) => {
+            return Ok(s.chars().map(|c| JsValue::String(c.to_string())).collect());
+        }
+        JsValue::Undefined | JsValue::Null => {
+            return Err(type_error(
+                "Array.from requires an iterable or array-like object",
+            ));
+        }
+        JsValue::Object(ptr) => *ptr,
+        _ => return Ok(Vec::new()),
+    };
+    let items = match vm.heap.get(ptr).map(|obj| &obj.data) {
+        Some(HeapData::Array(items) | HeapData::Set(items)) => items.clone(),
+        Some(HeapData::Map(entries)) => {
+            let entries = entries.clone();
+            return Ok(entries
+                .into_iter()
+                .map(|(key, value)| new_array(vm, vec![key, value]))
+                .collect());
+        }
+        Some(HeapData::Buffer(view)) => view
+            .bytes()
+            .iter()
+            .map(|b| JsValue::Number(*b as f64))
+            .collect(),
+        Some(HeapData::Object(props)) => {
+            let len = props
+                .get("length")
+                .map(|len| to_number(vm, len))
+                .filter(|len| *len > 0.0)
+                .map_or(0, |len| len.min(u32::MAX as f64) as usize);
+            (0..len)
+                .map(|i| {
+                    props
+                        .get(&i.to_string())
+                        .cloned()
+                        .unwrap_or(JsValue::Undefined)
+                })
+                .collect()
+        }
+        _ => Vec::new(),
+    };
+    Ok(items)
+}
+
+/// Array.from(source, mapFn?)
+pub fn native_array_from(vm: &mut VM, args: Vec<JsValue>) -> NativeResult {
+    let source = args.first().cloned().unwrap_or(JsValue::Undefined);
+    let map_fn = match args.get(1) {
+        None | Some(JsValue::Undefined) => None,
+        Some(f @ (JsValue::Function { .. } | JsValue::NativeFunction(_))) => Some(f.clone()),
+        Some(_) => {
+            return Err(type_error(
+                "Array.from: when provided, the second argument must be a function",
+            ));
+        }
+    };
+    let mut items = iterable_items(vm, &source)?;
+    if let Some(map_fn) = map_fn {
+        for (index, item) in items.iter_mut().enumerate() {
+            let args = vec![item.clone(), JsValue::Number(index as f64)];
+            *item = vm.call_function(map_fn.clone(), args)?;
+        }
+    }
+    Ok(new_array(vm, items))
+}
@@ -0,0 +1,305 @@
+//! Host process state behind the `process` global
+//!
+//! - `process.env` is live: reading `process.env.HOME` asks the host
+//!   environment each time, assigning sets the variable (as a string) and
+//!   `delete` removes it. `Object.keys` and `Object.entries` list the
+//!   variables the env permission covers. `get`/`set` remain as methods.
+//! - `process.argv` is `[execPath, script, ...args]`; `__args__` still holds
+//!   only the arguments after the script.
+//! - `process.on(signal, fn)` / `process.off(signal, fn)` listen for
+//!   `SIGINT`, `SIGTERM` and, on Unix, `SIGHUP`, `SIGUSR1` and `SIGUSR2`.
+//!   While a signal has a listener its default action (usually exiting) is
+//!   replaced: the OS handler only records the signal, and the event loop
+//!   calls the listeners with the signal name. Listeners do not keep the
+//!   loop alive on their own.
+//! - `process.exit(code?)` falls back to `process.exitCode`, which is also
+//!   the status of a script that finishes normally.
+
+use super::permitted;
+use crate::vm::permissions::{Capability, PermissionState};
+use crate::vm::value::{HeapData, HeapObject, JsValue};
+use crate::vm::{TaskPriority, VM};
+use std::sync::atomic::{AtomicU32, Ordering};
+
+/// Signals received since the event loop last looked, one bit per number
+static PENDING: AtomicU32 = AtomicU32::new(0);
+
+extern "C" fn record_signal(signum: libc::c_int) {
+    PENDING.fetch_or(1 << signum, Ordering::SeqCst);
+}
+
+fn signal_number(name: &str) -> Option<libc::c_int> {
+    match name {
+        "SIGINT" => Some(libc::SIGINT),
+        "SIGTERM" => Some(libc::SIGTERM),
+        #[cfg(unix)]
+        "SIGHUP" => Some(libc::SIGHUP),
+        #[cfg(unix)]
+        "SIGUSR1" => Some(libc::SIGUSR1),
+        #[cfg(unix)]
+        "SIGUSR2" => Some(libc::SIGUSR2),
+        _ => None,
+    }
+}
+
+/// Route `signum` to `record_signal`, or back to its default action
+fn install(signum: libc::c_int, listening: bool) {
+    let handler = if listening {
+        record_signal as extern "C" fn(libc::c_int) as libc::sighandler_t
+    } else {
+        libc::SIG_DFL
+    };
+    // SAFETY: record_signal only performs an atomic fetch_or, which is
+    // async-signal-safe
+    unsafe {
+        libc::signal(signum, handler);
+    }
+}
+
+/// Host state the `process` global exposes
+#[derive(Default)]
+pub struct ProcessState {
+    /// Heap pointer of `process.env`
+    pub env: Option<usize>,
+    /// Signal listeners in registration order
+    listeners: Vec<(String, JsValue)>,
+}
+
+impl ProcessState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether any signal has a listener
+    pub fn has_listeners(&self) -> bool {
+        !self.listeners.is_empty()
+    }
+
+    fn listening_for(&self, name: &str) -> bool {
+        self.listeners.iter().any(|(signal, _)| signal == name)
+    }
+}
+
+/// process.on(signal, listener)
+pub fn native_on(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
+    let (Some(JsValue::String(name)), Some(listener)) = (args.first(), args.get(1)) else {
+        eprintln!("process.on expects a signal name and a function");
+        return JsValue::Undefined;
+    };
+    let Some(signum) = signal_number(name) else {
+        eprintln!("process.on: unsupported event \"{}\"", name);
+        return JsValue::Undefined;
+    };
+    if !vm.process.listening_for(name) {
+        install(signum, true);
+    }
+    vm.process.listeners.push((name.clone(), listener.clone()));
+    JsValue::Undefined
+}
+
+/// process.off(signal, listener): the signal's default action comes back
+/// with its last listener
+pub fn native_off(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
+    let (Some(JsValue::String(name)), Some(listener)) = (args.first(), args.get(1)) else {
+        return JsValue::Undefined;
+    };
+    let listeners = &mut vm.process.listeners;
+    if let Some(index) = listeners
+        .iter()
+        .position(|(signal, f)| signal == name && f == listener)
+    {
+        listeners.remove(index);
+        if !vm.process.listening_for(name)
+            && let Some(signum) = signal_number(name)
+        {
+            install(signum, false);
+        }
+    }
+    JsValue::Undefined
+}
+
+/// Queue the listeners of every signal received since the last call
+pub fn pump_signals(vm: &mut VM) {
+    if !vm.process.has_listeners() {
+        return;
+    }
+    let pending = PENDING.swap(0, Ordering::SeqCst);
+    if pending == 0 {
+        return;
+    }
+    let ready: Vec<(String, JsValue)> = vm
+        .process
+        .listeners
+        .iter()
+        .filter(|(name, _)| signal_number(name).is_some_and(|signum| pending & (1 << signum) != 0))
+        .cloned()
+        .collect();
+    for (name, listener) in ready {
+        vm.queue_task(listener, vec![JsValue::String(name)], TaskPriority::High);
+    }
+}
+
+fn is_env(vm: &VM, ptr: usize) -> bool {
+    vm.process.env == Some(ptr)
+}
+
+/// `process.env[name]`, read from the host. `None` when `ptr` is not
+/// `process.env` or `name` is one of its methods.
+pub fn env_property(vm: &mut VM, ptr: usize, name: &str) -> Option<JsValue> {
+    if !is_env(vm, ptr) {
+        return None;
+    }
+    if let Some(HeapObject {
+        data: HeapData::Object(props),
+    }) = vm.heap.get(ptr)
+        && props.contains_key(name)
+    {
+        return None;
+    }
+    if !permitted(vm, Capability::Env, name) {
+        return Some(JsValue::Undefined);
+    }
+    Some(match std::env::var(name) {
+        Ok(value) => JsValue::String(value),
+        Err(_) => JsValue::Undefined,
+    })
+}
+
+/// `process.env[name] = value`; false when `ptr` is not `process.env`
+pub fn set_env_property(vm: &mut VM, ptr: usize, name: &str, value: &JsValue) -> bool {
+    if !is_env(vm, ptr) {
+        return false;
+    }
+    if !permitted(vm, Capability::Env, name) {
+        return true;
+    }
+    let JsValue::String(value) = super::native_string_constructor(vm, vec![value.clone()]) else {
+        return true;
+    };
+    if name.is_empty() || name.contains(['=', '\0']) || value.contains('\0') {
+        eprintln!("process.env: invalid variable \"{}\"", name);
+        return true;
+    }
+    // SAFETY: see native_setenv
+    unsafe {
+        std::env::set_var(name, value);
+    }
+    true
+}
+
+/// `delete process.env[name]`; `None` when `ptr` is not `process.env`
+pub fn delete_env_property(vm: &mut VM, ptr: usize, name: &str) -> Option<bool> {
+    if !is_env(vm, ptr) {
+        return None;
+    }
+    if !permitted(vm, Capability::Env, name) {
+        return Some(false);
+    }
+    if !name.is_empty() && !name.contains(['=', '\0']) {
+        // SAFETY: see native_setenv
+        unsafe {
+            std::env::remove_var(name);
+        }
+    }
+    Some(true)
+}
+
+/// Whether `name` is set in the host environment, for `name in
+/// process.env` and `hasOwnProperty`; `None` when `ptr` is not
+/// `process.env`. Like `env_entries`, only variables the env permission
+/// already covers count, without prompting.
+pub fn has_env_property(vm: &VM, ptr: usize, name: &str) -> Option<bool> {
+    if !is_env(vm, ptr) {
+        return None;
+    }
+    Some(
+        vm.permissions.query(Capability::Env, Some(name)) == PermissionState::Granted
+            && std::env::var_os(name).is_some(),
+    )
+}
+
+/// Variables listed by `Object.keys(process.env)`, sorted by name. Only
+/// the ones the env permission already covers, without prompting.
+pub fn env_entries(vm: &VM, ptr: usize) -> Option<Vec<(String, String)>> {
+    if !is_env(vm, ptr) {
+        return None;
+    }
+    let mut entries: Vec<(String, String)> = std::env::vars_os()
+        .filter_map(|(name, value)| Some((name.into_string().ok()?, value.into_string().ok()?)))
+        .filter(|(name, _)| {
+            vm.permissions.query(Capability::Env, Some(name)) == PermissionState::Granted
+        })
+        .collect();
+    entries.sort();
+    Some(entries)
+}
+
+/// `[execPath, script, ...args]`
+pub fn argv(vm: &VM, args: &[String]) -> Vec<JsValue> {
+    let exec_path = std::env::current_exe()
+        .map(|path| path.to_string_lossy().into_owned())
+        .unwrap_or_else(|_| "oitec".to_string());
+    let script = vm
+        .current_module_path
+        .as_ref()
+        .map(|path| {
+            std::fs::canonicalize(path)
+                .unwrap_or_else(|_| path.clone())
+                .to_string_lossy()
+                .into_owned()
+        })
+        .unwrap_or_default();
+    [exec_path, script]
+        .into_iter()
+        .chain(args.iter().cloned())
+        .map(JsValue::String)
+        .collect()
+}
+
+/// `process.exitCode`, if the script set it to a number
+pub fn exit_code(vm: &VM) -> Option<i32> {
+    let Some(JsValue::Object(ptr)) = vm.call_stack.first()?.locals.get("process") else {
+        return None;
+    };
+    match vm.heap.get(*ptr) {
+        Some(HeapObject {
+            data: HeapData::Object(props),
+        }) => match props.get("exitCode") {
+            Some(JsValue::Number(code)) => Some(*code as i32),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signal_names() {
+        assert_eq!(signal_number("SIGINT"), Some(libc::SIGINT));
+        assert_eq!(signal_number("SIGKILL"), None);
+        assert_eq!(signal_number("exit"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_raised_signal_queues_its_listeners() {
+        let mut vm = VM::new();
+        let listener = JsValue::NativeFunction(0);
+        native_on(
+            &mut vm,
+            vec![JsValue::String("SIGUSR2".into()), listener.clone()],
+        );
+        unsafe {
+            libc::raise(libc::SIGUSR2);
+        }
+        let queued = vm.task_queue.len();
+        pump_signals(&mut vm);
+        assert_eq!(vm.task_queue.len(), queued + 1);
+
+        native_off(&mut vm, vec![JsValue::String("SIGUSR2".into()), listener]);
+        assert!(!vm.process.has_listeners());
+    }
+}
@@ -0,0 +1,107 @@
+//! `std.simd` vectors
+//!
+//! The interpreter's side of `crate::ir::simd`: a vector is a plain array
+//! of its lanes, and each operation checks its operands, converts their
+//! lanes to the lane type and applies the lane-by-lane definition that the
+//! native vector instructions follow.
+
+use super::{to_number, type_error};
+use crate::ir::simd::{SimdKind, SimdOp};
+use crate::vm::VM;
+use crate::vm::value::{FallibleNativeFn, HeapData, HeapObject, JsValue, NativeResult};
+
+/// Lanes of the vector `value`: an array of exactly `kind.lanes()` elements
+fn lanes(vm: &VM, kind: SimdKind, value: Option<&JsValue>) -> Result<Vec<f64>, JsValue> {
+    if let Some(JsValue::Object(ptr)) = value
+        && let Some(HeapObject {
+            data: HeapData::Array(items),
+        }) = vm.heap.get(*ptr)
+        && items.len() == kind.lanes()
+    {
+        return Ok(items
+            .iter()
+            .map(|item| kind.lane(to_number(vm, item)))
+            .collect());
+    }
+    Err(type_error(&format!(
+        "expected an {}, an array of {} numbers",
+        kind,
+        kind.lanes()
+    )))
+}
+
+fn vector(vm: &mut VM, lanes: Vec<f64>) -> JsValue {
+    let ptr = vm.heap.len();
+    vm.heap.push(HeapObject {
+        data: HeapData::Array(lanes.into_iter().map(JsValue::Number).collect()),
+    });
+    JsValue::Object(ptr)
+}
+
+fn call(vm: &mut VM, kind: SimdKind, method: &str, args: &[JsValue]) -> NativeResult {
+    match method {
+        "make" => {
+            let lanes = (0..kind.lanes())
+                .map(|i| kind.lane(args.get(i).map_or(f64::NAN, |arg| to_number(vm, arg))))
+                .collect();
+            Ok(vector(vm, lanes))
+        }
+        "shuffle" => {
+            let a = lanes(vm, kind, args.first())?;
+            let b = lanes(vm, kind, args.get(1))?;
+            let indices: Vec<f64> = args.iter().skip(2).map(|i| to_number(vm, i)).collect();
+            let Some(SimdOp::Shuffle(indices)) = SimdOp::shuffle(kind, &indices) else {
+                return Err(type_error(&format!(
+                    "{}.shuffle takes {} lane indices from 0 to {}",
+                    kind,
+                    kind.lanes(),
+                    2 * kind.lanes() - 1
+                )));
+            };
+            Ok(vector(vm, kind.shuffle(&a, &b, &indices)))
+        }
+        "reduceAdd" => {
+            let v = lanes(vm, kind, args.first())?;
+            Ok(JsValue::Number(kind.reduce_add(&v)))
+        }
+        _ => {
+            let op = SimdOp::from_method(method).expect("lane-wise SIMD method");
+            let a = lanes(vm, kind, args.first())?;
+            let b = lanes(vm, kind, args.get(1))?;
+            Ok(vector(vm, kind.binary(&op, &a, &b)))
+        }
+    }
+}
+
+macro_rules! kind_natives {
+    ($kind:expr, $path:literal) => {
+        [
+            ("__call__", $path, |vm, args| call(vm, $kind, "make", &args)),
+            ("add", concat!($path, ".add"), |vm, args| {
+                call(vm, $kind, "add", &args)
+            }),
+            ("sub", concat!($path, ".sub"), |vm, args| {
+                call(vm, $kind, "sub", &args)
+            }),
+            ("mul", concat!($path, ".mul"), |vm, args| {
+                call(vm, $kind, "mul", &args)
+            }),
+            ("shuffle", concat!($path, ".shuffle"), |vm, args| {
+                call(vm, $kind, "shuffle", &args)
+            }),
+            ("reduceAdd", concat!($path, ".reduceAdd"), |vm, args| {
+                call(vm, $kind, "reduceAdd", &args)
+            }),
+        ]
+    };
+}
+
+/// The natives of `kind` as (property, name, function): the kind object is
+/// called to build a vector and its methods operate on vectors
+pub fn natives(kind: SimdKind) -> [(&'static str, &'static str, FallibleNativeFn); 6] {
+    match kind {
+        SimdKind::F64x2 => kind_natives!(SimdKind::F64x2, "std.simd.f64x2"),
+        SimdKind::F32x4 => kind_natives!(SimdKind::F32x4, "std.simd.f32x4"),
+        SimdKind::I32x4 => kind_natives!(SimdKind::I32x4, "std.simd.i32x4"),
+    }
+}
@@ -0,0 +1,581 @@
+//! Streams (`fs.createReadStream`, `fs.createWriteStream`, `fetch(url, { stream: true })`)
+//!
+//! A stream is a heap handle to a reader or writer kept in the VM's
+//! `StreamTable`, so a large file or response body is pulled one chunk at a
+//! time instead of as one string. Readable streams can be consumed two ways:
+//!
+//! - pulled: `await stream.read()` gives `{ value, done }` for the next chunk
+//! - pushed: `stream.on("data", fn)` starts delivering chunks, each as its
+//!   own event loop task, so other tasks and timers run between chunks;
+//!   `on("end", fn)` and `on("error", fn)` follow the last chunk
+//!
+//! Chunks are buffers, or strings when the stream was opened with
+//! `encoding: "utf8"`. A multi-byte character split across two reads is held
+//! back until the rest of it arrives.
+
+use super::buffer::alloc_buffer;
+use super::encoding::bytes_of;
+use crate::vm::permissions::Capability;
+use crate::vm::value::{BufferStorage, BufferView, HeapData, HeapObject, JsValue, Promise};
+use crate::vm::{TaskPriority, VM};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+
+/// Bytes read per chunk unless `highWaterMark` says otherwise
+pub const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+
+pub struct Readable {
+    reader: Box<dyn Read + Send>,
+    chunk_size: usize,
+    /// Chunks are UTF-8 text rather than buffers
+    text: bool,
+    /// Start of a character split by the last read
+    pending: Vec<u8>,
+    done: bool,
+    on_data: Option<JsValue>,
+    on_end: Option<JsValue>,
+    on_error: Option<JsValue>,
+    /// A pump task is queued
+    flowing: bool,
+}
+
+pub enum StreamEntry {
+    Readable(Readable),
+    Writable(Option<Box<dyn Write>>),
+}
+
+/// Open streams by id. An id outlives its stream's data: a closed stream
+/// stays in the table as finished.
+#[derive(Default)]
+pub struct StreamTable {
+    streams: HashMap<usize, StreamEntry>,
+    next_id: usize,
+    /// Native index of `native_pump`, registered on first use
+    pump: Option<usize>,
+}
+
+impl StreamTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn insert(&mut self, entry: StreamEntry) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.streams.insert(id, entry);
+        id
+    }
+
+    /// Number of streams still open
+    pub fn open_count(&self) -> usize {
+        self.streams
+            .values()
+            .filter(|entry| match entry {
+                StreamEntry::Readable(readable) => !readable.done,
+                StreamEntry::Writable(writer) => writer.is_some(),
+            })
+            .count()
+    }
+}
+
+impl Readable {
+    fn new(reader: Box<dyn Read + Send>, chunk_size: usize, text: bool) -> Self {
+        Self {
+            reader,
+            chunk_size,
+            text,
+            pending: Vec::new(),
+            done: false,
+            on_data: None,
+            on_end: None,
+            on_error: None,
+            flowing: false,
+        }
+    }
+
+    /// The next chunk's bytes, `None` at the end
+    fn next_chunk(&mut self) -> Result<Option<Vec<u8>>, String> {
+        if self.done {
+            return Ok(None);
+        }
+        let mut chunk = std::mem::take(&mut self.pending);
+        let start = chunk.len();
+        chunk.resize(start + self.chunk_size, 0);
+        let read = loop {
+            match self.reader.read(&mut chunk[start..]) {
+                Ok(n) => break n,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    self.done = true;
+                    return Err(e.to_string());
+                }
+            }
+        };
+        chunk.truncate(start + read);
+        if read == 0 {
+            self.done = true;
+            // Whatever is left can't become a whole character any more
+            return Ok((!chunk.is_empty()).then_some(chunk));
+        }
+        if self.text {
+            let complete = utf8_complete_len(&chunk);
+            self.pending = chunk.split_off(complete);
+        }
+        Ok(Some(chunk))
+    }
+}
+
+/// Length of the longest prefix of `bytes` that doesn't end inside a UTF-8
+/// sequence. Invalid bytes count as complete; decoding replaces them.
+fn utf8_complete_len(bytes: &[u8]) -> usize {
+    // Only the last three bytes can start an unfinished character
+    let tail = bytes.len().saturating_sub(3);
+    let Some(lead) = (tail..bytes.len()).rev().find(|&i| bytes[i] & 0xc0 != 0x80) else {
+        return bytes.len();
+    };
+    let width = match bytes[lead] {
+        0xc0..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf7 => 4,
+        _ => 1,
+    };
+    if bytes.len() - lead < width {
+        lead
+    } else {
+        bytes.len()
+    }
+}
+
+fn alloc_stream(vm: &mut VM, entry: StreamEntry) -> JsValue {
+    let id = vm.streams.insert(entry);
+    let ptr = vm.heap.len();
+    vm.heap.push(HeapObject {
+        data: HeapData::Stream(id),
+    });
+    JsValue::Object(ptr)
+}
+
+/// A readable stream over `reader`
+pub fn alloc_readable(
+    vm: &mut VM,
+    reader: Box<dyn Read + Send>,
+    chunk_size: usize,
+    text: bool,
+) -> JsValue {
+    alloc_stream(
+        vm,
+        StreamEntry::Readable(Readable::new(reader, chunk_size, text)),
+    )
+}
+
+/// A writable stream over `writer`
+pub fn alloc_writable(vm: &mut VM, writer: Box<dyn Write>) -> JsValue {
+    alloc_stream(vm, StreamEntry::Writable(Some(writer)))
+}
+
+/// Stream id of a stream object
+fn stream_id(vm: &VM, value: &JsValue) -> Option<usize> {
+    match value {
+        JsValue::Object(ptr) => match vm.heap.get(*ptr).map(|obj| &obj.data) {
+            Some(HeapData::Stream(id)) => Some(*id),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Take over what is left of the readable stream `value`, leaving it
+/// finished. `None` when it isn't a readable stream or its chunks are
+/// already being delivered to a listener.
+pub fn take_reader(vm: &mut VM, value: &JsValue) -> Option<Box<dyn Read + Send>> {
+    let id = stream_id(vm, value)?;
+    let stream = readable(vm, id).filter(|stream| !stream.flowing && !stream.done)?;
+    stream.done = true;
+    let pending = std::mem::take(&mut stream.pending);
+    let reader = std::mem::replace(&mut stream.reader, Box::new(std::io::empty()));
+    Some(Box::new(std::io::Cursor::new(pending).chain(reader)))
+}
+
+/// Close the stream `value`, if it is one
+pub fn close_value(vm: &mut VM, value: &JsValue) -> Result<(), String> {
+    match stream_id(vm, value) {
+        Some(id) => close(vm, id),
+        None => Ok(()),
+    }
+}
+
+/// `{ highWaterMark, encoding }` options: chunk size and whether chunks are
+/// text
+fn read_options(vm: &VM, arg: Option<&JsValue>) -> Result<(usize, bool), String> {
+    let Some(JsValue::Object(ptr)) = arg else {
+        return Ok((DEFAULT_CHUNK_SIZE, false));
+    };
+    let Some(HeapData::Object(options)) = vm.heap.get(*ptr).map(|obj| &obj.data) else {
+        return Ok((DEFAULT_CHUNK_SIZE, false));
+    };
+    let chunk_size = match options.get("highWaterMark") {
+        Some(JsValue::Number(n)) if *n >= 1.0 => *n as usize,
+        None | Some(JsValue::Undefined) => DEFAULT_CHUNK_SIZE,
+        Some(_) => return Err("highWaterMark must be a positive number".to_string()),
+    };
+    let text = match options.get("encoding") {
+        Some(JsValue::String(encoding)) => match encoding.to_ascii_lowercase().as_str() {
+            "utf8" | "utf-8" => true,
+            other => return Err(format!("unsupported stream encoding '{}'", other)),
+        },
+        _ => false,
+    };
+    Ok((chunk_size, text))
+}
+
+/// fs.createReadStream(path, { highWaterMark?, encoding? })
+pub fn native_create_read_stream(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
+    let Some(JsValue::String(path)) = args.first() else {
+        eprintln!("fs.createReadStream: path must be a string");
+        return JsValue::Undefined;
+    };
+    if !super::permitted(vm, Capability::Read, path) {
+        return JsValue::Undefined;
+    }
+    let (chunk_size, text) = match read_options(vm, args.get(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("fs.createReadStream: {}", e);
+            return JsValue::Undefined;
+        }
+    };
+    match File::open(path) {
+        Ok(file) => alloc_readable(vm, Box::new(file), chunk_size, text),
+        Err(e) => {
+            eprintln!("Error opening file '{}': {}", path, e);
+            JsValue::Undefined
+        }
+    }
+}
+
+/// fs.createWriteStream(path, { flags? }) - `flags: "a"` appends
+pub fn native_create_write_stream(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
+    let Some(JsValue::String(path)) = args.first() else {
+        eprintln!("fs.createWriteStream: path must be a string");
+        return JsValue::Undefined;
+    };
+    if !super::permitted(vm, Capability::Write, path) {
+        return JsValue::Undefined;
+    }
+    let append = match args.get(1) {
+        Some(JsValue::Object(ptr)) => match vm.heap.get(*ptr).map(|obj| &obj.data) {
+            Some(HeapData::Object(options)) => {
+                matches!(options.get("flags"), Some(JsValue::String(flags)) if flags == "a")
+            }
+            _ => false,
+        },
+        _ => false,
+    };
+    let file = std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .append(append)
+        .truncate(!append)
+        .open(path);
+    match file {
+        Ok(file) => alloc_writable(vm, Box::new(BufWriter::new(file))),
+        Err(e) => {
+            eprintln!("Error opening file '{}': {}", path, e);
+            JsValue::Undefined
+        }
+    }
+}
+
+/// A chunk as the script sees it
+fn chunk_value(vm: &mut VM, bytes: Vec<u8>, text: bool) -> JsValue {
+    if text {
+        JsValue::String(String::from_utf8_lossy(&bytes).into_owned())
+    } else {
+        alloc_buffer(vm, BufferView::new(BufferStorage::Owned(bytes)))
+    }
+}
+
+/// `{ value, done }`
+fn read_result(vm: &mut VM, value: JsValue, done: bool) -> JsValue {
+    let mut props = HashMap::new();
+    props.insert("value".to_string(), value);
+    props.insert("done".to_string(), JsValue::Boolean(done));
+    let ptr = vm.heap.len();
+    vm.heap.push(HeapObject {
+        data: HeapData::Object(props),
+    });
+    JsValue::Object(ptr)
+}
+
+fn resolved(value: JsValue) -> JsValue {
+    JsValue::Promise(Promise::with_value(value))
+}
+
+fn rejected(message: String) -> JsValue {
+    let promise = Promise::new();
+    promise.set_value(JsValue::String(message), false);
+    JsValue::Promise(promise)
+}
+
+fn readable(vm: &mut VM, id: usize) -> Option<&mut Readable> {
+    match vm.streams.streams.get_mut(&id) {
+        Some(StreamEntry::Readable(readable)) => Some(readable),
+        _ => None,
+    }
+}
+
+/// Next chunk of stream `id` as a script value, `None` at the end
+fn read_chunk(vm: &mut VM, id: usize) -> Result<Option<JsValue>, String> {
+    let Some(stream) = readable(vm, id) else {
+        return Err("not a readable stream".to_string());
+    };
+    let text = stream.text;
+    match stream.next_chunk()? {
+        Some(bytes) => Ok(Some(chunk_value(vm, bytes, text))),
+        None => Ok(None),
+    }
+}
+
+/// Event loop task delivering one chunk of the stream in `args[0]` to its
+/// `data` listener, then queueing itself for the next
+fn native_pump(vm: &mut VM, args: Vec<JsValue>) -> JsValue {
+    let Some(JsValue::Object(ptr)) = args.first() else {
+        return JsValue::Undefined;
+    };
+    let Some(HeapData::Stream(id)) = vm.heap.get(*ptr).map(|obj| &obj.data) else {
+        return JsValue::Undefined;
+    };
+    let id = *id;
+    let result = read_chunk(vm, id);
+    let Some(stream) = readable(vm, id) else {
+        return JsValue::Undefined;
+    };
+    let (on_data, on_end, on_error) = (
+        stream.on_data.clone(),
+        stream.on_end.clone(),
+        stream.on_error.clone(),
+    );
+    match result {
+        Ok(Some(chunk)) => {
+            if let Some(on_data) = on_data {
+                vm.queue_task(on_data, vec![chunk], TaskPriority::Normal);
+            }
+            let pump = pump_native(vm);
+            vm.queue_task(JsValue::NativeFunction(pump), args, TaskPriority::Normal);
+        }
+        Ok(None) => {
+            stream.flowing = false;
+            if let Some(on_end) = on_end {
+                vm.queue_task(on_end, vec![], TaskPriority::Normal);
+            }
+        }
+        Err(e) => {
+            stream.flowing = false;
+            match on_error {
+                Some(on_error) => {
+                    vm.queue_task(on_error, vec![JsValue::String(e)], TaskPriority::Normal)
+                }
+                None => eprintln!("Error reading stream: {}", e),
+            }
+        }
+    }
+    JsValue::Undefined
+}
+
+fn pump_native(vm: &mut VM) -> usize {
+    match vm.streams.pump {
+        Some(idx) => idx,
+        None => {
+            let idx = vm.register_native(native_pump);
+            vm.streams.pump = Some(idx);
+            idx
+        }
+    }
+}
+
+/// Write `chunk` (a string or anything holding bytes) to stream `id`
+fn write_chunk(vm: &mut VM, id: usize, chunk: Option<&JsValue>) -> Result<(), String> {
+    let bytes = match chunk {
+        Some(JsValue::String(s)) => s.as_bytes().to_vec(),
+        Some(value) => {
+            bytes_of(vm, value).ok_or("write expects a string, buffer, ByteStream or array")?
+        }
+        None => return Ok(()),
+    };
+    match vm.streams.streams.get_mut(&id) {
+        Some(StreamEntry::Writable(Some(writer))) => {
+            writer.write_all(&bytes).map_err(|e| e.to_string())
+        }
+        Some(StreamEntry::Writable(None)) => Err("write after close".to_string()),
+        _ => Err("not a writable stream".to_string()),
+    }
+}
+
+fn close(vm: &mut VM, id: usize) -> Result<(), String> {
+    match vm.streams.streams.get_mut(&id) {
+        Some(StreamEntry::Writable(writer)) => match writer.take() {
+            Some(mut writer) => writer.flush().map_err(|e| e.to_string()),
+            None => Ok(()),
+        },
+        Some(StreamEntry::Readable(readable)) => {
+            readable.done = true;
+            readable.reader = Box::new(std::io::empty());
+            Ok(())
+        }
+        None => Ok(()),
+    }
+}
+
+/// Call method `name` on the stream at `ptr`. `None` when streams have no
+/// such method.
+pub fn call_method(vm: &mut VM, ptr: usize, name: &str, args: &[JsValue]) -> Option<JsValue> {
+    let Some(HeapData::Stream(id)) = vm.heap.get(ptr).map(|obj| &obj.data) else {
+        return None;
+    };
+    let id = *id;
+    let result = match name {
+        "read" => match read_chunk(vm, id) {
+            Ok(Some(chunk)) => {
+                let result = read_result(vm, chunk, false);
+                resolved(result)
+            }
+            Ok(None) => {
+                let result = read_result(vm, JsValue::Undefined, true);
+                resolved(result)
+            }
+            Err(e) => rejected(e),
+        },
+        // Everything left, as one string or buffer
+        "text" | "readAll" => {
+            let text = name == "text";
+            let mut bytes = Vec::new();
+            loop {
+                let Some(stream) = readable(vm, id) else {
+                    return Some(rejected("not a readable stream".to_string()));
+                };
+                match stream.next_chunk() {
+                    Ok(Some(chunk)) => bytes.extend(chunk),
+                    Ok(None) => break,
+                    Err(e) => return Some(rejected(e)),
+                }
+            }
+            let value = chunk_value(vm, bytes, text);
+            resolved(value)
+        }
+        "on" => {
+            let (Some(JsValue::String(event)), Some(listener)) = (args.first(), args.get(1)) else {
+                eprintln!("stream.on: expected an event name and a function");
+                return Some(JsValue::Undefined);
+            };
+            let pump = pump_native(vm);
+            let Some(stream) = readable(vm, id) else {
+                eprintln!("stream.on: not a readable stream");
+                return Some(JsValue::Undefined);
+            };
+            let listener = Some(listener.clone());
+            match event.as_str() {
+                "data" => stream.on_data = listener,
+                "end" => stream.on_end = listener,
+                "error" => stream.on_error = listener,
+                _ => {
+                    eprintln!("stream.on: unknown event '{}'", event);
+                    return Some(JsValue::Undefined);
+                }
+            }
+            // A data listener starts the flow
+            if event == "data" && !stream.flowing {
+                stream.flowing = true;
+                vm.queue_task(
+                    JsValue::NativeFunction(pump),
+                    vec![JsValue::Object(ptr)],
+                    TaskPriority::Normal,
+                );
+            }
+            JsValue::Object(ptr)
+        }
+        // Copy everything left into a writable stream, then close it
+        "pipeTo" => {
+            let target = match args.first() {
+                Some(JsValue::Object(target)) => match vm.heap.get(*target).map(|o| &o.data) {
+                    Some(HeapData::Stream(target)) => *target,
+                    _ => return Some(rejected("pipeTo expects a writable stream".to_string())),
+                },
+                _ => return Some(rejected("pipeTo expects a writable stream".to_string())),
+            };
+            let mut total = 0;
+            loop {
+                let Some(stream) = readable(vm, id) else {
+                    return Some(rejected("not a readable stream".to_string()));
+                };
+                let chunk = match stream.next_chunk() {
+                    Ok(Some(chunk)) => chunk,
+                    Ok(None) => break,
+                    Err(e) => return Some(rejected(e)),
+                };
+                total += chunk.len();
+                let writer = match vm.streams.streams.get_mut(&target) {
+                    Some(StreamEntry::Writable(Some(writer))) => writer,
+                    _ => {
+                        return Some(rejected(
+                            "pipeTo expects an open writable stream".to_string(),
+                        ));
+                    }
+                };
+                if let Err(e) = writer.write_all(&chunk) {
+                    return Some(rejected(e.to_string()));
+                }
+            }
+            match close(vm, target) {
+                Ok(()) => resolved(JsValue::Number(total as f64)),
+                Err(e) => rejected(e),
+            }
+        }
+        "write" => match write_chunk(vm, id, args.first()) {
+            Ok(()) => JsValue::Boolean(true),
+            Err(e) => {
+                eprintln!("stream.write: {}", e);
+                JsValue::Boolean(false)
+            }
+        },
+        "close" | "end" | "cancel" => {
+            if name == "end"
+                && let Err(e) = write_chunk(vm, id, args.first())
+            {
+                eprintln!("stream.end: {}", e);
+            }
+            match close(vm, id) {
+                Ok(()) => resolved(JsValue::Undefined),
+                Err(e) => rejected(e),
+            }
+        }
+        _ => return None,
+    };
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_chunks_never_split_characters() {
+        // "é" is two bytes; three-byte reads would cut it in half
+        let reader = std::io::Cursor::new("aé€b".as_bytes().to_vec());
+        let mut stream = Readable::new(Box::new(reader), 3, true);
+        let mut chunks = Vec::new();
+        while let Some(chunk) = stream.next_chunk().unwrap() {
+            chunks.push(String::from_utf8(chunk).unwrap());
+        }
+        assert_eq!(chunks.concat(), "aé€b");
+        assert!(chunks.iter().all(|c| !c.is_empty()));
+    }
+
+    #[test]
+    fn test_utf8_complete_len() {
+        assert_eq!(utf8_complete_len(b"abc"), 3);
+        assert_eq!(utf8_complete_len(&[b'a', 0xe2, 0x82]), 1);
+        assert_eq!(utf8_complete_len(&[0xff, b'a', 0xc3]), 2);
+        assert_eq!(utf8_complete_len(&[0xff, b'a']), 2);
+    }
+}
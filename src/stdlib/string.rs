@@ -0,0 +1,255 @@
+//! String indexing in UTF-16 code units
+//!
+//! JS strings are sequences of UTF-16 code units: `length`, `str[i]`,
+//! `charCodeAt`, `slice` and `indexOf` all count units, so `"é".length` is 1
+//! and `"😀".length` is 2. The VM keeps strings as UTF-8, so these helpers
+//! translate; ASCII strings, where a byte is a unit, take a fast path.
+//!
+//! A Rust string cannot hold half of a surrogate pair. Where JS would
+//! produce a lone surrogate (`"😀"[0]`, a slice through a pair) the result
+//! holds U+FFFD instead.
+
+use icu_normalizer::{ComposingNormalizerBorrowed, DecomposingNormalizerBorrowed};
+use std::cmp::Ordering;
+
+/// Length in UTF-16 code units
+pub fn len(s: &str) -> usize {
+    if s.is_ascii() {
+        s.len()
+    } else {
+        s.encode_utf16().count()
+    }
+}
+
+fn units(s: &str) -> Vec<u16> {
+    s.encode_utf16().collect()
+}
+
+/// Clamp a relative position (negative counts from the end) into `0..=len`,
+/// the way `slice` and `at` read their arguments
+pub fn relative_index(n: f64, len: usize) -> usize {
+    if n.is_nan() {
+        0
+    } else if n < 0.0 {
+        (len as f64 + n.trunc()).max(0.0) as usize
+    } else {
+        n.min(len as f64) as usize
+    }
+}
+
+/// Clamp an absolute position into `0..=len` (`substring`, `indexOf`)
+pub fn clamp_index(n: f64, len: usize) -> usize {
+    if n.is_nan() || n < 0.0 {
+        0
+    } else {
+        n.min(len as f64) as usize
+    }
+}
+
+/// The code unit at `index` (`charCodeAt`)
+pub fn code_unit_at(s: &str, index: usize) -> Option<u16> {
+    if s.is_ascii() {
+        s.as_bytes().get(index).map(|&b| b as u16)
+    } else {
+        s.encode_utf16().nth(index)
+    }
+}
+
+/// The code unit at `index` as a one-unit string (`charAt`, `str[i]`)
+pub fn unit_at(s: &str, index: usize) -> Option<String> {
+    if s.is_ascii() {
+        return s.get(index..index + 1).map(str::to_string);
+    }
+    code_unit_at(s, index).map(|unit| String::from_utf16_lossy(&[unit]))
+}
+
+/// `str[index]`: `None` unless `index` is an integer in range
+pub fn element(s: &str, index: f64) -> Option<String> {
+    if index < 0.0 || index.fract() != 0.0 {
+        return None;
+    }
+    unit_at(s, index as usize)
+}
+
+/// The code point starting at `index`; a surrogate pair combines, a lone
+/// half is returned as is (`codePointAt`)
+pub fn code_point_at(s: &str, index: usize) -> Option<u32> {
+    if s.is_ascii() {
+        return s.as_bytes().get(index).map(|&b| b as u32);
+    }
+    let mut rest = s.encode_utf16().skip(index);
+    let first = rest.next()?;
+    if (0xD800..0xDC00).contains(&first)
+        && let Some(second) = rest.next()
+        && (0xDC00..0xE000).contains(&second)
+    {
+        return Some(0x10000 + ((first as u32 - 0xD800) << 10) + (second as u32 - 0xDC00));
+    }
+    Some(first as u32)
+}
+
+/// Units `start..end`, empty when `end <= start`
+pub fn slice(s: &str, start: usize, end: usize) -> String {
+    if end <= start {
+        return String::new();
+    }
+    if s.is_ascii() {
+        let end = end.min(s.len());
+        return s.get(start.min(end)..end).unwrap_or_default().to_string();
+    }
+    let units = units(s);
+    let end = end.min(units.len());
+    String::from_utf16_lossy(&units[start.min(end)..end])
+}
+
+/// Unit index of the first `search` at or after `from`
+pub fn index_of(s: &str, search: &str, from: usize) -> Option<usize> {
+    if s.is_ascii() && search.is_ascii() {
+        let from = from.min(s.len());
+        return s[from..].find(search).map(|i| i + from);
+    }
+    let (units, search) = (units(s), self::units(search));
+    let from = from.min(units.len());
+    if search.is_empty() {
+        return Some(from);
+    }
+    units[from..]
+        .windows(search.len())
+        .position(|window| window == search.as_slice())
+        .map(|i| i + from)
+}
+
+/// Unit index of the last `search` starting at or before `from`
+pub fn last_index_of(s: &str, search: &str, from: Option<usize>) -> Option<usize> {
+    let (units, search) = (units(s), self::units(search));
+    let last_start = units.len().checked_sub(search.len())?;
+    let from = from.unwrap_or(last_start).min(last_start);
+    (0..=from)
+        .rev()
+        .find(|&i| units[i..i + search.len()] == search[..])
+}
+
+/// Unit indices of the non-overlapping occurrences of `search`
+/// (`matchAll`); an empty `search` matches at every position
+pub fn match_indices(s: &str, search: &str) -> Vec<usize> {
+    let (len, step) = (len(s), self::len(search).max(1));
+    let mut indices = Vec::new();
+    let mut from = 0;
+    while from <= len
+        && let Some(index) = index_of(s, search, from)
+    {
+        indices.push(index);
+        from = index + step;
+    }
+    indices
+}
+
+/// `at(n)`: negative `n` counts back from the end
+pub fn at(s: &str, n: f64) -> Option<String> {
+    let len = len(s) as f64;
+    let n = if n.is_nan() { 0.0 } else { n.trunc() };
+    let index = if n < 0.0 { len + n } else { n };
+    if index < 0.0 || index >= len {
+        return None;
+    }
+    unit_at(s, index as usize)
+}
+
+/// `normalize(form)`: one of NFC (the default), NFD, NFKC or NFKD
+pub fn normalize(s: &str, form: Option<&str>) -> Result<String, String> {
+    let normalized = match form.unwrap_or("NFC") {
+        "NFC" => ComposingNormalizerBorrowed::new_nfc().normalize(s),
+        "NFD" => DecomposingNormalizerBorrowed::new_nfd().normalize(s),
+        "NFKC" => ComposingNormalizerBorrowed::new_nfkc().normalize(s),
+        "NFKD" => DecomposingNormalizerBorrowed::new_nfkd().normalize(s),
+        _ => {
+            return Err(
+                "RangeError: The normalization form should be one of NFC, NFD, NFKC, NFKD."
+                    .to_string(),
+            );
+        }
+    };
+    Ok(normalized.into_owned())
+}
+
+/// `localeCompare`, close to the root collation: letters compare without
+/// regard to accents or case first, then accents break ties, then case
+/// (lowercase first). Canonically equivalent strings compare equal.
+pub fn locale_compare(a: &str, b: &str) -> Ordering {
+    let nfd = DecomposingNormalizerBorrowed::new_nfd();
+    let (a, b) = (nfd.normalize(a), nfd.normalize(b));
+    let is_mark = |c: &char| ('\u{300}'..='\u{36f}').contains(c);
+    let base = |s: &str| -> Vec<char> {
+        s.chars()
+            .filter(|c| !is_mark(c))
+            .flat_map(char::to_lowercase)
+            .collect()
+    };
+    let accents = |s: &str| -> Vec<char> { s.chars().flat_map(char::to_lowercase).collect() };
+    // Lowercase sorts before uppercase
+    let case = |s: &str| -> Vec<bool> { s.chars().map(char::is_uppercase).collect() };
+    base(&a)
+        .cmp(&base(&b))
+        .then_with(|| accents(&a).cmp(&accents(&b)))
+        .then_with(|| case(&a).cmp(&case(&b)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_indices_count_utf16_units() {
+        assert_eq!(len("abc"), 3);
+        assert_eq!(len("héllo"), 5);
+        assert_eq!(len("a😀b"), 4);
+        assert_eq!(code_unit_at("a😀b", 1), Some(0xD83D));
+        assert_eq!(code_point_at("a😀b", 1), Some(0x1F600));
+        assert_eq!(code_point_at("a😀b", 2), Some(0xDE00));
+        assert_eq!(unit_at("a😀b", 3).as_deref(), Some("b"));
+        assert_eq!(unit_at("a😀b", 1).as_deref(), Some("\u{FFFD}"));
+        assert_eq!(element("héllo", 1.0).as_deref(), Some("é"));
+        assert_eq!(element("abc", -1.0), None);
+        assert_eq!(element("abc", 0.5), None);
+        assert_eq!(slice("a😀b", 1, 3), "😀");
+        assert_eq!(slice("héllo", 1, 3), "él");
+        assert_eq!(slice("abc", 2, 1), "");
+        assert_eq!(index_of("a😀b😀", "😀", 2), Some(4));
+        assert_eq!(index_of("héllo", "l", 0), Some(2));
+        assert_eq!(index_of("abc", "", 10), Some(3));
+        assert_eq!(last_index_of("héllo", "l", None), Some(3));
+        assert_eq!(last_index_of("héllo", "l", Some(2)), Some(2));
+        assert_eq!(last_index_of("ab", "abc", None), None);
+        assert_eq!(match_indices("a😀aa", "a"), vec![0, 3, 4]);
+        assert_eq!(match_indices("aaaa", "aa"), vec![0, 2]);
+        assert_eq!(match_indices("ab", ""), vec![0, 1, 2]);
+        assert_eq!(match_indices("ab", "c"), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn test_relative_positions() {
+        assert_eq!(relative_index(-2.0, 5), 3);
+        assert_eq!(relative_index(-9.0, 5), 0);
+        assert_eq!(relative_index(9.0, 5), 5);
+        assert_eq!(at("héllo", -1.0).as_deref(), Some("o"));
+        assert_eq!(at("héllo", 1.0).as_deref(), Some("é"));
+        assert_eq!(at("abc", -4.0), None);
+        assert_eq!(at("abc", 3.0), None);
+    }
+
+    #[test]
+    fn test_normalize_and_compare() {
+        let composed = "\u{e9}";
+        let decomposed = "e\u{301}";
+        assert_eq!(normalize(decomposed, None).unwrap(), composed);
+        assert_eq!(normalize(composed, Some("NFD")).unwrap(), decomposed);
+        assert_eq!(normalize("\u{fb01}", Some("NFKC")).unwrap(), "fi");
+        assert!(normalize("x", Some("nfc")).is_err());
+
+        assert_eq!(locale_compare(composed, decomposed), Ordering::Equal);
+        assert_eq!(locale_compare("a", "B"), Ordering::Less);
+        assert_eq!(locale_compare("résumé", "resume"), Ordering::Greater);
+        assert_eq!(locale_compare("résumé", "rf"), Ordering::Less);
+        assert_eq!(locale_compare("a", "A"), Ordering::Less);
+    }
+}
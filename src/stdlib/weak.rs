@@ -0,0 +1,149 @@
+//! `WeakMap`, `WeakSet` and `WeakRef`
+//!
+//! Keys and targets are objects, held by heap pointer rather than by value,
+//! so these collections never show up as retainers in heap snapshots. The
+//! VM does not collect its heap yet, so for now an entry stays until it is
+//! deleted and `deref()` always finds its target. There is no
+//! `FinalizationRegistry` until objects can actually be freed.
+
+use super::type_error;
+use crate::vm::VM;
+use crate::vm::value::{HeapData, HeapObject, JsValue, NativeResult};
+use std::collections::{HashMap, HashSet};
+
+/// Heap pointer of a value that can be held weakly
+fn weak_key(vm: &VM, value: &JsValue) -> Option<usize> {
+    match value {
+        JsValue::Object(ptr) if vm.heap.contains(*ptr) => Some(*ptr),
+        _ => None,
+    }
+}
+
+fn alloc(vm: &mut VM, data: HeapData) -> JsValue {
+    let ptr = vm.heap.len();
+    vm.heap.push(HeapObject { data });
+    JsValue::Object(ptr)
+}
+
+/// Elements of an array argument, or nothing
+fn initial_items(vm: &VM, arg: Option<&JsValue>) -> Vec<JsValue> {
+    match arg {
+        Some(JsValue::Object(ptr)) => match vm.heap.get(*ptr).map(|obj| &obj.data) {
+            Some(HeapData::Array(items)) => items.clone(),
+            _ => Vec::new(),
+        },
+        _ => Vec::new(),
+    }
+}
+
+/// new WeakMap([[key, value], ...])
+pub fn native_weak_map(vm: &mut VM, args: Vec<JsValue>) -> NativeResult {
+    let mut entries = HashMap::new();
+    for pair in initial_items(vm, args.first()) {
+        let pair = initial_items(vm, Some(&pair));
+        let key = pair.first().cloned().unwrap_or(JsValue::Undefined);
+        let Some(key) = weak_key(vm, &key) else {
+            return Err(type_error("Invalid value used as weak map key"));
+        };
+        entries.insert(key, pair.get(1).cloned().unwrap_or(JsValue::Undefined));
+    }
+    Ok(alloc(vm, HeapData::WeakMap(entries)))
+}
+
+/// new WeakSet([value, ...])
+pub fn native_weak_set(vm: &mut VM, args: Vec<JsValue>) -> NativeResult {
+    let mut members = HashSet::new();
+    for value in initial_items(vm, args.first()) {
+        let Some(ptr) = weak_key(vm, &value) else {
+            return Err(type_error("Invalid value used in weak set"));
+        };
+        members.insert(ptr);
+    }
+    Ok(alloc(vm, HeapData::WeakSet(members)))
+}
+
+/// new WeakRef(target)
+pub fn native_weak_ref(vm: &mut VM, args: Vec<JsValue>) -> NativeResult {
+    match args.first().and_then(|target| weak_key(vm, target)) {
+        Some(target) => Ok(alloc(vm, HeapData::WeakRef(target))),
+        None => Err(type_error("WeakRef: target must be an object")),
+    }
+}
+
+/// Call method `name` on the weak collection at `ptr`. `None` when it has no
+/// such method.
+pub fn call_method(vm: &mut VM, ptr: usize, name: &str, args: &[JsValue]) -> Option<JsValue> {
+    let key = args.first().and_then(|arg| weak_key(vm, arg));
+    let receiver = JsValue::Object(ptr);
+    let data = &mut vm.heap.get_mut(ptr)?.data;
+    let result = match (data, name) {
+        (HeapData::WeakMap(entries), "get") => key
+            .and_then(|key| entries.get(&key).cloned())
+            .unwrap_or(JsValue::Undefined),
+        (HeapData::WeakMap(entries), "has") => {
+            JsValue::Boolean(key.is_some_and(|key| entries.contains_key(&key)))
+        }
+        (HeapData::WeakMap(entries), "delete") => {
+            JsValue::Boolean(key.is_some_and(|key| entries.remove(&key).is_some()))
+        }
+        (HeapData::WeakMap(entries), "set") => {
+            let Some(key) = key else {
+                eprintln!("TypeError: Invalid value used as weak map key");
+                return Some(JsValue::Undefined);
+            };
+            entries.insert(key, args.get(1).cloned().unwrap_or(JsValue::Undefined));
+            receiver
+        }
+        (HeapData::WeakSet(members), "has") => {
+            JsValue::Boolean(key.is_some_and(|key| members.contains(&key)))
+        }
+        (HeapData::WeakSet(members), "delete") => {
+            JsValue::Boolean(key.is_some_and(|key| members.remove(&key)))
+        }
+        (HeapData::WeakSet(members), "add") => {
+            let Some(key) = key else {
+                eprintln!("TypeError: Invalid value used in weak set");
+                return Some(JsValue::Undefined);
+            };
+            members.insert(key);
+            receiver
+        }
+        (HeapData::WeakRef(target), "deref") => JsValue::Object(*target),
+        _ => return None,
+    };
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_weak_map_keys_by_identity() {
+        let mut vm = VM::new();
+        let a = alloc(&mut vm, HeapData::Object(HashMap::new()));
+        let b = alloc(&mut vm, HeapData::Object(HashMap::new()));
+        let Ok(JsValue::Object(map)) = native_weak_map(&mut vm, vec![]) else {
+            panic!("expected a WeakMap");
+        };
+
+        call_method(&mut vm, map, "set", &[a.clone(), JsValue::Number(1.0)]);
+        assert!(matches!(
+            call_method(&mut vm, map, "get", std::slice::from_ref(&a)),
+            Some(JsValue::Number(n)) if n == 1.0
+        ));
+        assert!(matches!(
+            call_method(&mut vm, map, "has", &[b]),
+            Some(JsValue::Boolean(false))
+        ));
+        assert!(matches!(
+            call_method(&mut vm, map, "has", &[JsValue::String("x".into())]),
+            Some(JsValue::Boolean(false))
+        ));
+        assert!(matches!(
+            call_method(&mut vm, map, "delete", std::slice::from_ref(&a)),
+            Some(JsValue::Boolean(true))
+        ));
+        assert!(call_method(&mut vm, map, "keys", &[]).is_none());
+    }
+}
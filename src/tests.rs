@@ -259,6 +259,85 @@ fn test_closure_captures_variable_for_async() {
     // Test passes if no panic occurs - the closure accessed captured data
 }
 
+#[test]
+fn test_closures_write_through_to_top_level_bindings() {
+    let mut vm = VM::new();
+    let ast = parse_js(
+        "let count = 0;
+         let log = '';
+         let add = (word) => { count = count + 1; log = log + word; };
+         add('a');
+         add('b');
+         function counter() {
+             let n = 0;
+             return () => { n = n + 1; return n + count; };
+         }
+         let next = counter();
+         next();
+         let last = next();",
+    );
+    let mut cg = Codegen::new();
+    let bytecode = cg.generate(&ast);
+    vm.load_program(bytecode);
+    vm.run_event_loop();
+
+    let globals = &vm.call_stack[0].locals;
+    assert_eq!(globals.get("count"), Some(&JsValue::Number(2.0)));
+    assert_eq!(globals.get("log"), Some(&JsValue::String("ab".to_string())));
+    // Function locals are still copied into the closure
+    assert_eq!(globals.get("last"), Some(&JsValue::Number(3.0)));
+}
+
+#[test]
+fn test_shared_top_level_captures_are_still_borrows() {
+    // Sharing top-level bindings changes what the closure sees at run time,
+    // not what the checker allows: the capture still borrows the binding.
+    let ast = parse_js(
+        "let state = { n: 0 };
+         let bump = () => { state.n = state.n + 1; };
+         let taken = state;",
+    );
+    let mut bc = BorrowChecker::new();
+    bc.enter_scope();
+    let results: Vec<_> = ast
+        .body
+        .iter()
+        .filter_map(|item| item.as_stmt())
+        .map(|stmt| bc.analyze_stmt(stmt))
+        .collect();
+    assert!(results[1].is_ok());
+    assert_eq!(
+        results[2].clone().unwrap_err(),
+        "BORROW ERROR: Cannot move 'state' while a closure captures it by reference"
+    );
+}
+
+#[test]
+fn test_top_level_bindings_stay_shared_when_a_function_shadows_them() {
+    let mut vm = VM::new();
+    let ast = parse_js(
+        "let count = 0;
+         function other() {
+             let count = 10;
+             return () => count;
+         }
+         let bump = () => { count = count + 1; };
+         bump();
+         bump();
+         let inner = other()();",
+    );
+    let mut cg = Codegen::new();
+    let bytecode = cg.generate(&ast);
+    vm.load_program(bytecode);
+    vm.run_event_loop();
+
+    let globals = &vm.call_stack[0].locals;
+    // `bump` sees the top-level `count`, not the one local to `other`
+    assert_eq!(globals.get("count"), Some(&JsValue::Number(2.0)));
+    // The closure in `other` sees its own `count`
+    assert_eq!(globals.get("inner"), Some(&JsValue::Number(10.0)));
+}
+
 /// Test that the borrow checker prevents use of a captured variable
 /// after it has been moved into a closure.
 ///
@@ -268,11 +347,11 @@ fn test_closure_captures_variable_for_async() {
 fn test_borrow_checker_prevents_use_after_capture() {
     let mut bc = BorrowChecker::new();
 
-    // This code captures `data` in a closure, then tries to use it again.
+    // This code moves `data` into a closure, then tries to use it again.
     // The borrow checker should reject the second use.
     let code = r#"
         let data = { message: "Hello" };
-        setTimeout(() => { console.log(data.message); }, 0);
+        setTimeout(move(() => { console.log(data.message); }), 0);
         data.message;
     "#;
 
@@ -289,7 +368,7 @@ fn test_borrow_checker_prevents_use_after_capture() {
     assert!(results[0].is_ok(), "let data = ... should pass");
 
     // setTimeout with closure that captures `data` should pass
-    // (but it marks `data` as CapturedByAsync)
+    // (but it marks `data` as moved into the closure)
     assert!(results[1].is_ok(), "setTimeout(...) should pass");
 
     // Trying to access `data` after it was captured should FAIL!
@@ -306,3 +385,2623 @@ fn test_borrow_checker_prevents_use_after_capture() {
         err
     );
 }
+
+// ==================== PROTOTYPE METHOD REGISTRY TESTS ====================
+
+fn native_test_shout(_vm: &mut VM, args: Vec<JsValue>) -> JsValue {
+    match args.first() {
+        Some(JsValue::String(s)) => JsValue::String(format!("{}!", s.to_uppercase())),
+        _ => JsValue::Undefined,
+    }
+}
+
+#[test]
+fn test_registered_prototype_methods_extend_builtins() {
+    use crate::vm::BuiltinProto;
+
+    let mut vm = VM::new();
+    vm.register_prototype_method(BuiltinProto::String, "shout", native_test_shout);
+
+    let ast = parse_js("let a = 'hi'.shout(); let b = (255).toString(16); let c = 'x'.missing(1);");
+    let mut cg = Codegen::new();
+    let bytecode = cg.generate(&ast);
+    vm.load_program(bytecode);
+    vm.run_event_loop();
+
+    let globals = &vm.call_stack[0].locals;
+    assert_eq!(globals.get("a"), Some(&JsValue::String("HI!".to_string())));
+    assert_eq!(globals.get("b"), Some(&JsValue::String("ff".to_string())));
+    assert_eq!(globals.get("c"), Some(&JsValue::Undefined));
+}
+
+#[test]
+fn test_number_boolean_conversion_globals() {
+    let mut vm = VM::new();
+    let ast = parse_js(
+        "let a = Number(' 42 '); let b = Number('0x1f'); let c = Number(true); \
+         let d = String(0 / 0); let e = Boolean(''); let f = Boolean('0'); \
+         let g = parseInt('ff', 16); let h = parseInt('12px'); let i = parseFloat('3.5e2xyz'); \
+         let j = isNaN(parseInt('abc')); let k = new Number('7');",
+    );
+    let mut cg = Codegen::new();
+    let bytecode = cg.generate(&ast);
+    vm.load_program(bytecode);
+    vm.run_event_loop();
+
+    let globals = &vm.call_stack[0].locals;
+    assert_eq!(globals.get("a"), Some(&JsValue::Number(42.0)));
+    assert_eq!(globals.get("b"), Some(&JsValue::Number(31.0)));
+    assert_eq!(globals.get("c"), Some(&JsValue::Number(1.0)));
+    assert_eq!(globals.get("d"), Some(&JsValue::String("NaN".to_string())));
+    assert_eq!(globals.get("e"), Some(&JsValue::Boolean(false)));
+    assert_eq!(globals.get("f"), Some(&JsValue::Boolean(true)));
+    assert_eq!(globals.get("g"), Some(&JsValue::Number(255.0)));
+    assert_eq!(globals.get("h"), Some(&JsValue::Number(12.0)));
+    assert_eq!(globals.get("i"), Some(&JsValue::Number(350.0)));
+    assert_eq!(globals.get("j"), Some(&JsValue::Boolean(true)));
+    assert_eq!(globals.get("k"), Some(&JsValue::Number(7.0)));
+}
+
+#[test]
+fn test_natives_called_as_functions_get_arguments_in_call_order() {
+    let mut vm = VM::new();
+    let ast = parse_js(
+        "let fromCharCode = String.fromCharCode; \
+         let word = fromCharCode(104, 105, 33); \
+         let method = String.fromCharCode(104, 105, 33); \
+         let radix = parseInt('11', 2);",
+    );
+    let mut cg = Codegen::new();
+    let bytecode = cg.generate(&ast);
+    vm.load_program(bytecode);
+    vm.run_event_loop();
+
+    let globals = &vm.call_stack[0].locals;
+    // A plain call and a method call pass the same order
+    assert_eq!(
+        globals.get("word"),
+        Some(&JsValue::String("hi!".to_string()))
+    );
+    assert_eq!(globals.get("method"), globals.get("word"));
+    assert_eq!(globals.get("radix"), Some(&JsValue::Number(3.0)));
+}
+
+#[test]
+fn test_multi_return_destructuring_skips_array() {
+    let mut vm = VM::new();
+    let ast = parse_js(
+        "function divmod(a, b) { return [(a - a % b) / b, a % b]; } \
+         function pair() { return [1, 2]; } \
+         let [q, r] = divmod(17, 5); \
+         let [, second] = pair(); \
+         let whole = pair(); \
+         let first = whole[0];",
+    );
+    let mut cg = Codegen::new();
+    let bytecode = cg.generate(&ast);
+    assert!(
+        bytecode
+            .iter()
+            .any(|op| matches!(op, OpCode::ReturnMulti(2)))
+    );
+    assert!(bytecode.iter().any(|op| matches!(op, OpCode::Unpack(2))));
+    vm.load_program(bytecode);
+    vm.run_event_loop();
+
+    let globals = &vm.call_stack[0].locals;
+    assert_eq!(globals.get("q"), Some(&JsValue::Number(3.0)));
+    assert_eq!(globals.get("r"), Some(&JsValue::Number(2.0)));
+    assert_eq!(globals.get("second"), Some(&JsValue::Number(2.0)));
+    assert_eq!(globals.get("first"), Some(&JsValue::Number(1.0)));
+}
+
+#[test]
+fn test_multi_return_from_setter_leaves_no_values() {
+    let run = |ret: &str| {
+        let mut vm = VM::new();
+        let ast = parse_js(&format!(
+            "class Pair {{ set both(v) {{ this.seen = v; return {}; }} }} \
+             let o = new Pair(); o.both = 4; o.both = 5; \
+             let total = o.seen + 1;",
+            ret
+        ));
+        let bytecode = Codegen::new().generate(&ast);
+        let multi = bytecode
+            .iter()
+            .any(|op| matches!(op, OpCode::ReturnMulti(2)));
+        vm.load_program(bytecode);
+        vm.run_event_loop();
+        (vm, multi)
+    };
+
+    let (single, _) = run("v");
+    let (multi, returns_multi) = run("[v, v]");
+    assert!(returns_multi);
+    assert_eq!(
+        multi.call_stack[0].locals.get("total"),
+        Some(&JsValue::Number(6.0))
+    );
+    // Nothing the setters returned is left behind
+    assert_eq!(multi.stack.len(), single.stack.len());
+}
+
+#[test]
+fn test_console_log_capture() {
+    let mut vm = VM::new();
+    let ast = parse_js("console.log('a', 1); console.log(true, null);");
+    let mut cg = Codegen::new();
+    let bytecode = cg.generate(&ast);
+    vm.captured_output = Some(String::new());
+    vm.load_program(bytecode);
+    vm.run_event_loop();
+
+    assert_eq!(vm.captured_output.as_deref(), Some("a 1\ntrue null\n"));
+}
+
+#[test]
+fn test_console_format_specifiers_table_and_timers() {
+    let mut vm = VM::new();
+    let ast = parse_js(
+        "console.info('%s has %d items (%i%%) %o', 'cart', '3', 42.9, 'x', '!'); \
+         console.log('100%', 5); \
+         console.table([{ a: 1, b: 'x' }, { a: 22 }, 7]); \
+         console.time('load'); console.timeEnd('load');",
+    );
+    let bytecode = Codegen::new().generate(&ast);
+    vm.captured_output = Some(String::new());
+    vm.load_program(bytecode);
+    vm.run_event_loop();
+
+    let output = vm.captured_output.clone().unwrap();
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(lines[0], "cart has 3 items (42%) 'x' !");
+    assert_eq!(lines[1], "100% 5");
+    assert_eq!(
+        &lines[2..9],
+        [
+            "┌─────────┬────┬─────┬────────┐",
+            "│ (index) │ a  │ b   │ Values │",
+            "├─────────┼────┼─────┼────────┤",
+            "│ 0       │ 1  │ 'x' │        │",
+            "│ 1       │ 22 │     │        │",
+            "│ 2       │    │     │ 7      │",
+            "└─────────┴────┴─────┴────────┘",
+        ]
+    );
+    assert!(lines[9].starts_with("load: ") && lines[9].ends_with("ms"));
+    assert!(vm.console_timers.is_empty());
+}
+
+#[test]
+fn test_console_locale_does_not_leak_into_data() {
+    let mut vm = VM::new();
+    let ast = parse_js(
+        "let s = String(1e21) + ' ' + 1e-7 + ' ' + 1234.5; \
+         let j = JSON.stringify({ 'a\"b': 1234.5, n: 1e21 }); \
+         console.log(1234567.5); console.log(s);",
+    );
+    let mut cg = Codegen::new();
+    let bytecode = cg.generate(&ast);
+    vm.captured_output = Some(String::new());
+    vm.console_locale = Some(crate::stdlib::console::ConsoleLocale::parse("de-DE").unwrap());
+    vm.load_program(bytecode);
+    vm.run_event_loop();
+
+    assert_eq!(
+        vm.captured_output.as_deref(),
+        Some("1.234.567,5\n1e+21 1e-7 1234.5\n")
+    );
+    let globals = &vm.call_stack[0].locals;
+    assert_eq!(
+        globals.get("j"),
+        Some(&JsValue::String(r#"{"a\"b":1234.5,"n":1e+21}"#.to_string()))
+    );
+}
+
+#[test]
+fn test_builtin_modules_have_default_exports() {
+    let mut vm = VM::new();
+    let bytecode = crate::compiler::Compiler::new()
+        .compile(
+            "import util from 'util';
+             import { inspect } from 'util';
+             import files from 'fs';
+             console.log(util.inspect([1]), inspect(2), files.existsSync === fs.existsSync);",
+        )
+        .unwrap();
+    vm.captured_output = Some(String::new());
+    vm.load_program(bytecode);
+    vm.run_event_loop();
+
+    assert_eq!(vm.captured_output.as_deref(), Some("[ 1 ] 2 true\n"));
+}
+
+#[test]
+fn test_console_inspects_nested_and_cyclic_objects() {
+    let mut vm = VM::new();
+    let ast = parse_js(
+        "let a = { name: 'root', list: [1, 2], deep: { x: { y: { z: 1 } } } }; a.self = a; \
+         console.log(a); console.log({ 'my key': 'v' }, []); \
+         let m = new Map(); m.set('k', [1]); \
+         let util = require('util'); \
+         let s = util.inspect(m, 0) + ' ' + util.inspect('hi'); \
+         let e = JSON.stringify(Object.entries({ b: 2, a: 1, 10: 1, 2: 0 }));",
+    );
+    let bytecode = Codegen::new().generate(&ast);
+    vm.captured_output = Some(String::new());
+    vm.load_program(bytecode);
+    vm.run_event_loop();
+
+    assert_eq!(
+        vm.captured_output.as_deref(),
+        Some(
+            "{\n  deep: { x: { y: [Object] } },\n  list: [ 1, 2 ],\n  name: 'root',\n  self: [Circular]\n}\n\
+             { 'my key': 'v' } []\n"
+        )
+    );
+    let globals = &vm.call_stack[0].locals;
+    assert_eq!(
+        globals.get("s"),
+        Some(&JsValue::String(
+            "Map(1) { 'k' => [Array] } 'hi'".to_string()
+        ))
+    );
+    assert_eq!(
+        globals.get("e"),
+        Some(&JsValue::String(
+            r#"[["2",0],["10",1],["a",1],["b",2]]"#.to_string()
+        ))
+    );
+}
+
+#[test]
+fn test_profiler_samples_function_stacks() {
+    let mut vm = VM::new();
+    let ast = parse_js(
+        "function fib(n) { if (n < 2) { return n; } return fib(n - 1) + fib(n - 2); } let r = fib(12);",
+    );
+    let mut cg = Codegen::new();
+    let bytecode = cg.generate(&ast);
+    vm.profiler = Some(crate::vm::Profiler::new(1));
+    vm.load_program(bytecode);
+    vm.run_event_loop();
+
+    let profile = vm.profiler.take().unwrap().finish(&vm.program);
+    assert!(
+        profile.stacks.iter().any(|(frames, _)| frames.len() > 2
+            && frames[0] == "(top level)"
+            && frames[1] == "fib")
+    );
+}
+
+#[test]
+fn test_coverage_counts_statement_lines() {
+    let mut vm = VM::new();
+    let mut compiler = crate::compiler::Compiler::new();
+    let bytecode = compiler
+        .compile("let total = 0;\nfor (let i = 0; i < 3; i++) {\n  total = total + i;\n}\nif (total > 100) {\n  total = 0;\n}\n")
+        .unwrap();
+    let mut coverage = crate::vm::Coverage::new();
+    coverage.add_chunk(std::path::Path::new("loop.ot"), 0, &compiler.line_table);
+    vm.coverage = Some(coverage);
+    vm.load_program(bytecode);
+    vm.run_event_loop();
+
+    let coverage = vm.coverage.take().unwrap();
+    let lines = &coverage.line_hits()[0].1;
+    assert_eq!(lines.get(&1), Some(&1));
+    assert_eq!(lines.get(&3), Some(&3));
+    assert_eq!(lines.get(&6), Some(&0));
+    assert!(coverage.to_lcov().contains("DA:3,3\n"));
+}
+
+#[test]
+fn test_runtime_features_describe_build() {
+    let mut vm = VM::new();
+    let ast = parse_js(
+        "let f = runtime.features; console.log(f.backend, f.modules.includes('fs'), f.modules.includes('console'), f.permissions.net);",
+    );
+    let bytecode = Codegen::new().generate(&ast);
+    vm.captured_output = Some(String::new());
+    vm.load_program(bytecode);
+    vm.run_event_loop();
+
+    assert_eq!(
+        vm.captured_output.as_deref(),
+        Some("interpreter true true true\n")
+    );
+}
+
+#[test]
+fn test_trace_logs_only_filtered_function() {
+    let path = std::env::temp_dir().join(format!("oite_trace_{}.log", std::process::id()));
+    let run = || {
+        let mut vm = VM::new();
+        let ast = parse_js("function add(a, b) { return a + b; } let r = add(1, 2); r = r + 1;");
+        let bytecode = Codegen::new().generate(&ast);
+        let file = std::fs::File::create(&path).unwrap();
+        vm.tracer = Some(crate::vm::Tracer::new(
+            Box::new(file),
+            vec!["add".to_string()],
+        ));
+        vm.load_program(bytecode);
+        vm.run_event_loop();
+        vm.tracer.take().unwrap().finish().unwrap();
+        std::fs::read_to_string(&path).unwrap()
+    };
+
+    let log = run();
+    assert!(!log.is_empty());
+    assert!(
+        log.lines()
+            .all(|line| line.contains(" d=2 ") && line.contains(" add "))
+    );
+    // `r + 1` at top level is filtered out
+    assert_eq!(log.lines().filter(|line| line.contains(" Add ")).count(), 1);
+    // No timestamps or addresses, so reruns diff clean
+    assert_eq!(run(), log);
+    std::fs::remove_file(&path).ok();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_symlinked_import_executes_module_once() {
+    let dir = std::env::temp_dir().join(format!("oite_double_exec_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        dir.join("counter.ot"),
+        "console.log('loaded'); export const n = 1;",
+    )
+    .unwrap();
+    let _ = std::fs::remove_file(dir.join("alias.ot"));
+    std::os::unix::fs::symlink(dir.join("counter.ot"), dir.join("alias.ot")).unwrap();
+    let main = dir.join("main.ot");
+    std::fs::write(&main, "").unwrap();
+
+    let mut vm = VM::new();
+    let bytecode = crate::compiler::Compiler::new()
+        .compile("import { n } from './counter'; import { n as m } from './alias';")
+        .unwrap();
+    vm.captured_output = Some(String::new());
+    vm.load_program_with_path(bytecode, main);
+    vm.run_event_loop();
+
+    assert_eq!(vm.captured_output.as_deref(), Some("loaded\n"));
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn test_module_body_continues_after_calling_an_import() {
+    let dir = std::env::temp_dir().join(format!("oite_module_calls_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("f.ot"), "export function f() { return 42; }").unwrap();
+    std::fs::write(
+        dir.join("g.ot"),
+        "import { f } from './f';
+         console.log('before');
+         let r = f();
+         console.log('got', r);
+         export const done = r + 1;",
+    )
+    .unwrap();
+    let main = dir.join("main.ot");
+    std::fs::write(&main, "").unwrap();
+
+    let mut vm = VM::new();
+    let bytecode = crate::compiler::Compiler::new()
+        .compile("import { done } from './g'; console.log('main', done);")
+        .unwrap();
+    vm.captured_output = Some(String::new());
+    vm.load_program_with_path(bytecode, main);
+    vm.run_event_loop();
+
+    assert_eq!(
+        vm.captured_output.as_deref(),
+        Some("before\ngot 42\nmain 43\n")
+    );
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn test_modules_run_in_their_own_scope() {
+    let dir = std::env::temp_dir().join(format!("oite_module_scope_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        dir.join("counter.ot"),
+        "let count = 0;
+         const secret = 'module';
+         export function bump() { count++; return count; }
+         export const label = secret + '!';",
+    )
+    .unwrap();
+    let main = dir.join("main.ot");
+    std::fs::write(&main, "").unwrap();
+
+    let mut vm = VM::new();
+    let bytecode = crate::compiler::Compiler::new()
+        .compile(
+            "let count = 10;
+             import { bump, label } from './counter';
+             let secret = 'main';
+             let first = bump();
+             let second = bump();
+             let shown = label;",
+        )
+        .unwrap();
+    vm.load_program_with_path(bytecode, main);
+    vm.run_event_loop();
+
+    let globals = &vm.call_stack[0].locals;
+    assert_eq!(globals.get("first"), Some(&JsValue::Number(1.0)));
+    assert_eq!(globals.get("second"), Some(&JsValue::Number(2.0)));
+    assert_eq!(globals.get("count"), Some(&JsValue::Number(10.0)));
+    assert_eq!(
+        globals.get("secret"),
+        Some(&JsValue::String("main".to_string()))
+    );
+    assert_eq!(
+        globals.get("shown"),
+        Some(&JsValue::String("module!".to_string()))
+    );
+
+    // Only declared exports make it into the namespace
+    let namespace = vm
+        .module_cache
+        .entries()
+        .values()
+        .find(|module| module.path.ends_with("counter.ot"))
+        .unwrap();
+    let crate::vm::value::HeapData::Object(props) = &vm.heap[namespace.namespace_object].data
+    else {
+        panic!("namespace is not an object");
+    };
+    assert!(props.contains_key("bump"));
+    assert!(!props.contains_key("count"));
+    assert!(!props.contains_key("secret"));
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn test_imports_are_live_and_cycles_resolve() {
+    let dir = std::env::temp_dir().join(format!("oite_live_imports_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        dir.join("counter.ot"),
+        "export let count = 0;
+         export function increment() { count++; }",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("a.ot"),
+        "import { b } from './b';
+         console.log('a loaded');
+         export function a() { return 'a' + b(); }",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("b.ot"),
+        "import { a } from './a';
+         console.log('b loaded');
+         export function b() { return 'b'; }
+         export function callA() { return a(); }",
+    )
+    .unwrap();
+    let main = dir.join("main.ot");
+    std::fs::write(&main, "").unwrap();
+
+    let mut vm = VM::new();
+    let bytecode = crate::compiler::Compiler::new()
+        .compile(
+            "import { count, increment } from './counter';
+             import * as counter from './counter';
+             import { a } from './a';
+             import { callA } from './b';
+             let before = count;
+             increment();
+             increment();
+             let after = count;
+             let viaNamespace = counter.count;
+             let reassigned = 'no';
+             try { count = 5; } catch (e) { reassigned = e; }
+             let cycle = callA();",
+        )
+        .unwrap();
+    vm.captured_output = Some(String::new());
+    vm.load_program_with_path(bytecode, main);
+    vm.run_event_loop();
+
+    let globals = &vm.call_stack[0].locals;
+    assert_eq!(globals.get("before"), Some(&JsValue::Number(0.0)));
+    assert_eq!(globals.get("after"), Some(&JsValue::Number(2.0)));
+    assert_eq!(globals.get("viaNamespace"), Some(&JsValue::Number(2.0)));
+    assert!(
+        matches!(globals.get("reassigned"), Some(JsValue::String(e)) if e.contains("constant"))
+    );
+    assert_eq!(
+        globals.get("cycle"),
+        Some(&JsValue::String("ab".to_string()))
+    );
+    // Each module of the cycle ran once, the one imported last first
+    assert_eq!(vm.captured_output.as_deref(), Some("b loaded\na loaded\n"));
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn test_reexports_and_default_exports() {
+    let dir = std::env::temp_dir().join(format!("oite_reexports_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let files = [
+        (
+            "base.ot",
+            "export let value = 1;
+             export function bump() { value++; }
+             export default 'base default';",
+        ),
+        (
+            "shapes.ot",
+            "export class Point { constructor(x) { this.x = x; } }
+             export enum Color { Red, Green }
+             export default class { name() { return 'anon class'; } }",
+        ),
+        (
+            "label.ot",
+            "export default function () { return 'anon fn'; }",
+        ),
+        (
+            "index.ot",
+            "export * from './base';
+             export { value as current, default as baseDefault } from './base';
+             export { default as Shape, Point, Color } from './shapes';
+             export * as label from './label';
+             export const own = 'own';",
+        ),
+    ];
+    for (name, source) in files {
+        std::fs::write(dir.join(name), source).unwrap();
+    }
+    let main = dir.join("main.ot");
+    std::fs::write(&main, "").unwrap();
+
+    let mut vm = VM::new();
+    let bytecode = crate::compiler::Compiler::new()
+        .compile(
+            "import { value, bump, current, baseDefault, Shape, Point, Color, label, own } from './index';
+             import * as index from './index';
+             import makeLabel from './label';
+             bump();
+             let starValue = value;
+             let renamed = current;
+             let defaultValue = baseDefault;
+             let className = new Shape().name();
+             let x = new Point(3).x;
+             let green = Color.Green;
+             let fnResult = makeLabel();
+             let viaNamespace = label.default();
+             let ownValue = own;
+             let starSkipsDefault = 'default' in index;",
+        )
+        .unwrap();
+    vm.load_program_with_path(bytecode, main);
+    vm.run_event_loop();
+
+    let globals = &vm.call_stack[0].locals;
+    let string = |s: &str| Some(JsValue::String(s.to_string()));
+    assert_eq!(globals.get("starValue"), Some(&JsValue::Number(2.0)));
+    assert_eq!(globals.get("renamed"), Some(&JsValue::Number(2.0)));
+    assert_eq!(globals.get("defaultValue").cloned(), string("base default"));
+    assert_eq!(globals.get("className").cloned(), string("anon class"));
+    assert_eq!(globals.get("x"), Some(&JsValue::Number(3.0)));
+    assert_eq!(globals.get("green"), Some(&JsValue::Number(1.0)));
+    assert_eq!(globals.get("fnResult").cloned(), string("anon fn"));
+    assert_eq!(globals.get("viaNamespace").cloned(), string("anon fn"));
+    assert_eq!(globals.get("ownValue").cloned(), string("own"));
+    assert_eq!(
+        globals.get("starSkipsDefault"),
+        Some(&JsValue::Boolean(false))
+    );
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn test_hot_reload_reruns_accepted_modules() {
+    let dir = std::env::temp_dir().join(format!("oite_hot_reload_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let counter = |version: &str| {
+        format!(
+            "export let count = import.meta.hot.data.count || 0;
+             count = count + 1;
+             export function label() {{ return '{}:' + count; }}
+             import.meta.hot.dispose((data) => {{ data.count = count; }});
+             import.meta.hot.accept();",
+            version
+        )
+    };
+    std::fs::write(dir.join("counter.ot"), counter("v1")).unwrap();
+    std::fs::write(dir.join("theme.ot"), "export const color = 'red';").unwrap();
+    std::fs::write(dir.join("plain.ot"), "export const n = 1;").unwrap();
+    let main = dir.join("main.ot");
+    std::fs::write(&main, "").unwrap();
+
+    let mut vm = VM::new();
+    vm.hot = Some(crate::vm::hot::HotReload::new(&main));
+    let bytecode = crate::compiler::Compiler::new()
+        .compile(
+            "import { label } from './counter';
+             import { color } from './theme.ot';
+             import { n } from './plain';
+             let applied = color;
+             import.meta.hot.accept('./theme.ot', (theme) => { applied = theme.color; });
+             function show() { return label(); }",
+        )
+        .unwrap();
+    vm.load_program_with_path(bytecode, main);
+    vm.run_event_loop();
+
+    let show = vm.call_stack[0].locals.get("show").cloned().unwrap();
+    let string = |s: &str| JsValue::String(s.to_string());
+    assert_eq!(vm.call_function(show.clone(), vec![]), Ok(string("v1:1")));
+
+    let path = |name: &str| crate::platform::canonicalize(&dir.join(name)).unwrap();
+    std::fs::write(dir.join("counter.ot"), counter("v2")).unwrap();
+    crate::vm::hot::apply_update(&mut vm, &[path("counter.ot")]);
+    assert_eq!(vm.call_function(show, vec![]), Ok(string("v2:2")));
+    assert!(!vm.hot.as_ref().unwrap().reload_requested());
+
+    std::fs::write(dir.join("theme.ot"), "export const color = 'blue';").unwrap();
+    crate::vm::hot::apply_update(&mut vm, &[path("theme.ot")]);
+    assert_eq!(
+        vm.call_stack[0].locals.get("applied").cloned(),
+        Some(string("blue"))
+    );
+    assert!(!vm.hot.as_ref().unwrap().reload_requested());
+
+    std::fs::write(dir.join("plain.ot"), "export const n = 2;").unwrap();
+    crate::vm::hot::apply_update(&mut vm, &[path("plain.ot")]);
+    assert!(vm.hot.as_ref().unwrap().reload_requested());
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn test_import_meta_describes_the_running_module() {
+    let dir = std::env::temp_dir().join(format!("oite_import_meta_{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("lib")).unwrap();
+    std::fs::write(
+        dir.join("lib/paths.ot"),
+        "export const url = import.meta.url;
+         export const dirname = import.meta.dirname;
+         export const asset = import.meta.resolve('./data.json');",
+    )
+    .unwrap();
+    std::fs::write(dir.join("lib/data.json"), "{}").unwrap();
+    let main = dir.join("main.ot");
+    std::fs::write(&main, "").unwrap();
+
+    let mut vm = VM::new();
+    let bytecode = crate::compiler::Compiler::new()
+        .compile(
+            "import { url, dirname, asset } from './lib/paths';
+             let libUrl = url;
+             let libDir = dirname;
+             let dataUrl = asset;
+             let mainFile = import.meta.filename;
+             let sibling = import.meta.resolve('./lib/paths.ot');
+             let builtin = import.meta.resolve('fs');",
+        )
+        .unwrap();
+    vm.load_program_with_path(bytecode, main.clone());
+    vm.run_event_loop();
+
+    let canonical = |p: &std::path::Path| crate::platform::canonicalize(p).unwrap();
+    let url = |p: &std::path::Path| Some(JsValue::String(crate::platform::file_url(&canonical(p))));
+    let lib = dir.join("lib");
+    let globals = &vm.call_stack[0].locals;
+    assert_eq!(globals.get("libUrl").cloned(), url(&lib.join("paths.ot")));
+    assert_eq!(
+        globals.get("libDir").cloned(),
+        Some(JsValue::String(
+            canonical(&lib).to_string_lossy().into_owned()
+        ))
+    );
+    assert_eq!(globals.get("dataUrl").cloned(), url(&lib.join("data.json")));
+    assert_eq!(
+        globals.get("mainFile").cloned(),
+        Some(JsValue::String(
+            canonical(&main).to_string_lossy().into_owned()
+        ))
+    );
+    assert_eq!(globals.get("sibling").cloned(), url(&lib.join("paths.ot")));
+    assert_eq!(
+        globals.get("builtin").cloned(),
+        Some(JsValue::String("node:fs".to_string()))
+    );
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn test_entry_script_in_an_import_cycle_runs_once() {
+    let dir = std::env::temp_dir().join(format!("oite_entry_cycle_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let source = "import { fromQ } from './q';
+                  console.log('p runs');
+                  export function fromP() { return 'p'; }
+                  let got = fromQ();";
+    let entry = dir.join("p.ot");
+    std::fs::write(&entry, source).unwrap();
+    std::fs::write(
+        dir.join("q.ot"),
+        "import { fromP } from './p';
+         console.log('q runs');
+         export function fromQ() { return 'q' + fromP(); }",
+    )
+    .unwrap();
+
+    let mut vm = VM::new();
+    let bytecode = crate::compiler::Compiler::new().compile(source).unwrap();
+    vm.captured_output = Some(String::new());
+    vm.load_program_with_path(bytecode, entry);
+    vm.run_event_loop();
+
+    assert_eq!(vm.captured_output.as_deref(), Some("q runs\np runs\n"));
+    // q sees p's exports once p has bound them
+    assert_eq!(
+        vm.call_stack[0].locals.get("got"),
+        Some(&JsValue::String("qp".to_string()))
+    );
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn test_stdlib_globals_are_pinned_unless_shadowed() {
+    let bytecode = crate::compiler::Compiler::new()
+        .compile("console.log('a'); let JSON = 1; console.log(JSON);")
+        .unwrap();
+    let console = crate::vm::opcodes::pinned_global_slot("console").unwrap();
+    assert!(
+        bytecode
+            .iter()
+            .any(|op| matches!(op, OpCode::LoadGlobal(slot) if *slot == console))
+    );
+    assert!(
+        !bytecode
+            .iter()
+            .any(|op| matches!(op, OpCode::Load(name) if name == "console"))
+    );
+    assert!(
+        bytecode
+            .iter()
+            .any(|op| matches!(op, OpCode::Load(name) if name == "JSON"))
+    );
+
+    let mut vm = VM::new();
+    vm.captured_output = Some(String::new());
+    vm.load_program(bytecode);
+    vm.run_event_loop();
+    assert_eq!(vm.captured_output.as_deref(), Some("a\n1\n"));
+
+    // Code compiled elsewhere reassigning a pinned global updates its slot
+    let fs = crate::vm::opcodes::pinned_global_slot("fs").unwrap();
+    let mut vm = VM::new();
+    vm.load_program(vec![
+        OpCode::Push(JsValue::Number(5.0)),
+        OpCode::Store("fs".into()),
+        OpCode::LoadGlobal(fs),
+        OpCode::Let("seen".into()),
+        OpCode::Halt,
+    ]);
+    vm.run_event_loop();
+    assert!(matches!(
+        vm.call_stack[0].locals.get("seen"),
+        Some(JsValue::Number(n)) if *n == 5.0
+    ));
+}
+
+#[test]
+fn test_clone_produces_independent_copy() {
+    let mut vm = VM::new();
+    let bytecode = crate::compiler::Compiler::new()
+        .compile(
+            "let a = { items: [1, 2] };
+             let b = a.clone(); b.items.push(3);
+             let c = structuredClone(a.items); c.push(4);
+             let d = a.items.clone(); d.push(5);
+             let e = [0]; e.push(e);
+             let f = e.clone();
+             console.log(a.items.length, b.items.length, c.length, d.length, f[1] === f, f[1] === e);",
+        )
+        .unwrap();
+    vm.captured_output = Some(String::new());
+    vm.load_program(bytecode);
+    vm.run_event_loop();
+
+    assert_eq!(vm.captured_output.as_deref(), Some("2 3 3 3 true false\n"));
+}
+
+#[test]
+fn test_structured_clone_copies_graphs_and_transfers_buffers() {
+    let mut vm = VM::new();
+    let bytecode = crate::compiler::Compiler::new()
+        .compile(
+            "class Point { constructor(x) { this.x = x; } norm() { return this.x; } }
+             let s = new Set(); s.add(2);
+             let m = new Map(); m.set('k', s);
+             let graph = [m, new Point(3)]; graph.push(graph);
+             let copy = structuredClone(graph);
+             console.log(copy[2] === copy, copy[0].get('k').has(2), copy[1].x, copy[1].norm === undefined);
+             let buf = Buffer.from([1, 2, 3]);
+             let view = buf.subarray(1);
+             let moved = structuredClone(buf, { transfer: [buf] });
+             console.log(moved.length, moved[2], buf.length, view.length);
+             try { structuredClone({ f: () => 1 }); } catch (e) { console.log(e); }
+             try { structuredClone(1, { transfer: [[1]] }); } catch (e) { console.log(e); }",
+        )
+        .unwrap();
+    vm.captured_output = Some(String::new());
+    vm.load_program(bytecode);
+    vm.run_event_loop();
+
+    assert_eq!(
+        vm.captured_output.as_deref(),
+        Some(
+            "true true 3 true\n3 3 0 0\n\
+             DataCloneError: functions cannot be cloned\n\
+             DataCloneError: only buffers can be transferred\n"
+        )
+    );
+}
+
+#[test]
+fn test_enums_lower_to_objects_and_const_enums_inline() {
+    let mut compiler = crate::compiler::Compiler::new();
+    let bytecode = compiler
+        .compile(
+            "enum Color { Red, Green = 5, Blue }
+             enum Dir { Up = 'UP', Down = `DOWN` }
+             const enum Flag { A = 1 << 0, B = 1 << 1, AB = A | B }
+             let r = Color.Red; let g = Color[5]; let b = Color.Blue;
+             let u = Dir.Up; let rev = Dir['UP']; let ab = Flag.AB;",
+        )
+        .unwrap();
+    assert!(compiler.warnings.is_empty());
+    assert!(
+        !bytecode
+            .iter()
+            .any(|op| matches!(op, OpCode::Load(name) | OpCode::Let(name) if name == "Flag"))
+    );
+
+    let mut vm = VM::new();
+    vm.load_program(bytecode);
+    vm.run_event_loop();
+    let globals = &vm.call_stack[0].locals;
+    assert_eq!(globals.get("r"), Some(&JsValue::Number(0.0)));
+    assert_eq!(globals.get("g"), Some(&JsValue::String("Green".into())));
+    assert_eq!(globals.get("b"), Some(&JsValue::Number(6.0)));
+    assert_eq!(globals.get("u"), Some(&JsValue::String("UP".into())));
+    assert_eq!(globals.get("rev"), Some(&JsValue::Undefined));
+    assert_eq!(globals.get("ab"), Some(&JsValue::Number(3.0)));
+
+    // A const enum that cannot be folded stays a runtime object
+    compiler
+        .compile("let n = 2; const enum K { X = n } let x = K.X;")
+        .unwrap();
+    assert_eq!(
+        compiler.warnings,
+        ["const enum 'K' has members that are not constant; it is kept as a regular enum"]
+    );
+}
+
+#[test]
+fn test_uncaught_exception_reports_debug_info_stack() {
+    let source = "function fail() {\n  throw 'boom';\n}\nfail();\n";
+    let mut compiler = crate::compiler::Compiler::new();
+    let bytecode = compiler.compile(source).unwrap();
+    let mut functions: Vec<(usize, String)> = crate::vm::profiler::function_names(&bytecode)
+        .into_iter()
+        .collect();
+    functions.sort();
+
+    let mut vm = VM::new();
+    vm.load_program(bytecode);
+    vm.debug_info = Some(crate::loader::DebugInfo {
+        file: "app.ot".to_string(),
+        functions,
+        lines: compiler.line_table.clone(),
+        source: Some(source.to_string()),
+    });
+    let panic =
+        std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| vm.run_event_loop())).unwrap_err();
+    let message = panic.downcast_ref::<String>().unwrap();
+
+    assert!(
+        message.contains("\n    at fail (app.ot:2)\n        throw 'boom';\n"),
+        "{}",
+        message
+    );
+    assert!(
+        message.ends_with("\n    at (top level) (app.ot:4)\n        fail();"),
+        "{}",
+        message
+    );
+}
+
+#[test]
+fn test_native_errors_are_thrown_with_a_native_frame() {
+    let source = "let caught = '';\ntry { Object.defineProperty(Object.freeze({}), 'x', { value: 1 }); } catch (e) { caught = e; }\nfunction track(value) {\n  return new WeakRef(value);\n}\ntrack(1);\n";
+    let mut compiler = crate::compiler::Compiler::new();
+    let bytecode = compiler.compile(source).unwrap();
+    let mut functions: Vec<(usize, String)> = crate::vm::profiler::function_names(&bytecode)
+        .into_iter()
+        .collect();
+    functions.sort();
+
+    let mut vm = VM::new();
+    vm.load_program(bytecode);
+    vm.debug_info = Some(crate::loader::DebugInfo {
+        file: "app.ot".to_string(),
+        functions,
+        lines: compiler.line_table.clone(),
+        source: Some(source.to_string()),
+    });
+    let panic =
+        std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| vm.run_event_loop())).unwrap_err();
+    let message = panic.downcast_ref::<String>().unwrap();
+
+    assert_eq!(
+        vm.call_stack[0].locals.get("caught"),
+        Some(&JsValue::String(
+            "TypeError: Cannot define property x, object is not extensible".into()
+        ))
+    );
+    assert!(
+        message.contains("WeakRef: target must be an object"),
+        "{}",
+        message
+    );
+    assert!(
+        message.contains("\n    at WeakRef (native)\n    at track (app.ot:4)\n"),
+        "{}",
+        message
+    );
+}
+
+#[test]
+fn test_event_loop_stats_count_tasks_and_queues() {
+    let bytecode = crate::compiler::Compiler::new()
+        .compile(
+            "function tick() { let s = runtime.eventLoop(); console.log(s.tasksRun, s.pendingTimers, s.queuedTasks); }",
+        )
+        .unwrap();
+    let address = crate::vm::profiler::function_names(&bytecode)
+        .into_iter()
+        .find(|(_, name)| name == "tick")
+        .map(|(address, _)| address)
+        .unwrap();
+
+    let mut vm = VM::new();
+    vm.captured_output = Some(String::new());
+    vm.load_program(bytecode);
+    for _ in 0..2 {
+        vm.schedule_timer(
+            JsValue::Function {
+                address,
+                env: None,
+                bound: None,
+            },
+            0,
+        );
+    }
+    vm.run_event_loop();
+
+    // The running task is not counted until it finishes
+    assert_eq!(vm.captured_output.as_deref(), Some("0 0 1\n1 0 0\n"));
+    assert_eq!(vm.loop_stats.tasks_run, 2);
+    assert_eq!(vm.loop_stats.slow_tasks, 0);
+}
+
+#[test]
+fn test_borrow_check_level_downgrades_errors() {
+    use crate::compiler::borrow_ck::BorrowCheckLevel;
+
+    let source = "let buf = [1];\nlet a = buf;\nlet b = buf;\n";
+    let mut compiler = crate::compiler::Compiler::new();
+    assert!(compiler.compile(source).is_err());
+
+    compiler.borrow_check = BorrowCheckLevel::Warn;
+    assert!(compiler.compile(source).is_ok());
+    assert_eq!(
+        compiler.warnings,
+        ["BORROW ERROR: Use of moved variable 'buf'"]
+    );
+
+    compiler.borrow_check = BorrowCheckLevel::Off;
+    assert!(compiler.compile(source).is_ok());
+    assert!(compiler.warnings.is_empty());
+    assert_eq!(BorrowCheckLevel::parse("warn"), Ok(BorrowCheckLevel::Warn));
+    assert!(BorrowCheckLevel::parse("strict").is_err());
+}
+
+#[test]
+fn test_queued_tasks_run_by_priority() {
+    let mut vm = VM::new();
+    let bytecode = crate::compiler::Compiler::new()
+        .compile(
+            "function idle() { console.log('low'); }
+             function io() { console.log('normal'); }
+             function urgent() { console.log('high'); }
+             runtime.queueTask(idle, 'low');
+             runtime.queueTask(io);
+             runtime.queueTask(urgent, 'high');
+             console.log(runtime.eventLoop().queuedByPriority.low);",
+        )
+        .unwrap();
+    vm.captured_output = Some(String::new());
+    vm.load_program(bytecode);
+    vm.run_event_loop();
+
+    assert_eq!(
+        vm.captured_output.as_deref(),
+        Some("1\nhigh\nnormal\nlow\n")
+    );
+}
+
+#[test]
+fn test_microtasks_and_immediates() {
+    let mut vm = VM::new();
+    let bytecode = crate::compiler::Compiler::new()
+        .compile(
+            "function later(tag) { console.log('immediate ' + tag); }
+             function cancelled() { console.log('cancelled'); }
+             function micro() { console.log('micro'); }
+             function nested() { console.log('task micro'); }
+             function task() { console.log('task'); queueMicrotask(nested); setImmediate(later, 2); }
+             setImmediate(later, 1);
+             clearImmediate(setImmediate(cancelled));
+             runtime.queueTask(task);
+             queueMicrotask(micro);
+             console.log('sync');",
+        )
+        .unwrap();
+    vm.captured_output = Some(String::new());
+    vm.load_program(bytecode);
+    vm.run_event_loop();
+
+    assert_eq!(
+        vm.captured_output.as_deref(),
+        Some("sync\nmicro\ntask\ntask micro\nimmediate 1\nimmediate 2\n")
+    );
+}
+
+#[test]
+fn test_completions_keep_the_loop_alive() {
+    let mut vm = VM::new();
+    let bytecode = crate::compiler::Compiler::new()
+        .compile("function done(value) { console.log('settled ' + value); }")
+        .unwrap();
+    vm.captured_output = Some(String::new());
+    vm.load_program(bytecode);
+    vm.run_until_halt();
+    let Some(done) = vm.call_stack[0].locals.get("done").cloned() else {
+        panic!("done is not defined");
+    };
+
+    let promise = crate::vm::value::Promise::new();
+    vm.register_promise_callback(
+        &promise,
+        Box::new(move |vm, value| {
+            vm.queue_task(done, vec![value], crate::vm::TaskPriority::Normal)
+        }),
+    );
+    let settler = promise.clone();
+    let worker = std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        settler.set_value(JsValue::Number(7.0), true);
+    });
+    vm.run_event_loop();
+    worker.join().unwrap();
+
+    assert_eq!(vm.captured_output.as_deref(), Some("settled 7\n"));
+    assert_eq!(vm.scheduler.outstanding(), 0);
+}
+
+#[test]
+fn test_class_static_members_and_expressions() {
+    let mut vm = VM::new();
+    let ast = parse_js(
+        "class Base {
+             static count = 1;
+             static { this.count = this.count + 1; Base.ready = true; }
+             static make() { this.count = this.count + 1; return new this(); }
+         }
+         class Child extends Base {}
+         let obj = Base.make();
+         let count = Base.count;
+         let ready = Base.ready;
+         let inherited = Child.make === Base.make;
+         let hasStatic = obj.make === undefined;
+         const Point = class { constructor(x) { this.x = x; } };
+         let p = new Point(3);
+         let px = p.x;
+         let name = Point.name;
+         let Named = class Self { static self() { return Self; } };
+         let same = Named.self() === Named;",
+    );
+    let bytecode = Codegen::new().generate(&ast);
+    vm.load_program(bytecode);
+    vm.run_event_loop();
+
+    let globals = &vm.call_stack[0].locals;
+    assert_eq!(globals.get("count"), Some(&JsValue::Number(3.0)));
+    assert_eq!(globals.get("ready"), Some(&JsValue::Boolean(true)));
+    assert_eq!(globals.get("inherited"), Some(&JsValue::Boolean(true)));
+    assert_eq!(globals.get("hasStatic"), Some(&JsValue::Boolean(true)));
+    assert_eq!(globals.get("px"), Some(&JsValue::Number(3.0)));
+    assert_eq!(globals.get("name"), Some(&JsValue::String("Point".into())));
+    assert_eq!(globals.get("same"), Some(&JsValue::Boolean(true)));
+}
+
+#[test]
+fn test_private_methods_accessors_and_brand_checks() {
+    let mut vm = VM::new();
+    let ast = parse_js(
+        "class Temperature {
+             #celsius = 0;
+             #unset;
+             get #fahrenheit() { return this.#celsius * 9 / 5 + 32; }
+             set #fahrenheit(f) { this.#celsius = (f - 32) * 5 / 9; }
+             #scale(value, factor) { return value * factor; }
+             update(f) { this.#fahrenheit = f; return this.#scale(this.#celsius, 2); }
+             fahrenheit() { return this.#fahrenheit; }
+             static isTemperature(obj) { return #celsius in obj; }
+             static hasScale(obj) { return #scale in obj; }
+             static hasUnset(obj) { return #unset in obj; }
+             static scaleOf(obj) { return obj.#scale(1, 1); }
+         }
+         let t = new Temperature();
+         let doubled = t.update(212);
+         let f = t.fahrenheit();
+         let branded = Temperature.isTemperature(t);
+         let plain = Temperature.isTemperature({});
+         let hasScale = Temperature.hasScale(t);
+         let hasUnset = Temperature.hasUnset(t);
+         let hidden = t.scale === undefined;
+         let error = 'none';
+         try { Temperature.scaleOf({}); } catch (e) { error = e; }",
+    );
+    let bytecode = Codegen::new().generate(&ast);
+    vm.load_program(bytecode);
+    vm.run_event_loop();
+
+    let globals = &vm.call_stack[0].locals;
+    assert_eq!(globals.get("doubled"), Some(&JsValue::Number(200.0)));
+    assert_eq!(globals.get("f"), Some(&JsValue::Number(212.0)));
+    assert_eq!(globals.get("branded"), Some(&JsValue::Boolean(true)));
+    assert_eq!(globals.get("plain"), Some(&JsValue::Boolean(false)));
+    assert_eq!(globals.get("hasScale"), Some(&JsValue::Boolean(true)));
+    assert_eq!(globals.get("hasUnset"), Some(&JsValue::Boolean(true)));
+    assert_eq!(globals.get("hidden"), Some(&JsValue::Boolean(true)));
+    assert!(matches!(globals.get("error"), Some(JsValue::String(e)) if e.starts_with("TypeError")));
+}
+
+#[test]
+fn test_function_call_apply_bind() {
+    let mut vm = VM::new();
+    let ast = parse_js(
+        "function describe(greeting, punct) { return greeting + ', ' + this.name + punct; }
+         let alice = { name: 'Alice' };
+         let called = describe.call(alice, 'Hi', '!');
+         let applied = describe.apply(alice, ['Hello', '?']);
+         let hey = describe.bind(alice, 'Hey');
+         let bound = hey('.');
+         let rebound = hey.bind({ name: 'Bob' })('!!');
+         class Counter {
+             constructor() { this.n = 0; this.inc = this.inc.bind(this); }
+             inc() { this.n = this.n + 1; return this.n; }
+         }
+         let counter = new Counter();
+         let inc = counter.inc;
+         inc();
+         let count = inc();",
+    );
+    let bytecode = Codegen::new().generate(&ast);
+    vm.load_program(bytecode);
+    vm.run_event_loop();
+
+    let globals = &vm.call_stack[0].locals;
+    let string = |s: &str| Some(JsValue::String(s.to_string()));
+    assert_eq!(globals.get("called").cloned(), string("Hi, Alice!"));
+    assert_eq!(globals.get("applied").cloned(), string("Hello, Alice?"));
+    assert_eq!(globals.get("bound").cloned(), string("Hey, Alice."));
+    assert_eq!(globals.get("rebound").cloned(), string("Hey, Alice!!"));
+    assert_eq!(globals.get("count"), Some(&JsValue::Number(2.0)));
+}
+
+#[test]
+fn test_arrow_functions_capture_lexical_this() {
+    let mut vm = VM::new();
+    let ast = parse_js(
+        "class Ticker {
+             constructor() {
+                 this.ticks = 0;
+                 this.tick = () => { this.ticks = this.ticks + 1; return this.ticks; };
+             }
+             later() { let run = (f) => f(); run(() => { this.ticks = this.ticks + 10; }); }
+             nested() { let make = (n) => () => n + this.ticks; return make(2); }
+         }
+         let ticker = new Ticker();
+         let tick = ticker.tick;
+         tick();
+         let other = { ticks: 100, run: ticker.tick };
+         let viaOther = other.run();
+         ticker.later();
+         let fromNested = ticker.nested()();
+         function Made() { let check = () => () => new.target === Made; this.ok = check()(); }
+         let made = new Made();
+         let sawTarget = made.ok;",
+    );
+    let bytecode = Codegen::new().generate(&ast);
+    vm.load_program(bytecode);
+    vm.run_event_loop();
+
+    let globals = &vm.call_stack[0].locals;
+    assert_eq!(globals.get("viaOther"), Some(&JsValue::Number(2.0)));
+    assert_eq!(globals.get("fromNested"), Some(&JsValue::Number(14.0)));
+    assert_eq!(globals.get("sawTarget"), Some(&JsValue::Boolean(true)));
+    let Some(JsValue::Object(ticker)) = globals.get("ticker").cloned() else {
+        panic!("ticker should be an object");
+    };
+    assert_eq!(
+        vm.get_prop_with_proto_chain(ticker, "ticks"),
+        JsValue::Number(12.0)
+    );
+}
+
+#[test]
+fn test_compile_time_dependency_injection() {
+    use swc_ecma_parser::TsSyntax;
+    let syntax = || {
+        Some(Syntax::Typescript(TsSyntax {
+            decorators: true,
+            ..Default::default()
+        }))
+    };
+
+    let mut vm = VM::new();
+    let mut compiler = crate::compiler::Compiler::new();
+    let bytecode = compiler
+        .compile_with_syntax(
+            "@Injectable()
+             class Database { queries = 0; }
+             @Injectable()
+             class UserRepo {
+                 constructor(private db: Database) {}
+                 find(id) { this.db.queries = this.db.queries + 1; return 'user' + id; }
+             }
+             interface Clock {}
+             @Injectable({ scope: 'transient' })
+             class SystemClock {}
+             @Injectable()
+             class UserService {
+                 constructor(private repo: UserRepo, @Inject(SystemClock) clock: Clock) {
+                     this.clock = clock;
+                 }
+             }
+             let service = inject(UserService);
+             let name = service.repo.find(7);
+             let shared = inject(UserService) === service;
+             let sameDb = inject(Database) === service.repo.db;
+             let freshClock = inject(SystemClock) !== service.clock;
+             let queries = inject(Database).queries;",
+            syntax(),
+        )
+        .unwrap();
+    vm.load_program(bytecode);
+    vm.run_event_loop();
+
+    let globals = &vm.call_stack[0].locals;
+    assert_eq!(
+        globals.get("name"),
+        Some(&JsValue::String("user7".to_string()))
+    );
+    assert_eq!(globals.get("shared"), Some(&JsValue::Boolean(true)));
+    assert_eq!(globals.get("sameDb"), Some(&JsValue::Boolean(true)));
+    assert_eq!(globals.get("freshClock"), Some(&JsValue::Boolean(true)));
+    assert_eq!(globals.get("queries"), Some(&JsValue::Number(1.0)));
+
+    let err = crate::compiler::Compiler::new()
+        .compile_with_syntax(
+            "class Mailer {}
+             @Injectable() class Signup { constructor(mailer: Mailer) {} }
+             inject(Signup);",
+            syntax(),
+        )
+        .unwrap_err();
+    assert!(err.contains("cannot inject Mailer into Signup"), "{}", err);
+
+    let err = crate::compiler::Compiler::new()
+        .compile_with_syntax(
+            "@Injectable() class A { constructor(b: B) {} }
+             @Injectable() class B { constructor(a: A) {} }
+             inject(A);",
+            syntax(),
+        )
+        .unwrap_err();
+    assert!(err.contains("dependency cycle: A -> B -> A"), "{}", err);
+}
+
+#[test]
+fn test_tagged_unions_and_exhaustive_switch() {
+    let mut vm = VM::new();
+    let mut compiler = crate::compiler::Compiler::new();
+    let bytecode = compiler
+        .compile(
+            "type Shape =
+               | Circle(radius: number)
+               | Rect(number, number)
+               | Empty;
+             function area(shape) {
+                 switch (shape.tag) {
+                     case 'Circle':
+                         return 3 * shape.radius * shape.radius;
+                     case 'Rect':
+                         return shape._0 * shape._1;
+                     case 'Empty':
+                         return 0;
+                 }
+             }
+             let total = area(Circle(1)) + area(Rect(2, 3)) + area(Empty);
+             let make = Circle;
+             let viaValue = make(2).radius;
+             let empties = 0;
+             let others = 0;
+             for (let shape of [Empty, Circle(1), Empty]) {
+                 switch (shape.tag) {
+                     case 'Empty':
+                         empties = empties + 1;
+                         break;
+                     default:
+                         others = others + 1;
+                 }
+             }",
+        )
+        .unwrap();
+    vm.load_program(bytecode);
+    vm.run_event_loop();
+
+    let globals = &vm.call_stack[0].locals;
+    assert_eq!(globals.get("total"), Some(&JsValue::Number(9.0)));
+    assert_eq!(globals.get("viaValue"), Some(&JsValue::Number(2.0)));
+    assert_eq!(globals.get("empties"), Some(&JsValue::Number(2.0)));
+    assert_eq!(globals.get("others"), Some(&JsValue::Number(1.0)));
+
+    let err = crate::compiler::Compiler::new()
+        .compile(
+            "type Result<T, E> = Ok(T) | Err(E);
+             let r = Ok(1);
+             switch (r.tag) { case 'Ok': r.value; }",
+        )
+        .unwrap_err();
+    assert!(err.contains("not exhaustive: missing Err"), "{}", err);
+}
+
+#[test]
+fn test_sandbox_permissions_and_query() {
+    use crate::vm::permissions::{Capability, Permissions, PromptAnswer};
+
+    let dir = std::env::temp_dir().join(format!("oite-permissions-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("allowed.txt"), "ok").unwrap();
+
+    let mut vm = VM::new();
+    let mut permissions = Permissions::sandboxed();
+    permissions.allow(
+        Capability::Read,
+        Some(vec![dir.to_string_lossy().into_owned()]),
+    );
+    permissions.set_prompter(|request| {
+        if request.starts_with("env") {
+            PromptAnswer::AllowAll
+        } else {
+            PromptAnswer::Deny
+        }
+    });
+    vm.permissions = permissions;
+
+    let source = format!(
+        "let dir = {:?};
+         let inside = fs.readFileSync(dir + '/allowed.txt');
+         let outside = fs.writeFileSync(dir + '/denied.txt', 'no');
+         let readState = permissions.query({{ name: 'read', path: dir + '/allowed.txt' }}).state;
+         let writeState = permissions.query({{ name: 'write', path: dir }}).state;
+         let envBefore = permissions.query({{ name: 'env', variable: 'HOME' }}).state;
+         process.env.get('HOME');
+         let envAfter = permissions.query({{ name: 'env' }}).state;",
+        dir.to_string_lossy()
+    );
+    let bytecode = Codegen::new().generate(&parse_js(&source));
+    vm.load_program(bytecode);
+    vm.run_event_loop();
+
+    let globals = &vm.call_stack[0].locals;
+    let string = |s: &str| Some(JsValue::String(s.to_string()));
+    assert_eq!(globals.get("inside").cloned(), string("ok"));
+    assert_eq!(globals.get("outside"), Some(&JsValue::Boolean(false)));
+    assert!(!dir.join("denied.txt").exists());
+    assert_eq!(globals.get("readState").cloned(), string("granted"));
+    assert_eq!(globals.get("writeState").cloned(), string("prompt"));
+    assert_eq!(globals.get("envBefore").cloned(), string("prompt"));
+    assert_eq!(globals.get("envAfter").cloned(), string("granted"));
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_fs_mmap_buffers_slice_without_copying() {
+    let dir = std::env::temp_dir().join(format!("oite-mmap-vm-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("lines.txt");
+    std::fs::write(&path, "alpha\nbeta\ngamma\n").unwrap();
+
+    let mut vm = VM::new();
+    let ast = parse_js(&format!(
+        "let buf = fs.mmap({:?});
+         let len = buf.length;
+         let nl = buf.indexOf(10);
+         let second = buf.subarray(nl + 1, buf.indexOf('\\n', nl + 1));
+         let word = second.toString();
+         let first = second[0];
+         let tail = buf.subarray(-6).toString();
+         console.log(second);",
+        path.to_str().unwrap()
+    ));
+    let bytecode = Codegen::new().generate(&ast);
+    vm.captured_output = Some(String::new());
+    vm.load_program(bytecode);
+    vm.run_event_loop();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let globals = &vm.call_stack[0].locals;
+    assert_eq!(globals.get("len"), Some(&JsValue::Number(17.0)));
+    assert_eq!(globals.get("nl"), Some(&JsValue::Number(5.0)));
+    assert_eq!(globals.get("word"), Some(&JsValue::String("beta".into())));
+    assert_eq!(globals.get("first"), Some(&JsValue::Number(98.0)));
+    assert_eq!(
+        globals.get("tail"),
+        Some(&JsValue::String("gamma\n".into()))
+    );
+    assert_eq!(
+        vm.captured_output.as_deref(),
+        Some("<Buffer 62 65 74 61>\n")
+    );
+
+    // Both views share the mapping
+    let view = |name: &str| match globals.get(name) {
+        Some(JsValue::Object(ptr)) => match &vm.heap[*ptr].data {
+            crate::vm::value::HeapData::Buffer(view) => view.clone(),
+            other => panic!("{} is not a buffer: {:?}", name, other),
+        },
+        other => panic!("{} is not an object: {:?}", name, other),
+    };
+    let (buf, second) = (view("buf"), view("second"));
+    assert!(std::sync::Arc::ptr_eq(&buf.storage, &second.storage));
+    assert_eq!(second.offset, 6);
+}
+
+#[test]
+fn test_define_property_and_freeze() {
+    let mut vm = VM::new();
+    let mut compiler = crate::compiler::Compiler::new();
+    let bytecode = compiler
+        .compile(
+            "let config = { name: 'app' };
+             Object.defineProperty(config, 'id', { value: 7 });
+             Object.defineProperty(config, 'upper', {
+                 get: function () { return 'APP'; },
+                 enumerable: true,
+             });
+             let keyCount = Object.keys(config).length;
+             let id = config.id;
+             let upper = config.upper;
+             let desc = Object.getOwnPropertyDescriptor(config, 'id');
+             let readOnlyError = '';
+             try { config.id = 8; } catch (e) { readOnlyError = e; }
+             let deleted = delete config.id;
+             let o = { a: 1 };
+             Object.defineProperty(o, 'z', { value: 3, enumerable: false });
+             let json = JSON.stringify(o);
+             let hidden = {};
+             Object.defineProperty(hidden, 'z', { value: 3 });
+             let hiddenJson = JSON.stringify(hidden);
+
+             let point = Object.freeze({ x: 1 });
+             let frozen = Object.isFrozen(point);
+             let addError = '';
+             try { point.y = 2; } catch (e) { addError = e; }
+             let writeError = '';
+             try { point['x'] = 3; } catch (e) { writeError = e; }
+             let x = point.x;",
+        )
+        .unwrap();
+    vm.load_program(bytecode);
+    vm.run_event_loop();
+
+    let globals = &vm.call_stack[0].locals;
+    let get = |name: &str| globals.get(name).cloned().unwrap_or(JsValue::Undefined);
+    assert_eq!(get("keyCount"), JsValue::Number(2.0));
+    assert_eq!(get("id"), JsValue::Number(7.0));
+    assert_eq!(get("upper"), JsValue::String("APP".to_string()));
+    let JsValue::Object(desc) = get("desc") else {
+        panic!("expected a descriptor");
+    };
+    let crate::vm::value::HeapData::Object(desc) = &vm.heap[desc].data else {
+        panic!("expected a descriptor object");
+    };
+    assert_eq!(desc.get("value"), Some(&JsValue::Number(7.0)));
+    assert_eq!(desc.get("writable"), Some(&JsValue::Boolean(false)));
+    assert_eq!(desc.get("enumerable"), Some(&JsValue::Boolean(false)));
+    assert!(matches!(get("readOnlyError"), JsValue::String(e) if e.contains("read only")));
+    assert_eq!(get("deleted"), JsValue::Boolean(false));
+    // JSON.stringify skips non-enumerable properties, as Object.keys does
+    assert_eq!(get("json"), JsValue::String("{\"a\":1}".to_string()));
+    assert_eq!(get("hiddenJson"), JsValue::String("{}".to_string()));
+
+    assert_eq!(get("frozen"), JsValue::Boolean(true));
+    assert!(matches!(get("addError"), JsValue::String(e) if e.contains("not extensible")));
+    assert!(matches!(get("writeError"), JsValue::String(e) if e.contains("read only")));
+    assert_eq!(get("x"), JsValue::Number(1.0));
+}
+
+#[test]
+fn test_operator_overloading() {
+    let source = "class Vec2 {
+             constructor(x, y) { this.x = x; this.y = y; }
+             [Symbol.operator.plus](other) { return new Vec2(this.x + other.x, this.y + other.y); }
+             [Symbol.operator.times](k) { return new Vec2(this.x * k, this.y * k); }
+             [Symbol.operator.lessThan](other) { return this.x < other.x; }
+             [Symbol.operator.negate]() { return new Vec2(-this.x, -this.y); }
+         }
+         let sum = new Vec2(1, 2) + new Vec2(3, 4) * 2;
+         let x = sum.x;
+         let y = sum.y;
+         let less = new Vec2(1, 0) < new Vec2(2, 0);
+         let negX = (-sum).x;
+         let plain = 2 + 3;";
+
+    let mut vm = VM::new();
+    vm.operator_overloading = true;
+    let bytecode = crate::compiler::Compiler::new().compile(source).unwrap();
+    vm.load_program(bytecode);
+    vm.run_event_loop();
+    let globals = &vm.call_stack[0].locals;
+    assert_eq!(globals.get("x"), Some(&JsValue::Number(7.0)));
+    assert_eq!(globals.get("y"), Some(&JsValue::Number(10.0)));
+    assert_eq!(globals.get("less"), Some(&JsValue::Boolean(true)));
+    assert_eq!(globals.get("negX"), Some(&JsValue::Number(-7.0)));
+    assert_eq!(globals.get("plain"), Some(&JsValue::Number(5.0)));
+
+    // Without the flag the methods are ordinary properties
+    let mut vm = VM::new();
+    let bytecode = crate::compiler::Compiler::new().compile(source).unwrap();
+    vm.load_program(bytecode);
+    vm.run_event_loop();
+    assert_eq!(vm.call_stack[0].locals.get("x"), Some(&JsValue::Undefined));
+
+    let err = crate::compiler::Compiler::new()
+        .compile("class A { [Symbol.operator.plux](o) { return o; } }")
+        .unwrap_err();
+    assert!(err.contains("Symbol.operator.plux"), "{}", err);
+}
+
+#[test]
+fn test_decimal_arithmetic() {
+    let source = "let price = Decimal('19.99');
+         let total = price.times(3).plus(new Decimal('0.10'));
+         let text = total.toString();
+         let third = Decimal(1).div(3, 4).toString();
+         let rounded = Decimal('2.345').round(2).toString();
+         let fixed = Decimal(0.1).plus(0.2).toFixed(2);
+         let equal = Decimal('0.3').eq(Decimal(0.1).plus(0.2));
+         let isDecimal = Decimal.isDecimal(total);
+         let scale = total.scale;";
+    let mut vm = VM::new();
+    let bytecode = crate::compiler::Compiler::new().compile(source).unwrap();
+    vm.load_program(bytecode);
+    vm.run_event_loop();
+    let globals = &vm.call_stack[0].locals;
+    let string = |s: &str| Some(JsValue::String(s.to_string()));
+    assert_eq!(globals.get("text").cloned(), string("60.07"));
+    assert_eq!(globals.get("third").cloned(), string("0.3333"));
+    assert_eq!(globals.get("rounded").cloned(), string("2.35"));
+    assert_eq!(globals.get("fixed").cloned(), string("0.30"));
+    assert_eq!(globals.get("equal"), Some(&JsValue::Boolean(true)));
+    assert_eq!(globals.get("isDecimal"), Some(&JsValue::Boolean(true)));
+    assert_eq!(globals.get("scale"), Some(&JsValue::Number(2.0)));
+
+    // With operator overloading, decimals work with plain operators
+    let mut vm = VM::new();
+    vm.operator_overloading = true;
+    let bytecode = crate::compiler::Compiler::new()
+        .compile(
+            "let sum = (Decimal('0.1') + 0.2).toString();
+             let less = Decimal('0.3') < Decimal('0.31');",
+        )
+        .unwrap();
+    vm.load_program(bytecode);
+    vm.run_event_loop();
+    let globals = &vm.call_stack[0].locals;
+    assert_eq!(globals.get("sum").cloned(), string("0.3"));
+    assert_eq!(globals.get("less"), Some(&JsValue::Boolean(true)));
+}
+
+#[test]
+fn test_weak_collections() {
+    let source = "let key = { id: 1 };
+         let other = { id: 2 };
+         let cache = new WeakMap([[other, 'two']]);
+         cache.set(key, 'one');
+         let hit = cache.get(key);
+         let seeded = cache.get(other);
+         let miss = cache.has({ id: 1 });
+         let deleted = cache.delete(key);
+         let gone = cache.has(key);
+         let seen = new WeakSet();
+         seen.add(key);
+         let member = seen.has(key);
+         let ref = new WeakRef(key);
+         let same = ref.deref() === key;";
+    let mut vm = VM::new();
+    let bytecode = crate::compiler::Compiler::new().compile(source).unwrap();
+    vm.load_program(bytecode);
+    vm.run_event_loop();
+    let globals = &vm.call_stack[0].locals;
+    let string = |s: &str| Some(JsValue::String(s.to_string()));
+    assert_eq!(globals.get("hit").cloned(), string("one"));
+    assert_eq!(globals.get("seeded").cloned(), string("two"));
+    assert_eq!(globals.get("miss"), Some(&JsValue::Boolean(false)));
+    assert_eq!(globals.get("deleted"), Some(&JsValue::Boolean(true)));
+    assert_eq!(globals.get("gone"), Some(&JsValue::Boolean(false)));
+    assert_eq!(globals.get("member"), Some(&JsValue::Boolean(true)));
+    assert_eq!(globals.get("same"), Some(&JsValue::Boolean(true)));
+
+    // Private fields are stored in one WeakMap per slot
+    let private_storage = "class Counter {
+             #count = 0;
+             inc() { this.#count = this.#count + 1; return this.#count; }
+         }
+         let counter = new Counter();
+         counter.inc();
+         let count = counter.inc();";
+    let mut vm = VM::new();
+    let bytecode = crate::compiler::Compiler::new()
+        .compile(private_storage)
+        .unwrap();
+    vm.load_program(bytecode);
+    vm.run_event_loop();
+    assert_eq!(
+        vm.call_stack[0].locals.get("count"),
+        Some(&JsValue::Number(2.0))
+    );
+}
+
+#[test]
+fn test_build_env_substitution() {
+    let source = "let mode = import.meta.env.MODE;
+         let api = process.env.API_URL;
+         let missing = import.meta.env.MISSING;
+         let log = 'none';
+         if (process.env.NODE_ENV !== 'production') {
+             log = 'development only';
+         } else {
+             log = 'quiet';
+         }
+         let label = import.meta.env.DEV ? 'dev build' : 'prod build';";
+    let mut env = crate::compiler::build_env::BuildEnv::new("production");
+    env.define("API_URL=https://api.example.com").unwrap();
+    let mut compiler = crate::compiler::Compiler::new();
+    compiler.build_env = Some(env);
+    let bytecode = compiler.compile(source).unwrap();
+
+    // The development branches are not compiled at all
+    let pushes = |text: &str| {
+        bytecode
+            .iter()
+            .any(|op| matches!(op, OpCode::Push(JsValue::String(s)) if s == text))
+    };
+    assert!(!pushes("development only"));
+    assert!(!pushes("dev build"));
+
+    let mut vm = VM::new();
+    vm.load_program(bytecode);
+    vm.run_event_loop();
+    let globals = &vm.call_stack[0].locals;
+    let string = |s: &str| Some(JsValue::String(s.to_string()));
+    assert_eq!(globals.get("mode").cloned(), string("production"));
+    assert_eq!(
+        globals.get("api").cloned(),
+        string("https://api.example.com")
+    );
+    assert_eq!(globals.get("missing"), Some(&JsValue::Undefined));
+    assert_eq!(globals.get("log").cloned(), string("quiet"));
+    assert_eq!(globals.get("label").cloned(), string("prod build"));
+}
+
+#[test]
+fn test_text_encoding_and_buffers() {
+    let source = "let bytes = new TextEncoder().encode('héllo');
+         let size = bytes.length;
+         let text = new TextDecoder().decode(bytes);
+         let base64 = Buffer.from('hello world').toString('base64');
+         let hex = Buffer.from('aGk=', 'base64').toString('hex');
+         let joined = Buffer.concat([Buffer.from([104, 105]), Buffer.from('!')]).toString();
+         let lossy = new TextDecoder().decode([104, 255]);
+         let strict = new TextDecoder('utf-8', { fatal: true }).decode([104, 255]);";
+    let mut vm = VM::new();
+    let bytecode = crate::compiler::Compiler::new().compile(source).unwrap();
+    vm.load_program(bytecode);
+    vm.run_event_loop();
+    let globals = &vm.call_stack[0].locals;
+    let string = |s: &str| Some(JsValue::String(s.to_string()));
+    assert_eq!(globals.get("size"), Some(&JsValue::Number(6.0)));
+    assert_eq!(globals.get("text").cloned(), string("héllo"));
+    assert_eq!(globals.get("base64").cloned(), string("aGVsbG8gd29ybGQ="));
+    assert_eq!(globals.get("hex").cloned(), string("6869"));
+    assert_eq!(globals.get("joined").cloned(), string("hi!"));
+    assert_eq!(globals.get("lossy").cloned(), string("h\u{fffd}"));
+    assert_eq!(globals.get("strict"), Some(&JsValue::Undefined));
+}
+
+#[test]
+fn test_file_streams_deliver_chunks_through_the_event_loop() {
+    let dir = std::env::temp_dir().join(format!("oite-streams-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let input = dir.join("in.txt");
+    let output = dir.join("out.txt");
+    let copy = dir.join("copy.txt");
+    std::fs::write(&input, "héllo wörld").unwrap();
+
+    let mut vm = VM::new();
+    let ast = parse_js(&format!(
+        "let chunks = 0;
+         let text = '';
+         let ended = false;
+         let out = fs.createWriteStream({out:?});
+         fs.createReadStream({input:?}, {{ highWaterMark: 4, encoding: 'utf8' }})
+             .on('data', (chunk) => {{ chunks = chunks + 1; text = text + chunk; out.write(chunk); }})
+             .on('end', () => {{ ended = true; out.end('!'); }});
+         fs.createReadStream({input:?}).pipeTo(fs.createWriteStream({copy:?}));
+         let missing = fs.createReadStream({missing:?});",
+        input = input.to_str().unwrap(),
+        out = output.to_str().unwrap(),
+        copy = copy.to_str().unwrap(),
+        missing = dir.join("missing.txt").to_str().unwrap(),
+    ));
+    let bytecode = Codegen::new().generate(&ast);
+    vm.load_program(bytecode);
+    vm.run_event_loop();
+    let written = std::fs::read_to_string(&output).unwrap();
+    let copied = std::fs::read_to_string(&copy).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let globals = &vm.call_stack[0].locals;
+    assert_eq!(
+        globals.get("text"),
+        Some(&JsValue::String("héllo wörld".into()))
+    );
+    assert_eq!(globals.get("chunks"), Some(&JsValue::Number(4.0)));
+    assert_eq!(globals.get("ended"), Some(&JsValue::Boolean(true)));
+    assert_eq!(globals.get("missing"), Some(&JsValue::Undefined));
+    assert_eq!(written, "héllo wörld!");
+    assert_eq!(copied, "héllo wörld");
+    assert_eq!(vm.streams.open_count(), 0);
+}
+
+#[test]
+#[cfg(unix)]
+fn test_spawned_children_report_output_and_exit() {
+    let mut vm = VM::new();
+    let ast = parse_js(
+        "let exited = [];
+         let child = process.spawn('sh', ['-c', 'cat; echo oops 1>&2; exit 3'], { input: 'hi' });
+         let pid = child.pid;
+         let listener = process.spawn('sh', ['-c', 'exit 0'], { stdout: 'ignore' });
+         listener.on('exit', (code, signal) => { exited.push(code); });
+         let noStdout = listener.stdout;",
+    );
+    let bytecode = Codegen::new().generate(&ast);
+    vm.load_program(bytecode);
+    vm.run_event_loop();
+
+    let globals = vm.call_stack[0].locals.clone();
+    assert!(matches!(globals.get("pid"), Some(JsValue::Number(n)) if *n > 0.0));
+    assert_eq!(globals.get("noStdout"), Some(&JsValue::Undefined));
+    let Some(JsValue::Object(exited)) = globals.get("exited") else {
+        panic!("expected an array");
+    };
+    assert!(matches!(
+        &vm.heap[*exited].data,
+        crate::vm::value::HeapData::Array(codes) if codes[..] == [JsValue::Number(0.0)]
+    ));
+
+    let Some(JsValue::Object(child)) = globals.get("child") else {
+        panic!("expected a child process");
+    };
+    let Some(JsValue::Promise(result)) =
+        crate::stdlib::child_process::call_method(&mut vm, *child, "wait", &[])
+    else {
+        panic!("expected a promise");
+    };
+    // The promise settles from the event loop
+    vm.run_event_loop();
+    let Some(JsValue::Object(result)) = result.get_value() else {
+        panic!("expected a result object");
+    };
+    let crate::vm::value::HeapData::Object(result) = &vm.heap[result].data else {
+        panic!("expected a result object");
+    };
+    assert_eq!(result.get("code"), Some(&JsValue::Number(3.0)));
+    assert_eq!(result.get("stdout"), Some(&JsValue::String("hi".into())));
+    assert_eq!(
+        result.get("stderr"),
+        Some(&JsValue::String("oops\n".into()))
+    );
+    assert_eq!(result.get("signal"), Some(&JsValue::Null));
+}
+
+#[test]
+fn test_async_functions_resolve_their_result() {
+    let mut vm = VM::new();
+    let ast = parse_js(
+        "let got = [];
+         async function none() { got.push('ran'); }
+         async function five() { return 5; }
+         let arrow = async () => { got.push('arrow'); };
+         async function main() {
+             got.push(await none());
+             got.push(await five());
+             got.push(await arrow());
+         }
+         main();",
+    );
+    let bytecode = Codegen::new().generate(&ast);
+    vm.load_program(bytecode);
+    vm.run_event_loop();
+
+    let globals = &vm.call_stack[0].locals;
+    let Some(JsValue::Object(got)) = globals.get("got") else {
+        panic!("expected an array");
+    };
+    let crate::vm::value::HeapData::Array(got) = &vm.heap[*got].data else {
+        panic!("expected an array");
+    };
+    // A body that ends without `return` resolves with undefined
+    assert_eq!(
+        got[..],
+        [
+            JsValue::String("ran".into()),
+            JsValue::Undefined,
+            JsValue::Number(5.0),
+            JsValue::String("arrow".into()),
+            JsValue::Undefined,
+        ]
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn test_awaiting_a_child_keeps_the_event_loop_running() {
+    let mut vm = VM::new();
+    let ast = parse_js(
+        "let order = [];
+         let child = process.spawn('sh', ['-c', 'sleep 0.2; exit 4']);
+         child.on('exit', (code, signal) => { order.push('exit ' + code); });
+         setImmediate(() => { order.push('immediate'); });
+         async function run() {
+             let result = await child.wait();
+             order.push('waited ' + result.code);
+         }
+         run();",
+    );
+    let bytecode = Codegen::new().generate(&ast);
+    vm.load_program(bytecode);
+    vm.run_event_loop();
+
+    // The immediate runs while the child sleeps, and the listener
+    // registered before wait() still hears the exit
+    let globals = &vm.call_stack[0].locals;
+    let Some(JsValue::Object(order)) = globals.get("order") else {
+        panic!("expected an array");
+    };
+    let crate::vm::value::HeapData::Array(order) = &vm.heap[*order].data else {
+        panic!("expected an array");
+    };
+    assert_eq!(
+        order[..],
+        [
+            JsValue::String("immediate".into()),
+            JsValue::String("waited 4".into()),
+            JsValue::String("exit 4".into()),
+        ]
+    );
+}
+
+#[test]
+fn test_manifest_transforms_make_files_importable() {
+    let dir = std::env::temp_dir().join(format!("oite-transforms-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        dir.join("script.toml"),
+        "[transforms]\njson = \"json\"\ntxt = \"text\"\n",
+    )
+    .unwrap();
+    std::fs::write(dir.join("config.json"), r#"{"name": "app", "port": 8080}"#).unwrap();
+    std::fs::write(dir.join("banner.txt"), "hello\n").unwrap();
+    let main = dir.join("main.ot");
+    std::fs::write(&main, "").unwrap();
+
+    let mut vm = VM::new();
+    let bytecode = crate::compiler::Compiler::new()
+        .compile(
+            "import config from './config.json';
+             import banner from './banner.txt';
+             let port = config.port;
+             let text = banner;",
+        )
+        .unwrap();
+    vm.load_program_with_path(bytecode, main);
+    vm.run_event_loop();
+
+    let globals = &vm.call_stack[0].locals;
+    assert_eq!(globals.get("port"), Some(&JsValue::Number(8080.0)));
+    assert_eq!(
+        globals.get("text"),
+        Some(&JsValue::String("hello\n".to_string()))
+    );
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn test_json_modules_import_as_their_default_export() {
+    let dir = std::env::temp_dir().join(format!("oite-json-modules-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        dir.join("config.json"),
+        r#"{"name": "app", "ports": [80, 443]}"#,
+    )
+    .unwrap();
+    std::fs::write(dir.join("limits.data"), r#"{"max": 3}"#).unwrap();
+    let main = dir.join("main.ot");
+    std::fs::write(&main, "").unwrap();
+
+    let mut vm = VM::new();
+    let bytecode = crate::compiler::Compiler::new()
+        .compile(
+            r#"import config from "./config.json" with { type: "json" };
+               import plain from "./config.json";
+               import limits from "./limits.data" with { type: "json" };
+               let name = config.name;
+               let port = config.ports[1];
+               let same = plain === config;
+               let max = limits.max;"#,
+        )
+        .unwrap();
+    vm.load_program_with_path(bytecode, main);
+    vm.run_event_loop();
+
+    let globals = &vm.call_stack[0].locals;
+    assert_eq!(globals.get("name"), Some(&JsValue::String("app".into())));
+    assert_eq!(globals.get("port"), Some(&JsValue::Number(443.0)));
+    assert_eq!(globals.get("same"), Some(&JsValue::Boolean(true)));
+    assert_eq!(globals.get("max"), Some(&JsValue::Number(3.0)));
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn test_process_env_reads_and_writes_the_host_environment() {
+    let name = format!("OITE_TEST_ENV_{}", std::process::id());
+    unsafe {
+        std::env::set_var(&name, "from host");
+    }
+
+    let mut vm = VM::new();
+    let ast = parse_js(&format!(
+        "let before = process.env.{name};
+         process.env.{name}_NEW = 42;
+         let computed = process.env['{name}_NEW'];
+         let listed = Object.keys(process.env).includes('{name}_NEW');
+         let removed = delete process.env.{name};
+         let after = process.env.{name};
+         let viaMethod = process.env.get('{name}_NEW');
+         let membership = ['{name}_NEW' in process.env, process.env.hasOwnProperty('{name}_NEW'),
+                           '{name}' in process.env, process.env.hasOwnProperty('{name}'),
+                           'get' in process.env].join();"
+    ));
+    let bytecode = Codegen::new().generate(&ast);
+    vm.load_program(bytecode);
+    vm.run_event_loop();
+    let added = std::env::var(format!("{}_NEW", name));
+    let still_set = std::env::var(&name).is_ok();
+    unsafe {
+        std::env::remove_var(format!("{}_NEW", name));
+    }
+
+    let globals = &vm.call_stack[0].locals;
+    assert_eq!(
+        globals.get("before"),
+        Some(&JsValue::String("from host".into()))
+    );
+    assert_eq!(globals.get("computed"), Some(&JsValue::String("42".into())));
+    assert_eq!(globals.get("listed"), Some(&JsValue::Boolean(true)));
+    assert_eq!(globals.get("removed"), Some(&JsValue::Boolean(true)));
+    assert_eq!(globals.get("after"), Some(&JsValue::Undefined));
+    assert_eq!(
+        globals.get("viaMethod"),
+        Some(&JsValue::String("42".into()))
+    );
+    assert_eq!(
+        globals.get("membership"),
+        Some(&JsValue::String("true,true,false,false,true".into()))
+    );
+    assert_eq!(added.as_deref(), Ok("42"));
+    assert!(!still_set);
+}
+
+#[test]
+fn test_argument_count_mismatch_keeps_bindings_in_place() {
+    let mut vm = VM::new();
+    let ast = parse_js(
+        "function pair(a, b) { return [a, b]; }
+         let few = pair(1);
+         let many = pair(1, 2, 3);
+         let outer = 7;
+         let kept = outer;
+         function point({ x, y }, [first], scale = 10, ...rest) {
+             return (x + y) * scale + first + rest.length;
+         }
+         let shaped = point({ x: 1, y: 2 }, [4], undefined, 'a', 'b');
+         let arrow = ((p, q) => q)(5);
+         function scaled(n, by = 2) { return n * by; }
+         let unscaled = scaled();
+         function call(pair) { return pair(1); }",
+    );
+    let mut cg = Codegen::new();
+    let bytecode = cg.generate(&ast);
+    // Calls to a declaration in scope that pass too few or too many
+    // arguments are reported; a rest parameter takes any number, and a
+    // parameter named `pair` is not the declaration
+    assert_eq!(
+        cg.warnings,
+        [
+            "Warning: 'pair' takes 2 arguments but is called with 1",
+            "Warning: 'pair' takes 2 arguments but is called with 3",
+            "Warning: 'scaled' takes at least 1 argument but is called with 0",
+        ]
+    );
+    vm.load_program(bytecode);
+    vm.run_event_loop();
+
+    let globals = vm.call_stack[0].locals.clone();
+    let items = |name: &str| match globals.get(name) {
+        Some(JsValue::Object(ptr)) => match &vm.heap[*ptr].data {
+            crate::vm::value::HeapData::Array(items) => items.clone(),
+            other => panic!("{} is not an array: {:?}", name, other),
+        },
+        other => panic!("{} is not an object: {:?}", name, other),
+    };
+    assert_eq!(items("few"), [JsValue::Number(1.0), JsValue::Undefined]);
+    assert_eq!(items("many"), [JsValue::Number(1.0), JsValue::Number(2.0)]);
+    assert_eq!(globals.get("kept"), Some(&JsValue::Number(7.0)));
+    assert_eq!(globals.get("shaped"), Some(&JsValue::Number(36.0)));
+    assert_eq!(globals.get("arrow"), Some(&JsValue::Undefined));
+}
+
+#[test]
+fn test_natives_call_back_into_script() {
+    let mut vm = VM::new();
+    let ast = parse_js(
+        "let seen = [];
+         let doubled = [3, 1, 2].map((n, i) => n * 2 + i);
+         let odd = [1, 2, 3, 4, 5].filter((n) => n % 2);
+         [10, 20].forEach((n) => seen.push(n));
+         let desc = [1, 10, 2, 5].sort((a, b) => b - a);
+         let byString = [10, 9, undefined, 1].sort();
+         function total(list) { let acc = { sum: 0 }; list.forEach((n) => { acc.sum = acc.sum + n; }); return acc.sum; }
+         let after = total([1, 2, 3]) + 1;
+         let caught = '';
+         try { [1, 2].map((n) => { if (n == 2) { throw 'bad ' + n; } return n; }); } catch (e) { caught = e; }
+         let resumed = 'yes';",
+    );
+    let bytecode = Codegen::new().generate(&ast);
+    vm.load_program(bytecode);
+    vm.run_event_loop();
+
+    let globals = vm.call_stack[0].locals.clone();
+    let numbers = |name: &str| -> Vec<JsValue> {
+        match globals.get(name) {
+            Some(JsValue::Object(ptr)) => match &vm.heap[*ptr].data {
+                crate::vm::value::HeapData::Array(items) => items.clone(),
+                other => panic!("{} is not an array: {:?}", name, other),
+            },
+            other => panic!("{} is not an object: {:?}", name, other),
+        }
+    };
+    let n = JsValue::Number;
+    assert_eq!(numbers("doubled"), [n(6.0), n(3.0), n(6.0)]);
+    assert_eq!(numbers("odd"), [n(1.0), n(3.0), n(5.0)]);
+    assert_eq!(numbers("seen"), [n(10.0), n(20.0)]);
+    assert_eq!(numbers("desc"), [n(10.0), n(5.0), n(2.0), n(1.0)]);
+    assert_eq!(
+        numbers("byString"),
+        [n(1.0), n(10.0), n(9.0), JsValue::Undefined]
+    );
+    assert_eq!(globals.get("after"), Some(&n(7.0)));
+    assert_eq!(
+        globals.get("caught"),
+        Some(&JsValue::String("bad 2".into()))
+    );
+    assert_eq!(globals.get("resumed"), Some(&JsValue::String("yes".into())));
+    assert!(vm.exception_handlers.is_empty());
+}
+
+#[test]
+fn test_string_methods_count_utf16_units() {
+    let mut vm = VM::new();
+    let ast = parse_js(
+        "let s = 'héllo😀!';
+         let len = s.length;
+         let third = s[1] + s.charAt(4);
+         let code = s.charCodeAt(5);
+         let point = s.codePointAt(5);
+         let last = s.at(-1) + s.at(-3).length;
+         let part = s.slice(1, -3) + '|' + s.substring(5, 7);
+         let found = s.indexOf('!') + s.lastIndexOf('l');
+         let replaced = 'a-b-c'.replaceAll('-', '+');
+         let nfc = 'e\u{301}'.normalize() == '\u{e9}';
+         let order = 'a'.localeCompare('B') + ',' + 'b'.localeCompare('a') + ',' + 'é'.localeCompare('e\u{301}');
+         let form = '';
+         try { 'x'.normalize('nfc'); } catch (e) { form = e; }",
+    );
+    let bytecode = Codegen::new().generate(&ast);
+    vm.load_program(bytecode);
+    vm.run_event_loop();
+
+    let globals = &vm.call_stack[0].locals;
+    let string = |value: &str| Some(JsValue::String(value.to_string()));
+    assert_eq!(globals.get("len").cloned(), Some(JsValue::Number(8.0)));
+    assert_eq!(globals.get("third").cloned(), string("éo"));
+    assert_eq!(globals.get("code").cloned(), Some(JsValue::Number(55357.0)));
+    assert_eq!(
+        globals.get("point").cloned(),
+        Some(JsValue::Number(128512.0))
+    );
+    assert_eq!(globals.get("last").cloned(), string("!1"));
+    assert_eq!(globals.get("part").cloned(), string("éllo|😀"));
+    assert_eq!(globals.get("found").cloned(), Some(JsValue::Number(10.0)));
+    assert_eq!(globals.get("replaced").cloned(), string("a+b+c"));
+    assert_eq!(globals.get("nfc").cloned(), Some(JsValue::Boolean(true)));
+    assert_eq!(globals.get("order").cloned(), string("-1,1,0"));
+    assert!(matches!(globals.get("form"), Some(JsValue::String(e)) if e.starts_with("RangeError")));
+}
+
+#[test]
+fn test_array_global() {
+    let mut vm = VM::new();
+    let ast = parse_js(
+        "let holes = new Array(3);
+         let holeCount = holes.length;
+         let listed = JSON.stringify(Array(1, 2)) + JSON.stringify(new Array('3'));
+         let checks = [Array.isArray([]), Array.isArray({ length: 0 }), Array.isArray('ab')].join();
+         let fromString = Array.from('a😀').join('|');
+         let fromLike = JSON.stringify(Array.from({ length: 3, 0: 'x', 2: 'z' }));
+         let m = new Map(); m.set('k', 1);
+         let fromMap = JSON.stringify(Array.from(m));
+         let mapped = Array.from([1, 2, 3], (x, i) => x * 10 + i).join();
+         let of = JSON.stringify(Array.of(7));
+         let bad = '';
+         try { new Array(-1); } catch (e) { bad = e; }
+         let notFn = '';
+         try { Array.from([1], 5); } catch (e) { notFn = e; }",
+    );
+    let bytecode = Codegen::new().generate(&ast);
+    vm.load_program(bytecode);
+    vm.run_event_loop();
+
+    let globals = &vm.call_stack[0].locals;
+    let string = |value: &str| Some(JsValue::String(value.to_string()));
+    assert_eq!(
+        globals.get("holeCount").cloned(),
+        Some(JsValue::Number(3.0))
+    );
+    assert_eq!(globals.get("listed").cloned(), string("[1,2][\"3\"]"));
+    assert_eq!(globals.get("checks").cloned(), string("true,false,false"));
+    assert_eq!(globals.get("fromString").cloned(), string("a|😀"));
+    assert_eq!(
+        globals.get("fromLike").cloned(),
+        string("[\"x\",null,\"z\"]")
+    );
+    assert_eq!(globals.get("fromMap").cloned(), string("[[\"k\",1]]"));
+    assert_eq!(globals.get("mapped").cloned(), string("10,21,32"));
+    assert_eq!(globals.get("of").cloned(), string("[7]"));
+    assert!(matches!(globals.get("bad"), Some(JsValue::String(e)) if e.starts_with("RangeError")));
+    assert!(matches!(globals.get("notFn"), Some(JsValue::String(e)) if e.starts_with("TypeError")));
+}
+
+#[test]
+fn test_equality_semantics() {
+    let mut vm = VM::new();
+    let ast = parse_js(
+        "let nan = NaN;
+         let strict = [nan === nan, nan !== nan, 0 === -0, 'a' === 'a', null === undefined].join();
+         let same = [Object.is(nan, nan), Object.is(0, -0), Object.is(-0, -0), Object.is('a', 'a')].join();
+         let arrays = [[nan].includes(nan), [nan].indexOf(nan), [-0].includes(0), [0].indexOf(-0)].join();
+         let m = new Map();
+         m.set(nan, 'nan');
+         m.set(-0, 'zero');
+         m.set('x', 1);
+         m.set(nan, 'again');
+         let keys = [m.get(nan), m.has(0), m.size, Array.from(m, (e) => e[0]).join('|')].join();
+         let s = new Set();
+         s.add(nan); s.add(nan); s.add(0); s.add(-0);
+         let members = s.size;
+         let a = {};
+         let f = () => 1;
+         m.set(f, 'fn');
+         let identity = [a === a, {} === {}, [] === [], f === f, m.get(f), m.get(() => 1)].join();",
+    );
+    let bytecode = Codegen::new().generate(&ast);
+    vm.load_program(bytecode);
+    vm.run_event_loop();
+
+    let globals = &vm.call_stack[0].locals;
+    let string = |value: &str| Some(JsValue::String(value.to_string()));
+    assert_eq!(
+        globals.get("strict").cloned(),
+        string("false,true,true,true,false")
+    );
+    assert_eq!(globals.get("same").cloned(), string("true,false,true,true"));
+    assert_eq!(globals.get("arrays").cloned(), string("true,-1,true,0"));
+    assert_eq!(globals.get("keys").cloned(), string("again,true,3,NaN|0|x"));
+    assert_eq!(globals.get("members").cloned(), Some(JsValue::Number(2.0)));
+    assert_eq!(
+        globals.get("identity").cloned(),
+        string("true,false,false,true,fn,")
+    );
+}
+
+#[test]
+fn test_number_formatting() {
+    let mut vm = VM::new();
+    let ast = parse_js(
+        "let sum = String(0.1 + 0.2) + ' ' + (1e21).toString() + ' ' + String(1e-7);
+         let fixed = [(1.005).toFixed(2), (2.5).toFixed(0), (-0.0001).toFixed(2), (1e21).toFixed(2), (12).toFixed()].join();
+         let precise = [(123.456).toPrecision(4), (0.0000001234).toPrecision(2), (123456).toPrecision(2), (7).toPrecision()].join();
+         let radix = [(255).toString(16), (0.5).toString(2), (-8).toString(8)].join();
+         let parsed = [parseInt('123456789012345678901234567890'), parseInt('0x1F'), parseInt('z', 36), parseInt('10', 4294967298), parseInt('7', 1)].join();
+         let floats = [parseFloat('.5e1x'), parseFloat('-Infinityx'), parseFloat('1e1000')].join();
+         let badFixed = '';
+         try { (1).toFixed(101); } catch (e) { badFixed = e; }
+         let badRadix = '';
+         try { (1).toString(1); } catch (e) { badRadix = e; }",
+    );
+    let bytecode = Codegen::new().generate(&ast);
+    vm.load_program(bytecode);
+    vm.run_event_loop();
+
+    let globals = &vm.call_stack[0].locals;
+    let string = |value: &str| Some(JsValue::String(value.to_string()));
+    assert_eq!(
+        globals.get("sum").cloned(),
+        string("0.30000000000000004 1e+21 1e-7")
+    );
+    assert_eq!(
+        globals.get("fixed").cloned(),
+        string("1.00,3,-0.00,1e+21,12")
+    );
+    assert_eq!(
+        globals.get("precise").cloned(),
+        string("123.5,1.2e-7,1.2e+5,7")
+    );
+    assert_eq!(globals.get("radix").cloned(), string("ff,0.1,-10"));
+    assert_eq!(
+        globals.get("parsed").cloned(),
+        string("1.2345678901234568e+29,31,35,2,NaN")
+    );
+    assert_eq!(
+        globals.get("floats").cloned(),
+        string("5,-Infinity,Infinity")
+    );
+    assert!(
+        matches!(globals.get("badFixed"), Some(JsValue::String(e)) if e.starts_with("RangeError"))
+    );
+    assert!(
+        matches!(globals.get("badRadix"), Some(JsValue::String(e)) if e.starts_with("RangeError"))
+    );
+}
+
+#[test]
+fn test_assignment_operators() {
+    let mut vm = VM::new();
+    let ast = parse_js(
+        "let x = 5;
+         let a = x++;
+         let b = ++x;
+         let o = { n: 1, arr: [10, 20] };
+         let log = [];
+         function key() { log.push('k'); return 1; }
+         let c = o.n++;
+         let d = ++o.n;
+         o.n += 10;
+         o.arr[key()] += 5;
+         let e = (o.n -= 3);
+         let p = 0; p ||= 7;
+         let q = 1; q &&= 9;
+         let r = null; r ??= 4;
+         let blank = ''; blank ||= 'filled';
+         let s = 5; s ??= key();
+         o.label ??= 'set';
+         o.label ||= key();
+         let y;
+         let z = y = 4;
+         o.arr[0] = o.n = 2;
+         let t = (1, 2, 3);
+         let sum = 0;
+         for (let i = 0, j = 10; i < 3; i++, j--) { sum += i * j; }
+         class C { set v(val) { this.raw = val * 2; } }
+         let inst = new C();
+         let sv = (inst.v = 5);
+         inst.v = 6;
+         class P { #n = 1; bump() { this.#n += 2; return this.#n++; } }
+         let pb = new P();
+         let bumped = [pb.bump(), pb.bump()].join('/');
+         let out = [a, b, x, c, d, e, o.n, o.arr.join('|'), log.length, p, q, r, s, o.label,
+                    z, y, t, sum, sv, inst.raw].join();",
+    );
+    let bytecode = Codegen::new().generate(&ast);
+    vm.load_program(bytecode);
+    vm.run_event_loop();
+
+    let globals = &vm.call_stack[0].locals;
+    assert_eq!(
+        globals.get("out").cloned(),
+        Some(JsValue::String(
+            "5,7,7,1,3,10,2,2|25,1,7,9,4,5,set,4,4,3,25,5,12".to_string()
+        ))
+    );
+    assert_eq!(
+        globals.get("blank").cloned(),
+        Some(JsValue::String("filled".to_string()))
+    );
+    assert_eq!(
+        globals.get("bumped").cloned(),
+        Some(JsValue::String("3/6".to_string()))
+    );
+}
+
+#[test]
+fn test_in_operator_and_has_own_property() {
+    let mut vm = VM::new();
+    let ast = parse_js(
+        "class Base { greet() { return 1; } }
+         class Kid extends Base { constructor() { super(); this.own = 1; } get g() { return 2; } }
+         let k = new Kid();
+         let dict = { a: 1, 2: 'two', u: undefined };
+         let arr = [10, 20];
+         let found = ['a' in dict, 2 in dict, 'u' in dict, 'b' in dict, 'own' in k, 'greet' in k, 'g' in k, 'x' in k,
+                      0 in arr, 2 in arr, 'length' in arr, 'push' in arr].join();
+         let own = [dict.hasOwnProperty('a'), dict.hasOwnProperty('greet'), k.hasOwnProperty('own'), k.hasOwnProperty('greet'),
+                    arr.hasOwnProperty(1), arr.hasOwnProperty('01'), arr.hasOwnProperty(2), 'abc'.hasOwnProperty(2),
+                    Object.hasOwn(dict, 2), Object.hasOwn(dict, 'u')].join();
+         let bad = '';
+         try { 'x' in 'str'; } catch (e) { bad = e; }",
+    );
+    let bytecode = Codegen::new().generate(&ast);
+    vm.load_program(bytecode);
+    vm.run_event_loop();
+
+    let globals = &vm.call_stack[0].locals;
+    let string = |value: &str| Some(JsValue::String(value.to_string()));
+    assert_eq!(
+        globals.get("found").cloned(),
+        string("true,true,true,false,true,true,true,false,true,false,true,true")
+    );
+    assert_eq!(
+        globals.get("own").cloned(),
+        string("true,false,true,false,true,false,false,true,true,true")
+    );
+    assert_eq!(
+        globals.get("bad").cloned(),
+        string("TypeError: Cannot use 'in' operator to search for 'x' in str")
+    );
+}
+
+#[test]
+fn test_each_function_creation_has_its_own_identity() {
+    let mut vm = VM::new();
+    let ast = parse_js(
+        "function mk() { return () => 1; }
+         function counter() { let n = 0; return () => n; }
+         let f = mk();
+         let g = f;
+         let bound = f.bind(null);
+         let result = [mk() === mk(), f === g, counter() === counter(), bound === f, bound === bound, Object.is(mk(), mk())].join();",
+    );
+    let bytecode = Codegen::new().generate(&ast);
+    vm.load_program(bytecode);
+    vm.run_event_loop();
+
+    let globals = &vm.call_stack[0].locals;
+    assert_eq!(
+        globals.get("result"),
+        Some(&JsValue::String("false,true,false,false,true,false".into()))
+    );
+}
+
+#[test]
+fn test_match_all_with_a_string_pattern() {
+    let mut vm = VM::new();
+    let ast = parse_js(
+        "let found = [];
+         for (const m of 'a😀a-aa'.matchAll('a')) {
+             found.push(m[0] + '@' + m.index);
+         }
+         let listed = found.join();
+         let indices = [...'a-b-a'.matchAll('a')].map((m) => m.index).join();
+         let input = 'xyx'.matchAll('x')[1].input;
+         let none = 'abc'.matchAll('z').length;
+         let error = '';
+         try { 'abc'.matchAll(1); } catch (e) { error = e; }",
+    );
+    let bytecode = Codegen::new().generate(&ast);
+    vm.load_program(bytecode);
+    vm.run_event_loop();
+
+    let globals = &vm.call_stack[0].locals;
+    let string = |value: &str| Some(JsValue::String(value.to_string()));
+    assert_eq!(globals.get("listed").cloned(), string("a@0,a@3,a@5,a@6"));
+    assert_eq!(globals.get("indices").cloned(), string("0,4"));
+    assert_eq!(globals.get("input").cloned(), string("xyx"));
+    assert_eq!(globals.get("none").cloned(), Some(JsValue::Number(0.0)));
+    assert!(matches!(globals.get("error"), Some(JsValue::String(e)) if e.starts_with("TypeError")));
+}
+
+fn native_test_join(_vm: &mut VM, args: Vec<JsValue>) -> JsValue {
+    let parts: Vec<String> = args
+        .iter()
+        .map(|arg| match arg {
+            JsValue::Number(n) => crate::stdlib::number_to_string(*n),
+            JsValue::String(s) => s.clone(),
+            _ => "?".to_string(),
+        })
+        .collect();
+    JsValue::String(parts.join(","))
+}
+
+#[test]
+fn test_natives_receive_arguments_in_call_order() {
+    use crate::vm::value::{HeapData, HeapObject};
+    use std::collections::HashMap;
+
+    let mut vm = VM::new();
+    let join = JsValue::NativeFunction(vm.register_native(native_test_join));
+    // A callable object, the way String and Number are
+    let mut props = HashMap::new();
+    props.insert("__call__".to_string(), join.clone());
+    let callable = vm.heap.len();
+    vm.heap.push(HeapObject {
+        data: HeapData::Object(props),
+    });
+    let globals = &mut vm.call_stack[0].locals;
+    globals.insert("join".into(), join);
+    globals.insert("callable".into(), JsValue::Object(callable));
+
+    let ast = parse_js(
+        "let a = join(1, 2, 3); let alias = join; let b = alias('x', 'y'); \
+         let c = callable(4, 5, 6); let d = { f: join }.f(7, 8); \
+         let e = parseInt('z', 36);",
+    );
+    let mut cg = Codegen::new();
+    let bytecode = cg.generate(&ast);
+    vm.load_program(bytecode);
+    vm.run_event_loop();
+
+    let globals = &vm.call_stack[0].locals;
+    assert_eq!(
+        globals.get("a"),
+        Some(&JsValue::String("1,2,3".to_string()))
+    );
+    assert_eq!(globals.get("b"), Some(&JsValue::String("x,y".to_string())));
+    assert_eq!(
+        globals.get("c"),
+        Some(&JsValue::String("4,5,6".to_string()))
+    );
+    assert_eq!(globals.get("d"), Some(&JsValue::String("7,8".to_string())));
+    assert_eq!(globals.get("e"), Some(&JsValue::Number(35.0)));
+}
+
+#[test]
+fn test_math_and_std_intrinsics() {
+    let mut vm = VM::new();
+    let bytecode = crate::compiler::Compiler::new()
+        .compile(
+            "const bits = std.intrinsics;
+             const x = -1.5;
+             console.log(Math.clz32(1), Math.clz32('8'), Math.imul(0xffffffff, 5), Math.imul(3));
+             console.log(Math.fround(5.5), Math.sqrt(16), Math.abs(x), Math.floor(x), Math.ceil(x), Math.trunc(x));
+             console.log(bits.popcnt32(255), bits.ctz32(8), bits.f32ToBits(1), bits.f32FromBits(1065353216));
+             console.log(bits.f64FromBits(bits.f64HighBits(x), bits.f64LowBits(x)) === x);",
+        )
+        .unwrap();
+    vm.captured_output = Some(String::new());
+    vm.load_program(bytecode);
+    vm.run_event_loop();
+
+    assert_eq!(
+        vm.captured_output.as_deref(),
+        Some("31 28 -5 0\n5.5 4 1.5 -2 -1 -1\n8 3 1065353216 1\ntrue\n")
+    );
+}
+
+#[test]
+fn test_std_simd_vectors() {
+    let mut vm = VM::new();
+    let bytecode = crate::compiler::Compiler::new()
+        .compile(
+            "const f = std.simd.f64x2;
+             const a = std.simd.f64x2(1.5, 2);
+             const b = f.mul(f.add(a, [0.5, 1]), f(10, 100));
+             console.log(b[0], b[1], f.reduceAdd(b), f.shuffle(a, b, 3, 0).join());
+             const i = std.simd.i32x4;
+             console.log(i.mul(i(2147483647, 3, -4, 65536), i(1, 2, 3, 65536)).join());
+             console.log(std.simd.f32x4.reduceAdd(std.simd.f32x4(0.1, 0.2, 0.3, 0.4)) === Math.fround(1.0000000149011612));
+             try { f.add(a, [1, 2, 3]); } catch (e) { console.log(e); }
+             try { f.shuffle(a, b, 0, 4); } catch (e) { console.log(e); }",
+        )
+        .unwrap();
+    vm.captured_output = Some(String::new());
+    vm.load_program(bytecode);
+    vm.run_event_loop();
+
+    assert_eq!(
+        vm.captured_output.as_deref(),
+        Some(
+            "20 300 320 300,1.5\n2147483647,6,-12,0\ntrue\n\
+             TypeError: expected an f64x2, an array of 2 numbers\n\
+             TypeError: f64x2.shuffle takes 2 lane indices from 0 to 3\n"
+        )
+    );
+}
+
+#[test]
+fn test_bootstrap_compiler_output_runs_on_the_vm() {
+    let source = "function fib(n) {
+             if (n < 2) { return n; }
+             return fib(n - 1) + fib(n - 2);
+         }
+         let total = 0;
+         let i = 0;
+         while (i < 5) {
+             total = total + i;
+             i = i + 1;
+         }
+         let point = { x: 3, y: 0 };
+         let items = [1, 2, 3];
+         items.push(point.x * point.y);
+         let key = \"y\";
+         point[key] = point.x + 1;
+         let square = function (v) { return v * v; };
+         let diff = function (a, b) { return a - b; };
+         console.log(total, fib(10), items.length, items[3], point.y, square(9));
+         console.log(-point.x, !false, ~5, 2 ** 10, 7 % 3, 1 < 2, \"a\" + \"b\", diff(9, 4));";
+
+    let bootstrap = crate::compile_with_bootstrap(source).unwrap();
+    // Parameters are read from the argument window
+    for index in [0, 1] {
+        assert!(
+            bootstrap
+                .iter()
+                .any(|op| matches!(op, OpCode::LoadArg(i) if *i == index))
+        );
+    }
+    let rust = crate::driver::CompilationDriver::new("parity.ot")
+        .compile("parity.ot", source)
+        .unwrap();
+    let expected = "10 55 4 0 4 81\n-3 true -6 1024 1 true ab 5\n";
+    assert_eq!(crate::run_captured(rust, "parity.ot"), expected);
+    assert_eq!(crate::run_captured(bootstrap, "parity.ot"), expected);
+}
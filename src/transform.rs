@@ -0,0 +1,229 @@
+//! Source transformers (`[transforms]` in `script.toml`)
+//!
+//! A transformer turns a file that isn't TypeScript into module source
+//! before it is parsed, so it can be imported like any other module:
+//!
+//! ```toml
+//! [transforms]
+//! json = "json"                        # export default <the JSON value>
+//! toml = "toml"                        # export default <the table as an object>
+//! txt = "text"                         # export default "<file contents>"
+//! svelte = ["node", "tools/sfc.js"]    # a command printing module source
+//! ```
+//!
+//! A command is run from the manifest's directory with the file's path as its
+//! last argument and the file's contents on stdin. Whatever it writes to
+//! stdout is the module; a non-zero exit fails the import with its stderr.
+//!
+//! `.json` files are JSON unless the manifest says otherwise, and an import
+//! with `with { type: "json" }` reads its file as JSON whatever the extension.
+
+use crate::manifest::Manifest;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Transformer {
+    Json,
+    Toml,
+    Text,
+    /// Program and arguments; the file path is appended
+    Command(Vec<String>),
+}
+
+impl Transformer {
+    /// Parse a `[transforms]` value: a built-in name or a command array
+    pub fn parse(value: &toml::Value) -> Result<Self, String> {
+        match value {
+            toml::Value::String(name) => match name.as_str() {
+                "json" => Ok(Transformer::Json),
+                "toml" => Ok(Transformer::Toml),
+                "text" => Ok(Transformer::Text),
+                _ => Err(format!(
+                    "unknown transformer '{}' (expected json, toml, text or a command array)",
+                    name
+                )),
+            },
+            toml::Value::Array(items) => {
+                let argv = items
+                    .iter()
+                    .map(|item| item.as_str().map(str::to_string))
+                    .collect::<Option<Vec<_>>>()
+                    .filter(|argv| !argv.is_empty())
+                    .ok_or("a transform command must be a non-empty array of strings")?;
+                Ok(Transformer::Command(argv))
+            }
+            _ => Err("a transform must be a name or a command array".to_string()),
+        }
+    }
+
+    /// Module source for `source`, the contents of `path`. Commands run in
+    /// `dir`.
+    pub fn apply(&self, path: &Path, source: &str, dir: Option<&Path>) -> Result<String, String> {
+        match self {
+            Transformer::Json => {
+                let value: serde_json::Value =
+                    serde_json::from_str(source).map_err(|e| format!("invalid JSON: {}", e))?;
+                Ok(default_export(&value))
+            }
+            Transformer::Toml => {
+                let table: toml::Table = source
+                    .parse()
+                    .map_err(|e: toml::de::Error| format!("invalid TOML: {}", e))?;
+                Ok(default_export(&toml_to_json(toml::Value::Table(table))))
+            }
+            Transformer::Text => Ok(default_export(&serde_json::Value::String(
+                source.to_string(),
+            ))),
+            Transformer::Command(argv) => run_command(argv, path, source, dir),
+        }
+    }
+}
+
+fn default_export(value: &serde_json::Value) -> String {
+    format!("export default {};\n", value)
+}
+
+/// TOML values as JSON; dates and times become their TOML text
+fn toml_to_json(value: toml::Value) -> serde_json::Value {
+    use serde_json::Value;
+    match value {
+        toml::Value::String(s) => Value::String(s),
+        toml::Value::Integer(n) => Value::from(n),
+        toml::Value::Float(n) => Value::from(n),
+        toml::Value::Boolean(b) => Value::Bool(b),
+        toml::Value::Datetime(d) => Value::String(d.to_string()),
+        toml::Value::Array(items) => Value::Array(items.into_iter().map(toml_to_json).collect()),
+        toml::Value::Table(table) => Value::Object(
+            table
+                .into_iter()
+                .map(|(key, value)| (key, toml_to_json(value)))
+                .collect(),
+        ),
+    }
+}
+
+fn run_command(
+    argv: &[String],
+    path: &Path,
+    source: &str,
+    dir: Option<&Path>,
+) -> Result<String, String> {
+    let mut command = Command::new(&argv[0]);
+    command
+        .args(&argv[1..])
+        .arg(path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    if let Some(dir) = dir {
+        command.current_dir(dir);
+    }
+    let mut child = command
+        .spawn()
+        .map_err(|e| format!("failed to run transformer '{}': {}", argv[0], e))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // A transformer that only reads the path may close stdin early
+        let _ = stdin.write_all(source.as_bytes());
+    }
+    let output = child
+        .wait_with_output()
+        .map_err(|e| format!("transformer '{}' failed: {}", argv[0], e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "transformer '{}' failed ({}): {}",
+            argv[0],
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    String::from_utf8(output.stdout)
+        .map_err(|_| format!("transformer '{}' printed invalid UTF-8", argv[0]))
+}
+
+/// `source` after the transformer the governing manifest configures for
+/// `path`'s extension, if any
+pub fn transform_source(path: &Path, source: String) -> Result<String, String> {
+    let Some(ext) = path.extension().and_then(|ext| ext.to_str()) else {
+        return Ok(source);
+    };
+    let manifest = Manifest::discover_or_default(path);
+    match manifest.transforms.get(ext) {
+        Some(transformer) => {
+            let dir = manifest.path.as_deref().and_then(Path::parent);
+            transformer
+                .apply(path, &source, dir)
+                .map_err(|e| format!("{}: {}", path.display(), e))
+        }
+        None => Ok(source),
+    }
+}
+
+/// The module an import of `path` sees: `source` parsed as JSON when the
+/// import asks for it, else after `transform_source`
+pub fn transform_import(path: &Path, source: String, json: bool) -> Result<String, String> {
+    if json {
+        Transformer::Json
+            .apply(path, &source, None)
+            .map_err(|e| format!("{}: {}", path.display(), e))
+    } else {
+        transform_source(path, source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_transformers() {
+        let path = Path::new("data.json");
+        let json = Transformer::Json.apply(path, r#"{"a": [1, 2]}"#, None);
+        assert_eq!(json.unwrap(), "export default {\"a\":[1,2]};\n");
+        assert!(Transformer::Json.apply(path, "{", None).is_err());
+
+        let toml = Transformer::Toml.apply(path, "name = \"x\"\n[db]\nport = 5432\n", None);
+        assert_eq!(
+            toml.unwrap(),
+            "export default {\"db\":{\"port\":5432},\"name\":\"x\"};\n"
+        );
+
+        let text = Transformer::Text.apply(path, "a \"b\"\n", None);
+        assert_eq!(text.unwrap(), "export default \"a \\\"b\\\"\\n\";\n");
+    }
+
+    #[test]
+    fn test_parse_transformer() {
+        let parse = |s: &str| {
+            let table: toml::Table = format!("t = {}", s).parse().unwrap();
+            Transformer::parse(&table["t"])
+        };
+        assert_eq!(parse("\"json\""), Ok(Transformer::Json));
+        assert_eq!(
+            parse("[\"node\", \"sfc.js\"]"),
+            Ok(Transformer::Command(vec!["node".into(), "sfc.js".into()]))
+        );
+        assert!(parse("\"yaml\"").is_err());
+        assert!(parse("[]").is_err());
+    }
+
+    #[test]
+    fn test_json_imports() {
+        let dir = std::env::temp_dir().join(format!("oite_transform_json_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let config = dir.join("config.json");
+        let data = dir.join("data.ot");
+
+        let json = transform_source(&config, "[1, 2]".to_string());
+        assert_eq!(json.unwrap(), "export default [1,2];\n");
+        let plain = transform_source(&data, "[1, 2]".to_string());
+        assert_eq!(plain.unwrap(), "[1, 2]");
+        let attributed = transform_import(&data, "{\"on\": true}".to_string(), true);
+        assert_eq!(attributed.unwrap(), "export default {\"on\":true};\n");
+        let invalid = transform_import(&data, "export default 1;".to_string(), true);
+        assert!(invalid.unwrap_err().contains("data.ot"));
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
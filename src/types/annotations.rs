@@ -0,0 +1,2225 @@
+//! Annotation enforcement for `oitec check` and `oitec build`
+//!
+//! Checks values against the types written on variables, parameters, return
+//! types, fields and call arguments: primitives, arrays, object shapes (type
+//! literals and interfaces), function types and unions.
+//!
+//! The pass runs on the AST before compilation and is deliberately
+//! conservative, so plain JavaScript passes untouched:
+//! - anything it can't type (unannotated `let`s, unknown names, most calls)
+//!   is `any`, which fits everywhere;
+//! - nothing is narrowed, so a union is accepted wherever one of its members
+//!   is;
+//! - annotations it can't resolve (subclasses, `Promise<T>`, ...) are not
+//!   checked.
+//!
+//! It reports values that can never fit their annotation.
+//!
+//! Generic functions and classes are instantiated at each call or `new`, from
+//! explicit type arguments or from the arguments' types. Calls to top-level
+//! functions are also recorded as `MonoHint`s, so `ir::typecheck` can
+//! specialize a function that is only ever called with one set of argument
+//! types.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use swc_common::{FileName, SourceMap, Spanned, sync::Lrc};
+use swc_ecma_ast::*;
+use swc_ecma_parser::{Parser, StringInput, Syntax, lexer::Lexer};
+
+use super::checker::{declare_interface, define_interface, register_type_decl};
+use super::convert::TypeConverter;
+use super::error::{Span, TypeError};
+use super::registry::TypeRegistry;
+use super::{
+    FunctionType, ObjectType, StructDef, Type, TypeContext, TypeId, TypeVarId, fresh_type_id,
+    fresh_type_var_id,
+};
+use crate::runtime::heap::ElementKind;
+
+/// Nesting beyond which two types are assumed compatible (recursive types)
+const MAX_DEPTH: usize = 16;
+
+/// Argument types of the calls to a top-level function
+#[derive(Debug, Clone, PartialEq)]
+pub struct MonoHint {
+    pub function: String,
+    pub params: Vec<Type>,
+}
+
+/// Result of `analyze_source`
+#[derive(Debug, Default)]
+pub struct Analysis {
+    pub errors: Vec<TypeError>,
+    /// One hint per distinct instantiation. A function only gets hints if
+    /// every use of it is a direct call with typed arguments.
+    pub hints: Vec<MonoHint>,
+    /// Declared signature of each top-level function declaration that
+    /// isn't overloaded
+    pub signatures: BTreeMap<String, FunctionType>,
+}
+
+/// Check `source` against its annotations. Sources that don't parse yield no
+/// errors; the compiler reports those.
+pub fn check_source(source: &str, syntax: Syntax) -> Vec<TypeError> {
+    analyze_source(source, syntax).errors
+}
+
+/// Check `source` and collect specialization hints
+pub fn analyze_source(source: &str, syntax: Syntax) -> Analysis {
+    let cm: Lrc<SourceMap> = Default::default();
+    let fm = cm.new_source_file(
+        FileName::Custom("main.ot".into()).into(),
+        source.to_string(),
+    );
+    let lexer = Lexer::new(syntax, Default::default(), StringInput::from(&*fm), None);
+    let Ok(program) = Parser::new_from(lexer).parse_program() else {
+        return Analysis::default();
+    };
+    let mut checker = AnnotationChecker::new(&cm);
+    checker.check_program(&program);
+
+    let mut hints = Vec::new();
+    for (function, calls) in checker.calls {
+        if checker.dynamic.contains(&function) {
+            continue;
+        }
+        for params in calls {
+            let hint = MonoHint {
+                function: function.clone(),
+                params,
+            };
+            if !hints.contains(&hint) {
+                hints.push(hint);
+            }
+        }
+    }
+    hints.sort_by(|a, b| a.function.cmp(&b.function));
+    let signatures = checker
+        .top_level_fns
+        .iter()
+        .filter_map(|name| match &checker.scopes[0].get(name)?.ty {
+            Type::Function(signature) => Some((name.clone(), (**signature).clone())),
+            _ => None,
+        })
+        .collect();
+    Analysis {
+        errors: checker.errors,
+        hints,
+        signatures,
+    }
+}
+
+struct Binding {
+    ty: Type,
+    /// Declared with an annotation, so assignments must fit it
+    annotated: bool,
+}
+
+/// Where a value is checked; decides how a top-level mismatch is reported
+#[derive(Clone, Copy, PartialEq)]
+enum Site {
+    Value,
+    Return,
+}
+
+struct AnnotationChecker<'a> {
+    cm: &'a SourceMap,
+    registry: TypeRegistry,
+    scopes: Vec<HashMap<String, Binding>>,
+    /// Declared return type of each enclosing function (None: not checked)
+    returns: Vec<Option<Type>>,
+    /// Generic parameters in scope
+    type_params: Vec<(String, TypeVarId)>,
+    /// Constructor signature of each class with a known shape
+    classes: HashMap<TypeId, FunctionType>,
+    /// Each interface, and whether its fields list every property it allows
+    interfaces: HashMap<TypeId, bool>,
+    /// Function declarations of the top-level scope
+    top_level_fns: HashSet<String>,
+    /// Argument types of each direct call to a top-level function
+    calls: HashMap<String, Vec<Vec<Type>>>,
+    /// Top-level functions used other than by typed direct calls
+    dynamic: HashSet<String>,
+    errors: Vec<TypeError>,
+}
+
+impl<'a> AnnotationChecker<'a> {
+    fn new(cm: &'a SourceMap) -> Self {
+        Self {
+            cm,
+            registry: TypeRegistry::new(),
+            scopes: vec![HashMap::new()],
+            returns: Vec::new(),
+            type_params: Vec::new(),
+            classes: HashMap::new(),
+            interfaces: HashMap::new(),
+            top_level_fns: HashSet::new(),
+            calls: HashMap::new(),
+            dynamic: HashSet::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    fn check_program(&mut self, program: &Program) {
+        match program {
+            Program::Module(module) => {
+                let decls = module.body.iter().filter_map(|item| match item {
+                    ModuleItem::Stmt(Stmt::Decl(decl)) => Some(decl),
+                    ModuleItem::ModuleDecl(ModuleDecl::ExportDecl(export)) => Some(&export.decl),
+                    _ => None,
+                });
+                self.hoist(decls);
+                for item in &module.body {
+                    match item {
+                        ModuleItem::Stmt(stmt) => self.check_stmt(stmt),
+                        ModuleItem::ModuleDecl(decl) => self.check_module_decl(decl),
+                    }
+                }
+            }
+            Program::Script(script) => {
+                self.hoist(script.body.iter().filter_map(stmt_decl));
+                for stmt in &script.body {
+                    self.check_stmt(stmt);
+                }
+            }
+        }
+    }
+
+    // ========================================================================
+    // Scopes
+    // ========================================================================
+
+    fn declare(&mut self, name: String, ty: Type, annotated: bool) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name, Binding { ty, annotated });
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Option<&Binding> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name))
+    }
+
+    /// Whether `name` refers to a top-level function declaration here
+    fn is_top_level_fn(&self, name: &str) -> bool {
+        self.top_level_fns.contains(name)
+            && self
+                .scopes
+                .iter()
+                .rposition(|scope| scope.contains_key(name))
+                == Some(0)
+    }
+
+    /// Declare every name bound by a pattern as `any`
+    fn declare_pattern(&mut self, pat: &Pat) {
+        match pat {
+            Pat::Ident(ident) => self.declare(ident.id.sym.to_string(), Type::Any, false),
+            Pat::Array(arr) => {
+                for elem in arr.elems.iter().flatten() {
+                    self.declare_pattern(elem);
+                }
+            }
+            Pat::Object(obj) => {
+                for prop in &obj.props {
+                    match prop {
+                        ObjectPatProp::KeyValue(kv) => self.declare_pattern(&kv.value),
+                        ObjectPatProp::Assign(assign) => {
+                            if let Some(value) = &assign.value {
+                                self.synth(value);
+                            }
+                            self.declare(assign.key.id.sym.to_string(), Type::Any, false);
+                        }
+                        ObjectPatProp::Rest(rest) => self.declare_pattern(&rest.arg),
+                    }
+                }
+            }
+            Pat::Rest(rest) => self.declare_pattern(&rest.arg),
+            Pat::Assign(assign) => {
+                self.synth(&assign.right);
+                self.declare_pattern(&assign.left);
+            }
+            Pat::Expr(_) | Pat::Invalid(_) => {}
+        }
+    }
+
+    /// Declare the functions, classes and types of a block before its
+    /// statements run. A function declared twice (overloads) is `any`.
+    fn hoist<'d>(&mut self, decls: impl Iterator<Item = &'d Decl>) {
+        let decls: Vec<&Decl> = decls.collect();
+        let mut interfaces: Vec<(TypeId, &TsInterfaceDecl)> = Vec::new();
+        let mut classes = Vec::new();
+        for decl in &decls {
+            match decl {
+                Decl::TsInterface(iface) => {
+                    // Declarations of one name merge
+                    let id = match interfaces
+                        .iter()
+                        .find(|(_, other)| other.id.sym == iface.id.sym)
+                    {
+                        Some((id, _)) => *id,
+                        None => declare_interface(&mut self.registry, iface),
+                    };
+                    interfaces.push((id, iface));
+                }
+                // Subclasses inherit members this pass can't see
+                Decl::Class(class) if class.class.super_class.is_none() => {
+                    let params = class.class.type_params.as_deref();
+                    let ids = params.map_or(0, |p| p.params.len());
+                    let def = StructDef::new(fresh_type_id(), class.ident.sym.to_string())
+                        .with_type_params((0..ids).map(|_| fresh_type_var_id()).collect());
+                    classes.push((self.registry.register_struct(def), &class.class));
+                }
+                _ => {}
+            }
+        }
+        for decl in &decls {
+            if let Decl::TsTypeAlias(_) = decl {
+                register_type_decl(&mut self.registry, decl);
+            }
+        }
+        // Bases first, so there are fields to inherit
+        while !interfaces.is_empty() {
+            let extends_pending = |iface: &TsInterfaceDecl| {
+                iface.extends.iter().any(|base| {
+                    interfaces.iter().any(|(_, other)| {
+                        matches!(&*base.expr, Expr::Ident(ident) if ident.sym == other.id.sym)
+                    })
+                })
+            };
+            // A cycle is broken at its first declaration
+            let next = interfaces
+                .iter()
+                .position(|(_, iface)| !extends_pending(iface))
+                .unwrap_or(0);
+            let (id, iface) = interfaces.remove(next);
+            self.define_interface(id, iface);
+        }
+        // Members may refer to any class of the block
+        for (id, class) in classes {
+            self.define_class(id, class);
+        }
+
+        let top_level = self.scopes.len() == 1;
+        let mut seen = Vec::new();
+        for decl in decls {
+            match decl {
+                Decl::Fn(fn_decl) => {
+                    let name = fn_decl.ident.sym.to_string();
+                    let overloaded = seen.contains(&name);
+                    let ty = if overloaded {
+                        Type::Any
+                    } else {
+                        Type::Function(Box::new(self.signature(&fn_decl.function)))
+                    };
+                    // Overloads have no single signature to specialize
+                    if top_level && overloaded {
+                        self.top_level_fns.remove(&name);
+                    } else if top_level {
+                        self.top_level_fns.insert(name.clone());
+                    }
+                    seen.push(name.clone());
+                    self.declare(name, ty, true);
+                }
+                Decl::Class(class) => self.declare(class.ident.sym.to_string(), Type::Any, false),
+                _ => {}
+            }
+        }
+    }
+
+    fn define_interface(&mut self, id: TypeId, iface: &TsInterfaceDecl) {
+        let mut complete = define_interface(&mut self.registry, id, iface);
+        // Fields of a class or an open interface don't list every property
+        for base in &iface.extends {
+            let base_id = match &*base.expr {
+                Expr::Ident(ident) => self.registry.lookup_by_name(&ident.sym),
+                _ => None,
+            };
+            complete &= base_id.and_then(|id| self.interfaces.get(&id)) == Some(&true);
+        }
+        let closed = self.interfaces.entry(id).or_insert(true);
+        *closed &= complete;
+    }
+
+    /// Record the instance shape and constructor of class `id`
+    fn define_class(&mut self, id: TypeId, class: &Class) {
+        let before = self.push_class_type_params(id, class);
+        let mut fields = Vec::new();
+        let mut ctor = Vec::new();
+        for member in &class.body {
+            match member {
+                ClassMember::ClassProp(prop)
+                    if !prop.is_static && is_public(prop.accessibility) =>
+                {
+                    let Some(name) = prop_name(&prop.key) else {
+                        continue;
+                    };
+                    let ty = self
+                        .convert_ann(prop.type_ann.as_deref())
+                        .unwrap_or(Type::Any);
+                    let ty = if prop.is_optional {
+                        Type::union([ty, Type::Null])
+                    } else {
+                        ty
+                    };
+                    fields.push((name, ty));
+                }
+                ClassMember::Method(method)
+                    if !method.is_static && is_public(method.accessibility) =>
+                {
+                    let Some(name) = prop_name(&method.key) else {
+                        continue;
+                    };
+                    let ty = match method.kind {
+                        MethodKind::Method => {
+                            Type::Function(Box::new(self.signature(&method.function)))
+                        }
+                        MethodKind::Getter => self.signature(&method.function).return_ty,
+                        MethodKind::Setter => continue,
+                    };
+                    fields.push((name, ty));
+                }
+                ClassMember::Constructor(constructor) => {
+                    for param in &constructor.params {
+                        let pat = ctor_param(param);
+                        let (name, ty) = self.param(&pat);
+                        if let ParamOrTsParamProp::TsParamProp(prop) = param
+                            && is_public(prop.accessibility)
+                        {
+                            fields.push((name.clone(), strip_null(&ty)));
+                        }
+                        ctor.push((name, ty));
+                    }
+                }
+                _ => {}
+            }
+        }
+        self.type_params.truncate(before);
+
+        let Some(def) = self.registry.get_struct_mut(id) else {
+            return;
+        };
+        def.fields = fields;
+        let instance = if def.type_params.is_empty() {
+            Type::Struct(id)
+        } else {
+            Type::Generic(
+                id,
+                def.type_params.iter().map(|v| Type::TypeVar(*v)).collect(),
+            )
+        };
+        let ctor = FunctionType::new(ctor, instance).with_type_params(def.type_params.clone());
+        self.classes.insert(id, ctor);
+    }
+
+    /// Bring a class's type parameters into scope, with the ids of its shape
+    /// when it has one
+    fn push_class_type_params(&mut self, id: TypeId, class: &Class) -> usize {
+        let ids = self
+            .registry
+            .get_struct(id)
+            .map(|def| def.type_params.clone())
+            .unwrap_or_default();
+        let names = class.type_params.iter().flat_map(|p| &p.params);
+        if ids.is_empty() {
+            return self.push_type_params(class.type_params.as_deref());
+        }
+        let before = self.type_params.len();
+        for (param, id) in names.zip(ids) {
+            self.type_params.push((param.name.sym.to_string(), id));
+        }
+        before
+    }
+
+    fn check_block(&mut self, stmts: &[Stmt]) {
+        self.scopes.push(HashMap::new());
+        self.hoist(stmts.iter().filter_map(stmt_decl));
+        for stmt in stmts {
+            self.check_stmt(stmt);
+        }
+        self.scopes.pop();
+    }
+
+    // ========================================================================
+    // Annotations
+    // ========================================================================
+
+    fn convert(&self, ty: &TsType) -> Option<Type> {
+        let (names, ids): (Vec<String>, Vec<TypeVarId>) = self.type_params.iter().cloned().unzip();
+        TypeConverter::new(&self.registry)
+            .with_type_params(&ids, &names)
+            .convert(ty)
+            .ok()
+    }
+
+    fn convert_ann(&self, ann: Option<&TsTypeAnn>) -> Option<Type> {
+        ann.and_then(|ann| self.convert(&ann.type_ann))
+    }
+
+    fn push_type_params(&mut self, params: Option<&TsTypeParamDecl>) -> usize {
+        let before = self.type_params.len();
+        for param in params.into_iter().flat_map(|p| &p.params) {
+            self.type_params
+                .push((param.name.sym.to_string(), fresh_type_var_id()));
+        }
+        before
+    }
+
+    /// Name and type of a parameter. Optional and defaulted parameters admit
+    /// `null`; a rest parameter is named with a leading `...`.
+    fn param(&self, pat: &Pat) -> (String, Type) {
+        match pat {
+            Pat::Ident(ident) => {
+                let ty = self
+                    .convert_ann(ident.type_ann.as_deref())
+                    .unwrap_or(Type::Any);
+                let ty = if ident.id.optional {
+                    Type::union([ty, Type::Null])
+                } else {
+                    ty
+                };
+                (ident.id.sym.to_string(), ty)
+            }
+            Pat::Assign(assign) => {
+                let (name, ty) = self.param(&assign.left);
+                (name, Type::union([ty, Type::Null]))
+            }
+            Pat::Rest(rest) => {
+                let name = match &*rest.arg {
+                    Pat::Ident(ident) => format!("...{}", ident.id.sym),
+                    _ => "...rest".to_string(),
+                };
+                let ty = self
+                    .convert_ann(rest.type_ann.as_deref())
+                    .unwrap_or_else(|| Type::Array(Box::new(Type::Any)));
+                (name, ty)
+            }
+            _ => ("_".to_string(), Type::Any),
+        }
+    }
+
+    /// Declared signature; unannotated parts are `any`. The return types of
+    /// async functions and generators are not checked.
+    fn signature(&mut self, function: &Function) -> FunctionType {
+        let before = self.push_type_params(function.type_params.as_deref());
+        let params = function.params.iter().map(|p| self.param(&p.pat)).collect();
+        let return_ty = if function.is_async || function.is_generator {
+            Type::Any
+        } else {
+            self.convert_ann(function.return_type.as_deref())
+                .unwrap_or(Type::Any)
+        };
+        let type_params = self.type_params[before..]
+            .iter()
+            .map(|(_, id)| *id)
+            .collect();
+        self.type_params.truncate(before);
+        FunctionType::new(params, return_ty).with_type_params(type_params)
+    }
+
+    /// Bind a parameter in the current scope, checking its default value
+    fn bind_param(&mut self, pat: &Pat, ty: Type) {
+        match pat {
+            Pat::Ident(ident) => {
+                let annotated = ident.type_ann.is_some();
+                self.declare(ident.id.sym.to_string(), ty, annotated);
+            }
+            Pat::Assign(assign) => {
+                let declared = strip_null(&ty);
+                self.check_expr_against(&assign.right, &declared, Site::Value);
+                self.bind_param(&assign.left, declared);
+            }
+            Pat::Rest(rest) => match &*rest.arg {
+                Pat::Ident(ident) => self.declare(ident.id.sym.to_string(), ty, true),
+                other => self.declare_pattern(other),
+            },
+            other => self.declare_pattern(other),
+        }
+    }
+
+    // ========================================================================
+    // Statements
+    // ========================================================================
+
+    fn check_module_decl(&mut self, decl: &ModuleDecl) {
+        match decl {
+            ModuleDecl::ExportDecl(export) => {
+                // Importers may call an exported function with anything
+                if let Decl::Fn(fn_decl) = &export.decl {
+                    self.dynamic.insert(fn_decl.ident.sym.to_string());
+                }
+                self.check_decl(&export.decl);
+            }
+            ModuleDecl::ExportDefaultDecl(export) => match &export.decl {
+                DefaultDecl::Fn(fn_expr) => {
+                    let signature = self.signature(&fn_expr.function);
+                    self.check_function(&fn_expr.function, signature);
+                }
+                DefaultDecl::Class(class) => self.check_class(&class.class, None),
+                DefaultDecl::TsInterfaceDecl(_) => {}
+            },
+            ModuleDecl::ExportDefaultExpr(export) => {
+                self.synth(&export.expr);
+            }
+            ModuleDecl::ExportNamed(export) if export.src.is_none() => {
+                for spec in &export.specifiers {
+                    if let ExportSpecifier::Named(named) = spec
+                        && let ModuleExportName::Ident(ident) = &named.orig
+                    {
+                        self.dynamic.insert(ident.sym.to_string());
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn check_stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Block(block) => self.check_block(&block.stmts),
+            Stmt::Expr(expr) => {
+                self.synth(&expr.expr);
+            }
+            Stmt::Return(ret) => self.check_return(ret),
+            Stmt::If(if_stmt) => {
+                self.synth(&if_stmt.test);
+                self.check_stmt(&if_stmt.cons);
+                if let Some(alt) = &if_stmt.alt {
+                    self.check_stmt(alt);
+                }
+            }
+            Stmt::While(while_stmt) => {
+                self.synth(&while_stmt.test);
+                self.check_stmt(&while_stmt.body);
+            }
+            Stmt::DoWhile(do_while) => {
+                self.check_stmt(&do_while.body);
+                self.synth(&do_while.test);
+            }
+            Stmt::For(for_stmt) => {
+                self.scopes.push(HashMap::new());
+                match &for_stmt.init {
+                    Some(VarDeclOrExpr::VarDecl(var)) => self.check_var_decl(var),
+                    Some(VarDeclOrExpr::Expr(expr)) => {
+                        self.synth(expr);
+                    }
+                    None => {}
+                }
+                if let Some(test) = &for_stmt.test {
+                    self.synth(test);
+                }
+                if let Some(update) = &for_stmt.update {
+                    self.synth(update);
+                }
+                self.check_stmt(&for_stmt.body);
+                self.scopes.pop();
+            }
+            Stmt::ForIn(for_in) => {
+                self.synth(&for_in.right);
+                self.scopes.push(HashMap::new());
+                self.bind_for_head(&for_in.left, Type::String);
+                self.check_stmt(&for_in.body);
+                self.scopes.pop();
+            }
+            Stmt::ForOf(for_of) => {
+                let right = self.synth(&for_of.right);
+                let elem = match self.resolve(&right) {
+                    Type::Array(elem) => *elem,
+                    Type::TypedArray(_) => Type::Number,
+                    Type::String if !for_of.is_await => Type::String,
+                    _ => Type::Any,
+                };
+                self.scopes.push(HashMap::new());
+                self.bind_for_head(&for_of.left, elem);
+                self.check_stmt(&for_of.body);
+                self.scopes.pop();
+            }
+            Stmt::Switch(switch) => {
+                self.synth(&switch.discriminant);
+                self.scopes.push(HashMap::new());
+                self.hoist(
+                    switch
+                        .cases
+                        .iter()
+                        .flat_map(|case| case.cons.iter().filter_map(stmt_decl)),
+                );
+                for case in &switch.cases {
+                    if let Some(test) = &case.test {
+                        self.synth(test);
+                    }
+                    for stmt in &case.cons {
+                        self.check_stmt(stmt);
+                    }
+                }
+                self.scopes.pop();
+            }
+            Stmt::Throw(throw) => {
+                self.synth(&throw.arg);
+            }
+            Stmt::Try(try_stmt) => {
+                self.check_block(&try_stmt.block.stmts);
+                if let Some(handler) = &try_stmt.handler {
+                    self.scopes.push(HashMap::new());
+                    if let Some(param) = &handler.param {
+                        self.declare_pattern(param);
+                    }
+                    self.check_block(&handler.body.stmts);
+                    self.scopes.pop();
+                }
+                if let Some(finalizer) = &try_stmt.finalizer {
+                    self.check_block(&finalizer.stmts);
+                }
+            }
+            Stmt::Labeled(labeled) => self.check_stmt(&labeled.body),
+            Stmt::Decl(decl) => self.check_decl(decl),
+            _ => {}
+        }
+    }
+
+    fn bind_for_head(&mut self, head: &ForHead, elem: Type) {
+        match head {
+            ForHead::VarDecl(var) => {
+                for decl in &var.decls {
+                    match &decl.name {
+                        Pat::Ident(ident) => {
+                            let declared = self.convert_ann(ident.type_ann.as_deref());
+                            let annotated = declared.is_some();
+                            let ty = declared.unwrap_or_else(|| elem.clone());
+                            self.declare(ident.id.sym.to_string(), ty, annotated);
+                        }
+                        other => self.declare_pattern(other),
+                    }
+                }
+            }
+            ForHead::UsingDecl(using) => {
+                for decl in &using.decls {
+                    self.declare_pattern(&decl.name);
+                }
+            }
+            ForHead::Pat(pat) => {
+                if let Pat::Expr(expr) = &**pat {
+                    self.synth(expr);
+                }
+            }
+        }
+    }
+
+    fn check_decl(&mut self, decl: &Decl) {
+        match decl {
+            Decl::Var(var) => self.check_var_decl(var),
+            Decl::Fn(fn_decl) => {
+                // Hoisted signature, unless the name is overloaded
+                let hoisted = match self.lookup(&fn_decl.ident.sym) {
+                    Some(Binding {
+                        ty: Type::Function(signature),
+                        ..
+                    }) => Some((**signature).clone()),
+                    _ => None,
+                };
+                let signature = hoisted.unwrap_or_else(|| self.signature(&fn_decl.function));
+                self.check_function(&fn_decl.function, signature);
+            }
+            Decl::Class(class) => {
+                let id = match class.class.super_class {
+                    None => self.registry.lookup_by_name(&class.ident.sym),
+                    Some(_) => None,
+                };
+                self.check_class(&class.class, id);
+            }
+            _ => {}
+        }
+    }
+
+    fn check_var_decl(&mut self, var: &VarDecl) {
+        for decl in &var.decls {
+            let Pat::Ident(ident) = &decl.name else {
+                if let Some(init) = &decl.init {
+                    self.synth(init);
+                }
+                self.declare_pattern(&decl.name);
+                continue;
+            };
+            let name = ident.id.sym.to_string();
+            match self.convert_ann(ident.type_ann.as_deref()) {
+                Some(declared) => {
+                    if let Some(init) = &decl.init {
+                        self.check_expr_against(init, &declared, Site::Value);
+                    }
+                    self.declare(name, declared, true);
+                }
+                None => {
+                    let init = decl.init.as_ref().map(|init| self.synth(init));
+                    // Only a `const` keeps the type of its initializer
+                    let ty = match init {
+                        Some(ty) if var.kind == VarDeclKind::Const => ty,
+                        _ => Type::Any,
+                    };
+                    self.declare(name, ty, false);
+                }
+            }
+        }
+    }
+
+    fn check_return(&mut self, ret: &ReturnStmt) {
+        let expected = self.returns.last().cloned().flatten();
+        match (&ret.arg, expected) {
+            (Some(arg), Some(expected)) => self.check_expr_against(arg, &expected, Site::Return),
+            (Some(arg), None) => {
+                self.synth(arg);
+            }
+            (None, Some(expected)) if !accepts_missing(&self.resolve(&expected)) => {
+                self.errors.push(TypeError::ReturnTypeMismatch {
+                    expected: self.describe(&expected),
+                    got: Type::Void,
+                    span: self.span(ret.span),
+                });
+            }
+            (None, _) => {}
+        }
+    }
+
+    /// Check a function body against `signature`
+    fn check_function(&mut self, function: &Function, signature: FunctionType) {
+        let declared = function.type_params.as_ref().map_or(0, |p| p.params.len());
+        let before = if declared > 0 && declared == signature.type_params.len() {
+            // Annotations in the body name the signature's type parameters
+            let before = self.type_params.len();
+            for (param, id) in function
+                .type_params
+                .iter()
+                .flat_map(|p| &p.params)
+                .zip(&signature.type_params)
+            {
+                self.type_params.push((param.name.sym.to_string(), *id));
+            }
+            before
+        } else {
+            self.push_type_params(function.type_params.as_deref())
+        };
+        self.scopes.push(HashMap::new());
+        for (param, (_, ty)) in function.params.iter().zip(signature.params) {
+            self.bind_param(&param.pat, ty);
+        }
+        let annotated = function.return_type.is_some() && signature.return_ty != Type::Any;
+        self.returns.push(annotated.then_some(signature.return_ty));
+        if let Some(body) = &function.body {
+            self.hoist(body.stmts.iter().filter_map(stmt_decl));
+            for stmt in &body.stmts {
+                self.check_stmt(stmt);
+            }
+        }
+        self.returns.pop();
+        self.scopes.pop();
+        self.type_params.truncate(before);
+    }
+
+    /// Check a class body. `id` is the class's shape, if it has one.
+    fn check_class(&mut self, class: &Class, id: Option<TypeId>) {
+        let before = match id {
+            Some(id) => self.push_class_type_params(id, class),
+            None => self.push_type_params(class.type_params.as_deref()),
+        };
+        for member in &class.body {
+            match member {
+                ClassMember::Constructor(ctor) => {
+                    self.scopes.push(HashMap::new());
+                    for param in &ctor.params {
+                        let pat = ctor_param(param);
+                        let (_, ty) = self.param(&pat);
+                        self.bind_param(&pat, ty);
+                    }
+                    self.returns.push(None);
+                    if let Some(body) = &ctor.body {
+                        self.check_block(&body.stmts);
+                    }
+                    self.returns.pop();
+                    self.scopes.pop();
+                }
+                ClassMember::Method(method) => {
+                    let signature = self.signature(&method.function);
+                    self.check_function(&method.function, signature);
+                }
+                ClassMember::PrivateMethod(method) => {
+                    let signature = self.signature(&method.function);
+                    self.check_function(&method.function, signature);
+                }
+                ClassMember::ClassProp(prop) => {
+                    self.check_field(prop.type_ann.as_deref(), prop.value.as_deref());
+                }
+                ClassMember::PrivateProp(prop) => {
+                    self.check_field(prop.type_ann.as_deref(), prop.value.as_deref());
+                }
+                ClassMember::StaticBlock(block) => self.check_block(&block.body.stmts),
+                _ => {}
+            }
+        }
+        self.type_params.truncate(before);
+    }
+
+    fn check_field(&mut self, ann: Option<&TsTypeAnn>, value: Option<&Expr>) {
+        let Some(value) = value else {
+            return;
+        };
+        match self.convert_ann(ann) {
+            Some(declared) => self.check_expr_against(value, &declared, Site::Value),
+            None => {
+                self.synth(value);
+            }
+        }
+    }
+
+    // ========================================================================
+    // Expressions
+    // ========================================================================
+
+    /// Check `expr` where a value of type `expected` is required
+    fn check_expr_against(&mut self, expr: &Expr, expected: &Type, site: Site) {
+        let target = self.resolve(expected);
+        match (expr, &target) {
+            (Expr::Paren(paren), _) => return self.check_expr_against(&paren.expr, expected, site),
+            (Expr::Cond(cond), _) => {
+                self.synth(&cond.test);
+                self.check_expr_against(&cond.cons, expected, site);
+                self.check_expr_against(&cond.alt, expected, site);
+                return;
+            }
+            _ => {}
+        }
+
+        match (expr, self.literal_target(expr, &target)) {
+            (Expr::Object(obj), Some(target)) => {
+                if let Some(fields) = self.shape(&target) {
+                    return self.check_object(obj, &fields, &target);
+                }
+            }
+            (Expr::Array(arr), Some(Type::Array(elem))) => {
+                for elem_expr in arr.elems.iter().flatten() {
+                    if elem_expr.spread.is_some() {
+                        self.synth(&elem_expr.expr);
+                    } else {
+                        self.check_expr_against(&elem_expr.expr, &elem, Site::Value);
+                    }
+                }
+                return;
+            }
+            (Expr::Arrow(arrow), Some(Type::Function(func))) => {
+                self.check_arrow(arrow, Some(&*func));
+                return;
+            }
+            _ => {}
+        }
+
+        let got = self.synth(expr);
+        if !self.assignable(&got, expected, 0) {
+            if let Some(field) = self.missing_field(&got, expected) {
+                self.errors.push(TypeError::MissingField {
+                    ty: self.type_name(expected),
+                    field,
+                    span: self.span(expr.span()),
+                });
+                return;
+            }
+            let expected = self.describe(expected);
+            let got = self.describe(&got);
+            let span = self.span(expr.span());
+            self.errors.push(match site {
+                Site::Value => TypeError::Mismatch {
+                    expected,
+                    got,
+                    span,
+                },
+                Site::Return => TypeError::ReturnTypeMismatch {
+                    expected,
+                    got,
+                    span,
+                },
+            });
+        }
+    }
+
+    /// The member of `target` a literal is checked against: `target` itself,
+    /// or the only union member of the literal's kind.
+    fn literal_target(&self, expr: &Expr, target: &Type) -> Option<Type> {
+        let fits = |ty: &Type| match expr {
+            Expr::Object(_) => self.shape(ty).is_some(),
+            Expr::Array(_) => matches!(ty, Type::Array(_)),
+            Expr::Arrow(_) => matches!(ty, Type::Function(_)),
+            _ => false,
+        };
+        match target {
+            Type::Union(members) => {
+                let mut candidates = members.iter().map(|m| self.resolve(m)).filter(|m| fits(m));
+                match (candidates.next(), candidates.next()) {
+                    (Some(only), None) => Some(only),
+                    _ => None,
+                }
+            }
+            ty if fits(ty) => Some(ty.clone()),
+            _ => None,
+        }
+    }
+
+    fn check_object(&mut self, obj: &ObjectLit, fields: &BTreeMap<String, Type>, target: &Type) {
+        if obj
+            .props
+            .iter()
+            .any(|p| matches!(p, PropOrSpread::Spread(_)))
+        {
+            // Spread fields are unknown; only check the values
+            self.synth(&Expr::Object(obj.clone()));
+            return;
+        }
+
+        let mut present = Vec::new();
+        for prop in &obj.props {
+            let PropOrSpread::Prop(prop) = prop else {
+                continue;
+            };
+            match &**prop {
+                Prop::KeyValue(kv) => {
+                    let name = prop_name(&kv.key);
+                    match name.as_ref().and_then(|n| fields.get(n)) {
+                        Some(field_ty) => self.check_expr_against(&kv.value, field_ty, Site::Value),
+                        None => {
+                            self.synth(&kv.value);
+                        }
+                    }
+                    present.extend(name.map(|name| (name, kv.key.span())));
+                }
+                Prop::Shorthand(ident) => {
+                    let name = ident.sym.to_string();
+                    if let Some(field_ty) = fields.get(&name) {
+                        self.check_expr_against(&Expr::Ident(ident.clone()), field_ty, Site::Value);
+                    }
+                    present.push((name, ident.span));
+                }
+                Prop::Method(method) => {
+                    let signature = self.signature(&method.function);
+                    self.check_function(&method.function, signature);
+                    present.extend(prop_name(&method.key).map(|name| (name, method.key.span())));
+                }
+                Prop::Getter(getter) => {
+                    self.check_accessor(None, getter.body.as_ref());
+                    present.extend(prop_name(&getter.key).map(|name| (name, getter.key.span())));
+                }
+                Prop::Setter(setter) => {
+                    self.check_accessor(Some(&*setter.param), setter.body.as_ref());
+                    present.extend(prop_name(&setter.key).map(|name| (name, setter.key.span())));
+                }
+                Prop::Assign(assign) => {
+                    self.synth(&assign.value);
+                    present.push((assign.key.sym.to_string(), assign.key.span));
+                }
+            }
+        }
+
+        for (name, ty) in fields {
+            if !present.iter().any(|(field, _)| field == name)
+                && !accepts_missing(&self.resolve(ty))
+            {
+                self.errors.push(TypeError::MissingField {
+                    ty: self.type_name(target),
+                    field: name.clone(),
+                    span: self.span(obj.span),
+                });
+            }
+        }
+        // Like TypeScript, a literal can't add fields an interface doesn't
+        // declare; they would be unreachable through the annotation
+        if self.is_closed(target) {
+            for (name, span) in &present {
+                if !fields.contains_key(name) {
+                    self.errors.push(TypeError::UnknownField {
+                        ty: self.type_name(target),
+                        field: name.clone(),
+                        span: self.span(*span),
+                    });
+                }
+            }
+        }
+    }
+
+    fn check_accessor(&mut self, param: Option<&Pat>, body: Option<&BlockStmt>) {
+        let Some(body) = body else {
+            return;
+        };
+        self.scopes.push(HashMap::new());
+        if let Some(param) = param {
+            self.declare_pattern(param);
+        }
+        self.returns.push(None);
+        self.check_block(&body.stmts);
+        self.returns.pop();
+        self.scopes.pop();
+    }
+
+    /// Type of an arrow function, checking its body. `expected` gives
+    /// unannotated parameters and the return value their types.
+    fn check_arrow(&mut self, arrow: &ArrowExpr, expected: Option<&FunctionType>) -> Type {
+        let before = self.push_type_params(arrow.type_params.as_deref());
+        let params: Vec<(String, Type)> = arrow
+            .params
+            .iter()
+            .enumerate()
+            .map(|(i, pat)| {
+                let (name, ty) = self.param(pat);
+                let contextual = match (pat, expected) {
+                    (Pat::Ident(ident), Some(func)) if ident.type_ann.is_none() => {
+                        func.params.get(i).map(|(_, ty)| ty.clone())
+                    }
+                    _ => None,
+                };
+                (name, contextual.unwrap_or(ty))
+            })
+            .collect();
+        let declared = if arrow.is_async || arrow.is_generator {
+            None
+        } else {
+            self.convert_ann(arrow.return_type.as_deref())
+                .or_else(|| expected.map(|func| func.return_ty.clone()))
+                .filter(|ty| !matches!(ty, Type::Void | Type::Any))
+        };
+
+        self.scopes.push(HashMap::new());
+        for (pat, (_, ty)) in arrow.params.iter().zip(&params) {
+            self.bind_param(pat, ty.clone());
+        }
+        let return_ty = match &*arrow.body {
+            BlockStmtOrExpr::Expr(body) => match &declared {
+                Some(declared) => {
+                    self.check_expr_against(body, declared, Site::Return);
+                    declared.clone()
+                }
+                None if arrow.is_async => {
+                    self.synth(body);
+                    Type::Any
+                }
+                None => self.synth(body),
+            },
+            BlockStmtOrExpr::BlockStmt(block) => {
+                self.returns.push(declared.clone());
+                self.hoist(block.stmts.iter().filter_map(stmt_decl));
+                for stmt in &block.stmts {
+                    self.check_stmt(stmt);
+                }
+                self.returns.pop();
+                declared.unwrap_or(Type::Any)
+            }
+        };
+        self.scopes.pop();
+        self.type_params.truncate(before);
+        Type::Function(Box::new(FunctionType::new(params, return_ty)))
+    }
+
+    /// Type of `expr`, checking any annotated positions inside it
+    fn synth(&mut self, expr: &Expr) -> Type {
+        match expr {
+            Expr::Lit(lit) => match lit {
+                Lit::Num(_) => Type::Number,
+                Lit::Str(_) => Type::String,
+                Lit::Bool(_) => Type::Boolean,
+                Lit::Null(_) => Type::Null,
+                _ => Type::Any,
+            },
+            Expr::Tpl(tpl) => {
+                for expr in &tpl.exprs {
+                    self.synth(expr);
+                }
+                Type::String
+            }
+            Expr::Ident(ident) => {
+                // A function used as a value may be called with anything
+                if self.is_top_level_fn(&ident.sym) {
+                    self.dynamic.insert(ident.sym.to_string());
+                }
+                match self.lookup(&ident.sym) {
+                    Some(binding) => binding.ty.clone(),
+                    None if &*ident.sym == "undefined" => Type::Null,
+                    None => Type::Any,
+                }
+            }
+            Expr::Array(arr) => {
+                let mut elems = Vec::new();
+                let mut spread = false;
+                for elem in arr.elems.iter().flatten() {
+                    let ty = self.synth(&elem.expr);
+                    if elem.spread.is_some() {
+                        spread = true;
+                    }
+                    elems.push(ty);
+                }
+                if spread || elems.is_empty() {
+                    Type::Array(Box::new(Type::Any))
+                } else {
+                    Type::Array(Box::new(Type::union(elems)))
+                }
+            }
+            Expr::Object(obj) => self.synth_object(obj),
+            Expr::Fn(fn_expr) => {
+                let signature = self.signature(&fn_expr.function);
+                self.check_function(&fn_expr.function, signature.clone());
+                Type::Function(Box::new(signature))
+            }
+            Expr::Arrow(arrow) => self.check_arrow(arrow, None),
+            Expr::Class(class) => {
+                self.check_class(&class.class, None);
+                Type::Any
+            }
+            Expr::Call(call) => {
+                let direct = match &call.callee {
+                    Callee::Expr(callee) => match &**callee {
+                        Expr::Ident(ident) if self.is_top_level_fn(&ident.sym) => {
+                            Some(ident.sym.to_string())
+                        }
+                        _ => None,
+                    },
+                    _ => None,
+                };
+                let callee = match (&call.callee, &direct) {
+                    (_, Some(name)) => self.lookup(name).map_or(Type::Any, |b| b.ty.clone()),
+                    (Callee::Expr(callee), None) => self.synth(callee),
+                    _ => Type::Any,
+                };
+                let ty = self.check_call(&callee, &call.args, call.type_args.as_deref(), call.span);
+                if let Some(name) = direct {
+                    self.record_call(name, &call.args);
+                }
+                ty
+            }
+            Expr::New(new) => self.check_new(new),
+            Expr::OptChain(chain) => {
+                match &*chain.base {
+                    OptChainBase::Member(member) => {
+                        self.synth_member(member);
+                    }
+                    OptChainBase::Call(call) => {
+                        self.synth(&call.callee);
+                        for arg in &call.args {
+                            self.synth(&arg.expr);
+                        }
+                    }
+                }
+                Type::Any
+            }
+            Expr::Member(member) => self.synth_member(member),
+            Expr::Assign(assign) => self.check_assign(assign),
+            Expr::Update(update) => {
+                self.synth(&update.arg);
+                Type::Number
+            }
+            Expr::Unary(unary) => {
+                self.synth(&unary.arg);
+                match unary.op {
+                    UnaryOp::Bang | UnaryOp::Delete => Type::Boolean,
+                    UnaryOp::TypeOf => Type::String,
+                    UnaryOp::Void => Type::Null,
+                    UnaryOp::Minus | UnaryOp::Plus | UnaryOp::Tilde => Type::Number,
+                }
+            }
+            Expr::Bin(bin) => {
+                let left = self.synth(&bin.left);
+                let right = self.synth(&bin.right);
+                let (left, right) = (self.resolve(&left), self.resolve(&right));
+                match bin.op {
+                    BinaryOp::Add => match (&left, &right) {
+                        (Type::String, _) | (_, Type::String) => Type::String,
+                        (Type::Number, Type::Number) => Type::Number,
+                        _ => Type::Any,
+                    },
+                    BinaryOp::Sub
+                    | BinaryOp::Mul
+                    | BinaryOp::Div
+                    | BinaryOp::Mod
+                    | BinaryOp::Exp
+                    | BinaryOp::BitAnd
+                    | BinaryOp::BitOr
+                    | BinaryOp::BitXor
+                    | BinaryOp::LShift
+                    | BinaryOp::RShift
+                    | BinaryOp::ZeroFillRShift => Type::Number,
+                    BinaryOp::Lt
+                    | BinaryOp::LtEq
+                    | BinaryOp::Gt
+                    | BinaryOp::GtEq
+                    | BinaryOp::EqEq
+                    | BinaryOp::NotEq
+                    | BinaryOp::EqEqEq
+                    | BinaryOp::NotEqEq
+                    | BinaryOp::In
+                    | BinaryOp::InstanceOf => Type::Boolean,
+                    BinaryOp::LogicalAnd | BinaryOp::LogicalOr | BinaryOp::NullishCoalescing => {
+                        Type::union([left, right])
+                    }
+                }
+            }
+            Expr::Cond(cond) => {
+                self.synth(&cond.test);
+                let cons = self.synth(&cond.cons);
+                let alt = self.synth(&cond.alt);
+                Type::union([cons, alt])
+            }
+            Expr::Seq(seq) => {
+                let mut last = Type::Any;
+                for expr in &seq.exprs {
+                    last = self.synth(expr);
+                }
+                last
+            }
+            Expr::Paren(paren) => self.synth(&paren.expr),
+            Expr::TsAs(as_expr) => {
+                self.synth(&as_expr.expr);
+                self.convert(&as_expr.type_ann).unwrap_or(Type::Any)
+            }
+            Expr::TsTypeAssertion(assertion) => {
+                self.synth(&assertion.expr);
+                self.convert(&assertion.type_ann).unwrap_or(Type::Any)
+            }
+            Expr::TsSatisfies(satisfies) => match self.convert(&satisfies.type_ann) {
+                Some(declared) => {
+                    self.check_expr_against(&satisfies.expr, &declared, Site::Value);
+                    self.synth_quiet(&satisfies.expr)
+                }
+                None => self.synth(&satisfies.expr),
+            },
+            Expr::TsNonNull(non_null) => strip_null(&self.synth(&non_null.expr)),
+            Expr::TsConstAssertion(assertion) => self.synth(&assertion.expr),
+            Expr::TsInstantiation(inst) => {
+                self.synth(&inst.expr);
+                Type::Any
+            }
+            Expr::Await(await_expr) => {
+                self.synth(&await_expr.arg);
+                Type::Any
+            }
+            Expr::Yield(yield_expr) => {
+                if let Some(arg) = &yield_expr.arg {
+                    self.synth(arg);
+                }
+                Type::Any
+            }
+            Expr::TaggedTpl(tagged) => {
+                self.synth(&tagged.tag);
+                for expr in &tagged.tpl.exprs {
+                    self.synth(expr);
+                }
+                Type::Any
+            }
+            _ => Type::Any,
+        }
+    }
+
+    /// Type of an expression already checked, without reporting twice
+    fn synth_quiet(&mut self, expr: &Expr) -> Type {
+        let errors = self.errors.len();
+        let ty = self.synth(expr);
+        self.errors.truncate(errors);
+        ty
+    }
+
+    fn synth_object(&mut self, obj: &ObjectLit) -> Type {
+        let mut fields = BTreeMap::new();
+        let mut spread = false;
+        for prop in &obj.props {
+            let prop = match prop {
+                PropOrSpread::Prop(prop) => prop,
+                PropOrSpread::Spread(spread_elem) => {
+                    self.synth(&spread_elem.expr);
+                    spread = true;
+                    continue;
+                }
+            };
+            let (name, ty) = match &**prop {
+                Prop::KeyValue(kv) => (prop_name(&kv.key), self.synth(&kv.value)),
+                Prop::Shorthand(ident) => (
+                    Some(ident.sym.to_string()),
+                    self.synth(&Expr::Ident(ident.clone())),
+                ),
+                Prop::Method(method) => {
+                    let signature = self.signature(&method.function);
+                    self.check_function(&method.function, signature.clone());
+                    (prop_name(&method.key), Type::Function(Box::new(signature)))
+                }
+                Prop::Assign(assign) => {
+                    self.synth(&assign.value);
+                    (Some(assign.key.sym.to_string()), Type::Any)
+                }
+                Prop::Getter(getter) => {
+                    self.check_accessor(None, getter.body.as_ref());
+                    (prop_name(&getter.key), Type::Any)
+                }
+                Prop::Setter(setter) => {
+                    self.check_accessor(Some(&*setter.param), setter.body.as_ref());
+                    (prop_name(&setter.key), Type::Any)
+                }
+            };
+            if let Some(name) = name {
+                fields.insert(name, ty);
+            }
+        }
+        if spread {
+            // Fields from the spread are unknown
+            return Type::Any;
+        }
+        Type::Object(ObjectType {
+            fields,
+            exact: false,
+        })
+    }
+
+    fn synth_member(&mut self, member: &MemberExpr) -> Type {
+        let obj = self.synth(&member.obj);
+        let obj = self.resolve(&obj);
+        match &member.prop {
+            MemberProp::Ident(name) => {
+                if let Some(fields) = self.shape(&obj) {
+                    return fields.get(&*name.sym).cloned().unwrap_or(Type::Any);
+                }
+                match (&obj, &*name.sym) {
+                    (Type::Array(_) | Type::TypedArray(_) | Type::String, "length") => Type::Number,
+                    _ => Type::Any,
+                }
+            }
+            MemberProp::Computed(computed) => {
+                self.synth(&computed.expr);
+                match obj {
+                    Type::Array(elem) => *elem,
+                    Type::TypedArray(_) => Type::Number,
+                    Type::String => Type::String,
+                    _ => Type::Any,
+                }
+            }
+            MemberProp::PrivateName(_) => Type::Any,
+        }
+    }
+
+    /// `new C(...)` of a class with a known shape is checked like a call to
+    /// its constructor
+    fn check_new(&mut self, new: &NewExpr) -> Type {
+        let ctor = match &*new.callee {
+            Expr::Ident(ident) => self
+                .registry
+                .lookup_by_name(&ident.sym)
+                .and_then(|id| self.classes.get(&id))
+                .cloned(),
+            _ => None,
+        };
+        let args = new.args.as_deref().unwrap_or_default();
+        let Some(ctor) = ctor else {
+            self.synth(&new.callee);
+            for arg in args {
+                self.synth(&arg.expr);
+            }
+            // Built-in typed array constructors, unless shadowed
+            return match &*new.callee {
+                Expr::Ident(ident) if self.lookup(&ident.sym).is_none() => {
+                    ElementKind::from_name(&ident.sym).map_or(Type::Any, Type::TypedArray)
+                }
+                _ => Type::Any,
+            };
+        };
+        let ctor = Type::Function(Box::new(ctor));
+        self.check_call(&ctor, args, new.type_args.as_deref(), new.span)
+    }
+
+    /// Check a call's arguments against the callee's signature
+    fn check_call(
+        &mut self,
+        callee: &Type,
+        args: &[ExprOrSpread],
+        type_args: Option<&TsTypeParamInstantiation>,
+        span: swc_common::Span,
+    ) -> Type {
+        let Type::Function(func) = self.resolve(callee) else {
+            for arg in args {
+                self.synth(&arg.expr);
+            }
+            return Type::Any;
+        };
+        let func = self.instantiate(*func, args, type_args);
+
+        let rest = func
+            .params
+            .iter()
+            .position(|(name, _)| name.starts_with("..."));
+        let fixed = rest.unwrap_or(func.params.len());
+        let mut spread = false;
+        for (i, arg) in args.iter().enumerate() {
+            if spread || arg.spread.is_some() {
+                spread = true;
+                self.synth(&arg.expr);
+                continue;
+            }
+            match self.param_at(&func, i) {
+                Some(param) => self.check_expr_against(&arg.expr, &param, Site::Value),
+                None => {
+                    self.synth(&arg.expr);
+                }
+            }
+        }
+
+        if !spread {
+            let typed =
+                func.return_ty != Type::Any || func.params.iter().any(|(_, ty)| *ty != Type::Any);
+            let required = func.params[..fixed]
+                .iter()
+                .rposition(|(_, ty)| !accepts_missing(&self.resolve(ty)))
+                .map_or(0, |last| last + 1);
+            if args.len() < required || (typed && rest.is_none() && args.len() > fixed) {
+                self.errors.push(TypeError::WrongArgCount {
+                    expected: if args.len() < required {
+                        required
+                    } else {
+                        fixed
+                    },
+                    got: args.len(),
+                    span: self.span(span),
+                });
+            }
+        }
+        func.return_ty
+    }
+
+    /// Type expected of argument `i`, if the function takes one
+    fn param_at(&self, func: &FunctionType, i: usize) -> Option<Type> {
+        let rest = func
+            .params
+            .iter()
+            .position(|(name, _)| name.starts_with("..."));
+        match rest {
+            Some(r) if i >= r => match self.resolve(&func.params[r].1) {
+                Type::Array(elem) => Some(*elem),
+                _ => Some(Type::Any),
+            },
+            _ => func.params.get(i).map(|(_, ty)| ty.clone()),
+        }
+    }
+
+    /// `func` with its type parameters replaced, from explicit type arguments
+    /// or else from the types of the arguments. Parameters nothing pins down
+    /// become `any`.
+    fn instantiate(
+        &mut self,
+        func: FunctionType,
+        args: &[ExprOrSpread],
+        type_args: Option<&TsTypeParamInstantiation>,
+    ) -> FunctionType {
+        if func.type_params.is_empty() {
+            return func;
+        }
+        let mut ctx = TypeContext::new();
+        match type_args {
+            Some(type_args) => {
+                for (id, ty) in func.type_params.iter().zip(&type_args.params) {
+                    ctx.bind_type_var(*id, self.convert(ty).unwrap_or(Type::Any));
+                }
+            }
+            None => {
+                for (i, arg) in args.iter().enumerate() {
+                    if arg.spread.is_some() {
+                        break;
+                    }
+                    // Callbacks are checked against the instantiated signature
+                    if matches!(&*arg.expr, Expr::Arrow(_) | Expr::Fn(_)) {
+                        continue;
+                    }
+                    let Some(param) = self.param_at(&func, i) else {
+                        break;
+                    };
+                    let got = self.synth_quiet(&arg.expr);
+                    self.infer_type_args(&param, &got, &func.type_params, &mut ctx, 0);
+                }
+            }
+        }
+        for id in &func.type_params {
+            if ctx.resolve_type_var(*id).is_none() {
+                ctx.bind_type_var(*id, Type::Any);
+            }
+        }
+        FunctionType {
+            params: func
+                .params
+                .iter()
+                .map(|(name, ty)| (name.clone(), ctx.substitute(ty)))
+                .collect(),
+            return_ty: ctx.substitute(&func.return_ty),
+            lifetime_params: func.lifetime_params,
+            type_params: Vec::new(),
+            is_method: func.is_method,
+        }
+    }
+
+    /// Bind the type parameters `vars` that occur in `param` from the
+    /// matching parts of `got`. The first binding of each one wins.
+    fn infer_type_args(
+        &self,
+        param: &Type,
+        got: &Type,
+        vars: &[TypeVarId],
+        ctx: &mut TypeContext,
+        depth: usize,
+    ) {
+        if depth > MAX_DEPTH {
+            return;
+        }
+        let next = depth + 1;
+        match (self.resolve(param), self.resolve(got)) {
+            (Type::TypeVar(id), got) if vars.contains(&id) => {
+                let known = !matches!(got, Type::Any | Type::Never | Type::Null | Type::Error);
+                if known && ctx.resolve_type_var(id).is_none() {
+                    ctx.bind_type_var(id, got);
+                }
+            }
+            (Type::Array(param), Type::Array(got)) => {
+                self.infer_type_args(&param, &got, vars, ctx, next)
+            }
+            (Type::Union(members), got) => {
+                for member in &members {
+                    self.infer_type_args(member, &got, vars, ctx, next);
+                }
+            }
+            (Type::Function(f), Type::Function(g)) => {
+                for ((_, p), (_, a)) in f.params.iter().zip(&g.params) {
+                    self.infer_type_args(p, a, vars, ctx, next);
+                }
+                self.infer_type_args(&f.return_ty, &g.return_ty, vars, ctx, next);
+            }
+            (param, got) => {
+                let (Some(want), Some(have)) = (self.shape(&param), self.shape(&got)) else {
+                    return;
+                };
+                for (name, ty) in &want {
+                    if let Some(field) = have.get(name) {
+                        self.infer_type_args(ty, field, vars, ctx, next);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Remember the argument types of a direct call to a top-level function.
+    /// A call whose arguments aren't all of a known kind makes it dynamic.
+    fn record_call(&mut self, name: String, args: &[ExprOrSpread]) {
+        let mut params = Vec::new();
+        for arg in args {
+            let ty = self.resolve(&self.shallow_type(&arg.expr));
+            let known = matches!(
+                ty,
+                Type::Number
+                    | Type::String
+                    | Type::Boolean
+                    | Type::Array(_)
+                    | Type::TypedArray(_)
+                    | Type::Object(_)
+                    | Type::Struct(_)
+                    | Type::Generic(..)
+                    | Type::Function(_)
+            );
+            if arg.spread.is_some() || !known {
+                self.dynamic.insert(name);
+                return;
+            }
+            params.push(ty);
+        }
+        self.calls.entry(name).or_default().push(params);
+    }
+
+    /// Type of `expr` from its form alone, without checking it
+    fn shallow_type(&self, expr: &Expr) -> Type {
+        let number = |ty: Type| match ty {
+            Type::Number => Type::Number,
+            _ => Type::Any,
+        };
+        match expr {
+            Expr::Lit(Lit::Num(_)) => Type::Number,
+            Expr::Lit(Lit::Str(_)) | Expr::Tpl(_) => Type::String,
+            Expr::Lit(Lit::Bool(_)) => Type::Boolean,
+            Expr::Ident(ident) => self.lookup(&ident.sym).map_or(Type::Any, |b| b.ty.clone()),
+            Expr::Paren(paren) => self.shallow_type(&paren.expr),
+            Expr::Unary(unary) => match unary.op {
+                UnaryOp::Bang | UnaryOp::Delete => Type::Boolean,
+                UnaryOp::TypeOf => Type::String,
+                UnaryOp::Plus => Type::Number,
+                // BigInts negate to BigInts
+                UnaryOp::Minus | UnaryOp::Tilde => number(self.shallow_type(&unary.arg)),
+                UnaryOp::Void => Type::Null,
+            },
+            Expr::Bin(bin) => {
+                let (left, right) = (self.shallow_type(&bin.left), self.shallow_type(&bin.right));
+                match bin.op {
+                    BinaryOp::EqEq
+                    | BinaryOp::NotEq
+                    | BinaryOp::EqEqEq
+                    | BinaryOp::NotEqEq
+                    | BinaryOp::Lt
+                    | BinaryOp::LtEq
+                    | BinaryOp::Gt
+                    | BinaryOp::GtEq
+                    | BinaryOp::In
+                    | BinaryOp::InstanceOf => Type::Boolean,
+                    BinaryOp::LogicalAnd | BinaryOp::LogicalOr | BinaryOp::NullishCoalescing => {
+                        Type::Any
+                    }
+                    BinaryOp::Add if left == Type::String || right == Type::String => Type::String,
+                    _ if left == Type::Number && right == Type::Number => Type::Number,
+                    _ => Type::Any,
+                }
+            }
+            Expr::Array(_) => Type::Array(Box::new(Type::Any)),
+            Expr::Object(_) => Type::Object(ObjectType::default()),
+            Expr::Fn(_) | Expr::Arrow(_) => {
+                Type::Function(Box::new(FunctionType::new(Vec::new(), Type::Any)))
+            }
+            _ => Type::Any,
+        }
+    }
+
+    fn check_assign(&mut self, assign: &AssignExpr) -> Type {
+        if assign.op != AssignOp::Assign {
+            if let AssignTarget::Simple(SimpleAssignTarget::Member(member)) = &assign.left {
+                self.synth_member(member);
+            }
+            return match self.synth(&assign.right) {
+                Type::String => Type::String,
+                Type::Number => Type::Number,
+                _ => Type::Any,
+            };
+        }
+
+        if let AssignTarget::Simple(SimpleAssignTarget::Ident(ident)) = &assign.left
+            && self.is_top_level_fn(&ident.id.sym)
+        {
+            self.dynamic.insert(ident.id.sym.to_string());
+        }
+        let target = match &assign.left {
+            AssignTarget::Simple(SimpleAssignTarget::Ident(ident)) => self
+                .lookup(&ident.id.sym)
+                .filter(|binding| binding.annotated)
+                .map(|binding| binding.ty.clone()),
+            AssignTarget::Simple(SimpleAssignTarget::Member(member)) => {
+                let ty = self.synth_member(member);
+                root_ident(&member.obj)
+                    .and_then(|root| self.lookup(root))
+                    .filter(|binding| binding.annotated)
+                    .map(|_| ty)
+            }
+            _ => None,
+        };
+        match target {
+            Some(target) => {
+                self.check_expr_against(&assign.right, &target, Site::Value);
+                target
+            }
+            None => self.synth(&assign.right),
+        }
+    }
+
+    // ========================================================================
+    // Compatibility
+    // ========================================================================
+
+    /// Expand aliases, including generic ones
+    fn resolve(&self, ty: &Type) -> Type {
+        match ty {
+            Type::Alias(_) | Type::Generic(..) => self.registry.resolve_alias(ty),
+            _ => ty.clone(),
+        }
+    }
+
+    /// Fields of an object type, interface or class instance
+    fn shape(&self, ty: &Type) -> Option<BTreeMap<String, Type>> {
+        match self.resolve(ty) {
+            Type::Object(obj) => Some(obj.fields),
+            Type::Struct(id) => self
+                .registry
+                .get_struct(id)
+                .map(|def| def.fields.iter().cloned().collect()),
+            Type::Generic(id, args) => {
+                let def = self.registry.get_struct(id)?;
+                let mut ctx = TypeContext::new();
+                for (param, arg) in def.type_params.iter().zip(args) {
+                    ctx.bind_type_var(*param, arg);
+                }
+                Some(
+                    def.fields
+                        .iter()
+                        .map(|(name, ty)| (name.clone(), ctx.substitute(ty)))
+                        .collect(),
+                )
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether a value of type `from` can be used where `to` is expected
+    fn assignable(&self, from: &Type, to: &Type, depth: usize) -> bool {
+        if from == to || depth > MAX_DEPTH {
+            return true;
+        }
+        let (from, to) = (self.resolve(from), self.resolve(to));
+        let next = depth + 1;
+        match (&from, &to) {
+            (
+                _,
+                Type::Any
+                | Type::Error
+                | Type::Infer(_)
+                | Type::TypeVar(_)
+                | Type::Enum(_)
+                | Type::Lifetime(_),
+            ) => true,
+            (
+                Type::Any
+                | Type::Error
+                | Type::Infer(_)
+                | Type::TypeVar(_)
+                | Type::Enum(_)
+                | Type::Never,
+                _,
+            ) => true,
+            (_, Type::Ref(inner) | Type::MutRef(inner))
+            | (_, Type::RefWithLifetime(_, inner) | Type::MutRefWithLifetime(_, inner)) => {
+                self.assignable(from.deref().unwrap_or(&from), inner, next)
+            }
+            (Type::Ref(inner) | Type::MutRef(inner), _)
+            | (Type::RefWithLifetime(_, inner) | Type::MutRefWithLifetime(_, inner), _) => {
+                self.assignable(inner, &to, next)
+            }
+            // Nothing is narrowed, so any member may be the one present
+            (Type::Union(members), _) => members.iter().any(|m| self.assignable(m, &to, next)),
+            (_, Type::Union(members)) => members.iter().any(|m| self.assignable(&from, m, next)),
+            (Type::Null | Type::Void, Type::Null | Type::Void) => true,
+            (Type::Array(a), Type::Array(b)) => self.assignable(a, b, next),
+            (Type::Function(f), Type::Function(g)) => self.function_assignable(f, g, next),
+            (
+                Type::Array(_) | Type::TypedArray(_) | Type::Function(_),
+                Type::Object(_) | Type::Struct(_),
+            ) => true,
+            (Type::Generic(a, x), Type::Generic(b, y)) if a == b && x.len() == y.len() => {
+                x.iter().zip(y).all(|(x, y)| self.assignable(x, y, next))
+            }
+            // Instances of generics this pass knows nothing about
+            (Type::Generic(..), _) if self.shape(&from).is_none() => true,
+            (_, Type::Generic(..)) if self.shape(&to).is_none() => true,
+            (
+                Type::Object(_) | Type::Struct(_) | Type::Generic(..),
+                Type::Object(_) | Type::Struct(_) | Type::Generic(..),
+            ) => {
+                let (Some(have), Some(want)) = (self.shape(&from), self.shape(&to)) else {
+                    return true;
+                };
+                // Instances may also have fields their constructor adds with
+                // `this.x = ...`
+                let open = match &from {
+                    Type::Struct(id) | Type::Generic(id, _) => self.classes.contains_key(id),
+                    _ => false,
+                };
+                want.iter().all(|(name, ty)| match have.get(name) {
+                    Some(field) => self.assignable(field, ty, next),
+                    None => open || accepts_missing(&self.resolve(ty)),
+                })
+            }
+            _ => false,
+        }
+    }
+
+    fn function_assignable(&self, f: &FunctionType, g: &FunctionType, depth: usize) -> bool {
+        // A function may ignore arguments, but can't require more than it gets
+        let extra = f
+            .params
+            .iter()
+            .skip(g.params.len())
+            .any(|(name, ty)| !name.starts_with("...") && !accepts_missing(&self.resolve(ty)));
+        if extra {
+            return false;
+        }
+        let params =
+            f.params.iter().zip(&g.params).all(|((_, a), (_, b))| {
+                self.assignable(b, a, depth) || self.assignable(a, b, depth)
+            });
+        params
+            && (matches!(g.return_ty, Type::Void)
+                || self.assignable(&f.return_ty, &g.return_ty, depth))
+    }
+
+    /// A required field of `to` that a `from` lacks, when both are shapes
+    fn missing_field(&self, from: &Type, to: &Type) -> Option<String> {
+        let (have, want) = (self.shape(from)?, self.shape(to)?);
+        if let Type::Struct(id) | Type::Generic(id, _) = self.resolve(from)
+            && self.classes.contains_key(&id)
+        {
+            return None;
+        }
+        want.into_iter()
+            .find(|(name, ty)| !have.contains_key(name) && !accepts_missing(&self.resolve(ty)))
+            .map(|(name, _)| name)
+    }
+
+    /// Whether `ty` is an interface whose fields list every property
+    fn is_closed(&self, ty: &Type) -> bool {
+        match self.resolve(ty) {
+            Type::Struct(id) | Type::Generic(id, _) => self.interfaces.get(&id) == Some(&true),
+            _ => false,
+        }
+    }
+
+    /// `ty` for messages: interfaces, classes and aliases by name, other
+    /// types spelled out
+    fn type_name(&self, ty: &Type) -> String {
+        let name = |id: TypeId| {
+            let def = self.registry.get_struct(id).map(|def| def.name.clone());
+            def.or_else(|| self.registry.get_alias(id).map(|alias| alias.name.clone()))
+        };
+        match ty {
+            Type::Struct(id) | Type::Alias(id) => name(*id),
+            Type::Generic(id, args) => name(*id).map(|name| {
+                let args: Vec<String> = args.iter().map(|arg| self.type_name(arg)).collect();
+                format!("{}<{}>", name, args.join(", "))
+            }),
+            _ => None,
+        }
+        .unwrap_or_else(|| self.describe(ty).to_string())
+    }
+
+    /// `ty` with aliases and interfaces spelled out, for messages
+    fn describe(&self, ty: &Type) -> Type {
+        self.describe_at(ty, 0)
+    }
+
+    fn describe_at(&self, ty: &Type, depth: usize) -> Type {
+        if depth > 2 {
+            return ty.clone();
+        }
+        match self.resolve(ty) {
+            Type::Struct(id) => match self.registry.get_struct(id) {
+                Some(def) => Type::Object(ObjectType {
+                    fields: def
+                        .fields
+                        .iter()
+                        .map(|(name, ty)| (name.clone(), self.describe_at(ty, depth + 1)))
+                        .collect(),
+                    exact: false,
+                }),
+                None => ty.clone(),
+            },
+            Type::Object(obj) => Type::Object(ObjectType {
+                fields: obj
+                    .fields
+                    .iter()
+                    .map(|(name, ty)| (name.clone(), self.describe_at(ty, depth + 1)))
+                    .collect(),
+                exact: obj.exact,
+            }),
+            generic @ Type::Generic(..) => match self.shape(&generic) {
+                Some(fields) => Type::Object(ObjectType {
+                    fields: fields
+                        .iter()
+                        .map(|(name, ty)| (name.clone(), self.describe_at(ty, depth + 1)))
+                        .collect(),
+                    exact: false,
+                }),
+                None => generic,
+            },
+            Type::Array(elem) => Type::Array(Box::new(self.describe_at(&elem, depth + 1))),
+            Type::Union(members) => {
+                Type::Union(members.iter().map(|m| self.describe_at(m, depth)).collect())
+            }
+            other => other,
+        }
+    }
+
+    fn span(&self, span: swc_common::Span) -> Span {
+        let loc = self.cm.lookup_char_pos(span.lo);
+        Span::new(
+            span.lo.0,
+            span.hi.0,
+            (loc.line as u32).saturating_sub(1),
+            loc.col.0 as u32,
+        )
+    }
+}
+
+fn stmt_decl(stmt: &Stmt) -> Option<&Decl> {
+    match stmt {
+        Stmt::Decl(decl) => Some(decl),
+        _ => None,
+    }
+}
+
+/// A constructor parameter as a plain pattern
+fn ctor_param(param: &ParamOrTsParamProp) -> Pat {
+    match param {
+        ParamOrTsParamProp::Param(param) => param.pat.clone(),
+        ParamOrTsParamProp::TsParamProp(prop) => match &prop.param {
+            TsParamPropParam::Ident(ident) => Pat::Ident(ident.clone()),
+            TsParamPropParam::Assign(assign) => Pat::Assign(assign.clone()),
+        },
+    }
+}
+
+fn is_public(accessibility: Option<Accessibility>) -> bool {
+    matches!(accessibility, None | Some(Accessibility::Public))
+}
+
+fn prop_name(key: &PropName) -> Option<String> {
+    match key {
+        PropName::Ident(ident) => Some(ident.sym.to_string()),
+        PropName::Str(s) => Some(String::from_utf8_lossy(s.value.as_bytes()).into_owned()),
+        PropName::Num(n) => Some(n.value.to_string()),
+        _ => None,
+    }
+}
+
+/// The variable at the base of `a.b.c`
+fn root_ident(expr: &Expr) -> Option<&str> {
+    match expr {
+        Expr::Ident(ident) => Some(&*ident.sym),
+        Expr::Member(member) => root_ident(&member.obj),
+        Expr::Paren(paren) => root_ident(&paren.expr),
+        _ => None,
+    }
+}
+
+/// Whether a value may be left out where `ty` is expected
+fn accepts_missing(ty: &Type) -> bool {
+    match ty {
+        Type::Any
+        | Type::Null
+        | Type::Void
+        | Type::Error
+        | Type::TypeVar(_)
+        | Type::Infer(_)
+        | Type::Generic(..) => true,
+        Type::Union(members) => members.iter().any(accepts_missing),
+        _ => false,
+    }
+}
+
+fn strip_null(ty: &Type) -> Type {
+    match ty {
+        Type::Union(members) => Type::union(members.iter().filter(|m| **m != Type::Null).cloned()),
+        other => other.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use swc_ecma_parser::TsSyntax;
+
+    fn errors(source: &str) -> Vec<String> {
+        check_source(source, Syntax::Typescript(TsSyntax::default()))
+            .iter()
+            .map(|e| e.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_reports_values_that_cannot_fit() {
+        let found = errors(
+            r#"
+interface Point { x: number; y: number; label?: string }
+function dist(p: Point, scale: number): number {
+    return "far";
+}
+let n: number = "one";
+let xs: number[] = [1, "two"];
+const origin: Point = { x: 0 };
+dist({ x: 1, y: 2 }, "3");
+dist({ x: 1, y: 2 });
+let pick: (a: number) => string = (a) => a * 2;
+let maybe: string | null = 4;
+"#,
+        );
+        assert_eq!(
+            found,
+            vec![
+                "return type mismatch at 4:12: expected number, got string",
+                "type mismatch at 6:17: expected number, got string",
+                "type mismatch at 7:24: expected number, got string",
+                "missing field 'y' required by type 'Point' at 8:23",
+                "type mismatch at 9:22: expected number, got string",
+                "wrong number of arguments at 10:1: expected 2, got 1",
+                "return type mismatch at 11:42: expected string, got number",
+                "type mismatch at 12:28: expected string | null, got number",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_position_left_out_under_a_file_prefix() {
+        let found = check_source(
+            "let n: number = \"one\";",
+            Syntax::Typescript(TsSyntax::default()),
+        );
+        assert_eq!(
+            found[0].to_string(),
+            "type mismatch at 1:17: expected number, got string"
+        );
+        assert_eq!(
+            format!("{:#}", found[0]),
+            "type mismatch: expected number, got string"
+        );
+    }
+
+    #[test]
+    fn test_accepts_well_typed_and_untyped_code() {
+        let found = errors(
+            r#"
+type Shape = { kind: string; size?: number };
+function area(s: Shape, ...rest: number[]): number {
+    return s.size ?? 0;
+}
+function first<T>(items: T[]): T { return items[0]; }
+function untyped(a, b) { return a + b; }
+const shapes: Shape[] = [{ kind: "square", size: 2 }, { kind: "dot" }];
+let total: number = area(shapes[0], 1, 2, 3);
+let label: string | number = "x";
+label = 5;
+let id: string = first(["a"]);
+let anything = untyped(1);
+anything = "changed";
+let count: number = anything;
+const cb: (n: number) => void = (n) => console.log(n + 1);
+let text: string = `${total}`;
+let big: number = total > 10 ? total : 0;
+"#,
+        );
+        assert!(found.is_empty(), "{:?}", found);
+    }
+
+    #[test]
+    fn test_checks_interfaces_structurally() {
+        let found = errors(
+            r#"
+interface Named { name: string }
+interface Person extends Named { age: number; greet(): string; "home-town"?: string }
+interface Tree { value: number; children: Tree[] }
+interface Dict { [key: string]: number }
+const p: Person = { name: "a", age: 1, greet() { return "hi"; }, nick: "x" };
+const q: Person = { age: 2, greet: () => "yo" };
+const r: Named = p;
+const s: Person = r;
+const t: Tree = { value: 1, children: [{ value: "2", children: [] }] };
+const d: Dict = { a: 1, b: 2 };
+interface Named { id?: number }
+const u: Named = { name: "n", id: 3 };
+"#,
+        );
+        assert_eq!(
+            found,
+            vec![
+                "unknown field 'nick' for type 'Person' at 6:66",
+                "missing field 'name' required by type 'Person' at 7:19",
+                "missing field 'age' required by type 'Person' at 9:19",
+                "type mismatch at 10:49: expected number, got string",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_instantiates_generic_functions_and_classes() {
+        let found = errors(
+            r#"
+function id<T>(x: T): T { return x; }
+class Box<T> {
+    value: T;
+    constructor(value: T) { this.value = value; }
+}
+let s: string = id(5);
+let n: number = id<number>("x");
+const b = new Box<number>("x");
+const c = new Box(1);
+let t: string = c.value;
+let u: Box<string> = c;
+let ok: Box<number> = c;
+let w: number = id(c).value;
+"#,
+        );
+        assert_eq!(
+            found,
+            vec![
+                "type mismatch at 7:17: expected string, got number",
+                "type mismatch at 8:28: expected number, got string",
+                "type mismatch at 9:27: expected number, got string",
+                "type mismatch at 11:17: expected string, got number",
+                "type mismatch at 12:22: expected { value: string }, got { value: number }",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_generic_class_example_from_the_docs() {
+        let found = errors(
+            r#"
+class Box<T> {
+    value: T;
+    constructor(value: T) { this.value = value; }
+}
+
+const boxed = new Box(1);            // Box<number>
+let label: string = boxed.value;     // error: expected string, got number
+let loose = new Box(1);
+let unchecked: string = loose.value;
+"#,
+        );
+        assert_eq!(
+            found,
+            vec!["type mismatch at 8:21: expected string, got number"]
+        );
+    }
+
+    #[test]
+    fn test_hints_cover_only_typed_direct_calls() {
+        let analysis = analyze_source(
+            r#"
+function add(a, b) { return a + b; }
+function greet(name) { return "hi " + name; }
+function inc(n) { return n + 1; }
+function twice(f, x) { return f(f(x)); }
+function later(x) { return x; }
+export function shared(x) { return x; }
+add(1, 2);
+add(3, 4);
+greet("a");
+greet(`b`);
+greet(5);
+twice(inc, 1);
+let v = 1;
+later(v);
+shared(1);
+"#,
+            Syntax::Typescript(TsSyntax::default()),
+        );
+        let names: Vec<&str> = analysis.hints.iter().map(|h| h.function.as_str()).collect();
+        assert_eq!(names, vec!["add", "greet", "greet", "twice"]);
+        assert_eq!(analysis.hints[0].params, vec![Type::Number, Type::Number]);
+        assert_eq!(analysis.hints[1].params, vec![Type::String]);
+        assert_eq!(analysis.hints[2].params, vec![Type::Number]);
+    }
+
+    #[test]
+    fn test_collects_top_level_signatures() {
+        let analysis = analyze_source(
+            r#"
+export function scale(v: number, by: number): number { return v * by; }
+function label(name: string) { return name; }
+function twice(x: number): number;
+function twice(x: string): string;
+function twice(x) { return x; }
+function outer() { function inner(a: number) {} }
+"#,
+            Syntax::Typescript(TsSyntax::default()),
+        );
+        let names: Vec<&str> = analysis.signatures.keys().map(String::as_str).collect();
+        assert_eq!(names, vec!["label", "outer", "scale"]);
+        let scale = &analysis.signatures["scale"];
+        assert_eq!(
+            scale.params,
+            vec![
+                ("v".to_string(), Type::Number),
+                ("by".to_string(), Type::Number)
+            ]
+        );
+        assert_eq!(scale.return_ty, Type::Number);
+        assert_eq!(analysis.signatures["label"].params[0].1, Type::String);
+    }
+}
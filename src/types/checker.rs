@@ -9,11 +9,13 @@
 use std::collections::BTreeMap;
 use swc_ecma_ast::*;
 
-use super::convert::TypeConverter;
+use super::convert::{TypeConverter, extract_type_param_names};
 use super::error::{Span, TypeError, TypeErrors};
-use super::inference::{InferenceEngine, TypeNarrower};
+use super::inference::{ElisionResult, InferenceEngine, TypeNarrower, apply_lifetime_elision};
 use super::registry::TypeRegistry;
-use super::{FunctionType, ObjectType, Ownership, Type, TypeVarId, VarType, fresh_type_var_id};
+use super::{
+    FunctionType, ObjectType, Ownership, Type, TypeId, TypeVarId, VarType, fresh_type_var_id,
+};
 
 // ============================================================================
 // Type Checker
@@ -118,88 +120,8 @@ impl<'a> TypeChecker<'a> {
     // ========================================================================
 
     fn collect_type_def(&mut self, stmt: &Stmt) {
-        // Look for type declarations
-        match stmt {
-            Stmt::Decl(Decl::TsTypeAlias(alias)) => {
-                let name = alias.id.sym.to_string();
-                let id = super::fresh_type_id();
-
-                // Convert type params
-                let type_params: Vec<TypeVarId> = alias
-                    .type_params
-                    .as_ref()
-                    .map(|p| p.params.iter().map(|_| fresh_type_var_id()).collect())
-                    .unwrap_or_default();
-
-                let param_names: Vec<String> = alias
-                    .type_params
-                    .as_ref()
-                    .map(|p| {
-                        p.params
-                            .iter()
-                            .map(|param| param.name.sym.to_string())
-                            .collect()
-                    })
-                    .unwrap_or_default();
-
-                // Convert the aliased type
-                let converter =
-                    TypeConverter::new(self.registry).with_type_params(&type_params, &param_names);
-
-                if let Ok(ty) = converter.convert(&alias.type_ann) {
-                    let type_alias = super::TypeAlias {
-                        id,
-                        name: name.clone(),
-                        ty,
-                        type_params,
-                    };
-                    self.registry.register_alias(type_alias);
-                }
-            }
-            Stmt::Decl(Decl::TsInterface(iface)) => {
-                // Convert interface to struct type
-                let name = iface.id.sym.to_string();
-                let id = super::fresh_type_id();
-
-                let type_params: Vec<TypeVarId> = iface
-                    .type_params
-                    .as_ref()
-                    .map(|p| p.params.iter().map(|_| fresh_type_var_id()).collect())
-                    .unwrap_or_default();
-
-                let mut def = super::StructDef::new(id, name).with_type_params(type_params.clone());
-
-                let param_names: Vec<String> = iface
-                    .type_params
-                    .as_ref()
-                    .map(|p| {
-                        p.params
-                            .iter()
-                            .map(|param| param.name.sym.to_string())
-                            .collect()
-                    })
-                    .unwrap_or_default();
-
-                let converter =
-                    TypeConverter::new(self.registry).with_type_params(&type_params, &param_names);
-
-                for member in &iface.body.body {
-                    if let TsTypeElement::TsPropertySignature(prop) = member
-                        && let Expr::Ident(ident) = &*prop.key
-                    {
-                        let field_name = ident.sym.to_string();
-                        let field_ty = prop
-                            .type_ann
-                            .as_ref()
-                            .and_then(|ann| converter.convert(&ann.type_ann).ok())
-                            .unwrap_or(Type::Any);
-                        def = def.with_field(field_name, field_ty);
-                    }
-                }
-
-                self.registry.register_struct(def);
-            }
-            _ => {}
+        if let Stmt::Decl(decl) = stmt {
+            register_type_decl(self.registry, decl);
         }
     }
 
@@ -245,7 +167,8 @@ impl<'a> TypeChecker<'a> {
                 })
                 .unwrap_or(Type::Void);
 
-            let func_type = FunctionType::new(params.clone(), return_ty.clone());
+            let span = Span::from_range(fn_decl.function.span.lo.0, fn_decl.function.span.hi.0);
+            let func_type = self.elide_lifetimes(&name, FunctionType::new(params, return_ty), span);
 
             // Register in context
             self.inference
@@ -254,6 +177,31 @@ impl<'a> TypeChecker<'a> {
         }
     }
 
+    /// Fill in elided lifetimes, reporting signatures whose returned
+    /// reference has no single source.
+    fn elide_lifetimes(&mut self, name: &str, func: FunctionType, span: Span) -> FunctionType {
+        match apply_lifetime_elision(&func, span) {
+            ElisionResult::Applied(func) | ElisionResult::NoElisionNeeded(func) => func,
+            ElisionResult::AmbiguousLifetime { span, candidates } => {
+                self.errors.push(TypeError::AmbiguousLifetime {
+                    function: name.to_string(),
+                    candidates,
+                    span,
+                });
+                func
+            }
+        }
+    }
+
+    fn convert_annotation(&mut self, ann: Option<&TsTypeAnn>) -> Type {
+        ann.and_then(|ann| {
+            TypeConverter::new(self.registry)
+                .convert(&ann.type_ann)
+                .ok()
+        })
+        .unwrap_or(Type::Any)
+    }
+
     // ========================================================================
     // Statement Checking
     // ========================================================================
@@ -737,25 +685,32 @@ impl<'a> TypeChecker<'a> {
                                 _ => None,
                             };
                             if let Some(name) = name {
-                                // Infer method type
+                                // Method type from its annotations
                                 let params: Vec<(String, Type)> = method
                                     .function
                                     .params
                                     .iter()
-                                    .map(|p| {
-                                        let param_name = match &p.pat {
-                                            Pat::Ident(ident) => ident.id.sym.to_string(),
-                                            _ => "_".to_string(),
-                                        };
-                                        (param_name, Type::Any)
+                                    .map(|p| match &p.pat {
+                                        Pat::Ident(ident) => (
+                                            ident.id.sym.to_string(),
+                                            self.convert_annotation(ident.type_ann.as_deref()),
+                                        ),
+                                        _ => ("_".to_string(), Type::Any),
                                     })
                                     .collect();
 
-                                let return_ty = Type::Any;
-                                fields.insert(
-                                    name,
-                                    Type::Function(Box::new(FunctionType::new(params, return_ty))),
+                                let return_ty =
+                                    self.convert_annotation(method.function.return_type.as_deref());
+                                let span = Span::from_range(
+                                    method.function.span.lo.0,
+                                    method.function.span.hi.0,
                                 );
+                                let method_ty = self.elide_lifetimes(
+                                    &name,
+                                    FunctionType::new(params, return_ty).as_method(),
+                                    span,
+                                );
+                                fields.insert(name, Type::Function(Box::new(method_ty)));
                             }
                         }
                         _ => {}
@@ -966,6 +921,118 @@ impl<'a> TypeChecker<'a> {
     }
 }
 
+/// Register a `type` alias or `interface` declaration under its name
+pub fn register_type_decl(registry: &mut TypeRegistry, decl: &Decl) {
+    match decl {
+        Decl::TsTypeAlias(alias) => {
+            let name = alias.id.sym.to_string();
+            let id = super::fresh_type_id();
+
+            // Convert type params
+            let type_params: Vec<TypeVarId> = alias
+                .type_params
+                .as_ref()
+                .map(|p| p.params.iter().map(|_| fresh_type_var_id()).collect())
+                .unwrap_or_default();
+
+            let param_names: Vec<String> = alias
+                .type_params
+                .as_ref()
+                .map(|p| {
+                    p.params
+                        .iter()
+                        .map(|param| param.name.sym.to_string())
+                        .collect()
+                })
+                .unwrap_or_default();
+
+            // Convert the aliased type
+            let converter =
+                TypeConverter::new(registry).with_type_params(&type_params, &param_names);
+
+            if let Ok(ty) = converter.convert(&alias.type_ann) {
+                let type_alias = super::TypeAlias {
+                    id,
+                    name: name.clone(),
+                    ty,
+                    type_params,
+                };
+                registry.register_alias(type_alias);
+            }
+        }
+        Decl::TsInterface(iface) => {
+            let id = declare_interface(registry, iface);
+            define_interface(registry, id, iface);
+        }
+        _ => {}
+    }
+}
+
+/// Register an interface's name and type parameters with no fields yet, so
+/// that interfaces declared together can refer to each other
+pub fn declare_interface(registry: &mut TypeRegistry, iface: &TsInterfaceDecl) -> TypeId {
+    let type_params: Vec<TypeVarId> = iface
+        .type_params
+        .as_ref()
+        .map(|p| p.params.iter().map(|_| fresh_type_var_id()).collect())
+        .unwrap_or_default();
+    let def = super::StructDef::new(super::fresh_type_id(), iface.id.sym.to_string())
+        .with_type_params(type_params);
+    registry.register_struct(def)
+}
+
+/// Add the members of an interface declared as `id`, after those of the
+/// interfaces it extends. A member declared again (by a merged declaration
+/// or over an inherited one) replaces the earlier field. Returns whether
+/// the fields describe every property: index signatures, setters, computed
+/// keys and bases that aren't known interfaces mean they don't.
+pub fn define_interface(registry: &mut TypeRegistry, id: TypeId, iface: &TsInterfaceDecl) -> bool {
+    let Some(def) = registry.get_struct(id) else {
+        return false;
+    };
+    let param_names = extract_type_param_names(&iface.type_params);
+    let converter = TypeConverter::new(registry).with_type_params(&def.type_params, &param_names);
+
+    let mut complete = true;
+    let mut fields = Vec::new();
+    for base in &iface.extends {
+        let base_def = match &*base.expr {
+            Expr::Ident(ident) => registry
+                .lookup_by_name(&ident.sym)
+                .and_then(|base_id| registry.get_struct(base_id)),
+            _ => None,
+        };
+        let Some(base_def) = base_def else {
+            complete = false;
+            continue;
+        };
+        let mut ctx = super::TypeContext::new();
+        let args = base.type_args.iter().flat_map(|args| &args.params);
+        for (param, arg) in base_def.type_params.iter().zip(args) {
+            ctx.bind_type_var(*param, converter.convert(arg).unwrap_or(Type::Any));
+        }
+        for (name, ty) in &base_def.fields {
+            fields.push((name.clone(), ctx.substitute(ty)));
+        }
+    }
+    for member in &iface.body.body {
+        match converter.convert_member(member) {
+            Some((name, ty)) => fields.push((name, ty.unwrap_or(Type::Any))),
+            None => complete = false,
+        }
+    }
+
+    if let Some(def) = registry.get_struct_mut(id) {
+        for (name, ty) in fields {
+            match def.fields.iter_mut().find(|(field, _)| *field == name) {
+                Some(field) => field.1 = ty,
+                None => def.fields.push((name, ty)),
+            }
+        }
+    }
+    complete
+}
+
 // ============================================================================
 // Tests
 // ============================================================================
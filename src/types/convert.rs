@@ -1,9 +1,9 @@
 //! Converts SWC TypeScript AST to tscl Type representation.
 
 use swc_ecma_ast::{
-    Expr, TsArrayType, TsFnOrConstructorType, TsFnParam, TsKeywordType, TsKeywordTypeKind, TsLit,
-    TsLitType, TsType, TsTypeAnn, TsTypeLit, TsTypeParamDecl, TsTypeParamInstantiation, TsTypeRef,
-    TsUnionOrIntersectionType, TsUnionType,
+    Expr, Lit, Pat, TsArrayType, TsFnOrConstructorType, TsFnParam, TsKeywordType,
+    TsKeywordTypeKind, TsLit, TsLitType, TsType, TsTypeAnn, TsTypeElement, TsTypeLit,
+    TsTypeParamDecl, TsTypeParamInstantiation, TsTypeRef, TsUnionOrIntersectionType, TsUnionType,
 };
 
 use super::error::{Span, TypeError};
@@ -13,6 +13,8 @@ use super::{
 };
 use std::collections::HashMap;
 
+use crate::runtime::heap::ElementKind;
+
 pub struct TypeConverter<'a> {
     registry: &'a TypeRegistry,
     type_vars: HashMap<String, TypeVarId>,
@@ -66,6 +68,14 @@ impl<'a> TypeConverter<'a> {
             TsType::TsTypeRef(ref_) => self.convert_type_ref(ref_),
             TsType::TsFnOrConstructorType(fn_type) => self.convert_fn_type(fn_type),
             TsType::TsTypeLit(lit) => self.convert_type_lit(lit),
+            TsType::TsParenthesizedType(paren) => self.convert(&paren.type_ann),
+            // Literal types widen to their primitive
+            TsType::TsLitType(lit) => match &lit.lit {
+                TsLit::Number(_) => Ok(Type::Number),
+                TsLit::Str(_) | TsLit::Tpl(_) => Ok(Type::String),
+                TsLit::Bool(_) => Ok(Type::Boolean),
+                TsLit::BigInt(_) => Ok(Type::Number),
+            },
             TsType::TsUnionOrIntersectionType(union) => {
                 match union {
                     TsUnionOrIntersectionType::TsUnionType(u) => self.convert_union(u),
@@ -86,20 +96,12 @@ impl<'a> TypeConverter<'a> {
     }
 
     fn convert_union(&self, union: &TsUnionType) -> Result<Type, TypeError> {
-        let first_non_nullable = union.types.iter().find(|ty| {
-            !matches!(
-                ty.as_ref(),
-                TsType::TsKeywordType(kw) if matches!(
-                    kw.kind,
-                    TsKeywordTypeKind::TsNullKeyword | TsKeywordTypeKind::TsUndefinedKeyword
-                )
-            )
-        });
-
-        match first_non_nullable {
-            Some(ty) => self.convert(ty),
-            None => Ok(Type::Void),
-        }
+        let members = union
+            .types
+            .iter()
+            .map(|ty| self.convert(ty))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Type::union(members))
     }
 
     fn convert_keyword(&self, kw: &TsKeywordType) -> Result<Type, TypeError> {
@@ -110,8 +112,8 @@ impl<'a> TypeConverter<'a> {
             TsKeywordTypeKind::TsVoidKeyword => Ok(Type::Void),
             TsKeywordTypeKind::TsNeverKeyword => Ok(Type::Never),
             TsKeywordTypeKind::TsAnyKeyword => Ok(Type::Any),
-            TsKeywordTypeKind::TsUndefinedKeyword => Ok(Type::Void),
-            TsKeywordTypeKind::TsNullKeyword => Ok(Type::Void),
+            TsKeywordTypeKind::TsUndefinedKeyword => Ok(Type::Null),
+            TsKeywordTypeKind::TsNullKeyword => Ok(Type::Null),
             TsKeywordTypeKind::TsUnknownKeyword => Ok(Type::Any),
             TsKeywordTypeKind::TsObjectKeyword => Ok(Type::Object(ObjectType::default())),
             _ => Err(TypeError::UnsupportedType {
@@ -156,6 +158,12 @@ impl<'a> TypeConverter<'a> {
             _ => {}
         }
 
+        if let Some(kind) = ElementKind::from_name(&name)
+            && self.registry.lookup_by_name(&name).is_none()
+        {
+            return Ok(Type::TypedArray(kind));
+        }
+
         if let Some(&var_id) = self.type_vars.get(&name) {
             return Ok(Type::TypeVar(var_id));
         }
@@ -265,26 +273,41 @@ impl<'a> TypeConverter<'a> {
         }
     }
 
+    /// Parameters of a function type. Optional parameters admit `null`, and a
+    /// rest parameter is named with a leading `...`.
     fn convert_fn_params(&self, params: &[TsFnParam]) -> Result<Vec<(String, Type)>, TypeError> {
         params
             .iter()
             .map(|p| {
-                let name = match p {
-                    TsFnParam::Ident(ident) => ident.id.sym.to_string(),
-                    TsFnParam::Array(_) => "_".to_string(),
-                    TsFnParam::Rest(_) => "rest".to_string(),
-                    TsFnParam::Object(_) => "_".to_string(),
-                };
-                let ty = match p {
-                    TsFnParam::Ident(ident) => ident
-                        .type_ann
-                        .as_ref()
+                let convert_ann = |ann: &Option<Box<TsTypeAnn>>| {
+                    ann.as_ref()
                         .map(|ann| self.convert(&ann.type_ann))
-                        .transpose()?
-                        .unwrap_or(Type::Any),
-                    _ => Type::Any,
+                        .transpose()
+                        .map(|ty| ty.unwrap_or(Type::Any))
                 };
-                Ok((name, ty))
+                match p {
+                    TsFnParam::Ident(ident) => {
+                        let ty = convert_ann(&ident.type_ann)?;
+                        let ty = if ident.id.optional {
+                            Type::union([ty, Type::Null])
+                        } else {
+                            ty
+                        };
+                        Ok((ident.id.sym.to_string(), ty))
+                    }
+                    TsFnParam::Rest(rest) => {
+                        let name = match &*rest.arg {
+                            Pat::Ident(ident) => format!("...{}", ident.id.sym),
+                            _ => "...rest".to_string(),
+                        };
+                        let ty = match convert_ann(&rest.type_ann)? {
+                            Type::Any => Type::Array(Box::new(Type::Any)),
+                            ty => ty,
+                        };
+                        Ok((name, ty))
+                    }
+                    TsFnParam::Array(_) | TsFnParam::Object(_) => Ok(("_".to_string(), Type::Any)),
+                }
             })
             .collect()
     }
@@ -313,18 +336,8 @@ impl<'a> TypeConverter<'a> {
         let mut fields = std::collections::BTreeMap::new();
 
         for member in &lit.members {
-            if let swc_ecma_ast::TsTypeElement::TsPropertySignature(prop) = member {
-                let name = match &*prop.key {
-                    Expr::Ident(ident) => ident.sym.to_string(),
-                    _ => continue,
-                };
-                let ty = prop
-                    .type_ann
-                    .as_ref()
-                    .map(|ann| self.convert(&ann.type_ann))
-                    .transpose()?
-                    .unwrap_or(Type::Any);
-                fields.insert(name, ty);
+            if let Some((name, ty)) = self.convert_member(member) {
+                fields.insert(name, ty?);
             }
         }
 
@@ -333,6 +346,69 @@ impl<'a> TypeConverter<'a> {
             exact: false,
         }))
     }
+
+    /// Field declared by a member of an interface or type literal: a
+    /// property, method or getter with a plain or string key. Optional
+    /// members admit `null`. Other members (index and call signatures,
+    /// setters, computed keys) declare no field.
+    pub fn convert_member(
+        &self,
+        member: &TsTypeElement,
+    ) -> Option<(String, Result<Type, TypeError>)> {
+        let key_name = |key: &Expr| match key {
+            Expr::Ident(ident) => Some(ident.sym.to_string()),
+            Expr::Lit(Lit::Str(s)) => Some(s.value.to_string_lossy().into_owned()),
+            _ => None,
+        };
+        let convert_ann =
+            |ann: &Option<Box<TsTypeAnn>>, optional: bool| -> Result<Type, TypeError> {
+                let ty = ann
+                    .as_ref()
+                    .map(|ann| self.convert(&ann.type_ann))
+                    .transpose()?
+                    .unwrap_or(Type::Any);
+                Ok(if optional {
+                    Type::union([ty, Type::Null])
+                } else {
+                    ty
+                })
+            };
+
+        match member {
+            TsTypeElement::TsPropertySignature(prop) if !prop.computed => {
+                let name = key_name(&prop.key)?;
+                Some((name, convert_ann(&prop.type_ann, prop.optional)))
+            }
+            TsTypeElement::TsMethodSignature(method) if !method.computed => {
+                let name = key_name(&method.key)?;
+                // Generic methods aren't modelled, and a method this can't
+                // convert is still a member
+                let ty = match &method.type_params {
+                    Some(_) => Type::Any,
+                    None => self
+                        .convert_fn_params(&method.params)
+                        .and_then(|params| {
+                            let return_ty = convert_ann(&method.type_ann, false)?;
+                            Ok(Type::Function(Box::new(FunctionType::new(
+                                params, return_ty,
+                            ))))
+                        })
+                        .unwrap_or(Type::Any),
+                };
+                let ty = if method.optional {
+                    Type::union([ty, Type::Null])
+                } else {
+                    ty
+                };
+                Some((name, Ok(ty)))
+            }
+            TsTypeElement::TsGetterSignature(getter) if !getter.computed => {
+                let name = key_name(&getter.key)?;
+                Some((name, convert_ann(&getter.type_ann, false)))
+            }
+            _ => None,
+        }
+    }
 }
 
 pub fn extract_type_param_names(params: &Option<Box<TsTypeParamDecl>>) -> Vec<String> {
@@ -72,6 +72,13 @@ pub enum TypeError {
         name: String,
         span: Span,
     },
+    /// A returned reference whose lifetime elision can't pick: the
+    /// reference parameters it might borrow from (none if there are none)
+    AmbiguousLifetime {
+        function: String,
+        candidates: Vec<String>,
+        span: Span,
+    },
     NotCallable {
         ty: Type,
         span: Span,
@@ -109,6 +116,18 @@ pub enum TypeError {
         field: String,
         span: Span,
     },
+    /// A required field absent from a value. `ty` names the expected type.
+    MissingField {
+        ty: String,
+        field: String,
+        span: Span,
+    },
+    /// An object literal field the expected interface doesn't declare
+    UnknownField {
+        ty: String,
+        field: String,
+        span: Span,
+    },
     NotIndexable {
         ty: Type,
         span: Span,
@@ -167,7 +186,10 @@ pub enum TypeError {
 }
 
 impl fmt::Display for TypeError {
+    /// `{:#}` leaves out where the error is, for output that already
+    /// starts with `file:line:col`
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let shown = !f.alternate();
         match self {
             TypeError::Mismatch {
                 expected,
@@ -176,21 +198,49 @@ impl fmt::Display for TypeError {
             } => {
                 write!(
                     f,
-                    "type mismatch at {}: expected {}, got {}",
-                    span, expected, got
+                    "type mismatch{}: expected {}, got {}",
+                    At(span, shown),
+                    expected,
+                    got
                 )
             }
             TypeError::UndefinedVariable { name, span } => {
-                write!(f, "undefined variable '{}' at {}", name, span)
+                write!(f, "undefined variable '{}'{}", name, At(span, shown))
             }
             TypeError::UndefinedType { name, span } => {
-                write!(f, "undefined type '{}' at {}", name, span)
+                write!(f, "undefined type '{}'{}", name, At(span, shown))
             }
             TypeError::UndefinedLifetime { name, span } => {
-                write!(f, "undefined lifetime '{}' at {}", name, span)
+                write!(f, "undefined lifetime '{}'{}", name, At(span, shown))
+            }
+            TypeError::AmbiguousLifetime {
+                function,
+                candidates,
+                span,
+            } if candidates.is_empty() => {
+                write!(
+                    f,
+                    "ambiguous lifetime{}: '{}' returns a reference but takes none to borrow it from; return an owned value or annotate it with RefL<\"static\", T>",
+                    At(span, shown),
+                    function
+                )
+            }
+            TypeError::AmbiguousLifetime {
+                function,
+                candidates,
+                span,
+            } => {
+                let names: Vec<String> = candidates.iter().map(|c| format!("'{}'", c)).collect();
+                write!(
+                    f,
+                    "ambiguous lifetime{}: the reference '{}' returns could borrow from {}; name the one it borrows from with an explicit lifetime (RefL)",
+                    At(span, shown),
+                    function,
+                    names.join(" or ")
+                )
             }
             TypeError::NotCallable { ty, span } => {
-                write!(f, "type '{}' is not callable at {}", ty, span)
+                write!(f, "type '{}' is not callable{}", ty, At(span, shown))
             }
             TypeError::WrongArgCount {
                 expected,
@@ -199,12 +249,14 @@ impl fmt::Display for TypeError {
             } => {
                 write!(
                     f,
-                    "wrong number of arguments at {}: expected {}, got {}",
-                    span, expected, got
+                    "wrong number of arguments{}: expected {}, got {}",
+                    At(span, shown),
+                    expected,
+                    got
                 )
             }
             TypeError::CannotInfer { span } => {
-                write!(f, "cannot infer type at {}", span)
+                write!(f, "cannot infer type{}", At(span, shown))
             }
             TypeError::UseAfterMove {
                 var,
@@ -213,8 +265,10 @@ impl fmt::Display for TypeError {
             } => {
                 write!(
                     f,
-                    "use of moved value '{}' at {} (moved at {})",
-                    var, used_at, moved_at
+                    "use of moved value '{}'{} (moved at {})",
+                    var,
+                    At(used_at, shown),
+                    moved_at
                 )
             }
             TypeError::BorrowConflict {
@@ -225,8 +279,11 @@ impl fmt::Display for TypeError {
             } => {
                 write!(
                     f,
-                    "cannot borrow '{}' as {} at {}: already borrowed as {}",
-                    var, new, span, existing
+                    "cannot borrow '{}' as {}{}: already borrowed as {}",
+                    var,
+                    new,
+                    At(span, shown),
+                    existing
                 )
             }
             TypeError::BorrowOutlives {
@@ -236,26 +293,49 @@ impl fmt::Display for TypeError {
             } => {
                 write!(
                     f,
-                    "borrow of '{}' at {} outlives its scope ending at {}",
-                    var, borrow_span, end_span
+                    "borrow of '{}'{} outlives its scope ending at {}",
+                    var,
+                    At(borrow_span, shown),
+                    end_span
                 )
             }
             TypeError::ImmutableAssignment { var, span } => {
                 write!(
                     f,
-                    "cannot assign to immutable variable '{}' at {}",
-                    var, span
+                    "cannot assign to immutable variable '{}'{}",
+                    var,
+                    At(span, shown)
                 )
             }
             TypeError::FieldNotFound { ty, field, span } => {
                 write!(
                     f,
-                    "field '{}' not found on type '{}' at {}",
-                    field, ty, span
+                    "field '{}' not found on type '{}'{}",
+                    field,
+                    ty,
+                    At(span, shown)
+                )
+            }
+            TypeError::MissingField { ty, field, span } => {
+                write!(
+                    f,
+                    "missing field '{}' required by type '{}'{}",
+                    field,
+                    ty,
+                    At(span, shown)
+                )
+            }
+            TypeError::UnknownField { ty, field, span } => {
+                write!(
+                    f,
+                    "unknown field '{}' for type '{}'{}",
+                    field,
+                    ty,
+                    At(span, shown)
                 )
             }
             TypeError::NotIndexable { ty, span } => {
-                write!(f, "type '{}' is not indexable at {}", ty, span)
+                write!(f, "type '{}' is not indexable{}", ty, At(span, shown))
             }
             TypeError::InvalidBinaryOp {
                 op,
@@ -265,19 +345,24 @@ impl fmt::Display for TypeError {
             } => {
                 write!(
                     f,
-                    "invalid binary operation '{}' between '{}' and '{}' at {}",
-                    op, left, right, span
+                    "invalid binary operation '{}' between '{}' and '{}'{}",
+                    op,
+                    left,
+                    right,
+                    At(span, shown)
                 )
             }
             TypeError::InvalidUnaryOp { op, ty, span } => {
                 write!(
                     f,
-                    "invalid unary operation '{}' on '{}' at {}",
-                    op, ty, span
+                    "invalid unary operation '{}' on '{}'{}",
+                    op,
+                    ty,
+                    At(span, shown)
                 )
             }
             TypeError::NotAssignable { span } => {
-                write!(f, "expression is not assignable at {}", span)
+                write!(f, "expression is not assignable{}", At(span, shown))
             }
             TypeError::TypeArgCountMismatch {
                 expected,
@@ -286,19 +371,27 @@ impl fmt::Display for TypeError {
             } => {
                 write!(
                     f,
-                    "type argument count mismatch at {}: expected {}, got {}",
-                    span, expected, got
+                    "type argument count mismatch{}: expected {}, got {}",
+                    At(span, shown),
+                    expected,
+                    got
                 )
             }
             TypeError::RecursiveType { name, span } => {
                 write!(
                     f,
-                    "recursive type '{}' without indirection at {}",
-                    name, span
+                    "recursive type '{}' without indirection{}",
+                    name,
+                    At(span, shown)
                 )
             }
             TypeError::CannotInferTypeArg { param_name, span } => {
-                write!(f, "cannot infer type argument '{}' at {}", param_name, span)
+                write!(
+                    f,
+                    "cannot infer type argument '{}'{}",
+                    param_name,
+                    At(span, shown)
+                )
             }
             TypeError::ReturnTypeMismatch {
                 expected,
@@ -307,29 +400,82 @@ impl fmt::Display for TypeError {
             } => {
                 write!(
                     f,
-                    "return type mismatch at {}: expected {}, got {}",
-                    span, expected, got
+                    "return type mismatch{}: expected {}, got {}",
+                    At(span, shown),
+                    expected,
+                    got
                 )
             }
             TypeError::MissingReturn { expected, span } => {
                 write!(
                     f,
-                    "missing return statement for type '{}' at {}",
-                    expected, span
+                    "missing return statement for type '{}'{}",
+                    expected,
+                    At(span, shown)
                 )
             }
             TypeError::UnreachableCode { span } => {
-                write!(f, "unreachable code at {}", span)
+                write!(f, "unreachable code{}", At(span, shown))
             }
             TypeError::DuplicateField { name, span } => {
-                write!(f, "duplicate field '{}' at {}", name, span)
+                write!(f, "duplicate field '{}'{}", name, At(span, shown))
             }
             TypeError::DuplicateTypeParam { name, span } => {
-                write!(f, "duplicate type parameter '{}' at {}", name, span)
+                write!(f, "duplicate type parameter '{}'{}", name, At(span, shown))
             }
             TypeError::UnsupportedType { description, span } => {
-                write!(f, "unsupported type {} at {}", description, span)
+                write!(f, "unsupported type {}{}", description, At(span, shown))
+            }
+        }
+    }
+}
+
+/// ` at line:col`, or nothing when the position is shown elsewhere
+struct At<'a>(&'a Span, bool);
+
+impl fmt::Display for At<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.1 {
+            write!(f, " at {}", self.0)?;
+        }
+        Ok(())
+    }
+}
+
+impl TypeError {
+    /// Where to point at the error
+    pub fn span(&self) -> Span {
+        match self {
+            TypeError::UseAfterMove { used_at: span, .. }
+            | TypeError::BorrowOutlives {
+                borrow_span: span, ..
             }
+            | TypeError::Mismatch { span, .. }
+            | TypeError::UndefinedVariable { span, .. }
+            | TypeError::UndefinedType { span, .. }
+            | TypeError::UndefinedLifetime { span, .. }
+            | TypeError::AmbiguousLifetime { span, .. }
+            | TypeError::NotCallable { span, .. }
+            | TypeError::WrongArgCount { span, .. }
+            | TypeError::CannotInfer { span }
+            | TypeError::BorrowConflict { span, .. }
+            | TypeError::ImmutableAssignment { span, .. }
+            | TypeError::FieldNotFound { span, .. }
+            | TypeError::MissingField { span, .. }
+            | TypeError::UnknownField { span, .. }
+            | TypeError::NotIndexable { span, .. }
+            | TypeError::InvalidBinaryOp { span, .. }
+            | TypeError::InvalidUnaryOp { span, .. }
+            | TypeError::NotAssignable { span }
+            | TypeError::TypeArgCountMismatch { span, .. }
+            | TypeError::RecursiveType { span, .. }
+            | TypeError::CannotInferTypeArg { span, .. }
+            | TypeError::ReturnTypeMismatch { span, .. }
+            | TypeError::MissingReturn { span, .. }
+            | TypeError::UnreachableCode { span }
+            | TypeError::DuplicateField { span, .. }
+            | TypeError::DuplicateTypeParam { span, .. }
+            | TypeError::UnsupportedType { span, .. } => *span,
         }
     }
 }
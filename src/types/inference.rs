@@ -200,6 +200,11 @@ impl<'a> InferenceEngine<'a> {
             // Error propagates
             (Type::Error, _) | (_, Type::Error) => Ok(false),
 
+            // A member unifies with its union
+            (Type::Union(members), ty) | (ty, Type::Union(members)) if members.contains(ty) => {
+                Ok(false)
+            }
+
             // Arrays must have same element type
             (Type::Array(e1), Type::Array(e2)) => self.unify(e1, e2, span),
 
@@ -432,6 +437,9 @@ impl<'a> InferenceEngine<'a> {
                 }
             }
             Type::Array(inner) => Type::Array(Box::new(self.apply_substitutions(inner))),
+            Type::Union(members) => {
+                Type::union(members.iter().map(|t| self.apply_substitutions(t)))
+            }
             Type::Ref(inner) => Type::Ref(Box::new(self.apply_substitutions(inner))),
             Type::MutRef(inner) => Type::MutRef(Box::new(self.apply_substitutions(inner))),
             Type::Object(obj) => Type::Object(ObjectType {
@@ -474,6 +482,7 @@ impl<'a> InferenceEngine<'a> {
                 false
             }
             Type::Array(inner) => self.occurs_in(id, inner),
+            Type::Union(members) => members.iter().any(|t| self.occurs_in(id, t)),
             Type::Ref(inner) | Type::MutRef(inner) => self.occurs_in(id, inner),
             Type::Object(obj) => obj.fields.values().any(|t| self.occurs_in(id, t)),
             Type::Function(func) => {
@@ -552,69 +561,99 @@ impl Default for TypeNarrower {
 pub enum ElisionResult {
     Applied(FunctionType),
     NoElisionNeeded(FunctionType),
-    AmbiguousLifetime { span: Span },
+    /// The return type borrows, but the rules can't tell from what.
+    /// `candidates` are the parameters holding references.
+    AmbiguousLifetime {
+        span: Span,
+        candidates: Vec<String>,
+    },
 }
 
+/// Fill in the lifetimes a signature leaves out:
+/// 1. each elided reference among the parameters gets a lifetime of its own;
+/// 2. in a method, elided references in the return type borrow from the
+///    receiver (a leading `self`/`this` parameter, or the implicit `this`);
+/// 3. otherwise, if the parameters hold exactly one lifetime, elided
+///    references in the return type get it.
+///
+/// A return type that borrows in any other case is ambiguous.
 pub fn apply_lifetime_elision(func: &FunctionType, span: Span) -> ElisionResult {
-    if !func.lifetime_params.is_empty() {
+    let elided_inputs = func.params.iter().any(|(_, ty)| has_elided_reference(ty));
+    if !elided_inputs && !has_elided_reference(&func.return_ty) {
         return ElisionResult::NoElisionNeeded(func.clone());
     }
 
-    if !needs_lifetime_elision(func) {
-        return ElisionResult::NoElisionNeeded(func.clone());
-    }
-
-    let input_lifetimes = collect_input_lifetimes(func);
-
-    if func.is_method {
-        return apply_method_elision(func, &input_lifetimes);
+    let mut lifetime_params = func.lifetime_params.clone();
+    let mut receiver = None;
+    let mut inputs: Vec<LifetimeId> = Vec::new();
+    let mut candidates = Vec::new();
+    let mut params = Vec::new();
+    for (index, (name, ty)) in func.params.iter().enumerate() {
+        let is_receiver = func.is_method && index == 0 && (name == "self" || name == "this");
+        let ty = elide_refs(ty, &mut || {
+            let label = if is_receiver { Some("self") } else { None };
+            declare_lifetime(&mut lifetime_params, label)
+        });
+        let mut found = Vec::new();
+        collect_lifetimes_from_type(&ty, &mut found);
+        if is_receiver {
+            receiver = found.first().copied();
+        }
+        if !found.is_empty() {
+            candidates.push(name.clone());
+        }
+        for lifetime in found {
+            if !inputs.contains(&lifetime) {
+                inputs.push(lifetime);
+            }
+        }
+        params.push((name.clone(), ty));
     }
 
-    match input_lifetimes.len() {
-        0 => ElisionResult::NoElisionNeeded(func.clone()),
-        1 => apply_single_input_elision(func, input_lifetimes[0]),
-        _ => ElisionResult::AmbiguousLifetime { span },
-    }
-}
+    let return_ty = if has_elided_reference(&func.return_ty) {
+        let source = match (func.is_method, receiver) {
+            (true, Some(receiver)) => receiver,
+            // The implicit `this` is borrowed for the call
+            (true, None) => declare_lifetime(&mut lifetime_params, Some("self")),
+            (false, _) if inputs.len() == 1 => inputs[0],
+            (false, _) => return ElisionResult::AmbiguousLifetime { span, candidates },
+        };
+        elide_refs(&func.return_ty, &mut || source)
+    } else {
+        func.return_ty.clone()
+    };
 
-fn needs_lifetime_elision(func: &FunctionType) -> bool {
-    has_reference_type(&func.return_ty)
+    ElisionResult::Applied(FunctionType {
+        lifetime_params,
+        type_params: func.type_params.clone(),
+        params,
+        return_ty,
+        is_method: func.is_method,
+    })
 }
 
-fn has_reference_type(ty: &Type) -> bool {
+/// Whether `ty` holds a reference written without a lifetime. References in
+/// nested function types belong to those functions' own signatures.
+fn has_elided_reference(ty: &Type) -> bool {
     match ty {
         Type::Ref(_) | Type::MutRef(_) => true,
-        Type::RefWithLifetime(_, _) | Type::MutRefWithLifetime(_, _) => true,
-        Type::Array(inner) => has_reference_type(inner),
-        Type::Object(obj) => obj.fields.values().any(has_reference_type),
-        Type::Function(f) => {
-            f.params.iter().any(|(_, t)| has_reference_type(t)) || has_reference_type(&f.return_ty)
+        Type::RefWithLifetime(_, inner) | Type::MutRefWithLifetime(_, inner) => {
+            has_elided_reference(inner)
         }
-        Type::Generic(_, args) => args.iter().any(has_reference_type),
+        Type::Array(inner) => has_elided_reference(inner),
+        Type::Object(obj) => obj.fields.values().any(has_elided_reference),
+        Type::Generic(_, args) => args.iter().any(has_elided_reference),
         _ => false,
     }
 }
 
-fn collect_input_lifetimes(func: &FunctionType) -> Vec<LifetimeId> {
-    let mut lifetimes = Vec::new();
-    for (_, ty) in &func.params {
-        collect_lifetimes_from_type(ty, &mut lifetimes);
-    }
-    lifetimes
-}
-
 fn collect_lifetimes_from_type(ty: &Type, lifetimes: &mut Vec<LifetimeId>) {
     match ty {
-        Type::Ref(_) | Type::MutRef(_) => {
-            let fresh = fresh_lifetime_id();
-            if !lifetimes.contains(&fresh) {
-                lifetimes.push(fresh);
-            }
-        }
-        Type::RefWithLifetime(id, _) | Type::MutRefWithLifetime(id, _) => {
+        Type::RefWithLifetime(id, inner) | Type::MutRefWithLifetime(id, inner) => {
             if !lifetimes.contains(id) {
                 lifetimes.push(*id);
             }
+            collect_lifetimes_from_type(inner, lifetimes);
         }
         Type::Array(inner) => collect_lifetimes_from_type(inner, lifetimes),
         Type::Object(obj) => {
@@ -622,12 +661,6 @@ fn collect_lifetimes_from_type(ty: &Type, lifetimes: &mut Vec<LifetimeId>) {
                 collect_lifetimes_from_type(ty, lifetimes);
             }
         }
-        Type::Function(f) => {
-            for (_, t) in &f.params {
-                collect_lifetimes_from_type(t, lifetimes);
-            }
-            collect_lifetimes_from_type(&f.return_ty, lifetimes);
-        }
         Type::Generic(_, args) => {
             for arg in args {
                 collect_lifetimes_from_type(arg, lifetimes);
@@ -637,100 +670,49 @@ fn collect_lifetimes_from_type(ty: &Type, lifetimes: &mut Vec<LifetimeId>) {
     }
 }
 
-fn apply_single_input_elision(func: &FunctionType, input_lifetime: LifetimeId) -> ElisionResult {
-    let lifetime_param = LifetimeParam {
-        id: input_lifetime,
-        name: "a".to_string(),
-        bounds: Vec::new(),
-    };
-
-    let new_return_ty = apply_lifetime_to_refs(&func.return_ty, input_lifetime);
-
-    let new_params: Vec<(String, Type)> = func
-        .params
-        .iter()
-        .map(|(name, ty)| (name.clone(), apply_lifetime_to_refs(ty, input_lifetime)))
-        .collect();
-
-    ElisionResult::Applied(FunctionType {
-        lifetime_params: vec![lifetime_param],
-        type_params: func.type_params.clone(),
-        params: new_params,
-        return_ty: new_return_ty,
-        is_method: func.is_method,
-    })
-}
-
-fn apply_method_elision(func: &FunctionType, input_lifetimes: &[LifetimeId]) -> ElisionResult {
-    let self_lifetime = if input_lifetimes.is_empty() {
-        fresh_lifetime_id()
-    } else {
-        input_lifetimes[0]
-    };
-
-    let lifetime_param = LifetimeParam {
-        id: self_lifetime,
-        name: "self".to_string(),
-        bounds: Vec::new(),
+/// Add a lifetime parameter, named `name` or the next free letter
+fn declare_lifetime(params: &mut Vec<LifetimeParam>, name: Option<&str>) -> LifetimeId {
+    let name = match name {
+        Some(name) => name.to_string(),
+        None => ('a'..='z')
+            .map(String::from)
+            .find(|letter| params.iter().all(|p| p.name != *letter))
+            .unwrap_or_else(|| format!("l{}", params.len())),
     };
-
-    let new_return_ty = apply_lifetime_to_refs(&func.return_ty, self_lifetime);
-
-    let new_params: Vec<(String, Type)> = func
-        .params
-        .iter()
-        .map(|(name, ty)| (name.clone(), apply_lifetime_to_refs(ty, self_lifetime)))
-        .collect();
-
-    ElisionResult::Applied(FunctionType {
-        lifetime_params: vec![lifetime_param],
-        type_params: func.type_params.clone(),
-        params: new_params,
-        return_ty: new_return_ty,
-        is_method: func.is_method,
-    })
+    let id = fresh_lifetime_id();
+    params.push(LifetimeParam::new(id, name));
+    id
 }
 
-fn apply_lifetime_to_refs(ty: &Type, lifetime: LifetimeId) -> Type {
+/// Give every reference without a lifetime the one `next` returns
+fn elide_refs(ty: &Type, next: &mut dyn FnMut() -> LifetimeId) -> Type {
     match ty {
         Type::Ref(inner) => {
-            Type::RefWithLifetime(lifetime, Box::new(apply_lifetime_to_refs(inner, lifetime)))
+            let lifetime = next();
+            Type::RefWithLifetime(lifetime, Box::new(elide_refs(inner, next)))
         }
         Type::MutRef(inner) => {
-            Type::MutRefWithLifetime(lifetime, Box::new(apply_lifetime_to_refs(inner, lifetime)))
+            let lifetime = next();
+            Type::MutRefWithLifetime(lifetime, Box::new(elide_refs(inner, next)))
         }
-        Type::RefWithLifetime(_, inner) => {
-            Type::RefWithLifetime(lifetime, Box::new(apply_lifetime_to_refs(inner, lifetime)))
+        Type::RefWithLifetime(lifetime, inner) => {
+            Type::RefWithLifetime(*lifetime, Box::new(elide_refs(inner, next)))
         }
-        Type::MutRefWithLifetime(_, inner) => {
-            Type::MutRefWithLifetime(lifetime, Box::new(apply_lifetime_to_refs(inner, lifetime)))
+        Type::MutRefWithLifetime(lifetime, inner) => {
+            Type::MutRefWithLifetime(*lifetime, Box::new(elide_refs(inner, next)))
         }
-        Type::Array(inner) => Type::Array(Box::new(apply_lifetime_to_refs(inner, lifetime))),
+        Type::Array(inner) => Type::Array(Box::new(elide_refs(inner, next))),
         Type::Object(obj) => Type::Object(ObjectType {
             fields: obj
                 .fields
                 .iter()
-                .map(|(k, v)| (k.clone(), apply_lifetime_to_refs(v, lifetime)))
+                .map(|(k, v)| (k.clone(), elide_refs(v, next)))
                 .collect(),
             exact: obj.exact,
         }),
-        Type::Function(f) => Type::Function(Box::new(FunctionType {
-            lifetime_params: f.lifetime_params.clone(),
-            type_params: f.type_params.clone(),
-            params: f
-                .params
-                .iter()
-                .map(|(n, t)| (n.clone(), apply_lifetime_to_refs(t, lifetime)))
-                .collect(),
-            return_ty: apply_lifetime_to_refs(&f.return_ty, lifetime),
-            is_method: f.is_method,
-        })),
-        Type::Generic(id, args) => Type::Generic(
-            *id,
-            args.iter()
-                .map(|a| apply_lifetime_to_refs(a, lifetime))
-                .collect(),
-        ),
+        Type::Generic(id, args) => {
+            Type::Generic(*id, args.iter().map(|a| elide_refs(a, next)).collect())
+        }
         _ => ty.clone(),
     }
 }
@@ -808,11 +790,60 @@ mod elision_tests {
         };
 
         match apply_lifetime_elision(&func, Span::default()) {
-            ElisionResult::AmbiguousLifetime { .. } => {}
+            ElisionResult::AmbiguousLifetime { candidates, .. } => {
+                assert_eq!(candidates, vec!["a".to_string(), "b".to_string()]);
+            }
             _ => panic!("Expected ambiguous lifetime error"),
         }
     }
 
+    #[test]
+    fn test_method_output_borrows_from_receiver() {
+        let func = FunctionType {
+            lifetime_params: Vec::new(),
+            type_params: Vec::new(),
+            params: vec![
+                ("self".to_string(), Type::Ref(Box::new(Type::Number))),
+                ("key".to_string(), Type::Ref(Box::new(Type::String))),
+            ],
+            return_ty: Type::Ref(Box::new(Type::Number)),
+            is_method: true,
+        };
+
+        let ElisionResult::Applied(new_func) = apply_lifetime_elision(&func, Span::default())
+        else {
+            panic!("Expected method elision to be applied");
+        };
+        let names: Vec<&str> = new_func
+            .lifetime_params
+            .iter()
+            .map(|p| p.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["self", "a"]);
+        let Type::RefWithLifetime(receiver, _) = &new_func.params[0].1 else {
+            panic!("Expected the receiver to get a lifetime");
+        };
+        assert!(matches!(
+            new_func.return_ty,
+            Type::RefWithLifetime(id, _) if id == *receiver
+        ));
+
+        // Without a receiver parameter the output borrows from the implicit `this`
+        let func = FunctionType {
+            params: vec![("key".to_string(), Type::Ref(Box::new(Type::String)))],
+            ..func
+        };
+        let ElisionResult::Applied(new_func) = apply_lifetime_elision(&func, Span::default())
+        else {
+            panic!("Expected method elision to be applied");
+        };
+        assert_eq!(new_func.lifetime_params[1].name, "self");
+        assert!(matches!(
+            new_func.return_ty,
+            Type::RefWithLifetime(id, _) if id == new_func.lifetime_params[1].id
+        ));
+    }
+
     #[test]
     fn test_no_elision_needed() {
         let func = FunctionType {
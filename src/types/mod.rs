@@ -1,5 +1,6 @@
 //! Type system with ownership, borrowing, and lifetime tracking.
 
+pub mod annotations;
 pub mod checker;
 pub mod convert;
 pub mod error;
@@ -10,6 +11,8 @@ use std::collections::{BTreeMap, HashMap};
 use std::fmt;
 use std::sync::atomic::{AtomicU32, Ordering};
 
+use crate::runtime::heap::ElementKind;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct TypeId(pub u32);
 
@@ -122,9 +125,13 @@ pub enum Type {
     Number,
     Boolean,
     Void,
+    /// `null` and `undefined`
+    Null,
     Never,
     String,
     Array(Box<Type>),
+    /// `Float64Array`, `Uint8Array`, ...: fixed-length, numeric elements
+    TypedArray(ElementKind),
     Object(ObjectType),
     Function(Box<FunctionType>),
     Struct(TypeId),
@@ -137,6 +144,8 @@ pub enum Type {
     RefWithLifetime(LifetimeId, Box<Type>),
     MutRefWithLifetime(LifetimeId, Box<Type>),
     Lifetime(LifetimeId),
+    /// `A | B`: any of the members. Build with `Type::union`.
+    Union(Vec<Type>),
     #[default]
     Any,
     Infer(InferId),
@@ -153,6 +162,7 @@ impl Type {
             self,
             Type::String
                 | Type::Array(_)
+                | Type::TypedArray(_)
                 | Type::Object(_)
                 | Type::Function(_)
                 | Type::Struct(_)
@@ -180,7 +190,12 @@ impl Type {
     pub fn is_heap(&self) -> bool {
         matches!(
             self,
-            Type::String | Type::Array(_) | Type::Object(_) | Type::Function(_) | Type::Struct(_)
+            Type::String
+                | Type::Array(_)
+                | Type::TypedArray(_)
+                | Type::Object(_)
+                | Type::Function(_)
+                | Type::Struct(_)
         )
     }
 
@@ -188,6 +203,7 @@ impl Type {
         match self {
             Type::TypeVar(_) | Type::Infer(_) => false,
             Type::Array(inner) => inner.is_concrete(),
+            Type::Union(members) => members.iter().all(|t| t.is_concrete()),
             Type::Object(obj) => obj.fields.values().all(|t| t.is_concrete()),
             Type::Function(func) => {
                 func.params.iter().all(|(_, t)| t.is_concrete()) && func.return_ty.is_concrete()
@@ -224,9 +240,33 @@ impl Type {
         matches!(self, Type::MutRef(_) | Type::MutRefWithLifetime(_, _))
     }
 
+    /// Union of `members`, flattened and without duplicates. `any` absorbs
+    /// the rest and a single member stands for itself.
+    pub fn union(members: impl IntoIterator<Item = Type>) -> Type {
+        let mut flat: Vec<Type> = Vec::new();
+        for member in members {
+            let nested = match member {
+                Type::Union(nested) => nested,
+                Type::Any => return Type::Any,
+                other => vec![other],
+            };
+            for ty in nested {
+                if !flat.contains(&ty) {
+                    flat.push(ty);
+                }
+            }
+        }
+        match flat.len() {
+            0 => Type::Never,
+            1 => flat.pop().unwrap(),
+            _ => Type::Union(flat),
+        }
+    }
+
     pub fn element_type(&self) -> Option<&Type> {
         match self {
             Type::Array(inner) => Some(inner),
+            Type::TypedArray(_) => Some(&Type::Number),
             _ => None,
         }
     }
@@ -238,9 +278,11 @@ impl fmt::Display for Type {
             Type::Number => write!(f, "number"),
             Type::Boolean => write!(f, "boolean"),
             Type::Void => write!(f, "void"),
+            Type::Null => write!(f, "null"),
             Type::Never => write!(f, "never"),
             Type::String => write!(f, "string"),
             Type::Array(inner) => write!(f, "{}[]", inner),
+            Type::TypedArray(kind) => write!(f, "{}", kind.name()),
             Type::Object(obj) => {
                 write!(f, "{{ ")?;
                 for (i, (name, ty)) in obj.fields.iter().enumerate() {
@@ -271,6 +313,15 @@ impl fmt::Display for Type {
             Type::RefWithLifetime(lt, inner) => write!(f, "&{} {}", lt, inner),
             Type::MutRefWithLifetime(lt, inner) => write!(f, "&{} mut {}", lt, inner),
             Type::Lifetime(lt) => write!(f, "{}", lt),
+            Type::Union(members) => {
+                for (i, member) in members.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " | ")?;
+                    }
+                    write!(f, "{}", member)?;
+                }
+                Ok(())
+            }
             Type::Any => write!(f, "any"),
             Type::Infer(id) => write!(f, "{}", id),
             Type::Error => write!(f, "<error>"),
@@ -574,6 +625,7 @@ impl TypeContext {
                 .map(|t| self.substitute(t))
                 .unwrap_or_else(|| ty.clone()),
             Type::Array(inner) => Type::Array(Box::new(self.substitute(inner))),
+            Type::Union(members) => Type::union(members.iter().map(|t| self.substitute(t))),
             Type::Ref(inner) => Type::Ref(Box::new(self.substitute(inner))),
             Type::MutRef(inner) => Type::MutRef(Box::new(self.substitute(inner))),
             Type::RefWithLifetime(lt, inner) => {
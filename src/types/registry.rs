@@ -140,6 +140,7 @@ impl TypeRegistry {
                 }
             }
             Type::Array(inner) => Type::Array(Box::new(self.resolve_alias(inner))),
+            Type::Union(members) => Type::union(members.iter().map(|t| self.resolve_alias(t))),
             Type::Ref(inner) => Type::Ref(Box::new(self.resolve_alias(inner))),
             Type::MutRef(inner) => Type::MutRef(Box::new(self.resolve_alias(inner))),
             Type::Generic(id, args) => {
@@ -229,6 +230,11 @@ impl TypeRegistry {
             Type::Array(inner) => {
                 Type::Array(Box::new(self.substitute_type_params(inner, params, args)))
             }
+            Type::Union(members) => Type::union(
+                members
+                    .iter()
+                    .map(|t| self.substitute_type_params(t, params, args)),
+            ),
             Type::Ref(inner) => {
                 Type::Ref(Box::new(self.substitute_type_params(inner, params, args)))
             }
@@ -0,0 +1,383 @@
+//! Self-contained executables (`oitec bundle <file> -o app`)
+//!
+//! A bundle is a copy of the running `oitec` binary with the program
+//! appended: a startup snapshot taken after the prelude (see
+//! `startup_snapshot`), the entry module's bytecode and every module it
+//! statically imports, transformed and compiled. When `oitec` starts it
+//! looks for a bundle at the end of its own executable and, finding one,
+//! runs it with all of its arguments instead of parsing a command line.
+//! No compiler toolchain or source files are needed where it runs.
+//! `import.meta.env`/`process.env` references are substituted as in `build`
+//! (see `compiler::build_env`), in the entry and in every bundled module.
+//!
+//! ```text
+//! payload:  snapshot bytes | entry path | varint count, op*
+//!           | varint count, (path, source, varint count, op*)*
+//! trailer:  u64 LE payload length | "OTBUNDLE"
+//! ```
+//!
+//! Modules are found by the paths they had when bundled; an import the
+//! bundle doesn't cover (a dynamic `import()` of a file, say) goes to the
+//! file system as usual.
+
+use super::VM;
+use super::opcodes::OpCode;
+use super::prefetch::{Prefetched, Prefetcher, static_imports};
+use super::startup_snapshot::{self, Reader, Writer};
+use crate::compiler::Compiler;
+use crate::driver::{CompilationDriver, syntax_for_path};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+/// Marks the end of an executable that carries a bundle
+pub const MAGIC: &[u8; 8] = b"OTBUNDLE";
+const TRAILER_LEN: usize = 16;
+
+/// A module compiled into a bundle
+pub struct BundledModule {
+    /// Canonical path of the source it was compiled from
+    pub path: PathBuf,
+    /// The source after its transform, which the module cache records
+    pub source: String,
+    pub bytecode: Vec<OpCode>,
+}
+
+pub struct Bundle {
+    /// VM state after the prelude
+    pub snapshot: Vec<u8>,
+    pub entry: PathBuf,
+    pub program: Vec<OpCode>,
+    pub modules: Vec<BundledModule>,
+}
+
+impl Bundle {
+    /// Compile `entry` and the modules it statically imports, and take a
+    /// snapshot of the prelude, the way `driver` runs them
+    pub fn build(driver: &mut CompilationDriver, entry: &str) -> Result<Self, String> {
+        let mut vm = VM::new();
+        driver.configure_vm(&mut vm);
+        // Build variables are for the program, not the prelude it starts from
+        let build_env = driver.compiler.build_env.take();
+        driver.load_prelude(&mut vm)?;
+        driver.load_compiler_modules(&mut vm, entry)?;
+        driver.compiler.build_env = build_env;
+        let snapshot = startup_snapshot::save(&vm)?;
+
+        let source = crate::driver::read_source(entry)?;
+        let program = driver
+            .compile(entry, &source)
+            .map_err(|e| format!("Compilation failed: {}", e))?;
+        let entry = crate::platform::canonicalize(Path::new(entry))
+            .map_err(|e| format!("Failed to resolve {}: {}", entry, e))?;
+
+        let mut modules = Vec::new();
+        let mut seen = HashSet::from([entry.clone()]);
+        let mut queue: VecDeque<PathBuf> = static_imports(&entry, &source).into();
+        while let Some(path) = queue.pop_front() {
+            if !seen.insert(path.clone()) {
+                continue;
+            }
+            let source = fs::read_to_string(&path)
+                .map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+                .and_then(|source| crate::transform::transform_source(&path, source))?;
+            let mut compiler = Compiler::new();
+            compiler.borrow_check = vm.compile_settings.borrow_check_for(&path);
+            compiler.build_env = driver.compiler.build_env.clone();
+            let bytecode = compiler
+                .compile_with_syntax(&source, Some(syntax_for_path(&path.to_string_lossy())))
+                .map_err(|e| format!("Failed to compile module {}: {}", path.display(), e))?;
+            for warning in compiler.warnings {
+                eprintln!("Warning: {}: {}", path.display(), warning);
+            }
+            queue.extend(static_imports(&path, &source));
+            modules.push(BundledModule {
+                path,
+                source,
+                bytecode,
+            });
+        }
+
+        Ok(Self {
+            snapshot,
+            entry,
+            program,
+            modules,
+        })
+    }
+
+    pub fn encode(&self) -> Result<Vec<u8>, String> {
+        let mut w = Writer::default();
+        w.bytes(&self.snapshot);
+        w.path(&self.entry);
+        w.program(&self.program)?;
+        w.usize(self.modules.len());
+        for module in &self.modules {
+            w.path(&module.path);
+            w.str(&module.source);
+            w.program(&module.bytecode)?;
+        }
+        Ok(w.bytes)
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, String> {
+        let mut r = Reader::new(bytes);
+        let snapshot = r.byte_vec()?;
+        let entry = r.path()?;
+        let program = r.program()?;
+        let modules = (0..r.usize()?)
+            .map(|_| {
+                Ok(BundledModule {
+                    path: r.path()?,
+                    source: r.str()?,
+                    bytecode: r.program()?,
+                })
+            })
+            .collect::<Result<_, String>>()?;
+        if !r.finished() {
+            return Err("bundle has trailing bytes".to_string());
+        }
+        Ok(Self {
+            snapshot,
+            entry,
+            program,
+            modules,
+        })
+    }
+
+    /// Write `runtime` (an `oitec` executable, without any bundle it
+    /// carries) to `output` with this bundle appended
+    pub fn write_executable(&self, runtime: &Path, output: &Path) -> Result<usize, String> {
+        let mut bytes = fs::read(runtime)
+            .map_err(|e| format!("Failed to read {}: {}", runtime.display(), e))?;
+        bytes.truncate(runtime_len(&bytes));
+        let payload = self.encode()?;
+        bytes.extend_from_slice(&payload);
+        bytes.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(MAGIC);
+        fs::write(output, &bytes)
+            .map_err(|e| format!("Failed to write {}: {}", output.display(), e))?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(output, fs::Permissions::from_mode(0o755))
+                .map_err(|e| format!("Failed to make {} executable: {}", output.display(), e))?;
+        }
+        Ok(bytes.len())
+    }
+
+    /// The bundle appended to the running executable, if it has one
+    pub fn embedded() -> Option<Result<Self, String>> {
+        let exe = std::env::current_exe().ok()?;
+        Self::read_appended(&exe)
+    }
+
+    /// The bundle appended to the executable at `path`, if it has one
+    pub fn read_appended(path: &Path) -> Option<Result<Self, String>> {
+        let mut file = fs::File::open(path).ok()?;
+        let len = file.metadata().ok()?.len();
+        if len < TRAILER_LEN as u64 {
+            return None;
+        }
+        let mut trailer = [0u8; TRAILER_LEN];
+        file.seek(SeekFrom::End(-(TRAILER_LEN as i64))).ok()?;
+        file.read_exact(&mut trailer).ok()?;
+        if &trailer[8..] != MAGIC {
+            return None;
+        }
+        let payload_len = u64::from_le_bytes(trailer[..8].try_into().unwrap_or_default());
+        let Some(start) = (len - TRAILER_LEN as u64).checked_sub(payload_len) else {
+            return Some(Err("bundle is truncated".to_string()));
+        };
+        let mut payload = vec![0u8; payload_len as usize];
+        let read = file
+            .seek(SeekFrom::Start(start))
+            .and_then(|_| file.read_exact(&mut payload));
+        Some(
+            read.map_err(|e| format!("Failed to read bundle: {}", e))
+                .and_then(|_| Self::decode(&payload)),
+        )
+    }
+
+    /// Restore the snapshot into `vm` and load the entry program, ready for
+    /// `run_event_loop`. `vm` must be fresh from `VM::new`.
+    pub fn install(self, vm: &mut VM) -> Result<(), String> {
+        startup_snapshot::restore(vm, &self.snapshot)?;
+        let mut sources = HashMap::new();
+        let mut compiled = HashMap::new();
+        for module in self.modules {
+            sources.insert(module.path.clone(), module.source.clone());
+            compiled.insert(
+                module.path,
+                Prefetched {
+                    source: module.source,
+                    bytecode: Ok(module.bytecode),
+                    line_table: Vec::new(),
+                    warnings: Vec::new(),
+                },
+            );
+        }
+        vm.prefetcher = Some(Prefetcher::with_modules(compiled));
+        vm.bundled = Some(BundledModules { sources });
+        vm.append_program(self.program);
+        vm.set_current_module_path(self.entry);
+        Ok(())
+    }
+}
+
+/// Sources of the modules in a bundle, by the path they were bundled from
+pub struct BundledModules {
+    sources: HashMap<PathBuf, String>,
+}
+
+impl BundledModules {
+    /// The bundled module a relative `specifier` imported from `importer`
+    /// names, tried as written, without its extension and as a directory
+    /// index
+    pub fn resolve(&self, importer: Option<&Path>, specifier: &str) -> Option<PathBuf> {
+        if !specifier.starts_with('.') {
+            return None;
+        }
+        let mut path = importer
+            .and_then(Path::parent)
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        for component in specifier.split('/') {
+            match component {
+                "" | "." => {}
+                ".." => {
+                    path.pop();
+                }
+                name => path.push(name),
+            }
+        }
+        if self.sources.contains_key(&path) {
+            return Some(path);
+        }
+        self.sources
+            .keys()
+            .filter(|module| {
+                module.with_extension("") == path
+                    || (module.parent() == Some(path.as_path())
+                        && module.file_stem().is_some_and(|stem| stem == "index"))
+            })
+            .min()
+            .cloned()
+    }
+
+    pub fn source(&self, path: &Path) -> Option<&str> {
+        self.sources.get(path).map(String::as_str)
+    }
+}
+
+/// Length of `exe` without the bundle appended to it, if any
+fn runtime_len(exe: &[u8]) -> usize {
+    let Some(trailer) = exe.len().checked_sub(TRAILER_LEN).map(|at| &exe[at..]) else {
+        return exe.len();
+    };
+    if &trailer[8..] != MAGIC {
+        return exe.len();
+    }
+    let payload_len = u64::from_le_bytes(trailer[..8].try_into().unwrap_or_default());
+    usize::try_from(payload_len)
+        .ok()
+        .and_then(|payload_len| (exe.len() - TRAILER_LEN).checked_sub(payload_len))
+        .unwrap_or(exe.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vm::value::JsValue;
+
+    #[test]
+    fn test_bundle_runs_without_its_sources() {
+        let dir = std::env::temp_dir().join(format!("oite-bundle-{}", std::process::id()));
+        fs::create_dir_all(dir.join("lib")).unwrap();
+        let entry = dir.join("main.ot");
+        fs::write(
+            &entry,
+            "import { twice } from './lib/math';\nimport { base } from './lib';\nlet result = twice(base);\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("lib/math.ot"),
+            "export function twice(n: number): number { return n * 2; }\n",
+        )
+        .unwrap();
+        fs::write(dir.join("lib/index.ot"), "export let base = 21;\n").unwrap();
+
+        let entry_name = entry.to_string_lossy().into_owned();
+        let mut driver = CompilationDriver::new(&entry_name);
+        let bundle = Bundle::build(&mut driver, &entry_name).unwrap();
+        assert_eq!(bundle.modules.len(), 2);
+
+        // The executable is the runtime followed by the bundle; bundling a
+        // bundled executable replaces what it carries
+        let runtime = dir.join("runtime");
+        fs::write(&runtime, b"\x7fELF runtime").unwrap();
+        let app = dir.join("app");
+        bundle.write_executable(&runtime, &app).unwrap();
+        bundle.write_executable(&app, &app).unwrap();
+        let written = fs::read(&app).unwrap();
+        assert!(written.starts_with(b"\x7fELF runtime"));
+        assert_eq!(runtime_len(&written), b"\x7fELF runtime".len());
+        assert!(Bundle::read_appended(&runtime).is_none());
+        let bundle = Bundle::read_appended(&app).unwrap().unwrap();
+
+        fs::remove_dir_all(&dir).unwrap();
+        let mut vm = VM::new();
+        bundle.install(&mut vm).unwrap();
+        vm.run_event_loop();
+        assert_eq!(
+            vm.call_stack[0].locals.get("result"),
+            Some(&JsValue::Number(42.0))
+        );
+    }
+
+    #[test]
+    fn test_bundle_inlines_build_env() {
+        let dir = std::env::temp_dir().join(format!("oite-bundle-env-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let name = format!("OITE_BUNDLE_ENV_{}", std::process::id());
+        let entry = dir.join("main.ot");
+        fs::write(
+            &entry,
+            format!(
+                "import {{ api }} from './config';\n\
+                 let direct = process.env.{name};\n\
+                 let imported = api;\n\
+                 let mode = import.meta.env.MODE;\n"
+            ),
+        )
+        .unwrap();
+        fs::write(
+            dir.join("config.ot"),
+            format!("export let api = process.env.{name};\n"),
+        )
+        .unwrap();
+
+        let entry_name = entry.to_string_lossy().into_owned();
+        let mut driver = CompilationDriver::new(&entry_name);
+        let mut build_env = crate::compiler::build_env::BuildEnv::new("production");
+        build_env.define(&format!("{}=from build", name)).unwrap();
+        driver.compiler.build_env = Some(build_env);
+        let bundle = Bundle::build(&mut driver, &entry_name).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        // The variable isn't set where the bundle runs; the build's value was
+        // compiled in
+        let mut vm = VM::new();
+        bundle.install(&mut vm).unwrap();
+        vm.run_event_loop();
+        let globals = &vm.call_stack[0].locals;
+        let from_build = Some(&JsValue::String("from build".into()));
+        assert_eq!(globals.get("direct"), from_build);
+        assert_eq!(globals.get("imported"), from_build);
+        assert_eq!(
+            globals.get("mode"),
+            Some(&JsValue::String("production".into()))
+        );
+    }
+}
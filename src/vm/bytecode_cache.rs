@@ -0,0 +1,233 @@
+//! Compiled bytecode cache for the run path
+//!
+//! Running a script compiles the prelude, the script and every module it
+//! imports from source each time. With the cache on (the default for
+//! `oitec <file>`, off with `--no-bytecode-cache`) each compile is saved
+//! under the project's `.cache/bytecode`, next to its `script.toml`, and
+//! reused while nothing it depends on changes:
+//! the source text, the syntax, the borrow-check level, the cache
+//! `VERSION` and the `oitec` version.
+//!
+//! ```text
+//! entry:  "OTBC" | u32 LE VERSION | varint count, op*
+//!         | varint count, (varint instruction, varint line)*
+//!         | varint count, warning string*
+//! ```
+//!
+//! A script outside any project caches under the user's cache directory
+//! (`$XDG_CACHE_HOME/oite/bytecode`, see `platform::user_cache_dir`), never
+//! the directory it happens to be run from. Keys hash the source, so scripts
+//! sharing that cache don't collide.
+//!
+//! Entries are written to a temporary file and renamed into place, so runs
+//! sharing a cache never see half an entry. A missing, stale or unreadable
+//! entry is just a miss.
+
+use super::opcodes::OpCode;
+use super::startup_snapshot::{self, Reader, Writer};
+use crate::compiler::borrow_ck::BorrowCheckLevel;
+use crate::manifest::Manifest;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::PathBuf;
+use swc_ecma_parser::Syntax;
+
+/// Magic bytes opening a cache entry
+pub const MAGIC: &[u8; 4] = b"OTBC";
+
+/// Cache format version. Bump it with any change to `OpCode` that its
+/// encoding in `startup_snapshot` doesn't already catch, so entries written
+/// before it are no longer used.
+pub const VERSION: u32 = 1;
+
+/// A compile read back from the cache
+pub struct CachedCompile {
+    pub bytecode: Vec<OpCode>,
+    pub line_table: Vec<(usize, u32)>,
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BytecodeCache {
+    dir: PathBuf,
+}
+
+impl BytecodeCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// The cache of the project `manifest` describes: `.cache/bytecode`
+    /// next to its `script.toml`, or `oite/bytecode` in the user's cache
+    /// directory without one
+    pub fn for_project(manifest: &Manifest) -> Self {
+        let project = manifest.path.as_deref().and_then(|path| path.parent());
+        match project {
+            Some(root) => Self::new(root.join(".cache").join("bytecode")),
+            None => Self::new(
+                crate::platform::user_cache_dir()
+                    .unwrap_or_else(std::env::temp_dir)
+                    .join("oite")
+                    .join("bytecode"),
+            ),
+        }
+    }
+
+    /// Key of a compile of `source`
+    pub fn key(source: &str, syntax: &Option<Syntax>, borrow_check: BorrowCheckLevel) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(VERSION.to_le_bytes());
+        hasher.update(startup_snapshot::VERSION.to_le_bytes());
+        hasher.update(env!("CARGO_PKG_VERSION"));
+        hasher.update(format!("{:?}\0{:?}\0", syntax, borrow_check));
+        hasher.update(source);
+        hex::encode(hasher.finalize())
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.otbc", key))
+    }
+
+    /// The compile saved under `key`, if there is a usable one
+    pub fn load(&self, key: &str) -> Option<CachedCompile> {
+        let bytes = fs::read(self.entry_path(key)).ok()?;
+        decode(&bytes).ok()
+    }
+
+    /// Save a compile under `key`. Failing to is not an error: the next
+    /// run compiles again.
+    pub fn store(
+        &self,
+        key: &str,
+        bytecode: &[OpCode],
+        line_table: &[(usize, u32)],
+        warnings: &[String],
+    ) {
+        let Ok(bytes) = encode(bytecode, line_table, warnings) else {
+            return;
+        };
+        let path = self.entry_path(key);
+        let temp = path.with_extension(format!("otbc.{}", std::process::id()));
+        let written = fs::create_dir_all(&self.dir)
+            .and_then(|()| fs::write(&temp, &bytes))
+            .and_then(|()| fs::rename(&temp, &path));
+        if written.is_err() {
+            let _ = fs::remove_file(&temp);
+        }
+    }
+}
+
+fn encode(
+    bytecode: &[OpCode],
+    line_table: &[(usize, u32)],
+    warnings: &[String],
+) -> Result<Vec<u8>, String> {
+    let mut w = Writer::default();
+    w.bytes.extend_from_slice(MAGIC);
+    w.bytes.extend_from_slice(&VERSION.to_le_bytes());
+    w.program(bytecode)?;
+    w.usize(line_table.len());
+    for &(ip, line) in line_table {
+        w.usize(ip);
+        w.usize(line as usize);
+    }
+    w.strings(warnings);
+    Ok(w.bytes)
+}
+
+fn decode(bytes: &[u8]) -> Result<CachedCompile, String> {
+    let mut r = Reader::new(bytes);
+    if r.take(MAGIC.len(), "magic")? != MAGIC {
+        return Err("not a bytecode cache entry".to_string());
+    }
+    let version = r.take(4, "version")?;
+    if version != VERSION.to_le_bytes() {
+        return Err("bytecode cache entry of another version".to_string());
+    }
+    let bytecode = r.program()?;
+    let line_table = (0..r.usize()?)
+        .map(|_| Ok((r.usize()?, r.u32_value()?)))
+        .collect::<Result<_, String>>()?;
+    let warnings = r.strings()?;
+    if !r.finished() {
+        return Err("bytecode cache entry has trailing bytes".to_string());
+    }
+    Ok(CachedCompile {
+        bytecode,
+        line_table,
+        warnings,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::Compiler;
+
+    #[test]
+    fn test_compiles_are_reused_until_their_inputs_change() {
+        let dir = std::env::temp_dir().join(format!("oite-bytecode-cache-{}", std::process::id()));
+        let cache = BytecodeCache::new(&dir);
+        let source = "let a = 1;\nlet b = a + 1;\n";
+
+        let mut compiler = Compiler::new();
+        compiler.bytecode_cache = Some(cache.clone());
+        // OpCode has no PartialEq; its Debug form shows every operand
+        let fresh = format!("{:?}", compiler.compile(source).unwrap());
+        let line_table = compiler.line_table.clone();
+        let entries = || fs::read_dir(&dir).unwrap().count();
+        assert_eq!(entries(), 1);
+
+        let key = BytecodeCache::key(source, &None, BorrowCheckLevel::default());
+        let cached = cache.load(&key).unwrap();
+        assert_eq!(format!("{:?}", cached.bytecode), fresh);
+        assert_eq!(cached.line_table, line_table);
+
+        // A hit hands back what the compile left behind
+        let mut compiler = Compiler::new();
+        compiler.bytecode_cache = Some(cache.clone());
+        assert_eq!(format!("{:?}", compiler.compile(source).unwrap()), fresh);
+        assert_eq!(compiler.line_table, line_table);
+        assert_eq!(entries(), 1);
+
+        // Other settings are other entries
+        compiler.borrow_check = BorrowCheckLevel::Off;
+        compiler.compile(source).unwrap();
+        assert_eq!(entries(), 2);
+
+        // Entries of another version are ignored
+        let mut stale = fs::read(cache.entry_path(&key)).unwrap();
+        stale[MAGIC.len()] ^= 0xff;
+        fs::write(cache.entry_path(&key), stale).unwrap();
+        assert!(cache.load(&key).is_none());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_cache_follows_the_project_not_the_working_directory() {
+        // The tests run from the crate root, which has nothing to do with
+        // the project compiled here
+        let cwd = std::env::current_dir().unwrap();
+        let project = std::env::temp_dir().join(format!("oite-cache-root-{}", std::process::id()));
+        fs::create_dir_all(project.join("src")).unwrap();
+        fs::write(project.join("script.toml"), "").unwrap();
+        let entry = project.join("src").join("main.ot");
+        fs::write(&entry, "let a = 1;\n").unwrap();
+
+        let mut driver = crate::driver::CompilationDriver::new(entry.to_str().unwrap())
+            .with_bytecode_cache(true);
+        driver.compile_file(entry.to_str().unwrap()).unwrap();
+        let entries = fs::read_dir(project.join(".cache").join("bytecode"))
+            .unwrap()
+            .count();
+        fs::remove_dir_all(&project).unwrap();
+        assert_eq!(entries, 1);
+
+        // Without a script.toml the cache is per user, not per directory
+        let loose = BytecodeCache::for_project(&Manifest::default());
+        assert!(loose.dir.is_absolute());
+        assert!(loose.dir.ends_with("oite/bytecode"));
+        assert!(!loose.dir.starts_with(&cwd));
+    }
+}
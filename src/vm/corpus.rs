@@ -0,0 +1,427 @@
+//! Corpus runner for the interpreter (`oitec corpus`, feature `corpus-runner`)
+//!
+//! Replays a directory of samples, bytecode files (`.otb`, `.bc`) and sources,
+//! and checks the interpreter's invariants before every instruction:
+//!
+//! - a frame is live, and every frame's return address and function lie
+//!   inside the program
+//! - exception handlers never record a deeper operand or call stack than
+//!   there is
+//! - every heap pointer on the operand stack and in the running frame
+//!   resolves to a live object
+//!
+//! Every `HEAP_CHECK_INTERVAL` instructions, and when a sample finishes, the
+//! pointers held by every frame, pinned global and heap object are checked
+//! too. The interpreter's `self.heap.get(ptr)` calls take all of this for
+//! granted, and a collector that moves objects will have to keep it true, so
+//! a violation is a bug to fix before that.
+//!
+//! Coverage is the set of opcode pairs executed back to back. A sample that
+//! adds no new pair exercises nothing the earlier ones didn't; `--keep <dir>`
+//! copies only the samples that did, which minimises a corpus.
+
+use crate::compiler::Compiler;
+use crate::driver::syntax_for_path;
+use crate::loader::BytecodeDecoder;
+use crate::vm::heap_snapshot::{for_each_child, value_pointers};
+use crate::vm::opcodes::OpCode;
+use crate::vm::permissions::Permissions;
+use crate::vm::value::JsValue;
+use crate::vm::{Frame, VM};
+use std::collections::HashSet;
+use std::mem::Discriminant;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+
+/// Instructions between checks of the whole heap
+pub const HEAP_CHECK_INTERVAL: u64 = 1024;
+
+/// Instructions a sample may run before it is stopped
+pub const DEFAULT_MAX_STEPS: u64 = 10_000_000;
+
+/// File extensions replayed as samples
+const SAMPLE_EXTENSIONS: [&str; 7] = ["otb", "bc", "ot", "ts", "tsx", "js", "jsx"];
+
+/// Two opcodes executed one after the other
+type Edge = (Discriminant<OpCode>, Discriminant<OpCode>);
+
+/// Panic payload for a broken invariant
+struct Violation(String);
+
+/// Panic payload for a sample that ran out of steps
+struct StepLimit;
+
+/// Per-run checking state, installed as `VM::invariants`
+pub struct Invariants {
+    steps: u64,
+    max_steps: u64,
+    last: Option<Discriminant<OpCode>>,
+    edges: HashSet<Edge>,
+}
+
+impl Invariants {
+    pub fn new(max_steps: u64) -> Self {
+        Self {
+            steps: 0,
+            max_steps,
+            last: None,
+            edges: HashSet::new(),
+        }
+    }
+}
+
+/// Check the VM before it executes `vm.program[vm.ip]`. Panics with a
+/// `Violation` when an invariant doesn't hold.
+pub(super) fn check_step(vm: &mut VM) {
+    let op = std::mem::discriminant(&vm.program[vm.ip]);
+    let Some(invariants) = vm.invariants.as_mut() else {
+        return;
+    };
+    invariants.steps += 1;
+    if let Some(last) = invariants.last.replace(op) {
+        invariants.edges.insert((last, op));
+    }
+    if invariants.steps > invariants.max_steps {
+        panic::panic_any(StepLimit);
+    }
+    let full = invariants.steps % HEAP_CHECK_INTERVAL == 0;
+
+    let result = check_frames(vm).and_then(|()| {
+        if full {
+            check_heap(vm)
+        } else {
+            check_live_values(vm)
+        }
+    });
+    if let Err(message) = result {
+        panic::panic_any(Violation(format!(
+            "at {} ({:?}): {}",
+            vm.ip, vm.program[vm.ip], message
+        )));
+    }
+}
+
+fn check_pointer(vm: &VM, ptr: usize, holder: &dyn Fn() -> String) -> Result<(), String> {
+    match vm.heap.resolve(ptr) {
+        Ok(_) => Ok(()),
+        Err(err) => Err(format!("{} holds {}", holder(), err)),
+    }
+}
+
+fn check_value(vm: &VM, value: &JsValue, holder: &dyn Fn() -> String) -> Result<(), String> {
+    let mut result = Ok(());
+    value_pointers(value, &mut |ptr| {
+        if result.is_ok() {
+            result = check_pointer(vm, ptr, holder);
+        }
+    });
+    result
+}
+
+fn check_frame_values(vm: &VM, depth: usize, frame: &Frame) -> Result<(), String> {
+    check_value(vm, &frame.this_context, &|| {
+        format!("frame {} `this`", depth)
+    })?;
+    for (name, value) in &frame.locals {
+        check_value(vm, value, &|| {
+            format!("local `{}` of frame {}", name, depth)
+        })?;
+    }
+    for (slot, value) in frame.indexed_locals.iter().enumerate() {
+        check_value(vm, value, &|| format!("slot {} of frame {}", slot, depth))?;
+    }
+    if let Some(environment) = frame.module {
+        check_pointer(vm, environment, &|| {
+            format!("module scope of frame {}", depth)
+        })?;
+    }
+    Ok(())
+}
+
+/// Frame and exception handler bookkeeping
+fn check_frames(vm: &VM) -> Result<(), String> {
+    if vm.call_stack.is_empty() {
+        return Err("no live frame".to_string());
+    }
+    let len = vm.program.len();
+    for (depth, frame) in vm.call_stack.iter().enumerate() {
+        // usize::MAX marks a frame that returns to the event loop
+        if frame.return_address > len && frame.return_address != usize::MAX {
+            return Err(format!(
+                "frame {} returns to {}, past the end of the program ({})",
+                depth, frame.return_address, len
+            ));
+        }
+        if let Some(address) = frame.function.filter(|&address| address >= len) {
+            return Err(format!(
+                "frame {} runs function {}, past the end of the program ({})",
+                depth, address, len
+            ));
+        }
+    }
+    for (i, handler) in vm.exception_handlers.iter().enumerate() {
+        if handler.stack_depth > vm.stack.len() {
+            return Err(format!(
+                "handler {} expects {} operands but the stack has {}",
+                i,
+                handler.stack_depth,
+                vm.stack.len()
+            ));
+        }
+        if handler.call_stack_depth > vm.call_stack.len() {
+            return Err(format!(
+                "handler {} expects {} frames but there are {}",
+                i,
+                handler.call_stack_depth,
+                vm.call_stack.len()
+            ));
+        }
+        if handler.catch_addr >= len || handler.finally_addr >= len {
+            return Err(format!(
+                "handler {} jumps past the end of the program ({})",
+                i, len
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Heap pointers the next instruction can reach directly
+fn check_live_values(vm: &VM) -> Result<(), String> {
+    for (i, value) in vm.stack.iter().enumerate() {
+        check_value(vm, value, &|| format!("stack slot {}", i))?;
+    }
+    match vm.call_stack.last() {
+        Some(frame) => check_frame_values(vm, vm.call_stack.len() - 1, frame),
+        None => Ok(()),
+    }
+}
+
+/// Every heap pointer the VM holds
+fn check_heap(vm: &VM) -> Result<(), String> {
+    check_live_values(vm)?;
+    for (depth, frame) in vm.call_stack.iter().enumerate() {
+        check_frame_values(vm, depth, frame)?;
+    }
+    for (slot, value) in vm.pinned_globals.iter().enumerate() {
+        check_value(vm, value, &|| format!("pinned global {}", slot))?;
+    }
+    for (ptr, object) in vm.heap.iter() {
+        let mut result = Ok(());
+        for_each_child(object, |child| {
+            if result.is_ok() {
+                result = check_pointer(vm, child, &|| format!("object {}", ptr));
+            }
+        });
+        result?;
+    }
+    Ok(())
+}
+
+/// How a sample's run ended
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    Completed,
+    /// The script threw, or overflowed the call stack; expected of some samples
+    ScriptError(String),
+    StepLimit,
+    /// An invariant check failed
+    Violation(String),
+    /// The interpreter panicked on its own
+    Crash(String),
+}
+
+impl Outcome {
+    /// Whether this outcome is an interpreter bug
+    pub fn is_failure(&self) -> bool {
+        matches!(self, Outcome::Violation(_) | Outcome::Crash(_))
+    }
+}
+
+pub struct SampleReport {
+    pub path: PathBuf,
+    pub outcome: Outcome,
+    pub steps: u64,
+    /// Opcode pairs no earlier sample executed
+    pub new_edges: usize,
+}
+
+/// Replays samples one after another, accumulating coverage
+pub struct CorpusRunner {
+    pub max_steps: u64,
+    edges: HashSet<Edge>,
+}
+
+impl CorpusRunner {
+    pub fn new(max_steps: u64) -> Self {
+        Self {
+            max_steps,
+            edges: HashSet::new(),
+        }
+    }
+
+    /// Opcode pairs executed by any sample so far
+    pub fn edge_count(&self) -> usize {
+        self.edges.len()
+    }
+
+    /// Load and run one sample. Fails when it can't be read, decoded or
+    /// compiled.
+    pub fn run_sample(&mut self, path: &Path) -> Result<SampleReport, String> {
+        let program = load_sample(path)?;
+        let mut vm = VM::new();
+        vm.permissions = Permissions::sandboxed();
+        vm.captured_output = Some(String::new());
+        vm.load_program_with_path(program, path.to_path_buf());
+        vm.invariants = Some(Invariants::new(self.max_steps));
+
+        let hook = panic::take_hook();
+        panic::set_hook(Box::new(|_| {}));
+        let result = panic::catch_unwind(AssertUnwindSafe(|| vm.run_event_loop()));
+        panic::set_hook(hook);
+
+        let outcome = match result {
+            Ok(()) => match check_frames(&vm).and_then(|()| check_heap(&vm)) {
+                Ok(()) => Outcome::Completed,
+                Err(message) => Outcome::Violation(format!("after the run: {}", message)),
+            },
+            Err(payload) => classify(payload),
+        };
+        let invariants = vm.invariants.take().expect("invariants were installed");
+        let before = self.edges.len();
+        self.edges.extend(invariants.edges);
+        Ok(SampleReport {
+            path: path.to_path_buf(),
+            outcome,
+            steps: invariants.steps,
+            new_edges: self.edges.len() - before,
+        })
+    }
+}
+
+fn classify(payload: Box<dyn std::any::Any + Send>) -> Outcome {
+    if payload.is::<StepLimit>() {
+        return Outcome::StepLimit;
+    }
+    let message = match payload.downcast::<Violation>() {
+        Ok(violation) => return Outcome::Violation(violation.0),
+        Err(payload) => match payload.downcast::<String>() {
+            Ok(message) => *message,
+            Err(payload) => payload
+                .downcast_ref::<&str>()
+                .map_or("panic with a non-string payload".to_string(), |s| {
+                    s.to_string()
+                }),
+        },
+    };
+    if message.starts_with("Uncaught exception") || message.starts_with("Stack overflow") {
+        Outcome::ScriptError(message)
+    } else {
+        Outcome::Crash(message)
+    }
+}
+
+fn load_sample(path: &Path) -> Result<Vec<OpCode>, String> {
+    let ext = path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+    if ext == "otb" || ext == "bc" {
+        let bytes = std::fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        return BytecodeDecoder::new(&bytes)
+            .decode_all()
+            .map_err(|e| format!("{}: {}", path.display(), e));
+    }
+    let source = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Compiler::new()
+        .compile_with_syntax(&source, Some(syntax_for_path(&path.to_string_lossy())))
+        .map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Samples under `path` (or `path` itself), in a stable order
+pub fn collect_samples(path: &Path) -> Result<Vec<PathBuf>, String> {
+    if path.is_file() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let mut samples = Vec::new();
+    let mut dirs = vec![path.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let entries = std::fs::read_dir(&dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                dirs.push(path);
+            } else if path
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| SAMPLE_EXTENSIONS.contains(&ext))
+            {
+                samples.push(path);
+            }
+        }
+    }
+    samples.sort();
+    Ok(samples)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vm::value::{HeapData, HeapObject};
+
+    #[test]
+    fn test_dangling_pointer_is_a_violation() {
+        let mut vm = VM::new();
+        vm.load_program(vec![OpCode::Push(JsValue::Undefined), OpCode::Halt]);
+        assert_eq!(check_heap(&vm), Ok(()));
+
+        let dangling = vm.heap.len() + 5;
+        vm.heap.push(HeapObject {
+            data: HeapData::Array(vec![JsValue::Object(dangling)]),
+        });
+        assert!(
+            check_heap(&vm)
+                .unwrap_err()
+                .contains("holds invalid heap handle")
+        );
+        let freed = vm.heap.len() - 1;
+        vm.heap.free(freed).unwrap();
+        assert_eq!(check_heap(&vm), Ok(()));
+
+        vm.stack.push(JsValue::Object(dangling));
+        assert!(
+            check_live_values(&vm)
+                .unwrap_err()
+                .starts_with("stack slot 0")
+        );
+        vm.stack[0] = JsValue::Object(freed);
+        assert!(
+            check_live_values(&vm)
+                .unwrap_err()
+                .ends_with(&format!("stale heap handle {:#x}", freed))
+        );
+    }
+
+    #[test]
+    fn test_step_limit_stops_endless_loops() {
+        let dir = std::env::temp_dir().join(format!("oite-corpus-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let endless = dir.join("endless.ot");
+        let thrower = dir.join("throws.ot");
+        std::fs::write(&endless, "let i = 0; while (true) { i = i + 1; }").unwrap();
+        std::fs::write(&thrower, "let o = { a: [1, 2] }; throw 'boom';").unwrap();
+        std::fs::write(dir.join("notes.txt"), "not a sample").unwrap();
+
+        let samples = collect_samples(&dir).unwrap();
+        assert_eq!(samples, vec![endless.clone(), thrower.clone()]);
+
+        let mut runner = CorpusRunner::new(10_000);
+        let first = runner.run_sample(&endless).unwrap();
+        let second = runner.run_sample(&thrower).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(first.outcome, Outcome::StepLimit);
+        assert_eq!(first.steps, 10_001);
+        assert!(first.new_edges > 0);
+        assert!(matches!(second.outcome, Outcome::ScriptError(_)));
+        assert!(second.new_edges > 0);
+        assert_eq!(runner.edge_count(), first.new_edges + second.new_edges);
+    }
+}
@@ -0,0 +1,164 @@
+//! Line coverage for VM runs (`oitec <file> --coverage`)
+//!
+//! The compiler records where each statement's code starts
+//! (`Compiler::line_table`). Registering a loaded chunk turns those
+//! instruction indices into probes; the VM bumps a probe's counter whenever
+//! it executes the instruction a probe sits on. Bytecode is not modified, so
+//! coverage runs execute the same program as normal runs.
+
+use serde_json::{Value, json};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+struct Probe {
+    file: usize,
+    line: u32,
+    hits: u64,
+}
+
+#[derive(Default)]
+pub struct Coverage {
+    files: Vec<PathBuf>,
+    probes: Vec<Probe>,
+    /// Probe indices per instruction index (empty for most instructions)
+    by_ip: Vec<Vec<usize>>,
+}
+
+impl Coverage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Track a chunk of `path` appended to the program at `offset`.
+    /// `line_table` holds chunk-relative instruction indices.
+    pub fn add_chunk(&mut self, path: &Path, offset: usize, line_table: &[(usize, u32)]) {
+        let file = match self.files.iter().position(|f| f == path) {
+            Some(index) => index,
+            None => {
+                self.files.push(path.to_path_buf());
+                self.files.len() - 1
+            }
+        };
+        for &(ip, line) in line_table {
+            let ip = offset + ip;
+            if self.by_ip.len() <= ip {
+                self.by_ip.resize_with(ip + 1, Vec::new);
+            }
+            self.by_ip[ip].push(self.probes.len());
+            self.probes.push(Probe {
+                file,
+                line,
+                hits: 0,
+            });
+        }
+    }
+
+    #[inline]
+    pub fn hit(&mut self, ip: usize) {
+        if let Some(probes) = self.by_ip.get(ip) {
+            for &probe in probes {
+                self.probes[probe].hits += 1;
+            }
+        }
+    }
+
+    /// Execution count per executable line, per file. A line holding several
+    /// statements reports its most executed one.
+    pub fn line_hits(&self) -> Vec<(&Path, BTreeMap<u32, u64>)> {
+        let mut files: Vec<(&Path, BTreeMap<u32, u64>)> = self
+            .files
+            .iter()
+            .map(|path| (path.as_path(), BTreeMap::new()))
+            .collect();
+        for probe in &self.probes {
+            let hits = files[probe.file].1.entry(probe.line).or_insert(0);
+            *hits = (*hits).max(probe.hits);
+        }
+        files
+    }
+
+    /// lcov tracefile (`genhtml`, Codecov, IDE gutters)
+    pub fn to_lcov(&self) -> String {
+        let mut out = String::new();
+        for (path, lines) in self.line_hits() {
+            out.push_str("TN:\n");
+            out.push_str(&format!("SF:{}\n", path.display()));
+            for (line, hits) in &lines {
+                out.push_str(&format!("DA:{},{}\n", line, hits));
+            }
+            out.push_str(&format!("LF:{}\n", lines.len()));
+            out.push_str(&format!(
+                "LH:{}\n",
+                lines.values().filter(|&&hits| hits > 0).count()
+            ));
+            out.push_str("end_of_record\n");
+        }
+        out
+    }
+
+    pub fn to_json(&self) -> Value {
+        let files: Vec<Value> = self
+            .line_hits()
+            .into_iter()
+            .map(|(path, lines)| {
+                let covered = lines.values().filter(|&&hits| hits > 0).count();
+                json!({
+                    "path": path.display().to_string(),
+                    "lines": lines
+                        .iter()
+                        .map(|(line, hits)| (line.to_string(), json!(hits)))
+                        .collect::<serde_json::Map<String, Value>>(),
+                    "covered": covered,
+                    "total": lines.len(),
+                })
+            })
+            .collect();
+        json!({ "files": files })
+    }
+
+    /// One `path: covered/total (pct%)` line per file
+    pub fn summary(&self) -> Vec<String> {
+        self.line_hits()
+            .into_iter()
+            .map(|(path, lines)| {
+                let covered = lines.values().filter(|&&hits| hits > 0).count();
+                let pct = if lines.is_empty() {
+                    100.0
+                } else {
+                    covered as f64 * 100.0 / lines.len() as f64
+                };
+                format!(
+                    "{}: {}/{} lines ({:.1}%)",
+                    path.display(),
+                    covered,
+                    lines.len(),
+                    pct
+                )
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counts_hits_per_line() {
+        let mut coverage = Coverage::new();
+        // Two statements on line 1, one each on lines 2 and 3
+        coverage.add_chunk(Path::new("a.ot"), 10, &[(0, 1), (2, 1), (5, 2), (8, 3)]);
+        for ip in [10, 12, 12, 15] {
+            coverage.hit(ip);
+        }
+        coverage.hit(11);
+
+        let hits = coverage.line_hits();
+        assert_eq!(hits[0].1, BTreeMap::from([(1, 2), (2, 1), (3, 0)]));
+        assert_eq!(
+            coverage.to_lcov(),
+            "TN:\nSF:a.ot\nDA:1,2\nDA:2,1\nDA:3,0\nLF:3\nLH:2\nend_of_record\n"
+        );
+        assert_eq!(coverage.to_json()["files"][0]["covered"], 2);
+    }
+}
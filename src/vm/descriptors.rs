@@ -0,0 +1,172 @@
+//! Property attributes (`Object.defineProperty`, `Object.freeze`)
+//!
+//! Objects are plain maps from name to value, so attributes live in a side
+//! table keyed by heap index. A property without an entry is writable,
+//! enumerable and configurable, which is what assignment creates, so only
+//! objects passed to `defineProperty` or `freeze` have an entry at all and
+//! every other write costs one hash lookup.
+
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PropertyFlags {
+    /// Assignment may change the value
+    pub writable: bool,
+    /// Listed by `Object.keys` and `Object.entries`
+    pub enumerable: bool,
+    /// May be deleted or redefined
+    pub configurable: bool,
+}
+
+impl PropertyFlags {
+    /// Attributes `defineProperty` gives a new property when the descriptor
+    /// leaves them out
+    pub const NONE: PropertyFlags = PropertyFlags {
+        writable: false,
+        enumerable: false,
+        configurable: false,
+    };
+}
+
+impl Default for PropertyFlags {
+    fn default() -> Self {
+        Self {
+            writable: true,
+            enumerable: true,
+            configurable: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct ObjectAttributes {
+    props: HashMap<String, PropertyFlags>,
+    /// New properties may be added
+    extensible: bool,
+}
+
+impl Default for ObjectAttributes {
+    fn default() -> Self {
+        Self {
+            props: HashMap::new(),
+            extensible: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DescriptorTable {
+    objects: HashMap<usize, ObjectAttributes>,
+}
+
+impl DescriptorTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Attributes of `key` on the object at `ptr`
+    pub fn flags(&self, ptr: usize, key: &str) -> PropertyFlags {
+        self.objects
+            .get(&ptr)
+            .and_then(|attrs| attrs.props.get(key))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn set_flags(&mut self, ptr: usize, key: &str, flags: PropertyFlags) {
+        let attrs = self.objects.entry(ptr).or_default();
+        if flags == PropertyFlags::default() {
+            attrs.props.remove(key);
+        } else {
+            attrs.props.insert(key.to_string(), flags);
+        }
+    }
+
+    pub fn is_extensible(&self, ptr: usize) -> bool {
+        self.objects.get(&ptr).is_none_or(|attrs| attrs.extensible)
+    }
+
+    pub fn prevent_extensions(&mut self, ptr: usize) {
+        self.objects.entry(ptr).or_default().extensible = false;
+    }
+
+    /// Every property with attributes of its own, by object
+    pub fn entries(&self) -> impl Iterator<Item = (usize, &str, PropertyFlags)> {
+        self.objects.iter().flat_map(|(&ptr, attrs)| {
+            attrs
+                .props
+                .iter()
+                .map(move |(key, &flags)| (ptr, key.as_str(), flags))
+        })
+    }
+
+    /// Objects that may not gain properties
+    pub fn non_extensible(&self) -> impl Iterator<Item = usize> {
+        self.objects
+            .iter()
+            .filter(|(_, attrs)| !attrs.extensible)
+            .map(|(&ptr, _)| ptr)
+    }
+
+    /// Forget the attributes of a deleted property
+    pub fn remove(&mut self, ptr: usize, key: &str) {
+        if let Some(attrs) = self.objects.get_mut(&ptr) {
+            attrs.props.remove(key);
+        }
+    }
+
+    /// Check an assignment to `key` on the object at `ptr`; `exists` says
+    /// whether the object already has the property. The error is the
+    /// TypeError to throw.
+    pub fn check_write(
+        &self,
+        ptr: usize,
+        key: &str,
+        exists: impl FnOnce() -> bool,
+    ) -> Result<(), String> {
+        let Some(attrs) = self.objects.get(&ptr) else {
+            return Ok(());
+        };
+        match attrs.props.get(key) {
+            Some(flags) if !flags.writable => Err(format!(
+                "TypeError: Cannot assign to read only property '{}' of object",
+                key
+            )),
+            Some(_) => Ok(()),
+            None if !attrs.extensible && !exists() => Err(format!(
+                "TypeError: Cannot add property {}, object is not extensible",
+                key
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frozen_object_rejects_writes() {
+        let mut table = DescriptorTable::new();
+        assert!(table.check_write(1, "x", || false).is_ok());
+
+        table.set_flags(
+            1,
+            "x",
+            PropertyFlags {
+                enumerable: true,
+                ..PropertyFlags::NONE
+            },
+        );
+        table.prevent_extensions(1);
+        assert!(table.check_write(1, "x", || true).is_err());
+        assert!(table.check_write(1, "y", || false).is_err());
+        assert!(table.check_write(2, "x", || true).is_ok());
+        assert!(table.flags(1, "x").enumerable);
+        assert!(table.flags(1, "y").writable);
+
+        table.set_flags(1, "x", PropertyFlags::default());
+        assert!(table.check_write(1, "x", || true).is_ok());
+    }
+}
@@ -0,0 +1,282 @@
+//! Bytecode disassembler (`oitec disasm <file>`)
+//!
+//! Lists a program one instruction per line, in a form meant for reading
+//! rather than the raw `Debug` dump of `--inspect-bc`:
+//!
+//! ```text
+//! constants:
+//!   #0  "greet"
+//!   #1  "name"
+//!
+//! (top level):
+//!        0  MakeClosure greet
+//!        1  Let #0 "greet"
+//!        2  Jump L1
+//!
+//! greet:
+//!        3  LoadArg 0
+//!        4  Let #1 "name"
+//! L1:
+//!        9  Load #0 "greet"
+//! ```
+//!
+//! Each function starts under its name (`anonymous@<address>` if it has
+//! none), jump targets get `L<n>` labels, code addresses show the function
+//! they start, pinned globals their name, and strings their index in the constant
+//! pool of a version 2 bytecode file (see `loader::encoder`). With a line
+//! table and the source text, each statement's line is shown ahead of its
+//! first instruction.
+
+use super::opcodes::{OpCode, PINNED_GLOBALS};
+use super::profiler::TOP_LEVEL;
+use super::startup_snapshot::Writer;
+use super::value::JsValue;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Write as _;
+
+/// The program listing. `functions` names function entry points (see
+/// `profiler::function_names`); `interleave` is a line table and the source
+/// it refers to.
+pub fn disassemble(
+    program: &[OpCode],
+    functions: &HashMap<usize, String>,
+    interleave: Option<(&[(usize, u32)], &str)>,
+) -> String {
+    let constants = constant_pool(program);
+    let labels: HashMap<usize, usize> = jump_targets(program)
+        .into_iter()
+        .enumerate()
+        .map(|(n, target)| (target, n + 1))
+        .collect();
+    let lines: HashMap<usize, u32> = interleave
+        .map(|(table, _)| table.iter().copied().collect())
+        .unwrap_or_default();
+    let source: Vec<&str> = interleave
+        .map(|(_, source)| source.lines().collect())
+        .unwrap_or_default();
+    let entries: BTreeSet<usize> = program
+        .iter()
+        .filter_map(|op| match op {
+            OpCode::MakeClosure(address) | OpCode::Push(JsValue::Function { address, .. }) => {
+                Some(*address)
+            }
+            _ => None,
+        })
+        .chain(functions.keys().copied())
+        .collect();
+    let listing = Listing {
+        functions,
+        labels: &labels,
+        constants: &constants,
+    };
+
+    let mut out = String::new();
+    if !constants.is_empty() {
+        out.push_str("constants:\n");
+        let mut pool: Vec<(&String, &usize)> = constants.iter().collect();
+        pool.sort_by_key(|(_, index)| **index);
+        for (constant, index) in pool {
+            let _ = writeln!(out, "  #{:<4} {:?}", index, constant);
+        }
+        out.push('\n');
+    }
+    let _ = writeln!(out, "{}:", TOP_LEVEL);
+    for (ip, op) in program.iter().enumerate() {
+        if entries.contains(&ip) {
+            let _ = writeln!(out, "\n{}:", listing.function(ip));
+        }
+        if let Some(label) = labels.get(&ip) {
+            let _ = writeln!(out, "L{}:", label);
+        }
+        if let Some(&line) = lines.get(&ip)
+            && let Some(text) = source.get((line as usize).wrapping_sub(1))
+        {
+            let _ = writeln!(out, "         ; {:>4} | {}", line, text.trim_end());
+        }
+        let _ = writeln!(out, "  {:>6}  {}", ip, listing.render(op));
+    }
+    if let Some(label) = labels.get(&program.len()) {
+        let _ = writeln!(out, "L{}:      (end)", label);
+    }
+    out
+}
+
+struct Listing<'a> {
+    functions: &'a HashMap<usize, String>,
+    labels: &'a HashMap<usize, usize>,
+    constants: &'a HashMap<String, usize>,
+}
+
+impl Listing<'_> {
+    fn render(&self, op: &OpCode) -> String {
+        match op {
+            OpCode::Push(value) => format!("Push {}", self.value(value)),
+            OpCode::Let(name) => format!("Let {}", self.string(name)),
+            OpCode::Store(name) => format!("Store {}", self.string(name)),
+            OpCode::Load(name) => format!("Load {}", self.string(name)),
+            OpCode::Drop(name) => format!("Drop {}", self.string(name)),
+            OpCode::SetProp(name) => format!("SetProp {}", self.string(name)),
+            OpCode::GetProp(name) => format!("GetProp {}", self.string(name)),
+            OpCode::Delete(name) => format!("Delete {}", self.string(name)),
+            OpCode::GetSuperProp(name) => format!("GetSuperProp {}", self.string(name)),
+            OpCode::CallMethod(name, argc) => {
+                format!("CallMethod {} {}", self.string(name), argc)
+            }
+            OpCode::Jump(target) => format!("Jump {}", self.label(*target)),
+            OpCode::JumpIfFalse(target) => format!("JumpIfFalse {}", self.label(*target)),
+            OpCode::MakeClosure(address) => format!("MakeClosure {}", self.function(*address)),
+            OpCode::SetupTry {
+                catch_addr,
+                finally_addr,
+            } => {
+                let mut text = format!("SetupTry catch {}", self.label(*catch_addr));
+                if *finally_addr != 0 {
+                    let _ = write!(text, " finally {}", self.label(*finally_addr));
+                }
+                text
+            }
+            OpCode::LoadGlobal(slot) => match PINNED_GLOBALS.get(*slot as usize) {
+                Some(name) => format!("LoadGlobal {} ({})", slot, name),
+                None => format!("LoadGlobal {}", slot),
+            },
+            OpCode::ImportAsync { specifier, json } => format!(
+                "ImportAsync {}{}",
+                self.string(specifier),
+                if *json { " json" } else { "" }
+            ),
+            OpCode::GetExport { name, is_default } => format!(
+                "GetExport {}{}",
+                self.string(name),
+                if *is_default { " default" } else { "" }
+            ),
+            OpCode::ImportBinding { name, local } => {
+                format!(
+                    "ImportBinding {} as {}",
+                    self.string(name),
+                    self.string(local)
+                )
+            }
+            OpCode::ReExport { name, exported } => {
+                format!(
+                    "ReExport {} as {}",
+                    self.string(name),
+                    self.string(exported)
+                )
+            }
+            // `Call(2)` reads as `Call 2`; the rest are shown as they are
+            other => {
+                let text = format!("{:?}", other);
+                match text.split_once('(') {
+                    Some((name, args)) if args.ends_with(')') => {
+                        format!("{} {}", name, &args[..args.len() - 1])
+                    }
+                    _ => text,
+                }
+            }
+        }
+    }
+
+    fn string(&self, s: &str) -> String {
+        match self.constants.get(s) {
+            Some(index) => format!("#{} {:?}", index, s),
+            None => format!("{:?}", s),
+        }
+    }
+
+    fn value(&self, value: &JsValue) -> String {
+        match value {
+            JsValue::String(s) => self.string(s),
+            JsValue::Number(n) => n.to_string(),
+            JsValue::Function { address, .. } => self.function(*address),
+            other => format!("{:?}", other),
+        }
+    }
+
+    fn label(&self, target: usize) -> String {
+        match self.labels.get(&target) {
+            Some(label) => format!("L{}", label),
+            None => format!("@{}", target),
+        }
+    }
+
+    fn function(&self, address: usize) -> String {
+        match self.functions.get(&address) {
+            Some(name) => name.clone(),
+            None => format!("anonymous@{}", address),
+        }
+    }
+}
+
+/// Instructions something jumps to, in program order
+fn jump_targets(program: &[OpCode]) -> BTreeSet<usize> {
+    let mut targets = BTreeSet::new();
+    for op in program {
+        match op {
+            OpCode::Jump(target) | OpCode::JumpIfFalse(target) => {
+                targets.insert(*target);
+            }
+            OpCode::SetupTry {
+                catch_addr,
+                finally_addr,
+            } => {
+                targets.insert(*catch_addr);
+                if *finally_addr != 0 {
+                    targets.insert(*finally_addr);
+                }
+            }
+            _ => {}
+        }
+    }
+    targets
+}
+
+/// Index of each string in the constant pool a version 2 bytecode file of
+/// `program` would have; empty if the program can't be written as one
+fn constant_pool(program: &[OpCode]) -> HashMap<String, usize> {
+    let mut writer = Writer::pooled();
+    if writer.program(program).is_err() {
+        return HashMap::new();
+    }
+    writer
+        .take_pool()
+        .into_iter()
+        .enumerate()
+        .map(|(index, constant)| (constant, index))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::Compiler;
+    use crate::vm::profiler::function_names;
+
+    #[test]
+    fn test_listing_resolves_labels_functions_and_constants() {
+        let source = "function greet(name) {\n  return 'hi ' + name;\n}\nlet n = 0;\nwhile (n < 2) {\n  console.log(greet('a'));\n  n = n + 1;\n}\n";
+        let mut compiler = Compiler::new();
+        let program = compiler.compile(source).unwrap();
+        let functions = function_names(&program);
+        let listing = disassemble(
+            &program,
+            &functions,
+            Some((compiler.line_table.as_slice(), source)),
+        );
+
+        assert!(listing.starts_with("constants:\n  #0"));
+        assert!(listing.contains("\ngreet:\n"));
+        assert!(listing.contains("Push greet"));
+        assert!(listing.contains("LoadGlobal 0 (console)"));
+        assert!(listing.contains("\"hi \""));
+        // The loop's condition is a labelled jump target
+        let jump = listing
+            .lines()
+            .find(|line| line.trim_start().contains("JumpIfFalse L"))
+            .unwrap();
+        let label = jump.rsplit(' ').next().unwrap();
+        assert!(listing.contains(&format!("\n{}:", label)));
+        // Statements are preceded by their source line
+        assert!(listing.contains("|   n = n + 1;"));
+        assert!(!listing.contains("Jump("));
+    }
+}
@@ -0,0 +1,294 @@
+//! The VM object heap, addressed by generational handles
+//!
+//! `JsValue::Object` holds a handle rather than a bare index: the low
+//! `INDEX_BITS` select a slot and the bits above them hold the generation
+//! the slot had when the object was allocated. Freeing an object bumps its
+//! slot's generation, so a handle kept past the free (or made up from a
+//! number) no longer resolves instead of silently reading whatever object
+//! reuses the slot. A slot that has never been freed is at generation 0,
+//! where a handle equals its index, so `let ptr = heap.len(); heap.push(..)`
+//! still yields the right handle.
+//!
+//! Handles stay below 2^52 so they survive a round trip through an f64.
+
+use super::value::HeapObject;
+use std::fmt;
+use std::ops::{Index, IndexMut};
+
+const INDEX_BITS: u32 = 32;
+const GENERATION_BITS: u32 = 20;
+const INDEX_MASK: usize = (1 << INDEX_BITS) - 1;
+const MAX_GENERATION: usize = (1 << GENERATION_BITS) - 1;
+
+#[derive(Debug, Clone)]
+struct Slot {
+    generation: usize,
+    /// `None` once the object has been freed
+    object: Option<HeapObject>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Heap {
+    slots: Vec<Slot>,
+    /// Freed slots that `alloc` may reuse
+    free: Vec<usize>,
+}
+
+/// Why a handle didn't resolve
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeapError {
+    /// The object was freed; its slot may since hold another one
+    Stale(usize),
+    /// Never handed out by this heap
+    Invalid(usize),
+}
+
+impl fmt::Display for HeapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HeapError::Stale(handle) => write!(f, "stale heap handle {:#x}", handle),
+            HeapError::Invalid(handle) => write!(f, "invalid heap handle {:#x}", handle),
+        }
+    }
+}
+
+fn make_handle(slot: usize, generation: usize) -> usize {
+    (generation << INDEX_BITS) | slot
+}
+
+fn slot_of(handle: usize) -> (usize, usize) {
+    (handle & INDEX_MASK, handle >> INDEX_BITS)
+}
+
+impl Heap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of slots, live or freed
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    /// Number of live objects
+    pub fn live_count(&self) -> usize {
+        self.slots
+            .iter()
+            .filter(|entry| entry.object.is_some())
+            .count()
+    }
+
+    /// Append an object in a fresh slot; its handle is the old `len()`
+    pub fn push(&mut self, object: HeapObject) {
+        self.slots.push(Slot {
+            generation: 0,
+            object: Some(object),
+        });
+    }
+
+    /// Store an object, reusing a freed slot when there is one
+    pub fn alloc(&mut self, object: HeapObject) -> usize {
+        match self.free.pop() {
+            Some(slot) => {
+                let entry = &mut self.slots[slot];
+                entry.object = Some(object);
+                make_handle(slot, entry.generation)
+            }
+            None => {
+                let handle = self.slots.len();
+                self.push(object);
+                handle
+            }
+        }
+    }
+
+    /// Slot index of a handle that still refers to a live object
+    pub fn slot(&self, handle: usize) -> Option<usize> {
+        self.check(handle).ok()
+    }
+
+    fn check(&self, handle: usize) -> Result<usize, HeapError> {
+        let (slot, generation) = slot_of(handle);
+        match self.slots.get(slot) {
+            Some(entry) if entry.generation == generation && entry.object.is_some() => Ok(slot),
+            Some(entry) if generation <= entry.generation => Err(HeapError::Stale(handle)),
+            _ => Err(HeapError::Invalid(handle)),
+        }
+    }
+
+    /// Current handle of a live slot
+    pub fn handle(&self, slot: usize) -> Option<usize> {
+        let entry = self.slots.get(slot)?;
+        entry
+            .object
+            .as_ref()
+            .map(|_| make_handle(slot, entry.generation))
+    }
+
+    pub fn contains(&self, handle: usize) -> bool {
+        self.slot(handle).is_some()
+    }
+
+    pub fn get(&self, handle: usize) -> Option<&HeapObject> {
+        self.resolve(handle).ok()
+    }
+
+    pub fn get_mut(&mut self, handle: usize) -> Option<&mut HeapObject> {
+        self.resolve_mut(handle).ok()
+    }
+
+    /// The object `handle` refers to, or why there is none
+    pub fn resolve(&self, handle: usize) -> Result<&HeapObject, HeapError> {
+        let slot = self.check(handle)?;
+        self.slots[slot]
+            .object
+            .as_ref()
+            .ok_or(HeapError::Stale(handle))
+    }
+
+    pub fn resolve_mut(&mut self, handle: usize) -> Result<&mut HeapObject, HeapError> {
+        let slot = self.check(handle)?;
+        self.slots[slot]
+            .object
+            .as_mut()
+            .ok_or(HeapError::Stale(handle))
+    }
+
+    /// Release an object. Every existing handle to it stops resolving, and
+    /// the slot goes back on the free list unless its generation is used up.
+    pub fn free(&mut self, handle: usize) -> Result<HeapObject, HeapError> {
+        let slot = self.check(handle)?;
+        let entry = &mut self.slots[slot];
+        let object = entry.object.take().ok_or(HeapError::Stale(handle))?;
+        if entry.generation < MAX_GENERATION {
+            entry.generation += 1;
+            self.free.push(slot);
+        }
+        Ok(object)
+    }
+
+    /// Live objects with their handles, in slot order
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &HeapObject)> {
+        self.slots.iter().enumerate().filter_map(|(slot, entry)| {
+            entry
+                .object
+                .as_ref()
+                .map(|object| (make_handle(slot, entry.generation), object))
+        })
+    }
+
+    /// Every slot's generation and object, freed slots included, followed by
+    /// the free list; `from_slots` rebuilds the same heap (`vm::snapshot`)
+    pub fn slots(&self) -> (impl Iterator<Item = (usize, Option<&HeapObject>)>, &[usize]) {
+        let slots = self
+            .slots
+            .iter()
+            .map(|entry| (entry.generation, entry.object.as_ref()));
+        (slots, &self.free)
+    }
+
+    pub fn from_slots(slots: Vec<(usize, Option<HeapObject>)>, free: Vec<usize>) -> Self {
+        let slots = slots
+            .into_iter()
+            .map(|(generation, object)| Slot { generation, object })
+            .collect();
+        Self { slots, free }
+    }
+}
+
+impl Index<usize> for Heap {
+    type Output = HeapObject;
+
+    fn index(&self, handle: usize) -> &HeapObject {
+        match self.resolve(handle) {
+            Ok(object) => object,
+            Err(err) => panic!("{}", err),
+        }
+    }
+}
+
+impl IndexMut<usize> for Heap {
+    fn index_mut(&mut self, handle: usize) -> &mut HeapObject {
+        match self.resolve_mut(handle) {
+            Ok(object) => object,
+            Err(err) => panic!("{}", err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vm::value::HeapData;
+
+    fn array(len: usize) -> HeapObject {
+        HeapObject {
+            data: HeapData::Array(vec![crate::vm::value::JsValue::Undefined; len]),
+        }
+    }
+
+    fn array_len(heap: &Heap, handle: usize) -> Option<usize> {
+        match &heap.get(handle)?.data {
+            HeapData::Array(items) => Some(items.len()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_fresh_handles_are_indices() {
+        let mut heap = Heap::new();
+        let first = heap.len();
+        heap.push(array(1));
+        assert_eq!(first, 0);
+        assert_eq!(heap.alloc(array(2)), 1);
+        assert_eq!(array_len(&heap, 1), Some(2));
+        assert_eq!(heap.handle(1), Some(1));
+    }
+
+    #[test]
+    fn test_stale_handle_is_rejected_after_reuse() {
+        let mut heap = Heap::new();
+        let old = heap.alloc(array(1));
+        assert!(heap.free(old).is_ok());
+        assert_eq!(heap.resolve(old).unwrap_err(), HeapError::Stale(old));
+        assert_eq!(heap.free(old).unwrap_err(), HeapError::Stale(old));
+
+        let new = heap.alloc(array(3));
+        assert_ne!(new, old);
+        assert_eq!(heap.slot(new), Some(0));
+        assert_eq!(array_len(&heap, new), Some(3));
+        assert_eq!(heap.resolve(old).unwrap_err(), HeapError::Stale(old));
+        assert_eq!(heap.len(), 1);
+        assert_eq!(
+            heap.iter().map(|(handle, _)| handle).collect::<Vec<_>>(),
+            [new]
+        );
+    }
+
+    #[test]
+    fn test_forged_handles_do_not_resolve() {
+        let mut heap = Heap::new();
+        let handle = heap.alloc(array(1));
+        assert!(!heap.contains(handle + 1));
+        assert!(!heap.contains(make_handle(handle, 7)));
+        assert!(!heap.contains(usize::MAX));
+        assert_eq!(
+            heap.resolve(handle + 1).unwrap_err(),
+            HeapError::Invalid(handle + 1)
+        );
+        assert!(heap.contains(handle));
+    }
+
+    #[test]
+    #[should_panic(expected = "stale heap handle")]
+    fn test_indexing_a_freed_handle_panics() {
+        let mut heap = Heap::new();
+        let handle = heap.alloc(array(1));
+        heap.free(handle).unwrap();
+        let _ = &heap[handle];
+    }
+}
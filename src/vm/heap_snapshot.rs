@@ -0,0 +1,559 @@
+//! Heap snapshots for diagnosing memory growth (`oitec heap`, `process.memoryUsage()`)
+//!
+//! The VM heap is never collected, so a snapshot reports what is still
+//! reachable from the roots (globals, live frames, the operand stack, pending
+//! tasks and loaded modules) separately from what is only kept alive by the
+//! heap itself. The walks below index by heap slot and skip freed slots.
+//!
+//! Sizes are estimates of the Rust-side footprint: the `HeapObject` itself,
+//! its entries, and the bytes of any strings stored inline.
+
+use crate::vm::VM;
+use crate::vm::heap::Heap;
+use crate::vm::value::{BufferStorage, HeapData, HeapObject, JsValue};
+use serde_json::{Value, json};
+use std::collections::VecDeque;
+use std::mem::size_of;
+
+/// Heap object kinds, in report order
+pub const KINDS: [&str; 12] = [
+    "Object",
+    "Array",
+    "ByteStream",
+    "Map",
+    "Set",
+    "Buffer",
+    "Decimal",
+    "WeakMap",
+    "WeakSet",
+    "WeakRef",
+    "Stream",
+    "Process",
+];
+
+/// Objects listed in the "largest objects" section
+const LARGEST_LIMIT: usize = 10;
+
+/// Retained-by marker for objects reachable from more than one root
+const SHARED: usize = usize::MAX;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Usage {
+    pub count: usize,
+    pub bytes: usize,
+}
+
+impl Usage {
+    fn add(&mut self, bytes: usize) {
+        self.count += 1;
+        self.bytes += bytes;
+    }
+
+    fn to_json(self) -> Value {
+        json!({ "count": self.count, "bytes": self.bytes })
+    }
+}
+
+/// Objects kept alive by exactly one root
+#[derive(Debug, Clone, PartialEq)]
+pub struct RootUsage {
+    pub name: String,
+    pub retained: Usage,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjectInfo {
+    pub index: usize,
+    pub kind: &'static str,
+    pub bytes: usize,
+    /// Number of references to this object from other heap objects
+    pub referrers: usize,
+    /// Shortest property path from a root, e.g. `cache.entries[3]`
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeapSnapshot {
+    /// Every heap slot, by kind (same order as `KINDS`)
+    pub by_kind: [Usage; 12],
+    pub total: Usage,
+    /// Objects reachable from any root
+    pub reachable: Usage,
+    /// Roots ordered by how much they alone keep alive, largest first
+    pub roots: Vec<RootUsage>,
+    /// Reachable objects with the largest shallow size
+    pub largest: Vec<ObjectInfo>,
+}
+
+impl HeapSnapshot {
+    pub fn capture(vm: &VM) -> Self {
+        let heap = &vm.heap;
+        let objects = live_slots(heap);
+        let sizes: Vec<usize> = objects
+            .iter()
+            .map(|object| object.map_or(0, shallow_size))
+            .collect();
+
+        let mut by_kind = [Usage::default(); 12];
+        let mut total = Usage::default();
+        let mut referrers = vec![0usize; heap.len()];
+        for (index, object) in objects.iter().enumerate() {
+            let Some(object) = object else { continue };
+            by_kind[kind_index(&object.data)].add(sizes[index]);
+            total.add(sizes[index]);
+            for_each_child(object, |child| {
+                if let Some(child) = heap.slot(child)
+                    && child != index
+                {
+                    referrers[child] += 1;
+                }
+            });
+        }
+
+        // One breadth-first walk per root assigns each object to the single
+        // root that retains it, or marks it shared
+        let roots = collect_roots(vm);
+        let mut owner = vec![None; heap.len()];
+        for (root_index, (_, values)) in roots.iter().enumerate() {
+            let mut seen = vec![false; heap.len()];
+            let mut queue = VecDeque::new();
+            for value in values {
+                value_pointers(value, &mut |ptr| queue.push_back(ptr));
+            }
+            while let Some(ptr) = queue.pop_front() {
+                let Some(ptr) = heap.slot(ptr) else { continue };
+                if seen[ptr] {
+                    continue;
+                }
+                seen[ptr] = true;
+                owner[ptr] = match owner[ptr] {
+                    None => Some(root_index),
+                    Some(existing) if existing == root_index => Some(existing),
+                    Some(_) => Some(SHARED),
+                };
+                if let Some(object) = objects[ptr] {
+                    for_each_child(object, |child| queue.push_back(child));
+                }
+            }
+        }
+
+        let mut reachable = Usage::default();
+        let mut retained = vec![Usage::default(); roots.len()];
+        for (ptr, owner) in owner.iter().enumerate() {
+            match owner {
+                Some(SHARED) => reachable.add(sizes[ptr]),
+                Some(root) => {
+                    reachable.add(sizes[ptr]);
+                    retained[*root].add(sizes[ptr]);
+                }
+                None => {}
+            }
+        }
+        let mut root_usage: Vec<RootUsage> = roots
+            .iter()
+            .zip(retained)
+            .filter(|(_, usage)| usage.count > 0)
+            .map(|((name, _), retained)| RootUsage {
+                name: name.clone(),
+                retained,
+            })
+            .collect();
+        root_usage.sort_by(|a, b| {
+            b.retained
+                .bytes
+                .cmp(&a.retained.bytes)
+                .then_with(|| a.name.cmp(&b.name))
+        });
+
+        let mut candidates: Vec<usize> = (0..heap.len()).filter(|&p| owner[p].is_some()).collect();
+        candidates.sort_by(|&a, &b| sizes[b].cmp(&sizes[a]).then(a.cmp(&b)));
+        candidates.truncate(LARGEST_LIMIT);
+        let paths = shortest_paths(heap, &objects, &roots, &candidates);
+        let largest = candidates
+            .iter()
+            .zip(paths)
+            .filter_map(|(&index, path)| {
+                Some(ObjectInfo {
+                    index: heap.handle(index)?,
+                    kind: KINDS[kind_index(&objects[index]?.data)],
+                    bytes: sizes[index],
+                    referrers: referrers[index],
+                    path,
+                })
+            })
+            .collect();
+
+        Self {
+            by_kind,
+            total,
+            reachable,
+            roots: root_usage,
+            largest,
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        let unreachable = Usage {
+            count: self.total.count - self.reachable.count,
+            bytes: self.total.bytes - self.reachable.bytes,
+        };
+        let by_kind: serde_json::Map<String, Value> = KINDS
+            .iter()
+            .zip(self.by_kind)
+            .map(|(kind, usage)| (kind.to_string(), usage.to_json()))
+            .collect();
+        json!({
+            "total": self.total.to_json(),
+            "reachable": self.reachable.to_json(),
+            "unreachable": unreachable.to_json(),
+            "byType": by_kind,
+            "roots": self.roots.iter().map(|r| json!({
+                "name": r.name,
+                "retained": r.retained.to_json(),
+            })).collect::<Vec<_>>(),
+            "largest": self.largest.iter().map(|o| json!({
+                "index": o.index,
+                "type": o.kind,
+                "bytes": o.bytes,
+                "referrers": o.referrers,
+                "path": o.path,
+            })).collect::<Vec<_>>(),
+        })
+    }
+
+    pub fn print(&self) {
+        println!("=== Heap snapshot ===");
+        println!(
+            "  {} objects, {} bytes ({} reachable objects, {} bytes)",
+            self.total.count, self.total.bytes, self.reachable.count, self.reachable.bytes
+        );
+        println!();
+        println!("  {:<12} {:>10} {:>12}", "Type", "Count", "Bytes");
+        for (kind, usage) in KINDS.iter().zip(self.by_kind) {
+            println!("  {:<12} {:>10} {:>12}", kind, usage.count, usage.bytes);
+        }
+        if !self.roots.is_empty() {
+            println!();
+            println!("  Retained by root:");
+            for root in self.roots.iter().take(LARGEST_LIMIT) {
+                println!(
+                    "    {:<30} {:>8} objects {:>12} bytes",
+                    root.name, root.retained.count, root.retained.bytes
+                );
+            }
+        }
+        if !self.largest.is_empty() {
+            println!();
+            println!("  Largest objects:");
+            for object in &self.largest {
+                println!(
+                    "    #{:<8} {:<10} {:>10} bytes  {} referrer(s)  {}",
+                    object.index,
+                    object.kind,
+                    object.bytes,
+                    object.referrers,
+                    object.path.as_deref().unwrap_or("?")
+                );
+            }
+        }
+    }
+}
+
+fn kind_index(data: &HeapData) -> usize {
+    match data {
+        HeapData::Object(_) => 0,
+        HeapData::Array(_) => 1,
+        HeapData::ByteStream(_) => 2,
+        HeapData::Map(_) => 3,
+        HeapData::Set(_) => 4,
+        HeapData::Buffer(_) => 5,
+        HeapData::Decimal(_) => 6,
+        HeapData::WeakMap(_) => 7,
+        HeapData::WeakSet(_) => 8,
+        HeapData::WeakRef(_) => 9,
+        HeapData::Stream(_) => 10,
+        HeapData::Process(_) => 11,
+    }
+}
+
+/// Estimated bytes owned by a heap object, not counting objects it points to
+fn shallow_size(object: &HeapObject) -> usize {
+    let value_size = |v: &JsValue| {
+        size_of::<JsValue>()
+            + match v {
+                JsValue::String(s) => s.capacity(),
+                _ => 0,
+            }
+    };
+    size_of::<HeapObject>()
+        + match &object.data {
+            HeapData::Object(props) => props
+                .iter()
+                .map(|(key, value)| size_of::<String>() + key.capacity() + value_size(value))
+                .sum(),
+            HeapData::Array(items) | HeapData::Set(items) => items.iter().map(value_size).sum(),
+            HeapData::ByteStream(bytes) => bytes.capacity(),
+            // Mapped files live outside the heap
+            HeapData::Buffer(view) => match *view.storage {
+                BufferStorage::Owned(_) => view.len,
+                BufferStorage::Mapped(_) => 0,
+            },
+            HeapData::Decimal(value) => value.digit_count(),
+            HeapData::WeakMap(entries) => entries
+                .values()
+                .map(|value| size_of::<usize>() + value_size(value))
+                .sum(),
+            HeapData::WeakSet(ptrs) => ptrs.len() * size_of::<usize>(),
+            // Readers, writers and children live in their own tables
+            HeapData::WeakRef(_) | HeapData::Stream(_) | HeapData::Process(_) => 0,
+            HeapData::Map(entries) => entries
+                .iter()
+                .map(|(key, value)| value_size(key) + value_size(value))
+                .sum(),
+        }
+}
+
+/// Heap pointer held directly by a value
+fn value_pointer(value: &JsValue) -> Option<usize> {
+    match value {
+        JsValue::Object(ptr) => Some(*ptr),
+        JsValue::Function { env, .. } => *env,
+        JsValue::Promise(promise) => promise.get_value().as_ref().and_then(value_pointer),
+        _ => None,
+    }
+}
+
+/// Every heap pointer inside a value, including accessor functions and the
+/// arguments of bound functions
+pub(crate) fn value_pointers(value: &JsValue, f: &mut impl FnMut(usize)) {
+    if let JsValue::Accessor(getter, setter) = value {
+        for function in [getter, setter].into_iter().flatten() {
+            value_pointers(function, f);
+        }
+    } else if let JsValue::Function {
+        env,
+        bound: Some(bound),
+        ..
+    } = value
+    {
+        env.iter().chain([bound]).for_each(|ptr| f(*ptr));
+    } else if let Some(ptr) = value_pointer(value) {
+        f(ptr);
+    }
+}
+
+/// Visit the objects `object` references
+pub(crate) fn for_each_child(object: &HeapObject, mut f: impl FnMut(usize)) {
+    match &object.data {
+        HeapData::Object(props) => props.values().for_each(|v| value_pointers(v, &mut f)),
+        HeapData::Array(items) | HeapData::Set(items) => {
+            items.iter().for_each(|v| value_pointers(v, &mut f))
+        }
+        HeapData::Map(entries) => {
+            for (key, value) in entries {
+                value_pointers(key, &mut f);
+                value_pointers(value, &mut f);
+            }
+        }
+        // Keys and targets are weak, so only WeakMap values are edges
+        HeapData::WeakMap(entries) => entries.values().for_each(|v| value_pointers(v, &mut f)),
+        HeapData::ByteStream(_)
+        | HeapData::Buffer(_)
+        | HeapData::Decimal(_)
+        | HeapData::WeakSet(_)
+        | HeapData::WeakRef(_)
+        | HeapData::Stream(_)
+        | HeapData::Process(_) => {}
+    }
+}
+
+/// Like `for_each_child`, with the edge label used in paths
+fn for_each_edge(object: &HeapObject, mut f: impl FnMut(usize, String)) {
+    match &object.data {
+        HeapData::Object(props) => {
+            for (key, value) in props {
+                value_pointers(value, &mut |ptr| f(ptr, format!(".{}", key)));
+            }
+        }
+        HeapData::Array(items) | HeapData::Set(items) => {
+            for (i, value) in items.iter().enumerate() {
+                value_pointers(value, &mut |ptr| f(ptr, format!("[{}]", i)));
+            }
+        }
+        HeapData::Map(entries) => {
+            for (i, (key, value)) in entries.iter().enumerate() {
+                value_pointers(key, &mut |ptr| f(ptr, format!("<key {}>", i)));
+                value_pointers(value, &mut |ptr| f(ptr, format!("<value {}>", i)));
+            }
+        }
+        HeapData::WeakMap(entries) => {
+            for (key, value) in entries {
+                value_pointers(value, &mut |ptr| f(ptr, format!("<value of #{}>", key)));
+            }
+        }
+        HeapData::ByteStream(_)
+        | HeapData::Buffer(_)
+        | HeapData::Decimal(_)
+        | HeapData::WeakSet(_)
+        | HeapData::WeakRef(_)
+        | HeapData::Stream(_)
+        | HeapData::Process(_) => {}
+    }
+}
+
+/// Named root sets: each global binding, then non-global frames, the operand
+/// stack, pending tasks and the namespaces, scopes and `import.meta` of
+/// loaded modules.
+fn collect_roots(vm: &VM) -> Vec<(String, Vec<JsValue>)> {
+    let mut roots = Vec::new();
+    if let Some(globals) = vm.call_stack.first() {
+        let mut names: Vec<&String> = globals.locals.keys().collect();
+        names.sort();
+        for name in names {
+            roots.push((name.clone(), vec![globals.locals[name].clone()]));
+        }
+    }
+    for (depth, frame) in vm.call_stack.iter().enumerate().skip(1) {
+        let mut values: Vec<JsValue> = frame.locals.values().cloned().collect();
+        values.extend(frame.indexed_locals.iter().cloned());
+        values.push(frame.this_context.clone());
+        values.extend(frame.new_target.iter().cloned());
+        roots.push((format!("(frame {})", depth), values));
+    }
+    roots.push(("(stack)".to_string(), vm.stack.clone()));
+
+    let mut pending = Vec::new();
+    for task in vm
+        .task_queue
+        .iter()
+        .chain(vm.timers.iter().map(|timer| &timer.task))
+    {
+        pending.push(task.function_ptr.clone());
+        pending.extend(task.args.iter().cloned());
+    }
+    roots.push(("(pending tasks)".to_string(), pending));
+
+    let mut modules: Vec<_> = vm.module_cache.entries().values().collect();
+    modules.sort_by(|a, b| a.path.cmp(&b.path));
+    for module in modules {
+        let mut values = vec![JsValue::Object(module.namespace_object)];
+        values.extend(
+            vm.module_scopes
+                .iter()
+                .filter(|scope| scope.path == module.path)
+                .map(|scope| JsValue::Object(scope.environment)),
+        );
+        values.extend(
+            vm.import_metas
+                .get(&module.path)
+                .map(|&meta| JsValue::Object(meta)),
+        );
+        roots.push((format!("(module {})", module.path.display()), values));
+    }
+    roots
+}
+
+/// Each slot's object, or `None` for a freed slot
+fn live_slots(heap: &Heap) -> Vec<Option<&HeapObject>> {
+    (0..heap.len())
+        .map(|slot| heap.handle(slot).and_then(|ptr| heap.get(ptr)))
+        .collect()
+}
+
+/// Shortest path from a root to each target, found by one breadth-first walk
+fn shortest_paths(
+    heap: &Heap,
+    objects: &[Option<&HeapObject>],
+    roots: &[(String, Vec<JsValue>)],
+    targets: &[usize],
+) -> Vec<Option<String>> {
+    // parent[ptr] = (previous object or None for a root edge, edge label)
+    let mut parent: Vec<Option<(Option<usize>, String)>> = vec![None; heap.len()];
+    let mut queue = VecDeque::new();
+    for (name, values) in roots {
+        for value in values {
+            value_pointers(value, &mut |ptr| {
+                if let Some(ptr) = heap.slot(ptr)
+                    && parent[ptr].is_none()
+                {
+                    parent[ptr] = Some((None, name.clone()));
+                    queue.push_back(ptr);
+                }
+            });
+        }
+    }
+    while let Some(ptr) = queue.pop_front() {
+        let Some(object) = objects[ptr] else { continue };
+        for_each_edge(object, |child, label| {
+            if let Some(child) = heap.slot(child)
+                && parent[child].is_none()
+            {
+                parent[child] = Some((Some(ptr), label));
+                queue.push_back(child);
+            }
+        });
+    }
+
+    targets
+        .iter()
+        .map(|&target| {
+            let mut labels = Vec::new();
+            let mut current = target;
+            // Bounded by heap size in case a root edge is missing
+            for _ in 0..heap.len() {
+                let (previous, label) = parent[current].as_ref()?;
+                labels.push(label.clone());
+                match previous {
+                    Some(previous) => current = *previous,
+                    None => {
+                        labels.reverse();
+                        return Some(labels.concat());
+                    }
+                }
+            }
+            None
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_snapshot_counts_kinds_and_retainers() {
+        let mut vm = VM::new_bare();
+        let array = vm.heap.len();
+        vm.heap.push(HeapObject {
+            data: HeapData::Array(vec![JsValue::Number(1.0); 8]),
+        });
+        let holder = vm.heap.len();
+        vm.heap.push(HeapObject {
+            data: HeapData::Object(HashMap::from([(
+                "items".to_string(),
+                JsValue::Object(array),
+            )])),
+        });
+        // Unreachable garbage
+        vm.heap.push(HeapObject {
+            data: HeapData::ByteStream(vec![0; 64]),
+        });
+        vm.call_stack[0]
+            .locals
+            .insert("cache".to_string(), JsValue::Object(holder));
+
+        let snapshot = HeapSnapshot::capture(&vm);
+        assert_eq!(snapshot.total.count, 3);
+        assert_eq!(snapshot.reachable.count, 2);
+        assert_eq!(snapshot.by_kind[1].count, 1);
+        assert_eq!(snapshot.by_kind[2].count, 1);
+        assert_eq!(snapshot.roots[0].name, "cache");
+        assert_eq!(snapshot.roots[0].retained.count, 2);
+
+        let array_info = snapshot.largest.iter().find(|o| o.index == array).unwrap();
+        assert_eq!(array_info.path.as_deref(), Some("cache.items"));
+        assert_eq!(array_info.referrers, 1);
+        assert_eq!(snapshot.to_json()["unreachable"]["count"], 1);
+    }
+}
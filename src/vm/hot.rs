@@ -0,0 +1,502 @@
+//! Hot module reloading for `oitec dev`
+//!
+//! `oitec dev app.ot` runs a script the way `oitec app.ot` does, then keeps
+//! watching it and every module it imports. When a module is saved it runs
+//! again in place, into the namespace its importers already hold, so their
+//! (live) imports see the new exports while the rest of the heap (globals,
+//! timers, objects the old version handed out) stays as it was.
+//!
+//! Modules take part through `import.meta.hot`, which only exists under
+//! `oitec dev`:
+//!
+//! ```javascript
+//! if (import.meta.hot) {
+//!   import.meta.hot.accept();                 // run me again when I change
+//!   import.meta.hot.accept("./theme", (theme) => applyTheme(theme));
+//!   import.meta.hot.dispose((data) => { data.count = count; });
+//! }
+//! let count = import.meta.hot ? import.meta.hot.data.count || 0 : 0;
+//! ```
+//!
+//! A change runs the changed module again, then each importer in turn up to
+//! the nearest modules that accept it, and calls their `accept` callbacks
+//! with the new namespace. When nothing accepts it before the entry script,
+//! or a module calls `import.meta.hot.invalidate()`, the whole program
+//! restarts in a fresh VM.
+//!
+//! Files are watched by polling their modification time on a helper thread,
+//! which holds a `Completion` so the event loop stays up between changes.
+
+use super::{CachedModule, Completion, HeapData, HeapObject, JsValue, VM};
+use crate::platform;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{Receiver, Sender, channel};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+/// How often watched files are checked for changes
+pub const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Methods of an `import.meta.hot` object
+pub const METHODS: [&str; 3] = ["accept", "dispose", "invalidate"];
+
+/// `import.meta.hot.accept(deps, callback)`
+struct Accept {
+    deps: Vec<PathBuf>,
+    callback: JsValue,
+    /// Whether the deps were given as an array, which the callback gets a
+    /// namespace (or undefined, for one that didn't change) for each of
+    array: bool,
+}
+
+/// Hot state of one module, kept across its versions
+struct HotModule {
+    /// Its `import.meta.hot` object
+    object: usize,
+    /// `import.meta.hot.data`, handed from each version to the next
+    data: usize,
+    /// Set by `accept()`, with the callbacks `accept(callback)` added
+    accepts_self: Option<Vec<JsValue>>,
+    accepts: Vec<Accept>,
+    dispose: Vec<JsValue>,
+}
+
+/// The modules to run again for a change, dependencies first, and the
+/// accept callbacks to call once they have
+#[derive(Default)]
+struct Update {
+    modules: Vec<PathBuf>,
+    callbacks: Vec<(JsValue, Vec<PathBuf>, bool)>,
+}
+
+pub struct HotReload {
+    entry: PathBuf,
+    modules: HashMap<PathBuf, HotModule>,
+    /// The modules importing each module
+    importers: HashMap<PathBuf, BTreeSet<PathBuf>>,
+    /// Watched files and the modification time last seen
+    watched: Arc<Mutex<HashMap<PathBuf, Option<SystemTime>>>>,
+    /// Tells the watcher thread to stop
+    stop: Arc<AtomicBool>,
+    reload: bool,
+}
+
+/// A completion for the watcher to wake the loop with, and the way back for
+/// the next one
+struct Handoff {
+    completion: Completion,
+    back: Sender<Handoff>,
+}
+
+impl HotReload {
+    /// Hot state for a program run from `entry`, watching nothing else yet
+    pub fn new(entry: &Path) -> Self {
+        let entry = canonical(entry);
+        let hot = Self {
+            entry: entry.clone(),
+            modules: HashMap::new(),
+            importers: HashMap::new(),
+            watched: Arc::new(Mutex::new(HashMap::new())),
+            stop: Arc::new(AtomicBool::new(false)),
+            reload: false,
+        };
+        hot.watch(&entry);
+        hot
+    }
+
+    /// Enable hot reloading in `vm` and start watching `entry` and the
+    /// modules it imports. The event loop then runs until a change needs a
+    /// restart.
+    pub fn start(vm: &mut VM, entry: &Path) {
+        let hot = Self::new(entry);
+        let (back, handoffs) = channel();
+        let first = Handoff {
+            completion: vm.scheduler.completion(),
+            back: back.clone(),
+        };
+        let _ = back.send(first);
+        let watched = hot.watched.clone();
+        let stop = hot.stop.clone();
+        std::thread::spawn(move || watch_files(&watched, &stop, handoffs));
+        vm.hot = Some(hot);
+    }
+
+    /// Whether a change asked for the program to restart
+    pub fn reload_requested(&self) -> bool {
+        self.reload
+    }
+
+    /// Record that `importer` (the entry script when `None`) imports the
+    /// module at `module`, and watch it
+    pub fn imported(&mut self, importer: Option<&Path>, module: &Path) {
+        let importer = importer.map_or_else(|| self.entry.clone(), canonical);
+        let module = canonical(module);
+        self.watch(&module);
+        self.importers.entry(module).or_default().insert(importer);
+    }
+
+    fn watch(&self, path: &Path) {
+        let mut watched = self.watched.lock().unwrap_or_else(|e| e.into_inner());
+        watched
+            .entry(path.to_path_buf())
+            .or_insert_with(|| modified(path));
+    }
+
+    /// The module whose `import.meta.hot` object is `object`
+    pub fn module_of(&self, object: usize) -> Option<PathBuf> {
+        self.modules
+            .iter()
+            .find(|(_, module)| module.object == object)
+            .map(|(path, _)| path.clone())
+    }
+
+    fn plan(&self, changed: &Path) -> Option<Update> {
+        let mut update = Update::default();
+        let mut queue = VecDeque::from([changed.to_path_buf()]);
+        let mut seen = HashSet::new();
+        while let Some(module) = queue.pop_front() {
+            if !seen.insert(module.clone()) {
+                continue;
+            }
+            if module == self.entry {
+                return None;
+            }
+            update.modules.push(module.clone());
+            let state = self.modules.get(&module);
+            if let Some(callbacks) = state.and_then(|state| state.accepts_self.as_ref()) {
+                update.callbacks.extend(
+                    callbacks
+                        .iter()
+                        .map(|callback| (callback.clone(), vec![module.clone()], false)),
+                );
+                continue;
+            }
+            for importer in self.importers.get(&module)? {
+                let accept = self.modules.get(importer).and_then(|state| {
+                    state
+                        .accepts
+                        .iter()
+                        .find(|accept| accept.deps.contains(&module))
+                });
+                match accept {
+                    Some(accept) => update.callbacks.push((
+                        accept.callback.clone(),
+                        accept.deps.clone(),
+                        accept.array,
+                    )),
+                    None => queue.push_back(importer.clone()),
+                }
+            }
+        }
+        Some(update)
+    }
+}
+
+impl Drop for HotReload {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    platform::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Watcher thread: wait for a watched file to change, then wake the loop to
+/// apply the update and hand back the next completion
+fn watch_files(
+    watched: &Mutex<HashMap<PathBuf, Option<SystemTime>>>,
+    stop: &AtomicBool,
+    handoffs: Receiver<Handoff>,
+) {
+    while let Ok(Handoff { completion, back }) = handoffs.recv() {
+        let changed = loop {
+            if stop.load(Ordering::Relaxed) {
+                return;
+            }
+            std::thread::sleep(POLL_INTERVAL);
+            let changed = changed_files(watched);
+            if !changed.is_empty() {
+                break changed;
+            }
+        };
+        completion.complete(move |vm| {
+            apply_update(vm, &changed);
+            // A restart drops the way back, which ends this thread
+            if vm.hot.as_ref().is_some_and(|hot| !hot.reload) {
+                let next = Handoff {
+                    completion: vm.scheduler.completion(),
+                    back: back.clone(),
+                };
+                let _ = back.send(next);
+            }
+        });
+    }
+}
+
+/// Watched files modified since last seen
+fn changed_files(watched: &Mutex<HashMap<PathBuf, Option<SystemTime>>>) -> Vec<PathBuf> {
+    let mut watched = watched.lock().unwrap_or_else(|e| e.into_inner());
+    let mut changed: Vec<PathBuf> = watched
+        .iter_mut()
+        .filter_map(|(path, seen)| {
+            let now = modified(path);
+            (now != *seen).then(|| {
+                *seen = now;
+                path.clone()
+            })
+        })
+        .collect();
+    changed.sort();
+    changed
+}
+
+/// Block until one of `paths` is modified
+pub fn wait_for_change(paths: &[PathBuf]) {
+    let watched: Mutex<HashMap<PathBuf, Option<SystemTime>>> = Mutex::new(
+        paths
+            .iter()
+            .map(|path| (path.clone(), modified(path)))
+            .collect(),
+    );
+    while changed_files(&watched).is_empty() {
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// The `import.meta.hot` object of the module at `path`, when hot
+/// reloading is on
+pub fn hot_object(vm: &mut VM, path: &Path) -> Option<usize> {
+    let hot = vm.hot.as_ref()?;
+    if let Some(module) = hot.modules.get(path) {
+        return Some(module.object);
+    }
+    let data = vm.heap.alloc(HeapObject {
+        data: HeapData::Object(HashMap::new()),
+    });
+    let object = vm.heap.alloc(HeapObject {
+        data: HeapData::Object(HashMap::from([("data".to_string(), JsValue::Object(data))])),
+    });
+    vm.hot.as_mut()?.modules.insert(
+        path.to_path_buf(),
+        HotModule {
+            object,
+            data,
+            accepts_self: None,
+            accepts: Vec::new(),
+            dispose: Vec::new(),
+        },
+    );
+    Some(object)
+}
+
+/// Call `import.meta.hot.<name>(...args)` for the module at `module`
+pub fn call_method(vm: &mut VM, module: &Path, name: &str, args: Vec<JsValue>) {
+    let accept = match (name, args.first()) {
+        ("accept", None | Some(JsValue::Function { .. } | JsValue::NativeFunction(_))) => None,
+        ("accept", Some(deps)) => {
+            let array = matches!(
+                deps,
+                JsValue::Object(ptr) if matches!(
+                    vm.heap.get(*ptr).map(|obj| &obj.data),
+                    Some(HeapData::Array(_))
+                )
+            );
+            let specifiers = match deps {
+                JsValue::String(s) => vec![JsValue::String(s.clone())],
+                JsValue::Object(ptr) => match vm.heap.get(*ptr).map(|obj| &obj.data) {
+                    Some(HeapData::Array(items)) => items.clone(),
+                    _ => Vec::new(),
+                },
+                _ => Vec::new(),
+            };
+            let deps: Option<Vec<PathBuf>> = specifiers
+                .iter()
+                .map(|specifier| match specifier {
+                    JsValue::String(s) => Some(canonical(&super::resolve_import(Some(module), s))),
+                    _ => None,
+                })
+                .collect();
+            let Some(deps) = deps.filter(|deps| !deps.is_empty()) else {
+                eprintln!("Error: import.meta.hot.accept expects a callback or module specifiers");
+                return;
+            };
+            Some(Accept {
+                deps,
+                callback: args.get(1).cloned().unwrap_or(JsValue::Undefined),
+                array,
+            })
+        }
+        _ => None,
+    };
+
+    let Some(hot) = vm.hot.as_mut() else {
+        return;
+    };
+    if name == "invalidate" {
+        eprintln!("[dev] {} invalidated itself", module.display());
+        hot.reload = true;
+        return;
+    }
+    let Some(state) = hot.modules.get_mut(module) else {
+        return;
+    };
+    match (name, accept) {
+        ("accept", Some(accept)) => state.accepts.push(accept),
+        ("accept", None) => state
+            .accepts_self
+            .get_or_insert_with(Vec::new)
+            .extend(args.into_iter().next()),
+        ("dispose", _) => match args.into_iter().next() {
+            Some(callback @ (JsValue::Function { .. } | JsValue::NativeFunction(_))) => {
+                state.dispose.push(callback)
+            }
+            _ => eprintln!("Error: import.meta.hot.dispose expects a callback"),
+        },
+        _ => {}
+    }
+}
+
+/// Apply changes to the files at `changed`: run the changed modules again
+/// and call the callbacks that accept them, or ask for a restart
+pub fn apply_update(vm: &mut VM, changed: &[PathBuf]) {
+    for path in changed {
+        let Some(hot) = vm.hot.as_ref() else {
+            return;
+        };
+        // A module that never finished loading has nothing to update
+        let update = hot.plan(path).filter(|update| {
+            update
+                .modules
+                .iter()
+                .all(|module| vm.module_cache.get_cache_info(module).is_some())
+        });
+        let Some(update) = update else {
+            eprintln!("[dev] {} changed, restarting", path.display());
+            if let Some(hot) = vm.hot.as_mut() {
+                hot.reload = true;
+            }
+            return;
+        };
+        for module in &update.modules {
+            if let Err(e) = rerun(vm, module) {
+                eprintln!("[dev] {}", e);
+                return;
+            }
+        }
+        for (callback, deps, array) in update.callbacks {
+            let mut namespaces: Vec<JsValue> = deps
+                .iter()
+                .map(|dep| match vm.module_cache.get_cache_info(dep) {
+                    Some((_, _, namespace)) if update.modules.contains(dep) => {
+                        JsValue::Object(namespace)
+                    }
+                    _ => JsValue::Undefined,
+                })
+                .collect();
+            let arg = if array {
+                JsValue::Object(vm.heap.alloc(HeapObject {
+                    data: HeapData::Array(namespaces),
+                }))
+            } else {
+                namespaces.pop().unwrap_or(JsValue::Undefined)
+            };
+            if !matches!(callback, JsValue::Undefined)
+                && let Err(exception) = vm.call_function(callback, vec![arg])
+            {
+                eprintln!(
+                    "[dev] accept callback threw: {}",
+                    crate::stdlib::console::console_string(vm, &exception)
+                );
+            }
+        }
+        eprintln!("[dev] updated {}", path.display());
+        if vm.hot.as_ref().is_some_and(|hot| hot.reload) {
+            return;
+        }
+    }
+}
+
+/// Run the module at `path` again from its current source, into the
+/// namespace it already has. Its `dispose` callbacks run first; on a compile
+/// error the namespace keeps the old version's exports.
+fn rerun(vm: &mut VM, path: &Path) -> Result<(), String> {
+    let key = path.to_path_buf();
+    let (_, _, namespace) = vm
+        .module_cache
+        .get_cache_info(&key)
+        .ok_or_else(|| format!("{} is not loaded", path.display()))?;
+    let source = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read module: {}", e))
+        .and_then(|source| crate::transform::transform_source(path, source))?;
+
+    // The new version registers its own callbacks
+    let disposed = vm
+        .hot
+        .as_mut()
+        .and_then(|hot| hot.modules.get_mut(path))
+        .map(|state| {
+            state.accepts_self = None;
+            state.accepts.clear();
+            (std::mem::take(&mut state.dispose), state.data)
+        });
+    if let Some((callbacks, data)) = disposed {
+        for callback in callbacks {
+            if let Err(exception) = vm.call_function(callback, vec![JsValue::Object(data)]) {
+                eprintln!(
+                    "[dev] dispose callback threw: {}",
+                    crate::stdlib::console::console_string(vm, &exception)
+                );
+            }
+        }
+    }
+
+    // Its exports and re-exports are set afresh, so names it no longer
+    // exports go away
+    let hash = super::ModuleCache::compute_hash(&key);
+    let exports = super::parse_module_exports(&source, &path.to_string_lossy());
+    let reexports = vm.reexports.clone();
+    for targets in vm.reexports.values_mut() {
+        targets.retain(|(to, _)| *to != namespace);
+    }
+    let previous = match vm.heap.get_mut(namespace) {
+        Some(HeapObject {
+            data: HeapData::Object(props),
+        }) => {
+            let previous = props.clone();
+            props.retain(|name, _| super::property::is_internal_key(name));
+            for name in exports.keys() {
+                props.insert(name.clone(), JsValue::Undefined);
+            }
+            props.insert("__source__".to_string(), JsValue::String(source.clone()));
+            props.insert("__hash__".to_string(), JsValue::String(hash.clone()));
+            previous
+        }
+        _ => return Err(format!("{} has no namespace", path.display())),
+    };
+
+    if let Err(e) = vm.execute_module(&source, path, namespace, &exports) {
+        vm.reexports = reexports;
+        if let Some(HeapObject {
+            data: HeapData::Object(props),
+        }) = vm.heap.get_mut(namespace)
+        {
+            *props = previous;
+        }
+        return Err(e);
+    }
+    vm.module_cache.insert(CachedModule {
+        path: key,
+        source,
+        hash,
+        load_time: SystemTime::now(),
+        namespace_object: namespace,
+    });
+    Ok(())
+}
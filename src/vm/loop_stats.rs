@@ -0,0 +1,68 @@
+//! Event-loop health counters (`runtime.eventLoop()`, `--warn-slow-tasks`)
+//!
+//! The loop records how long each task ran and how late each timer was
+//! queued after falling due. A task that runs past the slow-task threshold is
+//! reported on stderr: every other queued callback waited that long too.
+
+use std::time::Duration;
+
+#[derive(Debug, Default)]
+pub struct LoopStats {
+    pub tasks_run: u64,
+    pub total_task_time: Duration,
+    pub last_task_time: Duration,
+    pub max_task_time: Duration,
+    /// Tasks that ran longer than `warn_slow`
+    pub slow_tasks: u64,
+    /// Largest delay between a timer falling due and it being queued
+    pub max_timer_lag: Duration,
+    /// Report tasks that run longer than this
+    pub warn_slow: Option<Duration>,
+}
+
+impl LoopStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Account for one finished task. Returns true if it was slow.
+    pub fn record_task(&mut self, elapsed: Duration) -> bool {
+        self.tasks_run += 1;
+        self.total_task_time += elapsed;
+        self.last_task_time = elapsed;
+        self.max_task_time = self.max_task_time.max(elapsed);
+        let slow = self.warn_slow.is_some_and(|limit| elapsed > limit);
+        if slow {
+            self.slow_tasks += 1;
+        }
+        slow
+    }
+
+    pub fn record_timer_lag(&mut self, lag: Duration) {
+        self.max_timer_lag = self.max_timer_lag.max(lag);
+    }
+}
+
+/// Milliseconds as a script-visible number
+pub fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slow_tasks_need_a_threshold() {
+        let mut stats = LoopStats::new();
+        assert!(!stats.record_task(Duration::from_millis(500)));
+
+        stats.warn_slow = Some(Duration::from_millis(50));
+        assert!(!stats.record_task(Duration::from_millis(10)));
+        assert!(stats.record_task(Duration::from_millis(80)));
+        assert_eq!(stats.tasks_run, 3);
+        assert_eq!(stats.slow_tasks, 1);
+        assert_eq!(stats.max_task_time, Duration::from_millis(500));
+        assert_eq!(stats.last_task_time, Duration::from_millis(80));
+    }
+}
@@ -0,0 +1,110 @@
+//! Pluggable method registry for built-in prototypes
+//!
+//! `CallMethod` handles the core String/Array/Map/Set methods natively. Any
+//! method it does not know is looked up here before falling back to
+//! `undefined`, so the stdlib (or an embedder) can extend built-in prototypes
+//! without touching the interpreter loop.
+//!
+//! Registered methods are ordinary natives, plain or fallible. The receiver
+//! is passed as the first argument, followed by the call arguments in order.
+
+use crate::vm::heap::Heap;
+use crate::vm::value::{FallibleNativeFn, HeapData, JsValue, Native, NativeFn};
+use std::collections::HashMap;
+
+/// Built-in prototypes that can be extended through the registry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BuiltinProto {
+    String,
+    Number,
+    Boolean,
+    Array,
+    Map,
+    Set,
+}
+
+impl BuiltinProto {
+    /// Determine which built-in prototype applies to a receiver value.
+    /// Plain objects return `None` since they use their own prototype chain.
+    pub fn of(value: &JsValue, heap: &Heap) -> Option<Self> {
+        match value {
+            JsValue::String(_) => Some(BuiltinProto::String),
+            JsValue::Number(_) => Some(BuiltinProto::Number),
+            JsValue::Boolean(_) => Some(BuiltinProto::Boolean),
+            JsValue::Object(ptr) => match heap.get(*ptr).map(|obj| &obj.data) {
+                Some(HeapData::Array(_)) => Some(BuiltinProto::Array),
+                Some(HeapData::Map(_)) => Some(BuiltinProto::Map),
+                Some(HeapData::Set(_)) => Some(BuiltinProto::Set),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+/// Registry of additional methods for built-in prototypes
+#[derive(Debug, Default, Clone)]
+pub struct MethodRegistry {
+    methods: HashMap<BuiltinProto, HashMap<String, Native>>,
+}
+
+impl MethodRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register (or replace) a method on a built-in prototype.
+    pub fn register(&mut self, proto: BuiltinProto, name: &str, func: NativeFn) {
+        self.insert(proto, name, Native::Plain(func));
+    }
+
+    /// Register (or replace) a method that can throw; `label` names it in
+    /// stack traces.
+    pub fn register_fallible(
+        &mut self,
+        proto: BuiltinProto,
+        name: &str,
+        label: &'static str,
+        func: FallibleNativeFn,
+    ) {
+        self.insert(proto, name, Native::Fallible(label, func));
+    }
+
+    fn insert(&mut self, proto: BuiltinProto, name: &str, native: Native) {
+        self.methods
+            .entry(proto)
+            .or_default()
+            .insert(name.to_string(), native);
+    }
+
+    /// Look up a registered method.
+    pub fn get(&self, proto: BuiltinProto, name: &str) -> Option<Native> {
+        self.methods
+            .get(&proto)
+            .and_then(|table| table.get(name))
+            .copied()
+    }
+
+    /// Check whether a method has been registered.
+    pub fn contains(&self, proto: BuiltinProto, name: &str) -> bool {
+        self.get(proto, name).is_some()
+    }
+
+    /// Remove a registered method, returning it if present.
+    pub fn unregister(&mut self, proto: BuiltinProto, name: &str) -> Option<Native> {
+        self.methods
+            .get_mut(&proto)
+            .and_then(|table| table.remove(name))
+    }
+
+    /// Names of all methods registered for a prototype (sorted).
+    pub fn method_names(&self, proto: BuiltinProto) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .methods
+            .get(&proto)
+            .map(|table| table.keys().cloned().collect())
+            .unwrap_or_default();
+        names.sort();
+        names
+    }
+}
@@ -1,16 +1,68 @@
 /// Maximum call stack depth to prevent stack overflow in deeply recursive code
 pub const MAX_CALL_STACK_DEPTH: usize = 1000;
 
+/// How often an idle event loop checks for signals, and for exited children
+/// where no watcher thread reports them
+const IDLE_POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+/// Catch address of the handler `call_function` installs around its
+/// callee; past the end of any program, so the nested run stops there
+const CALLBACK_THREW: usize = usize::MAX - 1;
+
+pub mod bundle;
+pub mod bytecode_cache;
+#[cfg(feature = "corpus-runner")]
+pub mod corpus;
+pub mod coverage;
+pub mod descriptors;
+pub mod disasm;
+pub mod heap;
+pub mod heap_snapshot;
+pub mod hot;
+pub mod loop_stats;
+pub mod method_registry;
 pub mod module_cache;
 pub mod opcodes;
+pub mod operators;
+pub mod permissions;
+pub mod prefetch;
+pub mod profiler;
 pub mod property;
+pub mod realm;
+pub mod scheduler;
+pub mod startup_snapshot;
 pub mod stdlib_setup;
+pub mod task_queue;
+pub mod trace;
 pub mod value;
 
 pub use crate::compiler::Compiler;
+use crate::driver::{CompileSettings, syntax_for_path};
+use crate::loader::DebugInfo;
+use crate::manifest::Manifest;
+use crate::stdlib::child_process::ProcessTable;
+use crate::stdlib::console::ConsoleLocale;
+use crate::stdlib::number_to_string;
+use crate::stdlib::process::ProcessState;
+use crate::stdlib::stream::StreamTable;
+use crate::stdlib::string;
+use crate::vm::bundle::BundledModules;
+pub use crate::vm::coverage::Coverage;
+use crate::vm::descriptors::DescriptorTable;
+use crate::vm::heap::Heap;
+use crate::vm::hot::HotReload;
+pub use crate::vm::loop_stats::LoopStats;
+pub use crate::vm::method_registry::{BuiltinProto, MethodRegistry};
 pub use crate::vm::module_cache::CachedModule;
 pub use crate::vm::module_cache::ModuleCache;
 pub use crate::vm::opcodes::OpCode;
+use crate::vm::opcodes::{PINNED_GLOBALS, pinned_global_slot};
+pub use crate::vm::permissions::Permissions;
+pub use crate::vm::prefetch::Prefetcher;
+pub use crate::vm::profiler::Profiler;
+pub use crate::vm::scheduler::{Completion, Scheduler};
+pub use crate::vm::task_queue::{TaskPriority, TaskQueue};
+pub use crate::vm::trace::Tracer;
 pub use crate::vm::value::AsyncContext;
 pub use crate::vm::value::ContinuationCallback;
 pub use crate::vm::value::HeapData;
@@ -19,18 +71,40 @@ pub use crate::vm::value::JsValue;
 pub use crate::vm::value::NativeFn;
 pub use crate::vm::value::Promise;
 pub use crate::vm::value::PromiseState;
+pub use crate::vm::value::{FallibleNativeFn, Native, NativeResult};
 pub use sha2::Digest;
-pub use std::collections::{HashMap, VecDeque};
+pub use std::collections::HashMap;
+use std::collections::HashSet;
 pub use std::fs;
+use std::ops::Range;
 pub use std::path::{Path, PathBuf};
+use std::sync::Arc;
 pub use std::time::{Duration, Instant};
 pub use swc_common::{FileName, input::StringInput};
 pub use swc_ecma_parser::{Parser, Syntax, TsSyntax, lexer::Lexer};
 pub use tokio::runtime::Runtime;
-pub use tokio::sync::mpsc;
 
-/// Parse module source and extract exports as a HashMap
-fn parse_module_exports(source: &str, file_name: &str) -> HashMap<String, JsValue> {
+/// The names each local binding is exported under, from the exports'
+/// local names
+fn exported_as(exports: &HashMap<String, Option<String>>) -> HashMap<String, Vec<String>> {
+    let mut exported_as: HashMap<String, Vec<String>> = HashMap::new();
+    for (exported, local) in exports {
+        if let Some(local) = local {
+            exported_as
+                .entry(local.clone())
+                .or_default()
+                .push(exported.clone());
+        }
+    }
+    exported_as
+}
+
+/// The names a module exports, each mapped to the top-level binding that
+/// holds its value: `export { a as b }` maps `b` to `a`, and a default
+/// export maps to the declaration it names, or to `default` for a value.
+/// Names re-exported from another module (`export { a } from`) map to
+/// `None`; `export *` names are only known once the other module has run.
+fn parse_module_exports(source: &str, file_name: &str) -> HashMap<String, Option<String>> {
     let mut exports = HashMap::new();
 
     let cm: swc_common::SourceMap = Default::default();
@@ -54,146 +128,194 @@ fn parse_module_exports(source: &str, file_name: &str) -> HashMap<String, JsValu
 
     let mut parser = Parser::new_from(lexer);
 
-    match parser.parse_module() {
-        Ok(ast) => {
-            for item in &ast.body {
-                if let swc_ecma_ast::ModuleItem::ModuleDecl(decl) = item {
-                    match decl {
-                        swc_ecma_ast::ModuleDecl::ExportNamed(named) => {
-                            if let Some(_src) = &named.src {
-                                for spec in &named.specifiers {
-                                    match spec {
-                                        swc_ecma_ast::ExportSpecifier::Named(named) => {
-                                            let export_name = named
-                                                .exported
-                                                .as_ref()
-                                                .map(|e| {
-                                                    let atom = e.atom();
-                                                    let s: &str = &atom;
-                                                    s.to_string()
-                                                })
-                                                .unwrap_or_else(|| {
-                                                    let atom = named.orig.atom();
-                                                    let s: &str = &atom;
-                                                    s.to_string()
-                                                });
-                                            exports.insert(export_name, JsValue::Undefined);
-                                        }
-                                        swc_ecma_ast::ExportSpecifier::Default(_) => {
-                                            exports
-                                                .insert("default".to_string(), JsValue::Undefined);
-                                        }
-                                        swc_ecma_ast::ExportSpecifier::Namespace(ns) => {
-                                            let atom = ns.name.atom();
-                                            let s: &str = &atom;
-                                            exports.insert(s.to_string(), JsValue::Undefined);
-                                        }
-                                    }
-                                }
-                            } else {
-                                for spec in &named.specifiers {
-                                    match spec {
-                                        swc_ecma_ast::ExportSpecifier::Named(named) => {
-                                            let export_name = named
-                                                .exported
-                                                .as_ref()
-                                                .map(|e| {
-                                                    let atom = e.atom();
-                                                    let s: &str = &atom;
-                                                    s.to_string()
-                                                })
-                                                .unwrap_or_else(|| {
-                                                    let atom = named.orig.atom();
-                                                    let s: &str = &atom;
-                                                    s.to_string()
-                                                });
-                                            exports.insert(export_name, JsValue::Undefined);
-                                        }
-                                        swc_ecma_ast::ExportSpecifier::Default(_) => {
-                                            exports
-                                                .insert("default".to_string(), JsValue::Undefined);
-                                        }
-                                        swc_ecma_ast::ExportSpecifier::Namespace(ns) => {
-                                            let atom = ns.name.atom();
-                                            let s: &str = &atom;
-                                            exports.insert(s.to_string(), JsValue::Undefined);
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                        swc_ecma_ast::ModuleDecl::ExportAll(_all) => {
-                            exports.insert("*".to_string(), JsValue::Undefined);
+    let ast = match parser.parse_module() {
+        Ok(ast) => ast,
+        Err(e) => {
+            eprintln!("Warning: Failed to parse module for exports: {:?}", e);
+            return exports;
+        }
+    };
+    let atom = |name: &swc_ecma_ast::ModuleExportName| {
+        let atom = name.atom();
+        let s: &str = &atom;
+        s.to_string()
+    };
+    for item in &ast.body {
+        let swc_ecma_ast::ModuleItem::ModuleDecl(decl) = item else {
+            continue;
+        };
+        match decl {
+            swc_ecma_ast::ModuleDecl::ExportNamed(named) => {
+                for spec in &named.specifiers {
+                    match spec {
+                        swc_ecma_ast::ExportSpecifier::Named(spec) => {
+                            let exported = atom(spec.exported.as_ref().unwrap_or(&spec.orig));
+                            let local = named.src.is_none().then(|| atom(&spec.orig));
+                            exports.insert(exported, local);
                         }
-                        swc_ecma_ast::ModuleDecl::ExportDefaultDecl(_default_decl) => {
-                            exports.insert("default".to_string(), JsValue::Undefined);
+                        swc_ecma_ast::ExportSpecifier::Default(spec) => {
+                            exports.insert(spec.exported.sym.to_string(), None);
                         }
-                        swc_ecma_ast::ModuleDecl::ExportDefaultExpr(_) => {
-                            exports.insert("default".to_string(), JsValue::Undefined);
+                        // `export * as ns from` binds the namespace as `ns`
+                        swc_ecma_ast::ExportSpecifier::Namespace(ns) => {
+                            let name = atom(&ns.name);
+                            exports.insert(name.clone(), Some(name));
                         }
-                        swc_ecma_ast::ModuleDecl::ExportDecl(decl) => {
-                            use swc_ecma_ast::Decl::*;
-                            match &decl.decl {
-                                Fn(fn_decl) => {
-                                    exports
-                                        .insert(fn_decl.ident.sym.to_string(), JsValue::Undefined);
-                                }
-                                Var(var_decl) => {
-                                    for declarator in &var_decl.decls {
-                                        if let swc_ecma_ast::Pat::Ident(ident) = &declarator.name {
-                                            exports.insert(
-                                                ident.id.sym.to_string(),
-                                                JsValue::Undefined,
-                                            );
-                                        }
-                                    }
-                                }
-                                Class(class_decl) => {
-                                    exports.insert(
-                                        class_decl.ident.sym.to_string(),
-                                        JsValue::Undefined,
-                                    );
-                                }
-                                _ => {}
-                            }
-                        }
-                        _ => {}
                     }
-                } else if let swc_ecma_ast::ModuleItem::Stmt(stmt) = item
-                    && let swc_ecma_ast::Stmt::Decl(decl) = stmt
-                {
-                    match decl {
-                        swc_ecma_ast::Decl::Var(var_decl) => {
-                            for declarator in &var_decl.decls {
-                                if let swc_ecma_ast::Pat::Ident(ident) = &declarator.name {
-                                    exports.insert(ident.id.sym.to_string(), JsValue::Undefined);
-                                }
-                            }
-                        }
-                        swc_ecma_ast::Decl::Fn(fn_decl) => {
-                            exports.insert(fn_decl.ident.sym.to_string(), JsValue::Undefined);
-                        }
-                        swc_ecma_ast::Decl::Class(class_decl) => {
-                            exports.insert(class_decl.ident.sym.to_string(), JsValue::Undefined);
-                        }
-                        _ => {}
+                }
+            }
+            swc_ecma_ast::ModuleDecl::ExportDefaultDecl(default_decl) => {
+                let ident = match &default_decl.decl {
+                    swc_ecma_ast::DefaultDecl::Fn(f) => f.ident.as_ref(),
+                    swc_ecma_ast::DefaultDecl::Class(c) => c.ident.as_ref(),
+                    _ => None,
+                };
+                let local = ident.map_or("default".to_string(), |id| id.sym.to_string());
+                exports.insert("default".to_string(), Some(local));
+            }
+            swc_ecma_ast::ModuleDecl::ExportDefaultExpr(_) => {
+                exports.insert("default".to_string(), Some("default".to_string()));
+            }
+            swc_ecma_ast::ModuleDecl::ExportDecl(decl) => {
+                let names: Vec<String> = match &decl.decl {
+                    swc_ecma_ast::Decl::Fn(fn_decl) => vec![fn_decl.ident.sym.to_string()],
+                    swc_ecma_ast::Decl::Class(class_decl) => {
+                        vec![class_decl.ident.sym.to_string()]
                     }
+                    swc_ecma_ast::Decl::TsEnum(enum_decl) => vec![enum_decl.id.sym.to_string()],
+                    swc_ecma_ast::Decl::Var(var_decl) => var_decl
+                        .decls
+                        .iter()
+                        .filter_map(|declarator| match &declarator.name {
+                            swc_ecma_ast::Pat::Ident(ident) => Some(ident.id.sym.to_string()),
+                            _ => None,
+                        })
+                        .collect(),
+                    _ => Vec::new(),
+                };
+                for name in names {
+                    exports.insert(name.clone(), Some(name));
                 }
             }
-        }
-        Err(e) => {
-            eprintln!("Warning: Failed to parse module for exports: {:?}", e);
+            _ => {}
         }
     }
 
     exports
 }
 
+/// Path an import specifier refers to, relative to the importing module
+/// (or the working directory), trying the manifest's extensions in order. A
+/// bare specifier that isn't there is looked up in the manifest's module
+/// roots. The result may not exist.
+pub(crate) fn resolve_import(importer: Option<&Path>, specifier: &str) -> PathBuf {
+    let importer_dir = importer
+        .and_then(|p| if p.is_file() { p.parent() } else { Some(p) })
+        .map(|p| {
+            if p.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                p
+            }
+        })
+        .unwrap_or(Path::new("."));
+
+    let manifest = Manifest::discover_or_default(importer_dir);
+    let resolved = resolve_in(importer_dir, specifier, &manifest.extensions);
+    let bare = !specifier.starts_with('.') && !Path::new(specifier).is_absolute();
+    if bare && !resolved.exists() {
+        for root in manifest.module_roots() {
+            let candidate = resolve_in(&root, specifier, &manifest.extensions);
+            if candidate.exists() {
+                return candidate;
+            }
+        }
+    }
+    resolved
+}
+
+/// `specifier` under `dir`: the first of `import_candidates` that is a file,
+/// or the first candidate when none is
+fn resolve_in(dir: &Path, specifier: &str, extensions: &[String]) -> PathBuf {
+    let candidates = import_candidates(dir, specifier, extensions);
+    candidates
+        .iter()
+        .find(|candidate| candidate.is_file())
+        .or(candidates.first())
+        .cloned()
+        .unwrap_or_else(|| dir.to_path_buf())
+}
+
+/// Files `specifier` may name under `dir`, in the order an import tries
+/// them: the path as written, then with each of `extensions`, then its
+/// `index` file. A directory specifier (`./lib/`) only has index files.
+/// Every resolver (the VM, `oitec check` and the language server) goes
+/// through this list.
+pub(crate) fn import_candidates(
+    dir: &Path,
+    specifier: &str,
+    extensions: &[String],
+) -> Vec<PathBuf> {
+    let mut path = dir.to_path_buf();
+
+    for component in specifier.split('/') {
+        match component {
+            "." => {}
+            ".." => {
+                if !path.as_os_str().is_empty() {
+                    path.pop();
+                }
+            }
+            "" if specifier.starts_with("./") => {}
+            "" if specifier.starts_with("../") => {}
+            _ => path.push(component),
+        };
+    }
+
+    let index = extensions
+        .iter()
+        .map(|ext| path.join("index").with_extension(ext));
+    if path.as_os_str().is_empty() || specifier.ends_with('/') {
+        return index.collect();
+    }
+    std::iter::once(path.clone())
+        .chain(extensions.iter().map(|ext| path.with_extension(ext)))
+        .chain(index)
+        .collect()
+}
+
+/// `import.meta.resolve(specifier)`: the URL `import` would load the
+/// specifier from, relative to the calling module; `node:<name>` for a
+/// builtin module
+fn native_import_resolve(vm: &mut VM, args: Vec<JsValue>) -> NativeResult {
+    let Some(JsValue::String(specifier)) = args.first() else {
+        return Err(JsValue::String(
+            "TypeError: import.meta.resolve expects a module specifier".to_string(),
+        ));
+    };
+    let builtin = specifier.strip_prefix("node:").unwrap_or(specifier);
+    if vm.modules.contains_key(builtin) {
+        return Ok(JsValue::String(format!("node:{}", builtin)));
+    }
+    let importer = vm.running_module_path();
+    let importer = (!importer.as_os_str().is_empty()).then_some(importer.as_path());
+    let resolved = resolve_import(importer, specifier);
+    let resolved = crate::platform::canonicalize(&resolved)
+        .or_else(|_| std::path::absolute(&resolved))
+        .unwrap_or(resolved);
+    Ok(JsValue::String(crate::platform::file_url(&resolved)))
+}
+
 #[derive(Clone, Debug)]
 pub struct Frame {
     pub return_address: usize,
     pub locals: HashMap<String, JsValue>,
     pub indexed_locals: Vec<JsValue>,
+    /// Argument window: the values the caller passed, in call order. The
+    /// callee's prologue reads them with `LoadArg`/`LoadRestArgs`, so a call
+    /// with more or fewer arguments than parameters leaves the operand stack
+    /// alone.
+    pub args: Vec<JsValue>,
     pub this_context: JsValue,
     /// Stores the constructor that was called with new (for new.target)
     pub new_target: Option<JsValue>,
@@ -202,6 +324,18 @@ pub struct Frame {
     pub super_called: bool,
     /// For async functions: where to resume after await
     pub resume_ip: Option<usize>,
+    /// Entry address of the function running in this frame (None for the
+    /// global frame and native constructors)
+    pub function: Option<usize>,
+    /// Set for a setter call: `SetProp` leaves nothing on the stack, so the
+    /// setter's return value is dropped
+    pub discard_result: bool,
+    /// Environment of the module whose code runs in this frame. Names not
+    /// found in `locals` are looked up there before the caller's frames.
+    pub module: Option<usize>,
+    /// Set for a `new` call, to the stack height on entry: unless the
+    /// constructor returns an object, the call evaluates to `this`
+    pub construct_base: Option<usize>,
 }
 
 pub struct Task {
@@ -227,31 +361,127 @@ pub struct ExceptionHandler {
     pub call_stack_depth: usize,
 }
 
+/// Interpreter position saved around a nested run (`call_function`,
+/// `execute_module`)
+struct SavedPosition {
+    ip: usize,
+    stack_depth: usize,
+    frames: usize,
+    handlers: usize,
+}
+
+/// The top-level scope of an imported module: a heap object holding its
+/// bindings, shared by every function whose code lies in `code`. Writes to
+/// an exported binding are mirrored into the module's namespace object,
+/// which is what importers read.
+#[derive(Debug, Clone)]
+pub struct ModuleScope {
+    pub path: PathBuf,
+    pub code: Range<usize>,
+    pub environment: usize,
+    pub namespace: usize,
+    /// The names each exported binding is exported under
+    pub exports: HashMap<String, Vec<String>>,
+}
+
+/// A name bound by `import { name as local }`, read from the exporting
+/// module's namespace on every use
+#[derive(Debug, Clone)]
+pub struct ImportedBinding {
+    pub namespace: usize,
+    pub name: String,
+}
+
 pub struct VM {
     pub stack: Vec<JsValue>,
     pub call_stack: Vec<Frame>,
-    pub heap: Vec<HeapObject>,
-    pub native_functions: Vec<NativeFn>,
-    pub task_queue: VecDeque<Task>,
+    pub heap: Heap,
+    pub native_functions: Vec<Native>,
+    pub task_queue: TaskQueue,
     timers: Vec<TimerTask>,
-    pub program: Vec<OpCode>,
+    /// Bytecode of the entry script and the modules loaded so far, shared
+    /// with realms made from this VM until either side loads more
+    pub program: Arc<Vec<OpCode>>,
     pub modules: HashMap<String, JsValue>,
     pub ip: usize,
     pub function_call_counts: HashMap<usize, u64>,
     pub total_instructions: u64,
     pub exception_handlers: Vec<ExceptionHandler>,
     pub current_exception: Option<JsValue>,
+    /// Native that raised the exception being unwound, shown as the
+    /// innermost frame of its stack trace
+    native_origin: Option<&'static str>,
     pub current_module_path: Option<PathBuf>,
     pub async_runtime: Option<Runtime>,
-    pub async_task_tx: Option<mpsc::Sender<JsValue>>,
     pub module_cache: ModuleCache,
+    pub module_scopes: Vec<ModuleScope>,
+    /// Namespace of the entry script, cached once it imports anything, and
+    /// the names each of its bindings is exported under
+    entry_module: Option<(usize, HashMap<String, Vec<String>>)>,
+    /// The names each scope imports, by module environment (`None` for the
+    /// entry script)
+    pub imported_bindings: HashMap<Option<usize>, HashMap<String, ImportedBinding>>,
+    /// Exports passed on by other modules, by namespace and name: a write
+    /// to one is copied to each `(namespace, name)` listed
+    pub reexports: HashMap<(usize, String), Vec<(usize, String)>>,
+    /// `import.meta` of each module, by path
+    pub import_metas: HashMap<PathBuf, usize>,
+    /// Watches and updates modules under `oitec dev`
+    pub hot: Option<HotReload>,
+    /// Compiles statically imported modules ahead of their import
+    pub prefetcher: Option<Prefetcher>,
+    /// Modules of the bundle being run, ahead of the file system
+    pub bundled: Option<BundledModules>,
     pub compiler: Compiler,
+    /// Per-directory settings imported modules compile with, handed over
+    /// by the driver
+    pub compile_settings: CompileSettings,
     /// Async/await continuation state
     pub async_context: Option<AsyncContext>,
     /// Queue for resolved promise values to be processed
     pub resolved_queue: Vec<(ContinuationCallback, JsValue)>,
     /// Current promise being constructed (for resolve/reject callbacks)
     pub current_promise: Option<Promise>,
+    /// Extra methods for built-in prototypes, consulted by CallMethod
+    pub method_registry: MethodRegistry,
+    /// When set, console.log appends here instead of writing to stdout
+    pub captured_output: Option<String>,
+    /// Separators for numbers in console output (`--locale`)
+    pub console_locale: Option<ConsoleLocale>,
+    /// Running `console.time` timers by label
+    pub console_timers: HashMap<String, Instant>,
+    /// Sampling profiler, enabled by `oitec profile`
+    pub profiler: Option<Profiler>,
+    /// Per-statement execution counts, enabled by `--coverage`
+    pub coverage: Option<Coverage>,
+    /// Per-instruction execution log, enabled by `--trace`
+    pub tracer: Option<Tracer>,
+    /// Invariant checks before every instruction (`oitec corpus`)
+    #[cfg(feature = "corpus-runner")]
+    pub invariants: Option<corpus::Invariants>,
+    /// Current value of each `PINNED_GLOBALS` entry, kept in step with the
+    /// global frame by `Let`, `Store` and `Drop`
+    pub pinned_globals: Vec<JsValue>,
+    /// Function names, lines and source from a bytecode file's debug
+    /// section, used to describe the call stack when an exception escapes
+    pub debug_info: Option<DebugInfo>,
+    /// Task timings and timer lag, read by `runtime.eventLoop()`
+    pub loop_stats: LoopStats,
+    /// Completions from other threads, microtasks and immediates
+    pub scheduler: Scheduler,
+    /// Host capabilities natives may use (`--sandbox`, `--allow-*`)
+    pub permissions: Permissions,
+    /// Attributes set by `Object.defineProperty` and `Object.freeze`
+    pub descriptors: DescriptorTable,
+    /// Readers and writers behind `Stream` heap objects
+    pub streams: StreamTable,
+    /// Children behind `Process` heap objects
+    pub processes: ProcessTable,
+    /// `process.env` and signal listeners
+    pub process: ProcessState,
+    /// Dispatch operators on objects to their `Symbol.operator` methods
+    /// (`--operator-overloading`)
+    pub operator_overloading: bool,
 }
 
 impl Default for VM {
@@ -269,37 +499,69 @@ impl VM {
 
     /// Create a new VM without stdlib (for benchmarking).
     pub fn new_bare() -> Self {
-        let (tx, _) = mpsc::channel(100);
         Self {
             stack: Vec::new(),
             call_stack: vec![Frame {
                 return_address: usize::MAX, // Set to MAX so global return stops execution
                 locals: HashMap::new(),
                 indexed_locals: Vec::new(),
+                args: Vec::new(),
                 this_context: JsValue::Undefined,
                 new_target: None,
                 super_called: false,
                 resume_ip: None,
+                function: None,
+                discard_result: false,
+                module: None,
+                construct_base: None,
             }],
-            heap: Vec::new(),
+            heap: Heap::new(),
             native_functions: Vec::new(),
-            task_queue: VecDeque::new(),
+            task_queue: TaskQueue::new(),
             timers: Vec::new(),
-            program: Vec::new(),
+            program: Arc::new(Vec::new()),
             modules: HashMap::new(),
             ip: 0,
             function_call_counts: HashMap::new(),
             total_instructions: 0,
             exception_handlers: Vec::new(),
             current_exception: None,
+            native_origin: None,
             current_module_path: None,
             async_runtime: None,
-            async_task_tx: Some(tx),
             module_cache: ModuleCache::new(),
+            module_scopes: Vec::new(),
+            entry_module: None,
+            imported_bindings: HashMap::new(),
+            reexports: HashMap::new(),
+            import_metas: HashMap::new(),
+            hot: None,
+            prefetcher: None,
+            bundled: None,
             compiler: Compiler::new(),
+            compile_settings: CompileSettings::default(),
             async_context: None,
             resolved_queue: Vec::new(),
             current_promise: None,
+            method_registry: MethodRegistry::new(),
+            captured_output: None,
+            console_locale: None,
+            console_timers: HashMap::new(),
+            profiler: None,
+            coverage: None,
+            tracer: None,
+            #[cfg(feature = "corpus-runner")]
+            invariants: None,
+            pinned_globals: vec![JsValue::Undefined; PINNED_GLOBALS.len()],
+            debug_info: None,
+            loop_stats: LoopStats::new(),
+            scheduler: Scheduler::new(),
+            permissions: Permissions::unrestricted(),
+            descriptors: DescriptorTable::new(),
+            streams: StreamTable::new(),
+            processes: ProcessTable::new(),
+            process: ProcessState::new(),
+            operator_overloading: false,
         }
     }
 
@@ -375,6 +637,23 @@ impl VM {
         self.module_cache.invalidate_all();
     }
 
+    /// Start compiling the modules `entry` statically imports in the
+    /// background, replacing any earlier prefetch
+    pub fn prefetch_imports(&mut self, entry: &Path, source: &str) {
+        self.prefetcher = Some(Prefetcher::start(
+            entry,
+            source,
+            self.compile_settings.clone(),
+        ));
+    }
+
+    /// Drop any prefetch work not yet started, and wait for the compiles
+    /// already running
+    pub fn cancel_prefetch(&mut self) {
+        // Dropping the prefetcher cancels it and joins its workers
+        self.prefetcher = None;
+    }
+
     /// Check if a module is cached
     pub fn is_module_cached(&self, path: &PathBuf) -> bool {
         self.module_cache.get(path).is_some()
@@ -394,149 +673,118 @@ impl VM {
         })
     }
 
-    /// Compile and execute a module source, returning the export values
-    /// This is used by the ImportAsync handler to actually run imported modules
+    /// Compile and execute a module source in a scope of its own, filling in
+    /// `namespace` with the bindings `exports` names (see
+    /// `parse_module_exports`) as they are set. This is used by the
+    /// ImportAsync handler to actually run imported modules.
     pub fn execute_module(
         &mut self,
         source: &str,
         path: &Path,
-        export_names: &[String],
-    ) -> Result<HashMap<String, JsValue>, String> {
-        let syntax = if path.to_string_lossy().ends_with(".ts")
-            || path.to_string_lossy().ends_with(".tsx")
-        {
-            Some(Syntax::Typescript(TsSyntax {
-                decorators: true,
-                tsx: path.to_string_lossy().ends_with(".tsx"),
-                ..Default::default()
-            }))
-        } else if path.to_string_lossy().ends_with(".js")
-            || path.to_string_lossy().ends_with(".jsx")
-        {
-            Some(Syntax::Es(Default::default()))
-        } else {
-            Some(Syntax::Typescript(TsSyntax {
-                decorators: true,
-                ..Default::default()
-            }))
+        namespace: usize,
+        exports: &HashMap<String, Option<String>>,
+    ) -> Result<(), String> {
+        // A prefetched compile counts only if the file hasn't changed since
+        let prefetched = self
+            .prefetcher
+            .as_ref()
+            .and_then(|prefetcher| prefetcher.take(path))
+            .filter(|module| module.source == source);
+        let (bytecode, line_table, warnings) = match prefetched {
+            Some(module) => (module.bytecode, module.line_table, module.warnings),
+            None => {
+                self.compiler.borrow_check = self.compile_settings.borrow_check_for(path);
+                self.compiler.bytecode_cache = self.compile_settings.bytecode_cache.clone();
+                let bytecode = self
+                    .compiler
+                    .compile_with_syntax(source, Some(syntax_for_path(&path.to_string_lossy())));
+                let warnings = std::mem::take(&mut self.compiler.warnings);
+                (bytecode, self.compiler.line_table.clone(), warnings)
+            }
         };
+        for warning in warnings {
+            eprintln!("Warning: {}: {}", path.display(), warning);
+        }
+        let bytecode =
+            bytecode.map_err(|e| format!("Failed to compile module {}: {}", path.display(), e))?;
 
-        let bytecode = self
-            .compiler
-            .compile_with_syntax(source, syntax)
-            .map_err(|e| format!("Failed to compile module {}: {}", path.display(), e))?;
-
-        // Save IP BEFORE appending program, because append_program modifies IP
-        let saved_ip = self.ip;
+        // Save the position BEFORE appending program, because append_program modifies IP
+        let saved = self.save_position();
         let saved_module_path = self.current_module_path.clone();
-        // Save stack to prevent module execution from corrupting caller's stack
-        let saved_stack = self.stack.clone();
 
         let start_offset = self.append_program(bytecode);
         let end_offset = self.program.len();
+        if let Some(coverage) = self.coverage.as_mut() {
+            coverage.add_chunk(path, start_offset, &line_table);
+        }
+
+        // The module's top-level bindings live in an environment object that
+        // its functions keep seeing after it has run
+        let environment = self.heap.alloc(HeapObject {
+            data: HeapData::Object(HashMap::new()),
+        });
+        self.module_scopes.push(ModuleScope {
+            path: path.to_path_buf(),
+            code: start_offset..end_offset,
+            environment,
+            namespace,
+            exports: exported_as(exports),
+        });
+        self.call_stack.push(Frame {
+            return_address: usize::MAX,
+            locals: HashMap::new(),
+            indexed_locals: Vec::new(),
+            args: Vec::new(),
+            this_context: JsValue::Undefined,
+            new_target: None,
+            super_called: false,
+            resume_ip: None,
+            function: None,
+            discard_result: false,
+            construct_base: None,
+            module: Some(environment),
+        });
 
         self.current_module_path = Some(path.to_path_buf());
         self.ip = start_offset;
 
-        // Execute only the module's bytecode, not the entire program
-        // We run until we hit the module's Halt or reach the end of module bytecode
-        loop {
-            if self.ip >= end_offset {
-                break;
-            }
-            if self.ip >= self.program.len() {
-                break;
-            }
-            let result = self.exec_one();
-            // Don't stop the entire VM on module Halt - just break from module execution
-            if result == ExecResult::Stop {
-                // Check if this is a Halt within the module
-                if self.ip > 0 && self.ip <= end_offset + 5 {
-                    // This is likely the module's Halt, break normally
-                    break;
-                }
-                // Otherwise, it's a real stop
-                break;
-            }
-        }
+        // Run until the module's Halt, or until its frame returns to the
+        // sentinel. Calls into code outside the module, such as functions it
+        // imported, run to completion on the way.
+        self.run_until_return_sentinel();
 
-        self.ip = saved_ip;
+        // Drop anything the module left on the stack so it can't corrupt the caller's
+        self.restore_position(saved);
         self.current_module_path = saved_module_path;
-        // Restore stack to prevent module execution from corrupting caller's stack
-        self.stack = saved_stack;
-
-        let mut exports = HashMap::new();
-        let global_locals = &self.call_stack[0].locals;
-
-        for name in export_names {
-            if let Some(value) = global_locals.get(name) {
-                exports.insert(name.clone(), value.clone());
-            } else {
-                exports.insert(name.clone(), JsValue::Undefined);
-            }
-        }
 
-        Ok(exports)
+        Ok(())
     }
 
-    /// Poll a promise until it's resolved (synchronous wait)
+    /// Wait for a promise to settle, up to `timeout_ms` (synchronous wait)
     /// Returns the resolved value or undefined if timeout/error
     pub fn poll_promise(&mut self, promise: &Promise, timeout_ms: u64) -> JsValue {
-        let start = std::time::Instant::now();
-        let sleep_duration = std::time::Duration::from_millis(1);
-
-        loop {
-            match promise.get_state() {
-                PromiseState::Fulfilled => {
-                    let value = promise.get_value().unwrap_or(JsValue::Undefined);
-                    eprintln!("DEBUG poll_promise: fulfilled, value = {:?}", value);
-                    return value;
-                }
-                PromiseState::Rejected => {
-                    let value = promise.get_value().unwrap_or(JsValue::Undefined);
-                    eprintln!("DEBUG poll_promise: rejected, value = {:?}", value);
-                    return value;
-                }
-                PromiseState::Pending => {
-                    let elapsed = start.elapsed().as_millis();
-                    if elapsed > timeout_ms as u128 {
-                        eprintln!("DEBUG poll_promise: timeout after {}ms", elapsed);
-                        return JsValue::Undefined;
-                    }
-                    // Brief sleep to avoid busy-waiting
-                    std::thread::sleep(sleep_duration);
-                }
+        match promise.wait(Some(Duration::from_millis(timeout_ms))) {
+            PromiseState::Fulfilled | PromiseState::Rejected => {
+                promise.get_value().unwrap_or(JsValue::Undefined)
             }
+            PromiseState::Pending => JsValue::Undefined,
         }
     }
 
-    /// Register a callback to be invoked when a promise resolves
+    /// Register a callback to be invoked on the event loop when a promise
+    /// settles. The loop stays alive until it has run.
     pub fn register_promise_callback(
         &mut self,
         promise: &Promise,
-        callback: Box<dyn FnOnce(JsValue) + Send>,
+        callback: Box<dyn FnOnce(&mut VM, JsValue) + Send>,
     ) {
         let promise = promise.clone();
-        // Spawn a thread to wait for the promise
+        let completion = self.scheduler.completion();
+        // Wait on a helper thread, which sleeps until the promise settles
         std::thread::spawn(move || {
-            let sleep_duration = std::time::Duration::from_millis(1);
-            loop {
-                match promise.get_state() {
-                    PromiseState::Fulfilled => {
-                        let value = promise.get_value().unwrap_or(JsValue::Undefined);
-                        callback(value);
-                        break;
-                    }
-                    PromiseState::Rejected => {
-                        let value = promise.get_value().unwrap_or(JsValue::Undefined);
-                        callback(value);
-                        break;
-                    }
-                    PromiseState::Pending => {
-                        std::thread::sleep(sleep_duration);
-                    }
-                }
-            }
+            promise.wait(None);
+            let value = promise.get_value().unwrap_or(JsValue::Undefined);
+            completion.complete(move |vm| callback(vm, value));
         });
     }
 
@@ -557,6 +805,247 @@ impl VM {
 
     pub fn setup_stdlib(&mut self) {
         stdlib_setup::setup_stdlib(self);
+        self.refresh_pinned_globals();
+    }
+
+    /// Reload the pinned global slots from the global frame. Needed after
+    /// writing a pinned name into `call_stack[0].locals` directly rather
+    /// than through bytecode.
+    pub fn refresh_pinned_globals(&mut self) {
+        for (slot, name) in PINNED_GLOBALS.iter().enumerate() {
+            self.pinned_globals[slot] = self.call_stack[0]
+                .locals
+                .get(*name)
+                .cloned()
+                .unwrap_or(JsValue::Undefined);
+        }
+    }
+
+    /// One `at function (file:line)` line per frame, innermost first, when
+    /// a debug section is loaded. A native that threw the exception being
+    /// unwound comes first as `at name (native)`, with or without one.
+    pub fn stack_trace(&self) -> String {
+        let mut trace = String::new();
+        if let Some(name) = self.native_origin {
+            trace.push_str(&format!("\n    at {} (native)", name));
+        }
+        let Some(debug) = &self.debug_info else {
+            return trace;
+        };
+        let mut ip = self.ip;
+        for (depth, frame) in self.call_stack.iter().enumerate().rev() {
+            let name = match frame.function {
+                Some(address) => debug
+                    .function_name(address)
+                    .map(str::to_string)
+                    .unwrap_or_else(|| format!("anonymous@{}", address)),
+                None if depth == 0 => profiler::TOP_LEVEL.to_string(),
+                None => profiler::NATIVE.to_string(),
+            };
+            match debug.line_at(ip) {
+                Some(line) => {
+                    trace.push_str(&format!("\n    at {} ({}:{})", name, debug.file, line));
+                    if let Some(text) = debug.source_line(line) {
+                        trace.push_str(&format!("\n        {}", text.trim()));
+                    }
+                }
+                None => trace.push_str(&format!("\n    at {} ({})", name, debug.file)),
+            }
+            // The caller is paused on the call that pushed this frame
+            ip = frame.return_address.wrapping_sub(1);
+        }
+        trace
+    }
+
+    /// Environment of the module whose code is at `address`
+    fn module_env_at(&self, address: usize) -> Option<usize> {
+        self.module_scopes
+            .iter()
+            .find(|scope| scope.code.contains(&address))
+            .map(|scope| scope.environment)
+    }
+
+    /// Environment of the module whose top level is running, if it is
+    fn module_body(&self) -> Option<usize> {
+        let frame = self.call_stack.last()?;
+        frame.function.is_none().then_some(frame.module?)
+    }
+
+    /// Frames a name is looked up in, innermost first. A module body sees
+    /// its own frames and the globals, not the frames of its importer.
+    fn scope_depths(&self) -> impl Iterator<Item = usize> + use<> {
+        let floor = if self.module_scopes.is_empty() {
+            0
+        } else {
+            self.call_stack
+                .iter()
+                .rposition(|frame| frame.function.is_none() && frame.module.is_some())
+                .unwrap_or(0)
+        };
+        (floor..self.call_stack.len())
+            .rev()
+            .chain((floor > 0).then_some(0))
+    }
+
+    fn module_binding(&self, environment: usize, name: &str) -> Option<&JsValue> {
+        match self.heap.get(environment) {
+            Some(HeapObject {
+                data: HeapData::Object(bindings),
+            }) => bindings.get(name),
+            _ => None,
+        }
+    }
+
+    fn set_module_binding(&mut self, environment: usize, name: String, value: JsValue) {
+        let exported = self
+            .module_scopes
+            .iter()
+            .find(|scope| scope.environment == environment)
+            .and_then(|scope| Some((scope.namespace, scope.exports.get(&name)?.clone())));
+        if let Some((namespace, exported)) = exported {
+            for exported in exported {
+                self.set_export(namespace, exported, value.clone());
+            }
+        }
+        if let Some(HeapObject {
+            data: HeapData::Object(bindings),
+        }) = self.heap.get_mut(environment)
+        {
+            bindings.insert(name, value);
+        }
+    }
+
+    /// Set the export `name` of `namespace`, and wherever it is re-exported
+    fn set_export(&mut self, namespace: usize, name: String, value: JsValue) {
+        let mut pending = vec![(namespace, name)];
+        let mut seen = HashSet::new();
+        while let Some(export) = pending.pop() {
+            if !seen.insert(export.clone()) {
+                continue;
+            }
+            if let Some(targets) = self.reexports.get(&export) {
+                pending.extend(targets.iter().cloned());
+            }
+            let (namespace, name) = export;
+            if let Some(HeapObject {
+                data: HeapData::Object(props),
+            }) = self.heap.get_mut(namespace)
+            {
+                props.insert(name, value.clone());
+            }
+        }
+    }
+
+    /// Namespace of the module whose top level is running, if it is
+    fn module_namespace(&self) -> Option<usize> {
+        let environment = self.module_body()?;
+        self.module_scopes
+            .iter()
+            .find(|scope| scope.environment == environment)
+            .map(|scope| scope.namespace)
+    }
+
+    /// Path of the module whose code is running, or of the entry script
+    /// outside any module; empty for a script without a file
+    fn running_module_path(&self) -> PathBuf {
+        self.module_scopes
+            .iter()
+            .find(|scope| scope.code.contains(&self.ip))
+            .map(|scope| scope.path.clone())
+            .or_else(|| self.current_module_path.clone())
+            .map(|path| crate::platform::canonicalize(&path).unwrap_or(path))
+            .unwrap_or_default()
+    }
+
+    /// `import.meta` of the running module: `url`, `filename` and `dirname`
+    /// when it has a file, `resolve(specifier)`, and `hot` under `oitec dev`
+    fn import_meta(&mut self) -> usize {
+        let path = self.running_module_path();
+        if let Some(&meta) = self.import_metas.get(&path) {
+            return meta;
+        }
+        let mut props = HashMap::new();
+        if path.is_absolute() {
+            let dirname = path.parent().unwrap_or(&path);
+            for (name, value) in [
+                ("url", crate::platform::file_url(&path)),
+                ("filename", path.to_string_lossy().into_owned()),
+                ("dirname", dirname.to_string_lossy().into_owned()),
+            ] {
+                props.insert(name.to_string(), JsValue::String(value));
+            }
+        }
+        let resolve = self.register_fallible_native("import.meta.resolve", native_import_resolve);
+        props.insert("resolve".to_string(), JsValue::NativeFunction(resolve));
+        if let Some(hot) = hot::hot_object(self, &path) {
+            props.insert("hot".to_string(), JsValue::Object(hot));
+        }
+        let meta = self.heap.alloc(HeapObject {
+            data: HeapData::Object(props),
+        });
+        self.import_metas.insert(path, meta);
+        meta
+    }
+
+    /// Export the export `name` of `from` as `exported` of `to`, now and
+    /// whenever it changes
+    fn reexport(&mut self, from: usize, name: String, to: usize, exported: String) {
+        let value = match self.heap.get(from) {
+            Some(HeapObject {
+                data: HeapData::Object(props),
+            }) => props.get(&name).cloned(),
+            _ => None,
+        };
+        self.set_export(to, exported.clone(), value.unwrap_or(JsValue::Undefined));
+        self.reexports
+            .entry((from, name))
+            .or_default()
+            .push((to, exported));
+    }
+
+    /// The import `name` is bound to in the scope of the frame at `depth`:
+    /// a module's top level, or the entry script's for the global frame
+    fn imported_binding(&self, depth: usize, name: &str) -> Option<&ImportedBinding> {
+        if self.imported_bindings.is_empty() {
+            return None;
+        }
+        let scope = self.call_stack[depth].module;
+        if scope.is_none() && depth != 0 {
+            return None;
+        }
+        self.imported_bindings.get(&scope)?.get(name)
+    }
+
+    /// The value `name` is bound to where the running code sees it
+    fn lookup_var(&self, name: &str) -> Option<JsValue> {
+        for depth in self.scope_depths() {
+            let frame = &self.call_stack[depth];
+            if let Some(value) = frame.locals.get(name) {
+                return Some(value.clone());
+            }
+            if let Some(environment) = frame.module
+                && let Some(value) = self.module_binding(environment, name)
+            {
+                return Some(value.clone());
+            }
+            if let Some(import) = self.imported_binding(depth, name) {
+                let value = match self.heap.get(import.namespace) {
+                    Some(HeapObject {
+                        data: HeapData::Object(props),
+                    }) => props.get(&import.name).cloned(),
+                    _ => None,
+                };
+                return Some(value.unwrap_or(JsValue::Undefined));
+            }
+        }
+        None
+    }
+
+    /// Mirror a write to the global frame into its pinned slot, if any
+    fn sync_pinned_global(&mut self, name: &str, value: &JsValue) {
+        if let Some(slot) = pinned_global_slot(name) {
+            self.pinned_globals[slot as usize] = value.clone();
+        }
     }
 
     /// Set script command-line arguments as __args__ global variable.
@@ -566,10 +1055,145 @@ impl VM {
 
     pub fn register_native(&mut self, func: NativeFn) -> usize {
         let idx = self.native_functions.len();
-        self.native_functions.push(func);
+        self.native_functions.push(Native::Plain(func));
+        idx
+    }
+
+    /// Register a native that can throw: an `Err` it returns is raised in
+    /// the calling code like a `throw`, with `name` as the innermost frame
+    /// of the stack trace.
+    pub fn register_fallible_native(
+        &mut self,
+        name: &'static str,
+        func: FallibleNativeFn,
+    ) -> usize {
+        let idx = self.native_functions.len();
+        self.native_functions.push(Native::Fallible(name, func));
         idx
     }
 
+    /// Call the native registered at `idx`
+    pub fn call_native(&mut self, idx: usize, args: Vec<JsValue>) -> NativeResult {
+        self.invoke_native(self.native_functions[idx], args)
+    }
+
+    fn invoke_native(&mut self, native: Native, args: Vec<JsValue>) -> NativeResult {
+        match native {
+            Native::Plain(func) => Ok(func(self, args)),
+            Native::Fallible(name, func) => func(self, args).inspect_err(|_| {
+                self.native_origin = Some(name);
+            }),
+        }
+    }
+
+    /// Call `callee` from native code and return its result, or the
+    /// exception it threw. The interpreter's position (ip, operand stack,
+    /// frames and try handlers) is saved first and restored afterwards, so
+    /// a native running inside an instruction can call back into script.
+    /// An exception the callee does not catch stops at this call instead of
+    /// unwinding into handlers that belong to the native's caller.
+    pub fn call_function(&mut self, callee: JsValue, args: Vec<JsValue>) -> NativeResult {
+        let (address, env, bound) = match callee {
+            JsValue::Function {
+                address,
+                env,
+                bound,
+            } => (address, env, bound),
+            JsValue::NativeFunction(idx) => return self.call_native(idx, args),
+            other => {
+                return Err(crate::stdlib::type_error(&format!(
+                    "{} is not a function",
+                    crate::stdlib::console::console_string(self, &other)
+                )));
+            }
+        };
+        if self.call_stack.len() >= MAX_CALL_STACK_DEPTH {
+            panic!(
+                "Stack overflow: maximum call depth of {} exceeded",
+                MAX_CALL_STACK_DEPTH
+            );
+        }
+
+        let saved = self.save_position();
+        self.exception_handlers.push(ExceptionHandler {
+            catch_addr: CALLBACK_THREW,
+            finally_addr: 0,
+            stack_depth: saved.stack_depth,
+            call_stack_depth: saved.frames,
+        });
+        self.enter_function((address, env, bound), JsValue::Undefined, args, usize::MAX);
+        self.run_until_return_sentinel();
+
+        let result = if self.ip == CALLBACK_THREW {
+            Err(self.stack.pop().unwrap_or(JsValue::Undefined))
+        } else if self.stack.len() > saved.stack_depth {
+            Ok(self.stack.pop().unwrap_or(JsValue::Undefined))
+        } else {
+            Ok(JsValue::Undefined)
+        };
+        self.restore_position(saved);
+        result
+    }
+
+    /// Where the interpreter is, for a nested run to return to
+    fn save_position(&self) -> SavedPosition {
+        SavedPosition {
+            ip: self.ip,
+            stack_depth: self.stack.len(),
+            frames: self.call_stack.len(),
+            handlers: self.exception_handlers.len(),
+        }
+    }
+
+    /// Drop whatever a nested run left behind and resume at `saved`
+    fn restore_position(&mut self, saved: SavedPosition) {
+        self.ip = saved.ip;
+        self.stack.truncate(saved.stack_depth);
+        self.call_stack.truncate(saved.frames);
+        self.exception_handlers.truncate(saved.handlers);
+    }
+
+    /// Register a native method on a built-in prototype (String, Array, ...).
+    /// The method receives the receiver as its first argument.
+    pub fn register_prototype_method(&mut self, proto: BuiltinProto, name: &str, func: NativeFn) {
+        self.method_registry.register(proto, name, func);
+    }
+
+    /// Register a prototype method that can throw; `label` names it in
+    /// stack traces.
+    pub fn register_fallible_prototype_method(
+        &mut self,
+        proto: BuiltinProto,
+        name: &str,
+        label: &'static str,
+        func: FallibleNativeFn,
+    ) {
+        self.method_registry
+            .register_fallible(proto, name, label, func);
+    }
+
+    /// Invoke a registered prototype method for `CallMethod`.
+    /// Pops `arg_count` arguments and pushes the result. Returns `Ok(false)`
+    /// (leaving the stack untouched) if no method is registered under
+    /// `name`, and the exception if the method threw.
+    fn call_registered_method(
+        &mut self,
+        proto: BuiltinProto,
+        name: &str,
+        receiver: JsValue,
+        arg_count: usize,
+    ) -> Result<bool, JsValue> {
+        let Some(native) = self.method_registry.get(proto, name) else {
+            return Ok(false);
+        };
+        let args = std::iter::once(receiver)
+            .chain(self.pop_args(arg_count))
+            .collect();
+        let result = self.invoke_native(native, args)?;
+        self.stack.push(result);
+        Ok(true)
+    }
+
     pub fn schedule_timer(&mut self, callback: JsValue, delay_ms: u64) {
         self.timers.push(TimerTask {
             due: Instant::now() + Duration::from_millis(delay_ms),
@@ -580,16 +1204,27 @@ impl VM {
         });
     }
 
+    /// Queue a callback to run on the event loop at the given priority
+    pub fn queue_task(&mut self, callback: JsValue, args: Vec<JsValue>, priority: TaskPriority) {
+        let task = Task {
+            function_ptr: callback,
+            args,
+        };
+        self.task_queue.push(task, priority);
+    }
+
     pub fn load_program(&mut self, bytecode: Vec<OpCode>) {
-        self.program = bytecode;
+        self.program = Arc::new(bytecode);
         self.ip = 0;
         self.current_module_path = None;
+        self.entry_module = None;
     }
 
     pub fn load_program_with_path(&mut self, bytecode: Vec<OpCode>, path: PathBuf) {
-        self.program = bytecode;
+        self.program = Arc::new(bytecode);
         self.ip = 0;
         self.current_module_path = Some(path);
+        self.entry_module = None;
     }
 
     /// Update the current module path (for relative imports)
@@ -597,11 +1232,88 @@ impl VM {
         self.current_module_path = Some(path);
     }
 
+    /// Create the namespace of the module at `path` with its exports still
+    /// `undefined`, and cache it. Returns the namespace and the exports.
+    fn cache_module_namespace(
+        &mut self,
+        path: &PathBuf,
+        source: &str,
+    ) -> (usize, HashMap<String, Option<String>>) {
+        let hash = ModuleCache::compute_hash(path);
+        let exports = parse_module_exports(source, &path.to_string_lossy());
+        let mut props: HashMap<String, JsValue> = exports
+            .keys()
+            .map(|name| (name.clone(), JsValue::Undefined))
+            .collect();
+        props.insert(
+            "__path__".to_string(),
+            JsValue::String(path.to_string_lossy().into_owned()),
+        );
+        props.insert(
+            "__source__".to_string(),
+            JsValue::String(source.to_string()),
+        );
+        props.insert("__hash__".to_string(), JsValue::String(hash.clone()));
+        let namespace = self.heap.alloc(HeapObject {
+            data: HeapData::Object(props),
+        });
+        self.module_cache.insert(CachedModule {
+            path: path.clone(),
+            source: source.to_string(),
+            hash,
+            load_time: std::time::SystemTime::now(),
+            namespace_object: namespace,
+        });
+        (namespace, exports)
+    }
+
+    /// Cache the entry script as a module before the first import it runs,
+    /// so an import that cycles back to it gets its namespace instead of
+    /// running it a second time
+    fn register_entry_module(&mut self) {
+        if self.entry_module.is_some()
+            || self
+                .module_scopes
+                .iter()
+                .any(|scope| scope.code.contains(&self.ip))
+        {
+            return;
+        }
+        let Some(path) = self.current_module_path.clone() else {
+            return;
+        };
+        let Ok(canonical) = crate::platform::canonicalize(&path) else {
+            return;
+        };
+        let canonical = self.module_cache.identity_path(&canonical);
+        let Ok(source) = fs::read_to_string(&canonical) else {
+            return;
+        };
+        let (namespace, exports) = self.cache_module_namespace(&canonical, &source);
+        self.entry_module = Some((namespace, exported_as(&exports)));
+    }
+
+    /// Mirror a write to a top-level binding of the entry script into its
+    /// namespace, if the binding is exported
+    fn export_entry_binding(&mut self, name: &str, value: &JsValue) {
+        let Some((namespace, exported)) = self
+            .entry_module
+            .as_ref()
+            .and_then(|(namespace, exports)| Some((*namespace, exports.get(name)?.clone())))
+        else {
+            return;
+        };
+        for exported in exported {
+            self.set_export(namespace, exported, value.clone());
+        }
+    }
+
     /// Append bytecode to the existing program and return the starting offset.
     /// This rebases all address-containing instructions so they point to the correct
     /// locations in the combined program.
     pub fn append_program(&mut self, bytecode: Vec<OpCode>) -> usize {
         let start_offset = self.program.len();
+        let program = Arc::make_mut(&mut self.program);
 
         // Rebase all address-containing instructions
         for op in bytecode {
@@ -609,12 +1321,15 @@ impl VM {
                 OpCode::Jump(addr) => OpCode::Jump(addr + start_offset),
                 OpCode::JumpIfFalse(addr) => OpCode::JumpIfFalse(addr + start_offset),
                 OpCode::MakeClosure(addr) => OpCode::MakeClosure(addr + start_offset),
-                OpCode::Push(JsValue::Function { address, env }) => {
-                    OpCode::Push(JsValue::Function {
-                        address: address + start_offset,
-                        env,
-                    })
-                }
+                OpCode::Push(JsValue::Function {
+                    address,
+                    env,
+                    bound,
+                }) => OpCode::Push(JsValue::Function {
+                    address: address + start_offset,
+                    env,
+                    bound,
+                }),
                 OpCode::SetupTry {
                     catch_addr,
                     finally_addr,
@@ -632,7 +1347,7 @@ impl VM {
                 },
                 other => other,
             };
-            self.program.push(rebased_op);
+            program.push(rebased_op);
         }
 
         self.ip = start_offset;
@@ -640,33 +1355,146 @@ impl VM {
     }
 
     pub fn run_event_loop(&mut self) {
-        // 1) Run the initial script to completion.
+        // 1) Run the initial script to completion, then its microtasks.
         self.run_until_halt();
+        self.run_microtasks();
 
-        // 2) Drain the event loop: timers -> task queue -> execute task.
+        // 2) Drain the event loop: completions -> timers -> child exits ->
+        //    signals -> immediates or one queued task -> its microtasks.
         loop {
+            // `oitec dev` restarts the program in a fresh VM
+            if self.hot.as_ref().is_some_and(HotReload::reload_requested) {
+                break;
+            }
+            self.pump_completions(false, None);
             self.pump_timers();
+            crate::stdlib::child_process::pump_exits(self);
+            crate::stdlib::process::pump_signals(self);
 
-            if let Some(task) = self.task_queue.pop_front() {
-                self.execute_task(task);
+            let immediates = self.scheduler.due_immediates(!self.task_queue.is_empty());
+            if !immediates.is_empty() {
+                for task in immediates {
+                    self.run_task(task);
+                }
+                continue;
+            }
+            if let Some(task) = self.task_queue.pop() {
+                self.run_task(task);
                 continue;
             }
 
             // No immediate tasks left.
-            if self.timers.is_empty() {
+            let awaiting_children = self.processes.awaiting_exit();
+            if self.timers.is_empty() && !awaiting_children && self.scheduler.outstanding() == 0 {
                 break;
             }
 
-            // Timers exist but none ready: sleep until the next one is due.
-            if let Some(next_due) = self.next_timer_due() {
-                let now = Instant::now();
-                if next_due > now {
-                    std::thread::sleep(next_due - now);
+            // Block until a completion comes back or the next timer is due.
+            // Signals, and child exits where nothing reports them, are
+            // checked every IDLE_POLL_INTERVAL.
+            let now = Instant::now();
+            let watched = cfg!(unix) && self.scheduler.outstanding() > 0;
+            let polling = (awaiting_children && !watched) || self.process.has_listeners();
+            let deadline = match self.next_timer_due() {
+                Some(due) if polling => Some(due.min(now + IDLE_POLL_INTERVAL)),
+                Some(due) => Some(due),
+                None if polling => Some(now + IDLE_POLL_INTERVAL),
+                None => None,
+            };
+            if deadline.is_none_or(|deadline| deadline > now) {
+                self.pump_completions(true, deadline);
+                if let Some(profiler) = self.profiler.as_mut() {
+                    profiler.skip_idle();
                 }
-            } else {
-                // This shouldn't happen if timers is not empty, but handle it anyway
+            }
+        }
+    }
+
+    /// Turn the event loop under an `await` until `promise` settles or
+    /// nothing is left that could settle it. Tasks run nested on top of the
+    /// awaiting frame, which resumes where it was.
+    fn run_loop_until_settled(&mut self, promise: &Promise) {
+        while promise.get_state() == PromiseState::Pending {
+            self.pump_completions(false, None);
+            self.pump_timers();
+            crate::stdlib::child_process::pump_exits(self);
+            crate::stdlib::process::pump_signals(self);
+            if promise.get_state() != PromiseState::Pending {
                 break;
             }
+
+            let mut ready = self.scheduler.due_immediates(!self.task_queue.is_empty());
+            if ready.is_empty() {
+                ready.extend(self.task_queue.pop());
+            }
+            if !ready.is_empty() {
+                for task in ready {
+                    self.run_nested_task(task);
+                }
+                continue;
+            }
+
+            if self.timers.is_empty()
+                && !self.processes.awaiting_exit()
+                && self.scheduler.outstanding() == 0
+            {
+                break;
+            }
+            // The promise may also settle off the loop, so don't block long
+            let now = Instant::now();
+            let deadline = match self.next_timer_due() {
+                Some(due) => due.min(now + IDLE_POLL_INTERVAL),
+                None => now + IDLE_POLL_INTERVAL,
+            };
+            self.pump_completions(true, Some(deadline));
+        }
+    }
+
+    /// Run a task and its microtasks from inside a running frame
+    fn run_nested_task(&mut self, task: Task) {
+        let mut next = Some(task);
+        while let Some(task) = next {
+            if let Err(exception) = self.call_function(task.function_ptr, task.args) {
+                panic!("Uncaught exception: {:?}{}", exception, self.stack_trace());
+            }
+            next = self.scheduler.next_microtask();
+        }
+    }
+
+    /// Run the work completions handed back, first waiting for one until
+    /// `deadline` (or indefinitely) when `block` is set
+    fn pump_completions(&mut self, block: bool, deadline: Option<Instant>) {
+        let ready = if block {
+            self.scheduler.wait(deadline)
+        } else {
+            self.scheduler.ready()
+        };
+        for on_complete in ready {
+            on_complete(self);
+        }
+    }
+
+    /// Run a task and the microtasks it queues, reporting it when it held
+    /// the loop up for too long
+    fn run_task(&mut self, task: Task) {
+        let target = task.function_ptr.clone();
+        let started = Instant::now();
+        self.execute_task(task);
+        let elapsed = started.elapsed();
+        if self.loop_stats.record_task(elapsed) {
+            eprintln!(
+                "Warning: task {} blocked the event loop for {:.1} ms",
+                self.task_label(&target),
+                loop_stats::millis(elapsed)
+            );
+        }
+        self.run_microtasks();
+    }
+
+    /// Run queued microtasks, including ones they queue, until none are left
+    pub fn run_microtasks(&mut self) {
+        while let Some(task) = self.scheduler.next_microtask() {
+            self.execute_task(task);
         }
     }
 
@@ -674,6 +1502,22 @@ impl VM {
         self.timers.iter().map(|t| t.due).min()
     }
 
+    /// Timers scheduled but not yet due
+    pub fn pending_timers(&self) -> usize {
+        self.timers.len()
+    }
+
+    /// Function name (or native index) a task runs, for slow-task reports
+    fn task_label(&self, target: &JsValue) -> String {
+        match target {
+            JsValue::Function { address, .. } => profiler::function_names(&self.program)
+                .remove(address)
+                .unwrap_or_else(|| format!("anonymous@{}", address)),
+            JsValue::NativeFunction(index) => format!("native#{}", index),
+            other => format!("{:?}", other),
+        }
+    }
+
     fn pump_timers(&mut self) {
         let now = Instant::now();
         // Move all due timers into the task queue.
@@ -681,7 +1525,8 @@ impl VM {
         while i < self.timers.len() {
             if self.timers[i].due <= now {
                 let timer = self.timers.remove(i);
-                self.task_queue.push_back(timer.task);
+                self.loop_stats.record_timer_lag(now - timer.due);
+                self.task_queue.push(timer.task, TaskPriority::High);
             } else {
                 i += 1;
             }
@@ -702,6 +1547,155 @@ impl VM {
         crate::vm::property::find_setter_with_proto_chain(self, obj_ptr, name)
     }
 
+    fn private_slot(&self, this_ptr: usize, index: usize) -> Option<usize> {
+        crate::vm::property::private_slot(self, this_ptr, index)
+    }
+
+    fn private_value(&self, this_ptr: usize, index: usize) -> Option<JsValue> {
+        crate::vm::property::private_value(self, this_ptr, index)
+    }
+
+    /// Check an assignment to `key` on the object at `ptr` against its
+    /// property attributes
+    fn check_property_write(&self, ptr: usize, key: &str) -> Result<(), String> {
+        self.descriptors
+            .check_write(ptr, key, || match self.heap.get(ptr).map(|obj| &obj.data) {
+                Some(HeapData::Object(props)) => props.contains_key(key),
+                Some(HeapData::Array(arr)) => key.parse::<usize>().is_ok_and(|i| i < arr.len()),
+                _ => false,
+            })
+    }
+
+    /// Unwind to the innermost exception handler with `exception`, or panic
+    /// if there is none
+    fn throw_value(&mut self, exception: JsValue) -> ExecResult {
+        if let Some(handler) = self.exception_handlers.pop() {
+            self.native_origin = None;
+            // Unwind the stack to the handler's saved state
+            self.stack.truncate(handler.stack_depth);
+
+            // Unwind call stack if needed
+            while self.call_stack.len() > handler.call_stack_depth {
+                self.call_stack.pop();
+            }
+
+            if handler.catch_addr != 0 {
+                // We have a catch block - push exception and jump there
+                self.stack.push(exception);
+                self.ip = handler.catch_addr;
+
+                // If there's a finally, we need to remember to run it
+                // after the catch completes
+                if handler.finally_addr != 0 {
+                    // Re-push a handler for finally (catch_addr=0 means no catch, just finally)
+                    self.exception_handlers.push(ExceptionHandler {
+                        catch_addr: 0,
+                        finally_addr: handler.finally_addr,
+                        stack_depth: self.stack.len() - 1, // Exclude the exception value
+                        call_stack_depth: handler.call_stack_depth,
+                    });
+                }
+                return ExecResult::ContinueNoIpInc;
+            } else if handler.finally_addr != 0 {
+                // No catch, but there's a finally block
+                // Store exception for rethrow after finally
+                self.current_exception = Some(exception);
+                self.ip = handler.finally_addr;
+                return ExecResult::ContinueNoIpInc;
+            }
+        }
+
+        // No handler found - panic with uncaught exception
+        panic!("Uncaught exception: {:?}{}", exception, self.stack_trace());
+    }
+
+    /// Bound `this` and arguments of a function created by `bind`
+    fn bound_this_and_args(&self, bound: Option<usize>) -> Option<(JsValue, Vec<JsValue>)> {
+        match bound.and_then(|ptr| self.heap.get(ptr)) {
+            Some(HeapObject {
+                data: HeapData::Array(items),
+            }) => {
+                let (this, args) = items.split_first()?;
+                Some((this.clone(), args.to_vec()))
+            }
+            _ => None,
+        }
+    }
+
+    /// Argument list for `apply`: the elements of an array, or none
+    fn apply_arguments(&self, args: Option<&JsValue>) -> Vec<JsValue> {
+        match args {
+            Some(JsValue::Object(ptr)) => match self.heap.get(*ptr) {
+                Some(HeapObject {
+                    data: HeapData::Array(items),
+                }) => items.clone(),
+                _ => Vec::new(),
+            },
+            _ => Vec::new(),
+        }
+    }
+
+    /// Pop the `count` arguments of the call at `ip`, in call order. Fewer
+    /// values than that means the bytecode is malformed; the panic names the
+    /// call site instead of leaving a half-popped stack behind.
+    fn pop_args(&mut self, count: usize) -> Vec<JsValue> {
+        if self.stack.len() < count {
+            panic!(
+                "{:?} at ip={} passes {} arguments but only {} values are on the stack",
+                self.program.get(self.ip),
+                self.ip,
+                count,
+                self.stack.len()
+            );
+        }
+        self.stack.split_off(self.stack.len() - count)
+    }
+
+    /// Enter a bytecode function: push a frame carrying `args` as its
+    /// argument window, `this` and the captured environment. A bound function ignores
+    /// `this_context` in favour of its bound `this`, and its bound arguments
+    /// come before `args`.
+    fn enter_function(
+        &mut self,
+        (address, env, bound): (usize, Option<usize>, Option<usize>),
+        this_context: JsValue,
+        args: Vec<JsValue>,
+        return_address: usize,
+    ) {
+        let (this_context, args) = match self.bound_this_and_args(bound) {
+            Some((bound_this, mut bound_args)) => {
+                bound_args.extend(args);
+                (bound_this, bound_args)
+            }
+            None => (this_context, args),
+        };
+
+        let mut frame = Frame {
+            return_address,
+            locals: HashMap::new(),
+            indexed_locals: Vec::new(),
+            args,
+            this_context,
+            new_target: None,
+            super_called: false,
+            resume_ip: None,
+            function: Some(address),
+            discard_result: false,
+            module: self.module_env_at(address),
+            construct_base: None,
+        };
+        if let Some(HeapObject {
+            data: HeapData::Object(env_props),
+        }) = env.and_then(|ptr| self.heap.get(ptr))
+        {
+            for (name, value) in env_props {
+                frame.locals.insert(name.clone(), value.clone());
+            }
+        }
+        self.call_stack.push(frame);
+        self.ip = address;
+    }
+
     fn execute_task(&mut self, task: Task) {
         // Stack overflow protection
         if self.call_stack.len() >= MAX_CALL_STACK_DEPTH {
@@ -712,42 +1706,26 @@ impl VM {
         }
 
         match task.function_ptr {
-            JsValue::Function { address, env } => {
-                // Push args in call order so the function prologue `Store(...)` consumes correctly.
-                for arg in task.args {
-                    self.stack.push(arg);
-                }
-
-                let mut frame = Frame {
-                    return_address: usize::MAX, // sentinel: stop when returning
-                    locals: HashMap::new(),
-                    indexed_locals: Vec::new(),
-                    this_context: JsValue::Undefined,
-                    new_target: None,
-                    super_called: false,
-                    resume_ip: None,
-                };
-
-                // CLOSURE MAGIC: If this function has captured variables (env),
-                // load them into the new frame's locals. This is the key to
-                // surviving the Stack Frame Paradox!
-                if let Some(HeapObject {
-                    data: HeapData::Object(props),
-                }) = env.and_then(|ptr| self.heap.get(ptr))
-                {
-                    for (name, value) in props {
-                        frame.locals.insert(name.clone(), value.clone());
-                    }
-                }
-
-                self.call_stack.push(frame);
-                self.ip = address;
+            JsValue::Function {
+                address,
+                env,
+                bound,
+            } => {
+                // The args become the frame's argument window. The return
+                // address is a sentinel: stop when returning.
+                self.enter_function(
+                    (address, env, bound),
+                    JsValue::Undefined,
+                    task.args,
+                    usize::MAX,
+                );
                 self.run_until_return_sentinel();
             }
 
             JsValue::NativeFunction(idx) => {
-                let func = self.native_functions[idx];
-                let _ = func(self, task.args);
+                if let Err(exception) = self.call_native(idx, task.args) {
+                    self.throw_value(exception);
+                }
             }
 
             _ => panic!("Target is not callable"),
@@ -784,7 +1762,35 @@ impl VM {
         if self.ip >= self.program.len() {
             return ExecResult::Stop;
         }
+        if self.profiler.as_mut().is_some_and(|p| p.tick()) {
+            let stack = self.call_stack.iter().map(|f| f.function).collect();
+            if let Some(profiler) = self.profiler.as_mut() {
+                profiler.record(stack);
+            }
+        }
+        if let Some(coverage) = self.coverage.as_mut() {
+            coverage.hit(self.ip);
+        }
+        if let Some(tracer) = self.tracer.as_mut() {
+            let frame = self.call_stack.last().and_then(|f| f.function);
+            tracer.record(
+                &self.program,
+                self.ip,
+                frame,
+                self.call_stack.len(),
+                &self.stack,
+            );
+        }
+        #[cfg(feature = "corpus-runner")]
+        if self.invariants.is_some() {
+            corpus::check_step(self);
+        }
         let op = self.program[self.ip].clone();
+        if self.operator_overloading
+            && let Some(result) = self.dispatch_operator(&op)
+        {
+            return result;
+        }
         match op {
             OpCode::NewObject => {
                 let ptr = self.heap.len();
@@ -824,16 +1830,20 @@ impl VM {
                     let setter_addr_and_env = self.find_setter_with_proto_chain(ptr, &name);
 
                     if let Some((address, env)) = setter_addr_and_env {
-                        self.stack.push(value.clone());
                         let this_context = JsValue::Object(ptr);
                         let mut frame = Frame {
                             return_address: self.ip + 1,
                             locals: HashMap::new(),
                             indexed_locals: Vec::new(),
+                            args: vec![value],
                             this_context,
                             new_target: None,
                             super_called: false,
                             resume_ip: None,
+                            function: Some(address),
+                            discard_result: true,
+                            module: self.module_env_at(address),
+                            construct_base: None,
                         };
 
                         if let Some(HeapObject {
@@ -850,7 +1860,16 @@ impl VM {
                         return ExecResult::ContinueNoIpInc;
                     }
 
+                    // process.env writes go to the host environment
+                    if crate::stdlib::process::set_env_property(self, ptr, &name, &value) {
+                        self.ip += 1;
+                        return ExecResult::Continue;
+                    }
+
                     // No setter found, store the value directly
+                    if let Err(e) = self.check_property_write(ptr, &name) {
+                        return self.throw_value(JsValue::String(e));
+                    }
                     if let Some(heap_item) = self.heap.get_mut(ptr)
                         && let HeapData::Object(props) = &mut heap_item.data
                     {
@@ -871,7 +1890,7 @@ impl VM {
                     // Convert key to string
                     let key_name = match &key_val {
                         JsValue::String(s) => s.clone(),
-                        JsValue::Number(n) => n.to_string(),
+                        JsValue::Number(n) => number_to_string(*n),
                         JsValue::Object(_) => {
                             // For objects, use default string representation
                             "[object Object]".to_string()
@@ -879,10 +1898,27 @@ impl VM {
                         _ => format!("{:?}", key_val),
                     };
 
-                    if let Some(heap_item) = self.heap.get_mut(ptr)
-                        && let HeapData::Object(props) = &mut heap_item.data
-                    {
-                        props.insert(key_name, value);
+                    if crate::stdlib::process::set_env_property(self, ptr, &key_name, &value) {
+                        self.ip += 1;
+                        return ExecResult::Continue;
+                    }
+                    if let Err(e) = self.check_property_write(ptr, &key_name) {
+                        return self.throw_value(JsValue::String(e));
+                    }
+                    match self.heap.get_mut(ptr).map(|obj| &mut obj.data) {
+                        Some(HeapData::Object(props)) => {
+                            props.insert(key_name, value);
+                        }
+                        // An index past the end grows the array (JS semantics)
+                        Some(HeapData::Array(arr)) => {
+                            if let Ok(i) = key_name.parse::<usize>() {
+                                if i >= arr.len() {
+                                    arr.resize(i + 1, JsValue::Undefined);
+                                }
+                                arr[i] = value;
+                            }
+                        }
+                        _ => {}
                     }
                 }
             }
@@ -907,13 +1943,23 @@ impl VM {
                             self.ip += 1;
                             return ExecResult::Continue;
                         }
-                        self.stack.push(JsValue::Undefined);
+                        if let Some(HeapObject {
+                            data: HeapData::Buffer(view),
+                        }) = self.heap.get(ptr)
+                        {
+                            self.stack.push(crate::stdlib::buffer::byte_at(view, idx));
+                            self.ip += 1;
+                            return ExecResult::Continue;
+                        }
+                        // Any other object keys it by the number's string form
+                        let value = self.get_prop_with_proto_chain(ptr, &number_to_string(idx));
+                        self.stack.push(value);
                     }
                     (JsValue::Object(ptr), key_val) => {
                         // Convert key to string
                         let key_name = match &key_val {
                             JsValue::String(s) => s.clone(),
-                            JsValue::Number(n) => n.to_string(),
+                            JsValue::Number(n) => number_to_string(*n),
                             JsValue::Object(_) => "[object Object]".to_string(),
                             _ => format!("{:?}", key_val),
                         };
@@ -928,34 +1974,7 @@ impl VM {
                                         self.stack.push(val);
                                     } else if key_name == "length" {
                                         self.stack.push(JsValue::Number(arr.len() as f64));
-                                    } else if matches!(
-                                        key_name.as_str(),
-                                        "push"
-                                            | "pop"
-                                            | "shift"
-                                            | "unshift"
-                                            | "splice"
-                                            | "slice"
-                                            | "concat"
-                                            | "join"
-                                            | "indexOf"
-                                            | "lastIndexOf"
-                                            | "includes"
-                                            | "reverse"
-                                            | "fill"
-                                            | "at"
-                                            | "map"
-                                            | "filter"
-                                            | "forEach"
-                                            | "reduce"
-                                            | "find"
-                                            | "findIndex"
-                                            | "some"
-                                            | "every"
-                                            | "flat"
-                                            | "flatMap"
-                                            | "sort"
-                                    ) {
+                                    } else if property::ARRAY_METHODS.contains(&key_name.as_str()) {
                                         self.stack.push(JsValue::NativeFunction(0));
                                     } else {
                                         self.stack.push(JsValue::Undefined);
@@ -963,8 +1982,13 @@ impl VM {
                                 }
                                 _ => {
                                     // Object access: obj[key] - look up with prototype chain
-                                    let value = self.get_prop_with_proto_chain(ptr, &key_name);
-                                    self.stack.push(value.clone());
+                                    let value = match crate::stdlib::process::env_property(
+                                        self, ptr, &key_name,
+                                    ) {
+                                        Some(value) => value,
+                                        None => self.get_prop_with_proto_chain(ptr, &key_name),
+                                    };
+                                    self.stack.push(value);
                                 }
                             }
                         } else {
@@ -972,26 +1996,10 @@ impl VM {
                         }
                     }
                     (JsValue::String(s), JsValue::Number(idx)) => {
-                        // String char access: str[index]
-                        // Use O(1) byte indexing for ASCII strings (common case)
-                        let i = idx as usize;
-                        let bytes = s.as_bytes();
-                        let char_val = if i < bytes.len() {
-                            let b = bytes[i];
-                            if b < 128 {
-                                // ASCII: O(1) fast path
-                                JsValue::String((b as char).to_string())
-                            } else {
-                                // Non-ASCII: fallback to chars().nth() - O(n) but rare
-                                s.chars()
-                                    .nth(i)
-                                    .map(|c| JsValue::String(c.to_string()))
-                                    .unwrap_or(JsValue::Undefined)
-                            }
-                        } else {
-                            JsValue::Undefined
-                        };
-                        self.stack.push(char_val);
+                        // String char access: str[index], one UTF-16 code unit
+                        let element = string::element(&s, idx);
+                        self.stack
+                            .push(element.map(JsValue::String).unwrap_or(JsValue::Undefined));
                     }
                     _ => {
                         self.stack.push(JsValue::Undefined);
@@ -1010,17 +2018,22 @@ impl VM {
                                     let getter_name = format!("getter:{}", name);
                                     let val = self.get_prop_with_proto_chain(ptr, &getter_name);
 
-                                    if let JsValue::Function { address, env } = val {
+                                    if let JsValue::Function { address, env, .. } = val {
                                         let this_context = JsValue::Object(ptr);
 
                                         let mut frame = Frame {
                                             return_address: self.ip + 1,
                                             locals: HashMap::new(),
                                             indexed_locals: Vec::new(),
+                                            args: Vec::new(),
                                             this_context,
                                             new_target: None,
                                             super_called: false,
                                             resume_ip: None,
+                                            function: Some(address),
+                                            discard_result: false,
+                                            module: self.module_env_at(address),
+                                            construct_base: None,
                                         };
 
                                         if let Some(HeapObject {
@@ -1037,40 +2050,18 @@ impl VM {
                                         return ExecResult::ContinueNoIpInc;
                                     }
 
-                                    let val = self.get_prop_with_proto_chain(ptr, &name);
+                                    let val = match crate::stdlib::process::env_property(
+                                        self, ptr, &name,
+                                    ) {
+                                        Some(val) => val,
+                                        None => self.get_prop_with_proto_chain(ptr, &name),
+                                    };
                                     self.stack.push(val);
                                 }
                                 HeapData::Array(arr) => {
                                     if name == "length" {
                                         self.stack.push(JsValue::Number(arr.len() as f64));
-                                    } else if matches!(
-                                        name.as_str(),
-                                        "push"
-                                            | "pop"
-                                            | "shift"
-                                            | "unshift"
-                                            | "splice"
-                                            | "slice"
-                                            | "concat"
-                                            | "join"
-                                            | "indexOf"
-                                            | "lastIndexOf"
-                                            | "includes"
-                                            | "reverse"
-                                            | "fill"
-                                            | "at"
-                                            | "map"
-                                            | "filter"
-                                            | "forEach"
-                                            | "reduce"
-                                            | "find"
-                                            | "findIndex"
-                                            | "some"
-                                            | "every"
-                                            | "flat"
-                                            | "flatMap"
-                                            | "sort"
-                                    ) {
+                                    } else if property::ARRAY_METHODS.contains(&name.as_str()) {
                                         // Array methods are handled by CallMethod;
                                         // return a function-typed value so typeof reports "function"
                                         self.stack.push(JsValue::NativeFunction(0));
@@ -1099,23 +2090,47 @@ impl VM {
                                         self.stack.push(JsValue::Undefined);
                                     }
                                 }
+                                HeapData::Buffer(view) => {
+                                    if name == "length" {
+                                        self.stack.push(JsValue::Number(view.len as f64));
+                                    } else {
+                                        self.stack.push(JsValue::Undefined);
+                                    }
+                                }
+                                HeapData::Decimal(value) => {
+                                    if name == "scale" {
+                                        self.stack.push(JsValue::Number(value.scale() as f64));
+                                    } else {
+                                        self.stack.push(JsValue::Undefined);
+                                    }
+                                }
+                                HeapData::WeakMap(_)
+                                | HeapData::WeakSet(_)
+                                | HeapData::WeakRef(_)
+                                | HeapData::Stream(_) => {
+                                    self.stack.push(JsValue::Undefined);
+                                }
+                                HeapData::Process(id) => {
+                                    let id = *id;
+                                    let value =
+                                        crate::stdlib::child_process::get_property(self, id, &name);
+                                    self.stack.push(value);
+                                }
                             }
                         } else {
                             self.stack.push(JsValue::Undefined);
                         }
                     }
                     // Special case: looking up .prototype on a function value
-                    Some(JsValue::Function {
-                        address: _address,
-                        env: _env,
-                    }) if name == "prototype" => {
+                    Some(JsValue::Function { .. }) if name == "prototype" => {
                         // Functions don't have a prototype property by default in our VM
                         // This returns undefined
                         self.stack.push(JsValue::Undefined);
                     }
                     Some(JsValue::String(s)) => {
                         if name == "length" {
-                            self.stack.push(JsValue::Number(s.len() as f64));
+                            let len = string::len(&s);
+                            self.stack.push(JsValue::Number(len as f64));
                         } else {
                             self.stack.push(JsValue::Undefined);
                         }
@@ -1127,8 +2142,49 @@ impl VM {
                 }
             }
 
+            OpCode::Push(JsValue::Function {
+                address,
+                env: None,
+                bound,
+            }) => {
+                // Each evaluation of a function expression creates a new
+                // function: an empty environment of its own gives it an
+                // identity for `===`, as MakeClosure's does for closures
+                let env = self.heap.alloc(HeapObject {
+                    data: HeapData::Object(HashMap::new()),
+                });
+                self.stack.push(JsValue::Function {
+                    address,
+                    env: Some(env),
+                    bound,
+                });
+            }
+
             OpCode::Push(v) => self.stack.push(v),
 
+            OpCode::LoadArg(index) => {
+                let arg = self
+                    .call_stack
+                    .last()
+                    .and_then(|frame| frame.args.get(index))
+                    .cloned()
+                    .unwrap_or(JsValue::Undefined);
+                self.stack.push(arg);
+            }
+
+            OpCode::LoadRestArgs(index) => {
+                let rest = self
+                    .call_stack
+                    .last()
+                    .map(|frame| frame.args.get(index..).unwrap_or_default().to_vec())
+                    .unwrap_or_default();
+                let ptr = self.heap.len();
+                self.heap.push(HeapObject {
+                    data: HeapData::Array(rest),
+                });
+                self.stack.push(JsValue::Object(ptr));
+            }
+
             OpCode::Let(name) => {
                 let val = self.stack.pop().unwrap_or(JsValue::Undefined);
                 if self.call_stack.is_empty() {
@@ -1136,35 +2192,61 @@ impl VM {
                     eprintln!("Stack depth: {}", self.stack.len());
                     return ExecResult::Stop;
                 }
-                self.call_stack.last_mut().unwrap().locals.insert(name, val);
+                if self.call_stack.len() == 1 {
+                    self.sync_pinned_global(&name, &val);
+                    self.export_entry_binding(&name, &val);
+                }
+                match self.module_body() {
+                    Some(environment) => self.set_module_binding(environment, name, val),
+                    None => {
+                        self.call_stack.last_mut().unwrap().locals.insert(name, val);
+                    }
+                }
             }
 
             OpCode::Store(name) => {
                 let val = self.stack.pop().unwrap_or(JsValue::Undefined);
                 // Assign to an existing binding if found, otherwise create in current frame.
-                let mut stored = false;
-                for frame in self.call_stack.iter_mut().rev() {
+                let mut environment = None;
+                let mut stored = None;
+                for depth in self.scope_depths() {
+                    let frame = &self.call_stack[depth];
                     if frame.locals.contains_key(&name) {
-                        frame.locals.insert(name.clone(), val.clone());
-                        stored = true;
+                        stored = Some(depth);
+                        break;
+                    }
+                    if let Some(module) = frame.module
+                        && self.module_binding(module, &name).is_some()
+                    {
+                        environment = Some(module);
                         break;
                     }
+                    if self.imported_binding(depth, &name).is_some() {
+                        return self.throw_value(JsValue::String(
+                            "TypeError: Assignment to constant variable.".to_string(),
+                        ));
+                    }
                 }
-                if !stored {
-                    self.call_stack.last_mut().unwrap().locals.insert(name, val);
+                if stored.is_none() && environment.is_none() {
+                    environment = self.module_body();
+                }
+                match environment {
+                    Some(environment) => self.set_module_binding(environment, name, val),
+                    None => {
+                        let depth = stored.unwrap_or(self.call_stack.len() - 1);
+                        self.call_stack[depth]
+                            .locals
+                            .insert(name.clone(), val.clone());
+                        if depth == 0 {
+                            self.sync_pinned_global(&name, &val);
+                            self.export_entry_binding(&name, &val);
+                        }
+                    }
                 }
             }
 
             OpCode::Load(name) => {
-                // Search for variable from innermost to outermost frame.
-                let mut found = None;
-                for frame in self.call_stack.iter().rev() {
-                    if let Some(v) = frame.locals.get(&name) {
-                        found = Some(v.clone());
-                        break;
-                    }
-                }
-                let value = found.unwrap_or(JsValue::Undefined);
+                let value = self.lookup_var(&name).unwrap_or(JsValue::Undefined);
                 self.stack.push(value);
             }
 
@@ -1187,52 +2269,33 @@ impl VM {
                 }
 
                 let callee = self.stack.pop().expect("Missing callee");
-                let mut args = Vec::with_capacity(arg_count);
-                for _ in 0..arg_count {
-                    args.push(self.stack.pop().expect("Missing argument"));
-                }
-                args.reverse();
+                let args = self.pop_args(arg_count);
 
                 match callee {
-                    JsValue::Function { address, env } => {
+                    JsValue::Function {
+                        address,
+                        env,
+                        bound,
+                    } => {
                         // Record function call for tiered compilation
-                        self.record_function_call(address);
-
-                        for arg in &args {
-                            self.stack.push(arg.clone());
-                        }
-
-                        let mut frame = Frame {
-                            return_address: self.ip + 1,
-                            locals: HashMap::new(),
-                            indexed_locals: Vec::new(),
-                            this_context: JsValue::Undefined,
-                            new_target: None,
-                            super_called: false,
-                            resume_ip: None,
-                        };
-
-                        // CLOSURE CONTEXT SWITCH: Load captured variables from
-                        // the environment heap object into the new frame's locals.
-                        // This makes them available to the function body.
-                        if let Some(HeapObject {
-                            data: HeapData::Object(props),
-                        }) = env.and_then(|ptr| self.heap.get(ptr))
-                        {
-                            for (name, value) in props {
-                                frame.locals.insert(name.clone(), value.clone());
-                            }
-                        }
+                        self.record_function_call(address);
 
-                        self.call_stack.push(frame);
-                        self.ip = address;
+                        // CLOSURE CONTEXT SWITCH: captured variables from the
+                        // environment heap object become the new frame's locals.
+                        self.enter_function(
+                            (address, env, bound),
+                            JsValue::Undefined,
+                            args,
+                            self.ip + 1,
+                        );
                         return ExecResult::ContinueNoIpInc;
                     }
                     JsValue::NativeFunction(idx) => {
-                        args.reverse();
-                        let func = self.native_functions[idx];
-                        let result = func(self, args);
-                        self.stack.push(result);
+                        // In call order, as CallMethod passes them
+                        match self.call_native(idx, args) {
+                            Ok(result) => self.stack.push(result),
+                            Err(exception) => return self.throw_value(exception),
+                        }
                     }
                     JsValue::Object(ptr) => {
                         // Check if object has a __call__ property (callable object like String)
@@ -1242,26 +2305,28 @@ impl VM {
                         {
                             if let Some(JsValue::NativeFunction(idx)) = props.get("__call__") {
                                 let idx = *idx;
-                                args.reverse();
-                                let func = self.native_functions[idx];
-                                let result = func(self, args);
-                                self.stack.push(result);
-                            } else if let Some(JsValue::Function { address, env }) =
+                                match self.call_native(idx, args) {
+                                    Ok(result) => self.stack.push(result),
+                                    Err(exception) => return self.throw_value(exception),
+                                }
+                            } else if let Some(JsValue::Function { address, env, .. }) =
                                 props.get("__call__")
                             {
                                 let address = *address;
                                 let env = *env;
-                                for arg in &args {
-                                    self.stack.push(arg.clone());
-                                }
                                 let mut frame = Frame {
                                     return_address: self.ip + 1,
                                     locals: HashMap::new(),
                                     indexed_locals: Vec::new(),
+                                    args,
                                     this_context: JsValue::Object(ptr),
                                     new_target: None,
                                     super_called: false,
                                     resume_ip: None,
+                                    function: Some(address),
+                                    discard_result: false,
+                                    module: self.module_env_at(address),
+                                    construct_base: None,
                                 };
                                 if let Some(HeapObject {
                                     data: HeapData::Object(env_props),
@@ -1308,6 +2373,71 @@ impl VM {
                     return ExecResult::Stop;
                 }
                 let frame = self.call_stack.pop().expect("Missing frame");
+                if frame.discard_result {
+                    self.stack.pop();
+                }
+                if let Some(base) = frame.construct_base {
+                    let returned = if self.stack.len() > base {
+                        self.stack.pop()
+                    } else {
+                        None
+                    };
+                    self.stack.truncate(base);
+                    self.stack.push(match returned {
+                        Some(
+                            value @ (JsValue::Object(_)
+                            | JsValue::Function { .. }
+                            | JsValue::Promise(_)),
+                        ) => value,
+                        _ => frame.this_context,
+                    });
+                }
+                self.ip = frame.return_address;
+                if self.ip == usize::MAX {
+                    return ExecResult::Stop;
+                }
+                return ExecResult::ContinueNoIpInc;
+            }
+
+            OpCode::ReturnMulti(count) => {
+                let split = self.stack.len().saturating_sub(count);
+                let values = self.stack.split_off(split);
+                // A setter's result is dropped, as Return drops it
+                if self
+                    .call_stack
+                    .last()
+                    .is_some_and(|frame| frame.discard_result)
+                {
+                    let frame = self.call_stack.pop().expect("Missing frame");
+                    self.ip = frame.return_address;
+                    if self.ip == usize::MAX {
+                        return ExecResult::Stop;
+                    }
+                    return ExecResult::ContinueNoIpInc;
+                }
+                let unpacked_by_caller = self
+                    .call_stack
+                    .last()
+                    .and_then(|frame| self.program.get(frame.return_address))
+                    .is_some_and(|op| matches!(op, OpCode::Unpack(n) if *n == count));
+
+                if unpacked_by_caller {
+                    // Hand the values straight to the caller and skip its Unpack
+                    let frame = self.call_stack.pop().expect("Missing frame");
+                    self.stack.extend(values);
+                    self.ip = frame.return_address + 1;
+                    return ExecResult::ContinueNoIpInc;
+                }
+
+                // Caller wants the array itself - materialize it and return normally
+                let array_ptr = self.heap.len();
+                self.heap.push(HeapObject {
+                    data: HeapData::Array(values),
+                });
+                self.stack.push(JsValue::Object(array_ptr));
+                let Some(frame) = self.call_stack.pop() else {
+                    return ExecResult::Stop;
+                };
                 self.ip = frame.return_address;
                 if self.ip == usize::MAX {
                     return ExecResult::Stop;
@@ -1315,8 +2445,40 @@ impl VM {
                 return ExecResult::ContinueNoIpInc;
             }
 
+            OpCode::Unpack(count) => {
+                let source = self.stack.pop().expect("Unpack: missing value");
+                for i in 0..count {
+                    let element = match &source {
+                        JsValue::Object(ptr) => match self.heap.get(*ptr).map(|obj| &obj.data) {
+                            Some(HeapData::Array(arr)) => arr.get(i).cloned(),
+                            Some(HeapData::Object(props)) => props.get(&i.to_string()).cloned(),
+                            _ => None,
+                        },
+                        JsValue::String(s) => s.chars().nth(i).map(|c| JsValue::String(c.into())),
+                        _ => None,
+                    };
+                    self.stack.push(element.unwrap_or(JsValue::Undefined));
+                }
+            }
+
             OpCode::Drop(name) => {
-                self.call_stack.last_mut().unwrap().locals.remove(&name);
+                if self.call_stack.len() == 1 {
+                    self.sync_pinned_global(&name, &JsValue::Undefined);
+                }
+                if let Some(environment) = self.module_body()
+                    && let Some(HeapObject {
+                        data: HeapData::Object(bindings),
+                    }) = self.heap.get_mut(environment)
+                {
+                    bindings.remove(&name);
+                } else {
+                    self.call_stack.last_mut().unwrap().locals.remove(&name);
+                }
+            }
+
+            OpCode::LoadGlobal(slot) => {
+                let value = self.pinned_globals[slot as usize].clone();
+                self.stack.push(value);
             }
 
             OpCode::Add => {
@@ -1333,13 +2495,13 @@ impl VM {
                     }
                     (JsValue::String(a_str), b) => {
                         let b_str = match b {
-                            JsValue::Number(n) => n.to_string(),
+                            JsValue::Number(n) => number_to_string(n),
                             JsValue::Boolean(b) => b.to_string(),
                             JsValue::Null => "null".to_string(),
                             JsValue::Undefined => "undefined".to_string(),
                             JsValue::String(s) => s,
                             JsValue::Object(ptr) => format!("Object({})", ptr),
-                            JsValue::Function { address, env: _env } => {
+                            JsValue::Function { address, .. } => {
                                 format!("Function({})", address)
                             }
                             JsValue::NativeFunction(idx) => {
@@ -1351,13 +2513,13 @@ impl VM {
                     }
                     (a, JsValue::String(b_str)) => {
                         let a_str = match a {
-                            JsValue::Number(n) => n.to_string(),
+                            JsValue::Number(n) => number_to_string(n),
                             JsValue::Boolean(b) => b.to_string(),
                             JsValue::Null => "null".to_string(),
                             JsValue::Undefined => "undefined".to_string(),
                             JsValue::String(s) => s,
                             JsValue::Object(ptr) => format!("Object({})", ptr),
-                            JsValue::Function { address, env: _env } => {
+                            JsValue::Function { address, .. } => {
                                 format!("Function({})", address)
                             }
                             JsValue::NativeFunction(idx) => {
@@ -1448,9 +2610,16 @@ impl VM {
             OpCode::Delete(ref prop_name) => {
                 let obj_val = self.stack.pop().unwrap_or(JsValue::Undefined);
                 if let JsValue::Object(obj_id) = obj_val {
-                    if obj_id < self.heap.len() {
-                        if let HeapData::Object(ref mut props) = self.heap[obj_id].data {
+                    if let Some(deleted) =
+                        crate::stdlib::process::delete_env_property(self, obj_id, prop_name)
+                    {
+                        self.stack.push(JsValue::Boolean(deleted));
+                    } else if self.heap.contains(obj_id) {
+                        if !self.descriptors.flags(obj_id, prop_name).configurable {
+                            self.stack.push(JsValue::Boolean(false));
+                        } else if let HeapData::Object(ref mut props) = self.heap[obj_id].data {
                             props.remove(prop_name);
+                            self.descriptors.remove(obj_id, prop_name);
                             self.stack.push(JsValue::Boolean(true));
                         } else {
                             self.stack.push(JsValue::Boolean(false));
@@ -1571,7 +2740,7 @@ impl VM {
 
             OpCode::Print => {
                 let v = self.stack.pop().unwrap_or(JsValue::Undefined);
-                println!("➜ {:?}", v);
+                crate::stdlib::native_log(self, vec![v]);
             }
 
             OpCode::Pop => {
@@ -1585,13 +2754,7 @@ impl VM {
 
             OpCode::JumpIfFalse(target) => {
                 let condition = self.stack.pop().unwrap_or(JsValue::Undefined);
-                let is_falsy = match condition {
-                    JsValue::Boolean(b) => !b,
-                    JsValue::Number(n) => n == 0.0,
-                    JsValue::Null | JsValue::Undefined => true,
-                    _ => false,
-                };
-                if is_falsy {
+                if !crate::stdlib::to_boolean(&condition) {
                     self.ip = target;
                     return ExecResult::ContinueNoIpInc;
                 }
@@ -1624,7 +2787,7 @@ impl VM {
             OpCode::Eq => {
                 let b = self.stack.pop().unwrap();
                 let a = self.stack.pop().unwrap();
-                self.stack.push(JsValue::Boolean(a == b));
+                self.stack.push(JsValue::Boolean(a.strict_equals(&b)));
             }
 
             OpCode::EqEq => {
@@ -1633,7 +2796,7 @@ impl VM {
                 let a = self.stack.pop().unwrap();
 
                 // If strictly equal, push true
-                if a == b {
+                if a.strict_equals(&b) {
                     self.stack.push(JsValue::Boolean(true));
                 } else {
                     // Otherwise, try type coercion
@@ -1666,7 +2829,7 @@ impl VM {
             OpCode::Ne => {
                 let b = self.stack.pop().unwrap();
                 let a = self.stack.pop().unwrap();
-                self.stack.push(JsValue::Boolean(a != b));
+                self.stack.push(JsValue::Boolean(!a.strict_equals(&b)));
             }
 
             OpCode::NeEq => {
@@ -1675,7 +2838,7 @@ impl VM {
                 let a = self.stack.pop().unwrap();
 
                 // If strictly equal, return false
-                if a == b {
+                if a.strict_equals(&b) {
                     self.stack.push(JsValue::Boolean(false));
                 } else {
                     // Otherwise, try type coercion
@@ -1768,6 +2931,11 @@ impl VM {
                 let value = self.stack.pop().unwrap();
                 let array_ptr = self.stack.pop().unwrap();
 
+                if let (JsValue::Object(ptr), JsValue::Number(idx)) = (&array_ptr, &index_val)
+                    && let Err(e) = self.check_property_write(*ptr, &number_to_string(*idx))
+                {
+                    return self.throw_value(JsValue::String(e));
+                }
                 if let (JsValue::Object(ptr), JsValue::Number(idx)) = (array_ptr, index_val)
                     && let Some(HeapObject {
                         data: HeapData::Array(arr),
@@ -1782,6 +2950,14 @@ impl VM {
                 }
             }
 
+            OpCode::NewWeakMap => {
+                let ptr = self.heap.len();
+                self.heap.push(HeapObject {
+                    data: HeapData::WeakMap(HashMap::new()),
+                });
+                self.stack.push(JsValue::Object(ptr));
+            }
+
             OpCode::NewArray(size) => {
                 let ptr = self.heap.len();
                 let elements = vec![JsValue::Undefined; size];
@@ -1796,12 +2972,16 @@ impl VM {
                 let target = self.stack.pop().expect("Missing target (array or String)");
                 match (target, index_val) {
                     (JsValue::Object(ptr), JsValue::Number(idx)) => {
-                        if let Some(heap_obj) = self.heap.get(ptr)
-                            && let HeapData::Array(arr) = &heap_obj.data
-                        {
-                            let i = idx as usize;
-                            let val = arr.get(i).cloned().unwrap_or(JsValue::Undefined);
-                            self.stack.push(val);
+                        match self.heap.get(ptr).map(|obj| &obj.data) {
+                            Some(HeapData::Array(arr)) => {
+                                let i = idx as usize;
+                                let val = arr.get(i).cloned().unwrap_or(JsValue::Undefined);
+                                self.stack.push(val);
+                            }
+                            Some(HeapData::Buffer(view)) => {
+                                self.stack.push(crate::stdlib::buffer::byte_at(view, idx));
+                            }
+                            _ => {}
                         }
                     }
                     // Handle string index for arrays (needed for for...in loops)
@@ -1832,13 +3012,9 @@ impl VM {
                         }
                     }
                     (JsValue::String(s), JsValue::Number(idx)) => {
-                        let i = idx as usize;
-                        let char_val = s
-                            .chars()
-                            .nth(i)
-                            .map(|c| JsValue::String(c.to_string()))
-                            .unwrap_or(JsValue::Undefined);
-                        self.stack.push(char_val);
+                        let element = string::element(&s, idx);
+                        self.stack
+                            .push(element.map(JsValue::String).unwrap_or(JsValue::Undefined));
                     }
                     _ => {
                         self.stack.push(JsValue::Undefined);
@@ -1936,6 +3112,7 @@ impl VM {
                     self.stack.push(JsValue::Function {
                         address,
                         env: Some(ptr),
+                        bound: None,
                     });
                 } else {
                     panic!("MakeClosure expects an Object pointer on stack");
@@ -1955,15 +3132,11 @@ impl VM {
                 let constructor_val = self.stack.pop().expect("Missing constructor");
 
                 // Pop arguments
-                let mut args = Vec::with_capacity(arg_count);
-                for _ in 0..arg_count {
-                    args.push(self.stack.pop().expect("Missing argument"));
-                }
-                args.reverse();
+                let args = self.pop_args(arg_count);
 
                 // Extract the actual constructor function and prototype
                 let (address, env, prototype, new_target_val) = match &constructor_val {
-                    JsValue::Function { address, env } => {
+                    JsValue::Function { address, env, .. } => {
                         // For a plain function, new.target is the function itself
                         (*address, *env, None::<JsValue>, constructor_val.clone())
                     }
@@ -1976,7 +3149,7 @@ impl VM {
                             let ctor = props.get("constructor").cloned();
                             let proto = props.get("prototype").cloned();
                             match ctor {
-                                Some(JsValue::Function { address, env }) => {
+                                Some(JsValue::Function { address, env, .. }) => {
                                     // In ES6 JavaScript, new.target is the class itself (the constructor function)
                                     // The class wrapper has a 'constructor' property pointing to the constructor
                                     // So we need to use the wrapper as new-target, not the extracted constructor
@@ -2029,20 +3202,20 @@ impl VM {
                     props.insert("__proto__".to_string(), JsValue::Object(proto_ptr));
                 }
 
-                // Push args back for function prologue
-                for arg in &args {
-                    self.stack.push(arg.clone());
-                }
-
                 // Create frame with `this` bound to the new object
                 let mut frame = Frame {
                     return_address: self.ip + 1,
                     locals: HashMap::new(),
                     indexed_locals: Vec::new(),
+                    args: args.clone(),
                     this_context: this_obj.clone(),
                     new_target: Some(new_target_val.clone()),
                     super_called: false,
                     resume_ip: None,
+                    function: (address != 0).then_some(address),
+                    discard_result: false,
+                    module: self.module_env_at(address),
+                    construct_base: Some(self.stack.len()),
                 };
 
                 // Load captured environment if present
@@ -2113,6 +3286,7 @@ impl VM {
                         if let JsValue::Function {
                             address: exec_addr,
                             env,
+                            ..
                         } = executor
                         {
                             eprintln!("DEBUG: Construct - calling executor at {}", exec_addr);
@@ -2145,10 +3319,18 @@ impl VM {
                                 return_address: self.ip + 1,
                                 locals: HashMap::new(),
                                 indexed_locals: Vec::new(),
+                                args: vec![
+                                    JsValue::NativeFunction(resolve_idx),
+                                    JsValue::NativeFunction(reject_idx),
+                                ],
                                 this_context: JsValue::Undefined,
                                 new_target: Some(executor.clone()),
                                 super_called: false,
                                 resume_ip: None,
+                                function: Some(exec_addr),
+                                discard_result: false,
+                                module: self.module_env_at(exec_addr),
+                                construct_base: None,
                             };
 
                             // Set up locals: resolve and reject
@@ -2185,39 +3367,43 @@ impl VM {
                             return_address: self.ip + 1,
                             locals: HashMap::new(),
                             indexed_locals: Vec::new(),
+                            args: Vec::new(),
                             this_context: this_obj.clone(),
                             new_target: Some(new_target_val.clone()),
                             super_called: false,
                             resume_ip: None,
+                            function: None,
+                            discard_result: false,
+                            module: None,
+                            construct_base: None,
                         };
                         self.call_stack.push(native_frame);
 
-                        let native_result = if let JsValue::Object(ptr) = &new_target_val {
-                            if let Some(heap_obj) = self.heap.get(*ptr) {
-                                if let HeapData::Object(props) = &heap_obj.data {
-                                    if let Some(JsValue::NativeFunction(native_idx)) =
-                                        props.get("constructor")
+                        let native_result = match &new_target_val {
+                            JsValue::Object(ptr) => {
+                                match self.heap.get(*ptr).map(|obj| &obj.data) {
+                                    Some(HeapData::Object(props)) => match props.get("constructor")
                                     {
-                                        let func = self.native_functions[*native_idx];
-                                        func(self, args.clone())
-                                    } else {
-                                        JsValue::Undefined
-                                    }
-                                } else {
-                                    JsValue::Undefined
+                                        Some(JsValue::NativeFunction(native_idx)) => {
+                                            let native_idx = *native_idx;
+                                            self.call_native(native_idx, args.clone())
+                                        }
+                                        _ => Ok(JsValue::Undefined),
+                                    },
+                                    _ => Ok(JsValue::Undefined),
                                 }
-                            } else {
-                                JsValue::Undefined
                             }
-                        } else {
-                            JsValue::Undefined
+                            _ => Ok(JsValue::Undefined),
                         };
 
                         // Pop the native frame
                         self.call_stack.pop();
 
                         // Push result and continue
-                        self.stack.push(native_result);
+                        match native_result {
+                            Ok(result) => self.stack.push(result),
+                            Err(exception) => return self.throw_value(exception),
+                        }
                     }
                 } else {
                     // Regular function - just call, this is set in frame
@@ -2253,106 +3439,62 @@ impl VM {
                                 for _ in 0..arg_count {
                                     self.stack.pop();
                                 }
-                                // O(1) for ASCII strings
-                                self.stack.push(JsValue::Number(s.len() as f64));
+                                let len = string::len(&s);
+                                self.stack.push(JsValue::Number(len as f64));
                             }
-                            "charCodeAt" => {
-                                // Get char code at index
-                                let index = if arg_count > 0 {
-                                    match self.stack.pop() {
-                                        Some(JsValue::Number(n)) => n as usize,
-                                        _ => 0,
-                                    }
-                                } else {
-                                    0
+                            "charCodeAt" | "codePointAt" => {
+                                let args = self.pop_args(arg_count);
+                                let index = match args.first() {
+                                    Some(JsValue::Number(n)) if !n.is_nan() => n.trunc(),
+                                    _ => 0.0,
                                 };
-                                // Pop remaining args if any
-                                for _ in 1..arg_count {
-                                    self.stack.pop();
-                                }
-                                // O(1) for ASCII strings (common case)
-                                let bytes = s.as_bytes();
-                                let result = if index < bytes.len() {
-                                    let b = bytes[index];
-                                    if b < 128 {
-                                        // ASCII: O(1) fast path
-                                        JsValue::Number(b as f64)
-                                    } else {
-                                        // Non-ASCII: fallback to chars().nth()
-                                        s.chars()
-                                            .nth(index)
-                                            .map(|c| JsValue::Number(c as u32 as f64))
-                                            .unwrap_or(JsValue::Number(f64::NAN))
-                                    }
+                                let unit = if index < 0.0 {
+                                    None
+                                } else if name == "charCodeAt" {
+                                    string::code_unit_at(&s, index as usize).map(|u| u as f64)
                                 } else {
-                                    JsValue::Number(f64::NAN)
+                                    string::code_point_at(&s, index as usize).map(|c| c as f64)
+                                };
+                                self.stack.push(match (unit, name.as_str()) {
+                                    (Some(unit), _) => JsValue::Number(unit),
+                                    (None, "charCodeAt") => JsValue::Number(f64::NAN),
+                                    (None, _) => JsValue::Undefined,
+                                });
+                            }
+                            "at" => {
+                                let args = self.pop_args(arg_count);
+                                let n = match args.first() {
+                                    Some(JsValue::Number(n)) => *n,
+                                    _ => 0.0,
                                 };
-                                self.stack.push(result);
+                                self.stack.push(
+                                    string::at(&s, n)
+                                        .map(JsValue::String)
+                                        .unwrap_or(JsValue::Undefined),
+                                );
                             }
                             "slice" => {
-                                // Get start and end indices
-                                let mut args = Vec::with_capacity(arg_count);
-                                for _ in 0..arg_count {
-                                    args.push(self.stack.pop().expect("Missing argument"));
-                                }
-                                args.reverse();
-
-                                // O(1) length for ASCII strings
-                                let len = s.len() as i64;
-                                let start = args
-                                    .first()
-                                    .and_then(|v| match v {
-                                        JsValue::Number(n) => {
-                                            let n = *n as i64;
-                                            if n < 0 {
-                                                Some((len + n).max(0) as usize)
-                                            } else {
-                                                Some(n as usize)
-                                            }
-                                        }
-                                        _ => None,
-                                    })
-                                    .unwrap_or(0);
-                                let end = args
-                                    .get(1)
-                                    .and_then(|v| match v {
-                                        JsValue::Number(n) => {
-                                            let n = *n as i64;
-                                            if n < 0 {
-                                                Some((len + n).max(0) as usize)
-                                            } else {
-                                                Some(n as usize)
-                                            }
-                                        }
-                                        _ => None,
-                                    })
-                                    .unwrap_or(len as usize);
-
-                                // For ASCII strings, use byte slicing (O(1) + copy)
-                                let bytes = s.as_bytes();
-                                let is_ascii = bytes.iter().all(|&b| b < 128);
-                                let result = if is_ascii && end <= bytes.len() {
-                                    let start = start.min(bytes.len());
-                                    let end = end.min(bytes.len());
-                                    // Safe: we verified all bytes are ASCII
-                                    unsafe {
-                                        std::str::from_utf8_unchecked(&bytes[start..end.max(start)])
-                                            .to_string()
-                                    }
-                                } else {
-                                    // Non-ASCII fallback
-                                    s.chars()
-                                        .skip(start)
-                                        .take(end.saturating_sub(start))
-                                        .collect()
+                                // Get start and end indices, negative ones from the end
+                                let args = self.pop_args(arg_count);
+                                let len = string::len(&s);
+                                let start = match args.first() {
+                                    Some(JsValue::Number(n)) => string::relative_index(*n, len),
+                                    _ => 0,
                                 };
-                                self.stack.push(JsValue::String(result));
+                                let end = match args.get(1) {
+                                    Some(JsValue::Number(n)) => string::relative_index(*n, len),
+                                    _ => len,
+                                };
+                                self.stack
+                                    .push(JsValue::String(string::slice(&s, start, end)));
                             }
                             "indexOf" => {
                                 // Pop args in reverse order (last arg on top of stack)
                                 let start_index = if arg_count > 1 {
                                     match self.stack.pop() {
-                                        Some(JsValue::Number(n)) if n >= 0.0 => n as usize,
+                                        Some(JsValue::Number(n)) => {
+                                            string::clamp_index(n, usize::MAX)
+                                        }
                                         _ => 0,
                                     }
                                 } else {
@@ -2361,7 +3503,7 @@ impl VM {
                                 let search = if arg_count > 0 {
                                     match self.stack.pop() {
                                         Some(JsValue::String(ss)) => ss,
-                                        Some(JsValue::Number(n)) => n.to_string(),
+                                        Some(JsValue::Number(n)) => number_to_string(n),
                                         _ => String::new(),
                                     }
                                 } else {
@@ -2370,10 +3512,8 @@ impl VM {
                                 for _ in 2..arg_count {
                                     self.stack.pop();
                                 }
-                                let result = s
-                                    .get(start_index..)
-                                    .and_then(|sub| sub.find(&search))
-                                    .map(|i| (i + start_index) as f64)
+                                let result = string::index_of(&s, &search, start_index)
+                                    .map(|i| i as f64)
                                     .unwrap_or(-1.0);
                                 self.stack.push(JsValue::Number(result));
                             }
@@ -2382,7 +3522,7 @@ impl VM {
                                 let separator = if arg_count > 0 {
                                     match self.stack.pop() {
                                         Some(JsValue::String(sep)) => sep,
-                                        Some(JsValue::Number(n)) => n.to_string(),
+                                        Some(JsValue::Number(n)) => number_to_string(n),
                                         _ => String::new(),
                                     }
                                 } else {
@@ -2407,49 +3547,28 @@ impl VM {
                                 self.stack.push(JsValue::Object(arr_ptr));
                             }
                             "charAt" => {
-                                // Get character at index
-                                let index = if arg_count > 0 {
-                                    match self.stack.pop() {
-                                        Some(JsValue::Number(n)) => n as usize,
-                                        _ => 0,
-                                    }
-                                } else {
-                                    0
+                                // Get the code unit at index
+                                let args = self.pop_args(arg_count);
+                                let index = match args.first() {
+                                    Some(JsValue::Number(n)) if !n.is_nan() => n.trunc(),
+                                    _ => 0.0,
                                 };
-                                // Pop remaining args
-                                for _ in 1..arg_count {
-                                    self.stack.pop();
-                                }
-                                let result = s
-                                    .chars()
-                                    .nth(index)
-                                    .map(|c| JsValue::String(c.to_string()))
-                                    .unwrap_or(JsValue::String(String::new()));
-                                self.stack.push(result);
+                                let result = string::element(&s, index).unwrap_or_default();
+                                self.stack.push(JsValue::String(result));
                             }
                             "substring" => {
                                 // Get substring from start to end
-                                let mut args = Vec::with_capacity(arg_count);
-                                for _ in 0..arg_count {
-                                    args.push(self.stack.pop().expect("Missing argument"));
-                                }
-                                args.reverse();
+                                let args = self.pop_args(arg_count);
 
-                                let len = s.chars().count();
-                                let start = args
-                                    .first()
-                                    .and_then(|v| match v {
-                                        JsValue::Number(n) => Some((*n as usize).min(len)),
-                                        _ => None,
-                                    })
-                                    .unwrap_or(0);
-                                let end = args
-                                    .get(1)
-                                    .and_then(|v| match v {
-                                        JsValue::Number(n) => Some((*n as usize).min(len)),
-                                        _ => None,
-                                    })
-                                    .unwrap_or(len);
+                                let len = string::len(&s);
+                                let start = match args.first() {
+                                    Some(JsValue::Number(n)) => string::clamp_index(*n, len),
+                                    _ => 0,
+                                };
+                                let end = match args.get(1) {
+                                    Some(JsValue::Number(n)) => string::clamp_index(*n, len),
+                                    _ => len,
+                                };
 
                                 // substring swaps start/end if start > end
                                 let (actual_start, actual_end) = if start > end {
@@ -2457,13 +3576,11 @@ impl VM {
                                 } else {
                                     (start, end)
                                 };
-
-                                let result: String = s
-                                    .chars()
-                                    .skip(actual_start)
-                                    .take(actual_end - actual_start)
-                                    .collect();
-                                self.stack.push(JsValue::String(result));
+                                self.stack.push(JsValue::String(string::slice(
+                                    &s,
+                                    actual_start,
+                                    actual_end,
+                                )));
                             }
                             "trim" => {
                                 for _ in 0..arg_count {
@@ -2537,12 +3654,8 @@ impl VM {
                                 }
                                 self.stack.push(JsValue::Boolean(s.contains(&search)));
                             }
-                            "replace" => {
-                                let mut args = Vec::with_capacity(arg_count);
-                                for _ in 0..arg_count {
-                                    args.push(self.stack.pop().expect("Missing argument"));
-                                }
-                                args.reverse();
+                            "replace" | "replaceAll" => {
+                                let args = self.pop_args(arg_count);
 
                                 let search = args
                                     .first()
@@ -2559,10 +3672,71 @@ impl VM {
                                     })
                                     .unwrap_or_default();
 
-                                // Only replace first occurrence (JS behavior)
-                                let result = s.replacen(&search, &replacement, 1);
+                                // replace swaps the first occurrence, replaceAll every one
+                                let result = if name == "replaceAll" {
+                                    s.replace(&search, &replacement)
+                                } else {
+                                    s.replacen(&search, &replacement, 1)
+                                };
                                 self.stack.push(JsValue::String(result));
                             }
+                            "matchAll" => {
+                                let args = self.pop_args(arg_count);
+                                let search = match args.first() {
+                                    Some(JsValue::String(ss)) => ss.clone(),
+                                    _ => {
+                                        return self.throw_value(JsValue::String(
+                                            "TypeError: matchAll only supports string patterns"
+                                                .to_string(),
+                                        ));
+                                    }
+                                };
+
+                                // One match object per occurrence: the matched text
+                                // at `0`, plus `index` and `input` as in JS
+                                let matches = string::match_indices(&s, &search)
+                                    .into_iter()
+                                    .map(|index| {
+                                        let props = HashMap::from([
+                                            ("0".to_string(), JsValue::String(search.clone())),
+                                            ("length".to_string(), JsValue::Number(1.0)),
+                                            ("index".to_string(), JsValue::Number(index as f64)),
+                                            ("input".to_string(), JsValue::String(s.clone())),
+                                        ]);
+                                        JsValue::Object(self.heap.alloc(HeapObject {
+                                            data: HeapData::Object(props),
+                                        }))
+                                    })
+                                    .collect();
+                                let arr_ptr = self.heap.alloc(HeapObject {
+                                    data: HeapData::Array(matches),
+                                });
+                                self.stack.push(JsValue::Object(arr_ptr));
+                            }
+                            "normalize" => {
+                                let args = self.pop_args(arg_count);
+                                let form = match args.first() {
+                                    Some(JsValue::String(form)) => Some(form.as_str()),
+                                    _ => None,
+                                };
+                                match string::normalize(&s, form) {
+                                    Ok(normalized) => self.stack.push(JsValue::String(normalized)),
+                                    Err(e) => return self.throw_value(JsValue::String(e)),
+                                }
+                            }
+                            "localeCompare" => {
+                                let args = self.pop_args(arg_count);
+                                let other = args.first().cloned().unwrap_or(JsValue::Undefined);
+                                let other = match crate::stdlib::native_string_constructor(
+                                    self,
+                                    vec![other],
+                                ) {
+                                    JsValue::String(other) => other,
+                                    _ => String::new(),
+                                };
+                                let order = string::locale_compare(&s, &other) as i8;
+                                self.stack.push(JsValue::Number(order as f64));
+                            }
                             "repeat" => {
                                 let count = if arg_count > 0 {
                                     match self.stack.pop() {
@@ -2599,7 +3773,7 @@ impl VM {
                                 let search = if arg_count > 0 {
                                     match self.stack.pop() {
                                         Some(JsValue::String(ss)) => ss,
-                                        Some(JsValue::Number(n)) => n.to_string(),
+                                        Some(JsValue::Number(n)) => number_to_string(n),
                                         _ => String::new(),
                                     }
                                 } else {
@@ -2608,24 +3782,13 @@ impl VM {
                                 for _ in 2..arg_count {
                                     self.stack.pop();
                                 }
-                                let result = match end_index {
-                                    Some(end) => {
-                                        let end = (end + search.len()).min(s.len());
-                                        s.get(..end)
-                                            .and_then(|sub| sub.rfind(&search))
-                                            .map(|i| i as f64)
-                                            .unwrap_or(-1.0)
-                                    }
-                                    None => s.rfind(&search).map(|i| i as f64).unwrap_or(-1.0),
-                                };
+                                let result = string::last_index_of(&s, &search, end_index)
+                                    .map(|i| i as f64)
+                                    .unwrap_or(-1.0);
                                 self.stack.push(JsValue::Number(result));
                             }
                             "padStart" => {
-                                let mut args = Vec::with_capacity(arg_count);
-                                for _ in 0..arg_count {
-                                    args.push(self.stack.pop().expect("Missing argument"));
-                                }
-                                args.reverse();
+                                let args = self.pop_args(arg_count);
 
                                 let target_len = args
                                     .first()
@@ -2642,7 +3805,7 @@ impl VM {
                                     })
                                     .unwrap_or_else(|| " ".to_string());
 
-                                let current_len = s.chars().count();
+                                let current_len = string::len(&s);
                                 if current_len >= target_len || pad_str.is_empty() {
                                     self.stack.push(JsValue::String(s.clone()));
                                 } else {
@@ -2656,11 +3819,7 @@ impl VM {
                                 }
                             }
                             "padEnd" => {
-                                let mut args = Vec::with_capacity(arg_count);
-                                for _ in 0..arg_count {
-                                    args.push(self.stack.pop().expect("Missing argument"));
-                                }
-                                args.reverse();
+                                let args = self.pop_args(arg_count);
 
                                 let target_len = args
                                     .first()
@@ -2677,7 +3836,7 @@ impl VM {
                                     })
                                     .unwrap_or_else(|| " ".to_string());
 
-                                let current_len = s.chars().count();
+                                let current_len = string::len(&s);
                                 if current_len >= target_len || pad_str.is_empty() {
                                     self.stack.push(JsValue::String(s.clone()));
                                 } else {
@@ -2692,17 +3851,36 @@ impl VM {
                                 }
                             }
                             _ => {
-                                // Unsupported string method - pop args and return undefined
-                                for _ in 0..arg_count {
-                                    self.stack.pop();
+                                // Registered prototype extension, else pop args and return undefined
+                                match self.call_registered_method(
+                                    BuiltinProto::String,
+                                    &name,
+                                    JsValue::String(s),
+                                    arg_count,
+                                ) {
+                                    Ok(true) => {}
+                                    Ok(false) => {
+                                        for _ in 0..arg_count {
+                                            self.stack.pop();
+                                        }
+                                        self.stack.push(JsValue::Undefined);
+                                    }
+                                    Err(exception) => return self.throw_value(exception),
                                 }
-                                self.stack.push(JsValue::Undefined);
                             }
                         }
                         self.ip += 1;
                         return ExecResult::Continue;
                     }
                     JsValue::Object(ptr) => {
+                        // Array and Map methods taking their arguments as a list pop
+                        // them here, before the receiver is borrowed from the heap
+                        let args = match (self.heap.get(ptr).map(|obj| &obj.data), name.as_str()) {
+                            (Some(HeapData::Array(_)), "splice" | "push" | "unshift" | "slice")
+                            | (Some(HeapData::Map(_)), "set") => self.pop_args(arg_count),
+                            _ => Vec::new(),
+                        };
+
                         // Check if this is an array and handle array methods
                         if let Some(HeapObject {
                             data: HeapData::Array(arr),
@@ -2710,12 +3888,6 @@ impl VM {
                         {
                             // Handle splice inline since it needs heap access
                             if name == "splice" {
-                                let mut args = Vec::with_capacity(arg_count);
-                                for _ in 0..arg_count {
-                                    args.push(self.stack.pop().expect("Missing argument"));
-                                }
-                                args.reverse();
-
                                 let start = args
                                     .first()
                                     .and_then(|v| match v {
@@ -2765,11 +3937,6 @@ impl VM {
                                     return ExecResult::Continue;
                                 }
                                 "push" => {
-                                    let mut args = Vec::with_capacity(arg_count);
-                                    for _ in 0..arg_count {
-                                        args.push(self.stack.pop().expect("Missing argument"));
-                                    }
-                                    args.reverse();
                                     for arg in args {
                                         arr.push(arg);
                                     }
@@ -2800,11 +3967,6 @@ impl VM {
                                     return ExecResult::Continue;
                                 }
                                 "unshift" => {
-                                    let mut args = Vec::with_capacity(arg_count);
-                                    for _ in 0..arg_count {
-                                        args.push(self.stack.pop().expect("Missing argument"));
-                                    }
-                                    args.reverse();
                                     for (i, arg) in args.into_iter().enumerate() {
                                         arr.insert(i, arg);
                                     }
@@ -2817,7 +3979,7 @@ impl VM {
                                     let separator = if arg_count > 0 {
                                         match self.stack.pop() {
                                             Some(JsValue::String(s)) => s,
-                                            Some(JsValue::Number(n)) => n.to_string(),
+                                            Some(JsValue::Number(n)) => number_to_string(n),
                                             _ => ",".to_string(),
                                         }
                                     } else {
@@ -2832,10 +3994,9 @@ impl VM {
                                         .iter()
                                         .map(|v| match v {
                                             JsValue::String(s) => s.clone(),
-                                            JsValue::Number(n) => n.to_string(),
+                                            JsValue::Number(n) => number_to_string(*n),
                                             JsValue::Boolean(b) => b.to_string(),
-                                            JsValue::Null => "null".to_string(),
-                                            JsValue::Undefined => "undefined".to_string(),
+                                            // null and undefined join as empty strings
                                             _ => "".to_string(),
                                         })
                                         .collect();
@@ -2867,15 +4028,7 @@ impl VM {
                                         &[] as &[JsValue]
                                     };
                                     let result =
-                                        search_slice.iter().position(|v| match (v, &search) {
-                                            (JsValue::Number(a), JsValue::Number(b)) => a == b,
-                                            (JsValue::String(a), JsValue::String(b)) => a == b,
-                                            (JsValue::Boolean(a), JsValue::Boolean(b)) => a == b,
-                                            (JsValue::Null, JsValue::Null) => true,
-                                            (JsValue::Undefined, JsValue::Undefined) => true,
-                                            (JsValue::Object(a), JsValue::Object(b)) => a == b,
-                                            _ => false,
-                                        });
+                                        search_slice.iter().position(|v| v.strict_equals(&search));
                                     self.stack.push(JsValue::Number(
                                         result.map(|i| (i + start_index) as f64).unwrap_or(-1.0),
                                     ));
@@ -2907,15 +4060,7 @@ impl VM {
                                         None => arr.len(),
                                     };
                                     let result =
-                                        arr[..end].iter().rposition(|v| match (v, &search) {
-                                            (JsValue::Number(a), JsValue::Number(b)) => a == b,
-                                            (JsValue::String(a), JsValue::String(b)) => a == b,
-                                            (JsValue::Boolean(a), JsValue::Boolean(b)) => a == b,
-                                            (JsValue::Null, JsValue::Null) => true,
-                                            (JsValue::Undefined, JsValue::Undefined) => true,
-                                            (JsValue::Object(a), JsValue::Object(b)) => a == b,
-                                            _ => false,
-                                        });
+                                        arr[..end].iter().rposition(|v| v.strict_equals(&search));
                                     self.stack.push(JsValue::Number(
                                         result.map(|i| i as f64).unwrap_or(-1.0),
                                     ));
@@ -2931,26 +4076,12 @@ impl VM {
                                     for _ in 1..arg_count {
                                         self.stack.pop();
                                     }
-                                    let found = arr.iter().any(|v| match (v, &search) {
-                                        (JsValue::Number(a), JsValue::Number(b)) => a == b,
-                                        (JsValue::String(a), JsValue::String(b)) => a == b,
-                                        (JsValue::Boolean(a), JsValue::Boolean(b)) => a == b,
-                                        (JsValue::Null, JsValue::Null) => true,
-                                        (JsValue::Undefined, JsValue::Undefined) => true,
-                                        (JsValue::Object(a), JsValue::Object(b)) => a == b,
-                                        _ => false,
-                                    });
+                                    let found = arr.iter().any(|v| v.same_value_zero(&search));
                                     self.stack.push(JsValue::Boolean(found));
                                     self.ip += 1;
                                     return ExecResult::Continue;
                                 }
                                 "slice" => {
-                                    let mut args = Vec::with_capacity(arg_count);
-                                    for _ in 0..arg_count {
-                                        args.push(self.stack.pop().expect("Missing argument"));
-                                    }
-                                    args.reverse();
-
                                     let len = arr.len() as i64;
                                     let start = args
                                         .first()
@@ -3069,11 +4200,22 @@ impl VM {
                                     return ExecResult::Continue;
                                 }
                                 _ => {
-                                    // Unsupported array method - pop args and return undefined
-                                    for _ in 0..arg_count {
-                                        self.stack.pop();
+                                    // Registered prototype extension, else pop args and return undefined
+                                    match self.call_registered_method(
+                                        BuiltinProto::Array,
+                                        &name,
+                                        JsValue::Object(ptr),
+                                        arg_count,
+                                    ) {
+                                        Ok(true) => {}
+                                        Ok(false) => {
+                                            for _ in 0..arg_count {
+                                                self.stack.pop();
+                                            }
+                                            self.stack.push(JsValue::Undefined);
+                                        }
+                                        Err(exception) => return self.throw_value(exception),
                                     }
-                                    self.stack.push(JsValue::Undefined);
                                     self.ip += 1;
                                     return ExecResult::Continue;
                                 }
@@ -3097,15 +4239,7 @@ impl VM {
                                     }
                                     let result = map
                                         .iter()
-                                        .find(|(k, _)| match (k, &key) {
-                                            (JsValue::Number(a), JsValue::Number(b)) => a == b,
-                                            (JsValue::String(a), JsValue::String(b)) => a == b,
-                                            (JsValue::Boolean(a), JsValue::Boolean(b)) => a == b,
-                                            (JsValue::Null, JsValue::Null) => true,
-                                            (JsValue::Undefined, JsValue::Undefined) => true,
-                                            (JsValue::Object(a), JsValue::Object(b)) => a == b,
-                                            _ => false,
-                                        })
+                                        .find(|(k, _)| k.same_value_zero(&key))
                                         .map(|(_, v)| v.clone())
                                         .unwrap_or(JsValue::Undefined);
                                     self.stack.push(result);
@@ -3113,25 +4247,14 @@ impl VM {
                                     return ExecResult::Continue;
                                 }
                                 "set" => {
-                                    let mut args = Vec::with_capacity(arg_count);
-                                    for _ in 0..arg_count {
-                                        args.push(self.stack.pop().expect("Missing argument"));
-                                    }
-                                    args.reverse();
                                     let key = args.first().cloned().unwrap_or(JsValue::Undefined);
                                     let value = args.get(1).cloned().unwrap_or(JsValue::Undefined);
 
-                                    // Remove existing key if present
-                                    map.retain(|(k, _)| match (k, &key) {
-                                        (JsValue::Number(a), JsValue::Number(b)) => a != b,
-                                        (JsValue::String(a), JsValue::String(b)) => a != b,
-                                        (JsValue::Boolean(a), JsValue::Boolean(b)) => a != b,
-                                        (JsValue::Null, JsValue::Null) => false,
-                                        (JsValue::Undefined, JsValue::Undefined) => false,
-                                        (JsValue::Object(a), JsValue::Object(b)) => a != b,
-                                        _ => true,
-                                    });
-                                    map.push((key, value));
+                                    // An existing key keeps its place in iteration order
+                                    match map.iter_mut().find(|(k, _)| k.same_value_zero(&key)) {
+                                        Some(entry) => entry.1 = value,
+                                        None => map.push((key.into_key(), value)),
+                                    }
                                     self.stack.push(JsValue::Object(ptr)); // Return the map itself
                                     self.ip += 1;
                                     return ExecResult::Continue;
@@ -3145,15 +4268,7 @@ impl VM {
                                     for _ in 1..arg_count {
                                         self.stack.pop();
                                     }
-                                    let found = map.iter().any(|(k, _)| match (k, &key) {
-                                        (JsValue::Number(a), JsValue::Number(b)) => a == b,
-                                        (JsValue::String(a), JsValue::String(b)) => a == b,
-                                        (JsValue::Boolean(a), JsValue::Boolean(b)) => a == b,
-                                        (JsValue::Null, JsValue::Null) => true,
-                                        (JsValue::Undefined, JsValue::Undefined) => true,
-                                        (JsValue::Object(a), JsValue::Object(b)) => a == b,
-                                        _ => false,
-                                    });
+                                    let found = map.iter().any(|(k, _)| k.same_value_zero(&key));
                                     self.stack.push(JsValue::Boolean(found));
                                     self.ip += 1;
                                     return ExecResult::Continue;
@@ -3168,15 +4283,7 @@ impl VM {
                                         self.stack.pop();
                                     }
                                     let initial_len = map.len();
-                                    map.retain(|(k, _)| match (k, &key) {
-                                        (JsValue::Number(a), JsValue::Number(b)) => a != b,
-                                        (JsValue::String(a), JsValue::String(b)) => a != b,
-                                        (JsValue::Boolean(a), JsValue::Boolean(b)) => a != b,
-                                        (JsValue::Null, JsValue::Null) => false,
-                                        (JsValue::Undefined, JsValue::Undefined) => false,
-                                        (JsValue::Object(a), JsValue::Object(b)) => a != b,
-                                        _ => true,
-                                    });
+                                    map.retain(|(k, _)| !k.same_value_zero(&key));
                                     self.stack.push(JsValue::Boolean(map.len() < initial_len));
                                     self.ip += 1;
                                     return ExecResult::Continue;
@@ -3199,10 +4306,21 @@ impl VM {
                                     return ExecResult::Continue;
                                 }
                                 _ => {
-                                    for _ in 0..arg_count {
-                                        self.stack.pop();
+                                    match self.call_registered_method(
+                                        BuiltinProto::Map,
+                                        &name,
+                                        JsValue::Object(ptr),
+                                        arg_count,
+                                    ) {
+                                        Ok(true) => {}
+                                        Ok(false) => {
+                                            for _ in 0..arg_count {
+                                                self.stack.pop();
+                                            }
+                                            self.stack.push(JsValue::Undefined);
+                                        }
+                                        Err(exception) => return self.throw_value(exception),
                                     }
-                                    self.stack.push(JsValue::Undefined);
                                     self.ip += 1;
                                     return ExecResult::Continue;
                                 }
@@ -3225,17 +4343,9 @@ impl VM {
                                         self.stack.pop();
                                     }
                                     // Check if value already exists
-                                    let exists = set.iter().any(|v| match (v, &value) {
-                                        (JsValue::Number(a), JsValue::Number(b)) => a == b,
-                                        (JsValue::String(a), JsValue::String(b)) => a == b,
-                                        (JsValue::Boolean(a), JsValue::Boolean(b)) => a == b,
-                                        (JsValue::Null, JsValue::Null) => true,
-                                        (JsValue::Undefined, JsValue::Undefined) => true,
-                                        (JsValue::Object(a), JsValue::Object(b)) => a == b,
-                                        _ => false,
-                                    });
+                                    let exists = set.iter().any(|v| v.same_value_zero(&value));
                                     if !exists {
-                                        set.push(value);
+                                        set.push(value.into_key());
                                     }
                                     self.stack.push(JsValue::Object(ptr)); // Return the set itself
                                     self.ip += 1;
@@ -3250,15 +4360,7 @@ impl VM {
                                     for _ in 1..arg_count {
                                         self.stack.pop();
                                     }
-                                    let found = set.iter().any(|v| match (v, &value) {
-                                        (JsValue::Number(a), JsValue::Number(b)) => a == b,
-                                        (JsValue::String(a), JsValue::String(b)) => a == b,
-                                        (JsValue::Boolean(a), JsValue::Boolean(b)) => a == b,
-                                        (JsValue::Null, JsValue::Null) => true,
-                                        (JsValue::Undefined, JsValue::Undefined) => true,
-                                        (JsValue::Object(a), JsValue::Object(b)) => a == b,
-                                        _ => false,
-                                    });
+                                    let found = set.iter().any(|v| v.same_value_zero(&value));
                                     self.stack.push(JsValue::Boolean(found));
                                     self.ip += 1;
                                     return ExecResult::Continue;
@@ -3273,15 +4375,7 @@ impl VM {
                                         self.stack.pop();
                                     }
                                     let initial_len = set.len();
-                                    set.retain(|v| match (v, &value) {
-                                        (JsValue::Number(a), JsValue::Number(b)) => a != b,
-                                        (JsValue::String(a), JsValue::String(b)) => a != b,
-                                        (JsValue::Boolean(a), JsValue::Boolean(b)) => a != b,
-                                        (JsValue::Null, JsValue::Null) => false,
-                                        (JsValue::Undefined, JsValue::Undefined) => false,
-                                        (JsValue::Object(a), JsValue::Object(b)) => a != b,
-                                        _ => true,
-                                    });
+                                    set.retain(|v| !v.same_value_zero(&value));
                                     self.stack.push(JsValue::Boolean(set.len() < initial_len));
                                     self.ip += 1;
                                     return ExecResult::Continue;
@@ -3304,33 +4398,157 @@ impl VM {
                                     return ExecResult::Continue;
                                 }
                                 _ => {
-                                    for _ in 0..arg_count {
-                                        self.stack.pop();
+                                    match self.call_registered_method(
+                                        BuiltinProto::Set,
+                                        &name,
+                                        JsValue::Object(ptr),
+                                        arg_count,
+                                    ) {
+                                        Ok(true) => {}
+                                        Ok(false) => {
+                                            for _ in 0..arg_count {
+                                                self.stack.pop();
+                                            }
+                                            self.stack.push(JsValue::Undefined);
+                                        }
+                                        Err(exception) => return self.throw_value(exception),
                                     }
-                                    self.stack.push(JsValue::Undefined);
                                     self.ip += 1;
                                     return ExecResult::Continue;
                                 }
                             }
                         }
 
+                        // Buffer methods (subarray, toString, ...)
+                        if let Some(HeapObject {
+                            data: HeapData::Buffer(view),
+                        }) = self.heap.get(ptr)
+                        {
+                            let view = view.clone();
+                            let args = self.pop_args(arg_count);
+                            let result =
+                                crate::stdlib::buffer::call_method(self, &view, &name, &args)
+                                    .unwrap_or(JsValue::Undefined);
+                            self.stack.push(result);
+                            self.ip += 1;
+                            return ExecResult::Continue;
+                        }
+
+                        // Decimal methods (plus, div, toFixed, ...)
+                        if let Some(HeapObject {
+                            data: HeapData::Decimal(value),
+                        }) = self.heap.get(ptr)
+                        {
+                            let value = value.clone();
+                            let args = self.pop_args(arg_count);
+                            let result =
+                                crate::stdlib::decimal::call_method(self, &value, &name, &args)
+                                    .unwrap_or(JsValue::Undefined);
+                            self.stack.push(result);
+                            self.ip += 1;
+                            return ExecResult::Continue;
+                        }
+
+                        // WeakMap, WeakSet and WeakRef methods (get, add, deref, ...)
+                        if matches!(
+                            self.heap.get(ptr).map(|obj| &obj.data),
+                            Some(
+                                HeapData::WeakMap(_) | HeapData::WeakSet(_) | HeapData::WeakRef(_)
+                            )
+                        ) {
+                            let args = self.pop_args(arg_count);
+                            let result = crate::stdlib::weak::call_method(self, ptr, &name, &args)
+                                .unwrap_or(JsValue::Undefined);
+                            self.stack.push(result);
+                            self.ip += 1;
+                            return ExecResult::Continue;
+                        }
+
+                        // Stream methods (read, on, write, pipeTo, ...)
+                        if matches!(
+                            self.heap.get(ptr).map(|obj| &obj.data),
+                            Some(HeapData::Stream(_))
+                        ) {
+                            let args = self.pop_args(arg_count);
+                            let result =
+                                crate::stdlib::stream::call_method(self, ptr, &name, &args)
+                                    .unwrap_or(JsValue::Undefined);
+                            self.stack.push(result);
+                            self.ip += 1;
+                            return ExecResult::Continue;
+                        }
+
+                        // Child process methods (wait, kill, on)
+                        if matches!(
+                            self.heap.get(ptr).map(|obj| &obj.data),
+                            Some(HeapData::Process(_))
+                        ) {
+                            let args = self.pop_args(arg_count);
+                            let result =
+                                crate::stdlib::child_process::call_method(self, ptr, &name, &args)
+                                    .unwrap_or(JsValue::Undefined);
+                            self.stack.push(result);
+                            self.ip += 1;
+                            return ExecResult::Continue;
+                        }
+
+                        // `import.meta.hot` methods act on the module it belongs to
+                        if hot::METHODS.contains(&name.as_str())
+                            && let Some(module) =
+                                self.hot.as_ref().and_then(|hot| hot.module_of(ptr))
+                        {
+                            let args = self.pop_args(arg_count);
+                            hot::call_method(self, &module, &name, args);
+                            self.stack.push(JsValue::Undefined);
+                            self.ip += 1;
+                            return ExecResult::Continue;
+                        }
+
                         // Lookup the method in the object through prototype chain
                         let method = self.get_prop_with_proto_chain(ptr, &name);
 
-                        if let JsValue::NativeFunction(idx) = method {
-                            // For native functions, call directly
-                            let mut args = Vec::with_capacity(arg_count);
+                        // Objects without their own `clone` get a deep copy
+                        if name == "clone" && matches!(method, JsValue::Undefined) {
                             for _ in 0..arg_count {
-                                args.push(self.stack.pop().expect("Missing argument"));
+                                self.stack.pop();
                             }
-                            args.reverse();
-                            let func = self.native_functions[idx];
-                            let result = func(self, args);
+                            let copy =
+                                crate::stdlib::native_clone(self, vec![JsValue::Object(ptr)]);
+                            self.stack.push(copy);
+                            self.ip += 1;
+                            return ExecResult::Continue;
+                        }
+
+                        // ... and `hasOwnProperty`, which Object.prototype would give them
+                        if name == "hasOwnProperty" && matches!(method, JsValue::Undefined) {
+                            let args = self.pop_args(arg_count);
+                            let key = args.into_iter().next().unwrap_or(JsValue::Undefined);
+                            let has = crate::stdlib::native_object_has_own(
+                                self,
+                                vec![JsValue::Object(ptr), key],
+                            );
+                            self.stack.push(has);
+                            self.ip += 1;
+                            return ExecResult::Continue;
+                        }
+
+                        if let JsValue::NativeFunction(idx) = method {
+                            // For native functions, call directly
+                            let args = self.pop_args(arg_count);
+                            let result = match self.call_native(idx, args) {
+                                Ok(result) => result,
+                                Err(exception) => return self.throw_value(exception),
+                            };
                             self.stack.push(result);
                             // Increment IP before returning since we return early
                             self.ip += 1;
                             return ExecResult::Continue;
-                        } else if let JsValue::Function { address, env } = method {
+                        } else if let JsValue::Function {
+                            address,
+                            env,
+                            bound,
+                        } = method
+                        {
                             // Stack overflow protection
                             if self.call_stack.len() >= MAX_CALL_STACK_DEPTH {
                                 panic!(
@@ -3340,41 +4558,31 @@ impl VM {
                             }
 
                             // Collect arguments
-                            let mut args = Vec::with_capacity(arg_count);
-                            for _ in 0..arg_count {
-                                args.push(self.stack.pop().expect("Missing argument"));
-                            }
-                            args.reverse();
-
-                            // Push arguments in call order
-                            for arg in &args {
-                                self.stack.push(arg.clone());
-                            }
+                            let args = self.pop_args(arg_count);
 
                             // Create new frame with `this` bound to the receiver object
-                            let mut frame = Frame {
-                                return_address: self.ip + 1,
-                                locals: HashMap::new(),
-                                indexed_locals: Vec::new(),
-                                this_context: JsValue::Object(ptr),
-                                new_target: None,
-                                super_called: false,
-                                resume_ip: None,
-                            };
-
-                            // Load captured variables from environment
-                            if let Some(HeapObject {
-                                data: HeapData::Object(props),
-                            }) = env.and_then(|ptr| self.heap.get(ptr))
-                            {
-                                for (name, value) in props {
-                                    frame.locals.insert(name.clone(), value.clone());
-                                }
-                            }
-
-                            self.call_stack.push(frame);
-                            self.ip = address;
+                            self.enter_function(
+                                (address, env, bound),
+                                JsValue::Object(ptr),
+                                args,
+                                self.ip + 1,
+                            );
                             return ExecResult::ContinueNoIpInc;
+                        } else if let JsValue::Object(callable) = method
+                            && let Some(HeapObject {
+                                data: HeapData::Object(props),
+                            }) = self.heap.get(callable)
+                            && let Some(&JsValue::NativeFunction(idx)) = props.get("__call__")
+                        {
+                            // Callable objects such as `std.simd.f64x2(1, 2)`
+                            let args = self.pop_args(arg_count);
+                            let result = match self.call_native(idx, args) {
+                                Ok(result) => result,
+                                Err(exception) => return self.throw_value(exception),
+                            };
+                            self.stack.push(result);
+                            self.ip += 1;
+                            return ExecResult::Continue;
                         }
                         panic!("Method {} not found on object", name);
                     }
@@ -3404,8 +4612,109 @@ impl VM {
                             }
                         }
                     }
-                    _ => {
-                        self.stack.push(JsValue::Undefined);
+                    // fn.call(thisArg, ...args), fn.apply(thisArg, args), fn.bind(thisArg, ...args)
+                    JsValue::Function {
+                        address,
+                        env,
+                        bound,
+                    } if matches!(name.as_str(), "call" | "apply" | "bind") => {
+                        let mut args = self.pop_args(arg_count);
+                        let this_arg = if args.is_empty() {
+                            JsValue::Undefined
+                        } else {
+                            args.remove(0)
+                        };
+
+                        match name.as_str() {
+                            "bind" => {
+                                // Binding a bound function keeps its `this` and
+                                // appends to its arguments
+                                let items = match self.bound_this_and_args(bound) {
+                                    Some((bound_this, mut bound_args)) => {
+                                        bound_args.extend(args);
+                                        std::iter::once(bound_this).chain(bound_args).collect()
+                                    }
+                                    None => std::iter::once(this_arg).chain(args).collect(),
+                                };
+                                let bound_ptr = self.heap.len();
+                                self.heap.push(HeapObject {
+                                    data: HeapData::Array(items),
+                                });
+                                self.stack.push(JsValue::Function {
+                                    address,
+                                    env,
+                                    bound: Some(bound_ptr),
+                                });
+                                self.ip += 1;
+                                return ExecResult::Continue;
+                            }
+                            _ => {
+                                let args = if name == "apply" {
+                                    self.apply_arguments(args.first())
+                                } else {
+                                    args
+                                };
+                                self.record_function_call(address);
+                                self.enter_function(
+                                    (address, env, bound),
+                                    this_arg,
+                                    args,
+                                    self.ip + 1,
+                                );
+                                return ExecResult::ContinueNoIpInc;
+                            }
+                        }
+                    }
+                    // Natives ignore `this`, so binding one only makes sense
+                    // without extra arguments
+                    JsValue::NativeFunction(idx)
+                        if matches!(name.as_str(), "call" | "apply" | "bind") =>
+                    {
+                        let mut args = self.pop_args(arg_count);
+                        let rest = args.split_off(args.len().min(1));
+
+                        let result = match name.as_str() {
+                            "bind" => {
+                                if !rest.is_empty() {
+                                    eprintln!(
+                                        "bind: bound arguments are not supported for native functions"
+                                    );
+                                }
+                                JsValue::NativeFunction(idx)
+                            }
+                            _ => {
+                                let args = if name == "apply" {
+                                    self.apply_arguments(rest.first())
+                                } else {
+                                    rest
+                                };
+                                match self.call_native(idx, args) {
+                                    Ok(result) => result,
+                                    Err(exception) => return self.throw_value(exception),
+                                }
+                            }
+                        };
+                        self.stack.push(result);
+                        self.ip += 1;
+                        return ExecResult::Continue;
+                    }
+                    other => {
+                        // Primitive receivers (numbers, booleans) only have registered methods
+                        let handled = match BuiltinProto::of(&other, &self.heap) {
+                            Some(proto) => {
+                                match self.call_registered_method(proto, &name, other, arg_count) {
+                                    Ok(handled) => handled,
+                                    Err(exception) => return self.throw_value(exception),
+                                }
+                            }
+                            None => false,
+                        };
+                        if !handled {
+                            for _ in 0..arg_count {
+                                self.stack.pop();
+                            }
+                            self.stack.push(JsValue::Undefined);
+                        }
                         self.ip += 1;
                         return ExecResult::Continue;
                     }
@@ -3452,47 +4761,8 @@ impl VM {
             }
 
             OpCode::Throw => {
-                // Pop the exception value
                 let exception = self.stack.pop().unwrap_or(JsValue::Undefined);
-
-                // Find a handler
-                if let Some(handler) = self.exception_handlers.pop() {
-                    // Unwind the stack to the handler's saved state
-                    self.stack.truncate(handler.stack_depth);
-
-                    // Unwind call stack if needed
-                    while self.call_stack.len() > handler.call_stack_depth {
-                        self.call_stack.pop();
-                    }
-
-                    if handler.catch_addr != 0 {
-                        // We have a catch block - push exception and jump there
-                        self.stack.push(exception);
-                        self.ip = handler.catch_addr;
-
-                        // If there's a finally, we need to remember to run it
-                        // after the catch completes
-                        if handler.finally_addr != 0 {
-                            // Re-push a handler for finally (catch_addr=0 means no catch, just finally)
-                            self.exception_handlers.push(ExceptionHandler {
-                                catch_addr: 0,
-                                finally_addr: handler.finally_addr,
-                                stack_depth: self.stack.len() - 1, // Exclude the exception value
-                                call_stack_depth: handler.call_stack_depth,
-                            });
-                        }
-                        return ExecResult::ContinueNoIpInc;
-                    } else if handler.finally_addr != 0 {
-                        // No catch, but there's a finally block
-                        // Store exception for rethrow after finally
-                        self.current_exception = Some(exception);
-                        self.ip = handler.finally_addr;
-                        return ExecResult::ContinueNoIpInc;
-                    }
-                }
-
-                // No handler found - panic with uncaught exception
-                panic!("Uncaught exception: {:?}", exception);
+                return self.throw_value(exception);
             }
 
             OpCode::EnterFinally(rethrow) => {
@@ -3567,10 +4837,7 @@ impl VM {
                     .stack
                     .pop()
                     .expect("CallSuper: missing super constructor");
-                let mut args = Vec::with_capacity(arg_count);
-                for _ in 0..arg_count {
-                    args.push(self.stack.pop().expect("CallSuper: missing argument"));
-                }
+                let args = self.pop_args(arg_count);
 
                 // Get the actual constructor function
                 let ctor_fn = match super_ctor {
@@ -3595,23 +4862,23 @@ impl VM {
                     ),
                 };
 
-                if let JsValue::Function { address, env } = ctor_fn {
+                if let JsValue::Function { address, env, .. } = ctor_fn {
                     // Get current this context
                     let this_context = self.call_stack.last().unwrap().this_context.clone();
 
-                    args.reverse();
-                    for arg in &args {
-                        self.stack.push(arg.clone());
-                    }
-
                     let mut frame = Frame {
                         return_address: self.ip + 1,
                         locals: HashMap::new(),
                         indexed_locals: Vec::new(),
+                        args,
                         this_context,
                         new_target: None,
                         super_called: false,
                         resume_ip: None,
+                        function: Some(address),
+                        discard_result: false,
+                        module: self.module_env_at(address),
+                        construct_base: None,
                     };
 
                     // Load captured variables from closure environment
@@ -3696,111 +4963,66 @@ impl VM {
             OpCode::GetPrivateProp(field_index) => {
                 // Stack: [this] -> pops this, looks up private field, pushes value
                 let this_val = self.stack.pop().expect("GetPrivateProp: missing this");
-
-                let field_value = match &this_val {
-                    JsValue::Object(this_ptr) => {
-                        // Get the private field storage from the instance
-                        // We store "__private_storage__" on each instance pointing to the class's storage
-                        let private_storage_ptr = if let Some(HeapObject {
-                            data: HeapData::Object(props),
-                        }) = self.heap.get(*this_ptr)
-                        {
-                            props.get("__private_storage__").cloned()
-                        } else {
-                            None
-                        };
-
-                        // Look up the private field in the class's private storage
-                        if let Some(JsValue::Object(storage_ptr)) = private_storage_ptr {
-                            if let Some(HeapObject {
-                                data: HeapData::Array(field_map),
-                            }) = self.heap.get(storage_ptr)
-                            {
-                                // Each entry is a WeakMap for one private field
-                                if field_index >= field_map.len() {
-                                    JsValue::Undefined
-                                } else if let Some(JsValue::Object(weakmap_ptr)) =
-                                    field_map.get(field_index)
-                                {
-                                    // Look up this instance in the WeakMap
-                                    // For simplicity, we use a regular Map since Rust's
-                                    // WeakMap equivalent isn't available in our VM
-                                    if let Some(HeapObject {
-                                        data: HeapData::Object(field_map),
-                                    }) = self.heap.get(*weakmap_ptr)
-                                    {
-                                        let key = this_ptr.to_string();
-                                        field_map.get(&key).cloned().unwrap_or(JsValue::Undefined)
-                                    } else {
-                                        JsValue::Undefined
-                                    }
-                                } else {
-                                    JsValue::Undefined
-                                }
-                            } else {
-                                JsValue::Undefined
-                            }
-                        } else {
-                            JsValue::Undefined
-                        }
-                    }
+                let field_value = match this_val {
+                    // Each slot is a WeakMap keyed by the instance
+                    JsValue::Object(this_ptr) => self
+                        .private_value(this_ptr, field_index)
+                        .unwrap_or(JsValue::Undefined),
                     _ => JsValue::Undefined,
                 };
-
                 self.stack.push(field_value);
             }
 
             OpCode::SetPrivateProp(field_index) => {
-                // Stack: [value, this] -> pops both, sets private field
+                // Stack: [this, value] -> pops both, sets private field
                 let value = self.stack.pop().expect("SetPrivateProp: missing value");
                 let this_val = self.stack.pop().expect("SetPrivateProp: missing this");
 
-                if let JsValue::Object(this_ptr) = this_val {
-                    // Get the private field storage info first (before any mutable borrows)
-                    let weakmap_ptr = {
-                        // Get the private field storage from the instance
-                        let private_storage_ptr = if let Some(HeapObject {
-                            data: HeapData::Object(props),
-                        }) = self.heap.get(this_ptr)
-                        {
-                            props.get("__private_storage__").cloned()
-                        } else {
-                            None
-                        };
+                if let JsValue::Object(this_ptr) = this_val
+                    && let Some(slot_ptr) = self.private_slot(this_ptr, field_index)
+                    && let Some(heap_item) = self.heap.get_mut(slot_ptr)
+                    && let HeapData::WeakMap(field_map) = &mut heap_item.data
+                {
+                    field_map.insert(this_ptr, value);
+                }
+            }
 
-                        if let Some(JsValue::Object(storage_ptr)) = private_storage_ptr {
-                            if let Some(HeapObject {
-                                data: HeapData::Array(field_map),
-                            }) = self.heap.get(storage_ptr)
-                            {
-                                if field_index < field_map.len() {
-                                    if let Some(JsValue::Object(w_ptr)) = field_map.get(field_index)
-                                    {
-                                        Some(*w_ptr)
-                                    } else {
-                                        None
-                                    }
-                                } else {
-                                    None
-                                }
-                            } else {
-                                None
-                            }
-                        } else {
-                            None
-                        }
-                    };
+            OpCode::CallPrivateMethod(slot, arg_count) => {
+                // Stack: [args..., obj] -> calls obj's private method with this = obj
+                let receiver = self
+                    .stack
+                    .pop()
+                    .expect("CallPrivateMethod: missing receiver");
 
-                    // Now do the mutable operation
-                    if let Some(w_ptr) = weakmap_ptr {
-                        let key = this_ptr.to_string();
-                        if let Some(heap_item) = self.heap.get_mut(w_ptr)
-                            && let HeapData::Object(field_map) = &mut heap_item.data
-                        {
-                            field_map.insert(key, value);
-                        }
-                    }
-                }
+                let method = match receiver {
+                    JsValue::Object(this_ptr) => self.private_value(this_ptr, slot),
+                    _ => None,
+                };
+                let Some(JsValue::Function {
+                    address,
+                    env,
+                    bound,
+                }) = method
+                else {
+                    return self.throw_value(JsValue::String(
+                        "TypeError: Receiver must be an instance of the class declaring this private member"
+                            .to_string(),
+                    ));
+                };
+
+                let args = self.pop_args(arg_count);
+                self.enter_function((address, env, bound), receiver, args, self.ip + 1);
+                return ExecResult::ContinueNoIpInc;
+            }
+
+            OpCode::HasPrivateProp(slot) => {
+                // Stack: [obj] -> pops obj, pushes whether it carries the private slot
+                let obj = self.stack.pop().expect("HasPrivateProp: missing object");
+                let has = match obj {
+                    JsValue::Object(this_ptr) => self.private_value(this_ptr, slot).is_some(),
+                    _ => false,
+                };
+                self.stack.push(JsValue::Boolean(has));
             }
 
             OpCode::InstanceOf => {
@@ -3869,6 +5091,29 @@ impl VM {
                 self.stack.push(JsValue::Boolean(result));
             }
 
+            OpCode::In => {
+                // Stack: [key, object] -> pops both, pushes boolean
+                let obj = self.stack.pop().unwrap_or(JsValue::Undefined);
+                let key = property::property_key(&self.stack.pop().unwrap_or(JsValue::Undefined));
+                let result = match obj {
+                    JsValue::Object(ptr) => property::has_property(self, ptr, &key),
+                    JsValue::String(_)
+                    | JsValue::Number(_)
+                    | JsValue::Boolean(_)
+                    | JsValue::Null
+                    | JsValue::Undefined => {
+                        return self.throw_value(JsValue::String(format!(
+                            "TypeError: Cannot use 'in' operator to search for '{}' in {}",
+                            key,
+                            property::property_key(&obj)
+                        )));
+                    }
+                    // Functions and promises have no own properties the VM tracks
+                    _ => false,
+                };
+                self.stack.push(JsValue::Boolean(result));
+            }
+
             OpCode::NewTarget => {
                 // Push the new.target value from the current frame
                 let new_target = self
@@ -3889,24 +5134,22 @@ impl VM {
                 let target = self.stack.pop().expect("ApplyDecorator: missing target");
 
                 match decorator {
-                    JsValue::Function { address, env } => {
-                        // Clone target for use in the frame
-                        let target_for_frame = target.clone();
-
-                        // Call the decorator function with the target
-                        // Stack should be: [decorator, target] -> Call(1) pops target as arg, decorator as callee
-                        self.stack.push(decorator);
-                        self.stack.push(target);
-
-                        // Create a frame for the decorator call
+                    JsValue::Function { address, env, .. } => {
+                        // Call the decorator function with the target as its
+                        // only argument
                         let mut frame = Frame {
                             return_address: self.ip + 1,
                             locals: HashMap::new(),
                             indexed_locals: Vec::new(),
-                            this_context: target_for_frame.clone(),
-                            new_target: Some(target_for_frame),
+                            args: vec![target.clone()],
+                            this_context: target.clone(),
+                            new_target: Some(target),
                             super_called: false,
                             resume_ip: None,
+                            function: Some(address),
+                            discard_result: false,
+                            module: self.module_env_at(address),
+                            construct_base: None,
                         };
 
                         // Load captured variables from environment
@@ -3930,94 +5173,64 @@ impl VM {
                 }
             }
 
-            OpCode::ImportAsync(_specifier) => {
+            OpCode::ImportAsync { json, .. } => {
                 let specifier_str = match self.stack.pop() {
                     Some(JsValue::String(s)) => s,
-                    Some(_) => {
-                        self.stack.push(JsValue::Undefined);
-                        return ExecResult::Continue;
-                    }
-                    None => {
+                    _ => {
                         self.stack.push(JsValue::Undefined);
+                        self.ip += 1;
                         return ExecResult::Continue;
                     }
                 };
 
-                let importer_path = self.current_module_path.clone();
-
-                let resolved_path = {
-                    let importer_dir = importer_path
-                        .as_ref()
-                        .and_then(|p| {
-                            if p.is_file() {
-                                p.parent()
-                            } else if p.exists() && p.is_dir() {
-                                Some(p)
-                            } else {
-                                Some(p)
-                            }
-                        })
-                        .map(|p| {
-                            if p.as_os_str().is_empty() {
-                                Path::new(".")
-                            } else {
-                                p
-                            }
-                        })
-                        .unwrap_or(Path::new("."));
+                // Builtin modules (`fs`, `util`) shadow files of the same name
+                let builtin = specifier_str
+                    .strip_prefix("node:")
+                    .unwrap_or(&specifier_str);
+                if let Some(module) = self.modules.get(builtin) {
+                    self.stack.push(module.clone());
+                    self.ip += 1;
+                    return ExecResult::Continue;
+                }
 
-                    let mut resolved = importer_dir.to_path_buf();
+                let importer_path = self.current_module_path.clone();
 
-                    for component in specifier_str.split('/') {
-                        match component {
-                            "." => {}
-                            ".." => {
-                                if !resolved.as_os_str().is_empty() {
-                                    resolved.pop();
-                                }
-                            }
-                            "" if specifier_str.starts_with("./") => {}
-                            "" if specifier_str.starts_with("../") => {}
-                            _ => resolved.push(component),
-                        };
-                    }
+                // A bundle's own modules come from the executable, not the disk
+                let bundled = self
+                    .bundled
+                    .as_ref()
+                    .and_then(|bundled| bundled.resolve(importer_path.as_deref(), &specifier_str));
+                let canonical_path = match bundled {
+                    Some(path) => path,
+                    None => {
+                        let resolved_path =
+                            resolve_import(importer_path.as_deref(), &specifier_str);
 
-                    let extensions = ["ot", "ts", "js"];
-                    if resolved.as_os_str().is_empty() || specifier_str.ends_with('/') {
-                        for ext in &extensions {
-                            let index_path = resolved.join("index").with_extension(ext);
-                            if index_path.exists() {
-                                resolved = index_path;
-                                break;
-                            }
+                        if !resolved_path.exists() {
+                            eprintln!("Error: Module not found: {}", specifier_str);
+                            self.stack.push(JsValue::Undefined);
+                            self.ip += 1;
+                            return ExecResult::Continue;
                         }
-                    } else if !resolved.exists() {
-                        for ext in &extensions {
-                            let with_ext = resolved.with_extension(ext);
-                            if with_ext.exists() {
-                                resolved = with_ext;
-                                break;
+
+                        // Symlinks and case-only differences resolve to the same module
+                        match crate::platform::canonicalize(&resolved_path) {
+                            Ok(p) => self.module_cache.identity_path(&p),
+                            Err(e) => {
+                                eprintln!("Error canonicalizing path: {}", e);
+                                self.stack.push(JsValue::Undefined);
+                                self.ip += 1;
+                                return ExecResult::Continue;
                             }
                         }
                     }
-                    resolved
                 };
 
-                if !resolved_path.exists() {
-                    eprintln!("Error: Module not found: {}", specifier_str);
-                    self.stack.push(JsValue::Undefined);
-                    return ExecResult::Continue;
+                if let Some(hot) = self.hot.as_mut() {
+                    hot.imported(importer_path.as_deref(), &canonical_path);
                 }
 
-                // Check cache first
-                let canonical_path = match fs::canonicalize(&resolved_path) {
-                    Ok(p) => p,
-                    Err(e) => {
-                        eprintln!("Error canonicalizing path: {}", e);
-                        self.stack.push(JsValue::Undefined);
-                        return ExecResult::Continue;
-                    }
-                };
+                self.register_entry_module();
 
                 // Check if we have a valid cached version
                 if let Some(cached) = self.module_cache.get(&canonical_path) {
@@ -4025,50 +5238,36 @@ impl VM {
                     self.stack.push(JsValue::Object(cached.namespace_object));
                     // Fall through to ip += 1 at end of exec_one
                 } else {
-                    // Cache miss - load the module
-                    let result = fs::read_to_string(&canonical_path)
-                        .map_err(|e| format!("Failed to read module: {}", e));
+                    // Cache miss - load the module (bundled sources are
+                    // already transformed)
+                    let bundled_source = self
+                        .bundled
+                        .as_ref()
+                        .and_then(|bundled| bundled.source(&canonical_path));
+                    let result = match bundled_source {
+                        Some(source) => Ok(source.to_string()),
+                        None => fs::read_to_string(&canonical_path)
+                            .map_err(|e| format!("Failed to read module: {}", e))
+                            .and_then(|source| {
+                                crate::transform::transform_import(&canonical_path, source, json)
+                            }),
+                    };
 
                     match result {
                         Ok(source) => {
-                            let hash = ModuleCache::compute_hash(&canonical_path);
-                            let exports =
-                                parse_module_exports(&source, &canonical_path.to_string_lossy());
-                            let export_names: Vec<String> = exports.keys().cloned().collect();
-                            let mut namespace_props = HashMap::new();
-                            namespace_props.insert(
-                                "__path__".to_string(),
-                                JsValue::String(canonical_path.to_string_lossy().into_owned()),
-                            );
-                            namespace_props
-                                .insert("__source__".to_string(), JsValue::String(source.clone()));
-                            namespace_props
-                                .insert("__hash__".to_string(), JsValue::String(hash.clone()));
-                            match self.execute_module(&source, &canonical_path, &export_names) {
-                                Ok(module_exports) => {
-                                    for (name, value) in module_exports {
-                                        namespace_props.insert(name, value);
-                                    }
-                                }
-                                Err(e) => {
-                                    eprintln!("Error executing module '{}': {}", specifier_str, e);
-                                    for name in &export_names {
-                                        namespace_props.insert(name.clone(), JsValue::Undefined);
-                                    }
-                                }
+                            // Cached before it runs: an import that cycles back
+                            // here gets the namespace as far as it is filled in
+                            // instead of running the module again
+                            let (namespace_ptr, exports) =
+                                self.cache_module_namespace(&canonical_path, &source);
+                            if let Err(e) = self.execute_module(
+                                &source,
+                                &canonical_path,
+                                namespace_ptr,
+                                &exports,
+                            ) {
+                                eprintln!("Error executing module '{}': {}", specifier_str, e);
                             }
-                            let namespace_ptr = self.heap.len();
-                            self.heap.push(HeapObject {
-                                data: HeapData::Object(namespace_props),
-                            });
-                            let cached_module = CachedModule {
-                                path: canonical_path.clone(),
-                                source,
-                                hash,
-                                load_time: std::time::SystemTime::now(),
-                                namespace_object: namespace_ptr,
-                            };
-                            self.module_cache.insert(cached_module);
                             self.stack.push(JsValue::Object(namespace_ptr));
                         }
                         Err(e) => {
@@ -4112,7 +5311,9 @@ impl VM {
                     }
                     PromiseState::Pending => {
                         eprintln!("DEBUG Await: still pending, polling...");
-                        // Poll until resolved (with timeout)
+                        // Keep the event loop turning while the promise can
+                        // still settle from it, then poll (with timeout)
+                        self.run_loop_until_settled(&promise);
                         let result = self.poll_promise(&promise, 1000);
                         eprintln!("DEBUG Await: poll result = {:?}", result);
                         self.stack.push(result);
@@ -4124,30 +5325,70 @@ impl VM {
                 name,
                 is_default: _,
             } => {
-                let namespace = match self.stack.pop() {
-                    Some(JsValue::Object(ptr)) => {
-                        if let Some(HeapObject {
+                let export_value = match self.stack.pop() {
+                    Some(JsValue::Object(ptr)) => match self.heap.get(ptr) {
+                        Some(HeapObject {
                             data: HeapData::Object(props),
-                            ..
-                        }) = self.heap.get(ptr)
-                        {
-                            props.clone()
-                        } else {
-                            HashMap::new()
+                        }) => props.get(&name).cloned(),
+                        _ => None,
+                    },
+                    _ => None,
+                };
+                self.stack.push(export_value.unwrap_or(JsValue::Undefined));
+            }
+
+            OpCode::ReExport { name, exported } => {
+                // Outside a module (the entry script) there is nowhere to export to
+                if let Some(JsValue::Object(from)) = self.stack.pop()
+                    && let Some(to) = self.module_namespace()
+                {
+                    self.reexport(from, name, to, exported);
+                }
+            }
+
+            OpCode::ReExportAll => {
+                if let Some(JsValue::Object(from)) = self.stack.pop()
+                    && let Some(to) = self.module_namespace()
+                {
+                    // `default` is never passed on, and a module's own
+                    // exports (already in its namespace) win
+                    let exported = |ptr: usize| -> HashSet<String> {
+                        match self.heap.get(ptr) {
+                            Some(HeapObject {
+                                data: HeapData::Object(props),
+                            }) => props
+                                .keys()
+                                .filter(|name| {
+                                    *name != "default" && !property::is_internal_key(name)
+                                })
+                                .cloned()
+                                .collect(),
+                            _ => HashSet::new(),
                         }
+                    };
+                    let own = exported(to);
+                    let mut names: Vec<String> = exported(from).difference(&own).cloned().collect();
+                    names.sort();
+                    for name in names {
+                        self.reexport(from, name.clone(), to, name);
                     }
-                    Some(_) => {
-                        self.stack.push(JsValue::Undefined);
-                        return ExecResult::Continue;
-                    }
-                    None => {
-                        self.stack.push(JsValue::Undefined);
-                        return ExecResult::Continue;
-                    }
-                };
+                }
+            }
+
+            OpCode::ImportMeta => {
+                let meta = self.import_meta();
+                self.stack.push(JsValue::Object(meta));
+            }
 
-                let export_value = namespace.get(&name).cloned().unwrap_or(JsValue::Undefined);
-                self.stack.push(export_value);
+            OpCode::ImportBinding { name, local } => {
+                // A module that failed to load leaves the name undefined
+                if let Some(JsValue::Object(namespace)) = self.stack.pop() {
+                    let scope = self.call_stack.last().and_then(|frame| frame.module);
+                    self.imported_bindings
+                        .entry(scope)
+                        .or_default()
+                        .insert(local, ImportedBinding { namespace, name });
+                }
             }
 
             OpCode::ModuleResolutionError {
@@ -1,9 +1,42 @@
 use sha2::{Digest, Sha256};
 use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::time::SystemTime;
 
+/// Identity of a module file, independent of the path used to reach it.
+///
+/// Two imports name the same module if they reach the same file, whether
+/// through a symlink or (on case-insensitive file systems) a path that
+/// differs only by case. On Unix this is the device and inode number;
+/// elsewhere it is the canonical path, case-folded on Windows.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ModuleId {
+    Inode { dev: u64, ino: u64 },
+    Path(PathBuf),
+}
+
+impl ModuleId {
+    pub fn of(path: &Path) -> Self {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            if let Ok(metadata) = fs::metadata(path) {
+                return ModuleId::Inode {
+                    dev: metadata.dev(),
+                    ino: metadata.ino(),
+                };
+            }
+        }
+        let canonical = crate::platform::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        if cfg!(windows) {
+            ModuleId::Path(PathBuf::from(canonical.to_string_lossy().to_lowercase()))
+        } else {
+            ModuleId::Path(canonical)
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct CachedModule {
     pub path: PathBuf,
@@ -17,6 +50,8 @@ pub struct ModuleCache {
     pub entries: HashMap<PathBuf, CachedModule>,
     content_hashes: HashMap<PathBuf, String>,
     modification_times: HashMap<PathBuf, SystemTime>,
+    /// First path each module file was seen under; later aliases map to it
+    identities: HashMap<ModuleId, PathBuf>,
 }
 
 impl Default for ModuleCache {
@@ -31,10 +66,32 @@ impl ModuleCache {
             entries: HashMap::new(),
             content_hashes: HashMap::new(),
             modification_times: HashMap::new(),
+            identities: HashMap::new(),
+        }
+    }
+
+    /// The path under which the module at `path` is (or will be) cached.
+    /// Every alias of a file resolves to the first path it was imported by,
+    /// so a module reached through a symlink or a differently-cased path is
+    /// executed once and shares its state.
+    pub fn identity_path(&mut self, path: &Path) -> PathBuf {
+        let canonical = crate::platform::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        self.identities
+            .entry(ModuleId::of(&canonical))
+            .or_insert(canonical)
+            .clone()
+    }
+
+    /// Cache key for `path`: its registered identity path if it has one
+    fn key<'a>(&'a self, path: &'a PathBuf) -> &'a PathBuf {
+        if self.entries.contains_key(path) {
+            return path;
         }
+        self.identities.get(&ModuleId::of(path)).unwrap_or(path)
     }
 
     pub fn get(&self, path: &PathBuf) -> Option<&CachedModule> {
+        let path = self.key(path);
         if let Some(cached) = self.entries.get(path) {
             let current_hash = ModuleCache::compute_hash(path);
             if cached.hash == current_hash {
@@ -45,6 +102,7 @@ impl ModuleCache {
     }
 
     pub fn get_valid(&self, path: &PathBuf) -> Option<&CachedModule> {
+        let path = self.key(path);
         if let Some(cached) = self.entries.get(path)
             && let Ok(metadata) = fs::metadata(path)
             && let Ok(modified) = metadata.modified()
@@ -99,7 +157,8 @@ impl ModuleCache {
         }
     }
 
-    pub fn insert(&mut self, module: CachedModule) {
+    pub fn insert(&mut self, mut module: CachedModule) {
+        module.path = self.identity_path(&module.path);
         let path = module.path.clone();
         let hash = module.hash.clone();
 
@@ -113,6 +172,7 @@ impl ModuleCache {
     }
 
     pub fn invalidate(&mut self, path: &PathBuf) {
+        let path = &self.key(path).clone();
         self.entries.remove(path);
         self.content_hashes.remove(path);
         self.modification_times.remove(path);
@@ -122,6 +182,7 @@ impl ModuleCache {
         self.entries.clear();
         self.content_hashes.clear();
         self.modification_times.clear();
+        self.identities.clear();
     }
 
     pub fn len(&self) -> usize {
@@ -140,7 +201,7 @@ impl ModuleCache {
     }
 
     pub fn get_cache_info(&self, path: &PathBuf) -> Option<(SystemTime, String, usize)> {
-        self.entries.get(path).map(|cached| {
+        self.entries.get(self.key(path)).map(|cached| {
             (
                 cached.load_time,
                 cached.hash.clone(),
@@ -166,3 +227,60 @@ impl ModuleCache {
         self.content_hashes.contains_key(path)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cached(path: PathBuf) -> CachedModule {
+        CachedModule {
+            hash: ModuleCache::compute_hash(&path),
+            path,
+            source: String::new(),
+            load_time: SystemTime::now(),
+            namespace_object: 7,
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlinked_module_shares_cache_entry() {
+        let dir = std::env::temp_dir().join(format!("oite_module_id_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let real = dir.join("counter.ot");
+        let link = dir.join("alias.ot");
+        fs::write(&real, "export const n = 1;").unwrap();
+        let _ = fs::remove_file(&link);
+        std::os::unix::fs::symlink(&real, &link).unwrap();
+
+        let mut cache = ModuleCache::new();
+        let key = cache.identity_path(&real);
+        cache.insert(cached(key.clone()));
+
+        assert_eq!(cache.identity_path(&link), key);
+        assert_eq!(cache.get(&link).map(|m| m.namespace_object), Some(7));
+        assert_eq!(cache.len(), 1);
+
+        cache.invalidate(&link);
+        assert!(cache.is_empty());
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_distinct_files_keep_distinct_identities() {
+        let dir = std::env::temp_dir().join(format!("oite_module_id2_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.ot"), "").unwrap();
+        fs::write(dir.join("b.ot"), "").unwrap();
+
+        assert_ne!(
+            ModuleId::of(&dir.join("a.ot")),
+            ModuleId::of(&dir.join("b.ot"))
+        );
+        assert_eq!(
+            ModuleId::of(&dir.join("a.ot")),
+            ModuleId::of(&dir.join(".").join("a.ot"))
+        );
+        fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -1,5 +1,38 @@
 use crate::vm::value::JsValue;
 
+/// Stdlib globals the compiler resolves to fixed slots (`OpCode::LoadGlobal`)
+/// instead of a by-name lookup through every frame. Order is the slot index.
+pub const PINNED_GLOBALS: &[&str] = &[
+    "console",
+    "JSON",
+    "Object",
+    "String",
+    "Number",
+    "Boolean",
+    "Map",
+    "Set",
+    "process",
+    "fs",
+    "require",
+    "fetch",
+    "ByteStream",
+    "runtime",
+    "parseInt",
+    "parseFloat",
+    "isNaN",
+    "isFinite",
+    "Math",
+    "std",
+];
+
+/// Slot of `name` in `PINNED_GLOBALS`, if it is pinned
+pub fn pinned_global_slot(name: &str) -> Option<u32> {
+    PINNED_GLOBALS
+        .iter()
+        .position(|&pinned| pinned == name)
+        .map(|slot| slot as u32)
+}
+
 #[derive(Debug, Clone)]
 pub enum OpCode {
     LoadThis,
@@ -16,7 +49,20 @@ pub enum OpCode {
     Load(String),
     Drop(String),
     Call(usize),
+    /// Push argument N of the current call (undefined if the caller passed
+    /// fewer); function prologues bind their parameters with it
+    LoadArg(usize),
+    /// Push an array of the current call's arguments from N on (`...rest`)
+    LoadRestArgs(usize),
     Return,
+    /// Return a fixed-size array literal without allocating it: pops N values.
+    /// If the caller immediately unpacks with `Unpack(N)`, the values are left
+    /// on the stack and the `Unpack` is skipped; otherwise the array is built
+    /// and returned as with `Return`.
+    ReturnMulti(usize),
+    /// Array destructuring of a call result: pops an array and pushes its
+    /// first N elements (element 0 deepest)
+    Unpack(usize),
     Jump(usize),
     NewObject,
     NewObjectWithProto, // Creates object with given prototype
@@ -63,13 +109,15 @@ pub enum OpCode {
     /// combines it with the function address to create a Function value.
     /// This is the key to "lifting" captured variables from stack to heap.
     MakeClosure(usize), // address of the function body
-    /// Construct a new object: pops constructor, args, and `this` object from stack.
-    /// Binds `this` to the new object, calls the constructor, returns the object.
+    /// Construct a new object: pops constructor and args from stack.
+    /// Binds `this` to a new object, calls the constructor, returns the object.
     Construct(usize), // arg_count
     /// Store top of stack into indexed local variable slot
     StoreLocal(u32),
     /// Load indexed local variable slot onto stack
     LoadLocal(u32),
+    /// Load a stdlib global from its pinned slot (see `PINNED_GLOBALS`)
+    LoadGlobal(u32),
 
     // === Bitwise operators ===
     /// Bitwise AND (&)
@@ -119,10 +167,20 @@ pub enum OpCode {
     GetPrivateProp(usize),
     /// Set a private field: pops value and `this` from stack, sets field in class's private storage.
     SetPrivateProp(usize),
+    /// Call a private method: pops obj from [args..., obj], calls the function in obj's
+    /// private slot with `this` bound to obj. Throws a TypeError if obj has no such private member.
+    /// Private getters and setters are called the same way, with 0 and 1 arguments.
+    CallPrivateMethod(usize, usize),
+    /// Brand check (`#x in obj`): pops obj, pushes whether it has the private slot
+    HasPrivateProp(usize),
+    /// Push an empty WeakMap, the storage of one private slot
+    NewWeakMap,
 
     // === instanceof ===
     /// InstanceOf: pops constructor and object, checks if constructor.prototype is in object's prototype chain
     InstanceOf,
+    /// In: pops object and key, pushes whether the key is a property of the object or its prototype chain
+    In,
 
     // === new.target ===
     /// NewTarget: pushes the constructor that was called with new (stored in frame)
@@ -138,8 +196,12 @@ pub enum OpCode {
     // === ES Modules ===
     /// ImportAsync: Asynchronously load a module
     /// Stack: [module_url] -> [promise]
-    /// The promise resolves to the module namespace object
-    ImportAsync(String),
+    /// The promise resolves to the module namespace object. `json` loads the
+    /// file as JSON whatever its extension (`with { type: "json" }`).
+    ImportAsync {
+        specifier: String,
+        json: bool,
+    },
     /// Await: Await a promise value (must be in async context)
     /// Stack: [promise] -> [result]
     /// Suspends execution until promise resolves
@@ -150,6 +212,27 @@ pub enum OpCode {
         name: String,
         is_default: bool,
     },
+    /// ImportMeta: The running module's `import.meta` object
+    /// Stack: [] -> [meta]
+    ImportMeta,
+    /// ImportBinding: Bind `local` to the export `name` of a module
+    /// namespace; every read sees the export's current value
+    /// Stack: [namespace] -> []
+    ImportBinding {
+        name: String,
+        local: String,
+    },
+    /// ReExport: Export the export `name` of a module namespace from the
+    /// running module as `exported` (`export { name as exported } from`)
+    /// Stack: [namespace] -> []
+    ReExport {
+        name: String,
+        exported: String,
+    },
+    /// ReExportAll: Export every named export of a module namespace that
+    /// the running module doesn't export itself (`export * from`)
+    /// Stack: [namespace] -> []
+    ReExportAll,
     /// ModuleResolutionError: Error with source location and dependency chain
     ModuleResolutionError {
         message: String,
@@ -0,0 +1,121 @@
+//! Operator overloading (`--operator-overloading`)
+//!
+//! A class opts in by defining methods under well-known operator keys:
+//!
+//! ```javascript
+//! class Vec2 {
+//!     [Symbol.operator.plus](other) { return new Vec2(this.x + other.x, this.y + other.y); }
+//! }
+//! ```
+//!
+//! With the flag on, an arithmetic or comparison opcode whose left operand
+//! has a method for it calls `left[op](right)` and leaves the result instead
+//! of computing it; unary minus calls `value[op]()`. Everything else runs the
+//! opcode as usual, so numbers and strings pay one type check. `Decimal`
+//! values take part too, with their arithmetic done natively. The keys are
+//! plain strings (`Symbol.operator.plus` is `"@@operator.plus"`), which is
+//! also what the compiler turns a computed `[Symbol.operator.plus]` method
+//! name into.
+
+use crate::stdlib::decimal;
+use crate::vm::opcodes::OpCode;
+use crate::vm::property::get_prop_with_proto_chain;
+use crate::vm::value::{HeapData, JsValue};
+use crate::vm::{ExecResult, VM};
+
+/// Prefix of the property key an operator method is stored under
+pub const OPERATOR_KEY_PREFIX: &str = "@@operator.";
+
+/// Names under `Symbol.operator`
+pub const OPERATOR_NAMES: [&str; 10] = [
+    "plus",
+    "minus",
+    "times",
+    "divide",
+    "remainder",
+    "lessThan",
+    "lessThanOrEqual",
+    "greaterThan",
+    "greaterThanOrEqual",
+    "negate",
+];
+
+/// Property key for the operator called `name`, if there is one
+pub fn operator_key(name: &str) -> Option<String> {
+    OPERATOR_NAMES
+        .contains(&name)
+        .then(|| format!("{}{}", OPERATOR_KEY_PREFIX, name))
+}
+
+/// Operator `op` dispatches to, and how many operands it takes
+fn operator_of(op: &OpCode) -> Option<(&'static str, usize)> {
+    let name = match op {
+        OpCode::Add => "plus",
+        OpCode::Sub => "minus",
+        OpCode::Mul => "times",
+        OpCode::Div => "divide",
+        OpCode::Mod => "remainder",
+        OpCode::Lt => "lessThan",
+        OpCode::LtEq => "lessThanOrEqual",
+        OpCode::Gt => "greaterThan",
+        OpCode::GtEq => "greaterThanOrEqual",
+        OpCode::Neg => return Some(("negate", 1)),
+        _ => return None,
+    };
+    Some((name, 2))
+}
+
+impl VM {
+    /// Call the left operand's method for `op`, if it has one. `None` means
+    /// the opcode should run as usual.
+    pub(super) fn dispatch_operator(&mut self, op: &OpCode) -> Option<ExecResult> {
+        let (name, arity) = operator_of(op)?;
+        let first = self.stack.len().checked_sub(arity)?;
+        let JsValue::Object(ptr) = self.stack[first] else {
+            return None;
+        };
+        match self.heap.get(ptr).map(|obj| &obj.data) {
+            Some(HeapData::Object(_)) => {}
+            // Decimals overload every operator natively
+            Some(HeapData::Decimal(value)) => {
+                let value = value.clone();
+                let operands = self.stack.split_off(first);
+                let result = decimal::apply_operator(self, name, &value, operands.get(1));
+                self.stack.push(result);
+                self.ip += 1;
+                return Some(ExecResult::Continue);
+            }
+            _ => return None,
+        }
+        let key = format!("{}{}", OPERATOR_KEY_PREFIX, name);
+        let JsValue::Function {
+            address,
+            env,
+            bound,
+        } = get_prop_with_proto_chain(self, ptr, &key)
+        else {
+            return None;
+        };
+
+        let args = self.stack.split_off(first + 1);
+        let receiver = self.stack.pop().unwrap_or(JsValue::Undefined);
+        self.enter_function((address, env, bound), receiver, args, self.ip + 1);
+        Some(ExecResult::ContinueNoIpInc)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_operator_keys() {
+        assert_eq!(operator_key("plus").as_deref(), Some("@@operator.plus"));
+        assert_eq!(operator_key("plux"), None);
+        for name in OPERATOR_NAMES {
+            assert!(operator_key(name).is_some());
+        }
+        assert_eq!(operator_of(&OpCode::Neg), Some(("negate", 1)));
+        assert_eq!(operator_of(&OpCode::Eq), None);
+    }
+}
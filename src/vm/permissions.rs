@@ -0,0 +1,339 @@
+//! Host capabilities a script may use (`--sandbox`, `--allow-*`, `--prompt`)
+//!
+//! Scripts get every capability the process has unless the run is
+//! sandboxed. In a sandbox only the capabilities granted with `--allow-read`,
+//! `--allow-net=example.com` and friends are available; a grant with a list
+//! covers those paths (and everything below them), hosts, variables or
+//! commands only. With `--prompt`, touching anything else asks on the
+//! terminal instead of failing, and the answer is remembered for the rest of
+//! the run. Natives call `check` before they touch the host; scripts can ask
+//! ahead of time with `permissions.query({ name: "read", path })`.
+
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Capability {
+    Read,
+    Write,
+    Net,
+    Env,
+    Run,
+}
+
+impl Capability {
+    pub const ALL: [Capability; 5] = [
+        Capability::Read,
+        Capability::Write,
+        Capability::Net,
+        Capability::Env,
+        Capability::Run,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Capability::Read => "read",
+            Capability::Write => "write",
+            Capability::Net => "net",
+            Capability::Env => "env",
+            Capability::Run => "run",
+        }
+    }
+
+    pub fn parse(name: &str) -> Result<Self, String> {
+        Self::ALL
+            .into_iter()
+            .find(|cap| cap.name() == name)
+            .ok_or_else(|| {
+                format!(
+                    "unknown permission '{}' (expected read, write, net, env or run)",
+                    name
+                )
+            })
+    }
+
+    /// Key `permissions.query` reads the target from
+    pub fn target_key(self) -> &'static str {
+        match self {
+            Capability::Read | Capability::Write => "path",
+            Capability::Net => "host",
+            Capability::Env => "variable",
+            Capability::Run => "command",
+        }
+    }
+
+    fn is_path(self) -> bool {
+        matches!(self, Capability::Read | Capability::Write)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PermissionState {
+    Granted,
+    /// Not granted, but asking the user is allowed
+    Prompt,
+    Denied,
+}
+
+impl PermissionState {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PermissionState::Granted => "granted",
+            PermissionState::Prompt => "prompt",
+            PermissionState::Denied => "denied",
+        }
+    }
+}
+
+/// What the user answered to a prompt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PromptAnswer {
+    /// Grant this target only
+    Allow,
+    /// Grant the capability for everything
+    AllowAll,
+    Deny,
+}
+
+/// Asks the user about a request, e.g. `read access to "/etc/hosts"`
+pub type Prompter = fn(&str) -> PromptAnswer;
+
+#[derive(Debug, Clone)]
+enum Grant {
+    All,
+    Only(Vec<String>),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Permissions {
+    sandboxed: bool,
+    grants: HashMap<Capability, Grant>,
+    /// Requests the user already refused, so they are not asked twice
+    refused: HashSet<(Capability, Option<String>)>,
+    prompter: Option<Prompter>,
+}
+
+impl Permissions {
+    /// Every capability, without checks
+    pub fn unrestricted() -> Self {
+        Self::default()
+    }
+
+    /// Nothing until granted with `allow`
+    pub fn sandboxed() -> Self {
+        Self {
+            sandboxed: true,
+            ..Self::default()
+        }
+    }
+
+    pub fn is_sandboxed(&self) -> bool {
+        self.sandboxed
+    }
+
+    /// Grant `cap`, for every target or only for `targets`
+    pub fn allow(&mut self, cap: Capability, targets: Option<Vec<String>>) {
+        let targets = match targets {
+            None => {
+                self.grants.insert(cap, Grant::All);
+                return;
+            }
+            Some(targets) => targets,
+        };
+        match self.grants.entry(cap).or_insert(Grant::Only(Vec::new())) {
+            Grant::All => {}
+            Grant::Only(list) => list.extend(targets.iter().map(|t| normalize(cap, t))),
+        }
+    }
+
+    /// Ask with `prompter` instead of failing when something isn't granted
+    pub fn set_prompter(&mut self, prompter: Prompter) {
+        self.prompter = Some(prompter);
+    }
+
+    /// State of `cap` for `target`, or for every target when `None`
+    pub fn query(&self, cap: Capability, target: Option<&str>) -> PermissionState {
+        if !self.sandboxed || self.covers(cap, target) {
+            PermissionState::Granted
+        } else if self.prompter.is_none()
+            || self
+                .refused
+                .contains(&(cap, target.map(|t| normalize(cap, t))))
+        {
+            PermissionState::Denied
+        } else {
+            PermissionState::Prompt
+        }
+    }
+
+    /// Check `cap` for `target` before touching the host, prompting if
+    /// allowed. The error names the flag that would grant the access.
+    pub fn check(&mut self, cap: Capability, target: Option<&str>) -> Result<(), String> {
+        let request = match target {
+            Some(target) => format!("{} access to \"{}\"", cap.name(), target),
+            None => format!("{} access", cap.name()),
+        };
+        let state = self.query(cap, target);
+        if state == PermissionState::Prompt
+            && let Some(prompter) = self.prompter
+        {
+            match prompter(&request) {
+                PromptAnswer::Allow => {
+                    self.allow(cap, target.map(|t| vec![t.to_string()]));
+                    return Ok(());
+                }
+                PromptAnswer::AllowAll => {
+                    self.allow(cap, None);
+                    return Ok(());
+                }
+                PromptAnswer::Deny => {
+                    self.refused
+                        .insert((cap, target.map(|t| normalize(cap, t))));
+                }
+            }
+        }
+        if state == PermissionState::Granted {
+            return Ok(());
+        }
+        Err(format!(
+            "PermissionDenied: {} (run with --allow-{} to grant it)",
+            request,
+            cap.name()
+        ))
+    }
+
+    fn covers(&self, cap: Capability, target: Option<&str>) -> bool {
+        match (self.grants.get(&cap), target) {
+            (Some(Grant::All), _) => true,
+            (Some(Grant::Only(list)), Some(target)) => {
+                let target = normalize(cap, target);
+                if cap.is_path() {
+                    list.iter()
+                        .any(|granted| Path::new(&target).starts_with(granted))
+                } else if cap == Capability::Net {
+                    // A bare host covers every port on it
+                    let host = target.rsplit_once(':').map_or(target.as_str(), |(h, _)| h);
+                    list.iter()
+                        .any(|granted| *granted == target || granted == host)
+                } else {
+                    list.contains(&target)
+                }
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Paths are compared absolute, so `./data` and `data/x.txt` match
+fn normalize(cap: Capability, target: &str) -> String {
+    if !cap.is_path() {
+        return target.to_string();
+    }
+    let path = Path::new(target);
+    let absolute = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir()
+            .unwrap_or_else(|_| PathBuf::from("."))
+            .join(path)
+    };
+    // Drop `.` and resolve `..` without touching the file system, since the
+    // path may not exist yet
+    let mut clean = PathBuf::new();
+    for component in absolute.components() {
+        match component {
+            std::path::Component::CurDir => {}
+            std::path::Component::ParentDir => {
+                clean.pop();
+            }
+            other => clean.push(other),
+        }
+    }
+    clean.to_string_lossy().into_owned()
+}
+
+/// Ask on the terminal. Without one (piped stdin, CI) the answer is no.
+pub fn terminal_prompt(request: &str) -> PromptAnswer {
+    if !std::io::stdin().is_terminal() || !std::io::stderr().is_terminal() {
+        return PromptAnswer::Deny;
+    }
+    let mut stderr = std::io::stderr();
+    let _ = write!(
+        stderr,
+        "Allow {}? [y]es, [n]o, [A]llow all {} > ",
+        request,
+        request.split(' ').next().unwrap_or_default()
+    );
+    let _ = stderr.flush();
+    let mut answer = String::new();
+    if std::io::stdin().lock().read_line(&mut answer).is_err() {
+        return PromptAnswer::Deny;
+    }
+    match answer.trim() {
+        "y" | "Y" | "yes" => PromptAnswer::Allow,
+        "A" | "all" => PromptAnswer::AllowAll,
+        _ => PromptAnswer::Deny,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grants_and_prompts() {
+        let mut permissions = Permissions::unrestricted();
+        assert!(permissions.check(Capability::Run, Some("rm")).is_ok());
+
+        let mut permissions = Permissions::sandboxed();
+        permissions.allow(Capability::Read, Some(vec!["/data".to_string()]));
+        permissions.allow(Capability::Env, None);
+        assert_eq!(
+            permissions.query(Capability::Read, Some("/data/users/../x.csv")),
+            PermissionState::Granted
+        );
+        assert_eq!(
+            permissions.query(Capability::Read, Some("/etc/hosts")),
+            PermissionState::Denied
+        );
+        assert_eq!(
+            permissions.query(Capability::Read, None),
+            PermissionState::Denied
+        );
+        assert_eq!(
+            permissions.query(Capability::Env, Some("HOME")),
+            PermissionState::Granted
+        );
+        let err = permissions
+            .check(Capability::Net, Some("example.com"))
+            .unwrap_err();
+        assert!(err.contains("--allow-net"), "{}", err);
+
+        permissions.set_prompter(|request| {
+            if request.starts_with("net") {
+                PromptAnswer::Allow
+            } else {
+                PromptAnswer::Deny
+            }
+        });
+        assert_eq!(
+            permissions.query(Capability::Net, Some("example.com")),
+            PermissionState::Prompt
+        );
+        assert!(
+            permissions
+                .check(Capability::Net, Some("example.com"))
+                .is_ok()
+        );
+        assert_eq!(
+            permissions.query(Capability::Net, Some("example.com")),
+            PermissionState::Granted
+        );
+        assert!(permissions.check(Capability::Run, Some("ls")).is_err());
+        assert_eq!(
+            permissions.query(Capability::Run, Some("ls")),
+            PermissionState::Denied
+        );
+    }
+}
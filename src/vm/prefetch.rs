@@ -0,0 +1,332 @@
+//! Speculative module compilation (`oitec --prefetch <file>`)
+//!
+//! While the entry module starts running, worker threads follow its static
+//! imports and compile each module they reach, so the import finds its
+//! bytecode ready instead of compiling on the spot. Speculation never
+//! changes behavior: the VM re-reads every module it imports and only uses
+//! the prefetched bytecode if the source still matches.
+//!
+//! Work can be cancelled at any time. A module the VM claims before a worker
+//! reaches it is dropped from the queue, and `cancel` (or dropping the
+//! prefetcher) drops all queued work and waits for the workers to finish the
+//! compile in hand and exit, so none outlives the run that started it.
+
+use super::opcodes::OpCode;
+use super::resolve_import;
+use crate::compiler::Compiler;
+use crate::driver::{CompileSettings, syntax_for_path};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+
+/// Upper bound on worker threads
+const MAX_WORKERS: usize = 4;
+
+/// A module compiled ahead of its import
+pub struct Prefetched {
+    /// Source the bytecode was compiled from
+    pub source: String,
+    pub bytecode: Result<Vec<OpCode>, String>,
+    pub line_table: Vec<(usize, u32)>,
+    pub warnings: Vec<String>,
+}
+
+#[derive(Default)]
+struct State {
+    queue: VecDeque<PathBuf>,
+    /// Every module queued, compiled or claimed, so none is visited twice
+    seen: HashSet<PathBuf>,
+    in_flight: HashSet<PathBuf>,
+    done: HashMap<PathBuf, Prefetched>,
+    /// Workers waiting for work; they stop once all of them are
+    idle: usize,
+    /// Set by `cancel`, or once the whole graph has been compiled
+    stopped: bool,
+}
+
+#[derive(Default)]
+struct Shared {
+    state: Mutex<State>,
+    changed: Condvar,
+    settings: CompileSettings,
+}
+
+pub struct Prefetcher {
+    shared: Arc<Shared>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl Prefetcher {
+    /// Start compiling the static imports of `entry` (whose text is
+    /// `source`) and, transitively, theirs, each with the settings of its
+    /// directory.
+    pub fn start(entry: &Path, source: &str, settings: CompileSettings) -> Self {
+        let shared = Arc::new(Shared {
+            settings,
+            ..Default::default()
+        });
+        {
+            let mut state = shared.state.lock().unwrap();
+            state.seen.insert(module_key(entry));
+            for import in static_imports(entry, source) {
+                if state.seen.insert(import.clone()) {
+                    state.queue.push_back(import);
+                }
+            }
+        }
+
+        let count = std::thread::available_parallelism()
+            .map_or(1, |n| n.get())
+            .min(MAX_WORKERS);
+        let workers = (0..count)
+            .map(|_| {
+                let shared = Arc::clone(&shared);
+                std::thread::spawn(move || worker(&shared, count))
+            })
+            .collect();
+        Self { shared, workers }
+    }
+
+    /// A prefetcher holding `modules`, compiled elsewhere (see `bundle`),
+    /// with no work of its own to do
+    pub fn with_modules(modules: HashMap<PathBuf, Prefetched>) -> Self {
+        let state = State {
+            seen: modules.keys().cloned().collect(),
+            done: modules,
+            stopped: true,
+            ..Default::default()
+        };
+        Self {
+            shared: Arc::new(Shared {
+                state: Mutex::new(state),
+                ..Default::default()
+            }),
+            workers: Vec::new(),
+        }
+    }
+
+    /// Claim the prefetched compile of `path`. Waits if a worker is
+    /// compiling it right now; returns None if no worker has started on it,
+    /// in which case no worker will.
+    pub fn take(&self, path: &Path) -> Option<Prefetched> {
+        let key = module_key(path);
+        let mut state = self.shared.state.lock().unwrap();
+        while state.in_flight.contains(&key) {
+            state = self.shared.changed.wait(state).unwrap();
+        }
+        if let Some(module) = state.done.remove(&key) {
+            return Some(module);
+        }
+        state.seen.insert(key.clone());
+        state.queue.retain(|queued| *queued != key);
+        None
+    }
+
+    /// Stop starting new compiles and wait for the workers to exit. Modules
+    /// already compiled can still be taken.
+    pub fn cancel(&mut self) {
+        {
+            let mut state = self.shared.state.lock().unwrap();
+            state.stopped = true;
+            state.queue.clear();
+            self.shared.changed.notify_all();
+        }
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+
+    /// Block until every reachable module is compiled or work is cancelled
+    pub fn wait(&self) {
+        let mut state = self.shared.state.lock().unwrap();
+        while !state.stopped {
+            state = self.shared.changed.wait(state).unwrap();
+        }
+    }
+
+    /// Modules compiled and not yet taken
+    pub fn ready(&self) -> usize {
+        self.shared.state.lock().unwrap().done.len()
+    }
+}
+
+impl Drop for Prefetcher {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+fn worker(shared: &Shared, workers: usize) {
+    loop {
+        let path = {
+            let mut state = shared.state.lock().unwrap();
+            loop {
+                if state.stopped {
+                    return;
+                }
+                if let Some(path) = state.queue.pop_front() {
+                    state.in_flight.insert(path.clone());
+                    break path;
+                }
+                // Nothing queued and nobody compiling: nothing more will be
+                if state.in_flight.is_empty() && state.idle + 1 == workers {
+                    state.stopped = true;
+                    shared.changed.notify_all();
+                    return;
+                }
+                state.idle += 1;
+                state = shared.changed.wait(state).unwrap();
+                state.idle -= 1;
+            }
+        };
+
+        let source = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|source| crate::transform::transform_source(&path, source).ok());
+        let compiled = source.map(|source| {
+            let mut compiler = Compiler::new();
+            compiler.borrow_check = shared.settings.borrow_check_for(&path);
+            let syntax = syntax_for_path(&path.to_string_lossy());
+            let bytecode = compiler.compile_with_syntax(&source, Some(syntax));
+            compiler.bytecode_cache = shared.settings.bytecode_cache.clone();
+            let imports = static_imports(&path, &source);
+            let module = Prefetched {
+                source,
+                bytecode,
+                line_table: compiler.line_table,
+                warnings: compiler.warnings,
+            };
+            (module, imports)
+        });
+
+        let mut state = shared.state.lock().unwrap();
+        state.in_flight.remove(&path);
+        if let Some((module, imports)) = compiled {
+            state.done.insert(path, module);
+            if !state.stopped {
+                for import in imports {
+                    if state.seen.insert(import.clone()) {
+                        state.queue.push_back(import);
+                    }
+                }
+            }
+        }
+        shared.changed.notify_all();
+    }
+}
+
+/// Canonical path, so imports reaching a file by different routes agree
+fn module_key(path: &Path) -> PathBuf {
+    crate::platform::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Modules named by the static imports and re-exports of `source`, resolved
+/// the way the VM resolves them. Type-only imports and unresolvable or
+/// non-relative specifiers are skipped.
+pub(super) fn static_imports(path: &Path, source: &str) -> Vec<PathBuf> {
+    use swc_common::{FileName, SourceMap, input::StringInput};
+    use swc_ecma_ast::{ModuleDecl, ModuleItem};
+    use swc_ecma_parser::{Parser, lexer::Lexer};
+
+    let cm = SourceMap::default();
+    let fm = cm.new_source_file(
+        FileName::Custom(path.to_string_lossy().into_owned()).into(),
+        source.to_string(),
+    );
+    let syntax = syntax_for_path(&path.to_string_lossy());
+    let lexer = Lexer::new(syntax, Default::default(), StringInput::from(&*fm), None);
+    let Ok(module) = Parser::new_from(lexer).parse_module() else {
+        return Vec::new();
+    };
+
+    let mut imports = Vec::new();
+    for item in &module.body {
+        let ModuleItem::ModuleDecl(decl) = item else {
+            continue;
+        };
+        let src = match decl {
+            ModuleDecl::Import(import) if !import.type_only => &import.src,
+            ModuleDecl::ExportAll(export) if !export.type_only => &export.src,
+            ModuleDecl::ExportNamed(export) if !export.type_only => match &export.src {
+                Some(src) => src,
+                None => continue,
+            },
+            _ => continue,
+        };
+        let specifier = src.value.to_string_lossy();
+        if !specifier.starts_with('.') {
+            continue;
+        }
+        let resolved = resolve_import(Some(path), &specifier);
+        if resolved.is_file() {
+            imports.push(module_key(&resolved));
+        }
+    }
+    imports
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prefetches_static_import_graph() {
+        let dir = std::env::temp_dir().join(format!("oite-prefetch-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let entry = dir.join("main.ot");
+        let entry_source = "import { a } from './a';\nimport type { T } from './types';\n";
+        std::fs::write(&entry, entry_source).unwrap();
+        std::fs::write(
+            dir.join("a.ot"),
+            "export { b } from './b';\nexport let a = 1;\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("b.ot"), "export let b = 2;\n").unwrap();
+        std::fs::write(dir.join("types.ot"), "export type T = number;\n").unwrap();
+
+        let prefetcher = Prefetcher::start(&entry, entry_source, CompileSettings::default());
+        prefetcher.wait();
+        assert_eq!(prefetcher.ready(), 2);
+        let a = prefetcher.take(&dir.join("a.ot")).unwrap();
+        assert!(a.bytecode.is_ok());
+        assert!(a.source.contains("export let a"));
+        // Taken modules are handed out once; the rest of the graph follows
+        assert!(prefetcher.take(&dir.join("a.ot")).is_none());
+        assert!(prefetcher.take(&dir.join("b.ot")).is_some());
+        // Type-only imports are never compiled
+        assert!(prefetcher.take(&dir.join("types.ot")).is_none());
+        drop(prefetcher);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_cancel_waits_for_workers() {
+        let dir = std::env::temp_dir().join(format!("oite-prefetch-cancel-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let entry = dir.join("main.ot");
+        let entry_source: String = (0..32)
+            .map(|i| format!("import {{ m{i} }} from './m{i}';\n"))
+            .collect();
+        std::fs::write(&entry, &entry_source).unwrap();
+        for i in 0..32 {
+            std::fs::write(
+                dir.join(format!("m{i}.ot")),
+                format!("export let m{i} = {i};\n"),
+            )
+            .unwrap();
+        }
+
+        let mut prefetcher = Prefetcher::start(&entry, &entry_source, CompileSettings::default());
+        prefetcher.cancel();
+        // Every worker has dropped its handle on the shared state
+        assert_eq!(Arc::strong_count(&prefetcher.shared), 1);
+        let ready = prefetcher.ready();
+        assert!(ready <= 32);
+        assert_eq!(prefetcher.ready(), ready);
+        drop(prefetcher);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,244 @@
+//! Sampling profiler for the bytecode interpreter (`oitec profile`)
+//!
+//! Every `interval` executed instructions the VM records its call stack (the
+//! entry address of the function in each frame) weighted by the wall-clock
+//! time since the previous sample. Idle time spent sleeping in the event
+//! loop is excluded. Function names are recovered from the program after the
+//! run, so sampling itself never touches strings.
+
+use crate::vm::opcodes::OpCode;
+use crate::vm::value::JsValue;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::time::Instant;
+
+/// Instructions between samples when the caller doesn't choose
+pub const DEFAULT_INTERVAL: u32 = 1000;
+
+/// Frame label for the script's top-level code
+pub(crate) const TOP_LEVEL: &str = "(top level)";
+/// Frame label for frames not running a bytecode function (native constructors)
+pub(crate) const NATIVE: &str = "(native)";
+
+pub struct Profiler {
+    interval: u32,
+    countdown: u32,
+    last_sample: Instant,
+    /// Accumulated nanoseconds per call stack, outermost frame first
+    stacks: HashMap<Vec<Option<usize>>, u64>,
+}
+
+impl Profiler {
+    pub fn new(interval: u32) -> Self {
+        let interval = interval.max(1);
+        Self {
+            interval,
+            countdown: interval,
+            last_sample: Instant::now(),
+            stacks: HashMap::new(),
+        }
+    }
+
+    /// Count one instruction; true when a sample is due.
+    #[inline]
+    pub fn tick(&mut self) -> bool {
+        self.countdown -= 1;
+        if self.countdown == 0 {
+            self.countdown = self.interval;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn record(&mut self, stack: Vec<Option<usize>>) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_sample).as_nanos() as u64;
+        self.last_sample = now;
+        *self.stacks.entry(stack).or_insert(0) += elapsed;
+    }
+
+    /// Don't charge the time since the last sample to any stack (the VM was idle).
+    pub fn skip_idle(&mut self) {
+        self.last_sample = Instant::now();
+    }
+
+    /// Resolve the recorded stacks against the program that ran.
+    pub fn finish(&self, program: &[OpCode]) -> Profile {
+        let names = function_names(program);
+        let mut stacks: Vec<(Vec<String>, u64)> = self
+            .stacks
+            .iter()
+            .map(|(stack, &weight)| {
+                let frames = stack
+                    .iter()
+                    .enumerate()
+                    .map(|(depth, function)| match function {
+                        Some(address) => names
+                            .get(address)
+                            .cloned()
+                            .unwrap_or_else(|| format!("anonymous@{}", address)),
+                        None if depth == 0 => TOP_LEVEL.to_string(),
+                        None => NATIVE.to_string(),
+                    })
+                    .collect();
+                (frames, weight)
+            })
+            .collect();
+        stacks.sort();
+        Profile { stacks }
+    }
+}
+
+/// Name each function after the binding it is first stored into
+/// (`function f`, `let f = () => ...`, `obj.f = ...`). Names shared by
+/// several functions get their address appended so they stay distinct.
+pub(crate) fn function_names(program: &[OpCode]) -> HashMap<usize, String> {
+    let mut names: HashMap<usize, String> = HashMap::new();
+    for pair in program.windows(2) {
+        let address = match &pair[0] {
+            OpCode::Push(JsValue::Function { address, .. }) | OpCode::MakeClosure(address) => {
+                *address
+            }
+            _ => continue,
+        };
+        if let OpCode::Let(name) | OpCode::Store(name) | OpCode::SetProp(name) = &pair[1] {
+            names.entry(address).or_insert_with(|| name.clone());
+        }
+    }
+
+    let mut uses: HashMap<String, usize> = HashMap::new();
+    for name in names.values() {
+        *uses.entry(name.clone()).or_insert(0) += 1;
+    }
+    for (address, name) in names.iter_mut() {
+        if uses[name.as_str()] > 1 {
+            *name = format!("{}@{}", name, address);
+        }
+    }
+    names
+}
+
+/// Named call stacks with the nanoseconds spent in each
+#[derive(Debug, Clone, PartialEq)]
+pub struct Profile {
+    pub stacks: Vec<(Vec<String>, u64)>,
+}
+
+impl Profile {
+    pub fn total_ns(&self) -> u64 {
+        self.stacks.iter().map(|(_, weight)| weight).sum()
+    }
+
+    /// Brendan Gregg's collapsed-stack format (`a;b;c <weight>`), as read by
+    /// flamegraph.pl and inferno. Weights are microseconds.
+    pub fn to_collapsed(&self) -> String {
+        let mut out = String::new();
+        for (frames, weight) in &self.stacks {
+            let micros = weight / 1000;
+            if micros == 0 {
+                continue;
+            }
+            out.push_str(&frames.join(";"));
+            out.push_str(&format!(" {}\n", micros));
+        }
+        out
+    }
+
+    /// A sampled profile in speedscope's file format.
+    pub fn to_speedscope(&self, name: &str) -> Value {
+        let mut frame_index: HashMap<&str, usize> = HashMap::new();
+        let mut frames = Vec::new();
+        let mut samples = Vec::new();
+        let mut weights = Vec::new();
+        for (stack, weight) in &self.stacks {
+            let sample: Vec<usize> = stack
+                .iter()
+                .map(|frame| {
+                    *frame_index.entry(frame.as_str()).or_insert_with(|| {
+                        frames.push(json!({ "name": frame }));
+                        frames.len() - 1
+                    })
+                })
+                .collect();
+            samples.push(sample);
+            weights.push(*weight);
+        }
+        json!({
+            "$schema": "https://www.speedscope.app/file-format-schema.json",
+            "exporter": format!("oitec {}", env!("CARGO_PKG_VERSION")),
+            "name": name,
+            "activeProfileIndex": 0,
+            "shared": { "frames": frames },
+            "profiles": [{
+                "type": "sampled",
+                "name": name,
+                "unit": "nanoseconds",
+                "startValue": 0,
+                "endValue": self.total_ns(),
+                "samples": samples,
+                "weights": weights,
+            }],
+        })
+    }
+
+    /// Functions ordered by self time (time at the top of the stack), descending
+    pub fn self_times(&self) -> Vec<(String, u64)> {
+        let mut totals: HashMap<&str, u64> = HashMap::new();
+        for (frames, weight) in &self.stacks {
+            if let Some(top) = frames.last() {
+                *totals.entry(top.as_str()).or_insert(0) += weight;
+            }
+        }
+        let mut totals: Vec<(String, u64)> = totals
+            .into_iter()
+            .map(|(name, weight)| (name.to_string(), weight))
+            .collect();
+        totals.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        totals
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names_functions_after_their_binding() {
+        let program = vec![
+            OpCode::Push(JsValue::Function {
+                address: 4,
+                env: None,
+                bound: None,
+            }),
+            OpCode::Let("fib".to_string()),
+            OpCode::MakeClosure(9),
+            OpCode::SetProp("run".to_string()),
+            OpCode::Push(JsValue::Function {
+                address: 12,
+                env: None,
+                bound: None,
+            }),
+            OpCode::Store("run".to_string()),
+        ];
+        let names = function_names(&program);
+        assert_eq!(names[&4], "fib");
+        assert_eq!(names[&9], "run@9");
+        assert_eq!(names[&12], "run@12");
+    }
+
+    #[test]
+    fn test_collapsed_and_speedscope_output() {
+        let profile = Profile {
+            stacks: vec![
+                (vec![TOP_LEVEL.to_string()], 2_000),
+                (vec![TOP_LEVEL.to_string(), "fib".to_string()], 5_000),
+            ],
+        };
+        assert_eq!(profile.to_collapsed(), "(top level) 2\n(top level);fib 5\n");
+        let speedscope = profile.to_speedscope("test");
+        assert_eq!(speedscope["profiles"][0]["endValue"], 7_000);
+        assert_eq!(speedscope["profiles"][0]["samples"][1], json!([0, 1]));
+        assert_eq!(profile.self_times()[0], ("fib".to_string(), 5_000));
+    }
+}
@@ -1,8 +1,39 @@
+use crate::stdlib::number_to_string;
 use crate::vm::VM;
+use crate::vm::method_registry::BuiltinProto;
 use crate::vm::value::{HeapData, HeapObject, JsValue};
 
 const MAX_PROTO_DEPTH: usize = 100;
 
+/// Array methods `CallMethod` implements natively
+pub const ARRAY_METHODS: &[&str] = &[
+    "push",
+    "pop",
+    "shift",
+    "unshift",
+    "splice",
+    "slice",
+    "concat",
+    "join",
+    "indexOf",
+    "lastIndexOf",
+    "includes",
+    "reverse",
+    "fill",
+    "at",
+    "map",
+    "filter",
+    "forEach",
+    "reduce",
+    "find",
+    "findIndex",
+    "some",
+    "every",
+    "flat",
+    "flatMap",
+    "sort",
+];
+
 pub fn get_prop_with_proto_chain(vm: &VM, obj_ptr: usize, name: &str) -> JsValue {
     let mut current_ptr = Some(obj_ptr);
     let mut depth = 0;
@@ -54,7 +85,7 @@ pub fn find_setter_with_proto_chain(
         }) = vm.heap.get(ptr)
         {
             if let Some(setter_val) = props.get(&setter_name)
-                && let JsValue::Function { address, env } = setter_val
+                && let JsValue::Function { address, env, .. } = setter_val
             {
                 return Some((*address, *env));
             }
@@ -71,3 +102,120 @@ pub fn find_setter_with_proto_chain(
 
     None
 }
+
+/// Heap pointer of the WeakMap holding private slot `index` of `this_ptr`,
+/// read from the instance's `__private_storage__` array
+pub fn private_slot(vm: &VM, this_ptr: usize, index: usize) -> Option<usize> {
+    let Some(HeapObject {
+        data: HeapData::Object(props),
+    }) = vm.heap.get(this_ptr)
+    else {
+        return None;
+    };
+    let Some(JsValue::Object(storage_ptr)) = props.get("__private_storage__") else {
+        return None;
+    };
+    match vm.heap.get(*storage_ptr) {
+        Some(HeapObject {
+            data: HeapData::Array(slots),
+        }) => match slots.get(index) {
+            Some(JsValue::Object(slot_ptr)) => Some(*slot_ptr),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Value of private slot `index` of `this_ptr`, if the object has it
+pub fn private_value(vm: &VM, this_ptr: usize, index: usize) -> Option<JsValue> {
+    match vm.heap.get(private_slot(vm, this_ptr, index)?) {
+        Some(HeapObject {
+            data: HeapData::WeakMap(field_map),
+        }) => field_map.get(&this_ptr).cloned(),
+        _ => None,
+    }
+}
+
+/// A value used as a property key, as a string
+pub fn property_key(key: &JsValue) -> String {
+    match key {
+        JsValue::String(s) => s.clone(),
+        JsValue::Number(n) => number_to_string(*n),
+        JsValue::Boolean(b) => b.to_string(),
+        JsValue::Null => "null".to_string(),
+        JsValue::Undefined => "undefined".to_string(),
+        _ => "[object Object]".to_string(),
+    }
+}
+
+/// Whether `key` is `0`, `1`, ... below `len`, written the canonical way
+fn is_index_below(key: &str, len: usize) -> bool {
+    key.parse::<usize>()
+        .is_ok_and(|i| i < len && i.to_string() == key)
+}
+
+/// Keys the VM keeps for itself (`__proto__`, `getter:x`, ...), which no
+/// script sees as properties
+pub fn is_internal_key(key: &str) -> bool {
+    (key.starts_with("__") && key.ends_with("__"))
+        || key.starts_with("getter:")
+        || key.starts_with("setter:")
+}
+
+/// `Object.hasOwn(value, key)`: whether `key` is a property of `value`
+/// itself, not inherited. Accessors count as properties.
+pub fn has_own_property(vm: &VM, value: &JsValue, key: &str) -> bool {
+    match value {
+        JsValue::Object(ptr) => match vm.heap.get(*ptr).map(|obj| &obj.data) {
+            Some(HeapData::Object(props)) => {
+                !is_internal_key(key)
+                    && (props.contains_key(key)
+                        || props.contains_key(&format!("getter:{}", key))
+                        || props.contains_key(&format!("setter:{}", key))
+                        // `process.env` has the host's variables
+                        || crate::stdlib::process::has_env_property(vm, *ptr, key)
+                            .unwrap_or(false))
+            }
+            Some(HeapData::Array(arr)) => key == "length" || is_index_below(key, arr.len()),
+            _ => false,
+        },
+        JsValue::String(s) => key == "length" || is_index_below(key, crate::stdlib::string::len(s)),
+        _ => false,
+    }
+}
+
+/// `key in obj`: whether `key` is a property of the object at `ptr` or of
+/// its prototype chain. Built-in receivers also have their methods.
+pub fn has_property(vm: &VM, ptr: usize, key: &str) -> bool {
+    let value = JsValue::Object(ptr);
+    if let Some(proto) = BuiltinProto::of(&value, &vm.heap) {
+        return has_own_property(vm, &value, key)
+            || (proto == BuiltinProto::Array && ARRAY_METHODS.contains(&key))
+            || vm.method_registry.contains(proto, key);
+    }
+    let mut current_ptr = Some(ptr);
+    let mut depth = 0;
+
+    while let Some(ptr) = current_ptr {
+        if depth > MAX_PROTO_DEPTH {
+            break;
+        }
+        depth += 1;
+
+        let current = JsValue::Object(ptr);
+        if has_own_property(vm, &current, key) {
+            return true;
+        }
+        current_ptr = match vm.heap.get(ptr) {
+            Some(HeapObject {
+                data: HeapData::Object(props),
+            }) => match props.get("__proto__") {
+                Some(JsValue::Object(proto_ptr)) => Some(*proto_ptr),
+                _ => None,
+            },
+            _ => None,
+        };
+    }
+
+    false
+}
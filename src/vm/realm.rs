@@ -0,0 +1,124 @@
+//! Realms: isolated contexts over one compiled program
+//!
+//! `vm.new_realm()` makes a VM that shares `vm`'s compiled program and
+//! nothing else: it starts with its own globals, heap, module cache and
+//! event loop, and a copy of `vm`'s permissions that can be narrowed
+//! further. A host that runs plugins compiles the code once, then gives
+//! each plugin a realm of its own, where it cannot see or change another
+//! plugin's state.
+//!
+//! A heap handle from one realm means nothing in another, so values cross
+//! only through `marshal`, which copies them with `structuredClone`
+//! semantics (see `stdlib::clone`). Data gets through; functions, promises
+//! and host objects such as streams are refused with a `DataCloneError`,
+//! which keeps a realm from calling into another's code.
+//!
+//! The program is shared until a realm loads a module the other hasn't:
+//! the realm loading it then gets its own copy of the program to extend.
+
+use super::{JsValue, VM};
+use crate::stdlib::clone;
+use std::sync::Arc;
+
+impl VM {
+    /// A new realm running this VM's program. The realm has run nothing
+    /// yet: `run_event_loop` runs the entry script in its own globals.
+    pub fn new_realm(&self) -> VM {
+        let mut realm = VM::new();
+        realm.program = Arc::clone(&self.program);
+        realm.current_module_path = self.current_module_path.clone();
+        realm.compile_settings = self.compile_settings.clone();
+        realm.debug_info = self.debug_info.clone();
+        realm.permissions = self.permissions.clone();
+        realm.console_locale = self.console_locale;
+        realm.operator_overloading = self.operator_overloading;
+        realm
+    }
+
+    /// Whether `other` runs the same compiled program, unextended
+    pub fn shares_program(&self, other: &VM) -> bool {
+        Arc::ptr_eq(&self.program, &other.program)
+    }
+
+    /// The global named `name`, if the realm has defined it
+    pub fn global(&self, name: &str) -> Option<JsValue> {
+        self.call_stack[0].locals.get(name).cloned()
+    }
+
+    /// Define or replace the global `name`
+    pub fn set_global(&mut self, name: &str, value: JsValue) {
+        self.call_stack[0].locals.insert(name.to_string(), value);
+        self.refresh_pinned_globals();
+    }
+}
+
+/// Copy `value` out of `from` into `to`, moving the buffers in `transfer`
+/// rather than copying them. `from` is left untouched when the value can't
+/// be copied.
+pub fn marshal(
+    from: &mut VM,
+    to: &mut VM,
+    value: &JsValue,
+    transfer: &[JsValue],
+) -> Result<JsValue, JsValue> {
+    let data = clone::serialize(from, value, transfer)?;
+    Ok(clone::deserialize(to, data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vm::HeapData;
+
+    fn run(source: &str) -> VM {
+        let bytecode = crate::compiler::Compiler::new().compile(source).unwrap();
+        let mut vm = VM::new();
+        vm.load_program(bytecode);
+        vm.run_event_loop();
+        vm
+    }
+
+    #[test]
+    fn test_realms_share_the_program_but_not_globals() {
+        let mut host = run("let count = 0;
+             function bump(n) { count = count + n; return { count: count, tags: ['a'] }; }");
+        let mut plugin = host.new_realm();
+        assert!(plugin.shares_program(&host));
+        plugin.run_event_loop();
+
+        let bump = plugin.global("bump").unwrap();
+        plugin
+            .call_function(bump.clone(), vec![JsValue::Number(2.0)])
+            .unwrap();
+        let result = plugin
+            .call_function(bump, vec![JsValue::Number(3.0)])
+            .unwrap();
+        assert_eq!(plugin.global("count"), Some(JsValue::Number(5.0)));
+        assert_eq!(host.global("count"), Some(JsValue::Number(0.0)));
+
+        let copy = marshal(&mut plugin, &mut host, &result, &[]).unwrap();
+        let JsValue::Object(ptr) = copy else {
+            panic!("expected an object");
+        };
+        let HeapData::Object(props) = &host.heap[ptr].data else {
+            panic!("expected an object");
+        };
+        assert_eq!(props.get("count"), Some(&JsValue::Number(5.0)));
+        host.set_global("result", JsValue::Object(ptr));
+        assert!(host.global("result").is_some());
+    }
+
+    #[test]
+    fn test_functions_do_not_cross_realms() {
+        let mut host = run("function secret() { return 1; }");
+        let mut plugin = host.new_realm();
+        let secret = host.global("secret").unwrap();
+
+        let err = marshal(&mut host, &mut plugin, &secret, &[]).unwrap_err();
+        assert_eq!(
+            err,
+            JsValue::String("DataCloneError: functions cannot be cloned".into())
+        );
+        assert_eq!(plugin.global("secret"), None);
+    }
+}
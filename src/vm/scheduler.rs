@@ -0,0 +1,258 @@
+//! Wake-ups for the event loop
+//!
+//! Script runs on one thread, but much of what the event loop waits for
+//! finishes on others: a child's exit, a promise settled by the async
+//! runtime, a read on a helper thread. Such work takes a `Completion` from
+//! the VM before it starts and, once done, sends back a closure that the loop
+//! runs on its own thread, where it may touch the heap and queue callbacks.
+//! While completions are out the loop stays alive, blocked on their channel
+//! until one arrives or the next timer is due, instead of sleeping in steps.
+//!
+//! Next to tasks (see `task_queue`) the loop runs two lighter kinds of
+//! callback:
+//!
+//! - microtasks (`queueMicrotask(fn)`), drained after the script and after
+//!   every task, ahead of anything else
+//! - immediates (`setImmediate(fn, ...args)`), run as one batch once the
+//!   ready tasks are drained, or sooner when `STARVATION_LIMIT` tasks have
+//!   gone ahead of them. An immediate queued by another waits for the next
+//!   turn, so I/O and timers get a look in between.
+
+use super::task_queue::STARVATION_LIMIT;
+use super::{Task, VM};
+use std::collections::VecDeque;
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender, channel};
+use std::time::Instant;
+
+/// Work a completion hands back to the loop
+pub type OnComplete = Box<dyn FnOnce(&mut VM) + Send>;
+
+/// A promise to wake the event loop once, taken before starting work that
+/// finishes off the loop's thread. Dropping it unused just releases the loop.
+pub struct Completion {
+    tx: Sender<Option<OnComplete>>,
+    sent: bool,
+}
+
+impl Completion {
+    /// Run `on_complete` on the loop's thread
+    pub fn complete(mut self, on_complete: impl FnOnce(&mut VM) + Send + 'static) {
+        let _ = self.tx.send(Some(Box::new(on_complete)));
+        self.sent = true;
+    }
+}
+
+impl Drop for Completion {
+    fn drop(&mut self) {
+        if !self.sent {
+            let _ = self.tx.send(None);
+        }
+    }
+}
+
+pub struct Scheduler {
+    tx: Sender<Option<OnComplete>>,
+    rx: Receiver<Option<OnComplete>>,
+    /// Completions handed out and not yet back
+    outstanding: usize,
+    microtasks: VecDeque<Task>,
+    immediates: VecDeque<(u32, Task)>,
+    next_immediate: u32,
+    /// Tasks run ahead of waiting immediates
+    passed_over: u32,
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        let (tx, rx) = channel();
+        Self {
+            tx,
+            rx,
+            outstanding: 0,
+            microtasks: VecDeque::new(),
+            immediates: VecDeque::new(),
+            next_immediate: 1,
+            passed_over: 0,
+        }
+    }
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A completion the loop waits for
+    pub fn completion(&mut self) -> Completion {
+        self.outstanding += 1;
+        Completion {
+            tx: self.tx.clone(),
+            sent: false,
+        }
+    }
+
+    /// Completions handed out and not yet back
+    pub fn outstanding(&self) -> usize {
+        self.outstanding
+    }
+
+    /// Work handed back so far, without blocking
+    pub fn ready(&mut self) -> Vec<OnComplete> {
+        let mut ready = Vec::new();
+        while let Ok(message) = self.rx.try_recv() {
+            self.receive(message, &mut ready);
+        }
+        ready
+    }
+
+    /// Block until a completion comes back or `deadline` passes (forever
+    /// without one), then return everything handed back so far
+    pub fn wait(&mut self, deadline: Option<Instant>) -> Vec<OnComplete> {
+        let message = match deadline {
+            Some(deadline) => {
+                let timeout = deadline.saturating_duration_since(Instant::now());
+                match self.rx.recv_timeout(timeout) {
+                    Ok(message) => message,
+                    Err(RecvTimeoutError::Timeout | RecvTimeoutError::Disconnected) => {
+                        return Vec::new();
+                    }
+                }
+            }
+            None => match self.rx.recv() {
+                Ok(message) => message,
+                Err(_) => return Vec::new(),
+            },
+        };
+        let mut ready = Vec::new();
+        self.receive(message, &mut ready);
+        ready.extend(self.ready());
+        ready
+    }
+
+    fn receive(&mut self, message: Option<OnComplete>, ready: &mut Vec<OnComplete>) {
+        self.outstanding = self.outstanding.saturating_sub(1);
+        ready.extend(message);
+    }
+
+    pub fn queue_microtask(&mut self, task: Task) {
+        self.microtasks.push_back(task);
+    }
+
+    pub fn next_microtask(&mut self) -> Option<Task> {
+        self.microtasks.pop_front()
+    }
+
+    pub fn microtasks(&self) -> usize {
+        self.microtasks.len()
+    }
+
+    /// Queue an immediate, returning its id for `clearImmediate`
+    pub fn set_immediate(&mut self, task: Task) -> u32 {
+        let id = self.next_immediate;
+        self.next_immediate = self.next_immediate.wrapping_add(1).max(1);
+        self.immediates.push_back((id, task));
+        id
+    }
+
+    /// Drop a queued immediate; false when it already ran or never existed
+    pub fn clear_immediate(&mut self, id: u32) -> bool {
+        let before = self.immediates.len();
+        self.immediates.retain(|(queued, _)| *queued != id);
+        self.immediates.len() != before
+    }
+
+    pub fn immediates(&self) -> usize {
+        self.immediates.len()
+    }
+
+    /// The immediates to run now: all of them when no task is ready or they
+    /// have waited `STARVATION_LIMIT` turns, otherwise none
+    pub fn due_immediates(&mut self, tasks_ready: bool) -> Vec<Task> {
+        if self.immediates.is_empty() {
+            self.passed_over = 0;
+            return Vec::new();
+        }
+        if tasks_ready && self.passed_over < STARVATION_LIMIT {
+            self.passed_over += 1;
+            return Vec::new();
+        }
+        self.passed_over = 0;
+        self.immediates.drain(..).map(|(_, task)| task).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vm::TaskPriority;
+    use crate::vm::value::JsValue;
+    use std::time::Duration;
+
+    fn task(n: usize) -> Task {
+        Task {
+            function_ptr: JsValue::NativeFunction(n),
+            args: vec![],
+        }
+    }
+
+    fn ids(tasks: Vec<Task>) -> Vec<usize> {
+        tasks
+            .into_iter()
+            .filter_map(|task| match task.function_ptr {
+                JsValue::NativeFunction(n) => Some(n),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_completions_wake_the_waiting_loop() {
+        let mut scheduler = Scheduler::new();
+        let done = scheduler.completion();
+        let dropped = scheduler.completion();
+        assert_eq!(scheduler.outstanding(), 2);
+
+        let worker = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(10));
+            done.complete(|vm| vm.queue_task(JsValue::Null, vec![], TaskPriority::Low));
+            drop(dropped);
+        });
+        let started = Instant::now();
+        let mut ready = scheduler.wait(None);
+        worker.join().unwrap();
+        ready.extend(scheduler.ready());
+        assert!(started.elapsed() >= Duration::from_millis(10));
+        assert_eq!(ready.len(), 1);
+        assert_eq!(scheduler.outstanding(), 0);
+
+        let mut vm = VM::new_bare();
+        for on_complete in ready {
+            on_complete(&mut vm);
+        }
+        assert_eq!(vm.task_queue.len(), 1);
+
+        let deadline = Instant::now() + Duration::from_millis(5);
+        assert!(scheduler.wait(Some(deadline)).is_empty());
+        assert!(Instant::now() >= deadline);
+    }
+
+    #[test]
+    fn test_immediates_run_in_batches_without_starving() {
+        let mut scheduler = Scheduler::new();
+        let first = scheduler.set_immediate(task(1));
+        scheduler.set_immediate(task(2));
+        let cleared = scheduler.set_immediate(task(3));
+        assert!(scheduler.clear_immediate(cleared));
+        assert!(!scheduler.clear_immediate(cleared));
+        assert_ne!(first, cleared);
+
+        for _ in 0..STARVATION_LIMIT {
+            assert!(scheduler.due_immediates(true).is_empty());
+        }
+        assert_eq!(ids(scheduler.due_immediates(true)), vec![1, 2]);
+        assert!(scheduler.due_immediates(false).is_empty());
+
+        scheduler.set_immediate(task(4));
+        assert_eq!(ids(scheduler.due_immediates(false)), vec![4]);
+    }
+}
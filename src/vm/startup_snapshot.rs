@@ -0,0 +1,1105 @@
+//! Startup snapshots (`oitec snapshot`, `oitec --snapshot <file>`)
+//!
+//! Every run compiles and executes the prelude, and bootstrap or compiler
+//! sources also the compiler modules, before its own script. A snapshot
+//! saves the VM as it stands after that (program, globals, heap, loaded
+//! modules) so a later run can restore it instead:
+//!
+//! ```text
+//! oitec snapshot -o prelude.otsnap
+//! oitec --snapshot prelude.otsnap app.ot
+//! ```
+//!
+//! Natives are saved as their position among the natives a fresh VM
+//! registers, so a snapshot only loads into the build that wrote it; the
+//! header records the version and native count and anything else is
+//! refused. State that lives outside the heap (timers, queued tasks, open
+//! streams, child processes, pending promises) can't be saved, and a
+//! snapshot is refused while any of it is in use.
+//!
+//! The format is little-endian with LEB128 integers and length-prefixed
+//! UTF-8 strings, like the bytecode files in `loader`.
+
+use super::module_cache::CachedModule;
+use super::opcodes::OpCode;
+use super::value::{BufferStorage, BufferView, HeapData, HeapObject, JsValue, Native};
+use super::{ImportedBinding, ModuleScope, VM};
+use crate::stdlib::decimal::Decimal;
+use crate::vm::descriptors::PropertyFlags;
+use crate::vm::heap::Heap;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+/// Magic bytes opening a snapshot file
+pub const MAGIC: &[u8; 6] = b"OTSNAP";
+
+/// Snapshot format version
+pub const VERSION: u32 = 1;
+
+/// Extension of snapshot files
+pub const EXTENSION: &str = "otsnap";
+
+/// The state of `vm` as a snapshot. `vm` must be idle: no script running
+/// and nothing queued.
+pub fn save(vm: &VM) -> Result<Vec<u8>, String> {
+    if !vm.task_queue.is_empty()
+        || !vm.timers.is_empty()
+        || vm.scheduler.outstanding() > 0
+        || vm.scheduler.microtasks() > 0
+        || vm.scheduler.immediates() > 0
+    {
+        return Err("cannot snapshot a VM with timers or tasks still pending".to_string());
+    }
+    if vm.process.has_listeners() {
+        return Err("cannot snapshot a VM with signal listeners".to_string());
+    }
+
+    let base = VM::new().native_functions;
+    let mut w = Writer::default();
+    w.bytes.extend_from_slice(MAGIC);
+    w.u32(VERSION);
+    w.str(env!("CARGO_PKG_VERSION"));
+    w.usize(base.len());
+
+    w.usize(vm.native_functions.len());
+    for native in &vm.native_functions {
+        let index = base
+            .iter()
+            .position(|known| same_native(known, native))
+            .ok_or_else(|| {
+                format!(
+                    "cannot snapshot {}, a native created while the scripts ran",
+                    native_name(native)
+                )
+            })?;
+        w.usize(index);
+    }
+
+    w.program(&vm.program)?;
+
+    let (slots, free) = vm.heap.slots();
+    let slots: Vec<_> = slots.collect();
+    w.usize(slots.len());
+    for (generation, object) in slots {
+        w.usize(generation);
+        match object {
+            Some(object) => {
+                w.u8(1);
+                w.heap_data(&object.data)?;
+            }
+            None => w.u8(0),
+        }
+    }
+    w.usize(free.len());
+    for &slot in free {
+        w.usize(slot);
+    }
+
+    let globals = &vm.call_stack[0];
+    w.map(&globals.locals)?;
+    w.values(&globals.indexed_locals)?;
+    w.map(&vm.modules)?;
+    w.opt_usize(vm.process.env);
+
+    w.usize(vm.module_cache.entries.len());
+    for module in vm.module_cache.entries.values() {
+        w.path(&module.path);
+        w.str(&module.source);
+        w.str(&module.hash);
+        w.usize(module.namespace_object);
+    }
+    w.usize(vm.module_scopes.len());
+    for scope in &vm.module_scopes {
+        w.path(&scope.path);
+        w.usize(scope.code.start);
+        w.usize(scope.code.end);
+        w.usize(scope.environment);
+        w.usize(scope.namespace);
+        w.usize(scope.exports.len());
+        for (name, exported) in &scope.exports {
+            w.str(name);
+            w.strings(exported);
+        }
+    }
+    w.usize(vm.imported_bindings.len());
+    for (scope, bindings) in &vm.imported_bindings {
+        w.opt_usize(*scope);
+        w.usize(bindings.len());
+        for (local, binding) in bindings {
+            w.str(local);
+            w.usize(binding.namespace);
+            w.str(&binding.name);
+        }
+    }
+    w.usize(vm.reexports.len());
+    for ((from, name), targets) in &vm.reexports {
+        w.usize(*from);
+        w.str(name);
+        w.usize(targets.len());
+        for (to, exported) in targets {
+            w.usize(*to);
+            w.str(exported);
+        }
+    }
+    w.usize(vm.import_metas.len());
+    for (path, meta) in &vm.import_metas {
+        w.path(path);
+        w.usize(*meta);
+    }
+
+    let entries: Vec<_> = vm.descriptors.entries().collect();
+    w.usize(entries.len());
+    for (ptr, key, flags) in entries {
+        w.usize(ptr);
+        w.str(key);
+        w.u8(flags.writable as u8
+            | ((flags.enumerable as u8) << 1)
+            | ((flags.configurable as u8) << 2));
+    }
+    let sealed: Vec<usize> = vm.descriptors.non_extensible().collect();
+    w.usize(sealed.len());
+    for ptr in sealed {
+        w.usize(ptr);
+    }
+    Ok(w.bytes)
+}
+
+/// Replace the program, globals, heap and modules of `vm` with those of a
+/// snapshot. `vm` must have its stdlib set up, which provides the natives.
+pub fn restore(vm: &mut VM, bytes: &[u8]) -> Result<(), String> {
+    let mut r = Reader::new(bytes);
+    if r.take(MAGIC.len(), "magic")? != MAGIC {
+        return Err("not a snapshot file".to_string());
+    }
+    let version = r.u32()?;
+    let made_by = r.str()?;
+    let base_len = r.usize()?;
+    if version != VERSION
+        || made_by != env!("CARGO_PKG_VERSION")
+        || base_len > vm.native_functions.len()
+    {
+        return Err(format!(
+            "snapshot was made by oitec {} and does not fit this build ({}); make it again with `oitec snapshot`",
+            made_by,
+            env!("CARGO_PKG_VERSION")
+        ));
+    }
+
+    let natives = (0..r.usize()?)
+        .map(|_| {
+            let index = r.usize()?;
+            (index < base_len)
+                .then(|| vm.native_functions[index])
+                .ok_or_else(|| format!("snapshot names unknown native {}", index))
+        })
+        .collect::<Result<Vec<Native>, String>>()?;
+
+    let program = r.program()?;
+
+    let mut storages = Vec::new();
+    let slots = (0..r.usize()?)
+        .map(|_| {
+            let generation = r.usize()?;
+            let object = match r.u8()? {
+                0 => None,
+                _ => Some(HeapObject {
+                    data: r.heap_data(&mut storages)?,
+                }),
+            };
+            Ok((generation, object))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    let free = (0..r.usize()?)
+        .map(|_| r.usize())
+        .collect::<Result<Vec<_>, String>>()?;
+
+    let locals = r.map()?;
+    let indexed_locals = r.values()?;
+    let modules = r.map()?;
+    let env = r.opt_usize()?;
+
+    let mut cached = Vec::new();
+    for _ in 0..r.usize()? {
+        cached.push(CachedModule {
+            path: r.path()?,
+            source: r.str()?,
+            hash: r.str()?,
+            load_time: SystemTime::now(),
+            namespace_object: r.usize()?,
+        });
+    }
+    let mut module_scopes = Vec::new();
+    for _ in 0..r.usize()? {
+        let path = r.path()?;
+        let code = r.usize()?..r.usize()?;
+        let environment = r.usize()?;
+        let namespace = r.usize()?;
+        let mut exports = HashMap::new();
+        for _ in 0..r.usize()? {
+            exports.insert(r.str()?, r.strings()?);
+        }
+        module_scopes.push(ModuleScope {
+            path,
+            code,
+            environment,
+            namespace,
+            exports,
+        });
+    }
+    let mut imported_bindings = HashMap::new();
+    for _ in 0..r.usize()? {
+        let scope = r.opt_usize()?;
+        let mut bindings = HashMap::new();
+        for _ in 0..r.usize()? {
+            let local = r.str()?;
+            let namespace = r.usize()?;
+            let name = r.str()?;
+            bindings.insert(local, ImportedBinding { namespace, name });
+        }
+        imported_bindings.insert(scope, bindings);
+    }
+    let mut reexports = HashMap::new();
+    for _ in 0..r.usize()? {
+        let key = (r.usize()?, r.str()?);
+        let mut targets = Vec::new();
+        for _ in 0..r.usize()? {
+            targets.push((r.usize()?, r.str()?));
+        }
+        reexports.insert(key, targets);
+    }
+    let mut import_metas = HashMap::new();
+    for _ in 0..r.usize()? {
+        import_metas.insert(r.path()?, r.usize()?);
+    }
+    let mut descriptors = super::descriptors::DescriptorTable::new();
+    for _ in 0..r.usize()? {
+        let ptr = r.usize()?;
+        let key = r.str()?;
+        let bits = r.u8()?;
+        let flags = PropertyFlags {
+            writable: bits & 1 != 0,
+            enumerable: bits & 2 != 0,
+            configurable: bits & 4 != 0,
+        };
+        descriptors.set_flags(ptr, &key, flags);
+    }
+    for _ in 0..r.usize()? {
+        descriptors.prevent_extensions(r.usize()?);
+    }
+    if !r.finished() {
+        return Err("snapshot has trailing bytes".to_string());
+    }
+
+    vm.native_functions = natives;
+    vm.program = Arc::new(program);
+    vm.ip = vm.program.len();
+    vm.heap = Heap::from_slots(slots, free);
+    vm.stack.clear();
+    vm.call_stack.truncate(1);
+    vm.call_stack[0].locals = locals;
+    vm.call_stack[0].indexed_locals = indexed_locals;
+    vm.modules = modules;
+    vm.process.env = env;
+    vm.module_cache = super::ModuleCache::new();
+    for module in cached {
+        vm.module_cache.insert(module);
+    }
+    vm.module_scopes = module_scopes;
+    vm.imported_bindings = imported_bindings;
+    vm.reexports = reexports;
+    vm.import_metas = import_metas;
+    vm.descriptors = descriptors;
+    vm.current_module_path = None;
+    vm.refresh_pinned_globals();
+    super::stdlib_setup::refresh_runtime_features(vm);
+    Ok(())
+}
+
+fn native_name(native: &Native) -> &'static str {
+    match native {
+        Native::Fallible(name, _) => name,
+        Native::Plain(_) => "a function",
+    }
+}
+
+fn same_native(a: &Native, b: &Native) -> bool {
+    match (a, b) {
+        (Native::Plain(a), Native::Plain(b)) => *a as usize == *b as usize,
+        (Native::Fallible(a_name, a), Native::Fallible(b_name, b)) => {
+            a_name == b_name && *a as usize == *b as usize
+        }
+        _ => false,
+    }
+}
+
+/// Encodes the values of a snapshot; bundles, the bytecode cache and
+/// bytecode files (`loader`) use it for their programs too
+#[derive(Default)]
+pub(crate) struct Writer {
+    pub(crate) bytes: Vec<u8>,
+    /// Buffer storage already written, by address, so views keep sharing it
+    storages: HashMap<*const BufferStorage, usize>,
+    /// With a pool, strings are written as their index in it
+    pool: Option<StringPool>,
+}
+
+/// Strings in the order they were first written, for a constant pool
+#[derive(Default)]
+pub(crate) struct StringPool {
+    pub(crate) strings: Vec<String>,
+    index: HashMap<String, usize>,
+}
+
+impl StringPool {
+    fn intern(&mut self, s: &str) -> usize {
+        if let Some(&index) = self.index.get(s) {
+            return index;
+        }
+        self.strings.push(s.to_string());
+        self.index.insert(s.to_string(), self.strings.len() - 1);
+        self.strings.len() - 1
+    }
+}
+
+impl Writer {
+    /// A writer collecting strings into a pool, taken with `take_pool`
+    pub(crate) fn pooled() -> Self {
+        Self {
+            pool: Some(StringPool::default()),
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn take_pool(&mut self) -> Vec<String> {
+        self.pool
+            .take()
+            .map(|pool| pool.strings)
+            .unwrap_or_default()
+    }
+
+    fn u8(&mut self, byte: u8) {
+        self.bytes.push(byte);
+    }
+
+    fn u32(&mut self, n: u32) {
+        self.bytes.extend_from_slice(&n.to_le_bytes());
+    }
+
+    pub(crate) fn usize(&mut self, mut n: usize) {
+        loop {
+            let byte = (n & 0x7f) as u8;
+            n >>= 7;
+            if n == 0 {
+                self.u8(byte);
+                return;
+            }
+            self.u8(byte | 0x80);
+        }
+    }
+
+    fn opt_usize(&mut self, n: Option<usize>) {
+        match n {
+            Some(n) => {
+                self.u8(1);
+                self.usize(n);
+            }
+            None => self.u8(0),
+        }
+    }
+
+    fn bool(&mut self, b: bool) {
+        self.u8(b as u8);
+    }
+
+    pub(crate) fn bytes(&mut self, bytes: &[u8]) {
+        self.usize(bytes.len());
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    pub(crate) fn str(&mut self, s: &str) {
+        match self.pool.as_mut().map(|pool| pool.intern(s)) {
+            Some(index) => self.usize(index),
+            None => self.bytes(s.as_bytes()),
+        }
+    }
+
+    pub(crate) fn strings(&mut self, strings: &[String]) {
+        self.usize(strings.len());
+        for s in strings {
+            self.str(s);
+        }
+    }
+
+    pub(crate) fn path(&mut self, path: &std::path::Path) {
+        self.str(&path.to_string_lossy());
+    }
+
+    fn value(&mut self, value: &JsValue) -> Result<(), String> {
+        match value {
+            JsValue::Number(n) => {
+                self.u8(0);
+                self.bytes.extend_from_slice(&n.to_le_bytes());
+            }
+            JsValue::String(s) => {
+                self.u8(1);
+                self.str(s);
+            }
+            JsValue::Boolean(b) => {
+                self.u8(2);
+                self.bool(*b);
+            }
+            JsValue::Object(ptr) => {
+                self.u8(3);
+                self.usize(*ptr);
+            }
+            JsValue::Function {
+                address,
+                env,
+                bound,
+            } => {
+                self.u8(4);
+                self.usize(*address);
+                self.opt_usize(*env);
+                self.opt_usize(*bound);
+            }
+            JsValue::NativeFunction(idx) => {
+                self.u8(5);
+                self.usize(*idx);
+            }
+            JsValue::Null => self.u8(6),
+            JsValue::Undefined => self.u8(7),
+            JsValue::Accessor(get, set) => {
+                self.u8(8);
+                for half in [get, set] {
+                    match half {
+                        Some(f) => {
+                            self.u8(1);
+                            self.value(f)?;
+                        }
+                        None => self.u8(0),
+                    }
+                }
+            }
+            JsValue::Promise(_) => return Err("cannot snapshot a promise".to_string()),
+        }
+        Ok(())
+    }
+
+    fn values(&mut self, values: &[JsValue]) -> Result<(), String> {
+        self.usize(values.len());
+        values.iter().try_for_each(|value| self.value(value))
+    }
+
+    /// Keys are sorted so the same state always writes the same bytes
+    fn map(&mut self, map: &HashMap<String, JsValue>) -> Result<(), String> {
+        let mut entries: Vec<_> = map.iter().collect();
+        entries.sort_by_key(|(key, _)| *key);
+        self.usize(entries.len());
+        for (key, value) in entries {
+            self.str(key);
+            self.value(value)?;
+        }
+        Ok(())
+    }
+
+    fn heap_data(&mut self, data: &HeapData) -> Result<(), String> {
+        match data {
+            HeapData::Object(props) => {
+                self.u8(0);
+                self.map(props)?;
+            }
+            HeapData::Array(items) => {
+                self.u8(1);
+                self.values(items)?;
+            }
+            HeapData::ByteStream(bytes) => {
+                self.u8(2);
+                self.bytes(bytes);
+            }
+            HeapData::Map(entries) => {
+                self.u8(3);
+                self.usize(entries.len());
+                for (key, value) in entries {
+                    self.value(key)?;
+                    self.value(value)?;
+                }
+            }
+            HeapData::Set(items) => {
+                self.u8(4);
+                self.values(items)?;
+            }
+            HeapData::Buffer(view) => {
+                self.u8(5);
+                let key = Arc::as_ptr(&view.storage);
+                match self.storages.get(&key) {
+                    Some(&id) => {
+                        self.u8(0);
+                        self.usize(id);
+                    }
+                    None => {
+                        let id = self.storages.len();
+                        self.storages.insert(key, id);
+                        self.u8(1);
+                        self.bytes(view.storage.as_slice());
+                    }
+                }
+                self.usize(view.offset);
+                self.usize(view.len);
+            }
+            HeapData::Decimal(decimal) => {
+                self.u8(6);
+                self.str(&decimal.to_string());
+            }
+            HeapData::WeakMap(entries) => {
+                self.u8(7);
+                self.usize(entries.len());
+                for (key, value) in entries {
+                    self.usize(*key);
+                    self.value(value)?;
+                }
+            }
+            HeapData::WeakSet(members) => {
+                self.u8(8);
+                self.usize(members.len());
+                for member in members {
+                    self.usize(*member);
+                }
+            }
+            HeapData::WeakRef(target) => {
+                self.u8(9);
+                self.usize(*target);
+            }
+            HeapData::Stream(_) => return Err("cannot snapshot an open stream".to_string()),
+            HeapData::Process(_) => return Err("cannot snapshot a child process".to_string()),
+        }
+        Ok(())
+    }
+
+    pub(crate) fn program(&mut self, program: &[OpCode]) -> Result<(), String> {
+        self.usize(program.len());
+        program.iter().try_for_each(|op| self.op(op))
+    }
+
+    pub(crate) fn op(&mut self, op: &OpCode) -> Result<(), String> {
+        use OpCode::*;
+        let tag = match op {
+            LoadThis => 0,
+            Push(_) => 1,
+            Add => 2,
+            Sub => 3,
+            Print => 4,
+            Pop => 5,
+            Let(_) => 6,
+            Store(_) => 7,
+            Load(_) => 8,
+            Drop(_) => 9,
+            Call(_) => 10,
+            LoadArg(_) => 11,
+            LoadRestArgs(_) => 12,
+            Return => 13,
+            ReturnMulti(_) => 14,
+            Unpack(_) => 15,
+            Jump(_) => 16,
+            NewObject => 17,
+            NewObjectWithProto => 18,
+            SetProp(_) => 19,
+            GetProp(_) => 20,
+            SetPropComputed => 21,
+            GetPropComputed => 22,
+            Dup => 23,
+            Swap => 24,
+            Swap3 => 25,
+            Eq => 26,
+            EqEq => 27,
+            Ne => 28,
+            NeEq => 29,
+            Lt => 30,
+            LtEq => 31,
+            Gt => 32,
+            GtEq => 33,
+            Mod => 34,
+            And => 35,
+            Or => 36,
+            Not => 37,
+            Neg => 38,
+            TypeOf => 39,
+            Delete(_) => 40,
+            NewArray(_) => 41,
+            StoreElement => 42,
+            LoadElement => 43,
+            ArrayPush => 44,
+            ArraySpread => 45,
+            ObjectSpread => 46,
+            JumpIfFalse(_) => 47,
+            Halt => 48,
+            CallMethod(..) => 49,
+            Mul => 50,
+            Div => 51,
+            Require => 52,
+            MakeClosure(_) => 53,
+            Construct(_) => 54,
+            StoreLocal(_) => 55,
+            LoadLocal(_) => 56,
+            LoadGlobal(_) => 57,
+            BitAnd => 58,
+            BitOr => 59,
+            Xor => 60,
+            ShiftLeft => 61,
+            ShiftRight => 62,
+            ShiftRightUnsigned => 63,
+            Pow => 64,
+            Throw => 65,
+            SetupTry { .. } => 66,
+            PopTry => 67,
+            EnterFinally(_) => 68,
+            SetProto => 69,
+            LoadSuper => 70,
+            CallSuper(_) => 71,
+            GetSuperProp(_) => 72,
+            GetPrivateProp(_) => 73,
+            SetPrivateProp(_) => 74,
+            CallPrivateMethod(..) => 75,
+            HasPrivateProp(_) => 76,
+            NewWeakMap => 77,
+            InstanceOf => 78,
+            In => 79,
+            NewTarget => 80,
+            ApplyDecorator => 81,
+            ImportAsync { .. } => 82,
+            Await => 83,
+            GetExport { .. } => 84,
+            ImportMeta => 85,
+            ImportBinding { .. } => 86,
+            ReExport { .. } => 87,
+            ReExportAll => 88,
+            ModuleResolutionError { .. } => 89,
+        };
+        self.u8(tag);
+        match op {
+            Push(value) => self.value(value)?,
+            Let(name) | Store(name) | Load(name) | Drop(name) | SetProp(name) | GetProp(name)
+            | Delete(name) | GetSuperProp(name) => self.str(name),
+            Call(n) | LoadArg(n) | LoadRestArgs(n) | ReturnMulti(n) | Unpack(n) | Jump(n)
+            | NewArray(n) | JumpIfFalse(n) | MakeClosure(n) | Construct(n) | CallSuper(n)
+            | GetPrivateProp(n) | SetPrivateProp(n) | HasPrivateProp(n) => self.usize(*n),
+            StoreLocal(slot) | LoadLocal(slot) | LoadGlobal(slot) => self.usize(*slot as usize),
+            CallMethod(name, argc) => {
+                self.str(name);
+                self.usize(*argc);
+            }
+            SetupTry {
+                catch_addr,
+                finally_addr,
+            } => {
+                self.usize(*catch_addr);
+                self.usize(*finally_addr);
+            }
+            EnterFinally(rethrow) => self.bool(*rethrow),
+            CallPrivateMethod(slot, argc) => {
+                self.usize(*slot);
+                self.usize(*argc);
+            }
+            ImportAsync { specifier, json } => {
+                self.str(specifier);
+                self.bool(*json);
+            }
+            GetExport { name, is_default } => {
+                self.str(name);
+                self.bool(*is_default);
+            }
+            ImportBinding { name, local } => {
+                self.str(name);
+                self.str(local);
+            }
+            ReExport { name, exported } => {
+                self.str(name);
+                self.str(exported);
+            }
+            ModuleResolutionError {
+                message,
+                specifier,
+                importer,
+                dependency_chain,
+            } => {
+                self.str(message);
+                self.str(specifier);
+                self.str(importer);
+                self.strings(dependency_chain);
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+pub(crate) struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    /// Strings written by a pooled `Writer`
+    pool: Option<&'a [String]>,
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> Self {
+        Self {
+            bytes,
+            pos: 0,
+            pool: None,
+        }
+    }
+
+    /// A reader for what a pooled `Writer` wrote, given its pool
+    pub(crate) fn with_pool(bytes: &'a [u8], pool: &'a [String]) -> Self {
+        Self {
+            pool: Some(pool),
+            ..Self::new(bytes)
+        }
+    }
+
+    /// Whether every byte has been read
+    pub(crate) fn finished(&self) -> bool {
+        self.pos == self.bytes.len()
+    }
+
+    pub(crate) fn take(&mut self, n: usize, reading: &str) -> Result<&'a [u8], String> {
+        let end = self
+            .pos
+            .checked_add(n)
+            .filter(|&end| end <= self.bytes.len())
+            .ok_or_else(|| format!("snapshot is truncated (reading {})", reading))?;
+        let bytes = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1, "a byte")?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let bytes = self.take(4, "a u32")?;
+        Ok(u32::from_le_bytes(bytes.try_into().unwrap_or_default()))
+    }
+
+    pub(crate) fn usize(&mut self) -> Result<usize, String> {
+        let mut n = 0usize;
+        for shift in (0..usize::BITS).step_by(7) {
+            let byte = self.u8()?;
+            n |= ((byte & 0x7f) as usize) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(n);
+            }
+        }
+        Err("snapshot has an overlong integer".to_string())
+    }
+
+    pub(crate) fn u32_value(&mut self) -> Result<u32, String> {
+        u32::try_from(self.usize()?).map_err(|_| "snapshot has an out-of-range slot".to_string())
+    }
+
+    fn opt_usize(&mut self) -> Result<Option<usize>, String> {
+        match self.u8()? {
+            0 => Ok(None),
+            _ => self.usize().map(Some),
+        }
+    }
+
+    fn bool(&mut self) -> Result<bool, String> {
+        Ok(self.u8()? != 0)
+    }
+
+    pub(crate) fn byte_vec(&mut self) -> Result<Vec<u8>, String> {
+        let len = self.usize()?;
+        Ok(self.take(len, "bytes")?.to_vec())
+    }
+
+    pub(crate) fn str(&mut self) -> Result<String, String> {
+        if let Some(pool) = self.pool {
+            let index = self.usize()?;
+            return pool
+                .get(index)
+                .cloned()
+                .ok_or_else(|| format!("unknown constant {}", index));
+        }
+        String::from_utf8(self.byte_vec()?).map_err(|_| "snapshot has invalid UTF-8".to_string())
+    }
+
+    pub(crate) fn strings(&mut self) -> Result<Vec<String>, String> {
+        (0..self.usize()?).map(|_| self.str()).collect()
+    }
+
+    pub(crate) fn path(&mut self) -> Result<PathBuf, String> {
+        self.str().map(PathBuf::from)
+    }
+
+    fn value(&mut self) -> Result<JsValue, String> {
+        Ok(match self.u8()? {
+            0 => {
+                let bytes = self.take(8, "a number")?;
+                JsValue::Number(f64::from_le_bytes(bytes.try_into().unwrap_or_default()))
+            }
+            1 => JsValue::String(self.str()?),
+            2 => JsValue::Boolean(self.bool()?),
+            3 => JsValue::Object(self.usize()?),
+            4 => JsValue::Function {
+                address: self.usize()?,
+                env: self.opt_usize()?,
+                bound: self.opt_usize()?,
+            },
+            5 => JsValue::NativeFunction(self.usize()?),
+            6 => JsValue::Null,
+            7 => JsValue::Undefined,
+            8 => {
+                let mut half = || -> Result<Option<Box<JsValue>>, String> {
+                    match self.u8()? {
+                        0 => Ok(None),
+                        _ => Ok(Some(Box::new(self.value()?))),
+                    }
+                };
+                let get = half()?;
+                JsValue::Accessor(get, half()?)
+            }
+            tag => return Err(format!("snapshot has unknown value tag {}", tag)),
+        })
+    }
+
+    fn values(&mut self) -> Result<Vec<JsValue>, String> {
+        (0..self.usize()?).map(|_| self.value()).collect()
+    }
+
+    fn map(&mut self) -> Result<HashMap<String, JsValue>, String> {
+        (0..self.usize()?)
+            .map(|_| Ok((self.str()?, self.value()?)))
+            .collect()
+    }
+
+    fn heap_data(&mut self, storages: &mut Vec<Arc<BufferStorage>>) -> Result<HeapData, String> {
+        Ok(match self.u8()? {
+            0 => HeapData::Object(self.map()?),
+            1 => HeapData::Array(self.values()?),
+            2 => HeapData::ByteStream(self.byte_vec()?),
+            3 => HeapData::Map(
+                (0..self.usize()?)
+                    .map(|_| Ok((self.value()?, self.value()?)))
+                    .collect::<Result<_, String>>()?,
+            ),
+            4 => HeapData::Set(self.values()?),
+            5 => {
+                let storage = match self.u8()? {
+                    0 => storages
+                        .get(self.usize()?)
+                        .cloned()
+                        .ok_or_else(|| "snapshot names an unknown buffer".to_string())?,
+                    _ => {
+                        let storage = Arc::new(BufferStorage::Owned(self.byte_vec()?));
+                        storages.push(storage.clone());
+                        storage
+                    }
+                };
+                HeapData::Buffer(BufferView {
+                    storage,
+                    offset: self.usize()?,
+                    len: self.usize()?,
+                })
+            }
+            6 => HeapData::Decimal(Decimal::parse(&self.str()?)?),
+            7 => HeapData::WeakMap(
+                (0..self.usize()?)
+                    .map(|_| Ok((self.usize()?, self.value()?)))
+                    .collect::<Result<_, String>>()?,
+            ),
+            8 => HeapData::WeakSet(
+                (0..self.usize()?)
+                    .map(|_| self.usize())
+                    .collect::<Result<HashSet<usize>, String>>()?,
+            ),
+            9 => HeapData::WeakRef(self.usize()?),
+            tag => return Err(format!("snapshot has unknown object tag {}", tag)),
+        })
+    }
+
+    pub(crate) fn program(&mut self) -> Result<Vec<OpCode>, String> {
+        (0..self.usize()?).map(|_| self.op()).collect()
+    }
+
+    pub(crate) fn op(&mut self) -> Result<OpCode, String> {
+        use OpCode::*;
+        Ok(match self.u8()? {
+            0 => LoadThis,
+            1 => Push(self.value()?),
+            2 => Add,
+            3 => Sub,
+            4 => Print,
+            5 => Pop,
+            6 => Let(self.str()?),
+            7 => Store(self.str()?),
+            8 => Load(self.str()?),
+            9 => Drop(self.str()?),
+            10 => Call(self.usize()?),
+            11 => LoadArg(self.usize()?),
+            12 => LoadRestArgs(self.usize()?),
+            13 => Return,
+            14 => ReturnMulti(self.usize()?),
+            15 => Unpack(self.usize()?),
+            16 => Jump(self.usize()?),
+            17 => NewObject,
+            18 => NewObjectWithProto,
+            19 => SetProp(self.str()?),
+            20 => GetProp(self.str()?),
+            21 => SetPropComputed,
+            22 => GetPropComputed,
+            23 => Dup,
+            24 => Swap,
+            25 => Swap3,
+            26 => Eq,
+            27 => EqEq,
+            28 => Ne,
+            29 => NeEq,
+            30 => Lt,
+            31 => LtEq,
+            32 => Gt,
+            33 => GtEq,
+            34 => Mod,
+            35 => And,
+            36 => Or,
+            37 => Not,
+            38 => Neg,
+            39 => TypeOf,
+            40 => Delete(self.str()?),
+            41 => NewArray(self.usize()?),
+            42 => StoreElement,
+            43 => LoadElement,
+            44 => ArrayPush,
+            45 => ArraySpread,
+            46 => ObjectSpread,
+            47 => JumpIfFalse(self.usize()?),
+            48 => Halt,
+            49 => CallMethod(self.str()?, self.usize()?),
+            50 => Mul,
+            51 => Div,
+            52 => Require,
+            53 => MakeClosure(self.usize()?),
+            54 => Construct(self.usize()?),
+            55 => StoreLocal(self.u32_value()?),
+            56 => LoadLocal(self.u32_value()?),
+            57 => LoadGlobal(self.u32_value()?),
+            58 => BitAnd,
+            59 => BitOr,
+            60 => Xor,
+            61 => ShiftLeft,
+            62 => ShiftRight,
+            63 => ShiftRightUnsigned,
+            64 => Pow,
+            65 => Throw,
+            66 => SetupTry {
+                catch_addr: self.usize()?,
+                finally_addr: self.usize()?,
+            },
+            67 => PopTry,
+            68 => EnterFinally(self.bool()?),
+            69 => SetProto,
+            70 => LoadSuper,
+            71 => CallSuper(self.usize()?),
+            72 => GetSuperProp(self.str()?),
+            73 => GetPrivateProp(self.usize()?),
+            74 => SetPrivateProp(self.usize()?),
+            75 => CallPrivateMethod(self.usize()?, self.usize()?),
+            76 => HasPrivateProp(self.usize()?),
+            77 => NewWeakMap,
+            78 => InstanceOf,
+            79 => In,
+            80 => NewTarget,
+            81 => ApplyDecorator,
+            82 => ImportAsync {
+                specifier: self.str()?,
+                json: self.bool()?,
+            },
+            83 => Await,
+            84 => GetExport {
+                name: self.str()?,
+                is_default: self.bool()?,
+            },
+            85 => ImportMeta,
+            86 => ImportBinding {
+                name: self.str()?,
+                local: self.str()?,
+            },
+            87 => ReExport {
+                name: self.str()?,
+                exported: self.str()?,
+            },
+            88 => ReExportAll,
+            89 => ModuleResolutionError {
+                message: self.str()?,
+                specifier: self.str()?,
+                importer: self.str()?,
+                dependency_chain: self.strings()?,
+            },
+            tag => return Err(format!("snapshot has unknown opcode {}", tag)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(vm: &mut VM, source: &str) {
+        let bytecode = crate::compiler::Compiler::new().compile(source).unwrap();
+        vm.append_program(bytecode);
+        vm.run_event_loop();
+    }
+
+    #[test]
+    fn test_restored_vm_keeps_globals_and_heap() {
+        let mut vm = VM::new();
+        run(
+            &mut vm,
+            "let counter = { n: 1 };
+             function bump(by) { counter.n += by; return counter.n; }
+             let seen = new Map();
+             seen.set('a', [1, 2]);
+             const frozen = Object.freeze({ x: 1 });
+             let price = Decimal('1.50');
+             class Point { #x; constructor(x) { this.#x = x; } get x() { return this.#x; } }",
+        );
+        let bytes = save(&vm).unwrap();
+        assert_eq!(save(&vm).unwrap(), bytes);
+
+        let mut restored = VM::new();
+        restore(&mut restored, &bytes).unwrap();
+        run(
+            &mut restored,
+            "let total = bump(2);
+             let pair = seen.get('a')[1];
+             let isFrozen = Object.isFrozen(frozen);
+             let cents = price.toString();
+             let x = new Point(7).x;",
+        );
+        let globals = &restored.call_stack[0].locals;
+        assert_eq!(globals.get("total"), Some(&JsValue::Number(3.0)));
+        assert_eq!(globals.get("pair"), Some(&JsValue::Number(2.0)));
+        assert_eq!(globals.get("isFrozen"), Some(&JsValue::Boolean(true)));
+        assert_eq!(
+            globals.get("cents"),
+            Some(&JsValue::String("1.50".to_string()))
+        );
+        assert_eq!(globals.get("x"), Some(&JsValue::Number(7.0)));
+    }
+
+    #[test]
+    fn test_refuses_foreign_or_busy_state() {
+        let mut vm = VM::new();
+        assert!(restore(&mut vm, b"OTSNAP").is_err());
+        assert!(restore(&mut vm, b"not a snapshot").is_err());
+
+        let mut bytes = save(&vm).unwrap();
+        bytes.push(0);
+        assert!(restore(&mut vm, &bytes).unwrap_err().contains("trailing"));
+
+        vm.queue_task(JsValue::Null, vec![], crate::vm::TaskPriority::Low);
+        assert!(save(&vm).unwrap_err().contains("pending"));
+    }
+}
@@ -4,32 +4,78 @@
 //! - console (log, error)
 //! - ByteStream (binary serialization)
 //! - String.fromCharCode
+//! - Number, Boolean, parseInt, parseFloat, isNaN, isFinite
+//! - Object.keys, Object.entries, Object.defineProperty,
+//!   Object.getOwnPropertyDescriptor, Object.freeze, Object.isFrozen,
+//!   structuredClone
+//! - Symbol.operator (keys for operator overloading)
+//! - Decimal (exact base-10 arithmetic)
+//! - Math (clz32, imul, fround, sqrt, abs, floor, ceil, trunc) and
+//!   std.intrinsics (bit counts and float bit casts)
+//! - std.simd (f64x2, f32x4 and i32x4 vectors)
+//! - WeakMap, WeakSet, WeakRef
+//! - TextEncoder, TextDecoder, Buffer (from, alloc, concat, isBuffer)
 //! - require (module loading)
-//! - fs (minimal file I/O for bootstrap compiler)
+//! - setImmediate, clearImmediate, queueMicrotask
+//! - Promise.resolve (wraps async function results)
+//! - util (inspect)
+//! - permissions.query (which host capabilities are granted)
+//! - fs (minimal file I/O for bootstrap compiler, plus read/write streams)
+//! - process (env, stdin/stdout, exec, spawn)
+//! - runtime.features (what this build supports), runtime.eventLoop(),
+//!   runtime.queueTask()
 
-use crate::vm::VM;
-use crate::vm::value::{HeapData, HeapObject, JsValue};
+use crate::vm::permissions::{Capability, PermissionState};
+use crate::vm::value::{FallibleNativeFn, HeapData, HeapObject, JsValue, NativeFn};
+use crate::vm::{BuiltinProto, VM};
 
 pub fn setup_stdlib(vm: &mut VM) {
     setup_console(vm);
     setup_bytestream(vm);
     setup_string(vm);
+    setup_number_boolean(vm);
     setup_fs(vm);
     setup_json(vm);
     setup_globals(vm);
     setup_map_set(vm);
+    setup_promise(vm);
+    setup_array(vm);
     setup_process(vm);
     setup_fetch(vm);
     setup_object(vm);
+    setup_symbol(vm);
+    setup_decimal(vm);
+    setup_math(vm);
+    setup_weak(vm);
+    setup_encoding(vm);
+    setup_util(vm);
+    setup_permissions(vm);
+    setup_prototype_methods(vm);
+    // Last, so it can list everything registered above
+    setup_runtime(vm);
 }
 
 fn setup_console(vm: &mut VM) {
-    let log_idx = vm.register_native(crate::stdlib::native_log);
-    let error_idx = vm.register_native(crate::stdlib::native_error);
+    use crate::stdlib::{
+        native_error, native_log, native_table, native_time, native_time_end, native_time_log,
+    };
+    let log_idx = vm.register_native(native_log);
+    let error_idx = vm.register_native(native_error);
+    let table_idx = vm.register_native(native_table);
+    let time_idx = vm.register_native(native_time);
+    let time_log_idx = vm.register_native(native_time_log);
+    let time_end_idx = vm.register_native(native_time_end);
     let console_ptr = vm.heap.len();
     let mut console_props = std::collections::HashMap::new();
     console_props.insert("log".to_string(), JsValue::NativeFunction(log_idx));
+    console_props.insert("info".to_string(), JsValue::NativeFunction(log_idx));
+    console_props.insert("debug".to_string(), JsValue::NativeFunction(log_idx));
     console_props.insert("error".to_string(), JsValue::NativeFunction(error_idx));
+    console_props.insert("warn".to_string(), JsValue::NativeFunction(error_idx));
+    console_props.insert("table".to_string(), JsValue::NativeFunction(table_idx));
+    console_props.insert("time".to_string(), JsValue::NativeFunction(time_idx));
+    console_props.insert("timeLog".to_string(), JsValue::NativeFunction(time_log_idx));
+    console_props.insert("timeEnd".to_string(), JsValue::NativeFunction(time_end_idx));
     vm.heap.push(HeapObject {
         data: HeapData::Object(console_props),
     });
@@ -115,6 +161,11 @@ fn setup_string(vm: &mut VM) {
         "__call__".to_string(),
         JsValue::NativeFunction(string_constructor_idx),
     );
+    // `new String(x)` produces the primitive as well
+    string_props.insert(
+        "constructor".to_string(),
+        JsValue::NativeFunction(string_constructor_idx),
+    );
     vm.heap.push(HeapObject {
         data: HeapData::Object(string_props),
     });
@@ -131,6 +182,99 @@ fn setup_string(vm: &mut VM) {
     );
 }
 
+fn setup_number_boolean(vm: &mut VM) {
+    use crate::stdlib::{
+        native_boolean_constructor, native_is_finite, native_is_nan, native_number_constructor,
+        native_number_is_finite, native_number_is_integer, native_number_is_nan,
+        native_parse_float, native_parse_int,
+    };
+
+    let number_idx = vm.register_native(native_number_constructor);
+    let boolean_idx = vm.register_native(native_boolean_constructor);
+    let parse_int_idx = vm.register_native(native_parse_int);
+    let parse_float_idx = vm.register_native(native_parse_float);
+    let is_nan_idx = vm.register_native(native_is_nan);
+    let is_finite_idx = vm.register_native(native_is_finite);
+    let number_is_nan_idx = vm.register_native(native_number_is_nan);
+    let number_is_finite_idx = vm.register_native(native_number_is_finite);
+    let number_is_integer_idx = vm.register_native(native_number_is_integer);
+
+    // Number is callable (conversion) and constructible; wrappers are
+    // represented by their primitive value
+    let number_ptr = vm.heap.len();
+    let mut number_props = std::collections::HashMap::new();
+    number_props.insert("__call__".to_string(), JsValue::NativeFunction(number_idx));
+    number_props.insert(
+        "constructor".to_string(),
+        JsValue::NativeFunction(number_idx),
+    );
+    number_props.insert("NaN".to_string(), JsValue::Number(f64::NAN));
+    number_props.insert(
+        "POSITIVE_INFINITY".to_string(),
+        JsValue::Number(f64::INFINITY),
+    );
+    number_props.insert(
+        "NEGATIVE_INFINITY".to_string(),
+        JsValue::Number(f64::NEG_INFINITY),
+    );
+    number_props.insert(
+        "MAX_SAFE_INTEGER".to_string(),
+        JsValue::Number(9007199254740991.0),
+    );
+    number_props.insert(
+        "MIN_SAFE_INTEGER".to_string(),
+        JsValue::Number(-9007199254740991.0),
+    );
+    number_props.insert("EPSILON".to_string(), JsValue::Number(f64::EPSILON));
+    number_props.insert(
+        "parseInt".to_string(),
+        JsValue::NativeFunction(parse_int_idx),
+    );
+    number_props.insert(
+        "parseFloat".to_string(),
+        JsValue::NativeFunction(parse_float_idx),
+    );
+    number_props.insert(
+        "isNaN".to_string(),
+        JsValue::NativeFunction(number_is_nan_idx),
+    );
+    number_props.insert(
+        "isFinite".to_string(),
+        JsValue::NativeFunction(number_is_finite_idx),
+    );
+    number_props.insert(
+        "isInteger".to_string(),
+        JsValue::NativeFunction(number_is_integer_idx),
+    );
+    vm.heap.push(HeapObject {
+        data: HeapData::Object(number_props),
+    });
+
+    let boolean_ptr = vm.heap.len();
+    let mut boolean_props = std::collections::HashMap::new();
+    boolean_props.insert("__call__".to_string(), JsValue::NativeFunction(boolean_idx));
+    boolean_props.insert(
+        "constructor".to_string(),
+        JsValue::NativeFunction(boolean_idx),
+    );
+    vm.heap.push(HeapObject {
+        data: HeapData::Object(boolean_props),
+    });
+
+    let globals = &mut vm.call_stack[0].locals;
+    globals.insert("Number".into(), JsValue::Object(number_ptr));
+    globals.insert("Boolean".into(), JsValue::Object(boolean_ptr));
+    globals.insert("parseInt".into(), JsValue::NativeFunction(parse_int_idx));
+    globals.insert(
+        "parseFloat".into(),
+        JsValue::NativeFunction(parse_float_idx),
+    );
+    globals.insert("isNaN".into(), JsValue::NativeFunction(is_nan_idx));
+    globals.insert("isFinite".into(), JsValue::NativeFunction(is_finite_idx));
+    globals.insert("NaN".into(), JsValue::Number(f64::NAN));
+    globals.insert("Infinity".into(), JsValue::Number(f64::INFINITY));
+}
+
 fn setup_fs(vm: &mut VM) {
     use crate::stdlib::{
         native_exists_sync, native_mkdir_sync, native_read_file, native_readdir_sync,
@@ -144,6 +288,9 @@ fn setup_fs(vm: &mut VM) {
     let fs_mkdir_sync_idx = vm.register_native(native_mkdir_sync);
     let fs_readdir_sync_idx = vm.register_native(native_readdir_sync);
     let fs_stat_sync_idx = vm.register_native(native_stat_sync);
+    let fs_mmap_idx = vm.register_native(crate::stdlib::buffer::native_mmap);
+    let fs_read_stream_idx = vm.register_native(crate::stdlib::stream::native_create_read_stream);
+    let fs_write_stream_idx = vm.register_native(crate::stdlib::stream::native_create_write_stream);
 
     let fs_ptr = vm.heap.len();
     let mut fs_props = std::collections::HashMap::new();
@@ -175,6 +322,17 @@ fn setup_fs(vm: &mut VM) {
         "statSync".to_string(),
         JsValue::NativeFunction(fs_stat_sync_idx),
     );
+    fs_props.insert("mmap".to_string(), JsValue::NativeFunction(fs_mmap_idx));
+    fs_props.insert(
+        "createReadStream".to_string(),
+        JsValue::NativeFunction(fs_read_stream_idx),
+    );
+    fs_props.insert(
+        "createWriteStream".to_string(),
+        JsValue::NativeFunction(fs_write_stream_idx),
+    );
+    // `import fs from "fs"` binds the module object itself
+    fs_props.insert("default".to_string(), JsValue::Object(fs_ptr));
     vm.heap.push(HeapObject {
         data: HeapData::Object(fs_props),
     });
@@ -210,11 +368,22 @@ fn setup_json(vm: &mut VM) {
 }
 
 fn setup_globals(vm: &mut VM) {
-    let require_idx = vm.register_native(crate::stdlib::native_require);
+    use crate::stdlib::{
+        native_clear_immediate, native_queue_microtask, native_require, native_set_immediate,
+    };
 
-    vm.call_stack[0]
-        .locals
-        .insert("require".into(), JsValue::NativeFunction(require_idx));
+    let globals: [(&str, NativeFn); 4] = [
+        ("require", native_require),
+        ("setImmediate", native_set_immediate),
+        ("clearImmediate", native_clear_immediate),
+        ("queueMicrotask", native_queue_microtask),
+    ];
+    for (name, func) in globals {
+        let idx = vm.register_native(func);
+        vm.call_stack[0]
+            .locals
+            .insert(name.into(), JsValue::NativeFunction(idx));
+    }
 }
 
 fn setup_map_set(vm: &mut VM) {
@@ -243,11 +412,30 @@ fn setup_map_set(vm: &mut VM) {
         .insert("Set".into(), JsValue::Object(set_ptr));
 }
 
+fn setup_promise(vm: &mut VM) {
+    let resolve_idx = vm.register_native(crate::stdlib::native_promise_resolve);
+
+    let promise_ptr = vm.heap.len();
+    let mut promise_props = std::collections::HashMap::new();
+    // Mark this as a Promise constructor for detection in Construct opcode
+    promise_props.insert(
+        "__type__".to_string(),
+        JsValue::String("Promise".to_string()),
+    );
+    promise_props.insert("resolve".to_string(), JsValue::NativeFunction(resolve_idx));
+    vm.heap.push(HeapObject {
+        data: HeapData::Object(promise_props),
+    });
+    vm.call_stack[0]
+        .locals
+        .insert("Promise".into(), JsValue::Object(promise_ptr));
+}
+
 /// Set script arguments as __args__ global variable.
 /// Arguments are provided as strings and converted to a JS array.
 pub fn set_script_args(vm: &mut VM, args: Vec<String>) {
     // Convert args to JsValue strings
-    let js_args: Vec<JsValue> = args.into_iter().map(JsValue::String).collect();
+    let js_args: Vec<JsValue> = args.iter().cloned().map(JsValue::String).collect();
 
     // Create array on heap (arrays are stored as Object pointing to HeapData::Array)
     let array_ptr = vm.heap.len();
@@ -260,20 +448,25 @@ pub fn set_script_args(vm: &mut VM, args: Vec<String>) {
         .locals
         .insert("__args__".into(), JsValue::Object(array_ptr));
 
-    // Also set process.argv to the same array
+    // process.argv also names the executable and the script
+    let argv_ptr = vm.heap.len();
+    let argv = crate::stdlib::process::argv(vm, &args);
+    vm.heap.push(HeapObject {
+        data: HeapData::Array(argv),
+    });
     if let Some(JsValue::Object(process_ptr)) = vm.call_stack[0].locals.get("process").cloned()
         && let Some(HeapObject {
             data: HeapData::Object(props),
         }) = vm.heap.get_mut(process_ptr)
     {
-        props.insert("argv".to_string(), JsValue::Object(array_ptr));
+        props.insert("argv".to_string(), JsValue::Object(argv_ptr));
     }
 }
 
 fn setup_process(vm: &mut VM) {
     use crate::stdlib::{
-        native_chdir, native_cwd, native_exec, native_exit, native_getenv, native_setenv,
-        native_stdin_read_bytes, native_stdin_read_line, native_stdout_write,
+        native_chdir, native_cwd, native_exec, native_exit, native_getenv, native_memory_usage,
+        native_setenv, native_stdin_read_bytes, native_stdin_read_line, native_stdout_write,
     };
 
     // Register native functions
@@ -283,12 +476,18 @@ fn setup_process(vm: &mut VM) {
     let chdir_idx = vm.register_native(native_chdir);
     let exit_idx = vm.register_native(native_exit);
     let exec_idx = vm.register_native(native_exec);
+    let spawn_idx = vm.register_native(crate::stdlib::child_process::native_spawn);
+    let on_idx = vm.register_native(crate::stdlib::process::native_on);
+    let off_idx = vm.register_native(crate::stdlib::process::native_off);
     let stdin_read_line_idx = vm.register_native(native_stdin_read_line);
     let stdin_read_bytes_idx = vm.register_native(native_stdin_read_bytes);
     let stdout_write_idx = vm.register_native(native_stdout_write);
+    let memory_usage_idx = vm.register_native(native_memory_usage);
 
-    // Create process.env object with get/set methods
+    // Create process.env object with get/set methods; other properties read
+    // and write the host environment (see stdlib::process)
     let env_ptr = vm.heap.len();
+    vm.process.env = Some(env_ptr);
     let mut env_props = std::collections::HashMap::new();
     env_props.insert("get".to_string(), JsValue::NativeFunction(getenv_idx));
     env_props.insert("set".to_string(), JsValue::NativeFunction(setenv_idx));
@@ -339,6 +538,25 @@ fn setup_process(vm: &mut VM) {
     process_props.insert("chdir".to_string(), JsValue::NativeFunction(chdir_idx));
     process_props.insert("exit".to_string(), JsValue::NativeFunction(exit_idx));
     process_props.insert("exec".to_string(), JsValue::NativeFunction(exec_idx));
+    process_props.insert("spawn".to_string(), JsValue::NativeFunction(spawn_idx));
+    process_props.insert("on".to_string(), JsValue::NativeFunction(on_idx));
+    process_props.insert("off".to_string(), JsValue::NativeFunction(off_idx));
+    process_props.insert(
+        "execPath".to_string(),
+        JsValue::String(
+            std::env::current_exe()
+                .map(|path| path.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+        ),
+    );
+    process_props.insert(
+        "memoryUsage".to_string(),
+        JsValue::NativeFunction(memory_usage_idx),
+    );
+    process_props.insert(
+        "platform".to_string(),
+        JsValue::String(crate::platform::PROCESS_PLATFORM.to_string()),
+    );
     vm.heap.push(HeapObject {
         data: HeapData::Object(process_props),
     });
@@ -371,14 +589,46 @@ fn setup_fetch(vm: &mut VM) {
 }
 
 fn setup_object(vm: &mut VM) {
-    use crate::stdlib::native_object_keys;
+    use crate::stdlib::{
+        native_define_property, native_get_own_property_descriptor, native_move,
+        native_object_entries, native_object_freeze, native_object_has_own, native_object_is,
+        native_object_is_frozen, native_object_keys,
+    };
 
     let keys_idx = vm.register_native(native_object_keys);
+    let entries_idx = vm.register_native(native_object_entries);
+    let clone_idx = vm.register_fallible_native(
+        "structuredClone",
+        crate::stdlib::clone::native_structured_clone,
+    );
+    let move_idx = vm.register_native(native_move);
+    let define_idx = vm.register_fallible_native("Object.defineProperty", native_define_property);
+    let descriptor_idx = vm.register_native(native_get_own_property_descriptor);
+    let freeze_idx = vm.register_native(native_object_freeze);
+    let is_frozen_idx = vm.register_native(native_object_is_frozen);
+    let is_idx = vm.register_native(native_object_is);
+    let has_own_idx = vm.register_native(native_object_has_own);
 
     // Create Object global with keys method
     let object_ptr = vm.heap.len();
     let mut object_props = std::collections::HashMap::new();
     object_props.insert("keys".to_string(), JsValue::NativeFunction(keys_idx));
+    object_props.insert("entries".to_string(), JsValue::NativeFunction(entries_idx));
+    object_props.insert(
+        "defineProperty".to_string(),
+        JsValue::NativeFunction(define_idx),
+    );
+    object_props.insert(
+        "getOwnPropertyDescriptor".to_string(),
+        JsValue::NativeFunction(descriptor_idx),
+    );
+    object_props.insert("freeze".to_string(), JsValue::NativeFunction(freeze_idx));
+    object_props.insert(
+        "isFrozen".to_string(),
+        JsValue::NativeFunction(is_frozen_idx),
+    );
+    object_props.insert("is".to_string(), JsValue::NativeFunction(is_idx));
+    object_props.insert("hasOwn".to_string(), JsValue::NativeFunction(has_own_idx));
     vm.heap.push(HeapObject {
         data: HeapData::Object(object_props),
     });
@@ -386,4 +636,454 @@ fn setup_object(vm: &mut VM) {
     vm.call_stack[0]
         .locals
         .insert("Object".into(), JsValue::Object(object_ptr));
+    vm.call_stack[0]
+        .locals
+        .insert("structuredClone".into(), JsValue::NativeFunction(clone_idx));
+    vm.call_stack[0]
+        .locals
+        .insert("move".into(), JsValue::NativeFunction(move_idx));
+}
+
+/// `Decimal(value)` / `new Decimal(value)` and `Decimal.isDecimal`
+fn setup_array(vm: &mut VM) {
+    use crate::stdlib::{
+        native_array_constructor, native_array_from, native_array_is_array, native_array_of,
+    };
+
+    let array_idx = vm.register_fallible_native("Array", native_array_constructor);
+    let from_idx = vm.register_fallible_native("Array.from", native_array_from);
+    let is_array_idx = vm.register_native(native_array_is_array);
+    let of_idx = vm.register_native(native_array_of);
+
+    let array_ptr = vm.heap.len();
+    let mut array_props = std::collections::HashMap::new();
+    array_props.insert("__call__".to_string(), JsValue::NativeFunction(array_idx));
+    array_props.insert(
+        "constructor".to_string(),
+        JsValue::NativeFunction(array_idx),
+    );
+    array_props.insert("isArray".to_string(), JsValue::NativeFunction(is_array_idx));
+    array_props.insert("from".to_string(), JsValue::NativeFunction(from_idx));
+    array_props.insert("of".to_string(), JsValue::NativeFunction(of_idx));
+    vm.heap.push(HeapObject {
+        data: HeapData::Object(array_props),
+    });
+
+    vm.call_stack[0]
+        .locals
+        .insert("Array".into(), JsValue::Object(array_ptr));
+}
+
+fn setup_decimal(vm: &mut VM) {
+    let decimal_idx = vm.register_native(crate::stdlib::decimal::native_decimal);
+    let is_decimal_idx = vm.register_native(crate::stdlib::decimal::native_is_decimal);
+
+    let decimal_ptr = vm.heap.len();
+    let mut decimal_props = std::collections::HashMap::new();
+    decimal_props.insert("__call__".to_string(), JsValue::NativeFunction(decimal_idx));
+    decimal_props.insert(
+        "constructor".to_string(),
+        JsValue::NativeFunction(decimal_idx),
+    );
+    decimal_props.insert(
+        "isDecimal".to_string(),
+        JsValue::NativeFunction(is_decimal_idx),
+    );
+    vm.heap.push(HeapObject {
+        data: HeapData::Object(decimal_props),
+    });
+
+    vm.call_stack[0]
+        .locals
+        .insert("Decimal".into(), JsValue::Object(decimal_ptr));
+}
+
+/// The numeric intrinsics and `std.simd`; the rest of `Math` comes from
+/// `@rolls/math`
+fn setup_math(vm: &mut VM) {
+    use crate::ir::intrinsics::{Intrinsic, STD_INTRINSICS};
+    use crate::ir::simd::SimdKind;
+    use crate::stdlib::intrinsics::native;
+
+    let mut math_props = std::collections::HashMap::new();
+    let mut intrinsics_props = std::collections::HashMap::new();
+    for intrinsic in Intrinsic::ALL {
+        let idx = vm.register_native(native(intrinsic));
+        let props = if intrinsic.namespace() == STD_INTRINSICS {
+            &mut intrinsics_props
+        } else {
+            &mut math_props
+        };
+        props.insert(intrinsic.name().to_string(), JsValue::NativeFunction(idx));
+    }
+
+    let math_ptr = vm.heap.len();
+    vm.heap.push(HeapObject {
+        data: HeapData::Object(math_props),
+    });
+    let intrinsics_ptr = vm.heap.len();
+    vm.heap.push(HeapObject {
+        data: HeapData::Object(intrinsics_props),
+    });
+    let mut simd_props = std::collections::HashMap::new();
+    for kind in SimdKind::ALL {
+        let mut kind_props = std::collections::HashMap::new();
+        for (prop, name, func) in crate::stdlib::simd::natives(kind) {
+            let idx = vm.register_fallible_native(name, func);
+            kind_props.insert(prop.to_string(), JsValue::NativeFunction(idx));
+        }
+        let kind_ptr = vm.heap.len();
+        vm.heap.push(HeapObject {
+            data: HeapData::Object(kind_props),
+        });
+        simd_props.insert(kind.name().to_string(), JsValue::Object(kind_ptr));
+    }
+    let simd_ptr = vm.heap.len();
+    vm.heap.push(HeapObject {
+        data: HeapData::Object(simd_props),
+    });
+    let std_ptr = vm.heap.len();
+    let mut std_props = std::collections::HashMap::new();
+    std_props.insert("intrinsics".to_string(), JsValue::Object(intrinsics_ptr));
+    std_props.insert("simd".to_string(), JsValue::Object(simd_ptr));
+    vm.heap.push(HeapObject {
+        data: HeapData::Object(std_props),
+    });
+
+    vm.call_stack[0]
+        .locals
+        .insert("Math".into(), JsValue::Object(math_ptr));
+    vm.call_stack[0]
+        .locals
+        .insert("std".into(), JsValue::Object(std_ptr));
+}
+
+fn setup_weak(vm: &mut VM) {
+    use crate::stdlib::weak::{native_weak_map, native_weak_ref, native_weak_set};
+
+    let constructors = [
+        (
+            "WeakMap",
+            vm.register_fallible_native("WeakMap", native_weak_map),
+        ),
+        (
+            "WeakSet",
+            vm.register_fallible_native("WeakSet", native_weak_set),
+        ),
+        (
+            "WeakRef",
+            vm.register_fallible_native("WeakRef", native_weak_ref),
+        ),
+    ];
+    for (name, idx) in constructors {
+        let ptr = vm.heap.len();
+        let mut props = std::collections::HashMap::new();
+        props.insert("__call__".to_string(), JsValue::NativeFunction(idx));
+        props.insert("constructor".to_string(), JsValue::NativeFunction(idx));
+        vm.heap.push(HeapObject {
+            data: HeapData::Object(props),
+        });
+        vm.call_stack[0]
+            .locals
+            .insert(name.into(), JsValue::Object(ptr));
+    }
+}
+
+fn setup_encoding(vm: &mut VM) {
+    use crate::stdlib::encoding::{
+        native_buffer_alloc, native_buffer_concat, native_buffer_from, native_is_buffer,
+        native_text_decode, native_text_decode_fatal, native_text_decoder, native_text_encode,
+        native_text_encoder,
+    };
+
+    let globals: [(&str, &[(&str, NativeFn)]); 3] = [
+        (
+            "TextEncoder",
+            &[
+                ("constructor", native_text_encoder),
+                ("__call__", native_text_encoder),
+                ("__encode__", native_text_encode),
+            ],
+        ),
+        (
+            "TextDecoder",
+            &[
+                ("constructor", native_text_decoder),
+                ("__call__", native_text_decoder),
+                ("__decode__", native_text_decode),
+                ("__decode_fatal__", native_text_decode_fatal),
+            ],
+        ),
+        (
+            "Buffer",
+            &[
+                ("from", native_buffer_from),
+                ("alloc", native_buffer_alloc),
+                ("concat", native_buffer_concat),
+                ("isBuffer", native_is_buffer),
+            ],
+        ),
+    ];
+    for (name, natives) in globals {
+        let props = natives
+            .iter()
+            .map(|(key, func)| {
+                let idx = vm.register_native(*func);
+                (key.to_string(), JsValue::NativeFunction(idx))
+            })
+            .collect();
+        let ptr = vm.heap.len();
+        vm.heap.push(HeapObject {
+            data: HeapData::Object(props),
+        });
+        vm.call_stack[0]
+            .locals
+            .insert(name.into(), JsValue::Object(ptr));
+    }
+}
+
+/// `Symbol.operator`, the keys of operator methods (`--operator-overloading`)
+fn setup_symbol(vm: &mut VM) {
+    use crate::vm::operators::{OPERATOR_NAMES, operator_key};
+
+    let operator_ptr = vm.heap.len();
+    let operator_props = OPERATOR_NAMES
+        .iter()
+        .filter_map(|name| Some((name.to_string(), JsValue::String(operator_key(name)?))))
+        .collect();
+    vm.heap.push(HeapObject {
+        data: HeapData::Object(operator_props),
+    });
+
+    let symbol_ptr = vm.heap.len();
+    let mut symbol_props = std::collections::HashMap::new();
+    symbol_props.insert("operator".to_string(), JsValue::Object(operator_ptr));
+    vm.heap.push(HeapObject {
+        data: HeapData::Object(symbol_props),
+    });
+
+    vm.call_stack[0]
+        .locals
+        .insert("Symbol".into(), JsValue::Object(symbol_ptr));
+}
+
+/// `util`, available through `require("util")` and `import ... from "util"`
+fn setup_util(vm: &mut VM) {
+    let inspect_idx = vm.register_native(crate::stdlib::native_inspect);
+
+    let util_ptr = vm.heap.len();
+    let mut util_props = std::collections::HashMap::new();
+    util_props.insert("inspect".to_string(), JsValue::NativeFunction(inspect_idx));
+    util_props.insert("default".to_string(), JsValue::Object(util_ptr));
+    vm.heap.push(HeapObject {
+        data: HeapData::Object(util_props),
+    });
+
+    vm.modules
+        .insert("util".to_string(), JsValue::Object(util_ptr));
+}
+
+fn setup_permissions(vm: &mut VM) {
+    let query_idx = vm.register_native(crate::stdlib::native_permissions_query);
+
+    let permissions_ptr = vm.heap.len();
+    let mut permissions_props = std::collections::HashMap::new();
+    permissions_props.insert("query".to_string(), JsValue::NativeFunction(query_idx));
+    vm.heap.push(HeapObject {
+        data: HeapData::Object(permissions_props),
+    });
+
+    vm.call_stack[0]
+        .locals
+        .insert("permissions".into(), JsValue::Object(permissions_ptr));
+}
+
+/// Prototype methods for primitives that CallMethod has no native table for.
+/// Embedders can add their own the same way via `VM::register_prototype_method`.
+fn setup_prototype_methods(vm: &mut VM) {
+    use crate::stdlib::{
+        native_array_filter, native_array_for_each, native_array_map, native_array_sort,
+        native_boolean_to_string, native_clone, native_number_to_fixed, native_number_to_precision,
+        native_number_to_string, native_object_has_own,
+    };
+
+    let number_methods: [(&str, &'static str, FallibleNativeFn); 3] = [
+        (
+            "toString",
+            "Number.prototype.toString",
+            native_number_to_string,
+        ),
+        (
+            "toFixed",
+            "Number.prototype.toFixed",
+            native_number_to_fixed,
+        ),
+        (
+            "toPrecision",
+            "Number.prototype.toPrecision",
+            native_number_to_precision,
+        ),
+    ];
+    for (name, label, func) in number_methods {
+        vm.register_fallible_prototype_method(BuiltinProto::Number, name, label, func);
+    }
+    vm.register_prototype_method(BuiltinProto::Boolean, "toString", native_boolean_to_string);
+    // `.clone()`, the copy the borrow checker accepts in place of a move
+    for proto in [
+        BuiltinProto::String,
+        BuiltinProto::Array,
+        BuiltinProto::Map,
+        BuiltinProto::Set,
+    ] {
+        vm.register_prototype_method(proto, "clone", native_clone);
+        vm.register_prototype_method(proto, "hasOwnProperty", native_object_has_own);
+    }
+    // Methods that call back into script
+    let array_methods: [(&str, &'static str, FallibleNativeFn); 4] = [
+        ("forEach", "Array.prototype.forEach", native_array_for_each),
+        ("map", "Array.prototype.map", native_array_map),
+        ("filter", "Array.prototype.filter", native_array_filter),
+        ("sort", "Array.prototype.sort", native_array_sort),
+    ];
+    for (name, label, func) in array_methods {
+        vm.register_fallible_prototype_method(BuiltinProto::Array, name, label, func);
+    }
+}
+
+/// `runtime.features.permissions`: whether each capability is granted
+fn granted_capabilities(vm: &VM) -> std::collections::HashMap<String, JsValue> {
+    Capability::ALL
+        .into_iter()
+        .map(|cap| {
+            let granted = vm.permissions.query(cap, None) == PermissionState::Granted;
+            (cap.name().to_string(), JsValue::Boolean(granted))
+        })
+        .collect()
+}
+
+/// Bring the parts of `runtime.features` that depend on the command line
+/// (permissions, operator overloading) up to date, after a snapshot restored
+/// them as they were when it was made
+pub fn refresh_runtime_features(vm: &mut VM) {
+    let features = match vm.call_stack[0].locals.get("runtime") {
+        Some(JsValue::Object(runtime_ptr)) => match vm.heap.get(*runtime_ptr) {
+            Some(HeapObject {
+                data: HeapData::Object(props),
+            }) => props.get("features").cloned(),
+            _ => None,
+        },
+        _ => None,
+    };
+    let Some(JsValue::Object(features_ptr)) = features else {
+        return;
+    };
+    let permissions = granted_capabilities(vm);
+    let permissions_ptr = vm.heap.alloc(HeapObject {
+        data: HeapData::Object(permissions),
+    });
+    let operator_overloading = vm.operator_overloading;
+    if let Some(HeapObject {
+        data: HeapData::Object(features),
+    }) = vm.heap.get_mut(features_ptr)
+    {
+        features.insert("permissions".to_string(), JsValue::Object(permissions_ptr));
+        features.insert(
+            "operatorOverloading".to_string(),
+            JsValue::Boolean(operator_overloading),
+        );
+    }
+}
+
+/// `runtime.features`, for scripts that adapt to the build running them
+/// instead of failing on a missing global or backend:
+///
+/// - `backend`: what executes the script (`"interpreter"` for the VM)
+/// - `jit`: whether `oitec jit` can generate code for this host
+/// - `modules`: built-in globals and modules available without an import
+/// - `operatorOverloading`: whether operators on objects call their
+///   `Symbol.operator` methods (`--operator-overloading`)
+/// - `permissions`: which host capabilities the script may use everywhere;
+///   `permissions.query` answers for a particular path, host or variable
+/// - `tls`, `workStealing`: optional Cargo features compiled in
+///
+/// `runtime.eventLoop()` reports queue lengths and task timings;
+/// `runtime.queueTask(fn, priority)` queues work at high, normal or low priority.
+fn setup_runtime(vm: &mut VM) {
+    let mut modules: Vec<String> = vm.call_stack[0]
+        .locals
+        .iter()
+        .filter(|(name, value)| {
+            !name.starts_with("__")
+                && matches!(value, JsValue::Object(_) | JsValue::NativeFunction(_))
+        })
+        .map(|(name, _)| name.clone())
+        .chain(vm.modules.keys().cloned())
+        .collect();
+    modules.sort();
+    modules.dedup();
+
+    let modules_ptr = vm.heap.len();
+    vm.heap.push(HeapObject {
+        data: HeapData::Array(modules.into_iter().map(JsValue::String).collect()),
+    });
+
+    let permissions_ptr = vm.heap.len();
+    let permissions = granted_capabilities(vm);
+    vm.heap.push(HeapObject {
+        data: HeapData::Object(permissions),
+    });
+
+    let features_ptr = vm.heap.len();
+    let mut features = std::collections::HashMap::new();
+    features.insert(
+        "backend".to_string(),
+        JsValue::String("interpreter".to_string()),
+    );
+    features.insert(
+        "jit".to_string(),
+        JsValue::Boolean(crate::backend::jit_available()),
+    );
+    features.insert("modules".to_string(), JsValue::Object(modules_ptr));
+    features.insert(
+        "operatorOverloading".to_string(),
+        JsValue::Boolean(vm.operator_overloading),
+    );
+    features.insert("permissions".to_string(), JsValue::Object(permissions_ptr));
+    features.insert("tls".to_string(), JsValue::Boolean(cfg!(feature = "tls")));
+    features.insert(
+        "workStealing".to_string(),
+        JsValue::Boolean(cfg!(feature = "work-stealing")),
+    );
+    vm.heap.push(HeapObject {
+        data: HeapData::Object(features),
+    });
+
+    let event_loop_idx = vm.register_native(crate::stdlib::native_event_loop_stats);
+    let queue_task_idx = vm.register_native(crate::stdlib::native_queue_task);
+    let runtime_ptr = vm.heap.len();
+    let mut runtime_props = std::collections::HashMap::new();
+    runtime_props.insert("features".to_string(), JsValue::Object(features_ptr));
+    runtime_props.insert(
+        "eventLoop".to_string(),
+        JsValue::NativeFunction(event_loop_idx),
+    );
+    runtime_props.insert(
+        "queueTask".to_string(),
+        JsValue::NativeFunction(queue_task_idx),
+    );
+    runtime_props.insert(
+        "version".to_string(),
+        JsValue::String(env!("CARGO_PKG_VERSION").to_string()),
+    );
+    runtime_props.insert(
+        "arch".to_string(),
+        JsValue::String(std::env::consts::ARCH.to_string()),
+    );
+    vm.heap.push(HeapObject {
+        data: HeapData::Object(runtime_props),
+    });
+
+    vm.call_stack[0]
+        .locals
+        .insert("runtime".into(), JsValue::Object(runtime_ptr));
 }
@@ -0,0 +1,135 @@
+//! Ready tasks, by priority (`runtime.queueTask(fn, "high" | "normal" | "low")`)
+//!
+//! Due timers run at high priority, I/O callbacks at normal and idle work at
+//! low. The loop takes from the highest non-empty level, except that a level
+//! passed over `STARVATION_LIMIT` times in a row while it had work waiting is
+//! served next, so background work still makes progress under a steady stream
+//! of interactive tasks.
+
+use super::Task;
+use std::collections::VecDeque;
+
+/// Turns a waiting level may lose to higher ones before it gets one
+pub const STARVATION_LIMIT: u32 = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskPriority {
+    /// Timers
+    High,
+    /// I/O callbacks
+    Normal,
+    /// Idle work
+    Low,
+}
+
+impl TaskPriority {
+    pub const ALL: [TaskPriority; 3] = [Self::High, Self::Normal, Self::Low];
+
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "high" => Ok(Self::High),
+            "normal" => Ok(Self::Normal),
+            "low" => Ok(Self::Low),
+            _ => Err(format!(
+                "invalid task priority '{}' (expected high, normal or low)",
+                name
+            )),
+        }
+    }
+
+    fn level(self) -> usize {
+        self as usize
+    }
+}
+
+#[derive(Default)]
+pub struct TaskQueue {
+    levels: [VecDeque<Task>; 3],
+    /// Consecutive turns each level had work but another level ran
+    passed_over: [u32; 3],
+}
+
+impl TaskQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, task: Task, priority: TaskPriority) {
+        self.levels[priority.level()].push_back(task);
+    }
+
+    /// The next task to run, honoring priorities and starvation prevention
+    pub fn pop(&mut self) -> Option<Task> {
+        let waiting = |level: &usize| !self.levels[*level].is_empty();
+        let chosen = (1..3)
+            .filter(waiting)
+            .find(|&level| self.passed_over[level] >= STARVATION_LIMIT)
+            .or_else(|| (0..3).find(waiting))?;
+        for level in 0..3 {
+            if level == chosen {
+                self.passed_over[level] = 0;
+            } else if !self.levels[level].is_empty() {
+                self.passed_over[level] += 1;
+            }
+        }
+        self.levels[chosen].pop_front()
+    }
+
+    pub fn len(&self) -> usize {
+        self.levels.iter().map(VecDeque::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.levels.iter().all(VecDeque::is_empty)
+    }
+
+    /// Tasks waiting at one priority
+    pub fn len_of(&self, priority: TaskPriority) -> usize {
+        self.levels[priority.level()].len()
+    }
+
+    /// Every waiting task, highest priority first
+    pub fn iter(&self) -> impl Iterator<Item = &Task> {
+        self.levels.iter().flatten()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vm::value::JsValue;
+
+    fn task(n: usize) -> Task {
+        Task {
+            function_ptr: JsValue::NativeFunction(n),
+            args: vec![],
+        }
+    }
+
+    fn popped(queue: &mut TaskQueue) -> Option<usize> {
+        match queue.pop()?.function_ptr {
+            JsValue::NativeFunction(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_higher_priorities_run_first_without_starving_low() {
+        let mut queue = TaskQueue::new();
+        queue.push(task(100), TaskPriority::Low);
+        queue.push(task(50), TaskPriority::Normal);
+        for n in 0..20 {
+            queue.push(task(n), TaskPriority::High);
+        }
+        assert_eq!(queue.len(), 22);
+        assert_eq!(queue.len_of(TaskPriority::High), 20);
+
+        let order: Vec<usize> = std::iter::from_fn(|| popped(&mut queue)).collect();
+        let position = |n| order.iter().position(|&m| m == n).unwrap();
+        assert_eq!(&order[..8], &[0, 1, 2, 3, 4, 5, 6, 7]);
+        // Both lower levels get a turn long before the high queue drains
+        assert_eq!(position(50), STARVATION_LIMIT as usize);
+        assert_eq!(position(100), STARVATION_LIMIT as usize + 1);
+        assert!(queue.is_empty());
+    }
+}
@@ -0,0 +1,142 @@
+//! Opcode-level execution log (`oitec --trace <file> <script>`)
+//!
+//! One line per executed instruction: a sequence number, the instruction
+//! index, the call depth, the function running, the opcode and the top of
+//! the operand stack. Nothing time- or address-dependent is written, so two
+//! runs of the same program produce identical logs and can be diffed to find
+//! where a compiler change altered execution.
+
+use crate::vm::opcodes::OpCode;
+use crate::vm::profiler::{NATIVE, TOP_LEVEL, function_names};
+use crate::vm::value::JsValue;
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+/// Stack entries shown per line, nearest the top
+const STACK_VIEW: usize = 4;
+/// Longest rendering of a single value or opcode before it is cut
+const MAX_ITEM_CHARS: usize = 40;
+
+pub struct Tracer {
+    out: Box<dyn Write>,
+    /// Only trace instructions whose innermost function has one of these names
+    functions: Vec<String>,
+    names: HashMap<usize, String>,
+    /// Program length `names` was computed for; modules loaded later append code
+    names_for: usize,
+    seq: u64,
+    error: Option<io::Error>,
+}
+
+impl Tracer {
+    pub fn new(out: Box<dyn Write>, functions: Vec<String>) -> Self {
+        Self {
+            out,
+            functions,
+            names: HashMap::new(),
+            names_for: 0,
+            seq: 0,
+            error: None,
+        }
+    }
+
+    /// Log the instruction about to run at `ip`.
+    pub fn record(
+        &mut self,
+        program: &[OpCode],
+        ip: usize,
+        function: Option<usize>,
+        depth: usize,
+        stack: &[JsValue],
+    ) {
+        if self.error.is_some() {
+            return;
+        }
+        if self.names_for != program.len() {
+            self.names = function_names(program);
+            self.names_for = program.len();
+        }
+        let name = match function {
+            Some(address) => self
+                .names
+                .get(&address)
+                .cloned()
+                .unwrap_or_else(|| format!("anonymous@{}", address)),
+            None if depth == 1 => TOP_LEVEL.to_string(),
+            None => NATIVE.to_string(),
+        };
+        if !self.functions.is_empty() && !self.functions.contains(&name) {
+            return;
+        }
+
+        self.seq += 1;
+        let op = truncate(format!("{:?}", program[ip]));
+        let line = format!(
+            "{:>8} {:>6} d={:<3} {:<20} {:<40} {}\n",
+            self.seq,
+            ip,
+            depth,
+            name,
+            op,
+            stack_view(stack)
+        );
+        if let Err(e) = self.out.write_all(line.as_bytes()) {
+            self.error = Some(e);
+        }
+    }
+
+    /// Flush the log, reporting the first write error if any.
+    pub fn finish(mut self) -> io::Result<u64> {
+        if let Some(e) = self.error.take() {
+            return Err(e);
+        }
+        self.out.flush()?;
+        Ok(self.seq)
+    }
+}
+
+/// `[n] … a, b, c` with the top of the stack last
+fn stack_view(stack: &[JsValue]) -> String {
+    let shown = &stack[stack.len().saturating_sub(STACK_VIEW)..];
+    let items: Vec<String> = shown.iter().map(value_view).collect();
+    let elided = if stack.len() > STACK_VIEW { "… " } else { "" };
+    format!("[{}] {}{}", stack.len(), elided, items.join(", "))
+}
+
+fn value_view(value: &JsValue) -> String {
+    match value {
+        JsValue::Number(n) => n.to_string(),
+        JsValue::String(s) => truncate(format!("{:?}", s)),
+        JsValue::Boolean(b) => b.to_string(),
+        JsValue::Object(ptr) => format!("obj#{}", ptr),
+        JsValue::Function { address, .. } => format!("fn@{}", address),
+        JsValue::NativeFunction(index) => format!("native#{}", index),
+        JsValue::Null => "null".to_string(),
+        JsValue::Undefined => "undefined".to_string(),
+        JsValue::Accessor(..) => "accessor".to_string(),
+        JsValue::Promise(_) => "promise".to_string(),
+    }
+}
+
+fn truncate(mut text: String) -> String {
+    if let Some((cut, _)) = text.char_indices().nth(MAX_ITEM_CHARS) {
+        text.truncate(cut);
+        text.push('…');
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stack_view_shows_top_entries() {
+        let stack: Vec<JsValue> = (0..6).map(|n| JsValue::Number(n as f64)).collect();
+        assert_eq!(stack_view(&stack), "[6] … 2, 3, 4, 5");
+        assert_eq!(
+            stack_view(&[JsValue::String("x".repeat(50)), JsValue::Object(3)]),
+            format!("[2] \"{}…, obj#3", "x".repeat(39))
+        );
+    }
+}
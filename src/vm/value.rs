@@ -1,24 +1,49 @@
 // Memory representation. We will use a enum to implement ownership,
 // and we track wheter a value is "Owned" or a "reference" in the low-level representation
-use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
 
+/// A built-in function. `args` holds the arguments in call order, however
+/// the function was reached: called by name, as a method or through a
+/// callable object's `__call__`.
 pub type NativeFn = fn(&mut crate::vm::VM, Vec<JsValue>) -> JsValue;
 
+/// Result of a native that can throw: `Err` is raised as a VM exception
+pub type NativeResult = Result<JsValue, JsValue>;
+
+pub type FallibleNativeFn = fn(&mut crate::vm::VM, Vec<JsValue>) -> NativeResult;
+
+/// A registered native function
+#[derive(Debug, Clone, Copy)]
+pub enum Native {
+    Plain(NativeFn),
+    /// May throw. The name is the frame stack traces show for it.
+    Fallible(&'static str, FallibleNativeFn),
+}
+
 #[derive(Debug, Clone)]
 pub enum JsValue {
     Number(f64),
     String(String),
     Boolean(bool),
-    // In a real low-level VM, this would be a pointer to a Heap
+    /// Generational handle into the VM heap (see `vm::heap`)
     Object(usize),
     /// A function value with its code address and optional captured environment.
     /// The `env` field points to a HeapObject containing variables "lifted" from
     /// the enclosing scope. This enables closures to survive after their
     /// defining scope's stack frame is destroyed.
+    ///
+    /// `bound` is set on functions created by `bind`: it points to a heap
+    /// array holding the bound `this` followed by the bound arguments.
+    ///
+    /// The VM gives every function it creates an environment of its own,
+    /// empty when nothing is captured, so `env` is also the function's
+    /// identity: two evaluations of one function expression differ.
     Function {
         address: usize,
         env: Option<usize>, // Points to HeapObject with captured variables
+        bound: Option<usize>,
     },
     NativeFunction(usize),
     Null,
@@ -41,12 +66,14 @@ impl PartialEq for JsValue {
                 JsValue::Function {
                     address: a,
                     env: ae,
+                    bound: ab,
                 },
                 JsValue::Function {
                     address: b,
                     env: be,
+                    bound: bb,
                 },
-            ) => a == b && ae == be,
+            ) => a == b && ae == be && ab == bb,
             (JsValue::NativeFunction(a), JsValue::NativeFunction(b)) => a == b,
             (JsValue::Null, JsValue::Null) => true,
             (JsValue::Undefined, JsValue::Undefined) => true,
@@ -61,6 +88,68 @@ impl PartialEq for JsValue {
 
 impl Eq for JsValue {}
 
+impl JsValue {
+    /// `===`: numbers compare by value, so `NaN !== NaN` and `0 === -0`;
+    /// strings by content; objects, functions and promises by identity
+    pub fn strict_equals(&self, other: &JsValue) -> bool {
+        match (self, other) {
+            (JsValue::Number(a), JsValue::Number(b)) => a == b,
+            (JsValue::String(a), JsValue::String(b)) => a == b,
+            (JsValue::Boolean(a), JsValue::Boolean(b)) => a == b,
+            (JsValue::Null, JsValue::Null) | (JsValue::Undefined, JsValue::Undefined) => true,
+            (JsValue::Object(a), JsValue::Object(b)) => a == b,
+            // The environment handle identifies the function, and `bind`
+            // keeps it, so bound copies differ by their `bound` array. A
+            // function without one was not created by the VM and compares
+            // by code address.
+            (
+                JsValue::Function {
+                    env: Some(a),
+                    bound: a_bound,
+                    ..
+                },
+                JsValue::Function {
+                    env: Some(b),
+                    bound: b_bound,
+                    ..
+                },
+            ) => a == b && a_bound == b_bound,
+            (JsValue::Function { .. }, JsValue::Function { .. })
+            | (JsValue::NativeFunction(_), JsValue::NativeFunction(_))
+            | (JsValue::Promise(_), JsValue::Promise(_)) => self == other,
+            _ => false,
+        }
+    }
+
+    /// `Object.is` (SameValue): `===`, except that `NaN` is itself and `0`
+    /// and `-0` differ
+    pub fn same_value(&self, other: &JsValue) -> bool {
+        match (self, other) {
+            (JsValue::Number(a), JsValue::Number(b)) => {
+                (a.is_nan() && b.is_nan()) || a.to_bits() == b.to_bits()
+            }
+            _ => self.strict_equals(other),
+        }
+    }
+
+    /// SameValueZero, used by `includes`, Map keys and Set members: `===`,
+    /// except that `NaN` is itself
+    pub fn same_value_zero(&self, other: &JsValue) -> bool {
+        match (self, other) {
+            (JsValue::Number(a), JsValue::Number(b)) => (a.is_nan() && b.is_nan()) || a == b,
+            _ => self.strict_equals(other),
+        }
+    }
+
+    /// This value as a Map key or Set member, which store `-0` as `0`
+    pub fn into_key(self) -> JsValue {
+        match self {
+            JsValue::Number(0.0) => JsValue::Number(0.0),
+            other => other,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum PromiseState {
     Pending,
@@ -71,6 +160,8 @@ pub enum PromiseState {
 #[derive(Debug, Clone)]
 pub struct Promise {
     pub state: Arc<Mutex<PromiseInternal>>,
+    /// Signalled when the promise settles, for threads blocked in `wait`
+    settled: Arc<Condvar>,
 }
 
 impl PartialEq for Promise {
@@ -92,7 +183,10 @@ impl Promise {
             value: None,
             handlers: Vec::new(),
         }));
-        Self { state }
+        Self {
+            state,
+            settled: Arc::new(Condvar::new()),
+        }
     }
 
     pub fn with_value(value: JsValue) -> Self {
@@ -101,7 +195,27 @@ impl Promise {
             value: Some(value),
             handlers: Vec::new(),
         }));
-        Self { state }
+        Self {
+            state,
+            settled: Arc::new(Condvar::new()),
+        }
+    }
+
+    /// Block until the promise settles or `timeout` passes (forever without
+    /// one), returning its state then
+    pub fn wait(&self, timeout: Option<Duration>) -> PromiseState {
+        let pending = |internal: &mut PromiseInternal| internal.state == PromiseState::Pending;
+        let internal = self.state.lock().unwrap();
+        let internal = match timeout {
+            Some(timeout) => {
+                self.settled
+                    .wait_timeout_while(internal, timeout, pending)
+                    .unwrap()
+                    .0
+            }
+            None => self.settled.wait_while(internal, pending).unwrap(),
+        };
+        internal.state.clone()
     }
 
     pub fn get_state(&self) -> PromiseState {
@@ -130,18 +244,19 @@ impl Promise {
             internal.handlers.clear();
 
             drop(internal);
+            self.settled.notify_all();
 
             for handler in handlers {
                 if is_fulfilled {
                     if let Some(on_fulfilled) = handler.on_fulfilled
-                        && let JsValue::Function { address, env } = *on_fulfilled
+                        && let JsValue::Function { address, env, .. } = *on_fulfilled
                     {
                         let _ = address;
                         let _ = env;
                         let _ = value_to_store;
                     }
                 } else if let Some(on_rejected) = handler.on_rejected
-                    && let JsValue::Function { address, env } = *on_rejected
+                    && let JsValue::Function { address, env, .. } = *on_rejected
                 {
                     let _ = address;
                     let _ = env;
@@ -284,4 +399,72 @@ pub enum HeapData {
     Map(Vec<(JsValue, JsValue)>),
     /// Set - ordered unique values
     Set(Vec<JsValue>),
+    /// Buffer - a byte view, e.g. from `fs.mmap`
+    Buffer(BufferView),
+    /// Decimal - an exact base-10 number
+    Decimal(crate::stdlib::decimal::Decimal),
+    /// WeakMap - values keyed by the heap pointer of an object. Keys don't
+    /// keep their objects alive; the VM never frees objects yet, so for now
+    /// entries stay until deleted.
+    WeakMap(HashMap<usize, JsValue>),
+    /// WeakSet - heap pointers of the objects in the set
+    WeakSet(HashSet<usize>),
+    /// WeakRef - heap pointer of the target
+    WeakRef(usize),
+    /// Stream - id of a readable or writable stream in the VM's stream table
+    Stream(usize),
+    /// Process - id of a spawned child in the VM's process table
+    Process(usize),
+}
+
+/// Bytes behind one or more buffer views
+#[derive(Debug)]
+pub enum BufferStorage {
+    Owned(Vec<u8>),
+    Mapped(crate::platform::MappedFile),
+}
+
+impl BufferStorage {
+    pub fn as_slice(&self) -> &[u8] {
+        match self {
+            BufferStorage::Owned(bytes) => bytes,
+            BufferStorage::Mapped(file) => file.as_slice(),
+        }
+    }
+}
+
+/// `len` bytes at `offset` into shared storage. Slicing makes another view of
+/// the same storage; bytes are never copied.
+#[derive(Debug, Clone)]
+pub struct BufferView {
+    pub storage: Arc<BufferStorage>,
+    pub offset: usize,
+    pub len: usize,
+}
+
+impl BufferView {
+    /// A view of all of `storage`
+    pub fn new(storage: BufferStorage) -> Self {
+        let len = storage.as_slice().len();
+        Self {
+            storage: Arc::new(storage),
+            offset: 0,
+            len,
+        }
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.storage.as_slice()[self.offset..self.offset + self.len]
+    }
+
+    /// Bytes `start..end` of this view (clamped), sharing its storage
+    pub fn subarray(&self, start: usize, end: usize) -> Self {
+        let end = end.min(self.len);
+        let start = start.min(end);
+        Self {
+            storage: Arc::clone(&self.storage),
+            offset: self.offset + start,
+            len: end - start,
+        }
+    }
 }